@@ -0,0 +1,106 @@
+// Copyright 2019 Parity Technologies (UK) Ltd.
+//
+// Permission is hereby granted, free of charge, to any person obtaining a
+// copy of this software and associated documentation files (the "Software"),
+// to deal in the Software without restriction, including without limitation
+// the rights to use, copy, modify, merge, publish, distribute, sublicense,
+// and/or sell copies of the Software, and to permit persons to whom the
+// Software is furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS
+// OR IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING
+// FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
+// DEALINGS IN THE SOFTWARE.
+//! Benchmarks comparing the stream muxers.
+//!
+//! The connections run over the in-memory transport, so that the benchmarks measure the muxers
+//! rather than the sockets. All the inputs are fixed.
+
+use criterion::{Bencher, Criterion, criterion_group, criterion_main};
+use futures::{prelude::*, stream};
+use libp2p::core::{
+    StreamMuxer,
+    muxing,
+    transport::{ListenerEvent, MemoryTransport, Transport, memory::Channel},
+    upgrade::{self, InboundUpgrade, OutboundUpgrade},
+};
+use bytes::Bytes;
+use std::{fmt, io, sync::Arc};
+use tokio::runtime::current_thread::Runtime;
+
+/// Number of substreams opened at each iteration.
+const SUBSTREAMS: usize = 100;
+
+/// Data sent on each substream.
+const MESSAGE: &[u8] = b"substream churn!";
+
+/// Opens an in-memory connection upgraded with `config`, and returns the muxers of its dialer
+/// and listener ends.
+fn muxer_pair<U, M>(rt: &mut Runtime, name: &str, config: U) -> (Arc<M>, Arc<M>)
+where
+    U: InboundUpgrade<Channel<Bytes>, Output = M> + OutboundUpgrade<Channel<Bytes>, Output = M> + Clone,
+    <U as InboundUpgrade<Channel<Bytes>>>::Error: fmt::Debug,
+    <U as OutboundUpgrade<Channel<Bytes>>>::Error: fmt::Debug,
+{
+    let mut listener = MemoryTransport.listen_on(format!("/memory/{}", name).parse().unwrap()).unwrap();
+    let addr = listener.by_ref().wait()
+        .next()
+        .and_then(Result::ok)
+        .and_then(ListenerEvent::into_new_address)
+        .expect("the first event is the address");
+    let dialer_io = rt.block_on(MemoryTransport.dial(addr).unwrap()).unwrap();
+    let listener_io = listener.wait()
+        .filter_map(|event| event.ok().and_then(ListenerEvent::into_upgrade))
+        .next()
+        .expect("the listener is open")
+        .0
+        .wait()
+        .unwrap();
+
+    let outbound = upgrade::apply_outbound(dialer_io, config.clone()).map_err(|err| format!("{:?}", err));
+    let inbound = upgrade::apply_inbound(listener_io, config).map_err(|err| format!("{:?}", err));
+    let (dialer, listener) = rt.block_on(outbound.join(inbound)).unwrap();
+    (Arc::new(dialer), Arc::new(listener))
+}
+
+/// Opens `SUBSTREAMS` substreams one after the other, each sending `MESSAGE` before being
+/// closed.
+fn churn<M: StreamMuxer>(b: &mut Bencher, rt: &mut Runtime, dialer: Arc<M>, listener: Arc<M>) {
+    b.iter(|| {
+        let (dialer, listener) = (dialer.clone(), listener.clone());
+        let substreams = stream::iter_ok::<_, io::Error>(0 .. SUBSTREAMS).for_each(move |_| {
+            let open = muxing::outbound_from_ref_and_wrap(dialer.clone())
+                .map_err(Into::into)
+                .and_then(|substream| tokio_io::io::write_all(substream, MESSAGE))
+                .and_then(|(substream, _)| tokio_io::io::shutdown(substream));
+            let accept = muxing::inbound_from_ref_and_wrap(listener.clone())
+                .map_err(Into::into)
+                .and_then(|substream| tokio_io::io::read_to_end(substream, Vec::new()))
+                .map(|(_, data)| assert_eq!(data, MESSAGE));
+            open.join(accept).map(|_| ())
+        });
+        rt.block_on(substreams).unwrap();
+    })
+}
+
+fn substream_churn(c: &mut Criterion) {
+    c.bench_function("mplex: open and close 100 substreams", |b| {
+        let mut rt = Runtime::new().unwrap();
+        let (dialer, listener) = muxer_pair(&mut rt, "bench-churn-mplex", libp2p::mplex::MplexConfig::new());
+        churn(b, &mut rt, dialer, listener)
+    });
+    c.bench_function("yamux: open and close 100 substreams", |b| {
+        let mut rt = Runtime::new().unwrap();
+        let (dialer, listener) = muxer_pair(&mut rt, "bench-churn-yamux", libp2p::yamux::Config::default());
+        churn(b, &mut rt, dialer, listener)
+    });
+}
+
+criterion_group!(benches, substream_churn);
+criterion_main!(benches);
@@ -0,0 +1,144 @@
+// Copyright 2019 Parity Technologies (UK) Ltd.
+//
+// Permission is hereby granted, free of charge, to any person obtaining a
+// copy of this software and associated documentation files (the "Software"),
+// to deal in the Software without restriction, including without limitation
+// the rights to use, copy, modify, merge, publish, distribute, sublicense,
+// and/or sell copies of the Software, and to permit persons to whom the
+// Software is furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS
+// OR IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING
+// FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
+// DEALINGS IN THE SOFTWARE.
+//! Benchmarks of the multistream-select negotiation and of the length-prefixed framing of the
+//! upgrades.
+//!
+//! The negotiations run over the in-memory transport, so that they measure the protocol rather
+//! than the sockets. All the inputs are fixed.
+
+use criterion::{Benchmark, Criterion, Throughput, criterion_group, criterion_main};
+use futures::{future, prelude::*};
+use libp2p_core::{
+    Multiaddr, Transport,
+    transport::{ListenerEvent, MemoryTransport, memory::Channel},
+    upgrade::{self, InboundUpgrade, Negotiated, OutboundUpgrade, UpgradeInfo},
+};
+use bytes::Bytes;
+use std::{io, iter, vec};
+
+/// Upgrade supporting a list of protocols, which does nothing once negotiated besides
+/// flushing the substream.
+#[derive(Clone)]
+struct Protocols(Vec<&'static [u8]>);
+
+impl UpgradeInfo for Protocols {
+    type Info = &'static [u8];
+    type InfoIter = vec::IntoIter<&'static [u8]>;
+
+    fn protocol_info(&self) -> Self::InfoIter {
+        self.0.clone().into_iter()
+    }
+}
+
+impl<C: tokio_io::AsyncWrite> InboundUpgrade<C> for Protocols {
+    type Output = Negotiated<C>;
+    type Error = io::Error;
+    type Future = tokio_io::io::Flush<Negotiated<C>>;
+
+    fn upgrade_inbound(self, socket: Negotiated<C>, _: Self::Info) -> Self::Future {
+        tokio_io::io::flush(socket)
+    }
+}
+
+impl<C: tokio_io::AsyncWrite> OutboundUpgrade<C> for Protocols {
+    type Output = Negotiated<C>;
+    type Error = io::Error;
+    type Future = tokio_io::io::Flush<Negotiated<C>>;
+
+    fn upgrade_outbound(self, socket: Negotiated<C>, _: Self::Info) -> Self::Future {
+        tokio_io::io::flush(socket)
+    }
+}
+
+const PROTOCOLS: [&[u8]; 16] = [
+    b"/bench/proto/0", b"/bench/proto/1", b"/bench/proto/2", b"/bench/proto/3",
+    b"/bench/proto/4", b"/bench/proto/5", b"/bench/proto/6", b"/bench/proto/7",
+    b"/bench/proto/8", b"/bench/proto/9", b"/bench/proto/10", b"/bench/proto/11",
+    b"/bench/proto/12", b"/bench/proto/13", b"/bench/proto/14", b"/bench/proto/15",
+];
+
+/// Listener of the in-memory transport, and the address it listens on.
+fn memory_listener(name: &str) -> (<MemoryTransport as Transport>::Listener, Multiaddr) {
+    let addr: Multiaddr = format!("/memory/{}", name).parse().unwrap();
+    let mut listener = MemoryTransport.listen_on(addr).unwrap();
+    let addr = listener.by_ref().wait()
+        .next()
+        .and_then(Result::ok)
+        .and_then(ListenerEvent::into_new_address)
+        .expect("the first event is the address");
+    (listener, addr)
+}
+
+/// Opens an in-memory connection, and returns its dialer and listener ends.
+fn connect(listener: &mut <MemoryTransport as Transport>::Listener, addr: &Multiaddr)
+    -> (Channel<Bytes>, Channel<Bytes>)
+{
+    let dialer = MemoryTransport.dial(addr.clone()).unwrap().wait().unwrap();
+    let listener = listener.by_ref().wait()
+        .filter_map(|event| event.ok().and_then(ListenerEvent::into_upgrade))
+        .next()
+        .expect("the listener is open")
+        .0
+        .wait()
+        .unwrap();
+    (dialer, listener)
+}
+
+fn negotiate(c: &mut Criterion, name: &'static str, supported: Vec<&'static [u8]>, proposed: Vec<&'static [u8]>) {
+    c.bench_function(name, move |b| {
+        let (mut listener, addr) = memory_listener(&name.replace(' ', "-"));
+        b.iter(|| {
+            let (dialer_io, listener_io) = connect(&mut listener, &addr);
+            let dialer = upgrade::apply_outbound(dialer_io, Protocols(proposed.clone()));
+            let listener = upgrade::apply_inbound(listener_io, Protocols(supported.clone()));
+            dialer.join(listener).wait().ok().unwrap();
+        })
+    });
+}
+
+fn negotiation(c: &mut Criterion) {
+    negotiate(c, "negotiate one protocol", vec![PROTOCOLS[0]], vec![PROTOCOLS[0]]);
+    negotiate(c, "negotiate the last of 16 supported protocols", PROTOCOLS.to_vec(), vec![PROTOCOLS[15]]);
+    negotiate(c, "negotiate after 15 rejected proposals", vec![PROTOCOLS[15]], PROTOCOLS.to_vec());
+}
+
+fn framing(c: &mut Criterion) {
+    for &size in &[64, 16 * 1024] {
+        let message = iter::repeat(0xa5).take(size).collect::<Vec<u8>>();
+        let mut encoded = io::Cursor::new(Vec::new());
+        upgrade::write_one(&mut encoded, &message).wait().unwrap();
+        let encoded = encoded.into_inner();
+
+        let name = format!("{} bytes", size);
+        c.bench("write_one", Benchmark::new(name.clone(), move |b| {
+            b.iter(|| {
+                let mut out = io::Cursor::new(Vec::with_capacity(size + 4));
+                upgrade::write_one(&mut out, &message).wait().unwrap();
+                out
+            })
+        }).throughput(Throughput::Bytes(size as u32)));
+        c.bench("read_one", Benchmark::new(name, move |b| {
+            b.iter(|| upgrade::read_one(&encoded[..], size).wait().unwrap())
+        }).throughput(Throughput::Bytes(size as u32)));
+    }
+}
+
+criterion_group!(benches, negotiation, framing);
+criterion_main!(benches);
@@ -0,0 +1,140 @@
+// Copyright 2019 Parity Technologies (UK) Ltd.
+//
+// Permission is hereby granted, free of charge, to any person obtaining a
+// copy of this software and associated documentation files (the "Software"),
+// to deal in the Software without restriction, including without limitation
+// the rights to use, copy, modify, merge, publish, distribute, sublicense,
+// and/or sell copies of the Software, and to permit persons to whom the
+// Software is furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS
+// OR IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING
+// FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
+// DEALINGS IN THE SOFTWARE.
+//! Pool of reusable buffers for the frames of muxers and encryption protocols.
+//!
+//! Framing a stream of data usually allocates a buffer for each frame, which becomes significant
+//! for nodes with many connections exchanging small messages. A [`BufferPool`] can be shared
+//! between connections, and even protocols, to reuse these buffers instead.
+
+use bytes::{Bytes, BytesMut};
+use parking_lot::Mutex;
+use std::{fmt, sync::Arc};
+
+/// Pool of reusable `BytesMut` buffers.
+///
+/// Cloning a `BufferPool` returns a handle to the same pool.
+#[derive(Clone)]
+pub struct BufferPool {
+    inner: Arc<Mutex<Inner>>,
+}
+
+struct Inner {
+    /// Buffers ready to be reused, all empty.
+    buffers: Vec<BytesMut>,
+    /// Capacity of the buffers allocated by the pool.
+    buffer_size: usize,
+    /// Maximum number of buffers kept for reuse.
+    max_buffers: usize,
+}
+
+impl BufferPool {
+    /// Creates a pool allocating buffers of `buffer_size` bytes and keeping at most
+    /// `max_buffers` of them for reuse.
+    pub fn new(buffer_size: usize, max_buffers: usize) -> Self {
+        BufferPool {
+            inner: Arc::new(Mutex::new(Inner {
+                buffers: Vec::new(),
+                buffer_size,
+                max_buffers,
+            }))
+        }
+    }
+
+    /// Returns an empty buffer, reused from the pool if possible.
+    ///
+    /// The buffer has a capacity of at least the `buffer_size` of the pool.
+    pub fn get(&self) -> BytesMut {
+        let mut inner = self.inner.lock();
+        match inner.buffers.pop() {
+            Some(buffer) => buffer,
+            None => BytesMut::with_capacity(inner.buffer_size),
+        }
+    }
+
+    /// Gives a buffer back to the pool.
+    ///
+    /// The buffer is discarded if the pool is full or if it is smaller than the `buffer_size` of
+    /// the pool.
+    pub fn put(&self, mut buffer: BytesMut) {
+        let mut inner = self.inner.lock();
+        if inner.buffers.len() < inner.max_buffers && buffer.capacity() >= inner.buffer_size {
+            buffer.clear();
+            inner.buffers.push(buffer);
+        }
+    }
+
+    /// Gives a frozen buffer back to the pool, provided that it isn't shared with other `Bytes`
+    /// or `BytesMut`.
+    pub fn recycle(&self, bytes: Bytes) {
+        if let Ok(buffer) = bytes.try_mut() {
+            self.put(buffer)
+        }
+    }
+
+    /// Returns the number of buffers ready to be reused.
+    pub fn num_available(&self) -> usize {
+        self.inner.lock().buffers.len()
+    }
+}
+
+impl Default for BufferPool {
+    /// Creates a pool of up to 1024 buffers of 8KiB.
+    fn default() -> Self {
+        BufferPool::new(8 * 1024, 1024)
+    }
+}
+
+impl fmt::Debug for BufferPool {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let inner = self.inner.lock();
+        f.debug_struct("BufferPool")
+            .field("buffer_size", &inner.buffer_size)
+            .field("max_buffers", &inner.max_buffers)
+            .field("available", &inner.buffers.len())
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn buffers_are_reused() {
+        let pool = BufferPool::new(64, 1);
+        let mut buffer = pool.get();
+        buffer.extend_from_slice(b"hello");
+        let frozen = buffer.freeze();
+        let shared = frozen.clone();
+        pool.recycle(frozen);
+        assert_eq!(pool.num_available(), 0);
+        pool.recycle(shared);
+        assert_eq!(pool.num_available(), 1);
+
+        let buffer = pool.get();
+        assert!(buffer.is_empty());
+        assert!(buffer.capacity() >= 64);
+        assert_eq!(pool.num_available(), 0);
+
+        pool.put(BytesMut::with_capacity(64));
+        pool.put(BytesMut::with_capacity(64));
+        assert_eq!(pool.num_available(), 1);
+    }
+}
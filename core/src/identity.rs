@@ -149,55 +149,70 @@ impl PublicKey {
     /// Encode the public key into a protobuf structure for storage or
     /// exchange with other nodes.
     pub fn into_protobuf_encoding(self) -> Vec<u8> {
-        use protobuf::Message;
-        let mut public_key = keys_proto::PublicKey::new();
-        match self {
-            PublicKey::Ed25519(key) => {
-                public_key.set_Type(keys_proto::KeyType::Ed25519);
-                public_key.set_Data(key.encode().to_vec());
-            },
-            #[cfg(not(any(target_os = "emscripten", target_os = "unknown")))]
-            PublicKey::Rsa(key) => {
-                public_key.set_Type(keys_proto::KeyType::RSA);
-                public_key.set_Data(key.encode_x509());
-            },
-            #[cfg(feature = "secp256k1")]
-            PublicKey::Secp256k1(key) => {
-                public_key.set_Type(keys_proto::KeyType::Secp256k1);
-                public_key.set_Data(key.encode().to_vec());
-            },
-        };
+        use prost::Message;
 
-        public_key
-            .write_to_bytes()
-            .expect("Encoding public key into protobuf failed.")
+        let public_key = self.to_proto();
+        let mut buf = Vec::with_capacity(public_key.encoded_len());
+        public_key.encode(&mut buf).expect("Vec<u8> provides capacity as needed");
+        buf
     }
 
     /// Decode a public key from a protobuf structure, e.g. read from storage
     /// or received from another node.
     pub fn from_protobuf_encoding(bytes: &[u8]) -> Result<PublicKey, DecodingError> {
-        #[allow(unused_mut)] // Due to conditional compilation.
-        let mut pubkey = protobuf::parse_from_bytes::<keys_proto::PublicKey>(bytes)
+        use prost::Message;
+
+        let pubkey = keys_proto::PublicKey::decode(bytes)
             .map_err(|e| DecodingError::new("Protobuf").source(e))?;
+        PublicKey::from_proto(pubkey)
+    }
 
-        match pubkey.get_Type() {
+    /// Converts the public key into its protobuf message, for embedding it in other messages.
+    pub(crate) fn to_proto(&self) -> keys_proto::PublicKey {
+        match self {
+            PublicKey::Ed25519(key) =>
+                keys_proto::PublicKey {
+                    r#type: keys_proto::KeyType::Ed25519 as i32,
+                    data: key.encode().to_vec()
+                },
+            #[cfg(not(any(target_os = "emscripten", target_os = "unknown")))]
+            PublicKey::Rsa(key) =>
+                keys_proto::PublicKey {
+                    r#type: keys_proto::KeyType::Rsa as i32,
+                    data: key.encode_x509()
+                },
+            #[cfg(feature = "secp256k1")]
+            PublicKey::Secp256k1(key) =>
+                keys_proto::PublicKey {
+                    r#type: keys_proto::KeyType::Secp256k1 as i32,
+                    data: key.encode().to_vec()
+                },
+        }
+    }
+
+    /// Converts a protobuf message, e.g. embedded in another message, into a public key.
+    pub(crate) fn from_proto(pubkey: keys_proto::PublicKey) -> Result<PublicKey, DecodingError> {
+        let key_type = keys_proto::KeyType::from_i32(pubkey.r#type)
+            .ok_or_else(|| DecodingError::new(format!("unknown key type: {}", pubkey.r#type)))?;
+
+        match key_type {
             keys_proto::KeyType::Ed25519 => {
-                ed25519::PublicKey::decode(pubkey.get_Data())
+                ed25519::PublicKey::decode(&pubkey.data)
                     .map(PublicKey::Ed25519)
             },
             #[cfg(not(any(target_os = "emscripten", target_os = "unknown")))]
-            keys_proto::KeyType::RSA => {
-                rsa::PublicKey::decode_x509(&pubkey.take_Data())
+            keys_proto::KeyType::Rsa => {
+                rsa::PublicKey::decode_x509(&pubkey.data)
                     .map(PublicKey::Rsa)
             }
             #[cfg(any(target_os = "emscripten", target_os = "unknown"))]
-            keys_proto::KeyType::RSA => {
+            keys_proto::KeyType::Rsa => {
                 log::debug!("support for RSA was disabled at compile-time");
                 Err(DecodingError::new("Unsupported"))
             },
             #[cfg(feature = "secp256k1")]
             keys_proto::KeyType::Secp256k1 => {
-                secp256k1::PublicKey::decode(pubkey.get_Data())
+                secp256k1::PublicKey::decode(&pubkey.data)
                     .map(PublicKey::Secp256k1)
             }
             #[cfg(not(feature = "secp256k1"))]
@@ -214,3 +229,49 @@ impl PublicKey {
     }
 }
 
+/// Serializes the public key as the base-58 string of its protobuf encoding in human-readable
+/// formats, and as the protobuf encoding itself otherwise.
+#[cfg(feature = "serde")]
+impl serde::Serialize for PublicKey {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let bytes = self.clone().into_protobuf_encoding();
+        if serializer.is_human_readable() {
+            serializer.serialize_str(&bs58::encode(bytes).into_string())
+        } else {
+            serializer.serialize_bytes(&bytes)
+        }
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de> serde::Deserialize<'de> for PublicKey {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        use serde::de::{self, Error};
+        use std::fmt;
+
+        struct Visitor;
+
+        impl<'de> de::Visitor<'de> for Visitor {
+            type Value = PublicKey;
+
+            fn expecting(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                f.write_str("a protobuf-encoded public key")
+            }
+
+            fn visit_str<E: Error>(self, v: &str) -> Result<Self::Value, E> {
+                let bytes = bs58::decode(v).into_vec().map_err(Error::custom)?;
+                self.visit_bytes(&bytes)
+            }
+
+            fn visit_bytes<E: Error>(self, v: &[u8]) -> Result<Self::Value, E> {
+                PublicKey::from_protobuf_encoding(v).map_err(Error::custom)
+            }
+        }
+
+        if deserializer.is_human_readable() {
+            deserializer.deserialize_str(Visitor)
+        } else {
+            deserializer.deserialize_bytes(Visitor)
+        }
+    }
+}
@@ -39,23 +39,44 @@
 pub use multiaddr;
 pub use multistream_select::Negotiated;
 
-mod keys_proto;
 mod peer_id;
 mod translation;
 
+mod keys_proto {
+    include!(concat!(env!("OUT_DIR"), "/keys_proto.rs"));
+}
+mod envelope_proto {
+    include!(concat!(env!("OUT_DIR"), "/envelope_proto.rs"));
+}
+mod peer_record_proto {
+    include!(concat!(env!("OUT_DIR"), "/peer_record_proto.rs"));
+}
+
 #[cfg(test)]
 mod tests;
 
+pub mod buffer_pool;
 pub mod either;
 pub mod identity;
 pub mod muxing;
 pub mod nodes;
+pub mod peer_record;
+pub mod rng;
+pub mod routing;
+pub mod seen_cache;
+pub mod signed_envelope;
 pub mod transport;
 pub mod upgrade;
 
+pub use buffer_pool::BufferPool;
 pub use multiaddr::Multiaddr;
 pub use muxing::StreamMuxer;
 pub use peer_id::PeerId;
+pub use peer_record::PeerRecord;
+pub use rng::BoxedRng;
+pub use routing::{ContentRouting, PeerRouting};
+pub use seen_cache::SeenCache;
+pub use signed_envelope::SignedEnvelope;
 pub use identity::PublicKey;
 pub use transport::Transport;
 pub use translation::address_translation;
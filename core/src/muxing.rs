@@ -60,8 +60,10 @@ use std::fmt;
 use std::sync::atomic::{AtomicUsize, Ordering};
 use tokio_io::{AsyncRead, AsyncWrite};
 
+pub use self::coalesce::CoalescingMuxer;
 pub use self::singleton::SingletonMuxer;
 
+mod coalesce;
 mod singleton;
 
 /// Implemented on objects that can open and manage substreams.
@@ -0,0 +1,212 @@
+// Copyright 2019 Parity Technologies (UK) Ltd.
+//
+// Permission is hereby granted, free of charge, to any person obtaining a
+// copy of this software and associated documentation files (the "Software"),
+// to deal in the Software without restriction, including without limitation
+// the rights to use, copy, modify, merge, publish, distribute, sublicense,
+// and/or sell copies of the Software, and to permit persons to whom the
+// Software is furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS
+// OR IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING
+// FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
+// DEALINGS IN THE SOFTWARE.
+use crate::muxing::StreamMuxer;
+use futures::{prelude::*, task};
+use std::sync::atomic::{AtomicBool, Ordering};
+
+/// Wraps around a `StreamMuxer` and coalesces the flushes of its substreams.
+///
+/// Flushing a substream of most muxers flushes the whole connection, which results in one write
+/// to the socket per frame when many substreams write small amounts of data. With this wrapper,
+/// flushing a substream only records that a flush is needed and schedules the current task to
+/// be polled again. The connection is then flushed once, at the next flush boundary, which
+/// writes all the data buffered in the meantime together.
+///
+/// The flush boundaries are calls to `poll_inbound`, which the task handling the connection
+/// makes every time it is polled, and explicit calls to `flush_all` or `close`. As a consequence,
+/// the data written to a substream is guaranteed to be sent to the remote at the end of the
+/// current poll cycle of the connection rather than when `flush_substream` returns.
+///
+/// Errors that happen while flushing are reported by the next flush boundary.
+pub struct CoalescingMuxer<TMuxer> {
+    inner: TMuxer,
+    /// If true, a substream has been flushed since the last flush of the connection.
+    flush_pending: AtomicBool,
+}
+
+impl<TMuxer> CoalescingMuxer<TMuxer> {
+    /// Creates a new `CoalescingMuxer` around `inner`.
+    pub fn new(inner: TMuxer) -> Self {
+        CoalescingMuxer {
+            inner,
+            flush_pending: AtomicBool::new(false),
+        }
+    }
+}
+
+impl<TMuxer> CoalescingMuxer<TMuxer>
+where
+    TMuxer: StreamMuxer,
+{
+    /// Flushes the connection if a substream has been flushed since the last flush boundary.
+    fn flush_pending(&self) -> Poll<(), TMuxer::Error> {
+        if self.flush_pending.swap(false, Ordering::AcqRel) {
+            match self.inner.flush_all() {
+                Ok(Async::Ready(())) => {},
+                Ok(Async::NotReady) => {
+                    self.flush_pending.store(true, Ordering::Release);
+                    return Ok(Async::NotReady)
+                },
+                Err(err) => return Err(err),
+            }
+        }
+        Ok(Async::Ready(()))
+    }
+}
+
+impl<TMuxer> StreamMuxer for CoalescingMuxer<TMuxer>
+where
+    TMuxer: StreamMuxer,
+{
+    type Substream = TMuxer::Substream;
+    type OutboundSubstream = TMuxer::OutboundSubstream;
+    type Error = TMuxer::Error;
+
+    fn poll_inbound(&self) -> Poll<Self::Substream, Self::Error> {
+        // If the flush isn't over, the inner muxer notifies the task once it can make progress.
+        self.flush_pending()?;
+        self.inner.poll_inbound()
+    }
+
+    #[inline]
+    fn open_outbound(&self) -> Self::OutboundSubstream {
+        self.inner.open_outbound()
+    }
+
+    #[inline]
+    fn poll_outbound(&self, s: &mut Self::OutboundSubstream) -> Poll<Self::Substream, Self::Error> {
+        self.inner.poll_outbound(s)
+    }
+
+    #[inline]
+    fn destroy_outbound(&self, s: Self::OutboundSubstream) {
+        self.inner.destroy_outbound(s)
+    }
+
+    #[inline]
+    unsafe fn prepare_uninitialized_buffer(&self, buf: &mut [u8]) -> bool {
+        self.inner.prepare_uninitialized_buffer(buf)
+    }
+
+    #[inline]
+    fn read_substream(&self, s: &mut Self::Substream, buf: &mut [u8]) -> Poll<usize, Self::Error> {
+        self.inner.read_substream(s, buf)
+    }
+
+    #[inline]
+    fn write_substream(&self, s: &mut Self::Substream, buf: &[u8]) -> Poll<usize, Self::Error> {
+        self.inner.write_substream(s, buf)
+    }
+
+    fn flush_substream(&self, _: &mut Self::Substream) -> Poll<(), Self::Error> {
+        if !self.flush_pending.swap(true, Ordering::AcqRel) {
+            // Makes sure that the flush boundary is reached even if nothing else happens.
+            task::current().notify();
+        }
+        Ok(Async::Ready(()))
+    }
+
+    #[inline]
+    fn shutdown_substream(&self, s: &mut Self::Substream) -> Poll<(), Self::Error> {
+        self.inner.shutdown_substream(s)
+    }
+
+    #[inline]
+    fn destroy_substream(&self, s: Self::Substream) {
+        self.inner.destroy_substream(s)
+    }
+
+    #[inline]
+    fn is_remote_acknowledged(&self) -> bool {
+        self.inner.is_remote_acknowledged()
+    }
+
+    fn close(&self) -> Poll<(), Self::Error> {
+        self.flush_pending.store(false, Ordering::Release);
+        self.inner.close()
+    }
+
+    fn flush_all(&self) -> Poll<(), Self::Error> {
+        self.flush_pending.store(false, Ordering::Release);
+        self.inner.flush_all()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Endpoint, muxing::SingletonMuxer};
+    use futures::future;
+    use std::{io, sync::{Arc, atomic::AtomicUsize}};
+    use tokio_io::{AsyncRead, AsyncWrite};
+
+    /// Socket that discards the data written to it and counts the flushes.
+    struct Socket(Arc<AtomicUsize>);
+
+    impl io::Read for Socket {
+        fn read(&mut self, _: &mut [u8]) -> io::Result<usize> {
+            Err(io::ErrorKind::WouldBlock.into())
+        }
+    }
+
+    impl AsyncRead for Socket {}
+
+    impl io::Write for Socket {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            self.0.fetch_add(1, Ordering::SeqCst);
+            Ok(())
+        }
+    }
+
+    impl AsyncWrite for Socket {
+        fn shutdown(&mut self) -> Poll<(), io::Error> {
+            Ok(Async::Ready(()))
+        }
+    }
+
+    #[test]
+    fn flushes_are_coalesced() {
+        let flushes = Arc::new(AtomicUsize::new(0));
+        let muxer = CoalescingMuxer::new(SingletonMuxer::new(Socket(flushes.clone()), Endpoint::Dialer));
+
+        future::lazy(|| {
+            let mut outbound = muxer.open_outbound();
+            let mut substream = match muxer.poll_outbound(&mut outbound) {
+                Ok(Async::Ready(substream)) => substream,
+                _ => panic!("the singleton muxer yields its substream right away"),
+            };
+            for _ in 0 .. 3 {
+                muxer.write_substream(&mut substream, b"hello").unwrap();
+                assert!(muxer.flush_substream(&mut substream).unwrap().is_ready());
+            }
+            assert_eq!(flushes.load(Ordering::SeqCst), 0);
+
+            assert!(muxer.poll_inbound().unwrap().is_not_ready());
+            assert_eq!(flushes.load(Ordering::SeqCst), 1);
+            assert!(muxer.poll_inbound().unwrap().is_not_ready());
+            assert_eq!(flushes.load(Ordering::SeqCst), 1);
+            Ok::<_, ()>(())
+        }).wait().unwrap();
+    }
+}
@@ -29,7 +29,8 @@ use crate::{
 };
 use fnv::FnvHashMap;
 use futures::prelude::*;
-use std::{error, fmt, hash::Hash, mem};
+use smallvec::SmallVec;
+use std::{error, fmt, hash::Hash, mem, num::NonZeroUsize};
 
 pub use crate::nodes::tasks::StartTakeOver;
 
@@ -43,9 +44,14 @@ pub struct CollectionStream<TInEvent, TOutEvent, THandler, TReachErr, THandlerEr
     /// must be present in `nodes`.
     inner: tasks::Manager<TInEvent, TOutEvent, THandler, TReachErr, THandlerErr, TaskState<TConnInfo, TUserData>, TConnInfo>,
 
-    /// List of nodes, with the task id that handles this node. The corresponding entry in `tasks`
-    /// must always be in the `Connected` state.
-    nodes: FnvHashMap<TPeerId, TaskId>,
+    /// List of nodes, with the ids of the tasks that handle the connections to this node, from
+    /// the oldest to the most recent. The corresponding entries in `tasks` must always be in the
+    /// `Connected` state. The lists are never empty.
+    nodes: FnvHashMap<TPeerId, SmallVec<[TaskId; 1]>>,
+
+    /// Maximum number of connections to the same node. When a new connection is accepted while
+    /// the limit is reached, the oldest connection is replaced.
+    max_connections_per_node: NonZeroUsize,
 }
 
 impl<TInEvent, TOutEvent, THandler, TReachErr, THandlerErr, TUserData, TConnInfo, TPeerId> fmt::Debug for
@@ -77,6 +83,8 @@ pub enum CollectionEvent<'a, TInEvent, TOutEvent, THandler, TReachErr, THandlerE
     ///
     /// Can only happen after a node has been successfully reached.
     NodeClosed {
+        /// Identifier of the connection.
+        id: ConnectionId,
         /// Information about the connection.
         conn_info: TConnInfo,
         /// The error that happened.
@@ -85,6 +93,17 @@ pub enum CollectionEvent<'a, TInEvent, TOutEvent, THandler, TReachErr, THandlerE
         user_data: TUserData,
     },
 
+    /// A connection to a node has been gracefully closed following a call to
+    /// `PeerMut::disconnect`.
+    NodeDisconnected {
+        /// Identifier of the connection.
+        id: ConnectionId,
+        /// Information about the connection.
+        conn_info: TConnInfo,
+        /// User data that was passed when accepting.
+        user_data: TUserData,
+    },
+
     /// An error happened on the future that was trying to reach a node.
     ReachError {
         /// Identifier of the reach attempt that failed.
@@ -119,13 +138,21 @@ where TOutEvent: fmt::Debug,
                 .field(inner)
                 .finish()
             },
-            CollectionEvent::NodeClosed { ref conn_info, ref error, ref user_data } => {
+            CollectionEvent::NodeClosed { ref id, ref conn_info, ref error, ref user_data } => {
                 f.debug_struct("CollectionEvent::NodeClosed")
+                .field("id", id)
                 .field("conn_info", conn_info)
                 .field("user_data", user_data)
                 .field("error", error)
                 .finish()
             },
+            CollectionEvent::NodeDisconnected { ref id, ref conn_info, ref user_data } => {
+                f.debug_struct("CollectionEvent::NodeDisconnected")
+                .field("id", id)
+                .field("conn_info", conn_info)
+                .field("user_data", user_data)
+                .finish()
+            },
             CollectionEvent::ReachError { ref id, ref error, .. } => {
                 f.debug_struct("CollectionEvent::ReachError")
                 .field("id", id)
@@ -184,10 +211,30 @@ where
     TPeerId: Eq + Hash,
 {
     /// Returns `true` if accepting this reached node would replace an existing connection to that
-    /// node.
+    /// node, because the maximum number of connections per node is reached.
     #[inline]
     pub fn would_replace(&self) -> bool {
-        self.parent.nodes.contains_key(self.connection_info().peer_id())
+        self.parent.nodes.get(self.connection_info().peer_id())
+            .map_or(false, |ids| ids.len() >= self.parent.max_connections_per_node.get())
+    }
+
+    /// If accepting this reached node would replace an existing connection, returns the
+    /// identifier of the connection that would be replaced.
+    #[inline]
+    pub fn replaced_connection(&self) -> Option<ConnectionId> {
+        if self.would_replace() {
+            self.parent.nodes.get(self.connection_info().peer_id())
+                .and_then(|ids| ids.first())
+                .map(|id| ConnectionId(*id))
+        } else {
+            None
+        }
+    }
+
+    /// Returns the number of connections to the node that we have before accepting this one.
+    #[inline]
+    pub fn num_existing_connections(&self) -> usize {
+        self.parent.nodes.get(self.connection_info().peer_id()).map_or(0, |ids| ids.len())
     }
 
     /// Accepts the new node.
@@ -201,13 +248,22 @@ where
             .expect("conn_info is always Some when the object is alive; QED");
 
         // Set the state of the task to `Connected`.
-        let former_task_id = self.parent.nodes.insert(self_conn_info.peer_id().clone(), self.id);
-        *self.parent.inner.task(self.id)
-            .expect("A CollectionReachEvent is only ever created from a valid attempt; QED")
-            .user_data_mut() = TaskState::Connected(self_conn_info.clone(), user_data);
+        let max_connections = self.parent.max_connections_per_node.get();
+        let task_ids = self.parent.nodes.entry(self_conn_info.peer_id().clone())
+            .or_insert_with(SmallVec::new);
+        task_ids.push(self.id);
+        let former_task_id = if task_ids.len() > max_connections {
+            Some(task_ids.remove(0))
+        } else {
+            None
+        };
+        let mut task = self.parent.inner.task(self.id)
+            .expect("A CollectionReachEvent is only ever created from a valid attempt; QED");
+        task.span().record("peer_id", &tracing::field::debug(self_conn_info.peer_id()));
+        *task.user_data_mut() = TaskState::Connected(self_conn_info.clone(), user_data);
 
-        // It is possible that we already have a task connected to the same peer. In this
-        // case, we need to emit a `NodeReplaced` event.
+        // It is possible that we already have the maximum number of tasks connected to the same
+        // peer. In this case, we need to emit a `NodeReplaced` event.
         let tasks = &mut self.parent.inner;
         let ret_value = if let Some(former_task) = former_task_id.and_then(|i| tasks.task(i)) {
             debug_assert!(match *former_task.user_data() {
@@ -284,10 +340,28 @@ pub enum CollectionNodeAccept<TConnInfo, TUserData> {
 #[derive(Debug, Copy, Clone, Hash, PartialEq, Eq, PartialOrd, Ord)]
 pub struct ReachAttemptId(TaskId);
 
+/// Identifier of an established connection to a node.
+///
+/// A reach attempt that succeeds keeps the same identifier once it becomes a connection.
+#[derive(Debug, Copy, Clone, Hash, PartialEq, Eq, PartialOrd, Ord)]
+pub struct ConnectionId(TaskId);
+
+impl fmt::Display for ConnectionId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Display::fmt(&self.0, f)
+    }
+}
+
+impl From<ReachAttemptId> for ConnectionId {
+    fn from(id: ReachAttemptId) -> ConnectionId {
+        ConnectionId(id.0)
+    }
+}
+
 /// Information about a connection.
 pub trait ConnectionInfo {
     /// Identity of the node we are connected to.
-    type PeerId: Eq + Hash;
+    type PeerId: Eq + Hash + fmt::Debug;
 
     /// Returns the identity of the node we are connected to on this connection.
     fn peer_id(&self) -> &Self::PeerId;
@@ -313,9 +387,33 @@ where
         CollectionStream {
             inner: tasks::Manager::new(),
             nodes: Default::default(),
+            max_connections_per_node: NonZeroUsize::new(1).expect("1 > 0"),
         }
     }
 
+    /// Sets the maximum number of simultaneous connections to the same node. Defaults to 1.
+    ///
+    /// When accepting a new connection to a node that already has that many connections, the
+    /// oldest connection is closed and replaced.
+    #[inline]
+    pub fn set_max_connections_per_node(&mut self, max: NonZeroUsize) {
+        self.max_connections_per_node = max;
+    }
+
+    /// Returns the maximum number of simultaneous connections to the same node.
+    #[inline]
+    pub fn max_connections_per_node(&self) -> NonZeroUsize {
+        self.max_connections_per_node
+    }
+
+    /// Sets the way the tasks dedicated to the nodes are run.
+    ///
+    /// Only affects the tasks spawned after this method has been called.
+    #[inline]
+    pub fn set_executor(&mut self, executor: tasks::TaskExecutor) {
+        self.inner.set_executor(executor)
+    }
+
     /// Adds to the collection a future that tries to reach a remote.
     ///
     /// This method spawns a task dedicated to resolving this future and processing the node's
@@ -409,23 +507,50 @@ where
 
     /// Grants access to an object that allows controlling a peer of the collection.
     ///
+    /// If there are multiple connections to this peer, the most recent one is used.
+    ///
     /// Returns `None` if we don't have a connection to this peer.
     #[inline]
     pub fn peer_mut(&mut self, id: &TPeerId) -> Option<PeerMut<'_, TInEvent, TUserData, TConnInfo, TPeerId>> {
-        let task = match self.nodes.get(id) {
+        let task = match self.nodes.get(id).and_then(|ids| ids.last()) {
             Some(&task) => task,
             None => return None,
         };
 
-        match self.inner.task(task) {
-            Some(inner) => Some(PeerMut {
-                inner,
-                nodes: &mut self.nodes,
-            }),
+        self.connection_mut(ConnectionId(task))
+    }
+
+    /// Grants access to an object that allows controlling a specific connection of the
+    /// collection.
+    ///
+    /// Returns `None` if the connection doesn't exist or isn't established yet.
+    pub fn connection_mut(&mut self, id: ConnectionId) -> Option<PeerMut<'_, TInEvent, TUserData, TConnInfo, TPeerId>> {
+        match self.inner.task(id.0) {
+            Some(inner) => {
+                if let TaskState::Pending = inner.user_data() {
+                    return None
+                }
+                Some(PeerMut {
+                    inner,
+                    nodes: &mut self.nodes,
+                })
+            }
             None => None,
         }
     }
 
+    /// Returns the identifiers of the connections to the given peer, from the oldest to the most
+    /// recent.
+    #[inline]
+    pub fn connection_ids(&self, id: &TPeerId) -> impl Iterator<Item = ConnectionId> + '_ {
+        self.nodes.get(id).into_iter().flat_map(|ids| ids.iter().map(|id| ConnectionId(*id)))
+    }
+
+    /// Returns the total number of established connections.
+    pub fn num_connections(&self) -> usize {
+        self.nodes.values().map(|ids| ids.len()).sum()
+    }
+
     /// Returns true if we are connected to the given peer.
     ///
     /// This will return true only after a `NodeReached` event has been produced by `poll()`.
@@ -442,6 +567,17 @@ where
         self.nodes.keys()
     }
 
+    /// Returns the identifier, information and user data of all the established connections.
+    pub fn established_connections(&self) -> impl Iterator<Item = (ConnectionId, &TConnInfo, &TUserData)> {
+        let inner = &self.inner;
+        self.nodes.values()
+            .flat_map(|ids| ids.iter())
+            .filter_map(move |id| match inner.user_data(*id) {
+                Some(TaskState::Connected(conn_info, user_data)) => Some((ConnectionId(*id), conn_info, user_data)),
+                _ => None,
+            })
+    }
+
     /// Provides an API similar to `Stream`, except that it cannot error.
     ///
     /// > **Note**: we use a regular `poll` method instead of implementing `Stream` in order to
@@ -473,6 +609,10 @@ where
                         panic!("We switch the task state to Connected once we're connected, and \
                                 a tasks::Error::Node can only happen after we're connected; QED");
                     },
+                    (TaskState::Pending, tasks::Error::Closed, _) => {
+                        panic!("We only ask connected tasks to disconnect, and a task only \
+                                produces tasks::Error::Closed after being connected; QED");
+                    },
                     (TaskState::Pending, tasks::Error::Reach(_), None) => {
                         // TODO: this could be improved in the API of tasks::Manager
                         panic!("The tasks::Manager is guaranteed to always return the handler \
@@ -480,14 +620,25 @@ where
                     },
                     (TaskState::Connected(conn_info, user_data), tasks::Error::Node(err), _handler) => {
                         debug_assert!(_handler.is_none());
-                        let _node_task_id = self.nodes.remove(conn_info.peer_id());
-                        debug_assert_eq!(_node_task_id, Some(id));
+                        let _removed = remove_task_id(&mut self.nodes, conn_info.peer_id(), id);
+                        debug_assert!(_removed);
                         Async::Ready(CollectionEvent::NodeClosed {
+                            id: ConnectionId(id),
                             conn_info,
                             error: err,
                             user_data,
                         })
                     },
+                    (TaskState::Connected(conn_info, user_data), tasks::Error::Closed, _handler) => {
+                        debug_assert!(_handler.is_none());
+                        let _removed = remove_task_id(&mut self.nodes, conn_info.peer_id(), id);
+                        debug_assert!(_removed);
+                        Async::Ready(CollectionEvent::NodeDisconnected {
+                            id: ConnectionId(id),
+                            conn_info,
+                            user_data,
+                        })
+                    },
                     (TaskState::Connected(_, _), tasks::Error::Reach(_), _) => {
                         panic!("A tasks::Error::Reach can only happen before we are connected \
                                 to a node; therefore the TaskState won't be Connected; QED");
@@ -504,21 +655,16 @@ where
                 }))
             },
             tasks::Event::NodeEvent { task, event } => {
-                let conn_info = match task.user_data() {
-                    TaskState::Connected(conn_info, _) => conn_info.clone(),
-                    _ => panic!("we can only receive NodeEvent events from a task after we \
-                                 received a corresponding NodeReached event from that same task; \
-                                 when we receive a NodeReached event, we ensure that the entry in \
-                                 self.tasks is switched to the Connected state; QED"),
-                };
+                let id = task.id();
                 drop(task);
                 Async::Ready(CollectionEvent::NodeEvent {
                     // TODO: normally we'd build a `PeerMut` manually here, but the borrow checker
                     //       doesn't like it
-                    peer: self.peer_mut(&conn_info.peer_id())
+                    peer: self.connection_mut(ConnectionId(id))
                         .expect("we can only receive NodeEvent events from a task after we \
-                                 received a corresponding NodeReached event from that same task;\
-                                 when that happens, peer_mut will always return Some; QED"),
+                                 received a corresponding NodeReached event from that same task; \
+                                 when we receive a NodeReached event, we ensure that the entry in \
+                                 self.tasks is switched to the Connected state; QED"),
                     event,
                 })
             }
@@ -526,6 +672,30 @@ where
     }
 }
 
+/// Removes `task_id` from the list of tasks of `peer_id`, and removes the entry of the peer if the
+/// list becomes empty. Returns `false` if the task wasn't found.
+fn remove_task_id<TPeerId>(nodes: &mut FnvHashMap<TPeerId, SmallVec<[TaskId; 1]>>, peer_id: &TPeerId, task_id: TaskId) -> bool
+where
+    TPeerId: Eq + Hash,
+{
+    let (found, now_empty) = match nodes.get_mut(peer_id) {
+        Some(ids) => match ids.iter().position(|id| *id == task_id) {
+            Some(pos) => {
+                ids.remove(pos);
+                (true, ids.is_empty())
+            }
+            None => (false, false),
+        },
+        None => (false, false),
+    };
+
+    if now_empty {
+        nodes.remove(peer_id);
+    }
+
+    found
+}
+
 /// Reach attempt interrupt errors.
 #[derive(Debug)]
 pub enum InterruptError {
@@ -572,7 +742,7 @@ where
 /// Access to a peer in the collection.
 pub struct PeerMut<'a, TInEvent, TUserData, TConnInfo = PeerId, TPeerId = PeerId> {
     inner: TaskEntry<'a, TInEvent, TaskState<TConnInfo, TUserData>>,
-    nodes: &'a mut FnvHashMap<TPeerId, TaskId>,
+    nodes: &'a mut FnvHashMap<TPeerId, SmallVec<[TaskId; 1]>>,
 }
 
 impl<'a, TInEvent, TUserData, TConnInfo, TPeerId> PeerMut<'a, TInEvent, TUserData, TConnInfo, TPeerId> {
@@ -586,6 +756,11 @@ impl<'a, TInEvent, TUserData, TConnInfo, TPeerId> PeerMut<'a, TInEvent, TUserDat
                          state; QED")
         }
     }
+
+    /// Returns the identifier of the connection.
+    pub fn connection_id(&self) -> ConnectionId {
+        ConnectionId(self.inner.id())
+    }
 }
 
 impl<'a, TInEvent, TUserData, TConnInfo, TPeerId> PeerMut<'a, TInEvent, TUserData, TConnInfo, TPeerId>
@@ -626,14 +801,22 @@ where
         self.inner.complete_send_event()
     }
 
-    /// Closes the connections to this node. Returns the user data.
+    /// Starts gracefully closing this connection to the node.
+    ///
+    /// The connection stays in the collection until it is fully closed, at which point a
+    /// `NodeDisconnected` event is produced.
+    pub fn disconnect(&mut self) {
+        self.inner.disconnect()
+    }
+
+    /// Closes this connection to the node. Returns the user data.
     ///
-    /// No further event will be generated for this node.
+    /// No further event will be generated for this connection.
     pub fn close(self) -> TUserData {
         let task_id = self.inner.id();
         if let TaskState::Connected(conn_info, user_data) = self.inner.close().into_user_data() {
-            let old_task_id = self.nodes.remove(conn_info.peer_id());
-            debug_assert_eq!(old_task_id, Some(task_id));
+            let _removed = remove_task_id(self.nodes, conn_info.peer_id(), task_id);
+            debug_assert!(_removed);
             user_data
         } else {
             panic!("a PeerMut can only be created if an entry is present in nodes; an entry in \
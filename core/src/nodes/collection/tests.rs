@@ -123,6 +123,50 @@ fn accepting_a_node_yields_new_entry() {
     rt.block_on(fut).expect("running the future works");
 }
 
+#[test]
+fn multiple_connections_to_the_same_node_are_kept_up_to_the_limit() {
+    let mut cs = TestCollectionStream::new();
+    cs.set_max_connections_per_node(NonZeroUsize::new(2).unwrap());
+    let peer_id = PeerId::random();
+    for _ in 0 .. 3 {
+        let fut = future::ok((peer_id.clone(), DummyMuxer::new()));
+        cs.add_reach_attempt(fut, Handler::default());
+    }
+
+    let mut rt = Runtime::new().unwrap();
+    let mut accepted = Vec::new();
+    let fut = future::poll_fn(move || -> Poll<(), ()> {
+        while accepted.len() < 3 {
+            match cs.poll() {
+                Async::NotReady => return Ok(Async::NotReady),
+                Async::Ready(CollectionEvent::NodeReached(reach_ev)) => {
+                    let replaced = reach_ev.replaced_connection();
+                    let (accept_ev, _) = reach_ev.accept(());
+                    match accepted.len() {
+                        0 | 1 => {
+                            assert!(replaced.is_none());
+                            assert_matches!(accept_ev, CollectionNodeAccept::NewEntry);
+                        }
+                        2 => {
+                            assert_eq!(replaced, Some(accepted[0]));
+                            assert_matches!(accept_ev, CollectionNodeAccept::ReplacedExisting(..));
+                        }
+                        _ => unreachable!()
+                    }
+                }
+                Async::Ready(_) => unreachable!()
+            }
+            let newest = cs.connection_ids(&peer_id).last().expect("a connection was just accepted");
+            accepted.push(newest);
+        }
+        assert_eq!(cs.num_connections(), 2);
+        assert_eq!(cs.connection_ids(&peer_id).collect::<Vec<_>>(), vec![accepted[1], accepted[2]]);
+        assert_eq!(cs.peer_mut(&peer_id).unwrap().connection_id(), accepted[2]);
+        Ok(Async::Ready(()))
+    });
+    rt.block_on(fut).expect("running the future works");
+}
+
 #[test]
 fn events_in_a_node_reaches_the_collection_stream() {
     let cs = Arc::new(Mutex::new(TestCollectionStream::new()));
@@ -126,6 +126,8 @@ where
     Closed {
         /// The listener that closed.
         listener: TTrans::Listener,
+        /// The addresses that the listener was listening on, and which are no longer listened on.
+        addresses: Vec<Multiaddr>,
         /// The error that happened. `Ok` if gracefully closed.
         result: Result<(), <TTrans::Listener as Stream>::Error>,
     },
@@ -176,6 +178,13 @@ where
         self.listeners.iter().flat_map(|l| l.addresses.iter())
     }
 
+    /// Closes all the listeners, and returns the addresses they were listening on.
+    ///
+    /// No `Closed` event is produced for them.
+    pub fn close_all(&mut self) -> Vec<Multiaddr> {
+        self.listeners.drain(..).flat_map(|l| l.addresses.into_iter()).collect()
+    }
+
     /// Provides an API similar to `Stream`, except that it cannot error.
     pub fn poll(&mut self) -> Async<ListenersEvent<TTrans>> {
         // We remove each element from `listeners` one by one and add them back.
@@ -215,12 +224,14 @@ where
                 Ok(Async::Ready(None)) => {
                     return Async::Ready(ListenersEvent::Closed {
                         listener: listener.listener,
+                        addresses: listener.addresses.into_vec(),
                         result: Ok(()),
                     })
                 }
                 Err(err) => {
                     return Async::Ready(ListenersEvent::Closed {
                         listener: listener.listener,
+                        addresses: listener.addresses.into_vec(),
                         result: Err(err),
                     })
                 }
@@ -276,8 +287,9 @@ where
                 .debug_struct("ListenersEvent::Incoming")
                 .field("listen_addr", listen_addr)
                 .finish(),
-            ListenersEvent::Closed { result, .. } => f
+            ListenersEvent::Closed { addresses, result, .. } => f
                 .debug_struct("ListenersEvent::Closed")
+                .field("addresses", addresses)
                 .field("result", result)
                 .finish(),
         }
@@ -33,7 +33,7 @@ pub mod listeners;
 pub mod node;
 pub mod network;
 
-pub use collection::ConnectionInfo;
+pub use collection::{ConnectionId, ConnectionInfo};
 pub use node::Substream;
 pub use handled_node::{NodeHandlerEvent, NodeHandlerEndpoint};
-pub use network::{Peer, Network, NetworkEvent};
+pub use network::{EstablishedConnection, Peer, Network, NetworkEvent};
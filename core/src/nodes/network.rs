@@ -27,6 +27,7 @@ use crate::{
             CollectionNodeAccept,
             CollectionReachEvent,
             CollectionStream,
+            ConnectionId,
             ConnectionInfo,
             ReachAttemptId,
             InterruptedReachAttempt
@@ -36,7 +37,8 @@ use crate::{
             NodeHandler
         },
         handled_node::IntoNodeHandler,
-        node::Substream
+        node::Substream,
+        tasks::TaskExecutor
     },
     nodes::listeners::{ListenersEvent, ListenersStream},
     transport::{Transport, TransportError}
@@ -47,11 +49,15 @@ use std::{
     collections::hash_map::{Entry, OccupiedEntry},
     error,
     fmt,
+    iter,
     hash::Hash,
-    num::NonZeroUsize,
+    num::{NonZeroU8, NonZeroU32, NonZeroUsize},
+    time::Duration,
 };
+use smallvec::SmallVec;
+use wasm_timer::Instant;
 
-pub use crate::nodes::collection::StartTakeOver;
+pub use crate::nodes::collection::{ConnectionId, StartTakeOver};
 
 mod tests;
 
@@ -64,14 +70,14 @@ where
     listeners: ListenersStream<TTrans>,
 
     /// The nodes currently active.
-    active_nodes: CollectionStream<TInEvent, TOutEvent, THandler, InternalReachErr<TTrans::Error, TConnInfo>, THandlerErr, (), (TConnInfo, ConnectedPoint), TPeerId>,
+    active_nodes: CollectionStream<TInEvent, TOutEvent, THandler, InternalReachErr<TTrans::Error, TConnInfo>, THandlerErr, Instant, (TConnInfo, ConnectedPoint), TPeerId>,
 
     /// The reach attempts of the network.
     /// This needs to be a separate struct in order to handle multiple mutable borrows issues.
     reach_attempts: ReachAttempts<TPeerId>,
 
-    /// Max numer of incoming connections.
-    incoming_limit: Option<u32>,
+    /// Limits on the number of pending and established connections.
+    limits: ConnectionLimits,
 
     /// Unfinished take over message to be delivered.
     ///
@@ -81,7 +87,7 @@ where
     /// If the pair's second element is `AsyncSink::Ready`, the take over
     /// message has been sent and needs to be flushed using
     /// `PeerMut::complete_take_over`.
-    take_over_to_complete: Option<(TPeerId, AsyncSink<InterruptedReachAttempt<TInEvent, (TConnInfo, ConnectedPoint), ()>>)>
+    take_over_to_complete: Option<(TPeerId, AsyncSink<InterruptedReachAttempt<TInEvent, (TConnInfo, ConnectedPoint), Instant>>)>
 }
 
 impl<TTrans, TInEvent, TOutEvent, THandler, THandlerErr, TConnInfo, TPeerId> fmt::Debug for
@@ -96,7 +102,7 @@ where
             .field("listeners", &self.listeners)
             .field("active_nodes", &self.active_nodes)
             .field("reach_attempts", &self.reach_attempts)
-            .field("incoming_limit", &self.incoming_limit)
+            .field("limits", &self.limits)
             .field("take_over_to_complete", &self.take_over_to_complete)
             .finish()
     }
@@ -125,7 +131,7 @@ struct ReachAttempts<TPeerId> {
     /// the peer ID.
     other_reach_attempts: Vec<(ReachAttemptId, ConnectedPoint)>,
 
-    /// For each peer ID we're connected to, contains the endpoint we're connected to.
+    /// For each peer ID we're connected to, contains the endpoint of the most recent connection.
     /// Always in sync with `active_nodes`.
     connected_points: FnvHashMap<TPeerId, ConnectedPoint>,
 }
@@ -144,17 +150,177 @@ where
     }
 }
 
-/// Attempt to reach a peer.
+impl<TPeerId> ReachAttempts<TPeerId> {
+    /// Returns the number of outgoing connections that are currently being negotiated, whether
+    /// the `PeerId` of the remote is known or not.
+    fn num_pending_outgoing(&self) -> usize {
+        self.out_reach_attempts.values().map(|a| a.in_progress.len()).sum::<usize>() +
+            self.other_reach_attempts.iter().filter(|(_, e)| e.is_dialer()).count()
+    }
+
+    /// Returns the number of incoming connections that are currently being negotiated.
+    fn num_pending_incoming(&self) -> usize {
+        self.other_reach_attempts.iter().filter(|(_, e)| e.is_listener()).count()
+    }
+}
+
+/// Limits on the number of connections a `Network` maintains.
+///
+/// All limits are disabled by default, except for the number of connections per peer and the
+/// dial concurrency factor which both default to 1.
 #[derive(Debug, Clone)]
+pub struct ConnectionLimits {
+    max_pending_incoming: Option<u32>,
+    max_pending_outgoing: Option<u32>,
+    max_established: Option<u32>,
+    max_established_per_peer: NonZeroU32,
+    dial_concurrency_factor: NonZeroU8,
+}
+
+impl Default for ConnectionLimits {
+    fn default() -> Self {
+        ConnectionLimits {
+            max_pending_incoming: None,
+            max_pending_outgoing: None,
+            max_established: None,
+            max_established_per_peer: NonZeroU32::new(1).expect("1 > 0"),
+            dial_concurrency_factor: NonZeroU8::new(1).expect("1 > 0"),
+        }
+    }
+}
+
+impl ConnectionLimits {
+    /// Configures the maximum number of incoming connections being negotiated at the same time.
+    ///
+    /// When the limit is reached, the listeners are no longer polled until a pending incoming
+    /// connection either succeeds or fails.
+    pub fn with_max_pending_incoming(mut self, limit: Option<u32>) -> Self {
+        self.max_pending_incoming = limit;
+        self
+    }
+
+    /// Configures the maximum number of outgoing connections being negotiated at the same time.
+    ///
+    /// Dialing attempts beyond this limit fail with a `ConnectionLimit` error.
+    pub fn with_max_pending_outgoing(mut self, limit: Option<u32>) -> Self {
+        self.max_pending_outgoing = limit;
+        self
+    }
+
+    /// Configures the maximum number of established connections.
+    ///
+    /// Incoming connections that are reached while the limit is reached are closed with a
+    /// `ConnectionLimit` error. Dialing attempts are refused as long as the limit is reached.
+    pub fn with_max_established(mut self, limit: Option<u32>) -> Self {
+        self.max_established = limit;
+        self
+    }
+
+    /// Configures the maximum number of simultaneous connections to the same peer.
+    ///
+    /// When a new connection to a peer that already has that many connections is established,
+    /// the oldest connection is closed and replaced with the new one.
+    pub fn with_max_established_per_peer(mut self, limit: NonZeroU32) -> Self {
+        self.max_established_per_peer = limit;
+        self
+    }
+
+    /// Configures the number of addresses of the same peer that are dialed at the same time by
+    /// `PeerNotConnected::connect_iter_concurrent`.
+    ///
+    /// Additional attempts are only started as long as the limit of pending outgoing connections
+    /// isn't reached. The remaining addresses are queued and dialed as the attempts in progress
+    /// fail.
+    pub fn with_dial_concurrency_factor(mut self, factor: NonZeroU8) -> Self {
+        self.dial_concurrency_factor = factor;
+        self
+    }
+
+    /// Returns the maximum number of incoming connections being negotiated at the same time.
+    pub fn max_pending_incoming(&self) -> Option<u32> {
+        self.max_pending_incoming
+    }
+
+    /// Returns the maximum number of outgoing connections being negotiated at the same time.
+    pub fn max_pending_outgoing(&self) -> Option<u32> {
+        self.max_pending_outgoing
+    }
+
+    /// Returns the maximum number of established connections.
+    pub fn max_established(&self) -> Option<u32> {
+        self.max_established
+    }
+
+    /// Returns the maximum number of simultaneous connections to the same peer.
+    pub fn max_established_per_peer(&self) -> NonZeroU32 {
+        self.max_established_per_peer
+    }
+
+    /// Returns the number of addresses of the same peer that are dialed at the same time.
+    pub fn dial_concurrency_factor(&self) -> NonZeroU8 {
+        self.dial_concurrency_factor
+    }
+
+    /// Checks whether a new outgoing connection attempt is permitted, given the number of
+    /// established connections.
+    fn check_outgoing<TPeerId>(&self, reach_attempts: &ReachAttempts<TPeerId>, num_established: usize) -> Result<(), ConnectionLimit> {
+        check_limit(self.max_pending_outgoing, reach_attempts.num_pending_outgoing())?;
+        check_limit(self.max_established, num_established)
+    }
+
+    /// Checks whether a newly-reached incoming connection may be established, given the number
+    /// of established connections.
+    fn check_incoming_established(&self, num_established: usize) -> Result<(), ConnectionLimit> {
+        check_limit(self.max_established, num_established)
+    }
+}
+
+/// Returns an error if `current` has reached `limit`.
+fn check_limit(limit: Option<u32>, current: usize) -> Result<(), ConnectionLimit> {
+    match limit {
+        Some(limit) if current >= limit as usize => {
+            Err(ConnectionLimit { limit, current: current as u32 })
+        }
+        _ => Ok(())
+    }
+}
+
+/// Information about a connection limit that has been reached.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct ConnectionLimit {
+    /// The maximum number of connections.
+    pub limit: u32,
+    /// The current number of connections.
+    pub current: u32,
+}
+
+impl fmt::Display for ConnectionLimit {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Connection limit reached: {}/{}", self.current, self.limit)
+    }
+}
+
+impl error::Error for ConnectionLimit {}
+
+/// Attempt to reach a peer.
+#[derive(Debug, Clone, Default)]
 struct OutReachAttempt {
-    /// Identifier for the reach attempt.
-    id: ReachAttemptId,
-    /// Multiaddr currently being attempted.
-    cur_attempted: Multiaddr,
-    /// Multiaddresses to attempt if the current one fails.
+    /// Reach attempts currently in progress, with the multiaddr each of them is dialing.
+    ///
+    /// Only empty for the short time between an attempt failing and the next multiaddr of
+    /// `next_attempts` being dialed.
+    in_progress: SmallVec<[(ReachAttemptId, Multiaddr); 4]>,
+    /// Multiaddresses to attempt if one of the attempts in progress fails.
     next_attempts: Vec<Multiaddr>,
 }
 
+impl OutReachAttempt {
+    /// Returns the multiaddr dialed by the given reach attempt, if it belongs to this attempt.
+    fn attempted_addr(&self, id: ReachAttemptId) -> Option<&Multiaddr> {
+        self.in_progress.iter().find(|(i, _)| *i == id).map(|(_, a)| a)
+    }
+}
+
 /// Event that can happen on the `Network`.
 pub enum NetworkEvent<'a, TTrans, TInEvent, TOutEvent, THandler, THandlerErr, TConnInfo = PeerId, TPeerId = PeerId>
 where
@@ -164,6 +330,8 @@ where
     ListenerClosed {
         /// The listener which closed.
         listener: TTrans::Listener,
+        /// The addresses that the listener was listening on, and which are no longer listened on.
+        addresses: Vec<Multiaddr>,
         /// The error that happened. `Ok` if gracefully closed.
         result: Result<(), <TTrans::Listener as Stream>::Error>,
     },
@@ -197,7 +365,11 @@ where
     },
 
     /// A new connection to a peer has been opened.
+    ///
+    /// If multiple connections per peer are allowed, this event is produced for each of them.
     Connected {
+        /// Identifier of the new connection.
+        connection: ConnectionId,
         /// Information about the connection, including the peer ID.
         conn_info: TConnInfo,
         /// If `Listener`, then we received the connection. If `Dial`, then it's a connection that
@@ -207,6 +379,10 @@ where
 
     /// A connection to a peer has been replaced with a new one.
     Replaced {
+        /// Identifier of the new connection.
+        connection: ConnectionId,
+        /// Identifier of the connection that has been closed.
+        closed_connection: ConnectionId,
         /// Information about the new connection. The `TPeerId` is the same as the one as the one
         /// in `old_info`.
         new_info: TConnInfo,
@@ -222,6 +398,8 @@ where
 
     /// The handler of a node has produced an error.
     NodeClosed {
+        /// Identifier of the connection that has been closed.
+        connection: ConnectionId,
         /// Information about the connection that has been closed.
         conn_info: TConnInfo,
         /// Endpoint we were connected to.
@@ -230,6 +408,17 @@ where
         error: HandledNodeError<THandlerErr>,
     },
 
+    /// A connection has been gracefully closed following a call to
+    /// `PeerConnected::disconnect`.
+    NodeDisconnected {
+        /// Identifier of the connection that has been closed.
+        connection: ConnectionId,
+        /// Information about the connection that has been closed.
+        conn_info: TConnInfo,
+        /// Endpoint we were connected to.
+        endpoint: ConnectedPoint,
+    },
+
     /// Failed to reach a peer that we were trying to dial.
     DialError {
         /// New state of a peer.
@@ -259,6 +448,8 @@ where
 
     /// A node produced a custom event.
     NodeEvent {
+        /// Identifier of the connection that produced the event.
+        connection: ConnectionId,
         /// Connection that produced the event.
         conn_info: TConnInfo,
         /// Event that was produced by the node.
@@ -288,8 +479,9 @@ where
                     .field("listen_addr", listen_addr)
                     .finish()
             }
-            NetworkEvent::ListenerClosed { ref result, .. } => {
+            NetworkEvent::ListenerClosed { ref addresses, ref result, .. } => {
                 f.debug_struct("ListenerClosed")
+                    .field("addresses", addresses)
                     .field("result", result)
                     .finish()
             }
@@ -310,27 +502,45 @@ where
                     .field("error", error)
                     .finish()
             }
-            NetworkEvent::Connected { ref conn_info, ref endpoint } => {
+            NetworkEvent::Connected { ref connection, ref conn_info, ref endpoint } => {
                 f.debug_struct("Connected")
+                    .field("connection", connection)
                     .field("conn_info", conn_info)
                     .field("endpoint", endpoint)
                     .finish()
             }
-            NetworkEvent::Replaced { ref new_info, ref old_info, ref closed_endpoint, ref endpoint } => {
+            NetworkEvent::Replaced {
+                ref connection,
+                ref closed_connection,
+                ref new_info,
+                ref old_info,
+                ref closed_endpoint,
+                ref endpoint
+            } => {
                 f.debug_struct("Replaced")
+                    .field("connection", connection)
+                    .field("closed_connection", closed_connection)
                     .field("new_info", new_info)
                     .field("old_info", old_info)
                     .field("closed_endpoint", closed_endpoint)
                     .field("endpoint", endpoint)
                     .finish()
             }
-            NetworkEvent::NodeClosed { ref conn_info, ref endpoint, ref error } => {
+            NetworkEvent::NodeClosed { ref connection, ref conn_info, ref endpoint, ref error } => {
                 f.debug_struct("NodeClosed")
+                    .field("connection", connection)
                     .field("conn_info", conn_info)
                     .field("endpoint", endpoint)
                     .field("error", error)
                     .finish()
             }
+            NetworkEvent::NodeDisconnected { ref connection, ref conn_info, ref endpoint } => {
+                f.debug_struct("NodeDisconnected")
+                    .field("connection", connection)
+                    .field("conn_info", conn_info)
+                    .field("endpoint", endpoint)
+                    .finish()
+            }
             NetworkEvent::DialError { ref new_state, ref peer_id, ref multiaddr, ref error } => {
                 f.debug_struct("DialError")
                     .field("new_state", new_state)
@@ -345,8 +555,9 @@ where
                     .field("error", error)
                     .finish()
             }
-            NetworkEvent::NodeEvent { ref conn_info, ref event } => {
+            NetworkEvent::NodeEvent { ref connection, ref conn_info, ref event } => {
                 f.debug_struct("NodeEvent")
+                    .field("connection", connection)
                     .field("conn_info", conn_info)
                     .field("event", event)
                     .finish()
@@ -368,6 +579,8 @@ enum InternalReachErr<TTransErr, TConnInfo> {
     },
     /// The negotiated `PeerId` is the same as the one of the local node.
     FoundLocalPeerId,
+    /// A connection limit has been reached.
+    ConnectionLimit(ConnectionLimit),
 }
 
 impl<TTransErr, TConnInfo> fmt::Display for InternalReachErr<TTransErr, TConnInfo>
@@ -384,6 +597,7 @@ where
             InternalReachErr::FoundLocalPeerId => {
                 write!(f, "Remote has the same PeerId as us")
             }
+            InternalReachErr::ConnectionLimit(limit) => write!(f, "{}", limit),
         }
     }
 }
@@ -398,6 +612,7 @@ where
             InternalReachErr::Transport(err) => Some(err),
             InternalReachErr::PeerIdMismatch { .. } => None,
             InternalReachErr::FoundLocalPeerId => None,
+            InternalReachErr::ConnectionLimit(limit) => Some(limit),
         }
     }
 }
@@ -427,7 +642,10 @@ pub enum NetworkReachError<TTransErr, TConnInfo> {
     PeerIdMismatch {
         /// The information about the other connection.
         obtained: TConnInfo,
-    }
+    },
+
+    /// The attempt was refused because a connection limit has been reached.
+    ConnectionLimit(ConnectionLimit),
 }
 
 impl<TTransErr, TConnInfo> fmt::Display for NetworkReachError<TTransErr, TConnInfo>
@@ -441,6 +659,7 @@ where
             NetworkReachError::PeerIdMismatch { obtained } => {
                 write!(f, "Peer ID mismatch, obtained: {:?}", obtained)
             },
+            NetworkReachError::ConnectionLimit(limit) => write!(f, "{}", limit),
         }
     }
 }
@@ -454,6 +673,7 @@ where
         match self {
             NetworkReachError::Transport(err) => Some(err),
             NetworkReachError::PeerIdMismatch { .. } => None,
+            NetworkReachError::ConnectionLimit(limit) => Some(limit),
         }
     }
 }
@@ -465,6 +685,8 @@ pub enum UnknownPeerDialErr<TTransErr> {
     Transport(TransportError<TTransErr>),
     /// The negotiated `PeerId` is the same as the local node.
     FoundLocalPeerId,
+    /// The attempt was refused because a connection limit has been reached.
+    ConnectionLimit(ConnectionLimit),
 }
 
 impl<TTransErr> fmt::Display for UnknownPeerDialErr<TTransErr>
@@ -476,6 +698,7 @@ where TTransErr: fmt::Display
             UnknownPeerDialErr::FoundLocalPeerId => {
                 write!(f, "Unknown peer has same PeerId as us")
             },
+            UnknownPeerDialErr::ConnectionLimit(limit) => write!(f, "{}", limit),
         }
     }
 }
@@ -487,6 +710,7 @@ where TTransErr: error::Error + 'static
         match self {
             UnknownPeerDialErr::Transport(err) => Some(err),
             UnknownPeerDialErr::FoundLocalPeerId => None,
+            UnknownPeerDialErr::ConnectionLimit(limit) => Some(limit),
         }
     }
 }
@@ -502,6 +726,8 @@ pub enum IncomingError<TTransErr> {
     DeniedLowerPriority,
     /// The negotiated `PeerId` is the same as the local node.
     FoundLocalPeerId,
+    /// The connection was closed because a connection limit has been reached.
+    ConnectionLimit(ConnectionLimit),
 }
 
 impl<TTransErr> fmt::Display for IncomingError<TTransErr>
@@ -516,6 +742,7 @@ where TTransErr: fmt::Display
             IncomingError::FoundLocalPeerId => {
                 write!(f, "Incoming connection has same PeerId as us")
             },
+            IncomingError::ConnectionLimit(limit) => write!(f, "{}", limit),
         }
     }
 }
@@ -528,6 +755,7 @@ where TTransErr: error::Error + 'static
             IncomingError::Transport(err) => Some(err),
             IncomingError::DeniedLowerPriority => None,
             IncomingError::FoundLocalPeerId => None,
+            IncomingError::ConnectionLimit(limit) => Some(limit),
         }
     }
 }
@@ -545,7 +773,7 @@ where TTrans: Transport
     /// Address used to send back data to the remote.
     send_back_addr: Multiaddr,
     /// Reference to the `active_nodes` field of the `Network`.
-    active_nodes: &'a mut CollectionStream<TInEvent, TOutEvent, THandler, InternalReachErr<TTrans::Error, TConnInfo>, THandlerErr, (), (TConnInfo, ConnectedPoint), TPeerId>,
+    active_nodes: &'a mut CollectionStream<TInEvent, TOutEvent, THandler, InternalReachErr<TTrans::Error, TConnInfo>, THandlerErr, Instant, (TConnInfo, ConnectedPoint), TPeerId>,
     /// Reference to the `other_reach_attempts` field of the `Network`.
     other_reach_attempts: &'a mut Vec<(ReachAttemptId, ConnectedPoint)>,
 }
@@ -647,6 +875,44 @@ impl<'a> IncomingInfo<'a> {
     }
 }
 
+/// Snapshot of an established connection, as returned by `Network::connections`.
+#[derive(Debug)]
+pub struct EstablishedConnection<'a, TConnInfo> {
+    id: ConnectionId,
+    info: &'a TConnInfo,
+    endpoint: &'a ConnectedPoint,
+    established: Instant,
+}
+
+impl<'a, TConnInfo> EstablishedConnection<'a, TConnInfo> {
+    /// Returns the identifier of the connection.
+    pub fn id(&self) -> ConnectionId {
+        self.id
+    }
+
+    /// Returns the information about the connection produced by the transport, which contains
+    /// the identity of the remote.
+    pub fn info(&self) -> &'a TConnInfo {
+        self.info
+    }
+
+    /// Returns the endpoint of the connection, i.e. whether we dialed it or accepted it, and
+    /// the addresses involved.
+    pub fn endpoint(&self) -> &'a ConnectedPoint {
+        self.endpoint
+    }
+
+    /// Returns the moment the connection has been established.
+    pub fn established(&self) -> Instant {
+        self.established
+    }
+
+    /// Returns how long ago the connection has been established.
+    pub fn age(&self) -> Duration {
+        self.established.elapsed()
+    }
+}
+
 impl<TTrans, TInEvent, TOutEvent, TMuxer, THandler, THandlerErr, TConnInfo, TPeerId>
     Network<TTrans, TInEvent, TOutEvent, THandler, THandlerErr, TConnInfo, TPeerId>
 where
@@ -661,29 +927,29 @@ where
 {
     /// Creates a new node events stream.
     pub fn new(transport: TTrans, local_peer_id: TPeerId) -> Self {
-        // TODO: with_capacity?
-        Network {
-            listeners: ListenersStream::new(transport),
-            active_nodes: CollectionStream::new(),
-            reach_attempts: ReachAttempts {
-                local_peer_id,
-                out_reach_attempts: Default::default(),
-                other_reach_attempts: Vec::new(),
-                connected_points: Default::default(),
-            },
-            incoming_limit: None,
-            take_over_to_complete: None
-        }
+        Network::new_with_limits(transport, local_peer_id, ConnectionLimits::default())
     }
 
     /// Creates a new node event stream with incoming connections limit.
     pub fn new_with_incoming_limit(transport: TTrans,
         local_peer_id: TPeerId, incoming_limit: Option<u32>) -> Self
     {
+        let limits = ConnectionLimits::default().with_max_pending_incoming(incoming_limit);
+        Network::new_with_limits(transport, local_peer_id, limits)
+    }
+
+    /// Creates a new node event stream with the given connection limits.
+    pub fn new_with_limits(transport: TTrans, local_peer_id: TPeerId, limits: ConnectionLimits) -> Self {
+        let mut active_nodes = CollectionStream::new();
+        let max_per_peer = NonZeroUsize::new(limits.max_established_per_peer.get() as usize)
+            .expect("a NonZeroU32 converted to usize is never zero; QED");
+        active_nodes.set_max_connections_per_node(max_per_peer);
+
+        // TODO: with_capacity?
         Network {
-            incoming_limit,
+            limits,
             listeners: ListenersStream::new(transport),
-            active_nodes: CollectionStream::new(),
+            active_nodes,
             reach_attempts: ReachAttempts {
                 local_peer_id,
                 out_reach_attempts: Default::default(),
@@ -694,6 +960,15 @@ where
         }
     }
 
+    /// Sets the way the tasks dedicated to the connections are run.
+    ///
+    /// By default, the tasks are spawned on the default tokio executor if there is one, and
+    /// polled as part of polling the `Network` otherwise. Only affects the connections opened
+    /// after this method has been called.
+    pub fn set_executor(&mut self, executor: TaskExecutor) {
+        self.active_nodes.set_executor(executor)
+    }
+
     /// Returns the transport passed when building this object.
     pub fn transport(&self) -> &TTrans {
         self.listeners.transport()
@@ -709,9 +984,21 @@ where
         self.listeners.listen_addrs()
     }
 
+    /// Closes all the listeners, and returns the addresses they were listening on.
+    ///
+    /// No `ListenerClosed` event is produced for them.
+    pub fn close_listeners(&mut self) -> Vec<Multiaddr> {
+        self.listeners.close_all()
+    }
+
     /// Returns limit on incoming connections.
     pub fn incoming_limit(&self) -> Option<u32> {
-        self.incoming_limit
+        self.limits.max_pending_incoming
+    }
+
+    /// Returns the connection limits of the network.
+    pub fn limits(&self) -> &ConnectionLimits {
+        &self.limits
     }
 
     /// Call this function in order to know which address remotes should dial to
@@ -764,6 +1051,15 @@ where
     {
         let local_peer_id = self.reach_attempts.local_peer_id.clone();
         let connected_point = ConnectedPoint::Dialer { address: addr.clone() };
+
+        let num_established = self.active_nodes.num_connections();
+        if let Err(limit) = self.limits.check_outgoing(&self.reach_attempts, num_established) {
+            let future = future::err(InternalReachErr::ConnectionLimit(limit));
+            let reach_id = self.active_nodes.add_reach_attempt(future, handler);
+            self.reach_attempts.other_reach_attempts.push((reach_id, connected_point));
+            return Ok(())
+        }
+
         let future = self.transport().clone().dial(addr)?
             .map_err(|err| InternalReachErr::Transport(TransportError::Other(err)))
             .and_then({
@@ -828,6 +1124,12 @@ where
         self.active_nodes.complete_broadcast()
     }
 
+    /// Returns the total number of established connections, which can be larger than the number
+    /// of connected peers if multiple connections per peer are allowed.
+    pub fn num_connections(&self) -> usize {
+        self.active_nodes.num_connections()
+    }
+
     /// Returns a list of all the peers we are currently connected to.
     ///
     /// Calling `peer()` with each `PeerId` is guaranteed to produce a `PeerConnected`.
@@ -837,6 +1139,17 @@ where
         self.active_nodes.connections()
     }
 
+    /// Returns a snapshot of all the established connections, in no particular order.
+    pub fn connections(&self) -> impl Iterator<Item = EstablishedConnection<'_, TConnInfo>> {
+        self.active_nodes.established_connections()
+            .map(|(id, (info, endpoint), established)| EstablishedConnection {
+                id,
+                info,
+                endpoint,
+                established: *established,
+            })
+    }
+
     /// Returns a list of all the nodes we are currently trying to reach.
     ///
     /// Calling `peer()` with each `PeerId` is guaranteed to produce a `PeerPendingConnect`
@@ -899,12 +1212,10 @@ where
         })
     }
 
-    /// Starts dialing out a multiaddress. `rest` is the list of multiaddresses to attempt if
-    /// `first` fails.
+    /// Starts dialing out a multiaddress, as part of the outgoing attempt to the given peer.
     ///
-    /// It is a logic error to call this method if we already have an outgoing attempt to the
-    /// given peer.
-    fn start_dial_out(&mut self, peer_id: TPeerId, handler: THandler, first: Multiaddr, rest: Vec<Multiaddr>)
+    /// The outgoing attempt is created if there isn't any for this peer yet.
+    fn start_dial_out(&mut self, peer_id: TPeerId, handler: THandler, addr: Multiaddr)
     where
         TTrans: Transport<Output = (TConnInfo, TMuxer)>,
         TTrans::Dial: Send + 'static,
@@ -917,10 +1228,22 @@ where
         TConnInfo: Send + 'static,
         TPeerId: Send + 'static,
     {
-        let reach_id = match self.transport().clone().dial(first.clone()) {
+        let num_established = self.active_nodes.num_connections();
+        if let Err(limit) = self.limits.check_outgoing(&self.reach_attempts, num_established) {
+            let fut = future::err(InternalReachErr::ConnectionLimit(limit));
+            let reach_id = self.active_nodes.add_reach_attempt(fut, handler);
+            self.reach_attempts.out_reach_attempts
+                .entry(peer_id)
+                .or_insert_with(OutReachAttempt::default)
+                .in_progress
+                .push((reach_id, addr));
+            return
+        }
+
+        let reach_id = match self.transport().clone().dial(addr.clone()) {
             Ok(fut) => {
                 let expected_peer_id = peer_id.clone();
-                let connected_point = ConnectedPoint::Dialer { address: first.clone() };
+                let connected_point = ConnectedPoint::Dialer { address: addr.clone() };
                 let fut = fut
                     .map_err(|err| InternalReachErr::Transport(TransportError::Other(err)))
                     .and_then(move |(actual_conn_info, muxer)| {
@@ -938,16 +1261,11 @@ where
             },
         };
 
-        let former = self.reach_attempts.out_reach_attempts.insert(
-            peer_id,
-            OutReachAttempt {
-                id: reach_id,
-                cur_attempted: first,
-                next_attempts: rest,
-            },
-        );
-
-        debug_assert!(former.is_none());
+        self.reach_attempts.out_reach_attempts
+            .entry(peer_id)
+            .or_insert_with(OutReachAttempt::default)
+            .in_progress
+            .push((reach_id, addr));
     }
 
     /// Provides an API similar to `Stream`, except that it cannot error.
@@ -971,8 +1289,8 @@ where
     {
         // Start by polling the listeners for events, but only if the number
         // of incoming connections does not exceed the limit.
-        match self.incoming_limit {
-            Some(x) if self.incoming_negotiated().count() >= (x as usize)
+        match self.limits.max_pending_incoming {
+            Some(x) if self.reach_attempts.num_pending_incoming() >= (x as usize)
                 => (),
             _ => {
                 match self.listeners.poll() {
@@ -994,8 +1312,8 @@ where
                     Async::Ready(ListenersEvent::AddressExpired { listen_addr }) => {
                         return Async::Ready(NetworkEvent::ExpiredListenerAddress { listen_addr })
                     }
-                    Async::Ready(ListenersEvent::Closed { listener, result }) => {
-                        return Async::Ready(NetworkEvent::ListenerClosed { listener, result })
+                    Async::Ready(ListenersEvent::Closed { listener, addresses, result }) => {
+                        return Async::Ready(NetworkEvent::ListenerClosed { listener, addresses, result })
                     }
                 }
             }
@@ -1020,11 +1338,13 @@ where
         }
 
         // Poll the existing nodes.
+        let num_established = self.active_nodes.num_connections();
         let (action, out_event);
+        let mut closed_peer = None;
         match self.active_nodes.poll() {
             Async::NotReady => return Async::NotReady,
             Async::Ready(CollectionEvent::NodeReached(reach_event)) => {
-                let (a, e) = handle_node_reached(&mut self.reach_attempts, reach_event);
+                let (a, e) = handle_node_reached(&mut self.reach_attempts, &self.limits, num_established, reach_event);
                 action = a;
                 out_event = e;
             }
@@ -1034,31 +1354,63 @@ where
                 out_event = e;
             }
             Async::Ready(CollectionEvent::NodeClosed {
+                id,
                 conn_info,
                 error,
                 ..
             }) => {
-                let endpoint = self.reach_attempts.connected_points.remove(conn_info.peer_id())
-                    .expect("We insert into connected_points whenever a connection is \
-                             opened and remove only when a connection is closed; the \
-                             underlying API is guaranteed to always deliver a connection \
-                             closed message after it has been opened, and no two closed \
-                             messages; QED");
+                let (conn_info, endpoint) = conn_info;
+                closed_peer = Some(conn_info.peer_id().clone());
                 action = Default::default();
                 out_event = NetworkEvent::NodeClosed {
-                    conn_info: conn_info.0,
+                    connection: id,
+                    conn_info,
                     endpoint,
                     error,
                 };
             }
+            Async::Ready(CollectionEvent::NodeDisconnected { id, conn_info, .. }) => {
+                let (conn_info, endpoint) = conn_info;
+                closed_peer = Some(conn_info.peer_id().clone());
+                action = Default::default();
+                out_event = NetworkEvent::NodeDisconnected {
+                    connection: id,
+                    conn_info,
+                    endpoint,
+                };
+            }
             Async::Ready(CollectionEvent::NodeEvent { peer, event }) => {
                 action = Default::default();
-                out_event = NetworkEvent::NodeEvent { conn_info: peer.info().0.clone(), event };
+                out_event = NetworkEvent::NodeEvent {
+                    connection: peer.connection_id(),
+                    conn_info: peer.info().0.clone(),
+                    event,
+                };
+            }
+        }
+
+        // If other connections to the peer of a closed connection remain, the endpoint of the
+        // most recent one becomes the endpoint of the peer.
+        if let Some(peer_id) = closed_peer {
+            match self.active_nodes.peer_mut(&peer_id) {
+                Some(remaining) => {
+                    let endpoint = remaining.info().1.clone();
+                    self.reach_attempts.connected_points.insert(peer_id, endpoint);
+                }
+                None => {
+                    self.reach_attempts.connected_points.remove(&peer_id);
+                }
             }
         }
 
-        if let Some((peer_id, handler, first, rest)) = action.start_dial_out {
-            self.start_dial_out(peer_id, handler, first, rest);
+        if let Some((peer_id, handler, addr)) = action.start_dial_out {
+            self.start_dial_out(peer_id, handler, addr);
+        }
+
+        // The other attempts of an outgoing attempt that succeeded are no longer needed. They
+        // might have already been reached, in which case the events they generate are discarded.
+        for id in action.interrupt {
+            let _ = self.active_nodes.interrupt(id);
         }
 
         if let Some((peer_id, interrupt)) = action.take_over {
@@ -1090,10 +1442,13 @@ where
 #[derive(Debug)]
 #[must_use]
 struct ActionItem<THandler, TPeerId> {
-    start_dial_out: Option<(TPeerId, THandler, Multiaddr, Vec<Multiaddr>)>,
+    /// A multiaddress should be dialed as part of the outgoing attempt to the given `PeerId`.
+    start_dial_out: Option<(TPeerId, THandler, Multiaddr)>,
     /// The `ReachAttemptId` should be interrupted, and the task for the given `PeerId` should take
     /// over it.
     take_over: Option<(TPeerId, ReachAttemptId)>,
+    /// These `ReachAttemptId`s should be interrupted.
+    interrupt: SmallVec<[ReachAttemptId; 4]>,
 }
 
 impl<THandler, TPeerId> Default for ActionItem<THandler, TPeerId> {
@@ -1101,6 +1456,7 @@ impl<THandler, TPeerId> Default for ActionItem<THandler, TPeerId> {
         ActionItem {
             start_dial_out: None,
             take_over: None,
+            interrupt: SmallVec::new(),
         }
     }
 }
@@ -1113,7 +1469,9 @@ impl<THandler, TPeerId> Default for ActionItem<THandler, TPeerId> {
 /// >           panics will likely happen.
 fn handle_node_reached<'a, TTrans, TMuxer, TInEvent, TOutEvent, THandler, THandlerErr, TConnInfo, TPeerId>(
     reach_attempts: &mut ReachAttempts<TPeerId>,
-    event: CollectionReachEvent<'_, TInEvent, TOutEvent, THandler, InternalReachErr<TTrans::Error, TConnInfo>, THandlerErr, (), (TConnInfo, ConnectedPoint), TPeerId>,
+    limits: &ConnectionLimits,
+    num_established: usize,
+    event: CollectionReachEvent<'_, TInEvent, TOutEvent, THandler, InternalReachErr<TTrans::Error, TConnInfo>, THandlerErr, Instant, (TConnInfo, ConnectedPoint), TPeerId>,
 ) -> (ActionItem<THandler, TPeerId>, NetworkEvent<'a, TTrans, TInEvent, TOutEvent, THandler, THandlerErr, TConnInfo, TPeerId>)
 where
     TTrans: Transport<Output = (TConnInfo, TMuxer)> + Clone,
@@ -1134,6 +1492,22 @@ where
     {
         let (_, opened_endpoint) = reach_attempts.other_reach_attempts.swap_remove(in_pos);
         let has_dial_prio = has_dial_prio(&reach_attempts.local_peer_id, event.peer_id());
+        let multiple_connections = limits.max_established_per_peer.get() > 1;
+
+        // Incoming connections are only checked against the limit of established connections
+        // once they are fully negotiated. A connection that replaces an existing one doesn't
+        // change the number of established connections.
+        if !event.would_replace() {
+            if let ConnectedPoint::Listener { listen_addr, send_back_addr } = &opened_endpoint {
+                if let Err(limit) = limits.check_incoming_established(num_established) {
+                    return (Default::default(), NetworkEvent::IncomingConnectionError {
+                        listen_addr: listen_addr.clone(),
+                        send_back_addr: send_back_addr.clone(),
+                        error: IncomingError::ConnectionLimit(limit),
+                    });
+                }
+            }
+        }
 
         // If we already have an active connection to this peer, a priority system comes into play.
         // If we have a lower peer ID than the incoming one, we drop an incoming connection.
@@ -1150,23 +1524,29 @@ where
         }
 
         // Set the endpoint for this peer.
-        let closed_endpoint = reach_attempts.connected_points.insert(event.peer_id().clone(), opened_endpoint.clone());
+        reach_attempts.connected_points.insert(event.peer_id().clone(), opened_endpoint.clone());
 
         // If we have dial priority, we keep the current outgoing attempt because it may already
         // have succeeded without us knowing. It is possible that the remote has already closed
         // its ougoing attempt because it sees our outgoing attempt as a success.
         // However we cancel any further multiaddress to attempt in any situation.
-        let action = if has_dial_prio {
+        // If multiple connections per peer are allowed, the outgoing attempt is left untouched,
+        // as both connections can coexist.
+        let action = if multiple_connections {
+            ActionItem::default()
+        } else if has_dial_prio {
             if let Some(attempt) = reach_attempts.out_reach_attempts.get_mut(&event.peer_id()) {
-                debug_assert_ne!(attempt.id, event.reach_attempt_id());
+                debug_assert!(attempt.attempted_addr(event.reach_attempt_id()).is_none());
                 attempt.next_attempts.clear();
             }
             ActionItem::default()
         } else {
             if let Some(attempt) = reach_attempts.out_reach_attempts.remove(&event.peer_id()) {
-                debug_assert_ne!(attempt.id, event.reach_attempt_id());
+                debug_assert!(attempt.attempted_addr(event.reach_attempt_id()).is_none());
+                let mut ids = attempt.in_progress.into_iter().map(|(id, _)| id);
                 ActionItem {
-                    take_over: Some((event.peer_id().clone(), attempt.id)),
+                    take_over: ids.next().map(|id| (event.peer_id().clone(), id)),
+                    interrupt: ids.collect(),
                     .. Default::default()
                 }
             } else {
@@ -1174,21 +1554,22 @@ where
             }
         };
 
-        let (outcome, conn_info) = event.accept(());
-        if let CollectionNodeAccept::ReplacedExisting(old_info, ()) = outcome {
-            let closed_endpoint = closed_endpoint
-                .expect("We insert into connected_points whenever a connection is opened and \
-                         remove only when a connection is closed; the underlying API is \
-                         guaranteed to always deliver a connection closed message after it has \
-                         been opened, and no two closed messages; QED");
+        let connection = ConnectionId::from(event.reach_attempt_id());
+        let closed_connection = event.replaced_connection();
+        let (outcome, conn_info) = event.accept(Instant::now());
+        if let CollectionNodeAccept::ReplacedExisting(old_info, _) = outcome {
             return (action, NetworkEvent::Replaced {
+                connection,
+                closed_connection: closed_connection
+                    .expect("replaced_connection returns Some if accepting replaces a connection; QED"),
                 new_info: conn_info.0,
                 old_info: old_info.0,
                 endpoint: opened_endpoint,
-                closed_endpoint,
+                closed_endpoint: old_info.1,
             });
         } else {
             return (action, NetworkEvent::Connected {
+                connection,
                 conn_info: conn_info.0,
                 endpoint: opened_endpoint
             });
@@ -1197,7 +1578,7 @@ where
 
     // Otherwise, try for outgoing attempts.
     let is_outgoing_and_ok = if let Some(attempt) = reach_attempts.out_reach_attempts.get(event.peer_id()) {
-        attempt.id == event.reach_attempt_id()
+        attempt.attempted_addr(event.reach_attempt_id()).is_some()
     } else {
         false
     };
@@ -1209,29 +1590,42 @@ where
             .expect("is_outgoing_and_ok is true only if reach_attempts.out_reach_attempts.get(event.peer_id()) \
                         returned Some");
 
+        // The other attempts dialing the same peer concurrently are interrupted.
+        let mut action = ActionItem::default();
+        let mut address = None;
+        for (id, addr) in attempt.in_progress {
+            if id == event.reach_attempt_id() {
+                address = Some(addr);
+            } else {
+                action.interrupt.push(id);
+            }
+        }
+
         let opened_endpoint = ConnectedPoint::Dialer {
-            address: attempt.cur_attempted,
+            address: address.expect("is_outgoing_and_ok is true only if the attempt contains the \
+                                      reach attempt id; QED"),
         };
 
-        let closed_endpoint = reach_attempts.connected_points
+        reach_attempts.connected_points
             .insert(event.peer_id().clone(), opened_endpoint.clone());
 
-        let (outcome, conn_info) = event.accept(());
-        if let CollectionNodeAccept::ReplacedExisting(old_info, ()) = outcome {
-            let closed_endpoint = closed_endpoint
-                .expect("We insert into connected_points whenever a connection is opened and \
-                        remove only when a connection is closed; the underlying API is guaranteed \
-                        to always deliver a connection closed message after it has been opened, \
-                        and no two closed messages; QED");
-            return (Default::default(), NetworkEvent::Replaced {
+        let connection = ConnectionId::from(event.reach_attempt_id());
+        let closed_connection = event.replaced_connection();
+        let (outcome, conn_info) = event.accept(Instant::now());
+        if let CollectionNodeAccept::ReplacedExisting(old_info, _) = outcome {
+            return (action, NetworkEvent::Replaced {
+                connection,
+                closed_connection: closed_connection
+                    .expect("replaced_connection returns Some if accepting replaces a connection; QED"),
                 new_info: conn_info.0,
                 old_info: old_info.0,
                 endpoint: opened_endpoint,
-                closed_endpoint,
+                closed_endpoint: old_info.1,
             });
 
         } else {
-            return (Default::default(), NetworkEvent::Connected {
+            return (action, NetworkEvent::Connected {
+                connection,
                 conn_info: conn_info.0,
                 endpoint: opened_endpoint
             });
@@ -1280,15 +1674,23 @@ where
     let out_reach_peer_id = reach_attempts
         .out_reach_attempts
         .iter()
-        .find(|(_, a)| a.id == reach_id)
+        .find(|(_, a)| a.attempted_addr(reach_id).is_some())
         .map(|(p, _)| p.clone());
     if let Some(peer_id) = out_reach_peer_id {
-        let attempt = reach_attempts.out_reach_attempts.remove(&peer_id)
+        let mut attempt = reach_attempts.out_reach_attempts.remove(&peer_id)
             .expect("out_reach_peer_id is a key that is grabbed from out_reach_attempts");
 
-        let num_remain = attempt.next_attempts.len();
-        let failed_addr = attempt.cur_attempted.clone();
+        let pos = attempt.in_progress.iter().position(|(id, _)| *id == reach_id)
+            .expect("out_reach_peer_id is the peer of the attempt containing reach_id; QED");
+        let (_, failed_addr) = attempt.in_progress.remove(pos);
+
+        // There is no point in trying the remaining addresses if a connection limit has been
+        // reached.
+        if let InternalReachErr::ConnectionLimit(_) = error {
+            attempt.next_attempts.clear();
+        }
 
+        let num_remain = attempt.in_progress.len() + attempt.next_attempts.len();
         let new_state = if reach_attempts.connected_points.contains_key(&peer_id) {
             PeerState::Connected
         } else if num_remain == 0 {
@@ -1301,21 +1703,25 @@ where
         };
 
         let action = if !attempt.next_attempts.is_empty() {
-            let mut attempt = attempt;
             let next_attempt = attempt.next_attempts.remove(0);
             ActionItem {
-                start_dial_out: Some((peer_id.clone(), handler, next_attempt, attempt.next_attempts)),
+                start_dial_out: Some((peer_id.clone(), handler, next_attempt)),
                 .. Default::default()
             }
         } else {
             Default::default()
         };
 
+        if action.start_dial_out.is_some() || !attempt.in_progress.is_empty() {
+            reach_attempts.out_reach_attempts.insert(peer_id.clone(), attempt);
+        }
+
         let error = match error {
             InternalReachErr::Transport(err) => NetworkReachError::Transport(err),
             InternalReachErr::PeerIdMismatch { obtained } => {
                 NetworkReachError::PeerIdMismatch { obtained }
             },
+            InternalReachErr::ConnectionLimit(limit) => NetworkReachError::ConnectionLimit(limit),
             InternalReachErr::FoundLocalPeerId => {
                 unreachable!("We only generate FoundLocalPeerId within dial() or accept(); neither \
                               of these methods add an entry to out_reach_attempts; QED")
@@ -1342,6 +1748,7 @@ where
                 let error = match error {
                     InternalReachErr::Transport(err) => UnknownPeerDialErr::Transport(err),
                     InternalReachErr::FoundLocalPeerId => UnknownPeerDialErr::FoundLocalPeerId,
+                    InternalReachErr::ConnectionLimit(limit) => UnknownPeerDialErr::ConnectionLimit(limit),
                     InternalReachErr::PeerIdMismatch { .. } => {
                         unreachable!("We only generate PeerIdMismatch within start_dial_out(),
                                       which doesn't add any entry in other_reach_attempts; QED")
@@ -1357,6 +1764,7 @@ where
                 let error = match error {
                     InternalReachErr::Transport(err) => IncomingError::Transport(err),
                     InternalReachErr::FoundLocalPeerId => IncomingError::FoundLocalPeerId,
+                    InternalReachErr::ConnectionLimit(limit) => IncomingError::ConnectionLimit(limit),
                     InternalReachErr::PeerIdMismatch { .. } => {
                         unreachable!("We only generate PeerIdMismatch within start_dial_out(),
                                       which doesn't add any entry in other_reach_attempts; QED")
@@ -1564,7 +1972,7 @@ pub struct PeerConnected<'a, TTrans, TInEvent, TOutEvent, THandler, THandlerErr,
 where TTrans: Transport,
 {
     /// Reference to the `active_nodes` of the parent.
-    active_nodes: &'a mut CollectionStream<TInEvent, TOutEvent, THandler, InternalReachErr<TTrans::Error, TConnInfo>, THandlerErr, (), (TConnInfo, ConnectedPoint), TPeerId>,
+    active_nodes: &'a mut CollectionStream<TInEvent, TOutEvent, THandler, InternalReachErr<TTrans::Error, TConnInfo>, THandlerErr, Instant, (TConnInfo, ConnectedPoint), TPeerId>,
     /// Reference to the `connected_points` field of the parent.
     connected_points: &'a mut FnvHashMap<TPeerId, ConnectedPoint>,
     /// Reference to the `out_reach_attempts` field of the parent.
@@ -1578,22 +1986,90 @@ where
     TConnInfo: ConnectionInfo<PeerId = TPeerId>,
     TPeerId: Eq + Hash,
 {
-    /// Closes the connection to this node.
+    /// Closes all the connections to this node.
     ///
     /// No `NodeClosed` message will be generated for this node.
     // TODO: consider returning a `PeerNotConnected`; however this makes all the borrows things
     // much more annoying to deal with
     pub fn close(self) {
         if let Some(reach_attempt) = self.out_reach_attempts.remove(&self.peer_id) {
-            self.active_nodes
-                .interrupt(reach_attempt.id)
-                .expect("Elements in out_reach_attempts are in sync with active_nodes; QED");
+            for (id, _) in reach_attempt.in_progress {
+                self.active_nodes
+                    .interrupt(id)
+                    .expect("Elements in out_reach_attempts are in sync with active_nodes; QED");
+            }
         }
 
         self.connected_points.remove(&self.peer_id);
-        self.active_nodes.peer_mut(&self.peer_id)
-            .expect("A PeerConnected is always created with a PeerId in active_nodes; QED")
+        while let Some(peer) = self.active_nodes.peer_mut(&self.peer_id) {
+            peer.close();
+        }
+    }
+
+    /// Starts gracefully closing all the connections to this node, and interrupts the ongoing
+    /// dialing attempts.
+    ///
+    /// The pending data of the connections is flushed before they are closed. The connections
+    /// remain established until then, and a `NodeDisconnected` event is produced for each of
+    /// them once it is fully closed.
+    pub fn disconnect(&mut self) {
+        if let Some(reach_attempt) = self.out_reach_attempts.remove(&self.peer_id) {
+            for (id, _) in reach_attempt.in_progress {
+                self.active_nodes
+                    .interrupt(id)
+                    .expect("Elements in out_reach_attempts are in sync with active_nodes; QED");
+            }
+        }
+
+        let connections = self.active_nodes.connection_ids(&self.peer_id).collect::<Vec<_>>();
+        for connection in connections {
+            self.active_nodes.connection_mut(connection)
+                .expect("connection_ids only returns established connections; QED")
+                .disconnect();
+        }
+    }
+
+    /// Closes a single connection to this node.
+    ///
+    /// No `NodeClosed` message will be generated for this connection. Returns `false` if the
+    /// connection doesn't belong to this node. If this was the last connection to the node, the
+    /// node is no longer connected afterwards.
+    pub fn close_connection(&mut self, connection: ConnectionId) -> bool
+    where
+        TPeerId: Clone,
+    {
+        if !self.active_nodes.connection_ids(&self.peer_id).any(|c| c == connection) {
+            return false;
+        }
+
+        self.active_nodes.connection_mut(connection)
+            .expect("connection_ids only returns established connections; QED")
             .close();
+
+        match self.active_nodes.peer_mut(&self.peer_id) {
+            Some(remaining) => {
+                let endpoint = remaining.info().1.clone();
+                self.connected_points.insert(self.peer_id.clone(), endpoint);
+            }
+            None => {
+                self.connected_points.remove(&self.peer_id);
+                if let Some(reach_attempt) = self.out_reach_attempts.remove(&self.peer_id) {
+                    for (id, _) in reach_attempt.in_progress {
+                        self.active_nodes
+                            .interrupt(id)
+                            .expect("Elements in out_reach_attempts are in sync with active_nodes; QED");
+                    }
+                }
+            }
+        }
+
+        true
+    }
+
+    /// Returns the identifiers of the connections to this node, from the oldest to the most
+    /// recent.
+    pub fn connection_ids(&self) -> impl Iterator<Item = ConnectionId> + '_ {
+        self.active_nodes.connection_ids(&self.peer_id)
     }
 
     /// Returns the connection info for this node.
@@ -1631,6 +2107,35 @@ where
             .expect("A PeerConnected is always created with a PeerId in active_nodes; QED")
             .complete_send_event()
     }
+
+    /// Start sending an event to a specific connection of the node.
+    ///
+    /// Returns `Err` with the event if the connection doesn't belong to this node.
+    pub fn start_send_event_to(&mut self, connection: ConnectionId, event: TInEvent)
+        -> Result<StartSend<TInEvent, ()>, TInEvent>
+    {
+        if !self.active_nodes.connection_ids(&self.peer_id).any(|c| c == connection) {
+            return Err(event);
+        }
+
+        Ok(self.active_nodes.connection_mut(connection)
+            .expect("connection_ids only returns established connections; QED")
+            .start_send_event(event))
+    }
+
+    /// Complete sending an event message to a specific connection, initiated by
+    /// `start_send_event_to`.
+    ///
+    /// Returns `Ok(Async::Ready(()))` if the connection has been closed in the meantime.
+    pub fn complete_send_event_to(&mut self, connection: ConnectionId) -> Poll<(), ()> {
+        if !self.active_nodes.connection_ids(&self.peer_id).any(|c| c == connection) {
+            return Ok(Async::Ready(()));
+        }
+
+        self.active_nodes.connection_mut(connection)
+            .expect("connection_ids only returns established connections; QED")
+            .complete_send_event()
+    }
 }
 
 /// Access to a peer we are attempting to connect to.
@@ -1640,7 +2145,7 @@ where
     TTrans: Transport
 {
     attempt: OccupiedEntry<'a, TPeerId, OutReachAttempt>,
-    active_nodes: &'a mut CollectionStream<TInEvent, TOutEvent, THandler, InternalReachErr<TTrans::Error, TConnInfo>, THandlerErr, (), (TConnInfo, ConnectedPoint), TPeerId>,
+    active_nodes: &'a mut CollectionStream<TInEvent, TOutEvent, THandler, InternalReachErr<TTrans::Error, TConnInfo>, THandlerErr, Instant, (TConnInfo, ConnectedPoint), TPeerId>,
 }
 
 impl<'a, TTrans, TInEvent, TOutEvent, THandler, THandlerErr, TConnInfo, TPeerId>
@@ -1655,19 +2160,31 @@ where
     // borrows
     pub fn interrupt(self) {
         let attempt = self.attempt.remove();
-        if self.active_nodes.interrupt(attempt.id).is_err() {
-            // TODO: improve proof or remove; this is too complicated right now
-            panic!("We retreived this attempt.id from out_reach_attempts. We insert in \
-                    out_reach_attempts only at the same time as we call add_reach_attempt. \
-                    Whenever we receive a NodeReached, NodeReplaced or ReachError event, which \
-                    invalidate the attempt.id, we also remove the corresponding entry in \
-                    out_reach_attempts.");
+        for (id, _) in attempt.in_progress {
+            if self.active_nodes.interrupt(id).is_err() {
+                // TODO: improve proof or remove; this is too complicated right now
+                panic!("We retreived this id from out_reach_attempts. We insert in \
+                        out_reach_attempts only at the same time as we call add_reach_attempt. \
+                        Whenever we receive a NodeReached, NodeReplaced or ReachError event, which \
+                        invalidate the id, we also remove it from the corresponding entry in \
+                        out_reach_attempts.");
+            }
         }
     }
 
     /// Returns the multiaddress we're currently trying to dial.
+    ///
+    /// If several multiaddresses are dialed concurrently, returns the one that was dialed first.
     pub fn attempted_multiaddr(&self) -> &Multiaddr {
-        &self.attempt.get().cur_attempted
+        &self.attempt.get().in_progress.first()
+            .expect("PeerPendingConnect is only built for attempts with at least one reach \
+                     attempt in progress; QED")
+            .1
+    }
+
+    /// Returns the multiaddresses we're currently trying to dial.
+    pub fn attempted_multiaddrs(&self) -> impl Iterator<Item = &Multiaddr> {
+        self.attempt.get().in_progress.iter().map(|(_, addr)| addr)
     }
 
     /// Returns a list of the multiaddresses we're going to try if the current dialing fails.
@@ -1688,7 +2205,8 @@ where
     ///
     /// Doesn't do anything if that multiaddress is already in the queue.
     pub fn append_multiaddr_attempt(&mut self, addr: Multiaddr) {
-        if self.attempt.get().next_attempts.iter().any(|a| a == &addr) {
+        let attempt = self.attempt.get();
+        if attempt.next_attempts.iter().any(|a| a == &addr) || attempt.in_progress.iter().any(|(_, a)| a == &addr) {
             return;
         }
 
@@ -1744,7 +2262,8 @@ where
         TConnInfo: fmt::Debug + ConnectionInfo<PeerId = TPeerId> + Send + 'static,
         TPeerId: Eq + Hash + Clone + Send + 'static,
     {
-        self.connect_inner(handler, addr, Vec::new())
+        let mut handler = Some(handler);
+        self.connect_inner(move || handler.take().expect("called once per concurrent dial; QED"), iter::once(addr), 1)
     }
 
     /// Attempts a new connection to this node using the given multiaddresses.
@@ -1762,13 +2281,39 @@ where
         TConnInfo: fmt::Debug + ConnectionInfo<PeerId = TPeerId> + Send + 'static,
         TPeerId: Eq + Hash + Clone + Send + 'static,
     {
-        let mut addrs = addrs.into_iter();
-        let first = match addrs.next() {
-            Some(f) => f,
-            None => return Err(self)
-        };
-        let rest = addrs.collect();
-        Ok(self.connect_inner(handler, first, rest))
+        let mut addrs = addrs.into_iter().peekable();
+        if addrs.peek().is_none() {
+            return Err(self)
+        }
+        let mut handler = Some(handler);
+        Ok(self.connect_inner(move || handler.take().expect("called once per concurrent dial; QED"), addrs, 1))
+    }
+
+    /// Attempts a new connection to this node using the given multiaddresses, dialing several of
+    /// them at the same time.
+    ///
+    /// Up to `ConnectionLimits::dial_concurrency_factor` multiaddresses are dialed at once, each
+    /// with a handler produced by `new_handler`. The other ones are tried as the attempts in
+    /// progress fail. As soon as one attempt succeeds, the other ones are interrupted.
+    ///
+    /// Returns an error if the iterator is empty.
+    ///
+    /// If we reach a peer but the `PeerId` doesn't correspond to the one we're expecting, then
+    /// the whole connection is immediately closed.
+    pub fn connect_iter_concurrent<TIter, TFn>(self, addrs: TIter, new_handler: TFn)
+        -> Result<PeerPendingConnect<'a, TTrans, TInEvent, TOutEvent, THandler, THandlerErr, TConnInfo, TPeerId>, Self>
+    where
+        TIter: IntoIterator<Item = Multiaddr>,
+        TFn: FnMut() -> THandler,
+        TConnInfo: fmt::Debug + ConnectionInfo<PeerId = TPeerId> + Send + 'static,
+        TPeerId: Eq + Hash + Clone + Send + 'static,
+    {
+        let mut addrs = addrs.into_iter().peekable();
+        if addrs.peek().is_none() {
+            return Err(self)
+        }
+        let factor = usize::from(self.nodes.limits.dial_concurrency_factor.get());
+        Ok(self.connect_inner(new_handler, addrs, factor))
     }
 
     /// Moves the given node to a connected state using the given connection info and muxer.
@@ -1805,13 +2350,37 @@ where
     }
 
     /// Inner implementation of `connect`.
-    fn connect_inner(self, handler: THandler, first: Multiaddr, rest: Vec<Multiaddr>)
-        -> PeerPendingConnect<'a, TTrans, TInEvent, TOutEvent, THandler, THandlerErr, TConnInfo, TPeerId>
+    ///
+    /// Dials the first multiaddress, then up to `concurrency - 1` other ones as long as the limit
+    /// of pending outgoing connections allows it, and queues the rest. `addrs` must not be empty.
+    fn connect_inner(
+        self,
+        mut new_handler: impl FnMut() -> THandler,
+        mut addrs: impl Iterator<Item = Multiaddr>,
+        concurrency: usize,
+    ) -> PeerPendingConnect<'a, TTrans, TInEvent, TOutEvent, THandler, THandlerErr, TConnInfo, TPeerId>
     where
         TConnInfo: fmt::Debug + ConnectionInfo<PeerId = TPeerId> + Send + 'static,
         TPeerId: Eq + Hash + Clone + Send + 'static,
     {
-        self.nodes.start_dial_out(self.peer_id.clone(), handler, first, rest);
+        let first = addrs.next().expect("connect_inner is never called with no address; QED");
+        self.nodes.start_dial_out(self.peer_id.clone(), new_handler(), first);
+
+        for _ in 1..concurrency {
+            let num_established = self.nodes.active_nodes.num_connections();
+            if self.nodes.limits.check_outgoing(&self.nodes.reach_attempts, num_established).is_err() {
+                break
+            }
+            match addrs.next() {
+                Some(addr) => self.nodes.start_dial_out(self.peer_id.clone(), new_handler(), addr),
+                None => break,
+            }
+        }
+
+        if let Some(attempt) = self.nodes.reach_attempts.out_reach_attempts.get_mut(&self.peer_id) {
+            attempt.next_attempts.extend(addrs);
+        }
+
         PeerPendingConnect {
             attempt: match self.nodes.reach_attempts.out_reach_attempts.entry(self.peer_id) {
                 Entry::Occupied(e) => e,
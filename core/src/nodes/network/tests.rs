@@ -162,7 +162,7 @@ fn broadcasted_events_reach_active_nodes() {
             let mut network = network_fut.lock();
             match network.poll() {
                 Async::Ready(event) => {
-                    assert_matches!(event, NetworkEvent::NodeEvent { conn_info: _, event: inner_event } => {
+                    assert_matches!(event, NetworkEvent::NodeEvent { event: inner_event, .. } => {
                         // The event we sent reached the node and triggered sending the out event we told it to return
                         assert_matches!(inner_event, OutEvent::Custom("from handler 1"));
                     });
@@ -225,6 +225,38 @@ fn querying_for_connected_peer() {
     assert_matches!(peer, Peer::Connected( PeerConnected { .. } ));
 }
 
+#[test]
+fn connections_snapshot_lists_established_connections() {
+    let mut network = Network::<_, _, _, Handler, _>::new(DummyTransport::new(), PeerId::random());
+    assert_eq!(network.connections().count(), 0);
+
+    let addr = "/ip4/127.0.0.1/tcp/1234".parse::<Multiaddr>().expect("bad multiaddr");
+    network.dial(addr.clone(), Handler::default()).expect("dialing works");
+
+    let network = Arc::new(Mutex::new(network));
+    let mut rt = Runtime::new().unwrap();
+    let mut connected = None;
+    while connected.is_none() {
+        let network_fut = network.clone();
+        connected = rt.block_on(future::poll_fn(move || -> Poll<Option<(PeerId, ConnectionId)>, ()> {
+            let mut network = network_fut.lock();
+            match network.poll() {
+                Async::Ready(NetworkEvent::Connected { conn_info, connection, .. }) =>
+                    Ok(Async::Ready(Some((conn_info, connection)))),
+                _ => Ok(Async::Ready(None))
+            }
+        })).expect("tokio works");
+    }
+
+    let (peer_id, connection) = connected.unwrap();
+    let network = network.lock();
+    let connections = network.connections().collect::<Vec<_>>();
+    assert_eq!(connections.len(), 1);
+    assert_eq!(connections[0].id(), connection);
+    assert_eq!(connections[0].info(), &peer_id);
+    assert_matches!(connections[0].endpoint(), ConnectedPoint::Dialer { address } if *address == addr);
+}
+
 #[test]
 fn poll_with_closed_listener() {
     let mut transport = DummyTransport::new();
@@ -379,6 +411,57 @@ fn yields_node_error_when_there_is_an_error_after_successful_connect() {
     })).expect("tokio works");
 }
 
+#[test]
+fn disconnect_gracefully_closes_the_connections() {
+    let mut transport = DummyTransport::new();
+    let peer_id = PeerId::random();
+    transport.set_next_peer_id(&peer_id);
+    let network = Arc::new(Mutex::new(Network::<_, _, _, Handler, _>::new(transport, PeerId::random())));
+
+    {
+        let network1 = network.clone();
+        let mut network1 = network1.lock();
+        let peer = network1.peer(peer_id.clone());
+        let addr = "/unix/reachable".parse().expect("bad multiaddr");
+        peer.into_not_connected().unwrap().connect(addr, Handler::default());
+    }
+
+    let mut rt = Builder::new().core_threads(1).build().unwrap();
+
+    // Drive it forward until we connect to the node.
+    let network_fut = network.clone();
+    rt.block_on(future::poll_fn(move || -> Poll<_, ()> {
+        match network_fut.lock().poll() {
+            Async::NotReady => Ok(Async::NotReady),
+            Async::Ready(event) => {
+                assert_matches!(event, NetworkEvent::Connected { .. });
+                Ok(Async::Ready(()))
+            }
+        }
+    })).expect("tokio works");
+
+    network.lock().peer(peer_id.clone()).into_connected().unwrap().disconnect();
+
+    // The connection remains established until it is fully closed.
+    assert!(network.lock().peer(peer_id.clone()).into_connected().is_some());
+
+    let network_fut = network.clone();
+    let expected_peer_id = peer_id.clone();
+    rt.block_on(future::poll_fn(move || -> Poll<_, ()> {
+        match network_fut.lock().poll() {
+            Async::NotReady => Ok(Async::NotReady),
+            Async::Ready(event) => {
+                assert_matches!(event, NetworkEvent::NodeDisconnected { conn_info, .. } => {
+                    assert_eq!(conn_info, expected_peer_id);
+                });
+                Ok(Async::Ready(()))
+            }
+        }
+    })).expect("tokio works");
+
+    assert!(network.lock().peer(peer_id).into_connected().is_none());
+}
+
 #[test]
 fn local_prio_equivalence_relation() {
     for _ in 0..1000 {
@@ -443,3 +526,53 @@ fn limit_incoming_connections() {
         assert!(network.incoming_negotiated().count() <= (limit as usize));
     }
 }
+
+#[test]
+fn limit_pending_outgoing_connections() {
+    let mut transport = DummyTransport::new();
+    let peer_id = PeerId::random();
+    transport.set_next_peer_id(&peer_id);
+    let limits = ConnectionLimits::default().with_max_pending_outgoing(Some(1));
+    let mut network = Network::<_, _, _, Handler, _>::new_with_limits(transport, PeerId::random(), limits);
+    assert_eq!(network.limits().max_pending_outgoing(), Some(1));
+
+    let addr = "/ip4/127.0.0.1/tcp/1234".parse::<Multiaddr>().expect("bad multiaddr");
+    assert!(network.dial(addr.clone(), Handler::default()).is_ok());
+    // The second dial is accepted but immediately fails because of the limit.
+    assert!(network.dial(addr, Handler::default()).is_ok());
+
+    let network = Arc::new(Mutex::new(network));
+    let mut rt = Runtime::new().unwrap();
+    let mut limit_reached = false;
+    while !limit_reached {
+        let network_fut = network.clone();
+        limit_reached = rt.block_on(future::poll_fn(move || -> Poll<_, ()> {
+            let mut network = network_fut.lock();
+            match network.poll() {
+                Async::Ready(NetworkEvent::UnknownPeerDialError { error, .. }) => {
+                    let limit = assert_matches!(error, UnknownPeerDialErr::ConnectionLimit(l) => l);
+                    assert_eq!(limit, ConnectionLimit { limit: 1, current: 1 });
+                    Ok(Async::Ready(true))
+                },
+                _ => Ok(Async::Ready(false)),
+            }
+        })).expect("tokio works");
+    }
+}
+
+#[test]
+fn concurrent_dials_are_bounded_by_pending_outgoing_limit() {
+    let limits = ConnectionLimits::default()
+        .with_max_pending_outgoing(Some(2))
+        .with_dial_concurrency_factor(NonZeroU8::new(3).unwrap());
+    let mut network = Network::<_, _, _, Handler, _>::new_with_limits(DummyTransport::new(), PeerId::random(), limits);
+    let addrs = (1..=4)
+        .map(|port| format!("/ip4/127.0.0.1/tcp/{}", port).parse::<Multiaddr>().expect("bad multiaddr"))
+        .collect::<Vec<_>>();
+
+    let peer = network.peer(PeerId::random()).into_not_connected().unwrap();
+    let pending_peer = peer.connect_iter_concurrent(addrs.clone(), Handler::default)
+        .expect("addresses are not empty");
+    assert_eq!(pending_peer.attempted_multiaddrs().cloned().collect::<Vec<_>>(), &addrs[..2]);
+    assert_eq!(pending_peer.pending_multiaddrs().cloned().collect::<Vec<_>>(), &addrs[2..]);
+}
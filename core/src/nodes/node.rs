@@ -59,9 +59,13 @@ where
 }
 
 /// Future that signals the remote that we have closed the connection.
+///
+/// The data buffered by the muxer is flushed before the muxer is closed.
 pub struct Close<TMuxer> {
     /// Muxer to close.
     muxer: Arc<TMuxer>,
+    /// True if the muxer has been flushed.
+    flushed: bool,
 }
 
 /// A successfully opened substream.
@@ -128,7 +132,7 @@ where
     #[must_use]
     pub fn close(mut self) -> (Close<TMuxer>, Vec<TUserData>) {
         let substreams = self.cancel_outgoing();
-        let close = Close { muxer: self.muxer.clone() };
+        let close = Close { muxer: self.muxer.clone(), flushed: false };
         (close, substreams)
     }
 
@@ -216,6 +220,13 @@ where
     type Error = IoError;
 
     fn poll(&mut self) -> Poll<Self::Item, Self::Error> {
+        if !self.flushed {
+            match self.muxer.flush_all() {
+                Ok(Async::Ready(())) => self.flushed = true,
+                Ok(Async::NotReady) => return Ok(Async::NotReady),
+                Err(err) => return Err(err.into()),
+            }
+        }
         self.muxer.close().map_err(|e| e.into())
     }
 }
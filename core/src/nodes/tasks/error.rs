@@ -27,7 +27,9 @@ pub enum Error<R, H> {
     /// An error happend while we were trying to reach the node.
     Reach(R),
     /// An error happened after the node has been reached.
-    Node(HandledNodeError<H>)
+    Node(HandledNodeError<H>),
+    /// The connection has been gracefully closed at the request of the API.
+    Closed
 }
 
 impl<R, H> fmt::Display for Error<R, H>
@@ -38,7 +40,8 @@ where
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
             Error::Reach(err) => write!(f, "reach error: {}", err),
-            Error::Node(err) => write!(f, "node error: {}", err)
+            Error::Node(err) => write!(f, "node error: {}", err),
+            Error::Closed => write!(f, "closed on request")
         }
     }
 }
@@ -51,7 +54,8 @@ where
     fn source(&self) -> Option<&(dyn error::Error + 'static)> {
         match self {
             Error::Reach(err) => Some(err),
-            Error::Node(err) => Some(err)
+            Error::Node(err) => Some(err),
+            Error::Closed => None
         }
     }
 }
@@ -26,11 +26,13 @@ use crate::{
         node::Substream
     }
 };
-use fnv::FnvHashMap;
-use futures::{prelude::*, future::Executor, sync::mpsc};
+use fnv::{FnvHashMap, FnvHashSet};
+use log::warn;
+use futures::{prelude::*, executor, future::Executor, sync::mpsc, task::AtomicTask};
+use parking_lot::Mutex;
 use smallvec::SmallVec;
-use std::{collections::hash_map::{Entry, OccupiedEntry}, error, fmt};
-use super::{TaskId, task::{Task, FromTaskMessage, ToTaskMessage}, Error};
+use std::{collections::hash_map::{Entry, OccupiedEntry}, error, fmt, mem, sync::Arc};
+use super::{TaskId, task::{Task, FromTaskMessage, ToTaskMessage}, Error, queue};
 
 // Implementor notes
 // =================
@@ -51,6 +53,11 @@ use super::{TaskId, task::{Task, FromTaskMessage, ToTaskMessage}, Error};
 // reported, as it could potentially create confusions and race conditions in
 // the user's code. See similar comments in the documentation of `NodeStream`.
 //
+// Wakeups are kept to a minimum. The tasks send their messages through a
+// `queue` that only wakes up the `Manager` when it goes from empty to
+// non-empty, and that is drained all at once. The tasks polled locally are
+// only polled after they have been woken up.
+//
 
 /// Implementation of [`Stream`] that handles a collection of nodes.
 pub struct Manager<I, O, H, E, HE, T, C = PeerId> {
@@ -65,17 +72,47 @@ pub struct Manager<I, O, H, E, HE, T, C = PeerId> {
     next_task_id: TaskId,
 
     /// List of node tasks to spawn.
-    to_spawn: SmallVec<[Box<dyn Future<Item = (), Error = ()> + Send>; 8]>,
+    to_spawn: SmallVec<[ConnectionTask; 8]>,
+
+    /// How the tasks are spawned.
+    executor: TaskExecutor,
 
-    /// If no tokio executor is available, we move tasks to this list, and futures are polled on
+    /// If the executor refuses a task, we move it to this list, and futures are polled on
     /// the current thread instead.
-    local_spawns: Vec<Box<dyn Future<Item = (), Error = ()> + Send>>,
+    local_spawns: FnvHashMap<usize, executor::Spawn<ConnectionTask>>,
+
+    /// Identifier for the next entry of `local_spawns`.
+    next_local_id: usize,
+
+    /// Entries of `local_spawns` that have been woken up and must be polled.
+    local_ready: Arc<LocalReady>,
+
+    /// Whether we have already warned that the executor refused a task.
+    refused_warned: bool,
 
     /// Sender to emit events to the outside. Meant to be cloned and sent to tasks.
-    events_tx: mpsc::Sender<(FromTaskMessage<O, H, E, HE, C>, TaskId)>,
+    events_tx: queue::Sender<(FromTaskMessage<O, H, E, HE, C>, TaskId)>,
 
     /// Receiver side for the events.
-    events_rx: mpsc::Receiver<(FromTaskMessage<O, H, E, HE, C>, TaskId)>
+    events_rx: queue::Receiver<(FromTaskMessage<O, H, E, HE, C>, TaskId)>
+}
+
+/// Maximum number of events sent by the tasks that haven't been processed by the `Manager`.
+const EVENTS_CAPACITY: usize = 256;
+
+/// Records which of the tasks polled by the `Manager` itself have been woken up.
+struct LocalReady {
+    /// Identifiers of the woken up tasks.
+    ids: Mutex<FnvHashSet<usize>>,
+    /// Task polling the `Manager`.
+    task: AtomicTask,
+}
+
+impl executor::Notify for LocalReady {
+    fn notify(&self, id: usize) {
+        self.ids.lock().insert(id);
+        self.task.notify();
+    }
 }
 
 impl<I, O, H, E, HE, T, C> fmt::Debug for Manager<I, O, H, E, HE, T, C>
@@ -89,6 +126,38 @@ where
     }
 }
 
+/// Future driving a single node, as passed to a [`TaskExecutor`].
+pub type ConnectionTask = Box<dyn Future<Item = (), Error = ()> + Send>;
+
+/// Determines how the [`Manager`] runs the tasks dedicated to the nodes.
+pub enum TaskExecutor {
+    /// Spawns the tasks on the default tokio executor. If no executor is available, the tasks are
+    /// polled as part of polling the [`Manager`].
+    Default,
+    /// Polls the tasks as part of polling the [`Manager`], after they have been woken up. This
+    /// is appropriate for single-threaded programs.
+    Local,
+    /// Spawns the tasks on the given executor. If the executor refuses a task, for example
+    /// because it is at capacity, the task is polled as part of polling the [`Manager`].
+    Custom(Box<dyn Executor<ConnectionTask> + Send>),
+}
+
+impl fmt::Debug for TaskExecutor {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            TaskExecutor::Default => f.write_str("Default"),
+            TaskExecutor::Local => f.write_str("Local"),
+            TaskExecutor::Custom(_) => f.write_str("Custom"),
+        }
+    }
+}
+
+impl Default for TaskExecutor {
+    fn default() -> Self {
+        TaskExecutor::Default
+    }
+}
+
 /// Information about a running task.
 ///
 /// Contains the sender to deliver event messages to the task,
@@ -100,7 +169,9 @@ struct TaskInfo<I, T> {
     /// task associated data
     user_data: T,
     /// any pending event to deliver to the task
-    pending: Option<AsyncSink<ToTaskMessage<I>>>
+    pending: Option<AsyncSink<ToTaskMessage<I>>>,
+    /// span of the connection handled by the task
+    span: tracing::Span
 }
 
 /// Event produced by the [`Manager`].
@@ -139,17 +210,31 @@ pub enum Event<'a, I, O, H, E, HE, T, C = PeerId> {
 impl<I, O, H, E, HE, T, C> Manager<I, O, H, E, HE, T, C> {
     /// Creates a new task manager.
     pub fn new() -> Self {
-        let (tx, rx) = mpsc::channel(1);
+        let (tx, rx) = queue::channel(EVENTS_CAPACITY);
         Self {
             tasks: FnvHashMap::default(),
             next_task_id: TaskId(0),
             to_spawn: SmallVec::new(),
-            local_spawns: Vec::new(),
+            executor: TaskExecutor::Default,
+            local_spawns: FnvHashMap::default(),
+            next_local_id: 0,
+            local_ready: Arc::new(LocalReady {
+                ids: Mutex::new(FnvHashSet::default()),
+                task: AtomicTask::new(),
+            }),
+            refused_warned: false,
             events_tx: tx,
             events_rx: rx
         }
     }
 
+    /// Sets the way the tasks are run.
+    ///
+    /// Only affects the tasks spawned after this method has been called.
+    pub fn set_executor(&mut self, executor: TaskExecutor) {
+        self.executor = executor;
+    }
+
     /// Adds to the manager a future that tries to reach a node.
     ///
     /// This method spawns a task dedicated to resolving this future and
@@ -172,9 +257,10 @@ impl<I, O, H, E, HE, T, C> Manager<I, O, H, E, HE, T, C> {
         self.next_task_id.0 += 1;
 
         let (tx, rx) = mpsc::channel(4);
-        self.tasks.insert(task_id, TaskInfo { sender: tx, user_data, pending: None });
+        let span = connection_span(task_id);
+        self.tasks.insert(task_id, TaskInfo { sender: tx, user_data, pending: None, span: span.clone() });
 
-        let task = Box::new(Task::new(task_id, self.events_tx.clone(), rx, future, handler));
+        let task = Box::new(Task::new(task_id, self.events_tx.clone(), rx, future, handler, span));
         self.to_spawn.push(task);
         task_id
     }
@@ -202,10 +288,11 @@ impl<I, O, H, E, HE, T, C> Manager<I, O, H, E, HE, T, C> {
         self.next_task_id.0 += 1;
 
         let (tx, rx) = mpsc::channel(4);
-        self.tasks.insert(task_id, TaskInfo { sender: tx, user_data, pending: None });
+        let span = connection_span(task_id);
+        self.tasks.insert(task_id, TaskInfo { sender: tx, user_data, pending: None, span: span.clone() });
 
         let task: Task<futures::future::Empty<_, _>, _, _, _, _, _, _> =
-            Task::node(task_id, self.events_tx.clone(), rx, HandledNode::new(muxer, handler));
+            Task::node(task_id, self.events_tx.clone(), rx, HandledNode::new(muxer, handler), span);
 
         self.to_spawn.push(Box::new(task));
         task_id
@@ -279,6 +366,11 @@ impl<I, O, H, E, HE, T, C> Manager<I, O, H, E, HE, T, C> {
         }
     }
 
+    /// Returns the user data of a task, or `None` if the task id is invalid.
+    pub fn user_data(&self, id: TaskId) -> Option<&T> {
+        self.tasks.get(&id).map(|task| &task.user_data)
+    }
+
     /// Returns a list of all the active tasks.
     pub fn tasks<'a>(&'a self) -> impl Iterator<Item = TaskId> + 'a {
         self.tasks.keys().cloned()
@@ -286,25 +378,48 @@ impl<I, O, H, E, HE, T, C> Manager<I, O, H, E, HE, T, C> {
 
     /// Provides an API similar to `Stream`, except that it cannot produce an error.
     pub fn poll(&mut self) -> Async<Event<I, O, H, E, HE, T, C>> {
-        for to_spawn in self.to_spawn.drain() {
-            // We try to use the default executor, but fall back to polling the task manually if
-            // no executor is available. This makes it possible to use the core in environments
+        for to_spawn in mem::replace(&mut self.to_spawn, SmallVec::new()) {
+            // We try to use the configured executor, but fall back to polling the task manually
+            // if it refuses the task. This makes it possible to use the core in environments
             // outside of tokio.
-            let executor = tokio_executor::DefaultExecutor::current();
-            if let Err(err) = executor.execute(to_spawn) {
-                self.local_spawns.push(err.into_future())
+            let result = match self.executor {
+                TaskExecutor::Default => tokio_executor::DefaultExecutor::current().execute(to_spawn),
+                TaskExecutor::Local => {
+                    self.spawn_local(to_spawn);
+                    continue
+                }
+                TaskExecutor::Custom(ref executor) => executor.execute(to_spawn),
+            };
+            if let Err(err) = result {
+                // Falling back silently would make all the connections share the task polling
+                // the `Manager`, which defeats the purpose of spawning them.
+                if !self.refused_warned {
+                    warn!("Executor refused a connection task ({:?}); polling it from the \
+                        task of the manager instead", err.kind());
+                    self.refused_warned = true;
+                }
+                self.spawn_local(err.into_future())
             }
         }
 
-        for n in (0 .. self.local_spawns.len()).rev() {
-            let mut task = self.local_spawns.swap_remove(n);
-            match task.poll() {
-                Ok(Async::Ready(())) => {}
-                Ok(Async::NotReady) => self.local_spawns.push(task),
-                // It would normally be desirable to either report or log when a background task
-                // errors. However the default tokio executor doesn't do anything in case of error,
-                // and therefore we mimic this behaviour by also not doing anything.
-                Err(()) => {}
+        // Only the local tasks that have been woken up since they were last polled are polled
+        // again, instead of all of them.
+        self.local_ready.task.register();
+        let ready = mem::replace(&mut *self.local_ready.ids.lock(), FnvHashSet::default());
+        let notify = executor::NotifyHandle::from(self.local_ready.clone());
+        for id in ready {
+            let finished = match self.local_spawns.get_mut(&id) {
+                Some(task) => match task.poll_future_notify(&notify, id) {
+                    Ok(Async::NotReady) => false,
+                    // It would normally be desirable to either report or log when a background
+                    // task errors. However the default tokio executor doesn't do anything in case
+                    // of error, and therefore we mimic this behaviour by also not doing anything.
+                    Ok(Async::Ready(())) | Err(()) => true,
+                },
+                None => continue
+            };
+            if finished {
+                self.local_spawns.remove(&id);
             }
         }
 
@@ -320,7 +435,7 @@ impl<I, O, H, E, HE, T, C> Manager<I, O, H, E, HE, T, C> {
                 }
                 Ok(Async::NotReady) => return Async::NotReady,
                 Ok(Async::Ready(None)) => unreachable!("sender and receiver have same lifetime"),
-                Err(()) => unreachable!("An `mpsc::Receiver` does not error.")
+                Err(()) => unreachable!("A `queue::Receiver` does not error.")
             }
         };
 
@@ -352,6 +467,19 @@ impl<I, O, H, E, HE, T, C> Manager<I, O, H, E, HE, T, C> {
             }
         })
     }
+
+    /// Adds a task to the ones polled as part of polling the `Manager`.
+    fn spawn_local(&mut self, task: ConnectionTask) {
+        let id = self.next_local_id;
+        self.next_local_id += 1;
+        self.local_spawns.insert(id, executor::spawn(task));
+        self.local_ready.ids.lock().insert(id);
+    }
+}
+
+/// Creates the span of the connection handled by the given task.
+fn connection_span(id: TaskId) -> tracing::Span {
+    tracing::debug_span!("connection", connection_id = id.0 as u64, peer_id = tracing::field::Empty)
 }
 
 /// Access to a task in the collection.
@@ -395,6 +523,24 @@ impl<'a, E, T> TaskEntry<'a, E, T> {
         *self.inner.key()
     }
 
+    /// Returns the tracing span of the connection handled by the task.
+    ///
+    /// Everything the task does happens within this span. Its `peer_id` field is empty until
+    /// recorded by the owner of the task.
+    pub fn span(&self) -> &tracing::Span {
+        &self.inner.get().span
+    }
+
+    /// Asks the task to gracefully close its connection.
+    ///
+    /// The task keeps running until the connection is fully closed, after which a `TaskClosed`
+    /// event with `Error::Closed` is produced. Has no effect if the task has already ended.
+    pub fn disconnect(&mut self) {
+        // A new sender always has room for one message, therefore the message is never refused
+        // because the channel is full.
+        let _ = self.inner.get().sender.clone().start_send(ToTaskMessage::Close);
+    }
+
     /// Closes the task. Returns the user data.
     ///
     /// No further event will be generated for this task, but the connection inside the task will
@@ -31,15 +31,36 @@
 //! and messages can be sent to individual tasks or all (cf.
 //! [`Manager::start_broadcast`]). Messages produces by tasks can be
 //! retrieved by polling the manager (cf. [`Manager::poll`]).
+//!
+//! # Execution model
+//!
+//! Every connection, and its node handler, is driven by its own task, so
+//! that a slow handler only delays its own connection. The tasks are run by
+//! the [`TaskExecutor`] of the manager and exchange messages with it through
+//! bounded channels, which apply back-pressure in both directions. To make
+//! use of several cores, the executor must be multi-threaded, for example
+//! the default executor of a multi-threaded tokio runtime or a
+//! [`TaskExecutor::Custom`] wrapping a thread pool. The tasks that no
+//! executor accepts are polled from the task polling the manager, which
+//! only provides concurrency.
 
 mod error;
 mod manager;
+mod queue;
 mod task;
 
 pub use error::Error;
-pub use manager::{ClosedTask, TaskEntry, Manager, Event, StartTakeOver};
+pub use manager::{ClosedTask, ConnectionTask, TaskEntry, TaskExecutor, Manager, Event, StartTakeOver};
+
+use std::fmt;
 
 /// Task identifier.
 #[derive(Debug, Copy, Clone, Hash, PartialEq, Eq, PartialOrd, Ord)]
 pub struct TaskId(usize);
 
+impl fmt::Display for TaskId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
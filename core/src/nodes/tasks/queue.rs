@@ -0,0 +1,196 @@
+// Copyright 2019 Parity Technologies (UK) Ltd.
+//
+// Permission is hereby granted, free of charge, to any person obtaining a
+// copy of this software and associated documentation files (the "Software"),
+// to deal in the Software without restriction, including without limitation
+// the rights to use, copy, modify, merge, publish, distribute, sublicense,
+// and/or sell copies of the Software, and to permit persons to whom the
+// Software is furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS
+// OR IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING
+// FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
+// DEALINGS IN THE SOFTWARE.
+//! Bounded queue carrying the messages of all the tasks to the `Manager`.
+//!
+//! Compared to a `futures::sync::mpsc` channel, the receiver is only notified when the queue
+//! goes from empty to non-empty, and drains every queued message at once when polled. A burst
+//! of messages produced by many tasks therefore results in a single wakeup of the `Manager`.
+//! Likewise, senders blocked on a full queue are all woken up once per drain instead of once per
+//! received message.
+
+use futures::{prelude::*, task::{self, AtomicTask}};
+use parking_lot::Mutex;
+use std::{collections::VecDeque, mem, sync::Arc, sync::atomic::{AtomicBool, Ordering}};
+
+/// Creates a new queue that holds up to `capacity` messages.
+pub fn channel<T>(capacity: usize) -> (Sender<T>, Receiver<T>) {
+    let shared = Arc::new(Shared {
+        state: Mutex::new(State {
+            queue: VecDeque::new(),
+            blocked: Vec::new(),
+            receiver_alive: true,
+        }),
+        capacity,
+        notified: AtomicBool::new(false),
+        receiver_task: AtomicTask::new(),
+    });
+    let sender = Sender { shared: shared.clone() };
+    let receiver = Receiver { shared, buffer: VecDeque::new() };
+    (sender, receiver)
+}
+
+struct Shared<T> {
+    state: Mutex<State<T>>,
+    /// Maximum number of messages in `state.queue`.
+    capacity: usize,
+    /// True if the receiver has been notified and hasn't drained the queue since.
+    notified: AtomicBool,
+    /// Task of the receiver.
+    receiver_task: AtomicTask,
+}
+
+struct State<T> {
+    /// Messages sent and not drained yet.
+    queue: VecDeque<T>,
+    /// Senders waiting for the queue to have room.
+    blocked: Vec<task::Task>,
+    /// False once the receiver has been dropped.
+    receiver_alive: bool,
+}
+
+/// Error returned when sending a message to a queue whose receiver has been dropped.
+#[derive(Debug)]
+pub struct SendError<T>(pub T);
+
+/// Sending side of the queue. Can be cloned.
+pub struct Sender<T> {
+    shared: Arc<Shared<T>>,
+}
+
+impl<T> Clone for Sender<T> {
+    fn clone(&self) -> Self {
+        Sender { shared: self.shared.clone() }
+    }
+}
+
+impl<T> Sink for Sender<T> {
+    type SinkItem = T;
+    type SinkError = SendError<T>;
+
+    fn start_send(&mut self, item: T) -> StartSend<T, SendError<T>> {
+        {
+            let mut state = self.shared.state.lock();
+            if !state.receiver_alive {
+                return Err(SendError(item))
+            }
+            if state.queue.len() >= self.shared.capacity {
+                state.blocked.push(task::current());
+                return Ok(AsyncSink::NotReady(item))
+            }
+            state.queue.push_back(item);
+        }
+        if !self.shared.notified.swap(true, Ordering::AcqRel) {
+            self.shared.receiver_task.notify();
+        }
+        Ok(AsyncSink::Ready)
+    }
+
+    fn poll_complete(&mut self) -> Poll<(), SendError<T>> {
+        // Messages are handed over to the receiver as soon as they are sent.
+        Ok(Async::Ready(()))
+    }
+}
+
+/// Receiving side of the queue.
+pub struct Receiver<T> {
+    shared: Arc<Shared<T>>,
+    /// Messages drained from the queue and not returned yet.
+    buffer: VecDeque<T>,
+}
+
+impl<T> Stream for Receiver<T> {
+    type Item = T;
+    type Error = ();
+
+    fn poll(&mut self) -> Poll<Option<T>, ()> {
+        if let Some(item) = self.buffer.pop_front() {
+            return Ok(Async::Ready(Some(item)))
+        }
+
+        // Clearing the flag before draining guarantees that a message pushed after the drain
+        // notifies us again.
+        self.shared.receiver_task.register();
+        self.shared.notified.store(false, Ordering::Release);
+        let blocked = {
+            let mut state = self.shared.state.lock();
+            mem::swap(&mut state.queue, &mut self.buffer);
+            mem::replace(&mut state.blocked, Vec::new())
+        };
+        for task in blocked {
+            task.notify();
+        }
+
+        match self.buffer.pop_front() {
+            Some(item) => Ok(Async::Ready(Some(item))),
+            None => Ok(Async::NotReady)
+        }
+    }
+}
+
+impl<T> Drop for Receiver<T> {
+    fn drop(&mut self) {
+        let blocked = {
+            let mut state = self.shared.state.lock();
+            state.receiver_alive = false;
+            state.queue.clear();
+            mem::replace(&mut state.blocked, Vec::new())
+        };
+        for task in blocked {
+            task.notify();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use futures::{executor, future};
+    use std::sync::atomic::AtomicUsize;
+
+    struct CountingNotify(AtomicUsize);
+
+    impl executor::Notify for CountingNotify {
+        fn notify(&self, _: usize) {
+            self.0.fetch_add(1, Ordering::SeqCst);
+        }
+    }
+
+    #[test]
+    fn burst_notifies_receiver_once() {
+        let (tx, rx) = channel::<u32>(16);
+        let notify = Arc::new(CountingNotify(AtomicUsize::new(0)));
+        let handle = executor::NotifyHandle::from(notify.clone());
+        let mut rx = executor::spawn(rx);
+        assert!(rx.poll_stream_notify(&handle, 0).unwrap().is_not_ready());
+
+        for n in 0 .. 8 {
+            let mut tx = tx.clone();
+            let sent = future::lazy(move || tx.start_send(n)).wait().unwrap();
+            assert!(sent.is_ready());
+        }
+        assert_eq!(notify.0.load(Ordering::SeqCst), 1);
+
+        for n in 0 .. 8 {
+            assert_eq!(rx.poll_stream_notify(&handle, 0).unwrap(), Async::Ready(Some(n)));
+        }
+        assert!(rx.poll_stream_notify(&handle, 0).unwrap().is_not_ready());
+        assert_eq!(notify.0.load(Ordering::SeqCst), 1);
+    }
+}
@@ -21,13 +21,13 @@
 use crate::{
     muxing::StreamMuxer,
     nodes::{
-        handled_node::{HandledNode, IntoNodeHandler, NodeHandler},
+        handled_node::{HandledNode, HandledNodeError, IntoNodeHandler, NodeHandler},
         node::{Close, Substream}
     }
 };
 use futures::{prelude::*, stream, sync::mpsc};
 use smallvec::SmallVec;
-use super::{TaskId, Error};
+use super::{TaskId, Error, queue};
 
 /// Message to transmit from the public API to a task.
 #[derive(Debug)]
@@ -36,7 +36,10 @@ pub enum ToTaskMessage<T> {
     HandlerEvent(T),
     /// When received, stores the parameter inside the task and keeps it alive
     /// until we have an acknowledgment that the remote has accepted our handshake.
-    TakeOver(mpsc::Sender<ToTaskMessage<T>>)
+    TakeOver(mpsc::Sender<ToTaskMessage<T>>),
+    /// Closes the connection gracefully. The pending data is flushed and the muxer is closed,
+    /// after which the task reports `TaskClosed` with `Error::Closed`.
+    Close
 }
 
 /// Message to transmit from a task to the public API.
@@ -61,7 +64,7 @@ where
     id: TaskId,
 
     /// Sender to transmit messages to the outside.
-    sender: mpsc::Sender<(FromTaskMessage<O, H, E, <H::Handler as NodeHandler>::Error, C>, TaskId)>,
+    sender: queue::Sender<(FromTaskMessage<O, H, E, <H::Handler as NodeHandler>::Error, C>, TaskId)>,
 
     /// Receiver of messages from the outsize.
     receiver: stream::Fuse<mpsc::Receiver<ToTaskMessage<I>>>,
@@ -70,7 +73,13 @@ where
     state: State<F, M, H, I, O, E, C>,
 
     /// Channels to keep alive for as long as we don't have an acknowledgment from the remote.
-    taken_over: SmallVec<[mpsc::Sender<ToTaskMessage<I>>; 1]>
+    taken_over: SmallVec<[mpsc::Sender<ToTaskMessage<I>>; 1]>,
+
+    /// True if we received a `ToTaskMessage::Close`.
+    close_requested: bool,
+
+    /// Span of the connection, entered whenever the task is polled.
+    span: tracing::Span
 }
 
 impl<F, M, H, I, O, E, C> Task<F, M, H, I, O, E, C>
@@ -82,33 +91,39 @@ where
     /// Create a new task to connect and handle some node.
     pub fn new (
         i: TaskId,
-        s: mpsc::Sender<(FromTaskMessage<O, H, E, <H::Handler as NodeHandler>::Error, C>, TaskId)>,
+        s: queue::Sender<(FromTaskMessage<O, H, E, <H::Handler as NodeHandler>::Error, C>, TaskId)>,
         r: mpsc::Receiver<ToTaskMessage<I>>,
         f: F,
-        h: H
+        h: H,
+        span: tracing::Span
     ) -> Self {
         Task {
             id: i,
             sender: s,
             receiver: r.fuse(),
             state: State::Future { future: f, handler: h, events_buffer: Vec::new() },
-            taken_over: SmallVec::new()
+            taken_over: SmallVec::new(),
+            close_requested: false,
+            span
         }
     }
 
     /// Create a task for an existing node we are already connected to.
     pub fn node (
         i: TaskId,
-        s: mpsc::Sender<(FromTaskMessage<O, H, E, <H::Handler as NodeHandler>::Error, C>, TaskId)>,
+        s: queue::Sender<(FromTaskMessage<O, H, E, <H::Handler as NodeHandler>::Error, C>, TaskId)>,
         r: mpsc::Receiver<ToTaskMessage<I>>,
-        n: HandledNode<M, H::Handler>
+        n: HandledNode<M, H::Handler>,
+        span: tracing::Span
     ) -> Self {
         Task {
             id: i,
             sender: s,
             receiver: r.fuse(),
             state: State::Node(n),
-            taken_over: SmallVec::new()
+            taken_over: SmallVec::new(),
+            close_requested: false,
+            span
         }
     }
 }
@@ -153,6 +168,9 @@ where
     /// Node closing.
     Closing(Close<M>),
 
+    /// Node closing following a `ToTaskMessage::Close`. The outcome is reported once closed.
+    Disconnecting(Close<M>),
+
     /// Interim state that can only be observed externally if the future
     /// resolved to a value previously.
     Undefined
@@ -172,6 +190,8 @@ where
     // first in order to not prevent the outside from making progress because
     // they are blocked on the channel capacity.
     fn poll(&mut self) -> Poll<(), ()> {
+        let span = self.span.clone();
+        let _entered = span.enter();
         'poll: loop {
             match std::mem::replace(&mut self.state, State::Undefined) {
                 State::Future { mut future, handler, mut events_buffer } => {
@@ -184,6 +204,8 @@ where
                                 events_buffer.push(event),
                             Ok(Async::Ready(Some(ToTaskMessage::TakeOver(take_over)))) =>
                                 self.taken_over.push(take_over),
+                            Ok(Async::Ready(Some(ToTaskMessage::Close))) =>
+                                self.close_requested = true,
                             Err(()) => unreachable!("An `mpsc::Receiver` does not error.")
                         }
                     }
@@ -218,6 +240,8 @@ where
                                 node.inject_event(event),
                             Ok(Async::Ready(Some(ToTaskMessage::TakeOver(take_over)))) =>
                                 self.taken_over.push(take_over),
+                            Ok(Async::Ready(Some(ToTaskMessage::Close))) =>
+                                self.close_requested = true,
                             Ok(Async::Ready(None)) => {
                                 // Node closed by the external API; start closing.
                                 self.state = State::Closing(node.close());
@@ -226,6 +250,10 @@ where
                             Err(()) => unreachable!("An `mpsc::Receiver` does not error.")
                         }
                     }
+                    if self.close_requested {
+                        self.state = State::Disconnecting(node.close());
+                        continue 'poll
+                    }
                     // Process the node.
                     loop {
                         if !self.taken_over.is_empty() && node.is_remote_acknowledged() {
@@ -262,6 +290,8 @@ where
                                 }
                             Ok(Async::Ready(Some(ToTaskMessage::TakeOver(take_over)))) =>
                                 self.taken_over.push(take_over),
+                            Ok(Async::Ready(Some(ToTaskMessage::Close))) =>
+                                self.close_requested = true,
                             Ok(Async::Ready(None)) =>
                                 // Node closed by the external API; start closing.
                                 if let Some(n) = node {
@@ -309,6 +339,8 @@ where
                                 }
                             Ok(Async::Ready(Some(ToTaskMessage::TakeOver(take_over)))) =>
                                 self.taken_over.push(take_over),
+                            Ok(Async::Ready(Some(ToTaskMessage::Close))) =>
+                                self.close_requested = true,
                             Ok(Async::Ready(None)) =>
                                 // Node closed by the external API; start closing.
                                 if let Some(n) = node {
@@ -357,6 +389,33 @@ where
                             return Ok(Async::NotReady)
                         }
                     }
+                State::Disconnecting(mut closing) => {
+                    // The node is gone; events for its handler are discarded.
+                    loop {
+                        match self.receiver.poll() {
+                            Ok(Async::NotReady) => break,
+                            Ok(Async::Ready(Some(ToTaskMessage::TakeOver(take_over)))) =>
+                                self.taken_over.push(take_over),
+                            Ok(Async::Ready(Some(_))) => {}
+                            Ok(Async::Ready(None)) => {
+                                // The task has been interrupted; there is nobody to report to.
+                                self.state = State::Closing(closing);
+                                continue 'poll
+                            }
+                            Err(()) => unreachable!("An `mpsc::Receiver` does not error.")
+                        }
+                    }
+                    let result = match closing.poll() {
+                        Ok(Async::Ready(())) => Error::Closed,
+                        Err(err) => Error::Node(HandledNodeError::Node(err)),
+                        Ok(Async::NotReady) => {
+                            self.state = State::Disconnecting(closing);
+                            return Ok(Async::NotReady)
+                        }
+                    };
+                    let event = FromTaskMessage::TaskClosed(result, None);
+                    self.state = State::SendEvent { node: None, event }
+                }
                 // This happens if a previous poll has resolved the future.
                 // The API contract of futures is that we should not be polled again.
                 State::Undefined => panic!("`Task::poll()` called after completion.")
@@ -18,15 +18,26 @@
 // FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
 // DEALINGS IN THE SOFTWARE.
 
-use crate::PublicKey;
+use crate::{PublicKey, multiaddr::{Multiaddr, Protocol}};
 use bs58;
+use data_encoding::BASE32_NOPAD;
 use quick_error::quick_error;
 use multihash;
 use std::{convert::TryFrom, fmt, str::FromStr};
 
+/// Public keys whose protobuf encoding is at most this long are inlined in the `PeerId`
+/// with the identity multihash, rather than hashed with SHA-256.
+const MAX_INLINE_KEY_LENGTH: usize = 42;
+
+/// Version byte of the CIDs used to represent peer IDs as text.
+const CID_VERSION: u8 = 1;
+/// Multicodec of the content of the CIDs used to represent peer IDs as text.
+const LIBP2P_KEY_CODEC: u8 = 0x72;
+
 /// Identifier of a peer of the network.
 ///
-/// The data is a multihash of the public key of the peer.
+/// The data is a multihash of the public key of the peer: the public key itself (identity
+/// multihash) for small keys such as Ed25519 keys, and its SHA-256 hash otherwise.
 // TODO: maybe keep things in decoded version?
 #[derive(Clone, PartialEq, Eq, Hash)]
 pub struct PeerId {
@@ -52,8 +63,13 @@ impl PeerId {
     #[inline]
     pub fn from_public_key(key: PublicKey) -> PeerId {
         let key_enc = key.into_protobuf_encoding();
-        let multihash = multihash::encode(multihash::Hash::SHA2256, &key_enc)
-            .expect("sha2-256 is always supported");
+        let hash_algorithm = if key_enc.len() <= MAX_INLINE_KEY_LENGTH {
+            multihash::Hash::Identity
+        } else {
+            multihash::Hash::SHA2256
+        };
+        let multihash = multihash::encode(hash_algorithm, &key_enc)
+            .expect("identity and sha2-256 are always supported");
         PeerId { multihash }
     }
 
@@ -62,13 +78,7 @@ impl PeerId {
     #[inline]
     pub fn from_bytes(data: Vec<u8>) -> Result<PeerId, Vec<u8>> {
         match multihash::Multihash::from_bytes(data) {
-            Ok(multihash) => {
-                if multihash.algorithm() == multihash::Hash::SHA2256 {
-                    Ok(PeerId { multihash })
-                } else {
-                    Err(multihash.into_bytes())
-                }
-            },
+            Ok(multihash) => PeerId::from_multihash(multihash).map_err(|mh| mh.into_bytes()),
             Err(err) => Err(err.data),
         }
     }
@@ -77,10 +87,22 @@ impl PeerId {
     /// returns back the data as an error.
     #[inline]
     pub fn from_multihash(data: multihash::Multihash) -> Result<PeerId, multihash::Multihash> {
-        if data.algorithm() == multihash::Hash::SHA2256 {
-            Ok(PeerId { multihash: data })
-        } else {
-            Err(data)
+        match data.algorithm() {
+            multihash::Hash::SHA2256 => Ok(PeerId { multihash: data }),
+            multihash::Hash::Identity if data.digest().len() <= MAX_INLINE_KEY_LENGTH =>
+                Ok(PeerId { multihash: data }),
+            _ => Err(data)
+        }
+    }
+
+    /// Extracts the `PeerId` from the trailing `/p2p/<peer>` component of a multiaddr.
+    ///
+    /// Returns `None` if the multiaddr doesn't end with a `/p2p` component, or if that
+    /// component doesn't contain a valid `PeerId`.
+    pub fn try_from_multiaddr(address: &Multiaddr) -> Option<PeerId> {
+        match address.iter().last() {
+            Some(Protocol::P2p(hash)) => PeerId::from_multihash(hash).ok(),
+            _ => None
         }
     }
 
@@ -94,6 +116,18 @@ impl PeerId {
         }
     }
 
+    /// Generates a random peer ID from the given generator.
+    ///
+    /// Like `random`, but reproducible when the generator is seeded.
+    pub fn random_with(rng: &mut impl rand::RngCore) -> PeerId {
+        let mut bytes = [0; 32];
+        rng.fill_bytes(&mut bytes);
+        PeerId {
+            multihash: multihash::encode(multihash::Hash::SHA2256, &bytes)
+                .expect("SHA2-256 is always supported")
+        }
+    }
+
     /// Returns a raw bytes representation of this `PeerId`.
     ///
     /// Note that this is not the same as the public key of the peer.
@@ -116,6 +150,15 @@ impl PeerId {
         bs58::encode(self.multihash.as_bytes()).into_string()
     }
 
+    /// Returns the CIDv1 of this `PeerId` as a multibase `base32` string (`bafz...`).
+    pub fn to_base32(&self) -> String {
+        let mut cid = Vec::with_capacity(2 + self.multihash.as_bytes().len());
+        cid.push(CID_VERSION);
+        cid.push(LIBP2P_KEY_CODEC);
+        cid.extend_from_slice(self.multihash.as_bytes());
+        format!("b{}", BASE32_NOPAD.encode(&cid).to_lowercase())
+    }
+
     /// Returns the raw bytes of the hash of this `PeerId`.
     #[inline]
     pub fn digest(&self) -> &[u8] {
@@ -124,6 +167,9 @@ impl PeerId {
 
     /// Checks whether the public key passed as parameter matches the public key of this `PeerId`.
     ///
+    /// Both forms of `PeerId`s of small keys are accepted: the identity multihash of the key,
+    /// and its SHA-256 hash as produced by older versions.
+    ///
     /// Returns `None` if this `PeerId`s hash algorithm is not supported when encoding the
     /// given public key, otherwise `Some` boolean as the result of an equality check.
     pub fn is_public_key(&self, public_key: &PublicKey) -> Option<bool> {
@@ -202,6 +248,14 @@ quick_error! {
             cause(e)
             from()
         }
+        B32(e: data_encoding::DecodeError) {
+            display("base-32 decode error: {}", e)
+            cause(e)
+            from()
+        }
+        Cid {
+            display("not a CIDv1 of a libp2p public key")
+        }
         MultiHash {
             display("decoding multihash failed")
         }
@@ -211,16 +265,69 @@ quick_error! {
 impl FromStr for PeerId {
     type Err = ParseError;
 
-    #[inline]
+    /// Parses either the base-58 representation of the multihash, or the CIDv1 in
+    /// multibase `base32`, as produced by [`PeerId::to_base32`].
     fn from_str(s: &str) -> Result<Self, Self::Err> {
-        let bytes = bs58::decode(s).into_vec()?;
+        let bytes = if s.starts_with('b') {
+            let cid = BASE32_NOPAD.decode(s[1 ..].to_uppercase().as_bytes())?;
+            if cid.len() < 2 || cid[0] != CID_VERSION || cid[1] != LIBP2P_KEY_CODEC {
+                return Err(ParseError::Cid)
+            }
+            cid[2 ..].to_vec()
+        } else {
+            bs58::decode(s).into_vec()?
+        };
         PeerId::from_bytes(bytes).map_err(|_| ParseError::MultiHash)
     }
 }
 
+/// Serializes the `PeerId` as its base-58 string in human-readable formats, and as its
+/// bytes otherwise.
+#[cfg(feature = "serde")]
+impl serde::Serialize for PeerId {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        if serializer.is_human_readable() {
+            serializer.serialize_str(&self.to_base58())
+        } else {
+            serializer.serialize_bytes(self.as_bytes())
+        }
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de> serde::Deserialize<'de> for PeerId {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        use serde::de::{self, Error};
+
+        struct Visitor;
+
+        impl<'de> de::Visitor<'de> for Visitor {
+            type Value = PeerId;
+
+            fn expecting(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                f.write_str("a peer ID")
+            }
+
+            fn visit_str<E: Error>(self, v: &str) -> Result<Self::Value, E> {
+                v.parse().map_err(Error::custom)
+            }
+
+            fn visit_bytes<E: Error>(self, v: &[u8]) -> Result<Self::Value, E> {
+                PeerId::from_bytes(v.to_vec()).map_err(|_| Error::custom("invalid peer ID"))
+            }
+        }
+
+        if deserializer.is_human_readable() {
+            deserializer.deserialize_str(Visitor)
+        } else {
+            deserializer.deserialize_bytes(Visitor)
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
-    use crate::{PeerId, identity};
+    use crate::{Multiaddr, PeerId, identity};
 
     #[test]
     fn peer_id_is_public_key() {
@@ -229,6 +336,20 @@ mod tests {
         assert_eq!(peer_id.is_public_key(&key), Some(true));
     }
 
+    #[test]
+    fn small_keys_are_inlined() {
+        let key = identity::Keypair::generate_ed25519().public();
+        let peer_id = key.clone().into_peer_id();
+        let mh: multihash::Multihash = peer_id.clone().into();
+        assert_eq!(mh.algorithm(), multihash::Hash::Identity);
+        assert_eq!(peer_id.digest(), &key.clone().into_protobuf_encoding()[..]);
+
+        let legacy = multihash::encode(multihash::Hash::SHA2256, &key.clone().into_protobuf_encoding())
+            .unwrap();
+        let legacy = PeerId::from_multihash(legacy).unwrap();
+        assert_eq!(legacy.is_public_key(&key), Some(true));
+    }
+
     #[test]
     fn peer_id_into_bytes_then_from_bytes() {
         let peer_id = identity::Keypair::generate_ed25519().public().into_peer_id();
@@ -243,6 +364,42 @@ mod tests {
         assert_eq!(peer_id, second);
     }
 
+    #[test]
+    fn peer_id_to_base32_then_back() {
+        let peer_id = identity::Keypair::generate_ed25519().public().into_peer_id();
+        let encoded = peer_id.to_base32();
+        assert!(encoded.starts_with("bafz"));
+        let second: PeerId = encoded.parse().unwrap();
+        assert_eq!(peer_id, second);
+    }
+
+    #[test]
+    fn peer_id_from_multiaddr() {
+        let peer_id = PeerId::random();
+        let address: Multiaddr = "/ip4/127.0.0.1/tcp/1234".parse().unwrap();
+        assert_eq!(PeerId::try_from_multiaddr(&address), None);
+        let address = address.with_p2p(peer_id.clone()).unwrap();
+        assert_eq!(PeerId::try_from_multiaddr(&address), Some(peer_id));
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn serde_round_trip() {
+        let key = identity::Keypair::generate_ed25519().public();
+        let peer_id = key.clone().into_peer_id();
+
+        let json = serde_json::to_string(&peer_id).unwrap();
+        assert_eq!(json, format!("\"{}\"", peer_id.to_base58()));
+        assert_eq!(serde_json::from_str::<PeerId>(&json).unwrap(), peer_id);
+        let binary = bincode::serialize(&peer_id).unwrap();
+        assert_eq!(bincode::deserialize::<PeerId>(&binary).unwrap(), peer_id);
+
+        let json = serde_json::to_string(&key).unwrap();
+        assert_eq!(serde_json::from_str::<crate::PublicKey>(&json).unwrap(), key);
+        let binary = bincode::serialize(&key).unwrap();
+        assert_eq!(bincode::deserialize::<crate::PublicKey>(&binary).unwrap(), key);
+    }
+
     #[test]
     fn random_peer_id_is_valid() {
         for _ in 0 .. 5000 {
@@ -0,0 +1,234 @@
+// Copyright 2019 Parity Technologies (UK) Ltd.
+//
+// Permission is hereby granted, free of charge, to any person obtaining a
+// copy of this software and associated documentation files (the "Software"),
+// to deal in the Software without restriction, including without limitation
+// the rights to use, copy, modify, merge, publish, distribute, sublicense,
+// and/or sell copies of the Software, and to permit persons to whom the
+// Software is furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS
+// OR IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING
+// FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
+// DEALINGS IN THE SOFTWARE.
+
+//! Peer records, as described in the libp2p "routing records" specification.
+//!
+//! A peer record is a list of addresses a peer can be reached at, signed by that peer and
+//! transported in a [`SignedEnvelope`]. Records carry a sequence number, so that a more recent
+//! record can be told apart from an older one.
+
+use crate::{
+    Multiaddr, PeerId,
+    identity::{Keypair, error::{DecodingError, SigningError}},
+    peer_record_proto,
+    signed_envelope::{ReadPayloadError, SignedEnvelope},
+};
+use prost::Message;
+use std::{convert::TryFrom, error, fmt};
+#[cfg(not(all(target_arch = "wasm32", target_os = "unknown")))]
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Domain used when signing peer records.
+pub const DOMAIN: &str = "libp2p-peer-record";
+
+/// Payload type of peer records, i.e. the multicodec `libp2p-peer-record`.
+pub const PAYLOAD_TYPE: &[u8] = &[0x03, 0x01];
+
+/// Signed list of addresses of a peer.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PeerRecord {
+    peer_id: PeerId,
+    seq: u64,
+    addresses: Vec<Multiaddr>,
+    /// The envelope the record has been created from or signed into.
+    envelope: SignedEnvelope,
+}
+
+/// Returns the current UNIX time in milliseconds.
+#[cfg(not(all(target_arch = "wasm32", target_os = "unknown")))]
+fn unix_time_millis() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs() * 1000 + u64::from(d.subsec_millis()))
+        .unwrap_or(0)
+}
+
+/// Returns the current UNIX time in milliseconds.
+///
+/// `SystemTime::now` panics in browsers, so the clock of the page is used instead.
+#[cfg(all(target_arch = "wasm32", target_os = "unknown"))]
+fn unix_time_millis() -> u64 {
+    js_sys::Date::now() as u64
+}
+
+impl PeerRecord {
+    /// Creates and signs a new record for the given addresses.
+    ///
+    /// The sequence number is the current UNIX time in milliseconds, which guarantees that a
+    /// record created later has a higher sequence number.
+    pub fn new(key: &Keypair, addresses: Vec<Multiaddr>) -> Result<Self, SigningError> {
+        PeerRecord::with_seq(key, unix_time_millis(), addresses)
+    }
+
+    /// Creates and signs a new record with an explicit sequence number.
+    pub fn with_seq(key: &Keypair, seq: u64, addresses: Vec<Multiaddr>) -> Result<Self, SigningError> {
+        let peer_id = key.public().into_peer_id();
+        let payload = encode_record(&peer_id, seq, &addresses);
+        let envelope = SignedEnvelope::new(key, DOMAIN, PAYLOAD_TYPE.to_vec(), payload)?;
+
+        Ok(PeerRecord {
+            peer_id,
+            seq,
+            addresses,
+            envelope,
+        })
+    }
+
+    /// Extracts and verifies a record from a signed envelope.
+    ///
+    /// Fails if the signature is invalid, or if the record has been signed by a different peer
+    /// than the one it describes.
+    pub fn from_signed_envelope(envelope: SignedEnvelope) -> Result<Self, FromEnvelopeError> {
+        let payload = envelope.payload(DOMAIN, PAYLOAD_TYPE)
+            .map_err(FromEnvelopeError::BadPayload)?;
+
+        let record = peer_record_proto::PeerRecord::decode(payload)
+            .map_err(|e| FromEnvelopeError::InvalidPeerRecord(DecodingError::new("Protobuf").source(e)))?;
+
+        let peer_id = PeerId::from_bytes(record.peer_id)
+            .map_err(|_| FromEnvelopeError::InvalidPeerRecord(DecodingError::new("Invalid peer ID")))?;
+        let seq = record.seq;
+        let addresses = record.addresses.into_iter()
+            .map(|info| Multiaddr::try_from(info.multiaddr))
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|_| FromEnvelopeError::InvalidPeerRecord(DecodingError::new("Invalid multiaddr")))?;
+
+        if peer_id.is_public_key(envelope.key()) != Some(true) {
+            return Err(FromEnvelopeError::MismatchedSignature);
+        }
+
+        Ok(PeerRecord {
+            peer_id,
+            seq,
+            addresses,
+            envelope,
+        })
+    }
+
+    /// Returns the envelope containing the signed record.
+    pub fn to_signed_envelope(&self) -> SignedEnvelope {
+        self.envelope.clone()
+    }
+
+    /// Returns the envelope containing the signed record.
+    pub fn into_signed_envelope(self) -> SignedEnvelope {
+        self.envelope
+    }
+
+    /// Returns the peer the record describes.
+    pub fn peer_id(&self) -> &PeerId {
+        &self.peer_id
+    }
+
+    /// Returns the sequence number of the record. A higher number means a more recent record.
+    pub fn seq(&self) -> u64 {
+        self.seq
+    }
+
+    /// Returns the addresses of the peer.
+    pub fn addresses(&self) -> &[Multiaddr] {
+        &self.addresses
+    }
+}
+
+/// Encodes the protobuf payload of a peer record.
+fn encode_record(peer_id: &PeerId, seq: u64, addresses: &[Multiaddr]) -> Vec<u8> {
+    let record = peer_record_proto::PeerRecord {
+        peer_id: peer_id.as_bytes().to_vec(),
+        seq,
+        addresses: addresses.iter()
+            .map(|addr| peer_record_proto::peer_record::AddressInfo { multiaddr: addr.to_vec() })
+            .collect(),
+    };
+
+    let mut buf = Vec::with_capacity(record.encoded_len());
+    record.encode(&mut buf).expect("Vec<u8> provides capacity as needed");
+    buf
+}
+
+/// Error when extracting a `PeerRecord` from a `SignedEnvelope`.
+#[derive(Debug)]
+pub enum FromEnvelopeError {
+    /// The envelope doesn't contain a valid peer record payload.
+    BadPayload(ReadPayloadError),
+    /// The payload couldn't be decoded.
+    InvalidPeerRecord(DecodingError),
+    /// The record has been signed by a peer other than the one it describes.
+    MismatchedSignature,
+}
+
+impl fmt::Display for FromEnvelopeError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            FromEnvelopeError::BadPayload(err) => write!(f, "Bad payload: {}", err),
+            FromEnvelopeError::InvalidPeerRecord(err) => write!(f, "Invalid peer record: {}", err),
+            FromEnvelopeError::MismatchedSignature => write!(f, "Record signed by another peer"),
+        }
+    }
+}
+
+impl error::Error for FromEnvelopeError {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        match self {
+            FromEnvelopeError::BadPayload(err) => Some(err),
+            FromEnvelopeError::InvalidPeerRecord(err) => Some(err),
+            FromEnvelopeError::MismatchedSignature => None,
+        }
+    }
+}
+
+impl From<ReadPayloadError> for FromEnvelopeError {
+    fn from(err: ReadPayloadError) -> Self {
+        FromEnvelopeError::BadPayload(err)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn roundtrip_through_envelope() {
+        let key = Keypair::generate_ed25519();
+        let addr: Multiaddr = "/ip4/127.0.0.1/tcp/1234".parse().unwrap();
+        let record = PeerRecord::with_seq(&key, 7, vec![addr.clone()]).unwrap();
+
+        let bytes = record.to_signed_envelope().into_protobuf_encoding();
+        let envelope = SignedEnvelope::from_protobuf_encoding(&bytes).unwrap();
+        let decoded = PeerRecord::from_signed_envelope(envelope).unwrap();
+
+        assert_eq!(decoded.peer_id(), &key.public().into_peer_id());
+        assert_eq!(decoded.seq(), 7);
+        assert_eq!(decoded.addresses(), &[addr][..]);
+    }
+
+    #[test]
+    fn record_signed_by_another_peer_is_rejected() {
+        let key = Keypair::generate_ed25519();
+        let other = Keypair::generate_ed25519();
+        let payload = encode_record(&other.public().into_peer_id(), 1, &[]);
+        let envelope = SignedEnvelope::new(&key, DOMAIN, PAYLOAD_TYPE.to_vec(), payload).unwrap();
+
+        match PeerRecord::from_signed_envelope(envelope) {
+            Err(FromEnvelopeError::MismatchedSignature) => {}
+            other => panic!("unexpected result: {:?}", other),
+        }
+    }
+}
@@ -0,0 +1,95 @@
+// Copyright 2019 Parity Technologies (UK) Ltd.
+//
+// Permission is hereby granted, free of charge, to any person obtaining a
+// copy of this software and associated documentation files (the "Software"),
+// to deal in the Software without restriction, including without limitation
+// the rights to use, copy, modify, merge, publish, distribute, sublicense,
+// and/or sell copies of the Software, and to permit persons to whom the
+// Software is furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS
+// OR IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING
+// FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
+// DEALINGS IN THE SOFTWARE.
+//! Source of the randomness of the protocol logic.
+//!
+//! The behaviours draw their random choices, such as sequence numbers, the peers to sample
+//! or the dialing jitter, from a [`BoxedRng`] that tests can replace with a seeded generator
+//! to make runs reproducible. Key generation isn't affected and always uses the entropy of
+//! the operating system.
+
+use rand::{RngCore, SeedableRng, rngs::StdRng};
+use std::fmt;
+
+/// Random number generator of a component, boxed so that any generator can be injected.
+///
+/// The default is a cryptographically secure generator seeded from the entropy of the
+/// operating system. Tests can pass a [`BoxedRng::seeded`] generator to the `rng` or `set_rng`
+/// method of a component for reproducible runs.
+pub struct BoxedRng(Box<dyn RngCore + Send>);
+
+impl BoxedRng {
+    /// Wraps the given generator.
+    pub fn new(rng: impl RngCore + Send + 'static) -> Self {
+        BoxedRng(Box::new(rng))
+    }
+
+    /// Creates a generator seeded from the entropy of the operating system.
+    pub fn from_entropy() -> Self {
+        BoxedRng::new(StdRng::from_entropy())
+    }
+
+    /// Creates a generator whose output only depends on `seed`, for reproducible tests.
+    pub fn seeded(seed: u64) -> Self {
+        BoxedRng::new(StdRng::seed_from_u64(seed))
+    }
+}
+
+impl Default for BoxedRng {
+    fn default() -> Self {
+        BoxedRng::from_entropy()
+    }
+}
+
+impl fmt::Debug for BoxedRng {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("BoxedRng")
+    }
+}
+
+impl RngCore for BoxedRng {
+    fn next_u32(&mut self) -> u32 {
+        self.0.next_u32()
+    }
+
+    fn next_u64(&mut self) -> u64 {
+        self.0.next_u64()
+    }
+
+    fn fill_bytes(&mut self, dest: &mut [u8]) {
+        self.0.fill_bytes(dest)
+    }
+
+    fn try_fill_bytes(&mut self, dest: &mut [u8]) -> Result<(), rand::Error> {
+        self.0.try_fill_bytes(dest)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::Rng;
+
+    #[test]
+    fn seeded_generators_are_reproducible() {
+        let draw = |seed| BoxedRng::seeded(seed).gen::<[u64; 4]>();
+        assert_eq!(draw(3), draw(3));
+        assert_ne!(draw(3), draw(4));
+    }
+}
@@ -0,0 +1,240 @@
+// Copyright 2019 Parity Technologies (UK) Ltd.
+//
+// Permission is hereby granted, free of charge, to any person obtaining a
+// copy of this software and associated documentation files (the "Software"),
+// to deal in the Software without restriction, including without limitation
+// the rights to use, copy, modify, merge, publish, distribute, sublicense,
+// and/or sell copies of the Software, and to permit persons to whom the
+// Software is furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS
+// OR IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING
+// FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
+// DEALINGS IN THE SOFTWARE.
+
+//! Abstractions over the ways of finding the providers of some content or the addresses of a
+//! peer, for example a DHT or a delegated routing service.
+//!
+//! Code that only needs to look up providers or peers can be generic over [`ContentRouting`]
+//! and [`PeerRouting`] instead of depending on a concrete router. Routing operations complete
+//! asynchronously, and each router reports their outcome as [`RoutingEvent`]s through its own
+//! means, e.g. the events of its `NetworkBehaviour`.
+//!
+//! Multiple routers can be combined with a tuple, in which case each operation is started on
+//! all of them. For example, `(&mut dht, &mut delegated).find_providers(key)` queries both.
+
+use crate::{Multiaddr, PeerId};
+use multihash::Multihash;
+use std::{error, fmt};
+
+/// Finds the providers of some content, and announces the local node as a provider.
+pub trait ContentRouting {
+    /// Announces that the local node is a provider of the content identified by `key`.
+    ///
+    /// The outcome is reported as a [`RoutingEvent::Provided`].
+    fn provide(&mut self, key: Multihash);
+
+    /// Starts looking for the providers of the content identified by `key`.
+    ///
+    /// The outcome is reported as a [`RoutingEvent::FoundProviders`].
+    fn find_providers(&mut self, key: Multihash);
+}
+
+/// Finds the addresses of a peer.
+pub trait PeerRouting {
+    /// Starts looking for the addresses of `peer_id`.
+    ///
+    /// The outcome is reported as a [`RoutingEvent::FoundPeer`].
+    fn find_peer(&mut self, peer_id: PeerId);
+}
+
+/// Outcome of an operation started through [`ContentRouting`] or [`PeerRouting`].
+#[derive(Debug, Clone)]
+pub enum RoutingEvent {
+    /// Outcome of [`ContentRouting::provide`].
+    Provided {
+        /// The key of the content.
+        key: Multihash,
+        /// Whether the announcement succeeded.
+        result: Result<(), RoutingError>,
+    },
+    /// Outcome of [`ContentRouting::find_providers`].
+    FoundProviders {
+        /// The key of the content.
+        key: Multihash,
+        /// The providers that have been found.
+        result: Result<Vec<RoutingPeer>, RoutingError>,
+    },
+    /// Outcome of [`PeerRouting::find_peer`].
+    FoundPeer {
+        /// The peer that was looked up.
+        peer_id: PeerId,
+        /// The addresses of the peer that have been found.
+        result: Result<Vec<Multiaddr>, RoutingError>,
+    },
+}
+
+/// A peer returned by a router, along with the addresses the router knows for it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RoutingPeer {
+    /// Identity of the peer.
+    pub peer_id: PeerId,
+    /// Addresses of the peer. Can be empty if the router doesn't know any.
+    pub addresses: Vec<Multiaddr>,
+}
+
+impl RoutingPeer {
+    /// Builds a `RoutingPeer` without any known address.
+    pub fn new(peer_id: PeerId) -> Self {
+        RoutingPeer { peer_id, addresses: Vec::new() }
+    }
+}
+
+/// Error of a routing operation.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RoutingError {
+    /// The router didn't find what was looked up.
+    NotFound,
+    /// The operation didn't complete in time.
+    Timeout,
+    /// The router failed for another reason.
+    Other(String),
+}
+
+impl fmt::Display for RoutingError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RoutingError::NotFound => write!(f, "Not found"),
+            RoutingError::Timeout => write!(f, "Routing operation timed out"),
+            RoutingError::Other(err) => write!(f, "Routing failed: {}", err),
+        }
+    }
+}
+
+impl error::Error for RoutingError {}
+
+impl<'a, T> ContentRouting for &'a mut T
+where
+    T: ContentRouting + ?Sized
+{
+    fn provide(&mut self, key: Multihash) {
+        (**self).provide(key)
+    }
+
+    fn find_providers(&mut self, key: Multihash) {
+        (**self).find_providers(key)
+    }
+}
+
+impl<'a, T> PeerRouting for &'a mut T
+where
+    T: PeerRouting + ?Sized
+{
+    fn find_peer(&mut self, peer_id: PeerId) {
+        (**self).find_peer(peer_id)
+    }
+}
+
+impl<T> ContentRouting for Box<T>
+where
+    T: ContentRouting + ?Sized
+{
+    fn provide(&mut self, key: Multihash) {
+        (**self).provide(key)
+    }
+
+    fn find_providers(&mut self, key: Multihash) {
+        (**self).find_providers(key)
+    }
+}
+
+impl<T> PeerRouting for Box<T>
+where
+    T: PeerRouting + ?Sized
+{
+    fn find_peer(&mut self, peer_id: PeerId) {
+        (**self).find_peer(peer_id)
+    }
+}
+
+impl<A, B> ContentRouting for (A, B)
+where
+    A: ContentRouting,
+    B: ContentRouting,
+{
+    fn provide(&mut self, key: Multihash) {
+        self.0.provide(key.clone());
+        self.1.provide(key)
+    }
+
+    fn find_providers(&mut self, key: Multihash) {
+        self.0.find_providers(key.clone());
+        self.1.find_providers(key)
+    }
+}
+
+impl<A, B> PeerRouting for (A, B)
+where
+    A: PeerRouting,
+    B: PeerRouting,
+{
+    fn find_peer(&mut self, peer_id: PeerId) {
+        self.0.find_peer(peer_id.clone());
+        self.1.find_peer(peer_id)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Default)]
+    struct Recorder {
+        provided: Vec<Multihash>,
+        searched: Vec<Multihash>,
+        peers: Vec<PeerId>,
+    }
+
+    impl ContentRouting for Recorder {
+        fn provide(&mut self, key: Multihash) {
+            self.provided.push(key)
+        }
+
+        fn find_providers(&mut self, key: Multihash) {
+            self.searched.push(key)
+        }
+    }
+
+    impl PeerRouting for Recorder {
+        fn find_peer(&mut self, peer_id: PeerId) {
+            self.peers.push(peer_id)
+        }
+    }
+
+    #[test]
+    fn tuple_forwards_to_both_routers() {
+        let mut a = Recorder::default();
+        let mut b = Recorder::default();
+        let key = multihash::encode(multihash::Hash::SHA2256, b"hello").unwrap();
+        let peer_id = PeerId::random();
+
+        {
+            let mut both = (&mut a, &mut b);
+            both.provide(key.clone());
+            both.find_providers(key.clone());
+            both.find_peer(peer_id.clone());
+        }
+
+        for router in &[a, b] {
+            assert_eq!(router.provided, vec![key.clone()]);
+            assert_eq!(router.searched, vec![key.clone()]);
+            assert_eq!(router.peers, vec![peer_id.clone()]);
+        }
+    }
+}
@@ -0,0 +1,189 @@
+// Copyright 2019 Parity Technologies (UK) Ltd.
+//
+// Permission is hereby granted, free of charge, to any person obtaining a
+// copy of this software and associated documentation files (the "Software"),
+// to deal in the Software without restriction, including without limitation
+// the rights to use, copy, modify, merge, publish, distribute, sublicense,
+// and/or sell copies of the Software, and to permit persons to whom the
+// Software is furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS
+// OR IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING
+// FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
+// DEALINGS IN THE SOFTWARE.
+//! Bounded cache of recently seen identifiers, for example to detect duplicate pubsub messages.
+//!
+//! A [`SeenCache`] only remembers the identifiers inserted during a sliding time window, and
+//! never holds more than a configured number of bytes, whatever the rate at which identifiers
+//! are inserted. It is made of a ring of time slices, and whole slices are discarded at once
+//! when they become older than the window or when the cache exceeds its size limit.
+
+use fnv::FnvHashSet;
+use std::{collections::VecDeque, fmt, hash::Hash, mem, time::Duration};
+use wasm_timer::Instant;
+
+/// Number of time slices a window is divided into.
+const NUM_SLICES: u32 = 8;
+
+/// Cache of the identifiers seen during a sliding time window.
+pub struct SeenCache<K> {
+    /// The slices, from the oldest to the newest.
+    slices: VecDeque<Slice<K>>,
+    /// Duration covered by a slice.
+    slice_duration: Duration,
+    /// Duration during which an identifier is remembered, at least.
+    window: Duration,
+    /// Maximum number of bytes accounted for the identifiers.
+    max_bytes: usize,
+    /// Number of bytes accounted for the identifiers of all the slices.
+    bytes: usize,
+}
+
+/// Identifiers inserted during a slice of time.
+struct Slice<K> {
+    /// When the slice started.
+    start: Instant,
+    /// The identifiers.
+    entries: FnvHashSet<K>,
+    /// Number of bytes accounted for `entries`.
+    bytes: usize,
+}
+
+impl<K> SeenCache<K>
+where
+    K: Hash + Eq + AsRef<[u8]>
+{
+    /// Creates a cache remembering identifiers for `window`, and holding at most `max_bytes`
+    /// bytes of identifiers.
+    ///
+    /// If more than `max_bytes` bytes of identifiers are inserted during `window`, the oldest
+    /// ones are forgotten early.
+    pub fn new(window: Duration, max_bytes: usize) -> Self {
+        SeenCache {
+            slices: VecDeque::new(),
+            slice_duration: window / NUM_SLICES,
+            window,
+            max_bytes,
+            bytes: 0,
+        }
+    }
+
+    /// Returns `true` if the identifier has been inserted recently.
+    pub fn contains(&mut self, key: &K) -> bool {
+        self.expire(Instant::now());
+        self.slices.iter().any(|s| s.entries.contains(key))
+    }
+
+    /// Inserts an identifier. Returns `false` if it has been inserted recently, in which case
+    /// the cache is left untouched.
+    pub fn insert(&mut self, key: K) -> bool {
+        self.insert_at(key, Instant::now())
+    }
+
+    /// Returns the number of identifiers in the cache.
+    pub fn len(&self) -> usize {
+        self.slices.iter().map(|s| s.entries.len()).sum()
+    }
+
+    /// Returns `true` if the cache is empty.
+    pub fn is_empty(&self) -> bool {
+        self.slices.iter().all(|s| s.entries.is_empty())
+    }
+
+    /// Returns the number of bytes accounted for the identifiers in the cache.
+    pub fn bytes(&self) -> usize {
+        self.bytes
+    }
+
+    fn insert_at(&mut self, key: K, now: Instant) -> bool {
+        self.expire(now);
+        if self.slices.iter().any(|s| s.entries.contains(&key)) {
+            return false
+        }
+
+        // Discard the oldest slices, possibly including the newest one, until there is room for
+        // the identifier.
+        let size = entry_size(&key);
+        while self.bytes + size > self.max_bytes && !self.slices.is_empty() {
+            self.pop_oldest();
+        }
+
+        let needs_slice = self.slices.back()
+            .map_or(true, |s| now >= s.start + self.slice_duration);
+        if needs_slice {
+            self.slices.push_back(Slice { start: now, entries: FnvHashSet::default(), bytes: 0 });
+        }
+
+        let newest = self.slices.back_mut().expect("a slice has just been pushed if needed; QED");
+        newest.entries.insert(key);
+        newest.bytes += size;
+        self.bytes += size;
+        true
+    }
+
+    /// Discards the slices that ended more than `window` ago.
+    fn expire(&mut self, now: Instant) {
+        while let Some(oldest) = self.slices.front() {
+            if oldest.start + self.slice_duration + self.window > now {
+                break
+            }
+            self.pop_oldest();
+        }
+    }
+
+    fn pop_oldest(&mut self) {
+        if let Some(slice) = self.slices.pop_front() {
+            self.bytes -= slice.bytes;
+        }
+    }
+}
+
+/// Number of bytes accounted for an identifier.
+fn entry_size<K: AsRef<[u8]>>(key: &K) -> usize {
+    key.as_ref().len() + mem::size_of::<K>()
+}
+
+impl<K> fmt::Debug for SeenCache<K> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("SeenCache")
+            .field("window", &self.window)
+            .field("max_bytes", &self.max_bytes)
+            .field("bytes", &self.bytes)
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn identifiers_expire_and_stay_within_bounds() {
+        let window = Duration::from_secs(8);
+        let mut cache = SeenCache::new(window, 10 * entry_size(&vec![0u8; 4]));
+        let start = Instant::now();
+
+        assert!(cache.insert_at(vec![0; 4], start));
+        assert!(!cache.insert_at(vec![0; 4], start + Duration::from_secs(1)));
+        assert_eq!(cache.len(), 1);
+
+        // Once the window has elapsed, the identifier is forgotten.
+        assert!(cache.insert_at(vec![1; 4], start + Duration::from_secs(2)));
+        cache.expire(start + window + Duration::from_secs(2));
+        assert_eq!(cache.len(), 1);
+        assert!(cache.insert_at(vec![0; 4], start + window + Duration::from_secs(2)));
+
+        // The cache never holds more bytes than allowed.
+        for n in 0 .. 100u8 {
+            cache.insert_at(vec![n; 4], start + window + Duration::from_secs(3));
+            assert!(cache.bytes() <= 10 * entry_size(&vec![0u8; 4]));
+        }
+        assert!(!cache.is_empty());
+    }
+}
@@ -0,0 +1,195 @@
+// Copyright 2019 Parity Technologies (UK) Ltd.
+//
+// Permission is hereby granted, free of charge, to any person obtaining a
+// copy of this software and associated documentation files (the "Software"),
+// to deal in the Software without restriction, including without limitation
+// the rights to use, copy, modify, merge, publish, distribute, sublicense,
+// and/or sell copies of the Software, and to permit persons to whom the
+// Software is furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS
+// OR IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING
+// FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
+// DEALINGS IN THE SOFTWARE.
+
+//! Signed envelopes, as described in the libp2p "signed envelope" specification.
+//!
+//! An envelope wraps an arbitrary payload together with the public key of its author and a
+//! signature. The signature covers a *domain* string, which is never transmitted, so that a
+//! signature produced for one purpose can't be reused for another.
+
+use crate::envelope_proto;
+use crate::identity::{Keypair, PublicKey, error::{DecodingError, SigningError}};
+use prost::Message;
+use std::{error, fmt};
+
+/// A payload signed by the owner of a key.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SignedEnvelope {
+    key: PublicKey,
+    payload_type: Vec<u8>,
+    payload: Vec<u8>,
+    signature: Vec<u8>,
+}
+
+impl SignedEnvelope {
+    /// Signs the given payload with the keypair, for the given domain.
+    pub fn new(
+        key: &Keypair,
+        domain: &str,
+        payload_type: Vec<u8>,
+        payload: Vec<u8>,
+    ) -> Result<Self, SigningError> {
+        let buffer = signature_payload(domain, &payload_type, &payload);
+        let signature = key.sign(&buffer)?;
+
+        Ok(SignedEnvelope {
+            key: key.public(),
+            payload_type,
+            payload,
+            signature,
+        })
+    }
+
+    /// Returns `true` if the signature of the envelope is valid for the given domain.
+    pub fn verify(&self, domain: &str) -> bool {
+        let buffer = signature_payload(domain, &self.payload_type, &self.payload);
+        self.key.verify(&buffer, &self.signature)
+    }
+
+    /// Returns the payload, after checking the signature for the given domain and the type of
+    /// the payload.
+    pub fn payload(&self, domain: &str, expected_payload_type: &[u8]) -> Result<&[u8], ReadPayloadError> {
+        if self.payload_type != expected_payload_type {
+            return Err(ReadPayloadError::UnexpectedPayloadType {
+                expected: expected_payload_type.to_vec(),
+                got: self.payload_type.clone(),
+            });
+        }
+
+        if !self.verify(domain) {
+            return Err(ReadPayloadError::InvalidSignature);
+        }
+
+        Ok(&self.payload)
+    }
+
+    /// Like [`SignedEnvelope::payload`], but also returns the public key of the author, for
+    /// the many uses where the payload must be attributed to a peer.
+    pub fn payload_and_signing_key(
+        &self,
+        domain: &str,
+        expected_payload_type: &[u8],
+    ) -> Result<(&[u8], &PublicKey), ReadPayloadError> {
+        let payload = self.payload(domain, expected_payload_type)?;
+        Ok((payload, &self.key))
+    }
+
+    /// Returns the public key of the author of the envelope.
+    pub fn key(&self) -> &PublicKey {
+        &self.key
+    }
+
+    /// Returns the type of the payload.
+    pub fn payload_type(&self) -> &[u8] {
+        &self.payload_type
+    }
+
+    /// Encodes the envelope into a protobuf structure for storage or exchange with other nodes.
+    pub fn into_protobuf_encoding(self) -> Vec<u8> {
+        let envelope = envelope_proto::Envelope {
+            public_key: Some(self.key.to_proto()),
+            payload_type: self.payload_type,
+            payload: self.payload,
+            signature: self.signature,
+        };
+
+        let mut buf = Vec::with_capacity(envelope.encoded_len());
+        envelope.encode(&mut buf).expect("Vec<u8> provides capacity as needed");
+        buf
+    }
+
+    /// Decodes an envelope from a protobuf structure, e.g. received from another node.
+    ///
+    /// The signature isn't verified.
+    pub fn from_protobuf_encoding(bytes: &[u8]) -> Result<Self, DecodingError> {
+        let envelope = envelope_proto::Envelope::decode(bytes)
+            .map_err(|e| DecodingError::new("Protobuf").source(e))?;
+        let key = envelope.public_key
+            .ok_or_else(|| DecodingError::new("Missing public key in envelope"))?;
+
+        Ok(SignedEnvelope {
+            key: PublicKey::from_proto(key)?,
+            payload_type: envelope.payload_type,
+            payload: envelope.payload,
+            signature: envelope.signature,
+        })
+    }
+}
+
+/// Builds the buffer that is signed, made of the length-prefixed domain, payload type and
+/// payload.
+fn signature_payload(domain: &str, payload_type: &[u8], payload: &[u8]) -> Vec<u8> {
+    let mut buffer = Vec::with_capacity(domain.len() + payload_type.len() + payload.len() + 30);
+    let mut len = unsigned_varint::encode::u64_buffer();
+    for part in &[domain.as_bytes(), payload_type, payload] {
+        buffer.extend_from_slice(unsigned_varint::encode::u64(part.len() as u64, &mut len));
+        buffer.extend_from_slice(part);
+    }
+    buffer
+}
+
+/// Error when reading the payload of a `SignedEnvelope`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ReadPayloadError {
+    /// The signature of the envelope is invalid.
+    InvalidSignature,
+    /// The payload isn't of the expected type.
+    UnexpectedPayloadType {
+        /// The expected payload type.
+        expected: Vec<u8>,
+        /// The actual payload type.
+        got: Vec<u8>,
+    },
+}
+
+impl fmt::Display for ReadPayloadError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ReadPayloadError::InvalidSignature => write!(f, "Invalid signature"),
+            ReadPayloadError::UnexpectedPayloadType { expected, got } =>
+                write!(f, "Unexpected payload type, expected {:?} but got {:?}", expected, got),
+        }
+    }
+}
+
+impl error::Error for ReadPayloadError {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sign_then_verify() {
+        let key = Keypair::generate_ed25519();
+        let envelope = SignedEnvelope::new(&key, "test-domain", b"type".to_vec(), b"payload".to_vec())
+            .unwrap();
+
+        let decoded = SignedEnvelope::from_protobuf_encoding(&envelope.clone().into_protobuf_encoding())
+            .unwrap();
+        assert_eq!(decoded, envelope);
+        assert_eq!(decoded.payload("test-domain", b"type"), Ok(&b"payload"[..]));
+        assert_eq!(decoded.payload("other-domain", b"type"), Err(ReadPayloadError::InvalidSignature));
+        assert!(decoded.payload("test-domain", b"other").is_err());
+        assert_eq!(
+            decoded.payload_and_signing_key("test-domain", b"type"),
+            Ok((&b"payload"[..], &key.public()))
+        );
+    }
+}
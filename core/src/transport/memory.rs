@@ -29,14 +29,62 @@ use rw_stream_sink::RwStreamSink;
 use std::{collections::hash_map::Entry, error, fmt, io, num::NonZeroU64};
 
 lazy_static! {
-    static ref HUB: Mutex<FnvHashMap<NonZeroU64, mpsc::Sender<Channel<Bytes>>>> =
-        Mutex::new(FnvHashMap::default());
+    static ref HUB: Mutex<Hub> = Mutex::new(Hub::default());
 }
 
-/// Transport that supports `/memory/N` multiaddresses.
+/// Process-global registry of the listeners of all `MemoryTransport`s.
+#[derive(Default)]
+struct Hub {
+    /// Listeners, with the identifier of their registration.
+    listeners: FnvHashMap<Endpoint, (u64, mpsc::Sender<Channel<Bytes>>)>,
+    /// Identifier of the next registration.
+    next_registration: u64,
+}
+
+/// Endpoint a listener is registered under.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+enum Endpoint {
+    /// `/memory/N`.
+    Port(NonZeroU64),
+    /// `/memory/<name>`.
+    Name(String),
+}
+
+impl Endpoint {
+    fn to_multiaddr(&self) -> Multiaddr {
+        match self {
+            Endpoint::Port(port) => Protocol::Memory(port.get()).into(),
+            Endpoint::Name(name) => Protocol::MemoryName(name.as_str().into()).into(),
+        }
+    }
+}
+
+/// Transport that supports `/memory/N` and `/memory/<name>` multiaddresses.
+///
+/// Listeners are registered in a process-global registry until they are dropped or closed
+/// with [`MemoryTransport::close`], so that multiple swarms of the same process can reach each
+/// other with stable, human-readable addresses such as `/memory/alice`.
 #[derive(Debug, Copy, Clone, Default)]
 pub struct MemoryTransport;
 
+impl MemoryTransport {
+    /// Returns the addresses of all the listeners of the process.
+    pub fn listeners() -> Vec<Multiaddr> {
+        HUB.lock().listeners.keys().map(Endpoint::to_multiaddr).collect()
+    }
+
+    /// Closes the listener on the given address, if any.
+    ///
+    /// The address becomes unreachable immediately and the stream of the listener ends once
+    /// pending dials have completed. Returns whether there was a listener on the address.
+    pub fn close(addr: &Multiaddr) -> bool {
+        match parse_endpoint(addr) {
+            Some(endpoint) => HUB.lock().listeners.remove(&endpoint).is_some(),
+            None => false
+        }
+    }
+}
+
 /// Connection to a `MemoryTransport` currently being opened.
 pub struct DialFuture {
     sender: mpsc::Sender<Channel<Bytes>>,
@@ -76,39 +124,41 @@ impl Transport for MemoryTransport {
     type Dial = DialFuture;
 
     fn listen_on(self, addr: Multiaddr) -> Result<Self::Listener, TransportError<Self::Error>> {
-        let port = if let Ok(port) = parse_memory_addr(&addr) {
-            port
-        } else {
-            return Err(TransportError::MultiaddrNotSupported(addr));
-        };
-
         let mut hub = (&*HUB).lock();
 
-        let port = if let Some(port) = NonZeroU64::new(port) {
-            port
-        } else {
-            loop {
-                let port = match NonZeroU64::new(rand::random()) {
-                    Some(p) => p,
-                    None => continue,
-                };
-                if !hub.contains_key(&port) {
-                    break port;
+        let endpoint = if let Ok(port) = parse_memory_addr(&addr) {
+            if let Some(port) = NonZeroU64::new(port) {
+                Endpoint::Port(port)
+            } else {
+                loop {
+                    let port = match NonZeroU64::new(rand::random()) {
+                        Some(p) => Endpoint::Port(p),
+                        None => continue,
+                    };
+                    if !hub.listeners.contains_key(&port) {
+                        break port;
+                    }
                 }
             }
+        } else if let Some(endpoint) = parse_endpoint(&addr) {
+            endpoint
+        } else {
+            return Err(TransportError::MultiaddrNotSupported(addr));
         };
 
-
+        let registration = hub.next_registration;
         let (tx, rx) = mpsc::channel(2);
-        match hub.entry(port) {
+        match hub.listeners.entry(endpoint.clone()) {
             Entry::Occupied(_) =>
-                return Err(TransportError::Other(MemoryTransportError::Unreachable)),
-            Entry::Vacant(e) => e.insert(tx)
+                return Err(TransportError::Other(MemoryTransportError::AlreadyInUse)),
+            Entry::Vacant(e) => e.insert((registration, tx))
         };
+        hub.next_registration += 1;
 
         let listener = Listener {
-            port,
-            addr: Protocol::Memory(port.get()).into(),
+            addr: endpoint.to_multiaddr(),
+            endpoint,
+            registration,
             receiver: rx,
             tell_listen_addr: true
         };
@@ -117,18 +167,16 @@ impl Transport for MemoryTransport {
     }
 
     fn dial(self, addr: Multiaddr) -> Result<DialFuture, TransportError<Self::Error>> {
-        let port = if let Ok(port) = parse_memory_addr(&addr) {
-            if let Some(port) = NonZeroU64::new(port) {
-                port
-            } else {
-                return Err(TransportError::Other(MemoryTransportError::Unreachable));
-            }
+        let endpoint = if let Some(endpoint) = parse_endpoint(&addr) {
+            endpoint
+        } else if parse_memory_addr(&addr).is_ok() {
+            return Err(TransportError::Other(MemoryTransportError::Unreachable));
         } else {
             return Err(TransportError::MultiaddrNotSupported(addr));
         };
 
         let hub = HUB.lock();
-        if let Some(sender) = hub.get(&port) {
+        if let Some((_, sender)) = hub.listeners.get(&endpoint) {
             let (a_tx, a_rx) = mpsc::channel(4096);
             let (b_tx, b_rx) = mpsc::channel(4096);
             Ok(DialFuture {
@@ -146,9 +194,9 @@ impl Transport for MemoryTransport {
 /// Error that can be produced from the `MemoryTransport`.
 #[derive(Debug, Copy, Clone)]
 pub enum MemoryTransportError {
-    /// There's no listener on the given port.
+    /// There's no listener on the given port or name.
     Unreachable,
-    /// Tries to listen on a port that is already in use.
+    /// Tries to listen on a port or name that is already in use.
     AlreadyInUse,
 }
 
@@ -156,7 +204,7 @@ impl fmt::Display for MemoryTransportError {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match *self {
             MemoryTransportError::Unreachable => write!(f, "No listener on the given port."),
-            MemoryTransportError::AlreadyInUse => write!(f, "Port already occupied."),
+            MemoryTransportError::AlreadyInUse => write!(f, "Port or name already occupied."),
         }
     }
 }
@@ -165,8 +213,10 @@ impl error::Error for MemoryTransportError {}
 
 /// Listener for memory connections.
 pub struct Listener {
-    /// Port we're listening on.
-    port: NonZeroU64,
+    /// Endpoint we're listening on.
+    endpoint: Endpoint,
+    /// Identifier of our registration in the hub.
+    registration: u64,
     /// The address we are listening on.
     addr: Multiaddr,
     /// Receives incoming connections.
@@ -193,7 +243,7 @@ impl Stream for Listener {
         let event = ListenerEvent::Upgrade {
             upgrade: future::ok(channel),
             listen_addr: self.addr.clone(),
-            remote_addr: Protocol::Memory(self.port.get()).into()
+            remote_addr: self.addr.clone()
         };
         Ok(Async::Ready(Some(event)))
     }
@@ -201,11 +251,32 @@ impl Stream for Listener {
 
 impl Drop for Listener {
     fn drop(&mut self) {
-        let val_in = HUB.lock().remove(&self.port);
-        debug_assert!(val_in.is_some());
+        // The registration may have been closed, and the endpoint reused since.
+        let mut hub = HUB.lock();
+        if hub.listeners.get(&self.endpoint).map(|(r, _)| *r) == Some(self.registration) {
+            hub.listeners.remove(&self.endpoint);
+        }
     }
 }
 
+/// If the address is `/memory/n` with `n` non-zero, or `/memory/<name>`, returns the endpoint
+/// it designates.
+fn parse_endpoint(a: &Multiaddr) -> Option<Endpoint> {
+    let mut iter = a.iter();
+
+    let endpoint = match iter.next() {
+        Some(Protocol::Memory(port)) => Endpoint::Port(NonZeroU64::new(port)?),
+        Some(Protocol::MemoryName(name)) => Endpoint::Name(name.into_owned()),
+        _ => return None
+    };
+
+    if iter.next().is_some() {
+        return None;
+    }
+
+    Some(endpoint)
+}
+
 /// If the address is `/memory/n`, returns the value of `n`.
 fn parse_memory_addr(a: &Multiaddr) -> Result<u64, ()> {
     let mut iter = a.iter();
@@ -308,5 +379,34 @@ mod tests {
         assert!(transport.dial("/memory/810172461024613".parse().unwrap()).is_ok());
     }
 
+    #[test]
+    fn named_endpoints() {
+        let transport = MemoryTransport::default();
+        let addr: Multiaddr = "/memory/named-endpoints-alice".parse().unwrap();
+        assert!(transport.dial(addr.clone()).is_err());
+        let listener = transport.listen_on(addr.clone()).unwrap();
+        assert!(MemoryTransport::listeners().contains(&addr));
+        assert!(transport.listen_on(addr.clone()).is_err());
+        assert!(transport.dial(addr.clone()).is_ok());
+        drop(listener);
+        assert!(!MemoryTransport::listeners().contains(&addr));
+        assert!(transport.dial(addr).is_err());
+    }
+
+    #[test]
+    fn closing_listener() {
+        let transport = MemoryTransport::default();
+        let addr: Multiaddr = "/memory/closing-listener".parse().unwrap();
+        let listener = transport.listen_on(addr.clone()).unwrap();
+        assert!(MemoryTransport::close(&addr));
+        assert!(!MemoryTransport::close(&addr));
+        assert!(transport.dial(addr.clone()).is_err());
+
+        // The endpoint can be reused, and dropping the closed listener doesn't affect the new one.
+        let _listener = transport.listen_on(addr.clone()).unwrap();
+        drop(listener);
+        assert!(transport.dial(addr).is_ok());
+    }
+
     // TODO: test that is actually works
 }
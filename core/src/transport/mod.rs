@@ -39,6 +39,7 @@ pub mod dummy;
 pub mod map;
 pub mod map_err;
 pub mod memory;
+pub mod security;
 pub mod timeout;
 pub mod upgrade;
 
@@ -47,6 +48,7 @@ mod optional;
 pub use self::choice::OrTransport;
 pub use self::memory::MemoryTransport;
 pub use self::optional::OptionalTransport;
+pub use self::security::SecurityUpgrade;
 pub use self::upgrade::Upgrade;
 
 /// A transport provides connection-oriented communication between two peers
@@ -184,6 +186,21 @@ pub trait Transport {
         Upgrade::new(self, upgrade)
     }
 
+    /// Wraps this transport inside a [`SecurityUpgrade`].
+    ///
+    /// Behaves like [`Transport::with_upgrade`] until hints are registered with
+    /// [`SecurityUpgrade::with_hint`], after which the protocol of the upgrade is selected by
+    /// the last component of the addresses instead of being negotiated.
+    fn with_security_upgrade<U, O, E>(self, upgrade: U) -> SecurityUpgrade<Self, U>
+    where
+        Self: Sized,
+        Self::Output: AsyncRead + AsyncWrite,
+        U: InboundUpgrade<Self::Output, Output = O, Error = E>,
+        U: OutboundUpgrade<Self::Output, Output = O, Error = E>
+    {
+        SecurityUpgrade::new(self, upgrade)
+    }
+
     /// Applies a function producing an asynchronous result to every connection
     /// created by this transport.
     ///
@@ -0,0 +1,364 @@
+// Copyright 2019 Parity Technologies (UK) Ltd.
+//
+// Permission is hereby granted, free of charge, to any person obtaining a
+// copy of this software and associated documentation files (the "Software"),
+// to deal in the Software without restriction, including without limitation
+// the rights to use, copy, modify, merge, publish, distribute, sublicense,
+// and/or sell copies of the Software, and to permit persons to whom the
+// Software is furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS
+// OR IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING
+// FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
+// DEALINGS IN THE SOFTWARE.
+
+//! Security upgrades whose protocol can be selected by the addresses.
+//!
+//! A listener can advertise the security protocol it expects with a component of its
+//! addresses, e.g. `/ip4/1.2.3.4/tcp/4001/noise`. The dialers of such an address apply the
+//! protocol directly instead of negotiating it with multistream-select, which saves a round
+//! trip during the connection setup.
+
+use crate::{
+    transport::{Transport, TransportError, ListenerEvent, upgrade::TransportUpgradeError},
+    upgrade::{
+        OutboundUpgrade,
+        InboundUpgrade,
+        ProtocolName,
+        UpgradeInfo,
+        apply_inbound,
+        apply_inbound_with,
+        apply_outbound,
+        apply_outbound_with,
+        OutboundUpgradeApply,
+        InboundUpgradeApply
+    }
+};
+use futures::{future::Either, prelude::*, try_ready};
+use multiaddr::{Multiaddr, Protocol};
+use tokio_io::{AsyncRead, AsyncWrite};
+
+/// A `Transport` applying a security upgrade, whose protocol is selected by the last component
+/// of the addresses when it's one of the configured hints, and negotiated otherwise.
+///
+/// Each hint associates a multiaddress protocol, such as `/noise` or `/tls`, with the name of a
+/// protocol of the upgrade. The hint is removed from the addresses passed to the inner
+/// transport, ignoring a trailing `/p2p` component:
+///
+/// - Dialing `/ip4/1.2.3.4/tcp/4001/noise` dials `/ip4/1.2.3.4/tcp/4001`, and applies the
+///   protocol of the `/noise` hint without negotiation.
+/// - Listening on `/ip4/0.0.0.0/tcp/4001/noise` listens on `/ip4/0.0.0.0/tcp/4001`, reports
+///   the listened addresses with the `/noise` component, and applies the protocol of the hint
+///   without negotiation on all the inbound connections.
+///
+/// Without a hint, the protocol is negotiated as with `Transport::with_upgrade`. Dialers must
+/// therefore only use the hinted addresses advertised by the listeners.
+#[derive(Debug, Clone)]
+pub struct SecurityUpgrade<T, U> {
+    inner: T,
+    upgrade: U,
+    hints: Vec<(Protocol<'static>, Vec<u8>)>,
+}
+
+impl<T, U> SecurityUpgrade<T, U>
+where
+    U: UpgradeInfo
+{
+    /// Wraps around a `Transport` to apply `upgrade` to the connections, without any hint.
+    pub fn new(inner: T, upgrade: U) -> Self {
+        SecurityUpgrade { inner, upgrade, hints: Vec::new() }
+    }
+
+    /// Applies the protocol of the upgrade named `name` to the addresses ending with
+    /// `protocol`.
+    ///
+    /// # Panic
+    ///
+    /// Panics if the upgrade doesn't support a protocol named `name`.
+    pub fn with_hint(mut self, protocol: Protocol<'static>, name: impl AsRef<[u8]>) -> Self {
+        let name = name.as_ref().to_vec();
+        assert!(self.upgrade.protocol_info().into_iter().any(|info| info.protocol_name() == &name[..]),
+            "the upgrade doesn't support the protocol of the hint");
+        self.hints.retain(|(p, _)| *p != protocol);
+        self.hints.push((protocol, name));
+        self
+    }
+
+    /// Splits the hint off `addr`, if any. Returns the address to pass to the inner transport,
+    /// along with the hint and the name of its protocol.
+    fn split_hint(&self, addr: &Multiaddr) -> Option<(Multiaddr, Protocol<'static>, Vec<u8>)> {
+        let mut components = addr.iter().map(Protocol::acquire).collect::<Vec<_>>();
+        let p2p = match components.last() {
+            Some(Protocol::P2p(_)) => components.pop(),
+            _ => None,
+        };
+        let last = components.pop()?;
+        let (hint, name) = self.hints.iter().find(|(p, _)| *p == last)?.clone();
+        let mut inner = components.into_iter().collect::<Multiaddr>();
+        if let Some(p2p) = p2p {
+            inner.push(p2p);
+        }
+        Some((inner, hint, name))
+    }
+}
+
+impl<D, U, O, TUpgrErr> Transport for SecurityUpgrade<D, U>
+where
+    D: Transport,
+    D::Output: AsyncRead + AsyncWrite,
+    D::Error: 'static,
+    U: InboundUpgrade<D::Output, Output = O, Error = TUpgrErr>,
+    U: OutboundUpgrade<D::Output, Output = O, Error = TUpgrErr> + Clone,
+    TUpgrErr: std::error::Error + Send + Sync + 'static
+{
+    type Output = O;
+    type Error = TransportUpgradeError<D::Error, TUpgrErr>;
+    type Listener = SecurityListenerStream<D::Listener, U>;
+    type ListenerUpgrade = SecurityListenerUpgradeFuture<D::ListenerUpgrade, U>;
+    type Dial = SecurityDialFuture<D::Dial, U>;
+
+    fn dial(self, addr: Multiaddr) -> Result<Self::Dial, TransportError<Self::Error>> {
+        let (addr, protocol) = match self.split_hint(&addr) {
+            Some((inner_addr, _, name)) => (inner_addr, Some(name)),
+            None => (addr, None),
+        };
+        let outbound = self.inner.dial(addr)
+            .map_err(|err| err.map(TransportUpgradeError::Transport))?;
+        Ok(SecurityDialFuture {
+            future: outbound,
+            upgrade: Either::A(Some((self.upgrade, protocol)))
+        })
+    }
+
+    fn listen_on(self, addr: Multiaddr) -> Result<Self::Listener, TransportError<Self::Error>> {
+        let (addr, hint) = match self.split_hint(&addr) {
+            Some((inner_addr, hint, name)) => (inner_addr, Some((hint, name))),
+            None => (addr, None),
+        };
+        let inbound = self.inner.listen_on(addr)
+            .map_err(|err| err.map(TransportUpgradeError::Transport))?;
+        Ok(SecurityListenerStream { stream: inbound, upgrade: self.upgrade, hint })
+    }
+}
+
+/// Future of a connection dialed by a `SecurityUpgrade` transport.
+pub struct SecurityDialFuture<T, U>
+where
+    T: Future,
+    T::Item: AsyncRead + AsyncWrite,
+    U: OutboundUpgrade<T::Item>
+{
+    future: T,
+    upgrade: Either<Option<(U, Option<Vec<u8>>)>, OutboundUpgradeApply<T::Item, U>>
+}
+
+impl<T, U> Future for SecurityDialFuture<T, U>
+where
+    T: Future,
+    T::Item: AsyncRead + AsyncWrite,
+    U: OutboundUpgrade<T::Item>,
+    U::Error: std::error::Error + Send + Sync + 'static
+{
+    type Item = U::Output;
+    type Error = TransportUpgradeError<T::Error, U::Error>;
+
+    fn poll(&mut self) -> Poll<Self::Item, Self::Error> {
+        loop {
+            let next = match self.upgrade {
+                Either::A(ref mut up) => {
+                    let x = try_ready!(self.future.poll().map_err(TransportUpgradeError::Transport));
+                    let (u, protocol) = up.take().expect("SecurityDialFuture is constructed with Either::A(Some).");
+                    match protocol {
+                        Some(protocol) => Either::B(apply_outbound_with(x, u, &protocol)),
+                        None => Either::B(apply_outbound(x, u)),
+                    }
+                }
+                Either::B(ref mut up) => return up.poll().map_err(TransportUpgradeError::Upgrade)
+            };
+            self.upgrade = next
+        }
+    }
+}
+
+/// Listener of a `SecurityUpgrade` transport.
+pub struct SecurityListenerStream<T, U> {
+    stream: T,
+    upgrade: U,
+    /// The hint of the listened address, and the name of its protocol.
+    hint: Option<(Protocol<'static>, Vec<u8>)>,
+}
+
+impl<T, U, F> Stream for SecurityListenerStream<T, U>
+where
+    T: Stream<Item = ListenerEvent<F>>,
+    F: Future,
+    F::Item: AsyncRead + AsyncWrite,
+    U: InboundUpgrade<F::Item> + Clone
+{
+    type Item = ListenerEvent<SecurityListenerUpgradeFuture<F, U>>;
+    type Error = TransportUpgradeError<T::Error, U::Error>;
+
+    fn poll(&mut self) -> Poll<Option<Self::Item>, Self::Error> {
+        let event = match try_ready!(self.stream.poll().map_err(TransportUpgradeError::Transport)) {
+            Some(event) => event,
+            None => return Ok(Async::Ready(None))
+        };
+        let with_hint = |addr: Multiaddr| match &self.hint {
+            Some((hint, _)) => addr.with(hint.clone()),
+            None => addr,
+        };
+        let event = match event {
+            ListenerEvent::NewAddress(addr) => ListenerEvent::NewAddress(with_hint(addr)),
+            ListenerEvent::AddressExpired(addr) => ListenerEvent::AddressExpired(with_hint(addr)),
+            ListenerEvent::Upgrade { upgrade, listen_addr, remote_addr } => {
+                ListenerEvent::Upgrade {
+                    upgrade: SecurityListenerUpgradeFuture {
+                        future: upgrade,
+                        upgrade: Either::A(Some((
+                            self.upgrade.clone(),
+                            self.hint.as_ref().map(|(_, name)| name.clone())
+                        )))
+                    },
+                    listen_addr: with_hint(listen_addr),
+                    remote_addr
+                }
+            }
+        };
+        Ok(Async::Ready(Some(event)))
+    }
+}
+
+/// Future of a connection accepted by a `SecurityUpgrade` transport.
+pub struct SecurityListenerUpgradeFuture<T, U>
+where
+    T: Future,
+    T::Item: AsyncRead + AsyncWrite,
+    U: InboundUpgrade<T::Item>
+{
+    future: T,
+    upgrade: Either<Option<(U, Option<Vec<u8>>)>, InboundUpgradeApply<T::Item, U>>
+}
+
+impl<T, U> Future for SecurityListenerUpgradeFuture<T, U>
+where
+    T: Future,
+    T::Item: AsyncRead + AsyncWrite,
+    U: InboundUpgrade<T::Item>,
+    U::Error: std::error::Error + Send + Sync + 'static
+{
+    type Item = U::Output;
+    type Error = TransportUpgradeError<T::Error, U::Error>;
+
+    fn poll(&mut self) -> Poll<Self::Item, Self::Error> {
+        loop {
+            let next = match self.upgrade {
+                Either::A(ref mut up) => {
+                    let x = try_ready!(self.future.poll().map_err(TransportUpgradeError::Transport));
+                    let (u, protocol) = up.take().expect("SecurityListenerUpgradeFuture is constructed with Either::A(Some).");
+                    match protocol {
+                        Some(protocol) => Either::B(apply_inbound_with(x, u, &protocol)),
+                        None => Either::B(apply_inbound(x, u)),
+                    }
+                }
+                Either::B(ref mut up) => return up.poll().map_err(TransportUpgradeError::Upgrade)
+            };
+            self.upgrade = next
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{transport::MemoryTransport, upgrade::Negotiated};
+    use futures::future::{self, FutureResult};
+    use std::iter;
+    use void::Void;
+
+    /// Upgrade producing the name of the protocol applied, without any I/O.
+    #[derive(Debug, Clone)]
+    struct Names;
+
+    impl UpgradeInfo for Names {
+        type Info = &'static [u8];
+        type InfoIter = iter::Chain<iter::Once<&'static [u8]>, iter::Once<&'static [u8]>>;
+
+        fn protocol_info(&self) -> Self::InfoIter {
+            iter::once(&b"/noise"[..]).chain(iter::once(&b"/secio/1.0.0"[..]))
+        }
+    }
+
+    impl<C> InboundUpgrade<C> for Names {
+        type Output = &'static [u8];
+        type Error = Void;
+        type Future = FutureResult<Self::Output, Self::Error>;
+
+        fn upgrade_inbound(self, _: Negotiated<C>, info: Self::Info) -> Self::Future {
+            future::ok(info)
+        }
+    }
+
+    impl<C> OutboundUpgrade<C> for Names {
+        type Output = &'static [u8];
+        type Error = Void;
+        type Future = FutureResult<Self::Output, Self::Error>;
+
+        fn upgrade_outbound(self, _: Negotiated<C>, info: Self::Info) -> Self::Future {
+            future::ok(info)
+        }
+    }
+
+    #[test]
+    fn hint_split_off_address() {
+        let transport = SecurityUpgrade::new(MemoryTransport::default(), Names)
+            .with_hint(Protocol::Noise, "/noise");
+        let (inner, hint, name) = transport.split_hint(&"/memory/1/noise".parse().unwrap()).unwrap();
+        assert_eq!(inner, "/memory/1".parse().unwrap());
+        assert_eq!(hint, Protocol::Noise);
+        assert_eq!(name, b"/noise".to_vec());
+
+        let addr = "/memory/1/noise/p2p/QmcgpsyWgH8Y8ajJz1Cu72KnS5uo2Aa2LpzU7kinSupNKC".parse().unwrap();
+        let (inner, _, _) = transport.split_hint(&addr).unwrap();
+        assert_eq!(inner, "/memory/1/p2p/QmcgpsyWgH8Y8ajJz1Cu72KnS5uo2Aa2LpzU7kinSupNKC".parse().unwrap());
+
+        assert!(transport.split_hint(&"/memory/1".parse().unwrap()).is_none());
+        assert!(transport.split_hint(&"/memory/1/tls".parse().unwrap()).is_none());
+    }
+
+    #[test]
+    #[should_panic]
+    fn hint_of_unsupported_protocol() {
+        SecurityUpgrade::new(MemoryTransport::default(), Names).with_hint(Protocol::Tls, "/tls/1.0.0");
+    }
+
+    #[test]
+    fn hinted_protocol_applied_without_negotiation() {
+        let transport = SecurityUpgrade::new(MemoryTransport::default(), Names)
+            .with_hint(Protocol::Noise, "/noise");
+        let addr: Multiaddr = "/memory/security-hint/noise".parse().unwrap();
+
+        let mut listener = transport.clone().listen_on(addr.clone()).unwrap().wait();
+        match listener.next() {
+            Some(Ok(ListenerEvent::NewAddress(a))) => assert_eq!(a, addr),
+            _ => panic!("expected a new address"),
+        }
+
+        // Neither side sends anything, so the upgrades would never complete if the protocol
+        // were negotiated.
+        let dialed = transport.dial(addr.clone()).unwrap().wait().unwrap();
+        assert_eq!(dialed, &b"/noise"[..]);
+        match listener.next() {
+            Some(Ok(ListenerEvent::Upgrade { upgrade, listen_addr, .. })) => {
+                assert_eq!(listen_addr, addr);
+                assert_eq!(upgrade.wait().unwrap(), &b"/noise"[..]);
+            }
+            _ => panic!("expected an inbound connection"),
+        }
+    }
+}
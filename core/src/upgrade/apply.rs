@@ -22,7 +22,14 @@ use crate::ConnectedPoint;
 use crate::upgrade::{UpgradeInfo, InboundUpgrade, OutboundUpgrade, UpgradeError, ProtocolName};
 use futures::{future::Either, prelude::*};
 use log::debug;
-use multistream_select::{self, DialerSelectFuture, ListenerSelectFuture};
+use multistream_select::{
+    self,
+    DialerSelectFuture,
+    ListenerSelectFuture,
+    Negotiated,
+    NegotiationFailure,
+    ProtocolChoiceError
+};
 use std::mem;
 use tokio_io::{AsyncRead, AsyncWrite};
 
@@ -66,6 +73,59 @@ where
     }
 }
 
+/// Performs an upgrade on an inbound connection or substream with a protocol that both sides
+/// agreed upon beforehand, for example through the address that was dialed, without
+/// negotiating it.
+///
+/// The upgrade fails if `up` doesn't support the protocol.
+pub fn apply_inbound_with<C, U>(conn: C, up: U, protocol: &[u8]) -> InboundUpgradeApply<C, U>
+where
+    C: AsyncRead + AsyncWrite,
+    U: InboundUpgrade<C>,
+{
+    let inner = match find_protocol(&up, protocol) {
+        Ok(info) => InboundUpgradeApplyState::Upgrade {
+            future: up.upgrade_inbound(Negotiated::assumed(conn), info)
+        },
+        Err(err) => InboundUpgradeApplyState::Failed(err),
+    };
+    InboundUpgradeApply { inner }
+}
+
+/// Performs an upgrade on an outbound connection or substream with a protocol that both sides
+/// agreed upon beforehand, without negotiating it.
+///
+/// The upgrade fails if `up` doesn't support the protocol.
+pub fn apply_outbound_with<C, U>(conn: C, up: U, protocol: &[u8]) -> OutboundUpgradeApply<C, U>
+where
+    C: AsyncRead + AsyncWrite,
+    U: OutboundUpgrade<C>
+{
+    let inner = match find_protocol(&up, protocol) {
+        Ok(info) => OutboundUpgradeApplyState::Upgrade {
+            future: up.upgrade_outbound(Negotiated::assumed(conn), info)
+        },
+        Err(err) => OutboundUpgradeApplyState::Failed(err),
+    };
+    OutboundUpgradeApply { inner }
+}
+
+/// Returns the protocol of `up` named `protocol`.
+fn find_protocol<U>(up: &U, protocol: &[u8]) -> Result<U::Info, ProtocolChoiceError>
+where
+    U: UpgradeInfo
+{
+    let mut offered = Vec::new();
+    for info in up.protocol_info() {
+        if info.protocol_name() == protocol {
+            return Ok(info)
+        }
+        offered.push(String::from_utf8_lossy(info.protocol_name()).into_owned());
+    }
+    let remote = Some(vec![String::from_utf8_lossy(protocol).into_owned()]);
+    Err(ProtocolChoiceError::NoProtocolFound(NegotiationFailure { offered, remote }))
+}
+
 /// Future returned by `apply_inbound`. Drives the upgrade process.
 pub struct InboundUpgradeApply<C, U>
 where
@@ -86,6 +146,8 @@ where
     Upgrade {
         future: U::Future
     },
+    /// The protocol assumed without negotiation isn't supported.
+    Failed(ProtocolChoiceError),
     Undefined
 }
 
@@ -128,6 +190,7 @@ where
                         }
                     }
                 }
+                InboundUpgradeApplyState::Failed(err) => return Err(UpgradeError::Select(err)),
                 InboundUpgradeApplyState::Undefined =>
                     panic!("InboundUpgradeApplyState::poll called after completion")
             }
@@ -156,6 +219,8 @@ where
     Upgrade {
         future: U::Future
     },
+    /// The protocol assumed without negotiation isn't supported.
+    Failed(ProtocolChoiceError),
     Undefined
 }
 
@@ -198,6 +263,7 @@ where
                         }
                     }
                 }
+                OutboundUpgradeApplyState::Failed(err) => return Err(UpgradeError::Select(err)),
                 OutboundUpgradeApplyState::Undefined =>
                     panic!("OutboundUpgradeApplyState::poll called after completion")
             }
@@ -18,8 +18,8 @@
 // FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
 // DEALINGS IN THE SOFTWARE.
 
-use multistream_select::ProtocolChoiceError;
-use std::fmt;
+use multistream_select::{NegotiationFailure, ProtocolChoiceError};
+use std::{error, fmt, io};
 
 /// Error that can happen when upgrading a connection or substream to use a protocol.
 #[derive(Debug)]
@@ -61,11 +61,11 @@ where
     }
 }
 
-impl<E> std::error::Error for UpgradeError<E>
+impl<E> error::Error for UpgradeError<E>
 where
-    E: std::error::Error + 'static
+    E: error::Error + 'static
 {
-    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
         match self {
             UpgradeError::Select(e) => Some(e),
             UpgradeError::Apply(e) => Some(e),
@@ -79,3 +79,44 @@ impl<E> From<ProtocolChoiceError> for UpgradeError<E> {
     }
 }
 
+
+/// Looks for a protocol negotiation that failed because no protocol was supported by both sides
+/// in an error and the chain of its sources.
+///
+/// This is typically used on the errors produced when dialing or upgrading a connection, to
+/// report which protocols were offered and which ones the remote supports.
+pub fn negotiation_failure<'a>(err: &'a (dyn error::Error + 'static)) -> Option<&'a NegotiationFailure> {
+    let mut current = Some(err);
+    while let Some(err) = current {
+        if let Some(ProtocolChoiceError::NoProtocolFound(failure)) = err.downcast_ref() {
+            return Some(failure)
+        }
+        // The source of an `io::Error` is the source of the error it wraps, not the wrapped
+        // error itself.
+        current = match err.downcast_ref::<io::Error>().and_then(|e| e.get_ref()) {
+            Some(inner) => Some(inner as &(dyn error::Error + 'static)),
+            None => err.source(),
+        };
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn finds_wrapped_negotiation_failure() {
+        let failure = NegotiationFailure {
+            offered: vec!["/noise".to_owned()],
+            remote: Some(vec!["/secio/1.0.0".to_owned()]),
+        };
+        let err: UpgradeError<io::Error> =
+            UpgradeError::Select(ProtocolChoiceError::NoProtocolFound(failure.clone()));
+        let err = io::Error::new(io::ErrorKind::Other, err);
+        assert_eq!(negotiation_failure(&err), Some(&failure));
+
+        let err: UpgradeError<io::Error> = UpgradeError::Select(ProtocolChoiceError::UnexpectedMessage);
+        assert_eq!(negotiation_failure(&err), None);
+    }
+}
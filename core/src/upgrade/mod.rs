@@ -51,6 +51,22 @@
 //! there is no constraint on the traits that it should implement, however it is expected that it
 //! can be used by the user to control the behaviour of the protocol.
 //!
+//! # Pipelining upgrades
+//!
+//! Upgrades are often applied on top of each other, for example a multiplexing protocol on top of
+//! an encryption protocol. The negotiation of the next upgrade starts as soon as the handshake
+//! `Future` of the previous one resolves. Upgrades whose output is itself a stream should
+//! therefore resolve as soon as that stream may be written to, even if they have a last message
+//! to send, and leave that message buffered rather than flushing it. It is then flushed with the
+//! negotiation of the next upgrade, and both are sent in the same flight.
+//!
+//! # Buffering reads
+//!
+//! The `Negotiated` socket passed to the handshake reads directly from the substream. Protocols
+//! that read their messages a few bytes at a time, for example to decode variable-length
+//! integers or headers, should wrap it with `read_ahead` so that these don't each result in a
+//! small read against the muxer.
+//!
 //! > **Note**: You can use the `apply_inbound` or `apply_outbound` methods to try upgrade a
 //!             connection or substream. However if you use the recommended `Swarm` or
 //!             `ProtocolsHandler` APIs, the upgrade is automatically handled for you and you don't
@@ -63,19 +79,21 @@ mod either;
 mod error;
 mod map;
 mod optional;
+mod read_ahead;
 mod select;
 mod transfer;
 
 use futures::future::Future;
 
-pub use multistream_select::Negotiated;
+pub use multistream_select::{Negotiated, NegotiationFailure};
 pub use self::{
-    apply::{apply, apply_inbound, apply_outbound, InboundUpgradeApply, OutboundUpgradeApply},
+    apply::{apply, apply_inbound, apply_inbound_with, apply_outbound, apply_outbound_with, InboundUpgradeApply, OutboundUpgradeApply},
     denied::DeniedUpgrade,
     either::EitherUpgrade,
-    error::UpgradeError,
+    error::{negotiation_failure, UpgradeError},
     map::{MapInboundUpgrade, MapOutboundUpgrade, MapInboundUpgradeErr, MapOutboundUpgradeErr},
     optional::OptionalUpgrade,
+    read_ahead::{read_ahead, ReadAhead, DEFAULT_READ_AHEAD},
     select::SelectUpgrade,
     transfer::{write_one, WriteOne, read_one, ReadOne, read_one_then, ReadOneThen, ReadOneError, request_response, RequestResponse, read_respond, ReadRespond},
 };
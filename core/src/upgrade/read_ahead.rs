@@ -0,0 +1,190 @@
+// Copyright 2019 Parity Technologies (UK) Ltd.
+//
+// Permission is hereby granted, free of charge, to any person obtaining a
+// copy of this software and associated documentation files (the "Software"),
+// to deal in the Software without restriction, including without limitation
+// the rights to use, copy, modify, merge, publish, distribute, sublicense,
+// and/or sell copies of the Software, and to permit persons to whom the
+// Software is furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS
+// OR IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING
+// FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
+// DEALINGS IN THE SOFTWARE.
+//! Read-ahead buffering for negotiated substreams.
+
+use futures::Poll;
+use std::io::{self, BufRead, Read, Write};
+use tokio_io::{AsyncRead, AsyncWrite};
+
+/// Default capacity of the buffer of [`ReadAhead`].
+pub const DEFAULT_READ_AHEAD: usize = 8 * 1024;
+
+/// Wraps around a socket and reads from it ahead of what is requested.
+///
+/// Protocols that parse their messages a few bytes at a time, for example to decode a
+/// variable-length integer or a header, would otherwise issue one small read against the
+/// underlying substream, and therefore the muxer, for each of them. Reads that are at least as
+/// large as the buffer bypass it. Writes are passed through unchanged.
+///
+/// Also implements `BufRead`.
+#[derive(Debug)]
+pub struct ReadAhead<TSocket> {
+    inner: TSocket,
+    /// Data read from `inner`. Only `buffer[pos .. filled]` hasn't been consumed yet.
+    buffer: Box<[u8]>,
+    pos: usize,
+    filled: usize,
+}
+
+/// Wraps the socket in a [`ReadAhead`] buffering up to `capacity` bytes.
+///
+/// Typically called on the `Negotiated` socket passed to `upgrade_inbound` or `upgrade_outbound`.
+pub fn read_ahead<TSocket>(socket: TSocket, capacity: usize) -> ReadAhead<TSocket>
+where
+    TSocket: AsyncRead
+{
+    ReadAhead {
+        inner: socket,
+        buffer: vec![0; capacity].into_boxed_slice(),
+        pos: 0,
+        filled: 0,
+    }
+}
+
+impl<TSocket> ReadAhead<TSocket> {
+    /// Returns the data read ahead from the socket and not consumed yet.
+    pub fn buffered(&self) -> &[u8] {
+        &self.buffer[self.pos .. self.filled]
+    }
+
+    /// Returns a reference to the underlying socket.
+    ///
+    /// Reading from it directly skips the data that has been buffered.
+    pub fn get_ref(&self) -> &TSocket {
+        &self.inner
+    }
+
+    /// Returns a mutable reference to the underlying socket.
+    ///
+    /// Reading from it directly skips the data that has been buffered.
+    pub fn get_mut(&mut self) -> &mut TSocket {
+        &mut self.inner
+    }
+
+    /// Destroys the `ReadAhead` and returns the underlying socket, alongside the data read ahead
+    /// from it and not consumed yet.
+    pub fn into_parts(self) -> (TSocket, Vec<u8>) {
+        let buffered = self.buffered().to_vec();
+        (self.inner, buffered)
+    }
+}
+
+impl<TSocket> Read for ReadAhead<TSocket>
+where
+    TSocket: Read
+{
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if self.pos == self.filled && buf.len() >= self.buffer.len() {
+            return self.inner.read(buf)
+        }
+        let num_read = {
+            let available = self.fill_buf()?;
+            let num_read = std::cmp::min(available.len(), buf.len());
+            buf[.. num_read].copy_from_slice(&available[.. num_read]);
+            num_read
+        };
+        self.consume(num_read);
+        Ok(num_read)
+    }
+}
+
+impl<TSocket> BufRead for ReadAhead<TSocket>
+where
+    TSocket: Read
+{
+    fn fill_buf(&mut self) -> io::Result<&[u8]> {
+        if self.pos == self.filled {
+            self.filled = self.inner.read(&mut self.buffer)?;
+            self.pos = 0;
+        }
+        Ok(&self.buffer[self.pos .. self.filled])
+    }
+
+    fn consume(&mut self, amount: usize) {
+        self.pos = std::cmp::min(self.pos + amount, self.filled);
+    }
+}
+
+impl<TSocket> AsyncRead for ReadAhead<TSocket>
+where
+    TSocket: AsyncRead
+{
+}
+
+impl<TSocket> Write for ReadAhead<TSocket>
+where
+    TSocket: Write
+{
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.inner.write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+impl<TSocket> AsyncWrite for ReadAhead<TSocket>
+where
+    TSocket: AsyncWrite
+{
+    fn shutdown(&mut self) -> Poll<(), io::Error> {
+        self.inner.shutdown()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+
+    struct CountingReader {
+        inner: Cursor<Vec<u8>>,
+        reads: usize,
+    }
+
+    impl Read for CountingReader {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            self.reads += 1;
+            self.inner.read(buf)
+        }
+    }
+
+    impl AsyncRead for CountingReader {}
+
+    #[test]
+    fn small_reads_are_buffered() {
+        let data = (0 .. 100).collect::<Vec<u8>>();
+        let socket = CountingReader { inner: Cursor::new(data.clone()), reads: 0 };
+        let mut socket = read_ahead(socket, 64);
+
+        let mut out = Vec::new();
+        let mut byte = [0];
+        for _ in 0 .. 10 {
+            socket.read_exact(&mut byte).unwrap();
+            out.push(byte[0]);
+        }
+        assert_eq!(socket.get_ref().reads, 1);
+        assert_eq!(socket.buffered().len(), 54);
+
+        socket.read_to_end(&mut out).unwrap();
+        assert_eq!(out, data);
+    }
+}
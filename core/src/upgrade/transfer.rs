@@ -119,8 +119,8 @@ where
 /// Reads a message from the given socket. Only one message is processed and the socket is dropped,
 /// because we assume that the socket will not send anything more.
 ///
-/// The `max_size` parameter is the maximum size in bytes of the message that we accept. This is
-/// necessary in order to avoid DoS attacks where the remote sends us a message of several
+/// The `max_size` parameter is the maximum size in bytes of the message that we accept, inclusive.
+/// This is necessary in order to avoid DoS attacks where the remote sends us a message of several
 /// gigabytes.
 ///
 /// > **Note**: Assumes that a variable-length prefix indicates the length of the message. This is
@@ -204,10 +204,27 @@ where
 
                             let len_buf_with_data =
                                 &len_buf.get_ref()[..len_buf.position() as usize];
+                            match unsigned_varint::decode::usize(len_buf_with_data) {
+                                Ok(_) => {},
+                                Err(unsigned_varint::decode::Error::Insufficient) => {
+                                    // Every byte read so far has the continuation bit set, hence
+                                    // the length needs at least one more byte. Reject it as soon
+                                    // as this makes it larger than `max_size`.
+                                    let min_len = min_len_of_incomplete_prefix(len_buf_with_data);
+                                    if min_len.map_or(true, |min_len| min_len > max_size) {
+                                        return Err(ReadOneError::TooLarge {
+                                            requested: min_len.unwrap_or(usize::max_value()),
+                                            max: max_size,
+                                        });
+                                    }
+                                },
+                                Err(_) => return Err(ReadOneError::InvalidLengthPrefix),
+                            }
+
                             if let Ok((len, data_start)) =
                                 unsigned_varint::decode::usize(len_buf_with_data)
                             {
-                                if len >= max_size {
+                                if len > max_size {
                                     return Err(ReadOneError::TooLarge {
                                         requested: len,
                                         max: max_size,
@@ -257,6 +274,23 @@ where
     }
 }
 
+/// Returns the smallest length that a variable-length prefix starting with `prefix` can encode,
+/// knowing that all the bytes of `prefix` have their continuation bit set. Returns `None` if that
+/// length overflows a `usize`.
+fn min_len_of_incomplete_prefix(prefix: &[u8]) -> Option<usize> {
+    // Since the encoding is minimal, the last byte of the prefix isn't zero and the length is at
+    // least `1 << (7 * prefix.len())`.
+    let shift = 7 * prefix.len();
+    if shift >= mem::size_of::<usize>() * 8 {
+        return None
+    }
+    let mut min_len = 1 << shift;
+    for (n, byte) in prefix.iter().enumerate() {
+        min_len |= usize::from(byte & 0x7f) << (7 * n);
+    }
+    Some(min_len)
+}
+
 /// Error while reading one message.
 #[derive(Debug)]
 pub enum ReadOneError {
@@ -264,11 +298,14 @@ pub enum ReadOneError {
     Io(std::io::Error),
     /// Requested data is over the maximum allowed size.
     TooLarge {
-        /// Size requested by the remote.
+        /// Size requested by the remote. If the message has been rejected before its length was
+        /// fully received, this is the smallest size that the remote may have requested.
         requested: usize,
         /// Maximum allowed.
         max: usize,
     },
+    /// The variable-length prefix indicating the size of the message is invalid.
+    InvalidLengthPrefix,
 }
 
 impl From<std::io::Error> for ReadOneError {
@@ -278,11 +315,22 @@ impl From<std::io::Error> for ReadOneError {
     }
 }
 
+impl From<ReadOneError> for std::io::Error {
+    fn from(err: ReadOneError) -> std::io::Error {
+        match err {
+            ReadOneError::Io(err) => err,
+            err @ ReadOneError::TooLarge { .. } | err @ ReadOneError::InvalidLengthPrefix =>
+                std::io::Error::new(std::io::ErrorKind::InvalidData, err),
+        }
+    }
+}
+
 impl fmt::Display for ReadOneError {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match *self {
             ReadOneError::Io(ref err) => write!(f, "{}", err),
             ReadOneError::TooLarge { .. } => write!(f, "Received data size over maximum"),
+            ReadOneError::InvalidLengthPrefix => write!(f, "Received invalid data size prefix"),
         }
     }
 }
@@ -292,6 +340,7 @@ impl error::Error for ReadOneError {
         match *self {
             ReadOneError::Io(ref err) => Some(err),
             ReadOneError::TooLarge { .. } => None,
+            ReadOneError::InvalidLengthPrefix => None,
         }
     }
 }
@@ -547,6 +596,22 @@ mod tests {
         }
     }
 
+    #[test]
+    fn read_checks_length_inclusive() {
+        let mut len_buf = unsigned_varint::encode::u64_buffer();
+        let len_buf = unsigned_varint::encode::u64(100, &mut len_buf);
+
+        let mut in_buffer = len_buf.to_vec();
+        in_buffer.extend((0..100).map(|_| 0));
+
+        let future = read_one_then(Cursor::new(in_buffer), 100, (), move |out, ()| -> Result<_, ReadOneError> {
+            assert_eq!(out.len(), 100);
+            Ok(())
+        });
+
+        Runtime::new().unwrap().block_on(future).unwrap();
+    }
+
     #[test]
     fn read_one_accepts_empty() {
         let future = read_one_then(Cursor::new([]), 10_000, (), move |out, ()| -> Result<_, ReadOneError> {
@@ -568,4 +633,29 @@ mod tests {
             _ => panic!()
         }
     }
+
+    #[test]
+    fn read_rejects_length_before_fully_read() {
+        // The length isn't complete, but it is already known to be at least 1 << 14.
+        let future = read_one_then(Cursor::new([0x80, 0x80]), 100, (), move |_, ()| -> Result<(), ReadOneError> {
+            unreachable!()
+        });
+
+        match Runtime::new().unwrap().block_on(future) {
+            Err(ReadOneError::TooLarge { requested, max: 100 }) => assert_eq!(requested, 1 << 14),
+            _ => panic!()
+        }
+    }
+
+    #[test]
+    fn read_rejects_invalid_length() {
+        let future = read_one_then(Cursor::new([0xff; 11]), usize::max_value(), (), move |_, ()| -> Result<(), ReadOneError> {
+            unreachable!()
+        });
+
+        match Runtime::new().unwrap().block_on(future) {
+            Err(ReadOneError::InvalidLengthPrefix) => (),
+            _ => panic!()
+        }
+    }
 }
@@ -96,7 +96,7 @@ fn main() {
     tokio::run(futures::future::poll_fn(move || {
         loop {
             match swarm.poll().expect("Error while polling swarm") {
-                Async::Ready(Some(KademliaEvent::GetClosestPeersResult(res))) => {
+                Async::Ready(Some(KademliaEvent::GetClosestPeersResult(res, _))) => {
                     match res {
                         Ok(ok) => {
                             if !ok.peers.is_empty() {
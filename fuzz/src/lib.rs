@@ -0,0 +1,286 @@
+// Copyright 2019 Parity Technologies (UK) Ltd.
+//
+// Permission is hereby granted, free of charge, to any person obtaining a
+// copy of this software and associated documentation files (the "Software"),
+// to deal in the Software without restriction, including without limitation
+// the rights to use, copy, modify, merge, publish, distribute, sublicense,
+// and/or sell copies of the Software, and to permit persons to whom the
+// Software is furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS
+// OR IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING
+// FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
+// DEALINGS IN THE SOFTWARE.
+//! Fuzzing targets for the wire-format decoders of libp2p.
+//!
+//! Each target is a function taking arbitrary bytes, which must neither panic nor hang
+//! whatever the input. The binaries in `fuzz_targets` wrap them for `cargo fuzz`:
+//!
+//! ```text
+//! cargo +nightly fuzz run mplex
+//! ```
+//!
+//! The functions are public so that crates built on libp2p can call them from their own fuzzing
+//! harness, and so can the generators that turn arbitrary bytes into mostly well-formed input,
+//! which lets the fuzzer get past the framing and into the decoders behind it.
+
+use futures::{executor, future, prelude::*};
+use libp2p_core::{identity, muxing::StreamMuxer, upgrade};
+use multiaddr::Multiaddr;
+use multihash::Multihash;
+use std::{convert::TryFrom, io, sync::Arc};
+use tokio_io::{AsyncRead, AsyncWrite};
+
+/// Maximum number of times a future is polled before a target gives up on it.
+const MAX_POLLS: usize = 256;
+
+/// Protocols offered or supported by the multistream-select targets.
+const PROTOCOLS: [&[u8]; 3] = [b"/proto/1.0.0", b"/proto/2.0.0", b"/other/1.0.0"];
+
+/// Connection whose remote sends the fuzzing input, then closes its writing side.
+///
+/// Everything written on the connection is discarded.
+#[derive(Debug)]
+pub struct FuzzIo {
+    input: io::Cursor<Vec<u8>>,
+}
+
+impl FuzzIo {
+    /// Creates a connection on which the remote sends `input`.
+    pub fn new(input: impl Into<Vec<u8>>) -> Self {
+        FuzzIo { input: io::Cursor::new(input.into()) }
+    }
+}
+
+impl io::Read for FuzzIo {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        self.input.read(buf)
+    }
+}
+
+impl AsyncRead for FuzzIo {}
+
+impl io::Write for FuzzIo {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+impl AsyncWrite for FuzzIo {
+    fn shutdown(&mut self) -> Poll<(), io::Error> {
+        Ok(Async::Ready(()))
+    }
+}
+
+/// Appends `n` to `out` as an unsigned varint.
+fn push_uvarint(out: &mut Vec<u8>, n: u64) {
+    let mut buf = unsigned_varint::encode::u64_buffer();
+    out.extend_from_slice(unsigned_varint::encode::u64(n, &mut buf));
+}
+
+/// Returns the messages with which a dialer negotiates `protocol` with multistream-select.
+///
+/// Prepending them to the input of a listener gets it past the negotiation.
+pub fn multistream_handshake(protocol: &[u8]) -> Vec<u8> {
+    let mut out = Vec::new();
+    for message in &[&b"/multistream/1.0.0"[..], protocol] {
+        push_uvarint(&mut out, message.len() as u64 + 1);
+        out.extend_from_slice(message);
+        out.push(b'\n');
+    }
+    out
+}
+
+/// Turns arbitrary bytes into uvarint length-prefixed frames.
+///
+/// Each frame takes its length from one byte of the input, and its payload from the bytes
+/// that follow.
+pub fn length_prefixed_frames(data: &[u8]) -> Vec<u8> {
+    let mut out = Vec::with_capacity(data.len() * 2);
+    let mut data = data;
+    while let Some((&len, rest)) = data.split_first() {
+        let len = usize::from(len).min(rest.len());
+        push_uvarint(&mut out, len as u64);
+        out.extend_from_slice(&rest[..len]);
+        data = &rest[len..];
+    }
+    out
+}
+
+/// Turns arbitrary bytes into mplex frames with well-formed headers.
+///
+/// Each frame takes its stream ID, its flag and its length from three bytes of the input, and
+/// its payload from the bytes that follow.
+pub fn mplex_frames(data: &[u8]) -> Vec<u8> {
+    let mut out = Vec::with_capacity(data.len() * 2);
+    for chunk in data.chunks(3) {
+        if let &[id, flag, len] = chunk {
+            push_uvarint(&mut out, u64::from(id) << 3 | u64::from(flag % 7));
+            push_uvarint(&mut out, u64::from(len));
+            out.extend((0 .. len).map(|n| n.wrapping_mul(id)));
+        }
+    }
+    out
+}
+
+/// The targets only poll once in a while, so they don't need to be woken up.
+struct NoopNotify;
+
+impl executor::Notify for NoopNotify {
+    fn notify(&self, _: usize) {}
+}
+
+/// Polls `future` until it completes, or until it has been polled `MAX_POLLS` times.
+///
+/// The input being entirely available, a future that isn't ready will never be.
+fn drive<F: Future>(future: F) -> Option<Result<F::Item, F::Error>> {
+    let notify = executor::NotifyHandle::from(Arc::new(NoopNotify));
+    let mut task = executor::spawn(future);
+    for _ in 0 .. MAX_POLLS {
+        match task.poll_future_notify(&notify, 0) {
+            Ok(Async::Ready(item)) => return Some(Ok(item)),
+            Ok(Async::NotReady) => {}
+            Err(err) => return Some(Err(err)),
+        }
+    }
+    None
+}
+
+/// Accepts the inbound substreams of `muxer` and reads from them, until the input is exhausted.
+fn drive_muxer<M: StreamMuxer>(muxer: M) {
+    drive(future::poll_fn(move || -> Poll<(), ()> {
+        loop {
+            let mut substream = match muxer.poll_inbound() {
+                Ok(Async::Ready(substream)) => substream,
+                Ok(Async::NotReady) => return Ok(Async::NotReady),
+                Err(_) => return Ok(Async::Ready(())),
+            };
+            let mut buf = [0; 256];
+            let _ = muxer.read_substream(&mut substream, &mut buf);
+            muxer.destroy_substream(substream);
+        }
+    }));
+}
+
+/// Negotiates a protocol with multistream-select, as the listener.
+pub fn multistream_listener(data: &[u8]) {
+    let protocols = PROTOCOLS.to_vec();
+    drive(multistream_select::listener_select_proto(FuzzIo::new(data), protocols));
+}
+
+/// Negotiates a protocol with multistream-select, as the dialer.
+pub fn multistream_dialer(data: &[u8]) {
+    drive(multistream_select::dialer_select_proto(FuzzIo::new(data), PROTOCOLS.iter()));
+}
+
+/// Reads a length-prefixed message, both from the raw input and from frames built from it.
+pub fn length_delimited(data: &[u8]) {
+    drive(upgrade::read_one(FuzzIo::new(data), 4096));
+    drive(upgrade::read_one(FuzzIo::new(length_prefixed_frames(data)), 4096));
+}
+
+/// Decodes an unsigned varint, checking that its encoding decodes to the same value.
+pub fn uvarint(data: &[u8]) {
+    if let Ok((n, _)) = unsigned_varint::decode::u64(data) {
+        let mut buf = unsigned_varint::encode::u64_buffer();
+        let encoded = unsigned_varint::encode::u64(n, &mut buf);
+        assert_eq!(unsigned_varint::decode::u64(encoded).ok(), Some((n, &[][..])));
+    }
+}
+
+/// Decodes a multiaddress from bytes and from text, checking that it round-trips.
+pub fn multiaddr(data: &[u8]) {
+    if let Ok(addr) = Multiaddr::try_from(data.to_vec()) {
+        assert_eq!(addr.to_vec(), data);
+        assert_eq!(addr.to_string().parse::<Multiaddr>().ok(), Some(addr));
+    }
+    if let Ok(text) = std::str::from_utf8(data) {
+        if let Ok(addr) = text.parse::<Multiaddr>() {
+            assert_eq!(Multiaddr::try_from(addr.to_vec()).ok(), Some(addr));
+        }
+    }
+}
+
+/// Decodes a multihash, checking that it round-trips.
+pub fn multihash(data: &[u8]) {
+    if let Ok(hash) = Multihash::from_bytes(data.to_vec()) {
+        assert_eq!(hash.as_bytes(), data);
+    }
+}
+
+/// Performs the secio handshake, as the listener, against the messages of the input.
+///
+/// The frames exchanged after the handshake are authenticated with keys the input can't know,
+/// so the fuzzer only reaches the handshake messages and the first frame check.
+pub fn secio_handshake(data: &[u8]) {
+    let secret = identity::ed25519::SecretKey::from_bytes([1; 32])
+        .expect("any 32 bytes are a valid ed25519 secret key");
+    let config = libp2p_secio::SecioConfig::new(identity::Keypair::Ed25519(secret.into()));
+    let mut input = multistream_handshake(b"/secio/1.0.0");
+    input.extend_from_slice(data);
+    drive(upgrade::apply_inbound(FuzzIo::new(input), config));
+}
+
+/// Decodes mplex frames and the substreams they open.
+pub fn mplex(data: &[u8]) {
+    let mut input = multistream_handshake(b"/mplex/6.7.0");
+    input.extend_from_slice(data);
+    if let Some(Ok(muxer)) = drive(upgrade::apply_inbound(FuzzIo::new(input), libp2p_mplex::MplexConfig::new())) {
+        drive_muxer(muxer);
+    }
+}
+
+/// Like `mplex`, with frames built by `mplex_frames`.
+pub fn mplex_structured(data: &[u8]) {
+    mplex(&mplex_frames(data))
+}
+
+/// Decodes yamux frames and the substreams they open.
+pub fn yamux(data: &[u8]) {
+    let muxer = libp2p_yamux::Yamux::new(FuzzIo::new(data), yamux::Config::default(), yamux::Mode::Server);
+    drive_muxer(muxer);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TARGETS: [fn(&[u8]); 10] = [
+        multistream_listener, multistream_dialer, length_delimited, uvarint, multiaddr,
+        multihash, secio_handshake, mplex, mplex_structured, yamux,
+    ];
+
+    #[test]
+    fn targets_accept_sample_inputs() {
+        let mut inputs = vec![Vec::new(), vec![0xff; 64], (0 .. 255).collect::<Vec<u8>>()];
+        inputs.push(multistream_handshake(b"/proto/1.0.0"));
+        inputs.push(length_prefixed_frames(&inputs[2]));
+        inputs.push(mplex_frames(&inputs[2]));
+        inputs.push(b"/ip4/1.2.3.4/tcp/80".to_vec());
+        for target in TARGETS.iter() {
+            for input in &inputs {
+                target(input);
+            }
+        }
+    }
+
+    #[test]
+    fn handshake_is_accepted() {
+        let input = multistream_handshake(b"/proto/1.0.0");
+        let result = drive(multistream_select::listener_select_proto(FuzzIo::new(input), PROTOCOLS.to_vec()));
+        match result {
+            Some(Ok((protocol, _, _))) => assert_eq!(*protocol, &b"/proto/1.0.0"[..]),
+            _ => panic!("expected the negotiation to succeed"),
+        }
+    }
+}
@@ -0,0 +1,55 @@
+// Copyright 2019 Parity Technologies (UK) Ltd.
+//
+// Permission is hereby granted, free of charge, to any person obtaining a
+// copy of this software and associated documentation files (the "Software"),
+// to deal in the Software without restriction, including without limitation
+// the rights to use, copy, modify, merge, publish, distribute, sublicense,
+// and/or sell copies of the Software, and to permit persons to whom the
+// Software is furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS
+// OR IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING
+// FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
+// DEALINGS IN THE SOFTWARE.
+//! The rust-libp2p interop peer. See the documentation of the `libp2p-interop` crate for the
+//! environment variables it reads and what it prints.
+
+use futures::prelude::*;
+use libp2p_interop::{run_peer, PeerConfig, TEST_TIMEOUT};
+use std::{io::Write, process};
+use tokio::prelude::FutureExt;
+
+fn main() {
+    let config = match PeerConfig::from_env() {
+        Ok(config) => config,
+        Err(err) => {
+            eprintln!("{}", err);
+            process::exit(2);
+        }
+    };
+
+    let peer = run_peer(config, |addr| {
+        println!("LISTEN_ADDR={}", addr);
+        let _ = std::io::stdout().flush();
+    });
+    let outcome = tokio::runtime::current_thread::block_on_all(peer.timeout(TEST_TIMEOUT).then(Ok::<_, ()>))
+        .expect("the future never fails");
+
+    match outcome {
+        Ok(Ok(details)) => println!("RESULT={}", details),
+        Ok(Err(failure)) => {
+            eprintln!("test failed: {}", failure);
+            process::exit(1);
+        }
+        Err(err) => {
+            eprintln!("peer failed: {:?}", err);
+            process::exit(1);
+        }
+    }
+}
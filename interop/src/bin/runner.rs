@@ -0,0 +1,180 @@
+// Copyright 2019 Parity Technologies (UK) Ltd.
+//
+// Permission is hereby granted, free of charge, to any person obtaining a
+// copy of this software and associated documentation files (the "Software"),
+// to deal in the Software without restriction, including without limitation
+// the rights to use, copy, modify, merge, publish, distribute, sublicense,
+// and/or sell copies of the Software, and to permit persons to whom the
+// Software is furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS
+// OR IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING
+// FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
+// DEALINGS IN THE SOFTWARE.
+//! Runs the interop tests between the rust-libp2p peer and itself, and against the reference
+//! peers whose executables are given in `INTEROP_GO_PEER` and `INTEROP_JS_PEER`.
+//!
+//! Every combination of transport, security protocol, muxer and test case is run in both
+//! roles. The process exits with a non-zero status if any test failed.
+
+use libp2p_interop::{Muxer, PeerConfig, Role, Security, TestCase, TransportKind, TEST_TIMEOUT};
+use std::{
+    env,
+    io::{BufRead, BufReader},
+    path::PathBuf,
+    process::{self, Child, Command, Stdio},
+    thread,
+    time::{Duration, Instant},
+};
+
+/// An implementation taking part in the tests.
+struct Implementation {
+    name: &'static str,
+    executable: PathBuf,
+}
+
+fn implementations() -> Vec<Implementation> {
+    let rust = env::current_exe()
+        .expect("the runner has a path")
+        .with_file_name(format!("interop-peer{}", env::consts::EXE_SUFFIX));
+    let mut implementations = vec![Implementation { name: "rust", executable: rust }];
+    for &(name, var) in &[("go", "INTEROP_GO_PEER"), ("js", "INTEROP_JS_PEER")] {
+        if let Some(executable) = env::var_os(var) {
+            implementations.push(Implementation { name, executable: executable.into() });
+        }
+    }
+    implementations
+}
+
+fn spawn(implementation: &Implementation, config: &PeerConfig) -> Result<Child, String> {
+    Command::new(&implementation.executable)
+        .envs(config.to_env())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::inherit())
+        .spawn()
+        .map_err(|err| format!("failed to start {}: {}", implementation.name, err))
+}
+
+/// Waits for the child to exit, killing it after the timeout.
+fn wait(child: &mut Child, timeout: Duration) -> Result<bool, String> {
+    let deadline = Instant::now() + timeout;
+    loop {
+        if let Some(status) = child.try_wait().map_err(|err| err.to_string())? {
+            return Ok(status.success());
+        }
+        if Instant::now() >= deadline {
+            let _ = child.kill();
+            let _ = child.wait();
+            return Err("timed out".to_owned());
+        }
+        thread::sleep(Duration::from_millis(50));
+    }
+}
+
+/// Runs one test, returning the details reported by the dialer.
+fn run(listener: &Implementation, dialer: &Implementation, mut config: PeerConfig) -> Result<String, String> {
+    config.role = Role::Listener;
+    config.remote_addr = None;
+    let mut listener_child = spawn(listener, &config)?;
+    let listener_out = BufReader::new(listener_child.stdout.take().expect("stdout is piped"));
+
+    // The listener prints its address as its first line of interest.
+    let mut lines = listener_out.lines();
+    let listen_addr = loop {
+        match lines.next() {
+            Some(Ok(line)) => {
+                let mut parts = line.splitn(2, '=');
+                if parts.next() == Some("LISTEN_ADDR") {
+                    break parts.next().unwrap_or("").to_owned();
+                }
+            }
+            _ => {
+                let _ = listener_child.kill();
+                let _ = listener_child.wait();
+                return Err(format!("{} exited without reporting its address", listener.name));
+            }
+        }
+    };
+
+    config.role = Role::Dialer;
+    config.remote_addr = Some(listen_addr.parse().map_err(|err| format!("invalid listen address: {}", err))?);
+    let mut dialer_child = match spawn(dialer, &config) {
+        Ok(child) => child,
+        Err(err) => {
+            let _ = listener_child.kill();
+            let _ = listener_child.wait();
+            return Err(err);
+        }
+    };
+    let dialer_out = BufReader::new(dialer_child.stdout.take().expect("stdout is piped"));
+    let result = thread::spawn(move || {
+        dialer_out.lines()
+            .filter_map(Result::ok)
+            .filter_map(|line| {
+                let mut parts = line.splitn(2, '=');
+                if parts.next() == Some("RESULT") { parts.next().map(str::to_owned) } else { None }
+            })
+            .next()
+    });
+
+    let dialer_ok = wait(&mut dialer_child, TEST_TIMEOUT);
+    let listener_ok = wait(&mut listener_child, Duration::from_secs(5));
+    let details = result.join().ok().and_then(|r| r);
+    match (dialer_ok?, listener_ok, details) {
+        (true, Ok(true), Some(details)) => Ok(details),
+        (true, Ok(true), None) => Err("the dialer reported no result".to_owned()),
+        (true, Ok(false), _) => Err(format!("{} failed as the listener", listener.name)),
+        (true, Err(err), _) => Err(format!("{} as the listener: {}", listener.name, err)),
+        (false, _, _) => Err(format!("{} failed as the dialer", dialer.name)),
+    }
+}
+
+fn main() {
+    let implementations = implementations();
+    let rust = &implementations[0];
+    let mut failures = 0;
+    let mut total = 0;
+
+    for listener in &implementations {
+        for dialer in &implementations {
+            // Only the interoperability of rust-libp2p is under test here.
+            if listener.name != rust.name && dialer.name != rust.name {
+                continue;
+            }
+            for &transport in TransportKind::ALL {
+                for &security in Security::ALL {
+                    for &muxer in Muxer::ALL {
+                        for &test_case in TestCase::ALL {
+                            let config = PeerConfig {
+                                transport, security, muxer, test_case,
+                                role: Role::Listener,
+                                remote_addr: None,
+                            };
+                            let name = format!("{} -> {} {}/{}/{}/{}",
+                                dialer.name, listener.name, transport, security, muxer, test_case);
+                            total += 1;
+                            match run(listener, dialer, config) {
+                                Ok(details) => println!("ok    {}: {}", name, details),
+                                Err(err) => {
+                                    failures += 1;
+                                    println!("FAIL  {}: {}", name, err);
+                                }
+                            }
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    println!("{} passed, {} failed", total - failures, failures);
+    if failures > 0 {
+        process::exit(1);
+    }
+}
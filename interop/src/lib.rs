@@ -0,0 +1,382 @@
+// Copyright 2019 Parity Technologies (UK) Ltd.
+//
+// Permission is hereby granted, free of charge, to any person obtaining a
+// copy of this software and associated documentation files (the "Software"),
+// to deal in the Software without restriction, including without limitation
+// the rights to use, copy, modify, merge, publish, distribute, sublicense,
+// and/or sell copies of the Software, and to permit persons to whom the
+// Software is furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS
+// OR IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING
+// FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
+// DEALINGS IN THE SOFTWARE.
+//! Interoperability tests of rust-libp2p against other implementations.
+//!
+//! A test runs between two peers, a listener and a dialer, over a combination of a
+//! [`TransportKind`], a [`Security`] protocol and a [`Muxer`]. The peers are processes
+//! configured through environment variables, so that the reference peers of go-libp2p and
+//! js-libp2p can take part by following the same contract:
+//!
+//! - `TRANSPORT` is `tcp` or `ws`, `SECURITY` is `secio` or `noise`, `MUXER` is `mplex` or
+//!   `yamux`, and `TEST_CASE` is `ping`, `identify` or `transfer`;
+//! - `ROLE` is `listener` or `dialer`. The listener prints `LISTEN_ADDR=<multiaddr>` on a line
+//!   of its standard output once it listens, and exits once the dialer has disconnected. The
+//!   dialer dials `REMOTE_ADDR`, runs the test case, prints `RESULT=<details>` and exits with
+//!   a zero status if the test case succeeded.
+//!
+//! The `interop-peer` binary is the rust-libp2p peer. The `interop-runner` binary runs every
+//! combination between the rust peer and itself, and against the peers whose executables are
+//! given in `INTEROP_GO_PEER` and `INTEROP_JS_PEER`, in both roles.
+
+use futures::{future, prelude::*};
+use libp2p::{
+    NetworkBehaviour, Multiaddr, PeerId, Swarm, Transport,
+    core::{identity, muxing::StreamMuxerBox, transport::boxed::Boxed, upgrade},
+    identify::{Identify, IdentifyEvent},
+    mplex::MplexConfig,
+    noise::{self, NoiseConfig, RemoteIdentity, X25519},
+    perf::{Perf, PerfConfig, PerfEvent, RunParams},
+    ping::{Ping, PingConfig, PingEvent, PingSuccess},
+    secio::SecioConfig,
+    swarm::NetworkBehaviourEventProcess,
+    tcp::TcpConfig,
+    tokio_io::{AsyncRead, AsyncWrite},
+    websocket::WsConfig,
+    yamux,
+};
+use std::{env, fmt, io, str::FromStr, time::Duration};
+
+/// How long a test case may take.
+pub const TEST_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Number of bytes sent in each direction by the `transfer` test case.
+pub const TRANSFER_SIZE: u64 = 1024 * 1024;
+
+macro_rules! string_enum {
+    ($(#[$doc:meta])* $name:ident { $($variant:ident => $text:expr),+ $(,)* }) => {
+        $(#[$doc])*
+        #[derive(Debug, Copy, Clone, PartialEq, Eq)]
+        pub enum $name { $($variant),+ }
+
+        impl $name {
+            /// All the possible values.
+            pub const ALL: &'static [$name] = &[$($name::$variant),+];
+        }
+
+        impl fmt::Display for $name {
+            fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                f.write_str(match self { $($name::$variant => $text),+ })
+            }
+        }
+
+        impl FromStr for $name {
+            type Err = String;
+
+            fn from_str(s: &str) -> Result<Self, String> {
+                match s {
+                    $($text => Ok($name::$variant),)+
+                    _ => Err(format!("invalid {}: {:?}", stringify!($name), s)),
+                }
+            }
+        }
+    };
+}
+
+string_enum! {
+    /// Transport protocol of a test.
+    TransportKind { Tcp => "tcp", Ws => "ws" }
+}
+
+string_enum! {
+    /// Security protocol of a test.
+    Security { Secio => "secio", Noise => "noise" }
+}
+
+string_enum! {
+    /// Stream multiplexer of a test.
+    Muxer { Mplex => "mplex", Yamux => "yamux" }
+}
+
+string_enum! {
+    /// What a test checks.
+    TestCase { Ping => "ping", Identify => "identify", Transfer => "transfer" }
+}
+
+string_enum! {
+    /// Role of a peer in a test.
+    Role { Listener => "listener", Dialer => "dialer" }
+}
+
+/// Configuration of a peer, read from the environment.
+#[derive(Debug, Clone)]
+pub struct PeerConfig {
+    pub transport: TransportKind,
+    pub security: Security,
+    pub muxer: Muxer,
+    pub test_case: TestCase,
+    pub role: Role,
+    /// Address to dial, for the dialer.
+    pub remote_addr: Option<Multiaddr>,
+}
+
+impl PeerConfig {
+    /// Reads the configuration from the environment variables described in the crate
+    /// documentation.
+    pub fn from_env() -> Result<Self, String> {
+        fn var<T: FromStr<Err = String>>(name: &str) -> Result<T, String> {
+            env::var(name).map_err(|_| format!("{} isn't set", name))?.parse()
+        }
+        let role = var("ROLE")?;
+        let remote_addr = match role {
+            Role::Listener => None,
+            Role::Dialer => {
+                let addr = env::var("REMOTE_ADDR").map_err(|_| "REMOTE_ADDR isn't set".to_owned())?;
+                Some(addr.parse().map_err(|e| format!("invalid REMOTE_ADDR: {}", e))?)
+            }
+        };
+        Ok(PeerConfig {
+            transport: var("TRANSPORT")?,
+            security: var("SECURITY")?,
+            muxer: var("MUXER")?,
+            test_case: var("TEST_CASE")?,
+            role,
+            remote_addr,
+        })
+    }
+
+    /// Returns the environment variables describing this configuration.
+    pub fn to_env(&self) -> Vec<(&'static str, String)> {
+        let mut vars = vec![
+            ("TRANSPORT", self.transport.to_string()),
+            ("SECURITY", self.security.to_string()),
+            ("MUXER", self.muxer.to_string()),
+            ("TEST_CASE", self.test_case.to_string()),
+            ("ROLE", self.role.to_string()),
+        ];
+        if let Some(addr) = &self.remote_addr {
+            vars.push(("REMOTE_ADDR", addr.to_string()));
+        }
+        vars
+    }
+
+    /// Returns the address the listener listens on.
+    pub fn listen_addr(&self) -> Multiaddr {
+        match self.transport {
+            TransportKind::Tcp => "/ip4/127.0.0.1/tcp/0".parse().expect("valid multiaddr"),
+            TransportKind::Ws => "/ip4/127.0.0.1/tcp/0/ws".parse().expect("valid multiaddr"),
+        }
+    }
+}
+
+/// Connection of the transport, before the upgrades.
+pub trait Io: AsyncRead + AsyncWrite + Send {}
+
+impl<T: AsyncRead + AsyncWrite + Send> Io for T {}
+
+fn other_error(err: impl fmt::Display) -> io::Error {
+    io::Error::new(io::ErrorKind::Other, err.to_string())
+}
+
+/// Builds the transport of the given combination.
+pub fn build_transport(config: &PeerConfig, keypair: identity::Keypair)
+    -> Boxed<(PeerId, StreamMuxerBox), io::Error>
+{
+    let base = match config.transport {
+        TransportKind::Tcp => TcpConfig::new()
+            .map(|io, _| Box::new(io) as Box<dyn Io>)
+            .boxed(),
+        TransportKind::Ws => WsConfig::new(TcpConfig::new())
+            .map(|io, _| Box::new(io) as Box<dyn Io>)
+            .map_err(other_error)
+            .boxed(),
+    };
+
+    let secured = match config.security {
+        Security::Secio => base
+            .with_upgrade(SecioConfig::new(keypair))
+            .map(|output, _| (output.remote_key.into_peer_id(), Box::new(output.stream) as Box<dyn Io>))
+            .map_err(other_error)
+            .boxed(),
+        Security::Noise => {
+            let dh_keys = noise::Keypair::<X25519>::new()
+                .into_authentic(&keypair)
+                .expect("signing the noise static key with an identity key doesn't fail");
+            base.with_upgrade(NoiseConfig::xx(dh_keys))
+                .and_then(|(remote, io), _| match remote {
+                    RemoteIdentity::IdentityKey(key) => Ok((key.into_peer_id(), Box::new(io) as Box<dyn Io>)),
+                    _ => Err(io::Error::new(io::ErrorKind::InvalidData, "the remote didn't send its identity")),
+                })
+                .map_err(other_error)
+                .boxed()
+        }
+    };
+
+    let muxed = match config.muxer {
+        Muxer::Mplex => secured
+            .and_then(|(peer_id, io), endpoint| {
+                upgrade::apply(io, MplexConfig::new(), endpoint)
+                    .map(move |muxer| (peer_id, StreamMuxerBox::new(muxer)))
+                    .map_err(other_error)
+            })
+            .map_err(other_error)
+            .boxed(),
+        Muxer::Yamux => secured
+            .and_then(|(peer_id, io), endpoint| {
+                upgrade::apply(io, yamux::Config::default(), endpoint)
+                    .map(move |muxer| (peer_id, StreamMuxerBox::new(muxer)))
+                    .map_err(other_error)
+            })
+            .map_err(other_error)
+            .boxed(),
+    };
+
+    muxed.with_timeout(TEST_TIMEOUT).map_err(other_error).boxed()
+}
+
+/// Behaviour of the peers, serving all the test cases.
+#[derive(NetworkBehaviour)]
+pub struct Behaviour<TSubstream: AsyncRead + AsyncWrite> {
+    ping: Ping<TSubstream>,
+    identify: Identify<TSubstream>,
+    perf: Perf<TSubstream>,
+
+    /// Test case run by the dialer.
+    #[behaviour(ignore)]
+    test_case: Option<TestCase>,
+    /// Outcome of the test case, once known.
+    #[behaviour(ignore)]
+    outcome: Option<Result<String, String>>,
+}
+
+impl<TSubstream: AsyncRead + AsyncWrite> NetworkBehaviourEventProcess<PingEvent> for Behaviour<TSubstream> {
+    fn inject_event(&mut self, event: PingEvent) {
+        if self.test_case != Some(TestCase::Ping) || self.outcome.is_some() {
+            return;
+        }
+        match event.result {
+            Ok(PingSuccess::Ping { rtt }) => self.outcome = Some(Ok(format!("rtt={:?}", rtt))),
+            Ok(PingSuccess::Pong) => {}
+            Err(err) => self.outcome = Some(Err(format!("ping failed: {:?}", err))),
+        }
+    }
+}
+
+impl<TSubstream: AsyncRead + AsyncWrite> NetworkBehaviourEventProcess<IdentifyEvent> for Behaviour<TSubstream> {
+    fn inject_event(&mut self, event: IdentifyEvent) {
+        if self.test_case != Some(TestCase::Identify) || self.outcome.is_some() {
+            return;
+        }
+        match event {
+            IdentifyEvent::Identified { peer_id, info, .. } => {
+                self.outcome = Some(if info.public_key.clone().into_peer_id() == peer_id {
+                    Ok(format!("agent={:?} protocols={}", info.agent_version, info.protocols.len()))
+                } else {
+                    Err("the identified key doesn't match the peer".to_owned())
+                })
+            }
+            IdentifyEvent::Error { error, .. } => self.outcome = Some(Err(format!("identify failed: {:?}", error))),
+            _ => {}
+        }
+    }
+}
+
+impl<TSubstream: AsyncRead + AsyncWrite> NetworkBehaviourEventProcess<PerfEvent> for Behaviour<TSubstream> {
+    fn inject_event(&mut self, event: PerfEvent) {
+        if let PerfEvent::Finished { result, .. } = event {
+            self.outcome = Some(match result {
+                Ok(stats) => Ok(format!("upload={:?} download={:?}", stats.upload, stats.download)),
+                Err(err) => Err(format!("transfer failed: {:?}", err)),
+            });
+        }
+    }
+}
+
+/// Runs a peer with the given configuration until the end of the test.
+///
+/// The listener reports its address through `on_listen`. The dialer returns the outcome of the
+/// test case.
+pub fn run_peer(config: PeerConfig, mut on_listen: impl FnMut(&Multiaddr))
+    -> impl Future<Item = Result<String, String>, Error = io::Error>
+{
+    let keypair = identity::Keypair::generate_ed25519();
+    let local_peer_id = keypair.public().into_peer_id();
+    let transport = build_transport(&config, keypair.clone());
+    let behaviour = Behaviour {
+        ping: Ping::new(PingConfig::new().with_keep_alive(config.role == Role::Listener)),
+        identify: Identify::new("/interop/1.0.0".to_owned(), format!("rust-libp2p-interop/{}", env!("CARGO_PKG_VERSION")), keypair.public()),
+        perf: Perf::new(PerfConfig::default()),
+        test_case: match config.role {
+            Role::Listener => None,
+            Role::Dialer => Some(config.test_case),
+        },
+        outcome: None,
+    };
+    let mut swarm = Swarm::new(transport, behaviour, local_peer_id);
+
+    let setup = match &config.remote_addr {
+        Some(addr) => Swarm::dial_addr(&mut swarm, addr.clone()).map_err(other_error),
+        None => Swarm::listen_on(&mut swarm, config.listen_addr()).map(|_| ()).map_err(other_error),
+    };
+
+    let mut listening = false;
+    let mut was_connected = false;
+    let mut started = false;
+    future::result(setup).and_then(move |()| future::poll_fn(move || {
+        while let Async::Ready(Some(_)) = swarm.poll()? {}
+
+        if !listening {
+            if let Some(addr) = Swarm::listeners(&swarm).next() {
+                on_listen(addr);
+                listening = true;
+            }
+        }
+
+        let connected = Swarm::connections(&swarm).next().map(|c| c.info().clone());
+        if let Some(peer_id) = &connected {
+            was_connected = true;
+            if !started && swarm.test_case == Some(TestCase::Transfer) {
+                swarm.perf.perf(peer_id, RunParams { to_send: TRANSFER_SIZE, to_receive: TRANSFER_SIZE });
+                started = true;
+            }
+        }
+
+        match config.role {
+            Role::Dialer => match swarm.outcome.take() {
+                Some(outcome) => Ok(Async::Ready(outcome)),
+                None => Ok(Async::NotReady),
+            },
+            // The listener is done once the dialer has disconnected.
+            Role::Listener if was_connected && connected.is_none() => Ok(Async::Ready(Ok("served".to_owned()))),
+            Role::Listener => Ok(Async::NotReady),
+        }
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn names_round_trip() {
+        for kind in TransportKind::ALL {
+            assert_eq!(kind.to_string().parse::<TransportKind>(), Ok(*kind));
+        }
+        for security in Security::ALL {
+            assert_eq!(security.to_string().parse::<Security>(), Ok(*security));
+        }
+        for muxer in Muxer::ALL {
+            assert_eq!(muxer.to_string().parse::<Muxer>(), Ok(*muxer));
+        }
+        for test_case in TestCase::ALL {
+            assert_eq!(test_case.to_string().parse::<TestCase>(), Ok(*test_case));
+        }
+        assert!("quic".parse::<TransportKind>().is_err());
+    }
+}
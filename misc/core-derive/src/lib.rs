@@ -56,6 +56,9 @@ fn build_struct(ast: &DeriveInput, data_struct: &DataStruct) -> TokenStream {
     let into_proto_select_ident = quote!{::libp2p::swarm::IntoProtocolsHandlerSelect};
     let peer_id = quote!{::libp2p::core::PeerId};
     let connected_point = quote!{::libp2p::core::ConnectedPoint};
+    let connection_id = quote!{::libp2p::core::nodes::ConnectionId};
+    let behaviour_summary = quote!{::libp2p::swarm::BehaviourSummary};
+    let network_change = quote!{::libp2p::swarm::NetworkChange};
 
     // Name of the type parameter that represents the substream.
     let substream_generic = {
@@ -78,15 +81,61 @@ fn build_struct(ast: &DeriveInput, data_struct: &DataStruct) -> TokenStream {
         quote!{<#(#lf,)* #(#tp,)* #(#cst,)* #substream_generic>}
     };
 
+    // The final out event.
+    // If we find a `#[behaviour(out_event = "Foo")]` attribute on the struct, we set `Foo` as
+    // the out event. Otherwise we use `()`.
+    let out_event = {
+        let mut out = quote!{()};
+        for meta_items in ast.attrs.iter().filter_map(get_meta_items) {
+            for meta_item in meta_items {
+                match meta_item {
+                    syn::NestedMeta::Meta(syn::Meta::NameValue(ref m)) if m.ident == "out_event" => {
+                        if let syn::Lit::Str(ref s) = m.lit {
+                            let ident: syn::Type = syn::parse_str(&s.value()).unwrap();
+                            out = quote!{#ident};
+                        }
+                    }
+                    _ => ()
+                }
+            }
+        }
+        out
+    };
+
+    // Whether the events generated by the fields are passed to `NetworkBehaviourEventProcess`.
+    // If we find a `#[behaviour(event_process = false)]` attribute on the struct, the events are
+    // instead converted into the out event with `From` and returned from `poll()`.
+    let event_process = {
+        let mut event_process = true;
+        for meta_items in ast.attrs.iter().filter_map(get_meta_items) {
+            for meta_item in meta_items {
+                match meta_item {
+                    syn::NestedMeta::Meta(syn::Meta::NameValue(ref m)) if m.ident == "event_process" => {
+                        if let syn::Lit::Bool(ref b) = m.lit {
+                            event_process = b.value;
+                        }
+                    }
+                    _ => ()
+                }
+            }
+        }
+        event_process
+    };
+
     // Build the `where ...` clause of the trait implementation.
     let where_clause = {
         let mut additional = data_struct.fields.iter()
             .filter(|x| !is_ignored(x))
             .flat_map(|field| {
                 let ty = &field.ty;
+                let event_bound = if event_process {
+                    quote!{Self: #net_behv_event_proc<<#ty as #trait_to_impl>::OutEvent>}
+                } else {
+                    quote!{#out_event: From<<#ty as #trait_to_impl>::OutEvent>}
+                };
                 vec![
                     quote!{#ty: #trait_to_impl},
-                    quote!{Self: #net_behv_event_proc<<#ty as #trait_to_impl>::OutEvent>},
+                    event_bound,
                     quote!{<<#ty as #trait_to_impl>::ProtocolsHandler as #into_protocols_handler>::Handler: #protocols_handler<Substream = #substream_generic>},
                     // Note: this bound is required because of https://github.com/rust-lang/rust/issues/55697
                     quote!{<<<#ty as #trait_to_impl>::ProtocolsHandler as #into_protocols_handler>::Handler as #protocols_handler>::InboundProtocol: ::libp2p::core::InboundUpgrade<#substream_generic>},
@@ -109,27 +158,6 @@ fn build_struct(ast: &DeriveInput, data_struct: &DataStruct) -> TokenStream {
         }
     };
 
-    // The final out event.
-    // If we find a `#[behaviour(out_event = "Foo")]` attribute on the struct, we set `Foo` as
-    // the out event. Otherwise we use `()`.
-    let out_event = {
-        let mut out = quote!{()};
-        for meta_items in ast.attrs.iter().filter_map(get_meta_items) {
-            for meta_item in meta_items {
-                match meta_item {
-                    syn::NestedMeta::Meta(syn::Meta::NameValue(ref m)) if m.ident == "out_event" => {
-                        if let syn::Lit::Str(ref s) = m.lit {
-                            let ident: syn::Type = syn::parse_str(&s.value()).unwrap();
-                            out = quote!{#ident};
-                        }
-                    }
-                    _ => ()
-                }
-            }
-        }
-        out
-    };
-
     // Build the list of statements to put in the body of `addresses_of_peer()`.
     let addresses_of_peer_stmts = {
         data_struct.fields.iter().enumerate().filter_map(move |(field_n, field)| {
@@ -214,6 +242,34 @@ fn build_struct(ast: &DeriveInput, data_struct: &DataStruct) -> TokenStream {
         })
     };
 
+    // Build the list of statements to put in the body of `inject_connection_established()`.
+    let inject_connection_established_stmts = {
+        data_struct.fields.iter().enumerate().filter_map(move |(field_n, field)| {
+            if is_ignored(&field) {
+                return None;
+            }
+
+            Some(match field.ident {
+                Some(ref i) => quote!{ self.#i.inject_connection_established(peer_id, connection, endpoint); },
+                None => quote!{ self.#field_n.inject_connection_established(peer_id, connection, endpoint); },
+            })
+        })
+    };
+
+    // Build the list of statements to put in the body of `inject_connection_closed()`.
+    let inject_connection_closed_stmts = {
+        data_struct.fields.iter().enumerate().filter_map(move |(field_n, field)| {
+            if is_ignored(&field) {
+                return None;
+            }
+
+            Some(match field.ident {
+                Some(ref i) => quote!{ self.#i.inject_connection_closed(peer_id, connection, endpoint); },
+                None => quote!{ self.#field_n.inject_connection_closed(peer_id, connection, endpoint); },
+            })
+        })
+    };
+
     // Build the list of statements to put in the body of `inject_addr_reach_failure()`.
     let inject_addr_reach_failure_stmts = {
         data_struct.fields.iter().enumerate().filter_map(move |(field_n, field)| {
@@ -242,6 +298,20 @@ fn build_struct(ast: &DeriveInput, data_struct: &DataStruct) -> TokenStream {
         })
     };
 
+    // Build the list of statements to put in the body of `inject_banned_peer_connection()`.
+    let inject_banned_peer_connection_stmts = {
+        data_struct.fields.iter().enumerate().filter_map(move |(field_n, field)| {
+            if is_ignored(&field) {
+                return None;
+            }
+
+            Some(match field.ident {
+                Some(ref i) => quote!{ self.#i.inject_banned_peer_connection(peer_id, endpoint); },
+                None => quote!{ self.#field_n.inject_banned_peer_connection(peer_id, endpoint); },
+            })
+        })
+    };
+
     // Build the list of statements to put in the body of `inject_new_listen_addr()`.
     let inject_new_listen_addr_stmts = {
         data_struct.fields.iter().enumerate().filter_map(move |(field_n, field)| {
@@ -270,6 +340,20 @@ fn build_struct(ast: &DeriveInput, data_struct: &DataStruct) -> TokenStream {
         })
     };
 
+    // Build the list of statements to put in the body of `inject_listener_error()`.
+    let inject_listener_error_stmts = {
+        data_struct.fields.iter().enumerate().filter_map(move |(field_n, field)| {
+            if is_ignored(&field) {
+                return None;
+            }
+
+            Some(match field.ident {
+                Some(ref i) => quote!{ self.#i.inject_listener_error(err); },
+                None => quote!{ self.#field_n.inject_listener_error(err); },
+            })
+        })
+    };
+
     // Build the list of statements to put in the body of `inject_new_external_addr()`.
     let inject_new_external_addr_stmts = {
         data_struct.fields.iter().enumerate().filter_map(move |(field_n, field)| {
@@ -284,6 +368,62 @@ fn build_struct(ast: &DeriveInput, data_struct: &DataStruct) -> TokenStream {
         })
     };
 
+    // Build the list of statements to put in the body of `inject_network_change()`.
+    let inject_network_change_stmts = {
+        data_struct.fields.iter().enumerate().filter_map(move |(field_n, field)| {
+            if is_ignored(&field) {
+                return None;
+            }
+
+            Some(match field.ident {
+                Some(ref i) => quote!{ self.#i.inject_network_change(change); },
+                None => quote!{ self.#field_n.inject_network_change(change); },
+            })
+        })
+    };
+
+    // Build the list of statements to put in the body of `inject_shutdown()`.
+    let inject_shutdown_stmts = {
+        data_struct.fields.iter().enumerate().filter_map(move |(field_n, field)| {
+            if is_ignored(&field) {
+                return None;
+            }
+
+            Some(match field.ident {
+                Some(ref i) => quote!{ self.#i.inject_shutdown(); },
+                None => quote!{ self.#field_n.inject_shutdown(); },
+            })
+        })
+    };
+
+    // Build the list of statements to put in the body of `summarize()`.
+    let summarize_stmts = {
+        data_struct.fields.iter().enumerate().filter_map(move |(field_n, field)| {
+            if is_ignored(&field) {
+                return None;
+            }
+
+            Some(match field.ident {
+                Some(ref i) => quote!{ self.#i.summarize(summary); },
+                None => quote!{ self.#field_n.summarize(summary); },
+            })
+        })
+    };
+
+    // Build the list of statements to put in the body of `inject_remote_protocols()`.
+    let inject_remote_protocols_stmts = {
+        data_struct.fields.iter().enumerate().filter_map(move |(field_n, field)| {
+            if is_ignored(&field) {
+                return None;
+            }
+
+            Some(match field.ident {
+                Some(ref i) => quote!{ self.#i.inject_remote_protocols(peer_id, protocols); },
+                None => quote!{ self.#field_n.inject_remote_protocols(peer_id, protocols); },
+            })
+        })
+    };
+
     // Build the list of variants to put in the body of `inject_node_event()`.
     //
     // The event type is a construction of nested `#either_ident`s of the events of the children.
@@ -305,6 +445,26 @@ fn build_struct(ast: &DeriveInput, data_struct: &DataStruct) -> TokenStream {
         })
     });
 
+    // Build the list of variants to put in the body of `inject_connection_event()`.
+    //
+    // Same as `inject_node_event()`, but calls `inject_connection_event` on the child.
+    let inject_connection_event_stmts = data_struct.fields.iter().enumerate().filter(|f| !is_ignored(&f.1)).enumerate().map(|(enum_n, (field_n, field))| {
+        let mut elem = if enum_n != 0 {
+            quote!{ #either_ident::Second(ev) }
+        } else {
+            quote!{ ev }
+        };
+
+        for _ in 0 .. data_struct.fields.iter().filter(|f| !is_ignored(f)).count() - 1 - field_n {
+            elem = quote!{ #either_ident::First(#elem) };
+        }
+
+        Some(match field.ident {
+            Some(ref i) => quote!{ #elem => self.#i.inject_connection_event(peer_id, connection, ev) },
+            None => quote!{ #elem => self.#field_n.inject_connection_event(peer_id, connection, ev) },
+        })
+    });
+
     // The `ProtocolsHandler` associated type.
     let protocols_handler_ty = {
         let mut ph_ty = None;
@@ -389,11 +549,17 @@ fn build_struct(ast: &DeriveInput, data_struct: &DataStruct) -> TokenStream {
             wrapped_event = quote!{ #either_ident::First(#wrapped_event) };
         }
 
+        let generate_event_stmt = if event_process {
+            quote!{ #net_behv_event_proc::inject_event(self, event) }
+        } else {
+            quote!{ return Async::Ready(#network_behaviour_action::GenerateEvent(event.into())) }
+        };
+
         Some(quote!{
             loop {
                 match #field_name.poll(poll_params) {
                     Async::Ready(#network_behaviour_action::GenerateEvent(event)) => {
-                        #net_behv_event_proc::inject_event(self, event)
+                        #generate_event_stmt
                     }
                     Async::Ready(#network_behaviour_action::DialAddress { address }) => {
                         return Async::Ready(#network_behaviour_action::DialAddress { address });
@@ -407,8 +573,30 @@ fn build_struct(ast: &DeriveInput, data_struct: &DataStruct) -> TokenStream {
                             event: #wrapped_event,
                         });
                     }
-                    Async::Ready(#network_behaviour_action::ReportObservedAddr { address }) => {
-                        return Async::Ready(#network_behaviour_action::ReportObservedAddr { address });
+                    Async::Ready(#network_behaviour_action::SendEventToConnection { peer_id, connection, event }) => {
+                        return Async::Ready(#network_behaviour_action::SendEventToConnection {
+                            peer_id,
+                            connection,
+                            event: #wrapped_event,
+                        });
+                    }
+                    Async::Ready(#network_behaviour_action::ReportObservedAddr { address, observer }) => {
+                        return Async::Ready(#network_behaviour_action::ReportObservedAddr { address, observer });
+                    }
+                    Async::Ready(#network_behaviour_action::ReportRemoteProtocols { peer_id, protocols }) => {
+                        return Async::Ready(#network_behaviour_action::ReportRemoteProtocols { peer_id, protocols });
+                    }
+                    Async::Ready(#network_behaviour_action::ReportRoundTripTime { peer_id, rtt }) => {
+                        return Async::Ready(#network_behaviour_action::ReportRoundTripTime { peer_id, rtt });
+                    }
+                    Async::Ready(#network_behaviour_action::ReportTransfer { peer_id, bytes_received, bytes_sent, duration }) => {
+                        return Async::Ready(#network_behaviour_action::ReportTransfer { peer_id, bytes_received, bytes_sent, duration });
+                    }
+                    Async::Ready(#network_behaviour_action::ReportExternalAddr { address, source }) => {
+                        return Async::Ready(#network_behaviour_action::ReportExternalAddr { address, source });
+                    }
+                    Async::Ready(#network_behaviour_action::RemoveExternalAddr { address }) => {
+                        return Async::Ready(#network_behaviour_action::RemoveExternalAddr { address });
                     }
                     Async::NotReady => break,
                 }
@@ -447,6 +635,14 @@ fn build_struct(ast: &DeriveInput, data_struct: &DataStruct) -> TokenStream {
                 #(#inject_replaced_stmts);*
             }
 
+            fn inject_connection_established(&mut self, peer_id: &#peer_id, connection: &#connection_id, endpoint: &#connected_point) {
+                #(#inject_connection_established_stmts);*
+            }
+
+            fn inject_connection_closed(&mut self, peer_id: &#peer_id, connection: &#connection_id, endpoint: &#connected_point) {
+                #(#inject_connection_closed_stmts);*
+            }
+
             fn inject_addr_reach_failure(&mut self, peer_id: Option<&#peer_id>, addr: &#multiaddr, error: &dyn std::error::Error) {
                 #(#inject_addr_reach_failure_stmts);*
             }
@@ -455,6 +651,10 @@ fn build_struct(ast: &DeriveInput, data_struct: &DataStruct) -> TokenStream {
                 #(#inject_dial_failure_stmts);*
             }
 
+            fn inject_banned_peer_connection(&mut self, peer_id: &#peer_id, endpoint: &#connected_point) {
+                #(#inject_banned_peer_connection_stmts);*
+            }
+
             fn inject_new_listen_addr(&mut self, addr: &#multiaddr) {
                 #(#inject_new_listen_addr_stmts);*
             }
@@ -463,10 +663,30 @@ fn build_struct(ast: &DeriveInput, data_struct: &DataStruct) -> TokenStream {
                 #(#inject_expired_listen_addr_stmts);*
             }
 
+            fn inject_listener_error(&mut self, err: &dyn std::error::Error) {
+                #(#inject_listener_error_stmts);*
+            }
+
             fn inject_new_external_addr(&mut self, addr: &#multiaddr) {
                 #(#inject_new_external_addr_stmts);*
             }
 
+            fn inject_network_change(&mut self, change: &#network_change) {
+                #(#inject_network_change_stmts);*
+            }
+
+            fn inject_shutdown(&mut self) {
+                #(#inject_shutdown_stmts);*
+            }
+
+            fn inject_remote_protocols(&mut self, peer_id: &#peer_id, protocols: &[String]) {
+                #(#inject_remote_protocols_stmts);*
+            }
+
+            fn summarize(&self, summary: &mut #behaviour_summary) {
+                #(#summarize_stmts);*
+            }
+
             fn inject_node_event(
                 &mut self,
                 peer_id: #peer_id,
@@ -477,6 +697,17 @@ fn build_struct(ast: &DeriveInput, data_struct: &DataStruct) -> TokenStream {
                 }
             }
 
+            fn inject_connection_event(
+                &mut self,
+                peer_id: #peer_id,
+                connection: #connection_id,
+                event: <<Self::ProtocolsHandler as #into_protocols_handler>::Handler as #protocols_handler>::OutEvent
+            ) {
+                match event {
+                    #(#inject_connection_event_stmts),*
+                }
+            }
+
             fn poll(&mut self, poll_params: &mut impl #poll_parameters) -> ::libp2p::futures::Async<#network_behaviour_action<<<Self::ProtocolsHandler as #into_protocols_handler>::Handler as #protocols_handler>::InEvent, Self::OutEvent>> {
                 use libp2p::futures::prelude::*;
                 #(#poll_stmts)*
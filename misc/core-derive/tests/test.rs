@@ -195,6 +195,40 @@ fn custom_event_and_polling() {
     }
 }
 
+#[test]
+fn custom_event_no_event_process() {
+    #[allow(dead_code)]
+    enum MyEvent {
+        Ping(libp2p::ping::PingEvent),
+        Identify(libp2p::identify::IdentifyEvent),
+    }
+
+    impl From<libp2p::ping::PingEvent> for MyEvent {
+        fn from(event: libp2p::ping::PingEvent) -> Self {
+            MyEvent::Ping(event)
+        }
+    }
+
+    impl From<libp2p::identify::IdentifyEvent> for MyEvent {
+        fn from(event: libp2p::identify::IdentifyEvent) -> Self {
+            MyEvent::Identify(event)
+        }
+    }
+
+    #[allow(dead_code)]
+    #[derive(NetworkBehaviour)]
+    #[behaviour(out_event = "MyEvent", event_process = false)]
+    struct Foo<TSubstream> {
+        ping: libp2p::ping::Ping<TSubstream>,
+        identify: libp2p::identify::Identify<TSubstream>,
+    }
+
+    #[allow(dead_code)]
+    fn foo<TSubstream: libp2p::tokio_io::AsyncRead + libp2p::tokio_io::AsyncWrite>() {
+        require_net_behaviour::<Foo<TSubstream>>();
+    }
+}
+
 #[test]
 fn where_clause() {
     #[allow(dead_code)]
@@ -0,0 +1,298 @@
+// Copyright 2019 Parity Technologies (UK) Ltd.
+//
+// Permission is hereby granted, free of charge, to any person obtaining a
+// copy of this software and associated documentation files (the "Software"),
+// to deal in the Software without restriction, including without limitation
+// the rights to use, copy, modify, merge, publish, distribute, sublicense,
+// and/or sell copies of the Software, and to permit persons to whom the
+// Software is furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS
+// OR IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING
+// FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
+// DEALINGS IN THE SOFTWARE.
+//! Handling of the control connections.
+
+use crate::{
+    framing::{self, read_message, write_message},
+    messages::{DhtRequest, DhtResponse, PubsubRequest, Request, Response, StreamInfo},
+    node::{Command, Context, Substream},
+};
+use futures::{future::{self, Either, Loop}, prelude::*, sync::{mpsc, oneshot}};
+use libp2p::{
+    Multiaddr, PeerId,
+    multiaddr::Protocol,
+    stream::{Control, RawStream},
+    tokio_io::{AsyncRead, AsyncWrite, io as tokio_io},
+};
+use log::debug;
+use std::{io, path::PathBuf, time::Duration};
+use tokio::{runtime::current_thread, timer::Timeout};
+use tokio_uds::UnixStream;
+
+/// How long the requests whose timeout isn't specified may take.
+const DEFAULT_TIMEOUT: Duration = Duration::from_secs(60);
+
+type BoxFuture<T, E = io::Error> = Box<dyn Future<Item = T, Error = E>>;
+
+/// Processes the requests of a control connection until it is closed, or until it is taken
+/// over by a substream or a subscription.
+pub(crate) fn handle_connection(context: Context, socket: UnixStream) -> impl Future<Item = (), Error = ()> {
+    future::loop_fn(socket, move |socket| {
+        let context = context.clone();
+        read_message(socket).and_then(move |(socket, message)| -> BoxFuture<Loop<(), UnixStream>> {
+            let message = match message {
+                Some(message) => message,
+                None => return Box::new(future::ok(Loop::Break(()))),
+            };
+            let request = match Request::decode(&message) {
+                Ok(request) => request,
+                Err(err) => {
+                    return Box::new(respond(socket, Response::Error(err.to_string())).map(Loop::Continue))
+                }
+            };
+            Box::new(process(context, socket, request).map(|socket| match socket {
+                Some(socket) => Loop::Continue(socket),
+                None => Loop::Break(()),
+            }))
+        })
+    })
+    .map_err(|err| debug!("Control connection closed: {}", err))
+}
+
+/// Processes a request. Produces the socket back if the connection is still a control
+/// connection.
+fn process(context: Context, socket: UnixStream, request: Request) -> BoxFuture<Option<UnixStream>> {
+    match request {
+        Request::Identify => {
+            let response = query(&context, Command::Identify).map(Response::Identify);
+            answer(socket, response, None)
+        }
+        Request::Connect { peer_id, addresses, timeout } => {
+            let response = query(&context, |reply| Command::Connect { peer_id, addresses, reply })
+                .and_then(|result| result.map(|()| Response::Ok));
+            answer(socket, response, timeout)
+        }
+        Request::StreamOpen { peer_id, protocols, timeout } => {
+            let opened = open_stream(context.streams.clone(), peer_id.clone(), protocols)
+                .and_then(move |(stream, protocol)| {
+                    peer_address(&context, peer_id.clone()).map(move |address| {
+                        (stream, StreamInfo { peer_id, address, protocol })
+                    })
+                });
+            let opened = Timeout::new(opened, timeout.unwrap_or(DEFAULT_TIMEOUT)).map_err(timeout_error);
+            Box::new(opened.then(move |result| -> BoxFuture<Option<UnixStream>> {
+                match result {
+                    Ok((stream, info)) => {
+                        let spliced = write_message(socket, &Response::StreamInfo(info).encode())
+                            .and_then(|(socket, _)| splice(socket, stream))
+                            .map(|()| None);
+                        Box::new(spliced)
+                    }
+                    Err(err) => respond(socket, Response::Error(err)),
+                }
+            }))
+        }
+        Request::StreamHandler { address, protocols } => {
+            let response = register_handler(&context, address, protocols).map(|()| Response::Ok);
+            respond(socket, response.unwrap_or_else(Response::Error))
+        }
+        Request::Dht { request, timeout } => {
+            if !context.dht {
+                return respond(socket, Response::Error("DHT not enabled".to_owned()))
+            }
+            let streamed = match request {
+                DhtRequest::FindProviders { .. } | DhtRequest::GetClosestPeers(_) => true,
+                _ => false,
+            };
+            let results = query(&context, |reply| Command::Dht { request, reply })
+                .and_then(|result| result);
+            let results = Timeout::new(results, timeout.unwrap_or(DEFAULT_TIMEOUT)).map_err(timeout_error);
+            Box::new(results.then(move |result| -> BoxFuture<Option<UnixStream>> {
+                let mut results = match result {
+                    Ok(results) => results,
+                    Err(err) => return respond(socket, Response::Error(err)),
+                };
+                if streamed {
+                    let mut out = Vec::new();
+                    framing::encode_frame(&mut out, &Response::Dht(DhtResponse::Begin).encode());
+                    for result in results {
+                        framing::encode_frame(&mut out, &result.encode());
+                    }
+                    framing::encode_frame(&mut out, &DhtResponse::End.encode());
+                    Box::new(tokio_io::write_all(socket, out).map(|(socket, _)| Some(socket)))
+                } else if let Some(result) = results.pop() {
+                    respond(socket, Response::Dht(result))
+                } else {
+                    respond(socket, Response::Ok)
+                }
+            }))
+        }
+        Request::ListPeers => {
+            let response = query(&context, Command::ListPeers).map(Response::Peers);
+            answer(socket, response, None)
+        }
+        Request::Disconnect { peer_id } => {
+            let _ = context.commands.unbounded_send(Command::Disconnect(peer_id));
+            respond(socket, Response::Ok)
+        }
+        Request::Pubsub(request) => {
+            if !context.pubsub {
+                return respond(socket, Response::Error("Pubsub not enabled".to_owned()))
+            }
+            match request {
+                PubsubRequest::GetTopics => {
+                    let response = query(&context, Command::GetTopics)
+                        .map(|topics| Response::Pubsub { topics, peers: Vec::new() });
+                    answer(socket, response, None)
+                }
+                PubsubRequest::ListPeers(topic) => {
+                    let response = query(&context, |reply| Command::ListTopicPeers { topic, reply })
+                        .map(|peers| Response::Pubsub { topics: Vec::new(), peers });
+                    answer(socket, response, None)
+                }
+                PubsubRequest::Publish { topic, data } => {
+                    let _ = context.commands.unbounded_send(Command::Publish { topic, data });
+                    respond(socket, Response::Ok)
+                }
+                PubsubRequest::Subscribe(topic) => {
+                    let (messages, messages_rx) = mpsc::unbounded();
+                    let _ = context.commands.unbounded_send(Command::Subscribe { topic, messages });
+                    let subscription = write_message(socket, &Response::Ok.encode())
+                        .and_then(move |(socket, _)| {
+                            let (reader, writer) = socket.split();
+                            // The subscription ends when the client closes the connection.
+                            let closed = tokio_io::read_to_end(reader, Vec::new()).map(|_| ());
+                            let forwarded = messages_rx
+                                .map_err(|()| io::Error::new(io::ErrorKind::Other, "the node has stopped"))
+                                .fold(writer, |writer, message| {
+                                    write_message(writer, &message.encode()).map(|(writer, _)| writer)
+                                })
+                                .map(|_| ());
+                            closed.select(forwarded).map(|_| None).map_err(|(err, _)| err)
+                        });
+                    Box::new(subscription)
+                }
+            }
+        }
+    }
+}
+
+/// Sends a command to the node and waits for its reply.
+fn query<T, F>(context: &Context, command: F) -> impl Future<Item = T, Error = String>
+where
+    F: FnOnce(oneshot::Sender<T>) -> Command,
+{
+    let (reply, reply_rx) = oneshot::channel();
+    let _ = context.commands.unbounded_send(command(reply));
+    reply_rx.map_err(|oneshot::Canceled| "The node has stopped".to_owned())
+}
+
+/// Writes the response produced by `response`, or an error if it fails or takes longer than
+/// `timeout`.
+fn answer<F>(socket: UnixStream, response: F, timeout: Option<Duration>) -> BoxFuture<Option<UnixStream>>
+where
+    F: Future<Item = Response, Error = String> + 'static,
+{
+    let response = Timeout::new(response, timeout.unwrap_or(DEFAULT_TIMEOUT)).map_err(timeout_error);
+    Box::new(response.then(move |response| respond(socket, response.unwrap_or_else(Response::Error))))
+}
+
+fn respond(socket: UnixStream, response: Response) -> BoxFuture<Option<UnixStream>> {
+    Box::new(write_message(socket, &response.encode()).map(|(socket, _)| Some(socket)))
+}
+
+fn timeout_error(err: tokio::timer::timeout::Error<String>) -> String {
+    if err.is_elapsed() {
+        "Timeout".to_owned()
+    } else {
+        err.into_inner().unwrap_or_else(|| "Timer error".to_owned())
+    }
+}
+
+/// Opens a substream to a peer with the first protocol it supports among `protocols`.
+fn open_stream(control: Control<Substream>, peer_id: PeerId, protocols: Vec<String>)
+    -> impl Future<Item = (RawStream<Substream>, String), Error = String>
+{
+    future::loop_fn((protocols.into_iter(), None), move |(mut protocols, last_error)| {
+        match protocols.next() {
+            Some(protocol) => {
+                let opened = control.open_stream(peer_id.clone(), protocol.clone().into_bytes())
+                    .then(move |result| match result {
+                        Ok(stream) => Ok(Loop::Break((stream, protocol))),
+                        Err(err) => Ok(Loop::Continue((protocols, Some(err.to_string())))),
+                    });
+                Either::A(opened)
+            }
+            None => Either::B(future::err(last_error.unwrap_or_else(|| "No protocol given".to_owned()))),
+        }
+    })
+}
+
+/// Returns the address of a connection to a peer.
+fn peer_address(context: &Context, peer_id: PeerId) -> impl Future<Item = Multiaddr, Error = String> {
+    query(context, Command::ListPeers).and_then(move |peers| {
+        peers.into_iter()
+            .find(|info| info.peer_id == peer_id)
+            .and_then(|info| info.addresses.into_iter().next())
+            .ok_or_else(|| format!("Not connected to {}", peer_id))
+    })
+}
+
+/// Registers protocols whose inbound substreams are forwarded to the unix socket at `address`.
+fn register_handler(context: &Context, address: Multiaddr, protocols: Vec<String>) -> Result<(), String> {
+    let path = match address.iter().collect::<Vec<_>>().as_slice() {
+        [Protocol::Unix(path)] => PathBuf::from(path.to_string()),
+        _ => return Err(format!("Unsupported handler address {}", address)),
+    };
+
+    let mut registered = Vec::with_capacity(protocols.len());
+    for protocol in protocols {
+        match context.streams.accept(protocol.clone()) {
+            Ok(incoming) => registered.push((protocol, incoming)),
+            Err(err) => return Err(format!("{}: {}", protocol, err)),
+        }
+    }
+
+    for (protocol, incoming) in registered {
+        let context = context.clone();
+        let path = path.clone();
+        let forward = incoming.for_each(move |(peer_id, stream)| {
+            let protocol = protocol.clone();
+            let path = path.clone();
+            let forwarded = peer_address(&context, peer_id.clone())
+                .map_err(|err| io::Error::new(io::ErrorKind::Other, err))
+                .and_then(move |address| {
+                    let info = StreamInfo { peer_id, address, protocol };
+                    UnixStream::connect(&path).and_then(move |socket| {
+                        write_message(socket, &info.encode())
+                    })
+                })
+                .and_then(move |(socket, _)| splice(socket, stream))
+                .map_err(|err| debug!("Failed to forward an inbound substream: {}", err));
+            current_thread::spawn(forwarded);
+            Ok(())
+        });
+        current_thread::spawn(forward.map_err(|err| void::unreachable(err)));
+    }
+    Ok(())
+}
+
+/// Copies the data of each socket to the other one, until both are closed.
+fn splice<A, B>(a: A, b: B) -> impl Future<Item = (), Error = io::Error>
+where
+    A: AsyncRead + AsyncWrite,
+    B: AsyncRead + AsyncWrite,
+{
+    let (a_read, a_write) = a.split();
+    let (b_read, b_write) = b.split();
+    let forward = tokio_io::copy(a_read, b_write).and_then(|(_, _, b_write)| tokio_io::shutdown(b_write));
+    let backward = tokio_io::copy(b_read, a_write).and_then(|(_, _, a_write)| tokio_io::shutdown(a_write));
+    forward.join(backward).map(|_| ())
+}
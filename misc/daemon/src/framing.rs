@@ -0,0 +1,147 @@
+// Copyright 2019 Parity Technologies (UK) Ltd.
+//
+// Permission is hereby granted, free of charge, to any person obtaining a
+// copy of this software and associated documentation files (the "Software"),
+// to deal in the Software without restriction, including without limitation
+// the rights to use, copy, modify, merge, publish, distribute, sublicense,
+// and/or sell copies of the Software, and to permit persons to whom the
+// Software is furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS
+// OR IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING
+// FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
+// DEALINGS IN THE SOFTWARE.
+//! Reading and writing the length-prefixed messages of the control connections.
+//!
+//! After some requests, the control connection carries raw data or a stream of messages.
+//! Messages are therefore read without buffering anything past their end.
+
+use futures::{prelude::*, try_ready};
+use std::{io, mem};
+use libp2p::tokio_io::{AsyncRead, AsyncWrite, io::{WriteAll, write_all}};
+
+/// Maximum size in bytes of a message.
+pub const MAX_MESSAGE_SIZE: usize = 4 * 1024 * 1024;
+
+/// Appends `message` to `out`, preceded by its length.
+pub fn encode_frame(out: &mut Vec<u8>, message: &[u8]) {
+    let mut buf = unsigned_varint::encode::u64_buffer();
+    out.extend_from_slice(unsigned_varint::encode::u64(message.len() as u64, &mut buf));
+    out.extend_from_slice(message);
+}
+
+/// Writes a message, preceded by its length.
+pub fn write_message<TSocket: AsyncWrite>(socket: TSocket, message: &[u8]) -> WriteAll<TSocket, Vec<u8>> {
+    let mut out = Vec::with_capacity(message.len() + 4);
+    encode_frame(&mut out, message);
+    write_all(socket, out)
+}
+
+/// Reads a message preceded by its length. Produces `None` if the socket is closed before
+/// the first byte of the message.
+pub fn read_message<TSocket: AsyncRead>(socket: TSocket) -> ReadMessage<TSocket> {
+    ReadMessage {
+        socket: Some(socket),
+        len_buf: [0; 10],
+        len_pos: 0,
+        body: Vec::new(),
+        body_pos: None,
+    }
+}
+
+/// Future returned by [`read_message`].
+#[derive(Debug)]
+pub struct ReadMessage<TSocket> {
+    socket: Option<TSocket>,
+    len_buf: [u8; 10],
+    len_pos: usize,
+    body: Vec<u8>,
+    /// Number of bytes of the body read so far, or `None` while reading the length.
+    body_pos: Option<usize>,
+}
+
+impl<TSocket: AsyncRead> Future for ReadMessage<TSocket> {
+    type Item = (TSocket, Option<Vec<u8>>);
+    type Error = io::Error;
+
+    fn poll(&mut self) -> Poll<Self::Item, io::Error> {
+        loop {
+            let socket = self.socket.as_mut().expect("future polled after completion");
+
+            if let Some(body_pos) = self.body_pos {
+                if body_pos == self.body.len() {
+                    let socket = self.socket.take().expect("checked above");
+                    return Ok(Async::Ready((socket, Some(mem::replace(&mut self.body, Vec::new())))))
+                }
+                let num_read = try_ready!(socket.poll_read(&mut self.body[body_pos..]));
+                if num_read == 0 {
+                    return Err(io::ErrorKind::UnexpectedEof.into())
+                }
+                self.body_pos = Some(body_pos + num_read);
+                continue
+            }
+
+            // The length is read one byte at a time, so that we never read past it.
+            let mut byte = [0];
+            if try_ready!(socket.poll_read(&mut byte)) == 0 {
+                if self.len_pos == 0 {
+                    let socket = self.socket.take().expect("checked above");
+                    return Ok(Async::Ready((socket, None)))
+                }
+                return Err(io::ErrorKind::UnexpectedEof.into())
+            }
+            self.len_buf[self.len_pos] = byte[0];
+            self.len_pos += 1;
+
+            if byte[0] & 0x80 == 0 {
+                let (len, _) = unsigned_varint::decode::u64(&self.len_buf[..self.len_pos])
+                    .map_err(|_| io::Error::new(io::ErrorKind::InvalidData, "invalid message length"))?;
+                if len > MAX_MESSAGE_SIZE as u64 {
+                    return Err(io::Error::new(io::ErrorKind::InvalidData, "message too large"))
+                }
+                self.body = vec![0; len as usize];
+                self.body_pos = Some(0);
+            } else if self.len_pos == self.len_buf.len() {
+                return Err(io::Error::new(io::ErrorKind::InvalidData, "invalid message length"))
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+
+    #[test]
+    fn read_messages_back_to_back() {
+        let mut data = Vec::new();
+        encode_frame(&mut data, b"hello");
+        encode_frame(&mut data, &[7; 300]);
+        data.extend_from_slice(b"raw");
+
+        let (socket, first) = read_message(Cursor::new(data)).wait().unwrap();
+        assert_eq!(first, Some(b"hello".to_vec()));
+        let (socket, second) = read_message(socket).wait().unwrap();
+        assert_eq!(second, Some(vec![7; 300]));
+        // The data that follows the messages is left untouched.
+        assert_eq!(&socket.get_ref()[socket.position() as usize..], b"raw");
+    }
+
+    #[test]
+    fn end_of_stream() {
+        let (_, message) = read_message(Cursor::new(Vec::new())).wait().unwrap();
+        assert_eq!(message, None);
+
+        let mut data = Vec::new();
+        encode_frame(&mut data, b"hello");
+        data.truncate(3);
+        assert!(read_message(Cursor::new(data)).wait().is_err());
+    }
+}
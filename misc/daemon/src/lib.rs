@@ -0,0 +1,195 @@
+// Copyright 2019 Parity Technologies (UK) Ltd.
+//
+// Permission is hereby granted, free of charge, to any person obtaining a
+// copy of this software and associated documentation files (the "Software"),
+// to deal in the Software without restriction, including without limitation
+// the rights to use, copy, modify, merge, publish, distribute, sublicense,
+// and/or sell copies of the Software, and to permit persons to whom the
+// Software is furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS
+// OR IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING
+// FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
+// DEALINGS IN THE SOFTWARE.
+//! A standalone libp2p node, driven through the control protocol of the
+//! [libp2p daemon](https://github.com/libp2p/go-libp2p-daemon).
+//!
+//! Applications that aren't written in Rust connect to the unix socket of the daemon and send
+//! it requests, as described in `p2pd.proto`. Each message is preceded by its length, as an
+//! unsigned varint. The daemon answers each request with a `Response`, and the control
+//! connection can then be used for further requests, except in the following cases:
+//!
+//! - After answering a `STREAM_OPEN` request, the control connection carries the data of the
+//!   substream that has been opened.
+//! - After answering a `SUBSCRIBE` pubsub request, the daemon sends on the control connection
+//!   the messages published on the topic, as `PSMessage`s, until the client closes it.
+//! - `FIND_PROVIDERS` and `GET_CLOSEST_PEERS` DHT requests are answered with a `BEGIN` DHT
+//!   response, followed by a `DHTResponse` for each peer and by an `END` `DHTResponse`.
+//!
+//! The substreams of the protocols registered with a `STREAM_HANDLER` request are forwarded
+//! to new connections to the unix socket given in the request, each of them starting with a
+//! `StreamInfo` message.
+//!
+//! The node supports TCP, DNS and WebSockets, secio, and mplex and yamux. It always runs
+//! identify and ping, and runs Kademlia and gossipsub if enabled in the [`DaemonConfig`].
+//! Since the records of Kademlia are keyed by multihashes, the keys of the `GET_VALUE` and
+//! `PUT_VALUE` requests are wrapped in an identity multihash.
+//!
+//! The connection manager and peerstore requests, as well as the `SEARCH_VALUE` and
+//! `FIND_PEERS_CONNECTED_TO_PEER` DHT requests, aren't supported and are answered with an
+//! error.
+
+mod control;
+mod framing;
+mod messages;
+mod node;
+
+/// Protobuf messages of the control protocol, generated from `p2pd.proto`.
+mod proto {
+    include!(concat!(env!("OUT_DIR"), "/p2pd.pb.rs"));
+}
+
+/// Protobuf encoding of the keys, generated from the `keys.proto` of `libp2p-core`.
+mod keys_proto {
+    include!(concat!(env!("OUT_DIR"), "/keys_proto.rs"));
+}
+
+pub use framing::{MAX_MESSAGE_SIZE, ReadMessage, encode_frame, read_message, write_message};
+pub use prost::DecodeError;
+pub use messages::{
+    DhtRequest, DhtResponse, PeerInfo, PubsubMessage, PubsubRequest, Request, Response, StreamInfo
+};
+
+use futures::{future, prelude::*};
+use libp2p::{Multiaddr, identity::{Keypair, ed25519}};
+use std::{fs, io, os::unix::fs::FileTypeExt, path::PathBuf};
+use tokio::runtime::current_thread;
+use tokio_uds::UnixListener;
+
+/// Configuration of a daemon.
+#[derive(Clone)]
+pub struct DaemonConfig {
+    pub(crate) keypair: Keypair,
+    pub(crate) socket: PathBuf,
+    pub(crate) listen_addresses: Vec<Multiaddr>,
+    pub(crate) bootstrap_peers: Vec<Multiaddr>,
+    pub(crate) dht: bool,
+    pub(crate) pubsub: bool,
+}
+
+impl DaemonConfig {
+    /// Creates a configuration for a node with the given identity.
+    ///
+    /// By default, the control socket is `/tmp/p2pd.sock`, the node listens on a random TCP
+    /// port, and Kademlia and gossipsub are disabled.
+    pub fn new(keypair: Keypair) -> Self {
+        DaemonConfig {
+            keypair,
+            socket: PathBuf::from("/tmp/p2pd.sock"),
+            listen_addresses: vec!["/ip4/0.0.0.0/tcp/0".parse().expect("valid multiaddress")],
+            bootstrap_peers: Vec::new(),
+            dht: false,
+            pubsub: false,
+        }
+    }
+
+    /// Sets the path of the unix socket the control connections are accepted on.
+    pub fn with_socket(mut self, path: impl Into<PathBuf>) -> Self {
+        self.socket = path.into();
+        self
+    }
+
+    /// Sets the addresses the node listens on.
+    pub fn with_listen_addresses(mut self, addresses: Vec<Multiaddr>) -> Self {
+        self.listen_addresses = addresses;
+        self
+    }
+
+    /// Sets the peers connected to at startup, whose addresses must end with
+    /// `/p2p/<peer ID>`. They bootstrap the DHT if it is enabled.
+    pub fn with_bootstrap_peers(mut self, addresses: Vec<Multiaddr>) -> Self {
+        self.bootstrap_peers = addresses;
+        self
+    }
+
+    /// Enables or disables Kademlia.
+    pub fn with_dht(mut self, enabled: bool) -> Self {
+        self.dht = enabled;
+        self
+    }
+
+    /// Enables or disables gossipsub.
+    pub fn with_pubsub(mut self, enabled: bool) -> Self {
+        self.pubsub = enabled;
+        self
+    }
+}
+
+/// Runs a daemon. The future only finishes if the node or the control socket fails.
+///
+/// The control connections are spawned as tasks with `tokio::runtime::current_thread::spawn`,
+/// so the future must run on a current thread runtime.
+pub fn run(config: DaemonConfig) -> impl Future<Item = (), Error = io::Error> {
+    future::lazy(move || -> io::Result<_> {
+        let (node, context) = node::build(&config)?;
+        // A socket left over by a previous daemon would make binding fail.
+        if let Ok(metadata) = fs::symlink_metadata(&config.socket) {
+            if metadata.file_type().is_socket() {
+                fs::remove_file(&config.socket)?;
+            }
+        }
+        let listener = UnixListener::bind(&config.socket)?;
+        let accept = listener.incoming().for_each(move |socket| {
+            current_thread::spawn(control::handle_connection(context.clone(), socket));
+            Ok(())
+        });
+        Ok(accept.select(node).map(|_| ()).map_err(|(err, _)| err))
+    })
+    .flatten()
+}
+
+/// Decodes a private key in the protobuf format of the other libp2p implementations, as read
+/// from the file given to the `-id` flag of the daemons. Only Ed25519 keys are supported.
+pub fn decode_keypair(bytes: &[u8]) -> Result<Keypair, DecodeError> {
+    use prost::Message;
+
+    let mut key = keys_proto::PrivateKey::decode(bytes)?;
+    if key.r#type != keys_proto::KeyType::Ed25519 as i32 {
+        return Err(DecodeError::new("only Ed25519 keys are supported"))
+    }
+    ed25519::Keypair::decode(&mut key.data)
+        .map(Keypair::Ed25519)
+        .map_err(|_| DecodeError::new("invalid Ed25519 key"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use prost::Message;
+
+    #[test]
+    fn decode_ed25519_keypair() {
+        let encode = |key: keys_proto::PrivateKey| {
+            let mut bytes = Vec::new();
+            key.encode(&mut bytes).unwrap();
+            bytes
+        };
+
+        let keypair = ed25519::Keypair::generate();
+        let bytes = encode(keys_proto::PrivateKey {
+            r#type: keys_proto::KeyType::Ed25519 as i32,
+            data: keypair.encode().to_vec(),
+        });
+        let decoded = decode_keypair(&bytes).unwrap();
+        assert_eq!(decoded.public(), libp2p::identity::PublicKey::Ed25519(keypair.public()));
+
+        let rsa = encode(keys_proto::PrivateKey { r#type: keys_proto::KeyType::Rsa as i32, data: vec![0; 16] });
+        assert!(decode_keypair(&rsa).is_err());
+    }
+}
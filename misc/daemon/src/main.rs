@@ -0,0 +1,112 @@
+// Copyright 2019 Parity Technologies (UK) Ltd.
+//
+// Permission is hereby granted, free of charge, to any person obtaining a
+// copy of this software and associated documentation files (the "Software"),
+// to deal in the Software without restriction, including without limitation
+// the rights to use, copy, modify, merge, publish, distribute, sublicense,
+// and/or sell copies of the Software, and to permit persons to whom the
+// Software is furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS
+// OR IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING
+// FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
+// DEALINGS IN THE SOFTWARE.
+//! The `p2pd` binary. It accepts the following flags of the go daemon, as `-flag value`,
+//! `-flag=value`, or `-flag` alone for the boolean ones:
+//!
+//! - `-sock`: path of the control socket, `/tmp/p2pd.sock` by default;
+//! - `-hostAddrs`: comma-separated addresses the node listens on;
+//! - `-bootstrapPeers`: comma-separated addresses of peers to connect to at startup;
+//! - `-id`: file containing the private key of the node, in the protobuf format;
+//! - `-dht` and `-pubsub`: enable Kademlia and gossipsub.
+
+use libp2p::{Multiaddr, identity::Keypair};
+use libp2p_daemon::{DaemonConfig, decode_keypair, run};
+use std::{env, fs, process};
+
+fn main() {
+    env_logger::init();
+
+    let config = match parse_args(env::args().skip(1)) {
+        Ok(config) => config,
+        Err(err) => {
+            eprintln!("{}", err);
+            process::exit(2);
+        }
+    };
+
+    if let Err(err) = tokio::runtime::current_thread::block_on_all(run(config)) {
+        eprintln!("p2pd failed: {}", err);
+        process::exit(1);
+    }
+}
+
+fn parse_args(mut args: impl Iterator<Item = String>) -> Result<DaemonConfig, String> {
+    let mut socket = None;
+    let mut listen_addresses = None;
+    let mut bootstrap_peers = Vec::new();
+    let mut key_file = None;
+    let mut dht = false;
+    let mut pubsub = false;
+
+    while let Some(arg) = args.next() {
+        let arg = arg.trim_start_matches('-').to_owned();
+        let (name, inline_value) = match arg.find('=') {
+            Some(pos) => (arg[..pos].to_owned(), Some(arg[pos + 1..].to_owned())),
+            None => (arg, None),
+        };
+        let mut value = || inline_value.clone()
+            .or_else(|| args.next())
+            .ok_or_else(|| format!("Missing value of -{}", name));
+        match name.as_str() {
+            "sock" => socket = Some(value()?),
+            "hostAddrs" => listen_addresses = Some(parse_addresses(&value()?)?),
+            "bootstrapPeers" => bootstrap_peers = parse_addresses(&value()?)?,
+            "id" => key_file = Some(value()?),
+            "dht" => dht = parse_bool(inline_value.as_ref())?,
+            "pubsub" => pubsub = parse_bool(inline_value.as_ref())?,
+            _ => return Err(format!("Unknown flag -{}", name)),
+        }
+    }
+
+    let keypair = match key_file {
+        Some(path) => {
+            let bytes = fs::read(&path).map_err(|err| format!("Failed to read {}: {}", path, err))?;
+            decode_keypair(&bytes).map_err(|err| format!("Failed to decode {}: {}", path, err))?
+        }
+        None => Keypair::generate_ed25519(),
+    };
+
+    let mut config = DaemonConfig::new(keypair)
+        .with_bootstrap_peers(bootstrap_peers)
+        .with_dht(dht)
+        .with_pubsub(pubsub);
+    if let Some(socket) = socket {
+        config = config.with_socket(socket);
+    }
+    if let Some(addresses) = listen_addresses {
+        config = config.with_listen_addresses(addresses);
+    }
+    Ok(config)
+}
+
+fn parse_addresses(value: &str) -> Result<Vec<Multiaddr>, String> {
+    value.split(',')
+        .filter(|s| !s.is_empty())
+        .map(|s| s.parse().map_err(|err| format!("Invalid address {}: {}", s, err)))
+        .collect()
+}
+
+fn parse_bool(value: Option<&String>) -> Result<bool, String> {
+    match value.map(|s| s.as_str()) {
+        None | Some("true") => Ok(true),
+        Some("false") => Ok(false),
+        Some(other) => Err(format!("Invalid boolean {}", other)),
+    }
+}
@@ -0,0 +1,632 @@
+// Copyright 2019 Parity Technologies (UK) Ltd.
+//
+// Permission is hereby granted, free of charge, to any person obtaining a
+// copy of this software and associated documentation files (the "Software"),
+// to deal in the Software without restriction, including without limitation
+// the rights to use, copy, modify, merge, publish, distribute, sublicense,
+// and/or sell copies of the Software, and to permit persons to whom the
+// Software is furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS
+// OR IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING
+// FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
+// DEALINGS IN THE SOFTWARE.
+//! Messages of the daemon control protocol and their encoding.
+//!
+//! Every message is preceded on the control socket by its length, as an unsigned varint.
+
+use crate::proto;
+use libp2p::{Multiaddr, PeerId};
+use prost::{DecodeError, Message};
+use std::{convert::TryFrom, time::Duration};
+
+/// Identity and addresses of a peer.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PeerInfo {
+    pub peer_id: PeerId,
+    pub addresses: Vec<Multiaddr>,
+}
+
+/// Description of a substream, sent to the client along with the substream.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StreamInfo {
+    /// Peer at the other end of the substream.
+    pub peer_id: PeerId,
+    /// Address of the connection the substream belongs to.
+    pub address: Multiaddr,
+    /// Protocol negotiated on the substream.
+    pub protocol: String,
+}
+
+/// A request sent by a client of the daemon.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Request {
+    /// Asks for the identity and listen addresses of the daemon.
+    Identify,
+    /// Connects to a peer at the given addresses, or at the ones already known.
+    Connect { peer_id: PeerId, addresses: Vec<Multiaddr>, timeout: Option<Duration> },
+    /// Opens a substream to a peer with the first of the protocols it supports. Once answered,
+    /// the control connection carries the data of the substream.
+    StreamOpen { peer_id: PeerId, protocols: Vec<String>, timeout: Option<Duration> },
+    /// Registers protocols, whose inbound substreams are forwarded to new connections to
+    /// `address`.
+    StreamHandler { address: Multiaddr, protocols: Vec<String> },
+    /// Performs an operation on the DHT.
+    Dht { request: DhtRequest, timeout: Option<Duration> },
+    /// Asks for the peers the daemon is connected to.
+    ListPeers,
+    /// Closes all connections to a peer.
+    Disconnect { peer_id: PeerId },
+    /// Performs an operation on pubsub.
+    Pubsub(PubsubRequest),
+}
+
+/// An operation on the DHT.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DhtRequest {
+    FindPeer(PeerId),
+    FindProviders { cid: Vec<u8>, count: Option<u32> },
+    GetClosestPeers(Vec<u8>),
+    GetPublicKey(PeerId),
+    GetValue(Vec<u8>),
+    PutValue { key: Vec<u8>, value: Vec<u8> },
+    Provide(Vec<u8>),
+}
+
+/// An operation on pubsub.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PubsubRequest {
+    /// Asks for the topics the daemon is subscribed to.
+    GetTopics,
+    /// Asks for the peers subscribed to a topic.
+    ListPeers(String),
+    Publish { topic: String, data: Vec<u8> },
+    /// Subscribes to a topic. Once answered, the control connection carries the messages
+    /// published on the topic, as [`PubsubMessage`]s.
+    Subscribe(String),
+}
+
+/// A response of the daemon to a [`Request`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Response {
+    Ok,
+    Error(String),
+    Identify(PeerInfo),
+    StreamInfo(StreamInfo),
+    Peers(Vec<PeerInfo>),
+    Dht(DhtResponse),
+    Pubsub { topics: Vec<String>, peers: Vec<PeerId> },
+}
+
+/// A result of a DHT operation.
+///
+/// Operations that produce several values answer with a `Response::Dht(DhtResponse::Begin)`,
+/// followed by the values and by `DhtResponse::End`, each as a message of its own.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DhtResponse {
+    Begin,
+    Peer(PeerInfo),
+    Value(Vec<u8>),
+    End,
+}
+
+/// A message received on a topic the client subscribed to.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PubsubMessage {
+    pub source: PeerId,
+    pub data: Vec<u8>,
+    pub sequence_number: Vec<u8>,
+    pub topics: Vec<String>,
+}
+
+const IDENTIFY: i32 = 0;
+const CONNECT: i32 = 1;
+const STREAM_OPEN: i32 = 2;
+const STREAM_HANDLER: i32 = 3;
+const DHT: i32 = 4;
+const LIST_PEERS: i32 = 5;
+const DISCONNECT: i32 = 7;
+const PUBSUB: i32 = 8;
+
+const FIND_PEER: i32 = 0;
+const FIND_PROVIDERS: i32 = 2;
+const GET_CLOSEST_PEERS: i32 = 3;
+const GET_PUBLIC_KEY: i32 = 4;
+const GET_VALUE: i32 = 5;
+const PUT_VALUE: i32 = 7;
+const PROVIDE: i32 = 8;
+
+const PS_GET_TOPICS: i32 = 0;
+const PS_LIST_PEERS: i32 = 1;
+const PS_PUBLISH: i32 = 2;
+const PS_SUBSCRIBE: i32 = 3;
+
+const OK: i32 = 0;
+const ERROR: i32 = 1;
+
+const DHT_BEGIN: i32 = 0;
+const DHT_VALUE: i32 = 1;
+const DHT_END: i32 = 2;
+
+impl Request {
+    /// Encodes the request, without its length prefix.
+    pub fn encode(&self) -> Vec<u8> {
+        let mut message = proto::Request::default();
+        match self {
+            Request::Identify => message.r#type = IDENTIFY,
+            Request::Connect { peer_id, addresses, timeout } => {
+                message.r#type = CONNECT;
+                message.connect = Some(proto::ConnectRequest {
+                    peer: peer_id.as_bytes().to_vec(),
+                    addrs: addresses.iter().map(|addr| addr.to_vec()).collect(),
+                    timeout: encode_timeout(*timeout),
+                });
+            }
+            Request::StreamOpen { peer_id, protocols, timeout } => {
+                message.r#type = STREAM_OPEN;
+                message.stream_open = Some(proto::StreamOpenRequest {
+                    peer: peer_id.as_bytes().to_vec(),
+                    proto: protocols.clone(),
+                    timeout: encode_timeout(*timeout),
+                });
+            }
+            Request::StreamHandler { address, protocols } => {
+                message.r#type = STREAM_HANDLER;
+                message.stream_handler = Some(proto::StreamHandlerRequest {
+                    addr: address.to_vec(),
+                    proto: protocols.clone(),
+                });
+            }
+            Request::Dht { request, timeout } => {
+                message.r#type = DHT;
+                let mut body = proto::DhtRequest {
+                    timeout: encode_timeout(*timeout),
+                    ..Default::default()
+                };
+                match request {
+                    DhtRequest::FindPeer(peer_id) => {
+                        body.r#type = FIND_PEER;
+                        body.peer = Some(peer_id.as_bytes().to_vec());
+                    }
+                    DhtRequest::FindProviders { cid, count } => {
+                        body.r#type = FIND_PROVIDERS;
+                        body.cid = Some(cid.clone());
+                        body.count = count.map(|count| count as i32);
+                    }
+                    DhtRequest::GetClosestPeers(key) => {
+                        body.r#type = GET_CLOSEST_PEERS;
+                        body.key = Some(key.clone());
+                    }
+                    DhtRequest::GetPublicKey(peer_id) => {
+                        body.r#type = GET_PUBLIC_KEY;
+                        body.peer = Some(peer_id.as_bytes().to_vec());
+                    }
+                    DhtRequest::GetValue(key) => {
+                        body.r#type = GET_VALUE;
+                        body.key = Some(key.clone());
+                    }
+                    DhtRequest::PutValue { key, value } => {
+                        body.r#type = PUT_VALUE;
+                        body.key = Some(key.clone());
+                        body.value = Some(value.clone());
+                    }
+                    DhtRequest::Provide(cid) => {
+                        body.r#type = PROVIDE;
+                        body.cid = Some(cid.clone());
+                    }
+                }
+                message.dht = Some(body);
+            }
+            Request::ListPeers => message.r#type = LIST_PEERS,
+            Request::Disconnect { peer_id } => {
+                message.r#type = DISCONNECT;
+                message.disconnect = Some(proto::DisconnectRequest { peer: peer_id.as_bytes().to_vec() });
+            }
+            Request::Pubsub(request) => {
+                message.r#type = PUBSUB;
+                let body = match request {
+                    PubsubRequest::GetTopics => proto::PsRequest {
+                        r#type: PS_GET_TOPICS,
+                        ..Default::default()
+                    },
+                    PubsubRequest::ListPeers(topic) => proto::PsRequest {
+                        r#type: PS_LIST_PEERS,
+                        topic: Some(topic.clone()),
+                        data: None,
+                    },
+                    PubsubRequest::Publish { topic, data } => proto::PsRequest {
+                        r#type: PS_PUBLISH,
+                        topic: Some(topic.clone()),
+                        data: Some(data.clone()),
+                    },
+                    PubsubRequest::Subscribe(topic) => proto::PsRequest {
+                        r#type: PS_SUBSCRIBE,
+                        topic: Some(topic.clone()),
+                        data: None,
+                    },
+                };
+                message.pubsub = Some(body);
+            }
+        }
+        encode_message(&message)
+    }
+
+    /// Decodes a request, without its length prefix.
+    ///
+    /// The connection manager and peerstore requests, as well as the `SEARCH_VALUE` and
+    /// `FIND_PEERS_CONNECTED_TO_PEER` DHT requests, aren't supported and produce an error.
+    pub fn decode(bytes: &[u8]) -> Result<Self, DecodeError> {
+        let message = proto::Request::decode(bytes)?;
+        match message.r#type {
+            IDENTIFY => Ok(Request::Identify),
+            CONNECT => {
+                let body = message.connect.ok_or_else(missing_body)?;
+                Ok(Request::Connect {
+                    peer_id: decode_peer_id(body.peer)?,
+                    addresses: body.addrs.into_iter().map(decode_multiaddr).collect::<Result<_, _>>()?,
+                    timeout: decode_timeout(body.timeout),
+                })
+            }
+            STREAM_OPEN => {
+                let body = message.stream_open.ok_or_else(missing_body)?;
+                Ok(Request::StreamOpen {
+                    peer_id: decode_peer_id(body.peer)?,
+                    protocols: body.proto,
+                    timeout: decode_timeout(body.timeout),
+                })
+            }
+            STREAM_HANDLER => {
+                let body = message.stream_handler.ok_or_else(missing_body)?;
+                Ok(Request::StreamHandler { address: decode_multiaddr(body.addr)?, protocols: body.proto })
+            }
+            DHT => {
+                let body = message.dht.ok_or_else(missing_body)?;
+                let timeout = decode_timeout(body.timeout);
+                let missing_cid = || DecodeError::new("missing CID");
+                let missing_key = || DecodeError::new("missing key");
+                let missing_peer = || DecodeError::new("missing peer ID");
+                let request = match body.r#type {
+                    FIND_PEER => DhtRequest::FindPeer(decode_peer_id(body.peer.ok_or_else(missing_peer)?)?),
+                    FIND_PROVIDERS => DhtRequest::FindProviders {
+                        cid: body.cid.ok_or_else(missing_cid)?,
+                        count: body.count.filter(|c| *c > 0).map(|c| c as u32),
+                    },
+                    GET_CLOSEST_PEERS => DhtRequest::GetClosestPeers(body.key.ok_or_else(missing_key)?),
+                    GET_PUBLIC_KEY => DhtRequest::GetPublicKey(decode_peer_id(body.peer.ok_or_else(missing_peer)?)?),
+                    GET_VALUE => DhtRequest::GetValue(body.key.ok_or_else(missing_key)?),
+                    PUT_VALUE => DhtRequest::PutValue {
+                        key: body.key.ok_or_else(missing_key)?,
+                        value: body.value.ok_or_else(|| DecodeError::new("missing value"))?,
+                    },
+                    PROVIDE => DhtRequest::Provide(body.cid.ok_or_else(missing_cid)?),
+                    _ => return Err(DecodeError::new("unsupported DHT request")),
+                };
+                Ok(Request::Dht { request, timeout })
+            }
+            LIST_PEERS => Ok(Request::ListPeers),
+            DISCONNECT => {
+                let body = message.disconnect.ok_or_else(missing_body)?;
+                Ok(Request::Disconnect { peer_id: decode_peer_id(body.peer)? })
+            }
+            PUBSUB => {
+                let body = message.pubsub.ok_or_else(missing_body)?;
+                let missing_topic = || DecodeError::new("missing topic");
+                let request = match body.r#type {
+                    PS_GET_TOPICS => PubsubRequest::GetTopics,
+                    PS_LIST_PEERS => PubsubRequest::ListPeers(body.topic.ok_or_else(missing_topic)?),
+                    PS_PUBLISH => PubsubRequest::Publish {
+                        topic: body.topic.ok_or_else(missing_topic)?,
+                        data: body.data.unwrap_or_default(),
+                    },
+                    PS_SUBSCRIBE => PubsubRequest::Subscribe(body.topic.ok_or_else(missing_topic)?),
+                    _ => return Err(DecodeError::new("unknown pubsub request type")),
+                };
+                Ok(Request::Pubsub(request))
+            }
+            _ => Err(DecodeError::new("unsupported request")),
+        }
+    }
+}
+
+impl Response {
+    /// Encodes the response, without its length prefix.
+    pub fn encode(&self) -> Vec<u8> {
+        let mut message = proto::Response { r#type: OK, ..Default::default() };
+        match self {
+            Response::Ok => {}
+            Response::Error(msg) => {
+                message.r#type = ERROR;
+                message.error = Some(proto::ErrorResponse { msg: msg.clone() });
+            }
+            Response::Identify(info) => {
+                message.identify = Some(proto::IdentifyResponse {
+                    id: info.peer_id.as_bytes().to_vec(),
+                    addrs: info.addresses.iter().map(|addr| addr.to_vec()).collect(),
+                });
+            }
+            Response::StreamInfo(info) => message.stream_info = Some(info.to_proto()),
+            Response::Peers(peers) => message.peers = peers.iter().map(PeerInfo::to_proto).collect(),
+            Response::Dht(response) => message.dht = Some(response.to_proto()),
+            Response::Pubsub { topics, peers } => {
+                message.pubsub = Some(proto::PsResponse {
+                    topics: topics.clone(),
+                    peer_ids: peers.iter().map(|peer_id| peer_id.as_bytes().to_vec()).collect(),
+                });
+            }
+        }
+        encode_message(&message)
+    }
+
+    /// Decodes a response, without its length prefix.
+    pub fn decode(bytes: &[u8]) -> Result<Self, DecodeError> {
+        let message = proto::Response::decode(bytes)?;
+        match message.r#type {
+            OK => {}
+            ERROR => return Ok(Response::Error(message.error.map(|e| e.msg).unwrap_or_default())),
+            _ => return Err(DecodeError::new("unknown response type")),
+        }
+
+        if let Some(info) = message.stream_info {
+            Ok(Response::StreamInfo(StreamInfo::from_proto(info)?))
+        } else if let Some(identify) = message.identify {
+            Ok(Response::Identify(PeerInfo::from_proto(proto::PeerInfo { id: identify.id, addrs: identify.addrs })?))
+        } else if let Some(response) = message.dht {
+            Ok(Response::Dht(DhtResponse::from_proto(response)?))
+        } else if let Some(body) = message.pubsub {
+            Ok(Response::Pubsub {
+                topics: body.topics,
+                peers: body.peer_ids.into_iter().map(decode_peer_id).collect::<Result<_, _>>()?,
+            })
+        } else if !message.peers.is_empty() {
+            let peers = message.peers.into_iter().map(PeerInfo::from_proto).collect::<Result<_, _>>()?;
+            Ok(Response::Peers(peers))
+        } else {
+            Ok(Response::Ok)
+        }
+    }
+}
+
+impl StreamInfo {
+    /// Encodes the description, without its length prefix.
+    pub fn encode(&self) -> Vec<u8> {
+        encode_message(&self.to_proto())
+    }
+
+    /// Decodes a description, without its length prefix.
+    pub fn decode(bytes: &[u8]) -> Result<Self, DecodeError> {
+        StreamInfo::from_proto(proto::StreamInfo::decode(bytes)?)
+    }
+
+    fn to_proto(&self) -> proto::StreamInfo {
+        proto::StreamInfo {
+            peer: self.peer_id.as_bytes().to_vec(),
+            addr: self.address.to_vec(),
+            proto: self.protocol.clone(),
+        }
+    }
+
+    fn from_proto(info: proto::StreamInfo) -> Result<Self, DecodeError> {
+        Ok(StreamInfo {
+            peer_id: decode_peer_id(info.peer)?,
+            address: decode_multiaddr(info.addr)?,
+            protocol: info.proto,
+        })
+    }
+}
+
+impl DhtResponse {
+    /// Encodes the result, without its length prefix.
+    pub fn encode(&self) -> Vec<u8> {
+        encode_message(&self.to_proto())
+    }
+
+    /// Decodes a result, without its length prefix.
+    pub fn decode(bytes: &[u8]) -> Result<Self, DecodeError> {
+        DhtResponse::from_proto(proto::DhtResponse::decode(bytes)?)
+    }
+
+    fn to_proto(&self) -> proto::DhtResponse {
+        let mut response = proto::DhtResponse::default();
+        match self {
+            DhtResponse::Begin => response.r#type = DHT_BEGIN,
+            DhtResponse::Peer(info) => {
+                response.r#type = DHT_VALUE;
+                response.peer = Some(info.to_proto());
+            }
+            DhtResponse::Value(value) => {
+                response.r#type = DHT_VALUE;
+                response.value = Some(value.clone());
+            }
+            DhtResponse::End => response.r#type = DHT_END,
+        }
+        response
+    }
+
+    fn from_proto(response: proto::DhtResponse) -> Result<Self, DecodeError> {
+        match response.r#type {
+            DHT_BEGIN => Ok(DhtResponse::Begin),
+            DHT_VALUE => match (response.peer, response.value) {
+                (Some(info), _) => Ok(DhtResponse::Peer(PeerInfo::from_proto(info)?)),
+                (None, Some(value)) => Ok(DhtResponse::Value(value)),
+                (None, None) => Err(DecodeError::new("missing DHT value")),
+            },
+            DHT_END => Ok(DhtResponse::End),
+            _ => Err(DecodeError::new("unknown DHT response type")),
+        }
+    }
+}
+
+impl PubsubMessage {
+    /// Encodes the message, without its length prefix.
+    pub fn encode(&self) -> Vec<u8> {
+        encode_message(&proto::PsMessage {
+            from: Some(self.source.as_bytes().to_vec()),
+            data: Some(self.data.clone()),
+            seqno: Some(self.sequence_number.clone()),
+            topic_ids: self.topics.clone(),
+            signature: None,
+            key: None,
+        })
+    }
+
+    /// Decodes a message, without its length prefix.
+    pub fn decode(bytes: &[u8]) -> Result<Self, DecodeError> {
+        let message = proto::PsMessage::decode(bytes)?;
+        let source = message.from.ok_or_else(|| DecodeError::new("missing source"))?;
+        Ok(PubsubMessage {
+            source: decode_peer_id(source)?,
+            data: message.data.unwrap_or_default(),
+            sequence_number: message.seqno.unwrap_or_default(),
+            topics: message.topic_ids,
+        })
+    }
+}
+
+impl PeerInfo {
+    fn to_proto(&self) -> proto::PeerInfo {
+        proto::PeerInfo {
+            id: self.peer_id.as_bytes().to_vec(),
+            addrs: self.addresses.iter().map(|addr| addr.to_vec()).collect(),
+        }
+    }
+
+    fn from_proto(info: proto::PeerInfo) -> Result<Self, DecodeError> {
+        Ok(PeerInfo {
+            peer_id: decode_peer_id(info.id)?,
+            addresses: info.addrs.into_iter().filter_map(|addr| Multiaddr::try_from(addr).ok()).collect(),
+        })
+    }
+}
+
+fn encode_message(message: &impl Message) -> Vec<u8> {
+    let mut buf = Vec::with_capacity(message.encoded_len());
+    message.encode(&mut buf).expect("Vec<u8> provides capacity as needed");
+    buf
+}
+
+fn missing_body() -> DecodeError {
+    DecodeError::new("missing request body")
+}
+
+/// Encodes a timeout as a number of seconds.
+fn encode_timeout(timeout: Option<Duration>) -> Option<i64> {
+    timeout.map(|timeout| timeout.as_secs() as i64)
+}
+
+/// Decodes a timeout in seconds. Zero and negative values mean that there is no timeout.
+fn decode_timeout(value: Option<i64>) -> Option<Duration> {
+    value.filter(|secs| *secs > 0).map(|secs| Duration::from_secs(secs as u64))
+}
+
+fn decode_peer_id(bytes: Vec<u8>) -> Result<PeerId, DecodeError> {
+    PeerId::from_bytes(bytes).map_err(|_| DecodeError::new("invalid peer ID"))
+}
+
+fn decode_multiaddr(bytes: Vec<u8>) -> Result<Multiaddr, DecodeError> {
+    Multiaddr::try_from(bytes).map_err(|_| DecodeError::new("invalid multiaddress"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use libp2p::multiaddr::{Protocol, multiaddr};
+
+    fn info() -> PeerInfo {
+        PeerInfo {
+            peer_id: PeerId::random(),
+            addresses: vec![multiaddr![Ip4([1, 2, 3, 4]), Tcp(4001u16)]],
+        }
+    }
+
+    #[test]
+    fn requests_roundtrip() {
+        let peer_id = PeerId::random();
+        let requests = vec![
+            Request::Identify,
+            Request::Connect {
+                peer_id: peer_id.clone(),
+                addresses: info().addresses,
+                timeout: Some(Duration::from_secs(10)),
+            },
+            Request::StreamOpen {
+                peer_id: peer_id.clone(),
+                protocols: vec!["/echo/1.0.0".to_owned(), "/echo/2.0.0".to_owned()],
+                timeout: None,
+            },
+            Request::StreamHandler {
+                address: Multiaddr::from(Protocol::Unix("/tmp/handler.sock".into())),
+                protocols: vec!["/echo/1.0.0".to_owned()],
+            },
+            Request::Dht { request: DhtRequest::FindPeer(peer_id.clone()), timeout: None },
+            Request::Dht {
+                request: DhtRequest::FindProviders { cid: vec![1, 2, 3], count: Some(5) },
+                timeout: Some(Duration::from_secs(30)),
+            },
+            Request::Dht { request: DhtRequest::GetClosestPeers(b"key".to_vec()), timeout: None },
+            Request::Dht { request: DhtRequest::GetPublicKey(peer_id.clone()), timeout: None },
+            Request::Dht { request: DhtRequest::GetValue(b"key".to_vec()), timeout: None },
+            Request::Dht {
+                request: DhtRequest::PutValue { key: b"key".to_vec(), value: b"value".to_vec() },
+                timeout: None,
+            },
+            Request::Dht { request: DhtRequest::Provide(vec![4, 5, 6]), timeout: None },
+            Request::ListPeers,
+            Request::Disconnect { peer_id },
+            Request::Pubsub(PubsubRequest::GetTopics),
+            Request::Pubsub(PubsubRequest::ListPeers("news".to_owned())),
+            Request::Pubsub(PubsubRequest::Publish { topic: "news".to_owned(), data: b"hello".to_vec() }),
+            Request::Pubsub(PubsubRequest::Subscribe("news".to_owned())),
+        ];
+        for request in requests {
+            assert_eq!(Request::decode(&request.encode()), Ok(request));
+        }
+    }
+
+    #[test]
+    fn responses_roundtrip() {
+        let responses = vec![
+            Response::Ok,
+            Response::Error("no route to peer".to_owned()),
+            Response::Identify(info()),
+            Response::StreamInfo(StreamInfo {
+                peer_id: PeerId::random(),
+                address: info().addresses[0].clone(),
+                protocol: "/echo/1.0.0".to_owned(),
+            }),
+            Response::Peers(vec![info(), info()]),
+            Response::Dht(DhtResponse::Begin),
+            Response::Dht(DhtResponse::Peer(info())),
+            Response::Dht(DhtResponse::Value(b"value".to_vec())),
+            Response::Pubsub { topics: vec!["news".to_owned()], peers: vec![PeerId::random()] },
+        ];
+        for response in responses {
+            assert_eq!(Response::decode(&response.encode()), Ok(response));
+        }
+
+        let message = PubsubMessage {
+            source: PeerId::random(),
+            data: b"hello".to_vec(),
+            sequence_number: vec![0, 0, 0, 1],
+            topics: vec!["news".to_owned()],
+        };
+        assert_eq!(PubsubMessage::decode(&message.encode()), Ok(message));
+    }
+
+    #[test]
+    fn unsupported_requests() {
+        // CONNMANAGER, with a TRIM request.
+        let request = proto::Request {
+            r#type: 6,
+            conn_manager: Some(proto::ConnManagerRequest { r#type: 2, ..Default::default() }),
+            ..Default::default()
+        };
+        let bytes = encode_message(&request);
+        assert_eq!(Request::decode(&bytes), Err(DecodeError::new("unsupported request")));
+    }
+}
@@ -0,0 +1,618 @@
+// Copyright 2019 Parity Technologies (UK) Ltd.
+//
+// Permission is hereby granted, free of charge, to any person obtaining a
+// copy of this software and associated documentation files (the "Software"),
+// to deal in the Software without restriction, including without limitation
+// the rights to use, copy, modify, merge, publish, distribute, sublicense,
+// and/or sell copies of the Software, and to permit persons to whom the
+// Software is furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS
+// OR IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING
+// FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
+// DEALINGS IN THE SOFTWARE.
+//! The libp2p node of the daemon, driven by the commands of the control connections.
+
+use crate::{
+    DaemonConfig,
+    messages::{DhtRequest, DhtResponse, PeerInfo, PubsubMessage},
+};
+use futures::{future, prelude::*, sync::{mpsc, oneshot}};
+use libp2p::{
+    NetworkBehaviour, Multiaddr, PeerId, Swarm, Transport,
+    core::{ConnectedPoint, identity::PublicKey, muxing::StreamMuxerBox, nodes, transport::boxed::Boxed},
+    gossipsub::{Gossipsub, GossipsubConfig, GossipsubEvent, Topic, TopicHash},
+    identify::{Identify, IdentifyEvent},
+    kad::{
+        Kademlia, KademliaEvent, Quorum, Record, GetClosestPeersError, GetProvidersError,
+        GetRecordError, record::store::MemoryStore
+    },
+    multiaddr::Protocol,
+    multihash::{self, Hash, Multihash},
+    ping::{Ping, PingConfig, PingEvent},
+    stream::{Control, RawStreams, RawStreamsConfig},
+    swarm::{NetworkBehaviour, NetworkBehaviourEventProcess, SwarmEvent, toggle::Toggle},
+    tokio_io::{AsyncRead, AsyncWrite},
+};
+use log::debug;
+use std::{collections::{HashMap, HashSet}, io, mem};
+use void::Void;
+
+/// Substreams of the connections of the node.
+pub(crate) type Substream = nodes::Substream<StreamMuxerBox>;
+
+type DaemonSwarm = Swarm<Boxed<(PeerId, StreamMuxerBox), io::Error>, Behaviour<Substream>>;
+
+/// A command sent by a control connection to the node.
+#[derive(Debug)]
+pub(crate) enum Command {
+    Identify(oneshot::Sender<PeerInfo>),
+    Connect { peer_id: PeerId, addresses: Vec<Multiaddr>, reply: oneshot::Sender<Result<(), String>> },
+    ListPeers(oneshot::Sender<Vec<PeerInfo>>),
+    Disconnect(PeerId),
+    Dht { request: DhtRequest, reply: oneshot::Sender<Result<Vec<DhtResponse>, String>> },
+    GetTopics(oneshot::Sender<Vec<String>>),
+    ListTopicPeers { topic: String, reply: oneshot::Sender<Vec<PeerId>> },
+    Publish { topic: String, data: Vec<u8> },
+    Subscribe { topic: String, messages: mpsc::UnboundedSender<PubsubMessage> },
+}
+
+/// What the control connections need to talk to the node.
+#[derive(Debug, Clone)]
+pub(crate) struct Context {
+    pub(crate) commands: mpsc::UnboundedSender<Command>,
+    pub(crate) streams: Control<Substream>,
+    pub(crate) dht: bool,
+    pub(crate) pubsub: bool,
+}
+
+/// A connection requested by a client.
+struct PendingConnect {
+    /// Addresses being dialed. The connection fails once they have all been unreachable.
+    addresses: Vec<Multiaddr>,
+    replies: Vec<oneshot::Sender<Result<(), String>>>,
+}
+
+type DhtReply = oneshot::Sender<Result<Vec<DhtResponse>, String>>;
+
+/// A DHT query started for a client, waiting for its result.
+enum PendingQuery {
+    FindPeer { target: PeerId, reply: DhtReply },
+    ClosestPeers { reply: DhtReply },
+    Providers { count: Option<u32>, reply: DhtReply },
+    GetValue { reply: DhtReply },
+    PutValue { reply: DhtReply },
+    Provide { reply: DhtReply },
+}
+
+#[derive(NetworkBehaviour)]
+pub(crate) struct Behaviour<TSubstream: AsyncRead + AsyncWrite> {
+    identify: Identify<TSubstream>,
+    ping: Ping<TSubstream>,
+    streams: RawStreams<TSubstream>,
+    kademlia: Toggle<Kademlia<TSubstream, MemoryStore>>,
+    gossipsub: Toggle<Gossipsub<TSubstream>>,
+
+    /// Public keys of the peers, as learned through identify.
+    #[behaviour(ignore)]
+    public_keys: HashMap<PeerId, PublicKey>,
+    #[behaviour(ignore)]
+    pending_connects: HashMap<PeerId, PendingConnect>,
+    /// Pending DHT queries, with the key they're about.
+    #[behaviour(ignore)]
+    queries: Vec<(Multihash, PendingQuery)>,
+    /// Clients subscribed to topics.
+    #[behaviour(ignore)]
+    subscriptions: HashMap<TopicHash, Vec<mpsc::UnboundedSender<PubsubMessage>>>,
+    /// Remotes subscribed to topics.
+    #[behaviour(ignore)]
+    topic_peers: HashMap<TopicHash, HashSet<PeerId>>,
+}
+
+impl<TSubstream: AsyncRead + AsyncWrite> Behaviour<TSubstream> {
+    /// Removes the pending DHT queries about `key` that `is_match` accepts and returns them.
+    fn take_queries(&mut self, key: &Multihash, is_match: fn(&PendingQuery) -> bool) -> Vec<PendingQuery> {
+        let (taken, kept) = mem::replace(&mut self.queries, Vec::new())
+            .into_iter()
+            .partition::<Vec<_>, _>(|(k, query)| k == key && is_match(query));
+        self.queries = kept;
+        taken.into_iter().map(|(_, query)| query).collect()
+    }
+
+    fn peer_info(&mut self, peer_id: PeerId) -> PeerInfo {
+        let addresses = self.addresses_of_peer(&peer_id);
+        PeerInfo { peer_id, addresses }
+    }
+}
+
+impl<TSubstream: AsyncRead + AsyncWrite> NetworkBehaviourEventProcess<IdentifyEvent> for Behaviour<TSubstream> {
+    fn inject_event(&mut self, event: IdentifyEvent) {
+        if let IdentifyEvent::Identified { peer_id, info, .. } = event {
+            if let Some(kademlia) = self.kademlia.inner_mut() {
+                for address in info.listen_addrs {
+                    kademlia.add_address(&peer_id, address);
+                }
+            }
+            self.public_keys.insert(peer_id, info.public_key);
+        }
+    }
+}
+
+impl<TSubstream: AsyncRead + AsyncWrite> NetworkBehaviourEventProcess<PingEvent> for Behaviour<TSubstream> {
+    fn inject_event(&mut self, _: PingEvent) {}
+}
+
+impl<TSubstream: AsyncRead + AsyncWrite> NetworkBehaviourEventProcess<Void> for Behaviour<TSubstream> {
+    fn inject_event(&mut self, event: Void) {
+        void::unreachable(event)
+    }
+}
+
+impl<TSubstream: AsyncRead + AsyncWrite> NetworkBehaviourEventProcess<KademliaEvent> for Behaviour<TSubstream> {
+    fn inject_event(&mut self, event: KademliaEvent) {
+        match event {
+            KademliaEvent::GetClosestPeersResult(result, _) => {
+                let (key, peers) = match result {
+                    Ok(ok) => (ok.key, ok.peers),
+                    Err(GetClosestPeersError::Timeout { key, peers }) => (key, peers),
+                };
+                let queries = self.take_queries(&key, |query| match query {
+                    PendingQuery::FindPeer { .. } | PendingQuery::ClosestPeers { .. } => true,
+                    _ => false,
+                });
+                for query in queries {
+                    match query {
+                        PendingQuery::FindPeer { target, reply } => {
+                            let info = self.peer_info(target);
+                            let _ = reply.send(if info.addresses.is_empty() {
+                                Err("Peer not found".to_owned())
+                            } else {
+                                Ok(vec![DhtResponse::Peer(info)])
+                            });
+                        }
+                        PendingQuery::ClosestPeers { reply } => {
+                            let peers = peers.iter().map(|p| DhtResponse::Value(p.clone().into_bytes())).collect();
+                            let _ = reply.send(Ok(peers));
+                        }
+                        _ => unreachable!("only lookups of the closest peers are taken"),
+                    }
+                }
+            }
+            KademliaEvent::GetProvidersResult(result, _) => {
+                let (key, providers) = match result {
+                    Ok(ok) => (ok.key, ok.providers),
+                    Err(GetProvidersError::Timeout { key, providers, .. }) => (key, providers),
+                };
+                let queries = self.take_queries(&key, |query| match query {
+                    PendingQuery::Providers { .. } => true,
+                    _ => false,
+                });
+                for query in queries {
+                    if let PendingQuery::Providers { count, reply } = query {
+                        let count = count.map_or(providers.len(), |c| c as usize);
+                        let providers = providers.iter()
+                            .take(count)
+                            .map(|p| DhtResponse::Peer(self.peer_info(p.clone())))
+                            .collect();
+                        let _ = reply.send(Ok(providers));
+                    }
+                }
+            }
+            KademliaEvent::GetRecordResult(result, _) => {
+                let (key, result) = match result {
+                    Ok(ok) => match ok.records.into_iter().next() {
+                        Some(record) => (record.key, Ok(record.value)),
+                        None => return,
+                    },
+                    Err(GetRecordError::QuorumFailed { key, records, .. }) |
+                    Err(GetRecordError::Timeout { key, records, .. }) => match records.into_iter().next() {
+                        Some(record) => (key, Ok(record.value)),
+                        None => (key, Err("Record not found".to_owned())),
+                    },
+                    Err(GetRecordError::NotFound { key, .. }) => (key, Err("Record not found".to_owned())),
+                };
+                let queries = self.take_queries(&key, |query| match query {
+                    PendingQuery::GetValue { .. } => true,
+                    _ => false,
+                });
+                for query in queries {
+                    if let PendingQuery::GetValue { reply } = query {
+                        let _ = reply.send(result.clone().map(|value| vec![DhtResponse::Value(value)]));
+                    }
+                }
+            }
+            KademliaEvent::PutRecordResult(result, _) => {
+                let (key, result) = match result {
+                    Ok(ok) => (ok.key, Ok(Vec::new())),
+                    Err(err) => (err.key().clone(), Err(format!("Failed to put the value: {:?}", err))),
+                };
+                for query in self.take_queries(&key, |query| match query {
+                    PendingQuery::PutValue { .. } => true,
+                    _ => false,
+                }) {
+                    if let PendingQuery::PutValue { reply } = query {
+                        let _ = reply.send(result.clone());
+                    }
+                }
+            }
+            KademliaEvent::StartProvidingResult(result, _) => {
+                let (key, result) = match result {
+                    Ok(ok) => (ok.key, Ok(Vec::new())),
+                    Err(err) => (err.key().clone(), Err(format!("Failed to provide: {:?}", err))),
+                };
+                for query in self.take_queries(&key, |query| match query {
+                    PendingQuery::Provide { .. } => true,
+                    _ => false,
+                }) {
+                    if let PendingQuery::Provide { reply } = query {
+                        let _ = reply.send(result.clone());
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+impl<TSubstream: AsyncRead + AsyncWrite> NetworkBehaviourEventProcess<GossipsubEvent> for Behaviour<TSubstream> {
+    fn inject_event(&mut self, event: GossipsubEvent) {
+        match event {
+            GossipsubEvent::Message { message, .. } => {
+                let delivered = PubsubMessage {
+                    source: message.source.clone(),
+                    data: message.data.clone(),
+                    sequence_number: message.sequence_number.clone(),
+                    topics: message.topics.iter().map(|t| t.as_str().to_owned()).collect(),
+                };
+                for topic in &message.topics {
+                    let unsubscribe = match self.subscriptions.get_mut(topic) {
+                        Some(clients) => {
+                            // Clients that went away are noticed here.
+                            clients.retain(|client| client.unbounded_send(delivered.clone()).is_ok());
+                            clients.is_empty()
+                        }
+                        None => false,
+                    };
+                    if unsubscribe {
+                        self.subscriptions.remove(topic);
+                        if let Some(gossipsub) = self.gossipsub.inner_mut() {
+                            gossipsub.unsubscribe(topic);
+                        }
+                    }
+                }
+            }
+            GossipsubEvent::Subscribed { peer_id, topic } => {
+                self.topic_peers.entry(topic).or_default().insert(peer_id);
+            }
+            GossipsubEvent::Unsubscribed { peer_id, topic } => {
+                if let Some(peers) = self.topic_peers.get_mut(&topic) {
+                    peers.remove(&peer_id);
+                }
+            }
+        }
+    }
+}
+
+/// Builds the node, and returns the future that drives it along with the context of the
+/// control connections.
+pub(crate) fn build(config: &DaemonConfig) -> io::Result<(impl Future<Item = (), Error = io::Error>, Context)> {
+    let keypair = config.keypair.clone();
+    let local_peer_id = keypair.public().into_peer_id();
+    let transport = libp2p::build_development_transport(keypair.clone())
+        .map(|(peer_id, muxer), _| (peer_id, StreamMuxerBox::new(muxer)))
+        .map_err(|err| io::Error::new(io::ErrorKind::Other, err.to_string()))
+        .boxed();
+
+    let streams = RawStreams::new(RawStreamsConfig::default());
+    let context_streams = streams.control();
+    let kademlia = if config.dht {
+        Some(Kademlia::new(local_peer_id.clone(), MemoryStore::new(local_peer_id.clone())))
+    } else {
+        None
+    };
+    let gossipsub = if config.pubsub {
+        Some(Gossipsub::new(local_peer_id.clone(), GossipsubConfig::default()))
+    } else {
+        None
+    };
+    let behaviour = Behaviour {
+        identify: Identify::new(
+            "ipfs/0.1.0".to_owned(),
+            format!("rust-libp2p-daemon/{}", env!("CARGO_PKG_VERSION")),
+            keypair.public()
+        ),
+        ping: Ping::new(PingConfig::new()),
+        streams,
+        kademlia: Toggle::from(kademlia),
+        gossipsub: Toggle::from(gossipsub),
+        public_keys: HashMap::new(),
+        pending_connects: HashMap::new(),
+        queries: Vec::new(),
+        subscriptions: HashMap::new(),
+        topic_peers: HashMap::new(),
+    };
+    let mut swarm: DaemonSwarm = Swarm::new(transport, behaviour, local_peer_id);
+
+    for address in &config.listen_addresses {
+        Swarm::listen_on(&mut swarm, address.clone())
+            .map_err(|err| io::Error::new(io::ErrorKind::Other, format!("Failed to listen on {}: {:?}", address, err)))?;
+    }
+
+    for address in &config.bootstrap_peers {
+        let mut address = address.clone();
+        match address.pop() {
+            Some(Protocol::P2p(hash)) => {
+                let peer_id = PeerId::from_multihash(hash)
+                    .map_err(|_| io::Error::new(io::ErrorKind::InvalidInput, "invalid peer ID in a bootstrap address"))?;
+                swarm.streams.add_address(&peer_id, address.clone());
+                if let Some(kademlia) = swarm.kademlia.inner_mut() {
+                    kademlia.add_address(&peer_id, address);
+                }
+                Swarm::dial(&mut swarm, peer_id);
+            }
+            _ => return Err(io::Error::new(io::ErrorKind::InvalidInput, "bootstrap addresses must end with /p2p/<peer ID>")),
+        }
+    }
+    if !config.bootstrap_peers.is_empty() {
+        if let Some(kademlia) = swarm.kademlia.inner_mut() {
+            kademlia.bootstrap();
+        }
+    }
+
+    let (commands, mut commands_rx) = mpsc::unbounded();
+    let context = Context {
+        commands,
+        streams: context_streams,
+        dht: config.dht,
+        pubsub: config.pubsub,
+    };
+
+    let node = future::poll_fn(move || -> Poll<(), io::Error> {
+        loop {
+            match commands_rx.poll() {
+                Ok(Async::Ready(Some(command))) => handle_command(&mut swarm, command),
+                // The context, and therefore a sender, lives as long as the control socket.
+                Ok(Async::Ready(None)) | Err(()) => return Ok(Async::Ready(())),
+                Ok(Async::NotReady) => break,
+            }
+        }
+
+        loop {
+            match Swarm::poll_event(&mut swarm)? {
+                Async::Ready(SwarmEvent::ConnectionEstablished { peer_id, .. }) => {
+                    if let Some(pending) = swarm.pending_connects.remove(&peer_id) {
+                        for reply in pending.replies {
+                            let _ = reply.send(Ok(()));
+                        }
+                    }
+                }
+                Async::Ready(SwarmEvent::UnreachableAddr { address, cause, .. }) => {
+                    debug!("Failed to reach {}: {:?}", address, cause);
+                    let failed = swarm.pending_connects.iter_mut()
+                        .filter_map(|(peer_id, pending)| {
+                            pending.addresses.retain(|a| *a != address);
+                            if pending.addresses.is_empty() { Some(peer_id.clone()) } else { None }
+                        })
+                        .collect::<Vec<_>>();
+                    for peer_id in failed {
+                        if let Some(pending) = swarm.pending_connects.remove(&peer_id) {
+                            for reply in pending.replies {
+                                let _ = reply.send(Err(format!("Failed to connect to {}", peer_id)));
+                            }
+                        }
+                    }
+                }
+                Async::Ready(SwarmEvent::ConnectionClosed { peer_id, num_established: 0, .. }) => {
+                    for peers in swarm.topic_peers.values_mut() {
+                        peers.remove(&peer_id);
+                    }
+                }
+                Async::Ready(_) => {}
+                Async::NotReady => return Ok(Async::NotReady),
+            }
+        }
+    });
+
+    Ok((node, context))
+}
+
+fn handle_command(swarm: &mut DaemonSwarm, command: Command) {
+    match command {
+        Command::Identify(reply) => {
+            let peer_id = Swarm::local_peer_id(swarm).clone();
+            let mut addresses = Swarm::listeners(swarm).cloned().collect::<Vec<_>>();
+            addresses.extend(Swarm::external_addresses(swarm).cloned());
+            let _ = reply.send(PeerInfo { peer_id, addresses });
+        }
+        Command::Connect { peer_id, addresses, reply } => {
+            if Swarm::connection_info(swarm, &peer_id).is_some() {
+                let _ = reply.send(Ok(()));
+                return
+            }
+            for address in &addresses {
+                swarm.streams.add_address(&peer_id, address.clone());
+                if let Some(kademlia) = swarm.kademlia.inner_mut() {
+                    kademlia.add_address(&peer_id, address.clone());
+                }
+            }
+            if let Some(pending) = swarm.pending_connects.get_mut(&peer_id) {
+                pending.replies.push(reply);
+                return
+            }
+            let addresses = swarm.addresses_of_peer(&peer_id);
+            let dialed = addresses.into_iter()
+                .filter(|address| match Swarm::dial_addr(swarm, address.clone()) {
+                    Ok(()) => true,
+                    Err(err) => {
+                        debug!("Failed to dial {}: {:?}", address, err);
+                        false
+                    }
+                })
+                .collect::<Vec<_>>();
+            if dialed.is_empty() {
+                let _ = reply.send(Err(format!("No address to reach {}", peer_id)));
+            } else {
+                swarm.pending_connects.insert(peer_id, PendingConnect { addresses: dialed, replies: vec![reply] });
+            }
+        }
+        Command::ListPeers(reply) => {
+            let mut peers: HashMap<PeerId, Vec<Multiaddr>> = HashMap::new();
+            for connection in Swarm::connections(swarm) {
+                let address = match connection.endpoint() {
+                    ConnectedPoint::Dialer { address } => address.clone(),
+                    ConnectedPoint::Listener { send_back_addr, .. } => send_back_addr.clone(),
+                };
+                peers.entry(connection.info().clone()).or_default().push(address);
+            }
+            let peers = peers.into_iter()
+                .map(|(peer_id, addresses)| PeerInfo { peer_id, addresses })
+                .collect();
+            let _ = reply.send(peers);
+        }
+        Command::Disconnect(peer_id) => {
+            let _ = Swarm::disconnect_peer_id(swarm, peer_id);
+        }
+        Command::Dht { request, reply } => dht_request(swarm, request, reply),
+        Command::GetTopics(reply) => {
+            let _ = reply.send(swarm.subscriptions.keys().map(|t| t.as_str().to_owned()).collect());
+        }
+        Command::ListTopicPeers { topic, reply } => {
+            let peers = swarm.topic_peers.get(&TopicHash::from_raw(topic))
+                .map(|peers| peers.iter().cloned().collect())
+                .unwrap_or_default();
+            let _ = reply.send(peers);
+        }
+        Command::Publish { topic, data } => {
+            if let Some(gossipsub) = swarm.gossipsub.inner_mut() {
+                gossipsub.publish(Topic::new(topic), data);
+            }
+        }
+        Command::Subscribe { topic, messages } => {
+            let topic = Topic::new(topic);
+            if !swarm.subscriptions.contains_key(topic.hash()) {
+                match swarm.gossipsub.inner_mut() {
+                    Some(gossipsub) => { gossipsub.subscribe(topic.clone()); }
+                    None => return,
+                }
+            }
+            swarm.subscriptions.entry(topic.hash().clone()).or_default().push(messages);
+        }
+    }
+}
+
+fn dht_request(swarm: &mut DaemonSwarm, request: DhtRequest, reply: DhtReply) {
+    if let DhtRequest::GetPublicKey(peer_id) = &request {
+        let _ = reply.send(match swarm.public_keys.get(peer_id) {
+            Some(key) => Ok(vec![DhtResponse::Value(key.clone().into_protobuf_encoding())]),
+            None => Err("Public key unknown".to_owned()),
+        });
+        return
+    }
+    if let DhtRequest::FindPeer(peer_id) = &request {
+        let info = swarm.peer_info(peer_id.clone());
+        if !info.addresses.is_empty() {
+            let _ = reply.send(Ok(vec![DhtResponse::Peer(info)]));
+            return
+        }
+    }
+
+    let behaviour = &mut **swarm;
+    let kademlia = match behaviour.kademlia.inner_mut() {
+        Some(kademlia) => kademlia,
+        None => {
+            let _ = reply.send(Err("DHT not enabled".to_owned()));
+            return
+        }
+    };
+    let (key, query): (Multihash, PendingQuery) = match request {
+        DhtRequest::FindPeer(peer_id) => {
+            kademlia.get_closest_peers(peer_id.clone());
+            (peer_id.clone().into(), PendingQuery::FindPeer { target: peer_id, reply })
+        }
+        DhtRequest::FindProviders { cid, count } => {
+            let key = match cid_to_multihash(&cid) {
+                Some(key) => key,
+                None => {
+                    let _ = reply.send(Err("Invalid CID".to_owned()));
+                    return
+                }
+            };
+            kademlia.get_providers(key.clone());
+            (key, PendingQuery::Providers { count, reply })
+        }
+        DhtRequest::GetClosestPeers(key) => {
+            let key = record_key(&key);
+            kademlia.get_closest_peers(key.clone());
+            (key, PendingQuery::ClosestPeers { reply })
+        }
+        DhtRequest::GetValue(key) => {
+            let key = record_key(&key);
+            kademlia.get_record(&key, Quorum::One);
+            (key, PendingQuery::GetValue { reply })
+        }
+        DhtRequest::PutValue { key, value } => {
+            let key = record_key(&key);
+            kademlia.put_record(Record::new(key.clone(), value), Quorum::One);
+            (key, PendingQuery::PutValue { reply })
+        }
+        DhtRequest::Provide(cid) => {
+            let key = match cid_to_multihash(&cid) {
+                Some(key) => key,
+                None => {
+                    let _ = reply.send(Err("Invalid CID".to_owned()));
+                    return
+                }
+            };
+            kademlia.start_providing(key.clone());
+            (key, PendingQuery::Provide { reply })
+        }
+        DhtRequest::GetPublicKey(_) => unreachable!("handled above"),
+    };
+    behaviour.queries.push((key, query));
+}
+
+/// Key under which a value is stored in the DHT.
+///
+/// The keys of the daemon protocol are arbitrary bytes while the records of `Kademlia` are
+/// keyed by multihashes, so the bytes are wrapped in an identity multihash.
+fn record_key(key: &[u8]) -> Multihash {
+    multihash::encode(Hash::Identity, key).expect("identity hashing doesn't fail")
+}
+
+/// Extracts the multihash of a CID. Version 0 CIDs are a bare multihash, while version 1 CIDs
+/// are prefixed with their version and content type.
+fn cid_to_multihash(cid: &[u8]) -> Option<Multihash> {
+    if let Ok(hash) = Multihash::from_bytes(cid.to_vec()) {
+        return Some(hash)
+    }
+    let (version, rest) = unsigned_varint::decode::u64(cid).ok()?;
+    if version != 1 {
+        return None
+    }
+    let (_, rest) = unsigned_varint::decode::u64(rest).ok()?;
+    Multihash::from_bytes(rest.to_vec()).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn cid_versions() {
+        let hash = multihash::encode(Hash::SHA2256, b"hello").unwrap();
+        assert_eq!(cid_to_multihash(hash.as_bytes()), Some(hash.clone()));
+
+        // Version 1, raw content.
+        let mut cid = vec![1, 0x55];
+        cid.extend_from_slice(hash.as_bytes());
+        assert_eq!(cid_to_multihash(&cid), Some(hash));
+
+        assert_eq!(cid_to_multihash(&[2, 0x55, 0]), None);
+    }
+}
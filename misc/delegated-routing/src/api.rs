@@ -0,0 +1,208 @@
+// Copyright 2019 Parity Technologies (UK) Ltd.
+//
+// Permission is hereby granted, free of charge, to any person obtaining a
+// copy of this software and associated documentation files (the "Software"),
+// to deal in the Software without restriction, including without limitation
+// the rights to use, copy, modify, merge, publish, distribute, sublicense,
+// and/or sell copies of the Software, and to permit persons to whom the
+// Software is furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS
+// OR IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING
+// FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
+// DEALINGS IN THE SOFTWARE.
+
+//! Requests and responses of the `/routing/v1` HTTP API.
+
+use crate::client::HttpResponse;
+use data_encoding::BASE32_NOPAD;
+use libp2p_core::{Multiaddr, PeerId, routing::{RoutingError, RoutingPeer}};
+use log::debug;
+use multihash::Multihash;
+use serde::Deserialize;
+
+/// Version of the CIDs built from the keys.
+const CID_VERSION: u8 = 1;
+/// Multicodec of the CIDs built from the keys, which is `raw`.
+const RAW_CODEC: u8 = 0x55;
+
+/// Builds the URL returning the providers of `key`.
+pub fn providers_url(endpoint: &str, key: &Multihash) -> String {
+    format!("{}/routing/v1/providers/{}", endpoint, key_to_cid(key))
+}
+
+/// Builds the URL returning the records of `peer_id`.
+pub fn peers_url(endpoint: &str, peer_id: &PeerId) -> String {
+    format!("{}/routing/v1/peers/{}", endpoint, peer_id.to_base58())
+}
+
+/// Encodes `key` as a CIDv1 with the `raw` codec, in multibase `base32`.
+fn key_to_cid(key: &Multihash) -> String {
+    let mut cid = vec![CID_VERSION, RAW_CODEC];
+    cid.extend_from_slice(key.as_bytes());
+    format!("b{}", BASE32_NOPAD.encode(&cid).to_lowercase())
+}
+
+/// Body of the responses to the requests built with `providers_url`.
+///
+/// Empty lists are sent as `null` by some servers.
+#[derive(Debug, Deserialize)]
+struct ProvidersResponse {
+    #[serde(rename = "Providers", default)]
+    providers: Option<Vec<PeerRecord>>,
+}
+
+/// Body of the responses to the requests built with `peers_url`.
+#[derive(Debug, Deserialize)]
+struct PeersResponse {
+    #[serde(rename = "Peers", default)]
+    peers: Option<Vec<PeerRecord>>,
+}
+
+/// Record describing a peer, as found in the responses.
+#[derive(Debug, Deserialize)]
+struct PeerRecord {
+    #[serde(rename = "Schema", default)]
+    schema: String,
+    #[serde(rename = "ID", default)]
+    id: Option<String>,
+    #[serde(rename = "Addrs", default)]
+    addrs: Vec<String>,
+}
+
+impl PeerRecord {
+    /// Converts the record into a `RoutingPeer`, skipping the addresses that can't be parsed.
+    ///
+    /// Returns `None` if the record isn't a peer record or its ID is invalid.
+    fn into_routing_peer(self) -> Option<RoutingPeer> {
+        if self.schema != "peer" {
+            debug!("Ignoring record with unknown schema {:?}", self.schema);
+            return None;
+        }
+        let peer_id = match self.id.as_ref().map(|id| id.parse::<PeerId>()) {
+            Some(Ok(peer_id)) => peer_id,
+            _ => {
+                debug!("Ignoring record with invalid peer ID {:?}", self.id);
+                return None;
+            }
+        };
+        let addresses = self.addrs.iter()
+            .filter_map(|addr| match addr.parse::<Multiaddr>() {
+                Ok(addr) => Some(addr),
+                Err(err) => {
+                    debug!("Ignoring invalid address {:?} of {:?}: {}", addr, peer_id, err);
+                    None
+                }
+            })
+            .collect();
+        Some(RoutingPeer { peer_id, addresses })
+    }
+}
+
+/// Parses the response to a request built with `providers_url`.
+pub fn parse_providers(response: &HttpResponse) -> Result<Vec<RoutingPeer>, RoutingError> {
+    check_status(response)?;
+    let body: ProvidersResponse = serde_json::from_slice(&response.body)
+        .map_err(|err| RoutingError::Other(err.to_string()))?;
+    let providers: Vec<_> = body.providers.unwrap_or_default().into_iter()
+        .filter_map(PeerRecord::into_routing_peer)
+        .collect();
+    if providers.is_empty() {
+        return Err(RoutingError::NotFound);
+    }
+    Ok(providers)
+}
+
+/// Parses the response to a request built with `peers_url`, returning the addresses of
+/// `peer_id`.
+pub fn parse_peer(peer_id: &PeerId, response: &HttpResponse) -> Result<Vec<Multiaddr>, RoutingError> {
+    check_status(response)?;
+    let body: PeersResponse = serde_json::from_slice(&response.body)
+        .map_err(|err| RoutingError::Other(err.to_string()))?;
+    let mut addresses = Vec::new();
+    for peer in body.peers.unwrap_or_default().into_iter().filter_map(PeerRecord::into_routing_peer) {
+        if peer.peer_id != *peer_id {
+            continue;
+        }
+        for addr in peer.addresses {
+            if !addresses.contains(&addr) {
+                addresses.push(addr);
+            }
+        }
+    }
+    if addresses.is_empty() {
+        return Err(RoutingError::NotFound);
+    }
+    Ok(addresses)
+}
+
+/// Maps the status codes other than 200 to an error.
+fn check_status(response: &HttpResponse) -> Result<(), RoutingError> {
+    match response.status {
+        200 => Ok(()),
+        404 => Err(RoutingError::NotFound),
+        status => Err(RoutingError::Other(format!("unexpected HTTP status {}", status))),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use libp2p_core::identity;
+
+    fn response(status: u16, body: &str) -> HttpResponse {
+        HttpResponse { status, body: body.as_bytes().to_vec() }
+    }
+
+    #[test]
+    fn builds_urls() {
+        let key = multihash::encode(multihash::Hash::SHA2256, b"hello").unwrap();
+        let url = providers_url("http://127.0.0.1:8080", &key);
+        assert!(url.starts_with("http://127.0.0.1:8080/routing/v1/providers/bafkrei"));
+
+        let peer_id = identity::Keypair::generate_ed25519().public().into_peer_id();
+        let url = peers_url("http://127.0.0.1:8080", &peer_id);
+        assert_eq!(url, format!("http://127.0.0.1:8080/routing/v1/peers/{}", peer_id.to_base58()));
+    }
+
+    #[test]
+    fn parses_providers() {
+        let peer_id = identity::Keypair::generate_ed25519().public().into_peer_id();
+        let body = format!(r#"{{"Providers": [
+            {{"Schema": "peer", "ID": "{}", "Addrs": ["/ip4/1.2.3.4/tcp/4001", "not an address"]}},
+            {{"Schema": "bitswap", "ID": "{}"}},
+            {{"Schema": "peer", "ID": "invalid"}}
+        ]}}"#, peer_id.to_base58(), peer_id.to_base58());
+
+        let providers = parse_providers(&response(200, &body)).unwrap();
+        assert_eq!(providers.len(), 1);
+        assert_eq!(providers[0].peer_id, peer_id);
+        assert_eq!(providers[0].addresses, vec!["/ip4/1.2.3.4/tcp/4001".parse::<Multiaddr>().unwrap()]);
+
+        assert_eq!(parse_providers(&response(200, r#"{"Providers": null}"#)).unwrap_err(), RoutingError::NotFound);
+        assert_eq!(parse_providers(&response(404, "")).unwrap_err(), RoutingError::NotFound);
+        match parse_providers(&response(500, "")) {
+            Err(RoutingError::Other(_)) => {}
+            other => panic!("Unexpected result: {:?}", other),
+        }
+    }
+
+    #[test]
+    fn parses_peer() {
+        let peer_id = identity::Keypair::generate_ed25519().public().into_peer_id();
+        let other = identity::Keypair::generate_ed25519().public().into_peer_id();
+        let body = format!(r#"{{"Peers": [
+            {{"Schema": "peer", "ID": "{}", "Addrs": ["/ip4/1.2.3.4/tcp/4001"]}},
+            {{"Schema": "peer", "ID": "{}", "Addrs": ["/ip4/1.2.3.4/tcp/4001", "/ip4/5.6.7.8/tcp/4001"]}}
+        ]}}"#, peer_id.to_base58(), other.to_base58());
+
+        let addrs = parse_peer(&peer_id, &response(200, &body)).unwrap();
+        assert_eq!(addrs, vec!["/ip4/1.2.3.4/tcp/4001".parse::<Multiaddr>().unwrap()]);
+    }
+}
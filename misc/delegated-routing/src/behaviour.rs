@@ -0,0 +1,362 @@
+// Copyright 2019 Parity Technologies (UK) Ltd.
+//
+// Permission is hereby granted, free of charge, to any person obtaining a
+// copy of this software and associated documentation files (the "Software"),
+// to deal in the Software without restriction, including without limitation
+// the rights to use, copy, modify, merge, publish, distribute, sublicense,
+// and/or sell copies of the Software, and to permit persons to whom the
+// Software is furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS
+// OR IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING
+// FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
+// DEALINGS IN THE SOFTWARE.
+
+use crate::{api, client::{HttpClient, HttpResponse}};
+use fnv::FnvHashMap;
+use futures::prelude::*;
+use libp2p_core::{ConnectedPoint, Multiaddr, PeerId};
+use libp2p_core::routing::{ContentRouting, PeerRouting, RoutingError, RoutingEvent};
+use libp2p_swarm::{
+    NetworkBehaviour,
+    NetworkBehaviourAction,
+    PollParameters,
+    ProtocolsHandler,
+    protocols_handler::DummyProtocolsHandler
+};
+use log::debug;
+use multihash::Multihash;
+use std::{collections::VecDeque, marker::PhantomData, time::Duration};
+use tokio_io::{AsyncRead, AsyncWrite};
+use wasm_timer::{Delay, Instant};
+
+/// Configuration for the [`DelegatedRouting`] behaviour.
+#[derive(Debug, Clone)]
+pub struct DelegatedRoutingConfig {
+    endpoint: String,
+    timeout: Duration,
+    max_known_peers: usize,
+}
+
+impl DelegatedRoutingConfig {
+    /// Creates a configuration querying the given endpoint, e.g. `https://example.com`.
+    pub fn new(endpoint: impl Into<String>) -> Self {
+        let mut endpoint = endpoint.into();
+        while endpoint.ends_with('/') {
+            endpoint.pop();
+        }
+        DelegatedRoutingConfig {
+            endpoint,
+            timeout: Duration::from_secs(30),
+            max_known_peers: 256,
+        }
+    }
+
+    /// Returns the endpoint that is queried.
+    pub fn endpoint(&self) -> &str {
+        &self.endpoint
+    }
+
+    /// Sets the time after which a request that hasn't been answered fails.
+    ///
+    /// Defaults to 30 seconds.
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    /// Sets the number of peers whose addresses, as returned by the endpoint, are remembered
+    /// to be reported to the `Swarm` when dialing them.
+    ///
+    /// Defaults to 256.
+    pub fn with_max_known_peers(mut self, max: usize) -> Self {
+        self.max_known_peers = max;
+        self
+    }
+}
+
+/// `NetworkBehaviour` that performs peer and content routing by querying a delegated routing
+/// HTTP endpoint.
+///
+/// Lookups are started through the [`ContentRouting`] and [`PeerRouting`] traits, and their
+/// outcome is produced as a [`RoutingEvent`]. Announcing the local node as a provider requires
+/// signed records that aren't supported yet, and always fails.
+pub struct DelegatedRouting<TSubstream, TClient>
+where
+    TClient: HttpClient,
+{
+    /// The configuration of the behaviour.
+    config: DelegatedRoutingConfig,
+
+    /// The client sending the requests.
+    client: TClient,
+
+    /// Requests waiting for their response.
+    requests: Vec<PendingRequest<TClient::Future>>,
+
+    /// Events waiting to be returned by `poll`.
+    events: VecDeque<RoutingEvent>,
+
+    /// Addresses of the peers returned by the endpoint.
+    known_peers: FnvHashMap<PeerId, Vec<Multiaddr>>,
+
+    /// The keys of `known_peers`, from the oldest to the newest.
+    known_peers_order: VecDeque<PeerId>,
+
+    /// Marker to pin the generic.
+    marker: PhantomData<TSubstream>,
+}
+
+/// A request waiting for its response.
+struct PendingRequest<TFuture> {
+    /// The lookup performed by the request.
+    query: Query,
+    /// The response of the server.
+    response: TFuture,
+    /// Fires when the request times out.
+    timeout: Delay,
+}
+
+/// A lookup performed through the endpoint.
+enum Query {
+    Providers(Multihash),
+    Peer(PeerId),
+}
+
+impl<TSubstream, TClient> DelegatedRouting<TSubstream, TClient>
+where
+    TClient: HttpClient,
+{
+    /// Creates a new `DelegatedRouting` behaviour sending its requests with `client`.
+    pub fn new(config: DelegatedRoutingConfig, client: TClient) -> Self {
+        DelegatedRouting {
+            config,
+            client,
+            requests: Vec::new(),
+            events: VecDeque::new(),
+            known_peers: FnvHashMap::default(),
+            known_peers_order: VecDeque::new(),
+            marker: PhantomData,
+        }
+    }
+
+    /// Returns the configuration of the behaviour.
+    pub fn config(&self) -> &DelegatedRoutingConfig {
+        &self.config
+    }
+
+    /// Sends the request for `url` and remembers it as performing `query`.
+    fn start_request(&mut self, query: Query, url: String) {
+        debug!("Sending delegated routing request to {}", url);
+        let response = self.client.get(&url);
+        self.requests.push(PendingRequest {
+            query,
+            response,
+            timeout: Delay::new(Instant::now() + self.config.timeout),
+        });
+    }
+
+    /// Remembers the addresses of `peer_id`, forgetting the oldest peer if needed.
+    fn add_known_peer(&mut self, peer_id: &PeerId, addresses: &[Multiaddr]) {
+        if addresses.is_empty() || self.config.max_known_peers == 0 {
+            return;
+        }
+        if !self.known_peers.contains_key(peer_id) {
+            if self.known_peers.len() >= self.config.max_known_peers {
+                if let Some(oldest) = self.known_peers_order.pop_front() {
+                    self.known_peers.remove(&oldest);
+                }
+            }
+            self.known_peers_order.push_back(peer_id.clone());
+        }
+        let known = self.known_peers.entry(peer_id.clone()).or_insert_with(Vec::new);
+        for addr in addresses {
+            if !known.contains(addr) {
+                known.push(addr.clone());
+            }
+        }
+    }
+
+    /// Builds the event reporting the outcome of `query`.
+    fn query_finished(&mut self, query: Query, result: Result<HttpResponse, RoutingError>)
+        -> RoutingEvent
+    {
+        match query {
+            Query::Providers(key) => {
+                let result = result.and_then(|response| api::parse_providers(&response));
+                if let Ok(providers) = &result {
+                    for provider in providers {
+                        self.add_known_peer(&provider.peer_id, &provider.addresses);
+                    }
+                }
+                RoutingEvent::FoundProviders { key, result }
+            }
+            Query::Peer(peer_id) => {
+                let result = result.and_then(|response| api::parse_peer(&peer_id, &response));
+                if let Ok(addresses) = &result {
+                    self.add_known_peer(&peer_id, addresses);
+                }
+                RoutingEvent::FoundPeer { peer_id, result }
+            }
+        }
+    }
+}
+
+impl<TSubstream, TClient> ContentRouting for DelegatedRouting<TSubstream, TClient>
+where
+    TClient: HttpClient,
+{
+    fn provide(&mut self, key: Multihash) {
+        let error = RoutingError::Other("providing through a delegated router is not supported".into());
+        self.events.push_back(RoutingEvent::Provided { key, result: Err(error) });
+    }
+
+    fn find_providers(&mut self, key: Multihash) {
+        let url = api::providers_url(&self.config.endpoint, &key);
+        self.start_request(Query::Providers(key), url);
+    }
+}
+
+impl<TSubstream, TClient> PeerRouting for DelegatedRouting<TSubstream, TClient>
+where
+    TClient: HttpClient,
+{
+    fn find_peer(&mut self, peer_id: PeerId) {
+        let url = api::peers_url(&self.config.endpoint, &peer_id);
+        self.start_request(Query::Peer(peer_id), url);
+    }
+}
+
+impl<TSubstream, TClient> NetworkBehaviour for DelegatedRouting<TSubstream, TClient>
+where
+    TSubstream: AsyncRead + AsyncWrite,
+    TClient: HttpClient,
+{
+    type ProtocolsHandler = DummyProtocolsHandler<TSubstream>;
+    type OutEvent = RoutingEvent;
+
+    fn new_handler(&mut self) -> Self::ProtocolsHandler {
+        DummyProtocolsHandler::default()
+    }
+
+    fn addresses_of_peer(&mut self, peer_id: &PeerId) -> Vec<Multiaddr> {
+        self.known_peers.get(peer_id).cloned().unwrap_or_default()
+    }
+
+    fn inject_connected(&mut self, _: PeerId, _: ConnectedPoint) {}
+
+    fn inject_disconnected(&mut self, _: &PeerId, _: ConnectedPoint) {}
+
+    fn inject_node_event(
+        &mut self,
+        _: PeerId,
+        ev: <Self::ProtocolsHandler as ProtocolsHandler>::OutEvent,
+    ) {
+        void::unreachable(ev)
+    }
+
+    fn poll(
+        &mut self,
+        _: &mut impl PollParameters,
+    ) -> Async<
+        NetworkBehaviourAction<
+            <Self::ProtocolsHandler as ProtocolsHandler>::InEvent,
+            Self::OutEvent,
+        >,
+    > {
+        let mut n = 0;
+        while n < self.requests.len() {
+            let result = match self.requests[n].response.poll() {
+                Ok(Async::Ready(response)) => Some(Ok(response)),
+                Ok(Async::NotReady) => match self.requests[n].timeout.poll() {
+                    Ok(Async::NotReady) => None,
+                    // A timer error is treated like an expired timer, so that the request
+                    // doesn't stay pending forever.
+                    Ok(Async::Ready(())) | Err(_) => Some(Err(RoutingError::Timeout)),
+                },
+                Err(err) => {
+                    debug!("Delegated routing request failed: {}", err);
+                    Some(Err(RoutingError::Other(err.to_string())))
+                }
+            };
+
+            match result {
+                Some(result) => {
+                    let request = self.requests.swap_remove(n);
+                    let event = self.query_finished(request.query, result);
+                    self.events.push_back(event);
+                }
+                None => n += 1,
+            }
+        }
+
+        if let Some(event) = self.events.pop_front() {
+            return Async::Ready(NetworkBehaviourAction::GenerateEvent(event));
+        }
+
+        Async::NotReady
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use futures::future::{self, FutureResult};
+    use libp2p_core::{identity, transport::dummy::{DummyStream, DummyTransport}};
+    use libp2p_mplex::Multiplex;
+    use libp2p_swarm::Swarm;
+    use std::io;
+    use tokio::runtime::current_thread;
+
+    /// Client answering every request with the same response.
+    struct StaticClient {
+        response: HttpResponse,
+        urls: Vec<String>,
+    }
+
+    impl HttpClient for StaticClient {
+        type Future = FutureResult<HttpResponse, io::Error>;
+
+        fn get(&mut self, url: &str) -> Self::Future {
+            self.urls.push(url.to_owned());
+            future::ok(self.response.clone())
+        }
+    }
+
+    #[test]
+    fn find_peer() {
+        let local_id = identity::Keypair::generate_ed25519().public().into_peer_id();
+        let peer_id = identity::Keypair::generate_ed25519().public().into_peer_id();
+        let addr: Multiaddr = "/ip4/1.2.3.4/tcp/4001".parse().unwrap();
+        let body = format!(r#"{{"Peers": [{{"Schema": "peer", "ID": "{}", "Addrs": ["{}"]}}]}}"#,
+            peer_id.to_base58(), addr);
+        let client = StaticClient {
+            response: HttpResponse { status: 200, body: body.into_bytes() },
+            urls: Vec::new(),
+        };
+        let config = DelegatedRoutingConfig::new("http://127.0.0.1:8080/");
+        let transport = DummyTransport::<(PeerId, Multiplex<DummyStream>)>::new();
+        let mut swarm = Swarm::new(transport, DelegatedRouting::new(config, client), local_id);
+
+        swarm.find_peer(peer_id.clone());
+        assert_eq!(swarm.client.urls, vec![
+            format!("http://127.0.0.1:8080/routing/v1/peers/{}", peer_id.to_base58())
+        ]);
+
+        let event = current_thread::block_on_all(future::poll_fn(|| swarm.poll())).unwrap();
+        match event {
+            Some(RoutingEvent::FoundPeer { peer_id: id, result }) => {
+                assert_eq!(id, peer_id);
+                assert_eq!(result, Ok(vec![addr.clone()]));
+            }
+            other => panic!("Unexpected event: {:?}", other),
+        }
+        assert_eq!(swarm.addresses_of_peer(&peer_id), vec![addr]);
+    }
+}
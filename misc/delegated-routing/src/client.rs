@@ -0,0 +1,97 @@
+// Copyright 2019 Parity Technologies (UK) Ltd.
+//
+// Permission is hereby granted, free of charge, to any person obtaining a
+// copy of this software and associated documentation files (the "Software"),
+// to deal in the Software without restriction, including without limitation
+// the rights to use, copy, modify, merge, publish, distribute, sublicense,
+// and/or sell copies of the Software, and to permit persons to whom the
+// Software is furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS
+// OR IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING
+// FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
+// DEALINGS IN THE SOFTWARE.
+
+use futures::prelude::*;
+use std::io;
+
+/// Response to an HTTP request sent through an [`HttpClient`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HttpResponse {
+    /// Status code of the response.
+    pub status: u16,
+    /// Body of the response.
+    pub body: Vec<u8>,
+}
+
+/// Sends the HTTP requests of the delegated router.
+///
+/// This is a trait so that the environments that have no access to sockets, such as browsers,
+/// can provide their own implementation.
+pub trait HttpClient {
+    /// Future that resolves to the response of the server.
+    type Future: Future<Item = HttpResponse, Error = io::Error>;
+
+    /// Sends a `GET` request for `url`, accepting a JSON response.
+    fn get(&mut self, url: &str) -> Self::Future;
+}
+
+/// [`HttpClient`] built on top of `hyper`.
+///
+/// [`HyperClient::new`] only supports plain HTTP. Endpoints served over HTTPS require building
+/// a `hyper::Client` with a TLS connector and converting it with `From`.
+#[cfg(feature = "hyper")]
+#[derive(Debug, Clone)]
+pub struct HyperClient<C> {
+    client: hyper::Client<C>,
+}
+
+#[cfg(feature = "hyper")]
+impl HyperClient<hyper::client::HttpConnector> {
+    /// Creates a new client using plain HTTP.
+    pub fn new() -> Self {
+        HyperClient { client: hyper::Client::new() }
+    }
+}
+
+#[cfg(feature = "hyper")]
+impl<C> From<hyper::Client<C>> for HyperClient<C> {
+    fn from(client: hyper::Client<C>) -> Self {
+        HyperClient { client }
+    }
+}
+
+#[cfg(feature = "hyper")]
+impl<C> HttpClient for HyperClient<C>
+where
+    C: hyper::client::connect::Connect + Sync + 'static,
+    C::Transport: 'static,
+    C::Future: 'static,
+{
+    type Future = Box<dyn Future<Item = HttpResponse, Error = io::Error> + Send>;
+
+    fn get(&mut self, url: &str) -> Self::Future {
+        let request = hyper::Request::get(url)
+            .header(hyper::header::ACCEPT, "application/json")
+            .body(hyper::Body::empty());
+        let request = match request {
+            Ok(request) => request,
+            Err(err) => return Box::new(futures::future::err(io::Error::new(io::ErrorKind::InvalidInput, err))),
+        };
+
+        let future = self.client.request(request)
+            .and_then(|response| {
+                let status = response.status().as_u16();
+                response.into_body().concat2()
+                    .map(move |body| HttpResponse { status, body: body.to_vec() })
+            })
+            .map_err(|err| io::Error::new(io::ErrorKind::Other, err));
+        Box::new(future)
+    }
+}
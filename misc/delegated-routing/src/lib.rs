@@ -0,0 +1,45 @@
+// Copyright 2019 Parity Technologies (UK) Ltd.
+//
+// Permission is hereby granted, free of charge, to any person obtaining a
+// copy of this software and associated documentation files (the "Software"),
+// to deal in the Software without restriction, including without limitation
+// the rights to use, copy, modify, merge, publish, distribute, sublicense,
+// and/or sell copies of the Software, and to permit persons to whom the
+// Software is furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS
+// OR IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING
+// FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
+// DEALINGS IN THE SOFTWARE.
+
+//! Peer and content routing through a delegated routing HTTP endpoint.
+//!
+//! Nodes that can't afford to take part in a DHT, such as mobile or browser nodes, can instead
+//! ask a remote server to perform the lookups on their behalf. This crate provides the
+//! [`DelegatedRouting`] behaviour, which implements the [`ContentRouting`] and [`PeerRouting`]
+//! traits of `libp2p-core` by querying an endpoint that serves the `/routing/v1` HTTP API.
+//!
+//! # Usage
+//!
+//! The HTTP requests are sent through an [`HttpClient`], which lets the application pick an
+//! implementation suitable for its environment. When the `hyper` feature is enabled, which is
+//! the default, the [`HyperClient`] implementation is available.
+//!
+//! The outcome of the lookups is produced as [`RoutingEvent`]s by the `NetworkBehaviour`, and
+//! the addresses of the peers that have been found are reported to the `Swarm` so that they can
+//! be dialed.
+
+mod api;
+mod behaviour;
+mod client;
+
+pub use behaviour::{DelegatedRouting, DelegatedRoutingConfig};
+pub use client::{HttpClient, HttpResponse};
+#[cfg(feature = "hyper")]
+pub use client::HyperClient;
@@ -0,0 +1,363 @@
+// Copyright 2019 Parity Technologies (UK) Ltd.
+//
+// Permission is hereby granted, free of charge, to any person obtaining a
+// copy of this software and associated documentation files (the "Software"),
+// to deal in the Software without restriction, including without limitation
+// the rights to use, copy, modify, merge, publish, distribute, sublicense,
+// and/or sell copies of the Software, and to permit persons to whom the
+// Software is furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS
+// OR IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING
+// FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
+// DEALINGS IN THE SOFTWARE.
+//! C bindings for libp2p.
+//!
+//! This crate exposes a small C ABI on top of a libp2p node, for applications that aren't
+//! written in Rust. A node combines floodsub, for publish/subscribe messaging, with a
+//! request-response protocol whose requests and responses are opaque bytes. It is built as a
+//! static and as a dynamic library.
+//!
+//! # Threading
+//!
+//! Each node runs on its own background thread, created by [`libp2p_node_new`]. The other
+//! functions only hand a command to that thread and return immediately. Everything the node
+//! observes, including errors that happen after a command has been accepted, such as failing
+//! to listen on an address, is reported by invoking the [`Libp2pCallback`] of the node, always
+//! from the node's thread.
+//!
+//! The pointers inside a [`Libp2pEvent`] are only valid for the duration of the callback and
+//! must be copied if they are needed afterwards. The callback may call the functions of this
+//! crate, including [`libp2p_node_respond`].
+//!
+//! # Mobile platforms
+//!
+//! The crate is meant to be embedded in Android and iOS applications: on Android, the
+//! dynamic library is loaded by the application and called through a thin JNI layer; on
+//! iOS, the static library is linked into the application and called directly. For example:
+//!
+//! ```sh
+//! cargo build --release -p libp2p-ffi --target aarch64-linux-android
+//! cargo build --release -p libp2p-ffi --target aarch64-apple-ios
+//! ```
+//!
+//! The Android build needs the C compiler and linker of the NDK, configured through the
+//! `CC_<target>` and `CARGO_TARGET_<TARGET>_LINKER` environment variables.
+//!
+//! Mobile devices switch networks all the time, and the connections established through the
+//! previous network are dead without the node noticing. Applications should forward the
+//! changes reported by the platform, by `ConnectivityManager.NetworkCallback` on Android or
+//! `NWPathMonitor` on iOS, to [`libp2p_node_network_changed`].
+//!
+//! # Conventions
+//!
+//! Strings are NUL-terminated UTF-8. Functions returning a `c_int` return [`LIBP2P_OK`] on
+//! success, [`LIBP2P_ERR_INVALID_ARGUMENT`] if one of their arguments can't be parsed, and
+//! [`LIBP2P_ERR_STOPPED`] if the node's thread is no longer running.
+
+mod node;
+
+pub use node::Libp2pNode;
+
+use libp2p::{Multiaddr, PeerId, swarm::NetworkChange};
+use node::{Callback, Command};
+use std::{ffi::CStr, ptr, slice};
+use std::os::raw::{c_char, c_int, c_void};
+
+/// The function succeeded.
+pub const LIBP2P_OK: c_int = 0;
+/// One of the arguments is null or can't be parsed.
+pub const LIBP2P_ERR_INVALID_ARGUMENT: c_int = -1;
+/// The node's thread is no longer running.
+pub const LIBP2P_ERR_STOPPED: c_int = -2;
+
+/// A network interface became available, while the existing ones are still usable.
+pub const LIBP2P_NETWORK_INTERFACE_UP: c_int = 0;
+/// A network interface went away.
+pub const LIBP2P_NETWORK_INTERFACE_DOWN: c_int = 1;
+/// The device switched to another kind of connectivity, for example from WiFi to cellular.
+pub const LIBP2P_NETWORK_CONNECTIVITY_CHANGED: c_int = 2;
+
+/// Function invoked by a node for each of its events.
+///
+/// `user_data` is the pointer passed to [`libp2p_node_new`].
+pub type Libp2pCallback = extern "C" fn(user_data: *mut c_void, event: *const Libp2pEvent);
+
+/// Kind of a [`Libp2pEvent`].
+#[repr(C)]
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Libp2pEventKind {
+    /// We are now connected to `peer`.
+    Connected = 0,
+    /// We are no longer connected to `peer`.
+    Disconnected = 1,
+    /// `peer` published `data` on `topic`, which we are subscribed to.
+    Message = 2,
+    /// `peer` sent the request `data`, which must be answered with [`libp2p_node_respond`]
+    /// and `request_id`.
+    Request = 3,
+    /// `peer` answered our request `request_id` with `data`.
+    Response = 4,
+    /// Our request `request_id` to `peer` failed. `data` describes the error.
+    RequestFailed = 5,
+    /// Something went wrong. `data` describes the error.
+    Error = 6,
+}
+
+/// An event reported to a [`Libp2pCallback`].
+#[repr(C)]
+#[derive(Debug)]
+pub struct Libp2pEvent {
+    pub kind: Libp2pEventKind,
+    /// Base58 representation of the remote's `PeerId`, or null for `Error` events.
+    pub peer: *const c_char,
+    /// Topic of `Message` events, null otherwise.
+    pub topic: *const c_char,
+    /// Identifier of the request of `Request`, `Response` and `RequestFailed` events.
+    pub request_id: u64,
+    /// Payload of the event. Null if `data_len` is `0`.
+    pub data: *const u8,
+    pub data_len: usize,
+}
+
+/// Creates a node with a new identity and starts it on a background thread.
+///
+/// Requests and responses are exchanged on the protocol named `protocol`, for example
+/// `/my-app/1.0.0`. Returns null if `protocol` isn't valid or if the thread can't be started.
+/// The node must be destroyed with [`libp2p_node_free`].
+#[no_mangle]
+pub unsafe extern "C" fn libp2p_node_new(
+    protocol: *const c_char,
+    callback: Libp2pCallback,
+    user_data: *mut c_void
+) -> *mut Libp2pNode {
+    let protocol = match str_arg(protocol) {
+        Some(protocol) => protocol.as_bytes().to_vec(),
+        None => return ptr::null_mut(),
+    };
+    match Libp2pNode::spawn(protocol, Callback { f: callback, user_data }) {
+        Ok(node) => Box::into_raw(Box::new(node)),
+        Err(err) => {
+            log::error!("Failed to start the node thread: {}", err);
+            ptr::null_mut()
+        }
+    }
+}
+
+/// Stops a node and releases it. Does nothing if `node` is null.
+///
+/// Waits for the node's thread to finish, unless called from the node's callback.
+#[no_mangle]
+pub unsafe extern "C" fn libp2p_node_free(node: *mut Libp2pNode) {
+    if !node.is_null() {
+        drop(Box::from_raw(node));
+    }
+}
+
+/// Returns the base58 representation of the `PeerId` of the node.
+///
+/// The string is owned by the node and valid until [`libp2p_node_free`].
+#[no_mangle]
+pub unsafe extern "C" fn libp2p_node_peer_id(node: *const Libp2pNode) -> *const c_char {
+    match node.as_ref() {
+        Some(node) => node.peer_id().as_ptr(),
+        None => ptr::null(),
+    }
+}
+
+/// Starts listening on the given multiaddress, for example `/ip4/0.0.0.0/tcp/0`.
+#[no_mangle]
+pub unsafe extern "C" fn libp2p_node_listen(node: *const Libp2pNode, address: *const c_char) -> c_int {
+    match multiaddr_arg(address) {
+        Some(address) => send(node, Command::Listen(address)),
+        None => LIBP2P_ERR_INVALID_ARGUMENT,
+    }
+}
+
+/// Dials the given multiaddress.
+#[no_mangle]
+pub unsafe extern "C" fn libp2p_node_dial(node: *const Libp2pNode, address: *const c_char) -> c_int {
+    match multiaddr_arg(address) {
+        Some(address) => send(node, Command::Dial(address)),
+        None => LIBP2P_ERR_INVALID_ARGUMENT,
+    }
+}
+
+/// Subscribes to a topic. Messages published on it are reported as `Message` events.
+#[no_mangle]
+pub unsafe extern "C" fn libp2p_node_subscribe(node: *const Libp2pNode, topic: *const c_char) -> c_int {
+    match str_arg(topic) {
+        Some(topic) => send(node, Command::Subscribe(topic.to_owned())),
+        None => LIBP2P_ERR_INVALID_ARGUMENT,
+    }
+}
+
+/// Unsubscribes from a topic.
+#[no_mangle]
+pub unsafe extern "C" fn libp2p_node_unsubscribe(node: *const Libp2pNode, topic: *const c_char) -> c_int {
+    match str_arg(topic) {
+        Some(topic) => send(node, Command::Unsubscribe(topic.to_owned())),
+        None => LIBP2P_ERR_INVALID_ARGUMENT,
+    }
+}
+
+/// Publishes `data_len` bytes at `data` on a topic. The node doesn't need to be subscribed to
+/// the topic.
+#[no_mangle]
+pub unsafe extern "C" fn libp2p_node_publish(
+    node: *const Libp2pNode,
+    topic: *const c_char,
+    data: *const u8,
+    data_len: usize
+) -> c_int {
+    match (str_arg(topic), bytes_arg(data, data_len)) {
+        (Some(topic), Some(data)) => send(node, Command::Publish(topic.to_owned(), data)),
+        _ => LIBP2P_ERR_INVALID_ARGUMENT,
+    }
+}
+
+/// Sends the request of `data_len` bytes at `data` to a peer, given as the base58
+/// representation of its `PeerId`.
+///
+/// If `address` isn't null, it is remembered as an address of the peer, to be dialed if we're
+/// not connected to it. On success, the identifier of the request is written to `request_id`
+/// if it isn't null; it is later reported with the `Response` or `RequestFailed` event.
+#[no_mangle]
+pub unsafe extern "C" fn libp2p_node_send_request(
+    node: *const Libp2pNode,
+    peer: *const c_char,
+    address: *const c_char,
+    data: *const u8,
+    data_len: usize,
+    request_id: *mut u64
+) -> c_int {
+    let node = match node.as_ref() {
+        Some(node) => node,
+        None => return LIBP2P_ERR_INVALID_ARGUMENT,
+    };
+    let peer = match str_arg(peer).and_then(|p| p.parse::<PeerId>().ok()) {
+        Some(peer) => peer,
+        None => return LIBP2P_ERR_INVALID_ARGUMENT,
+    };
+    let address = if address.is_null() {
+        None
+    } else {
+        match multiaddr_arg(address) {
+            Some(address) => Some(address),
+            None => return LIBP2P_ERR_INVALID_ARGUMENT,
+        }
+    };
+    let data = match bytes_arg(data, data_len) {
+        Some(data) => data,
+        None => return LIBP2P_ERR_INVALID_ARGUMENT,
+    };
+
+    let id = node.next_request_id();
+    if !node.send(Command::SendRequest { id, peer, address, data }) {
+        return LIBP2P_ERR_STOPPED
+    }
+    if let Some(request_id) = request_id.as_mut() {
+        *request_id = id;
+    }
+    LIBP2P_OK
+}
+
+/// Answers the request reported by the `Request` event with the given `request_id`.
+#[no_mangle]
+pub unsafe extern "C" fn libp2p_node_respond(
+    node: *const Libp2pNode,
+    request_id: u64,
+    data: *const u8,
+    data_len: usize
+) -> c_int {
+    match bytes_arg(data, data_len) {
+        Some(data) => send(node, Command::Respond { id: request_id, data }),
+        None => LIBP2P_ERR_INVALID_ARGUMENT,
+    }
+}
+
+/// Informs the node that the network of the device has changed. `change` is one of the
+/// `LIBP2P_NETWORK_*` constants.
+///
+/// Unless `change` is `LIBP2P_NETWORK_INTERFACE_UP`, the connections are closed, and the
+/// peers that we failed to dial may be dialed again immediately.
+#[no_mangle]
+pub unsafe extern "C" fn libp2p_node_network_changed(node: *const Libp2pNode, change: c_int) -> c_int {
+    let change = match change {
+        LIBP2P_NETWORK_INTERFACE_UP => NetworkChange::InterfaceUp,
+        LIBP2P_NETWORK_INTERFACE_DOWN => NetworkChange::InterfaceDown,
+        LIBP2P_NETWORK_CONNECTIVITY_CHANGED => NetworkChange::ConnectivityChanged,
+        _ => return LIBP2P_ERR_INVALID_ARGUMENT,
+    };
+    send(node, Command::NetworkChanged(change))
+}
+
+unsafe fn send(node: *const Libp2pNode, command: Command) -> c_int {
+    match node.as_ref() {
+        Some(node) if node.send(command) => LIBP2P_OK,
+        Some(_) => LIBP2P_ERR_STOPPED,
+        None => LIBP2P_ERR_INVALID_ARGUMENT,
+    }
+}
+
+unsafe fn str_arg<'a>(s: *const c_char) -> Option<&'a str> {
+    if s.is_null() {
+        return None
+    }
+    CStr::from_ptr(s).to_str().ok()
+}
+
+unsafe fn multiaddr_arg(s: *const c_char) -> Option<Multiaddr> {
+    str_arg(s).and_then(|s| s.parse().ok())
+}
+
+unsafe fn bytes_arg(data: *const u8, len: usize) -> Option<Vec<u8>> {
+    if len == 0 {
+        Some(Vec::new())
+    } else if data.is_null() {
+        None
+    } else {
+        Some(slice::from_raw_parts(data, len).to_vec())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::ffi::CString;
+
+    extern "C" fn ignore(_: *mut c_void, _: *const Libp2pEvent) {}
+
+    #[test]
+    fn node_lifecycle() {
+        unsafe {
+            let protocol = CString::new("/test/1.0.0").unwrap();
+            let node = libp2p_node_new(protocol.as_ptr(), ignore, ptr::null_mut());
+            assert!(!node.is_null());
+
+            let peer_id = CStr::from_ptr(libp2p_node_peer_id(node)).to_str().unwrap();
+            assert!(peer_id.parse::<PeerId>().is_ok());
+
+            let address = CString::new("/ip4/127.0.0.1/tcp/0").unwrap();
+            assert_eq!(libp2p_node_listen(node, address.as_ptr()), LIBP2P_OK);
+            let invalid = CString::new("not an address").unwrap();
+            assert_eq!(libp2p_node_listen(node, invalid.as_ptr()), LIBP2P_ERR_INVALID_ARGUMENT);
+
+            let topic = CString::new("topic").unwrap();
+            assert_eq!(libp2p_node_subscribe(node, topic.as_ptr()), LIBP2P_OK);
+            assert_eq!(libp2p_node_publish(node, topic.as_ptr(), b"hello".as_ptr(), 5), LIBP2P_OK);
+            assert_eq!(libp2p_node_publish(node, topic.as_ptr(), ptr::null(), 5), LIBP2P_ERR_INVALID_ARGUMENT);
+
+            assert_eq!(libp2p_node_network_changed(node, LIBP2P_NETWORK_CONNECTIVITY_CHANGED), LIBP2P_OK);
+            assert_eq!(libp2p_node_network_changed(node, 42), LIBP2P_ERR_INVALID_ARGUMENT);
+
+            libp2p_node_free(node);
+        }
+    }
+}
@@ -0,0 +1,382 @@
+// Copyright 2019 Parity Technologies (UK) Ltd.
+//
+// Permission is hereby granted, free of charge, to any person obtaining a
+// copy of this software and associated documentation files (the "Software"),
+// to deal in the Software without restriction, including without limitation
+// the rights to use, copy, modify, merge, publish, distribute, sublicense,
+// and/or sell copies of the Software, and to permit persons to whom the
+// Software is furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS
+// OR IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING
+// FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
+// DEALINGS IN THE SOFTWARE.
+//! The node driven by the C API, and the thread it runs on.
+
+use crate::{Libp2pCallback, Libp2pEvent, Libp2pEventKind};
+use futures::{future, prelude::*, sync::mpsc};
+use libp2p::{
+    NetworkBehaviour, PeerId, Multiaddr, Swarm,
+    floodsub::{Floodsub, FloodsubEvent, TopicBuilder, TopicHash},
+    identity::Keypair,
+    request_response::{
+        RequestId, RequestResponse, RequestResponseCodec, RequestResponseConfig,
+        RequestResponseEvent, RequestResponseMessage, ResponseChannel
+    },
+    swarm::{NetworkBehaviourEventProcess, NetworkChange, SwarmEvent},
+    tokio_io::{AsyncRead, AsyncWrite},
+};
+use std::{collections::{HashMap, VecDeque}, ffi::CString, io, iter, ptr, thread};
+use std::os::raw::c_void;
+use std::sync::{Arc, atomic::{AtomicU64, Ordering}};
+use tokio::runtime::current_thread;
+
+/// A libp2p node running on a background thread.
+///
+/// Dropping the node stops its thread.
+pub struct Libp2pNode {
+    peer_id: CString,
+    next_request_id: Arc<AtomicU64>,
+    commands: mpsc::UnboundedSender<Command>,
+    thread: Option<thread::JoinHandle<()>>,
+}
+
+/// A command sent by the C API to the node's thread.
+#[derive(Debug)]
+pub(crate) enum Command {
+    Listen(Multiaddr),
+    Dial(Multiaddr),
+    Subscribe(String),
+    Unsubscribe(String),
+    Publish(String, Vec<u8>),
+    SendRequest { id: u64, peer: PeerId, address: Option<Multiaddr>, data: Vec<u8> },
+    Respond { id: u64, data: Vec<u8> },
+    NetworkChanged(NetworkChange),
+}
+
+impl Libp2pNode {
+    /// Generates a new identity and starts a node with it on a new thread. Requests and
+    /// responses are exchanged on `protocol`.
+    pub(crate) fn spawn(protocol: Vec<u8>, callback: Callback) -> io::Result<Libp2pNode> {
+        let keypair = Keypair::generate_ed25519();
+        let peer_id = CString::new(keypair.public().into_peer_id().to_base58())
+            .expect("base58 strings don't contain NUL bytes");
+        let next_request_id = Arc::new(AtomicU64::new(0));
+        let (commands, commands_rx) = mpsc::unbounded();
+
+        let thread = {
+            let next_request_id = next_request_id.clone();
+            thread::Builder::new()
+                .name("libp2p-ffi".to_owned())
+                .spawn(move || {
+                    let mut runtime = match current_thread::Runtime::new() {
+                        Ok(runtime) => runtime,
+                        Err(err) => {
+                            callback.emit(Event::Error(format!("Failed to start the runtime: {}", err)));
+                            return
+                        }
+                    };
+                    let _ = runtime.block_on(run(keypair, protocol, next_request_id, callback, commands_rx));
+                })?
+        };
+
+        Ok(Libp2pNode {
+            peer_id,
+            next_request_id,
+            commands,
+            thread: Some(thread),
+        })
+    }
+
+    /// Returns the base58 representation of the node's `PeerId`.
+    pub(crate) fn peer_id(&self) -> &CString {
+        &self.peer_id
+    }
+
+    /// Allocates the identifier of a new request.
+    pub(crate) fn next_request_id(&self) -> u64 {
+        self.next_request_id.fetch_add(1, Ordering::Relaxed)
+    }
+
+    /// Sends a command to the node's thread. Returns `false` if the thread has stopped.
+    pub(crate) fn send(&self, command: Command) -> bool {
+        self.commands.unbounded_send(command).is_ok()
+    }
+}
+
+impl Drop for Libp2pNode {
+    fn drop(&mut self) {
+        // Closing the channel makes the node's future finish.
+        self.commands.close_channel();
+        if let Some(thread) = self.thread.take() {
+            // Joining from the callback would wait for ourselves.
+            if thread.thread().id() != thread::current().id() {
+                let _ = thread.join();
+            }
+        }
+    }
+}
+
+/// The callback registered by the user, along with its opaque context pointer.
+#[derive(Clone, Copy)]
+pub(crate) struct Callback {
+    pub(crate) f: Libp2pCallback,
+    pub(crate) user_data: *mut c_void,
+}
+
+// The pointer is never dereferenced on our side; the user is responsible for making the data
+// it points to usable from the node's thread.
+unsafe impl Send for Callback {}
+
+impl Callback {
+    /// Reports an event to the user.
+    fn emit(&self, event: Event) {
+        let peer = event.peer().map(|p| CString::new(p.to_base58()).expect("base58 strings don't contain NUL bytes"));
+        let topic = event.topic().and_then(|t| CString::new(t).ok());
+        let (kind, request_id, data) = match event {
+            Event::Connected(_) => (Libp2pEventKind::Connected, 0, Vec::new()),
+            Event::Disconnected(_) => (Libp2pEventKind::Disconnected, 0, Vec::new()),
+            Event::Message { data, .. } => (Libp2pEventKind::Message, 0, data),
+            Event::Request { id, data, .. } => (Libp2pEventKind::Request, id, data),
+            Event::Response { id, data, .. } => (Libp2pEventKind::Response, id, data),
+            Event::RequestFailed { id, error, .. } => (Libp2pEventKind::RequestFailed, id, error.into_bytes()),
+            Event::Error(error) => (Libp2pEventKind::Error, 0, error.into_bytes()),
+        };
+        let event = Libp2pEvent {
+            kind,
+            peer: peer.as_ref().map_or(ptr::null(), |p| p.as_ptr()),
+            topic: topic.as_ref().map_or(ptr::null(), |t| t.as_ptr()),
+            request_id,
+            data: if data.is_empty() { ptr::null() } else { data.as_ptr() },
+            data_len: data.len(),
+        };
+        (self.f)(self.user_data, &event);
+    }
+}
+
+/// An event to report to the user.
+#[derive(Debug)]
+enum Event {
+    Connected(PeerId),
+    Disconnected(PeerId),
+    Message { source: PeerId, topic: String, data: Vec<u8> },
+    Request { peer: PeerId, id: u64, data: Vec<u8> },
+    Response { peer: PeerId, id: u64, data: Vec<u8> },
+    RequestFailed { peer: PeerId, id: u64, error: String },
+    Error(String),
+}
+
+impl Event {
+    fn peer(&self) -> Option<&PeerId> {
+        match self {
+            Event::Connected(peer) | Event::Disconnected(peer) => Some(peer),
+            Event::Message { source, .. } => Some(source),
+            Event::Request { peer, .. } | Event::Response { peer, .. } => Some(peer),
+            Event::RequestFailed { peer, .. } => Some(peer),
+            Event::Error(_) => None,
+        }
+    }
+
+    fn topic(&self) -> Option<&str> {
+        match self {
+            Event::Message { topic, .. } => Some(topic),
+            _ => None,
+        }
+    }
+}
+
+/// Codec whose requests and responses are the raw bytes of the messages.
+#[derive(Debug, Clone)]
+struct BytesCodec;
+
+impl RequestResponseCodec for BytesCodec {
+    type Request = Vec<u8>;
+    type Response = Vec<u8>;
+
+    fn encode_request(&self, request: Vec<u8>) -> Vec<u8> {
+        request
+    }
+
+    fn decode_request(&self, bytes: Vec<u8>) -> Result<Vec<u8>, io::Error> {
+        Ok(bytes)
+    }
+
+    fn encode_response(&self, response: Vec<u8>) -> Vec<u8> {
+        response
+    }
+
+    fn decode_response(&self, bytes: Vec<u8>) -> Result<Vec<u8>, io::Error> {
+        Ok(bytes)
+    }
+}
+
+#[derive(NetworkBehaviour)]
+struct Behaviour<TSubstream: AsyncRead + AsyncWrite> {
+    floodsub: Floodsub<TSubstream>,
+    request_response: RequestResponse<TSubstream, BytesCodec>,
+
+    /// Names of the topics we're subscribed to.
+    #[behaviour(ignore)]
+    topics: HashMap<TopicHash, String>,
+    /// Requests waiting for the user to respond, by the identifier given to the user.
+    #[behaviour(ignore)]
+    inbound: HashMap<u64, (RequestId, ResponseChannel<Vec<u8>>)>,
+    /// Identifiers given to the user for our pending requests.
+    #[behaviour(ignore)]
+    outbound: HashMap<RequestId, u64>,
+    #[behaviour(ignore)]
+    next_request_id: Arc<AtomicU64>,
+    #[behaviour(ignore)]
+    events: VecDeque<Event>,
+}
+
+impl<TSubstream: AsyncRead + AsyncWrite> NetworkBehaviourEventProcess<FloodsubEvent> for Behaviour<TSubstream> {
+    fn inject_event(&mut self, event: FloodsubEvent) {
+        if let FloodsubEvent::Message(message) = event {
+            for topic in &message.topics {
+                if let Some(name) = self.topics.get(topic) {
+                    self.events.push_back(Event::Message {
+                        source: message.source.clone(),
+                        topic: name.clone(),
+                        data: message.data.clone(),
+                    });
+                }
+            }
+        }
+    }
+}
+
+impl<TSubstream: AsyncRead + AsyncWrite> NetworkBehaviourEventProcess<RequestResponseEvent<Vec<u8>, Vec<u8>>> for Behaviour<TSubstream> {
+    fn inject_event(&mut self, event: RequestResponseEvent<Vec<u8>, Vec<u8>>) {
+        match event {
+            RequestResponseEvent::Message { peer, message: RequestResponseMessage::Request { request_id, request, channel } } => {
+                let id = self.next_request_id.fetch_add(1, Ordering::Relaxed);
+                self.inbound.insert(id, (request_id, channel));
+                self.events.push_back(Event::Request { peer, id, data: request });
+            }
+            RequestResponseEvent::Message { peer, message: RequestResponseMessage::Response { request_id, response } } => {
+                if let Some(id) = self.outbound.remove(&request_id) {
+                    self.events.push_back(Event::Response { peer, id, data: response });
+                }
+            }
+            RequestResponseEvent::OutboundFailure { peer, request_id, error } => {
+                if let Some(id) = self.outbound.remove(&request_id) {
+                    self.events.push_back(Event::RequestFailed { peer, id, error: format!("{:?}", error) });
+                }
+            }
+            RequestResponseEvent::InboundFailure { request_id, error, .. } => {
+                log::debug!("Failed to answer request {}: {:?}", request_id, error);
+                self.inbound.retain(|_, (r, _)| *r != request_id);
+            }
+        }
+    }
+}
+
+/// Builds the node and returns the future that drives it until `commands` is closed.
+fn run(
+    keypair: Keypair,
+    protocol: Vec<u8>,
+    next_request_id: Arc<AtomicU64>,
+    callback: Callback,
+    mut commands: mpsc::UnboundedReceiver<Command>
+) -> impl Future<Item = (), Error = ()> {
+    let local_peer_id = keypair.public().into_peer_id();
+    let transport = libp2p::build_development_transport(keypair.clone());
+    let behaviour = Behaviour {
+        floodsub: Floodsub::with_keypair(keypair),
+        request_response: RequestResponse::new(BytesCodec, iter::once(protocol), RequestResponseConfig::default()),
+        topics: HashMap::new(),
+        inbound: HashMap::new(),
+        outbound: HashMap::new(),
+        next_request_id,
+        events: VecDeque::new(),
+    };
+    let mut swarm = Swarm::new(transport, behaviour, local_peer_id);
+
+    future::poll_fn(move || {
+        loop {
+            let command = match commands.poll() {
+                Ok(Async::Ready(Some(command))) => command,
+                Ok(Async::Ready(None)) | Err(()) => return Ok(Async::Ready(())),
+                Ok(Async::NotReady) => break,
+            };
+            match command {
+                Command::Listen(address) => {
+                    if let Err(err) = Swarm::listen_on(&mut swarm, address.clone()) {
+                        callback.emit(Event::Error(format!("Failed to listen on {}: {:?}", address, err)));
+                    }
+                }
+                Command::Dial(address) => {
+                    if let Err(err) = Swarm::dial_addr(&mut swarm, address.clone()) {
+                        callback.emit(Event::Error(format!("Failed to dial {}: {:?}", address, err)));
+                    }
+                }
+                Command::Subscribe(name) => {
+                    let topic = TopicBuilder::new(name.clone()).build();
+                    swarm.topics.insert(topic.hash().clone(), name);
+                    swarm.floodsub.subscribe(topic);
+                }
+                Command::Unsubscribe(name) => {
+                    let topic = TopicBuilder::new(name).build();
+                    swarm.topics.remove(topic.hash());
+                    swarm.floodsub.unsubscribe(topic);
+                }
+                Command::Publish(name, data) => {
+                    let topic = TopicBuilder::new(name).build();
+                    swarm.floodsub.publish_any(&topic, data);
+                }
+                Command::SendRequest { id, peer, address, data } => {
+                    if let Some(address) = address {
+                        swarm.request_response.add_address(&peer, address);
+                    }
+                    let request_id = swarm.request_response.send_request(&peer, data);
+                    swarm.outbound.insert(request_id, id);
+                }
+                Command::Respond { id, data } => {
+                    if let Some((_, channel)) = swarm.inbound.remove(&id) {
+                        swarm.request_response.send_response(channel, data);
+                    } else {
+                        callback.emit(Event::Error(format!("Unknown or expired request {}", id)));
+                    }
+                }
+                Command::NetworkChanged(change) => {
+                    Swarm::notify_network_change(&mut swarm, change);
+                }
+            }
+        }
+
+        loop {
+            let polled = Swarm::poll_event(&mut swarm);
+            for event in swarm.events.drain(..) {
+                callback.emit(event);
+            }
+            match polled {
+                Ok(Async::Ready(SwarmEvent::ConnectionEstablished { peer_id, num_established, .. })) => {
+                    if num_established.get() == 1 {
+                        swarm.floodsub.add_node_to_partial_view(peer_id.clone());
+                        callback.emit(Event::Connected(peer_id));
+                    }
+                }
+                Ok(Async::Ready(SwarmEvent::ConnectionClosed { peer_id, num_established: 0, .. })) => {
+                    swarm.floodsub.remove_node_from_partial_view(&peer_id);
+                    callback.emit(Event::Disconnected(peer_id));
+                }
+                Ok(Async::Ready(SwarmEvent::UnreachableAddr { address, cause, .. })) => {
+                    callback.emit(Event::Error(format!("Failed to reach {}: {:?}", address, cause)));
+                }
+                Ok(Async::Ready(_)) => {}
+                Ok(Async::NotReady) => return Ok(Async::NotReady),
+                Err(err) => {
+                    callback.emit(Event::Error(format!("Node stopped: {}", err)));
+                    return Err(())
+                }
+            }
+        }
+    })
+}
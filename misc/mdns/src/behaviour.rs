@@ -18,7 +18,7 @@
 // FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
 // DEALINGS IN THE SOFTWARE.
 
-use crate::service::{MdnsService, MdnsPacket};
+use crate::service::{MdnsConfig, MdnsService, MdnsPacket};
 use futures::prelude::*;
 use libp2p_core::{address_translation, ConnectedPoint, Multiaddr, PeerId, multiaddr::Protocol};
 use libp2p_swarm::{
@@ -40,6 +40,12 @@ pub struct Mdns<TSubstream> {
     /// The inner service.
     service: MdnsService,
 
+    /// Time-to-live of the records we announce.
+    ttl: Duration,
+
+    /// Whether we refrain from answering queries.
+    listen_only: bool,
+
     /// List of nodes that we have discovered, the address, and when their TTL expires.
     ///
     /// Each combination of `PeerId` and `Multiaddr` can only appear once, but the same `PeerId`
@@ -58,8 +64,15 @@ pub struct Mdns<TSubstream> {
 impl<TSubstream> Mdns<TSubstream> {
     /// Builds a new `Mdns` behaviour.
     pub fn new() -> io::Result<Mdns<TSubstream>> {
+        Mdns::with_config(MdnsConfig::default())
+    }
+
+    /// Builds a new `Mdns` behaviour with the given configuration.
+    pub fn with_config(config: MdnsConfig) -> io::Result<Mdns<TSubstream>> {
         Ok(Mdns {
-            service: MdnsService::new()?,
+            service: MdnsService::with_config(&config)?,
+            ttl: config.ttl(),
+            listen_only: config.listen_only(),
             discovered_nodes: SmallVec::new(),
             closest_expiration: None,
             marker: PhantomData,
@@ -70,6 +83,21 @@ impl<TSubstream> Mdns<TSubstream> {
     pub fn has_node(&self, peer_id: &PeerId) -> bool {
         self.discovered_nodes.iter().any(|(p, _, _)| p == peer_id)
     }
+
+    /// Returns the addresses discovered through mDNS that haven't expired yet, with the peer
+    /// they belong to.
+    pub fn discovered_nodes(&self) -> impl Iterator<Item = (&PeerId, &Multiaddr)> {
+        self.discovered_nodes.iter().map(|(p, a, _)| (p, a))
+    }
+
+    /// Builds the timer that fires when the first of the discovered records expires.
+    fn next_expiration(&self) -> Option<Delay> {
+        self.discovered_nodes.iter()
+            .fold(None, |exp, &(_, _, elem_exp)| {
+                Some(exp.map(|exp| cmp::min(exp, elem_exp)).unwrap_or(elem_exp))
+            })
+            .map(Delay::new)
+    }
 }
 
 /// Event that can be produced by the `Mdns` behaviour.
@@ -81,7 +109,9 @@ pub enum MdnsEvent {
     /// The given combinations of `PeerId` and `Multiaddr` have expired.
     ///
     /// Each discovered record has a time-to-live. When this TTL expires and the address hasn't
-    /// been refreshed, we remove it from the list emit it as an `Expired` event.
+    /// been refreshed, we remove it from the list emit it as an `Expired` event. This happens in
+    /// particular when a peer leaves the network and stops answering our queries, and can be
+    /// used to prune the addresses of the peer that were learned through mDNS.
     Expired(ExpiredAddrsIter),
 }
 
@@ -185,16 +215,22 @@ where
         >,
     > {
         // Remove expired peers.
-        if let Some(ref mut closest_expiration) = self.closest_expiration {
+        //
+        // The timer is reset to the next expiration every time it fires, and polled again until
+        // it is pending, so that the task is woken up when the next record expires even if the
+        // remote stopped announcing itself and no packet is ever received from it again.
+        while let Some(closest_expiration) = self.closest_expiration.as_mut() {
             match closest_expiration.poll() {
                 Ok(Async::Ready(())) => {
                     let now = Instant::now();
                     let mut expired = SmallVec::<[(PeerId, Multiaddr); 4]>::new();
-                    while let Some(pos) = self.discovered_nodes.iter().position(|(_, _, exp)| *exp < now) {
+                    while let Some(pos) = self.discovered_nodes.iter().position(|(_, _, exp)| *exp <= now) {
                         let (peer_id, addr, _) = self.discovered_nodes.remove(pos);
                         expired.push((peer_id, addr));
                     }
 
+                    self.closest_expiration = self.next_expiration();
+
                     if !expired.is_empty() {
                         let event = MdnsEvent::Expired(ExpiredAddrsIter {
                             inner: expired.into_iter(),
@@ -203,8 +239,11 @@ where
                         return Async::Ready(NetworkBehaviourAction::GenerateEvent(event));
                     }
                 },
-                Ok(Async::NotReady) => (),
-                Err(err) => warn!("tokio timer has errored: {:?}", err),
+                Ok(Async::NotReady) => break,
+                Err(err) => {
+                    warn!("tokio timer has errored: {:?}", err);
+                    break
+                }
             }
         }
 
@@ -217,11 +256,13 @@ where
 
             match event {
                 MdnsPacket::Query(query) => {
-                    let _ = query.respond(
-                        params.local_peer_id().clone(),
-                        params.listened_addresses(),
-                        Duration::from_secs(5 * 60)
-                    );
+                    if !self.listen_only {
+                        let _ = query.respond(
+                            params.local_peer_id().clone(),
+                            params.listened_addresses(),
+                            self.ttl
+                        );
+                    }
                 },
                 MdnsPacket::Response(response) => {
                     // We replace the IP address with the address we observe the
@@ -264,17 +305,15 @@ where
                     break discovered;
                 },
                 MdnsPacket::ServiceDiscovery(disc) => {
-                    disc.respond(Duration::from_secs(5 * 60));
+                    if !self.listen_only {
+                        disc.respond(self.ttl);
+                    }
                 },
             }
         };
 
         // As the final step, we need to refresh `closest_expiration`.
-        self.closest_expiration = self.discovered_nodes.iter()
-            .fold(None, |exp, &(_, _, elem_exp)| {
-                Some(exp.map(|exp| cmp::min(exp, elem_exp)).unwrap_or(elem_exp))
-            })
-            .map(Delay::new);
+        self.closest_expiration = self.next_expiration();
         Async::Ready(NetworkBehaviourAction::GenerateEvent(MdnsEvent::Discovered(DiscoveredAddrsIter {
             inner: discovered.into_iter(),
         })))
@@ -36,7 +36,7 @@ const SERVICE_NAME: &[u8] = b"_p2p._udp.local";
 const META_QUERY_SERVICE: &[u8] = b"_services._dns-sd._udp.local";
 
 pub use self::behaviour::{Mdns, MdnsEvent};
-pub use self::service::MdnsService;
+pub use self::service::{MdnsConfig, MdnsService};
 
 mod behaviour;
 mod dns;
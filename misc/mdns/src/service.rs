@@ -22,17 +22,123 @@ use crate::{SERVICE_NAME, META_QUERY_SERVICE, dns};
 use dns_parser::{Packet, RData};
 use futures::{prelude::*, task};
 use libp2p_core::{Multiaddr, PeerId};
+use log::debug;
 use multiaddr::Protocol;
-use std::{fmt, io, net::Ipv4Addr, net::SocketAddr, str, time::Duration};
+use std::{fmt, io, net::{Ipv4Addr, Ipv6Addr, SocketAddr}, str, time::Duration};
 use tokio_reactor::Handle;
 use wasm_timer::{Instant, Interval};
 use tokio_udp::UdpSocket;
 
 pub use dns::MdnsResponseError;
 
+/// The UDP port of mDNS.
+const MDNS_PORT: u16 = 5353;
+/// The IPv4 multicast address of mDNS.
+const IPV4_MDNS_MULTICAST_ADDRESS: Ipv4Addr = Ipv4Addr::new(224, 0, 0, 251);
+/// The IPv6 (link-local) multicast address of mDNS.
+const IPV6_MDNS_MULTICAST_ADDRESS: Ipv6Addr = Ipv6Addr::new(0xFF02, 0, 0, 0, 0, 0, 0, 0xFB);
+
+/// Configuration of an mDNS service.
+#[derive(Debug, Clone)]
+pub struct MdnsConfig {
+    /// Name of the service that is queried and announced.
+    service_name: Vec<u8>,
+    /// Time-to-live of the records we announce.
+    ttl: Duration,
+    /// Interval between two queries.
+    query_interval: Duration,
+    /// Whether we refrain from sending queries.
+    silent: bool,
+    /// Whether we refrain from answering queries, i.e. from announcing ourselves.
+    listen_only: bool,
+}
+
+impl MdnsConfig {
+    /// Creates a new `MdnsConfig` with the following default settings:
+    ///
+    ///   * [`MdnsConfig::with_service_name`] `_p2p._udp.local`
+    ///   * [`MdnsConfig::with_ttl`] 5 minutes
+    ///   * [`MdnsConfig::with_query_interval`] 20 seconds
+    ///   * [`MdnsConfig::with_silent`] false
+    ///   * [`MdnsConfig::with_listen_only`] false
+    pub fn new() -> Self {
+        MdnsConfig {
+            service_name: SERVICE_NAME.to_vec(),
+            ttl: Duration::from_secs(5 * 60),
+            query_interval: Duration::from_secs(20),
+            silent: false,
+            listen_only: false,
+        }
+    }
+
+    /// Sets the name of the service that is queried and announced.
+    ///
+    /// Only nodes using the same service name discover each other, which allows separate
+    /// applications on the same network not to discover each other. The default is the
+    /// `_p2p._udp.local` name of the libp2p mDNS specifications.
+    ///
+    /// # Panic
+    ///
+    /// Panics if the name isn't made of dot-separated ASCII labels of 1 to 63 bytes.
+    pub fn with_service_name(mut self, name: impl Into<Vec<u8>>) -> Self {
+        let name = name.into();
+        assert!(name.is_ascii(), "mDNS service name must be ASCII");
+        assert!(name.split(|&c| c == b'.').all(|l| !l.is_empty() && l.len() < 64),
+            "mDNS service name labels must be between 1 and 63 bytes long");
+        self.service_name = name;
+        self
+    }
+
+    /// Sets the time-to-live of the records we announce, i.e. the duration after which the
+    /// nodes that discovered us forget about our addresses unless we announce them again.
+    pub fn with_ttl(mut self, ttl: Duration) -> Self {
+        self.ttl = ttl;
+        self
+    }
+
+    /// Sets the interval between two queries sent on the network.
+    pub fn with_query_interval(mut self, interval: Duration) -> Self {
+        self.query_interval = interval;
+        self
+    }
+
+    /// Sets whether we refrain from sending queries on the network, in which case we only
+    /// discover the nodes that answer the queries of others.
+    pub fn with_silent(mut self, silent: bool) -> Self {
+        self.silent = silent;
+        self
+    }
+
+    /// Sets whether we refrain from answering queries, i.e. whether we discover other nodes
+    /// without announcing ourselves.
+    pub fn with_listen_only(mut self, listen_only: bool) -> Self {
+        self.listen_only = listen_only;
+        self
+    }
+
+    /// Returns the time-to-live of the records we announce.
+    pub fn ttl(&self) -> Duration {
+        self.ttl
+    }
+
+    /// Returns whether we refrain from answering queries.
+    pub fn listen_only(&self) -> bool {
+        self.listen_only
+    }
+}
+
+impl Default for MdnsConfig {
+    fn default() -> Self {
+        MdnsConfig::new()
+    }
+}
+
 /// A running service that discovers libp2p peers and responds to other libp2p peers' queries on
 /// the local network.
 ///
+/// The service joins both the IPv4 (`224.0.0.251`) and the IPv6 (`FF02::FB`) mDNS multicast
+/// groups, if available, and answers queries on the IP version on which they were received.
+///
 /// # Usage
 ///
 /// In order to use mDNS to discover peers on the local network, use the `MdnsService`. This is
@@ -92,16 +198,28 @@ pub use dns::MdnsResponseError;
 /// }).for_each(|_| Ok(()));
 /// # }
 pub struct MdnsService {
-    /// Main socket for listening.
-    socket: UdpSocket,
-    /// Socket for sending queries on the network.
-    query_socket: UdpSocket,
+    /// Sockets and send buffers for IPv4, if IPv4 multicast could be set up.
+    ipv4: Option<MdnsSocket>,
+    /// Sockets and send buffers for IPv6, if IPv6 multicast could be set up.
+    ipv6: Option<MdnsSocket>,
+    /// Name of the service that is queried and answered.
+    service_name: Vec<u8>,
     /// Interval for sending queries.
     query_interval: Interval,
     /// Whether we send queries on the network at all.
     /// Note that we still need to have an interval for querying, as we need to wake up the socket
     /// regularly to recover from errors. Otherwise we could simply use an `Option<Interval>`.
     silent: bool,
+}
+
+/// The sockets of the `MdnsService` for one IP version.
+struct MdnsSocket {
+    /// Main socket for listening.
+    socket: UdpSocket,
+    /// Socket for sending queries on the network.
+    query_socket: UdpSocket,
+    /// Multicast address and port to which packets are sent.
+    multicast_addr: SocketAddr,
     /// Buffer used for receiving data from the main socket.
     recv_buffer: [u8; 2048],
     /// Buffers pending to send on the main socket.
@@ -114,45 +232,56 @@ impl MdnsService {
     /// Starts a new mDNS service.
     #[inline]
     pub fn new() -> io::Result<MdnsService> {
-        Self::new_inner(false)
+        Self::with_config(&MdnsConfig::new())
     }
 
     /// Same as `new`, but we don't send automatically send queries on the network.
     #[inline]
     pub fn silent() -> io::Result<MdnsService> {
-        Self::new_inner(true)
+        Self::with_config(&MdnsConfig::new().with_silent(true))
+    }
+
+    /// Starts a new mDNS service with the given configuration.
+    ///
+    /// > **Note**: The service itself doesn't answer queries. The time-to-live and the
+    /// > listen-only setting of the configuration apply to the `Mdns` behaviour.
+    pub fn with_config(config: &MdnsConfig) -> io::Result<MdnsService> {
+        Self::new_inner(config.service_name.clone(), config.query_interval, config.silent)
     }
 
     /// Starts a new mDNS service.
-    fn new_inner(silent: bool) -> io::Result<MdnsService> {
-        let socket = {
-            #[cfg(unix)]
-            fn platform_specific(s: &net2::UdpBuilder) -> io::Result<()> {
-                net2::unix::UnixUdpBuilderExt::reuse_port(s, true)?;
-                Ok(())
+    ///
+    /// Both the IPv4 and the IPv6 multicast groups are joined. The service works as long as
+    /// at least one of them could be joined, e.g. on hosts without IPv6 or on networks where
+    /// IPv4 multicast is filtered.
+    fn new_inner(service_name: Vec<u8>, query_interval: Duration, silent: bool)
+        -> io::Result<MdnsService>
+    {
+        let ipv4 = MdnsSocket::new_v4();
+        let ipv6 = MdnsSocket::new_v6();
+
+        let (ipv4, ipv6) = match (ipv4, ipv6) {
+            (Err(err), Err(err_v6)) => {
+                debug!("Failed to join the IPv6 mDNS multicast group: {:?}", err_v6);
+                return Err(err)
+            }
+            (ipv4, ipv6) => {
+                if let Err(err) = &ipv4 {
+                    debug!("Failed to join the IPv4 mDNS multicast group: {:?}", err);
+                }
+                if let Err(err) = &ipv6 {
+                    debug!("Failed to join the IPv6 mDNS multicast group: {:?}", err);
+                }
+                (ipv4.ok(), ipv6.ok())
             }
-            #[cfg(not(unix))]
-            fn platform_specific(_: &net2::UdpBuilder) -> io::Result<()> { Ok(()) }
-            let builder = net2::UdpBuilder::new_v4()?;
-            builder.reuse_address(true)?;
-            platform_specific(&builder)?;
-            builder.bind(("0.0.0.0", 5353))?
         };
 
-        let socket = UdpSocket::from_std(socket, &Handle::default())?;
-        socket.set_multicast_loop_v4(true)?;
-        socket.set_multicast_ttl_v4(255)?;
-        // TODO: correct interfaces?
-        socket.join_multicast_v4(&From::from([224, 0, 0, 251]), &Ipv4Addr::UNSPECIFIED)?;
-
         Ok(MdnsService {
-            socket,
-            query_socket: UdpSocket::bind(&From::from(([0, 0, 0, 0], 0)))?,
-            query_interval: Interval::new(Instant::now(), Duration::from_secs(20)),
+            ipv4,
+            ipv6,
+            service_name,
+            query_interval: Interval::new(Instant::now(), query_interval),
             silent,
-            recv_buffer: [0; 2048],
-            send_buffers: Vec::new(),
-            query_send_buffers: Vec::new(),
         })
     }
 
@@ -164,21 +293,89 @@ impl MdnsService {
         match self.query_interval.poll() {
             Ok(Async::Ready(_)) => {
                 if !self.silent {
-                    let query = dns::build_query();
-                    self.query_send_buffers.push(query.to_vec());
+                    let query = dns::build_query(&self.service_name);
+                    for socket in self.ipv4.iter_mut().chain(self.ipv6.iter_mut()) {
+                        socket.query_send_buffers.push(query.to_vec());
+                    }
                 }
             }
             Ok(Async::NotReady) => (),
             _ => unreachable!("A wasm_timer::Interval never errors"), // TODO: is that true?
         };
 
+        if let Some(ipv4) = &mut self.ipv4 {
+            if let Async::Ready(packet) = ipv4.poll(&self.service_name) {
+                return Async::Ready(packet)
+            }
+        }
+
+        if let Some(ipv6) = &mut self.ipv6 {
+            if let Async::Ready(packet) = ipv6.poll(&self.service_name) {
+                return Async::Ready(packet)
+            }
+        }
+
+        Async::NotReady
+    }
+}
+
+impl MdnsSocket {
+    /// Binds the sockets and joins the IPv4 mDNS multicast group.
+    fn new_v4() -> io::Result<MdnsSocket> {
+        let socket = {
+            let builder = net2::UdpBuilder::new_v4()?;
+            builder.reuse_address(true)?;
+            reuse_port(&builder)?;
+            builder.bind(("0.0.0.0", MDNS_PORT))?
+        };
+
+        let socket = UdpSocket::from_std(socket, &Handle::default())?;
+        socket.set_multicast_loop_v4(true)?;
+        socket.set_multicast_ttl_v4(255)?;
+        // TODO: correct interfaces?
+        socket.join_multicast_v4(&IPV4_MDNS_MULTICAST_ADDRESS, &Ipv4Addr::UNSPECIFIED)?;
+
+        Ok(MdnsSocket {
+            socket,
+            query_socket: UdpSocket::bind(&From::from(([0, 0, 0, 0], 0)))?,
+            multicast_addr: SocketAddr::new(IPV4_MDNS_MULTICAST_ADDRESS.into(), MDNS_PORT),
+            recv_buffer: [0; 2048],
+            send_buffers: Vec::new(),
+            query_send_buffers: Vec::new(),
+        })
+    }
+
+    /// Binds the sockets and joins the IPv6 mDNS multicast group.
+    fn new_v6() -> io::Result<MdnsSocket> {
+        let socket = {
+            let builder = net2::UdpBuilder::new_v6()?;
+            builder.only_v6(true)?;
+            builder.reuse_address(true)?;
+            reuse_port(&builder)?;
+            builder.bind(("::", MDNS_PORT))?
+        };
+
+        let socket = UdpSocket::from_std(socket, &Handle::default())?;
+        socket.set_multicast_loop_v6(true)?;
+        // TODO: correct interfaces?
+        socket.join_multicast_v6(&IPV6_MDNS_MULTICAST_ADDRESS, 0)?;
+
+        Ok(MdnsSocket {
+            socket,
+            query_socket: UdpSocket::bind(&From::from((Ipv6Addr::UNSPECIFIED, 0)))?,
+            multicast_addr: SocketAddr::new(IPV6_MDNS_MULTICAST_ADDRESS.into(), MDNS_PORT),
+            recv_buffer: [0; 2048],
+            send_buffers: Vec::new(),
+            query_send_buffers: Vec::new(),
+        })
+    }
+
+    /// Flushes the send buffers and polls the main socket for packets of the given service.
+    fn poll<'a>(&'a mut self, service_name: &'a [u8]) -> Async<MdnsPacket<'a>> {
         // Flush the send buffer of the main socket.
         while !self.send_buffers.is_empty() {
             let to_send = self.send_buffers.remove(0);
-            match self
-                .socket
-                .poll_send_to(&to_send, &From::from(([224, 0, 0, 251], 5353)))
-            {
+            match self.socket.poll_send_to(&to_send, &self.multicast_addr) {
                 Ok(Async::Ready(bytes_written)) => {
                     debug_assert_eq!(bytes_written, to_send.len());
                 }
@@ -196,13 +393,9 @@ impl MdnsService {
         }
 
         // Flush the query send buffer.
-        // This has to be after the push to `query_send_buffers`.
         while !self.query_send_buffers.is_empty() {
             let to_send = self.query_send_buffers.remove(0);
-            match self
-                .query_socket
-                .poll_send_to(&to_send, &From::from(([224, 0, 0, 251], 5353)))
-            {
+            match self.query_socket.poll_send_to(&to_send, &self.multicast_addr) {
                 Ok(Async::Ready(bytes_written)) => {
                     debug_assert_eq!(bytes_written, to_send.len());
                 }
@@ -228,11 +421,12 @@ impl MdnsService {
                             if packet
                                 .questions
                                 .iter()
-                                .any(|q| q.qname.to_string().as_bytes() == SERVICE_NAME)
+                                .any(|q| q.qname.to_string().as_bytes() == service_name)
                             {
                                 return Async::Ready(MdnsPacket::Query(MdnsQuery {
                                     from,
                                     query_id: packet.header.id,
+                                    service_name,
                                     send_buffers: &mut self.send_buffers,
                                 }));
                             } else if packet
@@ -245,6 +439,7 @@ impl MdnsService {
                                     MdnsServiceDiscovery {
                                         from,
                                         query_id: packet.header.id,
+                                        service_name,
                                         send_buffers: &mut self.send_buffers,
                                     },
                                 ));
@@ -260,6 +455,7 @@ impl MdnsService {
                             return Async::Ready(MdnsPacket::Response(MdnsResponse {
                                 packet,
                                 from,
+                                service_name,
                             }));
                         }
                     }
@@ -285,9 +481,22 @@ impl MdnsService {
     }
 }
 
+/// Enables `SO_REUSEPORT` on platforms that support it, so that several mDNS services can run
+/// on the same host.
+#[cfg(unix)]
+fn reuse_port(s: &net2::UdpBuilder) -> io::Result<()> {
+    net2::unix::UnixUdpBuilderExt::reuse_port(s, true)?;
+    Ok(())
+}
+
+#[cfg(not(unix))]
+fn reuse_port(_: &net2::UdpBuilder) -> io::Result<()> { Ok(()) }
+
 impl fmt::Debug for MdnsService {
     fn fmt(&self, fmt: &mut fmt::Formatter<'_>) -> fmt::Result {
         fmt.debug_struct("MdnsService")
+            .field("ipv4", &self.ipv4.is_some())
+            .field("ipv6", &self.ipv6.is_some())
             .field("silent", &self.silent)
             .finish()
     }
@@ -310,6 +519,8 @@ pub struct MdnsQuery<'a> {
     from: SocketAddr,
     /// Id of the received DNS query. We need to pass this ID back in the results.
     query_id: u16,
+    /// Name of the service of the query.
+    service_name: &'a [u8],
     /// Queue of pending buffers.
     send_buffers: &'a mut Vec<Vec<u8>>,
 }
@@ -334,7 +545,7 @@ impl<'a> MdnsQuery<'a> {
         TAddresses::IntoIter: ExactSizeIterator,
     {
         let response =
-            dns::build_query_response(self.query_id, peer_id, addresses.into_iter(), ttl)?;
+            dns::build_query_response(self.query_id, self.service_name, peer_id, addresses.into_iter(), ttl)?;
         self.send_buffers.push(response);
         Ok(())
     }
@@ -361,6 +572,8 @@ pub struct MdnsServiceDiscovery<'a> {
     from: SocketAddr,
     /// Id of the received DNS query. We need to pass this ID back in the results.
     query_id: u16,
+    /// Name of the service of the query.
+    service_name: &'a [u8],
     /// Queue of pending buffers.
     send_buffers: &'a mut Vec<Vec<u8>>,
 }
@@ -369,7 +582,7 @@ impl<'a> MdnsServiceDiscovery<'a> {
     /// Respond to the query.
     #[inline]
     pub fn respond(self, ttl: Duration) {
-        let response = dns::build_service_discovery_response(self.query_id, ttl);
+        let response = dns::build_service_discovery_response(self.query_id, self.service_name, ttl);
         self.send_buffers.push(response);
     }
 
@@ -393,6 +606,8 @@ impl<'a> fmt::Debug for MdnsServiceDiscovery<'a> {
 pub struct MdnsResponse<'a> {
     packet: Packet<'a>,
     from: SocketAddr,
+    /// Name of the service whose peers are reported.
+    service_name: &'a [u8],
 }
 
 impl<'a> MdnsResponse<'a> {
@@ -401,8 +616,9 @@ impl<'a> MdnsResponse<'a> {
     /// > **Note**: Keep in mind that this will also contain the responses we sent ourselves.
     pub fn discovered_peers<'b>(&'b self) -> impl Iterator<Item = MdnsPeer<'b>> {
         let packet = &self.packet;
+        let service_name = self.service_name;
         self.packet.answers.iter().filter_map(move |record| {
-            if record.name.to_string().as_bytes() != SERVICE_NAME {
+            if record.name.to_string().as_bytes() != service_name {
                 return None;
             }
 
@@ -417,7 +633,7 @@ impl<'a> MdnsResponse<'a> {
                     Some(n) => n.to_owned(),
                     None => return None,
                 };
-                if iter.next().map(|v| v.as_bytes()) != Some(SERVICE_NAME) {
+                if iter.next().map(|v| v.as_bytes()) != Some(service_name) {
                     return None;
                 }
                 name
@@ -0,0 +1,69 @@
+// Copyright 2019 Parity Technologies (UK) Ltd.
+//
+// Permission is hereby granted, free of charge, to any person obtaining a
+// copy of this software and associated documentation files (the "Software"),
+// to deal in the Software without restriction, including without limitation
+// the rights to use, copy, modify, merge, publish, distribute, sublicense,
+// and/or sell copies of the Software, and to permit persons to whom the
+// Software is furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS
+// OR IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING
+// FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
+// DEALINGS IN THE SOFTWARE.
+
+use crate::Recorder;
+use libp2p_gossipsub::GossipsubEvent;
+use prometheus::{IntCounterVec, Opts, Registry};
+
+/// Metrics of the gossipsub protocol.
+pub(crate) struct Metrics {
+    messages: IntCounterVec,
+    message_bytes: IntCounterVec,
+    subscriptions: IntCounterVec,
+}
+
+impl Metrics {
+    pub(crate) fn new(registry: &Registry) -> Result<Self, prometheus::Error> {
+        let messages = IntCounterVec::new(
+            Opts::new("libp2p_gossipsub_messages_total", "Number of messages received, by topic"),
+            &["topic"]
+        )?;
+        let message_bytes = IntCounterVec::new(
+            Opts::new("libp2p_gossipsub_message_bytes_total", "Size of the data of the messages received, by topic"),
+            &["topic"]
+        )?;
+        let subscriptions = IntCounterVec::new(
+            Opts::new("libp2p_gossipsub_subscription_changes_total", "Number of remote (un)subscriptions, by action"),
+            &["action"]
+        )?;
+        registry.register(Box::new(messages.clone()))?;
+        registry.register(Box::new(message_bytes.clone()))?;
+        registry.register(Box::new(subscriptions.clone()))?;
+        Ok(Metrics { messages, message_bytes, subscriptions })
+    }
+}
+
+impl Recorder<GossipsubEvent> for crate::Metrics {
+    fn record(&self, event: &GossipsubEvent) {
+        let metrics = &self.gossipsub;
+        match event {
+            GossipsubEvent::Message { message, .. } => {
+                for topic in &message.topics {
+                    metrics.messages.with_label_values(&[topic.as_str()]).inc();
+                    metrics.message_bytes.with_label_values(&[topic.as_str()]).inc_by(message.data.len() as i64);
+                }
+            }
+            GossipsubEvent::Subscribed { .. } =>
+                metrics.subscriptions.with_label_values(&["subscribe"]).inc(),
+            GossipsubEvent::Unsubscribed { .. } =>
+                metrics.subscriptions.with_label_values(&["unsubscribe"]).inc(),
+        }
+    }
+}
@@ -0,0 +1,52 @@
+// Copyright 2019 Parity Technologies (UK) Ltd.
+//
+// Permission is hereby granted, free of charge, to any person obtaining a
+// copy of this software and associated documentation files (the "Software"),
+// to deal in the Software without restriction, including without limitation
+// the rights to use, copy, modify, merge, publish, distribute, sublicense,
+// and/or sell copies of the Software, and to permit persons to whom the
+// Software is furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS
+// OR IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING
+// FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
+// DEALINGS IN THE SOFTWARE.
+
+use crate::Recorder;
+use libp2p_identify::IdentifyEvent;
+use prometheus::{IntCounterVec, Opts, Registry};
+
+/// Metrics of the identify protocol.
+pub(crate) struct Metrics {
+    events: IntCounterVec,
+}
+
+impl Metrics {
+    pub(crate) fn new(registry: &Registry) -> Result<Self, prometheus::Error> {
+        let events = IntCounterVec::new(
+            Opts::new("libp2p_identify_events_total", "Number of identify events, by kind"),
+            &["event"]
+        )?;
+        registry.register(Box::new(events.clone()))?;
+        Ok(Metrics { events })
+    }
+}
+
+impl Recorder<IdentifyEvent> for crate::Metrics {
+    fn record(&self, event: &IdentifyEvent) {
+        let kind = match event {
+            IdentifyEvent::Identified { .. } => "identified",
+            IdentifyEvent::Error { .. } => "error",
+            IdentifyEvent::Pushed { .. } => "pushed",
+            IdentifyEvent::SendBack { result: Ok(()), .. } => "sent",
+            IdentifyEvent::SendBack { result: Err(_), .. } => "send_error",
+        };
+        self.identify.events.with_label_values(&[kind]).inc();
+    }
+}
@@ -0,0 +1,93 @@
+// Copyright 2019 Parity Technologies (UK) Ltd.
+//
+// Permission is hereby granted, free of charge, to any person obtaining a
+// copy of this software and associated documentation files (the "Software"),
+// to deal in the Software without restriction, including without limitation
+// the rights to use, copy, modify, merge, publish, distribute, sublicense,
+// and/or sell copies of the Software, and to permit persons to whom the
+// Software is furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS
+// OR IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING
+// FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
+// DEALINGS IN THE SOFTWARE.
+
+use crate::{seconds, Recorder};
+use libp2p_kad::{KademliaEvent, QueryStats};
+use prometheus::{exponential_buckets, HistogramOpts, HistogramVec, IntCounterVec, Opts, Registry};
+
+/// Metrics of the Kademlia DHT.
+pub(crate) struct Metrics {
+    query_duration: HistogramVec,
+    query_results: IntCounterVec,
+    routing_updates: IntCounterVec,
+}
+
+impl Metrics {
+    pub(crate) fn new(registry: &Registry) -> Result<Self, prometheus::Error> {
+        let query_duration = HistogramVec::new(
+            HistogramOpts::new("libp2p_kad_query_duration_seconds", "Duration of queries, by type")
+                .buckets(exponential_buckets(0.1, 2.0, 10)?),
+            &["type"]
+        )?;
+        let query_results = IntCounterVec::new(
+            Opts::new("libp2p_kad_query_results_total", "Number of finished queries, by type and outcome"),
+            &["type", "outcome"]
+        )?;
+        let routing_updates = IntCounterVec::new(
+            Opts::new("libp2p_kad_routing_updates_total", "Number of routing table updates, by kind"),
+            &["kind"]
+        )?;
+        registry.register(Box::new(query_duration.clone()))?;
+        registry.register(Box::new(query_results.clone()))?;
+        registry.register(Box::new(routing_updates.clone()))?;
+        Ok(Metrics { query_duration, query_results, routing_updates })
+    }
+
+    fn record_query(&self, ty: &str, ok: bool, stats: &QueryStats) {
+        let outcome = if ok { "ok" } else { "error" };
+        self.query_results.with_label_values(&[ty, outcome]).inc();
+        if let Some(duration) = stats.duration() {
+            self.query_duration.with_label_values(&[ty]).observe(seconds(duration));
+        }
+    }
+}
+
+impl Recorder<KademliaEvent> for crate::Metrics {
+    fn record(&self, event: &KademliaEvent) {
+        let metrics = &self.kad;
+        match event {
+            KademliaEvent::BootstrapResult(r, stats) =>
+                metrics.record_query("bootstrap", r.is_ok(), stats),
+            KademliaEvent::GetClosestPeersResult(r, stats) =>
+                metrics.record_query("get_closest_peers", r.is_ok(), stats),
+            KademliaEvent::GetProvidersResult(r, stats) =>
+                metrics.record_query("get_providers", r.is_ok(), stats),
+            KademliaEvent::StartProvidingResult(r, stats) =>
+                metrics.record_query("start_providing", r.is_ok(), stats),
+            KademliaEvent::RepublishProviderResult(r, stats) =>
+                metrics.record_query("republish_provider", r.is_ok(), stats),
+            KademliaEvent::GetRecordResult(r, stats) =>
+                metrics.record_query("get_record", r.is_ok(), stats),
+            KademliaEvent::PutRecordResult(r, stats) =>
+                metrics.record_query("put_record", r.is_ok(), stats),
+            KademliaEvent::RepublishRecordResult(r, stats) =>
+                metrics.record_query("republish_record", r.is_ok(), stats),
+            KademliaEvent::QueryProgress(_) | KademliaEvent::Discovered { .. } => {}
+            KademliaEvent::RoutingUpdated { old_peer, .. } => {
+                let kind = if old_peer.is_some() { "evicted" } else { "added" };
+                metrics.routing_updates.with_label_values(&[kind]).inc();
+            }
+            KademliaEvent::UnroutablePeer { .. } =>
+                metrics.routing_updates.with_label_values(&["unroutable"]).inc(),
+            KademliaEvent::RoutablePeer { .. } =>
+                metrics.routing_updates.with_label_values(&["routable"]).inc(),
+        }
+    }
+}
@@ -0,0 +1,137 @@
+// Copyright 2019 Parity Technologies (UK) Ltd.
+//
+// Permission is hereby granted, free of charge, to any person obtaining a
+// copy of this software and associated documentation files (the "Software"),
+// to deal in the Software without restriction, including without limitation
+// the rights to use, copy, modify, merge, publish, distribute, sublicense,
+// and/or sell copies of the Software, and to permit persons to whom the
+// Software is furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS
+// OR IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING
+// FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
+// DEALINGS IN THE SOFTWARE.
+
+//! Prometheus metrics for libp2p.
+//!
+//! [`Metrics`] registers counters and histograms in a [`Registry`](prometheus::Registry) and
+//! updates them from the events of the [`Swarm`](libp2p_swarm::Swarm) and of the bundled
+//! network behaviours, through the [`Recorder`] trait.
+//!
+//! ```ignore
+//! let registry = prometheus::Registry::new();
+//! let metrics = Metrics::new(&registry)?;
+//! loop {
+//!     match ExpandedSwarm::poll_event(&mut swarm)? {
+//!         Async::Ready(SwarmEvent::Behaviour(MyEvent::Ping(event))) => metrics.record(&event),
+//!         Async::Ready(event) => metrics.record(&event),
+//!         Async::NotReady => break,
+//!     }
+//! }
+//! ```
+//!
+//! The metrics of each behaviour are available behind the feature of the same name, all of
+//! them enabled by default.
+
+#[cfg(feature = "gossipsub")]
+mod gossipsub;
+#[cfg(feature = "identify")]
+mod identify;
+#[cfg(feature = "kad")]
+mod kad;
+#[cfg(feature = "ping")]
+mod ping;
+mod swarm;
+
+use prometheus::Registry;
+use std::time::Duration;
+
+/// Records the events of type `TEvent` in the metrics.
+pub trait Recorder<TEvent> {
+    /// Updates the metrics according to `event`.
+    fn record(&self, event: &TEvent);
+}
+
+/// Set of metrics covering the swarm and the bundled behaviours.
+pub struct Metrics {
+    #[cfg(feature = "gossipsub")]
+    gossipsub: gossipsub::Metrics,
+    #[cfg(feature = "identify")]
+    identify: identify::Metrics,
+    #[cfg(feature = "kad")]
+    kad: kad::Metrics,
+    #[cfg(feature = "ping")]
+    ping: ping::Metrics,
+    swarm: swarm::Metrics,
+}
+
+impl Metrics {
+    /// Creates the metrics and registers them in `registry`.
+    ///
+    /// Fails if metrics with the same names are already registered.
+    pub fn new(registry: &Registry) -> Result<Self, prometheus::Error> {
+        Ok(Metrics {
+            #[cfg(feature = "gossipsub")]
+            gossipsub: gossipsub::Metrics::new(registry)?,
+            #[cfg(feature = "identify")]
+            identify: identify::Metrics::new(registry)?,
+            #[cfg(feature = "kad")]
+            kad: kad::Metrics::new(registry)?,
+            #[cfg(feature = "ping")]
+            ping: ping::Metrics::new(registry)?,
+            swarm: swarm::Metrics::new(registry)?,
+        })
+    }
+}
+
+/// Converts a duration to seconds, the unit of time of Prometheus.
+fn seconds(duration: Duration) -> f64 {
+    duration.as_secs() as f64 + f64::from(duration.subsec_nanos()) / 1_000_000_000.0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use libp2p_swarm::{DialFailureCause, SwarmEvent};
+
+    fn value(registry: &Registry, name: &str, label: Option<(&str, &str)>) -> Option<f64> {
+        registry.gather().iter()
+            .filter(|family| family.get_name() == name)
+            .flat_map(|family| family.get_metric().iter())
+            .find(|metric| label.map_or(true, |(n, v)| {
+                metric.get_label().iter().any(|l| l.get_name() == n && l.get_value() == v)
+            }))
+            .map(|metric| metric.get_counter().get_value())
+    }
+
+    #[test]
+    fn records_dial_failures() {
+        let registry = Registry::new();
+        let metrics = Metrics::new(&registry).unwrap();
+
+        let event: SwarmEvent<(), (), ()> = SwarmEvent::UnreachableAddr {
+            peer_id: None,
+            address: "/memory/1".parse().unwrap(),
+            cause: DialFailureCause::Transport,
+            negotiation: None,
+        };
+        metrics.record(&event);
+        metrics.record(&event);
+
+        let name = "libp2p_swarm_dial_failures_total";
+        assert_eq!(value(&registry, name, Some(("cause", "transport"))), Some(2.0));
+    }
+
+    #[test]
+    fn registering_twice_fails() {
+        let registry = Registry::new();
+        assert!(Metrics::new(&registry).is_ok());
+        assert!(Metrics::new(&registry).is_err());
+    }
+}
@@ -0,0 +1,56 @@
+// Copyright 2019 Parity Technologies (UK) Ltd.
+//
+// Permission is hereby granted, free of charge, to any person obtaining a
+// copy of this software and associated documentation files (the "Software"),
+// to deal in the Software without restriction, including without limitation
+// the rights to use, copy, modify, merge, publish, distribute, sublicense,
+// and/or sell copies of the Software, and to permit persons to whom the
+// Software is furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS
+// OR IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING
+// FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
+// DEALINGS IN THE SOFTWARE.
+
+use crate::{seconds, Recorder};
+use libp2p_ping::{PingEvent, PingFailure, PingSuccess};
+use prometheus::{exponential_buckets, Histogram, HistogramOpts, IntCounterVec, Opts, Registry};
+
+/// Metrics of the ping protocol.
+pub(crate) struct Metrics {
+    rtt: Histogram,
+    failures: IntCounterVec,
+}
+
+impl Metrics {
+    pub(crate) fn new(registry: &Registry) -> Result<Self, prometheus::Error> {
+        let rtt = Histogram::with_opts(
+            HistogramOpts::new("libp2p_ping_rtt_seconds", "Round-trip time of outbound pings")
+                .buckets(exponential_buckets(0.001, 2.0, 14)?)
+        )?;
+        let failures = IntCounterVec::new(
+            Opts::new("libp2p_ping_failures_total", "Number of failed outbound pings, by cause"),
+            &["cause"]
+        )?;
+        registry.register(Box::new(rtt.clone()))?;
+        registry.register(Box::new(failures.clone()))?;
+        Ok(Metrics { rtt, failures })
+    }
+}
+
+impl Recorder<PingEvent> for crate::Metrics {
+    fn record(&self, event: &PingEvent) {
+        match &event.result {
+            Ok(PingSuccess::Ping { rtt }) => self.ping.rtt.observe(seconds(*rtt)),
+            Ok(PingSuccess::Pong) => {}
+            Err(PingFailure::Timeout) => self.ping.failures.with_label_values(&["timeout"]).inc(),
+            Err(PingFailure::Other { .. }) => self.ping.failures.with_label_values(&["other"]).inc(),
+        }
+    }
+}
@@ -0,0 +1,123 @@
+// Copyright 2019 Parity Technologies (UK) Ltd.
+//
+// Permission is hereby granted, free of charge, to any person obtaining a
+// copy of this software and associated documentation files (the "Software"),
+// to deal in the Software without restriction, including without limitation
+// the rights to use, copy, modify, merge, publish, distribute, sublicense,
+// and/or sell copies of the Software, and to permit persons to whom the
+// Software is furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS
+// OR IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING
+// FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
+// DEALINGS IN THE SOFTWARE.
+
+use crate::{seconds, Recorder};
+use libp2p_swarm::{ConnectionClosedCause, SwarmEvent};
+use prometheus::{exponential_buckets, Histogram, HistogramOpts, IntCounterVec, IntGauge, Opts, Registry};
+
+/// Metrics of the connections of the swarm.
+pub(crate) struct Metrics {
+    connections: IntGauge,
+    connections_established: IntCounterVec,
+    connections_closed: IntCounterVec,
+    connection_establishment: Histogram,
+    dial_failures: IntCounterVec,
+    incoming_rate_limited: IntCounterVec,
+}
+
+impl Metrics {
+    pub(crate) fn new(registry: &Registry) -> Result<Self, prometheus::Error> {
+        let connections = IntGauge::new(
+            "libp2p_swarm_connections",
+            "Number of established connections"
+        )?;
+        let connections_established = IntCounterVec::new(
+            Opts::new(
+                "libp2p_swarm_connections_established_total",
+                "Number of connections established, by role of the local node"
+            ),
+            &["role"]
+        )?;
+        let connections_closed = IntCounterVec::new(
+            Opts::new("libp2p_swarm_connections_closed_total", "Number of connections closed, by cause"),
+            &["cause"]
+        )?;
+        let connection_establishment = Histogram::with_opts(
+            HistogramOpts::new(
+                "libp2p_swarm_connection_establishment_seconds",
+                "Time to reach an address and negotiate a connection with it"
+            )
+            .buckets(exponential_buckets(0.01, 2.0, 12)?)
+        )?;
+        let dial_failures = IntCounterVec::new(
+            Opts::new("libp2p_swarm_dial_failures_total", "Number of addresses that failed to be reached, by cause"),
+            &["cause"]
+        )?;
+        let incoming_rate_limited = IntCounterVec::new(
+            Opts::new(
+                "libp2p_swarm_incoming_rate_limited_total",
+                "Number of incoming connections refused by the rate limiter, by reason"
+            ),
+            &["reason"]
+        )?;
+
+        registry.register(Box::new(connections.clone()))?;
+        registry.register(Box::new(connections_established.clone()))?;
+        registry.register(Box::new(connections_closed.clone()))?;
+        registry.register(Box::new(connection_establishment.clone()))?;
+        registry.register(Box::new(dial_failures.clone()))?;
+        registry.register(Box::new(incoming_rate_limited.clone()))?;
+
+        Ok(Metrics {
+            connections,
+            connections_established,
+            connections_closed,
+            connection_establishment,
+            dial_failures,
+            incoming_rate_limited,
+        })
+    }
+}
+
+impl<TBvEv, TConnInfo, THandlerErr> Recorder<SwarmEvent<TBvEv, TConnInfo, THandlerErr>> for crate::Metrics {
+    fn record(&self, event: &SwarmEvent<TBvEv, TConnInfo, THandlerErr>) {
+        let metrics = &self.swarm;
+        match event {
+            SwarmEvent::Behaviour(_) => {}
+            SwarmEvent::ConnectionEstablished { endpoint, established_in, .. } => {
+                let role = if endpoint.is_dialer() { "dialer" } else { "listener" };
+                metrics.connections.inc();
+                metrics.connections_established.with_label_values(&[role]).inc();
+                if let Some(duration) = established_in {
+                    metrics.connection_establishment.observe(seconds(*duration));
+                }
+            }
+            SwarmEvent::ConnectionClosed { cause, .. } => {
+                let cause = match cause {
+                    ConnectionClosedCause::Replaced => "replaced",
+                    ConnectionClosedCause::KeepAliveTimeout => "keep_alive_timeout",
+                    ConnectionClosedCause::Io(_) => "io",
+                    ConnectionClosedCause::Handler(_) => "handler",
+                    ConnectionClosedCause::Disconnected => "disconnected",
+                    ConnectionClosedCause::Banned => "banned",
+                };
+                metrics.connections.dec();
+                metrics.connections_closed.with_label_values(&[cause]).inc();
+            }
+            SwarmEvent::UnreachableAddr { cause, .. } => {
+                metrics.dial_failures.with_label_values(&[cause.as_str()]).inc();
+            }
+            SwarmEvent::IncomingConnectionError { .. } => {}
+            SwarmEvent::IncomingConnectionRateLimited { reason, .. } => {
+                metrics.incoming_rate_limited.with_label_values(&[reason.as_str()]).inc();
+            }
+        }
+    }
+}
@@ -57,12 +57,24 @@ impl From<multihash::DecodeOwnedError> for Error {
     }
 }
 
+impl From<multihash::DecodeError> for Error {
+    fn from(err: multihash::DecodeError) -> Error {
+        Error::ParsingError(err.into())
+    }
+}
+
 impl From<bs58::decode::DecodeError> for Error {
     fn from(err: bs58::decode::DecodeError) -> Error {
         Error::ParsingError(err.into())
     }
 }
 
+impl From<data_encoding::DecodeError> for Error {
+    fn from(err: data_encoding::DecodeError) -> Error {
+        Error::ParsingError(err.into())
+    }
+}
+
 impl From<net::AddrParseError> for Error {
     fn from(err: net::AddrParseError) -> Error {
         Error::ParsingError(err.into())
@@ -5,6 +5,7 @@ pub use multihash;
 mod protocol;
 mod errors;
 mod from_url;
+mod registry;
 mod util;
 
 use bytes::{Bytes, BytesMut};
@@ -26,8 +27,13 @@ use std::{
 pub use self::errors::{Result, Error};
 pub use self::from_url::{FromUrlErr, from_url, from_url_lossy};
 pub use self::protocol::Protocol;
+pub use self::registry::{register, CustomProtocol, RegisterError, ValueSize};
 
 /// Representation of a Multiaddr.
+///
+/// The bytes of a multiaddr are reference-counted, or stored inline if small enough, so
+/// cloning a `Multiaddr` never copies them to a new heap allocation. Iterating over
+/// its components does not allocate either: multihashes share the buffer of the multiaddr.
 #[derive(PartialEq, Eq, Clone, Hash)]
 pub struct Multiaddr { bytes: Bytes }
 
@@ -114,6 +120,30 @@ impl Multiaddr {
         }
     }
 
+    /// Appends a `/p2p/<peer>` component, unless this multiaddr already ends with one.
+    ///
+    /// Returns the multiaddr unchanged if it already ends with the given peer, or
+    /// as an error if it ends with the `/p2p` component of another peer.
+    ///
+    /// ```
+    /// use parity_multiaddr::{multihash, Multiaddr, Protocol};
+    ///
+    /// let peer = multihash::Multihash::random(multihash::Hash::SHA2256);
+    /// let address: Multiaddr = "/ip4/127.0.0.1/tcp/10000".parse().unwrap();
+    /// let address = address.with_p2p(peer.clone()).unwrap();
+    /// assert_eq!(address.iter().last(), Some(Protocol::P2p(peer.clone())));
+    /// assert!(address.with_p2p(peer).is_ok());
+    /// ```
+    ///
+    pub fn with_p2p(self, peer: impl Into<multihash::Multihash>) -> StdResult<Self, Self> {
+        let peer = peer.into();
+        match self.iter().last() {
+            Some(Protocol::P2p(p)) if p == peer => Ok(self),
+            Some(Protocol::P2p(_)) => Err(self),
+            _ => Ok(self.with(Protocol::P2p(peer)))
+        }
+    }
+
     /// Returns the components of this multiaddress.
     ///
     /// # Example
@@ -131,7 +161,7 @@ impl Multiaddr {
     /// ```
     ///
     pub fn iter(&self) -> Iter<'_> {
-        Iter(&self.bytes)
+        Iter { data: &self.bytes, shared: &self.bytes }
     }
 
     /// Replace a [`Protocol`] at some position in this `Multiaddr`.
@@ -165,6 +195,85 @@ impl Multiaddr {
 
         if replaced { Some(address) } else { None }
     }
+
+    /// Returns true if this multiaddress goes through a relay, i.e. contains a `/p2p-circuit`
+    /// component.
+    pub fn is_relayed(&self) -> bool {
+        self.iter().any(|p| p == Protocol::P2pCircuit)
+    }
+
+    /// Returns true if the first IP component of this multiaddress is a loopback address.
+    pub fn is_loopback(&self) -> bool {
+        match self.first_ip() {
+            Some(IpAddr::V4(ip)) => ip.is_loopback(),
+            Some(IpAddr::V6(ip)) => ip.is_loopback(),
+            None => false
+        }
+    }
+
+    /// Returns true if the first IP component of this multiaddress is only reachable from a
+    /// private network: RFC 1918, shared (RFC 6598) and link-local IPv4 addresses, and unique
+    /// local and link-local IPv6 addresses.
+    pub fn is_private(&self) -> bool {
+        match self.first_ip() {
+            Some(IpAddr::V4(ip)) => is_private_ip4(&ip),
+            Some(IpAddr::V6(ip)) => is_private_ip6(&ip),
+            None => false
+        }
+    }
+
+    /// Returns true if this multiaddress is reachable from the public internet, as far as
+    /// can be told from the address alone.
+    ///
+    /// This is the case if its first IP component is a globally routable address, or if it
+    /// starts with a DNS name other than `localhost`. For relayed addresses, this tells about
+    /// the address of the relay.
+    pub fn is_public(&self) -> bool {
+        match self.iter().next() {
+            Some(Protocol::Dns4(name)) | Some(Protocol::Dns6(name)) | Some(Protocol::Dnsaddr(name)) =>
+                return name != "localhost" && !name.ends_with(".localhost"),
+            _ => {}
+        }
+        match self.first_ip() {
+            Some(IpAddr::V4(ip)) => !ip.is_loopback()
+                && !ip.is_unspecified()
+                && !ip.is_broadcast()
+                && !ip.is_documentation()
+                && !ip.is_multicast()
+                && !is_private_ip4(&ip),
+            Some(IpAddr::V6(ip)) => !ip.is_loopback()
+                && !ip.is_unspecified()
+                && !ip.is_multicast()
+                && !is_documentation_ip6(&ip)
+                && !is_private_ip6(&ip),
+            None => false
+        }
+    }
+
+    /// Returns the first IP component of this multiaddress.
+    fn first_ip(&self) -> Option<IpAddr> {
+        self.iter().find_map(|p| match p {
+            Protocol::Ip4(ip) => Some(ip.into()),
+            Protocol::Ip6(ip) => Some(ip.into()),
+            _ => None
+        })
+    }
+}
+
+fn is_private_ip4(ip: &Ipv4Addr) -> bool {
+    let o = ip.octets();
+    ip.is_private() || ip.is_link_local() || (o[0] == 100 && o[1] & 0xc0 == 64)
+}
+
+fn is_private_ip6(ip: &Ipv6Addr) -> bool {
+    let s = ip.segments();
+    // fc00::/7 and fe80::/10.
+    s[0] & 0xfe00 == 0xfc00 || s[0] & 0xffc0 == 0xfe80
+}
+
+fn is_documentation_ip6(ip: &Ipv6Addr) -> bool {
+    let s = ip.segments();
+    s[0] == 0x2001 && s[1] == 0xdb8
 }
 
 impl fmt::Debug for Multiaddr {
@@ -204,7 +313,7 @@ impl<'a> IntoIterator for &'a Multiaddr {
     type IntoIter = Iter<'a>;
 
     fn into_iter(self) -> Iter<'a> {
-        Iter(&self.bytes)
+        Iter { data: &self.bytes, shared: &self.bytes }
     }
 }
 
@@ -243,20 +352,28 @@ impl FromStr for Multiaddr {
 }
 
 /// Iterator over `Multiaddr` [`Protocol`]s.
-pub struct Iter<'a>(&'a [u8]);
+pub struct Iter<'a> {
+    /// The remaining bytes to parse.
+    data: &'a [u8],
+    /// The whole buffer of the multiaddr, which `data` is a suffix of.
+    shared: &'a Bytes
+}
 
 impl<'a> Iterator for Iter<'a> {
     type Item = Protocol<'a>;
 
     fn next(&mut self) -> Option<Self::Item> {
-        if self.0.is_empty() {
+        if self.data.is_empty() {
             return None;
         }
 
-        let (p, next_data) =
-            Protocol::from_bytes(self.0).expect("`Multiaddr` is known to be valid.");
+        let shared = self.shared;
+        let (p, next_data) = Protocol::parse(self.data, |data| {
+            let start = data.as_ptr() as usize - shared.as_ptr() as usize;
+            Ok(multihash::Multihash::from_shared(shared.slice(start, start + data.len()))?)
+        }).expect("`Multiaddr` is known to be valid.");
 
-        self.0 = next_data;
+        self.data = next_data;
         Some(p)
     }
 }
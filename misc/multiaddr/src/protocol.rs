@@ -2,8 +2,8 @@
 use arrayref::array_ref;
 use bs58;
 use byteorder::{BigEndian, ByteOrder, ReadBytesExt, WriteBytesExt};
-use crate::{Result, Error};
-use data_encoding::BASE32;
+use crate::{Result, Error, registry::{self, ValueSize}};
+use data_encoding::{BASE32, BASE32_NOPAD, BASE64URL_NOPAD, BASE64_NOPAD, HEXLOWER, HEXUPPER};
 use multihash::Multihash;
 use std::{
     borrow::Cow,
@@ -15,9 +15,11 @@ use std::{
 };
 use unsigned_varint::{encode, decode};
 
+const CERTHASH: u32 = 466;
 const DCCP: u32 = 33;
 const DNS4: u32 = 54;
 const DNS6: u32 = 55;
+const DNSADDR: u32 = 56;
 const HTTP: u32 = 480;
 const HTTPS: u32 = 443;
 const IP4: u32 = 4;
@@ -26,16 +28,22 @@ const P2P_WEBRTC_DIRECT: u32 = 276;
 const P2P_WEBRTC_STAR: u32 = 275;
 const P2P_WEBSOCKET_STAR: u32 = 479;
 const MEMORY: u32 = 777;
+const MEMORY_NAME: u32 = 7770;         // Note: not standard
+const NOISE: u32 = 454;
 const ONION: u32 = 444;
 const P2P: u32 = 421;
 const P2P_CIRCUIT: u32 = 290;
 const QUIC: u32 = 460;
+const QUIC_V1: u32 = 461;
 const SCTP: u32 = 132;
+const SNI: u32 = 449;
 const TCP: u32 = 6;
+const TLS: u32 = 448;
 const UDP: u32 = 273;
 const UDT: u32 = 301;
 const UNIX: u32 = 400;
 const UTP: u32 = 302;
+const WEBTRANSPORT: u32 = 465;
 const WS: u32 = 477;
 const WS_WITH_PATH: u32 = 4770;         // Note: not standard
 const WSS: u32 = 478;
@@ -49,9 +57,13 @@ const WSS_WITH_PATH: u32 = 4780;        // Note: not standard
 /// happen separately.
 #[derive(PartialEq, Eq, Clone, Debug)]
 pub enum Protocol<'a> {
+    /// Hash of a certificate that the remote is expected to present, e.g. with `WebTransport`.
+    Certhash(Multihash),
     Dccp(u16),
     Dns4(Cow<'a, str>),
     Dns6(Cow<'a, str>),
+    /// Domain name whose `_dnsaddr` TXT records contain multiaddresses.
+    Dnsaddr(Cow<'a, str>),
     Http,
     Https,
     Ip4(Ipv4Addr),
@@ -61,18 +73,33 @@ pub enum Protocol<'a> {
     P2pWebSocketStar,
     /// Contains the "port" to contact. Similar to TCP or UDP, 0 means "assign me a port".
     Memory(u64),
+    /// A named in-memory endpoint, written `/memory/<name>`. The name must not be a number.
+    MemoryName(Cow<'a, str>),
+    /// The rest of the address is secured with the Noise protocol.
+    Noise,
     Onion(Cow<'a, [u8; 10]>, u16),
     P2p(Multihash),
     P2pCircuit,
     Quic,
+    QuicV1,
     Sctp(u16),
+    /// Server name to indicate during the TLS handshake, following a `Tls` component.
+    Sni(Cow<'a, str>),
     Tcp(u16),
+    /// The rest of the address is layered over TLS.
+    Tls,
     Udp(u16),
     Udt,
     Unix(Cow<'a, str>),
     Utp,
+    WebTransport,
     Ws(Cow<'a, str>),
     Wss(Cow<'a, str>),
+    /// A protocol registered with [`register`](crate::register), as its code and the
+    /// binary representation of its value.
+    ///
+    /// Writing or displaying a `Custom` protocol whose code isn't registered panics.
+    Custom(u32, Cow<'a, [u8]>),
 }
 
 impl<'a> Protocol<'a> {
@@ -115,6 +142,16 @@ impl<'a> Protocol<'a> {
                 let s = iter.next().ok_or(Error::InvalidProtocolString)?;
                 Ok(Protocol::Dns6(Cow::Borrowed(s)))
             }
+            "dnsaddr" => {
+                let s = iter.next().ok_or(Error::InvalidProtocolString)?;
+                Ok(Protocol::Dnsaddr(Cow::Borrowed(s)))
+            }
+            "tls" => Ok(Protocol::Tls),
+            "noise" => Ok(Protocol::Noise),
+            "sni" => {
+                let s = iter.next().ok_or(Error::InvalidProtocolString)?;
+                Ok(Protocol::Sni(Cow::Borrowed(s)))
+            }
             "sctp" => {
                 let s = iter.next().ok_or(Error::InvalidProtocolString)?;
                 Ok(Protocol::Sctp(s.parse()?))
@@ -138,6 +175,13 @@ impl<'a> Protocol<'a> {
                     .and_then(|s| read_onion(&s.to_uppercase()))
                     .map(|(a, p)| Protocol::Onion(Cow::Owned(a), p)),
             "quic" => Ok(Protocol::Quic),
+            "quic-v1" => Ok(Protocol::QuicV1),
+            "webtransport" => Ok(Protocol::WebTransport),
+            "certhash" => {
+                let s = iter.next().ok_or(Error::InvalidProtocolString)?;
+                let decoded = multibase_decode(s)?;
+                Ok(Protocol::Certhash(Multihash::from_bytes(decoded)?))
+            }
             "ws" => Ok(Protocol::Ws(Cow::Borrowed("/"))),
             "wss" => Ok(Protocol::Wss(Cow::Borrowed("/"))),
             "x-parity-ws" => {
@@ -156,15 +200,43 @@ impl<'a> Protocol<'a> {
             "p2p-circuit" => Ok(Protocol::P2pCircuit),
             "memory" => {
                 let s = iter.next().ok_or(Error::InvalidProtocolString)?;
-                Ok(Protocol::Memory(s.parse()?))
+                if s.is_empty() {
+                    Err(Error::InvalidProtocolString)
+                } else if s.bytes().all(|b| b.is_ascii_digit()) {
+                    Ok(Protocol::Memory(s.parse()?))
+                } else {
+                    Ok(Protocol::MemoryName(Cow::Borrowed(s)))
+                }
+            }
+            name => {
+                let p = registry::by_name(name).ok_or(Error::UnknownProtocolString)?;
+                if p.size == ValueSize::None {
+                    return Ok(Protocol::Custom(p.code, Cow::Borrowed(&[][..])))
+                }
+                let s = iter.next().ok_or(Error::InvalidProtocolString)?;
+                let value = (p.parse)(s).ok_or(Error::InvalidProtocolString)?;
+                if let ValueSize::Fixed(n) = p.size {
+                    if value.len() != n {
+                        return Err(Error::InvalidProtocolString)
+                    }
+                }
+                Ok(Protocol::Custom(p.code, Cow::Owned(value)))
             }
-            _ => Err(Error::UnknownProtocolString)
         }
     }
 
     /// Parse a single `Protocol` value from its byte slice representation,
     /// returning the protocol as well as the remaining byte slice.
     pub fn from_bytes(input: &'a [u8]) -> Result<(Self, &'a [u8])> {
+        Protocol::parse(input, |data| Ok(Multihash::from_bytes(data.to_owned())?))
+    }
+
+    /// Like [`Protocol::from_bytes`], but multihashes are created from their byte
+    /// slice representation by `to_multihash`, which may avoid copying them.
+    pub(crate) fn parse<F>(input: &'a [u8], to_multihash: F) -> Result<(Self, &'a [u8])>
+    where
+        F: FnOnce(&'a [u8]) -> Result<Multihash>
+    {
         fn split_at(n: usize, input: &[u8]) -> Result<(&[u8], &[u8])> {
             if input.len() < n {
                 return Err(Error::DataLessThanLen)
@@ -173,6 +245,11 @@ impl<'a> Protocol<'a> {
         }
         let (id, input) = decode::u32(input)?;
         match id {
+            CERTHASH => {
+                let (n, input) = decode::usize(input)?;
+                let (data, rest) = split_at(n, input)?;
+                Ok((Protocol::Certhash(to_multihash(data)?), rest))
+            }
             DCCP => {
                 let (data, rest) = split_at(2, input)?;
                 let mut rdr = Cursor::new(data);
@@ -189,6 +266,18 @@ impl<'a> Protocol<'a> {
                 let (data, rest) = split_at(n, input)?;
                 Ok((Protocol::Dns6(Cow::Borrowed(str::from_utf8(data)?)), rest))
             }
+            DNSADDR => {
+                let (n, input) = decode::usize(input)?;
+                let (data, rest) = split_at(n, input)?;
+                Ok((Protocol::Dnsaddr(Cow::Borrowed(str::from_utf8(data)?)), rest))
+            }
+            TLS => Ok((Protocol::Tls, input)),
+            NOISE => Ok((Protocol::Noise, input)),
+            SNI => {
+                let (n, input) = decode::usize(input)?;
+                let (data, rest) = split_at(n, input)?;
+                Ok((Protocol::Sni(Cow::Borrowed(str::from_utf8(data)?)), rest))
+            }
             HTTP => Ok((Protocol::Http, input)),
             HTTPS => Ok((Protocol::Https, input)),
             IP4 => {
@@ -224,6 +313,11 @@ impl<'a> Protocol<'a> {
                 let num = rdr.read_u64::<BigEndian>()?;
                 Ok((Protocol::Memory(num), rest))
             }
+            MEMORY_NAME => {
+                let (n, input) = decode::usize(input)?;
+                let (data, rest) = split_at(n, input)?;
+                Ok((Protocol::MemoryName(Cow::Borrowed(str::from_utf8(data)?)), rest))
+            }
             ONION => {
                 let (data, rest) = split_at(12, input)?;
                 let port = BigEndian::read_u16(&data[10 ..]);
@@ -232,10 +326,11 @@ impl<'a> Protocol<'a> {
             P2P => {
                 let (n, input) = decode::usize(input)?;
                 let (data, rest) = split_at(n, input)?;
-                Ok((Protocol::P2p(Multihash::from_bytes(data.to_owned())?), rest))
+                Ok((Protocol::P2p(to_multihash(data)?), rest))
             }
             P2P_CIRCUIT => Ok((Protocol::P2pCircuit, input)),
             QUIC => Ok((Protocol::Quic, input)),
+            QUIC_V1 => Ok((Protocol::QuicV1, input)),
             SCTP => {
                 let (data, rest) = split_at(2, input)?;
                 let mut rdr = Cursor::new(data);
@@ -261,6 +356,7 @@ impl<'a> Protocol<'a> {
                 Ok((Protocol::Unix(Cow::Borrowed(str::from_utf8(data)?)), rest))
             }
             UTP => Ok((Protocol::Utp, input)),
+            WEBTRANSPORT => Ok((Protocol::WebTransport, input)),
             WS => Ok((Protocol::Ws(Cow::Borrowed("/")), input)),
             WS_WITH_PATH => {
                 let (n, input) = decode::usize(input)?;
@@ -273,7 +369,18 @@ impl<'a> Protocol<'a> {
                 let (data, rest) = split_at(n, input)?;
                 Ok((Protocol::Wss(Cow::Borrowed(str::from_utf8(data)?)), rest))
             }
-            _ => Err(Error::UnknownProtocolId(id))
+            _ => {
+                let p = registry::by_code(id).ok_or(Error::UnknownProtocolId(id))?;
+                let (data, rest) = match p.size {
+                    ValueSize::None => (&input[.. 0], input),
+                    ValueSize::Fixed(n) => split_at(n, input)?,
+                    ValueSize::Variable => {
+                        let (n, input) = decode::usize(input)?;
+                        split_at(n, input)?
+                    }
+                };
+                Ok((Protocol::Custom(id, Cow::Borrowed(data)), rest))
+            }
         }
     }
 
@@ -320,6 +427,20 @@ impl<'a> Protocol<'a> {
                 w.write_all(encode::usize(bytes.len(), &mut encode::usize_buffer()))?;
                 w.write_all(&bytes)?
             }
+            Protocol::Dnsaddr(s) => {
+                w.write_all(encode::u32(DNSADDR, &mut buf))?;
+                let bytes = s.as_bytes();
+                w.write_all(encode::usize(bytes.len(), &mut encode::usize_buffer()))?;
+                w.write_all(&bytes)?
+            }
+            Protocol::Tls => w.write_all(encode::u32(TLS, &mut buf))?,
+            Protocol::Noise => w.write_all(encode::u32(NOISE, &mut buf))?,
+            Protocol::Sni(s) => {
+                w.write_all(encode::u32(SNI, &mut buf))?;
+                let bytes = s.as_bytes();
+                w.write_all(encode::usize(bytes.len(), &mut encode::usize_buffer()))?;
+                w.write_all(&bytes)?
+            }
             Protocol::Unix(s) => {
                 w.write_all(encode::u32(UNIX, &mut buf))?;
                 let bytes = s.as_bytes();
@@ -332,12 +453,20 @@ impl<'a> Protocol<'a> {
                 w.write_all(encode::usize(bytes.len(), &mut encode::usize_buffer()))?;
                 w.write_all(&bytes)?
             }
+            Protocol::Certhash(multihash) => {
+                w.write_all(encode::u32(CERTHASH, &mut buf))?;
+                let bytes = multihash.as_bytes();
+                w.write_all(encode::usize(bytes.len(), &mut encode::usize_buffer()))?;
+                w.write_all(&bytes)?
+            }
             Protocol::Onion(addr, port) => {
                 w.write_all(encode::u32(ONION, &mut buf))?;
                 w.write_all(addr.as_ref())?;
                 w.write_u16::<BigEndian>(*port)?
             }
             Protocol::Quic => w.write_all(encode::u32(QUIC, &mut buf))?,
+            Protocol::QuicV1 => w.write_all(encode::u32(QUIC_V1, &mut buf))?,
+            Protocol::WebTransport => w.write_all(encode::u32(WEBTRANSPORT, &mut buf))?,
             Protocol::Utp => w.write_all(encode::u32(UTP, &mut buf))?,
             Protocol::Udt => w.write_all(encode::u32(UDT, &mut buf))?,
             Protocol::Http => w.write_all(encode::u32(HTTP, &mut buf))?,
@@ -364,6 +493,20 @@ impl<'a> Protocol<'a> {
                 w.write_all(encode::u32(MEMORY, &mut buf))?;
                 w.write_u64::<BigEndian>(*port)?
             }
+            Protocol::MemoryName(s) => {
+                w.write_all(encode::u32(MEMORY_NAME, &mut buf))?;
+                let bytes = s.as_bytes();
+                w.write_all(encode::usize(bytes.len(), &mut encode::usize_buffer()))?;
+                w.write_all(&bytes)?
+            }
+            Protocol::Custom(code, data) => {
+                let p = registry::by_code(*code).expect("custom protocols are registered");
+                w.write_all(encode::u32(*code, &mut buf))?;
+                if p.size == ValueSize::Variable {
+                    w.write_all(encode::usize(data.len(), &mut encode::usize_buffer()))?;
+                }
+                w.write_all(&data[..])?
+            }
         }
         Ok(())
     }
@@ -372,9 +515,11 @@ impl<'a> Protocol<'a> {
     pub fn acquire<'b>(self) -> Protocol<'b> {
         use self::Protocol::*;
         match self {
+            Certhash(a) => Certhash(a),
             Dccp(a) => Dccp(a),
             Dns4(cow) => Dns4(Cow::Owned(cow.into_owned())),
             Dns6(cow) => Dns6(Cow::Owned(cow.into_owned())),
+            Dnsaddr(cow) => Dnsaddr(Cow::Owned(cow.into_owned())),
             Http => Http,
             Https => Https,
             Ip4(a) => Ip4(a),
@@ -383,18 +528,25 @@ impl<'a> Protocol<'a> {
             P2pWebRtcStar => P2pWebRtcStar,
             P2pWebSocketStar => P2pWebSocketStar,
             Memory(a) => Memory(a),
+            MemoryName(cow) => MemoryName(Cow::Owned(cow.into_owned())),
+            Noise => Noise,
             Onion(addr, port) => Onion(Cow::Owned(addr.into_owned()), port),
             P2p(a) => P2p(a),
             P2pCircuit => P2pCircuit,
             Quic => Quic,
+            QuicV1 => QuicV1,
             Sctp(a) => Sctp(a),
+            Sni(cow) => Sni(Cow::Owned(cow.into_owned())),
             Tcp(a) => Tcp(a),
+            Tls => Tls,
             Udp(a) => Udp(a),
             Udt => Udt,
             Unix(cow) => Unix(Cow::Owned(cow.into_owned())),
             Utp => Utp,
+            WebTransport => WebTransport,
             Ws(cow) => Ws(Cow::Owned(cow.into_owned())),
             Wss(cow) => Wss(Cow::Owned(cow.into_owned())),
+            Custom(code, cow) => Custom(code, Cow::Owned(cow.into_owned())),
         }
     }
 }
@@ -403,9 +555,11 @@ impl<'a> fmt::Display for Protocol<'a> {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         use self::Protocol::*;
         match self {
+            Certhash(c) => write!(f, "/certhash/u{}", BASE64URL_NOPAD.encode(c.as_bytes())),
             Dccp(port) => write!(f, "/dccp/{}", port),
             Dns4(s) => write!(f, "/dns4/{}", s),
             Dns6(s) => write!(f, "/dns6/{}", s),
+            Dnsaddr(s) => write!(f, "/dnsaddr/{}", s),
             Http => f.write_str("/http"),
             Https => f.write_str("/https"),
             Ip4(addr) => write!(f, "/ip4/{}", addr),
@@ -414,19 +568,25 @@ impl<'a> fmt::Display for Protocol<'a> {
             P2pWebRtcStar => f.write_str("/p2p-webrtc-star"),
             P2pWebSocketStar => f.write_str("/p2p-websocket-star"),
             Memory(port) => write!(f, "/memory/{}", port),
+            MemoryName(name) => write!(f, "/memory/{}", name),
             Onion(addr, port) => {
                 let s = BASE32.encode(addr.as_ref());
                 write!(f, "/onion/{}:{}", s.to_lowercase(), port)
             }
             P2p(c) => write!(f, "/p2p/{}", bs58::encode(c.as_bytes()).into_string()),
             P2pCircuit => f.write_str("/p2p-circuit"),
+            Noise => f.write_str("/noise"),
             Quic => f.write_str("/quic"),
+            QuicV1 => f.write_str("/quic-v1"),
             Sctp(port) => write!(f, "/sctp/{}", port),
+            Sni(s) => write!(f, "/sni/{}", s),
             Tcp(port) => write!(f, "/tcp/{}", port),
+            Tls => f.write_str("/tls"),
             Udp(port) => write!(f, "/udp/{}", port),
             Udt => f.write_str("/udt"),
             Unix(s) => write!(f, "/unix/{}", s),
             Utp => f.write_str("/utp"),
+            WebTransport => f.write_str("/webtransport"),
             Ws(ref s) if s == "/" => f.write_str("/ws"),
             Ws(s) => {
                 let encoded = percent_encoding::percent_encode(s.as_bytes(), percent_encoding::PATH_SEGMENT_ENCODE_SET);
@@ -437,6 +597,14 @@ impl<'a> fmt::Display for Protocol<'a> {
                 let encoded = percent_encoding::percent_encode(s.as_bytes(), percent_encoding::PATH_SEGMENT_ENCODE_SET);
                 write!(f, "/x-parity-wss/{}", encoded)
             },
+            Custom(code, data) => {
+                let p = registry::by_code(*code).expect("custom protocols are registered");
+                if p.size == ValueSize::None {
+                    write!(f, "/{}", p.name)
+                } else {
+                    write!(f, "/{}/{}", p.name, (p.display)(data))
+                }
+            }
         }
     }
 }
@@ -496,3 +664,21 @@ fn read_onion(s: &str) -> Result<([u8; 10], u16)> {
 
     Ok((buf, port))
 }
+
+/// Decodes a string in the multibase format, i.e. prefixed with a character identifying its
+/// base. Only the bases commonly used in multiaddresses are supported.
+fn multibase_decode(s: &str) -> Result<Vec<u8>> {
+    let mut chars = s.chars();
+    let base = chars.next().ok_or(Error::InvalidProtocolString)?;
+    let data = chars.as_str();
+    match base {
+        'u' => Ok(BASE64URL_NOPAD.decode(data.as_bytes())?),
+        'm' => Ok(BASE64_NOPAD.decode(data.as_bytes())?),
+        'b' => Ok(BASE32_NOPAD.decode(data.to_uppercase().as_bytes())?),
+        'B' => Ok(BASE32_NOPAD.decode(data.as_bytes())?),
+        'z' => Ok(bs58::decode(data).into_vec()?),
+        'f' => Ok(HEXLOWER.decode(data.as_bytes())?),
+        'F' => Ok(HEXUPPER.decode(data.as_bytes())?),
+        _ => Err(Error::InvalidProtocolString)
+    }
+}
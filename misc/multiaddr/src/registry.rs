@@ -0,0 +1,108 @@
+//! Registration of multiaddress protocols that are not known to this crate.
+//!
+//! Protocols registered with [`register`] are parsed into and written from
+//! [`Protocol::Custom`](crate::Protocol::Custom) values.
+
+use crate::{Error, Protocol};
+use lazy_static::lazy_static;
+use std::{error, fmt, iter, sync::RwLock};
+
+/// Size of the value of a custom protocol in its binary representation.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ValueSize {
+    /// The protocol has no value, e.g. `/quic`.
+    None,
+    /// The value always has this number of bytes, e.g. `/tcp/<port>`.
+    Fixed(usize),
+    /// The value is prefixed with its length, e.g. `/dns4/<name>`.
+    Variable,
+}
+
+/// Definition of a protocol that is not known to this crate.
+#[derive(Clone, Copy)]
+pub struct CustomProtocol {
+    /// Code of the protocol in the binary representation.
+    pub code: u32,
+    /// Name of the protocol in the string representation.
+    pub name: &'static str,
+    /// Size of the value of the protocol in the binary representation.
+    pub size: ValueSize,
+    /// Converts the string representation of a value to its binary representation.
+    ///
+    /// Returns `None` if the value is invalid.
+    pub parse: fn(&str) -> Option<Vec<u8>>,
+    /// Converts the binary representation of a value to its string representation.
+    pub display: fn(&[u8]) -> String,
+}
+
+impl fmt::Debug for CustomProtocol {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("CustomProtocol")
+            .field("code", &self.code)
+            .field("name", &self.name)
+            .field("size", &self.size)
+            .finish()
+    }
+}
+
+lazy_static! {
+    static ref REGISTRY: RwLock<Vec<CustomProtocol>> = RwLock::new(Vec::new());
+}
+
+/// Registers a protocol, so that multiaddresses containing it can be parsed and written.
+///
+/// Fails if the code or the name of the protocol is already used by a protocol known to
+/// this crate or by a previously registered protocol.
+pub fn register(protocol: CustomProtocol) -> Result<(), RegisterError> {
+    // A built-in protocol fails to parse with a different error than an unknown one.
+    let mut buf = unsigned_varint::encode::u32_buffer();
+    let code = unsigned_varint::encode::u32(protocol.code, &mut buf);
+    if let Err(Error::UnknownProtocolId(_)) = Protocol::from_bytes(code) {} else {
+        return Err(RegisterError::CodeInUse(protocol.code))
+    }
+    if let Err(Error::UnknownProtocolString) = Protocol::from_str_parts(iter::once(protocol.name)) {} else {
+        return Err(RegisterError::NameInUse(protocol.name))
+    }
+
+    let mut registry = REGISTRY.write().unwrap_or_else(|e| e.into_inner());
+    if registry.iter().any(|p| p.code == protocol.code) {
+        return Err(RegisterError::CodeInUse(protocol.code))
+    }
+    if registry.iter().any(|p| p.name == protocol.name) {
+        return Err(RegisterError::NameInUse(protocol.name))
+    }
+    registry.push(protocol);
+    Ok(())
+}
+
+/// Returns the registered protocol with the given code.
+pub(crate) fn by_code(code: u32) -> Option<CustomProtocol> {
+    let registry = REGISTRY.read().unwrap_or_else(|e| e.into_inner());
+    registry.iter().find(|p| p.code == code).cloned()
+}
+
+/// Returns the registered protocol with the given name.
+pub(crate) fn by_name(name: &str) -> Option<CustomProtocol> {
+    let registry = REGISTRY.read().unwrap_or_else(|e| e.into_inner());
+    registry.iter().find(|p| p.name == name).cloned()
+}
+
+/// Error when registering a protocol.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RegisterError {
+    /// The code is already used by another protocol.
+    CodeInUse(u32),
+    /// The name is already used by another protocol.
+    NameInUse(&'static str),
+}
+
+impl fmt::Display for RegisterError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RegisterError::CodeInUse(code) => write!(f, "protocol code {} is already in use", code),
+            RegisterError::NameInUse(name) => write!(f, "protocol name {} is already in use", name),
+        }
+    }
+}
+
+impl error::Error for RegisterError {}
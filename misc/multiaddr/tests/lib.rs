@@ -76,7 +76,7 @@ struct Proto(Protocol<'static>);
 impl Arbitrary for Proto {
     fn arbitrary<G: Gen>(g: &mut G) -> Self {
         use Protocol::*;
-        match g.gen_range(0, 23) { // TODO: Add Protocol::Quic
+        match g.gen_range(0, 31) { // TODO: Add Protocol::Quic
              0 => Proto(Dccp(g.gen())),
              1 => Proto(Dns4(Cow::Owned(SubString::arbitrary(g).0))),
              2 => Proto(Dns6(Cow::Owned(SubString::arbitrary(g).0))),
@@ -105,6 +105,14 @@ impl Arbitrary for Proto {
                 g.fill(&mut a);
                 Proto(Onion(Cow::Owned(a), g.gen()))
             }
+            23 => Proto(QuicV1),
+            24 => Proto(WebTransport),
+            25 => Proto(Certhash(multihash("QmcgpsyWgH8Y8ajJz1Cu72KnS5uo2Aa2LpzU7kinSupNKC"))),
+            26 => Proto(Dnsaddr(Cow::Owned(SubString::arbitrary(g).0))),
+            27 => Proto(MemoryName(Cow::Owned(format!("n{}", SubString::arbitrary(g).0)))),
+            28 => Proto(Tls),
+            29 => Proto(Sni(Cow::Owned(SubString::arbitrary(g).0))),
+            30 => Proto(Noise),
              _ => panic!("outside range")
         }
     }
@@ -208,6 +216,16 @@ fn construct_success() {
     ma_valid("/ip4/127.0.0.1/tcp/9090/p2p-circuit/p2p/QmcgpsyWgH8Y8ajJz1Cu72KnS5uo2Aa2LpzU7kinSupNKC",
              "047F000001062382A202A503221220D52EBB89D85B02A284948203A62FF28389C57C9F42BEEC4EC20DB76A68911C0B",
              vec![Ip4(local.clone()), Tcp(9090), P2pCircuit, P2p(multihash("QmcgpsyWgH8Y8ajJz1Cu72KnS5uo2Aa2LpzU7kinSupNKC"))]);
+    ma_valid("/memory/alice", "DA3C05616C696365", vec![MemoryName("alice".into())]);
+    ma_valid("/dnsaddr/example.com", "380B6578616D706C652E636F6D", vec![Dnsaddr("example.com".into())]);
+    ma_valid("/dns4/example.com/tcp/443/tls/sni/example.com/ws",
+             "360B6578616D706C652E636F6D0601BBC003C1030B6578616D706C652E636F6DDD03",
+             vec![Dns4("example.com".into()), Tcp(443), Tls, Sni("example.com".into()), Ws("/".into())]);
+    ma_valid("/ip4/127.0.0.1/udp/1234/quic-v1", "047F000001910204D2CD03", vec![Ip4(local.clone()), Udp(1234), QuicV1]);
+    ma_valid("/ip4/127.0.0.1/tcp/1234/noise", "047F0000010604D2C603", vec![Ip4(local.clone()), Tcp(1234), Noise]);
+    ma_valid("/ip4/127.0.0.1/udp/1234/quic-v1/webtransport/certhash/uEiDVLruJ2FsCooSUggOmL_KDicV8n0K-7E7CDbdqaJEcCw",
+             "047F000001910204D2CD03D103D203221220D52EBB89D85B02A284948203A62FF28389C57C9F42BEEC4EC20DB76A68911C0B",
+             vec![Ip4(local.clone()), Udp(1234), QuicV1, WebTransport, Certhash(multihash("QmcgpsyWgH8Y8ajJz1Cu72KnS5uo2Aa2LpzU7kinSupNKC"))]);
 }
 
 #[test]
@@ -236,7 +254,10 @@ fn construct_fail() {
                      "/ip4/127.0.0.1/tcp",
                      "/ip4/127.0.0.1/p2p",
                      "/ip4/127.0.0.1/p2p/tcp",
-                     "/p2p-circuit/50"];
+                     "/p2p-circuit/50",
+                     "/certhash",
+                     "/certhash/xEiDVLruJ2FsCooSUggOmL_KDicV8n0K-7E7CDbdqaJEcCw",
+                     "/quic-v1/1234"];
 
     for address in &addresses {
         assert!(address.parse::<Multiaddr>().is_err(), address.to_string());
@@ -287,6 +308,38 @@ fn ser_and_deser_bincode() {
     assert_eq!(addr, deserialized);
 }
 
+#[test]
+fn clone_and_iter_share_bytes() {
+    let peer = Multihash::random(multihash::Hash::SHA2256);
+    let a: Multiaddr = "/ip4/1.2.3.4/tcp/80".parse().unwrap();
+    let a = a.with(Protocol::P2p(peer));
+
+    let b = a.clone();
+    assert_eq!(a.as_ref().as_ptr(), b.as_ref().as_ptr());
+
+    let start = a.as_ref().as_ptr() as usize;
+    let end = start + a.as_ref().len();
+    match a.iter().last() {
+        Some(Protocol::P2p(hash)) => {
+            let ptr = hash.as_bytes().as_ptr() as usize;
+            assert!(start <= ptr && ptr < end)
+        }
+        other => panic!("unexpected component {:?}", other)
+    }
+}
+
+#[test]
+fn with_p2p() {
+    let peer = multihash::Multihash::random(multihash::Hash::SHA2256);
+    let other = multihash::Multihash::random(multihash::Hash::SHA2256);
+    let a: Multiaddr = "/ip4/1.2.3.4/tcp/80".parse().unwrap();
+
+    let a = a.with_p2p(peer.clone()).unwrap();
+    assert_eq!(Some(Protocol::P2p(peer.clone())), a.iter().last());
+    assert_eq!(a.clone().with_p2p(peer).unwrap(), a);
+    assert_eq!(a.clone().with_p2p(other).unwrap_err(), a)
+}
+
 #[test]
 fn append() {
     let mut a: Multiaddr = Protocol::Ip4(Ipv4Addr::new(1, 2, 3, 4)).into();
@@ -328,3 +381,79 @@ fn replace_ip4_with_ip6() {
     assert_eq!(result.unwrap(), "/ip6/2001:db8::1/tcp/10000".parse::<Multiaddr>().unwrap())
 }
 
+
+#[test]
+fn custom_protocols() {
+    let fixed = CustomProtocol {
+        code: 0x3f_0001,
+        name: "x-test-fixed",
+        size: ValueSize::Fixed(2),
+        parse: |s| s.parse::<u16>().ok().map(|n| n.to_be_bytes().to_vec()),
+        display: |b| u16::from_be_bytes([b[0], b[1]]).to_string(),
+    };
+    let variable = CustomProtocol {
+        code: 0x3f_0002,
+        name: "x-test-variable",
+        size: ValueSize::Variable,
+        parse: |s| Some(s.as_bytes().to_vec()),
+        display: |b| String::from_utf8_lossy(b).into_owned(),
+    };
+    let none = CustomProtocol {
+        code: 0x3f_0003,
+        name: "x-test-none",
+        size: ValueSize::None,
+        parse: |_| None,
+        display: |_| String::new(),
+    };
+    register(fixed).unwrap();
+    register(variable).unwrap();
+    register(none).unwrap();
+
+    assert_eq!(register(fixed), Err(RegisterError::CodeInUse(0x3f_0001)));
+    assert_eq!(register(CustomProtocol { code: 0x3f_0004, .. variable }), Err(RegisterError::NameInUse("x-test-variable")));
+    assert_eq!(register(CustomProtocol { code: 6, name: "x-test-tcp", .. fixed }), Err(RegisterError::CodeInUse(6)));
+    assert_eq!(register(CustomProtocol { code: 0x3f_0005, name: "tcp", .. fixed }), Err(RegisterError::NameInUse("tcp")));
+
+    let s = "/ip4/1.2.3.4/x-test-fixed/1234/x-test-variable/hello/x-test-none";
+    let a: Multiaddr = s.parse().unwrap();
+    assert_eq!(a.to_string(), s);
+    assert_eq!(Multiaddr::try_from(a.to_vec()).unwrap(), a);
+
+    let mut i = a.iter().skip(1);
+    assert_eq!(Some(Protocol::Custom(0x3f_0001, vec![4, 210].into())), i.next());
+    assert_eq!(Some(Protocol::Custom(0x3f_0002, b"hello".to_vec().into())), i.next());
+    assert_eq!(Some(Protocol::Custom(0x3f_0003, Vec::new().into())), i.next());
+
+    assert!("/x-test-fixed/70000".parse::<Multiaddr>().is_err());
+}
+
+#[test]
+fn classification() {
+    let ma = |s: &str| Multiaddr::from_str(s).unwrap();
+
+    assert!(ma("/ip4/1.2.3.4/tcp/4001").is_public());
+    assert!(ma("/ip6/2a00:1450::1/tcp/4001").is_public());
+    assert!(ma("/dns4/example.com/tcp/443").is_public());
+    assert!(!ma("/dns4/localhost/tcp/443").is_public());
+    assert!(!ma("/ip4/0.0.0.0/tcp/4001").is_public());
+    assert!(!ma("/ip6/2001:db8::1/tcp/4001").is_public());
+
+    for addr in &["/ip4/10.0.0.1/tcp/1", "/ip4/192.168.1.1/tcp/1", "/ip4/100.64.0.1/tcp/1",
+                  "/ip4/169.254.0.1/tcp/1", "/ip6/fd00::1/tcp/1", "/ip6/fe80::1/tcp/1"] {
+        assert!(ma(addr).is_private(), "{}", addr);
+        assert!(!ma(addr).is_public(), "{}", addr);
+        assert!(!ma(addr).is_loopback(), "{}", addr);
+    }
+
+    assert!(ma("/ip4/127.0.0.1/tcp/1").is_loopback());
+    assert!(ma("/ip6/::1/tcp/1").is_loopback());
+    assert!(!ma("/ip4/127.0.0.1/tcp/1").is_public());
+    assert!(!ma("/ip4/127.0.0.1/tcp/1").is_private());
+    assert!(!ma("/memory/1").is_public());
+    assert!(!ma("/memory/1").is_private());
+
+    let relayed = ma("/ip4/1.2.3.4/tcp/4001/p2p/QmcgpsyWgH8Y8ajJz1Cu72KnS5uo2Aa2LpzU7kinSupNKC/p2p-circuit");
+    assert!(relayed.is_relayed());
+    assert!(relayed.is_public());
+    assert!(!ma("/ip4/1.2.3.4/tcp/4001").is_relayed());
+}
@@ -0,0 +1,73 @@
+// Copyright 2019 Parity Technologies (UK) Ltd.
+//
+// Permission is hereby granted, free of charge, to any person obtaining a
+// copy of this software and associated documentation files (the "Software"),
+// to deal in the Software without restriction, including without limitation
+// the rights to use, copy, modify, merge, publish, distribute, sublicense,
+// and/or sell copies of the Software, and to permit persons to whom the
+// Software is furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS
+// OR IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING
+// FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
+// DEALINGS IN THE SOFTWARE.
+
+//! Benchmarks of the hashing done when deriving peer IDs and DHT keys.
+//!
+//! Run with `cargo bench`, and with `cargo bench --features asm` to compare with the
+//! accelerated backend.
+
+use criterion::{Criterion, criterion_main, criterion_group};
+use parity_multihash::{encode, Hash};
+use rand::RngCore;
+
+/// Size of the protobuf encoding of an RSA-2048 public key, which is hashed to derive a
+/// peer ID.
+const RSA_PUBLIC_KEY_LEN: usize = 299;
+
+/// Size of a peer ID with a SHA2-256 multihash, which is hashed to derive its DHT key.
+const PEER_ID_LEN: usize = 34;
+
+fn random_bytes(len: usize) -> Vec<u8> {
+    let mut bytes = vec![0; len];
+    rand::thread_rng().fill_bytes(&mut bytes);
+    bytes
+}
+
+fn peer_id_derivation(c: &mut Criterion) {
+    let key = random_bytes(RSA_PUBLIC_KEY_LEN);
+    c.bench_function("sha2-256 of an RSA public key", move |b| {
+        b.iter(|| encode(Hash::SHA2256, &key).unwrap())
+    });
+}
+
+fn dht_key_hashing(c: &mut Criterion) {
+    let peer_id = random_bytes(PEER_ID_LEN);
+    c.bench_function("sha2-256 of a peer ID", move |b| {
+        b.iter(|| encode(Hash::SHA2256, &peer_id).unwrap())
+    });
+
+    // Publishing provider records hashes many keys in a row.
+    let keys = (0 .. 1000).map(|_| random_bytes(PEER_ID_LEN)).collect::<Vec<_>>();
+    c.bench_function("sha2-256 of 1000 peer IDs", move |b| {
+        b.iter(|| for key in &keys {
+            encode(Hash::SHA2256, key).unwrap();
+        })
+    });
+}
+
+fn sha2_512(c: &mut Criterion) {
+    let data = random_bytes(1024);
+    c.bench_function("sha2-512 of 1KiB", move |b| {
+        b.iter(|| encode(Hash::SHA2512, &data).unwrap())
+    });
+}
+
+criterion_group!(benches, peer_id_derivation, dht_key_hashing, sha2_512);
+criterion_main!(benches);
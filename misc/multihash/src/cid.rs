@@ -0,0 +1,318 @@
+//! Implementation of [content identifiers](https://github.com/multiformats/cid) (CIDs).
+//!
+//! A CID designates a piece of content by the multihash of its bytes, along with the
+//! [multicodec](https://github.com/multiformats/multicodec) describing how to interpret them.
+//! Version 0 CIDs are bare SHA2-256 multihashes of DAG-protobuf content, written in base58btc.
+//! Version 1 CIDs are prefixed with their version and multicodec, and written in any
+//! [multibase](https://github.com/multiformats/multibase).
+
+use crate::{encode, Hash, Multihash};
+use data_encoding::{BASE32_NOPAD, BASE64_NOPAD, HEXLOWER};
+use std::{error, fmt, str::FromStr};
+
+/// Multicodec of raw binary blocks.
+pub const RAW: u64 = 0x55;
+/// Multicodec of DAG-protobuf encoded blocks. The only codec allowed in version 0 CIDs.
+pub const DAG_PB: u64 = 0x70;
+/// Multicodec of DAG-CBOR encoded blocks.
+pub const DAG_CBOR: u64 = 0x71;
+/// Multicodec of libp2p public keys, as used by the CIDs of peer IDs.
+pub const LIBP2P_KEY: u64 = 0x72;
+
+/// Identifier of a block of content.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Cid {
+    version: u64,
+    codec: u64,
+    hash: Multihash,
+}
+
+impl Cid {
+    /// Builds a version 0 CID out of the SHA2-256 multihash of a DAG-protobuf block.
+    pub fn new_v0(hash: Multihash) -> Result<Cid, CidError> {
+        if hash.algorithm() != Hash::SHA2256 {
+            return Err(CidError::InvalidV0);
+        }
+        Ok(Cid { version: 0, codec: DAG_PB, hash })
+    }
+
+    /// Builds a version 1 CID.
+    pub fn new_v1(codec: u64, hash: Multihash) -> Cid {
+        Cid { version: 1, codec, hash }
+    }
+
+    /// Computes the CID of `data`, hashing it with the given algorithm.
+    pub fn from_data(version: u64, codec: u64, hash: Hash, data: &[u8]) -> Result<Cid, CidError> {
+        Prefix { version, codec, hash, hash_len: u64::from(hash.size()) }.to_cid(data)
+    }
+
+    /// Returns the version of this CID.
+    pub fn version(&self) -> u64 {
+        self.version
+    }
+
+    /// Returns the multicodec of the content designated by this CID.
+    pub fn codec(&self) -> u64 {
+        self.codec
+    }
+
+    /// Returns the multihash of the content designated by this CID.
+    ///
+    /// This is what the Kademlia DHT uses as the key of provider records.
+    pub fn hash(&self) -> &Multihash {
+        &self.hash
+    }
+
+    /// Turns this CID into the multihash of the content it designates.
+    pub fn into_hash(self) -> Multihash {
+        self.hash
+    }
+
+    /// Returns the version 1 CID designating the same content.
+    pub fn into_v1(self) -> Cid {
+        Cid::new_v1(self.codec, self.hash)
+    }
+
+    /// Returns the prefix of this CID, which contains everything but the digest.
+    pub fn prefix(&self) -> Prefix {
+        let hash = self.hash.algorithm();
+        Prefix { version: self.version, codec: self.codec, hash, hash_len: self.hash.digest().len() as u64 }
+    }
+
+    /// Checks whether `data` is the content designated by this CID.
+    pub fn verify(&self, data: &[u8]) -> bool {
+        match self.prefix().to_cid(data) {
+            Ok(cid) => cid == *self,
+            Err(_) => false,
+        }
+    }
+
+    /// Encodes this CID in its binary form.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        if self.version == 0 {
+            return self.hash.to_vec();
+        }
+
+        let mut out = Vec::with_capacity(self.hash.as_bytes().len() + 4);
+        write_varint(&mut out, self.version);
+        write_varint(&mut out, self.codec);
+        out.extend_from_slice(self.hash.as_bytes());
+        out
+    }
+
+    /// Decodes a CID from its binary form.
+    pub fn from_bytes(bytes: &[u8]) -> Result<Cid, CidError> {
+        // Version 0 CIDs are bare SHA2-256 multihashes.
+        if bytes.len() == 34 && bytes[0] == 0x12 && bytes[1] == 0x20 {
+            let hash = Multihash::from_bytes(bytes.to_vec()).map_err(|_| CidError::InvalidMultihash)?;
+            return Cid::new_v0(hash);
+        }
+
+        let (version, rest) = read_varint(bytes)?;
+        if version != 1 {
+            return Err(CidError::UnknownVersion(version));
+        }
+        let (codec, rest) = read_varint(rest)?;
+        let hash = Multihash::from_bytes(rest.to_vec()).map_err(|_| CidError::InvalidMultihash)?;
+        Ok(Cid::new_v1(codec, hash))
+    }
+
+    /// Encodes this CID as a string in the given multibase.
+    ///
+    /// Version 0 CIDs can only be written in base58btc, without multibase prefix.
+    pub fn to_string_of_base(&self, base: Base) -> Result<String, CidError> {
+        if self.version == 0 {
+            return match base {
+                Base::Base58Btc => Ok(bs58::encode(self.to_bytes()).into_string()),
+                _ => Err(CidError::InvalidV0),
+            };
+        }
+        Ok(base.encode(&self.to_bytes()))
+    }
+}
+
+/// Writes the canonical string representation of the CID: base58btc for version 0 and
+/// multibase base32 for version 1.
+impl fmt::Display for Cid {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.version == 0 {
+            f.write_str(&bs58::encode(self.to_bytes()).into_string())
+        } else {
+            f.write_str(&Base::Base32Lower.encode(&self.to_bytes()))
+        }
+    }
+}
+
+impl FromStr for Cid {
+    type Err = CidError;
+
+    /// Parses a version 0 CID written in base58btc (`Qm...`), or a version 1 CID in any of
+    /// the supported multibases.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if s.len() == 46 && s.starts_with("Qm") {
+            let bytes = bs58::decode(s).into_vec().map_err(|_| CidError::InvalidMultibase)?;
+            return Cid::from_bytes(&bytes);
+        }
+
+        let cid = Cid::from_bytes(&Base::decode(s)?)?;
+        if cid.version == 0 {
+            // Version 0 CIDs are never prefixed with a multibase.
+            return Err(CidError::InvalidV0);
+        }
+        Ok(cid)
+    }
+}
+
+/// Multibase supported for the string representation of CIDs.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Base {
+    /// Lowercase hexadecimal, prefixed with `f`.
+    Base16Lower,
+    /// Lowercase RFC 4648 base32 without padding, prefixed with `b`.
+    Base32Lower,
+    /// Bitcoin base58, prefixed with `z`.
+    Base58Btc,
+    /// RFC 4648 base64 without padding, prefixed with `m`.
+    Base64,
+}
+
+impl Base {
+    /// Returns the character identifying this multibase.
+    pub fn code(self) -> char {
+        match self {
+            Base::Base16Lower => 'f',
+            Base::Base32Lower => 'b',
+            Base::Base58Btc => 'z',
+            Base::Base64 => 'm',
+        }
+    }
+
+    /// Returns the multibase identified by the given character.
+    pub fn from_code(code: char) -> Option<Base> {
+        match code {
+            'f' => Some(Base::Base16Lower),
+            'b' => Some(Base::Base32Lower),
+            'z' => Some(Base::Base58Btc),
+            'm' => Some(Base::Base64),
+            _ => None,
+        }
+    }
+
+    /// Encodes `data` in this multibase, prefix included.
+    pub fn encode(self, data: &[u8]) -> String {
+        let encoded = match self {
+            Base::Base16Lower => HEXLOWER.encode(data),
+            Base::Base32Lower => BASE32_NOPAD.encode(data).to_lowercase(),
+            Base::Base58Btc => bs58::encode(data).into_string(),
+            Base::Base64 => BASE64_NOPAD.encode(data),
+        };
+        format!("{}{}", self.code(), encoded)
+    }
+
+    /// Decodes a string prefixed with the code of its multibase.
+    pub fn decode(s: &str) -> Result<Vec<u8>, CidError> {
+        let mut chars = s.chars();
+        let base = chars.next().and_then(Base::from_code).ok_or(CidError::InvalidMultibase)?;
+        let data = chars.as_str();
+        let decoded = match base {
+            Base::Base16Lower => HEXLOWER.decode(data.as_bytes()).ok(),
+            Base::Base32Lower => BASE32_NOPAD.decode(data.to_uppercase().as_bytes()).ok(),
+            Base::Base58Btc => bs58::decode(data).into_vec().ok(),
+            Base::Base64 => BASE64_NOPAD.decode(data.as_bytes()).ok(),
+        };
+        decoded.ok_or(CidError::InvalidMultibase)
+    }
+}
+
+/// Everything needed to compute the CID of a block, except for the block itself.
+///
+/// Bitswap 1.1.0 and above transmit blocks alongside their prefix, so that the receiver can
+/// compute and check their CID.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Prefix {
+    /// Version of the CID.
+    pub version: u64,
+    /// Multicodec of the content.
+    pub codec: u64,
+    /// Hashing algorithm.
+    pub hash: Hash,
+    /// Length of the digest.
+    pub hash_len: u64,
+}
+
+impl Prefix {
+    /// Encodes the prefix as the concatenation of four varints.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut out = Vec::with_capacity(8);
+        write_varint(&mut out, self.version);
+        write_varint(&mut out, self.codec);
+        write_varint(&mut out, u64::from(self.hash.code()));
+        write_varint(&mut out, self.hash_len);
+        out
+    }
+
+    /// Decodes a prefix.
+    pub fn from_bytes(bytes: &[u8]) -> Result<Prefix, CidError> {
+        let (version, rest) = read_varint(bytes)?;
+        let (codec, rest) = read_varint(rest)?;
+        let (code, rest) = read_varint(rest)?;
+        let (hash_len, _) = read_varint(rest)?;
+        if code > u64::from(u16::max_value()) {
+            return Err(CidError::InvalidMultihash);
+        }
+        let hash = Hash::from_code(code as u16).ok_or(CidError::InvalidMultihash)?;
+        Ok(Prefix { version, codec, hash, hash_len })
+    }
+
+    /// Hashes `data` and builds the corresponding CID.
+    pub fn to_cid(&self, data: &[u8]) -> Result<Cid, CidError> {
+        if self.hash_len != u64::from(self.hash.size()) {
+            return Err(CidError::InvalidMultihash);
+        }
+        let hash = encode(self.hash, data).map_err(|_| CidError::InvalidMultihash)?;
+        match self.version {
+            0 if self.codec == DAG_PB => Cid::new_v0(hash),
+            0 => Err(CidError::InvalidV0),
+            1 => Ok(Cid::new_v1(self.codec, hash)),
+            v => Err(CidError::UnknownVersion(v)),
+        }
+    }
+}
+
+fn write_varint(out: &mut Vec<u8>, value: u64) {
+    let mut buf = unsigned_varint::encode::u64_buffer();
+    out.extend_from_slice(unsigned_varint::encode::u64(value, &mut buf));
+}
+
+fn read_varint(bytes: &[u8]) -> Result<(u64, &[u8]), CidError> {
+    unsigned_varint::decode::u64(bytes).map_err(|_| CidError::InvalidVarint)
+}
+
+/// Error while building or decoding a CID.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CidError {
+    /// A varint is malformed.
+    InvalidVarint,
+    /// The version of the CID is not supported.
+    UnknownVersion(u64),
+    /// Version 0 CIDs must be SHA2-256 hashes of DAG-protobuf content, written in base58btc.
+    InvalidV0,
+    /// The multihash is malformed or uses an unsupported algorithm.
+    InvalidMultihash,
+    /// The multibase of the string representation is unsupported, or the string is malformed.
+    InvalidMultibase,
+}
+
+impl fmt::Display for CidError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CidError::InvalidVarint => write!(f, "Invalid varint in CID"),
+            CidError::UnknownVersion(v) => write!(f, "Unsupported CID version {}", v),
+            CidError::InvalidV0 => write!(f, "Invalid version 0 CID"),
+            CidError::InvalidMultihash => write!(f, "Invalid or unsupported multihash in CID"),
+            CidError::InvalidMultibase => write!(f, "Invalid or unsupported multibase in CID"),
+        }
+    }
+}
+
+impl error::Error for CidError {}
@@ -1,8 +1,11 @@
 /// List of types currently supported in the multihash spec.
 ///
-/// Not all hash types are supported by this library.
+/// All of them can be decoded. Encoding the SHA-3/Keccak and BLAKE2 hash types requires
+/// the `sha3` and `blake2` features respectively.
 #[derive(PartialEq, Eq, Clone, Debug, Copy, Hash)]
 pub enum Hash {
+    /// Identity hash (the input itself, of variable size)
+    Identity,
     /// SHA-1 (20-byte hash size)
     SHA1,
     /// SHA-256 (32-byte hash size)
@@ -27,11 +30,11 @@ pub enum Hash {
     Keccak512,
     /// BLAKE2b-512 (64-byte hash size)
     Blake2b512,
-    /// Encoding unsupported
+    /// BLAKE2b-256 (32-byte hash size)
     Blake2b256,
     /// BLAKE2s-256 (32-byte hash size)
     Blake2s256,
-    /// Encoding unsupported
+    /// BLAKE2s-128 (16-byte hash size)
     Blake2s128,
 }
 
@@ -39,6 +42,7 @@ impl Hash {
     /// Get the corresponding hash code.
     pub fn code(&self) -> u16 {
         match self {
+            Hash::Identity => 0x00,
             Hash::SHA1 => 0x11,
             Hash::SHA2256 => 0x12,
             Hash::SHA2512 => 0x13,
@@ -58,8 +62,11 @@ impl Hash {
     }
 
     /// Get the hash length in bytes.
+    ///
+    /// Returns 0 for `Identity`, whose length is the length of its input.
     pub fn size(&self) -> u8 {
         match self {
+            Hash::Identity => 0,
             Hash::SHA1 => 20,
             Hash::SHA2256 => 32,
             Hash::SHA2512 => 64,
@@ -81,6 +88,7 @@ impl Hash {
     /// Returns the algorithm corresponding to a code, or `None` if no algorithm is matching.
     pub fn from_code(code: u16) -> Option<Hash> {
         Some(match code {
+            0x00 => Hash::Identity,
             0x11 => Hash::SHA1,
             0x12 => Hash::SHA2256,
             0x13 => Hash::SHA2512,
@@ -4,10 +4,25 @@
 //!
 //! A `Multihash` is a structure that contains a hashing algorithm, plus some hashed data.
 //! A `MultihashRef` is the same as a `Multihash`, except that it doesn't own its data.
+//!
+//! The SHA-3/Keccak and BLAKE2 hash functions are available behind the `sha3` and `blake2`
+//! features respectively, both enabled by default. Multihashes using these functions can
+//! always be decoded; only encoding requires the corresponding feature.
+//!
+//! The `asm` feature switches SHA-1 and SHA-2 to assembly implementations, which use the SHA
+//! extensions of the CPU where available and are several times faster for the small inputs
+//! that make up peer IDs and DHT keys. Since features are unified, this also accelerates the
+//! other crates of the build that hash with `sha2`, such as the Kademlia key derivation.
+//! Libp2p exposes it as its `multihash-asm` feature.
+//!
+//! The [`cid`] module implements content identifiers, which designate content by its multihash.
 
+pub mod cid;
 mod errors;
 mod hashes;
 
+#[cfg(feature = "blake2")]
+use blake2::digest::{Input, VariableOutput};
 use bytes::{BufMut, Bytes, BytesMut};
 use rand::RngCore;
 use sha2::Digest;
@@ -22,16 +37,27 @@ fn digest_encode<D: Digest>(input: &[u8], output: &mut [u8]) {
     output.copy_from_slice(&D::digest(input))
 }
 
+/// Helper function for encoding input into output using a `VariableOutput` digest,
+/// whose output size is the size of `output`.
+#[cfg(feature = "blake2")]
+fn var_digest_encode<D: VariableOutput + Input>(input: &[u8], output: &mut [u8]) {
+    let mut hasher = D::new(output.len()).expect("multihash sizes are valid output sizes");
+    hasher.input(input);
+    hasher.variable_result(|res| output.copy_from_slice(res))
+}
+
 // And another one to keep the matching DRY
 macro_rules! match_encoder {
     ($hash_id:ident for ($input:expr, $output:expr) {
-        $( $hashtype:ident => $hash_ty:path, )*
+        $( $(#[$meta:meta])* $hashtype:ident => $encoder:expr, )*
     }) => ({
         match $hash_id {
             $(
-                Hash::$hashtype => digest_encode::<$hash_ty>($input, $output),
+                $(#[$meta])*
+                Hash::$hashtype => $encoder($input, $output),
             )*
 
+            #[allow(unreachable_patterns)]
             _ => return Err(EncodeError::UnsupportedType)
         }
     })
@@ -57,21 +83,39 @@ macro_rules! match_encoder {
 /// ```
 ///
 pub fn encode(hash: Hash, input: &[u8]) -> Result<Multihash, EncodeError> {
+    if hash == Hash::Identity {
+        return Ok(encode_identity(input))
+    }
+
     let (offset, mut output) = encode_hash(hash);
     match_encoder!(hash for (input, &mut output[offset ..]) {
-        SHA1 => sha1::Sha1,
-        SHA2256 => sha2::Sha256,
-        SHA2512 => sha2::Sha512,
-        SHA3224 => sha3::Sha3_224,
-        SHA3256 => sha3::Sha3_256,
-        SHA3384 => sha3::Sha3_384,
-        SHA3512 => sha3::Sha3_512,
-        Keccak224 => sha3::Keccak224,
-        Keccak256 => sha3::Keccak256,
-        Keccak384 => sha3::Keccak384,
-        Keccak512 => sha3::Keccak512,
-        Blake2b512 => blake2::Blake2b,
-        Blake2s256 => blake2::Blake2s,
+        SHA1 => digest_encode::<sha1::Sha1>,
+        SHA2256 => digest_encode::<sha2::Sha256>,
+        SHA2512 => digest_encode::<sha2::Sha512>,
+        #[cfg(feature = "sha3")]
+        SHA3224 => digest_encode::<sha3::Sha3_224>,
+        #[cfg(feature = "sha3")]
+        SHA3256 => digest_encode::<sha3::Sha3_256>,
+        #[cfg(feature = "sha3")]
+        SHA3384 => digest_encode::<sha3::Sha3_384>,
+        #[cfg(feature = "sha3")]
+        SHA3512 => digest_encode::<sha3::Sha3_512>,
+        #[cfg(feature = "sha3")]
+        Keccak224 => digest_encode::<sha3::Keccak224>,
+        #[cfg(feature = "sha3")]
+        Keccak256 => digest_encode::<sha3::Keccak256>,
+        #[cfg(feature = "sha3")]
+        Keccak384 => digest_encode::<sha3::Keccak384>,
+        #[cfg(feature = "sha3")]
+        Keccak512 => digest_encode::<sha3::Keccak512>,
+        #[cfg(feature = "blake2")]
+        Blake2b512 => digest_encode::<blake2::Blake2b>,
+        #[cfg(feature = "blake2")]
+        Blake2b256 => var_digest_encode::<blake2::VarBlake2b>,
+        #[cfg(feature = "blake2")]
+        Blake2s256 => digest_encode::<blake2::Blake2s>,
+        #[cfg(feature = "blake2")]
+        Blake2s128 => var_digest_encode::<blake2::VarBlake2s>,
     });
 
     Ok(Multihash { bytes: output.freeze() })
@@ -93,6 +137,22 @@ fn encode_hash(hash: Hash) -> (usize, BytesMut) {
     (code.len() + 1, output)
 }
 
+// Encode the given input with the identity hash, whose length is a varint
+// as its size is not fixed.
+fn encode_identity(input: &[u8]) -> Multihash {
+    let mut code_buf = encode::u16_buffer();
+    let code = encode::u16(Hash::Identity.code(), &mut code_buf);
+    let mut len_buf = encode::usize_buffer();
+    let len = encode::usize(input.len(), &mut len_buf);
+
+    let mut output = BytesMut::with_capacity(code.len() + len.len() + input.len());
+    output.put_slice(code);
+    output.put_slice(len);
+    output.put_slice(input);
+
+    Multihash { bytes: output.freeze() }
+}
+
 /// Represents a valid multihash.
 #[derive(Debug, Clone, PartialEq, Eq, Hash)]
 pub struct Multihash { bytes: Bytes }
@@ -106,6 +166,13 @@ impl Multihash {
         Ok(Multihash { bytes: Bytes::from(bytes) })
     }
 
+    /// Verifies whether `bytes` contains a valid multihash, and if so returns a `Multihash`
+    /// sharing the buffer of `bytes` rather than copying it.
+    pub fn from_shared(bytes: Bytes) -> Result<Multihash, DecodeError> {
+        MultihashRef::from_slice(&bytes)?;
+        Ok(Multihash { bytes })
+    }
+
     /// Generates a random `Multihash` from a cryptographically secure PRNG.
     pub fn random(hash: Hash) -> Multihash {
         let (offset, mut bytes) = encode_hash(hash);
@@ -180,6 +247,15 @@ impl<'a> MultihashRef<'a> {
         let (code, bytes) = decode::u16(&input).map_err(|_| DecodeError::BadInputLength)?;
 
         let alg = Hash::from_code(code).ok_or(DecodeError::UnknownCode)?;
+
+        if alg == Hash::Identity {
+            let (len, digest) = decode::usize(bytes).map_err(|_| DecodeError::BadInputLength)?;
+            if digest.len() != len {
+                return Err(DecodeError::BadInputLength)
+            }
+            return Ok(MultihashRef { bytes: input })
+        }
+
         let hash_len = usize::from(alg.size());
 
         // Length of input after hash code should be exactly hash_len + 1
@@ -204,9 +280,11 @@ impl<'a> MultihashRef<'a> {
 
     /// Returns the hashed data.
     pub fn digest(&self) -> &'a [u8] {
-        let bytes = decode::u16(&self.bytes)
-            .expect("multihash is known to be valid digest")
-            .1;
+        let (code, bytes) = decode::u16(&self.bytes)
+            .expect("multihash is known to be valid digest");
+        if code == Hash::Identity.code() {
+            return decode::usize(bytes).expect("multihash is known to be valid digest").1
+        }
         &bytes[1 ..]
     }
 
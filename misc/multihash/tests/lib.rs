@@ -41,7 +41,9 @@ fn multihash_encode() {
         Keccak384, b"hello world", "1C3065fc99339a2a40e99d3c40d695b22f278853ca0f925cde4254bcae5e22ece47e6441f91b6568425adc9d95b0072eb49f";
         Keccak512, b"hello world", "1D403ee2b40047b8060f68c67242175660f4174d0af5c01d47168ec20ed619b0b7c42181f40aa1046f39e2ef9efc6910782a998e0013d172458957957fac9405b67d";
         Blake2b512, b"hello world", "c0e40240021ced8799296ceca557832ab941a50b4a11f83478cf141f51f933f653ab9fbcc05a037cddbed06e309bf334942c4e58cdf1a46e237911ccd7fcf9787cbc7fd0";
+        Blake2b256, b"hello world", "a0e40220256c83b297114d201b30179f3f0ef0cace9783622da5974326b436178aeef610";
         Blake2s256, b"hello world", "e0e402209aec6806794561107e594b1f6a8a6b0c92a0cba9acf5e5e93cca06f781813b0b";
+        Blake2s128, b"hello world", "d0e4021037deae0226c30da2ab424a7b8ee14e83";
     }
 }
 
@@ -74,7 +76,9 @@ fn assert_decode() {
         Keccak384, "1C3065fc99339a2a40e99d3c40d695b22f278853ca0f925cde4254bcae5e22ece47e6441f91b6568425adc9d95b0072eb49f";
         Keccak512, "1D403ee2b40047b8060f68c67242175660f4174d0af5c01d47168ec20ed619b0b7c42181f40aa1046f39e2ef9efc6910782a998e0013d172458957957fac9405b67d";
         Blake2b512, "c0e40240021ced8799296ceca557832ab941a50b4a11f83478cf141f51f933f653ab9fbcc05a037cddbed06e309bf334942c4e58cdf1a46e237911ccd7fcf9787cbc7fd0";
+        Blake2b256, "a0e40220256c83b297114d201b30179f3f0ef0cace9783622da5974326b436178aeef610";
         Blake2s256, "e0e402209aec6806794561107e594b1f6a8a6b0c92a0cba9acf5e5e93cca06f781813b0b";
+        Blake2s128, "d0e4021037deae0226c30da2ab424a7b8ee14e83";
     }
 }
 
@@ -96,7 +100,7 @@ macro_rules! assert_roundtrip {
 fn assert_roundtrip() {
     assert_roundtrip!(
         SHA1, SHA2256, SHA2512, SHA3224, SHA3256, SHA3384, SHA3512, Keccak224, Keccak256,
-        Keccak384, Keccak512, Blake2b512, Blake2s256
+        Keccak384, Keccak512, Blake2b512, Blake2b256, Blake2s256, Blake2s128
     );
 }
 
@@ -118,3 +122,66 @@ fn hash_types() {
     assert_eq!(Hash::Blake2s256.size(), 32);
     assert_eq!(Hash::Blake2s128.size(), 16);
 }
+
+#[test]
+fn identity() {
+    let hash = encode(Hash::Identity, b"hello world").unwrap();
+    assert_eq!(hash.as_bytes(), &hex_to_bytes("000b68656c6c6f20776f726c64")[..]);
+    assert_eq!(hash.algorithm(), Hash::Identity);
+    assert_eq!(hash.digest(), b"hello world");
+
+    let long = vec![7; 300];
+    let hash = encode(Hash::Identity, &long).unwrap();
+    let decoded = MultihashRef::from_slice(hash.as_bytes()).unwrap();
+    assert_eq!(decoded.digest(), &long[..]);
+
+    assert!(MultihashRef::from_slice(&hex_to_bytes("000b68656c6c6f")).is_err());
+}
+
+#[test]
+fn cid_roundtrip() {
+    use parity_multihash::cid::*;
+
+    let v0 = Cid::from_data(0, DAG_PB, Hash::SHA2256, b"hello").unwrap();
+    assert_eq!(v0.to_bytes().len(), 34);
+    assert_eq!(Cid::from_bytes(&v0.to_bytes()).unwrap(), v0);
+
+    let v1 = Cid::from_data(1, RAW, Hash::SHA2256, b"hello").unwrap();
+    assert_eq!(Cid::from_bytes(&v1.to_bytes()).unwrap(), v1);
+    assert_eq!(Prefix::from_bytes(&v1.prefix().to_bytes()).unwrap(), v1.prefix());
+    assert!(v1.verify(b"hello"));
+    assert!(!v1.verify(b"world"));
+}
+
+#[test]
+fn cid_v0_requires_dag_pb() {
+    use parity_multihash::cid::*;
+
+    assert_eq!(Cid::from_data(0, RAW, Hash::SHA2256, b"hello"), Err(CidError::InvalidV0));
+    assert_eq!(Cid::from_data(0, DAG_PB, Hash::SHA3256, b"hello"), Err(CidError::InvalidV0));
+}
+
+#[test]
+fn cid_strings() {
+    use parity_multihash::cid::*;
+
+    let cid = Cid::from_data(1, RAW, Hash::SHA2256, b"hello world").unwrap();
+    assert_eq!(cid.to_string(), "bafkreifzjut3te2nhyekklss27nh3k72ysco7y32koao5eei66wof36n5e");
+    assert_eq!(
+        cid.to_string_of_base(Base::Base16Lower).unwrap(),
+        "f01551220b94d27b9934d3e08a52e52d7da7dabfac484efe37a5380ee9088f7ace2efcde9"
+    );
+    for base in &[Base::Base16Lower, Base::Base32Lower, Base::Base58Btc, Base::Base64] {
+        let s = cid.to_string_of_base(*base).unwrap();
+        assert_eq!(s.parse::<Cid>().unwrap(), cid);
+    }
+
+    let v0 = Cid::from_data(0, DAG_PB, Hash::SHA2256, b"hello world").unwrap();
+    let s = v0.to_string();
+    assert!(s.starts_with("Qm"));
+    assert_eq!(s.parse::<Cid>().unwrap(), v0);
+    assert_eq!(v0.to_string_of_base(Base::Base32Lower), Err(CidError::InvalidV0));
+    assert_eq!(v0.clone().into_v1().hash(), v0.hash());
+
+    assert_eq!("xabc".parse::<Cid>(), Err(CidError::InvalidMultibase));
+}
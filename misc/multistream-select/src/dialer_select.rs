@@ -23,10 +23,11 @@
 
 use futures::{future::Either, prelude::*, stream::StreamFuture};
 use crate::protocol::{Dialer, DialerFuture, Request, Response};
-use log::trace;
+use smallvec::SmallVec;
+use tracing::trace;
 use std::mem;
 use tokio_io::{AsyncRead, AsyncWrite};
-use crate::{Negotiated, ProtocolChoiceError};
+use crate::{NegotiationFailure, Negotiated, ProtocolChoiceError};
 
 /// Future, returned by `dialer_select_proto`, which selects a protocol and dialer
 /// either sequentially or by considering all protocols in parallel.
@@ -70,6 +71,8 @@ where
 {
     let protocols = protocols.into_iter();
     DialerSelectSeq {
+        span: tracing::trace_span!("multistream_select", role = "dialer"),
+        offered: SmallVec::new(),
         inner: DialerSelectSeqState::AwaitDialer {
             dialer_fut: Dialer::dial(inner),
             protocols
@@ -86,6 +89,10 @@ where
     I: Iterator,
     I::Item: AsRef<[u8]>
 {
+    span: tracing::Span,
+    /// Protocols proposed to the remote so far. Only turned into names for the error if the
+    /// negotiation fails, so that the common case of a single accepted protocol doesn't allocate.
+    offered: SmallVec<[I::Item; 1]>,
     inner: DialerSelectSeqState<R, I>
 }
 
@@ -117,6 +124,29 @@ where
     Undefined
 }
 
+impl<R, I> DialerSelectSeq<R, I>
+where
+    R: AsyncRead + AsyncWrite,
+    I: Iterator,
+    I::Item: AsRef<[u8]> + Clone
+{
+    /// Picks the next protocol to propose, or fails if we have proposed all of them.
+    fn next_protocol(&mut self, protocols: &mut I) -> Result<I::Item, ProtocolChoiceError> {
+        match protocols.next() {
+            Some(proto_name) => {
+                self.offered.push(proto_name.clone());
+                Ok(proto_name)
+            }
+            None => Err(ProtocolChoiceError::NoProtocolFound(NegotiationFailure {
+                offered: self.offered.iter()
+                    .map(|name| NegotiationFailure::protocol_name(name.as_ref()))
+                    .collect(),
+                remote: None,
+            }))
+        }
+    }
+}
+
 impl<R, I> Future for DialerSelectSeq<R, I>
 where
     R: AsyncRead + AsyncWrite,
@@ -127,6 +157,8 @@ where
     type Error = ProtocolChoiceError;
 
     fn poll(&mut self) -> Poll<Self::Item, Self::Error> {
+        let span = self.span.clone();
+        let _entered = span.enter();
         loop {
             match mem::replace(&mut self.inner, DialerSelectSeqState::Undefined) {
                 DialerSelectSeqState::AwaitDialer { mut dialer_fut, mut protocols } => {
@@ -137,7 +169,7 @@ where
                             return Ok(Async::NotReady)
                         }
                     };
-                    let proto_name = protocols.next().ok_or(ProtocolChoiceError::NoProtocolFound)?;
+                    let proto_name = self.next_protocol(&mut protocols)?;
                     self.inner = DialerSelectSeqState::NextProtocol {
                         dialer,
                         protocols,
@@ -203,11 +235,11 @@ where
                         Response::Protocol { ref name }
                             if name.as_ref() == proto_name.as_ref() =>
                         {
+                            trace!(protocol = ?proto_name.as_ref(), "negotiated");
                             return Ok(Async::Ready((proto_name, Negotiated(r.into_inner()))))
                         }
                         Response::ProtocolNotAvailable => {
-                            let proto_name = protocols.next()
-                                .ok_or(ProtocolChoiceError::NoProtocolFound)?;
+                            let proto_name = self.next_protocol(&mut protocols)?;
                             self.inner = DialerSelectSeqState::NextProtocol {
                                 dialer: r,
                                 protocols,
@@ -236,6 +268,7 @@ where
 {
     let protocols = protocols.into_iter();
     DialerSelectPar {
+        span: tracing::trace_span!("multistream_select", role = "dialer"),
         inner: DialerSelectParState::AwaitDialer { dialer_fut: Dialer::dial(inner), protocols }
     }
 }
@@ -249,6 +282,7 @@ where
     I: Iterator,
     I::Item: AsRef<[u8]>
 {
+    span: tracing::Span,
     inner: DialerSelectParState<R, I>
 }
 
@@ -299,6 +333,8 @@ where
     type Error = ProtocolChoiceError;
 
     fn poll(&mut self) -> Poll<Self::Item, Self::Error> {
+        let span = self.span.clone();
+        let _entered = span.enter();
         loop {
             match mem::replace(&mut self.inner, DialerSelectParState::Undefined) {
                 DialerSelectParState::AwaitDialer { mut dialer_fut, protocols } => {
@@ -361,18 +397,22 @@ where
                             return Err(ProtocolChoiceError::UnexpectedMessage)
                         };
                     let mut found = None;
+                    let mut offered = Vec::new();
                     for local_name in protocols {
-                        for remote_name in &supported {
-                            if remote_name.as_ref() == local_name.as_ref() {
-                                found = Some(local_name);
-                                break;
-                            }
-                        }
-                        if found.is_some() {
+                        if supported.iter().any(|remote_name| remote_name.as_ref() == local_name.as_ref()) {
+                            found = Some(local_name);
                             break;
                         }
+                        offered.push(NegotiationFailure::protocol_name(local_name.as_ref()));
                     }
-                    let proto_name = found.ok_or(ProtocolChoiceError::NoProtocolFound)?;
+                    let proto_name = found.ok_or_else(|| {
+                        ProtocolChoiceError::NoProtocolFound(NegotiationFailure {
+                            offered,
+                            remote: Some(supported.iter()
+                                .map(|p| NegotiationFailure::protocol_name(p.as_ref()))
+                                .collect()),
+                        })
+                    })?;
                     self.inner = DialerSelectParState::Protocol { dialer, proto_name }
                 }
                 DialerSelectParState::Protocol { mut dialer, proto_name } => {
@@ -416,6 +456,7 @@ where
                         Some(Response::Protocol { ref name })
                             if name.as_ref() == proto_name.as_ref() =>
                         {
+                            trace!(protocol = ?proto_name.as_ref(), "negotiated");
                             return Ok(Async::Ready((proto_name, Negotiated(dialer.into_inner()))))
                         }
                         _ => return Err(ProtocolChoiceError::UnexpectedMessage)
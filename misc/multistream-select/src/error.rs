@@ -34,7 +34,35 @@ pub enum ProtocolChoiceError {
     UnexpectedMessage,
 
     /// We don't support any protocol in common with the remote.
-    NoProtocolFound,
+    NoProtocolFound(NegotiationFailure),
+}
+
+/// Protocols involved in a negotiation that failed because the local node and the remote don't
+/// support any protocol in common.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct NegotiationFailure {
+    /// The protocols we offered: the ones we proposed to the remote as the dialer, or the ones
+    /// we support as the listener.
+    pub offered: Vec<String>,
+    /// The protocols supported by the remote, if known: the list it sent back when we asked for
+    /// it as the dialer, or the protocols it proposed as the listener.
+    pub remote: Option<Vec<String>>,
+}
+
+impl NegotiationFailure {
+    pub(crate) fn protocol_name(name: &[u8]) -> String {
+        String::from_utf8_lossy(name).into_owned()
+    }
+}
+
+impl fmt::Display for NegotiationFailure {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "offered [{}]", self.offered.join(", "))?;
+        if let Some(remote) = &self.remote {
+            write!(f, ", remote supports [{}]", remote.join(", "))?;
+        }
+        Ok(())
+    }
 }
 
 impl From<MultistreamSelectError> for ProtocolChoiceError {
@@ -56,7 +84,7 @@ impl Error for ProtocolChoiceError {
             ProtocolChoiceError::UnexpectedMessage => {
                 "received a message from the remote that makes no sense in the current context"
             }
-            ProtocolChoiceError::NoProtocolFound => {
+            ProtocolChoiceError::NoProtocolFound(_) => {
                 "we don't support any protocol in common with the remote"
             }
         }
@@ -72,6 +100,10 @@ impl Error for ProtocolChoiceError {
 
 impl fmt::Display for ProtocolChoiceError {
     fn fmt(&self, fmt: &mut fmt::Formatter<'_>) -> Result<(), fmt::Error> {
-        write!(fmt, "{}", Error::description(self))
+        match self {
+            ProtocolChoiceError::NoProtocolFound(failure) =>
+                write!(fmt, "{} ({})", Error::description(self), failure),
+            _ => write!(fmt, "{}", Error::description(self)),
+        }
     }
 }
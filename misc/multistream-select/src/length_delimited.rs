@@ -18,9 +18,10 @@
 // FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
 // DEALINGS IN THE SOFTWARE.
 
-use bytes::{Bytes, BytesMut, BufMut};
+use bytes::{Buf, Bytes, BytesMut};
 use futures::{try_ready, Async, Poll, Sink, StartSend, Stream, AsyncSink};
-use std::{io, u16};
+use iovec::IoVec;
+use std::{cmp, collections::VecDeque, io, u16};
 use tokio_io::{AsyncRead, AsyncWrite};
 use unsigned_varint as uvi;
 
@@ -38,9 +39,13 @@ pub struct LengthDelimited<R> {
     /// The inner I/O resource.
     inner: R,
     /// Read buffer for a single incoming unsigned-varint length-delimited frame.
+    ///
+    /// Frames are split off the front of the buffer, so that its remaining capacity is reused
+    /// for the next frames instead of allocating for each of them.
     read_buffer: BytesMut,
-    /// Write buffer for outgoing unsigned-varint length-delimited frames.
-    write_buffer: BytesMut,
+    /// Outgoing unsigned-varint length-delimited frames, as length prefixes and payloads that
+    /// are written without being copied.
+    write_buffer: WriteBuffer,
     /// The current read state, alternating between reading a frame
     /// length and reading a frame payload.
     read_state: ReadState,
@@ -71,7 +76,7 @@ impl<R> LengthDelimited<R> {
             inner,
             read_state: ReadState::default(),
             read_buffer: BytesMut::with_capacity(DEFAULT_BUFFER_SIZE),
-            write_buffer: BytesMut::with_capacity(DEFAULT_BUFFER_SIZE + MAX_LEN_BYTES as usize),
+            write_buffer: WriteBuffer::default(),
         }
     }
 
@@ -126,12 +131,15 @@ where
                     if (buf[*pos - 1] & 0x80) == 0 {
                         // MSB is not set, indicating the end of the length prefix.
                         let (len, _) = uvi::decode::u16(buf).map_err(|e| {
-                            log::debug!("invalid length prefix: {}", e);
+                            tracing::debug!("invalid length prefix: {}", e);
                             io::Error::new(io::ErrorKind::InvalidData, "invalid length prefix")
                         })?;
 
                         if len >= 1 {
                             self.read_state = ReadState::ReadData { len, pos: 0 };
+                            if self.read_buffer.capacity() < len as usize {
+                                self.read_buffer.reserve(cmp::max(len as usize, DEFAULT_BUFFER_SIZE));
+                            }
                             self.read_buffer.resize(len as usize, 0);
                         } else {
                             debug_assert_eq!(len, 0);
@@ -159,7 +167,7 @@ where
                     };
                     if *pos == *len as usize {
                         // Finished reading the frame.
-                        let frame = self.read_buffer.split_off(0).freeze();
+                        let frame = self.read_buffer.split_to(*len as usize).freeze();
                         self.read_state = ReadState::default();
                         return Ok(Async::Ready(Some(frame)));
                     }
@@ -196,24 +204,24 @@ where
 
         let mut uvi_buf = uvi::encode::u16_buffer();
         let uvi_len = uvi::encode::u16(len, &mut uvi_buf);
-        self.write_buffer.reserve(len as usize + uvi_len.len());
-        self.write_buffer.put(uvi_len);
-        self.write_buffer.put(msg);
+        // Prefixes are short enough to be stored inline by `Bytes`, without allocating.
+        self.write_buffer.push(Bytes::from(&uvi_len[..]));
+        self.write_buffer.push(msg);
 
         Ok(AsyncSink::Ready)
     }
 
     fn poll_complete(&mut self) -> Poll<(), Self::SinkError> {
         while !self.write_buffer.is_empty() {
-            let n = try_ready!(self.inner.poll_write(&self.write_buffer));
+            // Writes as many of the buffered chunks as possible at once, with vectored I/O if
+            // the inner resource supports it.
+            let n = try_ready!(self.inner.write_buf(&mut self.write_buffer));
 
             if n == 0 {
                 return Err(io::Error::new(
                     io::ErrorKind::WriteZero,
                     "Failed to write buffered frame."))
             }
-
-            let _ = self.write_buffer.split_to(n);
         }
 
         try_ready!(self.inner.poll_flush());
@@ -226,10 +234,68 @@ where
     }
 }
 
+/// Queue of chunks of data waiting to be written.
+#[derive(Debug, Default)]
+struct WriteBuffer {
+    chunks: VecDeque<Bytes>,
+    len: usize,
+}
+
+impl WriteBuffer {
+    fn push(&mut self, chunk: Bytes) {
+        if !chunk.is_empty() {
+            self.len += chunk.len();
+            self.chunks.push_back(chunk);
+        }
+    }
+
+    fn len(&self) -> usize {
+        self.len
+    }
+
+    fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+}
+
+impl Buf for WriteBuffer {
+    fn remaining(&self) -> usize {
+        self.len
+    }
+
+    fn bytes(&self) -> &[u8] {
+        self.chunks.front().map(|chunk| &chunk[..]).unwrap_or(&[])
+    }
+
+    fn advance(&mut self, mut cnt: usize) {
+        assert!(cnt <= self.len, "cannot advance past the end of the buffer");
+        self.len -= cnt;
+        while cnt > 0 {
+            let front = self.chunks.front_mut().expect("cnt <= len, hence there is a chunk; QED");
+            if cnt < front.len() {
+                let _ = front.split_to(cnt);
+                return
+            }
+            cnt -= front.len();
+            self.chunks.pop_front();
+        }
+    }
+
+    fn bytes_vec<'a>(&'a self, dst: &mut [&'a IoVec]) -> usize {
+        let mut n = 0;
+        for (chunk, dst) in self.chunks.iter().zip(dst.iter_mut()) {
+            *dst = IoVec::from_bytes(&chunk[..]).expect("chunks are never empty; QED");
+            n += 1;
+        }
+        n
+    }
+}
+
 #[cfg(test)]
 mod tests {
-    use futures::{Future, Stream};
-    use crate::length_delimited::LengthDelimited;
+    use bytes::{Buf, Bytes};
+    use futures::{Future, Sink, Stream};
+    use crate::length_delimited::{LengthDelimited, WriteBuffer};
     use std::io::{Cursor, ErrorKind};
 
     #[test]
@@ -335,5 +401,32 @@ mod tests {
             panic!()
         }
     }
-}
 
+    #[test]
+    fn write_frames() {
+        let framed = LengthDelimited::new(Cursor::new(Vec::new()));
+        let frames = vec![Bytes::from(&[9, 8, 7][..]), Bytes::new(), Bytes::from(vec![1; 200])];
+        let framed = framed.send_all(futures::stream::iter_ok::<_, std::io::Error>(frames))
+            .map(|(framed, _)| framed)
+            .wait()
+            .unwrap();
+        let written = framed.into_inner().into_inner();
+        let mut expected = vec![3, 9, 8, 7, 0, 0xc8, 0x01];
+        expected.extend_from_slice(&[1; 200]);
+        assert_eq!(written, expected);
+    }
+
+    #[test]
+    fn write_buffer_advance() {
+        let mut buffer = WriteBuffer::default();
+        buffer.push(Bytes::from(&[1, 2][..]));
+        buffer.push(Bytes::new());
+        buffer.push(Bytes::from(&[3, 4, 5][..]));
+        assert_eq!(buffer.remaining(), 5);
+        buffer.advance(3);
+        assert_eq!(buffer.bytes(), &[4, 5]);
+        buffer.advance(2);
+        assert!(buffer.is_empty());
+        assert_eq!(buffer.bytes(), &[] as &[u8]);
+    }
+}
@@ -79,12 +79,20 @@ use std::io;
 use tokio_io::{AsyncRead, AsyncWrite};
 
 pub use self::dialer_select::{dialer_select_proto, DialerSelectFuture};
-pub use self::error::ProtocolChoiceError;
+pub use self::error::{NegotiationFailure, ProtocolChoiceError};
 pub use self::listener_select::{listener_select_proto, ListenerSelectFuture};
 
 /// A stream after it has been negotiated.
 pub struct Negotiated<TInner>(pub(crate) TInner);
 
+impl<TInner> Negotiated<TInner> {
+    /// Wraps a stream whose protocol has been agreed upon without negotiation, for example
+    /// through the address that was dialed.
+    pub fn assumed(inner: TInner) -> Self {
+        Negotiated(inner)
+    }
+}
+
 impl<TInner> io::Read for Negotiated<TInner>
 where
     TInner: io::Read
@@ -28,10 +28,10 @@ use crate::protocol::{
     Listener,
     ListenerFuture,
 };
-use log::{debug, trace};
+use tracing::{debug, trace};
 use std::mem;
 use tokio_io::{AsyncRead, AsyncWrite};
-use crate::{Negotiated, ProtocolChoiceError};
+use crate::{NegotiationFailure, Negotiated, ProtocolChoiceError};
 
 /// Helps selecting a protocol amongst the ones supported.
 ///
@@ -54,6 +54,8 @@ where
     X: AsRef<[u8]>
 {
     ListenerSelectFuture {
+        span: tracing::trace_span!("multistream_select", role = "listener"),
+        requested: Vec::new(),
         inner: ListenerSelectState::AwaitListener {
             listener_fut: Listener::listen(inner),
             protocols
@@ -68,6 +70,9 @@ where
     for<'a> &'a I: IntoIterator<Item = X>,
     X: AsRef<[u8]>
 {
+    span: tracing::Span,
+    /// Protocols proposed by the remote so far.
+    requested: Vec<String>,
     inner: ListenerSelectState<R, I, X>
 }
 
@@ -103,6 +108,8 @@ where
     type Error = ProtocolChoiceError;
 
     fn poll(&mut self) -> Poll<Self::Item, Self::Error> {
+        let span = self.span.clone();
+        let _entered = span.enter();
         loop {
             match mem::replace(&mut self.inner, ListenerSelectState::Undefined) {
                 ListenerSelectState::AwaitListener { mut listener_fut, protocols } => {
@@ -153,12 +160,21 @@ where
                                 }
                             }
                             trace!("requested: {:?}, supported: {}", name, outcome.is_some());
+                            if outcome.is_none() {
+                                self.requested.push(NegotiationFailure::protocol_name(name.as_ref()));
+                            }
                             let sender = listener.send(send_back);
                             self.inner = ListenerSelectState::Outgoing { sender, protocols, outcome }
                         }
                         None => {
                             debug!("no protocol request received");
-                            return Err(ProtocolChoiceError::NoProtocolFound)
+                            let offered = (&protocols).into_iter()
+                                .map(|p| NegotiationFailure::protocol_name(p.as_ref()))
+                                .collect();
+                            return Err(ProtocolChoiceError::NoProtocolFound(NegotiationFailure {
+                                offered,
+                                remote: Some(mem::replace(&mut self.requested, Vec::new())),
+                            }))
                         }
                     }
                 }
@@ -171,6 +187,7 @@ where
                         }
                     };
                     if let Some(p) = outcome {
+                        trace!(protocol = ?p.as_ref(), "negotiated");
                         return Ok(Async::Ready((p, Negotiated(listener.into_inner()), protocols)))
                     } else {
                         let stream = listener.into_future();
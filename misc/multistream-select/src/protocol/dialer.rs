@@ -25,7 +25,7 @@ use super::*;
 use bytes::{Bytes, BytesMut};
 use crate::length_delimited::LengthDelimited;
 use crate::protocol::{Request, Response, MultistreamSelectError};
-use futures::{prelude::*, sink, Async, StartSend, try_ready};
+use futures::{prelude::*, Async, StartSend};
 use tokio_io::{AsyncRead, AsyncWrite};
 use std::marker;
 use unsigned_varint as uvi;
@@ -51,7 +51,8 @@ where
         let mut buf = BytesMut::new();
         Header::Multistream10.encode(&mut buf);
         DialerFuture {
-            inner: io.send(buf.freeze()),
+            inner: Some(io),
+            header: Some(buf.freeze()),
             _protocol_name: marker::PhantomData,
         }
     }
@@ -145,8 +146,12 @@ where
 }
 
 /// Future, returned by `Dialer::new`, which send the handshake and returns the actual `Dialer`.
+///
+/// The handshake is buffered but not flushed, so that it is sent along with the first request
+/// in a single write.
 pub struct DialerFuture<T: AsyncWrite, N: AsRef<[u8]>> {
-    inner: sink::Send<LengthDelimited<T>>,
+    inner: Option<LengthDelimited<T>>,
+    header: Option<Bytes>,
     _protocol_name: marker::PhantomData<N>,
 }
 
@@ -155,7 +160,14 @@ impl<T: AsyncWrite, N: AsRef<[u8]>> Future for DialerFuture<T, N> {
     type Error = MultistreamSelectError;
 
     fn poll(&mut self) -> Poll<Self::Item, Self::Error> {
-        let inner = try_ready!(self.inner.poll());
+        let mut inner = self.inner.take().expect("DialerFuture polled after completion");
+        if let Some(header) = self.header.take() {
+            if let AsyncSink::NotReady(header) = inner.start_send(header)? {
+                self.inner = Some(inner);
+                self.header = Some(header);
+                return Ok(Async::NotReady)
+            }
+        }
         Ok(Async::Ready(Dialer {
             inner,
             handshake_finished: false,
@@ -26,7 +26,7 @@ use bytes::{Bytes, BytesMut};
 use crate::length_delimited::LengthDelimited;
 use crate::protocol::{Request, Response, MultistreamSelectError};
 use futures::{prelude::*, sink, stream::StreamFuture};
-use log::{debug, trace};
+use tracing::{debug, trace};
 use std::{marker, mem};
 use tokio_io::{AsyncRead, AsyncWrite};
 
@@ -22,7 +22,7 @@
 
 #![cfg(test)]
 
-use crate::ProtocolChoiceError;
+use crate::{NegotiationFailure, ProtocolChoiceError};
 use crate::dialer_select::{dialer_select_proto_parallel, dialer_select_proto_serial};
 use crate::protocol::{Dialer, Request, Listener, Response};
 use crate::{dialer_select_proto, listener_select_proto};
@@ -133,7 +133,46 @@ fn no_protocol_found() {
         });
     let mut rt = Runtime::new().unwrap();
     match rt.block_on(client.join(server)) {
-        Err(ProtocolChoiceError::NoProtocolFound) => (),
+        Err(ProtocolChoiceError::NoProtocolFound(failure)) => {
+            assert_eq!(failure, NegotiationFailure {
+                offered: vec!["/proto3".to_owned(), "/proto4".to_owned()],
+                remote: None,
+            });
+        }
+        _ => panic!(),
+    }
+}
+
+#[test]
+fn no_protocol_found_parallel() {
+    let listener = TcpListener::bind(&"127.0.0.1:0".parse().unwrap()).unwrap();
+    let listener_addr = listener.local_addr().unwrap();
+
+    let server = listener
+        .incoming()
+        .into_future()
+        .map(|s| s.0.unwrap())
+        .map_err(|(e, _)| e.into())
+        .and_then(move |connec| {
+            let protos = vec![b"/proto1", b"/proto2"];
+            listener_select_proto(connec, VecRefIntoIter(protos)).map(|r| r.0)
+        });
+
+    let client = TcpStream::connect(&listener_addr)
+        .from_err()
+        .and_then(move |connec| {
+            let protos = vec![b"/proto3", b"/proto4"];
+            dialer_select_proto_parallel(connec, protos.into_iter()).map(|r| r.0)
+        });
+
+    let mut rt = Runtime::new().unwrap();
+    match rt.block_on(client.join(server)) {
+        Err(ProtocolChoiceError::NoProtocolFound(failure)) => {
+            assert_eq!(failure, NegotiationFailure {
+                offered: vec!["/proto3".to_owned(), "/proto4".to_owned()],
+                remote: Some(vec!["/proto1".to_owned(), "/proto2".to_owned()]),
+            });
+        }
         _ => panic!(),
     }
 }
@@ -0,0 +1,542 @@
+// Copyright 2019 Parity Technologies (UK) Ltd.
+//
+// Permission is hereby granted, free of charge, to any person obtaining a
+// copy of this software and associated documentation files (the "Software"),
+// to deal in the Software without restriction, including without limitation
+// the rights to use, copy, modify, merge, publish, distribute, sublicense,
+// and/or sell copies of the Software, and to permit persons to whom the
+// Software is furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS
+// OR IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING
+// FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
+// DEALINGS IN THE SOFTWARE.
+
+use crate::gateway::{BoxFuture, Gateway, GatewayProtocol, Mapping, MappingProtocol};
+use futures::prelude::*;
+use libp2p_core::{ConnectedPoint, Multiaddr, PeerId, multiaddr::Protocol};
+use libp2p_swarm::{
+    AddressSource,
+    NetworkBehaviour,
+    NetworkBehaviourAction,
+    PollParameters,
+    ProtocolsHandler,
+    protocols_handler::DummyProtocolsHandler
+};
+use log::{debug, warn};
+use std::{cmp, collections::VecDeque, io, marker::PhantomData, net::{Ipv4Addr, SocketAddrV4}, time::Duration};
+use tokio_io::{AsyncRead, AsyncWrite};
+use void::Void;
+use wasm_timer::{Delay, Instant};
+
+/// Configuration for the [`PortMapping`] behaviour.
+#[derive(Debug, Clone)]
+pub struct PortMappingConfig {
+    lease_duration: Duration,
+    discovery_timeout: Duration,
+    retry_interval: Duration,
+    description: String,
+    upnp: bool,
+    nat_pmp: bool,
+    gateway: Option<Ipv4Addr>,
+}
+
+impl Default for PortMappingConfig {
+    fn default() -> Self {
+        PortMappingConfig {
+            lease_duration: Duration::from_secs(60 * 60),
+            discovery_timeout: Duration::from_secs(10),
+            retry_interval: Duration::from_secs(5 * 60),
+            description: "rust-libp2p".to_owned(),
+            upnp: true,
+            nat_pmp: true,
+            gateway: None,
+        }
+    }
+}
+
+impl PortMappingConfig {
+    /// Sets the lifetime requested for the mappings. They are renewed when half of it has
+    /// elapsed.
+    ///
+    /// Defaults to one hour.
+    pub fn with_lease_duration(mut self, duration: Duration) -> Self {
+        self.lease_duration = duration;
+        self
+    }
+
+    /// Sets how long to wait for a gateway to answer during discovery.
+    ///
+    /// Defaults to 10 seconds.
+    pub fn with_discovery_timeout(mut self, timeout: Duration) -> Self {
+        self.discovery_timeout = timeout;
+        self
+    }
+
+    /// Sets how long to wait before retrying after the discovery of the gateway or a mapping
+    /// has failed.
+    ///
+    /// Defaults to 5 minutes.
+    pub fn with_retry_interval(mut self, interval: Duration) -> Self {
+        self.retry_interval = interval;
+        self
+    }
+
+    /// Sets the description of the mappings, shown by UPnP gateways.
+    ///
+    /// Defaults to `rust-libp2p`.
+    pub fn with_description(mut self, description: impl Into<String>) -> Self {
+        self.description = description.into();
+        self
+    }
+
+    /// Enables or disables UPnP. Enabled by default.
+    pub fn with_upnp(mut self, enabled: bool) -> Self {
+        self.upnp = enabled;
+        self
+    }
+
+    /// Enables or disables NAT-PMP. Enabled by default.
+    pub fn with_nat_pmp(mut self, enabled: bool) -> Self {
+        self.nat_pmp = enabled;
+        self
+    }
+
+    /// Sets the address of the gateway probed for NAT-PMP.
+    ///
+    /// By default, the default gateway of the system is used if it can be determined, which is
+    /// only the case on Linux.
+    pub fn with_gateway(mut self, gateway: Ipv4Addr) -> Self {
+        self.gateway = Some(gateway);
+        self
+    }
+
+    pub(crate) fn discovery_timeout(&self) -> Duration {
+        self.discovery_timeout
+    }
+
+    pub(crate) fn upnp(&self) -> bool {
+        self.upnp
+    }
+
+    pub(crate) fn nat_pmp(&self) -> bool {
+        self.nat_pmp
+    }
+
+    pub(crate) fn gateway(&self) -> Option<Ipv4Addr> {
+        self.gateway
+    }
+}
+
+/// Event produced by the [`PortMapping`] behaviour.
+#[derive(Debug)]
+pub enum PortMappingEvent {
+    /// A gateway supporting port mappings has been found.
+    GatewayFound {
+        /// The protocol spoken with the gateway.
+        protocol: GatewayProtocol,
+        /// The address of the gateway.
+        address: Ipv4Addr,
+    },
+
+    /// No gateway supporting port mappings could be found. The discovery is retried after the
+    /// retry interval.
+    GatewayNotFound {
+        /// The error of the last protocol that was tried.
+        error: io::Error,
+    },
+
+    /// The port of a listener has been mapped on the gateway, and the resulting address has
+    /// been added to the external addresses of the `Swarm`.
+    Mapped {
+        /// The listen address whose port has been mapped.
+        listen_addr: Multiaddr,
+        /// The external address the listener is reachable at.
+        external_addr: Multiaddr,
+    },
+
+    /// Mapping the port of a listener has failed. It is retried after the retry interval.
+    MappingFailed {
+        /// The listen address whose port couldn't be mapped.
+        listen_addr: Multiaddr,
+        /// The error that happened.
+        error: io::Error,
+    },
+
+    /// A mapping is no longer valid, either because the listener has been closed or because
+    /// it couldn't be renewed, and its address has been removed from the external addresses of
+    /// the `Swarm`.
+    Expired {
+        /// The listen address whose port was mapped.
+        listen_addr: Multiaddr,
+        /// The external address that is no longer valid.
+        external_addr: Multiaddr,
+    },
+}
+
+/// `NetworkBehaviour` that maps the ports of the listeners on the gateway of the local network
+/// with UPnP or NAT-PMP, so that the node is reachable from outside the network.
+///
+/// The gateway is looked for when the first listen address that can be mapped is reported.
+/// Only the TCP and UDP ports of private IPv4 addresses are mapped, to the same external port
+/// when the gateway allows it. The resulting addresses are reported to the `Swarm` as external
+/// addresses, and removed when the mappings expire.
+pub struct PortMapping<TSubstream> {
+    /// The configuration of the behaviour.
+    config: PortMappingConfig,
+
+    /// The state of the discovery of the gateway.
+    gateway: GatewayState,
+
+    /// The listen addresses that can be mapped.
+    mappings: Vec<ListenerMapping>,
+
+    /// Requests removing the mappings of closed listeners.
+    removals: Vec<BoxFuture<()>>,
+
+    /// Actions waiting to be returned by `poll`.
+    pending_actions: VecDeque<NetworkBehaviourAction<Void, PortMappingEvent>>,
+
+    /// Marker to pin the generic.
+    marker: PhantomData<TSubstream>,
+}
+
+/// State of the discovery of the gateway.
+enum GatewayState {
+    /// The discovery hasn't started yet.
+    Idle,
+    /// Looking for a gateway.
+    Discovering(BoxFuture<Gateway>),
+    /// A gateway has been found.
+    Found(Gateway),
+    /// No gateway has been found, and the discovery is retried when the timer fires.
+    NotFound(Delay),
+}
+
+/// The mapping of a listen address.
+struct ListenerMapping {
+    /// The listen address.
+    listen_addr: Multiaddr,
+    /// The protocol of the port to map.
+    protocol: MappingProtocol,
+    /// The local socket address, extracted from `listen_addr`.
+    local: SocketAddrV4,
+    /// The external address and port, if the port is currently mapped.
+    external: Option<(Multiaddr, u16)>,
+    /// What the mapping is waiting for.
+    state: MappingState,
+}
+
+/// State of the mapping of a listen address.
+enum MappingState {
+    /// Waiting for a gateway to be found.
+    WaitingForGateway,
+    /// A request creating or renewing the mapping is in progress.
+    Requesting(BoxFuture<Mapping>),
+    /// The mapping is renewed, or retried after a failure, when the timer fires.
+    Waiting(Delay),
+}
+
+impl<TSubstream> PortMapping<TSubstream> {
+    /// Creates a new `PortMapping` behaviour.
+    pub fn new(config: PortMappingConfig) -> Self {
+        PortMapping {
+            config,
+            gateway: GatewayState::Idle,
+            mappings: Vec::new(),
+            removals: Vec::new(),
+            pending_actions: VecDeque::new(),
+            marker: PhantomData,
+        }
+    }
+
+    /// Returns the gateway that has been found, with the protocol spoken with it.
+    pub fn gateway(&self) -> Option<(GatewayProtocol, Ipv4Addr)> {
+        match &self.gateway {
+            GatewayState::Found(gateway) => Some((gateway.protocol(), gateway.address())),
+            _ => None,
+        }
+    }
+
+    /// Returns the external addresses obtained by mapping ports, with the listen address they
+    /// belong to.
+    pub fn mapped_addresses(&self) -> impl Iterator<Item = (&Multiaddr, &Multiaddr)> {
+        self.mappings.iter()
+            .filter_map(|m| m.external.as_ref().map(|(addr, _)| (&m.listen_addr, addr)))
+    }
+
+    /// Polls the discovery of the gateway, and starts the mappings once it has been found.
+    fn poll_gateway(&mut self) {
+        loop {
+            match &mut self.gateway {
+                GatewayState::Idle => {
+                    if self.mappings.is_empty() {
+                        return;
+                    }
+                    self.gateway = GatewayState::Discovering(Gateway::discover(&self.config));
+                }
+                GatewayState::Discovering(discovery) => match discovery.poll() {
+                    Ok(Async::NotReady) => return,
+                    Ok(Async::Ready(gateway)) => {
+                        debug!("Found {} gateway at {}", gateway.protocol(), gateway.address());
+                        self.pending_actions.push_back(NetworkBehaviourAction::GenerateEvent(
+                            PortMappingEvent::GatewayFound {
+                                protocol: gateway.protocol(),
+                                address: gateway.address(),
+                            }
+                        ));
+                        for mapping in &mut self.mappings {
+                            mapping.start(&gateway, &self.config);
+                        }
+                        self.gateway = GatewayState::Found(gateway);
+                    }
+                    Err(error) => {
+                        debug!("No gateway found: {}", error);
+                        self.pending_actions.push_back(NetworkBehaviourAction::GenerateEvent(
+                            PortMappingEvent::GatewayNotFound { error }
+                        ));
+                        let retry = Delay::new(Instant::now() + self.config.retry_interval);
+                        self.gateway = GatewayState::NotFound(retry);
+                    }
+                },
+                GatewayState::Found(_) => return,
+                GatewayState::NotFound(retry) => match retry.poll() {
+                    Ok(Async::NotReady) => return,
+                    Ok(Async::Ready(())) | Err(_) => self.gateway = GatewayState::Idle,
+                },
+            }
+        }
+    }
+
+    /// Polls the requests of the mappings, and renews or retries them when needed.
+    fn poll_mappings(&mut self) {
+        let gateway = match &self.gateway {
+            GatewayState::Found(gateway) => gateway,
+            _ => return,
+        };
+
+        for mapping in &mut self.mappings {
+            loop {
+                match &mut mapping.state {
+                    MappingState::WaitingForGateway => mapping.start(gateway, &self.config),
+                    MappingState::Requesting(request) => match request.poll() {
+                        Ok(Async::NotReady) => break,
+                        Ok(Async::Ready(result)) => {
+                            let external_addr = external_addr(&mapping.listen_addr, result.external);
+                            let unchanged = mapping.external.as_ref()
+                                .map_or(false, |(addr, _)| *addr == external_addr);
+                            if !unchanged {
+                                mapping.expire(&mut self.pending_actions);
+                                debug!("Mapped {} to {}", mapping.listen_addr, external_addr);
+                                self.pending_actions.push_back(NetworkBehaviourAction::ReportExternalAddr {
+                                    address: external_addr.clone(),
+                                    source: AddressSource::PortMapping,
+                                });
+                                self.pending_actions.push_back(NetworkBehaviourAction::GenerateEvent(
+                                    PortMappingEvent::Mapped {
+                                        listen_addr: mapping.listen_addr.clone(),
+                                        external_addr: external_addr.clone(),
+                                    }
+                                ));
+                                mapping.external = Some((external_addr, result.external.port()));
+                            }
+                            let renew_in = cmp::max(result.lifetime / 2, Duration::from_secs(1));
+                            let renew = Delay::new(Instant::now() + renew_in);
+                            mapping.state = MappingState::Waiting(renew);
+                        }
+                        Err(error) => {
+                            warn!("Failed to map {}: {}", mapping.listen_addr, error);
+                            mapping.expire(&mut self.pending_actions);
+                            self.pending_actions.push_back(NetworkBehaviourAction::GenerateEvent(
+                                PortMappingEvent::MappingFailed {
+                                    listen_addr: mapping.listen_addr.clone(),
+                                    error,
+                                }
+                            ));
+                            let retry = Delay::new(Instant::now() + self.config.retry_interval);
+                            mapping.state = MappingState::Waiting(retry);
+                        }
+                    },
+                    MappingState::Waiting(timer) => match timer.poll() {
+                        Ok(Async::NotReady) => break,
+                        Ok(Async::Ready(())) | Err(_) => mapping.start(gateway, &self.config),
+                    },
+                }
+            }
+        }
+    }
+}
+
+impl ListenerMapping {
+    /// Sends the request creating or renewing the mapping.
+    fn start(&mut self, gateway: &Gateway, config: &PortMappingConfig) {
+        let request = gateway.map(self.protocol, self.local, config.lease_duration, &config.description);
+        self.state = MappingState::Requesting(request);
+    }
+
+    /// Removes the external address of the mapping, if any.
+    fn expire(&mut self, actions: &mut VecDeque<NetworkBehaviourAction<Void, PortMappingEvent>>) {
+        if let Some((external_addr, _)) = self.external.take() {
+            actions.push_back(NetworkBehaviourAction::RemoveExternalAddr {
+                address: external_addr.clone(),
+            });
+            actions.push_back(NetworkBehaviourAction::GenerateEvent(PortMappingEvent::Expired {
+                listen_addr: self.listen_addr.clone(),
+                external_addr,
+            }));
+        }
+    }
+}
+
+/// Extracts the protocol and the socket address to map from a listen address.
+///
+/// Only the private IPv4 addresses followed with a TCP or UDP port are mapped.
+fn mappable(listen_addr: &Multiaddr) -> Option<(MappingProtocol, SocketAddrV4)> {
+    let mut iter = listen_addr.iter();
+    let ip = match iter.next()? {
+        Protocol::Ip4(ip) if ip.is_private() => ip,
+        _ => return None,
+    };
+    match iter.next()? {
+        Protocol::Tcp(port) => Some((MappingProtocol::Tcp, SocketAddrV4::new(ip, port))),
+        Protocol::Udp(port) => Some((MappingProtocol::Udp, SocketAddrV4::new(ip, port))),
+        _ => None,
+    }
+}
+
+/// Builds the external address corresponding to `listen_addr` once mapped to `external`.
+fn external_addr(listen_addr: &Multiaddr, external: SocketAddrV4) -> Multiaddr {
+    listen_addr.iter()
+        .enumerate()
+        .map(|(n, protocol)| match (n, protocol) {
+            (0, _) => Protocol::Ip4(*external.ip()),
+            (1, Protocol::Tcp(_)) => Protocol::Tcp(external.port()),
+            (1, Protocol::Udp(_)) => Protocol::Udp(external.port()),
+            (_, protocol) => protocol,
+        })
+        .collect()
+}
+
+impl<TSubstream> NetworkBehaviour for PortMapping<TSubstream>
+where
+    TSubstream: AsyncRead + AsyncWrite,
+{
+    type ProtocolsHandler = DummyProtocolsHandler<TSubstream>;
+    type OutEvent = PortMappingEvent;
+
+    fn new_handler(&mut self) -> Self::ProtocolsHandler {
+        DummyProtocolsHandler::default()
+    }
+
+    fn addresses_of_peer(&mut self, _: &PeerId) -> Vec<Multiaddr> {
+        Vec::new()
+    }
+
+    fn inject_connected(&mut self, _: PeerId, _: ConnectedPoint) {}
+
+    fn inject_disconnected(&mut self, _: &PeerId, _: ConnectedPoint) {}
+
+    fn inject_node_event(
+        &mut self,
+        _: PeerId,
+        ev: <Self::ProtocolsHandler as ProtocolsHandler>::OutEvent,
+    ) {
+        void::unreachable(ev)
+    }
+
+    fn inject_new_listen_addr(&mut self, addr: &Multiaddr) {
+        let (protocol, local) = match mappable(addr) {
+            Some(mappable) => mappable,
+            None => return,
+        };
+        if self.mappings.iter().any(|m| m.listen_addr == *addr) {
+            return;
+        }
+        let mut mapping = ListenerMapping {
+            listen_addr: addr.clone(),
+            protocol,
+            local,
+            external: None,
+            state: MappingState::WaitingForGateway,
+        };
+        if let GatewayState::Found(gateway) = &self.gateway {
+            mapping.start(gateway, &self.config);
+        }
+        self.mappings.push(mapping);
+    }
+
+    fn inject_expired_listen_addr(&mut self, addr: &Multiaddr) {
+        let pos = match self.mappings.iter().position(|m| m.listen_addr == *addr) {
+            Some(pos) => pos,
+            None => return,
+        };
+        let mut mapping = self.mappings.remove(pos);
+        if let (Some((_, external_port)), GatewayState::Found(gateway)) = (&mapping.external, &self.gateway) {
+            self.removals.push(gateway.unmap(mapping.protocol, mapping.local, *external_port));
+        }
+        mapping.expire(&mut self.pending_actions);
+    }
+
+    fn poll(
+        &mut self,
+        _: &mut impl PollParameters,
+    ) -> Async<
+        NetworkBehaviourAction<
+            <Self::ProtocolsHandler as ProtocolsHandler>::InEvent,
+            Self::OutEvent,
+        >,
+    > {
+        self.poll_gateway();
+        self.poll_mappings();
+
+        let mut n = 0;
+        while n < self.removals.len() {
+            match self.removals[n].poll() {
+                Ok(Async::NotReady) => n += 1,
+                Ok(Async::Ready(())) => {
+                    self.removals.swap_remove(n);
+                }
+                Err(err) => {
+                    debug!("Failed to remove a port mapping: {}", err);
+                    self.removals.swap_remove(n);
+                }
+            }
+        }
+
+        if let Some(action) = self.pending_actions.pop_front() {
+            return Async::Ready(action);
+        }
+
+        Async::NotReady
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn mappable_addresses() {
+        let addr: Multiaddr = "/ip4/192.168.1.5/tcp/4001".parse().unwrap();
+        assert_eq!(mappable(&addr), Some((MappingProtocol::Tcp, "192.168.1.5:4001".parse().unwrap())));
+        let addr: Multiaddr = "/ip4/10.0.0.2/udp/4001/quic".parse().unwrap();
+        assert_eq!(mappable(&addr), Some((MappingProtocol::Udp, "10.0.0.2:4001".parse().unwrap())));
+
+        for addr in &["/ip4/127.0.0.1/tcp/4001", "/ip4/203.0.113.7/tcp/4001", "/ip6/fe80::1/tcp/4001"] {
+            assert_eq!(mappable(&addr.parse().unwrap()), None);
+        }
+    }
+
+    #[test]
+    fn builds_external_addresses() {
+        let listen_addr: Multiaddr = "/ip4/192.168.1.5/tcp/4001/ws".parse().unwrap();
+        let external = external_addr(&listen_addr, "203.0.113.7:4002".parse().unwrap());
+        assert_eq!(external, "/ip4/203.0.113.7/tcp/4002/ws".parse().unwrap());
+    }
+}
@@ -0,0 +1,252 @@
+// Copyright 2019 Parity Technologies (UK) Ltd.
+//
+// Permission is hereby granted, free of charge, to any person obtaining a
+// copy of this software and associated documentation files (the "Software"),
+// to deal in the Software without restriction, including without limitation
+// the rights to use, copy, modify, merge, publish, distribute, sublicense,
+// and/or sell copies of the Software, and to permit persons to whom the
+// Software is furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS
+// OR IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING
+// FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
+// DEALINGS IN THE SOFTWARE.
+
+//! Discovery of the gateway, and mapping of ports with either UPnP or NAT-PMP.
+
+use crate::{behaviour::PortMappingConfig, natpmp};
+use futures::{future, prelude::*};
+use log::debug;
+use std::{fmt, io, net::{Ipv4Addr, SocketAddrV4}, sync::Arc, time::Duration};
+
+/// Future returned by the operations on a gateway.
+pub type BoxFuture<T> = Box<dyn Future<Item = T, Error = io::Error> + Send>;
+
+/// Transport protocol of a mapped port.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum MappingProtocol {
+    Tcp,
+    Udp,
+}
+
+/// Protocol spoken with a gateway.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum GatewayProtocol {
+    /// UPnP Internet Gateway Device.
+    Upnp,
+    /// NAT Port Mapping Protocol.
+    NatPmp,
+}
+
+impl fmt::Display for GatewayProtocol {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            GatewayProtocol::Upnp => f.write_str("UPnP"),
+            GatewayProtocol::NatPmp => f.write_str("NAT-PMP"),
+        }
+    }
+}
+
+/// A port mapped on the gateway.
+#[derive(Debug, Clone)]
+pub struct Mapping {
+    /// The external address the port is reachable at.
+    pub external: SocketAddrV4,
+    /// How long the mapping lasts, unless renewed.
+    pub lifetime: Duration,
+}
+
+/// A gateway on which ports can be mapped.
+#[derive(Clone)]
+pub enum Gateway {
+    Upnp(Arc<igd::aio::Gateway>),
+    NatPmp(Ipv4Addr),
+}
+
+impl Gateway {
+    /// Looks for a gateway with the protocols enabled in the configuration, UPnP first.
+    pub fn discover(config: &PortMappingConfig) -> BoxFuture<Gateway> {
+        let timeout = config.discovery_timeout();
+        let nat_pmp_gateway = if config.nat_pmp() {
+            config.gateway().or_else(default_gateway)
+        } else {
+            None
+        };
+        let nat_pmp = move |err: io::Error| -> BoxFuture<Gateway> {
+            let gateway = match nat_pmp_gateway {
+                Some(gateway) => gateway,
+                None => return Box::new(future::err(err)),
+            };
+            debug!("Probing {} for NAT-PMP", gateway);
+            match natpmp::Request::new(gateway, natpmp::external_address_request(), timeout) {
+                Ok(request) => Box::new(request.and_then(move |response| {
+                    natpmp::parse_external_address(&response)?;
+                    Ok(Gateway::NatPmp(gateway))
+                })),
+                Err(err) => Box::new(future::err(err)),
+            }
+        };
+
+        if config.upnp() {
+            let options = igd::SearchOptions { timeout: Some(timeout), ..Default::default() };
+            Box::new(igd::aio::search_gateway(options)
+                .map(|gateway| Gateway::Upnp(Arc::new(gateway)))
+                .map_err(|err| io::Error::new(io::ErrorKind::Other, err.to_string()))
+                .or_else(nat_pmp))
+        } else {
+            nat_pmp(io::Error::new(io::ErrorKind::NotFound, "no gateway to probe"))
+        }
+    }
+
+    /// Returns the protocol spoken with the gateway.
+    pub fn protocol(&self) -> GatewayProtocol {
+        match self {
+            Gateway::Upnp(_) => GatewayProtocol::Upnp,
+            Gateway::NatPmp(_) => GatewayProtocol::NatPmp,
+        }
+    }
+
+    /// Returns the address of the gateway.
+    pub fn address(&self) -> Ipv4Addr {
+        match self {
+            Gateway::Upnp(gateway) => *gateway.addr.ip(),
+            Gateway::NatPmp(gateway) => *gateway,
+        }
+    }
+
+    /// Maps the port of `local` to the same external port, for `lifetime`.
+    pub fn map(&self, protocol: MappingProtocol, local: SocketAddrV4, lifetime: Duration, description: &str)
+        -> BoxFuture<Mapping>
+    {
+        match self {
+            Gateway::Upnp(gateway) => {
+                let gateway = gateway.clone();
+                let description = description.to_owned();
+                let igd_protocol = match protocol {
+                    MappingProtocol::Tcp => igd::PortMappingProtocol::TCP,
+                    MappingProtocol::Udp => igd::PortMappingProtocol::UDP,
+                };
+                let lease = lifetime.as_secs().min(u64::from(u32::max_value())) as u32;
+                Box::new(gateway.get_external_ip()
+                    .map_err(|err| io::Error::new(io::ErrorKind::Other, err.to_string()))
+                    .and_then(move |ip| {
+                        gateway.add_port(igd_protocol, local.port(), local, lease, &description)
+                            .map_err(|err| io::Error::new(io::ErrorKind::Other, err.to_string()))
+                            .map(move |()| Mapping {
+                                external: SocketAddrV4::new(ip, local.port()),
+                                lifetime,
+                            })
+                    }))
+            }
+            Gateway::NatPmp(gateway) => {
+                let gateway = *gateway;
+                let timeout = natpmp_timeout();
+                let request = natpmp::Request::new(gateway, natpmp::external_address_request(), timeout);
+                let request = match request {
+                    Ok(request) => request,
+                    Err(err) => return Box::new(future::err(err)),
+                };
+                Box::new(request
+                    .and_then(|response| natpmp::parse_external_address(&response))
+                    .and_then(move |ip| {
+                        let request = natpmp::mapping_request(protocol, local.port(), local.port(), lifetime);
+                        natpmp::Request::new(gateway, request, timeout)
+                            .into_future()
+                            .flatten()
+                            .and_then(move |response| natpmp::parse_mapping(&response, protocol))
+                            .map(move |(port, lifetime)| Mapping {
+                                external: SocketAddrV4::new(ip, port),
+                                lifetime,
+                            })
+                    }))
+            }
+        }
+    }
+
+    /// Removes the mapping of the port of `local` to `external_port`.
+    pub fn unmap(&self, protocol: MappingProtocol, local: SocketAddrV4, external_port: u16) -> BoxFuture<()> {
+        match self {
+            Gateway::Upnp(gateway) => {
+                let igd_protocol = match protocol {
+                    MappingProtocol::Tcp => igd::PortMappingProtocol::TCP,
+                    MappingProtocol::Udp => igd::PortMappingProtocol::UDP,
+                };
+                Box::new(gateway.remove_port(igd_protocol, external_port)
+                    .map_err(|err| io::Error::new(io::ErrorKind::Other, err.to_string())))
+            }
+            Gateway::NatPmp(gateway) => {
+                let request = natpmp::mapping_request(protocol, local.port(), 0, Duration::from_secs(0));
+                match natpmp::Request::new(*gateway, request, natpmp_timeout()) {
+                    Ok(request) => Box::new(request.map(|_| ())),
+                    Err(err) => Box::new(future::err(err)),
+                }
+            }
+        }
+    }
+}
+
+impl fmt::Debug for Gateway {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Gateway")
+            .field("protocol", &self.protocol())
+            .field("address", &self.address())
+            .finish()
+    }
+}
+
+/// Timeout of the NAT-PMP requests once the gateway has been found.
+fn natpmp_timeout() -> Duration {
+    Duration::from_secs(8)
+}
+
+/// Returns the default IPv4 gateway of the system, if it can be determined.
+#[cfg(target_os = "linux")]
+fn default_gateway() -> Option<Ipv4Addr> {
+    let routes = std::fs::read_to_string("/proc/net/route").ok()?;
+    parse_route_table(&routes)
+}
+
+/// Returns the default IPv4 gateway of the system, if it can be determined.
+#[cfg(not(target_os = "linux"))]
+fn default_gateway() -> Option<Ipv4Addr> {
+    None
+}
+
+/// Finds the default gateway in the content of `/proc/net/route`.
+///
+/// The addresses in this file are hexadecimal representations of the addresses in network
+/// order, read as native integers.
+#[cfg_attr(not(target_os = "linux"), allow(dead_code))]
+fn parse_route_table(routes: &str) -> Option<Ipv4Addr> {
+    routes.lines().skip(1).find_map(|line| {
+        let mut fields = line.split_whitespace().skip(1);
+        let destination = fields.next()?;
+        let gateway = u32::from_str_radix(fields.next()?, 16).ok()?;
+        if destination != "00000000" || gateway == 0 {
+            return None;
+        }
+        Some(Ipv4Addr::from(gateway.to_ne_bytes()))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_route_table() {
+        let routes = "Iface\tDestination\tGateway \tFlags\tRefCnt\tUse\tMetric\tMask\t\tMTU\tWindow\tIRTT\n\
+            eth0\t0001A8C0\t00000000\t0001\t0\t0\t0\t00FFFFFF\t0\t0\t0\n\
+            eth0\t00000000\t0101A8C0\t0003\t0\t0\t0\t00000000\t0\t0\t0\n";
+        if cfg!(target_endian = "little") {
+            assert_eq!(parse_route_table(routes), Some(Ipv4Addr::new(192, 168, 1, 1)));
+        }
+        assert_eq!(parse_route_table("Iface\tDestination\tGateway\n"), None);
+    }
+}
@@ -0,0 +1,39 @@
+// Copyright 2019 Parity Technologies (UK) Ltd.
+//
+// Permission is hereby granted, free of charge, to any person obtaining a
+// copy of this software and associated documentation files (the "Software"),
+// to deal in the Software without restriction, including without limitation
+// the rights to use, copy, modify, merge, publish, distribute, sublicense,
+// and/or sell copies of the Software, and to permit persons to whom the
+// Software is furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS
+// OR IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING
+// FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
+// DEALINGS IN THE SOFTWARE.
+
+//! Port mapping on the gateway of the local network.
+//!
+//! Most nodes on home networks are behind a NAT gateway, and can't be reached by other nodes
+//! even though they listen on a port. Many gateways allow mapping one of their external ports
+//! to a port of the local network, through either UPnP (Internet Gateway Device) or NAT-PMP.
+//!
+//! # Usage
+//!
+//! This crate provides the [`PortMapping`] struct, which implements the `NetworkBehaviour`
+//! trait. It looks for a gateway once the `Swarm` listens on a private IPv4 address, maps the
+//! TCP and UDP ports of the listeners, renews the mappings before they expire, and reports the
+//! resulting addresses to the `Swarm` as external addresses.
+
+mod behaviour;
+mod gateway;
+mod natpmp;
+
+pub use behaviour::{PortMapping, PortMappingConfig, PortMappingEvent};
+pub use gateway::GatewayProtocol;
@@ -0,0 +1,195 @@
+// Copyright 2019 Parity Technologies (UK) Ltd.
+//
+// Permission is hereby granted, free of charge, to any person obtaining a
+// copy of this software and associated documentation files (the "Software"),
+// to deal in the Software without restriction, including without limitation
+// the rights to use, copy, modify, merge, publish, distribute, sublicense,
+// and/or sell copies of the Software, and to permit persons to whom the
+// Software is furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS
+// OR IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING
+// FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
+// DEALINGS IN THE SOFTWARE.
+
+//! Client side of NAT-PMP, as defined in [RFC 6886](https://tools.ietf.org/html/rfc6886).
+
+use crate::gateway::MappingProtocol;
+use futures::{prelude::*, try_ready};
+use std::{io, net::{Ipv4Addr, SocketAddr, SocketAddrV4}, time::Duration};
+use tokio_udp::UdpSocket;
+use wasm_timer::{Delay, Instant};
+
+/// Port on which the gateway listens for NAT-PMP requests.
+pub const NAT_PMP_PORT: u16 = 5351;
+
+/// Version of the protocol, in the first byte of every packet.
+const VERSION: u8 = 0;
+/// Opcode of the requests for the external address of the gateway.
+const OP_EXTERNAL_ADDRESS: u8 = 0;
+/// Added to the opcode of a request to obtain the opcode of its response.
+const OP_RESPONSE: u8 = 128;
+
+/// Delay before the first retransmission of a request. It is doubled after each retransmission.
+const INITIAL_RETRANSMIT: Duration = Duration::from_millis(250);
+
+/// Builds a request for the external address of the gateway.
+pub fn external_address_request() -> Vec<u8> {
+    vec![VERSION, OP_EXTERNAL_ADDRESS]
+}
+
+/// Builds a request mapping `internal_port` for `lifetime`. A lifetime of zero removes the
+/// mapping.
+pub fn mapping_request(protocol: MappingProtocol, internal_port: u16, external_port: u16, lifetime: Duration)
+    -> Vec<u8>
+{
+    let lifetime = lifetime.as_secs().min(u64::from(u32::max_value())) as u32;
+    let mut request = vec![VERSION, mapping_opcode(protocol), 0, 0];
+    request.extend_from_slice(&internal_port.to_be_bytes());
+    request.extend_from_slice(&external_port.to_be_bytes());
+    request.extend_from_slice(&lifetime.to_be_bytes());
+    request
+}
+
+/// Parses the response to a request built with `external_address_request`.
+pub fn parse_external_address(response: &[u8]) -> Result<Ipv4Addr, io::Error> {
+    check_response(response, OP_EXTERNAL_ADDRESS, 12)?;
+    Ok(Ipv4Addr::new(response[8], response[9], response[10], response[11]))
+}
+
+/// Parses the response to a request built with `mapping_request`, returning the external port
+/// and the lifetime of the mapping.
+pub fn parse_mapping(response: &[u8], protocol: MappingProtocol) -> Result<(u16, Duration), io::Error> {
+    check_response(response, mapping_opcode(protocol), 16)?;
+    let external_port = u16::from_be_bytes([response[10], response[11]]);
+    let lifetime = u32::from_be_bytes([response[12], response[13], response[14], response[15]]);
+    Ok((external_port, Duration::from_secs(u64::from(lifetime))))
+}
+
+fn mapping_opcode(protocol: MappingProtocol) -> u8 {
+    match protocol {
+        MappingProtocol::Udp => 1,
+        MappingProtocol::Tcp => 2,
+    }
+}
+
+/// Checks the header and the result code of a response.
+fn check_response(response: &[u8], opcode: u8, len: usize) -> Result<(), io::Error> {
+    if response.len() < len || response[0] != VERSION || response[1] != OP_RESPONSE + opcode {
+        return Err(io::Error::new(io::ErrorKind::InvalidData, "invalid NAT-PMP response"));
+    }
+    match u16::from_be_bytes([response[2], response[3]]) {
+        0 => Ok(()),
+        code => Err(io::Error::new(io::ErrorKind::Other, format!("NAT-PMP request failed with result code {}", code))),
+    }
+}
+
+/// Sends a request to the gateway and resolves to its response.
+///
+/// The request is retransmitted with an exponential backoff until a response is received or
+/// the timeout expires.
+pub struct Request {
+    socket: UdpSocket,
+    gateway: SocketAddr,
+    request: Vec<u8>,
+    /// Whether the request must be (re)transmitted.
+    send: bool,
+    /// Delay before the next retransmission.
+    interval: Duration,
+    /// Fires when the request must be retransmitted.
+    retransmit: Delay,
+    /// Fires when the request times out.
+    deadline: Delay,
+    buffer: [u8; 16],
+}
+
+impl Request {
+    /// Creates a new request to the NAT-PMP port of `gateway`.
+    pub fn new(gateway: Ipv4Addr, request: Vec<u8>, timeout: Duration) -> Result<Self, io::Error> {
+        let socket = UdpSocket::bind(&From::from(([0, 0, 0, 0], 0)))?;
+        let now = Instant::now();
+        Ok(Request {
+            socket,
+            gateway: SocketAddrV4::new(gateway, NAT_PMP_PORT).into(),
+            request,
+            send: true,
+            interval: INITIAL_RETRANSMIT,
+            retransmit: Delay::new(now + INITIAL_RETRANSMIT),
+            deadline: Delay::new(now + timeout),
+            buffer: [0; 16],
+        })
+    }
+}
+
+impl Future for Request {
+    type Item = Vec<u8>;
+    type Error = io::Error;
+
+    fn poll(&mut self) -> Poll<Self::Item, Self::Error> {
+        loop {
+            if self.send {
+                try_ready!(self.socket.poll_send_to(&self.request, &self.gateway));
+                self.send = false;
+            }
+
+            if let Async::Ready((len, from)) = self.socket.poll_recv_from(&mut self.buffer)? {
+                // Ignore the packets that don't answer our request.
+                if from == self.gateway && len >= 2 && self.buffer[1] == OP_RESPONSE + self.request[1] {
+                    return Ok(Async::Ready(self.buffer[.. len].to_vec()));
+                }
+                continue;
+            }
+
+            match self.deadline.poll() {
+                Ok(Async::NotReady) => {}
+                Ok(Async::Ready(())) | Err(_) => {
+                    return Err(io::Error::new(io::ErrorKind::TimedOut, "NAT-PMP request timed out"));
+                }
+            }
+
+            match self.retransmit.poll() {
+                Ok(Async::NotReady) => return Ok(Async::NotReady),
+                Ok(Async::Ready(())) | Err(_) => {
+                    self.interval *= 2;
+                    self.retransmit.reset(Instant::now() + self.interval);
+                    self.send = true;
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn encodes_mapping_request() {
+        let request = mapping_request(MappingProtocol::Tcp, 4001, 4001, Duration::from_secs(3600));
+        assert_eq!(request, vec![0, 2, 0, 0, 0x0f, 0xa1, 0x0f, 0xa1, 0, 0, 0x0e, 0x10]);
+        assert_eq!(external_address_request(), vec![0, 0]);
+    }
+
+    #[test]
+    fn parses_responses() {
+        let response = [0, 128, 0, 0, 0, 0, 0, 1, 203, 0, 113, 7];
+        assert_eq!(parse_external_address(&response).unwrap(), Ipv4Addr::new(203, 0, 113, 7));
+
+        let response = [0, 129, 0, 0, 0, 0, 0, 1, 0x0f, 0xa1, 0x0f, 0xa2, 0, 0, 0x0e, 0x10];
+        let (port, lifetime) = parse_mapping(&response, MappingProtocol::Udp).unwrap();
+        assert_eq!(port, 4002);
+        assert_eq!(lifetime, Duration::from_secs(3600));
+
+        // Wrong opcode.
+        assert!(parse_mapping(&response, MappingProtocol::Tcp).is_err());
+        // Result code "not authorized".
+        let response = [0, 128, 0, 2, 0, 0, 0, 1, 0, 0, 0, 0];
+        assert!(parse_external_address(&response).is_err());
+    }
+}
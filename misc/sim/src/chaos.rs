@@ -0,0 +1,585 @@
+// Copyright 2019 Parity Technologies (UK) Ltd.
+//
+// Permission is hereby granted, free of charge, to any person obtaining a
+// copy of this software and associated documentation files (the "Software"),
+// to deal in the Software without restriction, including without limitation
+// the rights to use, copy, modify, merge, publish, distribute, sublicense,
+// and/or sell copies of the Software, and to permit persons to whom the
+// Software is furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS
+// OR IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING
+// FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
+// DEALINGS IN THE SOFTWARE.
+//! Fault injection on the connections and substreams of a transport.
+
+use futures::{prelude::*, try_ready};
+use libp2p_core::{Multiaddr, StreamMuxer, transport::{ListenerEvent, Transport, TransportError}};
+use parking_lot::Mutex;
+use rand::{Rng, SeedableRng, rngs::StdRng};
+use std::{cmp, collections::VecDeque, error, fmt, io, sync::Arc, time::Duration};
+use tokio_io::{AsyncRead, AsyncWrite};
+use wasm_timer::{Delay, Instant};
+
+/// Faults to inject, as probabilities.
+///
+/// The probabilities of [`FaultProfile::reset`], [`FaultProfile::truncate`] and
+/// [`FaultProfile::delay`] apply to every successful read, and their sum shouldn't exceed 1.
+#[derive(Debug, Clone)]
+pub struct FaultProfile {
+    dial_failure: f64,
+    establishment_delay: f64,
+    reset: f64,
+    truncate: f64,
+    delay: f64,
+    max_delay: Duration,
+    reorder: f64,
+}
+
+impl FaultProfile {
+    /// Creates a profile that doesn't inject any fault.
+    pub fn new() -> Self {
+        FaultProfile {
+            dial_failure: 0.0,
+            establishment_delay: 0.0,
+            reset: 0.0,
+            truncate: 0.0,
+            delay: 0.0,
+            max_delay: Duration::from_millis(100),
+            reorder: 0.0,
+        }
+    }
+
+    /// Makes the establishment of connections fail, dialed or incoming.
+    pub fn dial_failure(mut self, probability: f64) -> Self {
+        self.dial_failure = probability;
+        self
+    }
+
+    /// Delays the establishment of connections by up to the maximum delay.
+    pub fn establishment_delay(mut self, probability: f64) -> Self {
+        self.establishment_delay = probability;
+        self
+    }
+
+    /// Resets the stream on a read or a write. The stream then fails with `ConnectionReset`
+    /// forever, and the data of the read is lost.
+    pub fn reset(mut self, probability: f64) -> Self {
+        self.reset = probability;
+        self
+    }
+
+    /// Ends the stream on a read, as if the remote had closed it. The data of the read is lost.
+    pub fn truncate(mut self, probability: f64) -> Self {
+        self.truncate = probability;
+        self
+    }
+
+    /// Holds the data of a read back for up to the maximum delay.
+    pub fn delay(mut self, probability: f64) -> Self {
+        self.delay = probability;
+        self
+    }
+
+    /// Sets the maximum of the injected delays. Defaults to 100ms.
+    pub fn max_delay(mut self, max_delay: Duration) -> Self {
+        self.max_delay = max_delay;
+        self
+    }
+
+    /// Holds an inbound substream back, to deliver it after the next one.
+    pub fn reorder(mut self, probability: f64) -> Self {
+        self.reorder = probability;
+        self
+    }
+}
+
+impl Default for FaultProfile {
+    fn default() -> Self {
+        FaultProfile::new()
+    }
+}
+
+/// Source of the faults, shared by the [`ChaosTransport`] and [`ChaosMuxer`]s of a node.
+///
+/// The faults derive from the seed, so that a failing run can be replayed as long as the
+/// operations happen in the same order. Clones share the random number generator.
+#[derive(Clone)]
+pub struct Chaos {
+    profile: Arc<FaultProfile>,
+    rng: Arc<Mutex<StdRng>>,
+}
+
+/// Fault injected on a read.
+enum Fault {
+    None,
+    Reset,
+    Truncate,
+    Delay(Duration),
+}
+
+impl Chaos {
+    /// Creates a source of faults following `profile`.
+    pub fn new(profile: FaultProfile, seed: u64) -> Self {
+        Chaos {
+            profile: Arc::new(profile),
+            rng: Arc::new(Mutex::new(StdRng::seed_from_u64(seed))),
+        }
+    }
+
+    /// Returns the profile of the injected faults.
+    pub fn profile(&self) -> &FaultProfile {
+        &self.profile
+    }
+
+    fn happens(&self, probability: f64) -> bool {
+        probability > 0.0 && self.rng.lock().gen::<f64>() < probability
+    }
+
+    fn random_delay(&self) -> Duration {
+        let max = self.profile.max_delay.as_millis() as u64;
+        Duration::from_millis(self.rng.lock().gen_range(0, max + 1))
+    }
+
+    fn read_fault(&self) -> Fault {
+        let profile = &self.profile;
+        if profile.reset + profile.truncate + profile.delay <= 0.0 {
+            return Fault::None;
+        }
+        let roll = self.rng.lock().gen::<f64>();
+        if roll < profile.reset {
+            Fault::Reset
+        } else if roll < profile.reset + profile.truncate {
+            Fault::Truncate
+        } else if roll < profile.reset + profile.truncate + profile.delay {
+            Fault::Delay(self.random_delay())
+        } else {
+            Fault::None
+        }
+    }
+}
+
+impl fmt::Debug for Chaos {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("Chaos").field(&self.profile).finish()
+    }
+}
+
+fn reset_error() -> io::Error {
+    io::Error::new(io::ErrorKind::ConnectionReset, "injected reset")
+}
+
+/// Faults of a stream or substream.
+struct StreamFaults {
+    chaos: Chaos,
+    reset: bool,
+    truncated: bool,
+    /// Data of a read being delayed.
+    delayed: Option<(Delay, Vec<u8>)>,
+}
+
+impl StreamFaults {
+    fn new(chaos: Chaos) -> Self {
+        StreamFaults { chaos, reset: false, truncated: false, delayed: None }
+    }
+
+    fn read(&mut self, buf: &mut [u8], read: impl FnOnce(&mut [u8]) -> Poll<usize, io::Error>)
+        -> Poll<usize, io::Error>
+    {
+        if self.reset {
+            return Err(reset_error());
+        }
+        if self.truncated {
+            return Ok(Async::Ready(0));
+        }
+        if self.delayed.is_none() {
+            let n = try_ready!(read(buf));
+            if n == 0 {
+                return Ok(Async::Ready(0));
+            }
+            match self.chaos.read_fault() {
+                Fault::None => return Ok(Async::Ready(n)),
+                Fault::Reset => {
+                    self.reset = true;
+                    return Err(reset_error());
+                }
+                Fault::Truncate => {
+                    self.truncated = true;
+                    return Ok(Async::Ready(0));
+                }
+                Fault::Delay(delay) => {
+                    self.delayed = Some((Delay::new(Instant::now() + delay), buf[.. n].to_vec()));
+                }
+            }
+        }
+
+        let (delay, data) = self.delayed.as_mut().expect("set above if it wasn't");
+        // A failing timer only shortens the delay.
+        if let Ok(Async::NotReady) = delay.poll() {
+            return Ok(Async::NotReady);
+        }
+        let n = cmp::min(buf.len(), data.len());
+        buf[.. n].copy_from_slice(&data[.. n]);
+        data.drain(.. n);
+        if data.is_empty() {
+            self.delayed = None;
+        }
+        Ok(Async::Ready(n))
+    }
+
+    fn write(&mut self, write: impl FnOnce() -> Poll<usize, io::Error>) -> Poll<usize, io::Error> {
+        if self.reset || self.chaos.happens(self.chaos.profile.reset) {
+            self.reset = true;
+            return Err(reset_error());
+        }
+        write()
+    }
+}
+
+fn into_io<T>(poll: Poll<T, io::Error>) -> io::Result<T> {
+    match poll? {
+        Async::Ready(value) => Ok(value),
+        Async::NotReady => Err(io::ErrorKind::WouldBlock.into()),
+    }
+}
+
+/// Transport wrapping the connections of another transport in [`ChaosStream`]s, and failing
+/// or delaying their establishment according to a [`Chaos`].
+#[derive(Debug, Clone)]
+pub struct ChaosTransport<TInner> {
+    inner: TInner,
+    chaos: Chaos,
+}
+
+impl<TInner> ChaosTransport<TInner> {
+    /// Wraps `inner`.
+    pub fn new(inner: TInner, chaos: Chaos) -> Self {
+        ChaosTransport { inner, chaos }
+    }
+}
+
+impl<TInner> Transport for ChaosTransport<TInner>
+where
+    TInner: Transport,
+    TInner::Output: AsyncRead + AsyncWrite,
+{
+    type Output = ChaosStream<TInner::Output>;
+    type Error = ChaosError<TInner::Error>;
+    type Listener = ChaosListener<TInner::Listener>;
+    type ListenerUpgrade = ChaosUpgrade<TInner::ListenerUpgrade>;
+    type Dial = ChaosUpgrade<TInner::Dial>;
+
+    fn listen_on(self, addr: Multiaddr) -> Result<Self::Listener, TransportError<Self::Error>> {
+        let inner = self.inner.listen_on(addr).map_err(|err| err.map(ChaosError::Transport))?;
+        Ok(ChaosListener { inner, chaos: self.chaos })
+    }
+
+    fn dial(self, addr: Multiaddr) -> Result<Self::Dial, TransportError<Self::Error>> {
+        let inner = self.inner.dial(addr).map_err(|err| err.map(ChaosError::Transport))?;
+        Ok(ChaosUpgrade::new(inner, self.chaos))
+    }
+}
+
+/// Listener of a [`ChaosTransport`].
+pub struct ChaosListener<TInner> {
+    inner: TInner,
+    chaos: Chaos,
+}
+
+impl<TInner, TUpgrade> Stream for ChaosListener<TInner>
+where
+    TInner: Stream<Item = ListenerEvent<TUpgrade>>,
+{
+    type Item = ListenerEvent<ChaosUpgrade<TUpgrade>>;
+    type Error = ChaosError<TInner::Error>;
+
+    fn poll(&mut self) -> Poll<Option<Self::Item>, Self::Error> {
+        let event = try_ready!(self.inner.poll().map_err(ChaosError::Transport));
+        let chaos = &self.chaos;
+        Ok(Async::Ready(event.map(|event| event.map(|upgrade| ChaosUpgrade::new(upgrade, chaos.clone())))))
+    }
+}
+
+/// Establishment of a connection of a [`ChaosTransport`].
+pub struct ChaosUpgrade<TInner> {
+    inner: TInner,
+    fail: bool,
+    delay: Option<Delay>,
+    chaos: Chaos,
+}
+
+impl<TInner> ChaosUpgrade<TInner> {
+    fn new(inner: TInner, chaos: Chaos) -> Self {
+        let fail = chaos.happens(chaos.profile.dial_failure);
+        let delay = if chaos.happens(chaos.profile.establishment_delay) {
+            Some(Delay::new(Instant::now() + chaos.random_delay()))
+        } else {
+            None
+        };
+        ChaosUpgrade { inner, fail, delay, chaos }
+    }
+}
+
+impl<TInner> Future for ChaosUpgrade<TInner>
+where
+    TInner: Future,
+{
+    type Item = ChaosStream<TInner::Item>;
+    type Error = ChaosError<TInner::Error>;
+
+    fn poll(&mut self) -> Poll<Self::Item, Self::Error> {
+        if let Some(delay) = &mut self.delay {
+            if let Ok(Async::NotReady) = delay.poll() {
+                return Ok(Async::NotReady);
+            }
+            self.delay = None;
+        }
+        if self.fail {
+            return Err(ChaosError::Injected);
+        }
+        let stream = try_ready!(self.inner.poll().map_err(ChaosError::Transport));
+        Ok(Async::Ready(ChaosStream::new(stream, self.chaos.clone())))
+    }
+}
+
+/// Error of a [`ChaosTransport`].
+#[derive(Debug)]
+pub enum ChaosError<TErr> {
+    /// The establishment of the connection failed on purpose.
+    Injected,
+    /// Error of the underlying transport.
+    Transport(TErr),
+}
+
+impl<TErr> fmt::Display for ChaosError<TErr>
+where TErr: fmt::Display,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ChaosError::Injected => write!(f, "Injected connection failure"),
+            ChaosError::Transport(err) => write!(f, "{}", err),
+        }
+    }
+}
+
+impl<TErr> error::Error for ChaosError<TErr>
+where TErr: error::Error + 'static,
+{
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        match self {
+            ChaosError::Injected => None,
+            ChaosError::Transport(err) => Some(err),
+        }
+    }
+}
+
+/// Stream resetting, truncating or delaying the reads of another stream.
+pub struct ChaosStream<TInner> {
+    inner: TInner,
+    faults: StreamFaults,
+}
+
+impl<TInner> ChaosStream<TInner> {
+    /// Wraps `inner`.
+    pub fn new(inner: TInner, chaos: Chaos) -> Self {
+        ChaosStream { inner, faults: StreamFaults::new(chaos) }
+    }
+}
+
+impl<TInner: AsyncRead> io::Read for ChaosStream<TInner> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let inner = &mut self.inner;
+        into_io(self.faults.read(buf, |buf| inner.poll_read(buf)))
+    }
+}
+
+impl<TInner: AsyncRead> AsyncRead for ChaosStream<TInner> {
+    unsafe fn prepare_uninitialized_buffer(&self, buf: &mut [u8]) -> bool {
+        self.inner.prepare_uninitialized_buffer(buf)
+    }
+}
+
+impl<TInner: AsyncWrite> io::Write for ChaosStream<TInner> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let inner = &mut self.inner;
+        into_io(self.faults.write(|| inner.poll_write(buf)))
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+impl<TInner: AsyncWrite> AsyncWrite for ChaosStream<TInner> {
+    fn shutdown(&mut self) -> Poll<(), io::Error> {
+        self.inner.shutdown()
+    }
+}
+
+/// Stream muxer resetting, truncating or delaying the reads of the substreams of another
+/// muxer, and reordering its inbound substreams.
+///
+/// An inbound substream held back is delivered after the next one, and therefore never if no
+/// other substream is opened by the remote.
+pub struct ChaosMuxer<TInner: StreamMuxer> {
+    inner: TInner,
+    chaos: Chaos,
+    held: Mutex<Option<ChaosSubstream<TInner::Substream>>>,
+    released: Mutex<VecDeque<ChaosSubstream<TInner::Substream>>>,
+}
+
+impl<TInner: StreamMuxer> ChaosMuxer<TInner> {
+    /// Wraps `inner`.
+    pub fn new(inner: TInner, chaos: Chaos) -> Self {
+        ChaosMuxer {
+            inner,
+            chaos,
+            held: Mutex::new(None),
+            released: Mutex::new(VecDeque::new()),
+        }
+    }
+}
+
+/// Substream of a [`ChaosMuxer`].
+pub struct ChaosSubstream<TInner> {
+    inner: TInner,
+    faults: StreamFaults,
+}
+
+impl<TInner> StreamMuxer for ChaosMuxer<TInner>
+where
+    TInner: StreamMuxer,
+{
+    type Substream = ChaosSubstream<TInner::Substream>;
+    type OutboundSubstream = TInner::OutboundSubstream;
+    type Error = io::Error;
+
+    fn poll_inbound(&self) -> Poll<Self::Substream, io::Error> {
+        if let Some(substream) = self.released.lock().pop_front() {
+            return Ok(Async::Ready(substream));
+        }
+        loop {
+            let inner = try_ready!(self.inner.poll_inbound().map_err(Into::into));
+            let substream = ChaosSubstream { inner, faults: StreamFaults::new(self.chaos.clone()) };
+            let mut held = self.held.lock();
+            match held.take() {
+                Some(previous) => {
+                    self.released.lock().push_back(previous);
+                    return Ok(Async::Ready(substream));
+                }
+                None if self.chaos.happens(self.chaos.profile.reorder) => *held = Some(substream),
+                None => return Ok(Async::Ready(substream)),
+            }
+        }
+    }
+
+    fn open_outbound(&self) -> Self::OutboundSubstream {
+        self.inner.open_outbound()
+    }
+
+    fn poll_outbound(&self, s: &mut Self::OutboundSubstream) -> Poll<Self::Substream, io::Error> {
+        let inner = try_ready!(self.inner.poll_outbound(s).map_err(Into::into));
+        Ok(Async::Ready(ChaosSubstream { inner, faults: StreamFaults::new(self.chaos.clone()) }))
+    }
+
+    fn destroy_outbound(&self, s: Self::OutboundSubstream) {
+        self.inner.destroy_outbound(s)
+    }
+
+    fn read_substream(&self, s: &mut Self::Substream, buf: &mut [u8]) -> Poll<usize, io::Error> {
+        let ChaosSubstream { inner, faults } = s;
+        faults.read(buf, |buf| self.inner.read_substream(inner, buf).map_err(Into::into))
+    }
+
+    unsafe fn prepare_uninitialized_buffer(&self, buf: &mut [u8]) -> bool {
+        self.inner.prepare_uninitialized_buffer(buf)
+    }
+
+    fn write_substream(&self, s: &mut Self::Substream, buf: &[u8]) -> Poll<usize, io::Error> {
+        let ChaosSubstream { inner, faults } = s;
+        faults.write(|| self.inner.write_substream(inner, buf).map_err(Into::into))
+    }
+
+    fn flush_substream(&self, s: &mut Self::Substream) -> Poll<(), io::Error> {
+        self.inner.flush_substream(&mut s.inner).map_err(Into::into)
+    }
+
+    fn shutdown_substream(&self, s: &mut Self::Substream) -> Poll<(), io::Error> {
+        self.inner.shutdown_substream(&mut s.inner).map_err(Into::into)
+    }
+
+    fn destroy_substream(&self, s: Self::Substream) {
+        self.inner.destroy_substream(s.inner)
+    }
+
+    fn is_remote_acknowledged(&self) -> bool {
+        self.inner.is_remote_acknowledged()
+    }
+
+    fn close(&self) -> Poll<(), io::Error> {
+        self.inner.close().map_err(Into::into)
+    }
+
+    fn flush_all(&self) -> Poll<(), io::Error> {
+        self.inner.flush_all().map_err(Into::into)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{ConnectionScript, MockTransport};
+    use std::io::Read;
+
+    fn read_all(profile: FaultProfile, seed: u64) -> (Vec<u8>, Option<io::ErrorKind>) {
+        let transport = MockTransport::new();
+        let addr: Multiaddr = "/ip4/1.2.3.4/tcp/1".parse().unwrap();
+        transport.script_dial(addr.clone(), ConnectionScript::new().incoming(vec![1; 64]));
+        let transport = ChaosTransport::new(transport, Chaos::new(profile, seed));
+        let mut stream = transport.dial(addr).ok().unwrap().wait().ok().unwrap();
+
+        let mut data = Vec::new();
+        let mut buf = [0; 8];
+        loop {
+            match stream.read(&mut buf) {
+                Ok(0) => return (data, None),
+                Ok(n) => data.extend_from_slice(&buf[.. n]),
+                Err(ref err) if err.kind() == io::ErrorKind::WouldBlock => return (data, None),
+                Err(err) => return (data, Some(err.kind())),
+            }
+        }
+    }
+
+    #[test]
+    fn faults_derive_from_the_seed() {
+        let profile = FaultProfile::new().reset(0.1).truncate(0.1);
+        let runs = (0 .. 20).map(|seed| read_all(profile.clone(), seed)).collect::<Vec<_>>();
+        for (seed, run) in runs.iter().enumerate() {
+            assert_eq!(&read_all(profile.clone(), seed as u64), run);
+        }
+        assert!(runs.iter().any(|(data, _)| data.len() < 64));
+    }
+
+    #[test]
+    fn no_fault_by_default() {
+        assert_eq!(read_all(FaultProfile::new(), 0), (vec![1; 64], None));
+    }
+
+    #[test]
+    fn injected_dial_failure() {
+        let transport = MockTransport::new();
+        let addr: Multiaddr = "/ip4/1.2.3.4/tcp/1".parse().unwrap();
+        transport.script_dial(addr.clone(), ConnectionScript::new());
+        let chaos = Chaos::new(FaultProfile::new().dial_failure(1.0), 0);
+        match ChaosTransport::new(transport, chaos).dial(addr).ok().unwrap().wait() {
+            Err(ChaosError::Injected) => {}
+            _ => panic!("expected an injected failure"),
+        }
+    }
+}
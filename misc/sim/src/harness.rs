@@ -0,0 +1,696 @@
+// Copyright 2019 Parity Technologies (UK) Ltd.
+//
+// Permission is hereby granted, free of charge, to any person obtaining a
+// copy of this software and associated documentation files (the "Software"),
+// to deal in the Software without restriction, including without limitation
+// the rights to use, copy, modify, merge, publish, distribute, sublicense,
+// and/or sell copies of the Software, and to permit persons to whom the
+// Software is furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS
+// OR IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING
+// FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
+// DEALINGS IN THE SOFTWARE.
+//! Driver hosting a single `NetworkBehaviour`, without any transport.
+
+use crate::{NoopNotify, RecordedSubstream, ReplayStream};
+use futures::{executor, future::{self, FutureResult}, prelude::*, try_ready};
+use libp2p_core::{
+    ConnectedPoint, Multiaddr, PeerId,
+    upgrade::{self, InboundUpgrade, Negotiated, OutboundUpgrade, ProtocolName, UpgradeInfo},
+};
+use libp2p_swarm::{
+    IntoProtocolsHandler, NetworkBehaviour, NetworkBehaviourAction, PollParameters,
+    ProtocolsHandler, ProtocolsHandlerEvent, ProtocolsHandlerUpgrErr,
+};
+use parking_lot::Mutex;
+use std::{collections::{HashMap, VecDeque}, io::{self, Read, Write}, mem, sync::Arc, vec};
+use tokio_io::{AsyncRead, AsyncWrite};
+
+/// Maximum number of rounds of [`BehaviourHarness::run_until_idle`].
+const MAX_ROUNDS: usize = 10_000;
+
+type Handler<TBehaviour> =
+    <<TBehaviour as NetworkBehaviour>::ProtocolsHandler as IntoProtocolsHandler>::Handler;
+
+/// Action of the behaviour under test, as returned by [`BehaviourHarness::run_until_idle`].
+pub type HarnessAction<TBehaviour> = NetworkBehaviourAction<
+    <Handler<TBehaviour> as ProtocolsHandler>::InEvent,
+    <TBehaviour as NetworkBehaviour>::OutEvent,
+>;
+
+/// Hosts a single `NetworkBehaviour` and the handlers of its connections, in memory.
+///
+/// The test plays the role of the swarm and of the remotes: it reports connections and dial
+/// failures, injects handler events or opens inbound substreams carrying raw payloads, and
+/// asserts on the actions of the behaviour and on the bytes its handlers write. Substreams
+/// are negotiated with multistream-select like on a real connection, but nothing goes through
+/// a socket and everything runs on the calling thread.
+///
+/// `SendEvent` and `SendEventToConnection` actions are delivered to the handler of the peer;
+/// all the other actions are returned to the test. Each peer has a single connection, and the
+/// upgrade timeouts and keep-alive of the handlers are ignored.
+pub struct BehaviourHarness<TBehaviour>
+where
+    TBehaviour: NetworkBehaviour,
+    Handler<TBehaviour>: ProtocolsHandler<Substream = MemorySubstream>,
+{
+    behaviour: TBehaviour,
+    params: HarnessParameters,
+    connections: HashMap<PeerId, Connection<Handler<TBehaviour>>>,
+    /// Remote ends of all the substreams, driven at each round.
+    remotes: Vec<Arc<Mutex<RemoteState>>>,
+    /// Errors of the handlers whose connection has been closed as a result.
+    handler_errors: Vec<(PeerId, String)>,
+    notify: executor::NotifyHandle,
+}
+
+/// A connection with a remote, and its handler.
+struct Connection<THandler: ProtocolsHandler> {
+    endpoint: ConnectedPoint,
+    handler: THandler,
+    negotiating_in: Vec<executor::Spawn<upgrade::InboundUpgradeApply<MemorySubstream, THandler::InboundProtocol>>>,
+    negotiating_out: Vec<(THandler::OutboundOpenInfo, executor::Spawn<upgrade::OutboundUpgradeApply<MemorySubstream, THandler::OutboundProtocol>>)>,
+    /// Substreams opened by the handler that the test hasn't taken yet.
+    outbound: VecDeque<RemoteSubstream>,
+}
+
+impl<TBehaviour> BehaviourHarness<TBehaviour>
+where
+    TBehaviour: NetworkBehaviour,
+    Handler<TBehaviour>: ProtocolsHandler<Substream = MemorySubstream>,
+{
+    /// Hosts `behaviour`, with a random local peer ID.
+    pub fn new(behaviour: TBehaviour) -> Self {
+        Self::with_local_peer_id(behaviour, PeerId::random())
+    }
+
+    /// Hosts `behaviour`, with the given local peer ID.
+    pub fn with_local_peer_id(mut behaviour: TBehaviour, local_peer_id: PeerId) -> Self {
+        let supported_protocols = behaviour
+            .new_handler()
+            .inbound_protocol()
+            .protocol_info()
+            .into_iter()
+            .map(|info| info.protocol_name().to_vec())
+            .collect();
+        BehaviourHarness {
+            behaviour,
+            params: HarnessParameters {
+                supported_protocols,
+                listened_addrs: Vec::new(),
+                external_addrs: Vec::new(),
+                local_peer_id,
+            },
+            connections: HashMap::new(),
+            remotes: Vec::new(),
+            handler_errors: Vec::new(),
+            notify: executor::NotifyHandle::from(Arc::new(NoopNotify)),
+        }
+    }
+
+    /// Returns the behaviour under test.
+    pub fn behaviour(&self) -> &TBehaviour {
+        &self.behaviour
+    }
+
+    /// Returns the behaviour under test.
+    pub fn behaviour_mut(&mut self) -> &mut TBehaviour {
+        &mut self.behaviour
+    }
+
+    /// Returns the local peer ID reported to the behaviour.
+    pub fn local_peer_id(&self) -> &PeerId {
+        &self.params.local_peer_id
+    }
+
+    /// Returns true if the harness has a connection with the peer.
+    pub fn is_connected(&self, peer_id: &PeerId) -> bool {
+        self.connections.contains_key(peer_id)
+    }
+
+    /// Reports a new listen address to the behaviour.
+    pub fn add_listen_addr(&mut self, addr: Multiaddr) {
+        self.behaviour.inject_new_listen_addr(&addr);
+        self.params.listened_addrs.push(addr);
+    }
+
+    /// Reports a new external address to the behaviour.
+    pub fn add_external_addr(&mut self, addr: Multiaddr) {
+        self.behaviour.inject_new_external_addr(&addr);
+        self.params.external_addrs.push(addr);
+    }
+
+    /// Establishes a connection with the peer, creating its handler and calling
+    /// `inject_connected`.
+    ///
+    /// # Panic
+    ///
+    /// Panics if the peer is already connected.
+    pub fn connect(&mut self, peer_id: PeerId, endpoint: ConnectedPoint) {
+        assert!(!self.connections.contains_key(&peer_id), "{:?} is already connected", peer_id);
+        let handler = self.behaviour.new_handler().into_handler(&peer_id, &endpoint);
+        self.connections.insert(peer_id.clone(), Connection {
+            endpoint: endpoint.clone(),
+            handler,
+            negotiating_in: Vec::new(),
+            negotiating_out: Vec::new(),
+            outbound: VecDeque::new(),
+        });
+        self.behaviour.inject_connected(peer_id, endpoint);
+    }
+
+    /// Closes the connection with the peer, if any, and calls `inject_disconnected`.
+    pub fn disconnect(&mut self, peer_id: &PeerId) {
+        if let Some(connection) = self.connections.remove(peer_id) {
+            for remote in connection.outbound {
+                remote.state.lock().reset();
+            }
+            self.behaviour.inject_disconnected(peer_id, connection.endpoint);
+        }
+    }
+
+    /// Reports to the behaviour that dialing the peer failed.
+    pub fn dial_failure(&mut self, peer_id: &PeerId) {
+        self.behaviour.inject_dial_failure(peer_id);
+    }
+
+    /// Injects an event in the behaviour as if it had been generated by the handler of the peer.
+    pub fn inject_handler_event(
+        &mut self,
+        peer_id: PeerId,
+        event: <Handler<TBehaviour> as ProtocolsHandler>::OutEvent,
+    ) {
+        self.behaviour.inject_node_event(peer_id, event);
+    }
+
+    /// Opens a substream from the peer for the given protocol, and returns its remote end.
+    ///
+    /// The substream is negotiated and handed to the handler of the peer during the next call
+    /// to [`BehaviourHarness::run_until_idle`]. Data written to the remote end in the meantime
+    /// is sent once the negotiation succeeds.
+    ///
+    /// # Panic
+    ///
+    /// Panics if the peer isn't connected.
+    pub fn open_inbound(&mut self, peer_id: &PeerId, protocol: impl Into<Vec<u8>>) -> RemoteSubstream {
+        let connection = self.connections.get_mut(peer_id).expect("the peer must be connected");
+        let (local, remote) = MemorySubstream::pair();
+        let upgrade = connection.handler.listen_protocol().into_upgrade();
+        connection.negotiating_in.push(executor::spawn(upgrade::apply_inbound(local, upgrade)));
+        let negotiation = upgrade::apply_outbound(remote, RemoteProtocols(vec![protocol.into()]));
+        let substream = RemoteSubstream::new(Box::new(negotiation));
+        self.remotes.push(substream.state.clone());
+        substream
+    }
+
+    /// Opens a substream from the peer on which the remote side of a recorded inbound substream
+    /// is replayed, and returns its remote end.
+    ///
+    /// The recorded data includes the negotiation of the protocol, which therefore isn't
+    /// performed by the remote end. The data written by the handler can be compared with the
+    /// recording through [`RemoteSubstream::take_received`].
+    ///
+    /// # Panic
+    ///
+    /// Panics if the peer isn't connected.
+    pub fn replay_inbound(&mut self, peer_id: &PeerId, recorded: &RecordedSubstream) -> RemoteSubstream {
+        let connection = self.connections.get_mut(peer_id).expect("the peer must be connected");
+        let (local, remote) = MemorySubstream::pair();
+        let upgrade = connection.handler.listen_protocol().into_upgrade();
+        connection.negotiating_in.push(executor::spawn(upgrade::apply_inbound(local, upgrade)));
+        let substream = RemoteSubstream::replay(remote, recorded);
+        self.remotes.push(substream.state.clone());
+        substream
+    }
+
+    /// Returns the remote end of the oldest substream that the handler of the peer opened and
+    /// that hasn't been taken yet.
+    pub fn next_outbound(&mut self, peer_id: &PeerId) -> Option<RemoteSubstream> {
+        self.connections.get_mut(peer_id).and_then(|c| c.outbound.pop_front())
+    }
+
+    /// Returns the errors of the handlers since the last call, with the peer whose connection
+    /// has been closed as a result.
+    pub fn take_handler_errors(&mut self) -> Vec<(PeerId, String)> {
+        mem::replace(&mut self.handler_errors, Vec::new())
+    }
+
+    /// Polls the behaviour, its handlers and the substreams until none of them makes progress,
+    /// and returns the actions of the behaviour that aren't meant for a handler.
+    pub fn run_until_idle(&mut self) -> Vec<HarnessAction<TBehaviour>> {
+        let mut actions = Vec::new();
+        for _ in 0 .. MAX_ROUNDS {
+            if !self.round(&mut actions) {
+                break;
+            }
+        }
+        actions
+    }
+
+    /// Polls everything once. Returns true if anything made progress.
+    fn round(&mut self, actions: &mut Vec<HarnessAction<TBehaviour>>) -> bool {
+        let notify = self.notify.clone();
+        let mut progress = false;
+
+        for remote in &self.remotes {
+            progress |= remote.lock().progress(&notify);
+        }
+
+        let mut closed = Vec::new();
+        for (peer_id, connection) in self.connections.iter_mut() {
+            progress |= connection.poll_upgrades(&notify);
+
+            loop {
+                let handler = &mut connection.handler;
+                let polled = executor::spawn(future::poll_fn(|| Ok::<_, ()>(Async::Ready(handler.poll()))))
+                    .poll_future_notify(&notify, 0);
+                match polled {
+                    Ok(Async::Ready(Ok(Async::Ready(ProtocolsHandlerEvent::Custom(event))))) => {
+                        self.behaviour.inject_node_event(peer_id.clone(), event);
+                    }
+                    Ok(Async::Ready(Ok(Async::Ready(ProtocolsHandlerEvent::OutboundSubstreamRequest { protocol, info })))) => {
+                        let upgrade = protocol.into_upgrade();
+                        let names = upgrade.protocol_info()
+                            .into_iter()
+                            .map(|info| info.protocol_name().to_vec())
+                            .collect();
+                        let (local, remote) = MemorySubstream::pair();
+                        connection.negotiating_out.push((info, executor::spawn(upgrade::apply_outbound(local, upgrade))));
+                        let negotiation = upgrade::apply_inbound(remote, RemoteProtocols(names));
+                        let substream = RemoteSubstream::new(Box::new(negotiation));
+                        self.remotes.push(substream.state.clone());
+                        connection.outbound.push_back(substream);
+                    }
+                    Ok(Async::Ready(Err(err))) => {
+                        self.handler_errors.push((peer_id.clone(), err.to_string()));
+                        closed.push(peer_id.clone());
+                        break;
+                    }
+                    _ => break,
+                }
+                progress = true;
+            }
+        }
+        for peer_id in closed {
+            self.disconnect(&peer_id);
+            progress = true;
+        }
+
+        loop {
+            let behaviour = &mut self.behaviour;
+            let params = &mut self.params;
+            let polled = executor::spawn(future::poll_fn(|| Ok::<_, ()>(behaviour.poll(&mut *params))))
+                .poll_future_notify(&notify, 0);
+            match polled {
+                Ok(Async::Ready(NetworkBehaviourAction::SendEvent { peer_id, event }))
+                | Ok(Async::Ready(NetworkBehaviourAction::SendEventToConnection { peer_id, event, .. })) => {
+                    if let Some(connection) = self.connections.get_mut(&peer_id) {
+                        connection.handler.inject_event(event);
+                    }
+                }
+                Ok(Async::Ready(action)) => actions.push(action),
+                _ => break,
+            }
+            progress = true;
+        }
+
+        progress
+    }
+}
+
+impl<THandler> Connection<THandler>
+where
+    THandler: ProtocolsHandler<Substream = MemorySubstream>,
+{
+    /// Polls the negotiation of the substreams, and hands the negotiated ones to the handler.
+    fn poll_upgrades(&mut self, notify: &executor::NotifyHandle) -> bool {
+        let mut progress = false;
+        for n in (0 .. self.negotiating_in.len()).rev() {
+            match self.negotiating_in[n].poll_future_notify(notify, 0) {
+                Ok(Async::NotReady) => continue,
+                Ok(Async::Ready(output)) => {
+                    self.negotiating_in.remove(n);
+                    self.handler.inject_fully_negotiated_inbound(output);
+                }
+                Err(_) => {
+                    self.negotiating_in.remove(n);
+                }
+            }
+            progress = true;
+        }
+        for n in (0 .. self.negotiating_out.len()).rev() {
+            let result = match self.negotiating_out[n].1.poll_future_notify(notify, 0) {
+                Ok(Async::NotReady) => continue,
+                Ok(Async::Ready(output)) => Ok(output),
+                Err(err) => Err(err),
+            };
+            let (info, _) = self.negotiating_out.remove(n);
+            match result {
+                Ok(output) => self.handler.inject_fully_negotiated_outbound(output, info),
+                Err(err) => self.handler.inject_dial_upgrade_error(info, ProtocolsHandlerUpgrErr::Upgrade(err)),
+            }
+            progress = true;
+        }
+        progress
+    }
+}
+
+/// Parameters passed to the behaviour by the harness.
+struct HarnessParameters {
+    supported_protocols: Vec<Vec<u8>>,
+    listened_addrs: Vec<Multiaddr>,
+    external_addrs: Vec<Multiaddr>,
+    local_peer_id: PeerId,
+}
+
+impl PollParameters for HarnessParameters {
+    type SupportedProtocolsIter = vec::IntoIter<Vec<u8>>;
+    type ListenedAddressesIter = vec::IntoIter<Multiaddr>;
+    type ExternalAddressesIter = vec::IntoIter<Multiaddr>;
+
+    fn supported_protocols(&self) -> Self::SupportedProtocolsIter {
+        self.supported_protocols.clone().into_iter()
+    }
+
+    fn listened_addresses(&self) -> Self::ListenedAddressesIter {
+        self.listened_addrs.clone().into_iter()
+    }
+
+    fn external_addresses(&self) -> Self::ExternalAddressesIter {
+        self.external_addrs.clone().into_iter()
+    }
+
+    fn local_peer_id(&self) -> &PeerId {
+        &self.local_peer_id
+    }
+}
+
+/// Upgrade of the remote ends, which accepts or proposes a list of protocols and does nothing
+/// once negotiated.
+struct RemoteProtocols(Vec<Vec<u8>>);
+
+impl UpgradeInfo for RemoteProtocols {
+    type Info = Vec<u8>;
+    type InfoIter = vec::IntoIter<Vec<u8>>;
+
+    fn protocol_info(&self) -> Self::InfoIter {
+        self.0.clone().into_iter()
+    }
+}
+
+impl<C> InboundUpgrade<C> for RemoteProtocols {
+    type Output = (Negotiated<C>, Vec<u8>);
+    type Error = io::Error;
+    type Future = FutureResult<Self::Output, io::Error>;
+
+    fn upgrade_inbound(self, socket: Negotiated<C>, info: Vec<u8>) -> Self::Future {
+        future::ok((socket, info))
+    }
+}
+
+impl<C> OutboundUpgrade<C> for RemoteProtocols {
+    type Output = (Negotiated<C>, Vec<u8>);
+    type Error = io::Error;
+    type Future = FutureResult<Self::Output, io::Error>;
+
+    fn upgrade_outbound(self, socket: Negotiated<C>, info: Vec<u8>) -> Self::Future {
+        future::ok((socket, info))
+    }
+}
+
+/// Substream between a handler under test and a [`RemoteSubstream`].
+///
+/// Reading from an empty substream whose other end is still open returns `WouldBlock`; the
+/// harness polls again after every round instead of waking up tasks.
+#[derive(Debug)]
+pub struct MemorySubstream {
+    incoming: Arc<Mutex<Pipe>>,
+    outgoing: Arc<Mutex<Pipe>>,
+}
+
+#[derive(Debug, Default)]
+struct Pipe {
+    data: VecDeque<u8>,
+    closed: bool,
+}
+
+impl MemorySubstream {
+    /// Creates the two ends of a substream.
+    fn pair() -> (MemorySubstream, MemorySubstream) {
+        let a = Arc::new(Mutex::new(Pipe::default()));
+        let b = Arc::new(Mutex::new(Pipe::default()));
+        (
+            MemorySubstream { incoming: a.clone(), outgoing: b.clone() },
+            MemorySubstream { incoming: b, outgoing: a },
+        )
+    }
+}
+
+impl io::Read for MemorySubstream {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let mut pipe = self.incoming.lock();
+        if pipe.data.is_empty() {
+            return if pipe.closed { Ok(0) } else { Err(io::ErrorKind::WouldBlock.into()) };
+        }
+        let len = buf.len().min(pipe.data.len());
+        for (dst, src) in buf.iter_mut().zip(pipe.data.drain(.. len)) {
+            *dst = src;
+        }
+        Ok(len)
+    }
+}
+
+impl AsyncRead for MemorySubstream {}
+
+impl io::Write for MemorySubstream {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let mut pipe = self.outgoing.lock();
+        if pipe.closed {
+            return Err(io::ErrorKind::BrokenPipe.into());
+        }
+        pipe.data.extend(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+impl AsyncWrite for MemorySubstream {
+    fn shutdown(&mut self) -> Poll<(), io::Error> {
+        self.outgoing.lock().closed = true;
+        Ok(Async::Ready(()))
+    }
+}
+
+impl Drop for MemorySubstream {
+    fn drop(&mut self) {
+        self.outgoing.lock().closed = true;
+        self.incoming.lock().closed = true;
+    }
+}
+
+type RemoteNegotiation = Box<dyn Future<Item = (Negotiated<MemorySubstream>, Vec<u8>), Error = upgrade::UpgradeError<io::Error>>>;
+
+/// Remote end of a substream with a handler under test, played by the test.
+///
+/// Clones refer to the same substream.
+#[derive(Clone)]
+pub struct RemoteSubstream {
+    state: Arc<Mutex<RemoteState>>,
+}
+
+enum Negotiation {
+    Pending(executor::Spawn<RemoteNegotiation>),
+    Open(Negotiated<MemorySubstream>),
+    /// Substream whose negotiation is part of the data, for replays.
+    Raw(MemorySubstream),
+    Failed,
+}
+
+/// Stream of an open remote substream.
+trait RemoteIo: AsyncRead + AsyncWrite {}
+
+impl<T: AsyncRead + AsyncWrite> RemoteIo for T {}
+
+struct RemoteState {
+    negotiation: Negotiation,
+    protocol: Option<Vec<u8>>,
+    /// Data written by the test and not sent yet.
+    to_send: Vec<u8>,
+    /// Data sent by the handler and not taken by the test yet.
+    received: Vec<u8>,
+    /// True if the handler closed its side of the substream.
+    remote_closed: bool,
+    /// True if the test asked to close its side of the substream.
+    close: bool,
+    /// Recorded remote side playing on the substream, if any.
+    replay: Option<ReplayStream>,
+}
+
+impl RemoteSubstream {
+    fn new(negotiation: RemoteNegotiation) -> Self {
+        RemoteSubstream::with_negotiation(Negotiation::Pending(executor::spawn(negotiation)), None)
+    }
+
+    fn replay(stream: MemorySubstream, recorded: &RecordedSubstream) -> Self {
+        RemoteSubstream::with_negotiation(Negotiation::Raw(stream), Some(ReplayStream::new(recorded)))
+    }
+
+    fn with_negotiation(negotiation: Negotiation, replay: Option<ReplayStream>) -> Self {
+        RemoteSubstream {
+            state: Arc::new(Mutex::new(RemoteState {
+                negotiation,
+                protocol: None,
+                to_send: Vec::new(),
+                received: Vec::new(),
+                remote_closed: false,
+                close: false,
+                replay,
+            })),
+        }
+    }
+
+    /// Returns the negotiated protocol, once the negotiation has succeeded.
+    pub fn protocol(&self) -> Option<Vec<u8>> {
+        self.state.lock().protocol.clone()
+    }
+
+    /// Returns true if the negotiation of the protocol failed, or if the connection has been
+    /// closed.
+    pub fn is_failed(&self) -> bool {
+        match self.state.lock().negotiation {
+            Negotiation::Failed => true,
+            _ => false,
+        }
+    }
+
+    /// Queues data to send to the handler.
+    pub fn write(&self, data: &[u8]) {
+        self.state.lock().to_send.extend_from_slice(data);
+    }
+
+    /// Closes the writing side of the substream, once the queued data has been sent.
+    pub fn close(&self) {
+        self.state.lock().close = true;
+    }
+
+    /// Returns the data sent by the handler since the last call.
+    pub fn take_received(&self) -> Vec<u8> {
+        mem::replace(&mut self.state.lock().received, Vec::new())
+    }
+
+    /// Returns true if the handler has closed its writing side of the substream.
+    pub fn is_closed_by_handler(&self) -> bool {
+        self.state.lock().remote_closed
+    }
+}
+
+impl RemoteState {
+    /// Makes progress on the negotiation and the transfers. Returns true if anything happened.
+    fn progress(&mut self, notify: &executor::NotifyHandle) -> bool {
+        let mut progress = false;
+        if let Negotiation::Pending(negotiation) = &mut self.negotiation {
+            match negotiation.poll_future_notify(notify, 0) {
+                Ok(Async::NotReady) => return false,
+                Ok(Async::Ready((io, protocol))) => {
+                    self.protocol = Some(protocol);
+                    self.negotiation = Negotiation::Open(io);
+                }
+                Err(_) => {
+                    self.negotiation = Negotiation::Failed;
+                    return true;
+                }
+            }
+            progress = true;
+        }
+
+        let RemoteState { negotiation, to_send, received, remote_closed, close, replay, .. } = self;
+        let stream: &mut dyn RemoteIo = match negotiation {
+            Negotiation::Open(stream) => stream,
+            Negotiation::Raw(stream) => stream,
+            _ => return progress,
+        };
+        if let Some(replay) = replay {
+            let mut buf = [0; 1024];
+            loop {
+                match replay.read(&mut buf) {
+                    Ok(0) => {
+                        *close = true;
+                        break;
+                    }
+                    Ok(n) => to_send.extend_from_slice(&buf[.. n]),
+                    Err(_) => break,
+                }
+            }
+        }
+        let received_before = received.len();
+        let pending = mem::replace(to_send, Vec::new());
+        let mut unsent = &pending[..];
+        let result = executor::spawn(future::poll_fn(|| -> Poll<(), io::Error> {
+            while !unsent.is_empty() {
+                let written = try_ready!(stream.poll_write(unsent));
+                unsent = &unsent[written ..];
+                progress = true;
+            }
+            try_ready!(stream.poll_flush());
+            if *close {
+                try_ready!(stream.shutdown());
+            }
+            let mut buf = [0; 1024];
+            while !*remote_closed {
+                match try_ready!(stream.poll_read(&mut buf)) {
+                    0 => *remote_closed = true,
+                    n => received.extend_from_slice(&buf[.. n]),
+                }
+                progress = true;
+            }
+            Ok(Async::Ready(()))
+        })).poll_future_notify(notify, 0);
+        *to_send = unsent.to_vec();
+        if let Some(replay) = replay {
+            let _ = replay.write_all(&received[received_before ..]);
+        }
+        if result.is_err() {
+            self.reset();
+            progress = true;
+        }
+        progress
+    }
+
+    /// Marks the substream as failed, for example because the connection has been closed.
+    fn reset(&mut self) {
+        self.negotiation = Negotiation::Failed;
+        self.remote_closed = true;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use libp2p_ping::{Ping, PingConfig};
+
+    #[test]
+    fn inbound_ping_is_answered() {
+        let mut harness = BehaviourHarness::new(Ping::<MemorySubstream>::new(PingConfig::new()));
+        let peer_id = PeerId::random();
+        harness.connect(peer_id.clone(), ConnectedPoint::Dialer { address: "/memory/1".parse().unwrap() });
+
+        let substream = harness.open_inbound(&peer_id, &b"/ipfs/ping/1.0.0"[..]);
+        let payload = [7; 32];
+        substream.write(&payload);
+        harness.run_until_idle();
+
+        assert_eq!(substream.protocol(), Some(b"/ipfs/ping/1.0.0".to_vec()));
+        assert_eq!(substream.take_received(), payload.to_vec());
+        assert!(substream.is_closed_by_handler());
+    }
+}
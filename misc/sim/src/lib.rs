@@ -0,0 +1,415 @@
+// Copyright 2019 Parity Technologies (UK) Ltd.
+//
+// Permission is hereby granted, free of charge, to any person obtaining a
+// copy of this software and associated documentation files (the "Software"),
+// to deal in the Software without restriction, including without limitation
+// the rights to use, copy, modify, merge, publish, distribute, sublicense,
+// and/or sell copies of the Software, and to permit persons to whom the
+// Software is furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS
+// OR IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING
+// FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
+// DEALINGS IN THE SOFTWARE.
+//! Deterministic network simulation harness.
+//!
+//! A [`Simulation`] runs many nodes, typically swarms, in a single thread over the in-memory
+//! transport. Time is virtual: it advances by one tick at each call to [`Simulation::step`],
+//! which polls every online node in order. The simulation applies configurable latencies to
+//! the links between nodes, and a schedule of [`SimAction`]s taking nodes offline and online
+//! or partitioning the network.
+//!
+//! The keys of the nodes, the random churn and the random number generator available to the
+//! test all derive from the seed of the [`SimConfig`], so that a failing run can be replayed.
+//!
+//! Nodes are built by a closure receiving a [`SimNode`]. Swarms must use its transport and
+//! listen on its address, and should be built with `TaskExecutor::Local` so that their
+//! connections are driven by the simulation rather than by a thread pool.
+//!
+//! Timers inside the nodes, such as the ping interval or the Kademlia query timeout, still
+//! use the wall clock, and behaviours with their own source of randomness are only as
+//! reproducible as that source.
+//!
+//! The crate also provides a [`MockTransport`], whose dial and listen outcomes are scripted
+//! per address, to exercise the failure paths of behaviours in unit tests, and a
+//! [`BehaviourHarness`], which hosts a single `NetworkBehaviour` without any transport so that
+//! tests can inject connection events and substream payloads and assert on its actions.
+//!
+//! Finally, a [`ChaosTransport`] and a [`ChaosMuxer`] wrap a real transport and muxer to fail,
+//! delay, truncate or reset connections and substreams and reorder inbound substreams at
+//! random, following a [`FaultProfile`] and a seed.
+//!
+//! To reproduce protocol bugs, a [`RecordingMuxer`] records the data exchanged on the
+//! substreams of a connection into a [`Recording`] that can be saved to a file. The remote side
+//! of a recorded substream can then be replayed against an upgrade with a [`ReplayStream`], or
+//! against a behaviour with [`BehaviourHarness::replay_inbound`].
+
+mod chaos;
+mod harness;
+mod mock;
+mod record;
+mod transport;
+
+pub use chaos::{
+    Chaos, ChaosError, ChaosListener, ChaosMuxer, ChaosStream, ChaosSubstream, ChaosTransport,
+    ChaosUpgrade, FaultProfile,
+};
+pub use harness::{BehaviourHarness, HarnessAction, MemorySubstream, RemoteSubstream};
+pub use mock::{ConnectionScript, MockConnection, MockListener, MockTransport};
+pub use record::{
+    Direction, RecordedSubstream, Recorder, Recording, RecordingMuxer, RecordingSubstream,
+    ReplayStream,
+};
+pub use transport::{SimListener, SimStream, SimTransport};
+
+use futures::{executor, prelude::*};
+use libp2p_core::{identity, Multiaddr};
+use parking_lot::Mutex;
+use rand::{Rng, SeedableRng, rngs::StdRng};
+use std::{collections::BTreeMap, sync::{Arc, atomic::{AtomicUsize, Ordering}}};
+use transport::{Conditions, node_addr};
+
+/// Counter used to give each simulation of the process its own addresses.
+static NEXT_RUN: AtomicUsize = AtomicUsize::new(0);
+
+/// Configuration of a [`Simulation`].
+#[derive(Debug, Clone)]
+pub struct SimConfig {
+    seed: u64,
+    latency: u64,
+    links: Vec<(usize, usize, u64)>,
+}
+
+impl SimConfig {
+    /// Creates a configuration for a run derived from `seed`, without latency.
+    pub fn new(seed: u64) -> Self {
+        SimConfig { seed, latency: 0, links: Vec::new() }
+    }
+
+    /// Sets the latency of the links between nodes, in ticks.
+    pub fn latency(mut self, ticks: u64) -> Self {
+        self.latency = ticks;
+        self
+    }
+
+    /// Sets the latency of the link between nodes `a` and `b`, in ticks.
+    pub fn link_latency(mut self, a: usize, b: usize, ticks: u64) -> Self {
+        self.links.push((a, b, ticks));
+        self
+    }
+
+    /// Sets the latency of every link from a matrix indexed by node.
+    pub fn latency_matrix(mut self, matrix: &[Vec<u64>]) -> Self {
+        for (a, row) in matrix.iter().enumerate() {
+            for (b, ticks) in row.iter().enumerate().skip(a + 1) {
+                self.links.push((a, b, *ticks));
+            }
+        }
+        self
+    }
+}
+
+/// Change to the network applied by a [`Simulation`] at a given tick.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SimAction {
+    /// The node stops being polled, and its connections break.
+    Offline(usize),
+    /// The node is polled again, and can be reached.
+    Online(usize),
+    /// The network is split into the given groups. Nodes that aren't listed form one more group.
+    Partition(Vec<Vec<usize>>),
+    /// The partitions are removed.
+    Heal,
+    /// Changes the latency of the link between two nodes, in ticks.
+    Latency(usize, usize, u64),
+}
+
+/// What the closure building the nodes of a [`Simulation`] receives.
+pub struct SimNode {
+    /// Index of the node in the simulation.
+    pub index: usize,
+    /// Keypair of the node, derived from the seed.
+    pub keypair: identity::Keypair,
+    /// Transport connecting the node to the others.
+    pub transport: SimTransport,
+    /// Address the node must listen on.
+    pub listen_addr: Multiaddr,
+}
+
+/// Deterministic simulation of a network of nodes.
+pub struct Simulation<TNode: Stream> {
+    conditions: Arc<Mutex<Conditions>>,
+    nodes: Vec<executor::Spawn<TNode>>,
+    /// Nodes whose stream has ended.
+    finished: Vec<bool>,
+    addresses: Vec<Multiaddr>,
+    schedule: BTreeMap<u64, Vec<SimAction>>,
+    rng: StdRng,
+    notify: executor::NotifyHandle,
+}
+
+/// The nodes and the harnesses poll everything again at every step, so they don't need to be
+/// woken up.
+pub(crate) struct NoopNotify;
+
+impl executor::Notify for NoopNotify {
+    fn notify(&self, _: usize) {}
+}
+
+impl<TNode: Stream> Simulation<TNode> {
+    /// Creates a simulation of `count` nodes, built by `build`.
+    pub fn new(config: SimConfig, count: usize, mut build: impl FnMut(SimNode) -> TNode) -> Self {
+        let run = Arc::new(format!("sim{}-{}", NEXT_RUN.fetch_add(1, Ordering::Relaxed), config.seed));
+        let mut conditions = Conditions::new(count, config.latency);
+        for (a, b, ticks) in config.links {
+            conditions.set_latency(a, b, ticks);
+        }
+        let conditions = Arc::new(Mutex::new(conditions));
+        let mut rng = StdRng::seed_from_u64(config.seed);
+
+        let mut nodes = Vec::with_capacity(count);
+        let mut addresses = Vec::with_capacity(count);
+        for index in 0 .. count {
+            let mut secret = [0; 32];
+            rng.fill(&mut secret);
+            let secret = identity::ed25519::SecretKey::from_bytes(secret)
+                .expect("any 32 bytes are a valid ed25519 secret key");
+            let listen_addr = node_addr(&run, index);
+            addresses.push(listen_addr.clone());
+            nodes.push(executor::spawn(build(SimNode {
+                index,
+                keypair: identity::Keypair::Ed25519(secret.into()),
+                transport: SimTransport::new(index, run.clone(), conditions.clone()),
+                listen_addr,
+            })));
+        }
+
+        Simulation {
+            conditions,
+            nodes,
+            finished: vec![false; count],
+            addresses,
+            schedule: BTreeMap::new(),
+            rng,
+            notify: executor::NotifyHandle::from(Arc::new(NoopNotify)),
+        }
+    }
+
+    /// Returns the current virtual time, in ticks.
+    pub fn now(&self) -> u64 {
+        self.conditions.lock().now
+    }
+
+    /// Returns the address node `index` listens on.
+    pub fn address(&self, index: usize) -> &Multiaddr {
+        &self.addresses[index]
+    }
+
+    /// Returns the number of nodes.
+    pub fn len(&self) -> usize {
+        self.nodes.len()
+    }
+
+    /// Returns true if the simulation has no nodes.
+    pub fn is_empty(&self) -> bool {
+        self.nodes.is_empty()
+    }
+
+    /// Returns node `index`.
+    pub fn node(&self, index: usize) -> &TNode {
+        self.nodes[index].get_ref()
+    }
+
+    /// Returns node `index`.
+    pub fn node_mut(&mut self, index: usize) -> &mut TNode {
+        self.nodes[index].get_mut()
+    }
+
+    /// Returns true if node `index` is online.
+    pub fn is_online(&self, index: usize) -> bool {
+        self.conditions.lock().online[index]
+    }
+
+    /// Returns the random number generator of the run, derived from the seed.
+    ///
+    /// Tests should use it to pick the nodes to connect or to query, so that runs stay
+    /// reproducible.
+    pub fn rng(&mut self) -> &mut StdRng {
+        &mut self.rng
+    }
+
+    /// Schedules `action` to be applied at tick `at`, before the nodes are polled.
+    pub fn schedule(&mut self, at: u64, action: SimAction) {
+        self.schedule.entry(at).or_insert_with(Vec::new).push(action);
+    }
+
+    /// Schedules random churn: between ticks `from` and `until`, each node goes offline with
+    /// probability `fraction`, and comes back online after `downtime` ticks.
+    pub fn random_churn(&mut self, from: u64, until: u64, fraction: f64, downtime: u64) {
+        assert!(from < until);
+        for index in 0 .. self.nodes.len() {
+            if self.rng.gen_bool(fraction) {
+                let at = self.rng.gen_range(from, until);
+                self.schedule(at, SimAction::Offline(index));
+                self.schedule(at + downtime, SimAction::Online(index));
+            }
+        }
+    }
+
+    /// Applies `action` immediately.
+    pub fn apply(&mut self, action: SimAction) {
+        let mut conditions = self.conditions.lock();
+        match action {
+            SimAction::Offline(index) => conditions.online[index] = false,
+            SimAction::Online(index) => conditions.online[index] = true,
+            SimAction::Partition(groups) => {
+                let rest = groups.len() + 1;
+                for group in conditions.groups.iter_mut() {
+                    *group = rest;
+                }
+                for (group, members) in groups.into_iter().enumerate() {
+                    for index in members {
+                        conditions.groups[index] = group;
+                    }
+                }
+            }
+            SimAction::Heal => {
+                for group in conditions.groups.iter_mut() {
+                    *group = 0;
+                }
+            }
+            SimAction::Latency(a, b, ticks) => conditions.set_latency(a, b, ticks),
+        }
+    }
+
+    /// Advances the virtual time by one tick, then polls every online node until it is no
+    /// longer ready, in the order of their indices.
+    ///
+    /// Returns the events produced by the nodes, with their index, or the first error.
+    pub fn step(&mut self) -> Result<Vec<(usize, TNode::Item)>, (usize, TNode::Error)> {
+        let now = {
+            let mut conditions = self.conditions.lock();
+            conditions.now += 1;
+            conditions.now
+        };
+        let due = self.schedule.range(..= now).map(|(at, _)| *at).collect::<Vec<_>>();
+        for at in due {
+            for action in self.schedule.remove(&at).unwrap_or_default() {
+                self.apply(action);
+            }
+        }
+
+        let mut events = Vec::new();
+        for index in 0 .. self.nodes.len() {
+            if self.finished[index] || !self.is_online(index) {
+                continue;
+            }
+            loop {
+                match self.nodes[index].poll_stream_notify(&self.notify, index) {
+                    Ok(Async::Ready(Some(event))) => events.push((index, event)),
+                    Ok(Async::Ready(None)) => {
+                        self.finished[index] = true;
+                        break;
+                    }
+                    Ok(Async::NotReady) => break,
+                    Err(err) => return Err((index, err)),
+                }
+            }
+        }
+        Ok(events)
+    }
+
+    /// Steps the simulation until `f` returns true for an event, or until `max_ticks` ticks
+    /// have elapsed.
+    ///
+    /// Returns whether `f` has returned true.
+    pub fn run_until(
+        &mut self,
+        max_ticks: u64,
+        mut f: impl FnMut(usize, TNode::Item) -> bool,
+    ) -> Result<bool, (usize, TNode::Error)> {
+        for _ in 0 .. max_ticks {
+            let mut done = false;
+            for (index, event) in self.step()? {
+                done |= f(index, event);
+            }
+            if done {
+                return Ok(true);
+            }
+        }
+        Ok(false)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use libp2p_core::Transport;
+    use std::io;
+
+    type TestNode = Box<dyn Stream<Item = (), Error = io::Error>>;
+
+    /// Node 0 listens and produces an event when it receives data; node 1 sends it data.
+    fn two_nodes(config: SimConfig) -> Simulation<TestNode> {
+        let mut listen_addr = None;
+        Simulation::new(config, 2, |node| -> TestNode {
+            if node.index == 0 {
+                listen_addr = Some(node.listen_addr.clone());
+                Box::new(node.transport.listen_on(node.listen_addr).unwrap()
+                    .filter_map(|event| event.into_upgrade())
+                    .and_then(|(upgrade, _)| upgrade.and_then(|stream| tokio_io::io::read_exact(stream, [0; 5])))
+                    .map(|_| ()))
+            } else {
+                let addr = listen_addr.clone().unwrap();
+                Box::new(node.transport.dial(addr).unwrap()
+                    .and_then(|stream| tokio_io::io::write_all(stream, b"hello"))
+                    .map(|_| ())
+                    .into_stream()
+                    .filter(|_| false))
+            }
+        })
+    }
+
+    #[test]
+    fn latency_delays_connections_and_data() {
+        let mut sim = two_nodes(SimConfig::new(7).latency(10));
+        let received = loop {
+            let events = sim.step().unwrap();
+            if !events.is_empty() {
+                assert_eq!(events.len(), 1);
+                break sim.now();
+            }
+            assert!(sim.now() < 100);
+        };
+        // Ten ticks to connect, one for node 0 to be polled after node 1, and ten more for the
+        // data to arrive.
+        assert_eq!(received, 21);
+    }
+
+    #[test]
+    fn partitioned_nodes_cannot_connect() {
+        let mut sim = two_nodes(SimConfig::new(7));
+        sim.schedule(1, SimAction::Partition(vec![vec![0]]));
+        assert!(sim.run_until(1, |_, _| true).is_err());
+    }
+
+    #[test]
+    fn keys_derive_from_the_seed() {
+        let keys = |seed| {
+            let mut keys = Vec::new();
+            let _ = Simulation::new(SimConfig::new(seed), 3, |node| {
+                keys.push(node.keypair.public());
+                futures::stream::empty::<(), ()>()
+            });
+            keys
+        };
+        assert_eq!(keys(1), keys(1));
+        assert_ne!(keys(1), keys(2));
+    }
+}
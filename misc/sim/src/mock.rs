@@ -0,0 +1,370 @@
+// Copyright 2019 Parity Technologies (UK) Ltd.
+//
+// Permission is hereby granted, free of charge, to any person obtaining a
+// copy of this software and associated documentation files (the "Software"),
+// to deal in the Software without restriction, including without limitation
+// the rights to use, copy, modify, merge, publish, distribute, sublicense,
+// and/or sell copies of the Software, and to permit persons to whom the
+// Software is furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS
+// OR IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING
+// FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
+// DEALINGS IN THE SOFTWARE.
+//! Transport whose outcomes are scripted per address, to test failure paths.
+//!
+//! Each dial to an address consumes the next [`ConnectionScript`] registered for that address
+//! with [`MockTransport::script_dial`]. The script decides whether the dial is refused, how
+//! long the handshake takes, whether it fails or never completes, what the remote sends and
+//! after how many bytes the connection is reset. Listening on an address registered with
+//! [`MockTransport::script_listen`] produces one incoming connection per script.
+
+use futures::{future, prelude::*};
+use libp2p_core::{Multiaddr, transport::{ListenerEvent, Transport, TransportError}};
+use parking_lot::Mutex;
+use std::{cmp, collections::{HashMap, VecDeque}, fmt, io, sync::Arc, time::Duration};
+use tokio_io::{AsyncRead, AsyncWrite};
+use wasm_timer::{Delay, Instant};
+
+/// Future establishing a connection of a [`MockTransport`].
+type MockUpgrade = Box<dyn Future<Item = MockConnection, Error = io::Error> + Send>;
+
+/// Script of a single connection of a [`MockTransport`].
+///
+/// Clones share the record of the data written on the connection.
+#[derive(Debug, Clone)]
+pub struct ConnectionScript {
+    refuse: bool,
+    delay: Duration,
+    failure: Option<io::ErrorKind>,
+    hang: bool,
+    incoming: Vec<u8>,
+    reset_after: Option<usize>,
+    remote_addr: Option<Multiaddr>,
+    written: Arc<Mutex<Vec<u8>>>,
+}
+
+impl ConnectionScript {
+    /// Creates a script for a connection established immediately, on which the remote sends
+    /// nothing and never closes.
+    pub fn new() -> Self {
+        ConnectionScript {
+            refuse: false,
+            delay: Duration::from_secs(0),
+            failure: None,
+            hang: false,
+            incoming: Vec::new(),
+            reset_after: None,
+            remote_addr: None,
+            written: Arc::new(Mutex::new(Vec::new())),
+        }
+    }
+
+    /// Creates a script for a dial refused immediately by the transport.
+    pub fn refuse() -> Self {
+        ConnectionScript { refuse: true, .. ConnectionScript::new() }
+    }
+
+    /// Delays the establishment of the connection.
+    pub fn delay(mut self, delay: Duration) -> Self {
+        self.delay = delay;
+        self
+    }
+
+    /// Makes the establishment of the connection fail with the given error, after the delay.
+    pub fn fail(mut self, kind: io::ErrorKind) -> Self {
+        self.failure = Some(kind);
+        self
+    }
+
+    /// Makes the establishment of the connection never complete, to test upgrade timeouts.
+    pub fn hang(mut self) -> Self {
+        self.hang = true;
+        self
+    }
+
+    /// Sets the data the remote sends once the connection is established.
+    pub fn incoming(mut self, data: impl Into<Vec<u8>>) -> Self {
+        self.incoming = data.into();
+        self
+    }
+
+    /// Resets the connection once `bytes` bytes have been read or written in total.
+    pub fn reset_after(mut self, bytes: usize) -> Self {
+        self.reset_after = Some(bytes);
+        self
+    }
+
+    /// Sets the address reported for the remote of an incoming connection.
+    ///
+    /// Defaults to the listening address.
+    pub fn remote_addr(mut self, addr: Multiaddr) -> Self {
+        self.remote_addr = Some(addr);
+        self
+    }
+
+    /// Returns the data written on the connection so far.
+    pub fn written(&self) -> Vec<u8> {
+        self.written.lock().clone()
+    }
+
+    /// Returns the future establishing the connection.
+    fn connect(self) -> MockUpgrade {
+        if self.hang {
+            return Box::new(future::empty());
+        }
+        let delay = Delay::new(Instant::now() + self.delay)
+            .map_err(|err| io::Error::new(io::ErrorKind::Other, err));
+        Box::new(delay.and_then(move |()| match self.failure {
+            Some(kind) => Err(io::Error::new(kind, "scripted handshake failure")),
+            None => Ok(MockConnection {
+                incoming: self.incoming,
+                read: 0,
+                transferred: 0,
+                reset_after: self.reset_after,
+                written: self.written,
+            }),
+        }))
+    }
+}
+
+impl Default for ConnectionScript {
+    fn default() -> Self {
+        ConnectionScript::new()
+    }
+}
+
+#[derive(Default)]
+struct Scripts {
+    dials: HashMap<Multiaddr, VecDeque<ConnectionScript>>,
+    listens: HashMap<Multiaddr, Vec<ConnectionScript>>,
+    dial_attempts: Vec<Multiaddr>,
+}
+
+/// Transport whose outcomes are scripted per address.
+///
+/// Clones share the scripts, so that a test can keep a clone to add scripts and inspect the
+/// dialing attempts after giving the transport to a swarm.
+#[derive(Clone, Default)]
+pub struct MockTransport {
+    scripts: Arc<Mutex<Scripts>>,
+}
+
+impl MockTransport {
+    /// Creates a transport without any script.
+    pub fn new() -> Self {
+        MockTransport::default()
+    }
+
+    /// Appends a script for the next dial to `addr`.
+    ///
+    /// Dials to an address without remaining scripts are refused, and dials to an address that
+    /// has never been scripted report it as unsupported.
+    pub fn script_dial(&self, addr: Multiaddr, script: ConnectionScript) {
+        self.scripts.lock().dials.entry(addr).or_insert_with(VecDeque::new).push_back(script);
+    }
+
+    /// Allows listening on `addr`, and produces one incoming connection per script.
+    pub fn script_listen(&self, addr: Multiaddr, scripts: Vec<ConnectionScript>) {
+        self.scripts.lock().listens.insert(addr, scripts);
+    }
+
+    /// Returns the addresses dialed so far, in order.
+    pub fn dial_attempts(&self) -> Vec<Multiaddr> {
+        self.scripts.lock().dial_attempts.clone()
+    }
+}
+
+impl fmt::Debug for MockTransport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("MockTransport")
+            .field("dial_attempts", &self.scripts.lock().dial_attempts)
+            .finish()
+    }
+}
+
+impl Transport for MockTransport {
+    type Output = MockConnection;
+    type Error = io::Error;
+    type Listener = MockListener;
+    type ListenerUpgrade = MockUpgrade;
+    type Dial = MockUpgrade;
+
+    fn listen_on(self, addr: Multiaddr) -> Result<Self::Listener, TransportError<Self::Error>> {
+        let scripts = match self.scripts.lock().listens.remove(&addr) {
+            Some(scripts) => scripts,
+            None => return Err(TransportError::MultiaddrNotSupported(addr)),
+        };
+        let mut events = VecDeque::with_capacity(scripts.len() + 1);
+        events.push_back(ListenerEvent::NewAddress(addr.clone()));
+        for script in scripts {
+            let remote_addr = script.remote_addr.clone().unwrap_or_else(|| addr.clone());
+            events.push_back(ListenerEvent::Upgrade {
+                upgrade: script.connect(),
+                listen_addr: addr.clone(),
+                remote_addr,
+            });
+        }
+        Ok(MockListener { events })
+    }
+
+    fn dial(self, addr: Multiaddr) -> Result<Self::Dial, TransportError<Self::Error>> {
+        let mut scripts = self.scripts.lock();
+        scripts.dial_attempts.push(addr.clone());
+        let script = match scripts.dials.get_mut(&addr) {
+            Some(queue) => queue.pop_front().unwrap_or_else(ConnectionScript::refuse),
+            None => return Err(TransportError::MultiaddrNotSupported(addr)),
+        };
+        if script.refuse {
+            let err = io::Error::new(io::ErrorKind::ConnectionRefused, "scripted dial refusal");
+            return Err(TransportError::Other(err));
+        }
+        Ok(script.connect())
+    }
+}
+
+/// Listener of a [`MockTransport`].
+///
+/// Produces its scripted events, then never produces anything else.
+pub struct MockListener {
+    events: VecDeque<ListenerEvent<MockUpgrade>>,
+}
+
+impl Stream for MockListener {
+    type Item = ListenerEvent<MockUpgrade>;
+    type Error = io::Error;
+
+    fn poll(&mut self) -> Poll<Option<Self::Item>, Self::Error> {
+        match self.events.pop_front() {
+            Some(event) => Ok(Async::Ready(Some(event))),
+            None => Ok(Async::NotReady),
+        }
+    }
+}
+
+/// Connection of a [`MockTransport`].
+///
+/// Once the scripted incoming data has been read, reading never completes, as if the remote
+/// stayed silent.
+pub struct MockConnection {
+    incoming: Vec<u8>,
+    /// Number of bytes of `incoming` read so far.
+    read: usize,
+    /// Number of bytes read and written so far.
+    transferred: usize,
+    reset_after: Option<usize>,
+    written: Arc<Mutex<Vec<u8>>>,
+}
+
+impl MockConnection {
+    /// Returns how many bytes can still be transferred before the connection is reset.
+    fn remaining(&self, wanted: usize) -> io::Result<usize> {
+        match self.reset_after {
+            Some(limit) if self.transferred >= limit =>
+                Err(io::Error::new(io::ErrorKind::ConnectionReset, "scripted connection reset")),
+            Some(limit) => Ok(cmp::min(wanted, limit - self.transferred)),
+            None => Ok(wanted),
+        }
+    }
+}
+
+impl io::Read for MockConnection {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let available = self.incoming.len() - self.read;
+        if available == 0 {
+            self.remaining(0)?;
+            return Err(io::ErrorKind::WouldBlock.into());
+        }
+        let n = self.remaining(cmp::min(buf.len(), available))?;
+        buf[..n].copy_from_slice(&self.incoming[self.read .. self.read + n]);
+        self.read += n;
+        self.transferred += n;
+        Ok(n)
+    }
+}
+
+impl AsyncRead for MockConnection {}
+
+impl io::Write for MockConnection {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let n = self.remaining(buf.len())?;
+        self.written.lock().extend_from_slice(&buf[..n]);
+        self.transferred += n;
+        Ok(n)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.remaining(0).map(|_| ())
+    }
+}
+
+impl AsyncWrite for MockConnection {
+    fn shutdown(&mut self) -> Poll<(), io::Error> {
+        Ok(Async::Ready(()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::{Read, Write};
+
+    #[test]
+    fn scripted_dials() {
+        let transport = MockTransport::new();
+        let addr: Multiaddr = "/ip4/1.2.3.4/tcp/1".parse().unwrap();
+        let script = ConnectionScript::new().incoming(&b"hey"[..]).reset_after(5);
+        transport.script_dial(addr.clone(), ConnectionScript::refuse());
+        transport.script_dial(addr.clone(), ConnectionScript::new().fail(io::ErrorKind::TimedOut));
+        transport.script_dial(addr.clone(), script.clone());
+
+        match transport.clone().dial(addr.clone()) {
+            Err(TransportError::Other(err)) => assert_eq!(err.kind(), io::ErrorKind::ConnectionRefused),
+            _ => panic!("expected the dial to be refused"),
+        }
+        let err = transport.clone().dial(addr.clone()).unwrap().wait().err().unwrap();
+        assert_eq!(err.kind(), io::ErrorKind::TimedOut);
+
+        let mut connection = transport.clone().dial(addr.clone()).unwrap().wait().ok().unwrap();
+        let mut buf = [0; 8];
+        assert_eq!(connection.read(&mut buf).unwrap(), 3);
+        assert_eq!(&buf[..3], b"hey");
+        assert_eq!(connection.write(b"abc").unwrap(), 2);
+        assert_eq!(connection.write(b"c").unwrap_err().kind(), io::ErrorKind::ConnectionReset);
+        assert_eq!(script.written(), b"ab");
+
+        // The scripts of the address are exhausted.
+        assert!(transport.clone().dial(addr.clone()).is_err());
+        assert_eq!(transport.dial_attempts().len(), 4);
+    }
+
+    #[test]
+    fn scripted_listener() {
+        let transport = MockTransport::new();
+        let addr: Multiaddr = "/ip4/127.0.0.1/tcp/1".parse().unwrap();
+        transport.script_listen(addr.clone(), vec![ConnectionScript::new(), ConnectionScript::new().hang()]);
+
+        let mut listener = transport.listen_on(addr.clone()).ok().unwrap();
+        let events = future::poll_fn(|| {
+            let mut events = Vec::new();
+            while let Async::Ready(Some(event)) = listener.poll()? {
+                events.push(event);
+            }
+            Ok::<_, io::Error>(Async::Ready(events))
+        }).wait().unwrap();
+        assert_eq!(events.len(), 3);
+
+        let mut events = events.into_iter();
+        assert_eq!(events.next().unwrap().into_new_address(), Some(addr.clone()));
+        let (upgrade, remote_addr) = events.next().unwrap().into_upgrade().unwrap();
+        assert_eq!(remote_addr, addr);
+        assert!(upgrade.wait().is_ok());
+        assert!(events.next().unwrap().is_upgrade());
+    }
+}
@@ -0,0 +1,417 @@
+// Copyright 2019 Parity Technologies (UK) Ltd.
+//
+// Permission is hereby granted, free of charge, to any person obtaining a
+// copy of this software and associated documentation files (the "Software"),
+// to deal in the Software without restriction, including without limitation
+// the rights to use, copy, modify, merge, publish, distribute, sublicense,
+// and/or sell copies of the Software, and to permit persons to whom the
+// Software is furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS
+// OR IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING
+// FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
+// DEALINGS IN THE SOFTWARE.
+//! Recording of the substreams of a connection, and replay of a recorded remote.
+
+use futures::{prelude::*, try_ready};
+use libp2p_core::{Endpoint, StreamMuxer};
+use parking_lot::Mutex;
+use std::{cmp, fs, io::{self, Read, Write}, path::Path, sync::Arc};
+use tokio_io::{AsyncRead, AsyncWrite};
+
+/// Magic number at the start of the recording files.
+const MAGIC: &[u8; 8] = b"LP2PREC1";
+
+/// Direction of recorded data.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Direction {
+    /// Data sent by the local node.
+    Sent,
+    /// Data received from the remote.
+    Received,
+}
+
+/// Data exchanged on a substream, in order.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RecordedSubstream {
+    /// Identifier of the substream, in the order the substreams were opened.
+    pub id: u64,
+    /// `Dialer` if the local node opened the substream, `Listener` if the remote did.
+    pub endpoint: Endpoint,
+    /// The reads and writes on the substream.
+    pub chunks: Vec<(Direction, Vec<u8>)>,
+}
+
+impl RecordedSubstream {
+    /// Returns all the data sent by the local node.
+    pub fn sent(&self) -> Vec<u8> {
+        self.data(Direction::Sent)
+    }
+
+    /// Returns all the data received from the remote.
+    pub fn received(&self) -> Vec<u8> {
+        self.data(Direction::Received)
+    }
+
+    fn data(&self, direction: Direction) -> Vec<u8> {
+        self.chunks.iter()
+            .filter(|(d, _)| *d == direction)
+            .flat_map(|(_, data)| data.iter().cloned())
+            .collect()
+    }
+}
+
+/// Recording of the substreams of a connection.
+///
+/// The data is the one read from and written to the substreams, which includes the
+/// multistream-select negotiation and is in clear when the muxer runs on top of an encrypted
+/// connection.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Recording {
+    /// The substreams, in the order they were opened.
+    pub substreams: Vec<RecordedSubstream>,
+}
+
+impl Recording {
+    /// Serializes the recording.
+    pub fn write_to(&self, mut out: impl Write) -> io::Result<()> {
+        out.write_all(MAGIC)?;
+        out.write_all(&(self.substreams.len() as u64).to_be_bytes())?;
+        for substream in &self.substreams {
+            out.write_all(&substream.id.to_be_bytes())?;
+            out.write_all(&[match substream.endpoint { Endpoint::Dialer => 0, Endpoint::Listener => 1 }])?;
+            out.write_all(&(substream.chunks.len() as u64).to_be_bytes())?;
+            for (direction, data) in &substream.chunks {
+                out.write_all(&[match direction { Direction::Sent => 0, Direction::Received => 1 }])?;
+                out.write_all(&(data.len() as u64).to_be_bytes())?;
+                out.write_all(data)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Deserializes a recording written with [`Recording::write_to`].
+    pub fn read_from(mut input: impl Read) -> io::Result<Self> {
+        fn invalid(msg: &str) -> io::Error {
+            io::Error::new(io::ErrorKind::InvalidData, msg)
+        }
+        fn read_u64(input: &mut impl Read) -> io::Result<u64> {
+            let mut buf = [0; 8];
+            input.read_exact(&mut buf)?;
+            Ok(u64::from_be_bytes(buf))
+        }
+        fn read_u8(input: &mut impl Read) -> io::Result<u8> {
+            let mut buf = [0; 1];
+            input.read_exact(&mut buf)?;
+            Ok(buf[0])
+        }
+
+        let mut magic = [0; 8];
+        input.read_exact(&mut magic)?;
+        if &magic != MAGIC {
+            return Err(invalid("not a recording"));
+        }
+        let mut substreams = Vec::new();
+        for _ in 0 .. read_u64(&mut input)? {
+            let id = read_u64(&mut input)?;
+            let endpoint = match read_u8(&mut input)? {
+                0 => Endpoint::Dialer,
+                1 => Endpoint::Listener,
+                _ => return Err(invalid("invalid endpoint")),
+            };
+            let mut chunks = Vec::new();
+            for _ in 0 .. read_u64(&mut input)? {
+                let direction = match read_u8(&mut input)? {
+                    0 => Direction::Sent,
+                    1 => Direction::Received,
+                    _ => return Err(invalid("invalid direction")),
+                };
+                let len = read_u64(&mut input)?;
+                let mut data = Vec::new();
+                (&mut input).take(len).read_to_end(&mut data)?;
+                if data.len() as u64 != len {
+                    return Err(io::ErrorKind::UnexpectedEof.into());
+                }
+                chunks.push((direction, data));
+            }
+            substreams.push(RecordedSubstream { id, endpoint, chunks });
+        }
+        Ok(Recording { substreams })
+    }
+
+    /// Writes the recording to a file.
+    pub fn save(&self, path: impl AsRef<Path>) -> io::Result<()> {
+        self.write_to(io::BufWriter::new(fs::File::create(path)?))
+    }
+
+    /// Reads a recording from a file.
+    pub fn load(path: impl AsRef<Path>) -> io::Result<Self> {
+        Recording::read_from(io::BufReader::new(fs::File::open(path)?))
+    }
+
+    /// Returns the substreams opened by the remote.
+    pub fn inbound(&self) -> impl Iterator<Item = &RecordedSubstream> {
+        self.substreams.iter().filter(|s| s.endpoint == Endpoint::Listener)
+    }
+
+    /// Returns the substreams opened by the local node.
+    pub fn outbound(&self) -> impl Iterator<Item = &RecordedSubstream> {
+        self.substreams.iter().filter(|s| s.endpoint == Endpoint::Dialer)
+    }
+}
+
+/// Handle on the recording of a [`RecordingMuxer`].
+///
+/// Clones share the recording, so that a clone can save it while the muxer is in use.
+#[derive(Debug, Clone, Default)]
+pub struct Recorder {
+    recording: Arc<Mutex<Recording>>,
+}
+
+impl Recorder {
+    /// Creates an empty recording.
+    pub fn new() -> Self {
+        Recorder::default()
+    }
+
+    /// Returns a copy of the recording so far.
+    pub fn recording(&self) -> Recording {
+        self.recording.lock().clone()
+    }
+
+    fn open(&self, endpoint: Endpoint) -> u64 {
+        let mut recording = self.recording.lock();
+        let id = recording.substreams.len() as u64;
+        recording.substreams.push(RecordedSubstream { id, endpoint, chunks: Vec::new() });
+        id
+    }
+
+    fn record(&self, id: u64, direction: Direction, data: &[u8]) {
+        if data.is_empty() {
+            return;
+        }
+        let mut recording = self.recording.lock();
+        let chunks = &mut recording.substreams[id as usize].chunks;
+        match chunks.last_mut() {
+            Some((d, last)) if *d == direction => last.extend_from_slice(data),
+            _ => chunks.push((direction, data.to_vec())),
+        }
+    }
+}
+
+/// Stream muxer recording the data read from and written to the substreams of another muxer.
+pub struct RecordingMuxer<TInner> {
+    inner: TInner,
+    recorder: Recorder,
+}
+
+impl<TInner> RecordingMuxer<TInner> {
+    /// Wraps `inner`, recording into `recorder`.
+    pub fn new(inner: TInner, recorder: Recorder) -> Self {
+        RecordingMuxer { inner, recorder }
+    }
+}
+
+/// Substream of a [`RecordingMuxer`].
+pub struct RecordingSubstream<TInner> {
+    inner: TInner,
+    id: u64,
+}
+
+impl<TInner> StreamMuxer for RecordingMuxer<TInner>
+where
+    TInner: StreamMuxer,
+{
+    type Substream = RecordingSubstream<TInner::Substream>;
+    type OutboundSubstream = TInner::OutboundSubstream;
+    type Error = TInner::Error;
+
+    fn poll_inbound(&self) -> Poll<Self::Substream, Self::Error> {
+        let inner = try_ready!(self.inner.poll_inbound());
+        Ok(Async::Ready(RecordingSubstream { inner, id: self.recorder.open(Endpoint::Listener) }))
+    }
+
+    fn open_outbound(&self) -> Self::OutboundSubstream {
+        self.inner.open_outbound()
+    }
+
+    fn poll_outbound(&self, s: &mut Self::OutboundSubstream) -> Poll<Self::Substream, Self::Error> {
+        let inner = try_ready!(self.inner.poll_outbound(s));
+        Ok(Async::Ready(RecordingSubstream { inner, id: self.recorder.open(Endpoint::Dialer) }))
+    }
+
+    fn destroy_outbound(&self, s: Self::OutboundSubstream) {
+        self.inner.destroy_outbound(s)
+    }
+
+    fn read_substream(&self, s: &mut Self::Substream, buf: &mut [u8]) -> Poll<usize, Self::Error> {
+        let n = try_ready!(self.inner.read_substream(&mut s.inner, buf));
+        self.recorder.record(s.id, Direction::Received, &buf[.. n]);
+        Ok(Async::Ready(n))
+    }
+
+    unsafe fn prepare_uninitialized_buffer(&self, buf: &mut [u8]) -> bool {
+        self.inner.prepare_uninitialized_buffer(buf)
+    }
+
+    fn write_substream(&self, s: &mut Self::Substream, buf: &[u8]) -> Poll<usize, Self::Error> {
+        let n = try_ready!(self.inner.write_substream(&mut s.inner, buf));
+        self.recorder.record(s.id, Direction::Sent, &buf[.. n]);
+        Ok(Async::Ready(n))
+    }
+
+    fn flush_substream(&self, s: &mut Self::Substream) -> Poll<(), Self::Error> {
+        self.inner.flush_substream(&mut s.inner)
+    }
+
+    fn shutdown_substream(&self, s: &mut Self::Substream) -> Poll<(), Self::Error> {
+        self.inner.shutdown_substream(&mut s.inner)
+    }
+
+    fn destroy_substream(&self, s: Self::Substream) {
+        self.inner.destroy_substream(s.inner)
+    }
+
+    fn is_remote_acknowledged(&self) -> bool {
+        self.inner.is_remote_acknowledged()
+    }
+
+    fn close(&self) -> Poll<(), Self::Error> {
+        self.inner.close()
+    }
+
+    fn flush_all(&self) -> Poll<(), Self::Error> {
+        self.inner.flush_all()
+    }
+}
+
+/// Substream playing the remote side of a [`RecordedSubstream`].
+///
+/// A chunk of received data becomes readable once the local side has written as much data as
+/// it had sent before that chunk in the recording, so that the remote answers in the recorded
+/// order. After the last chunk, the substream reports the end of the stream. The data written
+/// by the local side is kept, to compare it with the recording.
+pub struct ReplayStream {
+    chunks: Vec<(Direction, Vec<u8>)>,
+    /// Index of the next chunk to read, and offset inside that chunk.
+    next: usize,
+    offset: usize,
+    written: Vec<u8>,
+}
+
+impl ReplayStream {
+    /// Creates a stream replaying the remote side of `substream`.
+    pub fn new(substream: &RecordedSubstream) -> Self {
+        ReplayStream { chunks: substream.chunks.clone(), next: 0, offset: 0, written: Vec::new() }
+    }
+
+    /// Returns the data written by the local side so far.
+    pub fn written(&self) -> &[u8] {
+        &self.written
+    }
+
+    /// Returns true if the local side has written exactly the data it sent in the recording.
+    pub fn matches_recording(&self) -> bool {
+        let sent = self.chunks.iter()
+            .filter(|(d, _)| *d == Direction::Sent)
+            .flat_map(|(_, data)| data.iter().cloned());
+        sent.eq(self.written.iter().cloned())
+    }
+
+    /// Number of bytes the local side had sent before the chunk at `index`.
+    fn sent_before(&self, index: usize) -> usize {
+        self.chunks[.. index].iter()
+            .filter(|(d, _)| *d == Direction::Sent)
+            .map(|(_, data)| data.len())
+            .sum()
+    }
+}
+
+impl Read for ReplayStream {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        while self.next < self.chunks.len() && self.chunks[self.next].0 == Direction::Sent {
+            self.next += 1;
+        }
+        if self.next == self.chunks.len() {
+            return Ok(0);
+        }
+        if self.written.len() < self.sent_before(self.next) {
+            return Err(io::ErrorKind::WouldBlock.into());
+        }
+        let data = &self.chunks[self.next].1[self.offset ..];
+        let n = cmp::min(buf.len(), data.len());
+        buf[.. n].copy_from_slice(&data[.. n]);
+        self.offset += n;
+        if self.offset == self.chunks[self.next].1.len() {
+            self.next += 1;
+            self.offset = 0;
+        }
+        Ok(n)
+    }
+}
+
+impl AsyncRead for ReplayStream {}
+
+impl Write for ReplayStream {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.written.extend_from_slice(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+impl AsyncWrite for ReplayStream {
+    fn shutdown(&mut self) -> Poll<(), io::Error> {
+        Ok(Async::Ready(()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn recording() -> Recording {
+        Recording {
+            substreams: vec![RecordedSubstream {
+                id: 0,
+                endpoint: Endpoint::Listener,
+                chunks: vec![
+                    (Direction::Received, b"ping".to_vec()),
+                    (Direction::Sent, b"pong".to_vec()),
+                    (Direction::Received, b"bye".to_vec()),
+                ],
+            }],
+        }
+    }
+
+    #[test]
+    fn recording_round_trip() {
+        let mut file = Vec::new();
+        recording().write_to(&mut file).unwrap();
+        assert_eq!(Recording::read_from(&file[..]).unwrap(), recording());
+        assert!(Recording::read_from(&file[.. file.len() - 1]).is_err());
+    }
+
+    #[test]
+    fn replay_follows_the_recorded_order() {
+        let recording = recording();
+        let mut stream = ReplayStream::new(recording.inbound().next().unwrap());
+        let mut buf = [0; 16];
+        assert_eq!(stream.read(&mut buf).unwrap(), 4);
+        assert_eq!(stream.read(&mut buf).unwrap_err().kind(), io::ErrorKind::WouldBlock);
+        stream.write_all(b"pong").unwrap();
+        assert_eq!(stream.read(&mut buf).unwrap(), 3);
+        assert_eq!(&buf[.. 3], b"bye");
+        assert_eq!(stream.read(&mut buf).unwrap(), 0);
+        assert!(stream.matches_recording());
+    }
+}
@@ -0,0 +1,321 @@
+// Copyright 2019 Parity Technologies (UK) Ltd.
+//
+// Permission is hereby granted, free of charge, to any person obtaining a
+// copy of this software and associated documentation files (the "Software"),
+// to deal in the Software without restriction, including without limitation
+// the rights to use, copy, modify, merge, publish, distribute, sublicense,
+// and/or sell copies of the Software, and to permit persons to whom the
+// Software is furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS
+// OR IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING
+// FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
+// DEALINGS IN THE SOFTWARE.
+//! Transport connecting the nodes of a [`Simulation`](crate::Simulation).
+
+use bytes::Bytes;
+use futures::{prelude::*, task, try_ready};
+use libp2p_core::{
+    multiaddr::{Multiaddr, Protocol},
+    transport::{ListenerEvent, MemoryTransport, Transport, TransportError, memory},
+};
+use parking_lot::Mutex;
+use std::{borrow::Cow, cmp, collections::{HashMap, VecDeque}, io, sync::Arc};
+use tokio_io::{AsyncRead, AsyncWrite};
+
+/// Future upgrading a connection accepted or dialed by a [`SimTransport`].
+type SimUpgrade = Box<dyn Future<Item = SimStream, Error = io::Error> + Send>;
+
+/// Network conditions shared by all the nodes of a simulation.
+#[derive(Debug)]
+pub(crate) struct Conditions {
+    /// Current virtual time, in ticks.
+    pub(crate) now: u64,
+    /// Latency of the links that don't have a specific one, in ticks.
+    pub(crate) default_latency: u64,
+    /// Latency of specific links, indexed by `(min, max)` of the node indices.
+    pub(crate) latencies: HashMap<(usize, usize), u64>,
+    /// Partition group of each node. Nodes can only reach the nodes of their own group.
+    pub(crate) groups: Vec<usize>,
+    /// Whether each node is online.
+    pub(crate) online: Vec<bool>,
+}
+
+impl Conditions {
+    pub(crate) fn new(nodes: usize, default_latency: u64) -> Self {
+        Conditions {
+            now: 0,
+            default_latency,
+            latencies: HashMap::new(),
+            groups: vec![0; nodes],
+            online: vec![true; nodes],
+        }
+    }
+
+    pub(crate) fn latency(&self, a: usize, b: usize) -> u64 {
+        let key = (cmp::min(a, b), cmp::max(a, b));
+        self.latencies.get(&key).cloned().unwrap_or(self.default_latency)
+    }
+
+    pub(crate) fn set_latency(&mut self, a: usize, b: usize, ticks: u64) {
+        self.latencies.insert((cmp::min(a, b), cmp::max(a, b)), ticks);
+    }
+
+    pub(crate) fn reachable(&self, a: usize, b: usize) -> bool {
+        self.online[a] && self.online[b] && self.groups[a] == self.groups[b]
+    }
+}
+
+/// Returns the address node `index` of the simulation run `run` listens on.
+pub(crate) fn node_addr(run: &str, index: usize) -> Multiaddr {
+    Protocol::MemoryName(Cow::Owned(format!("{}-{}", run, index))).into()
+}
+
+/// Transport of a simulated node.
+///
+/// Connects to the other nodes of the simulation over the in-memory transport, applying the
+/// latency, partitions and churn of the simulation. Only supports the addresses returned by
+/// `Simulation::address`.
+#[derive(Debug, Clone)]
+pub struct SimTransport {
+    local: usize,
+    run: Arc<String>,
+    conditions: Arc<Mutex<Conditions>>,
+}
+
+impl SimTransport {
+    pub(crate) fn new(local: usize, run: Arc<String>, conditions: Arc<Mutex<Conditions>>) -> Self {
+        SimTransport { local, run, conditions }
+    }
+
+    /// Returns the index of the node the address belongs to.
+    fn node_index(&self, addr: &Multiaddr) -> Option<usize> {
+        let mut iter = addr.iter();
+        let name = match (iter.next(), iter.next()) {
+            (Some(Protocol::MemoryName(name)), None) => name,
+            _ => return None,
+        };
+        let prefix = format!("{}-", self.run);
+        if !name.starts_with(&prefix) {
+            return None;
+        }
+        let index = name[prefix.len()..].parse::<usize>().ok()?;
+        if index < self.conditions.lock().online.len() {
+            Some(index)
+        } else {
+            None
+        }
+    }
+}
+
+fn to_io_error(err: memory::MemoryTransportError) -> io::Error {
+    io::Error::new(io::ErrorKind::ConnectionRefused, err.to_string())
+}
+
+fn unreachable() -> io::Error {
+    io::Error::new(io::ErrorKind::ConnectionRefused, "node unreachable in the simulation")
+}
+
+impl Transport for SimTransport {
+    type Output = SimStream;
+    type Error = io::Error;
+    type Listener = SimListener;
+    type ListenerUpgrade = SimUpgrade;
+    type Dial = SimUpgrade;
+
+    fn listen_on(self, addr: Multiaddr) -> Result<Self::Listener, TransportError<Self::Error>> {
+        if self.node_index(&addr) != Some(self.local) {
+            return Err(TransportError::MultiaddrNotSupported(addr));
+        }
+        let inner = MemoryTransport.listen_on(addr).map_err(|e| e.map(to_io_error))?;
+        Ok(SimListener { inner, local: self.local, conditions: self.conditions })
+    }
+
+    fn dial(self, addr: Multiaddr) -> Result<Self::Dial, TransportError<Self::Error>> {
+        let remote = match self.node_index(&addr) {
+            Some(remote) => remote,
+            None => return Err(TransportError::MultiaddrNotSupported(addr)),
+        };
+        let local = self.local;
+        let until = {
+            let conditions = self.conditions.lock();
+            if !conditions.reachable(local, remote) {
+                return Err(TransportError::Other(unreachable()));
+            }
+            conditions.now + conditions.latency(local, remote)
+        };
+        let dial = MemoryTransport.dial(addr).map_err(|e| e.map(to_io_error))?;
+        let conditions = self.conditions.clone();
+        let future = SimDelay { until, conditions: self.conditions }
+            .and_then(move |()| dial.map_err(to_io_error))
+            // Tell the listener who we are, as the in-memory transport doesn't.
+            .and_then(move |channel| tokio_io::io::write_all(channel, (local as u64).to_be_bytes()))
+            .and_then(move |(channel, _)| {
+                let stream = SimStream::new(channel, local, remote, conditions);
+                stream.check()?;
+                Ok(stream)
+            });
+        Ok(Box::new(future))
+    }
+}
+
+/// Listener of a [`SimTransport`].
+pub struct SimListener {
+    inner: memory::Listener,
+    local: usize,
+    conditions: Arc<Mutex<Conditions>>,
+}
+
+impl Stream for SimListener {
+    type Item = ListenerEvent<SimUpgrade>;
+    type Error = io::Error;
+
+    fn poll(&mut self) -> Poll<Option<Self::Item>, Self::Error> {
+        let event = match try_ready!(self.inner.poll().map_err(to_io_error)) {
+            Some(event) => event,
+            None => return Ok(Async::Ready(None)),
+        };
+        let local = self.local;
+        let conditions = self.conditions.clone();
+        Ok(Async::Ready(Some(event.map(move |upgrade| {
+            let future = upgrade
+                .map_err(to_io_error)
+                .and_then(|channel| tokio_io::io::read_exact(channel, [0; 8]))
+                .and_then(move |(channel, remote)| {
+                    let remote = u64::from_be_bytes(remote) as usize;
+                    if remote >= conditions.lock().online.len() {
+                        return Err(io::Error::new(io::ErrorKind::InvalidData, "unknown node"));
+                    }
+                    let stream = SimStream::new(channel, local, remote, conditions);
+                    stream.check()?;
+                    Ok(stream)
+                });
+            let future: SimUpgrade = Box::new(future);
+            future
+        }))))
+    }
+}
+
+/// Future that resolves once the virtual time has reached a given tick.
+struct SimDelay {
+    until: u64,
+    conditions: Arc<Mutex<Conditions>>,
+}
+
+impl Future for SimDelay {
+    type Item = ();
+    type Error = io::Error;
+
+    fn poll(&mut self) -> Poll<(), io::Error> {
+        if self.conditions.lock().now >= self.until {
+            Ok(Async::Ready(()))
+        } else {
+            // The virtual time doesn't wake up tasks; ask to be polled again.
+            task::current().notify();
+            Ok(Async::NotReady)
+        }
+    }
+}
+
+/// Connection between two simulated nodes.
+///
+/// Delays the incoming data by the latency of the link, and fails with `ConnectionReset` once
+/// the nodes can no longer reach each other.
+pub struct SimStream {
+    inner: memory::Channel<Bytes>,
+    local: usize,
+    remote: usize,
+    conditions: Arc<Mutex<Conditions>>,
+    /// Data received from the remote, with the tick at which it becomes readable.
+    pending: VecDeque<(u64, Vec<u8>)>,
+    /// Tick at which the end of the stream becomes visible, if the remote has closed it.
+    eof: Option<u64>,
+}
+
+impl SimStream {
+    fn new(inner: memory::Channel<Bytes>, local: usize, remote: usize, conditions: Arc<Mutex<Conditions>>) -> Self {
+        SimStream { inner, local, remote, conditions, pending: VecDeque::new(), eof: None }
+    }
+
+    /// Returns the index of the node at the other end of the connection.
+    pub fn remote(&self) -> usize {
+        self.remote
+    }
+
+    fn check(&self) -> io::Result<()> {
+        if self.conditions.lock().reachable(self.local, self.remote) {
+            Ok(())
+        } else {
+            Err(io::Error::new(io::ErrorKind::ConnectionReset, "link broken by the simulation"))
+        }
+    }
+}
+
+impl io::Read for SimStream {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        self.check()?;
+        let (now, arrival) = {
+            let conditions = self.conditions.lock();
+            (conditions.now, conditions.now + conditions.latency(self.local, self.remote))
+        };
+
+        while self.eof.is_none() {
+            let mut data = [0; 4096];
+            match self.inner.read(&mut data) {
+                Ok(0) => self.eof = Some(arrival),
+                Ok(n) => self.pending.push_back((arrival, data[..n].to_vec())),
+                Err(ref e) if e.kind() == io::ErrorKind::WouldBlock => break,
+                Err(e) => return Err(e),
+            }
+        }
+
+        match self.pending.front_mut() {
+            Some((at, data)) if *at <= now => {
+                let n = cmp::min(buf.len(), data.len());
+                buf[..n].copy_from_slice(&data[..n]);
+                data.drain(..n);
+                if data.is_empty() {
+                    self.pending.pop_front();
+                }
+                return Ok(n);
+            }
+            Some(_) => {}
+            None => match self.eof {
+                Some(at) if at <= now => return Ok(0),
+                Some(_) => {}
+                // The inner channel has registered the task.
+                None => return Err(io::ErrorKind::WouldBlock.into()),
+            },
+        }
+
+        // The data is still in flight; the virtual time doesn't wake up tasks.
+        task::current().notify();
+        Err(io::ErrorKind::WouldBlock.into())
+    }
+}
+
+impl AsyncRead for SimStream {}
+
+impl io::Write for SimStream {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.check()?;
+        self.inner.write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.check()?;
+        self.inner.flush()
+    }
+}
+
+impl AsyncWrite for SimStream {
+    fn shutdown(&mut self) -> Poll<(), io::Error> {
+        self.inner.shutdown()
+    }
+}
@@ -18,9 +18,9 @@
 // FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
 // DEALINGS IN THE SOFTWARE.
 
-use libp2p_core::Endpoint;
+use libp2p_core::{BufferPool, Endpoint};
 use std::io::{Error as IoError, ErrorKind as IoErrorKind};
-use std::mem;
+use std::{cmp, mem};
 use bytes::{BufMut, Bytes, BytesMut};
 use tokio_io::codec::{Decoder, Encoder};
 use unsigned_varint::{codec, encode};
@@ -30,6 +30,11 @@ use unsigned_varint::{codec, encode};
 // send a 4 TB-long packet full of zeroes that we kill our process with an OOM error.
 pub(crate) const MAX_FRAME_SIZE: usize = 1024 * 1024;
 
+// Maximum number of bytes reserved at once for the payload of a frame. The payload can be up to
+// `MAX_FRAME_SIZE`, but we don't want a remote to make us commit that much memory with just a
+// header, before having sent any of the payload.
+const MAX_RESERVE: usize = 64 * 1024;
+
 #[derive(Debug, Clone)]
 pub enum Elem {
     Open { substream_id: u32 },
@@ -81,6 +86,8 @@ impl Elem {
 pub struct Codec {
     varint_decoder: codec::Uvi<u32>,
     decoder_state: CodecDecodeState,
+    /// Pool to give the data of the encoded frames back to, if any.
+    buffer_pool: Option<BufferPool>,
 }
 
 #[derive(Debug, Clone)]
@@ -93,9 +100,15 @@ enum CodecDecodeState {
 
 impl Codec {
     pub fn new() -> Codec {
+        Codec::with_buffer_pool(None)
+    }
+
+    /// Creates a codec that gives the data of the frames back to `buffer_pool` once encoded.
+    pub fn with_buffer_pool(buffer_pool: Option<BufferPool>) -> Codec {
         Codec {
             varint_decoder: codec::Uvi::default(),
             decoder_state: CodecDecodeState::Begin,
+            buffer_pool,
         }
     }
 }
@@ -137,7 +150,7 @@ impl Decoder for Codec {
                 CodecDecodeState::HasHeaderAndLen(header, len) => {
                     if src.len() < len {
                         self.decoder_state = CodecDecodeState::HasHeaderAndLen(header, len);
-                        let to_reserve = len - src.len();
+                        let to_reserve = cmp::min(len - src.len(), MAX_RESERVE);
                         src.reserve(to_reserve);
                         return Ok(None);
                     }
@@ -213,7 +226,10 @@ impl Encoder for Codec {
         dst.reserve(header_bytes.len() + data_len_bytes.len() + data_len);
         dst.put(header_bytes);
         dst.put(data_len_bytes);
-        dst.put(data);
+        dst.put(&data[..]);
+        if let Some(pool) = &self.buffer_pool {
+            pool.recycle(data);
+        }
         Ok(())
     }
 }
@@ -238,4 +254,17 @@ mod tests {
         let ok_msg = Elem::Data{ substream_id: 123, endpoint, data };
         assert!(enc.encode(ok_msg, &mut out).is_ok());
     }
+
+    #[test]
+    fn encoded_data_is_recycled() {
+        let pool = BufferPool::new(64, 4);
+        let mut enc = Codec::with_buffer_pool(Some(pool.clone()));
+        let mut data = pool.get();
+        data.extend_from_slice(b"hello");
+        let msg = Elem::Data { substream_id: 1, endpoint: Endpoint::Dialer, data: data.freeze() };
+        let mut out = BytesMut::new();
+        enc.encode(msg, &mut out).unwrap();
+        assert_eq!(&out[..], &[(1 << 3) | 2, 5, b'h', b'e', b'l', b'l', b'o'][..]);
+        assert_eq!(pool.num_available(), 1);
+    }
 }
@@ -25,11 +25,12 @@ use std::io::{Error as IoError, ErrorKind as IoErrorKind};
 use std::sync::{atomic::AtomicUsize, atomic::Ordering, Arc};
 use bytes::Bytes;
 use libp2p_core::{
+    BufferPool,
     Endpoint,
     StreamMuxer,
     upgrade::{InboundUpgrade, OutboundUpgrade, UpgradeInfo, Negotiated}
 };
-use log::{debug, trace};
+use tracing::{debug, trace};
 use parking_lot::Mutex;
 use fnv::{FnvHashMap, FnvHashSet};
 use futures::{prelude::*, executor, future, stream::Fuse, task, task_local, try_ready};
@@ -48,6 +49,8 @@ pub struct MplexConfig {
     /// When sending data, split it into frames whose maximum size is this value
     /// (max 1MByte, as per the Mplex spec).
     split_send_size: usize,
+    /// Where the buffers of the outgoing data frames come from, if any.
+    buffer_pool: Option<BufferPool>,
 }
 
 impl MplexConfig {
@@ -93,6 +96,15 @@ impl MplexConfig {
         self
     }
 
+    /// Takes the buffers of the outgoing data frames from the given pool, and gives them back
+    /// once they have been encoded, instead of allocating a buffer for each frame.
+    ///
+    /// The pool can be shared with other connections and protocols.
+    pub fn buffer_pool(&mut self, pool: BufferPool) -> &mut Self {
+        self.buffer_pool = Some(pool);
+        self
+    }
+
     #[inline]
     fn upgrade<C>(self, i: C) -> Multiplex<C>
     where
@@ -102,7 +114,7 @@ impl MplexConfig {
         Multiplex {
             inner: Mutex::new(MultiplexInner {
                 error: Ok(()),
-                inner: executor::spawn(Framed::new(i, codec::Codec::new()).fuse()),
+                inner: executor::spawn(Framed::new(i, codec::Codec::with_buffer_pool(self.buffer_pool.clone())).fuse()),
                 config: self,
                 buffer: Vec::with_capacity(cmp::min(max_buffer_len, 512)),
                 opened_substreams: Default::default(),
@@ -128,6 +140,7 @@ impl Default for MplexConfig {
             max_buffer_len: 4096,
             max_buffer_behaviour: MaxBufferBehaviour::CloseAll,
             split_send_size: 1024,
+            buffer_pool: None,
         }
     }
 }
@@ -302,7 +315,7 @@ where C: AsyncRead + AsyncWrite,
         match elem {
             codec::Elem::Open { substream_id } => {
                 if !inner.opened_substreams.insert((substream_id, Endpoint::Listener)) {
-                    debug!("Received open message for substream {} which was already open", substream_id)
+                    debug!(substream = substream_id, "Received open message for substream which was already open")
                 }
             }
             codec::Elem::Close { substream_id, endpoint, .. } | codec::Elem::Reset { substream_id, endpoint, .. } => {
@@ -362,7 +375,7 @@ where C: AsyncRead + AsyncWrite
             }
         }));
 
-        debug!("Successfully opened inbound substream {}", num);
+        debug!(substream = num, "Successfully opened inbound substream");
         Ok(Async::Ready(Substream {
             current_data: Bytes::new(),
             num,
@@ -418,7 +431,7 @@ where C: AsyncRead + AsyncWrite
                     return Ok(Async::NotReady)
                 },
                 Err(err) => {
-                    debug!("Failed to open outbound substream {}", substream.num);
+                    debug!(substream = substream.num, "Failed to open outbound substream");
                     inner.buffer.retain(|elem| {
                         elem.substream_id() != substream.num || elem.endpoint() == Some(Endpoint::Dialer)
                     });
@@ -434,7 +447,7 @@ where C: AsyncRead + AsyncWrite
                     substream.state = OutboundSubstreamState::Flush;
                 },
                 OutboundSubstreamState::Flush => {
-                    debug!("Successfully opened outbound substream {}", substream.num);
+                    debug!(substream = substream.num, "Successfully opened outbound substream");
                     substream.state = OutboundSubstreamState::Done;
                     return Ok(Async::Ready(Substream {
                         num: substream.num,
@@ -519,10 +532,18 @@ where C: AsyncRead + AsyncWrite
         let mut inner = self.inner.lock();
 
         let to_write = cmp::min(buf.len(), inner.config.split_send_size);
+        let data = match inner.config.buffer_pool {
+            Some(ref pool) => {
+                let mut data = pool.get();
+                data.extend_from_slice(&buf[..to_write]);
+                data.freeze()
+            },
+            None => From::from(&buf[..to_write]),
+        };
 
         let elem = codec::Elem::Data {
             substream_id: substream.num,
-            data: From::from(&buf[..to_write]),
+            data,
             endpoint: substream.endpoint,
         };
 
@@ -23,7 +23,7 @@
 
 use futures::{future::{self, FutureResult}, prelude::*};
 use libp2p_core::upgrade::{InboundUpgrade, OutboundUpgrade, UpgradeInfo, Negotiated};
-use log::debug;
+use tracing::debug;
 use std::{io, iter, sync::atomic};
 use std::io::{Error as IoError};
 use tokio_io::{AsyncRead, AsyncWrite};
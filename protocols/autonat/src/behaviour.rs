@@ -0,0 +1,519 @@
+// Copyright 2019 Parity Technologies (UK) Ltd.
+//
+// Permission is hereby granted, free of charge, to any person obtaining a
+// copy of this software and associated documentation files (the "Software"),
+// to deal in the Software without restriction, including without limitation
+// the rights to use, copy, modify, merge, publish, distribute, sublicense,
+// and/or sell copies of the Software, and to permit persons to whom the
+// Software is furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS
+// OR IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING
+// FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
+// DEALINGS IN THE SOFTWARE.
+
+use crate::AutoNatConfig;
+use crate::protocol::{AutoNatCodec, DialRequest, DialResponse, ResponseError, PROTOCOL_NAME};
+use futures::prelude::*;
+use libp2p_core::{ConnectedPoint, Multiaddr, PeerId, multiaddr::Protocol};
+use libp2p_request_response::{
+    RequestId,
+    RequestProtocol,
+    RequestResponse,
+    RequestResponseConfig,
+    RequestResponseEvent,
+    RequestResponseHandler,
+    RequestResponseHandlerEvent,
+    RequestResponseMessage,
+    ResponseChannel
+};
+use libp2p_swarm::{ConnectionId, NetworkBehaviour, NetworkBehaviourAction, PollParameters};
+use smallvec::SmallVec;
+use std::{collections::{HashMap, VecDeque}, error, iter, mem, net::IpAddr};
+use tokio_io::{AsyncRead, AsyncWrite};
+use wasm_timer::{Delay, Instant};
+
+/// Reachability of the local node, as determined by the probes.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum NatStatus {
+    /// A remote could dial us at the given address.
+    Public(Multiaddr),
+    /// Remotes failed to dial us at any of our addresses.
+    Private,
+    /// No probe has succeeded yet.
+    Unknown,
+}
+
+impl NatStatus {
+    /// Returns true if the status is `Public`.
+    pub fn is_public(&self) -> bool {
+        match self {
+            NatStatus::Public(_) => true,
+            _ => false,
+        }
+    }
+
+    /// Returns true if both statuses are of the same kind, regardless of the public address.
+    fn same_kind(&self, other: &NatStatus) -> bool {
+        mem::discriminant(self) == mem::discriminant(other)
+    }
+}
+
+/// Event generated by the `AutoNat` network behaviour.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum AutoNatEvent {
+    /// The NAT status of the local node changed.
+    StatusChanged { old: NatStatus, new: NatStatus },
+}
+
+/// Network behaviour determining whether the local node is reachable from the outside.
+///
+/// At regular intervals, a connected peer, or a server added with [`AutoNat::add_server`], is
+/// asked to dial us back at our external and listened addresses. The answers to these probes
+/// determine our [`NatStatus`], which only changes once the probes disagree with it more times
+/// than our confidence in it.
+///
+/// The behaviour also answers the probes of other peers. It only dials the addresses whose IP
+/// address is the one it observes for the connection to the peer, so that it can't be used to
+/// make us dial third parties.
+pub struct AutoNat<TSubstream> {
+    /// The behaviour sending and answering the dial requests.
+    inner: RequestResponse<TSubstream, AutoNatCodec>,
+    /// Configuration options.
+    config: AutoNatConfig,
+    /// Our current NAT status.
+    nat_status: NatStatus,
+    /// Number of probes that confirmed the current status, up to `confidence_max`.
+    confidence: usize,
+    /// Peers explicitly added as servers.
+    servers: SmallVec<[PeerId; 4]>,
+    /// The established connections to each peer, with the address of the remote.
+    connected: HashMap<PeerId, SmallVec<[(ConnectionId, Multiaddr); 2]>>,
+    /// Fires when the next probe must be sent.
+    next_probe: Delay,
+    /// Incremented at each probe in order to rotate through the servers.
+    probe_counter: usize,
+    /// The probe waiting for a response, if any.
+    pending_probe: Option<RequestId>,
+    /// Probes of other peers whose addresses we are dialing.
+    dial_backs: Vec<DialBack>,
+    /// Queue of actions to return to the swarm.
+    pending_actions: VecDeque<NetworkBehaviourAction<RequestProtocol<AutoNatCodec>, AutoNatEvent>>,
+}
+
+/// A probe of another peer whose addresses we are dialing.
+struct DialBack {
+    peer: PeerId,
+    /// Addresses that haven't failed yet.
+    addrs: SmallVec<[Multiaddr; 4]>,
+    channel: ResponseChannel<DialResponse>,
+}
+
+impl<TSubstream> AutoNat<TSubstream> {
+    /// Creates an `AutoNat` behaviour with the given configuration.
+    pub fn new(config: AutoNatConfig) -> Self {
+        let inner_config = RequestResponseConfig::default().with_request_timeout(config.timeout);
+        AutoNat {
+            inner: RequestResponse::new(AutoNatCodec, iter::once(PROTOCOL_NAME), inner_config),
+            next_probe: Delay::new(Instant::now() + config.boot_delay),
+            config,
+            nat_status: NatStatus::Unknown,
+            confidence: 0,
+            servers: SmallVec::new(),
+            connected: HashMap::new(),
+            probe_counter: 0,
+            pending_probe: None,
+            dial_backs: Vec::new(),
+            pending_actions: VecDeque::new(),
+        }
+    }
+
+    /// Returns our current NAT status.
+    pub fn nat_status(&self) -> &NatStatus {
+        &self.nat_status
+    }
+
+    /// Returns the number of probes that confirmed the current status, up to the configured
+    /// maximum.
+    pub fn confidence(&self) -> usize {
+        self.confidence
+    }
+
+    /// Returns the address at which a remote could dial us, if our status is public.
+    pub fn public_address(&self) -> Option<&Multiaddr> {
+        match &self.nat_status {
+            NatStatus::Public(addr) => Some(addr),
+            _ => None,
+        }
+    }
+
+    /// Adds a peer to ask to dial us back, along with an address at which it can be reached.
+    pub fn add_server(&mut self, peer: PeerId, address: Option<Multiaddr>) {
+        if let Some(address) = address {
+            self.inner.add_address(&peer, address);
+        }
+        if !self.servers.contains(&peer) {
+            self.servers.push(peer);
+        }
+    }
+
+    /// Removes a peer added with `add_server`.
+    pub fn remove_server(&mut self, peer: &PeerId) {
+        self.servers.retain(|p| p != peer);
+    }
+
+    /// Returns the peer to send the next probe to, if any.
+    fn next_server(&mut self) -> Option<PeerId> {
+        let mut candidates = self.servers.iter().cloned().collect::<Vec<_>>();
+        if self.config.use_connected {
+            for (peer, connections) in &self.connected {
+                let direct = connections.iter().any(|(_, addr)| !addr.is_relayed());
+                if direct && !candidates.contains(peer) {
+                    candidates.push(peer.clone());
+                }
+            }
+        }
+        if candidates.is_empty() {
+            return None
+        }
+        self.probe_counter = self.probe_counter.wrapping_add(1);
+        Some(candidates.swap_remove(self.probe_counter % candidates.len()))
+    }
+
+    /// Processes the response to one of our probes.
+    fn on_probe_result(&mut self, result: Result<Multiaddr, ResponseError>) {
+        let new = match result {
+            Ok(addr) => NatStatus::Public(addr),
+            Err(ResponseError::DialError) => NatStatus::Private,
+            Err(error) => {
+                log::debug!("Inconclusive NAT probe: {}", error);
+                return
+            }
+        };
+
+        if new.same_kind(&self.nat_status) {
+            self.confidence = (self.confidence + 1).min(self.config.confidence_max);
+            // The public address may have changed without changing our reachability.
+            self.nat_status = new;
+        } else if self.confidence > 0 {
+            self.confidence -= 1;
+        } else {
+            let old = mem::replace(&mut self.nat_status, new.clone());
+            self.pending_actions.push_back(NetworkBehaviourAction::GenerateEvent(
+                AutoNatEvent::StatusChanged { old, new }
+            ));
+        }
+    }
+
+    /// Schedules the next probe, sooner if we aren't confident in our status.
+    fn schedule_probe(&mut self) {
+        let delay = if self.confidence >= self.config.confidence_max {
+            self.config.refresh_interval
+        } else {
+            self.config.retry_interval
+        };
+        self.next_probe.reset(Instant::now() + delay);
+    }
+
+    /// Processes a probe received from another peer.
+    fn on_dial_request(&mut self, peer: PeerId, request: DialRequest, channel: ResponseChannel<DialResponse>) {
+        if request.peer_id != peer {
+            self.refuse(channel, ResponseError::BadRequest, "peer ID mismatch");
+            return
+        }
+        if self.dial_backs.len() >= self.config.max_dial_backs {
+            self.refuse(channel, ResponseError::DialRefused, "too many dial backs");
+            return
+        }
+
+        // Only dial the IP addresses we observe for the peer.
+        let observed = self.connected.get(&peer)
+            .into_iter()
+            .flat_map(|conns| conns.iter())
+            .filter_map(|(_, addr)| ip_of(addr))
+            .collect::<SmallVec<[IpAddr; 2]>>();
+        let addrs = request.addrs.into_iter()
+            .filter(|a| !a.is_relayed() && ip_of(a).map_or(false, |ip| observed.contains(&ip)))
+            .take(self.config.max_peer_addresses)
+            .collect::<SmallVec<[Multiaddr; 4]>>();
+        if addrs.is_empty() {
+            self.refuse(channel, ResponseError::DialRefused, "no dialable address");
+            return
+        }
+
+        for address in &addrs {
+            log::debug!("Dialing back {:?} at {}", peer, address);
+            self.pending_actions.push_back(NetworkBehaviourAction::DialAddress { address: address.clone() });
+        }
+        self.dial_backs.push(DialBack { peer, addrs, channel });
+    }
+
+    /// Answers a probe with an error.
+    fn refuse(&mut self, channel: ResponseChannel<DialResponse>, error: ResponseError, text: &str) {
+        log::debug!("Refusing to dial back {:?}: {}", channel.peer(), text);
+        let response = DialResponse { result: Err(error), status_text: Some(text.to_owned()) };
+        self.inner.send_response(channel, response);
+    }
+
+    /// Processes an event of the request-response behaviour.
+    fn on_event(&mut self, event: RequestResponseEvent<DialRequest, DialResponse>) {
+        match event {
+            RequestResponseEvent::Message { peer, message: RequestResponseMessage::Request { request, channel, .. } } => {
+                self.on_dial_request(peer, request, channel);
+            }
+            RequestResponseEvent::Message { message: RequestResponseMessage::Response { request_id, response }, .. } => {
+                if self.pending_probe == Some(request_id) {
+                    self.pending_probe = None;
+                    self.on_probe_result(response.result);
+                    self.schedule_probe();
+                }
+            }
+            RequestResponseEvent::OutboundFailure { peer, request_id, error } => {
+                if self.pending_probe == Some(request_id) {
+                    log::debug!("NAT probe to {:?} failed: {}", peer, error);
+                    self.pending_probe = None;
+                    self.next_probe.reset(Instant::now() + self.config.retry_interval);
+                }
+            }
+            RequestResponseEvent::InboundFailure { peer, error, .. } => {
+                log::debug!("Failed to answer the NAT probe of {:?}: {}", peer, error);
+            }
+        }
+    }
+}
+
+impl<TSubstream> NetworkBehaviour for AutoNat<TSubstream>
+where
+    TSubstream: AsyncRead + AsyncWrite,
+{
+    type ProtocolsHandler = RequestResponseHandler<TSubstream, AutoNatCodec>;
+    type OutEvent = AutoNatEvent;
+
+    fn new_handler(&mut self) -> Self::ProtocolsHandler {
+        self.inner.new_handler()
+    }
+
+    fn addresses_of_peer(&mut self, peer_id: &PeerId) -> Vec<Multiaddr> {
+        self.inner.addresses_of_peer(peer_id)
+    }
+
+    fn inject_connected(&mut self, peer_id: PeerId, endpoint: ConnectedPoint) {
+        self.inner.inject_connected(peer_id, endpoint)
+    }
+
+    fn inject_disconnected(&mut self, peer_id: &PeerId, endpoint: ConnectedPoint) {
+        self.connected.remove(peer_id);
+        self.inner.inject_disconnected(peer_id, endpoint)
+    }
+
+    fn inject_connection_established(&mut self, peer_id: &PeerId, connection: &ConnectionId, endpoint: &ConnectedPoint) {
+        self.inner.inject_connection_established(peer_id, connection, endpoint);
+
+        let remote_addr = match endpoint {
+            ConnectedPoint::Dialer { address } => address,
+            ConnectedPoint::Listener { send_back_addr, .. } => send_back_addr,
+        };
+        self.connected.entry(peer_id.clone()).or_default().push((*connection, remote_addr.clone()));
+
+        if let ConnectedPoint::Dialer { address } = endpoint {
+            let position = self.dial_backs.iter()
+                .position(|d| &d.peer == peer_id && d.addrs.contains(address));
+            if let Some(position) = position {
+                let dial_back = self.dial_backs.swap_remove(position);
+                let response = DialResponse { result: Ok(address.clone()), status_text: None };
+                self.inner.send_response(dial_back.channel, response);
+            }
+        }
+    }
+
+    fn inject_connection_closed(&mut self, peer_id: &PeerId, connection: &ConnectionId, endpoint: &ConnectedPoint) {
+        if let Some(connections) = self.connected.get_mut(peer_id) {
+            connections.retain(|(c, _)| c != connection);
+            if connections.is_empty() {
+                self.connected.remove(peer_id);
+            }
+        }
+        self.inner.inject_connection_closed(peer_id, connection, endpoint)
+    }
+
+    fn inject_node_event(&mut self, peer_id: PeerId, event: RequestResponseHandlerEvent<AutoNatCodec>) {
+        self.inner.inject_node_event(peer_id, event)
+    }
+
+    fn inject_connection_event(&mut self, peer_id: PeerId, connection: ConnectionId, event: RequestResponseHandlerEvent<AutoNatCodec>) {
+        self.inner.inject_connection_event(peer_id, connection, event)
+    }
+
+    fn inject_addr_reach_failure(&mut self, peer_id: Option<&PeerId>, addr: &Multiaddr, error: &dyn error::Error) {
+        self.inner.inject_addr_reach_failure(peer_id, addr, error);
+
+        let mut n = 0;
+        while n < self.dial_backs.len() {
+            self.dial_backs[n].addrs.retain(|a| a != addr);
+            if self.dial_backs[n].addrs.is_empty() {
+                let dial_back = self.dial_backs.swap_remove(n);
+                let response = DialResponse { result: Err(ResponseError::DialError), status_text: None };
+                self.inner.send_response(dial_back.channel, response);
+            } else {
+                n += 1;
+            }
+        }
+    }
+
+    fn inject_dial_failure(&mut self, peer_id: &PeerId) {
+        self.inner.inject_dial_failure(peer_id)
+    }
+
+    fn poll(&mut self, params: &mut impl PollParameters)
+        -> Async<NetworkBehaviourAction<RequestProtocol<AutoNatCodec>, Self::OutEvent>>
+    {
+        loop {
+            if let Some(action) = self.pending_actions.pop_front() {
+                return Async::Ready(action)
+            }
+
+            if self.pending_probe.is_none() {
+                if let Ok(Async::Ready(())) = self.next_probe.poll() {
+                    let addrs = local_addresses(params);
+                    match self.next_server() {
+                        Some(server) if !addrs.is_empty() => {
+                            log::debug!("Asking {:?} to dial us back", server);
+                            let request = DialRequest { peer_id: params.local_peer_id().clone(), addrs };
+                            self.pending_probe = Some(self.inner.send_request(&server, request));
+                        }
+                        _ => self.next_probe.reset(Instant::now() + self.config.retry_interval),
+                    }
+                }
+            }
+
+            let action = match self.inner.poll(params) {
+                Async::NotReady => return Async::NotReady,
+                Async::Ready(NetworkBehaviourAction::GenerateEvent(event)) => {
+                    self.on_event(event);
+                    continue
+                }
+                Async::Ready(NetworkBehaviourAction::DialAddress { address }) => {
+                    NetworkBehaviourAction::DialAddress { address }
+                }
+                Async::Ready(NetworkBehaviourAction::DialPeer { peer_id }) => {
+                    NetworkBehaviourAction::DialPeer { peer_id }
+                }
+                Async::Ready(NetworkBehaviourAction::SendEvent { peer_id, event }) => {
+                    NetworkBehaviourAction::SendEvent { peer_id, event }
+                }
+                Async::Ready(NetworkBehaviourAction::SendEventToConnection { peer_id, connection, event }) => {
+                    NetworkBehaviourAction::SendEventToConnection { peer_id, connection, event }
+                }
+                Async::Ready(NetworkBehaviourAction::ReportObservedAddr { address, observer }) => {
+                    NetworkBehaviourAction::ReportObservedAddr { address, observer }
+                }
+                Async::Ready(NetworkBehaviourAction::ReportRemoteProtocols { peer_id, protocols }) => {
+                    NetworkBehaviourAction::ReportRemoteProtocols { peer_id, protocols }
+                }
+                Async::Ready(NetworkBehaviourAction::ReportRoundTripTime { peer_id, rtt }) => {
+                    NetworkBehaviourAction::ReportRoundTripTime { peer_id, rtt }
+                }
+                Async::Ready(NetworkBehaviourAction::ReportTransfer { peer_id, bytes_received, bytes_sent, duration }) => {
+                    NetworkBehaviourAction::ReportTransfer { peer_id, bytes_received, bytes_sent, duration }
+                }
+                Async::Ready(NetworkBehaviourAction::ReportExternalAddr { address, source }) => {
+                    NetworkBehaviourAction::ReportExternalAddr { address, source }
+                }
+                Async::Ready(NetworkBehaviourAction::RemoveExternalAddr { address }) => {
+                    NetworkBehaviourAction::RemoveExternalAddr { address }
+                }
+            };
+            return Async::Ready(action)
+        }
+    }
+}
+
+/// Returns the addresses to probe: the external addresses followed by the listened addresses,
+/// without the relayed ones.
+fn local_addresses(params: &mut impl PollParameters) -> Vec<Multiaddr> {
+    let mut addrs = params.external_addresses()
+        .filter(|a| !a.is_relayed())
+        .collect::<Vec<_>>();
+    for addr in params.listened_addresses() {
+        if !addr.is_relayed() && !addrs.contains(&addr) {
+            addrs.push(addr);
+        }
+    }
+    addrs
+}
+
+/// Returns the IP address of a multiaddress, if it starts with one.
+fn ip_of(addr: &Multiaddr) -> Option<IpAddr> {
+    match addr.iter().next() {
+        Some(Protocol::Ip4(ip)) => Some(ip.into()),
+        Some(Protocol::Ip6(ip)) => Some(ip.into()),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use libp2p_core::multiaddr::multiaddr;
+    use std::io;
+
+    type TestAutoNat = AutoNat<io::Cursor<Vec<u8>>>;
+
+    fn public() -> NatStatus {
+        NatStatus::Public(multiaddr![Ip4([1, 2, 3, 4]), Tcp(4001u16)])
+    }
+
+    fn take_events(autonat: &mut TestAutoNat) -> Vec<AutoNatEvent> {
+        autonat.pending_actions.drain(..)
+            .filter_map(|a| match a {
+                NetworkBehaviourAction::GenerateEvent(e) => Some(e),
+                _ => None,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn first_result_changes_status() {
+        let mut autonat = TestAutoNat::new(AutoNatConfig::default());
+        autonat.on_probe_result(Err(ResponseError::DialError));
+        assert_eq!(autonat.nat_status(), &NatStatus::Private);
+        assert_eq!(take_events(&mut autonat), vec![
+            AutoNatEvent::StatusChanged { old: NatStatus::Unknown, new: NatStatus::Private }
+        ]);
+    }
+
+    #[test]
+    fn confidence_delays_status_change() {
+        let mut autonat = TestAutoNat::new(AutoNatConfig::default());
+        autonat.on_probe_result(Ok(multiaddr![Ip4([1, 2, 3, 4]), Tcp(4001u16)]));
+        autonat.on_probe_result(Ok(multiaddr![Ip4([1, 2, 3, 4]), Tcp(4001u16)]));
+        autonat.on_probe_result(Ok(multiaddr![Ip4([1, 2, 3, 4]), Tcp(4001u16)]));
+        assert_eq!(autonat.confidence(), 2);
+        take_events(&mut autonat);
+
+        autonat.on_probe_result(Err(ResponseError::DialError));
+        autonat.on_probe_result(Err(ResponseError::DialError));
+        assert_eq!(autonat.nat_status(), &public());
+        assert!(take_events(&mut autonat).is_empty());
+
+        autonat.on_probe_result(Err(ResponseError::DialError));
+        assert_eq!(autonat.nat_status(), &NatStatus::Private);
+        assert_eq!(take_events(&mut autonat), vec![
+            AutoNatEvent::StatusChanged { old: public(), new: NatStatus::Private }
+        ]);
+    }
+
+    #[test]
+    fn refused_probe_is_inconclusive() {
+        let mut autonat = TestAutoNat::new(AutoNatConfig::default());
+        autonat.on_probe_result(Err(ResponseError::DialRefused));
+        assert_eq!(autonat.nat_status(), &NatStatus::Unknown);
+        assert!(take_events(&mut autonat).is_empty());
+    }
+}
@@ -0,0 +1,127 @@
+// Copyright 2019 Parity Technologies (UK) Ltd.
+//
+// Permission is hereby granted, free of charge, to any person obtaining a
+// copy of this software and associated documentation files (the "Software"),
+// to deal in the Software without restriction, including without limitation
+// the rights to use, copy, modify, merge, publish, distribute, sublicense,
+// and/or sell copies of the Software, and to permit persons to whom the
+// Software is furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS
+// OR IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING
+// FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
+// DEALINGS IN THE SOFTWARE.
+
+//! Implementation of the [AutoNAT] protocol.
+//!
+//! A node behind a NAT or a firewall can't be dialed by other peers, which affects how it should
+//! behave: it should, for example, listen through a relay and refrain from acting as a DHT
+//! server. The [`AutoNat`] network behaviour determines whether the local node is reachable by
+//! asking other peers to dial it back, and reports changes of its [`NatStatus`] with
+//! [`AutoNatEvent::StatusChanged`].
+//!
+//! The behaviour is built on the `libp2p-request-response` crate, and answers the dial requests
+//! of other peers as well.
+//!
+//! [AutoNAT]: https://github.com/libp2p/specs/tree/master/autonat
+
+mod behaviour;
+mod protocol;
+
+/// Protobuf messages of the protocol, generated from `structs.proto`.
+mod proto {
+    include!(concat!(env!("OUT_DIR"), "/structs.rs"));
+}
+
+pub use behaviour::{AutoNat, AutoNatEvent, NatStatus};
+pub use protocol::{AutoNatCodec, DialRequest, DialResponse, ResponseError, PROTOCOL_NAME};
+
+use std::time::Duration;
+
+/// Configuration of the [`AutoNat`] network behaviour.
+#[derive(Debug, Clone)]
+pub struct AutoNatConfig {
+    timeout: Duration,
+    boot_delay: Duration,
+    retry_interval: Duration,
+    refresh_interval: Duration,
+    confidence_max: usize,
+    use_connected: bool,
+    max_peer_addresses: usize,
+    max_dial_backs: usize,
+}
+
+impl Default for AutoNatConfig {
+    fn default() -> Self {
+        AutoNatConfig {
+            timeout: Duration::from_secs(30),
+            boot_delay: Duration::from_secs(15),
+            retry_interval: Duration::from_secs(90),
+            refresh_interval: Duration::from_secs(15 * 60),
+            confidence_max: 3,
+            use_connected: true,
+            max_peer_addresses: 16,
+            max_dial_backs: 30,
+        }
+    }
+}
+
+impl AutoNatConfig {
+    /// Sets how long a remote has to answer a probe. Defaults to 30 seconds.
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    /// Sets the delay before the first probe. Defaults to 15 seconds.
+    pub fn with_boot_delay(mut self, delay: Duration) -> Self {
+        self.boot_delay = delay;
+        self
+    }
+
+    /// Sets the interval between two probes while we aren't fully confident in our status, and
+    /// after a failed probe. Defaults to 90 seconds.
+    pub fn with_retry_interval(mut self, interval: Duration) -> Self {
+        self.retry_interval = interval;
+        self
+    }
+
+    /// Sets the interval between two probes once we are fully confident in our status.
+    /// Defaults to 15 minutes.
+    pub fn with_refresh_interval(mut self, interval: Duration) -> Self {
+        self.refresh_interval = interval;
+        self
+    }
+
+    /// Sets the number of consecutive probes that must contradict our status before it
+    /// changes, once fully confident. Defaults to 3.
+    pub fn with_confidence_max(mut self, confidence: usize) -> Self {
+        self.confidence_max = confidence;
+        self
+    }
+
+    /// Sets whether the peers we are connected to are asked to dial us back, in addition to
+    /// the servers added with [`AutoNat::add_server`]. Enabled by default.
+    pub fn with_use_connected(mut self, use_connected: bool) -> Self {
+        self.use_connected = use_connected;
+        self
+    }
+
+    /// Sets the maximum number of addresses of a peer that we dial back. Defaults to 16.
+    pub fn with_max_peer_addresses(mut self, max: usize) -> Self {
+        self.max_peer_addresses = max;
+        self
+    }
+
+    /// Sets the maximum number of peers that we dial back at the same time. Defaults to 30.
+    pub fn with_max_dial_backs(mut self, max: usize) -> Self {
+        self.max_dial_backs = max;
+        self
+    }
+}
@@ -0,0 +1,244 @@
+// Copyright 2019 Parity Technologies (UK) Ltd.
+//
+// Permission is hereby granted, free of charge, to any person obtaining a
+// copy of this software and associated documentation files (the "Software"),
+// to deal in the Software without restriction, including without limitation
+// the rights to use, copy, modify, merge, publish, distribute, sublicense,
+// and/or sell copies of the Software, and to permit persons to whom the
+// Software is furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS
+// OR IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING
+// FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
+// DEALINGS IN THE SOFTWARE.
+
+//! Messages of the AutoNAT protocol and their encoding.
+//!
+//! A peer sends a `DIAL` request carrying its addresses, and the remote answers with a
+//! `DIAL_RESPONSE` once it has tried to dial them back. Both messages are exchanged with the
+//! `libp2p-request-response` behaviour, each on a new substream.
+
+use crate::proto;
+use libp2p_core::{Multiaddr, PeerId};
+use libp2p_request_response::RequestResponseCodec;
+use prost::{DecodeError, Message};
+use std::{convert::TryFrom, fmt, io};
+
+/// Name of the protocol negotiated on the substreams.
+pub const PROTOCOL_NAME: &[u8] = b"/libp2p/autonat/1.0.0";
+
+const MESSAGE_TYPE_DIAL: i32 = 0;
+const MESSAGE_TYPE_DIAL_RESPONSE: i32 = 1;
+
+/// Request to dial back the addresses of the sender.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DialRequest {
+    /// Identity of the sender.
+    pub peer_id: PeerId,
+    /// Addresses to dial. Addresses that couldn't be decoded are skipped.
+    pub addrs: Vec<Multiaddr>,
+}
+
+/// Reason why a dial request failed.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum ResponseError {
+    /// None of the addresses could be dialed.
+    DialError,
+    /// The remote refused to dial the addresses.
+    DialRefused,
+    /// The request was malformed.
+    BadRequest,
+    /// The remote failed to process the request.
+    InternalError,
+}
+
+impl ResponseError {
+    fn code(self) -> i32 {
+        match self {
+            ResponseError::DialError => 100,
+            ResponseError::DialRefused => 101,
+            ResponseError::BadRequest => 200,
+            ResponseError::InternalError => 300,
+        }
+    }
+
+    fn from_code(code: i32) -> Option<Self> {
+        match code {
+            100 => Some(ResponseError::DialError),
+            101 => Some(ResponseError::DialRefused),
+            200 => Some(ResponseError::BadRequest),
+            300 => Some(ResponseError::InternalError),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for ResponseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ResponseError::DialError => write!(f, "E_DIAL_ERROR"),
+            ResponseError::DialRefused => write!(f, "E_DIAL_REFUSED"),
+            ResponseError::BadRequest => write!(f, "E_BAD_REQUEST"),
+            ResponseError::InternalError => write!(f, "E_INTERNAL_ERROR"),
+        }
+    }
+}
+
+/// Answer to a `DialRequest`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DialResponse {
+    /// The address that was successfully dialed, or the reason of the failure.
+    pub result: Result<Multiaddr, ResponseError>,
+    /// Human-readable description of the result.
+    pub status_text: Option<String>,
+}
+
+impl DialRequest {
+    /// Encodes the request as a `DIAL` message.
+    pub fn encode(&self) -> Vec<u8> {
+        encode_message(&proto::Message {
+            r#type: Some(MESSAGE_TYPE_DIAL),
+            dial: Some(proto::message::Dial {
+                peer: Some(proto::message::PeerInfo {
+                    id: Some(self.peer_id.as_bytes().to_vec()),
+                    addrs: self.addrs.iter().map(Multiaddr::to_vec).collect(),
+                }),
+            }),
+            dial_response: None,
+        })
+    }
+
+    /// Decodes a `DIAL` message.
+    pub fn decode(bytes: &[u8]) -> Result<Self, DecodeError> {
+        let message = proto::Message::decode(bytes)?;
+        if message.r#type != Some(MESSAGE_TYPE_DIAL) {
+            return Err(DecodeError::new("expected a DIAL message"))
+        }
+
+        let peer = message.dial
+            .ok_or_else(|| DecodeError::new("missing dial"))?
+            .peer
+            .ok_or_else(|| DecodeError::new("missing peer"))?;
+        let peer_id = peer.id.ok_or_else(|| DecodeError::new("missing peer ID"))?;
+        let peer_id = PeerId::from_bytes(peer_id).map_err(|_| DecodeError::new("invalid peer ID"))?;
+        let addrs = peer.addrs
+            .into_iter()
+            .filter_map(|addr| Multiaddr::try_from(addr).ok())
+            .collect();
+        Ok(DialRequest { peer_id, addrs })
+    }
+}
+
+impl DialResponse {
+    /// Encodes the response as a `DIAL_RESPONSE` message.
+    pub fn encode(&self) -> Vec<u8> {
+        let (status, addr) = match &self.result {
+            Ok(addr) => (0, Some(addr.to_vec())),
+            Err(error) => (error.code(), None),
+        };
+        encode_message(&proto::Message {
+            r#type: Some(MESSAGE_TYPE_DIAL_RESPONSE),
+            dial: None,
+            dial_response: Some(proto::message::DialResponse {
+                status: Some(status),
+                status_text: self.status_text.clone(),
+                addr,
+            }),
+        })
+    }
+
+    /// Decodes a `DIAL_RESPONSE` message.
+    pub fn decode(bytes: &[u8]) -> Result<Self, DecodeError> {
+        let message = proto::Message::decode(bytes)?;
+        if message.r#type != Some(MESSAGE_TYPE_DIAL_RESPONSE) {
+            return Err(DecodeError::new("expected a DIAL_RESPONSE message"))
+        }
+
+        let response = message.dial_response
+            .ok_or_else(|| DecodeError::new("missing dial response"))?;
+        let result = match response.status.ok_or_else(|| DecodeError::new("missing status"))? {
+            0 => {
+                let addr = response.addr.ok_or_else(|| DecodeError::new("missing address"))?;
+                Ok(Multiaddr::try_from(addr).map_err(|_| DecodeError::new("invalid address"))?)
+            }
+            code => Err(ResponseError::from_code(code).ok_or_else(|| DecodeError::new("unknown status"))?),
+        };
+        Ok(DialResponse { result, status_text: response.status_text })
+    }
+}
+
+fn encode_message(message: &proto::Message) -> Vec<u8> {
+    let mut buf = Vec::with_capacity(message.encoded_len());
+    message.encode(&mut buf).expect("Vec<u8> provides capacity as needed");
+    buf
+}
+
+/// Codec of the AutoNAT protocol, used with the `RequestResponse` behaviour.
+#[derive(Debug, Copy, Clone, Default)]
+pub struct AutoNatCodec;
+
+impl RequestResponseCodec for AutoNatCodec {
+    type Request = DialRequest;
+    type Response = DialResponse;
+
+    fn encode_request(&self, request: DialRequest) -> Vec<u8> {
+        request.encode()
+    }
+
+    fn decode_request(&self, bytes: Vec<u8>) -> Result<DialRequest, io::Error> {
+        DialRequest::decode(&bytes).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+    }
+
+    fn encode_response(&self, response: DialResponse) -> Vec<u8> {
+        response.encode()
+    }
+
+    fn decode_response(&self, bytes: Vec<u8>) -> Result<DialResponse, io::Error> {
+        DialResponse::decode(&bytes).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use libp2p_core::multiaddr::multiaddr;
+
+    #[test]
+    fn request_roundtrip() {
+        let request = DialRequest {
+            peer_id: PeerId::random(),
+            addrs: vec![
+                multiaddr![Ip4([1, 2, 3, 4]), Tcp(4001u16)],
+                multiaddr![Ip4([1, 2, 3, 4]), Tcp(4002u16)],
+            ],
+        };
+        assert_eq!(DialRequest::decode(&request.encode()), Ok(request));
+    }
+
+    #[test]
+    fn response_roundtrip() {
+        let success = DialResponse {
+            result: Ok(multiaddr![Ip4([1, 2, 3, 4]), Tcp(4001u16)]),
+            status_text: None,
+        };
+        assert_eq!(DialResponse::decode(&success.encode()), Ok(success));
+
+        let failure = DialResponse {
+            result: Err(ResponseError::DialRefused),
+            status_text: Some("too many dial backs".to_owned()),
+        };
+        assert_eq!(DialResponse::decode(&failure.encode()), Ok(failure));
+    }
+
+    #[test]
+    fn request_is_not_response() {
+        let request = DialRequest { peer_id: PeerId::random(), addrs: Vec::new() };
+        assert!(DialResponse::decode(&request.encode()).is_err());
+    }
+}
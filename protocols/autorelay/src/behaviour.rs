@@ -0,0 +1,560 @@
+// Copyright 2019 Parity Technologies (UK) Ltd.
+//
+// Permission is hereby granted, free of charge, to any person obtaining a
+// copy of this software and associated documentation files (the "Software"),
+// to deal in the Software without restriction, including without limitation
+// the rights to use, copy, modify, merge, publish, distribute, sublicense,
+// and/or sell copies of the Software, and to permit persons to whom the
+// Software is furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS
+// OR IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING
+// FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
+// DEALINGS IN THE SOFTWARE.
+
+use crate::AutoRelayConfig;
+use futures::prelude::*;
+use libp2p_autonat::{AutoNat, AutoNatEvent, NatStatus};
+use libp2p_core::{ConnectedPoint, Multiaddr, PeerId, either::EitherOutput};
+use libp2p_relay::v2::{RelayClient, RelayClientEvent, RelayClientHandler, HOP_PROTOCOL_NAME};
+use libp2p_swarm::{
+    AddressSource,
+    BehaviourSummary,
+    ConnectionId,
+    NetworkBehaviour,
+    NetworkBehaviourAction,
+    NetworkChange,
+    PollParameters,
+    ProtocolsHandler,
+    ProtocolsHandlerSelect
+};
+use log::debug;
+use std::{collections::{HashMap, VecDeque}, error, str};
+use tokio_io::{AsyncRead, AsyncWrite};
+use wasm_timer::{Delay, Instant};
+
+/// Event generated by the `AutoRelay` network behaviour.
+#[derive(Debug)]
+pub enum AutoRelayEvent {
+    /// Event generated by the inner `AutoNat` behaviour.
+    AutoNat(AutoNatEvent),
+    /// Event generated by the inner `RelayClient` behaviour.
+    RelayClient(RelayClientEvent),
+    /// The node is private, and a reservation is being made on the relay.
+    RelaySelected { relay: PeerId },
+    /// The relay is no longer used, because the node became public, or because the reservation
+    /// failed or the connection to the relay has been closed. The relayed addresses obtained
+    /// from the relay have been withdrawn.
+    RelayDropped { relay: PeerId },
+}
+
+/// Network behaviour selecting relays and holding reservations on them while the local node
+/// isn't publicly reachable.
+///
+/// See the crate documentation for details.
+pub struct AutoRelay<TSubstream> {
+    /// Determines whether the node is publicly reachable.
+    autonat: AutoNat<TSubstream>,
+    /// Makes the reservations on the relays.
+    client: RelayClient<TSubstream>,
+    /// Configuration options.
+    config: AutoRelayConfig,
+    /// Whether AutoNAT determined that the node is private.
+    private: bool,
+    /// Candidate relays with their known addresses, from the most to the least preferred.
+    candidates: Vec<(PeerId, Vec<Multiaddr>)>,
+    /// Relays that failed, with the time until which they aren't selected again.
+    failed: HashMap<PeerId, Instant>,
+    /// Fires when a failed relay can be selected again, if more relays are needed.
+    retry: Option<Delay>,
+    /// Selected relays, with the relayed addresses obtained from their reservation.
+    selected: Vec<(PeerId, Vec<Multiaddr>)>,
+    /// Queue of actions to return to the swarm.
+    pending_actions: VecDeque<NetworkBehaviourAction<InEvent<TSubstream>, AutoRelayEvent>>,
+}
+
+/// Event sent to the handler of a connection.
+type InEvent<TSubstream> = <Handler<TSubstream> as ProtocolsHandler>::InEvent;
+
+/// Protocols handler of the `AutoRelay` behaviour.
+type Handler<TSubstream> = ProtocolsHandlerSelect<
+    <AutoNat<TSubstream> as NetworkBehaviour>::ProtocolsHandler,
+    RelayClientHandler<TSubstream>
+>;
+
+impl<TSubstream> AutoRelay<TSubstream>
+where
+    TSubstream: AsyncRead + AsyncWrite + Send + 'static,
+{
+    /// Creates an `AutoRelay` behaviour driving the given `AutoNat` and `RelayClient`
+    /// behaviours.
+    pub fn new(autonat: AutoNat<TSubstream>, client: RelayClient<TSubstream>, config: AutoRelayConfig) -> Self {
+        let candidates = config.static_relays.iter()
+            .map(|(relay, addr)| (relay.clone(), vec![addr.clone()]))
+            .collect();
+        AutoRelay {
+            autonat,
+            client,
+            config,
+            private: false,
+            candidates,
+            failed: HashMap::new(),
+            retry: None,
+            selected: Vec::new(),
+            pending_actions: VecDeque::new(),
+        }
+    }
+
+    /// Returns the inner `AutoNat` behaviour.
+    pub fn autonat(&mut self) -> &mut AutoNat<TSubstream> {
+        &mut self.autonat
+    }
+
+    /// Returns the inner `RelayClient` behaviour.
+    pub fn client(&mut self) -> &mut RelayClient<TSubstream> {
+        &mut self.client
+    }
+
+    /// Adds a candidate relay, for example one that has been found in a DHT.
+    pub fn add_candidate(&mut self, relay: PeerId, addresses: Vec<Multiaddr>) {
+        match self.candidates.iter_mut().find(|(r, _)| *r == relay) {
+            Some((_, known)) => {
+                for addr in addresses {
+                    if !known.contains(&addr) {
+                        known.push(addr);
+                    }
+                }
+            }
+            None => self.candidates.push((relay, addresses)),
+        }
+        self.select_relays();
+    }
+
+    /// Returns the selected relays, with the relayed addresses obtained from them so far.
+    pub fn relays(&self) -> impl Iterator<Item = (&PeerId, &[Multiaddr])> {
+        self.selected.iter().map(|(relay, addrs)| (relay, &addrs[..]))
+    }
+
+    /// Selects relays among the candidates until `max_relays` are selected, if the node is
+    /// private.
+    fn select_relays(&mut self) {
+        if !self.private {
+            return
+        }
+        let now = Instant::now();
+        self.failed.retain(|_, until| *until > now);
+        while self.selected.len() < self.config.max_relays {
+            let selected = &self.selected;
+            let failed = &self.failed;
+            let candidate = self.candidates.iter()
+                .map(|(relay, _)| relay)
+                .find(|relay| !selected.iter().any(|(r, _)| r == *relay) && !failed.contains_key(*relay))
+                .cloned();
+            let relay = match candidate {
+                Some(relay) => relay,
+                None => break,
+            };
+            debug!("Selecting relay {:?}", relay);
+            self.client.reserve(relay.clone(), None);
+            self.selected.push((relay.clone(), Vec::new()));
+            self.pending_actions.push_back(NetworkBehaviourAction::GenerateEvent(
+                AutoRelayEvent::RelaySelected { relay }
+            ));
+        }
+
+        // Retry once the first failed relay can be selected again, if still needed.
+        self.retry = if self.selected.len() < self.config.max_relays {
+            self.failed.values().min().map(|until| Delay::new(*until))
+        } else {
+            None
+        };
+    }
+
+    /// Stops using `relay`, withdrawing its relayed addresses.
+    fn drop_relay(&mut self, relay: &PeerId) {
+        let position = match self.selected.iter().position(|(r, _)| r == relay) {
+            Some(position) => position,
+            None => return,
+        };
+        let (relay, addrs) = self.selected.remove(position);
+        self.client.cancel_reservation(&relay);
+        for address in addrs {
+            self.pending_actions.push_back(NetworkBehaviourAction::RemoveExternalAddr { address });
+        }
+        self.pending_actions.push_back(NetworkBehaviourAction::GenerateEvent(
+            AutoRelayEvent::RelayDropped { relay }
+        ));
+    }
+
+    /// Drops `relay` after a failure, and selects another one.
+    fn relay_failed(&mut self, relay: &PeerId) {
+        if !self.selected.iter().any(|(r, _)| r == relay) {
+            return
+        }
+        self.failed.insert(relay.clone(), Instant::now() + self.config.retry_delay);
+        self.drop_relay(relay);
+        self.select_relays();
+    }
+
+    /// Processes an event of the inner `AutoNat` behaviour.
+    fn on_autonat_event(&mut self, event: &AutoNatEvent) {
+        let AutoNatEvent::StatusChanged { new, .. } = event;
+        match new {
+            NatStatus::Private => {
+                self.private = true;
+                self.select_relays();
+            }
+            NatStatus::Public(_) => {
+                self.private = false;
+                self.retry = None;
+                let relays = self.selected.iter().map(|(r, _)| r.clone()).collect::<Vec<_>>();
+                for relay in relays {
+                    self.drop_relay(&relay);
+                }
+            }
+            NatStatus::Unknown => {}
+        }
+    }
+
+    /// Processes an event of the inner `RelayClient` behaviour.
+    fn on_client_event(&mut self, event: &RelayClientEvent) {
+        match event {
+            RelayClientEvent::ReservationAccepted { relay, addrs, .. } => {
+                let known = match self.selected.iter_mut().find(|(r, _)| r == relay) {
+                    Some((_, known)) => known,
+                    None => return,
+                };
+                for addr in addrs {
+                    if !known.contains(addr) {
+                        known.push(addr.clone());
+                        self.pending_actions.push_back(NetworkBehaviourAction::ReportExternalAddr {
+                            address: addr.clone(),
+                            source: AddressSource::Relayed,
+                        });
+                    }
+                }
+            }
+            RelayClientEvent::ReservationFailed { relay, error } => {
+                debug!("Reservation on relay {:?} failed: {:?}", relay, error);
+                self.relay_failed(relay);
+            }
+            RelayClientEvent::InboundCircuitEstablished { .. } |
+            RelayClientEvent::OutboundCircuitEstablished { .. } => {}
+        }
+    }
+}
+
+/// Converts an action of an inner behaviour into an action of the `AutoRelay` behaviour, or
+/// returns the event to process if the action is `GenerateEvent`.
+fn forward_action<TInEvent, TOutEvent, TEvent>(
+    action: NetworkBehaviourAction<TInEvent, TEvent>,
+    map_in: impl FnOnce(TInEvent) -> TOutEvent,
+) -> Result<NetworkBehaviourAction<TOutEvent, AutoRelayEvent>, TEvent> {
+    Ok(match action {
+        NetworkBehaviourAction::GenerateEvent(event) => return Err(event),
+        NetworkBehaviourAction::DialAddress { address } => NetworkBehaviourAction::DialAddress { address },
+        NetworkBehaviourAction::DialPeer { peer_id } => NetworkBehaviourAction::DialPeer { peer_id },
+        NetworkBehaviourAction::SendEvent { peer_id, event } => {
+            NetworkBehaviourAction::SendEvent { peer_id, event: map_in(event) }
+        }
+        NetworkBehaviourAction::SendEventToConnection { peer_id, connection, event } => {
+            NetworkBehaviourAction::SendEventToConnection { peer_id, connection, event: map_in(event) }
+        }
+        NetworkBehaviourAction::ReportObservedAddr { address, observer } => {
+            NetworkBehaviourAction::ReportObservedAddr { address, observer }
+        }
+        NetworkBehaviourAction::ReportRemoteProtocols { peer_id, protocols } => {
+            NetworkBehaviourAction::ReportRemoteProtocols { peer_id, protocols }
+        }
+        NetworkBehaviourAction::ReportRoundTripTime { peer_id, rtt } => {
+            NetworkBehaviourAction::ReportRoundTripTime { peer_id, rtt }
+        }
+        NetworkBehaviourAction::ReportTransfer { peer_id, bytes_received, bytes_sent, duration } => {
+            NetworkBehaviourAction::ReportTransfer { peer_id, bytes_received, bytes_sent, duration }
+        }
+        NetworkBehaviourAction::ReportExternalAddr { address, source } => {
+            NetworkBehaviourAction::ReportExternalAddr { address, source }
+        }
+        NetworkBehaviourAction::RemoveExternalAddr { address } => {
+            NetworkBehaviourAction::RemoveExternalAddr { address }
+        }
+    })
+}
+
+impl<TSubstream> NetworkBehaviour for AutoRelay<TSubstream>
+where
+    TSubstream: AsyncRead + AsyncWrite + Send + 'static,
+{
+    type ProtocolsHandler = Handler<TSubstream>;
+    type OutEvent = AutoRelayEvent;
+
+    fn new_handler(&mut self) -> Self::ProtocolsHandler {
+        self.autonat.new_handler().select(self.client.new_handler())
+    }
+
+    fn addresses_of_peer(&mut self, peer_id: &PeerId) -> Vec<Multiaddr> {
+        let mut addrs = self.autonat.addresses_of_peer(peer_id);
+        addrs.extend(self.client.addresses_of_peer(peer_id));
+        if let Some((_, candidate_addrs)) = self.candidates.iter().find(|(r, _)| r == peer_id) {
+            for addr in candidate_addrs {
+                if !addrs.contains(addr) {
+                    addrs.push(addr.clone());
+                }
+            }
+        }
+        addrs
+    }
+
+    fn inject_connected(&mut self, peer_id: PeerId, endpoint: ConnectedPoint) {
+        self.autonat.inject_connected(peer_id.clone(), endpoint.clone());
+        self.client.inject_connected(peer_id, endpoint);
+    }
+
+    fn inject_disconnected(&mut self, peer_id: &PeerId, endpoint: ConnectedPoint) {
+        self.autonat.inject_disconnected(peer_id, endpoint.clone());
+        self.client.inject_disconnected(peer_id, endpoint);
+        // The reservation is held on the connection to the relay.
+        self.relay_failed(peer_id);
+    }
+
+    fn inject_connection_established(&mut self, peer_id: &PeerId, connection: &ConnectionId, endpoint: &ConnectedPoint) {
+        self.autonat.inject_connection_established(peer_id, connection, endpoint);
+        self.client.inject_connection_established(peer_id, connection, endpoint);
+    }
+
+    fn inject_connection_closed(&mut self, peer_id: &PeerId, connection: &ConnectionId, endpoint: &ConnectedPoint) {
+        self.autonat.inject_connection_closed(peer_id, connection, endpoint);
+        self.client.inject_connection_closed(peer_id, connection, endpoint);
+    }
+
+    fn inject_replaced(&mut self, peer_id: PeerId, closed_endpoint: ConnectedPoint, new_endpoint: ConnectedPoint) {
+        self.autonat.inject_replaced(peer_id.clone(), closed_endpoint.clone(), new_endpoint.clone());
+        self.client.inject_replaced(peer_id, closed_endpoint, new_endpoint);
+    }
+
+    fn inject_node_event(
+        &mut self,
+        peer_id: PeerId,
+        event: <Self::ProtocolsHandler as ProtocolsHandler>::OutEvent,
+    ) {
+        match event {
+            EitherOutput::First(event) => self.autonat.inject_node_event(peer_id, event),
+            EitherOutput::Second(event) => self.client.inject_node_event(peer_id, event),
+        }
+    }
+
+    fn inject_connection_event(
+        &mut self,
+        peer_id: PeerId,
+        connection: ConnectionId,
+        event: <Self::ProtocolsHandler as ProtocolsHandler>::OutEvent,
+    ) {
+        match event {
+            EitherOutput::First(event) => self.autonat.inject_connection_event(peer_id, connection, event),
+            EitherOutput::Second(event) => self.client.inject_connection_event(peer_id, connection, event),
+        }
+    }
+
+    fn inject_addr_reach_failure(&mut self, peer_id: Option<&PeerId>, addr: &Multiaddr, error: &dyn error::Error) {
+        self.autonat.inject_addr_reach_failure(peer_id, addr, error);
+        self.client.inject_addr_reach_failure(peer_id, addr, error);
+    }
+
+    fn inject_dial_failure(&mut self, peer_id: &PeerId) {
+        self.autonat.inject_dial_failure(peer_id);
+        // Reports the failure of the reservation, if any.
+        self.client.inject_dial_failure(peer_id);
+    }
+
+    fn inject_banned_peer_connection(&mut self, peer_id: &PeerId, endpoint: &ConnectedPoint) {
+        self.autonat.inject_banned_peer_connection(peer_id, endpoint);
+        self.client.inject_banned_peer_connection(peer_id, endpoint);
+    }
+
+    fn inject_new_listen_addr(&mut self, addr: &Multiaddr) {
+        self.autonat.inject_new_listen_addr(addr);
+        self.client.inject_new_listen_addr(addr);
+    }
+
+    fn inject_expired_listen_addr(&mut self, addr: &Multiaddr) {
+        self.autonat.inject_expired_listen_addr(addr);
+        self.client.inject_expired_listen_addr(addr);
+    }
+
+    fn inject_listener_error(&mut self, err: &dyn error::Error) {
+        self.autonat.inject_listener_error(err);
+        self.client.inject_listener_error(err);
+    }
+
+    fn inject_new_external_addr(&mut self, addr: &Multiaddr) {
+        self.autonat.inject_new_external_addr(addr);
+        self.client.inject_new_external_addr(addr);
+    }
+
+    fn inject_network_change(&mut self, change: &NetworkChange) {
+        self.autonat.inject_network_change(change);
+        self.client.inject_network_change(change);
+    }
+
+    fn inject_shutdown(&mut self) {
+        self.autonat.inject_shutdown();
+        self.client.inject_shutdown();
+    }
+
+    fn inject_remote_protocols(&mut self, peer_id: &PeerId, protocols: &[String]) {
+        self.autonat.inject_remote_protocols(peer_id, protocols);
+        self.client.inject_remote_protocols(peer_id, protocols);
+
+        let hop = str::from_utf8(HOP_PROTOCOL_NAME).expect("the protocol name is valid UTF-8");
+        if protocols.iter().any(|p| p == hop) {
+            self.add_candidate(peer_id.clone(), Vec::new());
+        }
+    }
+
+    fn summarize(&self, summary: &mut BehaviourSummary) {
+        self.autonat.summarize(summary);
+        self.client.summarize(summary);
+    }
+
+    fn poll(&mut self, params: &mut impl PollParameters)
+        -> Async<NetworkBehaviourAction<<Self::ProtocolsHandler as ProtocolsHandler>::InEvent, Self::OutEvent>>
+    {
+        let retry = match self.retry.as_mut().map(|retry| retry.poll()) {
+            Some(Ok(Async::NotReady)) | None => false,
+            Some(Ok(Async::Ready(()))) | Some(Err(_)) => true,
+        };
+        if retry {
+            self.retry = None;
+            self.select_relays();
+        }
+
+        loop {
+            if let Some(action) = self.pending_actions.pop_front() {
+                return Async::Ready(action)
+            }
+
+            if let Async::Ready(action) = self.autonat.poll(params) {
+                match forward_action(action, EitherOutput::First) {
+                    Ok(action) => return Async::Ready(action),
+                    Err(event) => {
+                        self.on_autonat_event(&event);
+                        self.pending_actions.push_back(NetworkBehaviourAction::GenerateEvent(
+                            AutoRelayEvent::AutoNat(event)
+                        ));
+                    }
+                }
+                continue
+            }
+
+            if let Async::Ready(action) = self.client.poll(params) {
+                match forward_action(action, EitherOutput::Second) {
+                    Ok(action) => return Async::Ready(action),
+                    Err(event) => {
+                        self.on_client_event(&event);
+                        self.pending_actions.push_back(NetworkBehaviourAction::GenerateEvent(
+                            AutoRelayEvent::RelayClient(event)
+                        ));
+                    }
+                }
+                continue
+            }
+
+            return Async::NotReady
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use libp2p_autonat::AutoNatConfig;
+    use libp2p_core::multiaddr::multiaddr;
+    use libp2p_relay::v2::RelayClientConfig;
+    use std::io;
+
+    type TestAutoRelay = AutoRelay<io::Cursor<Vec<u8>>>;
+
+    fn auto_relay(config: AutoRelayConfig) -> TestAutoRelay {
+        let (_, client) = RelayClient::new_transport_and_behaviour(RelayClientConfig::default());
+        AutoRelay::new(AutoNat::new(AutoNatConfig::default()), client, config)
+    }
+
+    fn status_changed(new: NatStatus) -> AutoNatEvent {
+        AutoNatEvent::StatusChanged { old: NatStatus::Unknown, new }
+    }
+
+    fn take_events(auto_relay: &mut TestAutoRelay) -> Vec<String> {
+        auto_relay.pending_actions.drain(..)
+            .map(|a| match a {
+                NetworkBehaviourAction::GenerateEvent(AutoRelayEvent::RelaySelected { .. }) => "selected",
+                NetworkBehaviourAction::GenerateEvent(AutoRelayEvent::RelayDropped { .. }) => "dropped",
+                NetworkBehaviourAction::ReportExternalAddr { .. } => "reported",
+                NetworkBehaviourAction::RemoveExternalAddr { .. } => "removed",
+                _ => "other",
+            })
+            .map(String::from)
+            .collect()
+    }
+
+    #[test]
+    fn relays_selected_when_private() {
+        let relays = (0..3).map(|_| PeerId::random()).collect::<Vec<_>>();
+        let config = relays.iter().fold(AutoRelayConfig::default(), |config, relay| {
+            config.with_static_relay(relay.clone(), multiaddr![Ip4([1, 2, 3, 4]), Tcp(4001u16)])
+        });
+        let mut auto_relay = auto_relay(config);
+
+        auto_relay.add_candidate(PeerId::random(), Vec::new());
+        assert!(take_events(&mut auto_relay).is_empty());
+
+        auto_relay.on_autonat_event(&status_changed(NatStatus::Private));
+        assert_eq!(take_events(&mut auto_relay), vec!["selected", "selected"]);
+        let selected = auto_relay.relays().map(|(r, _)| r.clone()).collect::<Vec<_>>();
+        assert_eq!(selected, relays[..2].to_vec());
+    }
+
+    #[test]
+    fn relayed_addresses_withdrawn_when_public() {
+        let relay = PeerId::random();
+        let config = AutoRelayConfig::default()
+            .with_static_relay(relay.clone(), multiaddr![Ip4([1, 2, 3, 4]), Tcp(4001u16)]);
+        let mut auto_relay = auto_relay(config);
+        auto_relay.on_autonat_event(&status_changed(NatStatus::Private));
+        take_events(&mut auto_relay);
+
+        let addr = multiaddr![Ip4([1, 2, 3, 4]), Tcp(4001u16)];
+        auto_relay.on_client_event(&RelayClientEvent::ReservationAccepted {
+            relay: relay.clone(),
+            renewed: false,
+            addrs: vec![addr],
+            limit: None,
+        });
+        assert_eq!(take_events(&mut auto_relay), vec!["reported"]);
+
+        auto_relay.on_autonat_event(&status_changed(NatStatus::Public(multiaddr![Ip4([5, 6, 7, 8]), Tcp(4001u16)])));
+        assert_eq!(take_events(&mut auto_relay), vec!["removed", "dropped"]);
+        assert_eq!(auto_relay.relays().count(), 0);
+    }
+
+    #[test]
+    fn failed_relay_replaced() {
+        let relays = (0..2).map(|_| PeerId::random()).collect::<Vec<_>>();
+        let config = relays.iter().fold(AutoRelayConfig::default().with_max_relays(1), |config, relay| {
+            config.with_static_relay(relay.clone(), multiaddr![Ip4([1, 2, 3, 4]), Tcp(4001u16)])
+        });
+        let mut auto_relay = auto_relay(config);
+        auto_relay.on_autonat_event(&status_changed(NatStatus::Private));
+        take_events(&mut auto_relay);
+
+        auto_relay.relay_failed(&relays[0]);
+        assert_eq!(take_events(&mut auto_relay), vec!["dropped", "selected"]);
+        let selected = auto_relay.relays().map(|(r, _)| r.clone()).collect::<Vec<_>>();
+        assert_eq!(selected, vec![relays[1].clone()]);
+    }
+}
@@ -0,0 +1,81 @@
+// Copyright 2019 Parity Technologies (UK) Ltd.
+//
+// Permission is hereby granted, free of charge, to any person obtaining a
+// copy of this software and associated documentation files (the "Software"),
+// to deal in the Software without restriction, including without limitation
+// the rights to use, copy, modify, merge, publish, distribute, sublicense,
+// and/or sell copies of the Software, and to permit persons to whom the
+// Software is furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS
+// OR IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING
+// FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
+// DEALINGS IN THE SOFTWARE.
+
+//! Automatic use of relays by the nodes that aren't publicly reachable.
+//!
+//! The [`AutoRelay`] network behaviour combines the `AutoNat` and `RelayClient` behaviours.
+//! When AutoNAT determines that the local node is private, it selects relays among its
+//! candidates, reserves a slot on each of them, and advertises the relayed addresses obtained
+//! from the reservations as external addresses. When the node later becomes public, the
+//! reservations are cancelled and the relayed addresses withdrawn.
+//!
+//! The candidates are the relays configured with [`AutoRelayConfig::with_static_relay`], the
+//! ones added with [`AutoRelay::add_candidate`], for example after finding them in a DHT, and
+//! the connected peers that advertise the hop protocol of circuit relay v2.
+//!
+//! The connections relayed to the local node are accepted by the `RelayTransport` of the
+//! `RelayClient`, which must therefore listen on `/p2p-circuit`.
+
+mod behaviour;
+
+pub use behaviour::{AutoRelay, AutoRelayEvent};
+
+use libp2p_core::{Multiaddr, PeerId};
+use std::time::Duration;
+
+/// Configuration of the [`AutoRelay`] network behaviour.
+#[derive(Debug, Clone)]
+pub struct AutoRelayConfig {
+    max_relays: usize,
+    retry_delay: Duration,
+    static_relays: Vec<(PeerId, Multiaddr)>,
+}
+
+impl Default for AutoRelayConfig {
+    fn default() -> Self {
+        AutoRelayConfig {
+            max_relays: 2,
+            retry_delay: Duration::from_secs(10 * 60),
+            static_relays: Vec::new(),
+        }
+    }
+}
+
+impl AutoRelayConfig {
+    /// Sets the number of relays on which a reservation is held while the node is private.
+    /// Defaults to 2.
+    pub fn with_max_relays(mut self, max: usize) -> Self {
+        self.max_relays = max;
+        self
+    }
+
+    /// Sets how long a relay that failed is not selected again. Defaults to 10 minutes.
+    pub fn with_retry_delay(mut self, delay: Duration) -> Self {
+        self.retry_delay = delay;
+        self
+    }
+
+    /// Adds a relay reachable at `address`. Static relays are preferred over the other
+    /// candidates, in the order in which they have been added.
+    pub fn with_static_relay(mut self, relay: PeerId, address: Multiaddr) -> Self {
+        self.static_relays.push((relay, address));
+        self
+    }
+}
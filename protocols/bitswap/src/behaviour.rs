@@ -0,0 +1,423 @@
+// Copyright 2019 Parity Technologies (UK) Ltd.
+//
+// Permission is hereby granted, free of charge, to any person obtaining a
+// copy of this software and associated documentation files (the "Software"),
+// to deal in the Software without restriction, including without limitation
+// the rights to use, copy, modify, merge, publish, distribute, sublicense,
+// and/or sell copies of the Software, and to permit persons to whom the
+// Software is furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS
+// OR IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING
+// FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
+// DEALINGS IN THE SOFTWARE.
+use crate::blockstore::Blockstore;
+use crate::cid::Cid;
+use crate::protocol::{BitswapConfig, BitswapMessage, Presence, WantType, WantlistEntry};
+use fnv::FnvHashMap;
+use futures::prelude::*;
+use libp2p_core::{ConnectedPoint, Multiaddr, PeerId};
+use libp2p_swarm::{
+    NetworkBehaviour,
+    NetworkBehaviourAction,
+    PollParameters,
+    ProtocolsHandler,
+    OneShotHandler,
+    SubstreamTimedOut
+};
+use log::debug;
+use std::{collections::VecDeque, marker::PhantomData};
+use tokio_io::{AsyncRead, AsyncWrite};
+
+/// Network behaviour that exchanges blocks with the connected peers.
+///
+/// Blocks are read from and written to the `Blockstore` passed at initialization. Blocks wanted
+/// with `want_block` are requested from every connected peer, and written to the store once
+/// received and checked against their CID.
+pub struct Bitswap<TSubstream, TStore> {
+    /// Storage for the blocks we serve and receive.
+    store: TStore,
+
+    /// Events that need to be yielded to the outside when polling.
+    events: VecDeque<NetworkBehaviourAction<BitswapMessage, BitswapEvent>>,
+
+    /// Our wantlist, with the priority and kind of each entry.
+    wantlist: FnvHashMap<Cid, (i32, WantType)>,
+
+    /// List of peers we are connected to, with the entries of their wantlist that we haven't
+    /// been able to serve yet.
+    connected_peers: FnvHashMap<PeerId, FnvHashMap<Cid, WantlistEntry>>,
+
+    /// Marker to pin the generics.
+    marker: PhantomData<TSubstream>,
+}
+
+impl<TSubstream, TStore> Bitswap<TSubstream, TStore>
+where
+    TStore: Blockstore,
+{
+    /// Creates a `Bitswap` backed by the given store.
+    pub fn new(store: TStore) -> Self {
+        Bitswap {
+            store,
+            events: VecDeque::new(),
+            wantlist: FnvHashMap::default(),
+            connected_peers: FnvHashMap::default(),
+            marker: PhantomData,
+        }
+    }
+
+    /// Returns the store.
+    pub fn store(&self) -> &TStore {
+        &self.store
+    }
+
+    /// Returns the store.
+    pub fn store_mut(&mut self) -> &mut TStore {
+        &mut self.store
+    }
+
+    /// Returns the CIDs of our wantlist.
+    pub fn wantlist(&self) -> impl Iterator<Item = &Cid> {
+        self.wantlist.keys()
+    }
+
+    /// Asks the connected peers for the block designated by `cid`.
+    ///
+    /// A `BlockReceived` event is generated when the block arrives. Peers that support it answer
+    /// with a `Presence::DontHave` if they don't have the block.
+    pub fn want_block(&mut self, cid: Cid, priority: i32) {
+        self.want(cid, priority, WantType::Block)
+    }
+
+    /// Asks the connected peers whether they have the block designated by `cid`.
+    ///
+    /// Only peers that support bitswap 1.2.0 answer, with a `Presence` event.
+    pub fn want_have(&mut self, cid: Cid, priority: i32) {
+        self.want(cid, priority, WantType::Have)
+    }
+
+    fn want(&mut self, cid: Cid, priority: i32, want_type: WantType) {
+        if self.store.has(&cid) {
+            return;
+        }
+
+        // A want for the block supersedes a want for its presence.
+        let want_type = match self.wantlist.get(&cid) {
+            Some((_, WantType::Block)) => WantType::Block,
+            _ => want_type,
+        };
+        self.wantlist.insert(cid.clone(), (priority, want_type));
+
+        let entry = WantlistEntry { cid, priority, cancel: false, want_type, send_dont_have: true };
+        self.broadcast_entry(entry);
+    }
+
+    /// Removes a block from our wantlist.
+    pub fn cancel(&mut self, cid: &Cid) {
+        if let Some((priority, want_type)) = self.wantlist.remove(cid) {
+            let entry = WantlistEntry { cid: cid.clone(), priority, cancel: true, want_type, send_dont_have: false };
+            self.broadcast_entry(entry);
+        }
+    }
+
+    /// Adds a block to the store, and sends it to the peers that are waiting for it.
+    ///
+    /// Returns `false` and ignores the block if `data` doesn't match `cid`.
+    pub fn insert_block(&mut self, cid: Cid, data: Vec<u8>) -> bool {
+        if !cid.verify(&data) {
+            return false;
+        }
+
+        self.cancel(&cid);
+        self.serve_waiting_peers(&cid, &data);
+        self.store.put(cid, data);
+        true
+    }
+
+    /// Sends a single wantlist entry to all the connected peers.
+    fn broadcast_entry(&mut self, entry: WantlistEntry) {
+        for peer_id in self.connected_peers.keys() {
+            self.events.push_back(NetworkBehaviourAction::SendEvent {
+                peer_id: peer_id.clone(),
+                event: BitswapMessage { wantlist: vec![entry.clone()], ..BitswapMessage::default() },
+            });
+        }
+    }
+
+    /// Sends a newly-available block, or its presence, to the peers that asked for it.
+    fn serve_waiting_peers(&mut self, cid: &Cid, data: &[u8]) {
+        for (peer_id, wants) in self.connected_peers.iter_mut() {
+            let entry = match wants.remove(cid) {
+                Some(entry) => entry,
+                None => continue,
+            };
+
+            let mut message = BitswapMessage::default();
+            match entry.want_type {
+                WantType::Block => message.blocks.push((cid.clone(), data.to_vec())),
+                WantType::Have => message.presences.push((cid.clone(), Presence::Have)),
+            }
+            self.events.push_back(NetworkBehaviourAction::SendEvent {
+                peer_id: peer_id.clone(),
+                event: message,
+            });
+        }
+    }
+}
+
+impl<TSubstream, TStore> NetworkBehaviour for Bitswap<TSubstream, TStore>
+where
+    TSubstream: AsyncRead + AsyncWrite,
+    TStore: Blockstore,
+{
+    type ProtocolsHandler = OneShotHandler<TSubstream, BitswapConfig, BitswapMessage, InnerMessage>;
+    type OutEvent = BitswapEvent;
+
+    fn new_handler(&mut self) -> Self::ProtocolsHandler {
+        Default::default()
+    }
+
+    fn addresses_of_peer(&mut self, _: &PeerId) -> Vec<Multiaddr> {
+        Vec::new()
+    }
+
+    fn inject_connected(&mut self, id: PeerId, _: ConnectedPoint) {
+        // We need to send our wantlist to the newly-connected node.
+        if !self.wantlist.is_empty() {
+            let wantlist = self.wantlist
+                .iter()
+                .map(|(cid, &(priority, want_type))| WantlistEntry {
+                    cid: cid.clone(),
+                    priority,
+                    cancel: false,
+                    want_type,
+                    send_dont_have: true,
+                })
+                .collect();
+            self.events.push_back(NetworkBehaviourAction::SendEvent {
+                peer_id: id.clone(),
+                event: BitswapMessage { wantlist, full: true, ..BitswapMessage::default() },
+            });
+        }
+
+        self.connected_peers.insert(id, FnvHashMap::default());
+    }
+
+    fn inject_disconnected(&mut self, id: &PeerId, _: ConnectedPoint) {
+        let was_in = self.connected_peers.remove(id);
+        debug_assert!(was_in.is_some());
+    }
+
+    fn inject_node_event(
+        &mut self,
+        source: PeerId,
+        event: InnerMessage,
+    ) {
+        // We ignore successful sends event.
+        let message = match event {
+            InnerMessage::Rx(message) => message,
+            InnerMessage::Sent | InnerMessage::TimedOut => return,
+        };
+
+        let mut response = BitswapMessage::default();
+
+        // Update the wantlist of the remote, and answer what we can right away.
+        {
+            let wants = self.connected_peers
+                .get_mut(&source)
+                .expect("connected_peers is kept in sync with the peers we are connected to; we are guaranteed to only receive events from connected peers; QED");
+            if message.full {
+                wants.clear();
+            }
+            for entry in message.wantlist {
+                if entry.cancel {
+                    wants.remove(&entry.cid);
+                    continue;
+                }
+
+                match (entry.want_type, self.store.get(&entry.cid)) {
+                    (WantType::Block, Some(data)) => response.blocks.push((entry.cid, data)),
+                    (WantType::Have, Some(_)) => response.presences.push((entry.cid, Presence::Have)),
+                    (_, None) => {
+                        if entry.send_dont_have {
+                            response.presences.push((entry.cid.clone(), Presence::DontHave));
+                        }
+                        wants.insert(entry.cid.clone(), entry);
+                    }
+                }
+            }
+        }
+
+        if !response.is_empty() {
+            self.events.push_back(NetworkBehaviourAction::SendEvent {
+                peer_id: source.clone(),
+                event: response,
+            });
+        }
+
+        // The CIDs of the blocks have been computed from their content while decoding, so we
+        // only have to check that we actually asked for them.
+        for (cid, data) in message.blocks {
+            if !self.wantlist.contains_key(&cid) {
+                debug!("Ignoring unwanted block {} from {:?}", cid, source);
+                continue;
+            }
+
+            self.insert_block(cid.clone(), data.clone());
+            self.events.push_back(NetworkBehaviourAction::GenerateEvent(BitswapEvent::BlockReceived {
+                peer_id: source.clone(),
+                cid,
+                data,
+            }));
+        }
+
+        for (cid, presence) in message.presences {
+            if !self.wantlist.contains_key(&cid) {
+                continue;
+            }
+
+            self.events.push_back(NetworkBehaviourAction::GenerateEvent(BitswapEvent::Presence {
+                peer_id: source.clone(),
+                cid,
+                presence,
+            }));
+        }
+    }
+
+    fn poll(
+        &mut self,
+        _: &mut impl PollParameters,
+    ) -> Async<
+        NetworkBehaviourAction<
+            <Self::ProtocolsHandler as ProtocolsHandler>::InEvent,
+            Self::OutEvent,
+        >,
+    > {
+        if let Some(event) = self.events.pop_front() {
+            return Async::Ready(event);
+        }
+
+        Async::NotReady
+    }
+}
+
+/// Transmission between the `OneShotHandler` and the `Bitswap` behaviour.
+pub enum InnerMessage {
+    /// We received a message from a remote.
+    Rx(BitswapMessage),
+    /// We successfully sent a message.
+    Sent,
+    /// Sending a message took too long and the substream has been closed.
+    TimedOut,
+}
+
+impl From<BitswapMessage> for InnerMessage {
+    fn from(message: BitswapMessage) -> InnerMessage {
+        InnerMessage::Rx(message)
+    }
+}
+
+impl From<()> for InnerMessage {
+    fn from(_: ()) -> InnerMessage {
+        InnerMessage::Sent
+    }
+}
+
+impl From<SubstreamTimedOut> for InnerMessage {
+    fn from(_: SubstreamTimedOut) -> InnerMessage {
+        InnerMessage::TimedOut
+    }
+}
+
+/// Event that can happen on the bitswap behaviour.
+#[derive(Debug)]
+pub enum BitswapEvent {
+    /// A block of our wantlist has been received and added to the store.
+    BlockReceived {
+        /// Peer that sent the block.
+        peer_id: PeerId,
+        /// CID of the block.
+        cid: Cid,
+        /// Content of the block.
+        data: Vec<u8>,
+    },
+
+    /// A peer told us whether it has a block of our wantlist.
+    Presence {
+        /// Peer that sent the presence.
+        peer_id: PeerId,
+        /// CID of the block.
+        cid: Cid,
+        /// Whether the peer has the block.
+        presence: Presence,
+    },
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::blockstore::MemoryBlockstore;
+    use crate::cid::RAW;
+    use multihash::Hash;
+    use std::io;
+
+    type TestBitswap = Bitswap<io::Cursor<Vec<u8>>, MemoryBlockstore>;
+
+    fn connected() -> (TestBitswap, PeerId) {
+        let mut bitswap = TestBitswap::new(MemoryBlockstore::new());
+        let peer = PeerId::random();
+        let endpoint = ConnectedPoint::Dialer { address: "/ip4/127.0.0.1/tcp/1".parse().unwrap() };
+        bitswap.inject_connected(peer.clone(), endpoint);
+        (bitswap, peer)
+    }
+
+    #[test]
+    fn serves_wanted_blocks() {
+        let (mut bitswap, peer) = connected();
+        let cid = Cid::from_data(1, RAW, Hash::SHA2256, b"hello").unwrap();
+        let entry = WantlistEntry { cid: cid.clone(), priority: 1, cancel: false, want_type: WantType::Block, send_dont_have: true };
+        bitswap.inject_node_event(peer.clone(), InnerMessage::Rx(BitswapMessage { wantlist: vec![entry], ..BitswapMessage::default() }));
+
+        match bitswap.events.pop_front() {
+            Some(NetworkBehaviourAction::SendEvent { event, .. }) =>
+                assert_eq!(event.presences, vec![(cid.clone(), Presence::DontHave)]),
+            _ => panic!("expected a presence"),
+        }
+
+        assert!(bitswap.insert_block(cid.clone(), b"hello".to_vec()));
+        match bitswap.events.pop_front() {
+            Some(NetworkBehaviourAction::SendEvent { peer_id, event }) => {
+                assert_eq!(peer_id, peer);
+                assert_eq!(event.blocks, vec![(cid, b"hello".to_vec())]);
+            }
+            _ => panic!("expected a block"),
+        }
+    }
+
+    #[test]
+    fn stores_received_blocks() {
+        let (mut bitswap, peer) = connected();
+        let wanted = Cid::from_data(1, RAW, Hash::SHA2256, b"wanted").unwrap();
+        let unwanted = Cid::from_data(1, RAW, Hash::SHA2256, b"unwanted").unwrap();
+        bitswap.want_block(wanted.clone(), 1);
+        bitswap.events.clear();
+
+        let blocks = vec![(wanted.clone(), b"wanted".to_vec()), (unwanted.clone(), b"unwanted".to_vec())];
+        bitswap.inject_node_event(peer.clone(), InnerMessage::Rx(BitswapMessage { blocks, ..BitswapMessage::default() }));
+
+        assert!(bitswap.store().has(&wanted));
+        assert!(!bitswap.store().has(&unwanted));
+        assert_eq!(bitswap.wantlist().count(), 0);
+        assert!(bitswap.events.iter().any(|e| match e {
+            NetworkBehaviourAction::GenerateEvent(BitswapEvent::BlockReceived { cid, .. }) => cid == &wanted,
+            _ => false,
+        }));
+    }
+}
@@ -0,0 +1,72 @@
+// Copyright 2019 Parity Technologies (UK) Ltd.
+//
+// Permission is hereby granted, free of charge, to any person obtaining a
+// copy of this software and associated documentation files (the "Software"),
+// to deal in the Software without restriction, including without limitation
+// the rights to use, copy, modify, merge, publish, distribute, sublicense,
+// and/or sell copies of the Software, and to permit persons to whom the
+// Software is furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS
+// OR IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING
+// FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
+// DEALINGS IN THE SOFTWARE.
+use crate::cid::Cid;
+use fnv::FnvHashMap;
+
+/// Storage of the blocks that the local node serves to and receives from the network.
+pub trait Blockstore {
+    /// Returns the content of the block designated by `cid`, if we have it.
+    fn get(&self, cid: &Cid) -> Option<Vec<u8>>;
+
+    /// Returns true if we have the block designated by `cid`.
+    fn has(&self, cid: &Cid) -> bool {
+        self.get(cid).is_some()
+    }
+
+    /// Stores a block. The content is guaranteed to match `cid`.
+    fn put(&mut self, cid: Cid, data: Vec<u8>);
+}
+
+/// Implementation of `Blockstore` that keeps all the blocks in memory.
+#[derive(Debug, Clone, Default)]
+pub struct MemoryBlockstore {
+    blocks: FnvHashMap<Cid, Vec<u8>>,
+}
+
+impl MemoryBlockstore {
+    /// Creates an empty `MemoryBlockstore`.
+    pub fn new() -> Self {
+        MemoryBlockstore::default()
+    }
+
+    /// Returns the number of blocks in the store.
+    pub fn len(&self) -> usize {
+        self.blocks.len()
+    }
+
+    /// Returns true if the store is empty.
+    pub fn is_empty(&self) -> bool {
+        self.blocks.is_empty()
+    }
+}
+
+impl Blockstore for MemoryBlockstore {
+    fn get(&self, cid: &Cid) -> Option<Vec<u8>> {
+        self.blocks.get(cid).cloned()
+    }
+
+    fn has(&self, cid: &Cid) -> bool {
+        self.blocks.contains_key(cid)
+    }
+
+    fn put(&mut self, cid: Cid, data: Vec<u8>) {
+        self.blocks.insert(cid, data);
+    }
+}
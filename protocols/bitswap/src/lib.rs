@@ -0,0 +1,47 @@
+// Copyright 2019 Parity Technologies (UK) Ltd.
+//
+// Permission is hereby granted, free of charge, to any person obtaining a
+// copy of this software and associated documentation files (the "Software"),
+// to deal in the Software without restriction, including without limitation
+// the rights to use, copy, modify, merge, publish, distribute, sublicense,
+// and/or sell copies of the Software, and to permit persons to whom the
+// Software is furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS
+// OR IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING
+// FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
+// DEALINGS IN THE SOFTWARE.
+//! Implementation of the [bitswap] protocol.
+//!
+//! Bitswap exchanges blocks of content, designated by their CID, with the connected peers. Each
+//! peer sends its wantlist to the others, and receives the blocks of that list that the others
+//! have. Versions 1.0.0, 1.1.0 and 1.2.0 of the protocol are supported; the latter adds
+//! `want-have` entries and block presences, which let a node find out who has a block before
+//! asking for it.
+//!
+//! The [`Bitswap`] network behaviour serves and stores blocks through a [`Blockstore`], which
+//! lets applications plug in their own storage. [`MemoryBlockstore`] keeps everything in memory.
+//!
+//! [bitswap]: https://github.com/ipfs/specs/blob/master/BITSWAP.md
+
+pub mod cid;
+pub mod protocol;
+
+mod behaviour;
+mod blockstore;
+
+/// Protobuf messages of the protocol, generated from `message.proto`.
+mod proto {
+    include!(concat!(env!("OUT_DIR"), "/bitswap.message.pb.rs"));
+}
+
+pub use self::behaviour::{Bitswap, BitswapEvent, InnerMessage};
+pub use self::blockstore::{Blockstore, MemoryBlockstore};
+pub use self::cid::{Cid, CidError};
+pub use self::protocol::{BitswapMessage, Presence, WantType, WantlistEntry};
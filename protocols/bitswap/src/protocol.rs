@@ -0,0 +1,360 @@
+// Copyright 2019 Parity Technologies (UK) Ltd.
+//
+// Permission is hereby granted, free of charge, to any person obtaining a
+// copy of this software and associated documentation files (the "Software"),
+// to deal in the Software without restriction, including without limitation
+// the rights to use, copy, modify, merge, publish, distribute, sublicense,
+// and/or sell copies of the Software, and to permit persons to whom the
+// Software is furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS
+// OR IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING
+// FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
+// DEALINGS IN THE SOFTWARE.
+use crate::cid::{Cid, CidError, Prefix};
+use crate::proto::{self, message as proto_message};
+use libp2p_core::{InboundUpgrade, OutboundUpgrade, UpgradeInfo, upgrade};
+use prost::Message;
+use std::{error, fmt, io};
+use tokio_io::{AsyncRead, AsyncWrite};
+
+/// Name of version 1.2.0 of the protocol, which adds `want-have` and block presences.
+pub const PROTOCOL_1_2_0: &[u8] = b"/ipfs/bitswap/1.2.0";
+/// Name of version 1.1.0 of the protocol, which transmits blocks alongside their CID prefix.
+pub const PROTOCOL_1_1_0: &[u8] = b"/ipfs/bitswap/1.1.0";
+/// Name of version 1.0.0 of the protocol.
+pub const PROTOCOL_1_0_0: &[u8] = b"/ipfs/bitswap/1.0.0";
+
+/// Maximum size of a bitswap message.
+const MAX_MESSAGE_SIZE: usize = 4 * 1024 * 1024;
+
+/// Implementation of `InboundUpgrade` for the bitswap protocol.
+#[derive(Debug, Clone, Default)]
+pub struct BitswapConfig {}
+
+impl BitswapConfig {
+    /// Builds a new `BitswapConfig`.
+    pub fn new() -> BitswapConfig {
+        BitswapConfig {}
+    }
+}
+
+impl UpgradeInfo for BitswapConfig {
+    type Info = &'static [u8];
+    type InfoIter = std::vec::IntoIter<Self::Info>;
+
+    fn protocol_info(&self) -> Self::InfoIter {
+        vec![PROTOCOL_1_2_0, PROTOCOL_1_1_0, PROTOCOL_1_0_0].into_iter()
+    }
+}
+
+impl<TSocket> InboundUpgrade<TSocket> for BitswapConfig
+where
+    TSocket: AsyncRead,
+{
+    type Output = BitswapMessage;
+    type Error = BitswapDecodeError;
+    type Future = upgrade::ReadOneThen<upgrade::Negotiated<TSocket>, (), fn(Vec<u8>, ()) -> Result<BitswapMessage, BitswapDecodeError>>;
+
+    fn upgrade_inbound(self, socket: upgrade::Negotiated<TSocket>, _: Self::Info) -> Self::Future {
+        upgrade::read_one_then(socket, MAX_MESSAGE_SIZE, (), |packet, ()| {
+            BitswapMessage::from_bytes(&packet)
+        })
+    }
+}
+
+/// Kind of want of a wantlist entry.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum WantType {
+    /// The remote wants the block itself.
+    Block,
+    /// The remote only wants to know whether we have the block. Requires bitswap 1.2.0.
+    Have,
+}
+
+/// Entry of a wantlist.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct WantlistEntry {
+    /// The block that is wanted.
+    pub cid: Cid,
+    /// Priority of the entry. Higher is more urgent.
+    pub priority: i32,
+    /// If true, this entry revokes a previous want for the same block.
+    pub cancel: bool,
+    /// What is wanted.
+    pub want_type: WantType,
+    /// If true, the remote wants to be told when we don't have the block.
+    pub send_dont_have: bool,
+}
+
+/// Whether a peer has a block. Requires bitswap 1.2.0.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Presence {
+    /// The peer has the block.
+    Have,
+    /// The peer doesn't have the block.
+    DontHave,
+}
+
+/// Message exchanged over the bitswap protocol.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct BitswapMessage {
+    /// Entries of the wantlist of the sender.
+    pub wantlist: Vec<WantlistEntry>,
+    /// If true, `wantlist` replaces the whole wantlist of the sender rather than updating it.
+    pub full: bool,
+    /// Blocks sent in response to wants.
+    pub blocks: Vec<(Cid, Vec<u8>)>,
+    /// Presences sent in response to wants.
+    pub presences: Vec<(Cid, Presence)>,
+    /// Number of bytes the sender still has queued for the recipient.
+    pub pending_bytes: i32,
+}
+
+impl BitswapMessage {
+    /// Returns true if the message doesn't contain anything.
+    pub fn is_empty(&self) -> bool {
+        self.wantlist.is_empty() && !self.full && self.blocks.is_empty() && self.presences.is_empty()
+    }
+
+    /// Encodes the message for the given version of the protocol.
+    ///
+    /// Elements that the version can't express are dropped: `want-have` entries and presences
+    /// before 1.2.0, and blocks whose CID isn't a version 0 CID for 1.0.0.
+    pub fn to_bytes(&self, protocol: &[u8]) -> Vec<u8> {
+        let supports_presence = protocol == PROTOCOL_1_2_0;
+        let supports_prefix = protocol != PROTOCOL_1_0_0;
+
+        let mut message = proto::Message::default();
+
+        if !self.wantlist.is_empty() || self.full {
+            let entries = self.wantlist
+                .iter()
+                .filter(|entry| entry.want_type == WantType::Block || supports_presence)
+                .map(|entry| proto_message::wantlist::Entry {
+                    block: entry.cid.to_bytes(),
+                    priority: entry.priority,
+                    cancel: entry.cancel,
+                    want_type: match entry.want_type {
+                        WantType::Block => proto_message::wantlist::WantType::Block as i32,
+                        WantType::Have => proto_message::wantlist::WantType::Have as i32,
+                    },
+                    send_dont_have: entry.send_dont_have && supports_presence,
+                })
+                .collect();
+            message.wantlist = Some(proto_message::Wantlist { entries, full: self.full });
+        }
+
+        for (cid, data) in &self.blocks {
+            if supports_prefix {
+                message.payload.push(proto_message::Block {
+                    prefix: cid.prefix().to_bytes(),
+                    data: data.clone(),
+                });
+            } else if cid.version() == 0 {
+                message.blocks.push(data.clone());
+            }
+        }
+
+        if supports_presence {
+            for (cid, presence) in &self.presences {
+                let presence = match presence {
+                    Presence::Have => proto_message::BlockPresenceType::Have,
+                    Presence::DontHave => proto_message::BlockPresenceType::DontHave,
+                };
+                message.block_presences.push(proto_message::BlockPresence {
+                    cid: cid.to_bytes(),
+                    r#type: presence as i32,
+                });
+            }
+        }
+
+        message.pending_bytes = self.pending_bytes;
+
+        let mut buf = Vec::with_capacity(message.encoded_len());
+        message.encode(&mut buf).expect("Vec<u8> provides capacity as needed");
+        buf
+    }
+
+    /// Decodes a message of any version of the protocol.
+    ///
+    /// The CIDs of the blocks are computed from their content, so that a block can't be passed
+    /// for another one.
+    pub fn from_bytes(bytes: &[u8]) -> Result<BitswapMessage, BitswapDecodeError> {
+        let decoded = proto::Message::decode(bytes)?;
+        let mut message = BitswapMessage::default();
+
+        if let Some(wantlist) = decoded.wantlist {
+            for entry in wantlist.entries {
+                let want_type = if entry.want_type == proto_message::wantlist::WantType::Have as i32 {
+                    WantType::Have
+                } else {
+                    WantType::Block
+                };
+                message.wantlist.push(WantlistEntry {
+                    cid: Cid::from_bytes(&entry.block)?,
+                    priority: entry.priority,
+                    cancel: entry.cancel,
+                    want_type,
+                    send_dont_have: entry.send_dont_have,
+                });
+            }
+            message.full = wantlist.full;
+        }
+
+        for data in decoded.blocks {
+            let cid = Cid::from_data(0, crate::cid::DAG_PB, multihash::Hash::SHA2256, &data)?;
+            message.blocks.push((cid, data));
+        }
+
+        for block in decoded.payload {
+            let prefix = Prefix::from_bytes(&block.prefix)?;
+            message.blocks.push((prefix.to_cid(&block.data)?, block.data));
+        }
+
+        for presence in decoded.block_presences {
+            let kind = if presence.r#type == proto_message::BlockPresenceType::DontHave as i32 {
+                Presence::DontHave
+            } else {
+                Presence::Have
+            };
+            message.presences.push((Cid::from_bytes(&presence.cid)?, kind));
+        }
+
+        message.pending_bytes = decoded.pending_bytes;
+        Ok(message)
+    }
+}
+
+impl UpgradeInfo for BitswapMessage {
+    type Info = &'static [u8];
+    type InfoIter = std::vec::IntoIter<Self::Info>;
+
+    fn protocol_info(&self) -> Self::InfoIter {
+        vec![PROTOCOL_1_2_0, PROTOCOL_1_1_0, PROTOCOL_1_0_0].into_iter()
+    }
+}
+
+impl<TSocket> OutboundUpgrade<TSocket> for BitswapMessage
+where
+    TSocket: AsyncWrite,
+{
+    type Output = ();
+    type Error = io::Error;
+    type Future = upgrade::WriteOne<upgrade::Negotiated<TSocket>>;
+
+    fn upgrade_outbound(self, socket: upgrade::Negotiated<TSocket>, info: Self::Info) -> Self::Future {
+        let bytes = self.to_bytes(info);
+        upgrade::write_one(socket, bytes)
+    }
+}
+
+/// Error while receiving a bitswap message.
+#[derive(Debug)]
+pub enum BitswapDecodeError {
+    /// Error when reading the packet from the socket.
+    ReadError(upgrade::ReadOneError),
+    /// Error when decoding the raw buffer into a protobuf.
+    ProtobufError(prost::DecodeError),
+    /// A CID or block prefix is invalid.
+    InvalidCid(CidError),
+}
+
+impl From<upgrade::ReadOneError> for BitswapDecodeError {
+    fn from(err: upgrade::ReadOneError) -> Self {
+        BitswapDecodeError::ReadError(err)
+    }
+}
+
+impl From<prost::DecodeError> for BitswapDecodeError {
+    fn from(err: prost::DecodeError) -> Self {
+        BitswapDecodeError::ProtobufError(err)
+    }
+}
+
+impl From<CidError> for BitswapDecodeError {
+    fn from(err: CidError) -> Self {
+        BitswapDecodeError::InvalidCid(err)
+    }
+}
+
+impl fmt::Display for BitswapDecodeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match *self {
+            BitswapDecodeError::ReadError(ref err) =>
+                write!(f, "Error while reading from socket: {}", err),
+            BitswapDecodeError::ProtobufError(ref err) =>
+                write!(f, "{}", err),
+            BitswapDecodeError::InvalidCid(ref err) =>
+                write!(f, "{}", err),
+        }
+    }
+}
+
+impl error::Error for BitswapDecodeError {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        match *self {
+            BitswapDecodeError::ReadError(ref err) => Some(err),
+            BitswapDecodeError::ProtobufError(ref err) => Some(err),
+            BitswapDecodeError::InvalidCid(ref err) => Some(err),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cid::{DAG_PB, RAW};
+    use multihash::Hash;
+
+    fn message() -> BitswapMessage {
+        let v0 = Cid::from_data(0, DAG_PB, Hash::SHA2256, b"foo").unwrap();
+        let v1 = Cid::from_data(1, RAW, Hash::SHA2256, b"bar").unwrap();
+        BitswapMessage {
+            wantlist: vec![
+                WantlistEntry { cid: v0.clone(), priority: 3, cancel: false, want_type: WantType::Block, send_dont_have: true },
+                WantlistEntry { cid: v1.clone(), priority: -1, cancel: true, want_type: WantType::Have, send_dont_have: false },
+            ],
+            full: true,
+            blocks: vec![(v0.clone(), b"foo".to_vec()), (v1.clone(), b"bar".to_vec())],
+            presences: vec![(v0, Presence::Have), (v1, Presence::DontHave)],
+            pending_bytes: 12,
+        }
+    }
+
+    #[test]
+    fn roundtrip_1_2_0() {
+        let msg = message();
+        assert_eq!(BitswapMessage::from_bytes(&msg.to_bytes(PROTOCOL_1_2_0)).unwrap(), msg);
+    }
+
+    #[test]
+    fn older_versions_drop_unsupported_elements() {
+        let msg = message();
+
+        let decoded = BitswapMessage::from_bytes(&msg.to_bytes(PROTOCOL_1_1_0)).unwrap();
+        assert_eq!(decoded.wantlist.len(), 1);
+        assert_eq!(decoded.wantlist[0].send_dont_have, false);
+        assert_eq!(decoded.blocks, msg.blocks);
+        assert!(decoded.presences.is_empty());
+
+        let decoded = BitswapMessage::from_bytes(&msg.to_bytes(PROTOCOL_1_0_0)).unwrap();
+        assert_eq!(decoded.blocks, &msg.blocks[..1]);
+    }
+
+    #[test]
+    fn block_cid_is_computed_from_content() {
+        let msg = message();
+        let mut bytes = msg.to_bytes(PROTOCOL_1_1_0);
+        let pos = bytes.windows(3).position(|w| w == b"bar").unwrap();
+        bytes[pos] = b'c';
+        let decoded = BitswapMessage::from_bytes(&bytes).unwrap();
+        assert_ne!(decoded.blocks[1].0, msg.blocks[1].0);
+    }
+}
@@ -0,0 +1,329 @@
+// Copyright 2019 Parity Technologies (UK) Ltd.
+//
+// Permission is hereby granted, free of charge, to any person obtaining a
+// copy of this software and associated documentation files (the "Software"),
+// to deal in the Software without restriction, including without limitation
+// the rights to use, copy, modify, merge, publish, distribute, sublicense,
+// and/or sell copies of the Software, and to permit persons to whom the
+// Software is furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS
+// OR IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING
+// FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
+// DEALINGS IN THE SOFTWARE.
+
+use crate::DcutrConfig;
+use crate::handler::{DcutrError, DcutrHandler, DcutrHandlerEvent, DcutrHandlerIn, InboundConnect};
+use futures::prelude::*;
+use libp2p_core::{ConnectedPoint, Multiaddr, PeerId};
+use libp2p_swarm::{ConnectionId, NetworkBehaviour, NetworkBehaviourAction, PollParameters, node_event_connection};
+use smallvec::SmallVec;
+use std::{collections::{HashMap, VecDeque}, error, mem};
+use tokio_io::{AsyncRead, AsyncWrite};
+
+/// Event generated by the `Dcutr` network behaviour.
+#[derive(Debug)]
+pub enum DcutrEvent {
+    /// We started a hole punching attempt towards a peer we are connected to through a relay.
+    InitiatedDirectConnectionUpgrade { peer: PeerId, attempt: u8 },
+    /// A peer we are connected to through a relay started a hole punching attempt towards us.
+    RemoteInitiatedDirectConnectionUpgrade { peer: PeerId },
+    /// A direct connection to a peer has been established by hole punching.
+    DirectConnectionUpgradeSucceeded { peer: PeerId },
+    /// All the hole punching attempts we started towards a peer failed.
+    DirectConnectionUpgradeFailed { peer: PeerId, error: DcutrError },
+}
+
+/// Network behaviour implementing the Direct Connection Upgrade through Relay protocol.
+///
+/// When a peer connects to us through a relay, we exchange our addresses with it over the
+/// relayed connection, and both of us dial the other at the same time, which opens a hole in
+/// the NATs in front of each of us. Our addresses are the external addresses of the swarm,
+/// typically observed by other peers through the identify protocol, followed by the addresses
+/// we listen on. Simultaneous dials require the TCP transport to reuse the port of its
+/// listeners, as enabled with `TcpConfig::port_reuse`.
+///
+/// Once a direct connection is established, events sent to the peer without specifying a
+/// connection are delivered to the handler of this new connection, and the relayed connection
+/// is closed once its handlers are idle.
+pub struct Dcutr<TSubstream> {
+    /// Configuration options.
+    config: DcutrConfig,
+    /// The relayed connections to each peer, from the oldest to the most recent.
+    relayed: HashMap<PeerId, SmallVec<[ConnectionId; 2]>>,
+    /// Number of direct connections to each peer.
+    direct: HashMap<PeerId, usize>,
+    /// Peers towards which we started hole punching, with the number of attempts made.
+    attempts: HashMap<PeerId, u8>,
+    /// Addresses being dialed by hole punching, which haven't failed yet.
+    dialing: HashMap<PeerId, SmallVec<[Multiaddr; 4]>>,
+    /// Attempts to start in `poll()`, where our addresses are known, with the relayed connection
+    /// to use.
+    to_connect: VecDeque<(PeerId, ConnectionId, u8)>,
+    /// Attempts of remotes to answer in `poll()`, where our addresses are known.
+    inbound: VecDeque<(PeerId, ConnectionId, InboundConnect<TSubstream>)>,
+    /// Queue of actions to return to the swarm.
+    pending_actions: VecDeque<NetworkBehaviourAction<DcutrHandlerIn<TSubstream>, DcutrEvent>>,
+}
+
+impl<TSubstream> Dcutr<TSubstream> {
+    /// Creates a `Dcutr` behaviour with the given configuration.
+    pub fn new(config: DcutrConfig) -> Self {
+        Dcutr {
+            config,
+            relayed: HashMap::new(),
+            direct: HashMap::new(),
+            attempts: HashMap::new(),
+            dialing: HashMap::new(),
+            to_connect: VecDeque::new(),
+            inbound: VecDeque::new(),
+            pending_actions: VecDeque::new(),
+        }
+    }
+
+    /// Returns true if we have a direct connection to the peer.
+    pub fn is_directly_connected(&self, peer: &PeerId) -> bool {
+        self.direct.contains_key(peer)
+    }
+
+    /// Dials the addresses received from a peer.
+    fn dial(&mut self, peer: PeerId, remote_addrs: Vec<Multiaddr>) {
+        let addrs = remote_addrs.into_iter()
+            .filter(|a| !a.is_relayed())
+            .collect::<SmallVec<[Multiaddr; 4]>>();
+        if addrs.is_empty() {
+            self.on_attempt_failure(peer, DcutrError::DialFailure);
+            return
+        }
+        for address in &addrs {
+            log::debug!("Hole punching towards {:?} at {}", peer, address);
+            self.pending_actions.push_back(NetworkBehaviourAction::DialAddress { address: address.clone() });
+        }
+        self.dialing.insert(peer, addrs);
+    }
+
+    /// Starts a new attempt towards the peer if allowed by the configuration, or reports the
+    /// failure of the hole punching.
+    ///
+    /// Does nothing for the peers towards which we didn't start hole punching, as the remote is
+    /// in charge of retrying.
+    fn on_attempt_failure(&mut self, peer: PeerId, error: DcutrError) {
+        let attempt = match self.attempts.get(&peer) {
+            Some(attempt) => *attempt,
+            None => return,
+        };
+        let connection = self.relayed.get(&peer).and_then(|conns| conns.last()).cloned();
+        match connection {
+            Some(connection) if attempt < self.config.max_attempts => {
+                log::debug!("Hole punching attempt {} towards {:?} failed: {}", attempt, peer, error);
+                self.attempts.insert(peer.clone(), attempt + 1);
+                self.to_connect.push_back((peer, connection, attempt + 1));
+            }
+            _ => {
+                self.attempts.remove(&peer);
+                self.pending_actions.push_back(NetworkBehaviourAction::GenerateEvent(
+                    DcutrEvent::DirectConnectionUpgradeFailed { peer, error }
+                ));
+            }
+        }
+    }
+
+    /// Processes an event generated by the handler of a relayed connection.
+    fn on_handler_event(&mut self, peer: PeerId, connection: ConnectionId, event: DcutrHandlerEvent<TSubstream>) {
+        match event {
+            DcutrHandlerEvent::InboundConnect(request) => {
+                self.inbound.push_back((peer, connection, request));
+            }
+            DcutrHandlerEvent::InboundSync { remote_addrs } => self.dial(peer, remote_addrs),
+            DcutrHandlerEvent::OutboundSync { remote_addrs, .. } => self.dial(peer, remote_addrs),
+            DcutrHandlerEvent::OutboundFailure { error, .. } => self.on_attempt_failure(peer, error),
+        }
+    }
+}
+
+impl<TSubstream> NetworkBehaviour for Dcutr<TSubstream>
+where
+    TSubstream: AsyncRead + AsyncWrite + Send + 'static,
+{
+    type ProtocolsHandler = DcutrHandler<TSubstream>;
+    type OutEvent = DcutrEvent;
+
+    fn new_handler(&mut self) -> Self::ProtocolsHandler {
+        DcutrHandler::new(self.config.clone())
+    }
+
+    fn addresses_of_peer(&mut self, _: &PeerId) -> Vec<Multiaddr> {
+        Vec::new()
+    }
+
+    fn inject_connected(&mut self, _: PeerId, _: ConnectedPoint) {}
+
+    fn inject_disconnected(&mut self, peer_id: &PeerId, _: ConnectedPoint) {
+        self.relayed.remove(peer_id);
+        self.direct.remove(peer_id);
+        self.attempts.remove(peer_id);
+        self.dialing.remove(peer_id);
+    }
+
+    fn inject_connection_established(&mut self, peer_id: &PeerId, connection: &ConnectionId, endpoint: &ConnectedPoint) {
+        if is_relayed(endpoint) {
+            self.relayed.entry(peer_id.clone()).or_default().push(*connection);
+            // The peer that accepted the relayed connection starts the hole punching.
+            if let ConnectedPoint::Listener { .. } = endpoint {
+                if !self.direct.contains_key(peer_id) && !self.attempts.contains_key(peer_id) {
+                    self.attempts.insert(peer_id.clone(), 1);
+                    self.to_connect.push_back((peer_id.clone(), *connection, 1));
+                }
+            }
+            return
+        }
+
+        *self.direct.entry(peer_id.clone()).or_insert(0) += 1;
+        let punched = self.dialing.remove(peer_id).is_some();
+        if self.attempts.remove(peer_id).is_some() || punched {
+            self.pending_actions.push_back(NetworkBehaviourAction::GenerateEvent(
+                DcutrEvent::DirectConnectionUpgradeSucceeded { peer: peer_id.clone() }
+            ));
+        }
+    }
+
+    fn inject_connection_closed(&mut self, peer_id: &PeerId, connection: &ConnectionId, endpoint: &ConnectedPoint) {
+        if is_relayed(endpoint) {
+            if let Some(connections) = self.relayed.get_mut(peer_id) {
+                connections.retain(|c| c != connection);
+                if connections.is_empty() {
+                    self.relayed.remove(peer_id);
+                }
+            }
+        } else if let Some(num) = self.direct.get_mut(peer_id) {
+            *num -= 1;
+            if *num == 0 {
+                self.direct.remove(peer_id);
+            }
+        }
+    }
+
+    fn inject_node_event(&mut self, peer_id: PeerId, event: DcutrHandlerEvent<TSubstream>) {
+        match node_event_connection(&self.relayed, &peer_id) {
+            Some(connection) => self.inject_connection_event(peer_id, connection, event),
+            None => log::debug!("Dropping hole punching event from {:?}: not relayed", peer_id),
+        }
+    }
+
+    fn inject_connection_event(&mut self, peer_id: PeerId, connection: ConnectionId, event: DcutrHandlerEvent<TSubstream>) {
+        self.on_handler_event(peer_id, connection, event)
+    }
+
+    fn inject_addr_reach_failure(&mut self, _: Option<&PeerId>, addr: &Multiaddr, _: &dyn error::Error) {
+        let failed = self.dialing.iter_mut()
+            .filter_map(|(peer, addrs)| {
+                let len = addrs.len();
+                addrs.retain(|a| a != addr);
+                if addrs.len() < len && addrs.is_empty() { Some(peer.clone()) } else { None }
+            })
+            .collect::<Vec<_>>();
+        for peer in failed {
+            self.dialing.remove(&peer);
+            if !self.direct.contains_key(&peer) {
+                self.on_attempt_failure(peer, DcutrError::DialFailure);
+            }
+        }
+    }
+
+    fn poll(&mut self, params: &mut impl PollParameters)
+        -> Async<NetworkBehaviourAction<DcutrHandlerIn<TSubstream>, Self::OutEvent>>
+    {
+        if !self.to_connect.is_empty() || !self.inbound.is_empty() {
+            let local_addrs = local_addresses(params);
+
+            for (peer, connection, attempt) in mem::replace(&mut self.to_connect, VecDeque::new()) {
+                if self.direct.contains_key(&peer) {
+                    self.attempts.remove(&peer);
+                    continue
+                }
+                if local_addrs.is_empty() {
+                    self.on_attempt_failure(peer, DcutrError::NoAddresses);
+                    continue
+                }
+                self.pending_actions.push_back(NetworkBehaviourAction::SendEventToConnection {
+                    peer_id: peer.clone(),
+                    connection,
+                    event: DcutrHandlerIn::Connect { local_addrs: local_addrs.clone(), attempt },
+                });
+                self.pending_actions.push_back(NetworkBehaviourAction::GenerateEvent(
+                    DcutrEvent::InitiatedDirectConnectionUpgrade { peer, attempt }
+                ));
+            }
+
+            while let Some((peer, connection, request)) = self.inbound.pop_front() {
+                if local_addrs.is_empty() {
+                    // Dropping the request closes the substream.
+                    log::debug!("Ignoring hole punching attempt of {:?}: no local address", peer);
+                    continue
+                }
+                self.pending_actions.push_back(NetworkBehaviourAction::SendEventToConnection {
+                    peer_id: peer.clone(),
+                    connection,
+                    event: DcutrHandlerIn::AcceptInbound { request, local_addrs: local_addrs.clone() },
+                });
+                self.pending_actions.push_back(NetworkBehaviourAction::GenerateEvent(
+                    DcutrEvent::RemoteInitiatedDirectConnectionUpgrade { peer }
+                ));
+            }
+        }
+
+        if let Some(action) = self.pending_actions.pop_front() {
+            return Async::Ready(action)
+        }
+
+        Async::NotReady
+    }
+}
+
+/// Returns the addresses sent to the remotes: the external addresses followed by the listened
+/// addresses, without the relayed ones.
+fn local_addresses(params: &mut impl PollParameters) -> Vec<Multiaddr> {
+    let mut addrs = params.external_addresses()
+        .filter(|a| !a.is_relayed())
+        .collect::<Vec<_>>();
+    for addr in params.listened_addresses() {
+        if !addr.is_relayed() && !addrs.contains(&addr) {
+            addrs.push(addr);
+        }
+    }
+    addrs
+}
+
+/// Returns true if the connection goes through a relay.
+fn is_relayed(endpoint: &ConnectedPoint) -> bool {
+    match endpoint {
+        ConnectedPoint::Dialer { address } => address.is_relayed(),
+        ConnectedPoint::Listener { listen_addr, send_back_addr } => {
+            listen_addr.is_relayed() || send_back_addr.is_relayed()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use libp2p_core::multiaddr::multiaddr;
+
+    #[test]
+    fn relayed_endpoints() {
+        let relay = PeerId::random();
+        let direct = multiaddr![Ip4([1, 2, 3, 4]), Tcp(4001u16)];
+        let relayed = multiaddr![Ip4([1, 2, 3, 4]), Tcp(4001u16), P2p(relay), P2pCircuit];
+
+        assert!(!is_relayed(&ConnectedPoint::Dialer { address: direct.clone() }));
+        assert!(is_relayed(&ConnectedPoint::Dialer { address: relayed.clone() }));
+        assert!(is_relayed(&ConnectedPoint::Listener { listen_addr: relayed, send_back_addr: direct.clone() }));
+        assert!(!is_relayed(&ConnectedPoint::Listener { listen_addr: direct.clone(), send_back_addr: direct }));
+    }
+}
@@ -0,0 +1,257 @@
+// Copyright 2019 Parity Technologies (UK) Ltd.
+//
+// Permission is hereby granted, free of charge, to any person obtaining a
+// copy of this software and associated documentation files (the "Software"),
+// to deal in the Software without restriction, including without limitation
+// the rights to use, copy, modify, merge, publish, distribute, sublicense,
+// and/or sell copies of the Software, and to permit persons to whom the
+// Software is furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS
+// OR IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING
+// FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
+// DEALINGS IN THE SOFTWARE.
+
+use crate::DcutrConfig;
+use crate::protocol::{self, DcutrConnect, DcutrConnectError, DcutrListen, IoFuture};
+use futures::prelude::*;
+use libp2p_core::{Multiaddr, upgrade::{Negotiated, UpgradeError}};
+use libp2p_swarm::{
+    KeepAlive,
+    SubstreamProtocol,
+    ProtocolsHandler,
+    ProtocolsHandlerEvent,
+    ProtocolsHandlerUpgrErr
+};
+use std::{collections::VecDeque, fmt};
+use tokio_io::{AsyncRead, AsyncWrite};
+use void::Void;
+use wasm_timer::Instant;
+
+/// Hole punching attempt started by the remote, whose addresses we have received.
+pub struct InboundConnect<TSubstream> {
+    pub(crate) substream: Negotiated<TSubstream>,
+    pub(crate) remote_addrs: Vec<Multiaddr>,
+}
+
+impl<TSubstream> InboundConnect<TSubstream> {
+    /// Returns the addresses sent by the remote.
+    pub fn remote_addrs(&self) -> &[Multiaddr] {
+        &self.remote_addrs
+    }
+}
+
+impl<TSubstream> fmt::Debug for InboundConnect<TSubstream> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("InboundConnect")
+            .field("remote_addrs", &self.remote_addrs)
+            .finish()
+    }
+}
+
+/// Event sent to the `DcutrHandler` by the behaviour.
+pub enum DcutrHandlerIn<TSubstream> {
+    /// Starts a hole punching attempt, sending our addresses to the remote.
+    Connect {
+        local_addrs: Vec<Multiaddr>,
+        attempt: u8,
+    },
+    /// Answers a hole punching attempt of the remote with our addresses.
+    AcceptInbound {
+        request: InboundConnect<TSubstream>,
+        local_addrs: Vec<Multiaddr>,
+    },
+}
+
+/// Event produced by the `DcutrHandler`.
+pub enum DcutrHandlerEvent<TSubstream> {
+    /// The remote started a hole punching attempt.
+    InboundConnect(InboundConnect<TSubstream>),
+    /// The remote sent the `SYNC` message of an attempt it started: its addresses must be dialed
+    /// immediately.
+    InboundSync {
+        remote_addrs: Vec<Multiaddr>,
+    },
+    /// An attempt we started is synchronized: the addresses of the remote must be dialed.
+    OutboundSync {
+        remote_addrs: Vec<Multiaddr>,
+        attempt: u8,
+    },
+    /// An attempt we started failed before any dial.
+    OutboundFailure {
+        attempt: u8,
+        error: DcutrError,
+    },
+}
+
+/// Error of a hole punching attempt.
+#[derive(Debug)]
+pub enum DcutrError {
+    /// We don't know any address of ours to send to the remote.
+    NoAddresses,
+    /// The remote doesn't support the protocol.
+    UnsupportedProtocol,
+    /// The remote didn't answer in time.
+    Timeout,
+    /// The exchange of addresses with the remote failed.
+    Connect(DcutrConnectError),
+    /// None of the addresses of the remote could be dialed.
+    DialFailure,
+}
+
+impl fmt::Display for DcutrError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DcutrError::NoAddresses => write!(f, "No local address to send to the remote"),
+            DcutrError::UnsupportedProtocol => write!(f, "The remote doesn't support the protocol"),
+            DcutrError::Timeout => write!(f, "The remote didn't answer in time"),
+            DcutrError::Connect(e) => write!(f, "{}", e),
+            DcutrError::DialFailure => write!(f, "Failed to dial the remote"),
+        }
+    }
+}
+
+impl std::error::Error for DcutrError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            DcutrError::Connect(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+/// Protocol handler exchanging the addresses and synchronizing the dials of hole punching
+/// attempts over a relayed connection.
+pub struct DcutrHandler<TSubstream> {
+    /// Configuration options.
+    config: DcutrConfig,
+    /// Value to return from `connection_keep_alive`.
+    keep_alive: KeepAlive,
+    /// Attempts for which an outbound substream must be opened.
+    outbound: VecDeque<(DcutrConnect, u8)>,
+    /// Number of outbound substreams being opened or negotiated.
+    num_pending_outbound: usize,
+    /// Inbound attempts waiting for the `SYNC` message, with the addresses of the remote.
+    inbound: Vec<(Vec<Multiaddr>, IoFuture<()>)>,
+    /// Events to produce in `poll()`.
+    pending_events: VecDeque<DcutrHandlerEvent<TSubstream>>,
+}
+
+impl<TSubstream> DcutrHandler<TSubstream> {
+    pub(crate) fn new(config: DcutrConfig) -> Self {
+        let keep_alive = KeepAlive::Until(Instant::now() + config.connection_idle_timeout);
+        DcutrHandler {
+            config,
+            keep_alive,
+            outbound: VecDeque::new(),
+            num_pending_outbound: 0,
+            inbound: Vec::new(),
+            pending_events: VecDeque::new(),
+        }
+    }
+
+    /// Updates the keep-alive after the state of the handler has changed.
+    fn update_keep_alive(&mut self) {
+        let busy = !self.outbound.is_empty()
+            || self.num_pending_outbound > 0
+            || !self.inbound.is_empty();
+        if busy {
+            self.keep_alive = KeepAlive::Yes;
+        } else if let KeepAlive::Yes = self.keep_alive {
+            self.keep_alive = KeepAlive::Until(Instant::now() + self.config.connection_idle_timeout);
+        }
+    }
+}
+
+impl<TSubstream> ProtocolsHandler for DcutrHandler<TSubstream>
+where
+    TSubstream: AsyncRead + AsyncWrite + Send + 'static,
+{
+    type InEvent = DcutrHandlerIn<TSubstream>;
+    type OutEvent = DcutrHandlerEvent<TSubstream>;
+    type Error = Void;
+    type Substream = TSubstream;
+    type InboundProtocol = DcutrListen;
+    type OutboundProtocol = DcutrConnect;
+    type OutboundOpenInfo = u8;
+
+    fn listen_protocol(&self) -> SubstreamProtocol<Self::InboundProtocol> {
+        SubstreamProtocol::new(DcutrListen).with_timeout(self.config.request_timeout)
+    }
+
+    fn inject_fully_negotiated_inbound(&mut self, (remote_addrs, substream): (Vec<Multiaddr>, Negotiated<TSubstream>)) {
+        let request = InboundConnect { substream, remote_addrs };
+        self.pending_events.push_back(DcutrHandlerEvent::InboundConnect(request));
+    }
+
+    fn inject_fully_negotiated_outbound(&mut self, remote_addrs: Vec<Multiaddr>, attempt: u8) {
+        self.num_pending_outbound -= 1;
+        self.pending_events.push_back(DcutrHandlerEvent::OutboundSync { remote_addrs, attempt });
+        self.update_keep_alive();
+    }
+
+    fn inject_event(&mut self, event: Self::InEvent) {
+        match event {
+            DcutrHandlerIn::Connect { local_addrs, attempt } => {
+                self.outbound.push_back((DcutrConnect { local_addrs }, attempt));
+            }
+            DcutrHandlerIn::AcceptInbound { request, local_addrs } => {
+                let future = protocol::answer_connect(request.substream, local_addrs);
+                self.inbound.push((request.remote_addrs, future));
+            }
+        }
+        self.update_keep_alive();
+    }
+
+    fn inject_dial_upgrade_error(&mut self, attempt: u8, error: ProtocolsHandlerUpgrErr<DcutrConnectError>) {
+        self.num_pending_outbound -= 1;
+        let error = match error {
+            ProtocolsHandlerUpgrErr::Timeout | ProtocolsHandlerUpgrErr::Timer => DcutrError::Timeout,
+            ProtocolsHandlerUpgrErr::Upgrade(UpgradeError::Select(_)) => DcutrError::UnsupportedProtocol,
+            ProtocolsHandlerUpgrErr::Upgrade(UpgradeError::Apply(e)) => DcutrError::Connect(e),
+        };
+        self.pending_events.push_back(DcutrHandlerEvent::OutboundFailure { attempt, error });
+        self.update_keep_alive();
+    }
+
+    fn connection_keep_alive(&self) -> KeepAlive {
+        self.keep_alive
+    }
+
+    fn poll(&mut self) -> Poll<ProtocolsHandlerEvent<DcutrConnect, u8, Self::OutEvent>, Void> {
+        let mut n = 0;
+        while n < self.inbound.len() {
+            match self.inbound[n].1.poll() {
+                Ok(Async::NotReady) => n += 1,
+                Ok(Async::Ready(())) => {
+                    let (remote_addrs, _) = self.inbound.swap_remove(n);
+                    self.pending_events.push_back(DcutrHandlerEvent::InboundSync { remote_addrs });
+                }
+                Err(err) => {
+                    log::debug!("Failed to answer a hole punching attempt: {}", err);
+                    self.inbound.swap_remove(n);
+                }
+            }
+        }
+
+        self.update_keep_alive();
+
+        if let Some(event) = self.pending_events.pop_front() {
+            return Ok(Async::Ready(ProtocolsHandlerEvent::Custom(event)))
+        }
+
+        if let Some((upgrade, attempt)) = self.outbound.pop_front() {
+            self.num_pending_outbound += 1;
+            let protocol = SubstreamProtocol::new(upgrade).with_timeout(self.config.request_timeout);
+            return Ok(Async::Ready(ProtocolsHandlerEvent::OutboundSubstreamRequest { protocol, info: attempt }))
+        }
+
+        Ok(Async::NotReady)
+    }
+}
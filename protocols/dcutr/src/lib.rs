@@ -0,0 +1,92 @@
+// Copyright 2019 Parity Technologies (UK) Ltd.
+//
+// Permission is hereby granted, free of charge, to any person obtaining a
+// copy of this software and associated documentation files (the "Software"),
+// to deal in the Software without restriction, including without limitation
+// the rights to use, copy, modify, merge, publish, distribute, sublicense,
+// and/or sell copies of the Software, and to permit persons to whom the
+// Software is furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS
+// OR IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING
+// FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
+// DEALINGS IN THE SOFTWARE.
+
+//! Implementation of the [Direct Connection Upgrade through Relay] protocol.
+//!
+//! Two peers behind NATs can connect to each other through a relay, as implemented by the
+//! `libp2p-relay` crate, but the relay limits and slows down the connection. The [`Dcutr`]
+//! network behaviour uses the relayed connection to coordinate a direct connection: both peers
+//! exchange their addresses, then dial each other at the same time so that the packets of each
+//! dial open the way through the NAT of the other peer.
+//!
+//! The addresses exchanged are the ones the other peers observed for us, therefore outgoing
+//! connections must use the port we listen on. The TCP transport does so when created with
+//! `TcpConfig::port_reuse(true)`.
+//!
+//! [Direct Connection Upgrade through Relay]: https://github.com/libp2p/specs/blob/master/relay/DCUtR.md
+
+mod behaviour;
+mod handler;
+mod protocol;
+
+/// Protobuf messages of the protocol, generated from `message.proto`.
+mod proto {
+    include!(concat!(env!("OUT_DIR"), "/holepunch.pb.rs"));
+}
+
+pub use behaviour::{Dcutr, DcutrEvent};
+pub use handler::{DcutrError, DcutrHandler, DcutrHandlerEvent, DcutrHandlerIn, InboundConnect};
+pub use protocol::{DcutrConnect, DcutrConnectError, DcutrListen, HolePunch, MessageType, PROTOCOL_NAME};
+
+use std::time::Duration;
+
+/// Configuration of the [`Dcutr`] network behaviour.
+#[derive(Debug, Clone)]
+pub struct DcutrConfig {
+    max_attempts: u8,
+    request_timeout: Duration,
+    connection_idle_timeout: Duration,
+}
+
+impl Default for DcutrConfig {
+    fn default() -> Self {
+        DcutrConfig {
+            max_attempts: 3,
+            request_timeout: Duration::from_secs(10),
+            connection_idle_timeout: Duration::from_secs(10),
+        }
+    }
+}
+
+impl DcutrConfig {
+    /// Sets the number of hole punching attempts made before giving up. Defaults to 3.
+    ///
+    /// # Panic
+    ///
+    /// Panics if `attempts` is zero.
+    pub fn with_max_attempts(mut self, attempts: u8) -> Self {
+        assert!(attempts > 0, "at least one attempt must be made");
+        self.max_attempts = attempts;
+        self
+    }
+
+    /// Sets how long the remote has to answer the exchange of addresses. Defaults to 10 seconds.
+    pub fn with_request_timeout(mut self, timeout: Duration) -> Self {
+        self.request_timeout = timeout;
+        self
+    }
+
+    /// Sets how long a relayed connection without hole punching in progress is kept alive.
+    /// Defaults to 10 seconds.
+    pub fn with_connection_idle_timeout(mut self, timeout: Duration) -> Self {
+        self.connection_idle_timeout = timeout;
+        self
+    }
+}
@@ -0,0 +1,314 @@
+// Copyright 2019 Parity Technologies (UK) Ltd.
+//
+// Permission is hereby granted, free of charge, to any person obtaining a
+// copy of this software and associated documentation files (the "Software"),
+// to deal in the Software without restriction, including without limitation
+// the rights to use, copy, modify, merge, publish, distribute, sublicense,
+// and/or sell copies of the Software, and to permit persons to whom the
+// Software is furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS
+// OR IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING
+// FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
+// DEALINGS IN THE SOFTWARE.
+
+//! Messages of the hole punching protocol and upgrades applied on its substreams.
+//!
+//! The peer that accepted the relayed connection opens a substream and sends a `CONNECT` message
+//! with its addresses, to which the remote answers with a `CONNECT` message carrying its own.
+//! The time between the two is the round trip time of the relayed connection. A `SYNC` message
+//! then tells the remote to dial immediately, while we wait for half the round trip time before
+//! dialing, so that both dials cross the NATs at about the same time.
+
+use crate::proto;
+use futures::{future::{self, Loop}, prelude::*};
+use libp2p_core::{
+    InboundUpgrade,
+    Multiaddr,
+    OutboundUpgrade,
+    UpgradeInfo,
+    upgrade::Negotiated
+};
+use prost::{DecodeError, Message};
+use std::{convert::TryFrom, error, fmt, io, iter};
+use tokio_io::{AsyncRead, AsyncWrite};
+use wasm_timer::{Delay, Instant};
+
+/// Name of the protocol negotiated on the substreams.
+pub const PROTOCOL_NAME: &[u8] = b"/libp2p/dcutr";
+
+/// Maximum size of a message.
+const MAX_MESSAGE_SIZE: usize = 4096;
+
+/// Boxed future performing I/O on a substream.
+pub(crate) type IoFuture<T> = Box<dyn Future<Item = T, Error = io::Error> + Send>;
+
+/// Type of a hole punching message.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum MessageType {
+    /// Carries the addresses of the sender, to which the receiver should dial.
+    Connect,
+    /// Tells the receiver to dial the addresses it received.
+    Sync,
+}
+
+impl MessageType {
+    fn code(self) -> i32 {
+        match self {
+            MessageType::Connect => 100,
+            MessageType::Sync => 300,
+        }
+    }
+
+    fn from_code(code: i32) -> Option<Self> {
+        match code {
+            100 => Some(MessageType::Connect),
+            300 => Some(MessageType::Sync),
+            _ => None,
+        }
+    }
+}
+
+/// A message of the hole punching protocol.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HolePunch {
+    pub kind: MessageType,
+    /// Addresses of the sender. Addresses that couldn't be decoded are skipped.
+    pub obs_addrs: Vec<Multiaddr>,
+}
+
+impl HolePunch {
+    /// Encodes the message, without its length prefix.
+    pub fn encode(&self) -> Vec<u8> {
+        let message = proto::HolePunch {
+            r#type: self.kind.code(),
+            obs_addrs: self.obs_addrs.iter().map(Multiaddr::to_vec).collect(),
+        };
+        let mut buf = Vec::with_capacity(message.encoded_len());
+        message.encode(&mut buf).expect("Vec<u8> provides capacity as needed");
+        buf
+    }
+
+    /// Decodes a message, without its length prefix.
+    pub fn decode(bytes: &[u8]) -> Result<Self, DecodeError> {
+        let message = proto::HolePunch::decode(bytes)?;
+        // A missing type is decoded as 0, which isn't a valid type either.
+        let kind = MessageType::from_code(message.r#type)
+            .ok_or_else(|| DecodeError::new("unknown message type"))?;
+        let obs_addrs = message.obs_addrs
+            .into_iter()
+            .filter_map(|addr| Multiaddr::try_from(addr).ok())
+            .collect();
+        Ok(HolePunch { kind, obs_addrs })
+    }
+}
+
+/// Writes a length-prefixed message on a substream and flushes it.
+pub(crate) fn send_message<S>(socket: S, message: &HolePunch) -> IoFuture<S>
+where
+    S: AsyncWrite + Send + 'static,
+{
+    let body = message.encode();
+    let mut len = unsigned_varint::encode::usize_buffer();
+    let mut bytes = unsigned_varint::encode::usize(body.len(), &mut len).to_vec();
+    bytes.extend_from_slice(&body);
+    Box::new(tokio_io::io::write_all(socket, bytes).and_then(|(socket, _)| tokio_io::io::flush(socket)))
+}
+
+/// Reads a length-prefixed message from a substream.
+pub(crate) fn recv_message<S>(socket: S) -> IoFuture<(S, HolePunch)>
+where
+    S: AsyncRead + Send + 'static,
+{
+    let read_len = future::loop_fn((socket, 0u64, 0u32), |(socket, len, shift)| {
+        tokio_io::io::read_exact(socket, [0u8; 1]).and_then(move |(socket, byte)| {
+            let len = len | (u64::from(byte[0] & 0x7f) << shift);
+            if byte[0] & 0x80 == 0 {
+                Ok(Loop::Break((socket, len)))
+            } else if shift >= 56 {
+                Err(io::Error::new(io::ErrorKind::InvalidData, "invalid message length"))
+            } else {
+                Ok(Loop::Continue((socket, len, shift + 7)))
+            }
+        })
+    });
+
+    let message = read_len
+        .and_then(|(socket, len)| {
+            if len > MAX_MESSAGE_SIZE as u64 {
+                return Err(io::Error::new(io::ErrorKind::InvalidData, "message too large"))
+            }
+            Ok(tokio_io::io::read_exact(socket, vec![0; len as usize]))
+        })
+        .flatten()
+        .and_then(|(socket, bytes)| {
+            let message = HolePunch::decode(&bytes)
+                .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+            Ok((socket, message))
+        });
+    Box::new(message)
+}
+
+/// Upgrade applied on inbound substreams. Reads the `CONNECT` message of the remote, and
+/// produces its addresses along with the substream.
+#[derive(Debug, Copy, Clone, Default)]
+pub struct DcutrListen;
+
+impl UpgradeInfo for DcutrListen {
+    type Info = &'static [u8];
+    type InfoIter = iter::Once<Self::Info>;
+
+    fn protocol_info(&self) -> Self::InfoIter {
+        iter::once(PROTOCOL_NAME)
+    }
+}
+
+impl<TSocket> InboundUpgrade<TSocket> for DcutrListen
+where
+    TSocket: AsyncRead + AsyncWrite + Send + 'static,
+{
+    type Output = (Vec<Multiaddr>, Negotiated<TSocket>);
+    type Error = io::Error;
+    type Future = IoFuture<Self::Output>;
+
+    fn upgrade_inbound(self, socket: Negotiated<TSocket>, _: Self::Info) -> Self::Future {
+        let future = recv_message(socket).and_then(|(socket, message)| match message.kind {
+            MessageType::Connect => Ok((message.obs_addrs, socket)),
+            MessageType::Sync => Err(io::Error::new(io::ErrorKind::InvalidData, "expected a CONNECT message")),
+        });
+        Box::new(future)
+    }
+}
+
+/// Answers the `CONNECT` message received on an inbound substream with our own addresses, and
+/// waits for the `SYNC` message telling us to dial.
+pub(crate) fn answer_connect<S>(socket: S, local_addrs: Vec<Multiaddr>) -> IoFuture<()>
+where
+    S: AsyncRead + AsyncWrite + Send + 'static,
+{
+    let message = HolePunch { kind: MessageType::Connect, obs_addrs: local_addrs };
+    let future = send_message(socket, &message)
+        .and_then(recv_message)
+        .and_then(|(_, message)| match message.kind {
+            MessageType::Sync => Ok(()),
+            MessageType::Connect => Err(io::Error::new(io::ErrorKind::InvalidData, "expected a SYNC message")),
+        });
+    Box::new(future)
+}
+
+/// Upgrade applied on outbound substreams. Exchanges the addresses with the remote, sends the
+/// `SYNC` message, waits for half the measured round trip time, and produces the addresses of
+/// the remote, which must then be dialed.
+#[derive(Debug, Clone)]
+pub struct DcutrConnect {
+    pub(crate) local_addrs: Vec<Multiaddr>,
+}
+
+impl UpgradeInfo for DcutrConnect {
+    type Info = &'static [u8];
+    type InfoIter = iter::Once<Self::Info>;
+
+    fn protocol_info(&self) -> Self::InfoIter {
+        iter::once(PROTOCOL_NAME)
+    }
+}
+
+impl<TSocket> OutboundUpgrade<TSocket> for DcutrConnect
+where
+    TSocket: AsyncRead + AsyncWrite + Send + 'static,
+{
+    type Output = Vec<Multiaddr>;
+    type Error = DcutrConnectError;
+    type Future = Box<dyn Future<Item = Self::Output, Error = Self::Error> + Send>;
+
+    fn upgrade_outbound(self, socket: Negotiated<TSocket>, _: Self::Info) -> Self::Future {
+        let message = HolePunch { kind: MessageType::Connect, obs_addrs: self.local_addrs };
+        let future = send_message(socket, &message)
+            .map(|socket| (socket, Instant::now()))
+            .and_then(|(socket, sent)| recv_message(socket).map(move |(s, m)| (s, m, sent.elapsed())))
+            .map_err(DcutrConnectError::Io)
+            .and_then(|(socket, answer, rtt)| {
+                if answer.kind != MessageType::Connect {
+                    return Err(DcutrConnectError::UnexpectedMessage)
+                }
+                if answer.obs_addrs.is_empty() {
+                    return Err(DcutrConnectError::NoAddresses)
+                }
+                Ok((socket, answer.obs_addrs, rtt))
+            })
+            .and_then(|(socket, remote_addrs, rtt)| {
+                let sync = HolePunch { kind: MessageType::Sync, obs_addrs: Vec::new() };
+                send_message(socket, &sync)
+                    .and_then(move |_| Delay::new(Instant::now() + rtt / 2))
+                    .map(move |()| remote_addrs)
+                    .map_err(DcutrConnectError::Io)
+            });
+        Box::new(future)
+    }
+}
+
+/// Error while exchanging the addresses with a remote.
+#[derive(Debug)]
+pub enum DcutrConnectError {
+    /// The remote answered with something else than a `CONNECT` message.
+    UnexpectedMessage,
+    /// The remote didn't send any address to dial.
+    NoAddresses,
+    /// I/O error on the substream.
+    Io(io::Error),
+}
+
+impl fmt::Display for DcutrConnectError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DcutrConnectError::UnexpectedMessage => write!(f, "Unexpected answer from the remote"),
+            DcutrConnectError::NoAddresses => write!(f, "The remote didn't send any address"),
+            DcutrConnectError::Io(e) => write!(f, "I/O error: {}", e),
+        }
+    }
+}
+
+impl error::Error for DcutrConnectError {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        match self {
+            DcutrConnectError::Io(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use libp2p_core::multiaddr::multiaddr;
+
+    #[test]
+    fn encode_decode_roundtrip() {
+        let message = HolePunch {
+            kind: MessageType::Connect,
+            obs_addrs: vec![
+                multiaddr![Ip4([1, 2, 3, 4]), Tcp(4001u16)],
+                multiaddr![Ip6([0, 0, 0, 0, 0, 0, 0, 1]), Tcp(4002u16)],
+            ],
+        };
+        assert_eq!(HolePunch::decode(&message.encode()), Ok(message));
+
+        let sync = HolePunch { kind: MessageType::Sync, obs_addrs: Vec::new() };
+        assert_eq!(HolePunch::decode(&sync.encode()), Ok(sync));
+    }
+
+    #[test]
+    fn missing_type_is_rejected() {
+        // Only field 2, holding an address.
+        let addr = multiaddr![Ip4([1, 2, 3, 4]), Tcp(4001u16)].to_vec();
+        let mut bytes = vec![2 << 3 | 2, addr.len() as u8];
+        bytes.extend_from_slice(&addr);
+        assert!(HolePunch::decode(&bytes).is_err());
+    }
+}
@@ -18,23 +18,25 @@
 // FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
 // DEALINGS IN THE SOFTWARE.
 
-use crate::protocol::{FloodsubConfig, FloodsubMessage, FloodsubRpc, FloodsubSubscription, FloodsubSubscriptionAction};
+use crate::protocol::{FloodsubConfig, FloodsubMessage, FloodsubRpc, FloodsubSubscription, FloodsubSubscriptionAction, MessageId};
 use crate::topic::{Topic, TopicHash};
-use cuckoofilter::CuckooFilter;
 use fnv::FnvHashSet;
 use futures::prelude::*;
-use libp2p_core::{ConnectedPoint, Multiaddr, PeerId};
+use log::{debug, warn};
+use libp2p_core::{BoxedRng, ConnectedPoint, Multiaddr, PeerId, SeenCache, identity::Keypair};
 use libp2p_swarm::{
     NetworkBehaviour,
     NetworkBehaviourAction,
     PollParameters,
     ProtocolsHandler,
-    OneShotHandler
+    OneShotHandler,
+    SubstreamTimedOut
 };
-use rand;
+use rand::Rng;
 use smallvec::SmallVec;
-use std::{collections::VecDeque, iter, marker::PhantomData};
-use std::collections::hash_map::{DefaultHasher, HashMap};
+use std::{collections::VecDeque, iter, marker::PhantomData, time::Duration};
+use std::sync::Arc;
+use std::collections::HashMap;
 use tokio_io::{AsyncRead, AsyncWrite};
 
 /// Network behaviour that automatically identifies nodes periodically, and returns information
@@ -46,6 +48,12 @@ pub struct Floodsub<TSubstream> {
     /// Peer id of the local node. Used for the source of the messages that we publish.
     local_peer_id: PeerId,
 
+    /// Key the messages we publish are signed with, if any.
+    keypair: Option<Keypair>,
+
+    /// If true, received messages that aren't signed are dropped.
+    strict_signing: bool,
+
     /// List of peers to send messages to.
     target_peers: FnvHashSet<PeerId>,
 
@@ -58,9 +66,21 @@ pub struct Floodsub<TSubstream> {
     // erroneously.
     subscribed_topics: SmallVec<[Topic; 16]>,
 
-    // We keep track of the messages we received (in the format `hash(source ID, seq_no)`) so that
-    // we don't dispatch the same message twice if we receive it twice on the network.
-    received: CuckooFilter<DefaultHasher>,
+    // We keep track of the messages we received (by `MessageId`) so that we don't dispatch the
+    // same message twice if we receive it twice on the network.
+    received: SeenCache<MessageId>,
+
+    /// Function computing the identifier of a message.
+    message_id_fn: fn(&FloodsubMessage) -> MessageId,
+
+    /// Returns true for the topics whose subscriptions we track. All topics if `None`.
+    subscription_filter: Option<Arc<dyn Fn(&TopicHash) -> bool + Send + Sync>>,
+
+    /// Maximum number of topics a remote can be subscribed to.
+    max_subscriptions_per_peer: Option<usize>,
+
+    /// Source of the sequence numbers.
+    rng: BoxedRng,
 
     /// Marker to pin the generics.
     marker: PhantomData<TSubstream>,
@@ -68,18 +88,85 @@ pub struct Floodsub<TSubstream> {
 
 impl<TSubstream> Floodsub<TSubstream> {
     /// Creates a `Floodsub`.
+    ///
+    /// The messages we publish aren't signed. See `with_keypair` for signing them.
     pub fn new(local_peer_id: PeerId) -> Self {
         Floodsub {
             events: VecDeque::new(),
             local_peer_id,
+            keypair: None,
+            strict_signing: false,
             target_peers: FnvHashSet::default(),
             connected_peers: HashMap::new(),
             subscribed_topics: SmallVec::new(),
-            received: CuckooFilter::new(),
+            received: SeenCache::new(Duration::from_secs(120), 4 * 1024 * 1024),
+            message_id_fn: default_message_id,
+            subscription_filter: None,
+            max_subscriptions_per_peer: None,
+            rng: BoxedRng::default(),
             marker: PhantomData,
         }
     }
 
+    /// Creates a `Floodsub` that signs the messages it publishes with the given key.
+    ///
+    /// The local peer id is derived from the key.
+    pub fn with_keypair(local_key: Keypair) -> Self {
+        let mut floodsub = Floodsub::new(local_key.public().into_peer_id());
+        floodsub.keypair = Some(local_key);
+        floodsub
+    }
+
+    /// Sets whether received messages that aren't signed are dropped.
+    ///
+    /// Messages with an invalid signature are always dropped. Unsigned messages are accepted by
+    /// default, for compatibility with nodes that don't sign their messages.
+    pub fn set_strict_signing(&mut self, strict: bool) {
+        self.strict_signing = strict;
+    }
+
+    /// Sets the function computing the identifier of a message, which is used to detect
+    /// duplicates.
+    ///
+    /// The default concatenates the source of the message and its sequence number. Identifying
+    /// messages by a hash of their content instead avoids delivering twice the same data
+    /// published by different nodes.
+    pub fn set_message_id_fn(&mut self, f: fn(&FloodsubMessage) -> MessageId) {
+        self.message_id_fn = f;
+    }
+
+    /// Sets the filter of the topics whose subscriptions we track.
+    ///
+    /// Subscriptions of the remotes to topics for which the filter returns false are ignored,
+    /// so that remotes can't exhaust our memory by subscribing to a large number of arbitrary
+    /// topics. All the topics are accepted by default.
+    pub fn set_subscription_filter<F>(&mut self, filter: F)
+    where
+        F: Fn(&TopicHash) -> bool + Send + Sync + 'static,
+    {
+        self.subscription_filter = Some(Arc::new(filter));
+    }
+
+    /// Sets the maximum number of topics a remote can be subscribed to. Subscriptions beyond
+    /// that number are ignored. There is no limit by default.
+    pub fn set_max_subscriptions_per_peer(&mut self, max: usize) {
+        self.max_subscriptions_per_peer = Some(max);
+    }
+
+    /// Sets for how long the identifiers of the received messages are remembered to detect
+    /// duplicates, and the maximum number of bytes they can use. If more messages are received
+    /// during `window`, the oldest identifiers are forgotten early.
+    ///
+    /// The defaults are 2 minutes and 4MiB.
+    pub fn set_duplicate_cache(&mut self, window: Duration, max_bytes: usize) {
+        self.received = SeenCache::new(window, max_bytes);
+    }
+
+    /// Sets the generator of the sequence numbers of the published messages.
+    pub fn set_rng(&mut self, rng: BoxedRng) {
+        self.rng = rng;
+    }
+
     /// Add a node to the list of nodes to propagate messages to.
     #[inline]
     pub fn add_node_to_partial_view(&mut self, peer_id: PeerId) {
@@ -191,19 +278,28 @@ impl<TSubstream> Floodsub<TSubstream> {
     }
 
     fn publish_many_inner(&mut self, topic: impl IntoIterator<Item = impl Into<TopicHash>>, data: impl Into<Vec<u8>>, check_self_subscriptions: bool) {
-        let message = FloodsubMessage {
+        let mut message = FloodsubMessage {
             source: self.local_peer_id.clone(),
             data: data.into(),
             // If the sequence numbers are predictable, then an attacker could flood the network
             // with packets with the predetermined sequence numbers and absorb our legitimate
             // messages. We therefore use a random number.
-            sequence_number: rand::random::<[u8; 20]>().to_vec(),
+            sequence_number: self.rng.gen::<[u8; 20]>().to_vec(),
             topics: topic.into_iter().map(|t| t.into().clone()).collect(),
+            signature: None,
+            key: None,
         };
 
+        if let Some(keypair) = self.keypair.as_ref() {
+            if let Err(err) = message.sign(keypair) {
+                warn!("Failed to sign floodsub message: {:?}", err);
+                return;
+            }
+        }
+
         let self_subscribed = self.subscribed_topics.iter().any(|t| message.topics.iter().any(|u| t.hash() == u));
         if self_subscribed {
-            self.received.add(&message);
+            self.received.insert((self.message_id_fn)(&message));
         }
         // Don't publish the message if we have to check subscriptions
         // and we're not subscribed ourselves to any of the topics.
@@ -282,7 +378,7 @@ where
         // We ignore successful sends event.
         let event = match event {
             InnerMessage::Rx(event) => event,
-            InnerMessage::Sent => return,
+            InnerMessage::Sent | InnerMessage::TimedOut => return,
         };
 
         // Update connected peers topics
@@ -293,6 +389,14 @@ where
             match subscription.action {
                 FloodsubSubscriptionAction::Subscribe => {
                     if !remote_peer_topics.contains(&subscription.topic) {
+                        let over_limit = self.max_subscriptions_per_peer
+                            .map_or(false, |max| remote_peer_topics.len() >= max);
+                        let allowed = self.subscription_filter.as_ref()
+                            .map_or(true, |filter| filter(&subscription.topic));
+                        if over_limit || !allowed {
+                            debug!("Ignoring subscription of {:?} to {:?}", propagation_source, subscription.topic);
+                            continue;
+                        }
                         remote_peer_topics.push(subscription.topic.clone());
                     }
                     self.events.push_back(NetworkBehaviourAction::GenerateEvent(FloodsubEvent::Subscribed {
@@ -316,9 +420,16 @@ where
         let mut rpcs_to_dispatch: Vec<(PeerId, FloodsubRpc)> = Vec::new();
 
         for message in event.messages {
-            // Use `self.received` to skip the messages that we have already received in the past.
-            // Note that this can false positive.
-            if !self.received.test_and_add(&message) {
+            // Drop the messages that are forged, and the unsigned ones if signatures are required.
+            if message.signature.is_some() || self.strict_signing {
+                if !message.verify_signature() {
+                    debug!("Dropping message from {:?} with a missing or invalid signature", message.source);
+                    continue;
+                }
+            }
+
+            // Use `self.received` to skip the messages that we have already received recently.
+            if !self.received.insert((self.message_id_fn)(&message)) {
                 continue;
             }
 
@@ -374,12 +485,21 @@ where
     }
 }
 
+/// Identifies a message by its source and its sequence number.
+fn default_message_id(message: &FloodsubMessage) -> MessageId {
+    let mut id = message.source.as_bytes().to_vec();
+    id.extend_from_slice(&message.sequence_number);
+    MessageId::new(id)
+}
+
 /// Transmission between the `OneShotHandler` and the `FloodsubHandler`.
 pub enum InnerMessage {
     /// We received an RPC from a remote.
     Rx(FloodsubRpc),
     /// We successfully sent an RPC request.
     Sent,
+    /// Sending an RPC request took too long and the substream has been closed.
+    TimedOut,
 }
 
 impl From<FloodsubRpc> for InnerMessage {
@@ -396,6 +516,13 @@ impl From<()> for InnerMessage {
     }
 }
 
+impl From<SubstreamTimedOut> for InnerMessage {
+    #[inline]
+    fn from(_: SubstreamTimedOut) -> InnerMessage {
+        InnerMessage::TimedOut
+    }
+}
+
 /// Event that can happen on the floodsub behaviour.
 #[derive(Debug)]
 pub enum FloodsubEvent {
@@ -418,3 +545,152 @@ pub enum FloodsubEvent {
         topic: TopicHash,
     },
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::topic::TopicBuilder;
+    use libp2p_core::transport::dummy::DummyStream;
+
+    type TestFloodsub = Floodsub<DummyStream>;
+
+    fn endpoint() -> ConnectedPoint {
+        ConnectedPoint::Dialer { address: "/ip4/127.0.0.1/tcp/1234".parse().unwrap() }
+    }
+
+    /// Builds a behaviour subscribed to the topic `test` and connected to a peer.
+    fn build() -> (TestFloodsub, TopicHash, PeerId) {
+        let topic = TopicBuilder::new("test").build();
+        let hash = topic.hash().clone();
+        let mut floodsub = Floodsub::new(PeerId::random());
+        let peer_id = PeerId::random();
+        floodsub.inject_connected(peer_id.clone(), endpoint());
+        floodsub.subscribe(topic);
+        floodsub.events.clear();
+        (floodsub, hash, peer_id)
+    }
+
+    fn unsigned_message(topic: &TopicHash, data: &[u8]) -> FloodsubMessage {
+        FloodsubMessage {
+            source: PeerId::random(),
+            data: data.to_vec(),
+            sequence_number: vec![1],
+            topics: vec![topic.clone()],
+            signature: None,
+            key: None,
+        }
+    }
+
+    fn receive(floodsub: &mut TestFloodsub, peer_id: &PeerId, messages: Vec<FloodsubMessage>) {
+        let rpc = FloodsubRpc { messages, subscriptions: Vec::new() };
+        floodsub.inject_node_event(peer_id.clone(), InnerMessage::Rx(rpc));
+    }
+
+    /// Returns the messages delivered to the user.
+    fn delivered(floodsub: &TestFloodsub) -> Vec<FloodsubMessage> {
+        floodsub.events
+            .iter()
+            .filter_map(|event| match event {
+                NetworkBehaviourAction::GenerateEvent(FloodsubEvent::Message(message)) => Some(message.clone()),
+                _ => None,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn strict_signing_drops_unsigned_messages() {
+        let (mut floodsub, topic, peer_id) = build();
+        receive(&mut floodsub, &peer_id, vec![unsigned_message(&topic, b"lax")]);
+        assert_eq!(delivered(&floodsub).len(), 1);
+
+        floodsub.events.clear();
+        floodsub.set_strict_signing(true);
+        receive(&mut floodsub, &peer_id, vec![unsigned_message(&topic, b"strict")]);
+        assert!(delivered(&floodsub).is_empty());
+
+        let key = Keypair::generate_ed25519();
+        let mut signed = unsigned_message(&topic, b"signed");
+        signed.source = key.public().into_peer_id();
+        signed.sign(&key).unwrap();
+        receive(&mut floodsub, &peer_id, vec![signed.clone()]);
+        assert_eq!(delivered(&floodsub), vec![signed]);
+    }
+
+    #[test]
+    fn message_id_fn_deduplicates() {
+        let (mut floodsub, topic, peer_id) = build();
+        receive(&mut floodsub, &peer_id, vec![unsigned_message(&topic, b"a"), unsigned_message(&topic, b"a")]);
+        assert_eq!(delivered(&floodsub).len(), 2);
+
+        let (mut floodsub, topic, peer_id) = build();
+        floodsub.set_message_id_fn(|message| MessageId::new(message.data.clone()));
+        receive(&mut floodsub, &peer_id, vec![unsigned_message(&topic, b"a"), unsigned_message(&topic, b"a")]);
+        receive(&mut floodsub, &peer_id, vec![unsigned_message(&topic, b"b")]);
+        let data = delivered(&floodsub).into_iter().map(|message| message.data).collect::<Vec<_>>();
+        assert_eq!(data, vec![b"a".to_vec(), b"b".to_vec()]);
+    }
+
+    fn subscribe_remote(floodsub: &mut TestFloodsub, peer_id: &PeerId, topics: &[&str]) {
+        let subscriptions = topics.iter()
+            .map(|topic| FloodsubSubscription {
+                action: FloodsubSubscriptionAction::Subscribe,
+                topic: TopicBuilder::new(*topic).build().hash().clone(),
+            })
+            .collect();
+        let rpc = FloodsubRpc { messages: Vec::new(), subscriptions };
+        floodsub.inject_node_event(peer_id.clone(), InnerMessage::Rx(rpc));
+    }
+
+    /// Returns the topics the remotes have been reported subscribing to.
+    fn subscribed(floodsub: &TestFloodsub) -> Vec<TopicHash> {
+        floodsub.events
+            .iter()
+            .filter_map(|event| match event {
+                NetworkBehaviourAction::GenerateEvent(FloodsubEvent::Subscribed { topic, .. }) => Some(topic.clone()),
+                _ => None,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn subscription_filter_ignores_topics() {
+        let (mut floodsub, _, peer_id) = build();
+        let allowed = TopicBuilder::new("allowed").build().hash().clone();
+        let filter_topic = allowed.clone();
+        floodsub.set_subscription_filter(move |topic| topic == &filter_topic);
+        subscribe_remote(&mut floodsub, &peer_id, &["allowed", "denied"]);
+
+        assert_eq!(subscribed(&floodsub), vec![allowed.clone()]);
+        assert_eq!(floodsub.connected_peers[&peer_id].to_vec(), vec![allowed]);
+    }
+
+    #[test]
+    fn max_subscriptions_per_peer() {
+        let (mut floodsub, _, peer_id) = build();
+        floodsub.set_max_subscriptions_per_peer(2);
+        subscribe_remote(&mut floodsub, &peer_id, &["a", "b", "c"]);
+        subscribe_remote(&mut floodsub, &peer_id, &["a"]);
+
+        let topics = ["a", "b", "a"].iter()
+            .map(|topic| TopicBuilder::new(*topic).build().hash().clone())
+            .collect::<Vec<_>>();
+        assert_eq!(subscribed(&floodsub), topics);
+        assert_eq!(floodsub.connected_peers[&peer_id].len(), 2);
+    }
+
+    #[test]
+    fn duplicates_are_delivered_once() {
+        let (mut floodsub, topic, peer_id) = build();
+        let (a, b) = (unsigned_message(&topic, b"a"), unsigned_message(&topic, b"b"));
+        receive(&mut floodsub, &peer_id, vec![a.clone(), a.clone(), b.clone()]);
+        receive(&mut floodsub, &peer_id, vec![a.clone()]);
+        assert_eq!(delivered(&floodsub), vec![a.clone(), b.clone()]);
+
+        // The identifier of `a` is forgotten when that of `b` is inserted.
+        let (mut floodsub, topic, peer_id) = build();
+        let (a, b) = (unsigned_message(&topic, b"a"), unsigned_message(&topic, b"b"));
+        floodsub.set_duplicate_cache(Duration::from_secs(120), 0);
+        receive(&mut floodsub, &peer_id, vec![a.clone(), a.clone(), b.clone(), a.clone()]);
+        assert_eq!(delivered(&floodsub), vec![a.clone(), b, a]);
+    }
+}
@@ -24,9 +24,13 @@
 pub mod protocol;
 
 mod layer;
-mod rpc_proto;
 mod topic;
 
+/// Protobuf messages of the floodsub protocol, generated from `rpc.proto`.
+pub mod rpc_proto {
+    include!(concat!(env!("OUT_DIR"), "/floodsub.pb.rs"));
+}
+
 pub use self::layer::{Floodsub, FloodsubEvent};
-pub use self::protocol::{FloodsubMessage, FloodsubRpc};
+pub use self::protocol::{FloodsubMessage, FloodsubRpc, MessageId};
 pub use self::topic::{Topic, TopicBuilder, TopicHash};
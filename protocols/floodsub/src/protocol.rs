@@ -20,8 +20,9 @@
 
 use crate::rpc_proto;
 use crate::topic::TopicHash;
-use libp2p_core::{InboundUpgrade, OutboundUpgrade, UpgradeInfo, PeerId, upgrade};
-use protobuf::{ProtobufError, Message as ProtobufMessage};
+use libp2p_core::{InboundUpgrade, OutboundUpgrade, UpgradeInfo, PeerId, PublicKey, upgrade};
+use libp2p_core::identity::{Keypair, error::SigningError};
+use prost::Message;
 use std::{error, fmt, io, iter};
 use tokio_io::{AsyncRead, AsyncWrite};
 
@@ -57,41 +58,7 @@ where
 
     #[inline]
     fn upgrade_inbound(self, socket: upgrade::Negotiated<TSocket>, _: Self::Info) -> Self::Future {
-        upgrade::read_one_then(socket, 2048, (), |packet, ()| {
-            let mut rpc: rpc_proto::RPC = protobuf::parse_from_bytes(&packet)?;
-
-            let mut messages = Vec::with_capacity(rpc.get_publish().len());
-            for mut publish in rpc.take_publish().into_iter() {
-                messages.push(FloodsubMessage {
-                    source: PeerId::from_bytes(publish.take_from()).map_err(|_| {
-                        FloodsubDecodeError::InvalidPeerId
-                    })?,
-                    data: publish.take_data(),
-                    sequence_number: publish.take_seqno(),
-                    topics: publish
-                        .take_topicIDs()
-                        .into_iter()
-                        .map(TopicHash::from_raw)
-                        .collect(),
-                });
-            }
-
-            Ok(FloodsubRpc {
-                messages,
-                subscriptions: rpc
-                    .take_subscriptions()
-                    .into_iter()
-                    .map(|mut sub| FloodsubSubscription {
-                        action: if sub.get_subscribe() {
-                            FloodsubSubscriptionAction::Subscribe
-                        } else {
-                            FloodsubSubscriptionAction::Unsubscribe
-                        },
-                        topic: TopicHash::from_raw(sub.take_topicid()),
-                    })
-                    .collect(),
-            })
-        })
+        upgrade::read_one_then(socket, 2048, (), |packet, ()| FloodsubRpc::from_bytes(&packet))
     }
 }
 
@@ -101,7 +68,7 @@ pub enum FloodsubDecodeError {
     /// Error when reading the packet from the socket.
     ReadError(upgrade::ReadOneError),
     /// Error when decoding the raw buffer into a protobuf.
-    ProtobufError(ProtobufError),
+    ProtobufError(prost::DecodeError),
     /// Error when parsing the `PeerId` in the message.
     InvalidPeerId,
 }
@@ -113,9 +80,9 @@ impl From<upgrade::ReadOneError> for FloodsubDecodeError {
     }
 }
 
-impl From<ProtobufError> for FloodsubDecodeError {
+impl From<prost::DecodeError> for FloodsubDecodeError {
     #[inline]
-    fn from(err: ProtobufError) -> Self {
+    fn from(err: prost::DecodeError) -> Self {
         FloodsubDecodeError::ProtobufError(err)
     }
 }
@@ -178,35 +145,100 @@ where
 }
 
 impl FloodsubRpc {
-    /// Turns this `FloodsubRpc` into a message that can be sent to a substream.
-    fn into_bytes(self) -> Vec<u8> {
-        let mut proto = rpc_proto::RPC::new();
-
-        for message in self.messages {
-            let mut msg = rpc_proto::Message::new();
-            msg.set_from(message.source.into_bytes());
-            msg.set_data(message.data);
-            msg.set_seqno(message.sequence_number);
-            msg.set_topicIDs(
-                message
-                    .topics
+    /// Decodes an RPC received on a substream.
+    fn from_bytes(packet: &[u8]) -> Result<FloodsubRpc, FloodsubDecodeError> {
+        let rpc = rpc_proto::Rpc::decode(packet)?;
+
+        let mut messages = Vec::with_capacity(rpc.publish.len());
+        for publish in rpc.publish.into_iter() {
+            messages.push(FloodsubMessage {
+                source: PeerId::from_bytes(publish.from.unwrap_or_default()).map_err(|_| {
+                    FloodsubDecodeError::InvalidPeerId
+                })?,
+                data: publish.data.unwrap_or_default(),
+                sequence_number: publish.seqno.unwrap_or_default(),
+                topics: publish
+                    .topic_ids
                     .into_iter()
-                    .map(TopicHash::into_string)
+                    .map(TopicHash::from_raw)
                     .collect(),
-            );
-            proto.mut_publish().push(msg);
+                signature: publish.signature,
+                key: publish.key,
+            });
+        }
+
+        Ok(FloodsubRpc {
+            messages,
+            subscriptions: rpc
+                .subscriptions
+                .into_iter()
+                .map(|sub| FloodsubSubscription {
+                    action: if sub.subscribe.unwrap_or(false) {
+                        FloodsubSubscriptionAction::Subscribe
+                    } else {
+                        FloodsubSubscriptionAction::Unsubscribe
+                    },
+                    topic: TopicHash::from_raw(sub.topicid.unwrap_or_default()),
+                })
+                .collect(),
+        })
+    }
+
+    /// Turns this `FloodsubRpc` into a message that can be sent to a substream.
+    fn into_bytes(self) -> Vec<u8> {
+        let mut proto = rpc_proto::Rpc::default();
+
+        for mut message in self.messages {
+            let signature = message.signature.take();
+            let key = message.key.take();
+            let mut msg = message.into_unsigned_proto();
+            msg.signature = signature;
+            msg.key = key;
+            proto.publish.push(msg);
         }
 
         for topic in self.subscriptions {
-            let mut subscription = rpc_proto::RPC_SubOpts::new();
-            subscription.set_subscribe(topic.action == FloodsubSubscriptionAction::Subscribe);
-            subscription.set_topicid(topic.topic.into_string());
-            proto.mut_subscriptions().push(subscription);
+            proto.subscriptions.push(rpc_proto::rpc::SubOpts {
+                subscribe: Some(topic.action == FloodsubSubscriptionAction::Subscribe),
+                topicid: Some(topic.topic.into_string()),
+            });
         }
 
+        let mut bytes = Vec::with_capacity(proto.encoded_len());
         proto
-            .write_to_bytes()
-            .expect("there is no situation in which the protobuf message can be invalid")
+            .encode(&mut bytes)
+            .expect("Vec<u8> provides capacity as needed");
+        bytes
+    }
+}
+
+/// Identifier of a message, used to detect duplicates.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct MessageId(Vec<u8>);
+
+impl MessageId {
+    /// Builds a `MessageId` from raw bytes.
+    #[inline]
+    pub fn new(bytes: impl Into<Vec<u8>>) -> MessageId {
+        MessageId(bytes.into())
+    }
+
+    /// Returns the raw bytes of the identifier.
+    #[inline]
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.0
+    }
+
+    /// Turns the identifier into its raw bytes.
+    #[inline]
+    pub fn into_bytes(self) -> Vec<u8> {
+        self.0
+    }
+}
+
+impl AsRef<[u8]> for MessageId {
+    fn as_ref(&self) -> &[u8] {
+        &self.0
     }
 }
 
@@ -226,6 +258,70 @@ pub struct FloodsubMessage {
     ///
     /// Each message can belong to multiple topics at once.
     pub topics: Vec<TopicHash>,
+
+    /// Signature of the message by its source, if the message is signed.
+    pub signature: Option<Vec<u8>>,
+
+    /// Protobuf encoding of the public key of the source, which the signature is verified with.
+    pub key: Option<Vec<u8>>,
+}
+
+impl FloodsubMessage {
+    /// Prefix of the bytes that are signed, as defined by the pubsub specification.
+    const SIGNING_PREFIX: &'static [u8] = b"libp2p-pubsub:";
+
+    /// Signs the message with the key of its source, and attaches the public key to it.
+    pub(crate) fn sign(&mut self, key: &Keypair) -> Result<(), SigningError> {
+        let signature = key.sign(&self.signable_bytes())?;
+        self.signature = Some(signature);
+        self.key = Some(key.public().into_protobuf_encoding());
+        Ok(())
+    }
+
+    /// Returns `true` if the message is signed, and if the signature is valid and has been
+    /// produced by the source of the message.
+    pub fn verify_signature(&self) -> bool {
+        let (signature, key) = match (&self.signature, &self.key) {
+            (Some(signature), Some(key)) => (signature, key),
+            _ => return false,
+        };
+
+        let key = match PublicKey::from_protobuf_encoding(key) {
+            Ok(key) => key,
+            Err(_) => return false,
+        };
+
+        self.source.is_public_key(&key) == Some(true)
+            && key.verify(&self.signable_bytes(), signature)
+    }
+
+    /// Returns the bytes covered by the signature, i.e. the prefix followed by the encoding of
+    /// the message without its signature and key.
+    fn signable_bytes(&self) -> Vec<u8> {
+        let mut bytes = Self::SIGNING_PREFIX.to_vec();
+        let proto = self.clone().into_unsigned_proto();
+        bytes.reserve(proto.encoded_len());
+        proto
+            .encode(&mut bytes)
+            .expect("Vec<u8> provides capacity as needed");
+        bytes
+    }
+
+    /// Turns the message into its protobuf representation, without the signature and key.
+    fn into_unsigned_proto(self) -> rpc_proto::Message {
+        rpc_proto::Message {
+            from: Some(self.source.into_bytes()),
+            data: Some(self.data),
+            // Messages without a sequence number must be signed without the field.
+            seqno: Some(self.sequence_number).filter(|seqno| !seqno.is_empty()),
+            topic_ids: self.topics
+                .into_iter()
+                .map(TopicHash::into_string)
+                .collect(),
+            signature: None,
+            key: None,
+        }
+    }
 }
 
 /// A subscription received by the floodsub system.
@@ -245,3 +341,71 @@ pub enum FloodsubSubscriptionAction {
     /// The remote wants to unsubscribe from the given topic.
     Unsubscribe,
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn signed_message(key: &Keypair, sequence_number: Vec<u8>) -> FloodsubMessage {
+        let mut message = FloodsubMessage {
+            source: key.public().into_peer_id(),
+            data: b"hello".to_vec(),
+            sequence_number,
+            topics: vec![TopicHash::from_raw("test".to_owned())],
+            signature: None,
+            key: None,
+        };
+        message.sign(key).unwrap();
+        message
+    }
+
+    fn roundtrip(message: FloodsubMessage) -> FloodsubMessage {
+        let rpc = FloodsubRpc { messages: vec![message], subscriptions: Vec::new() };
+        FloodsubRpc::from_bytes(&rpc.into_bytes()).unwrap().messages.remove(0)
+    }
+
+    #[test]
+    fn signed_message_roundtrip() {
+        let message = signed_message(&Keypair::generate_ed25519(), vec![1, 2, 3]);
+        let decoded = roundtrip(message.clone());
+        assert_eq!(decoded, message);
+        assert!(decoded.verify_signature());
+    }
+
+    #[test]
+    fn signature_without_sequence_number_verified() {
+        let decoded = roundtrip(signed_message(&Keypair::generate_ed25519(), Vec::new()));
+        assert!(decoded.verify_signature());
+    }
+
+    #[test]
+    fn changed_payload_is_rejected() {
+        let mut message = signed_message(&Keypair::generate_ed25519(), vec![1]);
+        message.data = b"bye".to_vec();
+        assert!(!roundtrip(message).verify_signature());
+    }
+
+    #[test]
+    fn wrong_key_is_rejected() {
+        let mut message = signed_message(&Keypair::generate_ed25519(), vec![1]);
+        message.key = Some(Keypair::generate_ed25519().public().into_protobuf_encoding());
+        assert!(!roundtrip(message).verify_signature());
+    }
+
+    #[test]
+    fn source_not_matching_the_key_is_rejected() {
+        let key = Keypair::generate_ed25519();
+        let mut message = signed_message(&key, vec![1]);
+        message.source = PeerId::random();
+        message.sign(&key).unwrap();
+        assert!(!roundtrip(message).verify_signature());
+    }
+
+    #[test]
+    fn unsigned_message_is_not_verified() {
+        let mut message = signed_message(&Keypair::generate_ed25519(), vec![1]);
+        message.signature = None;
+        message.key = None;
+        assert!(!roundtrip(message).verify_signature());
+    }
+}
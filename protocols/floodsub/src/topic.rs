@@ -20,7 +20,7 @@
 
 use bs58;
 use crate::rpc_proto;
-use protobuf::Message;
+use prost::Message;
 
 /// Represents the hash of a topic.
 ///
@@ -91,18 +91,21 @@ impl TopicBuilder {
     where
         S: Into<String>,
     {
-        let mut builder = rpc_proto::TopicDescriptor::new();
-        builder.set_name(name.into());
+        let builder = rpc_proto::TopicDescriptor {
+            name: Some(name.into()),
+            auth: None,
+            enc: None,
+        };
 
         TopicBuilder { builder }
     }
 
     /// Turns the builder into an actual `Topic`.
     pub fn build(self) -> Topic {
-        let bytes = self
-            .builder
-            .write_to_bytes()
-            .expect("protobuf message is always valid");
+        let mut bytes = Vec::with_capacity(self.builder.encoded_len());
+        self.builder
+            .encode(&mut bytes)
+            .expect("Vec<u8> provides capacity as needed");
         // TODO: https://github.com/libp2p/rust-libp2p/issues/473
         let hash = TopicHash {
             hash: bs58::encode(&bytes).into_string(),
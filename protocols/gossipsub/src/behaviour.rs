@@ -0,0 +1,1694 @@
+// Copyright 2019 Parity Technologies (UK) Ltd.
+//
+// Permission is hereby granted, free of charge, to any person obtaining a
+// copy of this software and associated documentation files (the "Software"),
+// to deal in the Software without restriction, including without limitation
+// the rights to use, copy, modify, merge, publish, distribute, sublicense,
+// and/or sell copies of the Software, and to permit persons to whom the
+// Software is furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS
+// OR IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING
+// FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
+// DEALINGS IN THE SOFTWARE.
+
+use crate::config::{GossipsubConfig, SigningPolicy};
+use crate::handler::GossipsubHandler;
+use crate::mcache::MessageCache;
+use crate::peer_score::{PeerScore, PeerScoreThresholds};
+use crate::protocol::{
+    GossipsubControlAction,
+    GossipsubMessage,
+    GossipsubRpc,
+    GossipsubSubscription,
+    GossipsubSubscriptionAction,
+    MessageId,
+    PeerInfo
+};
+use crate::topic::{Topic, TopicHash};
+use futures::prelude::*;
+use libp2p_core::{BoxedRng, ConnectedPoint, Multiaddr, PeerId, PeerRecord, SeenCache};
+use libp2p_swarm::{NetworkBehaviour, NetworkBehaviourAction, PollParameters, ProtocolsHandler};
+use log::{debug, trace};
+use rand::{Rng, seq::SliceRandom};
+use std::{cmp::Ordering, collections::{HashMap, HashSet, VecDeque}, iter, marker::PhantomData, time::Duration};
+use tokio_io::{AsyncRead, AsyncWrite};
+use wasm_timer::{Delay, Instant};
+
+/// Network behaviour that implements the gossipsub protocol.
+///
+/// For each topic we're subscribed to, messages are exchanged with a limited set of peers, the
+/// *mesh*, whose size is kept within bounds at every heartbeat. Peers outside of the mesh are
+/// told about the messages we have recently seen with `IHAVE` control messages, and can request
+/// them with `IWANT`.
+///
+/// If enabled in the configuration, peers are scored according to their behaviour. Peers with a
+/// negative score are removed from the meshes, and peers with a score below the thresholds are
+/// excluded from the gossip, the publication of our messages, or ignored altogether.
+pub struct Gossipsub<TSubstream> {
+    /// Configuration of the behaviour.
+    config: GossipsubConfig,
+
+    /// Events that need to be yielded to the outside when polling.
+    events: VecDeque<NetworkBehaviourAction<GossipsubRpc, GossipsubEvent>>,
+
+    /// Peer id of the local node. Used for the source of the messages that we publish.
+    local_peer_id: PeerId,
+
+    /// Peers we're connected to, and the topics they're subscribed to.
+    peer_topics: HashMap<PeerId, HashSet<TopicHash>>,
+
+    /// Peers subscribed to each topic.
+    topic_peers: HashMap<TopicHash, HashSet<PeerId>>,
+
+    /// Mesh peers of each topic we're subscribed to. The keys are our subscriptions.
+    mesh: HashMap<TopicHash, HashSet<PeerId>>,
+
+    /// Peers we publish to for the topics we publish to without being subscribed.
+    fanout: HashMap<TopicHash, HashSet<PeerId>>,
+
+    /// Last time we published to each topic of `fanout`.
+    fanout_last_pub: HashMap<TopicHash, Instant>,
+
+    /// For each topic, the peers that must not be grafted before the given time.
+    backoffs: HashMap<TopicHash, HashMap<PeerId, Instant>>,
+
+    /// Addresses of the peers learned through peer exchange, that we're dialing.
+    px_addresses: HashMap<PeerId, Vec<Multiaddr>>,
+
+    /// Direct peers, that receive all the messages of their topics without being part of the
+    /// meshes, and their addresses.
+    direct_peers: HashMap<PeerId, Vec<Multiaddr>>,
+
+    /// Scores of the peers, if scoring is enabled.
+    peer_score: Option<PeerScore>,
+
+    /// Thresholds of the scores. All zero if scoring is disabled.
+    thresholds: PeerScoreThresholds,
+
+    /// Messages seen during the last heartbeats.
+    mcache: MessageCache,
+
+    /// Messages waiting for the user to validate them.
+    pending_validation: HashMap<MessageId, PendingValidation>,
+
+    // We keep track of the messages we received (by `MessageId`) so that we don't dispatch the
+    // same message twice if we receive it twice on the network. Identifiers are forgotten after
+    // `duplicate_cache_time`, or earlier if they would exceed `duplicate_cache_max_bytes`.
+    received: SeenCache<MessageId>,
+
+    /// When the next heartbeat happens.
+    next_heartbeat: Delay,
+
+    /// Number of heartbeats that happened.
+    heartbeat_ticks: u64,
+
+    /// Source of the sequence numbers and of the random choices of peers.
+    rng: BoxedRng,
+
+    /// Marker to pin the generics.
+    marker: PhantomData<TSubstream>,
+}
+
+impl<TSubstream> Gossipsub<TSubstream> {
+    /// Creates a `Gossipsub`.
+    pub fn new(local_peer_id: PeerId, config: GossipsubConfig) -> Self {
+        let (peer_score, thresholds) = match config.peer_score.clone() {
+            Some((params, thresholds)) => (Some(PeerScore::new(params)), thresholds),
+            None => (None, PeerScoreThresholds::default()),
+        };
+
+        Gossipsub {
+            events: VecDeque::new(),
+            local_peer_id,
+            peer_topics: HashMap::new(),
+            topic_peers: HashMap::new(),
+            mesh: HashMap::new(),
+            fanout: HashMap::new(),
+            fanout_last_pub: HashMap::new(),
+            backoffs: HashMap::new(),
+            px_addresses: HashMap::new(),
+            direct_peers: config.direct_peers.iter().cloned().collect(),
+            peer_score,
+            thresholds,
+            mcache: MessageCache::new(config.history_gossip, config.history_length),
+            pending_validation: HashMap::new(),
+            received: SeenCache::new(config.duplicate_cache_time, config.duplicate_cache_max_bytes),
+            next_heartbeat: Delay::new(Instant::now() + config.heartbeat_initial_delay),
+            heartbeat_ticks: 0,
+            rng: BoxedRng::default(),
+            config,
+            marker: PhantomData,
+        }
+    }
+
+    /// Sets the generator of the sequence numbers and of the random choices of peers.
+    pub fn set_rng(&mut self, rng: BoxedRng) {
+        self.rng = rng;
+    }
+
+    /// Subscribes to a topic, and builds the mesh of the topic.
+    ///
+    /// Returns true if the subscription worked. Returns false if we were already subscribed.
+    pub fn subscribe(&mut self, topic: Topic) -> bool {
+        let topic_hash = topic.hash();
+        if self.mesh.contains_key(topic_hash) {
+            return false;
+        }
+
+        for peer_id in self.peer_topics.keys() {
+            self.events.push_back(NetworkBehaviourAction::SendEvent {
+                peer_id: peer_id.clone(),
+                event: subscription_rpc(topic_hash.clone(), GossipsubSubscriptionAction::Subscribe),
+            });
+        }
+
+        self.join(topic_hash);
+        true
+    }
+
+    /// Unsubscribes from a topic, and leaves the mesh of the topic.
+    ///
+    /// Note that this only requires a `TopicHash` and not a full `Topic`.
+    ///
+    /// Returns true if we were subscribed to this topic.
+    pub fn unsubscribe(&mut self, topic: impl AsRef<TopicHash>) -> bool {
+        let topic_hash = topic.as_ref();
+        let mesh_peers = match self.mesh.remove(topic_hash) {
+            Some(peers) => peers,
+            None => return false,
+        };
+
+        for peer_id in self.peer_topics.keys() {
+            self.events.push_back(NetworkBehaviourAction::SendEvent {
+                peer_id: peer_id.clone(),
+                event: subscription_rpc(topic_hash.clone(), GossipsubSubscriptionAction::Unsubscribe),
+            });
+        }
+
+        for peer_id in mesh_peers {
+            let prune = self.make_prune(topic_hash, &peer_id, self.config.do_px);
+            if let Some(peer_score) = self.peer_score.as_mut() {
+                peer_score.prune(&peer_id, topic_hash);
+            }
+            add_backoff(&mut self.backoffs, topic_hash, &peer_id, self.config.prune_backoff);
+            self.send_control(peer_id, vec![prune]);
+        }
+
+        true
+    }
+
+    /// Publishes a message to the network.
+    ///
+    /// If flood publishing is enabled, the message is sent to all the peers subscribed to the
+    /// topic whose score is above `publish_threshold`. Otherwise, if we're subscribed to the
+    /// topic, the message is sent to the peers of the mesh, and if we aren't, it's sent to a
+    /// random set of peers subscribed to the topic, which is kept for the next publications.
+    pub fn publish(&mut self, topic: impl Into<TopicHash>, data: impl Into<Vec<u8>>) {
+        self.publish_many(iter::once(topic), data)
+    }
+
+    /// Publishes a message with multiple topics to the network.
+    ///
+    /// The message is signed or anonymized according to the signing policy of the
+    /// configuration.
+    pub fn publish_many(&mut self, topics: impl IntoIterator<Item = impl Into<TopicHash>>, data: impl Into<Vec<u8>>) {
+        let (source, keypair) = match &self.config.signing_policy {
+            SigningPolicy::StrictSign(keypair) | SigningPolicy::LaxSign(keypair) => {
+                (keypair.public().into_peer_id(), Some(keypair))
+            }
+            SigningPolicy::Unsigned => (self.local_peer_id.clone(), None),
+            SigningPolicy::Anonymous => (PeerId::random_with(&mut self.rng), None),
+        };
+        let mut message = GossipsubMessage {
+            source,
+            data: data.into(),
+            // If the sequence numbers are predictable, then an attacker could flood the network
+            // with packets with the predetermined sequence numbers and absorb our legitimate
+            // messages. We therefore use a random number.
+            sequence_number: self.rng.gen::<[u8; 8]>().to_vec(),
+            topics: topics.into_iter().map(Into::into).collect(),
+            signature: None,
+            key: None,
+        };
+        if let Some(keypair) = keypair {
+            if let Err(err) = message.sign(keypair) {
+                debug!("Failed to sign published message: {:?}", err);
+                return;
+            }
+        }
+
+        let id = (self.config.message_id_fn)(&message);
+        self.received.insert(id.clone());
+
+        let mut recipients = HashSet::new();
+        for topic_hash in &message.topics {
+            if self.config.flood_publish {
+                if let Some(peers) = self.topic_peers.get(topic_hash) {
+                    let peer_score = &self.peer_score;
+                    let direct_peers = &self.direct_peers;
+                    let publish_threshold = self.thresholds.publish_threshold;
+                    recipients.extend(peers.iter()
+                        .filter(|p| direct_peers.contains_key(*p) || score_of(peer_score, p) >= publish_threshold)
+                        .cloned());
+                }
+                continue;
+            }
+
+            recipients.extend(direct_topic_peers(&self.topic_peers, &self.direct_peers, topic_hash));
+
+            if let Some(mesh_peers) = self.mesh.get(topic_hash) {
+                recipients.extend(mesh_peers.iter().cloned());
+                continue;
+            }
+
+            if !self.fanout.contains_key(topic_hash) {
+                let peer_score = &self.peer_score;
+                let direct_peers = &self.direct_peers;
+                let publish_threshold = self.thresholds.publish_threshold;
+                let peers = get_random_peers(&mut self.rng, &self.topic_peers, topic_hash, self.config.mesh_n, |p| {
+                    !direct_peers.contains_key(p) && score_of(peer_score, p) >= publish_threshold
+                });
+                self.fanout.insert(topic_hash.clone(), peers.into_iter().collect());
+            }
+            recipients.extend(self.fanout[topic_hash].iter().cloned());
+            self.fanout_last_pub.insert(topic_hash.clone(), Instant::now());
+        }
+
+        self.mcache.put(id, message.clone());
+        for peer_id in recipients {
+            self.send_messages(peer_id, vec![message.clone()]);
+        }
+    }
+
+    /// Returns the peers of the mesh of a topic.
+    pub fn mesh_peers<'a>(&'a self, topic: &TopicHash) -> impl Iterator<Item = &'a PeerId> + 'a {
+        self.mesh.get(topic).into_iter().flat_map(|peers| peers.iter())
+    }
+
+    /// Reports whether a received message is valid, after a `GossipsubEvent::Message` has been
+    /// generated for it while message validation is enabled.
+    ///
+    /// Accepted messages are forwarded to the mesh and advertised in the gossip. Rejected
+    /// messages are dropped and penalize the score of the peer we received them from, while
+    /// ignored messages are dropped without penalty.
+    ///
+    /// Returns false if the message isn't waiting for validation, for example because it has
+    /// already been reported, or because it has been received more than `history_length`
+    /// heartbeats ago.
+    pub fn report_message_validation_result(&mut self, message_id: &MessageId, acceptance: MessageAcceptance) -> bool {
+        let pending = match self.pending_validation.remove(message_id) {
+            Some(pending) => pending,
+            None => return false,
+        };
+
+        match acceptance {
+            MessageAcceptance::Accept => {
+                if let Some(peer_score) = self.peer_score.as_mut() {
+                    peer_score.deliver_message(&pending.propagation_source, message_id, &pending.message.topics);
+                }
+                self.forward_message(message_id.clone(), pending.message, &pending.propagation_source);
+            }
+            MessageAcceptance::Reject => {
+                debug!("Message from {:?} has been rejected", pending.propagation_source);
+                if let Some(peer_score) = self.peer_score.as_mut() {
+                    peer_score.reject_message(&pending.propagation_source, &pending.message.topics);
+                }
+            }
+            MessageAcceptance::Ignore => {}
+        }
+
+        true
+    }
+
+    /// Returns the score of a peer, if scoring is enabled.
+    pub fn peer_score(&self, peer_id: &PeerId) -> Option<f64> {
+        self.peer_score.as_ref().map(|peer_score| peer_score.score(peer_id))
+    }
+
+    /// Sets the application-specific score of a connected peer, which contributes to its score
+    /// with the `app_specific_weight` of the scoring parameters.
+    ///
+    /// Returns false if scoring is disabled or if the peer is unknown.
+    pub fn set_application_score(&mut self, peer_id: &PeerId, score: f64) -> bool {
+        match self.peer_score.as_mut() {
+            Some(peer_score) => peer_score.set_application_score(peer_id, score),
+            None => false,
+        }
+    }
+
+    /// Builds the mesh of a topic we subscribed to, starting with the fanout peers if we
+    /// published to the topic.
+    fn join(&mut self, topic_hash: &TopicHash) {
+        let mut peers: HashSet<PeerId> = self.fanout.remove(topic_hash)
+            .unwrap_or_default()
+            .into_iter()
+            .filter(|p| {
+                !self.direct_peers.contains_key(p)
+                    && score_of(&self.peer_score, p) >= 0.0
+                    && !is_backed_off(&self.backoffs, topic_hash, p)
+            })
+            .take(self.config.mesh_n)
+            .collect();
+        self.fanout_last_pub.remove(topic_hash);
+
+        if peers.len() < self.config.mesh_n {
+            let needed = self.config.mesh_n - peers.len();
+            let peer_score = &self.peer_score;
+            let backoffs = &self.backoffs;
+            let direct_peers = &self.direct_peers;
+            let extra = get_random_peers(&mut self.rng, &self.topic_peers, topic_hash, needed, |p| {
+                !peers.contains(p)
+                    && !direct_peers.contains_key(p)
+                    && score_of(peer_score, p) >= 0.0
+                    && !is_backed_off(backoffs, topic_hash, p)
+            });
+            peers.extend(extra);
+        }
+
+        for peer_id in &peers {
+            if let Some(peer_score) = self.peer_score.as_mut() {
+                peer_score.graft(peer_id, topic_hash);
+            }
+            self.send_control(peer_id.clone(), vec![GossipsubControlAction::Graft { topic_hash: topic_hash.clone() }]);
+        }
+        self.mesh.insert(topic_hash.clone(), peers);
+    }
+
+    /// Builds a `PRUNE` for a peer, including other peers of the topic if `do_px` is true.
+    fn make_prune(&mut self, topic_hash: &TopicHash, peer_id: &PeerId, do_px: bool) -> GossipsubControlAction {
+        let peers = if do_px {
+            px_peers(&mut self.rng, &self.topic_peers, &self.peer_score, topic_hash, peer_id, self.config.prune_peers)
+        } else {
+            Vec::new()
+        };
+        prune_action(topic_hash, peers, self.config.prune_backoff)
+    }
+
+    /// Updates the topics of a peer.
+    fn handle_received_subscriptions(&mut self, subscriptions: Vec<GossipsubSubscription>, propagation_source: &PeerId) {
+        let peer_topics = match self.peer_topics.get_mut(propagation_source) {
+            Some(topics) => topics,
+            None => {
+                debug!("Received subscriptions from unknown peer {:?}", propagation_source);
+                return;
+            }
+        };
+
+        for subscription in subscriptions {
+            match subscription.action {
+                GossipsubSubscriptionAction::Subscribe => {
+                    if peer_topics.contains(&subscription.topic) {
+                        continue;
+                    }
+                    if !self.config.subscription_filter.accepts(&subscription.topic, peer_topics.len()) {
+                        debug!("Ignoring subscription of {:?} to {:?}", propagation_source, subscription.topic);
+                        continue;
+                    }
+                    peer_topics.insert(subscription.topic.clone());
+                    self.topic_peers
+                        .entry(subscription.topic.clone())
+                        .or_insert_with(HashSet::new)
+                        .insert(propagation_source.clone());
+                    self.events.push_back(NetworkBehaviourAction::GenerateEvent(GossipsubEvent::Subscribed {
+                        peer_id: propagation_source.clone(),
+                        topic: subscription.topic,
+                    }));
+                }
+                GossipsubSubscriptionAction::Unsubscribe => {
+                    if !peer_topics.remove(&subscription.topic) {
+                        continue;
+                    }
+                    remove_peer_from_topic(&mut self.topic_peers, &subscription.topic, propagation_source);
+                    if let Some(peers) = self.mesh.get_mut(&subscription.topic) {
+                        if peers.remove(propagation_source) {
+                            if let Some(peer_score) = self.peer_score.as_mut() {
+                                peer_score.prune(propagation_source, &subscription.topic);
+                            }
+                        }
+                    }
+                    if let Some(peers) = self.fanout.get_mut(&subscription.topic) {
+                        peers.remove(propagation_source);
+                    }
+                    self.events.push_back(NetworkBehaviourAction::GenerateEvent(GossipsubEvent::Unsubscribed {
+                        peer_id: propagation_source.clone(),
+                        topic: subscription.topic,
+                    }));
+                }
+            }
+        }
+    }
+
+    /// Dispatches a message to the user and forwards it to the mesh, unless we've already seen
+    /// it.
+    ///
+    /// If messages must be validated, the message is only forwarded once the user accepts it.
+    fn handle_received_message(&mut self, message: GossipsubMessage, propagation_source: &PeerId) {
+        // Checked before the message is considered received, so that an invalid copy doesn't
+        // prevent the valid one from being processed.
+        if !self.config.signing_policy.accepts(&message) {
+            debug!("Dropping message from {:?} violating the signing policy", propagation_source);
+            if let Some(peer_score) = self.peer_score.as_mut() {
+                peer_score.reject_message(propagation_source, &message.topics);
+            }
+            return;
+        }
+
+        let id = (self.config.message_id_fn)(&message);
+        if !self.received.insert(id.clone()) {
+            trace!("Ignoring already received message from {:?}", propagation_source);
+            if let Some(peer_score) = self.peer_score.as_mut() {
+                peer_score.duplicate_message(propagation_source, &id, &message.topics);
+            }
+            return;
+        }
+
+        let subscribed = message.topics.iter().any(|t| self.mesh.contains_key(t));
+        if subscribed {
+            let event = GossipsubEvent::Message {
+                propagation_source: propagation_source.clone(),
+                message_id: id.clone(),
+                message: message.clone(),
+            };
+            self.events.push_back(NetworkBehaviourAction::GenerateEvent(event));
+        }
+
+        if self.config.validate_messages {
+            // Nobody is going to validate the messages of topics we aren't subscribed to.
+            if subscribed {
+                self.pending_validation.insert(id, PendingValidation {
+                    message,
+                    propagation_source: propagation_source.clone(),
+                    received_tick: self.heartbeat_ticks,
+                });
+            }
+            return;
+        }
+
+        if let Some(peer_score) = self.peer_score.as_mut() {
+            peer_score.deliver_message(propagation_source, &id, &message.topics);
+        }
+        self.forward_message(id, message, propagation_source);
+    }
+
+    /// Caches a message and sends it to the mesh peers and the direct peers of its topics, except
+    /// its source and the peer we received it from.
+    fn forward_message(&mut self, id: MessageId, message: GossipsubMessage, propagation_source: &PeerId) {
+        self.mcache.put(id, message.clone());
+
+        let mut recipients = HashSet::new();
+        for topic_hash in &message.topics {
+            recipients.extend(direct_topic_peers(&self.topic_peers, &self.direct_peers, topic_hash)
+                .filter(|p| *p != *propagation_source && *p != message.source));
+            if let Some(mesh_peers) = self.mesh.get(topic_hash) {
+                recipients.extend(mesh_peers.iter()
+                    .filter(|p| *p != propagation_source && **p != message.source)
+                    .cloned());
+            }
+        }
+
+        for peer_id in recipients {
+            self.send_messages(peer_id, vec![message.clone()]);
+        }
+    }
+
+    /// Requests the advertised messages that we haven't seen yet.
+    fn handle_ihave(&mut self, peer_id: &PeerId, ihave: Vec<(TopicHash, Vec<MessageId>)>) {
+        if score_of(&self.peer_score, peer_id) < self.thresholds.gossip_threshold {
+            debug!("Ignoring IHAVE from {:?}, whose score is below the gossip threshold", peer_id);
+            return;
+        }
+
+        let mut message_ids = HashSet::new();
+        for (topic_hash, ids) in ihave {
+            if !self.mesh.contains_key(&topic_hash) {
+                continue;
+            }
+            let received = &mut self.received;
+            message_ids.extend(ids.into_iter().filter(|id| !received.contains(id)));
+        }
+
+        if !message_ids.is_empty() {
+            let message_ids = message_ids.into_iter().collect();
+            self.send_control(peer_id.clone(), vec![GossipsubControlAction::IWant { message_ids }]);
+        }
+    }
+
+    /// Sends the requested messages that are still in the cache.
+    fn handle_iwant(&mut self, peer_id: &PeerId, message_ids: Vec<MessageId>) {
+        if score_of(&self.peer_score, peer_id) < self.thresholds.gossip_threshold {
+            debug!("Ignoring IWANT from {:?}, whose score is below the gossip threshold", peer_id);
+            return;
+        }
+
+        let messages: Vec<_> = message_ids.iter()
+            .filter_map(|id| self.mcache.get(id))
+            .cloned()
+            .collect();
+
+        if !messages.is_empty() {
+            self.send_messages(peer_id.clone(), messages);
+        }
+    }
+
+    /// Adds a peer to the meshes of the topics we're subscribed to, unless it's backed off or
+    /// has a negative score, and prunes it from the others.
+    fn handle_graft(&mut self, peer_id: &PeerId, topics: Vec<TopicHash>) {
+        // Direct peers already receive all our messages, and are never part of the mesh.
+        if self.direct_peers.contains_key(peer_id) {
+            debug!("Direct peer {:?} sent a GRAFT", peer_id);
+            if let Some(peer_score) = self.peer_score.as_mut() {
+                peer_score.add_penalty(peer_id, 1);
+            }
+            let prunes = topics.iter()
+                .map(|topic_hash| prune_action(topic_hash, Vec::new(), self.config.prune_backoff))
+                .collect();
+            self.send_control(peer_id.clone(), prunes);
+            return;
+        }
+
+        let score = score_of(&self.peer_score, peer_id);
+        let mut prunes = Vec::new();
+        for topic_hash in topics {
+            let peers = match self.mesh.get_mut(&topic_hash) {
+                Some(peers) => peers,
+                None => {
+                    prunes.push(prune_action(&topic_hash, Vec::new(), self.config.prune_backoff));
+                    continue;
+                }
+            };
+
+            if peers.contains(peer_id) {
+                continue;
+            }
+
+            if is_backed_off(&self.backoffs, &topic_hash, peer_id) {
+                debug!("Peer {:?} grafted {:?} during its backoff", peer_id, topic_hash);
+                if let Some(peer_score) = self.peer_score.as_mut() {
+                    peer_score.add_penalty(peer_id, 1);
+                }
+            } else if score >= 0.0 {
+                peers.insert(peer_id.clone());
+                if let Some(peer_score) = self.peer_score.as_mut() {
+                    peer_score.graft(peer_id, &topic_hash);
+                }
+                continue;
+            }
+
+            add_backoff(&mut self.backoffs, &topic_hash, peer_id, self.config.prune_backoff);
+            prunes.push(prune_action(&topic_hash, Vec::new(), self.config.prune_backoff));
+        }
+
+        if !prunes.is_empty() {
+            self.send_control(peer_id.clone(), prunes);
+        }
+    }
+
+    /// Removes a peer from the mesh of a topic, and connects to the peers it sent us if its
+    /// score is high enough.
+    fn handle_prune(&mut self, peer_id: &PeerId, topic_hash: &TopicHash, px: Vec<PeerInfo>, backoff: Option<u64>) {
+        if let Some(peers) = self.mesh.get_mut(topic_hash) {
+            if peers.remove(peer_id) {
+                if let Some(peer_score) = self.peer_score.as_mut() {
+                    peer_score.prune(peer_id, topic_hash);
+                }
+            }
+        }
+
+        let backoff = backoff.map_or(self.config.prune_backoff, Duration::from_secs);
+        add_backoff(&mut self.backoffs, topic_hash, peer_id, backoff);
+
+        if !px.is_empty() && score_of(&self.peer_score, peer_id) >= self.thresholds.accept_px_threshold {
+            self.connect_px(px);
+        }
+    }
+
+    /// Dials the peers received through peer exchange that we aren't connected to.
+    fn connect_px(&mut self, px: Vec<PeerInfo>) {
+        for info in px.into_iter().take(self.config.prune_peers) {
+            let peer_id = match info.peer_id {
+                Some(peer_id) => peer_id,
+                None => continue,
+            };
+            if peer_id == self.local_peer_id || self.peer_topics.contains_key(&peer_id) {
+                continue;
+            }
+
+            if let Some(envelope) = info.signed_peer_record {
+                match PeerRecord::from_signed_envelope(envelope) {
+                    Ok(ref record) if record.peer_id() == &peer_id => {
+                        self.px_addresses.insert(peer_id.clone(), record.addresses().to_vec());
+                    }
+                    _ => {
+                        debug!("Ignoring invalid peer record of {:?} received through peer exchange", peer_id);
+                        continue;
+                    }
+                }
+            }
+
+            self.events.push_back(NetworkBehaviourAction::DialPeer { peer_id });
+        }
+    }
+
+    /// Maintains the meshes and the fanout peers, and gossips about the recent messages.
+    fn heartbeat(&mut self) {
+        self.heartbeat_ticks += 1;
+        if let Some(peer_score) = self.peer_score.as_mut() {
+            peer_score.refresh_scores();
+        }
+
+        let now = Instant::now();
+        for peers in self.backoffs.values_mut() {
+            peers.retain(|_, until| *until > now);
+        }
+        self.backoffs.retain(|_, peers| !peers.is_empty());
+
+        // Messages that haven't been validated by the time they would leave the message cache
+        // are dropped.
+        let ticks = self.heartbeat_ticks;
+        let history_length = self.config.history_length as u64;
+        self.pending_validation.retain(|_, pending| ticks - pending.received_tick < history_length);
+
+        let mut control: HashMap<PeerId, Vec<GossipsubControlAction>> = HashMap::new();
+        let opportunistic_graft = self.config.opportunistic_graft_ticks > 0
+            && self.heartbeat_ticks % self.config.opportunistic_graft_ticks == 0;
+
+        for (topic_hash, peers) in self.mesh.iter_mut() {
+            let mut to_prune = Vec::new();
+            let mut to_graft = Vec::new();
+
+            let peer_score = &self.peer_score;
+            let backoffs = &self.backoffs;
+            let direct_peers = &self.direct_peers;
+
+            // Peers with a negative score are removed, without peer exchange.
+            let negative: Vec<PeerId> = peers.iter()
+                .filter(|p| score_of(peer_score, p) < 0.0)
+                .cloned()
+                .collect();
+            for peer_id in negative {
+                peers.remove(&peer_id);
+                to_prune.push((peer_id, false));
+            }
+
+            if peers.len() < self.config.mesh_n_low {
+                let needed = self.config.mesh_n - peers.len();
+                to_graft.extend(get_random_peers(&mut self.rng, &self.topic_peers, topic_hash, needed, |p| {
+                    !peers.contains(p)
+                        && !direct_peers.contains_key(p)
+                        && score_of(peer_score, p) >= 0.0
+                        && !is_backed_off(backoffs, topic_hash, p)
+                }));
+            }
+
+            if peers.len() > self.config.mesh_n_high {
+                // Keep the peers with the best scores.
+                let mut shuffled: Vec<PeerId> = peers.iter().cloned().collect();
+                shuffled.shuffle(&mut self.rng);
+                shuffled.sort_by(|a, b| {
+                    score_of(peer_score, b)
+                        .partial_cmp(&score_of(peer_score, a))
+                        .unwrap_or(Ordering::Equal)
+                });
+                for peer_id in shuffled.into_iter().skip(self.config.mesh_n) {
+                    peers.remove(&peer_id);
+                    to_prune.push((peer_id, self.config.do_px));
+                }
+            }
+
+            if opportunistic_graft && peers.len() > 1 {
+                let mut scores: Vec<f64> = peers.iter().map(|p| score_of(peer_score, p)).collect();
+                scores.sort_by(|a, b| a.partial_cmp(b).unwrap_or(Ordering::Equal));
+                let median = scores[scores.len() / 2];
+                if median < self.thresholds.opportunistic_graft_threshold {
+                    let candidates = get_random_peers(&mut self.rng, &self.topic_peers, topic_hash, self.config.opportunistic_graft_peers, |p| {
+                        !peers.contains(p)
+                            && !direct_peers.contains_key(p)
+                            && score_of(peer_score, p) > median
+                            && !is_backed_off(backoffs, topic_hash, p)
+                    });
+                    to_graft.extend(candidates);
+                }
+            }
+
+            for peer_id in to_graft {
+                if let Some(peer_score) = self.peer_score.as_mut() {
+                    peer_score.graft(&peer_id, topic_hash);
+                }
+                control.entry(peer_id.clone())
+                    .or_insert_with(Vec::new)
+                    .push(GossipsubControlAction::Graft { topic_hash: topic_hash.clone() });
+                peers.insert(peer_id);
+            }
+
+            for (peer_id, do_px) in to_prune {
+                let px = if do_px {
+                    px_peers(&mut self.rng, &self.topic_peers, &self.peer_score, topic_hash, &peer_id, self.config.prune_peers)
+                } else {
+                    Vec::new()
+                };
+                if let Some(peer_score) = self.peer_score.as_mut() {
+                    peer_score.prune(&peer_id, topic_hash);
+                }
+                add_backoff(&mut self.backoffs, topic_hash, &peer_id, self.config.prune_backoff);
+                control.entry(peer_id)
+                    .or_insert_with(Vec::new)
+                    .push(prune_action(topic_hash, px, self.config.prune_backoff));
+            }
+        }
+
+        let fanout_ttl = self.config.fanout_ttl;
+        let fanout = &mut self.fanout;
+        self.fanout_last_pub.retain(|topic_hash, last_pub| {
+            if *last_pub + fanout_ttl < now {
+                fanout.remove(topic_hash);
+                false
+            } else {
+                true
+            }
+        });
+
+        let publish_threshold = self.thresholds.publish_threshold;
+        for (topic_hash, peers) in self.fanout.iter_mut() {
+            let topic_peers = &self.topic_peers;
+            let peer_score = &self.peer_score;
+            let direct_peers = &self.direct_peers;
+            peers.retain(|p| {
+                topic_peers.get(topic_hash).map_or(false, |t| t.contains(p))
+                    && score_of(peer_score, p) >= publish_threshold
+            });
+            if peers.len() < self.config.mesh_n {
+                let needed = self.config.mesh_n - peers.len();
+                let new_peers = get_random_peers(&mut self.rng, topic_peers, topic_hash, needed, |p| {
+                    !peers.contains(p) && !direct_peers.contains_key(p) && score_of(peer_score, p) >= publish_threshold
+                });
+                peers.extend(new_peers);
+            }
+        }
+
+        let gossip_threshold = self.thresholds.gossip_threshold;
+        for (topic_hash, peers) in self.mesh.iter().chain(self.fanout.iter()) {
+            let message_ids = self.mcache.get_gossip_ids(topic_hash);
+            if message_ids.is_empty() {
+                continue;
+            }
+
+            let peer_score = &self.peer_score;
+            let direct_peers = &self.direct_peers;
+            let gossip_peers = get_random_peers(&mut self.rng, &self.topic_peers, topic_hash, self.config.gossip_lazy, |p| {
+                !peers.contains(p) && !direct_peers.contains_key(p) && score_of(peer_score, p) >= gossip_threshold
+            });
+            for peer_id in gossip_peers {
+                control.entry(peer_id)
+                    .or_insert_with(Vec::new)
+                    .push(GossipsubControlAction::IHave {
+                        topic_hash: topic_hash.clone(),
+                        message_ids: message_ids.clone(),
+                    });
+            }
+        }
+
+        for (peer_id, actions) in control {
+            self.send_control(peer_id, actions);
+        }
+
+        // We connect to the direct peers at the first heartbeat, then periodically reconnect to
+        // the ones we've been disconnected from.
+        let direct_connect = self.heartbeat_ticks == 1 || (self.config.direct_connect_ticks > 0
+            && self.heartbeat_ticks % self.config.direct_connect_ticks == 0);
+        if direct_connect {
+            for peer_id in self.direct_peers.keys() {
+                if !self.peer_topics.contains_key(peer_id) {
+                    self.events.push_back(NetworkBehaviourAction::DialPeer { peer_id: peer_id.clone() });
+                }
+            }
+        }
+
+        self.mcache.shift();
+    }
+
+    /// Sends messages to a peer.
+    fn send_messages(&mut self, peer_id: PeerId, messages: Vec<GossipsubMessage>) {
+        self.events.push_back(NetworkBehaviourAction::SendEvent {
+            peer_id,
+            event: GossipsubRpc {
+                messages,
+                subscriptions: Vec::new(),
+                control_msgs: Vec::new(),
+            },
+        });
+    }
+
+    /// Sends control messages to a peer.
+    fn send_control(&mut self, peer_id: PeerId, control_msgs: Vec<GossipsubControlAction>) {
+        self.events.push_back(NetworkBehaviourAction::SendEvent {
+            peer_id,
+            event: GossipsubRpc {
+                messages: Vec::new(),
+                subscriptions: Vec::new(),
+                control_msgs,
+            },
+        });
+    }
+}
+
+impl<TSubstream> NetworkBehaviour for Gossipsub<TSubstream>
+where
+    TSubstream: AsyncRead + AsyncWrite,
+{
+    type ProtocolsHandler = GossipsubHandler<TSubstream>;
+    type OutEvent = GossipsubEvent;
+
+    fn new_handler(&mut self) -> Self::ProtocolsHandler {
+        GossipsubHandler::new(self.config.protocol_ids.clone(), self.config.max_transmit_size)
+    }
+
+    fn addresses_of_peer(&mut self, peer_id: &PeerId) -> Vec<Multiaddr> {
+        self.direct_peers.get(peer_id)
+            .or_else(|| self.px_addresses.get(peer_id))
+            .cloned()
+            .unwrap_or_default()
+    }
+
+    fn inject_connected(&mut self, id: PeerId, endpoint: ConnectedPoint) {
+        // We need to send our subscriptions to the newly-connected node.
+        if !self.mesh.is_empty() {
+            let subscriptions = self.mesh.keys()
+                .map(|topic_hash| GossipsubSubscription {
+                    topic: topic_hash.clone(),
+                    action: GossipsubSubscriptionAction::Subscribe,
+                })
+                .collect();
+            self.events.push_back(NetworkBehaviourAction::SendEvent {
+                peer_id: id.clone(),
+                event: GossipsubRpc {
+                    messages: Vec::new(),
+                    subscriptions,
+                    control_msgs: Vec::new(),
+                },
+            });
+        }
+
+        self.px_addresses.remove(&id);
+        if let Some(peer_score) = self.peer_score.as_mut() {
+            peer_score.add_peer(id.clone(), &endpoint);
+        }
+        self.peer_topics.insert(id, HashSet::new());
+    }
+
+    fn inject_disconnected(&mut self, id: &PeerId, _: ConnectedPoint) {
+        let topics = match self.peer_topics.remove(id) {
+            Some(topics) => topics,
+            None => return,
+        };
+
+        for topic_hash in topics {
+            remove_peer_from_topic(&mut self.topic_peers, &topic_hash, id);
+            if let Some(peers) = self.mesh.get_mut(&topic_hash) {
+                peers.remove(id);
+            }
+            if let Some(peers) = self.fanout.get_mut(&topic_hash) {
+                peers.remove(id);
+            }
+        }
+
+        if let Some(peer_score) = self.peer_score.as_mut() {
+            peer_score.remove_peer(id);
+        }
+    }
+
+    fn inject_dial_failure(&mut self, peer_id: &PeerId) {
+        self.px_addresses.remove(peer_id);
+    }
+
+    fn inject_node_event(
+        &mut self,
+        propagation_source: PeerId,
+        event: GossipsubRpc,
+    ) {
+        if !self.direct_peers.contains_key(&propagation_source)
+            && score_of(&self.peer_score, &propagation_source) < self.thresholds.graylist_threshold
+        {
+            debug!("Ignoring RPC from graylisted peer {:?}", propagation_source);
+            return;
+        }
+
+        if !event.subscriptions.is_empty() {
+            self.handle_received_subscriptions(event.subscriptions, &propagation_source);
+        }
+
+        for message in event.messages {
+            self.handle_received_message(message, &propagation_source);
+        }
+
+        let mut ihave = Vec::new();
+        let mut graft = Vec::new();
+        for action in event.control_msgs {
+            match action {
+                GossipsubControlAction::IHave { topic_hash, message_ids } =>
+                    ihave.push((topic_hash, message_ids)),
+                GossipsubControlAction::IWant { message_ids } =>
+                    self.handle_iwant(&propagation_source, message_ids),
+                GossipsubControlAction::Graft { topic_hash } =>
+                    graft.push(topic_hash),
+                GossipsubControlAction::Prune { topic_hash, peers, backoff } =>
+                    self.handle_prune(&propagation_source, &topic_hash, peers, backoff),
+            }
+        }
+
+        if !ihave.is_empty() {
+            self.handle_ihave(&propagation_source, ihave);
+        }
+        if !graft.is_empty() {
+            self.handle_graft(&propagation_source, graft);
+        }
+    }
+
+    fn poll(
+        &mut self,
+        _: &mut impl PollParameters,
+    ) -> Async<
+        NetworkBehaviourAction<
+            <Self::ProtocolsHandler as ProtocolsHandler>::InEvent,
+            Self::OutEvent,
+        >,
+    > {
+        if let Some(event) = self.events.pop_front() {
+            return Async::Ready(event);
+        }
+
+        loop {
+            match self.next_heartbeat.poll() {
+                Ok(Async::Ready(())) => {
+                    self.next_heartbeat.reset(Instant::now() + self.config.heartbeat_interval);
+                    self.heartbeat();
+                    if let Some(event) = self.events.pop_front() {
+                        return Async::Ready(event);
+                    }
+                }
+                Ok(Async::NotReady) => break,
+                Err(err) => {
+                    debug!("Gossipsub heartbeat timer errored: {:?}", err);
+                    break;
+                }
+            }
+        }
+
+        Async::NotReady
+    }
+}
+
+/// Builds an RPC containing a single subscription.
+fn subscription_rpc(topic: TopicHash, action: GossipsubSubscriptionAction) -> GossipsubRpc {
+    GossipsubRpc {
+        messages: Vec::new(),
+        subscriptions: vec![GossipsubSubscription { topic, action }],
+        control_msgs: Vec::new(),
+    }
+}
+
+/// Builds a `PRUNE` control message.
+fn prune_action(topic_hash: &TopicHash, peers: Vec<PeerInfo>, backoff: Duration) -> GossipsubControlAction {
+    GossipsubControlAction::Prune {
+        topic_hash: topic_hash.clone(),
+        peers,
+        backoff: Some(backoff.as_secs()),
+    }
+}
+
+/// Returns the score of a peer, or zero if scoring is disabled.
+fn score_of(peer_score: &Option<PeerScore>, peer_id: &PeerId) -> f64 {
+    peer_score.as_ref().map_or(0.0, |peer_score| peer_score.score(peer_id))
+}
+
+/// Returns true if the peer must not be grafted to the mesh of the topic.
+fn is_backed_off(backoffs: &HashMap<TopicHash, HashMap<PeerId, Instant>>, topic_hash: &TopicHash, peer_id: &PeerId) -> bool {
+    backoffs.get(topic_hash)
+        .and_then(|peers| peers.get(peer_id))
+        .map_or(false, |until| *until > Instant::now())
+}
+
+/// Prevents a peer from being grafted to the mesh of a topic for the given duration.
+fn add_backoff(backoffs: &mut HashMap<TopicHash, HashMap<PeerId, Instant>>, topic_hash: &TopicHash, peer_id: &PeerId, backoff: Duration) {
+    let until = Instant::now() + backoff;
+    let entry = backoffs.entry(topic_hash.clone())
+        .or_insert_with(HashMap::new)
+        .entry(peer_id.clone())
+        .or_insert(until);
+    if *entry < until {
+        *entry = until;
+    }
+}
+
+/// Returns the peers sent to a pruned peer: random peers of the topic with a non-negative score.
+fn px_peers(
+    rng: &mut impl Rng,
+    topic_peers: &HashMap<TopicHash, HashSet<PeerId>>,
+    peer_score: &Option<PeerScore>,
+    topic_hash: &TopicHash,
+    pruned: &PeerId,
+    n: usize,
+) -> Vec<PeerInfo> {
+    get_random_peers(rng, topic_peers, topic_hash, n, |p| p != pruned && score_of(peer_score, p) >= 0.0)
+        .into_iter()
+        .map(|peer_id| PeerInfo {
+            peer_id: Some(peer_id),
+            signed_peer_record: None,
+        })
+        .collect()
+}
+
+/// Returns the direct peers subscribed to a topic.
+fn direct_topic_peers<'a>(
+    topic_peers: &'a HashMap<TopicHash, HashSet<PeerId>>,
+    direct_peers: &'a HashMap<PeerId, Vec<Multiaddr>>,
+    topic_hash: &TopicHash,
+) -> impl Iterator<Item = PeerId> + 'a {
+    topic_peers.get(topic_hash)
+        .into_iter()
+        .flat_map(|peers| peers.iter())
+        .filter(move |p| direct_peers.contains_key(*p))
+        .cloned()
+}
+
+/// Removes a peer from the peers subscribed to a topic.
+fn remove_peer_from_topic(topic_peers: &mut HashMap<TopicHash, HashSet<PeerId>>, topic_hash: &TopicHash, peer_id: &PeerId) {
+    if let Some(peers) = topic_peers.get_mut(topic_hash) {
+        peers.remove(peer_id);
+        if peers.is_empty() {
+            topic_peers.remove(topic_hash);
+        }
+    }
+}
+
+/// Returns up to `n` random peers subscribed to a topic that pass the filter.
+fn get_random_peers(
+    rng: &mut impl Rng,
+    topic_peers: &HashMap<TopicHash, HashSet<PeerId>>,
+    topic_hash: &TopicHash,
+    n: usize,
+    filter: impl Fn(&PeerId) -> bool,
+) -> Vec<PeerId> {
+    let mut peers: Vec<PeerId> = topic_peers.get(topic_hash)
+        .map(|peers| peers.iter().filter(|p| filter(*p)).cloned().collect())
+        .unwrap_or_default();
+    peers.shuffle(rng);
+    peers.truncate(n);
+    peers
+}
+
+/// A received message waiting for the user to validate it.
+struct PendingValidation {
+    message: GossipsubMessage,
+    /// Peer we received the message from.
+    propagation_source: PeerId,
+    /// Heartbeat during which the message has been received.
+    received_tick: u64,
+}
+
+/// Result of the validation of a message by the user.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MessageAcceptance {
+    /// The message is valid, and is forwarded to the other peers.
+    Accept,
+    /// The message is invalid, and the peer that sent it is penalized.
+    Reject,
+    /// The message is neither forwarded nor penalized, for example because it's valid but
+    /// no longer relevant.
+    Ignore,
+}
+
+/// Event that can happen on the gossipsub behaviour.
+#[derive(Debug)]
+pub enum GossipsubEvent {
+    /// A message has been received.
+    ///
+    /// If message validation is enabled, the message isn't forwarded until it's reported with
+    /// `Gossipsub::report_message_validation_result`.
+    Message {
+        /// Peer we received the message from, which isn't necessarily its source.
+        propagation_source: PeerId,
+        /// Identifier of the message.
+        message_id: MessageId,
+        /// The message.
+        message: GossipsubMessage,
+    },
+
+    /// A remote subscribed to a topic.
+    Subscribed {
+        /// Remote that has subscribed.
+        peer_id: PeerId,
+        /// The topic it has subscribed to.
+        topic: TopicHash,
+    },
+
+    /// A remote unsubscribed from a topic.
+    Unsubscribed {
+        /// Remote that has unsubscribed.
+        peer_id: PeerId,
+        /// The topic it has subscribed from.
+        topic: TopicHash,
+    },
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::peer_score::{PeerScoreParams, TopicScoreParams};
+    use libp2p_core::transport::dummy::DummyStream;
+
+    type TestGossipsub = Gossipsub<DummyStream>;
+
+    fn endpoint() -> ConnectedPoint {
+        ConnectedPoint::Dialer { address: "/ip4/127.0.0.1/tcp/1234".parse().unwrap() }
+    }
+
+    fn control_rpc(control_msgs: Vec<GossipsubControlAction>) -> GossipsubRpc {
+        GossipsubRpc {
+            messages: Vec::new(),
+            subscriptions: Vec::new(),
+            control_msgs,
+        }
+    }
+
+    /// Builds a behaviour connected to `n` peers subscribed to `topic`.
+    fn build(topic: &Topic, n: usize) -> (TestGossipsub, Vec<PeerId>) {
+        build_with_config(topic, n, GossipsubConfig::default())
+    }
+
+    /// Same as `build`, with a custom configuration.
+    fn build_with_config(topic: &Topic, n: usize, config: GossipsubConfig) -> (TestGossipsub, Vec<PeerId>) {
+        let mut gs = Gossipsub::new(PeerId::random(), config);
+        let peers: Vec<PeerId> = (0..n).map(|_| PeerId::random()).collect();
+        for peer_id in &peers {
+            gs.inject_connected(peer_id.clone(), endpoint());
+            let rpc = subscription_rpc(topic.hash().clone(), GossipsubSubscriptionAction::Subscribe);
+            gs.inject_node_event(peer_id.clone(), rpc);
+        }
+        gs.events.clear();
+        (gs, peers)
+    }
+
+    /// Returns the control messages sent by the behaviour.
+    fn sent_control(gs: &TestGossipsub) -> Vec<(PeerId, GossipsubControlAction)> {
+        let mut sent = Vec::new();
+        for event in &gs.events {
+            if let NetworkBehaviourAction::SendEvent { peer_id, event } = event {
+                sent.extend(event.control_msgs.iter().map(|c| (peer_id.clone(), c.clone())));
+            }
+        }
+        sent
+    }
+
+    #[test]
+    fn subscribe_grafts_mesh_peers() {
+        let topic = Topic::new("test");
+        let (mut gs, _) = build(&topic, 20);
+
+        assert!(gs.subscribe(topic.clone()));
+        assert!(!gs.subscribe(topic.clone()));
+
+        let mesh: HashSet<PeerId> = gs.mesh_peers(topic.hash()).cloned().collect();
+        assert_eq!(mesh.len(), gs.config.mesh_n);
+        let grafted: HashSet<PeerId> = sent_control(&gs)
+            .into_iter()
+            .filter_map(|(peer_id, action)| match action {
+                GossipsubControlAction::Graft { .. } => Some(peer_id),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(grafted, mesh);
+    }
+
+    #[test]
+    fn graft_for_unsubscribed_topic_is_pruned() {
+        let topic = Topic::new("test");
+        let (mut gs, peers) = build(&topic, 1);
+
+        let graft = GossipsubControlAction::Graft { topic_hash: topic.hash().clone() };
+        gs.inject_node_event(peers[0].clone(), control_rpc(vec![graft]));
+
+        let prune = GossipsubControlAction::Prune {
+            topic_hash: topic.hash().clone(),
+            peers: Vec::new(),
+            backoff: Some(gs.config.prune_backoff.as_secs()),
+        };
+        assert_eq!(sent_control(&gs), vec![(peers[0].clone(), prune)]);
+        assert_eq!(gs.mesh_peers(topic.hash()).count(), 0);
+    }
+
+    #[test]
+    fn messages_are_delivered_and_forwarded_once() {
+        let topic = Topic::new("test");
+        let (mut gs, peers) = build(&topic, 3);
+        gs.subscribe(topic.clone());
+        gs.events.clear();
+
+        let rpc = GossipsubRpc {
+            messages: vec![GossipsubMessage {
+                source: PeerId::random(),
+                data: vec![1, 2, 3],
+                sequence_number: vec![1],
+                topics: vec![topic.hash().clone()],
+                signature: None,
+                key: None,
+            }],
+            subscriptions: Vec::new(),
+            control_msgs: Vec::new(),
+        };
+        gs.inject_node_event(peers[0].clone(), rpc.clone());
+        gs.inject_node_event(peers[1].clone(), rpc);
+
+        let mut delivered = 0;
+        let mut forwarded = HashSet::new();
+        for event in &gs.events {
+            match event {
+                NetworkBehaviourAction::GenerateEvent(GossipsubEvent::Message { .. }) => delivered += 1,
+                NetworkBehaviourAction::SendEvent { peer_id, event } if !event.messages.is_empty() => {
+                    forwarded.insert(peer_id.clone());
+                }
+                _ => {}
+            }
+        }
+        assert_eq!(delivered, 1);
+        assert_eq!(forwarded, peers[1..].iter().cloned().collect::<HashSet<_>>());
+    }
+
+    #[test]
+    fn custom_message_id_deduplicates_by_content() {
+        let topic = Topic::new("test");
+        let mut config = GossipsubConfig::default();
+        config.set_message_id_fn(|message| MessageId::new(message.data.clone()));
+        let (mut gs, peers) = build_with_config(&topic, 2, config);
+        gs.subscribe(topic.clone());
+        gs.events.clear();
+
+        // The same data, published by two different nodes.
+        for peer_id in &peers {
+            let rpc = GossipsubRpc {
+                messages: vec![GossipsubMessage {
+                    source: PeerId::random(),
+                    data: vec![1, 2, 3],
+                    sequence_number: vec![1],
+                    topics: vec![topic.hash().clone()],
+                    signature: None,
+                    key: None,
+                }],
+                subscriptions: Vec::new(),
+                control_msgs: Vec::new(),
+            };
+            gs.inject_node_event(peer_id.clone(), rpc);
+        }
+
+        let delivered = gs.events.iter()
+            .filter(|event| match event {
+                NetworkBehaviourAction::GenerateEvent(GossipsubEvent::Message { .. }) => true,
+                _ => false,
+            })
+            .count();
+        assert_eq!(delivered, 1);
+    }
+
+    #[test]
+    fn messages_are_forwarded_once_validated() {
+        let topic = Topic::new("test");
+        let mut config = GossipsubConfig::default();
+        config.set_validate_messages(true);
+        let (mut gs, peers) = build_with_config(&topic, 3, config);
+        gs.subscribe(topic.clone());
+        gs.events.clear();
+
+        let message = GossipsubMessage {
+            source: PeerId::random(),
+            data: vec![1, 2, 3],
+            sequence_number: vec![1],
+            topics: vec![topic.hash().clone()],
+            signature: None,
+            key: None,
+        };
+        gs.inject_node_event(peers[0].clone(), GossipsubRpc {
+            messages: vec![message],
+            subscriptions: Vec::new(),
+            control_msgs: Vec::new(),
+        });
+
+        let message_id = match gs.events.pop_front() {
+            Some(NetworkBehaviourAction::GenerateEvent(GossipsubEvent::Message { propagation_source, message_id, .. })) => {
+                assert_eq!(propagation_source, peers[0]);
+                message_id
+            }
+            _ => panic!("expected a message event"),
+        };
+        assert!(gs.events.is_empty());
+
+        assert!(gs.report_message_validation_result(&message_id, MessageAcceptance::Accept));
+        assert!(!gs.report_message_validation_result(&message_id, MessageAcceptance::Accept));
+
+        let forwarded: HashSet<PeerId> = gs.events.iter()
+            .filter_map(|event| match event {
+                NetworkBehaviourAction::SendEvent { peer_id, event } if !event.messages.is_empty() => Some(peer_id.clone()),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(forwarded, peers[1..].iter().cloned().collect::<HashSet<_>>());
+    }
+
+    #[test]
+    fn rejected_messages_penalize_the_propagation_source() {
+        let topic = Topic::new("test");
+        let mut params = PeerScoreParams::default();
+        let mut topic_params = TopicScoreParams::default();
+        // Keep the score from changing with the time spent in the mesh.
+        topic_params.time_in_mesh_weight = 0.0;
+        params.topics.insert(topic.hash().clone(), topic_params);
+        let mut config = GossipsubConfig::default();
+        config.set_validate_messages(true);
+        config.set_peer_score(params, PeerScoreThresholds::default());
+        let (mut gs, peers) = build_with_config(&topic, 2, config);
+        gs.subscribe(topic.clone());
+        gs.events.clear();
+
+        let message = GossipsubMessage {
+            source: PeerId::random(),
+            data: vec![1, 2, 3],
+            sequence_number: vec![1],
+            topics: vec![topic.hash().clone()],
+            signature: None,
+            key: None,
+        };
+        let message_id = (gs.config.message_id_fn)(&message);
+        gs.inject_node_event(peers[0].clone(), GossipsubRpc {
+            messages: vec![message],
+            subscriptions: Vec::new(),
+            control_msgs: Vec::new(),
+        });
+        gs.events.clear();
+
+        let score_before = gs.peer_score(&peers[0]).unwrap();
+        assert!(gs.report_message_validation_result(&message_id, MessageAcceptance::Reject));
+        assert!(gs.peer_score(&peers[0]).unwrap() < score_before);
+        assert!(gs.events.is_empty());
+    }
+
+    #[test]
+    fn ihave_requests_unseen_messages() {
+        let topic = Topic::new("test");
+        let (mut gs, peers) = build(&topic, 1);
+        gs.subscribe(topic.clone());
+        gs.events.clear();
+
+        let ihave = GossipsubControlAction::IHave {
+            topic_hash: topic.hash().clone(),
+            message_ids: vec![MessageId::new(vec![1])],
+        };
+        gs.inject_node_event(peers[0].clone(), control_rpc(vec![ihave]));
+
+        let iwant = GossipsubControlAction::IWant { message_ids: vec![MessageId::new(vec![1])] };
+        assert_eq!(sent_control(&gs), vec![(peers[0].clone(), iwant)]);
+    }
+
+    #[test]
+    fn heartbeat_prunes_excess_mesh_peers() {
+        let topic = Topic::new("test");
+        let (mut gs, peers) = build(&topic, 20);
+        gs.subscribe(topic.clone());
+        for peer_id in &peers {
+            let graft = GossipsubControlAction::Graft { topic_hash: topic.hash().clone() };
+            gs.inject_node_event(peer_id.clone(), control_rpc(vec![graft]));
+        }
+        assert_eq!(gs.mesh_peers(topic.hash()).count(), 20);
+        gs.events.clear();
+
+        gs.heartbeat();
+
+        assert_eq!(gs.mesh_peers(topic.hash()).count(), gs.config.mesh_n);
+        let pruned = sent_control(&gs)
+            .into_iter()
+            .filter(|(_, action)| match action {
+                GossipsubControlAction::Prune { .. } => true,
+                _ => false,
+            })
+            .count();
+        assert_eq!(pruned, 20 - gs.config.mesh_n);
+    }
+
+    #[test]
+    fn graft_during_backoff_is_refused_and_penalized() {
+        let topic = Topic::new("test");
+        let mut config = GossipsubConfig::default();
+        config.set_peer_score(PeerScoreParams::default(), PeerScoreThresholds::default());
+        let (mut gs, peers) = build_with_config(&topic, 1, config);
+        gs.subscribe(topic.clone());
+        assert_eq!(gs.mesh_peers(topic.hash()).count(), 1);
+
+        let prune = GossipsubControlAction::Prune {
+            topic_hash: topic.hash().clone(),
+            peers: Vec::new(),
+            backoff: None,
+        };
+        gs.inject_node_event(peers[0].clone(), control_rpc(vec![prune]));
+        assert_eq!(gs.mesh_peers(topic.hash()).count(), 0);
+        gs.events.clear();
+
+        let graft = GossipsubControlAction::Graft { topic_hash: topic.hash().clone() };
+        gs.inject_node_event(peers[0].clone(), control_rpc(vec![graft]));
+
+        assert_eq!(gs.mesh_peers(topic.hash()).count(), 0);
+        match sent_control(&gs).as_slice() {
+            [(peer_id, GossipsubControlAction::Prune { .. })] => assert_eq!(peer_id, &peers[0]),
+            sent => panic!("unexpected control messages: {:?}", sent),
+        }
+        assert!(gs.peer_score(&peers[0]).unwrap() < 0.0);
+    }
+
+    #[test]
+    fn prune_with_peer_exchange_dials_peers() {
+        let topic = Topic::new("test");
+        let (mut gs, peers) = build(&topic, 1);
+        gs.subscribe(topic.clone());
+        gs.events.clear();
+
+        let key = libp2p_core::identity::Keypair::generate_ed25519();
+        let addr: Multiaddr = "/ip4/10.0.0.1/tcp/4001".parse().unwrap();
+        let record = PeerRecord::with_seq(&key, 1, vec![addr.clone()]).unwrap();
+        let px_peer = key.public().into_peer_id();
+        let prune = GossipsubControlAction::Prune {
+            topic_hash: topic.hash().clone(),
+            peers: vec![PeerInfo {
+                peer_id: Some(px_peer.clone()),
+                signed_peer_record: Some(record.into_signed_envelope()),
+            }],
+            backoff: Some(10),
+        };
+        gs.inject_node_event(peers[0].clone(), control_rpc(vec![prune]));
+
+        let dialed: Vec<PeerId> = gs.events.iter()
+            .filter_map(|event| match event {
+                NetworkBehaviourAction::DialPeer { peer_id } => Some(peer_id.clone()),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(dialed, vec![px_peer.clone()]);
+        assert_eq!(gs.addresses_of_peer(&px_peer), vec![addr]);
+        assert!(is_backed_off(&gs.backoffs, topic.hash(), &peers[0]));
+    }
+
+    #[test]
+    fn flood_publish_reaches_all_topic_peers() {
+        let topic = Topic::new("test");
+        let count_recipients = |gs: &TestGossipsub| {
+            gs.events.iter()
+                .filter(|event| match event {
+                    NetworkBehaviourAction::SendEvent { event, .. } => !event.messages.is_empty(),
+                    _ => false,
+                })
+                .count()
+        };
+
+        let (mut gs, _) = build(&topic, 20);
+        gs.subscribe(topic.clone());
+        gs.events.clear();
+        gs.publish(topic.hash().clone(), vec![1]);
+        assert_eq!(count_recipients(&gs), 20);
+
+        let mut config = GossipsubConfig::default();
+        config.set_flood_publish(false);
+        let (mut gs, _) = build_with_config(&topic, 20, config);
+        gs.subscribe(topic.clone());
+        gs.events.clear();
+        gs.publish(topic.hash().clone(), vec![1]);
+        assert_eq!(count_recipients(&gs), gs.config.mesh_n);
+    }
+
+    #[test]
+    fn direct_peers_receive_messages_outside_of_the_mesh() {
+        let topic = Topic::new("test");
+        let direct = PeerId::random();
+        let addr: Multiaddr = "/ip4/10.0.0.1/tcp/4001".parse().unwrap();
+        let mut config = GossipsubConfig::default();
+        config.set_flood_publish(false);
+        config.add_direct_peer(direct.clone(), vec![addr.clone()]);
+        let (mut gs, _) = build_with_config(&topic, 20, config);
+        gs.inject_connected(direct.clone(), endpoint());
+        gs.inject_node_event(direct.clone(), subscription_rpc(topic.hash().clone(), GossipsubSubscriptionAction::Subscribe));
+        gs.subscribe(topic.clone());
+        assert!(gs.mesh_peers(topic.hash()).all(|p| *p != direct));
+        gs.events.clear();
+
+        gs.publish(topic.hash().clone(), vec![1]);
+        let recipients: HashSet<PeerId> = gs.events.iter()
+            .filter_map(|event| match event {
+                NetworkBehaviourAction::SendEvent { peer_id, event } if !event.messages.is_empty() => Some(peer_id.clone()),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(recipients.len(), gs.config.mesh_n + 1);
+        assert!(recipients.contains(&direct));
+        gs.events.clear();
+
+        let graft = GossipsubControlAction::Graft { topic_hash: topic.hash().clone() };
+        gs.inject_node_event(direct.clone(), control_rpc(vec![graft]));
+        assert!(gs.mesh_peers(topic.hash()).all(|p| *p != direct));
+        match sent_control(&gs).as_slice() {
+            [(peer_id, GossipsubControlAction::Prune { .. })] => assert_eq!(peer_id, &direct),
+            sent => panic!("unexpected control messages: {:?}", sent),
+        }
+        gs.events.clear();
+
+        gs.inject_disconnected(&direct, endpoint());
+        gs.heartbeat();
+        assert!(gs.events.iter().any(|event| match event {
+            NetworkBehaviourAction::DialPeer { peer_id } => *peer_id == direct,
+            _ => false,
+        }));
+        assert_eq!(gs.addresses_of_peer(&direct), vec![addr]);
+    }
+
+    #[test]
+    fn subscription_filter_ignores_refused_topics() {
+        let mut config = GossipsubConfig::default();
+        config.set_subscription_filter(|topic| topic.as_str().starts_with("allowed"));
+        config.set_max_subscriptions_per_peer(2);
+        let mut gs: TestGossipsub = Gossipsub::new(PeerId::random(), config);
+        let peer_id = PeerId::random();
+        gs.inject_connected(peer_id.clone(), endpoint());
+
+        let subscriptions = ["allowed-1", "garbage", "allowed-2", "allowed-3"].iter()
+            .map(|name| GossipsubSubscription {
+                topic: TopicHash::from_raw(*name),
+                action: GossipsubSubscriptionAction::Subscribe,
+            })
+            .collect();
+        gs.inject_node_event(peer_id.clone(), GossipsubRpc {
+            messages: Vec::new(),
+            subscriptions,
+            control_msgs: Vec::new(),
+        });
+
+        let expected: HashSet<TopicHash> = vec![TopicHash::from_raw("allowed-1"), TopicHash::from_raw("allowed-2")]
+            .into_iter()
+            .collect();
+        assert_eq!(gs.peer_topics[&peer_id], expected);
+        assert!(!gs.topic_peers.contains_key(&TopicHash::from_raw("garbage")));
+        assert_eq!(gs.events.len(), 2);
+    }
+
+    /// Returns the messages sent by the behaviour.
+    fn sent_messages(gs: &TestGossipsub) -> Vec<GossipsubMessage> {
+        gs.events.iter()
+            .filter_map(|event| match event {
+                NetworkBehaviourAction::SendEvent { event, .. } => Some(event.messages.clone()),
+                _ => None,
+            })
+            .flatten()
+            .collect()
+    }
+
+    fn delivered(gs: &TestGossipsub) -> usize {
+        gs.events.iter()
+            .filter(|event| match event {
+                NetworkBehaviourAction::GenerateEvent(GossipsubEvent::Message { .. }) => true,
+                _ => false,
+            })
+            .count()
+    }
+
+    #[test]
+    fn strict_signing_policy() {
+        let topic = Topic::new("test");
+        let keypair = libp2p_core::identity::Keypair::generate_ed25519();
+        let mut config = GossipsubConfig::default();
+        config.set_signing_policy(SigningPolicy::StrictSign(keypair.clone()));
+        let (mut gs, peers) = build_with_config(&topic, 2, config);
+        gs.subscribe(topic.clone());
+        gs.events.clear();
+
+        gs.publish(topic.hash().clone(), vec![1, 2, 3]);
+        let published = sent_messages(&gs);
+        assert!(!published.is_empty());
+        assert_eq!(published[0].source, keypair.public().into_peer_id());
+        assert!(published[0].verify_signature());
+        gs.events.clear();
+
+        let mut message = GossipsubMessage {
+            source: keypair.public().into_peer_id(),
+            data: vec![4, 5, 6],
+            sequence_number: vec![1],
+            topics: vec![topic.hash().clone()],
+            signature: None,
+            key: None,
+        };
+        let rpc = |message: &GossipsubMessage| GossipsubRpc {
+            messages: vec![message.clone()],
+            subscriptions: Vec::new(),
+            control_msgs: Vec::new(),
+        };
+        gs.inject_node_event(peers[0].clone(), rpc(&message));
+        assert_eq!(delivered(&gs), 0);
+
+        // The unsigned copy hasn't prevented the signed one from being processed.
+        message.sign(&keypair).unwrap();
+        gs.inject_node_event(peers[0].clone(), rpc(&message));
+        assert_eq!(delivered(&gs), 1);
+    }
+
+    #[test]
+    fn anonymous_signing_policy() {
+        let topic = Topic::new("test");
+        let mut config = GossipsubConfig::default();
+        config.set_signing_policy(SigningPolicy::Anonymous);
+        let (mut gs, peers) = build_with_config(&topic, 2, config);
+        gs.subscribe(topic.clone());
+        gs.events.clear();
+
+        gs.publish(topic.hash().clone(), vec![1, 2, 3]);
+        let published = sent_messages(&gs);
+        assert!(!published.is_empty());
+        assert_ne!(published[0].source, gs.local_peer_id);
+        assert!(published[0].signature.is_none() && published[0].key.is_none());
+        gs.events.clear();
+
+        let keypair = libp2p_core::identity::Keypair::generate_ed25519();
+        let mut message = GossipsubMessage {
+            source: keypair.public().into_peer_id(),
+            data: vec![4, 5, 6],
+            sequence_number: vec![1],
+            topics: vec![topic.hash().clone()],
+            signature: None,
+            key: None,
+        };
+        message.sign(&keypair).unwrap();
+        gs.inject_node_event(peers[0].clone(), GossipsubRpc {
+            messages: vec![message],
+            subscriptions: Vec::new(),
+            control_msgs: Vec::new(),
+        });
+        assert_eq!(delivered(&gs), 0);
+    }
+}
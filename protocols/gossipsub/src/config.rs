@@ -0,0 +1,595 @@
+// Copyright 2019 Parity Technologies (UK) Ltd.
+//
+// Permission is hereby granted, free of charge, to any person obtaining a
+// copy of this software and associated documentation files (the "Software"),
+// to deal in the Software without restriction, including without limitation
+// the rights to use, copy, modify, merge, publish, distribute, sublicense,
+// and/or sell copies of the Software, and to permit persons to whom the
+// Software is furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS
+// OR IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING
+// FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
+// DEALINGS IN THE SOFTWARE.
+
+use crate::peer_score::{PeerScoreParams, PeerScoreThresholds};
+use crate::protocol::{GossipsubMessage, MessageId};
+use crate::topic::TopicHash;
+use libp2p_core::{Multiaddr, PeerId, identity::Keypair};
+use std::{borrow::Cow, fmt, sync::Arc, time::Duration};
+
+/// Configuration of a `Gossipsub` behaviour.
+///
+/// The default values are the ones of the gossipsub specification and of the go-libp2p
+/// implementation.
+#[derive(Clone)]
+pub struct GossipsubConfig {
+    /// Names of the protocol negotiated on the substreams, by order of preference.
+    pub(crate) protocol_ids: Vec<Cow<'static, [u8]>>,
+    /// Number of heartbeats during which messages are kept in the message cache.
+    pub(crate) history_length: usize,
+    /// Number of past heartbeats whose messages are advertised in gossip.
+    pub(crate) history_gossip: usize,
+    /// Target number of peers in the mesh of a topic.
+    pub(crate) mesh_n: usize,
+    /// Minimum number of peers in the mesh of a topic, below which peers are grafted.
+    pub(crate) mesh_n_low: usize,
+    /// Maximum number of peers in the mesh of a topic, above which peers are pruned.
+    pub(crate) mesh_n_high: usize,
+    /// Number of peers outside of the mesh that receive gossip at each heartbeat.
+    pub(crate) gossip_lazy: usize,
+    /// Delay before the first heartbeat.
+    pub(crate) heartbeat_initial_delay: Duration,
+    /// Interval between two heartbeats.
+    pub(crate) heartbeat_interval: Duration,
+    /// Time after which the fanout peers of a topic we no longer publish to are forgotten.
+    pub(crate) fanout_ttl: Duration,
+    /// Maximum size of an RPC.
+    pub(crate) max_transmit_size: usize,
+    /// Time during which the identifiers of received messages are remembered.
+    pub(crate) duplicate_cache_time: Duration,
+    /// Maximum memory used by the identifiers of received messages, in bytes.
+    pub(crate) duplicate_cache_max_bytes: usize,
+    /// Function computing the identifier of a message.
+    pub(crate) message_id_fn: fn(&GossipsubMessage) -> MessageId,
+    /// Whether we send other peers of the topic to the peers we prune.
+    pub(crate) do_px: bool,
+    /// Number of peers sent to the peers we prune, and maximum number of peers we connect to
+    /// when pruned.
+    pub(crate) prune_peers: usize,
+    /// Time during which a pruned peer must not be grafted again.
+    pub(crate) prune_backoff: Duration,
+    /// Whether we send the messages we publish to all the peers of the topic rather than to
+    /// the mesh only.
+    pub(crate) flood_publish: bool,
+    /// Number of heartbeats between two attempts of opportunistic grafting.
+    pub(crate) opportunistic_graft_ticks: u64,
+    /// Number of peers grafted by opportunistic grafting.
+    pub(crate) opportunistic_graft_peers: usize,
+    /// Whether received messages are only forwarded once the user has validated them.
+    pub(crate) validate_messages: bool,
+    /// Parameters of the scoring of the peers, if enabled.
+    pub(crate) peer_score: Option<(PeerScoreParams, PeerScoreThresholds)>,
+    /// Peers we always exchange messages with, and their addresses.
+    pub(crate) direct_peers: Vec<(PeerId, Vec<Multiaddr>)>,
+    /// Number of heartbeats between two attempts to reconnect to the direct peers.
+    pub(crate) direct_connect_ticks: u64,
+    /// Filter of the subscriptions of the remotes that we track.
+    pub(crate) subscription_filter: SubscriptionFilter,
+    /// How the messages we publish are signed, and the signatures of received messages checked.
+    pub(crate) signing_policy: SigningPolicy,
+}
+
+impl Default for GossipsubConfig {
+    fn default() -> Self {
+        GossipsubConfig {
+            protocol_ids: vec![Cow::Borrowed(b"/meshsub/1.1.0"), Cow::Borrowed(b"/meshsub/1.0.0")],
+            history_length: 5,
+            history_gossip: 3,
+            mesh_n: 6,
+            mesh_n_low: 4,
+            mesh_n_high: 12,
+            gossip_lazy: 6,
+            heartbeat_initial_delay: Duration::from_millis(100),
+            heartbeat_interval: Duration::from_secs(1),
+            fanout_ttl: Duration::from_secs(60),
+            max_transmit_size: 1024 * 1024,
+            duplicate_cache_time: Duration::from_secs(120),
+            duplicate_cache_max_bytes: 4 * 1024 * 1024,
+            message_id_fn: default_message_id,
+            do_px: false,
+            prune_peers: 16,
+            prune_backoff: Duration::from_secs(60),
+            flood_publish: true,
+            opportunistic_graft_ticks: 60,
+            opportunistic_graft_peers: 2,
+            validate_messages: false,
+            peer_score: None,
+            direct_peers: Vec::new(),
+            direct_connect_ticks: 300,
+            subscription_filter: SubscriptionFilter::default(),
+            signing_policy: SigningPolicy::Unsigned,
+        }
+    }
+}
+
+impl GossipsubConfig {
+    /// Sets a custom protocol name.
+    ///
+    /// Gossipsub nodes only communicate with other nodes using the same protocol name. By
+    /// default, both `/meshsub/1.1.0` and `/meshsub/1.0.0` are supported.
+    pub fn set_protocol_id(&mut self, id: impl Into<Cow<'static, [u8]>>) -> &mut Self {
+        self.protocol_ids = vec![id.into()];
+        self
+    }
+
+    /// Sets the number of heartbeats during which messages are kept in the message cache, and
+    /// the number of heartbeats whose messages are advertised in gossip.
+    ///
+    /// The defaults are respectively 5 and 3.
+    ///
+    /// # Panic
+    ///
+    /// Panics if `length` is zero or if `gossip` is larger than `length`.
+    pub fn set_history(&mut self, length: usize, gossip: usize) -> &mut Self {
+        assert!(length > 0, "the message cache must keep at least one heartbeat");
+        assert!(gossip <= length, "can't gossip about messages that are no longer cached");
+        self.history_length = length;
+        self.history_gossip = gossip;
+        self
+    }
+
+    /// Sets the minimum, target and maximum number of peers in the mesh of a topic.
+    ///
+    /// The defaults are respectively 4, 6 and 12.
+    ///
+    /// # Panic
+    ///
+    /// Panics if the numbers aren't in ascending order.
+    pub fn set_mesh_n(&mut self, low: usize, target: usize, high: usize) -> &mut Self {
+        assert!(low <= target && target <= high, "mesh limits must be in ascending order");
+        self.mesh_n_low = low;
+        self.mesh_n = target;
+        self.mesh_n_high = high;
+        self
+    }
+
+    /// Sets the number of peers outside of the mesh that receive gossip at each heartbeat.
+    ///
+    /// The default is 6.
+    pub fn set_gossip_lazy(&mut self, gossip_lazy: usize) -> &mut Self {
+        self.gossip_lazy = gossip_lazy;
+        self
+    }
+
+    /// Sets the delay before the first heartbeat and the interval between two heartbeats.
+    ///
+    /// The defaults are respectively 100 milliseconds and 1 second.
+    pub fn set_heartbeat(&mut self, initial_delay: Duration, interval: Duration) -> &mut Self {
+        self.heartbeat_initial_delay = initial_delay;
+        self.heartbeat_interval = interval;
+        self
+    }
+
+    /// Sets the time after which the fanout peers of a topic we no longer publish to are
+    /// forgotten.
+    ///
+    /// The default is 60 seconds.
+    pub fn set_fanout_ttl(&mut self, ttl: Duration) -> &mut Self {
+        self.fanout_ttl = ttl;
+        self
+    }
+
+    /// Sets the maximum size of an RPC, sent or received.
+    ///
+    /// The default is 1 MiB.
+    pub fn set_max_transmit_size(&mut self, size: usize) -> &mut Self {
+        self.max_transmit_size = size;
+        self
+    }
+
+    /// Sets for how long and within how much memory the identifiers of received messages are
+    /// remembered in order to drop duplicates.
+    ///
+    /// Identifiers are forgotten after `window`, or earlier, oldest first, when they would use
+    /// more than `max_bytes`. A message seen again after its identifier has been forgotten is
+    /// handled as a new one. The default is 2 minutes and 4 MiB.
+    pub fn set_duplicate_cache(&mut self, window: Duration, max_bytes: usize) -> &mut Self {
+        self.duplicate_cache_time = window;
+        self.duplicate_cache_max_bytes = max_bytes;
+        self
+    }
+
+    /// Sets the function computing the identifier of a message.
+    ///
+    /// Identifiers are used to detect duplicate messages and in the gossip, and must therefore
+    /// be computed the same way by all the nodes of the network. The default concatenates the
+    /// source of the message and its sequence number, as go-libp2p does. Identifying messages
+    /// by a hash of their content instead avoids delivering and forwarding twice the same data
+    /// published by different nodes.
+    pub fn set_message_id_fn(&mut self, f: fn(&GossipsubMessage) -> MessageId) -> &mut Self {
+        self.message_id_fn = f;
+        self
+    }
+
+    /// Sets whether we send other peers of the topic to the peers we prune, and how many.
+    ///
+    /// The number of peers is also the maximum number of peers we connect to when we're pruned
+    /// by a peer whose score is above `accept_px_threshold`. Peer exchange is disabled by
+    /// default, with 16 peers.
+    pub fn set_peer_exchange(&mut self, enabled: bool, prune_peers: usize) -> &mut Self {
+        self.do_px = enabled;
+        self.prune_peers = prune_peers;
+        self
+    }
+
+    /// Sets the time during which a pruned peer must not be grafted again.
+    ///
+    /// The default is 60 seconds.
+    pub fn set_prune_backoff(&mut self, backoff: Duration) -> &mut Self {
+        self.prune_backoff = backoff;
+        self
+    }
+
+    /// Sets whether the messages we publish are sent to all the peers of the topic whose score
+    /// is above `publish_threshold`, rather than to the mesh only.
+    ///
+    /// Flood publishing is enabled by default.
+    pub fn set_flood_publish(&mut self, enabled: bool) -> &mut Self {
+        self.flood_publish = enabled;
+        self
+    }
+
+    /// Sets the number of heartbeats between two attempts of opportunistic grafting, and the
+    /// number of peers grafted at each attempt.
+    ///
+    /// When the median score of the mesh of a topic is below `opportunistic_graft_threshold`,
+    /// peers with a score above the median are grafted. The defaults are respectively 60 and 2.
+    pub fn set_opportunistic_graft(&mut self, ticks: u64, peers: usize) -> &mut Self {
+        self.opportunistic_graft_ticks = ticks;
+        self.opportunistic_graft_peers = peers;
+        self
+    }
+
+    /// Sets whether received messages are only forwarded once the user has validated them with
+    /// `Gossipsub::report_message_validation_result`.
+    ///
+    /// Validation is disabled by default, in which case messages are forwarded as soon as
+    /// they're received.
+    pub fn set_validate_messages(&mut self, enabled: bool) -> &mut Self {
+        self.validate_messages = enabled;
+        self
+    }
+
+    /// Enables the scoring of the peers with the given parameters and thresholds.
+    ///
+    /// Scoring is disabled by default, in which case the score of every peer is zero.
+    pub fn set_peer_score(&mut self, params: PeerScoreParams, thresholds: PeerScoreThresholds) -> &mut Self {
+        self.peer_score = Some((params, thresholds));
+        self
+    }
+
+    /// Adds a direct peer, with the addresses to reach it.
+    ///
+    /// Direct peers receive all the messages of the topics they're subscribed to, regardless of
+    /// the limits of the mesh and of their score, and are never grafted to nor pruned from the
+    /// mesh. The peering agreement must be configured on both sides: `GRAFT`s received from a
+    /// direct peer are refused and penalized. We connect to the direct peers at the first
+    /// heartbeat, and reconnect to them if needed every `direct_connect_ticks` heartbeats.
+    pub fn add_direct_peer(&mut self, peer_id: PeerId, addresses: Vec<Multiaddr>) -> &mut Self {
+        self.direct_peers.retain(|(p, _)| *p != peer_id);
+        self.direct_peers.push((peer_id, addresses));
+        self
+    }
+
+    /// Sets the number of heartbeats between two attempts to reconnect to the direct peers
+    /// we're disconnected from.
+    ///
+    /// The default is 300. Zero disables the reconnections.
+    pub fn set_direct_connect_ticks(&mut self, ticks: u64) -> &mut Self {
+        self.direct_connect_ticks = ticks;
+        self
+    }
+
+    /// Sets the filter of the topics whose subscriptions we track.
+    ///
+    /// Subscriptions of the remotes to topics for which the filter returns false are ignored:
+    /// the remotes aren't considered subscribed to them, and no event is generated. This
+    /// prevents remotes from exhausting our memory by subscribing to a large number of
+    /// arbitrary topics. All the topics are accepted by default.
+    pub fn set_subscription_filter<F>(&mut self, filter: F) -> &mut Self
+    where
+        F: Fn(&TopicHash) -> bool + Send + Sync + 'static,
+    {
+        self.subscription_filter.allowed = Some(Arc::new(filter));
+        self
+    }
+
+    /// Sets the maximum number of topics a remote can be subscribed to. Subscriptions beyond
+    /// that number are ignored.
+    ///
+    /// There is no limit by default.
+    pub fn set_max_subscriptions_per_peer(&mut self, max: usize) -> &mut Self {
+        self.subscription_filter.max_per_peer = Some(max);
+        self
+    }
+
+    /// Sets how the messages we publish are signed, and how the signatures of the received
+    /// messages are checked.
+    ///
+    /// The default is `SigningPolicy::Unsigned`.
+    pub fn set_signing_policy(&mut self, policy: SigningPolicy) -> &mut Self {
+        self.signing_policy = policy;
+        self
+    }
+}
+
+/// Builder for a `GossipsubConfig` tuned for a workload.
+///
+/// Small topics with frequent messages benefit from a small mesh and a short heartbeat, while
+/// large topics with rare messages need a larger mesh and a longer history for the gossip to
+/// reach every peer. Parameters that aren't set keep the values of `GossipsubConfig::default`,
+/// and the consistency of the parameters is checked by `build`.
+///
+/// ```
+/// use libp2p_gossipsub::GossipsubConfigBuilder;
+/// use std::time::Duration;
+///
+/// let config = GossipsubConfigBuilder::new()
+///     .mesh_n(3)
+///     .mesh_n_low(2)
+///     .mesh_n_high(4)
+///     .heartbeat_interval(Duration::from_millis(700))
+///     .build()
+///     .unwrap();
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct GossipsubConfigBuilder {
+    config: GossipsubConfig,
+}
+
+impl GossipsubConfigBuilder {
+    /// Creates a builder with the default parameters.
+    pub fn new() -> Self {
+        GossipsubConfigBuilder::default()
+    }
+
+    /// Sets the target number of peers in the mesh of a topic, known as `D`. The default is 6.
+    pub fn mesh_n(mut self, mesh_n: usize) -> Self {
+        self.config.mesh_n = mesh_n;
+        self
+    }
+
+    /// Sets the minimum number of peers in the mesh of a topic, below which peers are grafted,
+    /// known as `D_low`. The default is 4.
+    pub fn mesh_n_low(mut self, mesh_n_low: usize) -> Self {
+        self.config.mesh_n_low = mesh_n_low;
+        self
+    }
+
+    /// Sets the maximum number of peers in the mesh of a topic, above which peers are pruned,
+    /// known as `D_high`. The default is 12.
+    pub fn mesh_n_high(mut self, mesh_n_high: usize) -> Self {
+        self.config.mesh_n_high = mesh_n_high;
+        self
+    }
+
+    /// Sets the number of peers outside of the mesh that receive gossip at each heartbeat,
+    /// known as `D_lazy`. The default is 6.
+    pub fn gossip_lazy(mut self, gossip_lazy: usize) -> Self {
+        self.config.gossip_lazy = gossip_lazy;
+        self
+    }
+
+    /// Sets the time after which the fanout peers of a topic we no longer publish to are
+    /// forgotten. The default is 60 seconds.
+    pub fn fanout_ttl(mut self, fanout_ttl: Duration) -> Self {
+        self.config.fanout_ttl = fanout_ttl;
+        self
+    }
+
+    /// Sets the delay before the first heartbeat. The default is 100 milliseconds.
+    pub fn heartbeat_initial_delay(mut self, delay: Duration) -> Self {
+        self.config.heartbeat_initial_delay = delay;
+        self
+    }
+
+    /// Sets the interval between two heartbeats. The default is 1 second.
+    pub fn heartbeat_interval(mut self, interval: Duration) -> Self {
+        self.config.heartbeat_interval = interval;
+        self
+    }
+
+    /// Sets the number of heartbeats during which messages are kept in the message cache.
+    /// The default is 5.
+    pub fn history_length(mut self, history_length: usize) -> Self {
+        self.config.history_length = history_length;
+        self
+    }
+
+    /// Sets the number of past heartbeats whose messages are advertised in gossip, known as the
+    /// gossip window. The default is 3.
+    pub fn history_gossip(mut self, history_gossip: usize) -> Self {
+        self.config.history_gossip = history_gossip;
+        self
+    }
+
+    /// Checks the parameters and builds the configuration.
+    ///
+    /// Returns an error if the mesh limits aren't in ascending order, if the message cache
+    /// doesn't keep any heartbeat, if the gossip window is larger than the message cache, or
+    /// if the heartbeat interval is zero.
+    pub fn build(self) -> Result<GossipsubConfig, &'static str> {
+        let config = self.config;
+        if config.mesh_n_low > config.mesh_n || config.mesh_n > config.mesh_n_high {
+            return Err("mesh limits must be in ascending order");
+        }
+        if config.history_length == 0 {
+            return Err("the message cache must keep at least one heartbeat");
+        }
+        if config.history_gossip > config.history_length {
+            return Err("can't gossip about messages that are no longer cached");
+        }
+        if config.heartbeat_interval == Duration::from_secs(0) {
+            return Err("the heartbeat interval must not be zero");
+        }
+        Ok(config)
+    }
+}
+
+impl fmt::Debug for GossipsubConfig {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("GossipsubConfig")
+            .field("protocol_ids", &self.protocol_ids.iter().map(|id| String::from_utf8_lossy(id)).collect::<Vec<_>>())
+            .field("history_length", &self.history_length)
+            .field("history_gossip", &self.history_gossip)
+            .field("mesh_n", &self.mesh_n)
+            .field("mesh_n_low", &self.mesh_n_low)
+            .field("mesh_n_high", &self.mesh_n_high)
+            .field("gossip_lazy", &self.gossip_lazy)
+            .field("heartbeat_initial_delay", &self.heartbeat_initial_delay)
+            .field("heartbeat_interval", &self.heartbeat_interval)
+            .field("fanout_ttl", &self.fanout_ttl)
+            .field("max_transmit_size", &self.max_transmit_size)
+            .field("duplicate_cache_time", &self.duplicate_cache_time)
+            .field("duplicate_cache_max_bytes", &self.duplicate_cache_max_bytes)
+            .field("do_px", &self.do_px)
+            .field("prune_peers", &self.prune_peers)
+            .field("prune_backoff", &self.prune_backoff)
+            .field("flood_publish", &self.flood_publish)
+            .field("opportunistic_graft_ticks", &self.opportunistic_graft_ticks)
+            .field("opportunistic_graft_peers", &self.opportunistic_graft_peers)
+            .field("validate_messages", &self.validate_messages)
+            .field("peer_score", &self.peer_score)
+            .field("direct_peers", &self.direct_peers)
+            .field("direct_connect_ticks", &self.direct_connect_ticks)
+            .field("subscription_filter", &self.subscription_filter)
+            .field("signing_policy", &self.signing_policy)
+            .finish()
+    }
+}
+
+/// Filter of the subscriptions of the remotes that we track.
+#[derive(Clone, Default)]
+pub(crate) struct SubscriptionFilter {
+    /// Returns true for the topics whose subscriptions are tracked. All topics if `None`.
+    pub(crate) allowed: Option<Arc<dyn Fn(&TopicHash) -> bool + Send + Sync>>,
+    /// Maximum number of topics a remote can be subscribed to.
+    pub(crate) max_per_peer: Option<usize>,
+}
+
+impl SubscriptionFilter {
+    /// Returns true if a remote subscribed to `subscribed` topics can subscribe to the topic.
+    pub(crate) fn accepts(&self, topic_hash: &TopicHash, subscribed: usize) -> bool {
+        if self.max_per_peer.map_or(false, |max| subscribed >= max) {
+            return false;
+        }
+        self.allowed.as_ref().map_or(true, |allowed| allowed(topic_hash))
+    }
+}
+
+impl fmt::Debug for SubscriptionFilter {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("SubscriptionFilter")
+            .field("allowed", &self.allowed.as_ref().map(|_| "<function>"))
+            .field("max_per_peer", &self.max_per_peer)
+            .finish()
+    }
+}
+
+/// Authenticity of the messages, as defined by the signature policies of the pubsub
+/// specification.
+///
+/// All the peers of a network are expected to use compatible policies: peers with a strict
+/// policy drop the messages published by anonymous peers, and vice versa.
+#[derive(Clone)]
+pub enum SigningPolicy {
+    /// The messages we publish are signed with the keypair, whose peer ID is their source.
+    /// Received messages must carry a valid signature of their source.
+    StrictSign(Keypair),
+    /// The messages we publish are signed with the keypair, whose peer ID is their source.
+    /// Received messages may be unsigned, but are dropped if they carry an invalid signature.
+    LaxSign(Keypair),
+    /// The messages we publish have the local peer ID as source, but aren't signed. Received
+    /// messages may be unsigned, but are dropped if they carry an invalid signature.
+    Unsigned,
+    /// The messages we publish aren't signed, and have a random source and sequence number, so
+    /// that they can't be traced back to us. Received messages carrying a signature or a key
+    /// are dropped.
+    Anonymous,
+}
+
+impl fmt::Debug for SigningPolicy {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SigningPolicy::StrictSign(keypair) => {
+                f.debug_tuple("StrictSign").field(&keypair.public().into_peer_id()).finish()
+            }
+            SigningPolicy::LaxSign(keypair) => {
+                f.debug_tuple("LaxSign").field(&keypair.public().into_peer_id()).finish()
+            }
+            SigningPolicy::Unsigned => f.write_str("Unsigned"),
+            SigningPolicy::Anonymous => f.write_str("Anonymous"),
+        }
+    }
+}
+
+impl SigningPolicy {
+    /// Returns true if a received message is acceptable under this policy.
+    pub(crate) fn accepts(&self, message: &GossipsubMessage) -> bool {
+        match self {
+            SigningPolicy::StrictSign(_) => message.verify_signature(),
+            SigningPolicy::LaxSign(_) | SigningPolicy::Unsigned => {
+                (message.signature.is_none() && message.key.is_none()) || message.verify_signature()
+            }
+            SigningPolicy::Anonymous => message.signature.is_none() && message.key.is_none(),
+        }
+    }
+}
+
+/// Identifies a message by its source and its sequence number.
+pub(crate) fn default_message_id(message: &GossipsubMessage) -> MessageId {
+    let mut id = message.source.as_bytes().to_vec();
+    id.extend_from_slice(&message.sequence_number);
+    MessageId::new(id)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn builder_sets_mesh_parameters() {
+        let config = GossipsubConfigBuilder::new()
+            .mesh_n(8)
+            .mesh_n_low(6)
+            .mesh_n_high(16)
+            .gossip_lazy(10)
+            .fanout_ttl(Duration::from_secs(120))
+            .heartbeat_interval(Duration::from_secs(2))
+            .history_length(10)
+            .history_gossip(5)
+            .build()
+            .unwrap();
+        assert_eq!((config.mesh_n_low, config.mesh_n, config.mesh_n_high), (6, 8, 16));
+        assert_eq!(config.gossip_lazy, 10);
+        assert_eq!(config.fanout_ttl, Duration::from_secs(120));
+        assert_eq!(config.heartbeat_interval, Duration::from_secs(2));
+        assert_eq!((config.history_length, config.history_gossip), (10, 5));
+    }
+
+    #[test]
+    fn builder_rejects_inconsistent_parameters() {
+        assert!(GossipsubConfigBuilder::new().mesh_n(20).build().is_err());
+        assert!(GossipsubConfigBuilder::new().mesh_n_low(7).build().is_err());
+        assert!(GossipsubConfigBuilder::new().history_gossip(6).build().is_err());
+        assert!(GossipsubConfigBuilder::new().history_length(0).build().is_err());
+        assert!(GossipsubConfigBuilder::new().heartbeat_interval(Duration::from_secs(0)).build().is_err());
+    }
+}
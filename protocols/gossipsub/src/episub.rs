@@ -0,0 +1,907 @@
+// Copyright 2019 Parity Technologies (UK) Ltd.
+//
+// Permission is hereby granted, free of charge, to any person obtaining a
+// copy of this software and associated documentation files (the "Software"),
+// to deal in the Software without restriction, including without limitation
+// the rights to use, copy, modify, merge, publish, distribute, sublicense,
+// and/or sell copies of the Software, and to permit persons to whom the
+// Software is furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS
+// OR IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING
+// FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
+// DEALINGS IN THE SOFTWARE.
+//! Epidemic broadcast trees (Plumtree) as an alternative router to gossipsub.
+//!
+//! For each topic, peers are either *eager*, and receive the full messages, or *lazy*, and only
+//! receive the identifiers of the messages with `IHAVE`. Receiving a message twice means that
+//! the eager peers form a cycle, so the sender of the duplicate is made lazy with a `PRUNE`. If
+//! a message announced with `IHAVE` isn't received through the eager peers in time, the
+//! announcer is made eager again with a `GRAFT` and asked for the message with `IWANT`. The
+//! eager peers therefore converge to a spanning tree, which is repaired as nodes leave.
+//!
+//! The wire format is the one of gossipsub, so that the same handler is used.
+
+use crate::behaviour::GossipsubEvent;
+use crate::config::{default_message_id, SubscriptionFilter};
+use crate::handler::GossipsubHandler;
+use crate::mcache::MessageCache;
+use crate::protocol::{
+    GossipsubControlAction,
+    GossipsubMessage,
+    GossipsubRpc,
+    GossipsubSubscription,
+    GossipsubSubscriptionAction,
+    MessageId
+};
+use crate::topic::{Topic, TopicHash};
+use futures::prelude::*;
+use libp2p_core::{BoxedRng, ConnectedPoint, Multiaddr, PeerId, SeenCache};
+use libp2p_swarm::{NetworkBehaviour, NetworkBehaviourAction, PollParameters, ProtocolsHandler};
+use log::debug;
+use rand::{Rng, seq::SliceRandom};
+use std::{borrow::Cow, collections::{HashMap, HashSet, VecDeque}, iter, marker::PhantomData, sync::Arc, time::Duration};
+use tokio_io::{AsyncRead, AsyncWrite};
+use wasm_timer::{Delay, Instant};
+
+/// Configuration of an `Episub` behaviour.
+#[derive(Clone)]
+pub struct EpisubConfig {
+    /// Names of the protocol negotiated on the substreams, by order of preference.
+    protocol_ids: Vec<Cow<'static, [u8]>>,
+    /// Maximum number of eager peers of a topic when peers join it.
+    eager_n: usize,
+    /// Interval between two heartbeats, at which `IHAVE`s are sent and missing messages are
+    /// looked after.
+    heartbeat_interval: Duration,
+    /// Time we wait for a message announced with `IHAVE` before grafting the announcer.
+    graft_timeout: Duration,
+    /// Number of heartbeats during which messages are kept to answer `IWANT`s.
+    history_length: usize,
+    /// Maximum size of an RPC.
+    max_transmit_size: usize,
+    /// Time during which the identifiers of received messages are remembered.
+    duplicate_cache_time: Duration,
+    /// Maximum memory used by the identifiers of received messages, in bytes.
+    duplicate_cache_max_bytes: usize,
+    /// Function computing the identifier of a message.
+    message_id_fn: fn(&GossipsubMessage) -> MessageId,
+    /// Filter of the subscriptions of the remotes that we track.
+    subscription_filter: SubscriptionFilter,
+}
+
+impl Default for EpisubConfig {
+    fn default() -> Self {
+        EpisubConfig {
+            protocol_ids: vec![Cow::Borrowed(b"/episub/1.0.0")],
+            eager_n: 6,
+            heartbeat_interval: Duration::from_millis(200),
+            graft_timeout: Duration::from_secs(1),
+            history_length: 30,
+            max_transmit_size: 1024 * 1024,
+            duplicate_cache_time: Duration::from_secs(120),
+            duplicate_cache_max_bytes: 4 * 1024 * 1024,
+            message_id_fn: default_message_id,
+            subscription_filter: SubscriptionFilter::default(),
+        }
+    }
+}
+
+impl EpisubConfig {
+    /// Sets a custom protocol name.
+    pub fn set_protocol_id(&mut self, id: impl Into<Cow<'static, [u8]>>) -> &mut Self {
+        self.protocol_ids = vec![id.into()];
+        self
+    }
+
+    /// Sets the maximum number of eager peers of a topic when peers join it. Peers beyond that
+    /// number start lazy. The default is 6.
+    ///
+    /// # Panic
+    ///
+    /// Panics if `eager_n` is zero.
+    pub fn set_eager_n(&mut self, eager_n: usize) -> &mut Self {
+        assert!(eager_n > 0);
+        self.eager_n = eager_n;
+        self
+    }
+
+    /// Sets the interval between two heartbeats. The default is 200ms.
+    pub fn set_heartbeat_interval(&mut self, interval: Duration) -> &mut Self {
+        self.heartbeat_interval = interval;
+        self
+    }
+
+    /// Sets the time we wait for a message announced with `IHAVE` before asking for it. The
+    /// default is 1s.
+    pub fn set_graft_timeout(&mut self, timeout: Duration) -> &mut Self {
+        self.graft_timeout = timeout;
+        self
+    }
+
+    /// Sets the number of heartbeats during which messages are kept to answer requests. The
+    /// default is 30.
+    ///
+    /// # Panic
+    ///
+    /// Panics if `length` is zero.
+    pub fn set_history_length(&mut self, length: usize) -> &mut Self {
+        assert!(length > 0);
+        self.history_length = length;
+        self
+    }
+
+    /// Sets the maximum size of an RPC, sent or received. The default is 1MiB.
+    pub fn set_max_transmit_size(&mut self, size: usize) -> &mut Self {
+        self.max_transmit_size = size;
+        self
+    }
+
+    /// Sets for how long and within how much memory the identifiers of received messages are
+    /// remembered in order to drop duplicates. The default is 2 minutes and 4 MiB.
+    pub fn set_duplicate_cache(&mut self, window: Duration, max_bytes: usize) -> &mut Self {
+        self.duplicate_cache_time = window;
+        self.duplicate_cache_max_bytes = max_bytes;
+        self
+    }
+
+    /// Sets the function computing the identifier of a message. By default, the identifier is
+    /// the concatenation of the source and the sequence number.
+    pub fn set_message_id_fn(&mut self, f: fn(&GossipsubMessage) -> MessageId) -> &mut Self {
+        self.message_id_fn = f;
+        self
+    }
+
+    /// Sets the filter of the topics whose subscriptions we track. Subscriptions of the remotes
+    /// to other topics are ignored. All the topics are accepted by default.
+    pub fn set_subscription_filter<F>(&mut self, filter: F) -> &mut Self
+    where
+        F: Fn(&TopicHash) -> bool + Send + Sync + 'static,
+    {
+        self.subscription_filter.allowed = Some(Arc::new(filter));
+        self
+    }
+
+    /// Sets the maximum number of topics a remote can be subscribed to. There is no limit by
+    /// default.
+    pub fn set_max_subscriptions_per_peer(&mut self, max: usize) -> &mut Self {
+        self.subscription_filter.max_per_peer = Some(max);
+        self
+    }
+}
+
+/// Network behaviour that disseminates messages along epidemic broadcast trees.
+///
+/// The API is the one of `Gossipsub`, and the same events are generated, so that applications
+/// can switch from one router to the other.
+pub struct Episub<TSubstream> {
+    /// Configuration of the behaviour.
+    config: EpisubConfig,
+
+    /// Events that need to be yielded to the outside when polling.
+    events: VecDeque<NetworkBehaviourAction<GossipsubRpc, GossipsubEvent>>,
+
+    /// Peer id of the local node. Used for the source of the messages that we publish.
+    local_peer_id: PeerId,
+
+    /// Peers we're connected to, and the topics they're subscribed to.
+    peer_topics: HashMap<PeerId, HashSet<TopicHash>>,
+
+    /// Eager and lazy peers of each topic we're subscribed to. The keys are our subscriptions.
+    trees: HashMap<TopicHash, TopicPeers>,
+
+    /// Messages announced by lazy peers that we haven't received yet.
+    missing: HashMap<MessageId, MissingMessage>,
+
+    /// Identifiers to announce to lazy peers at the next heartbeat.
+    lazy_queue: HashMap<PeerId, Vec<(TopicHash, MessageId)>>,
+
+    /// Messages seen during the last heartbeats, to answer `IWANT`s.
+    mcache: MessageCache,
+
+    // We keep track of the messages we received (by `MessageId`) so that we don't dispatch the
+    // same message twice if we receive it twice on the network. Identifiers are forgotten after
+    // `duplicate_cache_time`, or earlier if they would exceed `duplicate_cache_max_bytes`.
+    received: SeenCache<MessageId>,
+
+    /// When the next heartbeat happens.
+    next_heartbeat: Delay,
+
+    /// Source of the sequence numbers and of the random choices of peers.
+    rng: BoxedRng,
+
+    /// Marker to pin the generics.
+    marker: PhantomData<TSubstream>,
+}
+
+/// Peers of a topic we're subscribed to.
+#[derive(Debug, Default)]
+struct TopicPeers {
+    /// Peers we push the full messages to.
+    eager: HashSet<PeerId>,
+    /// Peers we only announce the messages to.
+    lazy: HashSet<PeerId>,
+}
+
+impl TopicPeers {
+    /// Adds a peer that joined the topic, eager if there is room for it.
+    fn add(&mut self, peer_id: PeerId, eager_n: usize) {
+        if self.eager.contains(&peer_id) {
+            return;
+        }
+        if self.eager.len() < eager_n {
+            self.lazy.remove(&peer_id);
+            self.eager.insert(peer_id);
+        } else {
+            self.lazy.insert(peer_id);
+        }
+    }
+
+    fn remove(&mut self, peer_id: &PeerId) {
+        self.eager.remove(peer_id);
+        self.lazy.remove(peer_id);
+    }
+
+    fn make_eager(&mut self, peer_id: &PeerId) {
+        if self.lazy.remove(peer_id) {
+            self.eager.insert(peer_id.clone());
+        }
+    }
+
+    fn make_lazy(&mut self, peer_id: &PeerId) {
+        if self.eager.remove(peer_id) {
+            self.lazy.insert(peer_id.clone());
+        }
+    }
+}
+
+/// A message announced by lazy peers.
+struct MissingMessage {
+    /// Topic the message was announced on.
+    topic_hash: TopicHash,
+    /// Peers that announced the message, that we haven't asked for it yet.
+    announcers: VecDeque<PeerId>,
+    /// When we ask the next announcer for the message.
+    deadline: Instant,
+}
+
+impl<TSubstream> Episub<TSubstream> {
+    /// Creates an `Episub`.
+    pub fn new(local_peer_id: PeerId, config: EpisubConfig) -> Self {
+        Episub {
+            events: VecDeque::new(),
+            local_peer_id,
+            peer_topics: HashMap::new(),
+            trees: HashMap::new(),
+            missing: HashMap::new(),
+            lazy_queue: HashMap::new(),
+            mcache: MessageCache::new(0, config.history_length),
+            received: SeenCache::new(config.duplicate_cache_time, config.duplicate_cache_max_bytes),
+            next_heartbeat: Delay::new(Instant::now() + config.heartbeat_interval),
+            rng: BoxedRng::default(),
+            config,
+            marker: PhantomData,
+        }
+    }
+
+    /// Sets the generator of the sequence numbers and of the random choices of peers.
+    pub fn set_rng(&mut self, rng: BoxedRng) {
+        self.rng = rng;
+    }
+
+    /// Subscribes to a topic.
+    ///
+    /// Returns true if the subscription worked. Returns false if we were already subscribed.
+    pub fn subscribe(&mut self, topic: Topic) -> bool {
+        let topic_hash = topic.hash();
+        if self.trees.contains_key(topic_hash) {
+            return false;
+        }
+
+        let mut peers = TopicPeers::default();
+        let mut topic_peers = Vec::new();
+        for (peer_id, topics) in self.peer_topics.iter() {
+            self.events.push_back(NetworkBehaviourAction::SendEvent {
+                peer_id: peer_id.clone(),
+                event: subscription_rpc(topic_hash.clone(), GossipsubSubscriptionAction::Subscribe),
+            });
+            if topics.contains(topic_hash) {
+                topic_peers.push(peer_id.clone());
+            }
+        }
+
+        topic_peers.shuffle(&mut self.rng);
+        for peer_id in topic_peers {
+            peers.add(peer_id, self.config.eager_n);
+        }
+        self.trees.insert(topic_hash.clone(), peers);
+        true
+    }
+
+    /// Unsubscribes from a topic.
+    ///
+    /// Note that this only requires a `TopicHash` and not a full `Topic`.
+    ///
+    /// Returns true if we were subscribed to this topic.
+    pub fn unsubscribe(&mut self, topic: impl AsRef<TopicHash>) -> bool {
+        let topic_hash = topic.as_ref();
+        if self.trees.remove(topic_hash).is_none() {
+            return false;
+        }
+
+        for peer_id in self.peer_topics.keys() {
+            self.events.push_back(NetworkBehaviourAction::SendEvent {
+                peer_id: peer_id.clone(),
+                event: subscription_rpc(topic_hash.clone(), GossipsubSubscriptionAction::Unsubscribe),
+            });
+        }
+
+        self.missing.retain(|_, missing| &missing.topic_hash != topic_hash);
+        true
+    }
+
+    /// Publishes a message to the network.
+    ///
+    /// If we're subscribed to the topic, the message is pushed to the eager peers and announced
+    /// to the lazy ones. Otherwise, it's sent to a random set of peers subscribed to the topic.
+    pub fn publish(&mut self, topic: impl Into<TopicHash>, data: impl Into<Vec<u8>>) {
+        self.publish_many(iter::once(topic), data)
+    }
+
+    /// Publishes a message with multiple topics to the network.
+    pub fn publish_many(&mut self, topics: impl IntoIterator<Item = impl Into<TopicHash>>, data: impl Into<Vec<u8>>) {
+        let message = GossipsubMessage {
+            source: self.local_peer_id.clone(),
+            data: data.into(),
+            // If the sequence numbers are predictable, then an attacker could flood the network
+            // with packets with the predetermined sequence numbers and absorb our legitimate
+            // messages. We therefore use a random number.
+            sequence_number: self.rng.gen::<[u8; 8]>().to_vec(),
+            topics: topics.into_iter().map(Into::into).collect(),
+            signature: None,
+            key: None,
+        };
+
+        let id = (self.config.message_id_fn)(&message);
+        self.received.insert(id.clone());
+
+        let mut recipients = HashSet::new();
+        for topic_hash in &message.topics {
+            if self.trees.contains_key(topic_hash) {
+                continue;
+            }
+            let mut peers: Vec<_> = self.peer_topics.iter()
+                .filter(|(_, topics)| topics.contains(topic_hash))
+                .map(|(peer_id, _)| peer_id.clone())
+                .collect();
+            peers.shuffle(&mut self.rng);
+            recipients.extend(peers.into_iter().take(self.config.eager_n));
+        }
+        for peer_id in recipients {
+            self.send_messages(peer_id, vec![message.clone()]);
+        }
+
+        self.broadcast(id, message, None);
+    }
+
+    /// Returns the eager peers of a topic we're subscribed to.
+    pub fn eager_peers<'a>(&'a self, topic: &TopicHash) -> impl Iterator<Item = &'a PeerId> + 'a {
+        self.trees.get(topic).into_iter().flat_map(|peers| peers.eager.iter())
+    }
+
+    /// Returns the lazy peers of a topic we're subscribed to.
+    pub fn lazy_peers<'a>(&'a self, topic: &TopicHash) -> impl Iterator<Item = &'a PeerId> + 'a {
+        self.trees.get(topic).into_iter().flat_map(|peers| peers.lazy.iter())
+    }
+
+    /// Pushes a message to the eager peers of its topics, except the one we received it from,
+    /// and queues its announcement to the lazy ones.
+    fn broadcast(&mut self, id: MessageId, message: GossipsubMessage, propagation_source: Option<&PeerId>) {
+        let mut eager = HashSet::new();
+        for topic_hash in &message.topics {
+            let peers = match self.trees.get(topic_hash) {
+                Some(peers) => peers,
+                None => continue,
+            };
+            eager.extend(peers.eager.iter().filter(|p| Some(*p) != propagation_source).cloned());
+            for peer_id in peers.lazy.iter().filter(|p| Some(*p) != propagation_source) {
+                self.lazy_queue
+                    .entry(peer_id.clone())
+                    .or_insert_with(Vec::new)
+                    .push((topic_hash.clone(), id.clone()));
+            }
+        }
+
+        for peer_id in eager {
+            self.send_messages(peer_id, vec![message.clone()]);
+        }
+        self.mcache.put(id, message);
+    }
+
+    /// Handles subscriptions changes of a remote.
+    fn handle_received_subscriptions(&mut self, subscriptions: Vec<GossipsubSubscription>, propagation_source: &PeerId) {
+        let peer_topics = match self.peer_topics.get_mut(propagation_source) {
+            Some(topics) => topics,
+            None => {
+                debug!("Received subscriptions from unknown peer {:?}", propagation_source);
+                return;
+            }
+        };
+
+        for subscription in subscriptions {
+            match subscription.action {
+                GossipsubSubscriptionAction::Subscribe => {
+                    if peer_topics.contains(&subscription.topic) {
+                        continue;
+                    }
+                    if !self.config.subscription_filter.accepts(&subscription.topic, peer_topics.len()) {
+                        debug!("Ignoring subscription of {:?} to {:?}", propagation_source, subscription.topic);
+                        continue;
+                    }
+                    peer_topics.insert(subscription.topic.clone());
+                    if let Some(peers) = self.trees.get_mut(&subscription.topic) {
+                        peers.add(propagation_source.clone(), self.config.eager_n);
+                    }
+                    self.events.push_back(NetworkBehaviourAction::GenerateEvent(GossipsubEvent::Subscribed {
+                        peer_id: propagation_source.clone(),
+                        topic: subscription.topic,
+                    }));
+                }
+                GossipsubSubscriptionAction::Unsubscribe => {
+                    if !peer_topics.remove(&subscription.topic) {
+                        continue;
+                    }
+                    if let Some(peers) = self.trees.get_mut(&subscription.topic) {
+                        peers.remove(propagation_source);
+                    }
+                    self.events.push_back(NetworkBehaviourAction::GenerateEvent(GossipsubEvent::Unsubscribed {
+                        peer_id: propagation_source.clone(),
+                        topic: subscription.topic,
+                    }));
+                }
+            }
+        }
+    }
+
+    /// Handles a message pushed by a remote, either eagerly or in response to an `IWANT`.
+    fn handle_received_message(&mut self, message: GossipsubMessage, propagation_source: &PeerId) {
+        let id = (self.config.message_id_fn)(&message);
+
+        if !self.received.insert(id.clone()) {
+            // The eager peers form a cycle: cut it.
+            let mut prunes = Vec::new();
+            for topic_hash in &message.topics {
+                if let Some(peers) = self.trees.get_mut(topic_hash) {
+                    if peers.eager.contains(propagation_source) {
+                        peers.make_lazy(propagation_source);
+                        prunes.push(GossipsubControlAction::Prune {
+                            topic_hash: topic_hash.clone(),
+                            peers: Vec::new(),
+                            backoff: None,
+                        });
+                    }
+                }
+            }
+            if !prunes.is_empty() {
+                self.send_control(propagation_source.clone(), prunes);
+            }
+            return;
+        }
+
+        if !self.trees.keys().any(|t| message.topics.contains(t)) {
+            return;
+        }
+
+        // The sender is on the path of the message, so it's a good eager peer, whether it
+        // pushed the message or we asked for it.
+        self.missing.remove(&id);
+        for topic_hash in &message.topics {
+            if let Some(peers) = self.trees.get_mut(topic_hash) {
+                peers.make_eager(propagation_source);
+            }
+        }
+
+        self.events.push_back(NetworkBehaviourAction::GenerateEvent(GossipsubEvent::Message {
+            propagation_source: propagation_source.clone(),
+            message_id: id.clone(),
+            message: message.clone(),
+        }));
+        self.broadcast(id, message, Some(propagation_source));
+    }
+
+    /// Handles messages announced by a lazy peer.
+    fn handle_ihave(&mut self, peer_id: &PeerId, topic_hash: TopicHash, message_ids: Vec<MessageId>) {
+        if !self.trees.contains_key(&topic_hash) {
+            return;
+        }
+
+        let deadline = Instant::now() + self.config.graft_timeout;
+        for id in message_ids {
+            if self.received.contains(&id) {
+                continue;
+            }
+            let missing = self.missing.entry(id).or_insert_with(|| MissingMessage {
+                topic_hash: topic_hash.clone(),
+                announcers: VecDeque::new(),
+                deadline,
+            });
+            if !missing.announcers.contains(peer_id) {
+                missing.announcers.push_back(peer_id.clone());
+            }
+        }
+    }
+
+    /// Answers the messages requested by a peer.
+    fn handle_iwant(&mut self, peer_id: &PeerId, message_ids: Vec<MessageId>) {
+        let messages: Vec<_> = message_ids.iter()
+            .filter_map(|id| self.mcache.get(id))
+            .cloned()
+            .collect();
+
+        if !messages.is_empty() {
+            self.send_messages(peer_id.clone(), messages);
+        }
+    }
+
+    /// Sends the queued announcements, asks for the messages that didn't arrive in time, and
+    /// makes sure the tree of each topic is connected to at least one peer.
+    fn heartbeat(&mut self) {
+        let now = Instant::now();
+
+        for (peer_id, announcements) in self.lazy_queue.drain() {
+            let mut ihaves: HashMap<TopicHash, Vec<MessageId>> = HashMap::new();
+            for (topic_hash, id) in announcements {
+                ihaves.entry(topic_hash).or_insert_with(Vec::new).push(id);
+            }
+            let control_msgs = ihaves.into_iter()
+                .map(|(topic_hash, message_ids)| GossipsubControlAction::IHave { topic_hash, message_ids })
+                .collect();
+            self.events.push_back(NetworkBehaviourAction::SendEvent {
+                peer_id,
+                event: GossipsubRpc {
+                    messages: Vec::new(),
+                    subscriptions: Vec::new(),
+                    control_msgs,
+                },
+            });
+        }
+
+        let mut requests: HashMap<PeerId, Vec<GossipsubControlAction>> = HashMap::new();
+        let mut exhausted = Vec::new();
+        for (id, missing) in self.missing.iter_mut() {
+            if missing.deadline > now {
+                continue;
+            }
+            let announcer = match missing.announcers.pop_front() {
+                Some(announcer) => announcer,
+                None => {
+                    exhausted.push(id.clone());
+                    continue;
+                }
+            };
+            missing.deadline = now + self.config.graft_timeout;
+
+            if let Some(peers) = self.trees.get_mut(&missing.topic_hash) {
+                peers.make_eager(&announcer);
+            }
+            let actions = requests.entry(announcer).or_insert_with(Vec::new);
+            let graft = GossipsubControlAction::Graft { topic_hash: missing.topic_hash.clone() };
+            if !actions.contains(&graft) {
+                actions.push(graft);
+            }
+            actions.push(GossipsubControlAction::IWant { message_ids: vec![id.clone()] });
+        }
+        for id in exhausted {
+            self.missing.remove(&id);
+        }
+
+        // A topic without eager peers would only receive messages after a graft timeout.
+        for (topic_hash, peers) in self.trees.iter_mut() {
+            if !peers.eager.is_empty() {
+                continue;
+            }
+            let lazy: Vec<_> = peers.lazy.iter().cloned().collect();
+            if let Some(peer_id) = lazy.choose(&mut self.rng) {
+                peers.make_eager(peer_id);
+                requests.entry(peer_id.clone())
+                    .or_insert_with(Vec::new)
+                    .push(GossipsubControlAction::Graft { topic_hash: topic_hash.clone() });
+            }
+        }
+
+        for (peer_id, control_msgs) in requests {
+            self.send_control(peer_id, control_msgs);
+        }
+
+        self.mcache.shift();
+    }
+
+    /// Sends messages to a peer.
+    fn send_messages(&mut self, peer_id: PeerId, messages: Vec<GossipsubMessage>) {
+        self.events.push_back(NetworkBehaviourAction::SendEvent {
+            peer_id,
+            event: GossipsubRpc {
+                messages,
+                subscriptions: Vec::new(),
+                control_msgs: Vec::new(),
+            },
+        });
+    }
+
+    /// Sends control messages to a peer.
+    fn send_control(&mut self, peer_id: PeerId, control_msgs: Vec<GossipsubControlAction>) {
+        self.events.push_back(NetworkBehaviourAction::SendEvent {
+            peer_id,
+            event: GossipsubRpc {
+                messages: Vec::new(),
+                subscriptions: Vec::new(),
+                control_msgs,
+            },
+        });
+    }
+}
+
+impl<TSubstream> NetworkBehaviour for Episub<TSubstream>
+where
+    TSubstream: AsyncRead + AsyncWrite,
+{
+    type ProtocolsHandler = GossipsubHandler<TSubstream>;
+    type OutEvent = GossipsubEvent;
+
+    fn new_handler(&mut self) -> Self::ProtocolsHandler {
+        GossipsubHandler::new(self.config.protocol_ids.clone(), self.config.max_transmit_size)
+    }
+
+    fn addresses_of_peer(&mut self, _: &PeerId) -> Vec<Multiaddr> {
+        Vec::new()
+    }
+
+    fn inject_connected(&mut self, id: PeerId, _: ConnectedPoint) {
+        // We need to send our subscriptions to the newly-connected node.
+        if !self.trees.is_empty() {
+            let subscriptions = self.trees.keys()
+                .map(|topic_hash| GossipsubSubscription {
+                    topic: topic_hash.clone(),
+                    action: GossipsubSubscriptionAction::Subscribe,
+                })
+                .collect();
+            self.events.push_back(NetworkBehaviourAction::SendEvent {
+                peer_id: id.clone(),
+                event: GossipsubRpc {
+                    messages: Vec::new(),
+                    subscriptions,
+                    control_msgs: Vec::new(),
+                },
+            });
+        }
+
+        self.peer_topics.insert(id, HashSet::new());
+    }
+
+    fn inject_disconnected(&mut self, id: &PeerId, _: ConnectedPoint) {
+        self.peer_topics.remove(id);
+        self.lazy_queue.remove(id);
+        for peers in self.trees.values_mut() {
+            peers.remove(id);
+        }
+        for missing in self.missing.values_mut() {
+            missing.announcers.retain(|p| p != id);
+        }
+    }
+
+    fn inject_node_event(
+        &mut self,
+        propagation_source: PeerId,
+        event: GossipsubRpc,
+    ) {
+        if !event.subscriptions.is_empty() {
+            self.handle_received_subscriptions(event.subscriptions, &propagation_source);
+        }
+
+        for message in event.messages {
+            self.handle_received_message(message, &propagation_source);
+        }
+
+        for action in event.control_msgs {
+            match action {
+                GossipsubControlAction::IHave { topic_hash, message_ids } =>
+                    self.handle_ihave(&propagation_source, topic_hash, message_ids),
+                GossipsubControlAction::IWant { message_ids } =>
+                    self.handle_iwant(&propagation_source, message_ids),
+                GossipsubControlAction::Graft { topic_hash } => {
+                    if let Some(peers) = self.trees.get_mut(&topic_hash) {
+                        peers.make_eager(&propagation_source);
+                    }
+                }
+                GossipsubControlAction::Prune { topic_hash, .. } => {
+                    if let Some(peers) = self.trees.get_mut(&topic_hash) {
+                        peers.make_lazy(&propagation_source);
+                    }
+                }
+            }
+        }
+    }
+
+    fn poll(
+        &mut self,
+        _: &mut impl PollParameters,
+    ) -> Async<
+        NetworkBehaviourAction<
+            <Self::ProtocolsHandler as ProtocolsHandler>::InEvent,
+            Self::OutEvent,
+        >,
+    > {
+        if let Some(event) = self.events.pop_front() {
+            return Async::Ready(event);
+        }
+
+        loop {
+            match self.next_heartbeat.poll() {
+                Ok(Async::Ready(())) => {
+                    self.next_heartbeat.reset(Instant::now() + self.config.heartbeat_interval);
+                    self.heartbeat();
+                    if let Some(event) = self.events.pop_front() {
+                        return Async::Ready(event);
+                    }
+                }
+                Ok(Async::NotReady) => break,
+                Err(err) => {
+                    debug!("Episub heartbeat timer errored: {:?}", err);
+                    break;
+                }
+            }
+        }
+
+        Async::NotReady
+    }
+}
+
+/// Builds an RPC containing a single subscription.
+fn subscription_rpc(topic: TopicHash, action: GossipsubSubscriptionAction) -> GossipsubRpc {
+    GossipsubRpc {
+        messages: Vec::new(),
+        subscriptions: vec![GossipsubSubscription { topic, action }],
+        control_msgs: Vec::new(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use libp2p_core::transport::dummy::DummyStream;
+
+    type TestEpisub = Episub<DummyStream>;
+
+    fn endpoint() -> ConnectedPoint {
+        ConnectedPoint::Dialer { address: "/ip4/127.0.0.1/tcp/1234".parse().unwrap() }
+    }
+
+    /// Builds a behaviour subscribed to `topic` and connected to `n` peers subscribed to it.
+    fn build(topic: &Topic, n: usize, config: EpisubConfig) -> (TestEpisub, Vec<PeerId>) {
+        let mut es = Episub::new(PeerId::random(), config);
+        let peers: Vec<PeerId> = (0..n).map(|_| PeerId::random()).collect();
+        for peer_id in &peers {
+            es.inject_connected(peer_id.clone(), endpoint());
+            let rpc = subscription_rpc(topic.hash().clone(), GossipsubSubscriptionAction::Subscribe);
+            es.inject_node_event(peer_id.clone(), rpc);
+        }
+        es.subscribe(topic.clone());
+        es.events.clear();
+        (es, peers)
+    }
+
+    fn message_rpc(topic: &Topic, data: &[u8]) -> GossipsubRpc {
+        GossipsubRpc {
+            messages: vec![GossipsubMessage {
+                source: PeerId::random(),
+                data: data.to_vec(),
+                sequence_number: vec![0],
+                topics: vec![topic.hash().clone()],
+                signature: None,
+                key: None,
+            }],
+            subscriptions: Vec::new(),
+            control_msgs: Vec::new(),
+        }
+    }
+
+    /// Returns the control messages sent by the behaviour.
+    fn sent_control(es: &TestEpisub) -> Vec<(PeerId, GossipsubControlAction)> {
+        let mut sent = Vec::new();
+        for event in &es.events {
+            if let NetworkBehaviourAction::SendEvent { peer_id, event } = event {
+                sent.extend(event.control_msgs.iter().map(|c| (peer_id.clone(), c.clone())));
+            }
+        }
+        sent
+    }
+
+    #[test]
+    fn peers_beyond_eager_n_are_lazy() {
+        let topic = Topic::new("test");
+        let mut config = EpisubConfig::default();
+        config.set_eager_n(2);
+        let (es, _) = build(&topic, 5, config);
+
+        assert_eq!(es.eager_peers(topic.hash()).count(), 2);
+        assert_eq!(es.lazy_peers(topic.hash()).count(), 3);
+    }
+
+    #[test]
+    fn duplicates_prune_the_sender() {
+        let topic = Topic::new("test");
+        let (mut es, peers) = build(&topic, 3, EpisubConfig::default());
+        let rpc = message_rpc(&topic, b"hello");
+
+        es.inject_node_event(peers[0].clone(), rpc.clone());
+        let delivered = es.events.iter().filter(|e| match e {
+            NetworkBehaviourAction::GenerateEvent(GossipsubEvent::Message { .. }) => true,
+            _ => false,
+        }).count();
+        assert_eq!(delivered, 1);
+        es.events.clear();
+
+        es.inject_node_event(peers[1].clone(), rpc);
+        let sent = sent_control(&es);
+        assert_eq!(sent.len(), 1);
+        assert_eq!(sent[0].0, peers[1]);
+        match &sent[0].1 {
+            GossipsubControlAction::Prune { topic_hash, .. } => assert_eq!(topic_hash, topic.hash()),
+            action => panic!("unexpected action: {:?}", action),
+        }
+        assert!(es.lazy_peers(topic.hash()).any(|p| p == &peers[1]));
+        assert!(es.eager_peers(topic.hash()).any(|p| p == &peers[0]));
+    }
+
+    #[test]
+    fn missing_messages_graft_the_announcer() {
+        let topic = Topic::new("test");
+        let mut config = EpisubConfig::default();
+        config.set_eager_n(1).set_graft_timeout(Duration::from_secs(0));
+        let (mut es, peers) = build(&topic, 2, config);
+        let lazy = es.lazy_peers(topic.hash()).next().unwrap().clone();
+        assert!(peers.contains(&lazy));
+
+        let id = MessageId::new(b"missing".to_vec());
+        let ihave = GossipsubControlAction::IHave { topic_hash: topic.hash().clone(), message_ids: vec![id.clone()] };
+        es.inject_node_event(lazy.clone(), GossipsubRpc {
+            messages: Vec::new(),
+            subscriptions: Vec::new(),
+            control_msgs: vec![ihave],
+        });
+        es.heartbeat();
+
+        let sent = sent_control(&es);
+        assert!(sent.contains(&(lazy.clone(), GossipsubControlAction::Graft { topic_hash: topic.hash().clone() })));
+        assert!(sent.contains(&(lazy.clone(), GossipsubControlAction::IWant { message_ids: vec![id] })));
+        assert!(es.eager_peers(topic.hash()).any(|p| p == &lazy));
+    }
+
+    #[test]
+    fn received_messages_are_announced_to_lazy_peers() {
+        let topic = Topic::new("test");
+        let mut config = EpisubConfig::default();
+        config.set_eager_n(1);
+        let (mut es, _) = build(&topic, 3, config);
+        let eager = es.eager_peers(topic.hash()).next().unwrap().clone();
+
+        es.inject_node_event(eager, message_rpc(&topic, b"hello"));
+        es.events.clear();
+        es.heartbeat();
+
+        let announced: HashSet<PeerId> = sent_control(&es)
+            .into_iter()
+            .filter_map(|(peer_id, action)| match action {
+                GossipsubControlAction::IHave { .. } => Some(peer_id),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(announced, es.lazy_peers(topic.hash()).cloned().collect());
+    }
+}
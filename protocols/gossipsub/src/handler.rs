@@ -0,0 +1,242 @@
+// Copyright 2019 Parity Technologies (UK) Ltd.
+//
+// Permission is hereby granted, free of charge, to any person obtaining a
+// copy of this software and associated documentation files (the "Software"),
+// to deal in the Software without restriction, including without limitation
+// the rights to use, copy, modify, merge, publish, distribute, sublicense,
+// and/or sell copies of the Software, and to permit persons to whom the
+// Software is furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS
+// OR IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING
+// FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
+// DEALINGS IN THE SOFTWARE.
+
+use crate::protocol::{GossipsubCodec, GossipsubRpc, ProtocolConfig};
+use futures::prelude::*;
+use libp2p_core::upgrade::{InboundUpgrade, Negotiated, OutboundUpgrade};
+use libp2p_swarm::{
+    KeepAlive,
+    ProtocolsHandler,
+    ProtocolsHandlerEvent,
+    ProtocolsHandlerUpgrErr,
+    SubstreamProtocol
+};
+use log::debug;
+use std::{borrow::Cow, collections::VecDeque, io};
+use tokio_codec::Framed;
+use tokio_io::{AsyncRead, AsyncWrite};
+
+/// Protocol handler that manages the gossipsub substreams of a connection.
+///
+/// Contrary to floodsub, a single long-lived substream is used in each direction, as other
+/// implementations expect: RPCs received from the remote are read from the substream it opened,
+/// and the ones we send are written to the substream we opened.
+pub struct GossipsubHandler<TSubstream>
+where
+    TSubstream: AsyncRead + AsyncWrite,
+{
+    /// Upgrade configuration for the gossipsub protocol.
+    listen_protocol: SubstreamProtocol<ProtocolConfig>,
+
+    /// The substream opened by the remote, if any.
+    inbound_substream: Option<InboundSubstreamState<TSubstream>>,
+
+    /// The substream we opened, if any.
+    outbound_substream: Option<OutboundSubstreamState<TSubstream>>,
+
+    /// True if we are in the process of opening the outbound substream.
+    outbound_substream_establishing: bool,
+
+    /// RPCs waiting to be sent to the remote.
+    send_queue: VecDeque<GossipsubRpc>,
+}
+
+/// State of the substream opened by the remote.
+enum InboundSubstreamState<TSubstream> {
+    /// Waiting for an RPC from the remote.
+    WaitingInput(Framed<Negotiated<TSubstream>, GossipsubCodec>),
+    /// The substream is being closed.
+    Closing(Framed<Negotiated<TSubstream>, GossipsubCodec>),
+}
+
+/// State of the substream opened by us.
+enum OutboundSubstreamState<TSubstream> {
+    /// Waiting for an RPC to send.
+    WaitingOutput(Framed<Negotiated<TSubstream>, GossipsubCodec>),
+    /// Waiting to send an RPC to the remote.
+    PendingSend(Framed<Negotiated<TSubstream>, GossipsubCodec>, GossipsubRpc),
+    /// Waiting to flush the substream so that the data arrives to the remote.
+    PendingFlush(Framed<Negotiated<TSubstream>, GossipsubCodec>),
+}
+
+impl<TSubstream> GossipsubHandler<TSubstream>
+where
+    TSubstream: AsyncRead + AsyncWrite,
+{
+    /// Builds a new `GossipsubHandler`.
+    pub fn new(protocol_ids: Vec<Cow<'static, [u8]>>, max_transmit_size: usize) -> Self {
+        GossipsubHandler {
+            listen_protocol: SubstreamProtocol::new(ProtocolConfig::new(protocol_ids, max_transmit_size)),
+            inbound_substream: None,
+            outbound_substream: None,
+            outbound_substream_establishing: false,
+            send_queue: VecDeque::new(),
+        }
+    }
+}
+
+impl<TSubstream> ProtocolsHandler for GossipsubHandler<TSubstream>
+where
+    TSubstream: AsyncRead + AsyncWrite,
+{
+    type InEvent = GossipsubRpc;
+    type OutEvent = GossipsubRpc;
+    type Error = io::Error;
+    type Substream = TSubstream;
+    type InboundProtocol = ProtocolConfig;
+    type OutboundProtocol = ProtocolConfig;
+    type OutboundOpenInfo = GossipsubRpc;
+
+    #[inline]
+    fn listen_protocol(&self) -> SubstreamProtocol<Self::InboundProtocol> {
+        self.listen_protocol.clone()
+    }
+
+    fn inject_fully_negotiated_inbound(
+        &mut self,
+        substream: <Self::InboundProtocol as InboundUpgrade<TSubstream>>::Output
+    ) {
+        // A new substream from the remote replaces the previous one.
+        self.inbound_substream = Some(InboundSubstreamState::WaitingInput(substream));
+    }
+
+    fn inject_fully_negotiated_outbound(
+        &mut self,
+        substream: <Self::OutboundProtocol as OutboundUpgrade<TSubstream>>::Output,
+        message: Self::OutboundOpenInfo
+    ) {
+        self.outbound_substream_establishing = false;
+        if self.outbound_substream.is_none() {
+            self.outbound_substream = Some(OutboundSubstreamState::PendingSend(substream, message));
+        } else {
+            self.send_queue.push_front(message);
+        }
+    }
+
+    #[inline]
+    fn inject_event(&mut self, message: GossipsubRpc) {
+        self.send_queue.push_back(message);
+    }
+
+    #[inline]
+    fn inject_dial_upgrade_error(
+        &mut self,
+        _: Self::OutboundOpenInfo,
+        err: ProtocolsHandlerUpgrErr<<Self::OutboundProtocol as OutboundUpgrade<TSubstream>>::Error>
+    ) {
+        debug!("Failed to open a gossipsub substream: {:?}", err);
+        self.outbound_substream_establishing = false;
+    }
+
+    #[inline]
+    fn connection_keep_alive(&self) -> KeepAlive {
+        KeepAlive::Yes
+    }
+
+    fn poll(
+        &mut self,
+    ) -> Poll<
+        ProtocolsHandlerEvent<Self::OutboundProtocol, Self::OutboundOpenInfo, Self::OutEvent>,
+        io::Error,
+    > {
+        loop {
+            match self.inbound_substream.take() {
+                Some(InboundSubstreamState::WaitingInput(mut substream)) => match substream.poll() {
+                    Ok(Async::Ready(Some(message))) => {
+                        self.inbound_substream = Some(InboundSubstreamState::WaitingInput(substream));
+                        return Ok(Async::Ready(ProtocolsHandlerEvent::Custom(message)));
+                    }
+                    Ok(Async::Ready(None)) => {
+                        self.inbound_substream = Some(InboundSubstreamState::Closing(substream));
+                    }
+                    Ok(Async::NotReady) => {
+                        self.inbound_substream = Some(InboundSubstreamState::WaitingInput(substream));
+                        break;
+                    }
+                    Err(err) => {
+                        debug!("Error on the inbound gossipsub substream: {:?}", err);
+                        self.inbound_substream = Some(InboundSubstreamState::Closing(substream));
+                    }
+                },
+                Some(InboundSubstreamState::Closing(mut substream)) => match substream.close() {
+                    Ok(Async::NotReady) => {
+                        self.inbound_substream = Some(InboundSubstreamState::Closing(substream));
+                        break;
+                    }
+                    Ok(Async::Ready(())) | Err(_) => break,
+                },
+                None => break,
+            }
+        }
+
+        loop {
+            match self.outbound_substream.take() {
+                Some(OutboundSubstreamState::WaitingOutput(substream)) => {
+                    if let Some(message) = self.send_queue.pop_front() {
+                        self.outbound_substream = Some(OutboundSubstreamState::PendingSend(substream, message));
+                    } else {
+                        self.outbound_substream = Some(OutboundSubstreamState::WaitingOutput(substream));
+                        break;
+                    }
+                }
+                Some(OutboundSubstreamState::PendingSend(mut substream, message)) => match substream.start_send(message) {
+                    Ok(AsyncSink::Ready) => {
+                        self.outbound_substream = Some(OutboundSubstreamState::PendingFlush(substream));
+                    }
+                    Ok(AsyncSink::NotReady(message)) => {
+                        self.outbound_substream = Some(OutboundSubstreamState::PendingSend(substream, message));
+                        break;
+                    }
+                    Err(err) => {
+                        // A new substream is opened for the next message.
+                        debug!("Error on the outbound gossipsub substream: {:?}", err);
+                        break;
+                    }
+                },
+                Some(OutboundSubstreamState::PendingFlush(mut substream)) => match substream.poll_complete() {
+                    Ok(Async::Ready(())) => {
+                        self.outbound_substream = Some(OutboundSubstreamState::WaitingOutput(substream));
+                    }
+                    Ok(Async::NotReady) => {
+                        self.outbound_substream = Some(OutboundSubstreamState::PendingFlush(substream));
+                        break;
+                    }
+                    Err(err) => {
+                        debug!("Error on the outbound gossipsub substream: {:?}", err);
+                        break;
+                    }
+                },
+                None => break,
+            }
+        }
+
+        if self.outbound_substream.is_none() && !self.outbound_substream_establishing {
+            if let Some(message) = self.send_queue.pop_front() {
+                self.outbound_substream_establishing = true;
+                return Ok(Async::Ready(ProtocolsHandlerEvent::OutboundSubstreamRequest {
+                    protocol: self.listen_protocol.clone(),
+                    info: message,
+                }));
+            }
+        }
+
+        Ok(Async::NotReady)
+    }
+}
@@ -0,0 +1,73 @@
+// Copyright 2019 Parity Technologies (UK) Ltd.
+//
+// Permission is hereby granted, free of charge, to any person obtaining a
+// copy of this software and associated documentation files (the "Software"),
+// to deal in the Software without restriction, including without limitation
+// the rights to use, copy, modify, merge, publish, distribute, sublicense,
+// and/or sell copies of the Software, and to permit persons to whom the
+// Software is furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS
+// OR IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING
+// FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
+// DEALINGS IN THE SOFTWARE.
+
+//! Implements the gossipsub protocol, see also the:
+//! [spec](https://github.com/libp2p/specs/tree/master/pubsub/gossipsub).
+//!
+//! Gossipsub is an extension of floodsub that limits the amplification of messages: for each
+//! topic, messages are only sent to a bounded set of peers, the mesh, which is maintained with
+//! `GRAFT` and `PRUNE` control messages. The identifiers of recently seen messages are gossiped
+//! to other peers with `IHAVE`, which can then request them with `IWANT`.
+//!
+//! The v1.1 extensions are supported as well: peers can be scored according to their behaviour
+//! (see `GossipsubConfig::set_peer_score`), pruned peers are given a backoff during which they
+//! must not graft again, `PRUNE`s can carry other peers of the topic (peer exchange), messages
+//! we publish are flooded to all the peers of the topic, and meshes whose peers perform poorly
+//! are opportunistically improved.
+//!
+//! Received messages can be validated by the application before being forwarded (see
+//! `GossipsubConfig::set_validate_messages`): they are then only forwarded once reported as
+//! accepted with `Gossipsub::report_message_validation_result`.
+//!
+//! The protocol is compatible with the go-libp2p implementation. Both `/meshsub/1.1.0` and
+//! `/meshsub/1.0.0` are supported by default.
+//!
+//! For very large and stable topics, the [`Episub`] behaviour is an alternative router based on
+//! epidemic broadcast trees (Plumtree): messages are pushed along a spanning tree of the peers,
+//! and only announced with `IHAVE` to the others. It exposes the same API and generates the
+//! same events as [`Gossipsub`], but doesn't interoperate with it.
+//!
+//! The authenticity of the messages is set by the `SigningPolicy` of the configuration: messages
+//! can be signed and their signature required or only checked when present, or be published
+//! anonymously. By default, messages aren't signed, and the signatures of the received messages
+//! are only checked when present. Messages of the `Episub` behaviour are never signed.
+
+pub mod protocol;
+
+mod behaviour;
+mod config;
+mod episub;
+mod handler;
+mod mcache;
+mod peer_score;
+mod topic;
+
+/// Protobuf messages of the gossipsub protocol, generated from `rpc.proto`.
+pub mod rpc_proto {
+    include!(concat!(env!("OUT_DIR"), "/gossipsub.pb.rs"));
+}
+
+pub use self::behaviour::{Gossipsub, GossipsubEvent, MessageAcceptance};
+pub use self::config::{GossipsubConfig, GossipsubConfigBuilder, SigningPolicy};
+pub use self::episub::{Episub, EpisubConfig};
+pub use self::handler::GossipsubHandler;
+pub use self::peer_score::{PeerScoreParams, PeerScoreThresholds, TopicScoreParams};
+pub use self::protocol::{GossipsubMessage, GossipsubRpc, MessageId, PeerInfo};
+pub use self::topic::{Topic, TopicHash};
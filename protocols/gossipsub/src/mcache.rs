@@ -0,0 +1,134 @@
+// Copyright 2019 Parity Technologies (UK) Ltd.
+//
+// Permission is hereby granted, free of charge, to any person obtaining a
+// copy of this software and associated documentation files (the "Software"),
+// to deal in the Software without restriction, including without limitation
+// the rights to use, copy, modify, merge, publish, distribute, sublicense,
+// and/or sell copies of the Software, and to permit persons to whom the
+// Software is furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS
+// OR IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING
+// FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
+// DEALINGS IN THE SOFTWARE.
+
+use crate::protocol::{GossipsubMessage, MessageId};
+use crate::topic::TopicHash;
+use std::collections::HashMap;
+
+/// Entry of the history of the cache.
+#[derive(Debug, Clone)]
+struct CacheEntry {
+    id: MessageId,
+    topics: Vec<TopicHash>,
+}
+
+/// Cache of the messages seen during the last heartbeats.
+///
+/// Messages are served to the peers that request them with `IWANT`, and the ones seen during the
+/// most recent heartbeats are advertised with `IHAVE`.
+#[derive(Debug, Clone)]
+pub struct MessageCache {
+    /// The cached messages.
+    msgs: HashMap<MessageId, GossipsubMessage>,
+    /// Messages seen during each of the last heartbeats, the most recent first.
+    history: Vec<Vec<CacheEntry>>,
+    /// Number of heartbeats whose messages are advertised.
+    gossip: usize,
+}
+
+impl MessageCache {
+    /// Creates a cache keeping the messages of the last `history` heartbeats, and advertising
+    /// the ones of the last `gossip` heartbeats.
+    pub fn new(gossip: usize, history: usize) -> MessageCache {
+        MessageCache {
+            msgs: HashMap::new(),
+            history: vec![Vec::new(); history.max(1)],
+            gossip,
+        }
+    }
+
+    /// Adds a message to the cache.
+    pub fn put(&mut self, id: MessageId, msg: GossipsubMessage) {
+        let entry = CacheEntry {
+            id: id.clone(),
+            topics: msg.topics.clone(),
+        };
+
+        if self.msgs.insert(id, msg).is_none() {
+            self.history[0].push(entry);
+        }
+    }
+
+    /// Returns a message from the cache, if it's still in there.
+    pub fn get(&self, id: &MessageId) -> Option<&GossipsubMessage> {
+        self.msgs.get(id)
+    }
+
+    /// Returns the identifiers of the messages of the given topic to advertise.
+    pub fn get_gossip_ids(&self, topic: &TopicHash) -> Vec<MessageId> {
+        self.history
+            .iter()
+            .take(self.gossip)
+            .flat_map(|entries| entries.iter())
+            .filter(|entry| entry.topics.contains(topic))
+            .map(|entry| entry.id.clone())
+            .collect()
+    }
+
+    /// Shifts the history by one heartbeat, dropping the messages of the oldest one.
+    pub fn shift(&mut self) {
+        if let Some(entries) = self.history.pop() {
+            for entry in entries {
+                self.msgs.remove(&entry.id);
+            }
+        }
+        self.history.insert(0, Vec::new());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use libp2p_core::PeerId;
+
+    fn message(seq: u8, topic: &str) -> (MessageId, GossipsubMessage) {
+        let msg = GossipsubMessage {
+            source: PeerId::random(),
+            data: vec![seq],
+            sequence_number: vec![seq],
+            topics: vec![TopicHash::from_raw(topic)],
+            signature: None,
+            key: None,
+        };
+        (MessageId::new(vec![seq]), msg)
+    }
+
+    #[test]
+    fn gossip_only_covers_recent_heartbeats() {
+        let mut cache = MessageCache::new(1, 2);
+        let topic = TopicHash::from_raw("topic");
+
+        let (id1, msg1) = message(1, "topic");
+        cache.put(id1.clone(), msg1);
+        assert_eq!(cache.get_gossip_ids(&topic), vec![id1.clone()]);
+        assert!(cache.get_gossip_ids(&TopicHash::from_raw("other")).is_empty());
+
+        cache.shift();
+        let (id2, msg2) = message(2, "topic");
+        cache.put(id2.clone(), msg2);
+        assert_eq!(cache.get_gossip_ids(&topic), vec![id2.clone()]);
+        assert!(cache.get(&id1).is_some());
+
+        cache.shift();
+        assert!(cache.get(&id1).is_none());
+        assert!(cache.get(&id2).is_some());
+        assert!(cache.get_gossip_ids(&topic).is_empty());
+    }
+}
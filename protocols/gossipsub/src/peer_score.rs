@@ -0,0 +1,603 @@
+// Copyright 2019 Parity Technologies (UK) Ltd.
+//
+// Permission is hereby granted, free of charge, to any person obtaining a
+// copy of this software and associated documentation files (the "Software"),
+// to deal in the Software without restriction, including without limitation
+// the rights to use, copy, modify, merge, publish, distribute, sublicense,
+// and/or sell copies of the Software, and to permit persons to whom the
+// Software is furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS
+// OR IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING
+// FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
+// DEALINGS IN THE SOFTWARE.
+
+//! Scoring of the peers, as described in the gossipsub v1.1 specification.
+//!
+//! The score of a peer is the sum of:
+//!
+//! - For each topic, weighted by the topic weight and capped by the topic score cap:
+//!   - P1, the time the peer has been in our mesh;
+//!   - P2, the number of messages first delivered by the peer;
+//!   - P3, the square of the deficit of messages delivered by the peer while in the mesh;
+//!   - P3b, a sticky penalty applied when the peer leaves the mesh with a deficit;
+//!   - P4, the square of the number of invalid messages delivered by the peer.
+//! - P5, a score set by the application;
+//! - P6, the square of the number of peers sharing an IP address with the peer, beyond a
+//!   threshold;
+//! - P7, the square of the penalties applied when the peer misbehaves.
+//!
+//! Counters decay periodically, so that the score reflects the recent behaviour of the peer.
+
+use crate::protocol::MessageId;
+use crate::topic::TopicHash;
+use libp2p_core::{ConnectedPoint, PeerId, multiaddr::Protocol};
+use std::{collections::{HashMap, HashSet}, net::IpAddr, time::Duration};
+use wasm_timer::Instant;
+
+/// How long we remember the peers that delivered a message.
+const DELIVERY_RECORD_TTL: Duration = Duration::from_secs(2 * 60);
+
+/// Parameters of the scoring of the peers.
+#[derive(Debug, Clone)]
+pub struct PeerScoreParams {
+    /// Scoring parameters of each topic. Topics without parameters don't contribute to the
+    /// score.
+    pub topics: HashMap<TopicHash, TopicScoreParams>,
+    /// Cap of the sum of the weighted topic scores. No cap if zero.
+    pub topic_score_cap: f64,
+    /// Weight of the application-specific score (P5).
+    pub app_specific_weight: f64,
+    /// Weight of the IP colocation factor (P6). Should be negative.
+    pub ip_colocation_factor_weight: f64,
+    /// Number of peers sharing an IP address above which P6 applies.
+    pub ip_colocation_factor_threshold: f64,
+    /// Weight of the behaviour penalty (P7). Should be negative.
+    pub behaviour_penalty_weight: f64,
+    /// Decay of the behaviour penalty.
+    pub behaviour_penalty_decay: f64,
+    /// Interval at which the counters decay.
+    pub decay_interval: Duration,
+    /// Value below which a decaying counter is reset to zero.
+    pub decay_to_zero: f64,
+    /// How long the score of a disconnected peer is kept.
+    pub retain_score: Duration,
+}
+
+impl Default for PeerScoreParams {
+    fn default() -> Self {
+        PeerScoreParams {
+            topics: HashMap::new(),
+            topic_score_cap: 3600.0,
+            app_specific_weight: 10.0,
+            ip_colocation_factor_weight: -5.0,
+            ip_colocation_factor_threshold: 10.0,
+            behaviour_penalty_weight: -10.0,
+            behaviour_penalty_decay: 0.2,
+            decay_interval: Duration::from_secs(1),
+            decay_to_zero: 0.1,
+            retain_score: Duration::from_secs(3600),
+        }
+    }
+}
+
+/// Parameters of the scoring of the peers for a topic.
+#[derive(Debug, Clone)]
+pub struct TopicScoreParams {
+    /// Weight of the topic score in the score of the peer.
+    pub topic_weight: f64,
+
+    /// Weight of the time in mesh (P1).
+    pub time_in_mesh_weight: f64,
+    /// Unit of the time in mesh.
+    pub time_in_mesh_quantum: Duration,
+    /// Cap of the time in mesh, in units.
+    pub time_in_mesh_cap: f64,
+
+    /// Weight of the first message deliveries (P2).
+    pub first_message_deliveries_weight: f64,
+    /// Decay of the first message deliveries.
+    pub first_message_deliveries_decay: f64,
+    /// Cap of the first message deliveries.
+    pub first_message_deliveries_cap: f64,
+
+    /// Weight of the mesh message deliveries deficit (P3). Should be negative.
+    pub mesh_message_deliveries_weight: f64,
+    /// Decay of the mesh message deliveries.
+    pub mesh_message_deliveries_decay: f64,
+    /// Cap of the mesh message deliveries.
+    pub mesh_message_deliveries_cap: f64,
+    /// Number of mesh message deliveries below which the peer has a deficit.
+    pub mesh_message_deliveries_threshold: f64,
+    /// Time after the first delivery of a message during which a duplicate delivery by a mesh
+    /// peer still counts.
+    pub mesh_message_deliveries_window: Duration,
+    /// Time a peer must have been in the mesh before P3 applies.
+    pub mesh_message_deliveries_activation: Duration,
+
+    /// Weight of the mesh failure penalty (P3b). Should be negative.
+    pub mesh_failure_penalty_weight: f64,
+    /// Decay of the mesh failure penalty.
+    pub mesh_failure_penalty_decay: f64,
+
+    /// Weight of the invalid message deliveries (P4). Should be negative.
+    pub invalid_message_deliveries_weight: f64,
+    /// Decay of the invalid message deliveries.
+    pub invalid_message_deliveries_decay: f64,
+}
+
+impl Default for TopicScoreParams {
+    fn default() -> Self {
+        TopicScoreParams {
+            topic_weight: 0.5,
+            time_in_mesh_weight: 1.0,
+            time_in_mesh_quantum: Duration::from_millis(1),
+            time_in_mesh_cap: 3600.0,
+            first_message_deliveries_weight: 1.0,
+            first_message_deliveries_decay: 0.5,
+            first_message_deliveries_cap: 2000.0,
+            mesh_message_deliveries_weight: -1.0,
+            mesh_message_deliveries_decay: 0.5,
+            mesh_message_deliveries_cap: 100.0,
+            mesh_message_deliveries_threshold: 20.0,
+            mesh_message_deliveries_window: Duration::from_millis(10),
+            mesh_message_deliveries_activation: Duration::from_secs(5),
+            mesh_failure_penalty_weight: -1.0,
+            mesh_failure_penalty_decay: 0.5,
+            invalid_message_deliveries_weight: -1.0,
+            invalid_message_deliveries_decay: 0.3,
+        }
+    }
+}
+
+/// Thresholds of the score of a peer, which determine how we interact with it.
+///
+/// All the thresholds are zero by default.
+#[derive(Debug, Clone, Default)]
+pub struct PeerScoreThresholds {
+    /// Score below which we don't gossip with the peer. Should be negative.
+    pub gossip_threshold: f64,
+    /// Score below which we don't flood-publish our messages to the peer. Should be lower than
+    /// `gossip_threshold`.
+    pub publish_threshold: f64,
+    /// Score below which we ignore all the RPCs of the peer. Should be lower than
+    /// `publish_threshold`.
+    pub graylist_threshold: f64,
+    /// Score above which we accept the peers exchanged in the `PRUNE`s of the peer. Should be
+    /// positive.
+    pub accept_px_threshold: f64,
+    /// Median score of the mesh of a topic below which we graft peers with a better score.
+    /// Should be positive.
+    pub opportunistic_graft_threshold: f64,
+}
+
+/// Counters of a peer for a topic.
+#[derive(Debug, Clone, Default)]
+struct TopicStats {
+    /// When the peer joined the mesh, if it's in the mesh.
+    graft_time: Option<Instant>,
+    /// Whether the peer has been in the mesh long enough for P3 to apply.
+    mesh_message_deliveries_active: bool,
+    first_message_deliveries: f64,
+    mesh_message_deliveries: f64,
+    mesh_failure_penalty: f64,
+    invalid_message_deliveries: f64,
+}
+
+/// Counters of a peer.
+#[derive(Debug, Clone)]
+struct PeerStats {
+    /// If the peer is disconnected, until when its score is kept.
+    expires_at: Option<Instant>,
+    /// IP addresses of the peer.
+    ips: Vec<IpAddr>,
+    topics: HashMap<TopicHash, TopicStats>,
+    behaviour_penalty: f64,
+    application_score: f64,
+}
+
+/// Peers that delivered a message.
+#[derive(Debug, Clone)]
+struct DeliveryRecord {
+    first_seen: Instant,
+    peers: HashSet<PeerId>,
+}
+
+/// Scores of the peers.
+#[derive(Debug, Clone)]
+pub(crate) struct PeerScore {
+    params: PeerScoreParams,
+    peer_stats: HashMap<PeerId, PeerStats>,
+    /// Peers connected from each IP address.
+    peer_ips: HashMap<IpAddr, HashSet<PeerId>>,
+    deliveries: HashMap<MessageId, DeliveryRecord>,
+    last_decay: Instant,
+}
+
+impl PeerScore {
+    pub(crate) fn new(params: PeerScoreParams) -> PeerScore {
+        PeerScore {
+            params,
+            peer_stats: HashMap::new(),
+            peer_ips: HashMap::new(),
+            deliveries: HashMap::new(),
+            last_decay: Instant::now(),
+        }
+    }
+
+    /// Returns the score of a peer.
+    pub(crate) fn score(&self, peer_id: &PeerId) -> f64 {
+        let stats = match self.peer_stats.get(peer_id) {
+            Some(stats) => stats,
+            None => return 0.0,
+        };
+        let now = Instant::now();
+
+        let mut topics_score = 0.0;
+        for (topic_hash, topic_stats) in &stats.topics {
+            let params = match self.params.topics.get(topic_hash) {
+                Some(params) => params,
+                None => continue,
+            };
+
+            let mut topic_score = 0.0;
+
+            if let Some(graft_time) = topic_stats.graft_time {
+                let quantum = duration_to_f64(params.time_in_mesh_quantum).max(std::f64::EPSILON);
+                let p1 = (duration_to_f64(now - graft_time) / quantum).min(params.time_in_mesh_cap);
+                topic_score += p1 * params.time_in_mesh_weight;
+            }
+
+            topic_score += topic_stats.first_message_deliveries * params.first_message_deliveries_weight;
+
+            if topic_stats.mesh_message_deliveries_active
+                && topic_stats.mesh_message_deliveries < params.mesh_message_deliveries_threshold
+            {
+                let deficit = params.mesh_message_deliveries_threshold - topic_stats.mesh_message_deliveries;
+                topic_score += deficit * deficit * params.mesh_message_deliveries_weight;
+            }
+
+            topic_score += topic_stats.mesh_failure_penalty * params.mesh_failure_penalty_weight;
+
+            topic_score += topic_stats.invalid_message_deliveries
+                * topic_stats.invalid_message_deliveries
+                * params.invalid_message_deliveries_weight;
+
+            topics_score += topic_score * params.topic_weight;
+        }
+
+        if self.params.topic_score_cap > 0.0 && topics_score > self.params.topic_score_cap {
+            topics_score = self.params.topic_score_cap;
+        }
+
+        let mut score = topics_score;
+        score += stats.application_score * self.params.app_specific_weight;
+
+        for ip in &stats.ips {
+            let peers_in_ip = self.peer_ips.get(ip).map_or(0, |peers| peers.len()) as f64;
+            if peers_in_ip > self.params.ip_colocation_factor_threshold {
+                let surplus = peers_in_ip - self.params.ip_colocation_factor_threshold;
+                score += surplus * surplus * self.params.ip_colocation_factor_weight;
+            }
+        }
+
+        score += stats.behaviour_penalty * stats.behaviour_penalty * self.params.behaviour_penalty_weight;
+
+        score
+    }
+
+    /// Starts tracking a newly connected peer.
+    pub(crate) fn add_peer(&mut self, peer_id: PeerId, endpoint: &ConnectedPoint) {
+        let ip = ip_of_endpoint(endpoint);
+        let stats = self.peer_stats.entry(peer_id.clone()).or_insert_with(|| PeerStats {
+            expires_at: None,
+            ips: Vec::new(),
+            topics: HashMap::new(),
+            behaviour_penalty: 0.0,
+            application_score: 0.0,
+        });
+        stats.expires_at = None;
+
+        if let Some(ip) = ip {
+            if !stats.ips.contains(&ip) {
+                stats.ips.push(ip);
+            }
+            self.peer_ips.entry(ip).or_insert_with(HashSet::new).insert(peer_id);
+        }
+    }
+
+    /// Handles the disconnection of a peer. Its score is kept for a while if it's negative, so
+    /// that reconnecting doesn't reset it.
+    pub(crate) fn remove_peer(&mut self, peer_id: &PeerId) {
+        if self.score(peer_id) > 0.0 {
+            self.forget_peer(peer_id);
+            return;
+        }
+
+        let now = Instant::now();
+        let retain_score = self.params.retain_score;
+        let mut ips = Vec::new();
+        if let Some(stats) = self.peer_stats.get_mut(peer_id) {
+            for (topic_hash, topic_stats) in stats.topics.iter_mut() {
+                if topic_stats.graft_time.take().is_some() {
+                    if let Some(params) = self.params.topics.get(topic_hash) {
+                        apply_mesh_failure_penalty(topic_stats, params);
+                    }
+                }
+            }
+            stats.expires_at = Some(now + retain_score);
+            ips = stats.ips.clone();
+        }
+
+        for ip in ips {
+            remove_ip(&mut self.peer_ips, &ip, peer_id);
+        }
+    }
+
+    /// Records that a peer joined our mesh for a topic.
+    pub(crate) fn graft(&mut self, peer_id: &PeerId, topic_hash: &TopicHash) {
+        if let Some(stats) = self.peer_stats.get_mut(peer_id) {
+            let topic_stats = stats.topics.entry(topic_hash.clone()).or_insert_with(TopicStats::default);
+            topic_stats.graft_time = Some(Instant::now());
+            topic_stats.mesh_message_deliveries_active = false;
+        }
+    }
+
+    /// Records that a peer left our mesh for a topic.
+    pub(crate) fn prune(&mut self, peer_id: &PeerId, topic_hash: &TopicHash) {
+        if let Some(stats) = self.peer_stats.get_mut(peer_id) {
+            if let Some(topic_stats) = stats.topics.get_mut(topic_hash) {
+                topic_stats.graft_time = None;
+                if let Some(params) = self.params.topics.get(topic_hash) {
+                    apply_mesh_failure_penalty(topic_stats, params);
+                }
+                topic_stats.mesh_message_deliveries_active = false;
+            }
+        }
+    }
+
+    /// Records the first delivery of a message.
+    pub(crate) fn deliver_message(&mut self, peer_id: &PeerId, id: &MessageId, topics: &[TopicHash]) {
+        let mut peers = HashSet::new();
+        peers.insert(peer_id.clone());
+        self.deliveries.insert(id.clone(), DeliveryRecord {
+            first_seen: Instant::now(),
+            peers,
+        });
+
+        if let Some(stats) = self.peer_stats.get_mut(peer_id) {
+            for topic_hash in topics {
+                let params = match self.params.topics.get(topic_hash) {
+                    Some(params) => params,
+                    None => continue,
+                };
+                let topic_stats = stats.topics.entry(topic_hash.clone()).or_insert_with(TopicStats::default);
+                topic_stats.first_message_deliveries =
+                    (topic_stats.first_message_deliveries + 1.0).min(params.first_message_deliveries_cap);
+                if topic_stats.graft_time.is_some() {
+                    topic_stats.mesh_message_deliveries =
+                        (topic_stats.mesh_message_deliveries + 1.0).min(params.mesh_message_deliveries_cap);
+                }
+            }
+        }
+    }
+
+    /// Records the delivery of a message we had already received.
+    ///
+    /// Mesh peers that deliver it shortly after the first delivery are still credited, as they
+    /// likely forwarded it at the same time.
+    pub(crate) fn duplicate_message(&mut self, peer_id: &PeerId, id: &MessageId, topics: &[TopicHash]) {
+        let record = match self.deliveries.get_mut(id) {
+            Some(record) => record,
+            None => return,
+        };
+        if !record.peers.insert(peer_id.clone()) {
+            return;
+        }
+        let elapsed = Instant::now() - record.first_seen;
+
+        if let Some(stats) = self.peer_stats.get_mut(peer_id) {
+            for topic_hash in topics {
+                let params = match self.params.topics.get(topic_hash) {
+                    Some(params) => params,
+                    None => continue,
+                };
+                if elapsed > params.mesh_message_deliveries_window {
+                    continue;
+                }
+                if let Some(topic_stats) = stats.topics.get_mut(topic_hash) {
+                    if topic_stats.graft_time.is_some() {
+                        topic_stats.mesh_message_deliveries =
+                            (topic_stats.mesh_message_deliveries + 1.0).min(params.mesh_message_deliveries_cap);
+                    }
+                }
+            }
+        }
+    }
+
+    /// Records the delivery of a message that the application considers invalid.
+    pub(crate) fn reject_message(&mut self, peer_id: &PeerId, topics: &[TopicHash]) {
+        if let Some(stats) = self.peer_stats.get_mut(peer_id) {
+            for topic_hash in topics {
+                if !self.params.topics.contains_key(topic_hash) {
+                    continue;
+                }
+                let topic_stats = stats.topics.entry(topic_hash.clone()).or_insert_with(TopicStats::default);
+                topic_stats.invalid_message_deliveries += 1.0;
+            }
+        }
+    }
+
+    /// Applies behaviour penalties to a peer.
+    pub(crate) fn add_penalty(&mut self, peer_id: &PeerId, count: usize) {
+        if let Some(stats) = self.peer_stats.get_mut(peer_id) {
+            stats.behaviour_penalty += count as f64;
+        }
+    }
+
+    /// Sets the application-specific score of a peer.
+    pub(crate) fn set_application_score(&mut self, peer_id: &PeerId, score: f64) -> bool {
+        match self.peer_stats.get_mut(peer_id) {
+            Some(stats) => {
+                stats.application_score = score;
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Decays the counters if the decay interval has elapsed, and forgets the disconnected
+    /// peers whose score has expired.
+    pub(crate) fn refresh_scores(&mut self) {
+        let now = Instant::now();
+        if now < self.last_decay + self.params.decay_interval {
+            return;
+        }
+        self.last_decay = now;
+
+        let expired: Vec<PeerId> = self.peer_stats.iter()
+            .filter(|(_, stats)| stats.expires_at.map_or(false, |at| at <= now))
+            .map(|(peer_id, _)| peer_id.clone())
+            .collect();
+        for peer_id in expired {
+            self.forget_peer(&peer_id);
+        }
+
+        let decay_to_zero = self.params.decay_to_zero;
+        let decay = |value: f64, factor: f64| {
+            let value = value * factor;
+            if value < decay_to_zero { 0.0 } else { value }
+        };
+
+        for stats in self.peer_stats.values_mut() {
+            for (topic_hash, topic_stats) in stats.topics.iter_mut() {
+                let params = match self.params.topics.get(topic_hash) {
+                    Some(params) => params,
+                    None => continue,
+                };
+                topic_stats.first_message_deliveries =
+                    decay(topic_stats.first_message_deliveries, params.first_message_deliveries_decay);
+                topic_stats.mesh_message_deliveries =
+                    decay(topic_stats.mesh_message_deliveries, params.mesh_message_deliveries_decay);
+                topic_stats.mesh_failure_penalty =
+                    decay(topic_stats.mesh_failure_penalty, params.mesh_failure_penalty_decay);
+                topic_stats.invalid_message_deliveries =
+                    decay(topic_stats.invalid_message_deliveries, params.invalid_message_deliveries_decay);
+                if let Some(graft_time) = topic_stats.graft_time {
+                    if now - graft_time > params.mesh_message_deliveries_activation {
+                        topic_stats.mesh_message_deliveries_active = true;
+                    }
+                }
+            }
+            stats.behaviour_penalty = decay(stats.behaviour_penalty, self.params.behaviour_penalty_decay);
+        }
+
+        self.deliveries.retain(|_, record| now - record.first_seen < DELIVERY_RECORD_TTL);
+    }
+
+    /// Removes all the counters of a peer.
+    fn forget_peer(&mut self, peer_id: &PeerId) {
+        if let Some(stats) = self.peer_stats.remove(peer_id) {
+            for ip in stats.ips {
+                remove_ip(&mut self.peer_ips, &ip, peer_id);
+            }
+        }
+    }
+}
+
+/// Applies P3b if the peer leaves the mesh while having a deficit of mesh message deliveries.
+fn apply_mesh_failure_penalty(topic_stats: &mut TopicStats, params: &TopicScoreParams) {
+    if topic_stats.mesh_message_deliveries_active
+        && topic_stats.mesh_message_deliveries < params.mesh_message_deliveries_threshold
+    {
+        let deficit = params.mesh_message_deliveries_threshold - topic_stats.mesh_message_deliveries;
+        topic_stats.mesh_failure_penalty += deficit * deficit;
+    }
+}
+
+fn remove_ip(peer_ips: &mut HashMap<IpAddr, HashSet<PeerId>>, ip: &IpAddr, peer_id: &PeerId) {
+    if let Some(peers) = peer_ips.get_mut(ip) {
+        peers.remove(peer_id);
+        if peers.is_empty() {
+            peer_ips.remove(ip);
+        }
+    }
+}
+
+/// Returns the IP address of the remote of a connection, if any.
+fn ip_of_endpoint(endpoint: &ConnectedPoint) -> Option<IpAddr> {
+    let address = match endpoint {
+        ConnectedPoint::Dialer { address } => address,
+        ConnectedPoint::Listener { send_back_addr, .. } => send_back_addr,
+    };
+    address.iter().find_map(|protocol| match protocol {
+        Protocol::Ip4(ip) => Some(IpAddr::V4(ip)),
+        Protocol::Ip6(ip) => Some(IpAddr::V6(ip)),
+        _ => None,
+    })
+}
+
+fn duration_to_f64(duration: Duration) -> f64 {
+    duration.as_secs() as f64 + f64::from(duration.subsec_nanos()) / 1_000_000_000.0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn endpoint(ip: &str) -> ConnectedPoint {
+        ConnectedPoint::Dialer { address: format!("/ip4/{}/tcp/1234", ip).parse().unwrap() }
+    }
+
+    #[test]
+    fn first_deliveries_increase_the_score() {
+        let topic = TopicHash::from_raw("test");
+        let mut params = PeerScoreParams::default();
+        params.topics.insert(topic.clone(), TopicScoreParams::default());
+        let mut score = PeerScore::new(params);
+
+        let peer_id = PeerId::random();
+        score.add_peer(peer_id.clone(), &endpoint("1.2.3.4"));
+        assert_eq!(score.score(&peer_id), 0.0);
+
+        score.deliver_message(&peer_id, &MessageId::new(vec![1]), &[topic.clone()]);
+        score.deliver_message(&peer_id, &MessageId::new(vec![2]), &[topic]);
+        assert_eq!(score.score(&peer_id), 2.0 * 0.5);
+    }
+
+    #[test]
+    fn colocated_peers_are_penalized() {
+        let mut params = PeerScoreParams::default();
+        params.ip_colocation_factor_threshold = 1.0;
+        let mut score = PeerScore::new(params);
+
+        let peers: Vec<PeerId> = (0..3).map(|_| PeerId::random()).collect();
+        for peer_id in &peers {
+            score.add_peer(peer_id.clone(), &endpoint("1.2.3.4"));
+        }
+        let other = PeerId::random();
+        score.add_peer(other.clone(), &endpoint("5.6.7.8"));
+
+        assert_eq!(score.score(&peers[0]), 2.0 * 2.0 * -5.0);
+        assert_eq!(score.score(&other), 0.0);
+    }
+
+    #[test]
+    fn negative_score_survives_reconnection() {
+        let mut score = PeerScore::new(PeerScoreParams::default());
+        let peer_id = PeerId::random();
+        score.add_peer(peer_id.clone(), &endpoint("1.2.3.4"));
+        score.add_penalty(&peer_id, 1);
+        assert_eq!(score.score(&peer_id), -10.0);
+
+        score.remove_peer(&peer_id);
+        score.add_peer(peer_id.clone(), &endpoint("1.2.3.4"));
+        assert_eq!(score.score(&peer_id), -10.0);
+    }
+}
@@ -0,0 +1,639 @@
+// Copyright 2019 Parity Technologies (UK) Ltd.
+//
+// Permission is hereby granted, free of charge, to any person obtaining a
+// copy of this software and associated documentation files (the "Software"),
+// to deal in the Software without restriction, including without limitation
+// the rights to use, copy, modify, merge, publish, distribute, sublicense,
+// and/or sell copies of the Software, and to permit persons to whom the
+// Software is furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS
+// OR IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING
+// FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
+// DEALINGS IN THE SOFTWARE.
+
+use crate::rpc_proto;
+use crate::topic::TopicHash;
+use bytes::BytesMut;
+use futures::future;
+use libp2p_core::{
+    identity::{Keypair, PublicKey, error::SigningError},
+    InboundUpgrade,
+    OutboundUpgrade,
+    UpgradeInfo,
+    PeerId,
+    SignedEnvelope,
+    upgrade::Negotiated
+};
+use prost::Message;
+use std::{borrow::Cow, error, fmt, io, vec};
+use tokio_codec::{Decoder, Encoder, Framed};
+use tokio_io::{AsyncRead, AsyncWrite};
+use unsigned_varint::codec::UviBytes;
+
+/// Implementation of the `ConnectionUpgrade` for the gossipsub protocol.
+///
+/// The upgrade produces a long-lived substream on which RPCs are sent or received, each of them
+/// prefixed with its length.
+#[derive(Debug, Clone)]
+pub struct ProtocolConfig {
+    protocol_ids: Vec<Cow<'static, [u8]>>,
+    max_transmit_size: usize,
+}
+
+impl ProtocolConfig {
+    /// Builds a new `ProtocolConfig`.
+    ///
+    /// The protocol names are given by order of preference. `max_transmit_size` is the maximum
+    /// size of a single RPC, sent or received.
+    pub fn new(protocol_ids: Vec<Cow<'static, [u8]>>, max_transmit_size: usize) -> ProtocolConfig {
+        ProtocolConfig {
+            protocol_ids,
+            max_transmit_size,
+        }
+    }
+}
+
+impl UpgradeInfo for ProtocolConfig {
+    type Info = Cow<'static, [u8]>;
+    type InfoIter = vec::IntoIter<Self::Info>;
+
+    #[inline]
+    fn protocol_info(&self) -> Self::InfoIter {
+        self.protocol_ids.clone().into_iter()
+    }
+}
+
+impl<TSocket> InboundUpgrade<TSocket> for ProtocolConfig
+where
+    TSocket: AsyncRead + AsyncWrite,
+{
+    type Output = Framed<Negotiated<TSocket>, GossipsubCodec>;
+    type Error = io::Error;
+    type Future = future::FutureResult<Self::Output, io::Error>;
+
+    #[inline]
+    fn upgrade_inbound(self, socket: Negotiated<TSocket>, _: Self::Info) -> Self::Future {
+        future::ok(Framed::new(socket, GossipsubCodec::new(self.max_transmit_size)))
+    }
+}
+
+impl<TSocket> OutboundUpgrade<TSocket> for ProtocolConfig
+where
+    TSocket: AsyncRead + AsyncWrite,
+{
+    type Output = Framed<Negotiated<TSocket>, GossipsubCodec>;
+    type Error = io::Error;
+    type Future = future::FutureResult<Self::Output, io::Error>;
+
+    #[inline]
+    fn upgrade_outbound(self, socket: Negotiated<TSocket>, _: Self::Info) -> Self::Future {
+        future::ok(Framed::new(socket, GossipsubCodec::new(self.max_transmit_size)))
+    }
+}
+
+/// Codec turning the frames of a gossipsub substream into `GossipsubRpc`s.
+pub struct GossipsubCodec {
+    /// Codec handling the length prefix of the frames.
+    length_codec: UviBytes<Vec<u8>>,
+}
+
+impl GossipsubCodec {
+    fn new(max_transmit_size: usize) -> GossipsubCodec {
+        let mut length_codec = UviBytes::default();
+        length_codec.set_max_len(max_transmit_size);
+        GossipsubCodec { length_codec }
+    }
+}
+
+impl Encoder for GossipsubCodec {
+    type Item = GossipsubRpc;
+    type Error = io::Error;
+
+    fn encode(&mut self, item: GossipsubRpc, dst: &mut BytesMut) -> Result<(), io::Error> {
+        self.length_codec.encode(item.into_bytes(), dst)
+    }
+}
+
+impl Decoder for GossipsubCodec {
+    type Item = GossipsubRpc;
+    type Error = io::Error;
+
+    fn decode(&mut self, src: &mut BytesMut) -> Result<Option<GossipsubRpc>, io::Error> {
+        let packet = match self.length_codec.decode(src)? {
+            Some(packet) => packet,
+            None => return Ok(None),
+        };
+
+        GossipsubRpc::from_bytes(&packet)
+            .map(Some)
+            .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))
+    }
+}
+
+/// Error while decoding a `GossipsubRpc`.
+#[derive(Debug)]
+pub enum GossipsubDecodeError {
+    /// Error when decoding the raw buffer into a protobuf.
+    ProtobufError(prost::DecodeError),
+    /// Error when parsing the `PeerId` in the message.
+    InvalidPeerId,
+}
+
+impl From<prost::DecodeError> for GossipsubDecodeError {
+    #[inline]
+    fn from(err: prost::DecodeError) -> Self {
+        GossipsubDecodeError::ProtobufError(err)
+    }
+}
+
+impl fmt::Display for GossipsubDecodeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match *self {
+            GossipsubDecodeError::ProtobufError(ref err) =>
+                write!(f, "Error while decoding protobuf: {}", err),
+            GossipsubDecodeError::InvalidPeerId =>
+                write!(f, "Error while decoding PeerId from message"),
+        }
+    }
+}
+
+impl error::Error for GossipsubDecodeError {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        match *self {
+            GossipsubDecodeError::ProtobufError(ref err) => Some(err),
+            GossipsubDecodeError::InvalidPeerId => None,
+        }
+    }
+}
+
+/// An RPC received or sent by the gossipsub system.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GossipsubRpc {
+    /// List of messages that were part of this RPC query.
+    pub messages: Vec<GossipsubMessage>,
+    /// List of subscriptions.
+    pub subscriptions: Vec<GossipsubSubscription>,
+    /// List of control messages.
+    pub control_msgs: Vec<GossipsubControlAction>,
+}
+
+impl GossipsubRpc {
+    /// Turns this `GossipsubRpc` into a message that can be sent to a substream.
+    fn into_bytes(self) -> Vec<u8> {
+        let mut proto = rpc_proto::Rpc {
+            subscriptions: self.subscriptions
+                .into_iter()
+                .map(|subscription| rpc_proto::rpc::SubOpts {
+                    subscribe: Some(subscription.action == GossipsubSubscriptionAction::Subscribe),
+                    topic_id: Some(subscription.topic.into_string()),
+                })
+                .collect(),
+            publish: self.messages
+                .into_iter()
+                .map(GossipsubMessage::into_proto)
+                .collect(),
+            control: None,
+        };
+
+        if !self.control_msgs.is_empty() {
+            let mut control = rpc_proto::ControlMessage::default();
+            for action in self.control_msgs {
+                match action {
+                    GossipsubControlAction::IHave { topic_hash, message_ids } => {
+                        control.ihave.push(rpc_proto::ControlIHave {
+                            topic_id: Some(topic_hash.into_string()),
+                            message_ids: message_ids.into_iter().map(MessageId::into_bytes).collect(),
+                        });
+                    }
+                    GossipsubControlAction::IWant { message_ids } => {
+                        control.iwant.push(rpc_proto::ControlIWant {
+                            message_ids: message_ids.into_iter().map(MessageId::into_bytes).collect(),
+                        });
+                    }
+                    GossipsubControlAction::Graft { topic_hash } => {
+                        control.graft.push(rpc_proto::ControlGraft {
+                            topic_id: Some(topic_hash.into_string()),
+                        });
+                    }
+                    GossipsubControlAction::Prune { topic_hash, peers, backoff } => {
+                        control.prune.push(rpc_proto::ControlPrune {
+                            topic_id: Some(topic_hash.into_string()),
+                            peers: peers
+                                .into_iter()
+                                .map(|peer| rpc_proto::PeerInfo {
+                                    peer_id: peer.peer_id.map(PeerId::into_bytes),
+                                    signed_peer_record: peer.signed_peer_record
+                                        .map(SignedEnvelope::into_protobuf_encoding),
+                                })
+                                .collect(),
+                            backoff,
+                        });
+                    }
+                }
+            }
+            proto.control = Some(control);
+        }
+
+        let mut buf = Vec::with_capacity(proto.encoded_len());
+        proto.encode(&mut buf).expect("Vec<u8> provides capacity as needed");
+        buf
+    }
+
+    /// Decodes a `GossipsubRpc` received on a substream.
+    fn from_bytes(bytes: &[u8]) -> Result<GossipsubRpc, GossipsubDecodeError> {
+        let rpc = rpc_proto::Rpc::decode(bytes)?;
+
+        let mut messages = Vec::with_capacity(rpc.publish.len());
+        for message in rpc.publish {
+            messages.push(GossipsubMessage::from_proto(message)?);
+        }
+
+        let subscriptions = rpc.subscriptions
+            .into_iter()
+            .map(|sub| GossipsubSubscription {
+                action: if sub.subscribe.unwrap_or(false) {
+                    GossipsubSubscriptionAction::Subscribe
+                } else {
+                    GossipsubSubscriptionAction::Unsubscribe
+                },
+                topic: TopicHash::from_raw(sub.topic_id.unwrap_or_default()),
+            })
+            .collect();
+
+        let mut control_msgs = Vec::new();
+        if let Some(control) = rpc.control {
+            control_msgs.extend(control.ihave.into_iter().map(|ihave| GossipsubControlAction::IHave {
+                topic_hash: TopicHash::from_raw(ihave.topic_id.unwrap_or_default()),
+                message_ids: ihave.message_ids.into_iter().map(MessageId::new).collect(),
+            }));
+            control_msgs.extend(control.iwant.into_iter().map(|iwant| GossipsubControlAction::IWant {
+                message_ids: iwant.message_ids.into_iter().map(MessageId::new).collect(),
+            }));
+            control_msgs.extend(control.graft.into_iter().map(|graft| GossipsubControlAction::Graft {
+                topic_hash: TopicHash::from_raw(graft.topic_id.unwrap_or_default()),
+            }));
+            for prune in control.prune {
+                let mut peers = Vec::with_capacity(prune.peers.len());
+                for peer in prune.peers {
+                    let peer_id = match peer.peer_id {
+                        Some(bytes) => Some(PeerId::from_bytes(bytes)
+                            .map_err(|_| GossipsubDecodeError::InvalidPeerId)?),
+                        None => None,
+                    };
+                    peers.push(PeerInfo {
+                        peer_id,
+                        // An invalid record is ignored rather than failing the whole RPC.
+                        signed_peer_record: peer.signed_peer_record
+                            .and_then(|record| SignedEnvelope::from_protobuf_encoding(&record).ok()),
+                    });
+                }
+                control_msgs.push(GossipsubControlAction::Prune {
+                    topic_hash: TopicHash::from_raw(prune.topic_id.unwrap_or_default()),
+                    peers,
+                    backoff: prune.backoff,
+                });
+            }
+        }
+
+        Ok(GossipsubRpc {
+            messages,
+            subscriptions,
+            control_msgs,
+        })
+    }
+}
+
+/// Identifier of a message, used to detect duplicates and in the gossip control messages.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct MessageId(Vec<u8>);
+
+impl MessageId {
+    /// Builds a `MessageId` from raw bytes.
+    #[inline]
+    pub fn new(bytes: impl Into<Vec<u8>>) -> MessageId {
+        MessageId(bytes.into())
+    }
+
+    /// Returns the raw bytes of the identifier.
+    #[inline]
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.0
+    }
+
+    /// Turns the identifier into its raw bytes.
+    #[inline]
+    pub fn into_bytes(self) -> Vec<u8> {
+        self.0
+    }
+}
+
+impl AsRef<[u8]> for MessageId {
+    fn as_ref(&self) -> &[u8] {
+        &self.0
+    }
+}
+
+/// A message received by the gossipsub system.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct GossipsubMessage {
+    /// Id of the peer that published this message.
+    pub source: PeerId,
+
+    /// Content of the message. Its meaning is out of scope of this library.
+    pub data: Vec<u8>,
+
+    /// A random sequence number.
+    pub sequence_number: Vec<u8>,
+
+    /// List of topics this message belongs to.
+    ///
+    /// Each message can belong to multiple topics at once.
+    pub topics: Vec<TopicHash>,
+
+    /// Signature of the message by its source, if any.
+    pub signature: Option<Vec<u8>>,
+
+    /// Protobuf encoding of the public key of the source, if it can't be extracted from its
+    /// peer ID.
+    pub key: Option<Vec<u8>>,
+}
+
+/// Prefix of the bytes covered by the signature of a message.
+const SIGNING_PREFIX: &[u8] = b"libp2p-pubsub:";
+
+impl GossipsubMessage {
+    /// Turns the message into its protobuf representation.
+    ///
+    /// An empty sequence number is left out rather than encoded as an empty field, as publishers
+    /// that don't set one omit the field, and the encoding covered by their signature must be
+    /// reproduced exactly.
+    fn into_proto(self) -> rpc_proto::Message {
+        rpc_proto::Message {
+            from: Some(self.source.into_bytes()),
+            data: Some(self.data),
+            seqno: Some(self.sequence_number).filter(|seqno| !seqno.is_empty()),
+            topic_ids: self.topics
+                .into_iter()
+                .map(TopicHash::into_string)
+                .collect(),
+            signature: self.signature,
+            key: self.key,
+        }
+    }
+
+    /// Builds a message from its protobuf representation.
+    fn from_proto(message: rpc_proto::Message) -> Result<GossipsubMessage, GossipsubDecodeError> {
+        let source = message.from.ok_or(GossipsubDecodeError::InvalidPeerId)?;
+        Ok(GossipsubMessage {
+            source: PeerId::from_bytes(source).map_err(|_| GossipsubDecodeError::InvalidPeerId)?,
+            data: message.data.unwrap_or_default(),
+            sequence_number: message.seqno.unwrap_or_default(),
+            topics: message.topic_ids
+                .into_iter()
+                .map(TopicHash::from_raw)
+                .collect(),
+            signature: message.signature,
+            key: message.key,
+        })
+    }
+
+    /// Returns the bytes covered by the signature, i.e. the prefix followed by the encoding of
+    /// the message without its signature and key.
+    fn signable_bytes(&self) -> Vec<u8> {
+        let mut proto = self.clone().into_proto();
+        proto.signature = None;
+        proto.key = None;
+
+        let mut bytes = SIGNING_PREFIX.to_vec();
+        bytes.reserve(proto.encoded_len());
+        proto.encode(&mut bytes).expect("Vec<u8> provides capacity as needed");
+        bytes
+    }
+
+    /// Signs the message with the key of its source.
+    ///
+    /// The public key is only attached to the message if it isn't inlined in the peer ID of the
+    /// source.
+    pub(crate) fn sign(&mut self, keypair: &Keypair) -> Result<(), SigningError> {
+        self.signature = Some(keypair.sign(&self.signable_bytes())?);
+
+        let key = keypair.public().into_protobuf_encoding();
+        self.key = if self.source.digest() == &key[..] { None } else { Some(key) };
+        Ok(())
+    }
+
+    /// Returns true if the message carries a valid signature of its source.
+    pub(crate) fn verify_signature(&self) -> bool {
+        let signature = match &self.signature {
+            Some(signature) => signature,
+            None => return false,
+        };
+        let key = match &self.key {
+            Some(key) => PublicKey::from_protobuf_encoding(key),
+            None => PublicKey::from_protobuf_encoding(self.source.digest()),
+        };
+        let key = match key {
+            Ok(key) => key,
+            Err(_) => return false,
+        };
+        if self.source.is_public_key(&key) != Some(true) {
+            return false;
+        }
+
+        key.verify(&self.signable_bytes(), signature)
+    }
+}
+
+/// A subscription received by the gossipsub system.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct GossipsubSubscription {
+    /// Action to perform.
+    pub action: GossipsubSubscriptionAction,
+    /// The topic from which to subscribe or unsubscribe.
+    pub topic: TopicHash,
+}
+
+/// Action that a subscription wants to perform.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum GossipsubSubscriptionAction {
+    /// The remote wants to subscribe to the given topic.
+    Subscribe,
+    /// The remote wants to unsubscribe from the given topic.
+    Unsubscribe,
+}
+
+/// A control message, used to maintain the mesh and to gossip about messages.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum GossipsubControlAction {
+    /// The node has recently seen messages on a topic and can send them on request.
+    IHave {
+        /// The topic of the messages.
+        topic_hash: TopicHash,
+        /// The identifiers of the messages.
+        message_ids: Vec<MessageId>,
+    },
+    /// The node requests the messages with the given identifiers.
+    IWant {
+        /// The identifiers of the requested messages.
+        message_ids: Vec<MessageId>,
+    },
+    /// The node has added the remote to its mesh for the topic.
+    Graft {
+        /// The topic of the mesh.
+        topic_hash: TopicHash,
+    },
+    /// The node has removed the remote from its mesh for the topic.
+    Prune {
+        /// The topic of the mesh.
+        topic_hash: TopicHash,
+        /// Other peers of the topic that the remote can connect to instead.
+        peers: Vec<PeerInfo>,
+        /// Time in seconds during which the remote must not graft us again.
+        backoff: Option<u64>,
+    },
+}
+
+/// A peer exchanged in a `PRUNE`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PeerInfo {
+    /// The identity of the peer.
+    pub peer_id: Option<PeerId>,
+    /// The signed peer record of the peer, containing its addresses.
+    pub signed_peer_record: Option<SignedEnvelope>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rpc_roundtrip() {
+        let rpc = GossipsubRpc {
+            messages: vec![GossipsubMessage {
+                source: PeerId::random(),
+                data: b"hello".to_vec(),
+                sequence_number: vec![0, 0, 0, 0, 0, 0, 0, 1],
+                topics: vec![TopicHash::from_raw("a"), TopicHash::from_raw("b")],
+                signature: Some(vec![1, 2, 3]),
+                key: None,
+            }],
+            subscriptions: vec![
+                GossipsubSubscription {
+                    action: GossipsubSubscriptionAction::Subscribe,
+                    topic: TopicHash::from_raw("a"),
+                },
+                GossipsubSubscription {
+                    action: GossipsubSubscriptionAction::Unsubscribe,
+                    topic: TopicHash::from_raw("c"),
+                },
+            ],
+            control_msgs: vec![
+                GossipsubControlAction::IHave {
+                    topic_hash: TopicHash::from_raw("a"),
+                    message_ids: vec![MessageId::new(vec![1, 2]), MessageId::new(vec![0xff])],
+                },
+                GossipsubControlAction::IWant {
+                    message_ids: vec![MessageId::new(vec![3])],
+                },
+                GossipsubControlAction::Graft { topic_hash: TopicHash::from_raw("a") },
+                GossipsubControlAction::Prune {
+                    topic_hash: TopicHash::from_raw("b"),
+                    peers: vec![PeerInfo { peer_id: Some(PeerId::random()), signed_peer_record: None }],
+                    backoff: Some(60),
+                },
+            ],
+        };
+
+        let decoded = GossipsubRpc::from_bytes(&rpc.clone().into_bytes()).unwrap();
+        assert_eq!(decoded, rpc);
+    }
+
+    #[test]
+    fn message_without_source_is_rejected() {
+        let rpc = rpc_proto::Rpc {
+            publish: vec![rpc_proto::Message {
+                data: Some(b"data".to_vec()),
+                ..Default::default()
+            }],
+            ..Default::default()
+        };
+        let mut bytes = Vec::new();
+        rpc.encode(&mut bytes).unwrap();
+
+        match GossipsubRpc::from_bytes(&bytes) {
+            Err(GossipsubDecodeError::InvalidPeerId) => {}
+            other => panic!("unexpected result: {:?}", other),
+        }
+    }
+
+    fn message(source: PeerId) -> GossipsubMessage {
+        GossipsubMessage {
+            source,
+            data: b"hello".to_vec(),
+            sequence_number: vec![0, 0, 0, 0, 0, 0, 0, 1],
+            topics: vec![TopicHash::from_raw("a")],
+            signature: None,
+            key: None,
+        }
+    }
+
+    #[test]
+    fn signature_verified() {
+        let keypair = Keypair::generate_ed25519();
+        let mut message = message(keypair.public().into_peer_id());
+        assert!(!message.verify_signature());
+
+        message.sign(&keypair).unwrap();
+        assert!(message.key.is_none());
+        let decoded = GossipsubRpc::from_bytes(&GossipsubRpc {
+            messages: vec![message.clone()],
+            subscriptions: Vec::new(),
+            control_msgs: Vec::new(),
+        }.into_bytes()).unwrap();
+        assert_eq!(decoded.messages, vec![message.clone()]);
+        assert!(decoded.messages[0].verify_signature());
+
+        message.data = b"tampered".to_vec();
+        assert!(!message.verify_signature());
+    }
+
+    #[test]
+    fn signature_without_sequence_number_verified() {
+        // A publisher that doesn't set a sequence number signs an encoding without the field.
+        let keypair = Keypair::generate_ed25519();
+        let proto = rpc_proto::Message {
+            from: Some(keypair.public().into_peer_id().into_bytes()),
+            data: Some(b"hello".to_vec()),
+            seqno: None,
+            topic_ids: vec!["a".to_owned()],
+            signature: None,
+            key: None,
+        };
+        let mut signed = SIGNING_PREFIX.to_vec();
+        proto.encode(&mut signed).unwrap();
+        let signature = keypair.sign(&signed).unwrap();
+
+        let message = GossipsubMessage::from_proto(rpc_proto::Message {
+            signature: Some(signature),
+            ..proto
+        }).unwrap();
+        assert!(message.sequence_number.is_empty());
+        assert!(message.verify_signature());
+    }
+
+    #[test]
+    fn signature_of_other_peer_rejected() {
+        let keypair = Keypair::generate_ed25519();
+        let mut message = message(PeerId::random());
+        message.sign(&keypair).unwrap();
+        assert!(message.key.is_some());
+        assert!(!message.verify_signature());
+    }
+}
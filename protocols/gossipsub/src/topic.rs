@@ -0,0 +1,104 @@
+// Copyright 2019 Parity Technologies (UK) Ltd.
+//
+// Permission is hereby granted, free of charge, to any person obtaining a
+// copy of this software and associated documentation files (the "Software"),
+// to deal in the Software without restriction, including without limitation
+// the rights to use, copy, modify, merge, publish, distribute, sublicense,
+// and/or sell copies of the Software, and to permit persons to whom the
+// Software is furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS
+// OR IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING
+// FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
+// DEALINGS IN THE SOFTWARE.
+
+use std::fmt;
+
+/// Represents the hash of a topic.
+///
+/// The API of gossipsub identifies topics by their hash. Contrary to floodsub, the hash of a
+/// topic is its name, as this is what other gossipsub implementations put on the wire.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct TopicHash {
+    hash: String,
+}
+
+impl TopicHash {
+    /// Builds a new `TopicHash` from the given hash.
+    #[inline]
+    pub fn from_raw(hash: impl Into<String>) -> TopicHash {
+        TopicHash { hash: hash.into() }
+    }
+
+    #[inline]
+    pub fn into_string(self) -> String {
+        self.hash
+    }
+
+    #[inline]
+    pub fn as_str(&self) -> &str {
+        &self.hash
+    }
+}
+
+impl fmt::Display for TopicHash {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.hash)
+    }
+}
+
+/// A gossipsub topic.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Topic {
+    hash: TopicHash,
+}
+
+impl Topic {
+    /// Builds the topic with the given name.
+    #[inline]
+    pub fn new(name: impl Into<String>) -> Topic {
+        Topic {
+            hash: TopicHash::from_raw(name),
+        }
+    }
+
+    /// Returns the hash of the topic.
+    #[inline]
+    pub fn hash(&self) -> &TopicHash {
+        &self.hash
+    }
+}
+
+impl AsRef<TopicHash> for Topic {
+    #[inline]
+    fn as_ref(&self) -> &TopicHash {
+        &self.hash
+    }
+}
+
+impl AsRef<TopicHash> for TopicHash {
+    #[inline]
+    fn as_ref(&self) -> &TopicHash {
+        self
+    }
+}
+
+impl From<Topic> for TopicHash {
+    #[inline]
+    fn from(topic: Topic) -> TopicHash {
+        topic.hash
+    }
+}
+
+impl<'a> From<&'a Topic> for TopicHash {
+    #[inline]
+    fn from(topic: &'a Topic) -> TopicHash {
+        topic.hash.clone()
+    }
+}
@@ -0,0 +1,497 @@
+// Copyright 2019 Parity Technologies (UK) Ltd.
+//
+// Permission is hereby granted, free of charge, to any person obtaining a
+// copy of this software and associated documentation files (the "Software"),
+// to deal in the Software without restriction, including without limitation
+// the rights to use, copy, modify, merge, publish, distribute, sublicense,
+// and/or sell copies of the Software, and to permit persons to whom the
+// Software is furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS
+// OR IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING
+// FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
+// DEALINGS IN THE SOFTWARE.
+use crate::protocol::{
+    Extensions,
+    GraphsyncConfig,
+    GraphsyncMessage,
+    GraphsyncRequest,
+    GraphsyncResponse,
+    RequestId,
+    ResponseStatus
+};
+use crate::store::{IpldStore, TraversalError};
+use fnv::{FnvHashMap, FnvHashSet};
+use futures::prelude::*;
+use libp2p_bitswap::cid::Cid;
+use libp2p_core::{ConnectedPoint, Multiaddr, PeerId};
+use libp2p_swarm::{
+    NetworkBehaviour,
+    NetworkBehaviourAction,
+    PollParameters,
+    ProtocolsHandler,
+    OneShotHandler,
+    SubstreamTimedOut
+};
+use log::debug;
+use smallvec::SmallVec;
+use std::{collections::VecDeque, marker::PhantomData, mem};
+use tokio_io::{AsyncRead, AsyncWrite};
+
+/// Maximum total size of the blocks sent in a single message. Larger responses are split over
+/// several messages.
+const MAX_BLOCKS_SIZE: usize = 1024 * 1024;
+
+/// Network behaviour that requests DAGs from remotes and serves DAGs from an `IpldStore`.
+pub struct Graphsync<TSubstream, TStore> {
+    /// Storage of the blocks, and interpreter of the selectors.
+    store: TStore,
+
+    /// Events that need to be yielded to the outside when polling.
+    events: VecDeque<NetworkBehaviourAction<GraphsyncMessage, GraphsyncEvent>>,
+
+    /// Identifier of the next request we send.
+    next_request_id: i32,
+
+    /// Peers we are connected to.
+    connected_peers: FnvHashSet<PeerId>,
+
+    /// Addresses of peers, added with `add_address`.
+    addresses: FnvHashMap<PeerId, SmallVec<[Multiaddr; 6]>>,
+
+    /// Messages to peers we are not connected to yet.
+    pending_messages: FnvHashMap<PeerId, SmallVec<[GraphsyncMessage; 4]>>,
+
+    /// Requests we sent and for which no terminal response has been received yet.
+    outbound_requests: FnvHashMap<PeerId, FnvHashSet<RequestId>>,
+
+    /// Marker to pin the generics.
+    marker: PhantomData<TSubstream>,
+}
+
+impl<TSubstream, TStore> Graphsync<TSubstream, TStore>
+where
+    TStore: IpldStore,
+{
+    /// Creates a `Graphsync` backed by the given store.
+    pub fn new(store: TStore) -> Self {
+        Graphsync {
+            store,
+            events: VecDeque::new(),
+            next_request_id: 0,
+            connected_peers: FnvHashSet::default(),
+            addresses: FnvHashMap::default(),
+            pending_messages: FnvHashMap::default(),
+            outbound_requests: FnvHashMap::default(),
+            marker: PhantomData,
+        }
+    }
+
+    /// Returns the store.
+    pub fn store(&self) -> &TStore {
+        &self.store
+    }
+
+    /// Returns the store.
+    pub fn store_mut(&mut self) -> &mut TStore {
+        &mut self.store
+    }
+
+    /// Adds a known address for a peer that can be used for dialing attempts by the `Swarm`.
+    pub fn add_address(&mut self, peer: &PeerId, address: Multiaddr) {
+        let addresses = self.addresses.entry(peer.clone()).or_default();
+        if !addresses.contains(&address) {
+            addresses.push(address);
+        }
+    }
+
+    /// Removes an address of a peer previously added with `add_address`.
+    pub fn remove_address(&mut self, peer: &PeerId, address: &Multiaddr) {
+        if let Some(addresses) = self.addresses.get_mut(peer) {
+            addresses.retain(|a| a != address);
+            if addresses.is_empty() {
+                self.addresses.remove(peer);
+            }
+        }
+    }
+
+    /// Asks `peer_id` for the blocks designated by `selector`, starting at `root`.
+    ///
+    /// The peer is dialed if we are not connected to it. The received blocks are passed to
+    /// `IpldStore::put`, and every response generates a `ResponseReceived` event.
+    pub fn request(&mut self, peer_id: PeerId, root: Cid, selector: Vec<u8>, extensions: Extensions) -> RequestId {
+        let id = RequestId(self.next_request_id);
+        self.next_request_id = self.next_request_id.wrapping_add(1);
+
+        let request = GraphsyncRequest::New { id, root, selector, extensions, priority: 1 };
+        self.outbound_requests.entry(peer_id.clone()).or_default().insert(id);
+        self.send(peer_id, GraphsyncMessage { requests: vec![request], ..GraphsyncMessage::default() });
+        id
+    }
+
+    /// Cancels an outstanding request.
+    pub fn cancel(&mut self, peer_id: &PeerId, id: RequestId) {
+        let removed = self.outbound_requests
+            .get_mut(peer_id)
+            .map_or(false, |requests| requests.remove(&id));
+        if removed {
+            let request = GraphsyncRequest::Cancel { id };
+            self.send(peer_id.clone(), GraphsyncMessage { requests: vec![request], ..GraphsyncMessage::default() });
+        }
+    }
+
+    /// Sends a message to a peer, dialing it if necessary.
+    fn send(&mut self, peer_id: PeerId, message: GraphsyncMessage) {
+        if self.connected_peers.contains(&peer_id) {
+            self.events.push_back(NetworkBehaviourAction::SendEvent { peer_id, event: message });
+        } else {
+            let pending = self.pending_messages.entry(peer_id.clone()).or_default();
+            if pending.is_empty() {
+                self.events.push_back(NetworkBehaviourAction::DialPeer { peer_id });
+            }
+            pending.push(message);
+        }
+    }
+
+    /// Answers a request from a remote.
+    fn answer(&mut self, peer_id: &PeerId, id: RequestId, root: &Cid, selector: &[u8], extensions: &Extensions) {
+        let traversal = if self.store.validate_request(peer_id, root, selector, extensions) {
+            self.store.traverse(root, selector)
+        } else {
+            Err(TraversalError::Other("request rejected".to_owned()))
+        };
+
+        let (blocks, status) = match traversal {
+            Ok(traversal) => {
+                let status = if traversal.complete {
+                    ResponseStatus::RequestCompletedFull
+                } else {
+                    ResponseStatus::RequestCompletedPartial
+                };
+                (traversal.blocks, status)
+            }
+            Err(TraversalError::NotFound) => (Vec::new(), ResponseStatus::RequestFailedContentNotFound),
+            Err(err) => {
+                debug!("Rejecting graphsync request {} from {:?}: {}", id, peer_id, err);
+                (Vec::new(), ResponseStatus::RequestRejected)
+            }
+        };
+
+        // Split the blocks over several messages, all but the last one carrying a partial
+        // response.
+        let mut current = Vec::new();
+        let mut current_size = 0;
+        for (cid, data) in blocks {
+            if !current.is_empty() && current_size + data.len() > MAX_BLOCKS_SIZE {
+                let blocks = mem::replace(&mut current, Vec::new());
+                current_size = 0;
+                let response = GraphsyncResponse { id, status: ResponseStatus::PartialResponse, extensions: Extensions::new() };
+                self.events.push_back(NetworkBehaviourAction::SendEvent {
+                    peer_id: peer_id.clone(),
+                    event: GraphsyncMessage { responses: vec![response], blocks, ..GraphsyncMessage::default() },
+                });
+            }
+            current_size += data.len();
+            current.push((cid, data));
+        }
+
+        let response = GraphsyncResponse { id, status, extensions: Extensions::new() };
+        self.events.push_back(NetworkBehaviourAction::SendEvent {
+            peer_id: peer_id.clone(),
+            event: GraphsyncMessage { responses: vec![response], blocks: current, ..GraphsyncMessage::default() },
+        });
+        self.events.push_back(NetworkBehaviourAction::GenerateEvent(GraphsyncEvent::RequestServed {
+            peer_id: peer_id.clone(),
+            request_id: id,
+            status,
+        }));
+    }
+
+    /// Reports the failure of all the outstanding requests to a peer.
+    fn fail_requests(&mut self, peer_id: &PeerId) {
+        if let Some(requests) = self.outbound_requests.remove(peer_id) {
+            for request_id in requests {
+                self.events.push_back(NetworkBehaviourAction::GenerateEvent(GraphsyncEvent::OutboundFailure {
+                    peer_id: peer_id.clone(),
+                    request_id,
+                }));
+            }
+        }
+    }
+}
+
+impl<TSubstream, TStore> NetworkBehaviour for Graphsync<TSubstream, TStore>
+where
+    TSubstream: AsyncRead + AsyncWrite,
+    TStore: IpldStore,
+{
+    type ProtocolsHandler = OneShotHandler<TSubstream, GraphsyncConfig, GraphsyncMessage, InnerMessage>;
+    type OutEvent = GraphsyncEvent;
+
+    fn new_handler(&mut self) -> Self::ProtocolsHandler {
+        Default::default()
+    }
+
+    fn addresses_of_peer(&mut self, peer_id: &PeerId) -> Vec<Multiaddr> {
+        self.addresses.get(peer_id).map(|a| a.to_vec()).unwrap_or_default()
+    }
+
+    fn inject_connected(&mut self, id: PeerId, _: ConnectedPoint) {
+        if let Some(pending) = self.pending_messages.remove(&id) {
+            for message in pending {
+                self.events.push_back(NetworkBehaviourAction::SendEvent {
+                    peer_id: id.clone(),
+                    event: message,
+                });
+            }
+        }
+
+        self.connected_peers.insert(id);
+    }
+
+    fn inject_disconnected(&mut self, id: &PeerId, _: ConnectedPoint) {
+        self.connected_peers.remove(id);
+        self.fail_requests(id);
+    }
+
+    fn inject_dial_failure(&mut self, peer_id: &PeerId) {
+        self.pending_messages.remove(peer_id);
+        self.fail_requests(peer_id);
+    }
+
+    fn inject_node_event(
+        &mut self,
+        source: PeerId,
+        event: InnerMessage,
+    ) {
+        // We ignore successful sends event.
+        let message = match event {
+            InnerMessage::Rx(message) => message,
+            InnerMessage::Sent | InnerMessage::TimedOut => return,
+        };
+
+        // Responses are computed at once, so cancellations and updates have nothing to act on.
+        for request in message.requests {
+            if let GraphsyncRequest::New { id, root, selector, extensions, .. } = request {
+                self.answer(&source, id, &root, &selector, &extensions);
+            }
+        }
+
+        // Blocks are only accepted from peers we have sent requests to. Their CIDs have been
+        // computed from their content while decoding.
+        let expecting = self.outbound_requests.get(&source).map_or(false, |r| !r.is_empty());
+        if expecting {
+            for (cid, data) in message.blocks {
+                self.store.put(cid.clone(), data);
+                self.events.push_back(NetworkBehaviourAction::GenerateEvent(GraphsyncEvent::BlockReceived {
+                    peer_id: source.clone(),
+                    cid,
+                }));
+            }
+        } else if !message.blocks.is_empty() {
+            debug!("Ignoring {} unrequested blocks from {:?}", message.blocks.len(), source);
+        }
+
+        for response in message.responses {
+            let known = match self.outbound_requests.get_mut(&source) {
+                Some(requests) if response.status.is_terminal() => requests.remove(&response.id),
+                Some(requests) => requests.contains(&response.id),
+                None => false,
+            };
+            if !known {
+                continue;
+            }
+
+            self.events.push_back(NetworkBehaviourAction::GenerateEvent(GraphsyncEvent::ResponseReceived {
+                peer_id: source.clone(),
+                request_id: response.id,
+                status: response.status,
+                extensions: response.extensions,
+            }));
+        }
+    }
+
+    fn poll(
+        &mut self,
+        _: &mut impl PollParameters,
+    ) -> Async<
+        NetworkBehaviourAction<
+            <Self::ProtocolsHandler as ProtocolsHandler>::InEvent,
+            Self::OutEvent,
+        >,
+    > {
+        if let Some(event) = self.events.pop_front() {
+            return Async::Ready(event);
+        }
+
+        Async::NotReady
+    }
+}
+
+/// Transmission between the `OneShotHandler` and the `Graphsync` behaviour.
+pub enum InnerMessage {
+    /// We received a message from a remote.
+    Rx(GraphsyncMessage),
+    /// We successfully sent a message.
+    Sent,
+    /// Sending a message took too long and the substream has been closed.
+    TimedOut,
+}
+
+impl From<GraphsyncMessage> for InnerMessage {
+    fn from(message: GraphsyncMessage) -> InnerMessage {
+        InnerMessage::Rx(message)
+    }
+}
+
+impl From<()> for InnerMessage {
+    fn from(_: ()) -> InnerMessage {
+        InnerMessage::Sent
+    }
+}
+
+impl From<SubstreamTimedOut> for InnerMessage {
+    fn from(_: SubstreamTimedOut) -> InnerMessage {
+        InnerMessage::TimedOut
+    }
+}
+
+/// Event that can happen on the graphsync behaviour.
+#[derive(Debug)]
+pub enum GraphsyncEvent {
+    /// A block has been received in response to one of our requests, and passed to the store.
+    BlockReceived {
+        /// Peer that sent the block.
+        peer_id: PeerId,
+        /// CID of the block.
+        cid: Cid,
+    },
+
+    /// A response to one of our requests has been received. The request is over if the status
+    /// is terminal.
+    ResponseReceived {
+        /// Peer that sent the response.
+        peer_id: PeerId,
+        /// Identifier of the request.
+        request_id: RequestId,
+        /// Status of the request.
+        status: ResponseStatus,
+        /// Extensions of the response.
+        extensions: Extensions,
+    },
+
+    /// The connection to the peer was lost, or couldn't be established, before the request
+    /// completed.
+    OutboundFailure {
+        /// Peer the request was sent to.
+        peer_id: PeerId,
+        /// Identifier of the request.
+        request_id: RequestId,
+    },
+
+    /// A request from a remote has been answered.
+    RequestServed {
+        /// Peer that sent the request.
+        peer_id: PeerId,
+        /// Identifier of the request, chosen by the remote.
+        request_id: RequestId,
+        /// Status sent to the remote.
+        status: ResponseStatus,
+    },
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::store::Traversal;
+    use libp2p_bitswap::cid::RAW;
+    use multihash::Hash;
+    use std::{collections::HashMap, io};
+
+    /// Store whose traversals only return the root.
+    #[derive(Default)]
+    struct RootOnlyStore(HashMap<Cid, Vec<u8>>);
+
+    impl IpldStore for RootOnlyStore {
+        fn traverse(&self, root: &Cid, _: &[u8]) -> Result<Traversal, TraversalError> {
+            let data = self.0.get(root).ok_or(TraversalError::NotFound)?;
+            Ok(Traversal { blocks: vec![(root.clone(), data.clone())], complete: true })
+        }
+
+        fn put(&mut self, cid: Cid, data: Vec<u8>) {
+            self.0.insert(cid, data);
+        }
+    }
+
+    type TestGraphsync = Graphsync<io::Cursor<Vec<u8>>, RootOnlyStore>;
+
+    fn endpoint() -> ConnectedPoint {
+        ConnectedPoint::Dialer { address: "/ip4/127.0.0.1/tcp/1".parse().unwrap() }
+    }
+
+    #[test]
+    fn serves_requests_from_store() {
+        let mut graphsync = TestGraphsync::new(RootOnlyStore::default());
+        let root = Cid::from_data(1, RAW, Hash::SHA2256, b"root").unwrap();
+        let missing = Cid::from_data(1, RAW, Hash::SHA2256, b"missing").unwrap();
+        graphsync.store_mut().put(root.clone(), b"root".to_vec());
+
+        let peer = PeerId::random();
+        graphsync.inject_connected(peer.clone(), endpoint());
+        let requests = vec![
+            GraphsyncRequest::New { id: RequestId(1), root: root.clone(), selector: Vec::new(), extensions: Extensions::new(), priority: 1 },
+            GraphsyncRequest::New { id: RequestId(2), root: missing, selector: Vec::new(), extensions: Extensions::new(), priority: 1 },
+        ];
+        graphsync.inject_node_event(peer.clone(), InnerMessage::Rx(GraphsyncMessage { requests, ..GraphsyncMessage::default() }));
+
+        let statuses = graphsync.events.iter().filter_map(|e| match e {
+            NetworkBehaviourAction::SendEvent { event, .. } => Some((event.responses[0].status, event.blocks.len())),
+            _ => None,
+        }).collect::<Vec<_>>();
+        assert_eq!(statuses, vec![
+            (ResponseStatus::RequestCompletedFull, 1),
+            (ResponseStatus::RequestFailedContentNotFound, 0),
+        ]);
+    }
+
+    #[test]
+    fn stores_blocks_of_requests() {
+        let mut graphsync = TestGraphsync::new(RootOnlyStore::default());
+        let root = Cid::from_data(1, RAW, Hash::SHA2256, b"root").unwrap();
+        let peer = PeerId::random();
+
+        let id = graphsync.request(peer.clone(), root.clone(), Vec::new(), Extensions::new());
+        match graphsync.events.pop_front() {
+            Some(NetworkBehaviourAction::DialPeer { peer_id }) => assert_eq!(peer_id, peer),
+            _ => panic!("expected a dial"),
+        }
+        graphsync.inject_connected(peer.clone(), endpoint());
+        match graphsync.events.pop_front() {
+            Some(NetworkBehaviourAction::SendEvent { event, .. }) => assert_eq!(event.requests[0].id(), id),
+            _ => panic!("expected the request"),
+        }
+
+        let response = GraphsyncResponse { id, status: ResponseStatus::RequestCompletedFull, extensions: Extensions::new() };
+        let message = GraphsyncMessage {
+            responses: vec![response],
+            blocks: vec![(root.clone(), b"root".to_vec())],
+            ..GraphsyncMessage::default()
+        };
+        graphsync.inject_node_event(peer.clone(), InnerMessage::Rx(message));
+
+        assert!(graphsync.store().0.contains_key(&root));
+        assert_eq!(graphsync.events.len(), 2);
+
+        // Blocks aren't accepted once the request is over.
+        let other = Cid::from_data(1, RAW, Hash::SHA2256, b"other").unwrap();
+        let message = GraphsyncMessage { blocks: vec![(other.clone(), b"other".to_vec())], ..GraphsyncMessage::default() };
+        graphsync.inject_node_event(peer, InnerMessage::Rx(message));
+        assert!(!graphsync.store().0.contains_key(&other));
+    }
+}
@@ -0,0 +1,51 @@
+// Copyright 2019 Parity Technologies (UK) Ltd.
+//
+// Permission is hereby granted, free of charge, to any person obtaining a
+// copy of this software and associated documentation files (the "Software"),
+// to deal in the Software without restriction, including without limitation
+// the rights to use, copy, modify, merge, publish, distribute, sublicense,
+// and/or sell copies of the Software, and to permit persons to whom the
+// Software is furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS
+// OR IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING
+// FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
+// DEALINGS IN THE SOFTWARE.
+//! Implementation of the [graphsync] protocol.
+//!
+//! Graphsync transfers the blocks of a DAG designated by a root CID and an IPLD selector, in a
+//! single request instead of one round-trip per block as bitswap would need. This is what go
+//! nodes use to exchange chain data, among other things.
+//!
+//! The [`Graphsync`] network behaviour sends requests to remotes and answers their requests.
+//! Decoding blocks and interpreting selectors is left to an [`IpldStore`], which walks the DAGs
+//! requested by remotes and stores the blocks received in response to our requests.
+//!
+//! [graphsync]: https://github.com/ipld/specs/blob/master/block-layer/graphsync/graphsync.md
+
+pub mod protocol;
+
+mod behaviour;
+mod store;
+
+/// Protobuf messages of the protocol, generated from `message.proto`.
+mod proto {
+    include!(concat!(env!("OUT_DIR"), "/graphsync.message.pb.rs"));
+}
+
+pub use self::behaviour::{Graphsync, GraphsyncEvent, InnerMessage};
+pub use self::protocol::{
+    Extensions,
+    GraphsyncMessage,
+    GraphsyncRequest,
+    GraphsyncResponse,
+    RequestId,
+    ResponseStatus
+};
+pub use self::store::{IpldStore, Traversal, TraversalError};
@@ -0,0 +1,461 @@
+// Copyright 2019 Parity Technologies (UK) Ltd.
+//
+// Permission is hereby granted, free of charge, to any person obtaining a
+// copy of this software and associated documentation files (the "Software"),
+// to deal in the Software without restriction, including without limitation
+// the rights to use, copy, modify, merge, publish, distribute, sublicense,
+// and/or sell copies of the Software, and to permit persons to whom the
+// Software is furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS
+// OR IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING
+// FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
+// DEALINGS IN THE SOFTWARE.
+use crate::proto::{self, message as proto_message};
+use libp2p_bitswap::cid::{Cid, CidError, Prefix};
+use libp2p_core::{InboundUpgrade, OutboundUpgrade, UpgradeInfo, upgrade};
+use prost::{DecodeError, Message};
+use std::{collections::BTreeMap, error, fmt, io, iter};
+use tokio_io::{AsyncRead, AsyncWrite};
+
+/// Name of the protocol.
+pub const PROTOCOL_NAME: &[u8] = b"/ipfs/graphsync/1.0.0";
+
+/// Maximum size of a graphsync message.
+const MAX_MESSAGE_SIZE: usize = 4 * 1024 * 1024;
+
+/// Extensions attached to requests and responses, by name.
+pub type Extensions = BTreeMap<String, Vec<u8>>;
+
+/// Identifier of a request, chosen by the requester.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct RequestId(pub(crate) i32);
+
+impl fmt::Display for RequestId {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+/// Status of a response.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum ResponseStatus {
+    /// The request has been received and is being processed.
+    RequestAcknowledged,
+    /// Other peers may have the content.
+    AdditionalPeers,
+    /// The request requires payment.
+    NotEnoughGas,
+    /// The content is available through another protocol.
+    OtherProtocol,
+    /// Part of the response; more is coming.
+    PartialResponse,
+    /// The responder paused the request.
+    RequestPaused,
+    /// All the blocks matching the selector have been sent.
+    RequestCompletedFull,
+    /// Some of the blocks matching the selector were missing and haven't been sent.
+    RequestCompletedPartial,
+    /// The responder refused to process the request.
+    RequestRejected,
+    /// The responder is too busy to process the request.
+    RequestFailedBusy,
+    /// The request failed for an unknown reason.
+    RequestFailedUnknown,
+    /// The request failed for legal reasons.
+    RequestFailedLegal,
+    /// The responder doesn't have the root of the request.
+    RequestFailedContentNotFound,
+    /// The request has been cancelled.
+    RequestCancelled,
+    /// A status code unknown to this implementation.
+    Other(i32),
+}
+
+impl ResponseStatus {
+    /// Returns true if no further response will follow for the request.
+    pub fn is_terminal(&self) -> bool {
+        self.code() >= 20
+    }
+
+    /// Returns true if the status reports a failure.
+    pub fn is_failure(&self) -> bool {
+        self.code() >= 30
+    }
+
+    fn code(&self) -> i32 {
+        match *self {
+            ResponseStatus::RequestAcknowledged => 10,
+            ResponseStatus::AdditionalPeers => 11,
+            ResponseStatus::NotEnoughGas => 12,
+            ResponseStatus::OtherProtocol => 13,
+            ResponseStatus::PartialResponse => 14,
+            ResponseStatus::RequestPaused => 15,
+            ResponseStatus::RequestCompletedFull => 20,
+            ResponseStatus::RequestCompletedPartial => 21,
+            ResponseStatus::RequestRejected => 30,
+            ResponseStatus::RequestFailedBusy => 31,
+            ResponseStatus::RequestFailedUnknown => 32,
+            ResponseStatus::RequestFailedLegal => 33,
+            ResponseStatus::RequestFailedContentNotFound => 34,
+            ResponseStatus::RequestCancelled => 35,
+            ResponseStatus::Other(code) => code,
+        }
+    }
+
+    fn from_code(code: i32) -> ResponseStatus {
+        match code {
+            10 => ResponseStatus::RequestAcknowledged,
+            11 => ResponseStatus::AdditionalPeers,
+            12 => ResponseStatus::NotEnoughGas,
+            13 => ResponseStatus::OtherProtocol,
+            14 => ResponseStatus::PartialResponse,
+            15 => ResponseStatus::RequestPaused,
+            20 => ResponseStatus::RequestCompletedFull,
+            21 => ResponseStatus::RequestCompletedPartial,
+            30 => ResponseStatus::RequestRejected,
+            31 => ResponseStatus::RequestFailedBusy,
+            32 => ResponseStatus::RequestFailedUnknown,
+            33 => ResponseStatus::RequestFailedLegal,
+            34 => ResponseStatus::RequestFailedContentNotFound,
+            35 => ResponseStatus::RequestCancelled,
+            code => ResponseStatus::Other(code),
+        }
+    }
+}
+
+/// Request sent to a graphsync responder.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum GraphsyncRequest {
+    /// Request for the blocks of a DAG.
+    New {
+        /// Identifier of the request.
+        id: RequestId,
+        /// Root of the DAG.
+        root: Cid,
+        /// IPLD selector, encoded in DAG-CBOR, designating the blocks to send.
+        selector: Vec<u8>,
+        /// Extensions of the request.
+        extensions: Extensions,
+        /// Priority of the request. Higher is more urgent.
+        priority: i32,
+    },
+    /// Updates the extensions of an outstanding request.
+    Update {
+        /// Identifier of the request.
+        id: RequestId,
+        /// New extensions of the request.
+        extensions: Extensions,
+    },
+    /// Cancels an outstanding request.
+    Cancel {
+        /// Identifier of the request.
+        id: RequestId,
+    },
+}
+
+impl GraphsyncRequest {
+    /// Returns the identifier of the request.
+    pub fn id(&self) -> RequestId {
+        match *self {
+            GraphsyncRequest::New { id, .. } => id,
+            GraphsyncRequest::Update { id, .. } => id,
+            GraphsyncRequest::Cancel { id } => id,
+        }
+    }
+}
+
+/// Response to a request. The blocks are carried separately, by the message.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GraphsyncResponse {
+    /// Identifier of the request.
+    pub id: RequestId,
+    /// Status of the request.
+    pub status: ResponseStatus,
+    /// Extensions of the response.
+    pub extensions: Extensions,
+}
+
+/// Message exchanged over the graphsync protocol.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct GraphsyncMessage {
+    /// If true, `requests` replaces all the outstanding requests of the sender.
+    pub complete_request_list: bool,
+    /// Requests of the sender.
+    pub requests: Vec<GraphsyncRequest>,
+    /// Responses to requests of the recipient.
+    pub responses: Vec<GraphsyncResponse>,
+    /// Blocks related to the responses.
+    pub blocks: Vec<(Cid, Vec<u8>)>,
+}
+
+impl GraphsyncMessage {
+    /// Encodes the message.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let requests = self.requests
+            .iter()
+            .map(|request| {
+                let mut r = proto_message::Request { id: request.id().0, ..Default::default() };
+                match request {
+                    GraphsyncRequest::New { root, selector, extensions, priority, .. } => {
+                        r.root = root.to_bytes();
+                        r.selector = selector.clone();
+                        r.extensions = extensions.clone().into_iter().collect();
+                        r.priority = *priority;
+                    }
+                    GraphsyncRequest::Update { extensions, .. } => {
+                        r.extensions = extensions.clone().into_iter().collect();
+                        r.update = true;
+                    }
+                    GraphsyncRequest::Cancel { .. } => r.cancel = true,
+                }
+                r
+            })
+            .collect();
+
+        let responses = self.responses
+            .iter()
+            .map(|response| proto_message::Response {
+                id: response.id.0,
+                status: response.status.code(),
+                extensions: response.extensions.clone().into_iter().collect(),
+            })
+            .collect();
+
+        let data = self.blocks
+            .iter()
+            .map(|(cid, data)| proto_message::Block {
+                prefix: cid.prefix().to_bytes(),
+                data: data.clone(),
+            })
+            .collect();
+
+        let message = proto::Message {
+            complete_request_list: self.complete_request_list,
+            requests,
+            responses,
+            data,
+        };
+
+        let mut buf = Vec::with_capacity(message.encoded_len());
+        message.encode(&mut buf).expect("Vec<u8> provides capacity as needed");
+        buf
+    }
+
+    /// Decodes a message. The CIDs of the blocks are computed from their content.
+    pub fn from_bytes(bytes: &[u8]) -> Result<GraphsyncMessage, GraphsyncDecodeError> {
+        let decoded = proto::Message::decode(bytes)?;
+
+        let requests = decoded.requests
+            .into_iter()
+            .map(decode_request)
+            .collect::<Result<_, _>>()?;
+
+        let responses = decoded.responses
+            .into_iter()
+            .map(|response| GraphsyncResponse {
+                id: RequestId(response.id),
+                status: ResponseStatus::from_code(response.status),
+                extensions: response.extensions.into_iter().collect(),
+            })
+            .collect();
+
+        let blocks = decoded.data
+            .into_iter()
+            .map(|block| -> Result<_, GraphsyncDecodeError> {
+                let cid = Prefix::from_bytes(&block.prefix)?.to_cid(&block.data)?;
+                Ok((cid, block.data))
+            })
+            .collect::<Result<_, _>>()?;
+
+        Ok(GraphsyncMessage {
+            complete_request_list: decoded.complete_request_list,
+            requests,
+            responses,
+            blocks,
+        })
+    }
+}
+
+fn decode_request(request: proto_message::Request) -> Result<GraphsyncRequest, GraphsyncDecodeError> {
+    let id = RequestId(request.id);
+    if request.cancel {
+        return Ok(GraphsyncRequest::Cancel { id });
+    }
+
+    let extensions = request.extensions.into_iter().collect();
+    if request.update {
+        return Ok(GraphsyncRequest::Update { id, extensions });
+    }
+
+    if request.root.is_empty() {
+        return Err(DecodeError::new("missing request root").into());
+    }
+
+    Ok(GraphsyncRequest::New {
+        id,
+        root: Cid::from_bytes(&request.root)?,
+        selector: request.selector,
+        extensions,
+        priority: request.priority,
+    })
+}
+
+/// Implementation of `InboundUpgrade` for the graphsync protocol.
+#[derive(Debug, Clone, Default)]
+pub struct GraphsyncConfig {}
+
+impl GraphsyncConfig {
+    /// Builds a new `GraphsyncConfig`.
+    pub fn new() -> GraphsyncConfig {
+        GraphsyncConfig {}
+    }
+}
+
+impl UpgradeInfo for GraphsyncConfig {
+    type Info = &'static [u8];
+    type InfoIter = iter::Once<Self::Info>;
+
+    fn protocol_info(&self) -> Self::InfoIter {
+        iter::once(PROTOCOL_NAME)
+    }
+}
+
+impl<TSocket> InboundUpgrade<TSocket> for GraphsyncConfig
+where
+    TSocket: AsyncRead,
+{
+    type Output = GraphsyncMessage;
+    type Error = GraphsyncDecodeError;
+    type Future = upgrade::ReadOneThen<upgrade::Negotiated<TSocket>, (), fn(Vec<u8>, ()) -> Result<GraphsyncMessage, GraphsyncDecodeError>>;
+
+    fn upgrade_inbound(self, socket: upgrade::Negotiated<TSocket>, _: Self::Info) -> Self::Future {
+        upgrade::read_one_then(socket, MAX_MESSAGE_SIZE, (), |packet, ()| {
+            GraphsyncMessage::from_bytes(&packet)
+        })
+    }
+}
+
+impl UpgradeInfo for GraphsyncMessage {
+    type Info = &'static [u8];
+    type InfoIter = iter::Once<Self::Info>;
+
+    fn protocol_info(&self) -> Self::InfoIter {
+        iter::once(PROTOCOL_NAME)
+    }
+}
+
+impl<TSocket> OutboundUpgrade<TSocket> for GraphsyncMessage
+where
+    TSocket: AsyncWrite,
+{
+    type Output = ();
+    type Error = io::Error;
+    type Future = upgrade::WriteOne<upgrade::Negotiated<TSocket>>;
+
+    fn upgrade_outbound(self, socket: upgrade::Negotiated<TSocket>, _: Self::Info) -> Self::Future {
+        upgrade::write_one(socket, self.to_bytes())
+    }
+}
+
+/// Error while receiving a graphsync message.
+#[derive(Debug)]
+pub enum GraphsyncDecodeError {
+    /// Error when reading the packet from the socket.
+    ReadError(upgrade::ReadOneError),
+    /// Error when decoding the raw buffer into a protobuf.
+    ProtobufError(DecodeError),
+    /// A CID or block prefix is invalid.
+    InvalidCid(CidError),
+}
+
+impl From<upgrade::ReadOneError> for GraphsyncDecodeError {
+    fn from(err: upgrade::ReadOneError) -> Self {
+        GraphsyncDecodeError::ReadError(err)
+    }
+}
+
+impl From<DecodeError> for GraphsyncDecodeError {
+    fn from(err: DecodeError) -> Self {
+        GraphsyncDecodeError::ProtobufError(err)
+    }
+}
+
+impl From<CidError> for GraphsyncDecodeError {
+    fn from(err: CidError) -> Self {
+        GraphsyncDecodeError::InvalidCid(err)
+    }
+}
+
+impl fmt::Display for GraphsyncDecodeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match *self {
+            GraphsyncDecodeError::ReadError(ref err) =>
+                write!(f, "Error while reading from socket: {}", err),
+            GraphsyncDecodeError::ProtobufError(ref err) =>
+                write!(f, "{}", err),
+            GraphsyncDecodeError::InvalidCid(ref err) =>
+                write!(f, "{}", err),
+        }
+    }
+}
+
+impl error::Error for GraphsyncDecodeError {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        match *self {
+            GraphsyncDecodeError::ReadError(ref err) => Some(err),
+            GraphsyncDecodeError::ProtobufError(ref err) => Some(err),
+            GraphsyncDecodeError::InvalidCid(ref err) => Some(err),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use libp2p_bitswap::cid::RAW;
+    use multihash::Hash;
+
+    #[test]
+    fn message_roundtrip() {
+        let cid = Cid::from_data(1, RAW, Hash::SHA2256, b"block").unwrap();
+        let mut extensions = Extensions::new();
+        extensions.insert("graphsync/do-not-send-cids".to_owned(), vec![1, 2, 3]);
+
+        let message = GraphsyncMessage {
+            complete_request_list: true,
+            requests: vec![
+                GraphsyncRequest::New {
+                    id: RequestId(-5),
+                    root: cid.clone(),
+                    selector: vec![0xa1, 0x61, 0x2e, 0xa0],
+                    extensions: extensions.clone(),
+                    priority: 7,
+                },
+                GraphsyncRequest::Update { id: RequestId(2), extensions: extensions.clone() },
+                GraphsyncRequest::Cancel { id: RequestId(3) },
+            ],
+            responses: vec![
+                GraphsyncResponse { id: RequestId(1), status: ResponseStatus::PartialResponse, extensions },
+                GraphsyncResponse { id: RequestId(4), status: ResponseStatus::Other(99), extensions: Extensions::new() },
+            ],
+            blocks: vec![(cid, b"block".to_vec())],
+        };
+
+        assert_eq!(GraphsyncMessage::from_bytes(&message.to_bytes()).unwrap(), message);
+    }
+
+    #[test]
+    fn status_classification() {
+        assert!(!ResponseStatus::PartialResponse.is_terminal());
+        assert!(ResponseStatus::RequestCompletedPartial.is_terminal());
+        assert!(!ResponseStatus::RequestCompletedFull.is_failure());
+        assert!(ResponseStatus::RequestFailedContentNotFound.is_failure());
+    }
+}
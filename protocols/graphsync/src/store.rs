@@ -0,0 +1,78 @@
+// Copyright 2019 Parity Technologies (UK) Ltd.
+//
+// Permission is hereby granted, free of charge, to any person obtaining a
+// copy of this software and associated documentation files (the "Software"),
+// to deal in the Software without restriction, including without limitation
+// the rights to use, copy, modify, merge, publish, distribute, sublicense,
+// and/or sell copies of the Software, and to permit persons to whom the
+// Software is furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS
+// OR IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING
+// FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
+// DEALINGS IN THE SOFTWARE.
+use crate::protocol::Extensions;
+use libp2p_bitswap::cid::Cid;
+use libp2p_core::PeerId;
+use std::{error, fmt};
+
+/// Storage of IPLD blocks, and interpreter of the selectors of graphsync requests.
+///
+/// Graphsync itself doesn't know how to decode blocks or selectors. The store is responsible for
+/// walking the DAG designated by a request, and for storing the blocks received in response to
+/// our own requests.
+pub trait IpldStore {
+    /// Walks the DAG starting at `root` and returns the blocks designated by `selector`, in
+    /// traversal order.
+    ///
+    /// The selector is encoded in DAG-CBOR. If some of the designated blocks are missing from
+    /// the store, the traversal must return the others and set `complete` to `false`.
+    fn traverse(&self, root: &Cid, selector: &[u8]) -> Result<Traversal, TraversalError>;
+
+    /// Stores a block received in response to one of our requests. The content is guaranteed to
+    /// match `cid`.
+    fn put(&mut self, cid: Cid, data: Vec<u8>);
+
+    /// Decides whether to answer a request from `peer_id`. Accepts every request by default.
+    fn validate_request(&self, _peer_id: &PeerId, _root: &Cid, _selector: &[u8], _extensions: &Extensions) -> bool {
+        true
+    }
+}
+
+/// Result of walking a DAG.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Traversal {
+    /// Blocks designated by the selector, in traversal order.
+    pub blocks: Vec<(Cid, Vec<u8>)>,
+    /// False if some of the blocks designated by the selector are missing.
+    pub complete: bool,
+}
+
+/// Error while walking a DAG.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TraversalError {
+    /// The root of the DAG isn't in the store.
+    NotFound,
+    /// The selector couldn't be decoded or isn't supported.
+    InvalidSelector,
+    /// Any other failure.
+    Other(String),
+}
+
+impl fmt::Display for TraversalError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TraversalError::NotFound => write!(f, "Root block not found"),
+            TraversalError::InvalidSelector => write!(f, "Invalid or unsupported selector"),
+            TraversalError::Other(err) => write!(f, "{}", err),
+        }
+    }
+}
+
+impl error::Error for TraversalError {}
@@ -19,8 +19,9 @@
 // DEALINGS IN THE SOFTWARE.
 
 use crate::listen_handler::IdentifyListenHandler;
-use crate::periodic_id_handler::{PeriodicIdHandler, PeriodicIdHandlerEvent};
+use crate::periodic_id_handler::{self, PeriodicIdHandler, PeriodicIdHandlerEvent};
 use crate::protocol::{IdentifyInfo, IdentifySender, IdentifySenderFuture};
+use crate::push_handler::{IdentifyPushHandler, IdentifyPushHandlerEvent};
 use futures::prelude::*;
 use libp2p_core::{
     ConnectedPoint,
@@ -33,25 +34,38 @@ use libp2p_core::{
 use libp2p_swarm::{
     NetworkBehaviour,
     NetworkBehaviourAction,
+    NetworkChange,
     PollParameters,
     ProtocolsHandler,
     ProtocolsHandlerSelect,
     ProtocolsHandlerUpgrErr
 };
+use log::debug;
 use smallvec::SmallVec;
-use std::{collections::HashMap, collections::VecDeque, io};
+use std::{collections::HashMap, collections::VecDeque, fmt, io, sync::Arc, time::Duration};
 use tokio_io::{AsyncRead, AsyncWrite};
 use void::Void;
+use wasm_timer::{Delay, Instant};
 
 /// Network behaviour that automatically identifies nodes periodically, returns information
 /// about them, and answers identify queries from other nodes.
+///
+/// The address of the local node that a remote reports having observed is passed to the `Swarm`
+/// as a `NetworkBehaviourAction::ReportObservedAddr`. The `Swarm` then promotes it to an external
+/// address of the local node once enough distinct peers have reported it (see
+/// `SwarmBuilder::external_address_confirmations`), and the external addresses are in turn sent
+/// to remotes. Nodes behind a NAT therefore advertise their public addresses without the
+/// application having to call `Swarm::add_external_address`.
+///
+/// When the listen or external addresses of the local node change, the updated information is
+/// pushed to all the connected remotes that support the `/ipfs/id/push/1.0.0` protocol, once
+/// the addresses have been stable for `IdentifyConfig::set_push_delay`.
 pub struct Identify<TSubstream> {
-    /// Protocol version to send back to remotes.
-    protocol_version: String,
-    /// Agent version to send back to remotes.
-    agent_version: String,
-    /// The public key of the local node. To report on the wire.
-    local_public_key: PublicKey,
+    /// The configuration of the behaviour.
+    config: IdentifyConfig,
+    /// The most recently received information of remotes, with the peers in order of
+    /// insertion. Bounded by `IdentifyConfig::cache_size`.
+    cache: (HashMap<PeerId, IdentifyInfo>, VecDeque<PeerId>),
     /// For each peer we're connected to, the observed address to send back to it.
     observed_addresses: HashMap<PeerId, Multiaddr>,
     /// List of senders to answer, with the observed multiaddr.
@@ -59,20 +73,219 @@ pub struct Identify<TSubstream> {
     /// List of futures that send back information back to remotes.
     futures: SmallVec<[(PeerId, IdentifySenderFuture<Negotiated<TSubstream>>); 4]>,
     /// Events that need to be produced outside when polling..
-    events: VecDeque<NetworkBehaviourAction<EitherOutput<Void, Void>, IdentifyEvent>>,
+    events: VecDeque<NetworkBehaviourAction<EitherOutput<EitherOutput<Void, Void>, IdentifyInfo>, IdentifyEvent>>,
+    /// Fires when the information of the local node must be pushed to the remotes, after the
+    /// addresses of the local node changed.
+    push_timer: Option<Delay>,
+}
+
+/// Configuration of an `Identify` behaviour.
+#[derive(Clone)]
+pub struct IdentifyConfig {
+    /// Protocol version to send back to remotes.
+    protocol_version: String,
+    /// Agent version to send back to remotes.
+    agent_version: String,
+    /// The public key of the local node. To report on the wire.
+    local_public_key: PublicKey,
+    /// Delay between the moment we connect and the first time we identify.
+    initial_delay: Duration,
+    /// Delay between two identifications of a remote over the same connection.
+    interval: Duration,
+    /// Maximum number of remotes whose information is kept in the cache.
+    cache_size: usize,
+    /// Predicate that the listen addresses sent to remotes must satisfy, if any.
+    address_filter: Option<Arc<dyn Fn(&Multiaddr) -> bool + Send + Sync>>,
+    /// Time the addresses of the local node must be stable for before being pushed to the
+    /// remotes. `None` if the information isn't pushed.
+    push_delay: Option<Duration>,
+}
+
+impl IdentifyConfig {
+    /// Creates a new configuration for the given protocol version (e.g. `ipfs/1.0.0`)
+    /// and local public key.
+    ///
+    /// The agent version defaults to `rust-libp2p/<version of this crate>`.
+    pub fn new(protocol_version: String, local_public_key: PublicKey) -> Self {
+        IdentifyConfig {
+            protocol_version,
+            agent_version: format!("rust-libp2p/{}", env!("CARGO_PKG_VERSION")),
+            local_public_key,
+            initial_delay: periodic_id_handler::DELAY_TO_FIRST_ID,
+            interval: periodic_id_handler::DELAY_TO_NEXT_ID,
+            cache_size: 100,
+            address_filter: None,
+            push_delay: Some(Duration::from_secs(2)),
+        }
+    }
+
+    /// Sets the agent version sent to remotes, i.e. the name and version of the client.
+    pub fn set_agent_version(&mut self, agent_version: String) -> &mut Self {
+        self.agent_version = agent_version;
+        self
+    }
+
+    /// Sets the delay between the moment a connection is established and the first
+    /// identification of the remote.
+    ///
+    /// The default is 500 milliseconds.
+    pub fn set_initial_delay(&mut self, delay: Duration) -> &mut Self {
+        self.initial_delay = delay;
+        self
+    }
+
+    /// Sets the interval at which remotes are identified again for as long as the
+    /// connection to them stays open, which keeps the information of long-lived
+    /// connections up to date.
+    ///
+    /// The default is 5 minutes.
+    pub fn set_interval(&mut self, interval: Duration) -> &mut Self {
+        self.interval = interval;
+        self
+    }
+
+    /// Sets the maximum number of remotes whose information is kept in the cache,
+    /// see [`Identify::cached_info`].
+    ///
+    /// When the cache is full, the information of the least recently identified
+    /// remote is evicted. A size of zero disables the cache. The default is 100.
+    pub fn set_cache_size(&mut self, cache_size: usize) -> &mut Self {
+        self.cache_size = cache_size;
+        self
+    }
+
+    /// Sets a predicate that the listen and external addresses of the local node must satisfy
+    /// to be sent to remotes.
+    ///
+    /// This prevents addresses that are only reachable from a private network, such as
+    /// loopback or RFC1918 addresses, from being advertised to the rest of the network.
+    /// By default, all the addresses are sent.
+    ///
+    /// ```
+    /// # use libp2p_core::identity;
+    /// # use libp2p_identify::IdentifyConfig;
+    /// # let local_public_key = identity::Keypair::generate_ed25519().public();
+    /// let mut config = IdentifyConfig::new("ipfs/1.0.0".to_string(), local_public_key);
+    /// config.set_address_filter(|addr| addr.is_public());
+    /// ```
+    pub fn set_address_filter<F>(&mut self, filter: F) -> &mut Self
+    where
+        F: Fn(&Multiaddr) -> bool + Send + Sync + 'static,
+    {
+        self.address_filter = Some(Arc::new(filter));
+        self
+    }
+
+    /// Sets the time the listen and external addresses of the local node must be stable for
+    /// before the updated information is pushed to the connected remotes.
+    ///
+    /// Every change of the addresses restarts the delay, so that a burst of changes, e.g. when
+    /// a mobile device switches networks, results in a single push. `None` disables the push.
+    /// The default is 2 seconds.
+    pub fn set_push_delay(&mut self, delay: Option<Duration>) -> &mut Self {
+        self.push_delay = delay;
+        self
+    }
+}
+
+impl fmt::Debug for IdentifyConfig {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("IdentifyConfig")
+            .field("protocol_version", &self.protocol_version)
+            .field("agent_version", &self.agent_version)
+            .field("local_public_key", &self.local_public_key)
+            .field("initial_delay", &self.initial_delay)
+            .field("interval", &self.interval)
+            .field("cache_size", &self.cache_size)
+            .field("address_filter", &self.address_filter.is_some())
+            .field("push_delay", &self.push_delay)
+            .finish()
+    }
 }
 
 impl<TSubstream> Identify<TSubstream> {
     /// Creates a `Identify`.
     pub fn new(protocol_version: String, agent_version: String, local_public_key: PublicKey) -> Self {
+        let mut config = IdentifyConfig::new(protocol_version, local_public_key);
+        config.set_agent_version(agent_version);
+        Identify::with_config(config)
+    }
+
+    /// Creates a `Identify` with the given configuration.
+    pub fn with_config(config: IdentifyConfig) -> Self {
         Identify {
-            protocol_version,
-            agent_version,
-            local_public_key,
+            config,
+            cache: (HashMap::new(), VecDeque::new()),
             observed_addresses: HashMap::new(),
             to_answer: SmallVec::new(),
             futures: SmallVec::new(),
             events: VecDeque::new(),
+            push_timer: None,
+        }
+    }
+
+    /// Returns the most recent information received from the given peer, if it is
+    /// still in the cache.
+    ///
+    /// The information of a peer stays in the cache after it disconnected, until it
+    /// is evicted by the information of other peers.
+    pub fn cached_info(&self, peer_id: &PeerId) -> Option<&IdentifyInfo> {
+        self.cache.0.get(peer_id)
+    }
+
+    /// Returns the addresses among `addrs` that pass the address filter of the configuration.
+    fn advertised_addresses(&self, addrs: impl Iterator<Item = Multiaddr>) -> Vec<Multiaddr> {
+        match self.config.address_filter {
+            Some(ref filter) => addrs.filter(|addr| filter(addr)).collect(),
+            None => addrs.collect(),
+        }
+    }
+
+    /// Builds the information of the local node that is sent to remotes.
+    fn local_info(&self, params: &mut impl PollParameters) -> IdentifyInfo {
+        // The protocol names can be bytes, but the identify protocol except UTF-8 strings.
+        // There's not much we can do to solve this conflict except strip non-UTF-8 characters.
+        let protocols = params
+            .supported_protocols()
+            .map(|p| String::from_utf8_lossy(&p).to_string())
+            .collect();
+
+        let listen_addrs = self.advertised_addresses(
+            params.external_addresses().chain(params.listened_addresses())
+        );
+
+        IdentifyInfo {
+            public_key: self.config.local_public_key.clone(),
+            protocol_version: self.config.protocol_version.clone(),
+            agent_version: self.config.agent_version.clone(),
+            listen_addrs,
+            protocols,
+        }
+    }
+
+    /// (Re)starts the delay after which the information of the local node is pushed to the
+    /// remotes, following a change of the addresses.
+    fn schedule_push(&mut self) {
+        if let Some(delay) = self.config.push_delay {
+            self.push_timer = Some(Delay::new(Instant::now() + delay));
+        }
+    }
+
+    /// Inserts the information received from a peer in the cache, evicting the least
+    /// recently identified peer if the cache is full.
+    fn cache_info(&mut self, peer_id: PeerId, info: IdentifyInfo) {
+        if self.config.cache_size == 0 {
+            return
+        }
+        let (infos, order) = &mut self.cache;
+        if infos.insert(peer_id.clone(), info).is_some() {
+            order.retain(|p| p != &peer_id);
+        }
+        order.push_back(peer_id);
+        while order.len() > self.config.cache_size {
+            if let Some(evicted) = order.pop_front() {
+                infos.remove(&evicted);
+            }
         }
     }
 }
@@ -81,15 +294,21 @@ impl<TSubstream> NetworkBehaviour for Identify<TSubstream>
 where
     TSubstream: AsyncRead + AsyncWrite,
 {
-    type ProtocolsHandler = ProtocolsHandlerSelect<IdentifyListenHandler<TSubstream>, PeriodicIdHandler<TSubstream>>;
+    type ProtocolsHandler = ProtocolsHandlerSelect<
+        ProtocolsHandlerSelect<IdentifyListenHandler<TSubstream>, PeriodicIdHandler<TSubstream>>,
+        IdentifyPushHandler<TSubstream>
+    >;
     type OutEvent = IdentifyEvent;
 
     fn new_handler(&mut self) -> Self::ProtocolsHandler {
-        IdentifyListenHandler::new().select(PeriodicIdHandler::new())
+        let handler = PeriodicIdHandler::with_interval(self.config.initial_delay, self.config.interval);
+        IdentifyListenHandler::new().select(handler).select(IdentifyPushHandler::new())
     }
 
-    fn addresses_of_peer(&mut self, _: &PeerId) -> Vec<Multiaddr> {
-        Vec::new()
+    fn addresses_of_peer(&mut self, peer_id: &PeerId) -> Vec<Multiaddr> {
+        self.cached_info(peer_id)
+            .map(|info| info.listen_addrs.clone())
+            .unwrap_or_default()
     }
 
     fn inject_connected(&mut self, peer_id: PeerId, endpoint: ConnectedPoint) {
@@ -105,38 +324,81 @@ where
         self.observed_addresses.remove(peer_id);
     }
 
+    fn inject_new_listen_addr(&mut self, _: &Multiaddr) {
+        self.schedule_push();
+    }
+
+    fn inject_expired_listen_addr(&mut self, _: &Multiaddr) {
+        self.schedule_push();
+    }
+
+    fn inject_new_external_addr(&mut self, _: &Multiaddr) {
+        self.schedule_push();
+    }
+
+    fn inject_network_change(&mut self, _: &NetworkChange) {
+        self.schedule_push();
+    }
+
     fn inject_node_event(
         &mut self,
         peer_id: PeerId,
         event: <Self::ProtocolsHandler as ProtocolsHandler>::OutEvent,
     ) {
         match event {
-            EitherOutput::Second(PeriodicIdHandlerEvent::Identified(remote)) => {
+            EitherOutput::First(EitherOutput::Second(PeriodicIdHandlerEvent::Identified(remote))) => {
+                let protocols = remote.info.protocols.clone();
+                self.cache_info(peer_id.clone(), remote.info.clone());
                 self.events
                     .push_back(NetworkBehaviourAction::GenerateEvent(IdentifyEvent::Identified {
-                        peer_id,
+                        peer_id: peer_id.clone(),
                         info: remote.info,
                         observed_addr: remote.observed_addr.clone(),
                     }));
+                self.events
+                    .push_back(NetworkBehaviourAction::ReportRemoteProtocols {
+                        peer_id: peer_id.clone(),
+                        protocols,
+                    });
                 self.events
                     .push_back(NetworkBehaviourAction::ReportObservedAddr {
                         address: remote.observed_addr,
+                        observer: peer_id,
                     });
             }
-            EitherOutput::First(sender) => {
+            EitherOutput::First(EitherOutput::First(sender)) => {
                 let observed = self.observed_addresses.get(&peer_id)
                     .expect("We only receive events from nodes we're connected to. We insert \
                              into the hashmap when we connect to a node and remove only when we \
                              disconnect; QED");
                 self.to_answer.push((peer_id, sender, observed.clone()));
             }
-            EitherOutput::Second(PeriodicIdHandlerEvent::IdentificationError(err)) => {
+            EitherOutput::First(EitherOutput::Second(PeriodicIdHandlerEvent::IdentificationError(err))) => {
                 self.events
                     .push_back(NetworkBehaviourAction::GenerateEvent(IdentifyEvent::Error {
                         peer_id,
                         error: err,
                     }));
             }
+            EitherOutput::Second(IdentifyPushHandlerEvent::Received(info)) => {
+                let protocols = info.protocols.clone();
+                self.cache_info(peer_id.clone(), info.clone());
+                self.events
+                    .push_back(NetworkBehaviourAction::GenerateEvent(IdentifyEvent::Pushed {
+                        peer_id: peer_id.clone(),
+                        info,
+                    }));
+                self.events
+                    .push_back(NetworkBehaviourAction::ReportRemoteProtocols {
+                        peer_id,
+                        protocols,
+                    });
+            }
+            EitherOutput::Second(IdentifyPushHandlerEvent::Pushed) => {}
+            EitherOutput::Second(IdentifyPushHandlerEvent::PushError(err)) => {
+                // Remotes that don't support the push protocol are identified periodically.
+                debug!("Failed to push identify info to {:?}: {:?}", peer_id, err);
+            }
         }
     }
 
@@ -153,26 +415,31 @@ where
             return Async::Ready(event);
         }
 
-        for (peer_id, sender, observed) in self.to_answer.drain() {
-            // The protocol names can be bytes, but the identify protocol except UTF-8 strings.
-            // There's not much we can do to solve this conflict except strip non-UTF-8 characters.
-            let protocols = params
-                .supported_protocols()
-                .map(|p| String::from_utf8_lossy(&p).to_string())
-                .collect();
-
-            let mut listen_addrs: Vec<_> = params.external_addresses().collect();
-            listen_addrs.extend(params.listened_addresses());
-
-            let send_back_info = IdentifyInfo {
-                public_key: self.local_public_key.clone(),
-                protocol_version: self.protocol_version.clone(),
-                agent_version: self.agent_version.clone(),
-                listen_addrs,
-                protocols,
-            };
-
-            let future = sender.send(send_back_info, &observed);
+        let push_ready = match self.push_timer.as_mut().map(|timer| timer.poll()) {
+            Some(Ok(Async::Ready(()))) => true,
+            Some(Ok(Async::NotReady)) | None => false,
+            Some(Err(err)) => {
+                debug!("Identify push timer errored: {:?}", err);
+                true
+            }
+        };
+        if push_ready {
+            self.push_timer = None;
+            let info = self.local_info(params);
+            for peer_id in self.observed_addresses.keys() {
+                self.events.push_back(NetworkBehaviourAction::SendEvent {
+                    peer_id: peer_id.clone(),
+                    event: EitherOutput::Second(info.clone()),
+                });
+            }
+            if let Some(event) = self.events.pop_front() {
+                return Async::Ready(event);
+            }
+        }
+
+        let to_answer = self.to_answer.drain().collect::<SmallVec<[_; 4]>>();
+        for (peer_id, sender, observed) in to_answer {
+            let future = sender.send(self.local_info(params), &observed);
             self.futures.push((peer_id, future));
         }
 
@@ -221,6 +488,13 @@ pub enum IdentifyEvent {
         /// The error that happened.
         error: ProtocolsHandlerUpgrErr<io::Error>,
     },
+    /// A remote pushed its updated information, e.g. after its addresses changed.
+    Pushed {
+        /// Peer that pushed its information.
+        peer_id: PeerId,
+        /// Information of the remote.
+        info: IdentifyInfo,
+    },
     /// Finished sending back our identification information to a remote.
     SendBack {
         /// Peer that we sent our identification info to.
@@ -232,7 +506,7 @@ pub enum IdentifyEvent {
 
 #[cfg(test)]
 mod tests {
-    use crate::{Identify, IdentifyEvent};
+    use crate::{Identify, IdentifyConfig, IdentifyEvent, IdentifyInfo};
     use futures::{future, prelude::*};
     use libp2p_core::{
         identity,
@@ -244,7 +518,7 @@ mod tests {
     };
     use libp2p_tcp::TcpConfig;
     use libp2p_secio::SecioConfig;
-    use libp2p_swarm::Swarm;
+    use libp2p_swarm::{NetworkBehaviour, Swarm};
     use libp2p_mplex::MplexConfig;
     use rand::Rng;
     use std::{fmt, io};
@@ -337,4 +611,46 @@ mod tests {
             }))
             .unwrap();
     }
+
+    #[test]
+    fn cache_evicts_least_recently_identified() {
+        let pubkey = identity::Keypair::generate_ed25519().public();
+        let mut config = IdentifyConfig::new("a".to_string(), pubkey.clone());
+        config.set_cache_size(2);
+        let mut identify = Identify::<io::Cursor<Vec<u8>>>::with_config(config);
+
+        let info = |agent_version: &str| IdentifyInfo {
+            public_key: pubkey.clone(),
+            protocol_version: "a".to_string(),
+            agent_version: agent_version.to_string(),
+            listen_addrs: vec!["/ip4/127.0.0.1/tcp/1234".parse().unwrap()],
+            protocols: Vec::new(),
+        };
+
+        let peers = (0 .. 3).map(|_| PeerId::random()).collect::<Vec<_>>();
+        identify.cache_info(peers[0].clone(), info("0"));
+        identify.cache_info(peers[1].clone(), info("1"));
+        // Identifying the first peer again makes the second one the oldest.
+        identify.cache_info(peers[0].clone(), info("0bis"));
+        identify.cache_info(peers[2].clone(), info("2"));
+
+        assert_eq!(identify.cached_info(&peers[0]).unwrap().agent_version, "0bis");
+        assert!(identify.cached_info(&peers[1]).is_none());
+        assert_eq!(identify.cached_info(&peers[2]).unwrap().agent_version, "2");
+        assert_eq!(identify.addresses_of_peer(&peers[2]).len(), 1);
+        assert!(identify.addresses_of_peer(&peers[1]).is_empty());
+    }
+
+    #[test]
+    fn address_filter_applies_to_advertised_addresses() {
+        let pubkey = identity::Keypair::generate_ed25519().public();
+        let mut config = IdentifyConfig::new("a".to_string(), pubkey);
+        config.set_address_filter(|addr| !addr.to_string().starts_with("/ip4/127."));
+        let identify = Identify::<io::Cursor<Vec<u8>>>::with_config(config);
+
+        let public: Multiaddr = "/ip4/1.2.3.4/tcp/1234".parse().unwrap();
+        let loopback: Multiaddr = "/ip4/127.0.0.1/tcp/1234".parse().unwrap();
+        let advertised = identify.advertised_addresses(vec![loopback, public.clone()].into_iter());
+        assert_eq!(advertised, vec![public]);
+    }
 }
@@ -65,14 +65,19 @@
 //! a `IdentifySender` struct that can be used to transmit back to the remote the information about
 //! it.
 
-pub use self::identify::{Identify, IdentifyEvent};
+pub use self::identify::{Identify, IdentifyConfig, IdentifyEvent};
 pub use self::id_transport::IdentifyTransport;
 pub use self::protocol::IdentifyInfo;
 
 pub mod listen_handler;
 pub mod periodic_id_handler;
 pub mod protocol;
+pub mod push_handler;
 
 mod identify;
 mod id_transport;
-mod structs_proto;
+
+/// Protobuf messages of the identify protocol, generated from `structs.proto`.
+pub mod structs_proto {
+    include!(concat!(env!("OUT_DIR"), "/structs.rs"));
+}
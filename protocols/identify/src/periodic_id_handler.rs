@@ -34,9 +34,9 @@ use wasm_timer::{Delay, Instant};
 use void::{Void, unreachable};
 
 /// Delay between the moment we connect and the first time we identify.
-const DELAY_TO_FIRST_ID: Duration = Duration::from_millis(500);
+pub(crate) const DELAY_TO_FIRST_ID: Duration = Duration::from_millis(500);
 /// After an identification succeeded, wait this long before the next time.
-const DELAY_TO_NEXT_ID: Duration = Duration::from_secs(5 * 60);
+pub(crate) const DELAY_TO_NEXT_ID: Duration = Duration::from_secs(5 * 60);
 /// After we failed to identify the remote, try again after the given delay.
 const TRY_AGAIN_ON_ERR: Duration = Duration::from_secs(60 * 60);
 
@@ -52,6 +52,9 @@ pub struct PeriodicIdHandler<TSubstream> {
     /// Future that fires when we need to identify the node again.
     next_id: Delay,
 
+    /// Delay between two identifications of the remote.
+    interval: Duration,
+
     /// If `true`, we have started an identification of the remote at least once in the past.
     first_id_happened: bool,
 
@@ -72,10 +75,17 @@ impl<TSubstream> PeriodicIdHandler<TSubstream> {
     /// Builds a new `PeriodicIdHandler`.
     #[inline]
     pub fn new() -> Self {
+        PeriodicIdHandler::with_interval(DELAY_TO_FIRST_ID, DELAY_TO_NEXT_ID)
+    }
+
+    /// Builds a new `PeriodicIdHandler` that identifies the remote after `initial_delay`,
+    /// then every `interval` for as long as the connection is open.
+    pub fn with_interval(initial_delay: Duration, interval: Duration) -> Self {
         PeriodicIdHandler {
             config: IdentifyProtocolConfig,
             pending_result: None,
-            next_id: Delay::new(Instant::now() + DELAY_TO_FIRST_ID),
+            next_id: Delay::new(Instant::now() + initial_delay),
+            interval,
             first_id_happened: false,
             marker: PhantomData,
         }
@@ -151,7 +161,7 @@ where
         match self.next_id.poll()? {
             Async::NotReady => Ok(Async::NotReady),
             Async::Ready(()) => {
-                self.next_id.reset(Instant::now() + DELAY_TO_NEXT_ID);
+                self.next_id.reset(Instant::now() + self.interval);
                 let ev = ProtocolsHandlerEvent::OutboundSubstreamRequest {
                     protocol: SubstreamProtocol::new(self.config.clone()),
                     info: (),
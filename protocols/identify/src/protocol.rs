@@ -18,25 +18,23 @@
 // FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
 // DEALINGS IN THE SOFTWARE.
 
-use bytes::BytesMut;
 use crate::structs_proto;
-use futures::{future::{self, FutureResult}, Async, AsyncSink, Future, Poll, Sink, Stream};
+use futures::{future::{self, FutureResult}, Async, Future, Poll};
 use futures::try_ready;
 use libp2p_core::{
     Multiaddr,
     PublicKey,
-    upgrade::{InboundUpgrade, OutboundUpgrade, UpgradeInfo, Negotiated}
+    upgrade::{self, InboundUpgrade, OutboundUpgrade, UpgradeInfo, Negotiated}
 };
 use log::{debug, trace};
-use protobuf::Message as ProtobufMessage;
-use protobuf::parse_from_bytes as protobuf_parse_from_bytes;
-use protobuf::RepeatedField;
+use prost::Message;
 use std::convert::TryFrom;
 use std::io::{Error as IoError, ErrorKind as IoErrorKind};
 use std::iter;
-use tokio_codec::Framed;
 use tokio_io::{AsyncRead, AsyncWrite};
-use unsigned_varint::codec;
+
+/// Maximum size in bytes of an identify message.
+const MAX_MESSAGE_SIZE: usize = 4096;
 
 /// Configuration for an upgrade to the identity protocol.
 #[derive(Debug, Clone)]
@@ -54,7 +52,7 @@ pub struct RemoteInfo {
 
 /// Object used to send back information to the client.
 pub struct IdentifySender<T> {
-    inner: Framed<T, codec::UviBytes<Vec<u8>>>,
+    inner: T,
 }
 
 impl<T> IdentifySender<T> where T: AsyncWrite {
@@ -64,43 +62,17 @@ impl<T> IdentifySender<T> where T: AsyncWrite {
         debug!("Sending identify info to client");
         trace!("Sending: {:?}", info);
 
-        let listen_addrs = info.listen_addrs
-            .into_iter()
-            .map(|addr| addr.to_vec())
-            .collect();
-
-        let pubkey_bytes = info.public_key.into_protobuf_encoding();
-
-        let mut message = structs_proto::Identify::new();
-        message.set_agentVersion(info.agent_version);
-        message.set_protocolVersion(info.protocol_version);
-        message.set_publicKey(pubkey_bytes);
-        message.set_listenAddrs(listen_addrs);
-        message.set_observedAddr(observed_addr.to_vec());
-        message.set_protocols(RepeatedField::from_vec(info.protocols));
-
-        let bytes = message
-            .write_to_bytes()
-            .expect("writing protobuf failed; should never happen");
-
         IdentifySenderFuture {
-            inner: self.inner,
-            item: Some(bytes),
+            inner: upgrade::write_one(self.inner, encode_info(info, Some(observed_addr))),
         }
     }
 }
 
 /// Future returned by `IdentifySender::send()`. Must be processed to the end in order to send
 /// the information to the remote.
-// Note: we don't use a `futures::sink::Sink` because it requires `T` to implement `Sink`, which
-//       means that we would require `T: AsyncWrite` in this struct definition. This requirement
-//       would then propagate everywhere.
 #[must_use = "futures do nothing unless polled"]
 pub struct IdentifySenderFuture<T> {
-    /// The Sink where to send the data.
-    inner: Framed<T, codec::UviBytes<Vec<u8>>>,
-    /// Bytes to send, or `None` if we've already sent them.
-    item: Option<Vec<u8>>,
+    inner: upgrade::WriteOne<T>,
 }
 
 impl<T> Future for IdentifySenderFuture<T>
@@ -110,16 +82,7 @@ where T: AsyncWrite
     type Error = IoError;
 
     fn poll(&mut self) -> Poll<Self::Item, Self::Error> {
-        if let Some(item) = self.item.take() {
-            if let AsyncSink::NotReady(item) = self.inner.start_send(item)? {
-                self.item = Some(item);
-                return Ok(Async::NotReady);
-            }
-        }
-
-        // A call to `close()` implies flushing.
-        try_ready!(self.inner.close());
-        Ok(Async::Ready(()))
+        self.inner.poll()
     }
 }
 
@@ -139,6 +102,15 @@ pub struct IdentifyInfo {
     pub protocols: Vec<String>,
 }
 
+/// Configuration for an upgrade to the identify push protocol, with which remotes send us
+/// their updated information.
+#[derive(Debug, Clone)]
+pub struct IdentifyPushProtocolConfig;
+
+/// Upgrade that pushes the information of the local node to a remote.
+#[derive(Debug, Clone)]
+pub struct IdentifyPush(pub IdentifyInfo);
+
 impl UpgradeInfo for IdentifyProtocolConfig {
     type Info = &'static [u8];
     type InfoIter = iter::Once<Self::Info>;
@@ -158,7 +130,6 @@ where
 
     fn upgrade_inbound(self, socket: Negotiated<C>, _: Self::Info) -> Self::Future {
         trace!("Upgrading inbound connection");
-        let socket = Framed::new(socket, codec::UviBytes::default());
         let sender = IdentifySender { inner: socket };
         future::ok(sender)
     }
@@ -174,17 +145,18 @@ where
 
     fn upgrade_outbound(self, socket: Negotiated<C>, _: Self::Info) -> Self::Future {
         IdentifyOutboundFuture {
-            inner: Framed::new(socket, codec::UviBytes::<BytesMut>::default()),
-            shutdown: false,
+            shutdown: Some(tokio_io::io::shutdown(socket)),
+            read: None,
         }
     }
 }
 
 /// Future returned by `OutboundUpgrade::upgrade_outbound`.
 pub struct IdentifyOutboundFuture<T> {
-    inner: Framed<T, codec::UviBytes<BytesMut>>,
-    /// If true, we have finished shutting down the writing part of `inner`.
-    shutdown: bool,
+    /// Shuts down the writing side of the substream. `None` once done.
+    shutdown: Option<tokio_io::io::Shutdown<T>>,
+    /// Reads the information sent by the remote, once the substream has been shut down.
+    read: Option<upgrade::ReadOneThen<T, (), fn(Vec<u8>, ()) -> Result<RemoteInfo, IoError>>>,
 }
 
 impl<T> Future for IdentifyOutboundFuture<T>
@@ -194,45 +166,137 @@ where T: AsyncRead + AsyncWrite,
     type Error = IoError;
 
     fn poll(&mut self) -> Poll<Self::Item, Self::Error> {
-        if !self.shutdown {
-            try_ready!(self.inner.close());
-            self.shutdown = true;
+        if let Some(shutdown) = self.shutdown.as_mut() {
+            let socket = try_ready!(shutdown.poll());
+            self.shutdown = None;
+            self.read = Some(upgrade::read_one_then(socket, MAX_MESSAGE_SIZE, (), parse_remote_info as fn(_, _) -> _));
         }
 
-        let msg = match try_ready!(self.inner.poll()) {
-            Some(i) => i,
-            None => {
-                debug!("Identify protocol stream closed before receiving info");
-                return Err(IoErrorKind::InvalidData.into());
-            }
-        };
+        self.read.as_mut()
+            .expect("The substream has been shut down, therefore the read is in progress; qed")
+            .poll()
+    }
+}
 
-        debug!("Received identify message");
+impl UpgradeInfo for IdentifyPushProtocolConfig {
+    type Info = &'static [u8];
+    type InfoIter = iter::Once<Self::Info>;
 
-        let (info, observed_addr) = match parse_proto_msg(msg) {
-            Ok(v) => v,
-            Err(err) => {
-                debug!("Failed to parse protobuf message; error = {:?}", err);
-                return Err(err)
-            }
-        };
+    fn protocol_info(&self) -> Self::InfoIter {
+        iter::once(b"/ipfs/id/push/1.0.0")
+    }
+}
 
-        trace!("Remote observes us as {:?}", observed_addr);
-        trace!("Information received: {:?}", info);
+impl<C> InboundUpgrade<C> for IdentifyPushProtocolConfig
+where
+    C: AsyncRead + AsyncWrite,
+{
+    type Output = IdentifyInfo;
+    type Error = IoError;
+    type Future = upgrade::ReadOneThen<Negotiated<C>, (), fn(Vec<u8>, ()) -> Result<IdentifyInfo, IoError>>;
 
-        Ok(Async::Ready(RemoteInfo {
-            info,
-            observed_addr: observed_addr.clone(),
-            _priv: ()
-        }))
+    fn upgrade_inbound(self, socket: Negotiated<C>, _: Self::Info) -> Self::Future {
+        upgrade::read_one_then(socket, MAX_MESSAGE_SIZE, (), parse_pushed_info as fn(_, _) -> _)
     }
 }
 
-// Turns a protobuf message into an `IdentifyInfo` and an observed address. If something bad
-// happens, turn it into an `IoError`.
-fn parse_proto_msg(msg: BytesMut) -> Result<(IdentifyInfo, Multiaddr), IoError> {
-    match protobuf_parse_from_bytes::<structs_proto::Identify>(&msg) {
-        Ok(mut msg) => {
+impl UpgradeInfo for IdentifyPush {
+    type Info = &'static [u8];
+    type InfoIter = iter::Once<Self::Info>;
+
+    fn protocol_info(&self) -> Self::InfoIter {
+        iter::once(b"/ipfs/id/push/1.0.0")
+    }
+}
+
+impl<C> OutboundUpgrade<C> for IdentifyPush
+where
+    C: AsyncRead + AsyncWrite,
+{
+    type Output = ();
+    type Error = IoError;
+    type Future = upgrade::WriteOne<Negotiated<C>>;
+
+    fn upgrade_outbound(self, socket: Negotiated<C>, _: Self::Info) -> Self::Future {
+        debug!("Pushing identify info to remote");
+        trace!("Pushing: {:?}", self.0);
+        upgrade::write_one(socket, encode_info(self.0, None))
+    }
+}
+
+/// Turns an `IdentifyInfo` into a protobuf message. The observed address is omitted when
+/// pushing information, as it is only relevant in an answer.
+fn encode_info(info: IdentifyInfo, observed_addr: Option<&Multiaddr>) -> Vec<u8> {
+    let listen_addrs = info.listen_addrs
+        .into_iter()
+        .map(|addr| addr.to_vec())
+        .collect();
+
+    let pubkey_bytes = info.public_key.into_protobuf_encoding();
+
+    let message = structs_proto::Identify {
+        agent_version: Some(info.agent_version),
+        protocol_version: Some(info.protocol_version),
+        public_key: Some(pubkey_bytes),
+        listen_addrs,
+        observed_addr: observed_addr.map(|addr| addr.to_vec()),
+        protocols: info.protocols,
+    };
+
+    let mut bytes = Vec::with_capacity(message.encoded_len());
+    message
+        .encode(&mut bytes)
+        .expect("Vec<u8> provides capacity as needed");
+    bytes
+}
+
+/// Parses the message pushed by a remote into an `IdentifyInfo`.
+fn parse_pushed_info(msg: Vec<u8>, (): ()) -> Result<IdentifyInfo, IoError> {
+    debug!("Received pushed identify message");
+
+    match parse_proto_msg(&msg) {
+        Ok((info, _)) => {
+            trace!("Information pushed: {:?}", info);
+            Ok(info)
+        }
+        Err(err) => {
+            debug!("Failed to parse protobuf message; error = {:?}", err);
+            Err(err)
+        }
+    }
+}
+
+/// Parses the message sent by the remote into a `RemoteInfo`.
+fn parse_remote_info(msg: Vec<u8>, (): ()) -> Result<RemoteInfo, IoError> {
+    debug!("Received identify message");
+
+    let (info, observed_addr) = match parse_proto_msg(&msg) {
+        Ok((info, Some(observed_addr))) => (info, observed_addr),
+        Ok((_, None)) => {
+            debug!("Identify message without observed address");
+            return Err(IoError::new(IoErrorKind::InvalidData, "missing observed address"))
+        }
+        Err(err) => {
+            debug!("Failed to parse protobuf message; error = {:?}", err);
+            return Err(err)
+        }
+    };
+
+    trace!("Remote observes us as {:?}", observed_addr);
+    trace!("Information received: {:?}", info);
+
+    Ok(RemoteInfo {
+        info,
+        observed_addr,
+        _priv: ()
+    })
+}
+
+// Turns a protobuf message into an `IdentifyInfo` and an observed address, if any. If something
+// bad happens, turn it into an `IoError`.
+fn parse_proto_msg(msg: &[u8]) -> Result<(IdentifyInfo, Option<Multiaddr>), IoError> {
+    match structs_proto::Identify::decode(msg) {
+        Ok(msg) => {
             // Turn a `Vec<u8>` into a `Multiaddr`. If something bad happens, turn it into
             // an `IoError`.
             fn bytes_to_multiaddr(bytes: Vec<u8>) -> Result<Multiaddr, IoError> {
@@ -242,22 +306,25 @@ fn parse_proto_msg(msg: BytesMut) -> Result<(IdentifyInfo, Multiaddr), IoError>
 
             let listen_addrs = {
                 let mut addrs = Vec::new();
-                for addr in msg.take_listenAddrs().into_iter() {
+                for addr in msg.listen_addrs.into_iter() {
                     addrs.push(bytes_to_multiaddr(addr)?);
                 }
                 addrs
             };
 
-            let public_key = PublicKey::from_protobuf_encoding(msg.get_publicKey())
+            let public_key = PublicKey::from_protobuf_encoding(&msg.public_key.unwrap_or_default())
                 .map_err(|e| IoError::new(IoErrorKind::InvalidData, e))?;
 
-            let observed_addr = bytes_to_multiaddr(msg.take_observedAddr())?;
+            let observed_addr = match msg.observed_addr {
+                Some(addr) => Some(bytes_to_multiaddr(addr)?),
+                None => None,
+            };
             let info = IdentifyInfo {
                 public_key,
-                protocol_version: msg.take_protocolVersion(),
-                agent_version: msg.take_agentVersion(),
+                protocol_version: msg.protocol_version.unwrap_or_default(),
+                agent_version: msg.agent_version.unwrap_or_default(),
                 listen_addrs,
-                protocols: msg.take_protocols().into_vec(),
+                protocols: msg.protocols,
             };
 
             Ok((info, observed_addr))
@@ -269,7 +336,13 @@ fn parse_proto_msg(msg: BytesMut) -> Result<(IdentifyInfo, Multiaddr), IoError>
 
 #[cfg(test)]
 mod tests {
-    use crate::protocol::{IdentifyInfo, RemoteInfo, IdentifyProtocolConfig};
+    use crate::protocol::{
+        IdentifyInfo,
+        IdentifyPush,
+        IdentifyPushProtocolConfig,
+        RemoteInfo,
+        IdentifyProtocolConfig
+    };
     use tokio::runtime::current_thread::Runtime;
     use libp2p_tcp::TcpConfig;
     use futures::{Future, Stream};
@@ -359,4 +432,71 @@ mod tests {
         let _ = rt.block_on(future).unwrap();
         bg_thread.join().unwrap();
     }
+
+    #[test]
+    fn correct_push_transfer() {
+        // The client pushes its info to the server, which checks that it was successfully
+        // received.
+        let send_pubkey = identity::Keypair::generate_ed25519().public();
+        let recv_pubkey = send_pubkey.clone();
+
+        let (tx, rx) = mpsc::channel();
+
+        let bg_thread = thread::spawn(move || {
+            let transport = TcpConfig::new();
+
+            let mut listener = transport
+                .listen_on("/ip4/127.0.0.1/tcp/0".parse().unwrap())
+                .unwrap();
+
+            let addr = listener.by_ref().wait()
+                .next()
+                .expect("some event")
+                .expect("no error")
+                .into_new_address()
+                .expect("listen address");
+
+            tx.send(addr).unwrap();
+
+            let future = listener
+                .filter_map(ListenerEvent::into_upgrade)
+                .into_future()
+                .map_err(|(err, _)| err)
+                .and_then(|(client, _)| client.unwrap().0)
+                .and_then(|socket| {
+                    apply_inbound(socket, IdentifyPushProtocolConfig)
+                        .map_err(|e| io::Error::new(io::ErrorKind::Other, e))
+                })
+                .and_then(|info| {
+                    assert_eq!(info.public_key, recv_pubkey);
+                    assert_eq!(info.protocol_version, "proto_version");
+                    assert_eq!(info.agent_version, "agent_version");
+                    assert_eq!(info.listen_addrs, &["/ip4/80.81.82.83/tcp/500".parse().unwrap()]);
+                    assert_eq!(info.protocols, &["proto1".to_string()]);
+                    Ok(())
+                });
+            let mut rt = Runtime::new().unwrap();
+            let _ = rt.block_on(future).unwrap();
+        });
+
+        let transport = TcpConfig::new();
+
+        let future = transport.dial(rx.recv().unwrap())
+            .unwrap()
+            .and_then(|socket| {
+                let info = IdentifyInfo {
+                    public_key: send_pubkey,
+                    protocol_version: "proto_version".to_owned(),
+                    agent_version: "agent_version".to_owned(),
+                    listen_addrs: vec!["/ip4/80.81.82.83/tcp/500".parse().unwrap()],
+                    protocols: vec!["proto1".to_string()],
+                };
+                apply_outbound(socket, IdentifyPush(info))
+                    .map_err(|e| io::Error::new(io::ErrorKind::Other, e))
+            });
+
+        let mut rt = Runtime::new().unwrap();
+        let _ = rt.block_on(future).unwrap();
+        bg_thread.join().unwrap();
+    }
 }
@@ -0,0 +1,158 @@
+// Copyright 2019 Parity Technologies (UK) Ltd.
+//
+// Permission is hereby granted, free of charge, to any person obtaining a
+// copy of this software and associated documentation files (the "Software"),
+// to deal in the Software without restriction, including without limitation
+// the rights to use, copy, modify, merge, publish, distribute, sublicense,
+// and/or sell copies of the Software, and to permit persons to whom the
+// Software is furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS
+// OR IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING
+// FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
+// DEALINGS IN THE SOFTWARE.
+
+use crate::protocol::{IdentifyInfo, IdentifyPush, IdentifyPushProtocolConfig};
+use futures::prelude::*;
+use libp2p_core::upgrade::{InboundUpgrade, OutboundUpgrade};
+use libp2p_swarm::{
+    KeepAlive,
+    SubstreamProtocol,
+    ProtocolsHandler,
+    ProtocolsHandlerEvent,
+    ProtocolsHandlerUpgrErr
+};
+use smallvec::SmallVec;
+use std::{io, marker::PhantomData};
+use tokio_io::{AsyncRead, AsyncWrite};
+use void::Void;
+
+/// Protocol handler that pushes the information of the local node to the remote, and receives
+/// the information pushed by the remote.
+pub struct IdentifyPushHandler<TSubstream> {
+    /// Information waiting for an outbound substream to be pushed.
+    pending_pushes: SmallVec<[IdentifyInfo; 1]>,
+
+    /// Number of pushes whose outbound substream is being opened or written to.
+    ongoing_pushes: usize,
+
+    /// Events to yield to the user.
+    pending_results: SmallVec<[IdentifyPushHandlerEvent; 4]>,
+
+    /// Marker for strong typing.
+    marker: PhantomData<TSubstream>,
+}
+
+/// Event produced by the push handler.
+#[derive(Debug)]
+pub enum IdentifyPushHandlerEvent {
+    /// The remote pushed its updated information.
+    Received(IdentifyInfo),
+    /// Our information has been pushed to the remote.
+    Pushed,
+    /// Failed to push our information to the remote, for example because it doesn't support
+    /// the push protocol.
+    PushError(ProtocolsHandlerUpgrErr<io::Error>),
+}
+
+impl<TSubstream> IdentifyPushHandler<TSubstream> {
+    /// Builds a new `IdentifyPushHandler`.
+    #[inline]
+    pub fn new() -> Self {
+        IdentifyPushHandler {
+            pending_pushes: SmallVec::new(),
+            ongoing_pushes: 0,
+            pending_results: SmallVec::new(),
+            marker: PhantomData,
+        }
+    }
+}
+
+impl<TSubstream> ProtocolsHandler for IdentifyPushHandler<TSubstream>
+where
+    TSubstream: AsyncRead + AsyncWrite,
+{
+    type InEvent = IdentifyInfo;
+    type OutEvent = IdentifyPushHandlerEvent;
+    type Error = Void;
+    type Substream = TSubstream;
+    type InboundProtocol = IdentifyPushProtocolConfig;
+    type OutboundProtocol = IdentifyPush;
+    type OutboundOpenInfo = ();
+
+    #[inline]
+    fn listen_protocol(&self) -> SubstreamProtocol<Self::InboundProtocol> {
+        SubstreamProtocol::new(IdentifyPushProtocolConfig)
+    }
+
+    fn inject_fully_negotiated_inbound(
+        &mut self,
+        info: <Self::InboundProtocol as InboundUpgrade<TSubstream>>::Output
+    ) {
+        self.pending_results.push(IdentifyPushHandlerEvent::Received(info))
+    }
+
+    fn inject_fully_negotiated_outbound(
+        &mut self,
+        (): <Self::OutboundProtocol as OutboundUpgrade<TSubstream>>::Output,
+        _: Self::OutboundOpenInfo
+    ) {
+        self.ongoing_pushes -= 1;
+        self.pending_results.push(IdentifyPushHandlerEvent::Pushed)
+    }
+
+    #[inline]
+    fn inject_event(&mut self, info: Self::InEvent) {
+        // Only the most recent information is worth pushing.
+        self.pending_pushes.clear();
+        self.pending_pushes.push(info);
+    }
+
+    #[inline]
+    fn inject_dial_upgrade_error(&mut self, _: Self::OutboundOpenInfo, err: ProtocolsHandlerUpgrErr<<Self::OutboundProtocol as OutboundUpgrade<Self::Substream>>::Error>) {
+        self.ongoing_pushes -= 1;
+        self.pending_results.push(IdentifyPushHandlerEvent::PushError(err))
+    }
+
+    #[inline]
+    fn connection_keep_alive(&self) -> KeepAlive {
+        if self.pending_pushes.is_empty() && self.ongoing_pushes == 0 {
+            KeepAlive::No
+        } else {
+            KeepAlive::Yes
+        }
+    }
+
+    fn poll(
+        &mut self,
+    ) -> Poll<
+        ProtocolsHandlerEvent<
+            Self::OutboundProtocol,
+            Self::OutboundOpenInfo,
+            Self::OutEvent,
+        >,
+        Self::Error,
+    > {
+        if !self.pending_results.is_empty() {
+            return Ok(Async::Ready(ProtocolsHandlerEvent::Custom(
+                self.pending_results.remove(0),
+            )));
+        }
+
+        if let Some(info) = self.pending_pushes.pop() {
+            self.ongoing_pushes += 1;
+            return Ok(Async::Ready(ProtocolsHandlerEvent::OutboundSubstreamRequest {
+                protocol: SubstreamProtocol::new(IdentifyPush(info)),
+                info: (),
+            }));
+        }
+
+        Ok(Async::NotReady)
+    }
+}
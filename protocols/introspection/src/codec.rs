@@ -0,0 +1,304 @@
+// Copyright 2019 Parity Technologies (UK) Ltd.
+//
+// Permission is hereby granted, free of charge, to any person obtaining a
+// copy of this software and associated documentation files (the "Software"),
+// to deal in the Software without restriction, including without limitation
+// the rights to use, copy, modify, merge, publish, distribute, sublicense,
+// and/or sell copies of the Software, and to permit persons to whom the
+// Software is furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS
+// OR IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING
+// FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
+// DEALINGS IN THE SOFTWARE.
+
+//! Snapshots of the state of a node and their encoding, as described in `introspection.proto`.
+
+use crate::proto;
+use libp2p_core::{Multiaddr, PeerId};
+use libp2p_request_response::RequestResponseCodec;
+use prost::{DecodeError, Message};
+use std::{convert::TryFrom, io, time::{Duration, SystemTime, UNIX_EPOCH}};
+
+/// Name of the protocol negotiated on the substreams.
+pub const PROTOCOL_NAME: &[u8] = b"/libp2p/introspection/1.0.0";
+
+/// Snapshot of the state of a node.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct State {
+    /// When the snapshot was taken.
+    pub timestamp: SystemTime,
+    /// Identity of the node.
+    pub peer_id: PeerId,
+    /// Addresses the node is listening on.
+    pub listen_addrs: Vec<Multiaddr>,
+    /// The established connections of the node.
+    pub connections: Vec<Connection>,
+    /// Traffic of all the connections, if known.
+    pub traffic: Option<Traffic>,
+    /// State of the DHT, if the node takes part in one.
+    pub dht: Option<DhtState>,
+}
+
+/// An established connection.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Connection {
+    /// Identifier of the connection within the node.
+    pub id: String,
+    /// Identity of the remote.
+    pub peer_id: PeerId,
+    /// Whether the node dialed the connection or accepted it.
+    pub role: Role,
+    /// Address of the remote.
+    pub remote_addr: Multiaddr,
+    /// Address of the listener that accepted the connection, if the node is the responder.
+    pub local_addr: Option<Multiaddr>,
+    /// When the connection was established.
+    pub opened: SystemTime,
+    /// Traffic of the connection, if known.
+    pub traffic: Option<Traffic>,
+}
+
+/// Which side of a connection opened it.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Role {
+    /// The node dialed the connection.
+    Initiator,
+    /// The node accepted the connection.
+    Responder,
+}
+
+/// Amount of data transferred.
+#[derive(Debug, Copy, Clone, Default, PartialEq, Eq)]
+pub struct Traffic {
+    /// Total number of bytes received.
+    pub total_in: u64,
+    /// Total number of bytes sent.
+    pub total_out: u64,
+    /// Number of bytes received per second, averaged over a short period.
+    pub rate_in: u64,
+    /// Number of bytes sent per second, averaged over a short period.
+    pub rate_out: u64,
+}
+
+/// State of the DHT.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct DhtState {
+    /// Name of the protocol of the DHT.
+    pub protocol: String,
+    /// Number of peers in each k-bucket, from the closest to the farthest.
+    pub buckets: Vec<u32>,
+    /// Number of records stored by the node.
+    pub records: u64,
+}
+
+impl State {
+    /// Encodes the snapshot, without its length prefix.
+    pub fn encode(&self) -> Vec<u8> {
+        let message = proto::State {
+            timestamp: to_millis(self.timestamp),
+            peer_id: self.peer_id.as_bytes().to_vec(),
+            listen_addrs: self.listen_addrs.iter().map(|addr| addr.to_vec()).collect(),
+            connections: self.connections.iter().map(Connection::to_proto).collect(),
+            traffic: self.traffic.as_ref().map(Traffic::to_proto),
+            dht: self.dht.as_ref().map(DhtState::to_proto),
+        };
+        let mut buf = Vec::with_capacity(message.encoded_len());
+        message.encode(&mut buf).expect("Vec<u8> provides capacity as needed");
+        buf
+    }
+
+    /// Decodes a snapshot, without its length prefix.
+    pub fn decode(bytes: &[u8]) -> Result<Self, DecodeError> {
+        let message = proto::State::decode(bytes)?;
+        Ok(State {
+            timestamp: from_millis(message.timestamp),
+            peer_id: decode_peer_id(message.peer_id)?,
+            listen_addrs: message.listen_addrs
+                .into_iter()
+                .filter_map(|addr| Multiaddr::try_from(addr).ok())
+                .collect(),
+            connections: message.connections
+                .into_iter()
+                .map(Connection::from_proto)
+                .collect::<Result<_, _>>()?,
+            traffic: message.traffic.map(Traffic::from_proto),
+            dht: message.dht.map(DhtState::from_proto),
+        })
+    }
+}
+
+impl Connection {
+    fn to_proto(&self) -> proto::Connection {
+        let role = match self.role {
+            Role::Initiator => proto::connection::Role::Initiator,
+            Role::Responder => proto::connection::Role::Responder,
+        };
+        proto::Connection {
+            id: self.id.clone(),
+            peer_id: self.peer_id.as_bytes().to_vec(),
+            role: role as i32,
+            remote_addr: self.remote_addr.to_vec(),
+            local_addr: self.local_addr.as_ref().map(|addr| addr.to_vec()).unwrap_or_default(),
+            opened: to_millis(self.opened),
+            traffic: self.traffic.as_ref().map(Traffic::to_proto),
+        }
+    }
+
+    fn from_proto(connection: proto::Connection) -> Result<Self, DecodeError> {
+        let role = match proto::connection::Role::from_i32(connection.role) {
+            Some(proto::connection::Role::Initiator) => Role::Initiator,
+            Some(proto::connection::Role::Responder) => Role::Responder,
+            _ => return Err(DecodeError::new("missing or unknown role")),
+        };
+        let local_addr = if connection.local_addr.is_empty() {
+            None
+        } else {
+            Some(decode_multiaddr(connection.local_addr)?)
+        };
+        Ok(Connection {
+            id: connection.id,
+            peer_id: decode_peer_id(connection.peer_id)?,
+            role,
+            remote_addr: decode_multiaddr(connection.remote_addr)?,
+            local_addr,
+            opened: from_millis(connection.opened),
+            traffic: connection.traffic.map(Traffic::from_proto),
+        })
+    }
+}
+
+impl Traffic {
+    fn to_proto(&self) -> proto::Traffic {
+        proto::Traffic {
+            total_in: self.total_in,
+            total_out: self.total_out,
+            rate_in: self.rate_in,
+            rate_out: self.rate_out,
+        }
+    }
+
+    fn from_proto(traffic: proto::Traffic) -> Self {
+        Traffic {
+            total_in: traffic.total_in,
+            total_out: traffic.total_out,
+            rate_in: traffic.rate_in,
+            rate_out: traffic.rate_out,
+        }
+    }
+}
+
+impl DhtState {
+    fn to_proto(&self) -> proto::Dht {
+        proto::Dht {
+            protocol: self.protocol.clone(),
+            buckets: self.buckets.clone(),
+            records: self.records,
+        }
+    }
+
+    fn from_proto(dht: proto::Dht) -> Self {
+        DhtState {
+            protocol: dht.protocol,
+            buckets: dht.buckets,
+            records: dht.records,
+        }
+    }
+}
+
+fn decode_peer_id(bytes: Vec<u8>) -> Result<PeerId, DecodeError> {
+    if bytes.is_empty() {
+        return Err(DecodeError::new("missing peer ID"));
+    }
+    PeerId::from_bytes(bytes).map_err(|_| DecodeError::new("invalid peer ID"))
+}
+
+fn decode_multiaddr(bytes: Vec<u8>) -> Result<Multiaddr, DecodeError> {
+    if bytes.is_empty() {
+        return Err(DecodeError::new("missing multiaddress"));
+    }
+    Multiaddr::try_from(bytes).map_err(|_| DecodeError::new("invalid multiaddress"))
+}
+
+fn to_millis(time: SystemTime) -> u64 {
+    time.duration_since(UNIX_EPOCH).map(|d| d.as_millis() as u64).unwrap_or(0)
+}
+
+fn from_millis(millis: u64) -> SystemTime {
+    UNIX_EPOCH + Duration::from_millis(millis)
+}
+
+/// Codec of the introspection protocol, used with the `RequestResponse` behaviour.
+///
+/// Requests are empty `StateRequest` messages, and responses are `State` snapshots.
+#[derive(Debug, Copy, Clone, Default)]
+pub struct IntrospectionCodec;
+
+impl RequestResponseCodec for IntrospectionCodec {
+    type Request = ();
+    type Response = State;
+
+    fn encode_request(&self, _: ()) -> Vec<u8> {
+        Vec::new()
+    }
+
+    fn decode_request(&self, bytes: Vec<u8>) -> Result<(), io::Error> {
+        // Validate the message, even though `StateRequest` has no field yet.
+        proto::StateRequest::decode(&bytes)
+            .map(|_| ())
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+    }
+
+    fn encode_response(&self, response: State) -> Vec<u8> {
+        response.encode()
+    }
+
+    fn decode_response(&self, bytes: Vec<u8>) -> Result<State, io::Error> {
+        State::decode(&bytes).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use libp2p_core::multiaddr::multiaddr;
+
+    #[test]
+    fn encode_decode_roundtrip() {
+        let peer_id = PeerId::random();
+        let state = State {
+            timestamp: from_millis(1_571_140_800_123),
+            peer_id: PeerId::random(),
+            listen_addrs: vec![multiaddr![Ip4([0, 0, 0, 0]), Tcp(4001u16)]],
+            connections: vec![
+                Connection {
+                    id: "3".to_owned(),
+                    peer_id: peer_id.clone(),
+                    role: Role::Initiator,
+                    remote_addr: multiaddr![Ip4([1, 2, 3, 4]), Tcp(4001u16)],
+                    local_addr: None,
+                    opened: from_millis(1_571_140_000_000),
+                    traffic: Some(Traffic { total_in: 10, total_out: 20, rate_in: 1, rate_out: 2 }),
+                },
+                Connection {
+                    id: "4".to_owned(),
+                    peer_id,
+                    role: Role::Responder,
+                    remote_addr: multiaddr![Ip4([1, 2, 3, 4]), Tcp(53412u16)],
+                    local_addr: Some(multiaddr![Ip4([0, 0, 0, 0]), Tcp(4001u16)]),
+                    opened: from_millis(1_571_140_500_000),
+                    traffic: None,
+                },
+            ],
+            traffic: Some(Traffic { total_in: 1000, total_out: 2000, rate_in: 0, rate_out: 0 }),
+            dht: Some(DhtState { protocol: "/ipfs/kad/1.0.0".to_owned(), buckets: vec![0, 3, 20], records: 5 }),
+        };
+        assert_eq!(State::decode(&state.encode()), Ok(state));
+    }
+}
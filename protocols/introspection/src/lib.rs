@@ -0,0 +1,388 @@
+// Copyright 2019 Parity Technologies (UK) Ltd.
+//
+// Permission is hereby granted, free of charge, to any person obtaining a
+// copy of this software and associated documentation files (the "Software"),
+// to deal in the Software without restriction, including without limitation
+// the rights to use, copy, modify, merge, publish, distribute, sublicense,
+// and/or sell copies of the Software, and to permit persons to whom the
+// Software is furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS
+// OR IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING
+// FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
+// DEALINGS IN THE SOFTWARE.
+
+//! Implementation of an introspection protocol, serving snapshots of the internal state of a
+//! node so that external dashboards can visualize it while it runs.
+//!
+//! A snapshot, described in `introspection.proto`, contains the addresses the node listens on,
+//! its established connections, the traffic going through them and the state of the DHT. The
+//! dashboard asks for a snapshot by sending an empty request on a substream of the
+//! `/libp2p/introspection/1.0.0` protocol, and typically does so periodically.
+//!
+//! # Usage
+//!
+//! The [`Introspection`] struct implements the [`NetworkBehaviour`] trait. It keeps track of
+//! the connections of the `Swarm` and answers the requests of the peers allowed by its
+//! [`IntrospectionConfig`]. No peer is allowed by default, as snapshots reveal a lot about the
+//! node. The traffic and the DHT state aren't known to the `Swarm`: they are added to the
+//! snapshots by the [`StateProvider`]s registered with [`Introspection::add_provider`].
+//!
+//! On the dashboard side, [`Introspection::watch`] requests a snapshot from a peer at a regular
+//! interval, and the snapshots are reported as [`IntrospectionEvent::State`].
+//!
+//! [`NetworkBehaviour`]: libp2p_swarm::NetworkBehaviour
+
+mod codec;
+
+/// Protobuf messages of the protocol, generated from `introspection.proto`.
+mod proto {
+    include!(concat!(env!("OUT_DIR"), "/introspection.pb.rs"));
+}
+
+pub use codec::{Connection, DhtState, IntrospectionCodec, Role, State, Traffic, PROTOCOL_NAME};
+pub use prost::DecodeError;
+
+use futures::prelude::*;
+use libp2p_core::{ConnectedPoint, Multiaddr, PeerId};
+use libp2p_request_response::{
+    OutboundFailure,
+    RequestProtocol,
+    RequestResponse,
+    RequestResponseConfig,
+    RequestResponseEvent,
+    RequestResponseHandler,
+    RequestResponseHandlerEvent,
+    RequestResponseMessage,
+    ResponseChannel
+};
+use libp2p_swarm::{ConnectionId, NetworkBehaviour, NetworkBehaviourAction, PollParameters};
+use std::{collections::{HashMap, HashSet, VecDeque}, error, iter, sync::Arc, time::{Duration, SystemTime}};
+use tokio_io::{AsyncRead, AsyncWrite};
+use wasm_timer::{Delay, Instant};
+
+/// Configuration of the [`Introspection`] network behaviour.
+#[derive(Debug, Clone, Default)]
+pub struct IntrospectionConfig {
+    allow_all: bool,
+    allowed_peers: HashSet<PeerId>,
+}
+
+impl IntrospectionConfig {
+    /// Allows a peer to request snapshots.
+    pub fn with_allowed_peer(mut self, peer: PeerId) -> Self {
+        self.allowed_peers.insert(peer);
+        self
+    }
+
+    /// Allows every peer to request snapshots. Only use this on a trusted network.
+    pub fn with_allow_all(mut self, allow_all: bool) -> Self {
+        self.allow_all = allow_all;
+        self
+    }
+
+    fn is_allowed(&self, peer: &PeerId) -> bool {
+        self.allow_all || self.allowed_peers.contains(peer)
+    }
+}
+
+/// Adds information that the `Swarm` doesn't know about to the snapshots.
+///
+/// This is implemented for closures, so that for example the DHT state can be filled in from
+/// the application:
+///
+/// ```ignore
+/// introspection.add_provider(move |state: &mut State| {
+///     state.dht = Some(DhtState { protocol: "/ipfs/kad/1.0.0".into(), buckets, records });
+/// });
+/// ```
+pub trait StateProvider: Send {
+    /// Fills in the snapshot about to be sent.
+    fn provide(&self, state: &mut State);
+}
+
+impl<F> StateProvider for F
+where
+    F: Fn(&mut State) + Send,
+{
+    fn provide(&self, state: &mut State) {
+        self(state)
+    }
+}
+
+impl<T> StateProvider for Arc<T>
+where
+    T: StateProvider + Sync + ?Sized,
+{
+    fn provide(&self, state: &mut State) {
+        (**self).provide(state)
+    }
+}
+
+/// Event generated by the `Introspection` network behaviour.
+#[derive(Debug)]
+pub enum IntrospectionEvent {
+    /// A peer sent us a snapshot of its state.
+    State { peer: PeerId, state: State },
+    /// Requesting a snapshot from a peer failed.
+    Failure { peer: PeerId, error: OutboundFailure },
+    /// We sent a snapshot of our state to a peer.
+    Served { enquirer: PeerId },
+    /// We refused to send a snapshot to a peer that isn't allowed by the configuration.
+    Refused { enquirer: PeerId },
+}
+
+/// Network behaviour serving snapshots of the state of the node, and requesting the snapshots
+/// of other nodes.
+///
+/// See the crate root documentation for more information.
+pub struct Introspection<TSubstream> {
+    /// The behaviour sending and answering the requests.
+    inner: RequestResponse<TSubstream, IntrospectionCodec>,
+    /// Configuration options.
+    config: IntrospectionConfig,
+    /// The established connections, as reported by the `Swarm`.
+    connections: HashMap<ConnectionId, Connection>,
+    /// Contributors to the snapshots.
+    providers: Vec<Box<dyn StateProvider>>,
+    /// Peers we periodically request a snapshot from, with the interval and the timer of the
+    /// next request.
+    watched: HashMap<PeerId, (Duration, Delay)>,
+    /// Queue of events to return to the swarm.
+    pending_events: VecDeque<IntrospectionEvent>,
+}
+
+impl<TSubstream> Introspection<TSubstream> {
+    /// Creates an `Introspection` behaviour with the given configuration.
+    pub fn new(config: IntrospectionConfig) -> Self {
+        let inner_config = RequestResponseConfig::default().with_max_retries(0);
+        Introspection {
+            inner: RequestResponse::new(IntrospectionCodec, iter::once(PROTOCOL_NAME), inner_config),
+            config,
+            connections: HashMap::new(),
+            providers: Vec::new(),
+            watched: HashMap::new(),
+            pending_events: VecDeque::new(),
+        }
+    }
+
+    /// Registers a contributor to the snapshots. Contributors are called in the order in which
+    /// they have been registered, after the connections have been filled in.
+    pub fn add_provider(&mut self, provider: impl StateProvider + 'static) {
+        self.providers.push(Box::new(provider));
+    }
+
+    /// Requests a single snapshot from a peer, dialing it first if we're not connected to it.
+    pub fn request_state(&mut self, peer: &PeerId) {
+        self.inner.send_request(peer, ());
+    }
+
+    /// Requests a snapshot from a peer now, then every `interval`, until `unwatch` is called.
+    pub fn watch(&mut self, peer: &PeerId, interval: Duration) {
+        self.request_state(peer);
+        self.watched.insert(peer.clone(), (interval, Delay::new(Instant::now() + interval)));
+    }
+
+    /// Stops requesting snapshots from a peer.
+    pub fn unwatch(&mut self, peer: &PeerId) {
+        self.watched.remove(peer);
+    }
+
+    /// Adds an address at which a peer can be dialed.
+    pub fn add_address(&mut self, peer: &PeerId, address: Multiaddr) {
+        self.inner.add_address(peer, address)
+    }
+
+    /// Builds a snapshot of the current state of the node.
+    fn snapshot(&self, params: &impl PollParameters) -> State {
+        let mut connections = self.connections.values().cloned().collect::<Vec<_>>();
+        connections.sort_by(|a, b| a.opened.cmp(&b.opened));
+        let mut state = State {
+            timestamp: SystemTime::now(),
+            peer_id: params.local_peer_id().clone(),
+            listen_addrs: params.listened_addresses().collect(),
+            connections,
+            traffic: None,
+            dht: None,
+        };
+        for provider in &self.providers {
+            provider.provide(&mut state);
+        }
+        state
+    }
+
+    /// Answers a request received from a peer.
+    fn on_request(&mut self, peer: PeerId, channel: ResponseChannel<State>, params: &impl PollParameters) {
+        if !self.config.is_allowed(&peer) {
+            // Dropping the channel closes the substream without answering.
+            self.pending_events.push_back(IntrospectionEvent::Refused { enquirer: peer });
+            return
+        }
+        let state = self.snapshot(params);
+        self.inner.send_response(channel, state);
+        self.pending_events.push_back(IntrospectionEvent::Served { enquirer: peer });
+    }
+}
+
+/// Converts an action of the inner `RequestResponse` behaviour into an action of the wrapping
+/// behaviour, or returns the event to process if the action is `GenerateEvent`.
+fn forward_action<TOutEvent>(
+    action: NetworkBehaviourAction<RequestProtocol<IntrospectionCodec>, RequestResponseEvent<(), State>>
+) -> Result<NetworkBehaviourAction<RequestProtocol<IntrospectionCodec>, TOutEvent>, RequestResponseEvent<(), State>> {
+    Ok(match action {
+        NetworkBehaviourAction::GenerateEvent(event) => return Err(event),
+        NetworkBehaviourAction::DialAddress { address } => NetworkBehaviourAction::DialAddress { address },
+        NetworkBehaviourAction::DialPeer { peer_id } => NetworkBehaviourAction::DialPeer { peer_id },
+        NetworkBehaviourAction::SendEvent { peer_id, event } => NetworkBehaviourAction::SendEvent { peer_id, event },
+        NetworkBehaviourAction::SendEventToConnection { peer_id, connection, event } => {
+            NetworkBehaviourAction::SendEventToConnection { peer_id, connection, event }
+        }
+        NetworkBehaviourAction::ReportObservedAddr { address, observer } => {
+            NetworkBehaviourAction::ReportObservedAddr { address, observer }
+        }
+        NetworkBehaviourAction::ReportRemoteProtocols { peer_id, protocols } => {
+            NetworkBehaviourAction::ReportRemoteProtocols { peer_id, protocols }
+        }
+        NetworkBehaviourAction::ReportRoundTripTime { peer_id, rtt } => {
+            NetworkBehaviourAction::ReportRoundTripTime { peer_id, rtt }
+        }
+        NetworkBehaviourAction::ReportTransfer { peer_id, bytes_received, bytes_sent, duration } => {
+            NetworkBehaviourAction::ReportTransfer { peer_id, bytes_received, bytes_sent, duration }
+        }
+        NetworkBehaviourAction::ReportExternalAddr { address, source } => {
+            NetworkBehaviourAction::ReportExternalAddr { address, source }
+        }
+        NetworkBehaviourAction::RemoveExternalAddr { address } => {
+            NetworkBehaviourAction::RemoveExternalAddr { address }
+        }
+    })
+}
+
+impl<TSubstream> NetworkBehaviour for Introspection<TSubstream>
+where
+    TSubstream: AsyncRead + AsyncWrite,
+{
+    type ProtocolsHandler = RequestResponseHandler<TSubstream, IntrospectionCodec>;
+    type OutEvent = IntrospectionEvent;
+
+    fn new_handler(&mut self) -> Self::ProtocolsHandler {
+        self.inner.new_handler()
+    }
+
+    fn addresses_of_peer(&mut self, peer_id: &PeerId) -> Vec<Multiaddr> {
+        self.inner.addresses_of_peer(peer_id)
+    }
+
+    fn inject_connected(&mut self, peer_id: PeerId, endpoint: ConnectedPoint) {
+        self.inner.inject_connected(peer_id, endpoint)
+    }
+
+    fn inject_disconnected(&mut self, peer_id: &PeerId, endpoint: ConnectedPoint) {
+        self.inner.inject_disconnected(peer_id, endpoint)
+    }
+
+    fn inject_connection_established(&mut self, peer_id: &PeerId, connection: &ConnectionId, endpoint: &ConnectedPoint) {
+        let (role, remote_addr, local_addr) = match endpoint {
+            ConnectedPoint::Dialer { address } => (Role::Initiator, address.clone(), None),
+            ConnectedPoint::Listener { listen_addr, send_back_addr } => {
+                (Role::Responder, send_back_addr.clone(), Some(listen_addr.clone()))
+            }
+        };
+        self.connections.insert(*connection, Connection {
+            id: connection.to_string(),
+            peer_id: peer_id.clone(),
+            role,
+            remote_addr,
+            local_addr,
+            opened: SystemTime::now(),
+            traffic: None,
+        });
+        self.inner.inject_connection_established(peer_id, connection, endpoint)
+    }
+
+    fn inject_connection_closed(&mut self, peer_id: &PeerId, connection: &ConnectionId, endpoint: &ConnectedPoint) {
+        self.connections.remove(connection);
+        self.inner.inject_connection_closed(peer_id, connection, endpoint)
+    }
+
+    fn inject_node_event(&mut self, peer_id: PeerId, event: RequestResponseHandlerEvent<IntrospectionCodec>) {
+        self.inner.inject_node_event(peer_id, event)
+    }
+
+    fn inject_connection_event(&mut self, peer_id: PeerId, connection: ConnectionId, event: RequestResponseHandlerEvent<IntrospectionCodec>) {
+        self.inner.inject_connection_event(peer_id, connection, event)
+    }
+
+    fn inject_addr_reach_failure(&mut self, peer_id: Option<&PeerId>, addr: &Multiaddr, error: &dyn error::Error) {
+        self.inner.inject_addr_reach_failure(peer_id, addr, error)
+    }
+
+    fn inject_dial_failure(&mut self, peer_id: &PeerId) {
+        self.inner.inject_dial_failure(peer_id)
+    }
+
+    fn poll(&mut self, params: &mut impl PollParameters)
+        -> Async<NetworkBehaviourAction<RequestProtocol<IntrospectionCodec>, Self::OutEvent>>
+    {
+        let mut due = Vec::new();
+        for (peer, (interval, delay)) in self.watched.iter_mut() {
+            match delay.poll() {
+                Ok(Async::NotReady) => {}
+                Ok(Async::Ready(())) | Err(_) => {
+                    *delay = Delay::new(Instant::now() + *interval);
+                    due.push(peer.clone());
+                }
+            }
+        }
+        for peer in due {
+            self.request_state(&peer);
+        }
+
+        loop {
+            if let Some(event) = self.pending_events.pop_front() {
+                return Async::Ready(NetworkBehaviourAction::GenerateEvent(event))
+            }
+
+            match self.inner.poll(params) {
+                Async::NotReady => return Async::NotReady,
+                Async::Ready(action) => match forward_action(action) {
+                    Ok(action) => return Async::Ready(action),
+                    Err(RequestResponseEvent::Message {
+                        peer,
+                        message: RequestResponseMessage::Request { channel, .. },
+                    }) => self.on_request(peer, channel, &*params),
+                    Err(RequestResponseEvent::Message {
+                        peer,
+                        message: RequestResponseMessage::Response { response, .. },
+                    }) => self.pending_events.push_back(IntrospectionEvent::State { peer, state: response }),
+                    Err(RequestResponseEvent::OutboundFailure { peer, error, .. }) => {
+                        self.pending_events.push_back(IntrospectionEvent::Failure { peer, error });
+                    }
+                    Err(RequestResponseEvent::InboundFailure { peer, error, .. }) => {
+                        log::debug!("Failed to send a snapshot to {:?}: {}", peer, error);
+                    }
+                },
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn nobody_is_allowed_by_default() {
+        let peer = PeerId::random();
+        assert!(!IntrospectionConfig::default().is_allowed(&peer));
+        assert!(IntrospectionConfig::default().with_allowed_peer(peer.clone()).is_allowed(&peer));
+        assert!(!IntrospectionConfig::default().with_allowed_peer(PeerId::random()).is_allowed(&peer));
+        assert!(IntrospectionConfig::default().with_allow_all(true).is_allowed(&peer));
+    }
+}
@@ -0,0 +1,61 @@
+// Copyright 2019 Parity Technologies (UK) Ltd.
+//
+// Permission is hereby granted, free of charge, to any person obtaining a
+// copy of this software and associated documentation files (the "Software"),
+// to deal in the Software without restriction, including without limitation
+// the rights to use, copy, modify, merge, publish, distribute, sublicense,
+// and/or sell copies of the Software, and to permit persons to whom the
+// Software is furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS
+// OR IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING
+// FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
+// DEALINGS IN THE SOFTWARE.
+//! Benchmarks of the lookup of the closest peers in the routing table.
+
+use criterion::{Criterion, criterion_group, criterion_main};
+use libp2p_core::PeerId;
+use libp2p_kad::kbucket::{Entry, K_VALUE, KBucketsTable, Key, NodeStatus};
+use rand::{SeedableRng, rngs::StdRng};
+use std::time::Duration;
+
+/// Number of peers offered to the routing table, most of which don't fit.
+const PEERS: usize = 10_000;
+
+/// Number of lookups at each iteration.
+const LOOKUPS: usize = 100;
+
+fn closest_peers(c: &mut Criterion) {
+    c.bench_function("closest 20 peers of 100 targets", |b| {
+        let mut rng = StdRng::seed_from_u64(0);
+        let mut table = KBucketsTable::<Key<PeerId>, ()>::new(
+            Key::from(PeerId::random_with(&mut rng)),
+            Duration::from_secs(60)
+        );
+        for _ in 0 .. PEERS {
+            let key = Key::from(PeerId::random_with(&mut rng));
+            if let Entry::Absent(entry) = table.entry(&key) {
+                let _ = entry.insert((), NodeStatus::Connected);
+            }
+        }
+        let targets = (0 .. LOOKUPS)
+            .map(|_| Key::from(PeerId::random_with(&mut rng)))
+            .collect::<Vec<_>>();
+
+        b.iter(|| {
+            for target in &targets {
+                let closest = table.closest_keys(target).take(K_VALUE.get()).count();
+                assert_eq!(closest, K_VALUE.get());
+            }
+        })
+    });
+}
+
+criterion_group!(benches, closest_peers);
+criterion_main!(benches);
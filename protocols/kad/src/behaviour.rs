@@ -25,14 +25,15 @@ mod test;
 use crate::addresses::Addresses;
 use crate::handler::{KademliaHandler, KademliaRequestId, KademliaHandlerEvent, KademliaHandlerIn};
 use crate::jobs::*;
-use crate::kbucket::{self, KBucketsTable, NodeStatus};
+use crate::kbucket::{self, KBucketConfig, KBucketsTable, NodeStatus};
 use crate::protocol::{KadConnectionType, KadPeer};
-use crate::query::{Query, QueryId, QueryPool, QueryConfig, QueryPoolState};
+use crate::query::{Query, QueryId, QueryPool, QueryConfig, QueryPoolState, QueryStats};
 use crate::record::{store::{self, RecordStore}, Record, ProviderRecord};
 use fnv::{FnvHashMap, FnvHashSet};
 use futures::prelude::*;
-use libp2p_core::{ConnectedPoint, Multiaddr, PeerId};
-use libp2p_swarm::{NetworkBehaviour, NetworkBehaviourAction, PollParameters, ProtocolsHandler};
+use libp2p_core::{BoxedRng, ConnectedPoint, Multiaddr, PeerId};
+use libp2p_core::routing::{ContentRouting, PeerRouting, RoutingError, RoutingEvent, RoutingPeer};
+use libp2p_swarm::{NetworkBehaviour, NetworkBehaviourAction, NetworkChange, PollParameters, ProtocolsHandler};
 use log::{info, debug, warn};
 use multihash::Multihash;
 use smallvec::SmallVec;
@@ -40,7 +41,7 @@ use std::{borrow::Cow, error, iter, marker::PhantomData, time::Duration};
 use std::collections::VecDeque;
 use std::num::NonZeroUsize;
 use tokio_io::{AsyncRead, AsyncWrite};
-use wasm_timer::Instant;
+use wasm_timer::{Delay, Instant};
 
 /// Network behaviour that handles Kademlia.
 pub struct Kademlia<TSubstream, TStore> {
@@ -50,6 +51,27 @@ pub struct Kademlia<TSubstream, TStore> {
     /// An optional protocol name override to segregate DHTs in the network.
     protocol_name_override: Option<Cow<'static, [u8]>>,
 
+    /// Whether the local node answers the requests of other nodes.
+    mode: KademliaMode,
+
+    /// Decides whether a peer connected on an address may enter the routing table.
+    routing_filter: Option<Box<dyn FnMut(&PeerId, &Multiaddr) -> bool + Send>>,
+
+    /// How peers the local node connects to enter the routing table.
+    kbucket_inserts: KademliaBucketInserts,
+
+    /// Whether the local node dials the peers it needs to contact.
+    auto_dial: bool,
+
+    /// The maximum number of peers dialed at the same time, if any.
+    max_concurrent_dials: Option<NonZeroUsize>,
+
+    /// The peers currently being dialed.
+    dialing: FnvHashSet<PeerId>,
+
+    /// The peers waiting to be dialed because of `max_concurrent_dials`.
+    queued_dials: VecDeque<PeerId>,
+
     /// The currently active (i.e. in-progress) queries.
     queries: QueryPool<QueryInner>,
 
@@ -76,9 +98,28 @@ pub struct Kademlia<TSubstream, TStore> {
     /// The TTL of provider records.
     provider_record_ttl: Option<Duration>,
 
+    /// The timer for the next automatic bootstrap and the interval between
+    /// two automatic bootstraps.
+    bootstrap_timer: Option<(Delay, Duration)>,
+
+    /// The time the addresses of the local node must be stable for before
+    /// the provider records are re-published.
+    address_change_delay: Option<Duration>,
+
+    /// The timer for the re-publication of the provider records, following
+    /// a change of the addresses of the local node.
+    address_change_timer: Option<Delay>,
+
+    /// Source of the targets of the bucket refreshes.
+    rng: BoxedRng,
+
     /// Queued events to return when the behaviour is being polled.
     queued_events: VecDeque<NetworkBehaviourAction<KademliaHandlerIn<QueryId>, KademliaEvent>>,
 
+    /// The peers looked up through [`PeerRouting::find_peer`], with the addresses
+    /// discovered for them so far.
+    find_peer_requests: FnvHashMap<PeerId, Vec<Multiaddr>>,
+
     /// Marker to pin the generics.
     marker: PhantomData<TSubstream>,
 
@@ -91,7 +132,7 @@ pub struct Kademlia<TSubstream, TStore> {
 /// The configuration is consumed by [`Kademlia::new`].
 #[derive(Debug, Clone)]
 pub struct KademliaConfig {
-    kbucket_pending_timeout: Duration,
+    kbucket_config: KBucketConfig,
     query_config: QueryConfig,
     protocol_name_override: Option<Cow<'static, [u8]>>,
     record_ttl: Option<Duration>,
@@ -99,12 +140,45 @@ pub struct KademliaConfig {
     record_publication_interval: Option<Duration>,
     provider_record_ttl: Option<Duration>,
     provider_publication_interval: Option<Duration>,
+    bootstrap_interval: Option<Duration>,
+    mode: KademliaMode,
+    kbucket_inserts: KademliaBucketInserts,
+    auto_dial: bool,
+    max_concurrent_dials: Option<NonZeroUsize>,
+    address_change_delay: Option<Duration>,
+}
+
+/// The mode of operation of a `Kademlia` behaviour.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum KademliaMode {
+    /// The local node only queries the DHT.
+    ///
+    /// Incoming Kademlia substreams are refused, so that queries of other
+    /// nodes never wait for or get answers from the local node. Suited to
+    /// nodes that are not publicly reachable, e.g. behind a NAT.
+    Client,
+    /// The local node queries the DHT and answers the requests of other nodes.
+    Server,
+}
+
+/// How peers the local node connects to enter the routing table.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum KademliaBucketInserts {
+    /// Peers are added to the routing table as soon as the local node
+    /// connects to them on a known address.
+    OnConnected,
+    /// Peers are only added to the routing table with [`Kademlia::add_address`].
+    ///
+    /// A [`KademliaEvent::RoutablePeer`] is emitted instead for every peer
+    /// that would otherwise have been added, so that the application can
+    /// confirm it, e.g. after an application-level handshake.
+    Manual,
 }
 
 impl Default for KademliaConfig {
     fn default() -> Self {
         KademliaConfig {
-            kbucket_pending_timeout: Duration::from_secs(60),
+            kbucket_config: KBucketConfig::default(),
             query_config: QueryConfig::default(),
             protocol_name_override: None,
             record_ttl: Some(Duration::from_secs(36 * 60 * 60)),
@@ -112,6 +186,12 @@ impl Default for KademliaConfig {
             record_publication_interval: Some(Duration::from_secs(24 * 60 * 60)),
             provider_publication_interval: Some(Duration::from_secs(12 * 60 * 60)),
             provider_record_ttl: Some(Duration::from_secs(24 * 60 * 60)),
+            bootstrap_interval: Some(Duration::from_secs(10 * 60)),
+            mode: KademliaMode::Server,
+            kbucket_inserts: KademliaBucketInserts::OnConnected,
+            auto_dial: true,
+            max_concurrent_dials: None,
+            address_change_delay: Some(Duration::from_secs(10)),
         }
     }
 }
@@ -137,6 +217,29 @@ impl KademliaConfig {
         self
     }
 
+    /// Sets the allowed level of parallelism for iterative queries.
+    ///
+    /// The `α` parameter in the Kademlia paper. The maximum number of peers
+    /// that an iterative query is allowed to wait for in parallel while
+    /// iterating towards the closest nodes to a target. The default is
+    /// [`ALPHA_VALUE`].
+    pub fn set_parallelism(&mut self, parallelism: NonZeroUsize) -> &mut Self {
+        self.query_config.parallelism = parallelism;
+        self
+    }
+
+    /// Sets the timeout for a single request of an iterative query.
+    ///
+    /// A peer that does not respond within this timeout no longer counts
+    /// towards the parallelism of the query, which can then move on to
+    /// other peers. A late response is still taken into account.
+    ///
+    /// The default is 10 seconds.
+    pub fn set_request_timeout(&mut self, timeout: Duration) -> &mut Self {
+        self.query_config.peer_timeout = timeout;
+        self
+    }
+
     /// Sets the replication factor to use.
     ///
     /// The replication factor determines to how many closest peers
@@ -146,6 +249,30 @@ impl KademliaConfig {
         self
     }
 
+    /// Sets the number of disjoint paths over which iterative queries are run.
+    ///
+    /// With more than one path, the known closest peers are distributed among the
+    /// paths and a peer is only ever contacted on behalf of a single path, whose
+    /// results are merged when the query finishes, as described in S/Kademlia.
+    /// This makes it harder for adversarial nodes to misdirect a lookup, at the cost
+    /// of more requests. The default is 1, i.e. a regular Kademlia lookup.
+    pub fn set_disjoint_paths(&mut self, paths: NonZeroUsize) -> &mut Self {
+        self.query_config.disjoint_paths = paths;
+        self
+    }
+
+    /// Sets whether iterative queries prefer low-latency peers.
+    ///
+    /// When enabled, a query contacts the peer with the lowest round-trip time
+    /// first among the peers at the same logarithmic distance to the target,
+    /// i.e. the peers that would share a k-bucket of the target. The round-trip
+    /// times are the averages reported to the `Swarm`, e.g. by the ping protocol.
+    /// Enabled by default.
+    pub fn set_prefer_low_latency(&mut self, enabled: bool) -> &mut Self {
+        self.query_config.prefer_low_latency = enabled;
+        self
+    }
+
     /// Sets the TTL for stored records.
     ///
     /// The TTL should be significantly longer than the (re-)publication
@@ -219,6 +346,95 @@ impl KademliaConfig {
         self.provider_publication_interval = interval;
         self
     }
+
+    /// Sets the initial mode of operation of the local node.
+    ///
+    /// The mode can be changed later with [`Kademlia::set_mode`], e.g. once the
+    /// reachability of the local node is known. The default is
+    /// [`KademliaMode::Server`].
+    pub fn set_mode(&mut self, mode: KademliaMode) -> &mut Self {
+        self.mode = mode;
+        self
+    }
+
+    /// Sets the interval at which the local node automatically bootstraps,
+    /// refreshing its routing table.
+    ///
+    /// See [`Kademlia::bootstrap`]. The default is 10 minutes.
+    ///
+    /// `None` means that the local node only bootstraps when explicitly asked to.
+    pub fn set_bootstrap_interval(&mut self, interval: Option<Duration>) -> &mut Self {
+        self.bootstrap_interval = interval;
+        self
+    }
+
+    /// Sets how peers the local node connects to enter the routing table.
+    ///
+    /// With [`KademliaBucketInserts::Manual`], the peers discovered through
+    /// queries or inbound connections are never added automatically, so that
+    /// unauthenticated peers cannot shape the routing table. The default is
+    /// [`KademliaBucketInserts::OnConnected`].
+    pub fn set_kbucket_inserts(&mut self, inserts: KademliaBucketInserts) -> &mut Self {
+        self.kbucket_inserts = inserts;
+        self
+    }
+
+    /// Sets the maximum number of peers in a bucket of the routing table,
+    /// i.e. the `k` parameter of Kademlia.
+    ///
+    /// The default is [`K_VALUE`].
+    pub fn set_kbucket_size(&mut self, size: NonZeroUsize) -> &mut Self {
+        self.kbucket_config.set_max_nodes(size);
+        self
+    }
+
+    /// Sets the size of the replacement cache of the buckets of the routing
+    /// table, i.e. the number of peers that can be pending insertion into a
+    /// full bucket, waiting for one of its disconnected peers to be evicted.
+    ///
+    /// The default is `1`.
+    pub fn set_kbucket_replacement_cache_size(&mut self, size: usize) -> &mut Self {
+        self.kbucket_config.set_max_pending(size);
+        self
+    }
+
+    /// Sets whether the local node dials the peers it needs to contact.
+    ///
+    /// When disabled, queries only contact the peers the local node is
+    /// already connected to, and treat the other peers as unreachable.
+    /// Discovered peers are still reported with [`KademliaEvent::Discovered`],
+    /// so that the application can decide which ones to connect to. The
+    /// least-recently connected peer of a full bucket isn't dialed either when
+    /// a new peer is pending insertion, so that it is evicted once the
+    /// pending entry times out. Enabled by default.
+    pub fn set_auto_dial(&mut self, enabled: bool) -> &mut Self {
+        self.auto_dial = enabled;
+        self
+    }
+
+    /// Sets the maximum number of peers the local node dials at the same time.
+    ///
+    /// Further dials are delayed until an ongoing dial succeeds or fails, at
+    /// the risk of the corresponding requests timing out. The default is `None`,
+    /// i.e. no limit.
+    pub fn set_max_concurrent_dials(&mut self, max: Option<NonZeroUsize>) -> &mut Self {
+        self.max_concurrent_dials = max;
+        self
+    }
+
+    /// Sets the time the listen and external addresses of the local node
+    /// must be stable for before the provider records of the local node are
+    /// re-published with the new addresses.
+    ///
+    /// Every change of the addresses restarts the delay, so that a burst of
+    /// changes results in a single re-publication. `None` disables the
+    /// re-publication, in which case other nodes only learn about the new
+    /// addresses with the next periodic re-publication. The default is
+    /// 10 seconds.
+    pub fn set_republish_on_address_change(&mut self, delay: Option<Duration>) -> &mut Self {
+        self.address_change_delay = delay;
+        self
+    }
 }
 
 impl<TSubstream, TStore> Kademlia<TSubstream, TStore>
@@ -249,11 +465,23 @@ where
             .provider_publication_interval
             .map(AddProviderJob::new);
 
+        let bootstrap_timer = config
+            .bootstrap_interval
+            .map(|interval| (Delay::new(Instant::now() + interval), interval));
+
         Kademlia {
             store,
-            kbuckets: KBucketsTable::new(local_key, config.kbucket_pending_timeout),
+            kbuckets: KBucketsTable::with_config(local_key, config.kbucket_config),
             protocol_name_override: config.protocol_name_override,
+            mode: config.mode,
+            routing_filter: None,
+            kbucket_inserts: config.kbucket_inserts,
+            auto_dial: config.auto_dial,
+            max_concurrent_dials: config.max_concurrent_dials,
+            dialing: Default::default(),
+            queued_dials: VecDeque::new(),
             queued_events: VecDeque::with_capacity(config.query_config.replication_factor.get()),
+            find_peer_requests: Default::default(),
             queries: QueryPool::new(config.query_config),
             connected_peers: Default::default(),
             pending_rpcs,
@@ -261,6 +489,10 @@ where
             put_record_job,
             record_ttl: config.record_ttl,
             provider_record_ttl: config.provider_record_ttl,
+            bootstrap_timer,
+            address_change_delay: config.address_change_delay,
+            address_change_timer: None,
+            rng: BoxedRng::default(),
             marker: PhantomData,
         }
     }
@@ -321,9 +553,7 @@ where
                         debug!("Bucket full. Peer not added to routing table: {}", peer)
                     },
                     kbucket::InsertResult::Pending { disconnected } => {
-                        self.queued_events.push_back(NetworkBehaviourAction::DialPeer {
-                            peer_id: disconnected.into_preimage(),
-                        })
+                        self.dial(disconnected.into_preimage())
                     },
                 }
             },
@@ -331,14 +561,90 @@ where
         }
     }
 
+    /// Removes a peer from the routing table.
+    ///
+    /// Returns the addresses of the peer if it was in the routing table, either in a
+    /// bucket or pending insertion. The peer may enter the routing table again, e.g.
+    /// when the local node connects to it, unless excluded by the routing filter.
+    pub fn remove_peer(&mut self, peer: &PeerId) -> Option<Addresses> {
+        let key = kbucket::Key::new(peer.clone());
+        match self.kbuckets.entry(&key) {
+            kbucket::Entry::Present(entry, _) => Some(entry.remove().node.value),
+            kbucket::Entry::Pending(entry, _) => Some(entry.remove().node.value),
+            kbucket::Entry::Absent(..) | kbucket::Entry::SelfEntry => None,
+        }
+    }
+
+    /// Removes an address of a peer from the routing table.
+    ///
+    /// If the address is the last one of the peer, the peer is removed
+    /// from the routing table.
+    pub fn remove_address(&mut self, peer: &PeerId, address: &Multiaddr) {
+        let key = kbucket::Key::new(peer.clone());
+        let last = match self.kbuckets.entry(&key).value() {
+            Some(addrs) => addrs.remove(address).is_err() && addrs.first() == address,
+            None => false,
+        };
+        if last {
+            self.remove_peer(peer);
+        }
+    }
+
+    /// Installs a filter deciding whether a peer may enter the routing table
+    /// with a given address.
+    ///
+    /// The filter is applied to the addresses of the peers the local node connects to,
+    /// which are otherwise added to the routing table automatically, e.g. to only keep
+    /// publicly reachable addresses or allowlisted peers. Addresses added with
+    /// [`Kademlia::add_address`] bypass the filter. By default, all peers are allowed.
+    pub fn set_routing_filter<F>(&mut self, filter: F)
+    where
+        F: FnMut(&PeerId, &Multiaddr) -> bool + Send + 'static
+    {
+        self.routing_filter = Some(Box::new(filter));
+    }
+
+    /// Sets the generator of the random keys looked up to refresh the buckets.
+    pub fn set_rng(&mut self, rng: BoxedRng) {
+        self.rng = rng;
+    }
+
     /// Returns an iterator over all peer IDs of nodes currently contained in a bucket
     /// of the Kademlia routing table.
     pub fn kbuckets_entries(&mut self) -> impl Iterator<Item = &PeerId> {
         self.kbuckets.iter().map(|entry| entry.node.key.preimage())
     }
 
+    /// Returns the peers of the routing table with their addresses, e.g. to persist
+    /// them across restarts with `SwarmState`.
+    pub fn routing_table(&mut self) -> Vec<(PeerId, Vec<Multiaddr>)> {
+        self.kbuckets.iter()
+            .map(|entry| (entry.node.key.preimage().clone(), entry.node.value.iter().cloned().collect()))
+            .collect()
+    }
+
+    /// Adds the peers of a routing table previously obtained with
+    /// [`Kademlia::routing_table`] as with [`Kademlia::add_address`], then
+    /// bootstraps if any peer was added.
+    pub fn restore_routing_table<I>(&mut self, entries: I)
+    where
+        I: IntoIterator<Item = (PeerId, Vec<Multiaddr>)>
+    {
+        let mut restored = false;
+        for (peer, addrs) in entries {
+            for addr in addrs {
+                self.add_address(&peer, addr);
+                restored = true;
+            }
+        }
+        if restored {
+            self.bootstrap()
+        }
+    }
+
     /// Performs a lookup for the closest peers to the given key.
     ///
+    /// The peers that respond are reported as they do in [`KademliaEvent::QueryProgress`].
     /// The result of this operation is delivered in [`KademliaEvent::GetClosestPeersResult`].
     pub fn get_closest_peers<K>(&mut self, key: K)
     where
@@ -354,7 +660,13 @@ where
 
     /// Performs a lookup for a record in the DHT.
     ///
-    /// The result of this operation is delivered in [`KademliaEvent::GetRecordResult`].
+    /// The lookup succeeds as soon as `quorum` records have been obtained, counting
+    /// the record stored locally, if any. Otherwise the records obtained so far are
+    /// reported in a [`GetRecordError::QuorumFailed`] or [`GetRecordError::Timeout`].
+    ///
+    /// The records obtained from remote peers are reported as they arrive in
+    /// [`KademliaEvent::QueryProgress`]. The result of this operation is delivered in
+    /// [`KademliaEvent::GetRecordResult`].
     pub fn get_record(&mut self, key: &Multihash, quorum: Quorum) {
         let quorum = quorum.eval(self.queries.config().replication_factor);
         let mut records = Vec::with_capacity(quorum.get());
@@ -366,7 +678,7 @@ where
                 records.push(record.into_owned());
                 if quorum.get() == 1 {
                     self.queued_events.push_back(NetworkBehaviourAction::GenerateEvent(
-                        KademliaEvent::GetRecordResult(Ok(GetRecordOk { records }))
+                        KademliaEvent::GetRecordResult(Ok(GetRecordOk { records }), QueryStats::empty())
                     ));
                     return;
                 }
@@ -382,6 +694,10 @@ where
 
     /// Stores a record in the DHT.
     ///
+    /// The operation succeeds as soon as `quorum` of the closest peers to the key have
+    /// stored the record. Otherwise the number of peers that did is reported in a
+    /// [`PutRecordError::QuorumFailed`] or [`PutRecordError::Timeout`].
+    ///
     /// The result of this operation is delivered in [`KademliaEvent::PutRecordResult`].
     ///
     /// The record is always stored locally with the given expiration. If the record's
@@ -403,7 +719,7 @@ where
                         key: record.key,
                         cause: err,
                     }
-                ))
+                ), QueryStats::empty())
             ));
         } else {
             record.expires = record.expires.or_else(||
@@ -435,6 +751,28 @@ where
         }
     }
 
+    /// Returns the current mode of operation of the local node.
+    pub fn mode(&self) -> KademliaMode {
+        self.mode
+    }
+
+    /// Switches the local node between client and server mode.
+    ///
+    /// The new mode applies to existing connections as well as to new ones, but
+    /// requests that other nodes already sent are still answered.
+    pub fn set_mode(&mut self, mode: KademliaMode) {
+        if self.mode == mode {
+            return
+        }
+        self.mode = mode;
+        for peer_id in &self.connected_peers {
+            self.queued_events.push_back(NetworkBehaviourAction::SendEvent {
+                peer_id: peer_id.clone(),
+                event: KademliaHandlerIn::SetAllowListening(mode == KademliaMode::Server),
+            });
+        }
+    }
+
     /// Gets a mutable reference to the record store.
     pub fn store_mut(&mut self) -> &mut TStore {
         &mut self.store
@@ -453,6 +791,9 @@ where
     /// The result(s) of this operation are delivered in [`KademliaEvent::BootstrapResult`],
     /// with one event per bootstrapping query.
     ///
+    /// Unless disabled with [`KademliaConfig::set_bootstrap_interval`], the local node
+    /// also bootstraps periodically, as long as its routing table is not empty.
+    ///
     /// > **Note**: Bootstrapping requires at least one node of the DHT to be known.
     /// > See [`Kademlia::add_address`].
     pub fn bootstrap(&mut self) {
@@ -489,7 +830,7 @@ where
             self.queued_events.push_back(NetworkBehaviourAction::GenerateEvent(
                 KademliaEvent::StartProvidingResult(Err(
                     AddProviderError::LocalStorageError { key, cause: err }
-                ))
+                ), QueryStats::empty())
             ));
         } else {
             let target = kbucket::Key::from(key.clone());
@@ -511,6 +852,7 @@ where
 
     /// Performs a lookup for providers of a value to the given key.
     ///
+    /// The providers are reported as they are found in [`KademliaEvent::QueryProgress`].
     /// The result of this operation is delivered in [`KademliaEvent::GetProvidersResult`].
     pub fn get_providers(&mut self, key: Multihash) {
         let info = QueryInfo::GetProviders {
@@ -523,6 +865,113 @@ where
         self.queries.add_iter_closest(target.clone(), peers, inner);
     }
 
+    /// Finishes the ongoing lookups for the given key started with
+    /// [`Kademlia::get_closest_peers`], [`Kademlia::get_providers`] or
+    /// [`Kademlia::get_record`], for example once the results reported in
+    /// [`KademliaEvent::QueryProgress`] are sufficient.
+    ///
+    /// No more requests are sent for these lookups, and their final result is delivered with
+    /// what has been found so far. A record lookup that obtained fewer records than its quorum
+    /// therefore results in a [`GetRecordError::QuorumFailed`].
+    ///
+    /// Returns `false` if there is no such lookup.
+    pub fn finish_query(&mut self, key: &Multihash) -> bool {
+        let mut finished = false;
+        for query in self.queries.iter_mut() {
+            let query_key = match &query.inner.info {
+                QueryInfo::GetClosestPeers { key } => key,
+                QueryInfo::GetProviders { key, .. } => key,
+                QueryInfo::GetRecord { key, .. } => key,
+                _ => continue,
+            };
+            if query_key == key {
+                query.finish();
+                finished = true;
+            }
+        }
+        finished
+    }
+
+    /// Converts an event produced by this behaviour into the corresponding [`RoutingEvent`],
+    /// if any.
+    ///
+    /// Provider announcements and lookups are always converted, while the results of
+    /// [`Kademlia::get_closest_peers`] are only converted for the lookups started with
+    /// [`PeerRouting::find_peer`]. All the events of the behaviour should be passed to this
+    /// method, as the addresses of the peers being looked up are gathered from the
+    /// [`KademliaEvent::Discovered`] events.
+    pub fn routing_event(&mut self, event: &KademliaEvent) -> Option<RoutingEvent> {
+        match event {
+            KademliaEvent::StartProvidingResult(result, _) => {
+                let (key, result) = match result {
+                    Ok(ok) => (ok.key.clone(), Ok(())),
+                    Err(AddProviderError::Timeout { key }) => (key.clone(), Err(RoutingError::Timeout)),
+                    Err(AddProviderError::LocalStorageError { key, cause }) =>
+                        (key.clone(), Err(RoutingError::Other(format!("{:?}", cause)))),
+                };
+                Some(RoutingEvent::Provided { key, result })
+            }
+            KademliaEvent::GetProvidersResult(result, _) => {
+                let (key, providers, timeout) = match result {
+                    Ok(ok) => (&ok.key, &ok.providers, false),
+                    Err(GetProvidersError::Timeout { key, providers, .. }) => (key, providers, true),
+                };
+                let result = if !providers.is_empty() {
+                    Ok(providers.iter().map(|p| self.routing_peer(p)).collect())
+                } else if timeout {
+                    Err(RoutingError::Timeout)
+                } else {
+                    Err(RoutingError::NotFound)
+                };
+                Some(RoutingEvent::FoundProviders { key: key.clone(), result })
+            }
+            KademliaEvent::Discovered { peer_id, addresses, .. } => {
+                if let Some(known) = self.find_peer_requests.get_mut(peer_id) {
+                    for addr in addresses {
+                        if !known.contains(addr) {
+                            known.push(addr.clone());
+                        }
+                    }
+                }
+                None
+            }
+            KademliaEvent::GetClosestPeersResult(result, _) => {
+                let (key, timeout) = match result {
+                    Ok(ok) => (&ok.key, false),
+                    Err(GetClosestPeersError::Timeout { key, .. }) => (key, true),
+                };
+                let peer_id = PeerId::from_multihash(key.clone()).ok()?;
+                let discovered = self.find_peer_requests.remove(&peer_id)?;
+                let mut addresses = self.routing_peer(&peer_id).addresses;
+                for addr in discovered {
+                    if !addresses.contains(&addr) {
+                        addresses.push(addr);
+                    }
+                }
+                let result = if !addresses.is_empty() {
+                    Ok(addresses)
+                } else if timeout {
+                    Err(RoutingError::Timeout)
+                } else {
+                    Err(RoutingError::NotFound)
+                };
+                Some(RoutingEvent::FoundPeer { peer_id, result })
+            }
+            _ => None,
+        }
+    }
+
+    /// Builds a [`RoutingPeer`] with the addresses of `peer_id` in the routing table.
+    fn routing_peer(&mut self, peer_id: &PeerId) -> RoutingPeer {
+        let key = kbucket::Key::new(peer_id.clone());
+        let addresses = match self.kbuckets.entry(&key) {
+            kbucket::Entry::Present(mut entry, _) => entry.value().iter().cloned().collect(),
+            kbucket::Entry::Pending(mut entry, _) => entry.value().iter().cloned().collect(),
+            _ => Vec::new(),
+        };
+        RoutingPeer { peer_id: peer_id.clone(), addresses }
+    }
+
     /// Processes discovered peers from a successful request in an iterative `Query`.
     fn discovered<'a, I>(&'a mut self, query_id: &QueryId, source: &PeerId, peers: I)
     where
@@ -567,9 +1016,19 @@ where
     }
 
     /// Collects all peers who are known to be providers of the value for a given `Multihash`.
+    ///
+    /// Expired provider records are removed from the store.
     fn provider_peers(&mut self, key: &Multihash, source: &PeerId) -> Vec<KadPeer> {
+        let now = Instant::now();
+        let (expired, providers): (Vec<_>, Vec<_>) = self.store.providers(key)
+            .into_iter()
+            .partition(|p| p.is_expired(now));
+        for p in expired {
+            self.store.remove_provider(&p.key, &p.provider);
+        }
+
         let kbuckets = &mut self.kbuckets;
-        self.store.providers(key)
+        providers
             .into_iter()
             .filter_map(move |p|
                 if &p.provider != source {
@@ -601,8 +1060,47 @@ where
         self.queries.add_iter_closest(target.clone(), peers, inner);
     }
 
+    /// Dials a peer, unless automatic dialing is disabled.
+    ///
+    /// The dial is delayed if the maximum number of concurrent dials is reached.
+    fn dial(&mut self, peer_id: PeerId) {
+        if !self.auto_dial || self.dialing.contains(&peer_id) || self.queued_dials.contains(&peer_id) {
+            return
+        }
+        if self.max_concurrent_dials.map_or(false, |max| self.dialing.len() >= max.get()) {
+            self.queued_dials.push_back(peer_id);
+            return
+        }
+        self.dialing.insert(peer_id.clone());
+        self.queued_events.push_back(NetworkBehaviourAction::DialPeer { peer_id });
+    }
+
+    /// Records the end of a dial, successful or not, and starts the delayed
+    /// dials for which there is room.
+    fn dial_finished(&mut self, peer_id: &PeerId) {
+        self.dialing.remove(peer_id);
+        while self.max_concurrent_dials.map_or(true, |max| self.dialing.len() < max.get()) {
+            match self.queued_dials.pop_front() {
+                Some(peer_id) => if !self.connected_peers.contains(&peer_id) {
+                    self.dial(peer_id)
+                },
+                None => break,
+            }
+        }
+    }
+
+    /// (Re)starts the delay after which the provider records are re-published,
+    /// following a change of the addresses of the local node.
+    fn address_changed(&mut self) {
+        if let Some(delay) = self.address_change_delay {
+            self.address_change_timer = Some(Delay::new(Instant::now() + delay));
+        }
+    }
+
     /// Updates the connection status of a peer in the Kademlia routing table.
     fn connection_updated(&mut self, peer: PeerId, address: Option<Multiaddr>, new_status: NodeStatus) {
+        let routing_filter = &mut self.routing_filter;
+        let address = address.filter(|a| routing_filter.as_mut().map_or(true, |f| f(&peer, a)));
         let key = kbucket::Key::new(peer.clone());
         match self.kbuckets.entry(&key) {
             kbucket::Entry::Present(mut entry, old_status) => {
@@ -635,6 +1133,12 @@ where
                 // Only connected nodes with a known address are newly inserted.
                 if new_status == NodeStatus::Connected {
                     if let Some(address) = address {
+                        if self.kbucket_inserts == KademliaBucketInserts::Manual {
+                            self.queued_events.push_back(NetworkBehaviourAction::GenerateEvent(
+                                KademliaEvent::RoutablePeer { peer, address }
+                            ));
+                            return;
+                        }
                         let addresses = Addresses::new(address);
                         match entry.insert(addresses.clone(), new_status) {
                             kbucket::InsertResult::Inserted => {
@@ -651,9 +1155,7 @@ where
                             },
                             kbucket::InsertResult::Pending { disconnected } => {
                                 debug_assert!(!self.connected_peers.contains(disconnected.preimage()));
-                                self.queued_events.push_back(NetworkBehaviourAction::DialPeer {
-                                    peer_id: disconnected.into_preimage(),
-                                })
+                                self.dial(disconnected.into_preimage())
                             },
                         }
                     } else {
@@ -680,6 +1182,7 @@ where
                     // a bucket refresh should be performed for every bucket farther away than
                     // the first non-empty bucket (which are most likely no more than the last
                     // few, i.e. farthest, buckets).
+                    let rng = &mut self.rng;
                     let targets = self.kbuckets.buckets()
                         .skip_while(|b| b.num_entries() == 0)
                         .skip(1) // Skip the bucket with the closest neighbour.
@@ -697,13 +1200,13 @@ where
                             // Pr(bucket-253) = 1 - (7/8)^16   ~= 0.88
                             // Pr(bucket-252) = 1 - (15/16)^16 ~= 0.64
                             // ...
-                            let mut target = kbucket::Key::new(PeerId::random());
+                            let mut target = kbucket::Key::new(PeerId::random_with(rng));
                             for _ in 0 .. 16 {
                                 let d = local_key.distance(&target);
                                 if b.contains(&d) {
                                     break;
                                 }
-                                target = kbucket::Key::new(PeerId::random());
+                                target = kbucket::Key::new(PeerId::random_with(rng));
                             }
                             target
                         }).collect::<Vec<_>>();
@@ -715,13 +1218,13 @@ where
                         self.queries.add_iter_closest(target.clone(), peers, inner);
                     }
                 }
-                Some(KademliaEvent::BootstrapResult(Ok(BootstrapOk { peer })))
+                Some(KademliaEvent::BootstrapResult(Ok(BootstrapOk { peer }), result.stats))
             }
 
             QueryInfo::GetClosestPeers { key, .. } => {
                 Some(KademliaEvent::GetClosestPeersResult(Ok(
                     GetClosestPeersOk { key, peers: result.peers.collect() }
-                )))
+                ), result.stats))
             }
 
             QueryInfo::GetProviders { key, providers } => {
@@ -731,7 +1234,7 @@ where
                         providers,
                         closest_peers: result.peers.collect()
                     }
-                )))
+                ), result.stats))
             }
 
             QueryInfo::PrepareAddProvider { key, context } => {
@@ -744,7 +1247,7 @@ where
                     external_addresses,
                     context,
                 });
-                self.queries.add_fixed(closest_peers, inner);
+                self.queries.add_fixed_with_stats(closest_peers, inner, result.stats);
                 None
             }
 
@@ -753,17 +1256,18 @@ where
                     AddProviderContext::Publish => {
                         Some(KademliaEvent::StartProvidingResult(Ok(
                             AddProviderOk { key }
-                        )))
+                        ), result.stats))
                     }
                     AddProviderContext::Republish => {
                         Some(KademliaEvent::RepublishProviderResult(Ok(
                             AddProviderOk { key }
-                        )))
+                        ), result.stats))
                     }
                 }
             }
 
             QueryInfo::GetRecord { key, records, quorum, cache_at } => {
+                let stats = result.stats;
                 let result = if records.len() >= quorum.get() { // [not empty]
                     if let Some(cache_key) = cache_at {
                         // Cache the record at the closest node to the key that
@@ -784,18 +1288,19 @@ where
                 } else {
                     Err(GetRecordError::QuorumFailed { key, records, quorum })
                 };
-                Some(KademliaEvent::GetRecordResult(result))
+                Some(KademliaEvent::GetRecordResult(result, stats))
             }
 
             QueryInfo::PreparePutRecord { record, quorum, context } => {
                 let closest_peers = result.peers.map(kbucket::Key::from);
                 let info = QueryInfo::PutRecord { record, quorum, context, num_results: 0 };
                 let inner = QueryInner::new(info);
-                self.queries.add_fixed(closest_peers, inner);
+                self.queries.add_fixed_with_stats(closest_peers, inner, result.stats);
                 None
             }
 
             QueryInfo::PutRecord { record, quorum, num_results, context } => {
+                let stats = result.stats;
                 let result = |key: Multihash| {
                     if num_results >= quorum.get() {
                         Ok(PutRecordOk { key })
@@ -805,9 +1310,9 @@ where
                 };
                 match context {
                     PutRecordContext::Publish =>
-                        Some(KademliaEvent::PutRecordResult(result(record.key))),
+                        Some(KademliaEvent::PutRecordResult(result(record.key), stats)),
                     PutRecordContext::Republish =>
-                        Some(KademliaEvent::RepublishRecordResult(result(record.key))),
+                        Some(KademliaEvent::RepublishRecordResult(result(record.key), stats)),
                     PutRecordContext::Replicate => {
                         debug!("Record replicated: {:?}", record.key);
                         None
@@ -827,26 +1332,26 @@ where
         match result.inner.info {
             QueryInfo::Bootstrap { peer } =>
                 Some(KademliaEvent::BootstrapResult(Err(
-                    BootstrapError::Timeout { peer }))),
+                    BootstrapError::Timeout { peer }), result.stats)),
 
             QueryInfo::PrepareAddProvider { key, context } =>
                 Some(match context {
                     AddProviderContext::Publish =>
                         KademliaEvent::StartProvidingResult(Err(
-                            AddProviderError::Timeout { key })),
+                            AddProviderError::Timeout { key }), result.stats),
                     AddProviderContext::Republish =>
                         KademliaEvent::RepublishProviderResult(Err(
-                            AddProviderError::Timeout { key })),
+                            AddProviderError::Timeout { key }), result.stats),
                 }),
 
             QueryInfo::AddProvider { key, context, .. } =>
                 Some(match context {
                     AddProviderContext::Publish =>
                         KademliaEvent::StartProvidingResult(Err(
-                            AddProviderError::Timeout { key })),
+                            AddProviderError::Timeout { key }), result.stats),
                     AddProviderContext::Republish =>
                         KademliaEvent::RepublishProviderResult(Err(
-                            AddProviderError::Timeout { key })),
+                            AddProviderError::Timeout { key }), result.stats),
                 }),
 
             QueryInfo::GetClosestPeers { key } =>
@@ -854,7 +1359,7 @@ where
                     GetClosestPeersError::Timeout {
                         key,
                         peers: result.peers.collect()
-                    }))),
+                    }), result.stats)),
 
             QueryInfo::PreparePutRecord { record, quorum, context, .. } => {
                 let err = Err(PutRecordError::Timeout {
@@ -864,9 +1369,9 @@ where
                 });
                 match context {
                     PutRecordContext::Publish =>
-                        Some(KademliaEvent::PutRecordResult(err)),
+                        Some(KademliaEvent::PutRecordResult(err, result.stats)),
                     PutRecordContext::Republish =>
-                        Some(KademliaEvent::RepublishRecordResult(err)),
+                        Some(KademliaEvent::RepublishRecordResult(err, result.stats)),
                     PutRecordContext::Replicate => {
                         warn!("Locating closest peers for replication failed: {:?}", err);
                         None
@@ -887,9 +1392,9 @@ where
                 });
                 match context {
                     PutRecordContext::Publish =>
-                        Some(KademliaEvent::PutRecordResult(err)),
+                        Some(KademliaEvent::PutRecordResult(err, result.stats)),
                     PutRecordContext::Republish =>
-                        Some(KademliaEvent::RepublishRecordResult(err)),
+                        Some(KademliaEvent::RepublishRecordResult(err, result.stats)),
                     PutRecordContext::Replicate => {
                         debug!("Replicatiing record failed: {:?}", err);
                         None
@@ -903,7 +1408,7 @@ where
 
             QueryInfo::GetRecord { key, records, quorum, .. } =>
                 Some(KademliaEvent::GetRecordResult(Err(
-                    GetRecordError::Timeout { key, records, quorum }))),
+                    GetRecordError::Timeout { key, records, quorum }), result.stats)),
 
             QueryInfo::GetProviders { key, providers } =>
                 Some(KademliaEvent::GetProvidersResult(Err(
@@ -911,7 +1416,7 @@ where
                         key,
                         providers,
                         closest_peers: result.peers.collect()
-                    }))),
+                    }), result.stats)),
         }
     }
 
@@ -943,11 +1448,14 @@ where
         let k = self.queries.config().replication_factor.get();
         let num_beyond_k = (usize::max(k, num_between) - k) as u32;
         let expiration = self.record_ttl.map(|ttl|
-            now + Duration::from_secs(ttl.as_secs() >> num_beyond_k)
+            now + Duration::from_secs(ttl.as_secs().checked_shr(num_beyond_k).unwrap_or(0))
         );
         // The smaller TTL prevails. Only if neither TTL is set is the record
         // stored "forever".
-        record.expires = record.expires.or(expiration).min(expiration);
+        record.expires = match (record.expires, expiration) {
+            (Some(a), Some(b)) => Some(a.min(b)),
+            (a, b) => a.or(b),
+        };
 
         if let Some(job) = self.put_record_job.as_mut() {
             // Ignore the record in the next run of the replication
@@ -1014,6 +1522,29 @@ where
     }
 }
 
+impl<TSubstream, TStore> ContentRouting for Kademlia<TSubstream, TStore>
+where
+    for<'a> TStore: RecordStore<'a>
+{
+    fn provide(&mut self, key: Multihash) {
+        self.start_providing(key)
+    }
+
+    fn find_providers(&mut self, key: Multihash) {
+        self.get_providers(key)
+    }
+}
+
+impl<TSubstream, TStore> PeerRouting for Kademlia<TSubstream, TStore>
+where
+    for<'a> TStore: RecordStore<'a>
+{
+    fn find_peer(&mut self, peer_id: PeerId) {
+        self.find_peer_requests.entry(peer_id.clone()).or_insert_with(Vec::new);
+        self.get_closest_peers(peer_id)
+    }
+}
+
 impl<TSubstream, TStore> NetworkBehaviour for Kademlia<TSubstream, TStore>
 where
     TSubstream: AsyncRead + AsyncWrite,
@@ -1023,7 +1554,10 @@ where
     type OutEvent = KademliaEvent;
 
     fn new_handler(&mut self) -> Self::ProtocolsHandler {
-        let mut handler = KademliaHandler::dial_and_listen();
+        let mut handler = match self.mode {
+            KademliaMode::Client => KademliaHandler::dial_only(),
+            KademliaMode::Server => KademliaHandler::dial_and_listen(),
+        };
         if let Some(name) = self.protocol_name_override.as_ref() {
             handler = handler.with_protocol_name(name.clone());
         }
@@ -1072,7 +1606,8 @@ where
         };
 
         self.connection_updated(peer.clone(), address, NodeStatus::Connected);
-        self.connected_peers.insert(peer);
+        self.connected_peers.insert(peer.clone());
+        self.dial_finished(&peer);
     }
 
     fn inject_addr_reach_failure(
@@ -1120,6 +1655,23 @@ where
         for query in self.queries.iter_mut() {
             query.on_failure(peer_id);
         }
+        self.dial_finished(peer_id);
+    }
+
+    fn inject_new_listen_addr(&mut self, _: &Multiaddr) {
+        self.address_changed();
+    }
+
+    fn inject_expired_listen_addr(&mut self, _: &Multiaddr) {
+        self.address_changed();
+    }
+
+    fn inject_new_external_addr(&mut self, _: &Multiaddr) {
+        self.address_changed();
+    }
+
+    fn inject_network_change(&mut self, _: &NetworkChange) {
+        self.address_changed();
     }
 
     fn inject_disconnected(&mut self, id: &PeerId, _old_endpoint: ConnectedPoint) {
@@ -1165,6 +1717,16 @@ where
                 closer_peers,
                 user_data,
             } => {
+                if let Some(query) = self.queries.get(&user_data) {
+                    if let QueryInfo::GetClosestPeers { key } = &query.inner.info {
+                        self.queued_events.push_back(NetworkBehaviourAction::GenerateEvent(
+                            KademliaEvent::QueryProgress(QueryProgress::ClosestPeer {
+                                key: key.clone(),
+                                peer: source.clone(),
+                            })
+                        ));
+                    }
+                }
                 self.discovered(&user_data, &source, closer_peers.iter());
             }
 
@@ -1190,9 +1752,18 @@ where
                 self.discovered(&user_data, &source, peers);
                 if let Some(query) = self.queries.get_mut(&user_data) {
                     if let QueryInfo::GetProviders {
-                        providers, ..
+                        key, providers
                     } = &mut query.inner.info {
                         for peer in provider_peers {
+                            if providers.contains(&peer.node_id) {
+                                continue
+                            }
+                            self.queued_events.push_back(NetworkBehaviourAction::GenerateEvent(
+                                KademliaEvent::QueryProgress(QueryProgress::Provider {
+                                    key: key.clone(),
+                                    provider: peer.node_id.clone(),
+                                })
+                            ));
                             providers.push(peer.node_id);
                         }
                     }
@@ -1258,6 +1829,9 @@ where
                         key, records, quorum, cache_at
                     } = &mut query.inner.info {
                         if let Some(record) = record {
+                            self.queued_events.push_back(NetworkBehaviourAction::GenerateEvent(
+                                KademliaEvent::QueryProgress(QueryProgress::Record(record.clone()))
+                            ));
                             records.push(record);
                             if records.len() == quorum.get() {
                                 query.finish()
@@ -1319,6 +1893,19 @@ where
         // Calculate the available capacity for queries triggered by background jobs.
         let mut jobs_query_capacity = JOBS_MAX_QUERIES - self.queries.size();
 
+        // Re-publish the provider records once the addresses of the local
+        // node are stable again.
+        if let Some(mut timer) = self.address_change_timer.take() {
+            match timer.poll() {
+                Ok(Async::NotReady) => self.address_change_timer = Some(timer),
+                Ok(Async::Ready(())) | Err(_) => {
+                    if let Some(job) = self.add_provider_job.as_mut() {
+                        job.asap();
+                    }
+                }
+            }
+        }
+
         // Run the periodic provider announcement job.
         if let Some(mut job) = self.add_provider_job.take() {
             let num = usize::min(JOBS_MAX_NEW_QUERIES, jobs_query_capacity);
@@ -1351,6 +1938,20 @@ where
             self.put_record_job = Some(job);
         }
 
+        // Bootstrap periodically to refresh the routing table.
+        let mut bootstrap = false;
+        if let Some((timer, interval)) = &mut self.bootstrap_timer {
+            if let Ok(Async::Ready(())) = timer.poll() {
+                timer.reset(now + *interval);
+                // Register the task to be notified of the next deadline.
+                let _ = timer.poll();
+                bootstrap = true;
+            }
+        }
+        if bootstrap && self.kbuckets.iter().next().is_some() {
+            self.bootstrap()
+        }
+
         loop {
             // Drain queued events first.
             if let Some(event) = self.queued_events.pop_front() {
@@ -1370,7 +1971,7 @@ where
 
             // Look for a finished query.
             loop {
-                match self.queries.poll(now) {
+                match self.queries.poll(now, |peer| parameters.peer_stats(peer).and_then(|s| s.rtt())) {
                     QueryPoolState::Finished(q) => {
                         if let Some(event) = self.query_finished(q, parameters) {
                             return Async::Ready(NetworkBehaviourAction::GenerateEvent(event))
@@ -1396,10 +1997,12 @@ where
                                 peer_id, event
                             });
                         } else if &peer_id != self.kbuckets.local_key().preimage() {
-                            self.pending_rpcs.push((peer_id.clone(), event));
-                            self.queued_events.push_back(NetworkBehaviourAction::DialPeer {
-                                peer_id
-                            });
+                            if self.auto_dial {
+                                self.pending_rpcs.push((peer_id.clone(), event));
+                                self.dial(peer_id);
+                            } else {
+                                query.on_failure(&peer_id);
+                            }
                         }
                     }
                     QueryPoolState::Waiting(None) | QueryPoolState::Idle => break,
@@ -1421,9 +2024,13 @@ where
 /// for a query to succeed.
 #[derive(Debug, Copy, Clone, PartialEq, Eq)]
 pub enum Quorum {
+    /// A single node, as in standard Kademlia.
     One,
+    /// More than half of the replication factor.
     Majority,
+    /// As many nodes as the replication factor.
     All,
+    /// The given number of nodes, capped at the replication factor.
     N(NonZeroUsize)
 }
 
@@ -1445,31 +2052,39 @@ impl Quorum {
 /// The events produced by the `Kademlia` behaviour.
 ///
 /// See [`Kademlia::poll`].
+///
+/// The results of queries are accompanied by the [`QueryStats`] of the
+/// query, e.g. the number of requests sent and the duration of the query.
 #[derive(Debug)]
 pub enum KademliaEvent {
     /// The result of [`Kademlia::bootstrap`].
-    BootstrapResult(BootstrapResult),
+    BootstrapResult(BootstrapResult, QueryStats),
 
     /// The result of [`Kademlia::get_closest_peers`].
-    GetClosestPeersResult(GetClosestPeersResult),
+    GetClosestPeersResult(GetClosestPeersResult, QueryStats),
 
     /// The result of [`Kademlia::get_providers`].
-    GetProvidersResult(GetProvidersResult),
+    GetProvidersResult(GetProvidersResult, QueryStats),
 
     /// The result of [`Kademlia::start_providing`].
-    StartProvidingResult(AddProviderResult),
+    StartProvidingResult(AddProviderResult, QueryStats),
 
     /// The result of a (automatic) republishing of a provider record.
-    RepublishProviderResult(AddProviderResult),
+    RepublishProviderResult(AddProviderResult, QueryStats),
 
     /// The result of [`Kademlia::get_record`].
-    GetRecordResult(GetRecordResult),
+    GetRecordResult(GetRecordResult, QueryStats),
 
     /// The result of [`Kademlia::put_record`].
-    PutRecordResult(PutRecordResult),
+    PutRecordResult(PutRecordResult, QueryStats),
 
     /// The result of a (automatic) republishing of a (value-)record.
-    RepublishRecordResult(PutRecordResult),
+    RepublishRecordResult(PutRecordResult, QueryStats),
+
+    /// A result found by an ongoing lookup, reported before the final result of the lookup.
+    ///
+    /// See [`Kademlia::finish_query`] to end a lookup early.
+    QueryProgress(QueryProgress),
 
     /// A peer has been discovered during a query.
     Discovered {
@@ -1499,9 +2114,34 @@ pub enum KademliaEvent {
     /// listen address for the peer must be provided via [`Kademlia::add_address`].
     UnroutablePeer {
         peer: PeerId
+    },
+
+    /// A peer has connected on a known listen address, but has not been added
+    /// to the routing table because of [`KademliaBucketInserts::Manual`].
+    ///
+    /// The peer is added to the routing table once confirmed with
+    /// [`Kademlia::add_address`].
+    RoutablePeer {
+        peer: PeerId,
+        address: Multiaddr,
     }
 }
 
+/// A result found by an ongoing lookup.
+#[derive(Debug, Clone)]
+pub enum QueryProgress {
+    /// A peer close to the key of a [`Kademlia::get_closest_peers`] lookup responded. The
+    /// final result of the lookup consists of the closest of these peers.
+    ClosestPeer { key: Multihash, peer: PeerId },
+
+    /// A [`Kademlia::get_providers`] lookup found a provider. Each provider is only reported
+    /// once per lookup.
+    Provider { key: Multihash, provider: PeerId },
+
+    /// A [`Kademlia::get_record`] lookup obtained a record from a remote peer.
+    Record(Record),
+}
+
 /// The result of [`Kademlia::get_record`].
 pub type GetRecordResult = Result<GetRecordOk, GetRecordError>;
 
@@ -1514,8 +2154,11 @@ pub struct GetRecordOk {
 /// The error result of [`Kademlia::get_record`].
 #[derive(Debug, Clone)]
 pub enum GetRecordError {
+    /// No peer returned a record.
     NotFound { key: Multihash, closest_peers: Vec<PeerId> },
+    /// The lookup finished with fewer records than the quorum.
     QuorumFailed { key: Multihash, records: Vec<Record>, quorum: NonZeroUsize },
+    /// The lookup timed out before obtaining the quorum.
     Timeout { key: Multihash, records: Vec<Record>, quorum: NonZeroUsize }
 }
 
@@ -1552,16 +2195,19 @@ pub struct PutRecordOk {
 /// The error result of [`Kademlia::put_record`].
 #[derive(Debug)]
 pub enum PutRecordError {
+    /// Fewer peers than the quorum stored the record.
     QuorumFailed {
         key: Multihash,
         num_results: usize,
         quorum: NonZeroUsize
     },
+    /// The operation timed out before the quorum stored the record.
     Timeout {
         key: Multihash,
         num_results: usize,
         quorum: NonZeroUsize
     },
+    /// The record could not be stored locally.
     LocalStorageError {
         key: Multihash,
         cause: store::Error
@@ -130,7 +130,7 @@ fn bootstrap() {
                 for (i, swarm) in swarms.iter_mut().enumerate() {
                     loop {
                         match swarm.poll().unwrap() {
-                            Async::Ready(Some(KademliaEvent::BootstrapResult(Ok(ok)))) => {
+                            Async::Ready(Some(KademliaEvent::BootstrapResult(Ok(ok), _))) => {
                                 assert_eq!(i, 0);
                                 assert_eq!(ok.peer, swarm_ids[0]);
                                 let known = swarm.kbuckets.iter()
@@ -154,6 +154,143 @@ fn bootstrap() {
     }
 }
 
+#[test]
+fn periodic_bootstrap() {
+    let mut config = KademliaConfig::default();
+    config.set_bootstrap_interval(Some(Duration::from_millis(10)));
+    let (swarm_ids, mut swarms) = build_connected_nodes_with_config(2, 1, config);
+
+    // Only the first node knows another node, so it is the only one bootstrapping.
+    current_thread::run(
+        future::poll_fn(move || {
+            for (i, swarm) in swarms.iter_mut().enumerate() {
+                loop {
+                    match swarm.poll().unwrap() {
+                        Async::Ready(Some(KademliaEvent::BootstrapResult(Ok(ok), _))) => {
+                            assert_eq!(i, 0);
+                            assert_eq!(ok.peer, swarm_ids[0]);
+                            return Ok(Async::Ready(()));
+                        }
+                        Async::Ready(_) => (),
+                        Async::NotReady => break,
+                    }
+                }
+            }
+            Ok(Async::NotReady)
+        }))
+}
+
+#[test]
+fn manual_routing_table_updates() {
+    let (_, mut swarms) = build_nodes(1);
+    let peer = PeerId::random();
+    let addr1: Multiaddr = Protocol::Memory(1).into();
+    let addr2: Multiaddr = Protocol::Memory(2).into();
+
+    // Manually added addresses bypass the routing filter.
+    swarms[0].set_routing_filter(|_, _| false);
+    swarms[0].add_address(&peer, addr1.clone());
+    swarms[0].add_address(&peer, addr2.clone());
+    assert!(swarms[0].kbuckets_entries().any(|p| p == &peer));
+
+    // Removing the last address removes the peer.
+    swarms[0].remove_address(&peer, &addr1);
+    assert!(swarms[0].kbuckets_entries().any(|p| p == &peer));
+    swarms[0].remove_address(&peer, &addr2);
+    assert!(!swarms[0].kbuckets_entries().any(|p| p == &peer));
+
+    swarms[0].add_address(&peer, addr1.clone());
+    assert_eq!(swarms[0].remove_peer(&peer).map(Addresses::into_vec), Some(vec![addr1]));
+    assert!(swarms[0].remove_peer(&peer).is_none());
+}
+
+#[test]
+fn routing_table_restored() {
+    let (_, mut swarms) = build_nodes(2);
+    let peer = PeerId::random();
+    let addr: Multiaddr = Protocol::Memory(1).into();
+    swarms[0].add_address(&peer, addr.clone());
+    let routing_table = swarms[0].routing_table();
+    assert_eq!(routing_table, vec![(peer.clone(), vec![addr])]);
+
+    swarms[1].restore_routing_table(routing_table);
+    assert!(swarms[1].kbuckets_entries().any(|p| p == &peer));
+}
+
+#[test]
+fn routing_filter() {
+    let (_, mut swarms) = build_nodes(1);
+    let allowed = PeerId::random();
+    let denied = PeerId::random();
+    let addr: Multiaddr = Protocol::Memory(1).into();
+
+    let allowed2 = allowed.clone();
+    swarms[0].set_routing_filter(move |p, _| p == &allowed2);
+    swarms[0].connection_updated(allowed.clone(), Some(addr.clone()), NodeStatus::Connected);
+    swarms[0].connection_updated(denied.clone(), Some(addr), NodeStatus::Connected);
+
+    let peers = swarms[0].kbuckets_entries().cloned().collect::<Vec<_>>();
+    assert_eq!(peers, vec![allowed]);
+}
+
+#[test]
+fn manual_bucket_inserts() {
+    let mut cfg = KademliaConfig::default();
+    cfg.set_kbucket_inserts(KademliaBucketInserts::Manual);
+    let (_, mut swarms) = build_nodes_with_config(1, cfg);
+    let peer = PeerId::random();
+    let addr: Multiaddr = Protocol::Memory(1).into();
+
+    swarms[0].connection_updated(peer.clone(), Some(addr.clone()), NodeStatus::Connected);
+    assert!(!swarms[0].kbuckets_entries().any(|p| p == &peer));
+    match swarms[0].queued_events.pop_front() {
+        Some(NetworkBehaviourAction::GenerateEvent(KademliaEvent::RoutablePeer { peer: p, address })) => {
+            assert_eq!(p, peer);
+            assert_eq!(address, addr);
+        }
+        _ => panic!("Expected a `RoutablePeer` event"),
+    }
+
+    // The application confirms the peer.
+    swarms[0].add_address(&peer, addr);
+    assert!(swarms[0].kbuckets_entries().any(|p| p == &peer));
+}
+
+fn dialed_peers(swarm: &mut TestSwarm) -> Vec<PeerId> {
+    swarm.queued_events.drain(..)
+        .filter_map(|e| match e {
+            NetworkBehaviourAction::DialPeer { peer_id } => Some(peer_id),
+            _ => None,
+        })
+        .collect()
+}
+
+#[test]
+fn auto_dial_disabled() {
+    let mut cfg = KademliaConfig::default();
+    cfg.set_auto_dial(false);
+    let (_, mut swarms) = build_nodes_with_config(1, cfg);
+
+    swarms[0].dial(PeerId::random());
+    assert!(dialed_peers(&mut swarms[0]).is_empty());
+}
+
+#[test]
+fn max_concurrent_dials() {
+    let mut cfg = KademliaConfig::default();
+    cfg.set_max_concurrent_dials(NonZeroUsize::new(1));
+    let (_, mut swarms) = build_nodes_with_config(1, cfg);
+    let (a, b) = (PeerId::random(), PeerId::random());
+
+    swarms[0].dial(a.clone());
+    swarms[0].dial(b.clone());
+    assert_eq!(dialed_peers(&mut swarms[0]), vec![a.clone()]);
+
+    // The second dial starts once the first one is over.
+    swarms[0].inject_dial_failure(&a);
+    assert_eq!(dialed_peers(&mut swarms[0]), vec![b]);
+}
+
 #[test]
 fn query_iter() {
     fn distances<K>(key: &kbucket::Key<K>, peers: Vec<PeerId>) -> Vec<Distance> {
@@ -185,7 +322,7 @@ fn query_iter() {
                 for (i, swarm) in swarms.iter_mut().enumerate() {
                     loop {
                         match swarm.poll().unwrap() {
-                            Async::Ready(Some(KademliaEvent::GetClosestPeersResult(Ok(ok)))) => {
+                            Async::Ready(Some(KademliaEvent::GetClosestPeersResult(Ok(ok), _))) => {
                                 assert_eq!(ok.key, search_target);
                                 assert_eq!(swarm_ids[i], expected_swarm_id);
                                 assert!(expected_peer_ids.iter().all(|p| ok.peers.contains(p)));
@@ -229,7 +366,7 @@ fn unresponsive_not_returned_direct() {
             for swarm in &mut swarms {
                 loop {
                     match swarm.poll().unwrap() {
-                        Async::Ready(Some(KademliaEvent::GetClosestPeersResult(Ok(ok)))) => {
+                        Async::Ready(Some(KademliaEvent::GetClosestPeersResult(Ok(ok), _))) => {
                             assert_eq!(ok.key, search_target);
                             assert_eq!(ok.peers.len(), 0);
                             return Ok(Async::Ready(()));
@@ -244,6 +381,77 @@ fn unresponsive_not_returned_direct() {
         }))
 }
 
+#[test]
+fn query_stats() {
+    // Ask a node that only knows about unreachable peers for the closest peers,
+    // one request at a time, and check that every request is accounted for.
+
+    let mut cfg = KademliaConfig::default();
+    cfg.set_parallelism(NonZeroUsize::new(1).unwrap());
+    let (_, mut swarms) = build_nodes_with_config(1, cfg);
+
+    let num_peers = 10;
+    for _ in 0 .. num_peers {
+        swarms[0].add_address(&PeerId::random(), Protocol::Udp(10u16).into());
+    }
+
+    swarms[0].get_closest_peers(PeerId::random());
+
+    current_thread::run(
+        future::poll_fn(move || {
+            for swarm in &mut swarms {
+                loop {
+                    match swarm.poll().unwrap() {
+                        Async::Ready(Some(KademliaEvent::GetClosestPeersResult(Ok(_), stats))) => {
+                            assert_eq!(stats.num_requests(), num_peers);
+                            assert_eq!(stats.num_failures(), num_peers);
+                            assert_eq!(stats.num_successes(), 0);
+                            assert_eq!(stats.num_pending(), 0);
+                            assert!(stats.duration().is_some());
+                            return Ok(Async::Ready(()));
+                        }
+                        Async::Ready(_) => (),
+                        Async::NotReady => break,
+                    }
+                }
+            }
+
+            Ok(Async::NotReady)
+        }))
+}
+
+#[test]
+fn client_mode_refuses_requests() {
+    // Node #1 knows about node #2, which is in client mode and must not answer.
+    let (port_base, mut swarms) = build_nodes(2);
+    let client_id = Swarm::local_peer_id(&swarms[1]).clone();
+    swarms[1].set_mode(KademliaMode::Client);
+    assert_eq!(swarms[1].mode(), KademliaMode::Client);
+    swarms[0].add_address(&client_id, Protocol::Memory(port_base + 1).into());
+
+    let search_target = PeerId::random();
+    swarms[0].get_closest_peers(search_target.clone());
+
+    current_thread::run(
+        future::poll_fn(move || {
+            for swarm in &mut swarms {
+                loop {
+                    match swarm.poll().unwrap() {
+                        Async::Ready(Some(KademliaEvent::GetClosestPeersResult(Ok(ok), _))) => {
+                            assert_eq!(ok.key, search_target);
+                            assert!(ok.peers.is_empty());
+                            return Ok(Async::Ready(()));
+                        }
+                        Async::Ready(_) => (),
+                        Async::NotReady => break,
+                    }
+                }
+            }
+
+            Ok(Async::NotReady)
+        }))
+}
+
 #[test]
 fn unresponsive_not_returned_indirect() {
     // Build two nodes. Node #2 knows about node #1. Node #1 contains fake addresses to
@@ -270,7 +478,7 @@ fn unresponsive_not_returned_indirect() {
             for swarm in &mut swarms {
                 loop {
                     match swarm.poll().unwrap() {
-                        Async::Ready(Some(KademliaEvent::GetClosestPeersResult(Ok(ok)))) => {
+                        Async::Ready(Some(KademliaEvent::GetClosestPeersResult(Ok(ok), _))) => {
                             assert_eq!(ok.key, search_target);
                             assert_eq!(ok.peers.len(), 1);
                             assert_eq!(ok.peers[0], first_peer_id);
@@ -286,6 +494,45 @@ fn unresponsive_not_returned_indirect() {
         }))
 }
 
+#[test]
+fn find_peer_routing() {
+    let (port_base, mut swarms) = build_nodes(3);
+
+    let swarm_ids: Vec<_> = swarms.iter().map(Swarm::local_peer_id).cloned().collect();
+
+    swarms[0].add_address(&swarm_ids[1], Protocol::Memory(port_base + 1).into());
+    swarms[1].add_address(&swarm_ids[2], Protocol::Memory(port_base + 2).into());
+
+    let target = swarm_ids[2].clone();
+    PeerRouting::find_peer(&mut *swarms[0], target.clone());
+
+    current_thread::run(
+        future::poll_fn(move || {
+            for swarm in &mut swarms {
+                loop {
+                    match swarm.poll().unwrap() {
+                        Async::Ready(Some(event)) => {
+                            match swarm.routing_event(&event) {
+                                Some(RoutingEvent::FoundPeer { peer_id, result }) => {
+                                    assert_eq!(peer_id, target);
+                                    let addrs = result.expect("peer to be found");
+                                    assert!(addrs.contains(&Protocol::Memory(port_base + 2).into()));
+                                    return Ok(Async::Ready(()));
+                                }
+                                Some(e) => panic!("Unexpected routing event: {:?}", e),
+                                None => (),
+                            }
+                        }
+                        Async::Ready(None) => (),
+                        Async::NotReady => break,
+                    }
+                }
+            }
+
+            Ok(Async::NotReady)
+        }))
+}
+
 #[test]
 fn get_record_not_found() {
     let (port_base, mut swarms) = build_nodes(3);
@@ -303,7 +550,7 @@ fn get_record_not_found() {
             for swarm in &mut swarms {
                 loop {
                     match swarm.poll().unwrap() {
-                        Async::Ready(Some(KademliaEvent::GetRecordResult(Err(e)))) => {
+                        Async::Ready(Some(KademliaEvent::GetRecordResult(Err(e), _))) => {
                             if let GetRecordError::NotFound { key, closest_peers, } = e {
                                 assert_eq!(key, target_key);
                                 assert_eq!(closest_peers.len(), 2);
@@ -361,8 +608,8 @@ fn put_record() {
                 for swarm in &mut swarms {
                     loop {
                         match swarm.poll().unwrap() {
-                            Async::Ready(Some(KademliaEvent::PutRecordResult(res))) |
-                            Async::Ready(Some(KademliaEvent::RepublishRecordResult(res))) => {
+                            Async::Ready(Some(KademliaEvent::PutRecordResult(res, _))) |
+                            Async::Ready(Some(KademliaEvent::RepublishRecordResult(res, _))) => {
                                 match res {
                                     Err(e) => panic!(e),
                                     Ok(ok) => {
@@ -438,6 +685,53 @@ fn put_record() {
     QuickCheck::new().tests(3).quickcheck(prop as fn(_,_))
 }
 
+#[test]
+fn put_record_without_ttl_keeps_expiration() {
+    let mut config = KademliaConfig::default();
+    config.set_record_ttl(None);
+    let (port_base, mut swarms) = build_nodes_with_config(2, config);
+
+    let swarm_ids: Vec<_> = swarms.iter().map(Swarm::local_peer_id).cloned().collect();
+
+    swarms[0].add_address(&swarm_ids[1], Protocol::Memory(port_base + 1).into());
+
+    let mut record = Record::new(multihash::encode(SHA2256, &vec![1,2,3]).unwrap(), vec![4,5,6]);
+    record.expires = Some(Instant::now() + Duration::from_secs(60));
+    swarms[0].put_record(record.clone(), Quorum::One);
+
+    current_thread::run(
+        future::poll_fn(move || {
+            for i in 0 .. swarms.len() {
+                loop {
+                    match swarms[i].poll().unwrap() {
+                        Async::Ready(Some(KademliaEvent::PutRecordResult(Ok(_), _))) => {
+                            // The expiration of the record must be preserved by the
+                            // replica, even though the replica has no TTL of its own.
+                            let replica = swarms[1].store.get(&record.key).unwrap();
+                            assert!(replica.expires.is_some());
+                            return Ok(Async::Ready(()));
+                        }
+                        Async::Ready(Some(KademliaEvent::PutRecordResult(Err(e), _))) => panic!(e),
+                        Async::Ready(_) => (),
+                        Async::NotReady => break,
+                    }
+                }
+            }
+
+            Ok(Async::NotReady)
+        }))
+}
+
+#[test]
+fn quorum_eval() {
+    let k = NonZeroUsize::new(20).unwrap();
+    assert_eq!(Quorum::One.eval(k).get(), 1);
+    assert_eq!(Quorum::Majority.eval(k).get(), 11);
+    assert_eq!(Quorum::All.eval(k).get(), 20);
+    assert_eq!(Quorum::N(NonZeroUsize::new(5).unwrap()).eval(k).get(), 5);
+    assert_eq!(Quorum::N(NonZeroUsize::new(50).unwrap()).eval(k).get(), 20);
+}
+
 #[test]
 fn get_value() {
     let (port_base, mut swarms) = build_nodes(3);
@@ -457,7 +751,7 @@ fn get_value() {
             for swarm in &mut swarms {
                 loop {
                     match swarm.poll().unwrap() {
-                        Async::Ready(Some(KademliaEvent::GetRecordResult(Ok(ok)))) => {
+                        Async::Ready(Some(KademliaEvent::GetRecordResult(Ok(ok), _))) => {
                             assert_eq!(ok.records.len(), 1);
                             assert_eq!(ok.records.first(), Some(&record));
                             return Ok(Async::Ready(()));
@@ -493,7 +787,7 @@ fn get_value_many() {
             for swarm in &mut swarms {
                 loop {
                     match swarm.poll().unwrap() {
-                        Async::Ready(Some(KademliaEvent::GetRecordResult(Ok(ok)))) => {
+                        Async::Ready(Some(KademliaEvent::GetRecordResult(Ok(ok), _))) => {
                             assert_eq!(ok.records.len(), num_results);
                             assert_eq!(ok.records.first(), Some(&record));
                             return Ok(Async::Ready(()));
@@ -507,6 +801,55 @@ fn get_value_many() {
         }))
 }
 
+#[test]
+fn get_value_finished_early() {
+    let num_nodes = 12;
+    let (_, mut swarms) = build_connected_nodes(num_nodes, num_nodes);
+
+    let record = Record::new(multihash::encode(SHA2256, &vec![1,2,3]).unwrap(), vec![4,5,6]);
+
+    for i in 1 .. num_nodes {
+        swarms[i].store.put(record.clone()).unwrap();
+    }
+
+    let quorum = Quorum::N(NonZeroUsize::new(num_nodes).unwrap());
+    swarms[0].get_record(&record.key, quorum);
+
+    let mut progress = 0;
+    current_thread::run(
+        future::poll_fn(move || {
+            for swarm in &mut swarms {
+                loop {
+                    match swarm.poll().unwrap() {
+                        Async::Ready(Some(KademliaEvent::QueryProgress(QueryProgress::Record(r)))) => {
+                            assert_eq!(r, record);
+                            progress += 1;
+                            assert!(swarm.finish_query(&record.key));
+                        }
+                        Async::Ready(Some(KademliaEvent::GetRecordResult(Err(e), _))) => {
+                            match e {
+                                GetRecordError::QuorumFailed { records, .. } => {
+                                    // Responses already in flight are still accounted for.
+                                    assert!(!records.is_empty());
+                                    assert_eq!(records.len(), progress);
+                                }
+                                e => panic!("Unexpected error result: {:?}", e),
+                            }
+                            assert!(!swarm.finish_query(&record.key));
+                            return Ok(Async::Ready(()));
+                        }
+                        Async::Ready(Some(KademliaEvent::GetRecordResult(Ok(_), _))) => {
+                            panic!("The query should have been finished early")
+                        }
+                        Async::Ready(_) => (),
+                        Async::NotReady => break,
+                    }
+                }
+            }
+            Ok(Async::NotReady)
+        }))
+}
+
 #[test]
 fn add_provider() {
     fn prop(replication_factor: usize, keys: Vec<kbucket::Key<Multihash>>) {
@@ -538,8 +881,8 @@ fn add_provider() {
                 for swarm in &mut swarms {
                     loop {
                         match swarm.poll().unwrap() {
-                            Async::Ready(Some(KademliaEvent::StartProvidingResult(res))) |
-                            Async::Ready(Some(KademliaEvent::RepublishProviderResult(res))) => {
+                            Async::Ready(Some(KademliaEvent::StartProvidingResult(res, _))) |
+                            Async::Ready(Some(KademliaEvent::RepublishProviderResult(res, _))) => {
                                 match res {
                                     Err(e) => panic!(e),
                                     Ok(ok) => {
@@ -363,7 +363,12 @@ pub enum KademliaHandlerIn<TUserData> {
         value: Vec<u8>,
         /// Identifier of the request that was made by the remote.
         request_id: KademliaRequestId,
-    }
+    },
+
+    /// Changes whether incoming Kademlia substreams are accepted from now on.
+    ///
+    /// Substreams that are already open are not affected.
+    SetAllowListening(bool),
 }
 
 /// Unique identifier for a request. Must be passed back in order to answer a request from
@@ -470,7 +475,11 @@ where
             EitherOutput::Second(p) => void::unreachable(p),
         };
 
-        debug_assert!(self.allow_listening);
+        // The substream may have been negotiated before listening was disallowed.
+        if !self.allow_listening {
+            return
+        }
+
         let connec_unique_id = self.next_connec_unique_id;
         self.next_connec_unique_id.0 += 1;
         self.substreams
@@ -479,6 +488,9 @@ where
 
     fn inject_event(&mut self, message: KademliaHandlerIn<TUserData>) {
         match message {
+            KademliaHandlerIn::SetAllowListening(allow_listening) => {
+                self.allow_listening = allow_listening;
+            }
             KademliaHandlerIn::Reset(request_id) => {
                 let pos = self.substreams.iter().position(|state| match state {
                         SubstreamState::InWaitingUser(conn_id, _) =>
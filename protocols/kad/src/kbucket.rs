@@ -43,18 +43,25 @@
 //
 // 1. Routing Table Layout
 //
-// The routing table is currently implemented as a fixed-size "array" of
-// buckets, ordered by increasing distance relative to a local key
-// that identifies the local peer. This is an often-used, simplified
-// implementation that approximates the properties of the b-tree (or prefix tree)
-// implementation described in the full paper [0], whereby buckets are split on-demand.
-// This should be treated as an implementation detail, however, so that the
-// implementation may change in the future without breaking the API.
+// The routing table is implemented as the prefix tree described in the full
+// paper [0], whereby buckets are split on-demand. Since only the bucket
+// covering the local key is ever split, the tree degenerates into a list of
+// buckets ordered by increasing distance relative to the local key that
+// identifies the local peer: the first bucket covers all the distances up to
+// a certain bucket index, and every other bucket covers a single bucket index,
+// i.e. the distances sharing the same most significant bit. The table starts
+// with a single bucket, and the first bucket is split whenever it is full and
+// a key that falls into it is looked up. The number of buckets is therefore
+// logarithmic in the number of peers known, instead of always being 256,
+// which keeps closest-peer scans cheap. This should be treated as an
+// implementation detail, however, so that the implementation may change in
+// the future without breaking the API.
 //
 // 2. Replacement Cache
 //
 // In this implementation, the "replacement cache" for unresponsive peers
-// consists of a single entry per bucket. Furthermore, this implementation is
+// consists of a configurable number of pending entries per bucket, one by
+// default (see `KBucketConfig`). Furthermore, this implementation is
 // currently tailored to connection-oriented transports, meaning that the
 // "LRU"-based ordering of entries in a bucket is actually based on the last reported
 // connection status of the corresponding peers, from least-recently (dis)connected to
@@ -73,7 +80,6 @@ mod key;
 
 pub use entry::*;
 
-use arrayvec::{self, ArrayVec};
 use bucket::KBucket;
 use std::collections::VecDeque;
 use std::time::{Duration, Instant};
@@ -86,7 +92,9 @@ const NUM_BUCKETS: usize = 256;
 pub struct KBucketsTable<TKey, TVal> {
     /// The key identifying the local peer that owns the routing table.
     local_key: TKey,
-    /// The buckets comprising the routing table.
+    /// The buckets comprising the routing table, ordered by increasing distance
+    /// to the `local_key`. The first bucket covers the bucket indices up to
+    /// `split_index()`, every other bucket covers a single bucket index.
     buckets: Vec<KBucket<TKey, TVal>>,
     /// The list of evicted entries that have been replaced with pending
     /// entries since the last call to [`KBucketsTable::take_applied_pending`].
@@ -144,9 +152,17 @@ where
     /// a [`PendingEntry`] after which it becomes eligible for insertion into
     /// a full bucket, replacing the least-recently (dis)connected node.
     pub fn new(local_key: TKey, pending_timeout: Duration) -> Self {
+        let mut config = KBucketConfig::default();
+        config.set_pending_timeout(pending_timeout);
+        KBucketsTable::with_config(local_key, config)
+    }
+
+    /// Creates a new, empty Kademlia routing table whose buckets use the
+    /// given configuration.
+    pub fn with_config(local_key: TKey, config: KBucketConfig) -> Self {
         KBucketsTable {
             local_key,
-            buckets: (0 .. NUM_BUCKETS).map(|_| KBucket::new(pending_timeout)).collect(),
+            buckets: vec![KBucket::with_config(config)],
             applied_pending: VecDeque::new()
         }
     }
@@ -161,7 +177,9 @@ where
     pub fn entry<'a>(&'a mut self, key: &'a TKey) -> Entry<'a, TKey, TVal> {
         let index = BucketIndex::new(&self.local_key.as_ref().distance(key));
         if let Some(i) = index {
-            let bucket = &mut self.buckets[i.get()];
+            self.split_for(i, key);
+            let position = self.position(i);
+            let bucket = &mut self.buckets[position];
             if let Some(applied) = bucket.apply_pending() {
                 self.applied_pending.push_back(applied)
             }
@@ -194,15 +212,19 @@ where
     /// Returns a by-reference iterator over all buckets.
     ///
     /// The buckets are ordered by proximity to the `local_key`, i.e. the first
-    /// bucket is the closest bucket (containing at most one key).
+    /// bucket is the closest bucket, covering all the distances up to the
+    /// second bucket.
     pub fn buckets<'a>(&'a mut self) -> impl Iterator<Item = KBucketRef<'a, TKey, TVal>> + 'a {
+        let split_index = self.split_index();
         let applied_pending = &mut self.applied_pending;
-        self.buckets.iter_mut().enumerate().map(move |(i, b)| {
+        self.buckets.iter_mut().enumerate().map(move |(p, b)| {
             if let Some(applied) = b.apply_pending() {
                 applied_pending.push_back(applied)
             }
+            let last = BucketIndex(split_index + p);
             KBucketRef {
-                index: BucketIndex(i),
+                first: if p == 0 { BucketIndex(0) } else { last },
+                last,
                 bucket: b
             }
         })
@@ -237,7 +259,8 @@ where
             iter: None,
             table: self,
             buckets_iter: ClosestBucketsIter::new(distance),
-            fmap: |b: &KBucket<TKey, _>| -> ArrayVec<_> {
+            position: None,
+            fmap: |b: &KBucket<TKey, _>| -> Vec<_> {
                 b.iter().map(|(n,_)| n.key.clone()).collect()
             }
         }
@@ -257,7 +280,8 @@ where
             iter: None,
             table: self,
             buckets_iter: ClosestBucketsIter::new(distance),
-            fmap: |b: &KBucket<_, TVal>| -> ArrayVec<_> {
+            position: None,
+            fmap: |b: &KBucket<_, TVal>| -> Vec<_> {
                 b.iter().map(|(n, status)| EntryView {
                     node: n.clone(),
                     status
@@ -279,15 +303,60 @@ where
         let distance = target.as_ref().distance(&local_key);
         let mut iter = ClosestBucketsIter::new(distance).take_while(|i| i.get() != 0);
         if let Some(i) = iter.next() {
-            let num_first = self.buckets[i.get()].iter()
-                .filter(|(n,_)| n.key.as_ref().distance(&local_key) <= distance)
+            let num_first = self.buckets[self.position(i)].iter()
+                .filter(|(n,_)| {
+                    let d = n.key.as_ref().distance(&local_key);
+                    BucketIndex::new(&d) == Some(i) && d <= distance
+                })
                 .count();
-            let num_rest: usize = iter.map(|i| self.buckets[i.get()].num_entries()).sum();
+            let num_rest: usize = iter.map(|i| self.num_entries_at(i)).sum();
             num_first + num_rest
         } else {
             0
         }
     }
+
+    /// Returns the highest bucket index covered by the first bucket.
+    fn split_index(&self) -> usize {
+        NUM_BUCKETS - self.buckets.len()
+    }
+
+    /// Returns the position in `buckets` of the bucket covering the given index.
+    fn position(&self, i: BucketIndex) -> usize {
+        i.get().saturating_sub(self.split_index())
+    }
+
+    /// Returns the number of entries whose distance to the `local_key` falls
+    /// into the given bucket index.
+    fn num_entries_at(&self, i: BucketIndex) -> usize {
+        let position = self.position(i);
+        if position > 0 || self.split_index() == 0 {
+            self.buckets[position].num_entries()
+        } else {
+            let local_key = self.local_key.as_ref();
+            self.buckets[0].iter()
+                .filter(|(n,_)| BucketIndex::new(&local_key.distance(&n.key)) == Some(i))
+                .count()
+        }
+    }
+
+    /// Splits the first bucket for as long as it is full and would have to
+    /// hold the given absent key, which falls into bucket index `i`.
+    ///
+    /// Each split moves the entries of the highest bucket index covered by
+    /// the first bucket into a new bucket of their own.
+    fn split_for(&mut self, i: BucketIndex, key: &TKey) {
+        while self.position(i) == 0 && self.split_index() > 0 {
+            let first = &self.buckets[0];
+            if !first.is_full() || first.position(key).is_some() || first.as_pending(key).is_some() {
+                return
+            }
+            let split_index = Some(BucketIndex(self.split_index()));
+            let local_key = self.local_key.as_ref();
+            let split = self.buckets[0].split_off(|k| BucketIndex::new(&local_key.distance(k)) == split_index);
+            self.buckets.insert(1, split);
+        }
+    }
 }
 
 /// An iterator over (some projection of) the closest entries in a
@@ -303,8 +372,12 @@ struct ClosestIter<'a, TTarget, TKey, TVal, TMap, TOut> {
     /// The iterator over the bucket indices in the order determined by the
     /// distance of the local key to the target.
     buckets_iter: ClosestBucketsIter,
+    /// The position of the last traversed bucket. The bucket indices covered
+    /// by the first bucket are consecutive in the order of `buckets_iter`, so
+    /// it is enough to skip the indices that map to this position.
+    position: Option<usize>,
     /// The iterator over the entries in the currently traversed bucket.
-    iter: Option<arrayvec::IntoIter<[TOut; K_VALUE.get()]>>,
+    iter: Option<std::vec::IntoIter<TOut>>,
     /// The projection function / mapping applied on each bucket as
     /// it is encountered, producing the next `iter`ator.
     fmap: TMap
@@ -406,7 +479,7 @@ where
     TTarget: AsRef<KeyBytes>,
     TKey: Clone + AsRef<KeyBytes>,
     TVal: Clone,
-    TMap: Fn(&KBucket<TKey, TVal>) -> ArrayVec<[TOut; K_VALUE.get()]>,
+    TMap: Fn(&KBucket<TKey, TVal>) -> Vec<TOut>,
     TOut: AsRef<KeyBytes>
 {
     type Item = TOut;
@@ -419,8 +492,17 @@ where
                     None => self.iter = None
                 }
                 None => {
-                    if let Some(i) = self.buckets_iter.next() {
-                        let bucket = &mut self.table.buckets[i.get()];
+                    let mut position = None;
+                    while let Some(i) = self.buckets_iter.next() {
+                        let p = self.table.position(i);
+                        if self.position != Some(p) {
+                            position = Some(p);
+                            break
+                        }
+                    }
+                    if let Some(p) = position {
+                        self.position = position;
+                        let bucket = &mut self.table.buckets[p];
                         if let Some(applied) = bucket.apply_pending() {
                             self.table.applied_pending.push_back(applied)
                         }
@@ -440,7 +522,10 @@ where
 
 /// A reference to a bucket in a `KBucketsTable`.
 pub struct KBucketRef<'a, TPeerId, TVal> {
-    index: BucketIndex,
+    /// The lowest bucket index covered by the bucket.
+    first: BucketIndex,
+    /// The highest bucket index covered by the bucket.
+    last: BucketIndex,
     bucket: &'a mut KBucket<TPeerId, TVal>
 }
 
@@ -461,7 +546,7 @@ where
 
     /// Tests whether the given distance falls into this bucket.
     pub fn contains(&self, d: &Distance) -> bool {
-        BucketIndex::new(d).map_or(false, |i| i == self.index)
+        BucketIndex::new(d).map_or(false, |i| self.first.get() <= i.get() && i.get() <= self.last.get())
     }
 
     /// Generates a random distance that falls into this bucket.
@@ -471,7 +556,14 @@ where
     /// the XOR distance between `a` and `b` is `d`. In other words, it gives
     /// rise to a random key falling into this bucket. See [`Key::from_distance`].
     pub fn rand_distance(&self, rng: &mut impl rand::Rng) -> Distance {
-        self.index.rand_distance(rng)
+        // Each bucket index covers twice as many distances as the one below
+        // it, so that the distance is approximately uniformly distributed
+        // over the bucket.
+        let mut i = self.last.get();
+        while i > self.first.get() && rng.gen::<bool>() {
+            i -= 1;
+        }
+        BucketIndex(i).rand_distance(rng)
     }
 }
 
@@ -490,20 +582,13 @@ mod tests {
             let local_key = Key::from(PeerId::random());
             let timeout = Duration::from_secs(g.gen_range(1, 360));
             let mut table = TestTable::new(local_key.clone().into(), timeout);
-            let mut num_total = g.gen_range(0, 100);
-            for (i, b) in &mut table.buckets.iter_mut().enumerate().rev() {
-                let ix = BucketIndex(i);
-                let num = g.gen_range(0, usize::min(K_VALUE.get(), num_total) + 1);
-                num_total -= num;
-                for _ in 0 .. num {
-                    let distance = ix.rand_distance(g);
-                    let key = local_key.for_distance(distance);
-                    let node = Node { key: key.clone(), value: () };
-                    let status = NodeStatus::arbitrary(g);
-                    match b.insert(node, status) {
-                        InsertResult::Inserted => {}
-                        _ => panic!()
-                    }
+            let num_total = g.gen_range(0, 100);
+            for _ in 0 .. num_total {
+                let ix = BucketIndex(g.gen_range(0, NUM_BUCKETS));
+                let key = local_key.for_distance(ix.rand_distance(g));
+                let status = NodeStatus::arbitrary(g);
+                if let Entry::Absent(e) = table.entry(&key) {
+                    let _ = e.insert((), status);
                 }
             }
             table
@@ -621,7 +706,8 @@ mod tests {
         }
 
         // Expire the timeout for the pending entry on the full bucket.`
-        let full_bucket = &mut table.buckets[full_bucket_index.unwrap().get()];
+        let full_bucket_position = table.position(full_bucket_index.unwrap());
+        let full_bucket = &mut table.buckets[full_bucket_position];
         let elapsed = Instant::now() - Duration::from_secs(1);
         full_bucket.pending_mut().unwrap().set_ready_at(elapsed);
 
@@ -639,6 +725,35 @@ mod tests {
         assert_eq!(None, table.take_applied_pending());
     }
 
+    #[test]
+    fn buckets_are_split_on_demand() {
+        let local_key = Key::from(PeerId::random());
+        let mut table = KBucketsTable::<_, ()>::new(local_key.clone(), Duration::from_secs(5));
+        assert_eq!(table.buckets.len(), 1);
+
+        for _ in 0 .. 1000 {
+            let key = Key::from(PeerId::random());
+            if let Entry::Absent(e) = table.entry(&key) {
+                let _ = e.insert((), NodeStatus::Connected);
+            }
+        }
+
+        // Random keys mostly fall into the farthest bucket indices, hence only
+        // a few splits are needed.
+        assert!(table.buckets.len() > 1 && table.buckets.len() < 32);
+        let split_index = table.split_index();
+        for (p, bucket) in table.buckets.iter().enumerate() {
+            for (n, _) in bucket.iter() {
+                let i = BucketIndex::new(&local_key.distance(&n.key)).unwrap().get();
+                if p == 0 {
+                    assert!(i <= split_index);
+                } else {
+                    assert_eq!(i, split_index + p);
+                }
+            }
+        }
+    }
+
     #[test]
     fn count_nodes_between() {
         fn prop(mut table: TestTable, target: Key<PeerId>) -> bool {
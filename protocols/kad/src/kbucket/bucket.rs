@@ -27,6 +27,7 @@
 
 pub use crate::K_VALUE;
 use super::*;
+use std::{mem, num::NonZeroUsize};
 
 /// A `PendingNode` is a `Node` that is pending insertion into a `KBucket`.
 #[derive(Debug, Clone)]
@@ -74,6 +75,10 @@ impl<TKey, TVal> PendingNode<TKey, TVal> {
     pub fn set_ready_at(&mut self, t: Instant) {
         self.replace = t;
     }
+
+    pub fn into_node(self) -> Node<TKey, TVal> {
+        self.node
+    }
 }
 
 /// A `Node` in a bucket, representing a peer participating
@@ -87,17 +92,63 @@ pub struct Node<TKey, TVal> {
     pub value: TVal,
 }
 
+/// The configuration of the buckets of a `KBucketsTable`.
+#[derive(Debug, Copy, Clone)]
+pub struct KBucketConfig {
+    max_nodes: NonZeroUsize,
+    max_pending: usize,
+    pending_timeout: Duration,
+}
+
+impl Default for KBucketConfig {
+    fn default() -> Self {
+        KBucketConfig {
+            max_nodes: K_VALUE,
+            max_pending: 1,
+            pending_timeout: Duration::from_secs(60),
+        }
+    }
+}
+
+impl KBucketConfig {
+    /// Sets the maximum number of nodes in a bucket, i.e. the `k` parameter of Kademlia.
+    ///
+    /// The default is [`K_VALUE`].
+    pub fn set_max_nodes(&mut self, max_nodes: NonZeroUsize) -> &mut Self {
+        self.max_nodes = max_nodes;
+        self
+    }
+
+    /// Sets the size of the replacement cache of a bucket, i.e. the maximum number of
+    /// nodes pending insertion into the bucket while it is full.
+    ///
+    /// The default is `1`. With `0`, nodes are never inserted into a full bucket.
+    pub fn set_max_pending(&mut self, max_pending: usize) -> &mut Self {
+        self.max_pending = max_pending;
+        self
+    }
+
+    /// Sets the duration after which a pending node becomes eligible for insertion into a
+    /// full bucket, replacing the least-recently (dis)connected node.
+    ///
+    /// The default is 60 seconds.
+    pub fn set_pending_timeout(&mut self, timeout: Duration) -> &mut Self {
+        self.pending_timeout = timeout;
+        self
+    }
+}
+
 /// The position of a node in a `KBucket`, i.e. a non-negative integer
-/// in the range `[0, K_VALUE)`.
+/// in the range `[0, k)`.
 #[derive(Copy, Clone, Debug, PartialEq, Eq, PartialOrd, Ord)]
 pub struct Position(usize);
 
-/// A `KBucket` is a list of up to `K_VALUE` keys and associated values,
+/// A `KBucket` is a list of up to `k` keys and associated values,
 /// ordered from least-recently connected to most-recently connected.
 #[derive(Debug, Clone)]
 pub struct KBucket<TKey, TVal> {
     /// The nodes contained in the bucket.
-    nodes: ArrayVec<[Node<TKey, TVal>; K_VALUE.get()]>,
+    nodes: Vec<Node<TKey, TVal>>,
 
     /// The position (index) in `nodes` that marks the first connected node.
     ///
@@ -105,7 +156,7 @@ pub struct KBucket<TKey, TVal> {
     /// most-recently connected, all entries above this index are also considered
     /// connected, i.e. the range `[0, first_connected_pos)` marks the sub-list of entries
     /// that are considered disconnected and the range
-    /// `[first_connected_pos, K_VALUE)` marks sub-list of entries that are
+    /// `[first_connected_pos, k)` marks sub-list of entries that are
     /// considered connected.
     ///
     /// `None` indicates that there are no connected entries in the bucket, i.e.
@@ -113,15 +164,18 @@ pub struct KBucket<TKey, TVal> {
     /// considered disconnected.
     first_connected_pos: Option<usize>,
 
-    /// A node that is pending to be inserted into a full bucket, should the
-    /// least-recently connected (and currently disconnected) node not be
-    /// marked as connected within `unresponsive_timeout`.
-    pending: Option<PendingNode<TKey, TVal>>,
-
-    /// The timeout window before a new pending node is eligible for insertion,
-    /// if the least-recently connected node is not updated as being connected
-    /// in the meantime.
-    pending_timeout: Duration
+    /// The replacement cache, i.e. the nodes that are pending to be inserted
+    /// into the full bucket, ordered by the time at which they become eligible.
+    /// The first pending node is inserted should the least-recently connected
+    /// (and currently disconnected) node not be marked as connected within
+    /// `config.pending_timeout`.
+    pending: VecDeque<PendingNode<TKey, TVal>>,
+
+    /// The maximum number of nodes and pending nodes, and the timeout window
+    /// before a new pending node is eligible for insertion, if the
+    /// least-recently connected node is not updated as being connected in the
+    /// meantime.
+    config: KBucketConfig
 }
 
 /// The result of inserting an entry into a bucket.
@@ -162,30 +216,49 @@ where
     TKey: Clone + AsRef<KeyBytes>,
     TVal: Clone
 {
-    /// Creates a new `KBucket` with the given timeout for pending entries.
+    /// Creates a new `KBucket` with the default configuration and the given
+    /// timeout for pending entries.
     pub fn new(pending_timeout: Duration) -> Self {
+        let mut config = KBucketConfig::default();
+        config.set_pending_timeout(pending_timeout);
+        KBucket::with_config(config)
+    }
+
+    /// Creates a new `KBucket` with the given configuration.
+    pub fn with_config(config: KBucketConfig) -> Self {
         KBucket {
-            nodes: ArrayVec::new(),
+            nodes: Vec::with_capacity(config.max_nodes.get()),
             first_connected_pos: None,
-            pending: None,
-            pending_timeout,
+            pending: VecDeque::new(),
+            config,
         }
     }
 
-    /// Returns a reference to the pending node of the bucket, if there is any.
+    /// Returns a reference to the first pending node of the bucket, if there is any.
     pub fn pending(&self) -> Option<&PendingNode<TKey, TVal>> {
-        self.pending.as_ref()
+        self.pending.front()
     }
 
-    /// Returns a mutable reference to the pending node of the bucket, if there is any.
+    /// Returns a mutable reference to the first pending node of the bucket, if there is any.
     pub fn pending_mut(&mut self) -> Option<&mut PendingNode<TKey, TVal>> {
-        self.pending.as_mut()
+        self.pending.front_mut()
     }
 
-    /// Returns a reference to the pending node of the bucket, if there is any
-    /// with a matching key.
+    /// Returns a reference to the pending node of the bucket with a matching key,
+    /// if there is any.
     pub fn as_pending(&self, key: &TKey) -> Option<&PendingNode<TKey, TVal>> {
-        self.pending().filter(|p| p.node.key.as_ref() == key.as_ref())
+        self.pending.iter().find(|p| p.node.key.as_ref() == key.as_ref())
+    }
+
+    /// Returns a mutable reference to the pending node of the bucket with a
+    /// matching key, if there is any.
+    pub fn as_pending_mut(&mut self, key: &TKey) -> Option<&mut PendingNode<TKey, TVal>> {
+        self.pending.iter_mut().find(|p| p.node.key.as_ref() == key.as_ref())
+    }
+
+    /// Returns `true` if the bucket holds the maximum number of nodes.
+    pub fn is_full(&self) -> bool {
+        self.nodes.len() >= self.config.max_nodes.get()
     }
 
     /// Returns a reference to a node in the bucket.
@@ -198,16 +271,16 @@ where
         self.nodes.iter().enumerate().map(move |(p, n)| (n, self.status(Position(p))))
     }
 
-    /// Inserts the pending node into the bucket, if its timeout has elapsed,
+    /// Inserts the first pending node into the bucket, if its timeout has elapsed,
     /// replacing the least-recently connected node.
     ///
     /// If a pending node has been inserted, its key is returned together with
     /// the node that was replaced. `None` indicates that the nodes in the
     /// bucket remained unchanged.
     pub fn apply_pending(&mut self) -> Option<AppliedPending<TKey, TVal>> {
-        if let Some(pending) = self.pending.take() {
+        if let Some(pending) = self.pending.pop_front() {
             if pending.replace <= Instant::now() {
-                if self.nodes.is_full() {
+                if self.is_full() {
                     if self.status(Position(0)) == NodeStatus::Connected {
                         // The bucket is full with connected nodes. Drop the pending node.
                         return None
@@ -250,16 +323,16 @@ where
                     }
                 }
             } else {
-                self.pending = Some(pending);
+                self.pending.push_front(pending);
             }
         }
 
         return None
     }
 
-    /// Updates the status of the pending node, if any.
-    pub fn update_pending(&mut self, status: NodeStatus) {
-        if let Some(pending) = &mut self.pending {
+    /// Updates the status of the pending node with a matching key, if any.
+    pub fn update_pending(&mut self, key: &TKey, status: NodeStatus) {
+        if let Some(pending) = self.as_pending_mut(key) {
             pending.status = status
         }
     }
@@ -272,12 +345,33 @@ where
         // prefix list of disconnected nodes or the suffix list of connected
         // nodes (i.e. most-recently disconnected or most-recently connected,
         // respectively).
+        if let Some((node, _, pos)) = self.remove(key) {
+            // If the least-recently connected node re-establishes its
+            // connected status, drop the pending node that was waiting
+            // to replace it.
+            if pos == Position(0) && status == NodeStatus::Connected {
+                self.pending.pop_front();
+            }
+            // Reinsert the node with the desired status.
+            match self.insert(node, status) {
+                InsertResult::Inserted => {},
+                _ => unreachable!("The node is removed before being (re)inserted.")
+            }
+        }
+    }
+
+    /// Removes the node referred to by the given key, if it is in the bucket,
+    /// returning it together with its status and former position.
+    ///
+    /// A pending node, if any, is not inserted in its place before the next call
+    /// to `apply_pending`.
+    pub fn remove(&mut self, key: &TKey) -> Option<(Node<TKey, TVal>, NodeStatus, Position)> {
         if let Some(pos) = self.position(key) {
             // Remove the node from its current position.
-            let old_status = self.status(pos);
+            let status = self.status(pos);
             let node = self.nodes.remove(pos.0);
             // Adjust `first_connected_pos` accordingly.
-            match old_status {
+            match status {
                 NodeStatus::Connected =>
                     if self.first_connected_pos.map_or(false, |p| p == pos.0) {
                         if pos.0 == self.nodes.len() {
@@ -289,17 +383,48 @@ where
                     self.first_connected_pos = self.first_connected_pos
                         .and_then(|p| p.checked_sub(1))
             }
-            // If the least-recently connected node re-establishes its
-            // connected status, drop the pending node.
-            if pos == Position(0) && status == NodeStatus::Connected {
-                self.pending = None
-            }
-            // Reinsert the node with the desired status.
-            match self.insert(node, status) {
+            Some((node, status, pos))
+        } else {
+            None
+        }
+    }
+
+    /// Removes the pending node with a matching key, if any.
+    pub fn remove_pending(&mut self, key: &TKey) -> Option<PendingNode<TKey, TVal>> {
+        let pos = self.pending.iter().position(|p| p.node.key.as_ref() == key.as_ref())?;
+        self.pending.remove(pos)
+    }
+
+    /// Moves the nodes and pending nodes whose key satisfies `f` into a new
+    /// bucket with the same configuration, preserving their status and
+    /// relative order.
+    pub fn split_off<F>(&mut self, f: F) -> KBucket<TKey, TVal>
+    where
+        F: Fn(&TKey) -> bool
+    {
+        let mut other = KBucket::with_config(self.config);
+        let nodes = mem::replace(&mut self.nodes, Vec::with_capacity(self.config.max_nodes.get()));
+        let first_connected_pos = self.first_connected_pos.take();
+        for (pos, node) in nodes.into_iter().enumerate() {
+            let status = if first_connected_pos.map_or(false, |p| pos >= p) {
+                NodeStatus::Connected
+            } else {
+                NodeStatus::Disconnected
+            };
+            let bucket = if f(&node.key) { &mut other } else { &mut *self };
+            match bucket.insert(node, status) {
                 InsertResult::Inserted => {},
-                _ => unreachable!("The node is removed before being (re)inserted.")
+                _ => unreachable!("The nodes come from a bucket with the same capacity.")
             }
         }
+        for pending in mem::replace(&mut self.pending, VecDeque::new()) {
+            if f(&pending.node.key) {
+                other.pending.push_back(pending)
+            } else {
+                self.pending.push_back(pending)
+            }
+        }
+        other
     }
 
     /// Inserts a new node into the bucket with the given status.
@@ -307,9 +432,9 @@ where
     /// The status of the node to insert determines the result as follows:
     ///
     ///   * `NodeStatus::Connected`: If the bucket is full and either all nodes are connected
-    ///     or there is already a pending node, insertion fails with `InsertResult::Full`.
-    ///     If the bucket is full but at least one node is disconnected and there is no pending
-    ///     node, the new node is inserted as pending, yielding `InsertResult::Pending`.
+    ///     or the replacement cache is full, insertion fails with `InsertResult::Full`.
+    ///     If the bucket is full but at least one node is disconnected and the replacement
+    ///     cache has room, the new node is inserted as pending, yielding `InsertResult::Pending`.
     ///     Otherwise the bucket has free slots and the new node is added to the end of the
     ///     bucket as the most-recently connected node.
     ///
@@ -322,14 +447,16 @@ where
     pub fn insert(&mut self, node: Node<TKey, TVal>, status: NodeStatus) -> InsertResult<TKey> {
         match status {
             NodeStatus::Connected => {
-                if self.nodes.is_full() {
-                    if self.first_connected_pos == Some(0) || self.pending.is_some() {
+                if self.is_full() {
+                    if self.first_connected_pos == Some(0)
+                        || self.pending.len() >= self.config.max_pending
+                    {
                         return InsertResult::Full
                     } else {
-                        self.pending = Some(PendingNode {
+                        self.pending.push_back(PendingNode {
                             node,
                             status: NodeStatus::Connected,
-                            replace: Instant::now() + self.pending_timeout,
+                            replace: Instant::now() + self.config.pending_timeout,
                         });
                         return InsertResult::Pending {
                             disconnected: self.nodes[0].key.clone()
@@ -342,7 +469,7 @@ where
                 InsertResult::Inserted
             }
             NodeStatus::Disconnected => {
-                if self.nodes.is_full() {
+                if self.is_full() {
                     return InsertResult::Full
                 }
                 if let Some(ref mut first_connected_pos) = self.first_connected_pos {
@@ -597,6 +724,35 @@ mod tests {
         assert_eq!(K_VALUE.get() - 1, bucket.num_disconnected());
     }
 
+    #[test]
+    fn replacement_cache() {
+        let mut config = KBucketConfig::default();
+        config.set_max_pending(2);
+        let mut bucket = KBucket::<Key<PeerId>, ()>::with_config(config);
+        fill_bucket(&mut bucket, NodeStatus::Disconnected);
+
+        let nodes = (0 .. 3)
+            .map(|_| Node { key: Key::new(PeerId::random()), value: () })
+            .collect::<Vec<_>>();
+        for node in &nodes[.. 2] {
+            match bucket.insert(node.clone(), NodeStatus::Connected) {
+                InsertResult::Pending { .. } => {},
+                x => panic!("{:?}", x)
+            }
+        }
+
+        // The replacement cache is full.
+        match bucket.insert(nodes[2].clone(), NodeStatus::Connected) {
+            InsertResult::Full => {},
+            x => panic!("{:?}", x)
+        }
+
+        // The pending nodes are applied in the order they were inserted.
+        bucket.pending_mut().unwrap().set_ready_at(Instant::now() - Duration::from_secs(1));
+        assert_eq!(bucket.apply_pending().map(|a| a.inserted), Some(nodes[0].clone()));
+        assert_eq!(bucket.pending().map(|p| p.key()), Some(&nodes[1].key));
+        assert!(bucket.as_pending(&nodes[1].key).is_some());
+    }
 
     #[test]
     fn bucket_update() {
@@ -627,4 +783,30 @@ mod tests {
 
         quickcheck(prop as fn(_,_,_) -> _);
     }
+
+    #[test]
+    fn bucket_remove() {
+        fn prop(mut bucket: KBucket<Key<PeerId>, ()>, pos: Position) -> bool {
+            let num_nodes = bucket.num_entries();
+
+            // Capture position and key of the random node to remove.
+            let pos = pos.0 % num_nodes;
+            let key = bucket.nodes[pos].key.clone();
+
+            // Record the (ordered) list of status of all nodes in the bucket.
+            let mut expected = bucket.iter().map(|(n,s)| (n.key.clone(), s)).collect::<Vec<_>>();
+            let (_, status) = expected.remove(pos);
+
+            // Remove the node from the bucket.
+            let removed = bucket.remove(&key).map(|(n, s, p)| (n.key, s, p.0));
+
+            // The status and relative order of all other nodes must be preserved.
+            let actual = bucket.iter().map(|(n,s)| (n.key.clone(), s)).collect::<Vec<_>>();
+            removed == Some((key.clone(), status, pos))
+                && bucket.position(&key).is_none()
+                && expected == actual
+        }
+
+        quickcheck(prop as fn(_,_) -> _);
+    }
 }
@@ -21,7 +21,7 @@
 //! The `Entry` API for quering and modifying the entries of a `KBucketsTable`
 //! representing the nodes participating in the Kademlia DHT.
 
-pub use super::bucket::{Node, NodeStatus, InsertResult, AppliedPending, K_VALUE};
+pub use super::bucket::{Node, NodeStatus, InsertResult, AppliedPending, KBucketConfig, K_VALUE};
 pub use super::key::*;
 
 use super::*;
@@ -194,6 +194,14 @@ where
         self.0.bucket.update(self.0.key, status);
         Self::new(self.0.bucket, self.0.key)
     }
+
+    /// Removes the entry from the bucket.
+    pub fn remove(self) -> EntryView<TKey, TVal> {
+        let (node, status, _pos) = self.0.bucket
+            .remove(self.0.key)
+            .expect("We can only build a PresentEntry if the entry is in the bucket; QED");
+        EntryView { node, status }
+    }
 }
 
 /// An entry waiting for a slot to be available in a bucket.
@@ -217,16 +225,26 @@ where
     /// Returns the value associated with the key.
     pub fn value(&mut self) -> &mut TVal {
         self.0.bucket
-            .pending_mut()
+            .as_pending_mut(self.0.key)
             .expect("We can only build a ConnectedPendingEntry if the entry is pending; QED")
             .value_mut()
     }
 
     /// Updates the status of the pending entry.
     pub fn update(self, status: NodeStatus) -> PendingEntry<'a, TKey, TVal> {
-        self.0.bucket.update_pending(status);
+        self.0.bucket.update_pending(self.0.key, status);
         PendingEntry::new(self.0.bucket, self.0.key)
     }
+
+    /// Removes the pending entry from the bucket.
+    pub fn remove(self) -> EntryView<TKey, TVal> {
+        let pending = self.0.bucket
+            .remove_pending(self.0.key)
+            .expect("We can only build a PendingEntry if the entry is pending; QED");
+        let status = pending.status();
+        let node = pending.into_node();
+        EntryView { node, status }
+    }
 }
 
 /// An entry that is not present in any bucket.
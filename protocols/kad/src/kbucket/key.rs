@@ -163,6 +163,17 @@ impl AsRef<KeyBytes> for KeyBytes {
 #[derive(Copy, Clone, PartialEq, Eq, Default, PartialOrd, Ord, Debug)]
 pub struct Distance(pub(super) bigint::U256);
 
+impl Distance {
+    /// Returns the integer part of the base 2 logarithm of the distance, i.e. the
+    /// position of its highest bit set, or `None` if the distance is zero.
+    ///
+    /// Two keys with the same logarithmic distance to a third key fall into the
+    /// same k-bucket of the routing table of that key.
+    pub fn ilog2(&self) -> Option<u32> {
+        (256 - self.0.leading_zeros()).checked_sub(1)
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -223,4 +234,12 @@ mod tests {
         }
         quickcheck(prop as fn(_,_) -> _)
     }
+
+    #[test]
+    fn ilog2() {
+        assert_eq!(Distance::default().ilog2(), None);
+        assert_eq!(Distance(U256::from(1)).ilog2(), Some(0));
+        assert_eq!(Distance(U256::from(5)).ilog2(), Some(2));
+        assert_eq!(Distance(U256::max_value()).ilog2(), Some(255));
+    }
 }
@@ -32,11 +32,15 @@ pub mod record;
 mod addresses;
 mod behaviour;
 mod jobs;
-mod protobuf_structs;
 mod query;
 
+/// Protobuf messages of the Kademlia protocol, generated from `dht.proto`.
+pub mod dht_proto {
+    include!(concat!(env!("OUT_DIR"), "/dht.pb.rs"));
+}
+
 pub use addresses::Addresses;
-pub use behaviour::{Kademlia, KademliaConfig, KademliaEvent, Quorum};
+pub use behaviour::{Kademlia, KademliaBucketInserts, KademliaConfig, KademliaEvent, KademliaMode, QueryProgress, Quorum};
 pub use behaviour::{
     BootstrapResult,
     BootstrapOk,
@@ -63,6 +67,7 @@ pub use behaviour::{
     GetProvidersError,
 };
 pub use protocol::KadConnectionType;
+pub use query::QueryStats;
 pub use record::{store, Record, ProviderRecord};
 
 use std::num::NonZeroUsize;
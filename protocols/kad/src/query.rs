@@ -21,10 +21,10 @@
 mod peers;
 
 use peers::PeersIterState;
-use peers::closest::{ClosestPeersIter, ClosestPeersIterConfig};
+use peers::closest::{ClosestPeersIter, ClosestPeersIterConfig, disjoint::ClosestDisjointPeersIter};
 use peers::fixed::FixedPeersIter;
 
-use crate::K_VALUE;
+use crate::{ALPHA_VALUE, K_VALUE};
 use crate::kbucket::{Key, KeyBytes};
 use either::Either;
 use fnv::FnvHashMap;
@@ -97,17 +97,44 @@ impl<TInner> QueryPool<TInner> {
         self.add(peer_iter, inner)
     }
 
+    /// Adds a query to the pool that contacts a fixed set of peers, carrying
+    /// over the statistics of a previous query.
+    ///
+    /// This is used for the second phase of queries that first look up the
+    /// closest peers to a key, so that the statistics of the final result
+    /// cover both phases.
+    pub fn add_fixed_with_stats<I>(&mut self, peers: I, inner: TInner, stats: QueryStats) -> QueryId
+    where
+        I: IntoIterator<Item = Key<PeerId>>
+    {
+        let id = self.add_fixed(peers, inner);
+        if let Some(query) = self.queries.get_mut(&id) {
+            query.stats = stats;
+        }
+        id
+    }
+
     /// Adds a query to the pool that iterates towards the closest peers to the target.
+    ///
+    /// If more than one disjoint path is configured, the query iterates over as
+    /// many disjoint paths.
     pub fn add_iter_closest<T, I>(&mut self, target: T, peers: I, inner: TInner) -> QueryId
     where
         T: Into<KeyBytes>,
         I: IntoIterator<Item = Key<PeerId>>
     {
         let cfg = ClosestPeersIterConfig {
+            parallelism: self.config.parallelism.get(),
             num_results: self.config.replication_factor.get(),
-            .. ClosestPeersIterConfig::default()
+            peer_timeout: self.config.peer_timeout,
+        };
+        let peer_iter = if self.config.disjoint_paths.get() > 1 {
+            let paths = self.config.disjoint_paths;
+            QueryPeerIter::ClosestDisjoint(
+                ClosestDisjointPeersIter::with_config(cfg, paths, target, peers))
+        } else {
+            QueryPeerIter::Closest(ClosestPeersIter::with_config(cfg, target, peers))
         };
-        let peer_iter = QueryPeerIter::Closest(ClosestPeersIter::with_config(cfg, target, peers));
         self.add(peer_iter, inner)
     }
 
@@ -130,14 +157,22 @@ impl<TInner> QueryPool<TInner> {
     }
 
     /// Polls the pool to advance the queries.
-    pub fn poll(&mut self, now: Instant) -> QueryPoolState<TInner> {
+    ///
+    /// If [`QueryConfig::prefer_low_latency`] is set, `rtt` provides the
+    /// round-trip times used to choose among equally close peers.
+    pub fn poll<F>(&mut self, now: Instant, rtt: F) -> QueryPoolState<TInner>
+    where
+        F: Fn(&PeerId) -> Option<Duration>
+    {
+        let prefer_low_latency = self.config.prefer_low_latency;
+        let latency = |peer: &PeerId| if prefer_low_latency { rtt(peer) } else { None };
         let mut finished = None;
         let mut timeout = None;
         let mut waiting = None;
 
         for (&query_id, query) in self.queries.iter_mut() {
-            query.started = query.started.or(Some(now));
-            match query.next(now) {
+            query.stats.start = query.stats.start.or(Some(now));
+            match query.next(now, &latency) {
                 PeersIterState::Finished => {
                     finished = Some(query_id);
                     break
@@ -148,7 +183,7 @@ impl<TInner> QueryPool<TInner> {
                     break
                 }
                 PeersIterState::Waiting(None) | PeersIterState::WaitingAtCapacity => {
-                    let elapsed = now - query.started.unwrap_or(now);
+                    let elapsed = now - query.stats.start.unwrap_or(now);
                     if elapsed >= self.config.timeout {
                         timeout = Some(query_id);
                         break
@@ -159,16 +194,19 @@ impl<TInner> QueryPool<TInner> {
 
         if let Some((query_id, peer_id)) = waiting {
             let query = self.queries.get_mut(&query_id).expect("s.a.");
+            query.stats.requests += 1;
             return QueryPoolState::Waiting(Some((query, peer_id)))
         }
 
         if let Some(query_id) = finished {
-            let query = self.queries.remove(&query_id).expect("s.a.");
+            let mut query = self.queries.remove(&query_id).expect("s.a.");
+            query.stats.end = Some(now);
             return QueryPoolState::Finished(query)
         }
 
         if let Some(query_id) = timeout {
-            let query = self.queries.remove(&query_id).expect("s.a.");
+            let mut query = self.queries.remove(&query_id).expect("s.a.");
+            query.stats.end = Some(now);
             return QueryPoolState::Timeout(query)
         }
 
@@ -187,15 +225,30 @@ pub struct QueryId(usize);
 /// The configuration for queries in a `QueryPool`.
 #[derive(Debug, Clone)]
 pub struct QueryConfig {
+    /// The timeout of a query as a whole.
     pub timeout: Duration,
     pub replication_factor: NonZeroUsize,
+    /// The maximum number of requests a query has in flight at any time,
+    /// i.e. the `α` parameter of the Kademlia paper.
+    pub parallelism: NonZeroUsize,
+    /// The timeout of a single request to a peer of an iterative query.
+    pub peer_timeout: Duration,
+    /// The number of disjoint paths over which iterative queries are run.
+    pub disjoint_paths: NonZeroUsize,
+    /// Whether iterative queries contact the peers with the lowest round-trip
+    /// time first among the peers equally close to the target.
+    pub prefer_low_latency: bool,
 }
 
 impl Default for QueryConfig {
     fn default() -> Self {
         QueryConfig {
             timeout: Duration::from_secs(60),
-            replication_factor: NonZeroUsize::new(K_VALUE.get()).expect("K_VALUE > 0")
+            replication_factor: NonZeroUsize::new(K_VALUE.get()).expect("K_VALUE > 0"),
+            parallelism: ALPHA_VALUE,
+            peer_timeout: Duration::from_secs(10),
+            disjoint_paths: NonZeroUsize::new(1).expect("1 > 0"),
+            prefer_low_latency: true,
         }
     }
 }
@@ -206,9 +259,8 @@ pub struct Query<TInner> {
     id: QueryId,
     /// The peer iterator that drives the query state.
     peer_iter: QueryPeerIter,
-    /// The instant when the query started (i.e. began waiting for the first
-    /// result from a peer).
-    started: Option<Instant>,
+    /// The statistics of the query.
+    stats: QueryStats,
     /// The opaque inner query state.
     pub inner: TInner,
 }
@@ -216,13 +268,14 @@ pub struct Query<TInner> {
 /// The peer selection strategies that can be used by queries.
 enum QueryPeerIter {
     Closest(ClosestPeersIter),
+    ClosestDisjoint(ClosestDisjointPeersIter),
     Fixed(FixedPeersIter)
 }
 
 impl<TInner> Query<TInner> {
     /// Creates a new query without starting it.
     fn new(id: QueryId, peer_iter: QueryPeerIter, inner: TInner) -> Self {
-        Query { id, inner, peer_iter, started: None }
+        Query { id, inner, peer_iter, stats: QueryStats::empty() }
     }
 
     /// Gets the unique ID of the query.
@@ -230,10 +283,19 @@ impl<TInner> Query<TInner> {
         self.id
     }
 
+    /// Gets the current statistics of the query.
+    pub fn stats(&self) -> &QueryStats {
+        &self.stats
+    }
+
     /// Informs the query that the attempt to contact `peer` failed.
     pub fn on_failure(&mut self, peer: &PeerId) {
+        if self.is_waiting(peer) {
+            self.stats.failure += 1;
+        }
         match &mut self.peer_iter {
             QueryPeerIter::Closest(iter) => iter.on_failure(peer),
+            QueryPeerIter::ClosestDisjoint(iter) => iter.on_failure(peer),
             QueryPeerIter::Fixed(iter) => iter.on_failure(peer)
         }
     }
@@ -245,8 +307,12 @@ impl<TInner> Query<TInner> {
     where
         I: IntoIterator<Item = PeerId>
     {
+        if self.is_waiting(peer) {
+            self.stats.success += 1;
+        }
         match &mut self.peer_iter {
             QueryPeerIter::Closest(iter) => iter.on_success(peer, new_peers),
+            QueryPeerIter::ClosestDisjoint(iter) => iter.on_success(peer, new_peers),
             QueryPeerIter::Fixed(iter) => iter.on_success(peer)
         }
     }
@@ -255,14 +321,19 @@ impl<TInner> Query<TInner> {
     pub fn is_waiting(&self, peer: &PeerId) -> bool {
         match &self.peer_iter {
             QueryPeerIter::Closest(iter) => iter.is_waiting(peer),
+            QueryPeerIter::ClosestDisjoint(iter) => iter.is_waiting(peer),
             QueryPeerIter::Fixed(iter) => iter.is_waiting(peer)
         }
     }
 
     /// Advances the state of the underlying peer iterator.
-    fn next(&mut self, now: Instant) -> PeersIterState {
+    fn next<F>(&mut self, now: Instant, rtt: F) -> PeersIterState
+    where
+        F: Fn(&PeerId) -> Option<Duration>
+    {
         match &mut self.peer_iter {
-            QueryPeerIter::Closest(iter) => iter.next(now),
+            QueryPeerIter::Closest(iter) => iter.next_with_rtt(now, rtt),
+            QueryPeerIter::ClosestDisjoint(iter) => iter.next_with_rtt(now, rtt),
             QueryPeerIter::Fixed(iter) => iter.next()
         }
     }
@@ -274,6 +345,7 @@ impl<TInner> Query<TInner> {
     pub fn finish(&mut self) {
         match &mut self.peer_iter {
             QueryPeerIter::Closest(iter) => iter.finish(),
+            QueryPeerIter::ClosestDisjoint(iter) => iter.finish(),
             QueryPeerIter::Fixed(iter) => iter.finish()
         }
     }
@@ -281,10 +353,11 @@ impl<TInner> Query<TInner> {
     /// Consumes the query, producing the final `QueryResult`.
     pub fn into_result(self) -> QueryResult<TInner, impl Iterator<Item = PeerId>> {
         let peers = match self.peer_iter {
-            QueryPeerIter::Closest(iter) => Either::Left(iter.into_result()),
+            QueryPeerIter::Closest(iter) => Either::Left(Either::Left(iter.into_result())),
+            QueryPeerIter::ClosestDisjoint(iter) => Either::Left(Either::Right(iter.into_result())),
             QueryPeerIter::Fixed(iter) => Either::Right(iter.into_result())
         };
-        QueryResult { inner: self.inner, peers }
+        QueryResult { inner: self.inner, peers, stats: self.stats }
     }
 }
 
@@ -293,6 +366,74 @@ pub struct QueryResult<TInner, TPeers> {
     /// The opaque inner query state.
     pub inner: TInner,
     /// The successfully contacted peers.
-    pub peers: TPeers
+    pub peers: TPeers,
+    /// The statistics of the query.
+    pub stats: QueryStats
+}
+
+/// Execution statistics of a query.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct QueryStats {
+    requests: u32,
+    success: u32,
+    failure: u32,
+    start: Option<Instant>,
+    end: Option<Instant>
+}
+
+impl QueryStats {
+    /// Creates statistics for a query that never sent a request.
+    pub fn empty() -> Self {
+        QueryStats {
+            requests: 0,
+            success: 0,
+            failure: 0,
+            start: None,
+            end: None,
+        }
+    }
+
+    /// Gets the total number of requests initiated by the query.
+    pub fn num_requests(&self) -> u32 {
+        self.requests
+    }
+
+    /// Gets the number of successful requests.
+    pub fn num_successes(&self) -> u32 {
+        self.success
+    }
+
+    /// Gets the number of failed requests, including the requests to peers
+    /// that could not be reached.
+    pub fn num_failures(&self) -> u32 {
+        self.failure
+    }
+
+    /// Gets the number of pending requests.
+    ///
+    /// > **Note**: A query can finish while still having pending
+    /// > requests, if the termination conditions are already met.
+    /// > Requests that exceeded the per-peer timeout also remain
+    /// > pending, even if a response eventually arrives.
+    pub fn num_pending(&self) -> u32 {
+        self.requests.saturating_sub(self.success + self.failure)
+    }
+
+    /// Gets the duration of the query.
+    ///
+    /// If the query has not yet finished, the duration is measured from the
+    /// start of the query to the current instant. If the query never started,
+    /// `None` is returned.
+    pub fn duration(&self) -> Option<Duration> {
+        if let Some(s) = self.start {
+            if let Some(e) = self.end {
+                Some(e - s)
+            } else {
+                Some(Instant::now() - s)
+            }
+        } else {
+            None
+        }
+    }
 }
 
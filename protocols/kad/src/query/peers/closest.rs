@@ -18,6 +18,8 @@
 // FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
 // DEALINGS IN THE SOFTWARE.
 
+pub mod disjoint;
+
 use super::*;
 
 use crate::{K_VALUE, ALPHA_VALUE};
@@ -258,6 +260,19 @@ impl ClosestPeersIter {
 
     /// Advances the state of the iterator, potentially getting a new peer to contact.
     pub fn next(&mut self, now: Instant) -> PeersIterState {
+        self.next_with_rtt(now, |_| None)
+    }
+
+    /// Advances the state of the iterator like [`ClosestPeersIter::next`], contacting
+    /// the peer with the lowest round-trip time returned by `rtt` among the peers
+    /// equally close to the target, i.e. at the same logarithmic distance.
+    ///
+    /// Peers without a known round-trip time are contacted after the others, and
+    /// the closest peer is contacted among peers with the same round-trip time.
+    pub fn next_with_rtt<F>(&mut self, now: Instant, rtt: F) -> PeersIterState
+    where
+        F: Fn(&PeerId) -> Option<Duration>
+    {
         if let State::Finished = self.state {
             return PeersIterState::Finished
         }
@@ -272,7 +287,10 @@ impl ClosestPeersIter {
         // Check if the iterator is at capacity w.r.t. the allowed parallelism.
         let at_capacity = self.at_capacity();
 
-        for peer in self.closest_peers.values_mut() {
+        // The distance of the closest peer to contact, if any.
+        let mut candidate = None;
+
+        for (distance, peer) in self.closest_peers.iter_mut() {
             match peer.state {
                 PeerState::Waiting(timeout) => {
                     if now >= timeout {
@@ -310,10 +328,8 @@ impl ClosestPeersIter {
 
                 PeerState::NotContacted =>
                     if !at_capacity {
-                        let timeout = now + self.config.peer_timeout;
-                        peer.state = PeerState::Waiting(timeout);
-                        self.num_waiting += 1;
-                        return PeersIterState::Waiting(Some(Cow::Borrowed(peer.key.preimage())))
+                        candidate = Some(*distance);
+                        break
                     } else {
                         return PeersIterState::WaitingAtCapacity
                     }
@@ -324,6 +340,26 @@ impl ClosestPeersIter {
             }
         }
 
+        if let Some(distance) = candidate {
+            let log_distance = distance.ilog2();
+            let fastest = self.closest_peers.range(distance ..)
+                .take_while(|(d, _)| d.ilog2() == log_distance)
+                .filter(|(_, peer)| match peer.state {
+                    PeerState::NotContacted => true,
+                    _ => false
+                })
+                .min_by_key(|(_, peer)| {
+                    let peer_rtt = rtt(peer.key.preimage());
+                    (peer_rtt.is_none(), peer_rtt)
+                })
+                .map(|(d, _)| *d)
+                .unwrap_or(distance);
+            let peer = self.closest_peers.get_mut(&fastest).expect("s.a.");
+            peer.state = PeerState::Waiting(now + self.config.peer_timeout);
+            self.num_waiting += 1;
+            return PeersIterState::Waiting(Some(Cow::Borrowed(peer.key.preimage())))
+        }
+
         if self.num_waiting > 0 {
             // The iterator is still waiting for results and not at capacity w.r.t.
             // the allowed parallelism, but there are no new peers to contact
@@ -614,6 +650,42 @@ mod tests {
         QuickCheck::new().tests(10).quickcheck(prop as fn(_) -> _)
     }
 
+    #[test]
+    fn equally_close_peers_by_rtt() {
+        let target = Key::from(Into::<Multihash>::into(PeerId::random()));
+        let peers = random_peers(20).map(Key::from).collect::<Vec<_>>();
+        let config = ClosestPeersIterConfig { parallelism: 1, .. ClosestPeersIterConfig::default() };
+        let mut iter = ClosestPeersIter::with_config(config, target.clone(), peers.clone());
+
+        // The peers at the logarithmic distance of the closest peer, by increasing distance.
+        let mut by_distance = peers.into_iter()
+            .map(|key| (key.distance(&target), key.into_preimage()))
+            .collect::<Vec<_>>();
+        by_distance.sort_by_key(|(d, _)| *d);
+        let log_distance = by_distance[0].0.ilog2();
+        let equally_close = by_distance.into_iter()
+            .take_while(|(d, _)| d.ilog2() == log_distance)
+            .map(|(_, peer)| peer)
+            .collect::<Vec<_>>();
+
+        // The farthest of them is the fastest, the closest one has no known RTT.
+        let fastest = equally_close.last().unwrap().clone();
+        let rtt = |peer: &PeerId| {
+            if peer == &equally_close[0] {
+                None
+            } else if peer == &fastest {
+                Some(Duration::from_millis(10))
+            } else {
+                Some(Duration::from_millis(100))
+            }
+        };
+        let expected = if equally_close.len() > 1 { &fastest } else { &equally_close[0] };
+        match iter.next_with_rtt(Instant::now(), rtt) {
+            PeersIterState::Waiting(Some(peer)) => assert_eq!(peer.as_ref(), expected),
+            _ => panic!("expected a peer to contact"),
+        }
+    }
+
     #[test]
     fn no_duplicates() {
         fn prop(mut iter: ClosestPeersIter) -> bool {
@@ -0,0 +1,282 @@
+// Copyright 2019 Parity Technologies (UK) Ltd.
+//
+// Permission is hereby granted, free of charge, to any person obtaining a
+// copy of this software and associated documentation files (the "Software"),
+// to deal in the Software without restriction, including without limitation
+// the rights to use, copy, modify, merge, publish, distribute, sublicense,
+// and/or sell copies of the Software, and to permit persons to whom the
+// Software is furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS
+// OR IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING
+// FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
+// DEALINGS IN THE SOFTWARE.
+
+use super::*;
+
+use fnv::FnvHashMap;
+use std::{collections::BTreeMap, num::NonZeroUsize};
+
+/// A peer iterator that looks up the closest peers to a target over several
+/// disjoint paths, as described in the S/Kademlia paper.
+///
+/// Each path is driven by its own [`ClosestPeersIter`], initialised with a share
+/// of the known closest peers. A peer is only ever contacted on behalf of the
+/// first path that selects it and is considered failed by every other path, so
+/// that an adversarial peer can only poison the path it has been reached on.
+/// The result sets of the paths are only merged when the iterator finishes.
+#[derive(Debug, Clone)]
+pub struct ClosestDisjointPeersIter {
+    /// The target whose distance to any peer determines the order of the results.
+    target: KeyBytes,
+
+    /// The number of closest peers to return in the merged result.
+    num_results: usize,
+
+    /// The iterators of the disjoint paths.
+    iters: Vec<ClosestPeersIter>,
+
+    /// The index of the path on whose behalf each peer has been contacted.
+    contacted_peers: FnvHashMap<PeerId, usize>,
+
+    /// The index of the path whose iterator is advanced first on the next call
+    /// to `next`, so that all paths get to contact peers in turn.
+    next_path: usize,
+}
+
+impl ClosestDisjointPeersIter {
+    /// Creates a new iterator with the given configuration for each path.
+    pub fn with_config<I, T>(
+        config: ClosestPeersIterConfig,
+        num_paths: NonZeroUsize,
+        target: T,
+        known_closest_peers: I,
+    ) -> Self
+    where
+        I: IntoIterator<Item = Key<PeerId>>,
+        T: Into<KeyBytes>
+    {
+        let target = target.into();
+
+        // Distribute the known closest peers among the paths.
+        let mut peers = vec![Vec::new(); num_paths.get()];
+        for (i, peer) in known_closest_peers.into_iter().enumerate() {
+            peers[i % num_paths.get()].push(peer);
+        }
+
+        let iters = peers.into_iter()
+            .map(|peers| ClosestPeersIter::with_config(config.clone(), target.clone(), peers))
+            .collect();
+
+        ClosestDisjointPeersIter {
+            target,
+            num_results: config.num_results,
+            iters,
+            contacted_peers: FnvHashMap::default(),
+            next_path: 0,
+        }
+    }
+
+    /// Callback for delivering the result of a successful request to a peer.
+    ///
+    /// The closer peers are only incorporated into the path on whose behalf
+    /// `peer` has been contacted.
+    pub fn on_success<I>(&mut self, peer: &PeerId, closer_peers: I)
+    where
+        I: IntoIterator<Item = PeerId>
+    {
+        if let Some(&i) = self.contacted_peers.get(peer) {
+            self.iters[i].on_success(peer, closer_peers)
+        }
+    }
+
+    /// Callback for informing the iterator about a failed request to a peer.
+    pub fn on_failure(&mut self, peer: &PeerId) {
+        if let Some(&i) = self.contacted_peers.get(peer) {
+            self.iters[i].on_failure(peer)
+        }
+    }
+
+    /// Returns true if the iterator is waiting for a response from the given peer.
+    pub fn is_waiting(&self, peer: &PeerId) -> bool {
+        self.iters.iter().any(|iter| iter.is_waiting(peer))
+    }
+
+    /// Advances the state of the iterator, potentially getting a new peer to contact.
+    ///
+    /// The iterator is finished once the iterators of all paths are finished.
+    pub fn next(&mut self, now: Instant) -> PeersIterState {
+        self.next_with_rtt(now, |_| None)
+    }
+
+    /// Advances the state of the iterator like [`ClosestDisjointPeersIter::next`],
+    /// preferring peers with a low round-trip time on each path, as described in
+    /// [`ClosestPeersIter::next_with_rtt`].
+    pub fn next_with_rtt<F>(&mut self, now: Instant, rtt: F) -> PeersIterState
+    where
+        F: Fn(&PeerId) -> Option<Duration>
+    {
+        let num_paths = self.iters.len();
+        let mut waiting = false;
+        let mut at_capacity = false;
+
+        for n in 0 .. num_paths {
+            let i = (self.next_path + n) % num_paths;
+            loop {
+                let peer = match self.iters[i].next_with_rtt(now, &rtt) {
+                    PeersIterState::Waiting(Some(peer)) => peer.into_owned(),
+                    PeersIterState::Waiting(None) => {
+                        waiting = true;
+                        break
+                    }
+                    PeersIterState::WaitingAtCapacity => {
+                        at_capacity = true;
+                        break
+                    }
+                    PeersIterState::Finished => break
+                };
+
+                if self.contacted_peers.contains_key(&peer) {
+                    // The peer belongs to another path, so this path
+                    // must get along without it.
+                    self.iters[i].on_failure(&peer);
+                    continue
+                }
+
+                self.contacted_peers.insert(peer.clone(), i);
+                self.next_path = (i + 1) % num_paths;
+                return PeersIterState::Waiting(Some(Cow::Owned(peer)))
+            }
+        }
+
+        if waiting {
+            PeersIterState::Waiting(None)
+        } else if at_capacity {
+            PeersIterState::WaitingAtCapacity
+        } else {
+            PeersIterState::Finished
+        }
+    }
+
+    /// Immediately transitions the iterator to [`PeersIterState::Finished`].
+    pub fn finish(&mut self) {
+        for iter in &mut self.iters {
+            iter.finish()
+        }
+    }
+
+    /// Consumes the iterator, returning the closest peers found on all paths.
+    pub fn into_result(self) -> impl Iterator<Item = PeerId> {
+        let target = self.target;
+        let closest_peers = self.iters.into_iter()
+            .flat_map(ClosestPeersIter::into_result)
+            .map(|peer| (Key::from(peer.clone()).distance(&target), peer))
+            .collect::<BTreeMap<_, _>>();
+        closest_peers.into_iter().map(|(_, peer)| peer).take(self.num_results)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use libp2p_core::PeerId;
+    use quickcheck::*;
+    use multihash::Multihash;
+    use rand::{Rng, thread_rng};
+    use std::collections::HashSet;
+
+    fn random_peers(n: usize) -> impl Iterator<Item = PeerId> + Clone {
+        (0 .. n).map(|_| PeerId::random())
+    }
+
+    fn random_iter<G: Rng>(g: &mut G) -> ClosestDisjointPeersIter {
+        let known_closest_peers = random_peers(g.gen_range(1, 60)).map(Key::from);
+        let target = Key::from(Into::<Multihash>::into(PeerId::random()));
+        let config = ClosestPeersIterConfig {
+            parallelism: g.gen_range(1, 10),
+            num_results: g.gen_range(1, 25),
+            peer_timeout: Duration::from_secs(g.gen_range(10, 30)),
+        };
+        let num_paths = NonZeroUsize::new(g.gen_range(1, 5)).unwrap();
+        ClosestDisjointPeersIter::with_config(config, num_paths, target, known_closest_peers)
+    }
+
+    impl Arbitrary for ClosestDisjointPeersIter {
+        fn arbitrary<G: Gen>(g: &mut G) -> ClosestDisjointPeersIter {
+            random_iter(g)
+        }
+    }
+
+    #[test]
+    fn new_iter() {
+        let iter = random_iter(&mut thread_rng());
+        assert!(iter.contacted_peers.is_empty());
+        assert_eq!(iter.into_result().count(), 0);
+    }
+
+    #[test]
+    fn paths_are_disjoint() {
+        fn prop(mut iter: ClosestDisjointPeersIter) {
+            let now = Instant::now();
+            let mut rng = thread_rng();
+            // A small pool of peers, so that paths are likely to be
+            // pointed to the same peers.
+            let pool = random_peers(50).collect::<Vec<_>>();
+            let mut contacted = HashSet::new();
+
+            loop {
+                match iter.next(now) {
+                    PeersIterState::Waiting(Some(peer)) => {
+                        let peer = peer.into_owned();
+                        // Every peer is contacted at most once, on behalf of a single path.
+                        assert!(contacted.insert(peer.clone()));
+                        let closer = (0 .. rng.gen_range(0, 5))
+                            .map(|_| pool[rng.gen_range(0, pool.len())].clone())
+                            .collect::<Vec<_>>();
+                        iter.on_success(&peer, closer);
+                    }
+                    PeersIterState::Finished => break,
+                    state => panic!("Unexpected iterator state: {:?}", state),
+                }
+            }
+
+            let target = iter.target.clone();
+            let num_results = iter.num_results;
+            let result = iter.into_result().map(Key::from).collect::<Vec<_>>();
+            assert!(result.len() <= num_results);
+            assert!(result.windows(2).all(|w| w[0].distance(&target) < w[1].distance(&target)));
+            assert!(result.iter().all(|k| contacted.contains(k.preimage())));
+        }
+
+        QuickCheck::new().tests(10).quickcheck(prop as fn(_))
+    }
+
+    #[test]
+    fn failures_are_reported_to_the_contacting_path() {
+        let target = Key::from(Into::<Multihash>::into(PeerId::random()));
+        let peers = random_peers(2).map(Key::from).collect::<Vec<_>>();
+        let num_paths = NonZeroUsize::new(2).unwrap();
+        let mut iter = ClosestDisjointPeersIter::with_config(
+            ClosestPeersIterConfig::default(), num_paths, target, peers);
+        let now = Instant::now();
+
+        let mut contacted = Vec::new();
+        while let PeersIterState::Waiting(Some(peer)) = iter.next(now) {
+            contacted.push(peer.into_owned());
+        }
+        assert_eq!(contacted.len(), 2);
+        assert!(contacted.iter().all(|p| iter.is_waiting(p)));
+
+        for p in &contacted {
+            iter.on_failure(p);
+        }
+        assert_eq!(iter.next(now), PeersIterState::Finished);
+        assert_eq!(iter.into_result().count(), 0);
+    }
+}
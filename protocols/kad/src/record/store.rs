@@ -18,9 +18,11 @@
 // FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
 // DEALINGS IN THE SOFTWARE.
 
+mod disk;
 mod memory;
 
-pub use memory::MemoryStore;
+pub use disk::DiskStore;
+pub use memory::{MemoryStore, MemoryStoreConfig};
 
 use crate::K_VALUE;
 use super::*;
@@ -39,6 +41,8 @@ pub enum Error {
     MaxProvidedKeys,
     /// The value of a record to be stored is too large.
     ValueTooLarge,
+    /// Writing the record to a persistent store failed.
+    Io(std::io::Error),
 }
 
 /// Trait for types implementing a record store.
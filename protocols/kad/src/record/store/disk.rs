@@ -0,0 +1,413 @@
+// Copyright 2019 Parity Technologies (UK) Ltd.
+//
+// Permission is hereby granted, free of charge, to any person obtaining a
+// copy of this software and associated documentation files (the "Software"),
+// to deal in the Software without restriction, including without limitation
+// the rights to use, copy, modify, merge, publish, distribute, sublicense,
+// and/or sell copies of the Software, and to permit persons to whom the
+// Software is furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS
+// OR IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING
+// FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
+// DEALINGS IN THE SOFTWARE.
+
+use super::*;
+
+use libp2p_core::PeerId;
+use log::warn;
+use multihash::Multihash;
+use std::borrow::Cow;
+use std::{fs, io};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use wasm_timer::Instant;
+
+/// Implementation of a `RecordStore` persisting its records in a directory.
+///
+/// All the records are kept in memory by an inner `MemoryStore`, which enforces the
+/// quotas, and every modification is written through to the disk. Opening a `DiskStore`
+/// on a directory written by a previous instance restores all the records and provider
+/// records that haven't expired in the meantime.
+///
+/// Each record is stored in its own file, named after the hexadecimal representation of its
+/// key, in the `records` sub-directory. The provider records of a key are stored together in
+/// a file of the `providers` sub-directory. Since the expiration times of the records are
+/// measured by a monotonic clock, they are converted to and from the system clock when
+/// written and read.
+pub struct DiskStore {
+    /// The in-memory copy of the stored records.
+    memory: MemoryStore,
+    /// The directory containing the records.
+    records_dir: PathBuf,
+    /// The directory containing the provider records.
+    providers_dir: PathBuf,
+}
+
+impl DiskStore {
+    /// Opens a `DiskStore` in the given directory with a default configuration.
+    ///
+    /// The directory is created if it doesn't exist.
+    pub fn open(local_id: PeerId, path: impl AsRef<Path>) -> io::Result<Self> {
+        Self::open_with_config(local_id, path, Default::default())
+    }
+
+    /// Opens a `DiskStore` in the given directory with the given configuration.
+    ///
+    /// The directory is created if it doesn't exist. Records that are expired, or that don't
+    /// fit in the store w.r.t. the configuration, are deleted from the disk.
+    pub fn open_with_config(local_id: PeerId, path: impl AsRef<Path>, config: MemoryStoreConfig)
+        -> io::Result<Self>
+    {
+        let records_dir = path.as_ref().join("records");
+        let providers_dir = path.as_ref().join("providers");
+        fs::create_dir_all(&records_dir)?;
+        fs::create_dir_all(&providers_dir)?;
+
+        let mut memory = MemoryStore::with_config(local_id, config);
+        let now = Instant::now();
+
+        for entry in fs::read_dir(&records_dir)? {
+            let file = entry?.path();
+            match decode_record(&fs::read(&file)?) {
+                Ok(ref r) if r.is_expired(now) => fs::remove_file(&file)?,
+                Ok(r) => if memory.put(r).is_err() {
+                    fs::remove_file(&file)?
+                },
+                Err(e) => {
+                    warn!("Removing corrupted record file {:?}: {:?}", file, e);
+                    fs::remove_file(&file)?
+                }
+            }
+        }
+
+        for entry in fs::read_dir(&providers_dir)? {
+            let file = entry?.path();
+            match decode_providers(&fs::read(&file)?) {
+                Ok((key, providers)) => {
+                    for p in providers.into_iter().filter(|p| !p.is_expired(now)) {
+                        let _ = memory.add_provider(p);
+                    }
+                    // Rewrite the file without the records that were dropped.
+                    let providers = memory.providers(&key);
+                    if providers.is_empty() {
+                        fs::remove_file(&file)?
+                    } else {
+                        write_atomic(&file, &encode_providers(&key, &providers))?
+                    }
+                }
+                Err(e) => {
+                    warn!("Removing corrupted provider file {:?}: {:?}", file, e);
+                    fs::remove_file(&file)?
+                }
+            }
+        }
+
+        Ok(DiskStore { memory, records_dir, providers_dir })
+    }
+
+    /// Retains the records satisfying a predicate.
+    pub fn retain<F>(&mut self, mut f: F)
+    where
+        F: FnMut(&Multihash, &mut Record) -> bool
+    {
+        let mut removed = Vec::new();
+        self.memory.retain(|k, r| {
+            let keep = f(k, r);
+            if !keep {
+                removed.push(k.clone());
+            }
+            keep
+        });
+        for k in removed {
+            if let Err(e) = remove_file(&self.record_path(&k)) {
+                warn!("Failed to remove record from disk: {:?}", e);
+            }
+        }
+    }
+
+    fn record_path(&self, key: &Multihash) -> PathBuf {
+        self.records_dir.join(multihash::to_hex(key.as_bytes()))
+    }
+
+    fn providers_path(&self, key: &Multihash) -> PathBuf {
+        self.providers_dir.join(multihash::to_hex(key.as_bytes()))
+    }
+
+    /// Writes the provider records of the given key to the disk.
+    fn sync_providers(&self, key: &Multihash) -> io::Result<()> {
+        let providers = self.memory.providers(key);
+        let path = self.providers_path(key);
+        if providers.is_empty() {
+            remove_file(&path)
+        } else {
+            write_atomic(&path, &encode_providers(key, &providers))
+        }
+    }
+}
+
+impl<'a> RecordStore<'a> for DiskStore {
+    type RecordsIter = <MemoryStore as RecordStore<'a>>::RecordsIter;
+    type ProvidedIter = <MemoryStore as RecordStore<'a>>::ProvidedIter;
+
+    fn get(&'a self, k: &Multihash) -> Option<Cow<Record>> {
+        self.memory.get(k)
+    }
+
+    fn put(&'a mut self, r: Record) -> Result<()> {
+        let path = self.record_path(&r.key);
+        let bytes = encode_record(&r);
+        let key = r.key.clone();
+        let old = self.memory.get(&key).map(Cow::into_owned);
+        self.memory.put(r)?;
+        if let Err(e) = write_atomic(&path, &bytes) {
+            // Restore the previous state so that memory and disk stay in sync.
+            match old {
+                Some(old) => { let _ = self.memory.put(old); }
+                None => self.memory.remove(&key),
+            }
+            return Err(Error::Io(e))
+        }
+        Ok(())
+    }
+
+    fn remove(&'a mut self, k: &Multihash) {
+        self.memory.remove(k);
+        if let Err(e) = remove_file(&self.record_path(k)) {
+            warn!("Failed to remove record from disk: {:?}", e);
+        }
+    }
+
+    fn records(&'a self) -> Self::RecordsIter {
+        self.memory.records()
+    }
+
+    fn add_provider(&'a mut self, record: ProviderRecord) -> Result<()> {
+        let key = record.key.clone();
+        self.memory.add_provider(record)?;
+        self.sync_providers(&key).map_err(Error::Io)
+    }
+
+    fn providers(&'a self, key: &Multihash) -> Vec<ProviderRecord> {
+        self.memory.providers(key)
+    }
+
+    fn provided(&'a self) -> Self::ProvidedIter {
+        self.memory.provided()
+    }
+
+    fn remove_provider(&'a mut self, k: &Multihash, p: &PeerId) {
+        self.memory.remove_provider(k, p);
+        if let Err(e) = self.sync_providers(k) {
+            warn!("Failed to remove provider record from disk: {:?}", e);
+        }
+    }
+}
+
+/// Writes a file by renaming a temporary file, so that a crash never leaves it truncated.
+fn write_atomic(path: &Path, bytes: &[u8]) -> io::Result<()> {
+    let tmp = path.with_extension("tmp");
+    fs::write(&tmp, bytes)?;
+    fs::rename(&tmp, path)
+}
+
+/// Removes a file, ignoring it not being there.
+fn remove_file(path: &Path) -> io::Result<()> {
+    match fs::remove_file(path) {
+        Err(ref e) if e.kind() == io::ErrorKind::NotFound => Ok(()),
+        other => other,
+    }
+}
+
+/// Converts an expiration time to milliseconds since the UNIX epoch, zero meaning never.
+fn expires_to_millis(expires: Option<Instant>) -> u64 {
+    expires.map_or(0, |t| {
+        let now = Instant::now();
+        let remaining = if t > now { t - now } else { Duration::from_secs(0) };
+        let at = (SystemTime::now() + remaining).duration_since(UNIX_EPOCH)
+            .unwrap_or_else(|_| Duration::from_secs(0));
+        // Never write zero for an actual expiration time.
+        (at.as_secs() * 1000 + u64::from(at.subsec_millis())).max(1)
+    })
+}
+
+/// Converts milliseconds since the UNIX epoch back to an expiration time.
+fn millis_to_expires(millis: u64) -> Option<Instant> {
+    if millis == 0 {
+        return None
+    }
+    let at = UNIX_EPOCH + Duration::from_millis(millis);
+    let remaining = at.duration_since(SystemTime::now()).unwrap_or_else(|_| Duration::from_secs(0));
+    Some(Instant::now() + remaining)
+}
+
+fn put_bytes(out: &mut Vec<u8>, bytes: &[u8]) {
+    out.extend_from_slice(&(bytes.len() as u32).to_be_bytes());
+    out.extend_from_slice(bytes);
+}
+
+/// Encodes a record as the length-prefixed key and publisher (empty if none), the
+/// expiration time and the value.
+fn encode_record(r: &Record) -> Vec<u8> {
+    let mut out = Vec::with_capacity(r.value.len() + 128);
+    put_bytes(&mut out, r.key.as_bytes());
+    put_bytes(&mut out, r.publisher.as_ref().map_or(&[][..], PeerId::as_bytes));
+    out.extend_from_slice(&expires_to_millis(r.expires).to_be_bytes());
+    out.extend_from_slice(&r.value);
+    out
+}
+
+/// Encodes the provider records of a key as the length-prefixed key followed by the
+/// length-prefixed provider and expiration time of each record.
+fn encode_providers(key: &Multihash, providers: &[ProviderRecord]) -> Vec<u8> {
+    let mut out = Vec::new();
+    put_bytes(&mut out, key.as_bytes());
+    for p in providers {
+        put_bytes(&mut out, p.provider.as_bytes());
+        out.extend_from_slice(&expires_to_millis(p.expires).to_be_bytes());
+    }
+    out
+}
+
+/// Reads the values written by `encode_record` and `encode_providers`.
+struct Reader<'a>(&'a [u8]);
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, n: usize) -> io::Result<&'a [u8]> {
+        if self.0.len() < n {
+            return Err(io::ErrorKind::UnexpectedEof.into())
+        }
+        let (bytes, rest) = self.0.split_at(n);
+        self.0 = rest;
+        Ok(bytes)
+    }
+
+    fn u64(&mut self) -> io::Result<u64> {
+        let mut buf = [0; 8];
+        buf.copy_from_slice(self.take(8)?);
+        Ok(u64::from_be_bytes(buf))
+    }
+
+    fn bytes(&mut self) -> io::Result<&'a [u8]> {
+        let mut buf = [0; 4];
+        buf.copy_from_slice(self.take(4)?);
+        self.take(u32::from_be_bytes(buf) as usize)
+    }
+
+    fn key(&mut self) -> io::Result<Multihash> {
+        Multihash::from_bytes(self.bytes()?.to_vec())
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, format!("{:?}", e)))
+    }
+
+    fn peer_id(&mut self) -> io::Result<PeerId> {
+        PeerId::from_bytes(self.bytes()?.to_vec())
+            .map_err(|_| io::Error::new(io::ErrorKind::InvalidData, "invalid peer id"))
+    }
+}
+
+fn decode_record(bytes: &[u8]) -> io::Result<Record> {
+    let mut r = Reader(bytes);
+    let key = r.key()?;
+    let publisher = match r.bytes()? {
+        [] => None,
+        bytes => Some(PeerId::from_bytes(bytes.to_vec())
+            .map_err(|_| io::Error::new(io::ErrorKind::InvalidData, "invalid peer id"))?),
+    };
+    let expires = millis_to_expires(r.u64()?);
+    Ok(Record { key, value: r.0.to_vec(), publisher, expires })
+}
+
+fn decode_providers(bytes: &[u8]) -> io::Result<(Multihash, Vec<ProviderRecord>)> {
+    let mut r = Reader(bytes);
+    let key = r.key()?;
+    let mut providers = Vec::new();
+    while !r.0.is_empty() {
+        let provider = r.peer_id()?;
+        let expires = millis_to_expires(r.u64()?);
+        providers.push(ProviderRecord { key: key.clone(), provider, expires });
+    }
+    Ok((key, providers))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use multihash::Hash::SHA2256;
+    use quickcheck::*;
+
+    #[test]
+    fn records_survive_reopening() {
+        fn prop(r: Record) {
+            let dir = tempfile::tempdir().unwrap();
+            let id = PeerId::random();
+            {
+                let mut store = DiskStore::open(id.clone(), dir.path()).unwrap();
+                assert!(store.put(r.clone()).is_ok());
+            }
+            let store = DiskStore::open(id, dir.path()).unwrap();
+            match store.get(&r.key) {
+                Some(stored) => {
+                    assert_eq!(stored.value, r.value);
+                    assert_eq!(stored.publisher, r.publisher);
+                    assert_eq!(stored.expires.is_some(), r.expires.is_some());
+                }
+                // The record may have expired while the store was closed.
+                None => assert!(r.is_expired(Instant::now() + Duration::from_secs(1))),
+            }
+        }
+        quickcheck(prop as fn(_))
+    }
+
+    #[test]
+    fn removed_records_stay_removed() {
+        let dir = tempfile::tempdir().unwrap();
+        let id = PeerId::random();
+        let r = Record::new(Multihash::random(SHA2256), vec![1, 2, 3]);
+        {
+            let mut store = DiskStore::open(id.clone(), dir.path()).unwrap();
+            assert!(store.put(r.clone()).is_ok());
+            store.remove(&r.key);
+        }
+        let store = DiskStore::open(id, dir.path()).unwrap();
+        assert!(store.get(&r.key).is_none());
+    }
+
+    #[test]
+    fn providers_survive_reopening() {
+        let dir = tempfile::tempdir().unwrap();
+        let id = PeerId::random();
+        let key = Multihash::random(SHA2256);
+        let local = ProviderRecord::new(key.clone(), id.clone());
+        let remote = ProviderRecord::new(key.clone(), PeerId::random());
+        {
+            let mut store = DiskStore::open(id.clone(), dir.path()).unwrap();
+            assert!(store.add_provider(local.clone()).is_ok());
+            assert!(store.add_provider(remote.clone()).is_ok());
+            store.remove_provider(&key, &remote.provider);
+        }
+        let store = DiskStore::open(id, dir.path()).unwrap();
+        assert_eq!(vec![local.clone()], store.providers(&key));
+        assert_eq!(vec![Cow::Borrowed(&local)], store.provided().collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn expired_records_are_dropped_on_open() {
+        let dir = tempfile::tempdir().unwrap();
+        let id = PeerId::random();
+        let mut r = Record::new(Multihash::random(SHA2256), vec![1, 2, 3]);
+        r.expires = Some(Instant::now());
+        {
+            let mut store = DiskStore::open(id.clone(), dir.path()).unwrap();
+            assert!(store.put(r.clone()).is_ok());
+        }
+        let store = DiskStore::open(id, dir.path()).unwrap();
+        assert!(store.get(&r.key).is_none());
+        assert_eq!(fs::read_dir(dir.path().join("records")).unwrap().count(), 0);
+    }
+}
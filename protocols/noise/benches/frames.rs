@@ -0,0 +1,76 @@
+// Copyright 2019 Parity Technologies (UK) Ltd.
+//
+// Permission is hereby granted, free of charge, to any person obtaining a
+// copy of this software and associated documentation files (the "Software"),
+// to deal in the Software without restriction, including without limitation
+// the rights to use, copy, modify, merge, publish, distribute, sublicense,
+// and/or sell copies of the Software, and to permit persons to whom the
+// Software is furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS
+// OR IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING
+// FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
+// DEALINGS IN THE SOFTWARE.
+//! Benchmarks of the throughput of an established noise session over the in-memory transport.
+
+use criterion::{Benchmark, Criterion, Throughput, criterion_group, criterion_main};
+use futures::{prelude::*, stream};
+use libp2p_core::{Transport, identity, transport::{ListenerEvent, MemoryTransport}};
+use libp2p_noise::{Keypair, NoiseConfig, X25519};
+use tokio::runtime::current_thread::Runtime;
+use tokio_io::io;
+
+/// Size of each frame written by the dialer.
+const FRAME_SIZE: usize = 100;
+
+/// Number of frames written at each iteration.
+const FRAMES: usize = 1000;
+
+fn identity(seed: u8) -> identity::Keypair {
+    let secret = identity::ed25519::SecretKey::from_bytes([seed; 32]).unwrap();
+    identity::Keypair::Ed25519(secret.into())
+}
+
+fn frames(c: &mut Criterion) {
+    c.bench("noise", Benchmark::new("send 1000 frames of 100 bytes over an established session", |b| {
+        let mut rt = Runtime::new().unwrap();
+        let transport = |seed| {
+            let dh_keys = Keypair::<X25519>::new().into_authentic(&identity(seed)).unwrap();
+            MemoryTransport.with_upgrade(NoiseConfig::xx(dh_keys)).map(|(_, io), _| io)
+        };
+
+        let mut listener = transport(1).listen_on("/memory/noise-bench-frames".parse().unwrap()).unwrap();
+        let addr = listener.by_ref().wait()
+            .next()
+            .and_then(Result::ok)
+            .and_then(ListenerEvent::into_new_address)
+            .expect("the first event is the address");
+        let inbound = listener
+            .filter_map(ListenerEvent::into_upgrade)
+            .into_future()
+            .map_err(|(err, _)| format!("{:?}", err))
+            .and_then(|(upgrade, _)| upgrade.expect("the listener is open").0.map_err(|err| format!("{:?}", err)));
+        let outbound = transport(2).dial(addr).unwrap().map_err(|err| format!("{:?}", err));
+        let (mut dialer, mut listener) = rt.block_on(outbound.join(inbound)).map(|(d, l)| (Some(d), Some(l))).ok().unwrap();
+
+        let frame = vec![0x5a; FRAME_SIZE];
+        b.iter(|| {
+            let write = stream::iter_ok::<_, std::io::Error>(0 .. FRAMES)
+                .fold(dialer.take().unwrap(), |socket, _| io::write_all(socket, frame.clone()).map(|(socket, _)| socket))
+                .and_then(io::flush);
+            let read = io::read_exact(listener.take().unwrap(), vec![0; FRAME_SIZE * FRAMES]).map(|(socket, _)| socket);
+            let (d, l) = rt.block_on(write.join(read)).ok().unwrap();
+            dialer = Some(d);
+            listener = Some(l);
+        })
+    }).throughput(Throughput::Bytes((FRAME_SIZE * FRAMES) as u32)).sample_size(20));
+}
+
+criterion_group!(benches, frames);
+criterion_main!(benches);
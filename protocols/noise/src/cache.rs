@@ -0,0 +1,190 @@
+// Copyright 2019 Parity Technologies (UK) Ltd.
+//
+// Permission is hereby granted, free of charge, to any person obtaining a
+// copy of this software and associated documentation files (the "Software"),
+// to deal in the Software without restriction, including without limitation
+// the rights to use, copy, modify, merge, publish, distribute, sublicense,
+// and/or sell copies of the Software, and to permit persons to whom the
+// Software is furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS
+// OR IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING
+// FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
+// DEALINGS IN THE SOFTWARE.
+
+//! Cache of the static DH public keys of remotes.
+
+use crate::protocol::{Protocol, PublicKey};
+use libp2p_core::{identity, PeerId};
+use std::{collections::{HashMap, VecDeque}, fmt, sync::{Arc, Mutex}};
+
+/// Default number of remotes whose static DH public key is remembered.
+pub const DEFAULT_KEY_CACHE_SIZE: usize = 1024;
+
+/// Cache of the static DH public keys that have been verified to be authentic w.r.t. the
+/// public identity key of a remote.
+///
+/// Verifying the signature of a static DH public key requires an asymmetric operation with
+/// the identity key of the remote, which is expensive for RSA keys. Since both keys are static,
+/// the result of that verification is remembered, and handshakes with a known remote that
+/// presents the same static DH public key and signature skip it.
+///
+/// Cloning a `KeyCache` yields a handle to the same cache.
+#[derive(Clone)]
+pub struct KeyCache {
+    inner: Arc<Mutex<Inner>>,
+}
+
+struct Inner {
+    /// Maximum number of entries.
+    capacity: usize,
+    /// Verified keys, by remote.
+    entries: HashMap<PeerId, Entry>,
+    /// Remotes in `entries`, from the oldest insertion to the newest.
+    order: VecDeque<PeerId>,
+}
+
+/// A static DH public key and the signature that has been verified for it.
+struct Entry {
+    dh_pk: Vec<u8>,
+    sig: Option<Vec<u8>>,
+}
+
+impl KeyCache {
+    /// Creates a cache remembering the keys of at most `capacity` remotes. A capacity of zero
+    /// disables the cache.
+    pub fn new(capacity: usize) -> Self {
+        KeyCache {
+            inner: Arc::new(Mutex::new(Inner {
+                capacity,
+                entries: HashMap::new(),
+                order: VecDeque::new(),
+            }))
+        }
+    }
+
+    /// Returns the verified static DH public key of a remote, if known.
+    ///
+    /// This can be used to dial a known remote with the `IK` handshake pattern, which
+    /// requires the static DH public key of the responder in advance.
+    pub fn static_dh_key<C: Protocol<C>>(&self, peer_id: &PeerId) -> Option<PublicKey<C>> {
+        let inner = self.inner.lock().expect("not poisoned");
+        inner.entries.get(peer_id).and_then(|e| C::public_from_bytes(&e.dh_pk).ok())
+    }
+
+    /// Forgets the keys of all remotes.
+    pub fn clear(&self) {
+        let mut inner = self.inner.lock().expect("not poisoned");
+        inner.entries.clear();
+        inner.order.clear();
+    }
+
+    /// Returns the number of remotes whose key is known.
+    pub fn len(&self) -> usize {
+        self.inner.lock().expect("not poisoned").entries.len()
+    }
+
+    /// Returns `true` if the key of no remote is known.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Verifies that a static DH public key is authentic w.r.t. a public identity key, like
+    /// [`Protocol::verify`], unless the same key and signature have already been verified for
+    /// that remote.
+    pub(crate) fn verify<C>(&self, id_pk: &identity::PublicKey, dh_pk: &PublicKey<C>, sig: &Option<Vec<u8>>) -> bool
+    where
+        C: Protocol<C> + AsRef<[u8]>
+    {
+        let peer_id = id_pk.clone().into_peer_id();
+
+        {
+            let inner = self.inner.lock().expect("not poisoned");
+            if inner.capacity == 0 {
+                drop(inner);
+                return C::verify(id_pk, dh_pk, sig)
+            }
+            if let Some(e) = inner.entries.get(&peer_id) {
+                if e.dh_pk == dh_pk.as_ref() && &e.sig == sig {
+                    return true
+                }
+            }
+        }
+
+        // The lock is not held during the verification, so that concurrent handshakes with
+        // different remotes don't wait for each other.
+        if !C::verify(id_pk, dh_pk, sig) {
+            return false
+        }
+
+        let mut inner = self.inner.lock().expect("not poisoned");
+        let entry = Entry { dh_pk: dh_pk.as_ref().to_vec(), sig: sig.clone() };
+        if inner.entries.insert(peer_id.clone(), entry).is_none() {
+            inner.order.push_back(peer_id);
+            while inner.order.len() > inner.capacity {
+                if let Some(oldest) = inner.order.pop_front() {
+                    inner.entries.remove(&oldest);
+                }
+            }
+        }
+        true
+    }
+}
+
+impl Default for KeyCache {
+    fn default() -> Self {
+        KeyCache::new(DEFAULT_KEY_CACHE_SIZE)
+    }
+}
+
+impl fmt::Debug for KeyCache {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("KeyCache")
+            .field("len", &self.len())
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Keypair, X25519};
+
+    #[test]
+    fn verified_keys_are_remembered() {
+        let id_keys = identity::Keypair::generate_ed25519();
+        let dh_keys = Keypair::<X25519>::new().into_authentic(&id_keys).unwrap();
+        let id_pk = id_keys.public();
+        let peer_id = id_pk.clone().into_peer_id();
+        let sig = dh_keys.clone().into_identity().signature;
+
+        let cache = KeyCache::new(1);
+        assert!(cache.static_dh_key::<X25519>(&peer_id).is_none());
+
+        // An invalid signature is not remembered.
+        assert!(!cache.verify(&id_pk, dh_keys.public(), &Some(vec![0; 64])));
+        assert!(cache.is_empty());
+
+        assert!(cache.verify(&id_pk, dh_keys.public(), &sig));
+        assert!(cache.static_dh_key::<X25519>(&peer_id) == Some(dh_keys.public().clone()));
+
+        // A different key of the same remote is verified again.
+        let other = Keypair::<X25519>::new();
+        assert!(!cache.verify(&id_pk, other.public(), &sig));
+        assert!(cache.static_dh_key::<X25519>(&peer_id) == Some(dh_keys.public().clone()));
+
+        // The oldest remote is forgotten once the capacity is reached.
+        let id_keys2 = identity::Keypair::generate_ed25519();
+        let dh_keys2 = Keypair::<X25519>::new().into_authentic(&id_keys2).unwrap();
+        let sig2 = dh_keys2.clone().into_identity().signature;
+        assert!(cache.verify(&id_keys2.public(), dh_keys2.public(), &sig2));
+        assert_eq!(cache.len(), 1);
+        assert!(cache.static_dh_key::<X25519>(&peer_id).is_none());
+    }
+}
@@ -20,15 +20,18 @@
 
 //! Noise protocol handshake I/O.
 
-mod payload;
+mod payload_proto {
+    include!(concat!(env!("OUT_DIR"), "/payload.proto.rs"));
+}
 
+use crate::cache::KeyCache;
 use crate::error::NoiseError;
 use crate::protocol::{Protocol, PublicKey, KeypairIdentity};
 use libp2p_core::identity;
 use futures::{future, Async, Future, future::FutureResult, Poll};
 use std::{mem, io};
 use tokio_io::{io as nio, AsyncWrite, AsyncRead};
-use protobuf::Message;
+use prost::Message;
 
 use super::NoiseOutput;
 
@@ -130,10 +133,11 @@ where
         io: T,
         session: Result<snow::Session, NoiseError>,
         identity: KeypairIdentity,
-        identity_x: IdentityExchange
+        identity_x: IdentityExchange,
+        cache: KeyCache
     ) -> Handshake<T, C> {
         Handshake(Box::new(
-            State::new(io, session, identity, identity_x)
+            State::new(io, session, identity, identity_x, cache)
                 .and_then(State::send_identity)
                 .and_then(State::recv_identity)
                 .and_then(State::finish)))
@@ -160,9 +164,10 @@ where
         session: Result<snow::Session, NoiseError>,
         identity: KeypairIdentity,
         identity_x: IdentityExchange,
+        cache: KeyCache
     ) -> Handshake<T, C> {
         Handshake(Box::new(
-            State::new(io, session, identity, identity_x)
+            State::new(io, session, identity, identity_x, cache)
                 .and_then(State::recv_identity)
                 .and_then(State::send_identity)
                 .and_then(State::finish)))
@@ -190,10 +195,11 @@ where
         io: T,
         session: Result<snow::Session, NoiseError>,
         identity: KeypairIdentity,
-        identity_x: IdentityExchange
+        identity_x: IdentityExchange,
+        cache: KeyCache
     ) -> Handshake<T, C> {
         Handshake(Box::new(
-            State::new(io, session, identity, identity_x)
+            State::new(io, session, identity, identity_x, cache)
                 .and_then(State::send_empty)
                 .and_then(State::recv_identity)
                 .and_then(State::send_identity)
@@ -222,10 +228,11 @@ where
         io: T,
         session: Result<snow::Session, NoiseError>,
         identity: KeypairIdentity,
-        identity_x: IdentityExchange
+        identity_x: IdentityExchange,
+        cache: KeyCache
     ) -> Handshake<T, C> {
         Handshake(Box::new(
-            State::new(io, session, identity, identity_x)
+            State::new(io, session, identity, identity_x, cache)
                 .and_then(State::recv_empty)
                 .and_then(State::send_identity)
                 .and_then(State::recv_identity)
@@ -249,6 +256,8 @@ struct State<T> {
     id_remote_pubkey: Option<identity::PublicKey>,
     /// Whether to send the public identity key of the local node to the remote.
     send_identity: bool,
+    /// The static DH public keys already verified for known remotes.
+    cache: KeyCache,
 }
 
 impl<T: io::Read> io::Read for State<T> {
@@ -291,7 +300,8 @@ impl<T> State<T> {
         io: T,
         session: Result<snow::Session, NoiseError>,
         identity: KeypairIdentity,
-        identity_x: IdentityExchange
+        identity_x: IdentityExchange,
+        cache: KeyCache
     ) -> FutureResult<Self, NoiseError> {
         let (id_remote_pubkey, send_identity) = match identity_x {
             IdentityExchange::Mutual => (None, true),
@@ -305,7 +315,8 @@ impl<T> State<T> {
                 io: NoiseOutput::new(io, s),
                 dh_remote_pubkey_sig: None,
                 id_remote_pubkey,
-                send_identity
+                send_identity,
+                cache
             }
         ))
     }
@@ -333,7 +344,7 @@ impl<T> State<T>
                     (_, None) => RemoteIdentity::Unknown,
                     (None, Some(dh_pk)) => RemoteIdentity::StaticDhKey(dh_pk),
                     (Some(id_pk), Some(dh_pk)) => {
-                        if C::verify(&id_pk, &dh_pk, &self.dh_remote_pubkey_sig) {
+                        if self.cache.verify(&id_pk, &dh_pk, &self.dh_remote_pubkey_sig) {
                             RemoteIdentity::IdentityKey(id_pk)
                         } else {
                             return future::err(NoiseError::InvalidKey)
@@ -495,9 +506,9 @@ where
                 },
                 RecvIdentityState::ReadPayload(mut read_payload) => {
                     if let Async::Ready((mut st, bytes)) = read_payload.poll()? {
-                        let pb: payload::Identity = protobuf::parse_from_bytes(&bytes)?;
+                        let pb = payload_proto::Identity::decode(&bytes[..])?;
                         if !pb.pubkey.is_empty() {
-                            let pk = identity::PublicKey::from_protobuf_encoding(pb.get_pubkey())
+                            let pk = identity::PublicKey::from_protobuf_encoding(&pb.pubkey)
                                 .map_err(|_| NoiseError::InvalidKey)?;
                             if let Some(ref k) = st.id_remote_pubkey {
                                 if k != &pk {
@@ -550,14 +561,15 @@ where
         loop {
             match mem::replace(&mut self.state, SendIdentityState::Done) {
                 SendIdentityState::Init(st) => {
-                    let mut pb = payload::Identity::new();
+                    let mut pb = payload_proto::Identity::default();
                     if st.send_identity {
-                        pb.set_pubkey(st.identity.public.clone().into_protobuf_encoding());
+                        pb.pubkey = st.identity.public.clone().into_protobuf_encoding();
                     }
                     if let Some(ref sig) = st.identity.signature {
-                        pb.set_signature(sig.clone());
+                        pb.signature = sig.clone();
                     }
-                    let pb_bytes = pb.write_to_bytes()?;
+                    let mut pb_bytes = Vec::with_capacity(pb.encoded_len());
+                    pb.encode(&mut pb_bytes).expect("Vec<u8> provides capacity as needed");
                     let len = (pb_bytes.len() as u16).to_be_bytes();
                     let write_len = nio::write_all(st, len);
                     self.state = SendIdentityState::WritePayloadLen(write_len, pb_bytes);
@@ -49,12 +49,22 @@
 //! # }
 //! ```
 //!
+//! # Reconnections
+//!
+//! Every `NoiseConfig` remembers the static DH public keys of the remotes whose authenticity
+//! w.r.t. their public identity key has been verified, see [`KeyCache`]. Reconnections to
+//! known remotes skip that verification, and the cached keys can be used to dial them with
+//! the `IK` handshake pattern. Share a single cache between configurations with
+//! [`NoiseConfig::with_key_cache`].
+//!
 //! [noise]: http://noiseprotocol.org/
 
+mod cache;
 mod error;
 mod io;
 mod protocol;
 
+pub use cache::{KeyCache, DEFAULT_KEY_CACHE_SIZE};
 pub use error::NoiseError;
 pub use io::NoiseOutput;
 pub use io::handshake::{Handshake, RemoteIdentity, IdentityExchange};
@@ -71,9 +81,26 @@ pub struct NoiseConfig<P, C: Zeroize, R = ()> {
     dh_keys: AuthenticKeypair<C>,
     params: ProtocolParams,
     remote: R,
+    cache: KeyCache,
     _marker: std::marker::PhantomData<P>
 }
 
+impl<P, C: Zeroize, R> NoiseConfig<P, C, R> {
+    /// Sets the cache of the verified static DH public keys of remotes.
+    ///
+    /// By default, every configuration has its own cache of [`DEFAULT_KEY_CACHE_SIZE`] remotes,
+    /// shared with its clones.
+    pub fn with_key_cache(mut self, cache: KeyCache) -> Self {
+        self.cache = cache;
+        self
+    }
+
+    /// Returns the cache of the verified static DH public keys of remotes.
+    pub fn key_cache(&self) -> &KeyCache {
+        &self.cache
+    }
+}
+
 impl<C> NoiseConfig<IX, C>
 where
     C: Protocol<C> + Zeroize
@@ -84,6 +111,7 @@ where
             dh_keys,
             params: C::params_ix(),
             remote: (),
+            cache: KeyCache::default(),
             _marker: std::marker::PhantomData
         }
     }
@@ -99,6 +127,7 @@ where
             dh_keys,
             params: C::params_xx(),
             remote: (),
+            cache: KeyCache::default(),
             _marker: std::marker::PhantomData
         }
     }
@@ -117,6 +146,7 @@ where
             dh_keys,
             params: C::params_ik(),
             remote: (),
+            cache: KeyCache::default(),
             _marker: std::marker::PhantomData
         }
     }
@@ -139,6 +169,7 @@ where
             dh_keys,
             params: C::params_ik(),
             remote: (remote_dh, remote_id),
+            cache: KeyCache::default(),
             _marker: std::marker::PhantomData
         }
     }
@@ -163,7 +194,8 @@ where
             .map_err(NoiseError::from);
         Handshake::rt1_responder(socket, session,
             self.dh_keys.into_identity(),
-            IdentityExchange::Mutual)
+            IdentityExchange::Mutual,
+            self.cache)
     }
 }
 
@@ -184,7 +216,8 @@ where
             .map_err(NoiseError::from);
         Handshake::rt1_initiator(socket, session,
             self.dh_keys.into_identity(),
-            IdentityExchange::Mutual)
+            IdentityExchange::Mutual,
+            self.cache)
     }
 }
 
@@ -207,7 +240,8 @@ where
             .map_err(NoiseError::from);
         Handshake::rt15_responder(socket, session,
             self.dh_keys.into_identity(),
-            IdentityExchange::Mutual)
+            IdentityExchange::Mutual,
+            self.cache)
     }
 }
 
@@ -228,7 +262,8 @@ where
             .map_err(NoiseError::from);
         Handshake::rt15_initiator(socket, session,
             self.dh_keys.into_identity(),
-            IdentityExchange::Mutual)
+            IdentityExchange::Mutual,
+            self.cache)
     }
 }
 
@@ -251,7 +286,8 @@ where
             .map_err(NoiseError::from);
         Handshake::rt1_responder(socket, session,
             self.dh_keys.into_identity(),
-            IdentityExchange::Receive)
+            IdentityExchange::Receive,
+            self.cache)
     }
 }
 
@@ -273,7 +309,8 @@ where
             .map_err(NoiseError::from);
         Handshake::rt1_initiator(socket, session,
             self.dh_keys.into_identity(),
-            IdentityExchange::Send { remote: self.remote.1 })
+            IdentityExchange::Send { remote: self.remote.1 },
+            self.cache)
     }
 }
 
@@ -204,8 +204,17 @@ impl<T: AsRef<[u8]>> AsRef<[u8]> for PublicKey<T> {
 /// the choices, because it comes with unwanted additional dependencies,
 /// notably rust-crypto, and to avoid being affected by changes to
 /// the defaults.
+///
+/// On `wasm32`, where *ring* is not available, hash functions and
+/// symmetric ciphers are delegated to the `DefaultResolver` instead.
 struct Resolver;
 
+/// Resolver of the hash functions and symmetric ciphers.
+#[cfg(not(target_arch = "wasm32"))]
+type PrimitivesResolver = snow::resolvers::RingResolver;
+#[cfg(target_arch = "wasm32")]
+type PrimitivesResolver = snow::resolvers::DefaultResolver;
+
 impl snow::resolvers::CryptoResolver for Resolver {
     fn resolve_rng(&self) -> Option<Box<dyn snow::types::Random>> {
         Some(Box::new(Rng(rand::rngs::StdRng::from_entropy())))
@@ -220,11 +229,11 @@ impl snow::resolvers::CryptoResolver for Resolver {
     }
 
     fn resolve_hash(&self, choice: &snow::params::HashChoice) -> Option<Box<dyn snow::types::Hash>> {
-        snow::resolvers::RingResolver.resolve_hash(choice)
+        PrimitivesResolver.resolve_hash(choice)
     }
 
     fn resolve_cipher(&self, choice: &snow::params::CipherChoice) -> Option<Box<dyn snow::types::Cipher>> {
-        snow::resolvers::RingResolver.resolve_cipher(choice)
+        PrimitivesResolver.resolve_cipher(choice)
     }
 }
 
@@ -26,7 +26,7 @@ use lazy_static::lazy_static;
 use libp2p_core::UpgradeInfo;
 use libp2p_core::{identity, identity::ed25519};
 use rand::Rng;
-use ring::digest::{SHA512, digest};
+use sha2::{Digest, Sha512};
 use x25519_dalek::{X25519_BASEPOINT_BYTES, x25519};
 use zeroize::Zeroize;
 
@@ -221,7 +221,7 @@ impl SecretKey<X25519> {
         // the same to yield a Curve25519 keypair with the same public key.
         // let ed25519_sk = ed25519::SecretKey::from(ed);
         let mut curve25519_sk: [u8; 32] = [0; 32];
-        let hash = digest(&SHA512, ed25519_sk.as_ref());
+        let hash = Sha512::digest(ed25519_sk.as_ref());
         curve25519_sk.copy_from_slice(&hash.as_ref()[..32]);
         let sk = SecretKey(X25519(curve25519_sk)); // Copy
         curve25519_sk.zeroize();
@@ -0,0 +1,177 @@
+// Copyright 2019 Parity Technologies (UK) Ltd.
+//
+// Permission is hereby granted, free of charge, to any person obtaining a
+// copy of this software and associated documentation files (the "Software"),
+// to deal in the Software without restriction, including without limitation
+// the rights to use, copy, modify, merge, publish, distribute, sublicense,
+// and/or sell copies of the Software, and to permit persons to whom the
+// Software is furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS
+// OR IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING
+// FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
+// DEALINGS IN THE SOFTWARE.
+
+use crate::{PerfConfig, PerfFailure, RunId};
+use crate::protocol::{self, Perf, PerfRun, PerfServe, RunParams, RunStats, ServeStats};
+use futures::prelude::*;
+use libp2p_core::upgrade::{Negotiated, UpgradeError};
+use libp2p_swarm::{
+    KeepAlive,
+    SubstreamProtocol,
+    ProtocolsHandler,
+    ProtocolsHandlerEvent,
+    ProtocolsHandlerUpgrErr
+};
+use std::{collections::VecDeque, io};
+use tokio_io::{AsyncRead, AsyncWrite};
+use void::Void;
+use wasm_timer::Instant;
+
+/// Event produced by the `PerfHandler`.
+#[derive(Debug)]
+pub enum PerfHandlerEvent {
+    /// A measurement started by the local node finished.
+    Finished {
+        id: RunId,
+        result: Result<RunStats, PerfFailure>,
+    },
+    /// A measurement started by the remote finished.
+    Served(Result<ServeStats, io::Error>),
+}
+
+/// Protocol handler performing the measurements requested by the behaviour, and serving the
+/// measurements requested by the remote.
+pub struct PerfHandler<TSubstream> {
+    /// Configuration options.
+    config: PerfConfig,
+    /// Value to return from `connection_keep_alive`.
+    keep_alive: KeepAlive,
+    /// Measurements for which a substream must be opened.
+    pending_runs: VecDeque<(RunId, RunParams)>,
+    /// Number of measurements whose substream is being opened or negotiated.
+    num_pending_outbound: usize,
+    /// Ongoing measurements started by the local node.
+    outbound: Vec<(RunId, PerfRun<Negotiated<TSubstream>>)>,
+    /// Ongoing measurements started by the remote.
+    inbound: Vec<PerfServe<Negotiated<TSubstream>>>,
+    /// Events to produce in `poll()`.
+    pending_events: VecDeque<PerfHandlerEvent>,
+}
+
+impl<TSubstream> PerfHandler<TSubstream> {
+    pub(crate) fn new(config: PerfConfig) -> Self {
+        let keep_alive = KeepAlive::Until(Instant::now() + config.connection_keep_alive);
+        PerfHandler {
+            config,
+            keep_alive,
+            pending_runs: VecDeque::new(),
+            num_pending_outbound: 0,
+            outbound: Vec::new(),
+            inbound: Vec::new(),
+            pending_events: VecDeque::new(),
+        }
+    }
+
+    /// Updates the keep-alive after a measurement has completed.
+    fn update_keep_alive(&mut self) {
+        if self.pending_runs.is_empty()
+            && self.num_pending_outbound == 0
+            && self.outbound.is_empty()
+            && self.inbound.is_empty()
+        {
+            self.keep_alive = KeepAlive::Until(Instant::now() + self.config.connection_keep_alive);
+        } else {
+            self.keep_alive = KeepAlive::Yes;
+        }
+    }
+}
+
+impl<TSubstream> ProtocolsHandler for PerfHandler<TSubstream>
+where
+    TSubstream: AsyncRead + AsyncWrite,
+{
+    type InEvent = (RunId, RunParams);
+    type OutEvent = PerfHandlerEvent;
+    type Error = Void;
+    type Substream = TSubstream;
+    type InboundProtocol = Perf;
+    type OutboundProtocol = Perf;
+    type OutboundOpenInfo = (RunId, RunParams);
+
+    fn listen_protocol(&self) -> SubstreamProtocol<Perf> {
+        SubstreamProtocol::new(Perf).with_timeout(self.config.negotiation_timeout)
+    }
+
+    fn inject_fully_negotiated_inbound(&mut self, socket: Negotiated<TSubstream>) {
+        self.inbound.push(protocol::serve(socket));
+        self.keep_alive = KeepAlive::Yes;
+    }
+
+    fn inject_fully_negotiated_outbound(&mut self, socket: Negotiated<TSubstream>, (id, params): (RunId, RunParams)) {
+        self.num_pending_outbound -= 1;
+        self.outbound.push((id, protocol::run(socket, params)));
+    }
+
+    fn inject_event(&mut self, run: (RunId, RunParams)) {
+        self.pending_runs.push_back(run);
+        self.keep_alive = KeepAlive::Yes;
+    }
+
+    fn inject_dial_upgrade_error(&mut self, (id, _): (RunId, RunParams), error: ProtocolsHandlerUpgrErr<Void>) {
+        self.num_pending_outbound -= 1;
+        self.update_keep_alive();
+        let error = match error {
+            ProtocolsHandlerUpgrErr::Timeout | ProtocolsHandlerUpgrErr::Timer => PerfFailure::Timeout,
+            ProtocolsHandlerUpgrErr::Upgrade(UpgradeError::Select(_)) => PerfFailure::UnsupportedProtocol,
+            ProtocolsHandlerUpgrErr::Upgrade(UpgradeError::Apply(e)) => void::unreachable(e),
+        };
+        self.pending_events.push_back(PerfHandlerEvent::Finished { id, result: Err(error) });
+    }
+
+    fn connection_keep_alive(&self) -> KeepAlive {
+        self.keep_alive
+    }
+
+    fn poll(&mut self) -> Poll<ProtocolsHandlerEvent<Perf, (RunId, RunParams), PerfHandlerEvent>, Void> {
+        if let Some(event) = self.pending_events.pop_front() {
+            return Ok(Async::Ready(ProtocolsHandlerEvent::Custom(event)))
+        }
+
+        for n in (0 .. self.outbound.len()).rev() {
+            let result = match self.outbound[n].1.poll() {
+                Ok(Async::NotReady) => continue,
+                Ok(Async::Ready(stats)) => Ok(stats),
+                Err(err) => Err(PerfFailure::Io(err)),
+            };
+            let (id, _) = self.outbound.swap_remove(n);
+            self.update_keep_alive();
+            return Ok(Async::Ready(ProtocolsHandlerEvent::Custom(PerfHandlerEvent::Finished { id, result })))
+        }
+
+        for n in (0 .. self.inbound.len()).rev() {
+            let result = match self.inbound[n].poll() {
+                Ok(Async::NotReady) => continue,
+                Ok(Async::Ready(stats)) => Ok(stats),
+                Err(err) => Err(err),
+            };
+            self.inbound.swap_remove(n);
+            self.update_keep_alive();
+            return Ok(Async::Ready(ProtocolsHandlerEvent::Custom(PerfHandlerEvent::Served(result))))
+        }
+
+        if let Some(run) = self.pending_runs.pop_front() {
+            self.num_pending_outbound += 1;
+            let protocol = SubstreamProtocol::new(Perf).with_timeout(self.config.negotiation_timeout);
+            return Ok(Async::Ready(ProtocolsHandlerEvent::OutboundSubstreamRequest { protocol, info: run }))
+        }
+
+        Ok(Async::NotReady)
+    }
+}
@@ -0,0 +1,313 @@
+// Copyright 2019 Parity Technologies (UK) Ltd.
+//
+// Permission is hereby granted, free of charge, to any person obtaining a
+// copy of this software and associated documentation files (the "Software"),
+// to deal in the Software without restriction, including without limitation
+// the rights to use, copy, modify, merge, publish, distribute, sublicense,
+// and/or sell copies of the Software, and to permit persons to whom the
+// Software is furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS
+// OR IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING
+// FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
+// DEALINGS IN THE SOFTWARE.
+
+//! This module implements the `/perf/1.0.0` protocol.
+//!
+//! The perf protocol measures the throughput between two nodes: the dialer uploads a given
+//! number of bytes, then downloads a given number of bytes, and reports the time each of the
+//! two phases took. Since the measurements run on regular substreams, they can be used to
+//! compare combinations of transports and stream multiplexers.
+//!
+//! # Usage
+//!
+//! The [`Perf`] struct implements the [`NetworkBehaviour`] trait. It serves the measurements
+//! requested by remotes, and starts a measurement with a peer when [`Perf::perf`] is called.
+//! The results are reported as [`PerfEvent`]s.
+//!
+//! The [`protocol::run`] and [`protocol::serve`] functions perform a measurement on any
+//! substream on which the protocol has been negotiated, without a [`Swarm`].
+//!
+//! [`NetworkBehaviour`]: libp2p_swarm::NetworkBehaviour
+//! [`Swarm`]: libp2p_swarm::Swarm
+
+pub mod handler;
+pub mod protocol;
+
+pub use handler::{PerfHandler, PerfHandlerEvent};
+pub use protocol::{RunParams, RunStats, ServeStats};
+
+use futures::prelude::*;
+use libp2p_core::{ConnectedPoint, Multiaddr, PeerId};
+use libp2p_swarm::{NetworkBehaviour, NetworkBehaviourAction, PollParameters};
+use log::debug;
+use smallvec::SmallVec;
+use std::{collections::{HashMap, HashSet, VecDeque}, error, fmt, io, marker::PhantomData, time::Duration};
+use tokio_io::{AsyncRead, AsyncWrite};
+
+/// Configuration of a `Perf` behaviour.
+#[derive(Debug, Clone)]
+pub struct PerfConfig {
+    connection_keep_alive: Duration,
+    negotiation_timeout: Duration,
+}
+
+impl Default for PerfConfig {
+    fn default() -> Self {
+        PerfConfig {
+            connection_keep_alive: Duration::from_secs(10),
+            negotiation_timeout: Duration::from_secs(10),
+        }
+    }
+}
+
+impl PerfConfig {
+    /// Sets how long an idle connection is kept alive. Defaults to 10 seconds.
+    pub fn with_connection_keep_alive(mut self, keep_alive: Duration) -> Self {
+        self.connection_keep_alive = keep_alive;
+        self
+    }
+
+    /// Sets how long opening a substream and negotiating the protocol may take. The
+    /// measurement itself isn't bounded in time. Defaults to 10 seconds.
+    pub fn with_negotiation_timeout(mut self, timeout: Duration) -> Self {
+        self.negotiation_timeout = timeout;
+        self
+    }
+}
+
+/// Identifier of a measurement started with [`Perf::perf`].
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct RunId(u64);
+
+impl fmt::Display for RunId {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+/// Event generated by the `Perf` network behaviour.
+#[derive(Debug)]
+pub enum PerfEvent {
+    /// A measurement started with [`Perf::perf`] finished.
+    Finished {
+        /// The peer the measurement was performed with.
+        peer: PeerId,
+        /// The identifier of the measurement.
+        id: RunId,
+        /// The timings of the measurement, or the reason why it failed.
+        result: Result<RunStats, PerfFailure>,
+    },
+    /// A measurement requested by a remote finished.
+    Served {
+        /// The peer that requested the measurement.
+        peer: PeerId,
+        /// What has been transferred.
+        stats: ServeStats,
+    },
+}
+
+/// Reason why a measurement failed.
+#[derive(Debug)]
+pub enum PerfFailure {
+    /// We couldn't connect to the peer.
+    DialFailure,
+    /// The connection closed before the measurement finished.
+    ConnectionClosed,
+    /// The substream couldn't be opened or negotiated in time.
+    Timeout,
+    /// The remote doesn't support the protocol.
+    UnsupportedProtocol,
+    /// An error happened while transferring the data.
+    Io(io::Error),
+}
+
+impl fmt::Display for PerfFailure {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            PerfFailure::DialFailure => write!(f, "Failed to dial the peer"),
+            PerfFailure::ConnectionClosed => write!(f, "Connection closed before the measurement finished"),
+            PerfFailure::Timeout => write!(f, "Timeout while opening the substream"),
+            PerfFailure::UnsupportedProtocol => write!(f, "The remote doesn't support the perf protocol"),
+            PerfFailure::Io(err) => write!(f, "I/O error: {}", err),
+        }
+    }
+}
+
+impl error::Error for PerfFailure {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        match self {
+            PerfFailure::Io(err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+/// `Perf` is a [`NetworkBehaviour`] that measures the throughput to other nodes, and serves
+/// the measurements they request.
+///
+/// See the crate root documentation for more information.
+pub struct Perf<TSubstream> {
+    /// Configuration options.
+    config: PerfConfig,
+    /// Identifier of the next measurement.
+    next_run_id: u64,
+    /// The peers we are connected to.
+    connected: HashSet<PeerId>,
+    /// Addresses of peers, in addition to the ones that the `Swarm` already knows about.
+    addresses: HashMap<PeerId, SmallVec<[Multiaddr; 6]>>,
+    /// Measurements with peers we are not connected to yet.
+    pending_runs: HashMap<PeerId, SmallVec<[(RunId, RunParams); 2]>>,
+    /// Measurements sent to the handler of a connection, with the peer they are performed with.
+    running: HashMap<RunId, PeerId>,
+    /// Queue of actions to return to the swarm.
+    pending_actions: VecDeque<NetworkBehaviourAction<(RunId, RunParams), PerfEvent>>,
+    _marker: PhantomData<TSubstream>,
+}
+
+impl<TSubstream> Perf<TSubstream> {
+    /// Creates a new `Perf` behaviour.
+    pub fn new(config: PerfConfig) -> Self {
+        Perf {
+            config,
+            next_run_id: 0,
+            connected: HashSet::new(),
+            addresses: HashMap::new(),
+            pending_runs: HashMap::new(),
+            running: HashMap::new(),
+            pending_actions: VecDeque::new(),
+            _marker: PhantomData,
+        }
+    }
+
+    /// Starts a measurement with a peer, dialing it first if we're not connected to it.
+    ///
+    /// The result is reported with a `PerfEvent::Finished` carrying the returned `RunId`.
+    pub fn perf(&mut self, peer: &PeerId, params: RunParams) -> RunId {
+        let id = RunId(self.next_run_id);
+        self.next_run_id += 1;
+
+        if self.connected.contains(peer) {
+            self.start(peer.clone(), id, params);
+        } else {
+            let queue = self.pending_runs.entry(peer.clone()).or_default();
+            if queue.is_empty() {
+                self.pending_actions.push_back(NetworkBehaviourAction::DialPeer { peer_id: peer.clone() });
+            }
+            queue.push((id, params));
+        }
+
+        id
+    }
+
+    /// Adds an address at which a peer can be dialed.
+    pub fn add_address(&mut self, peer: &PeerId, address: Multiaddr) {
+        let addresses = self.addresses.entry(peer.clone()).or_default();
+        if !addresses.contains(&address) {
+            addresses.push(address);
+        }
+    }
+
+    /// Removes an address of a peer previously added with `add_address`.
+    pub fn remove_address(&mut self, peer: &PeerId, address: &Multiaddr) {
+        if let Some(addresses) = self.addresses.get_mut(peer) {
+            addresses.retain(|a| a != address);
+            if addresses.is_empty() {
+                self.addresses.remove(peer);
+            }
+        }
+    }
+
+    /// Sends a measurement to the handler of the connection to the peer.
+    fn start(&mut self, peer_id: PeerId, id: RunId, params: RunParams) {
+        self.running.insert(id, peer_id.clone());
+        self.pending_actions.push_back(NetworkBehaviourAction::SendEvent { peer_id, event: (id, params) });
+    }
+}
+
+impl<TSubstream> NetworkBehaviour for Perf<TSubstream>
+where
+    TSubstream: AsyncRead + AsyncWrite,
+{
+    type ProtocolsHandler = PerfHandler<TSubstream>;
+    type OutEvent = PerfEvent;
+
+    fn new_handler(&mut self) -> Self::ProtocolsHandler {
+        PerfHandler::new(self.config.clone())
+    }
+
+    fn addresses_of_peer(&mut self, peer_id: &PeerId) -> Vec<Multiaddr> {
+        self.addresses.get(peer_id).map(|a| a.to_vec()).unwrap_or_default()
+    }
+
+    fn inject_connected(&mut self, peer_id: PeerId, _: ConnectedPoint) {
+        self.connected.insert(peer_id.clone());
+        if let Some(runs) = self.pending_runs.remove(&peer_id) {
+            for (id, params) in runs {
+                self.start(peer_id.clone(), id, params);
+            }
+        }
+    }
+
+    fn inject_disconnected(&mut self, peer_id: &PeerId, _: ConnectedPoint) {
+        self.connected.remove(peer_id);
+
+        let interrupted = self.running.iter()
+            .filter(|(_, peer)| *peer == peer_id)
+            .map(|(id, _)| *id)
+            .collect::<Vec<_>>();
+        for id in interrupted {
+            self.running.remove(&id);
+            self.pending_actions.push_back(NetworkBehaviourAction::GenerateEvent(PerfEvent::Finished {
+                peer: peer_id.clone(),
+                id,
+                result: Err(PerfFailure::ConnectionClosed),
+            }));
+        }
+    }
+
+    fn inject_dial_failure(&mut self, peer_id: &PeerId) {
+        if let Some(runs) = self.pending_runs.remove(peer_id) {
+            for (id, _) in runs {
+                self.pending_actions.push_back(NetworkBehaviourAction::GenerateEvent(PerfEvent::Finished {
+                    peer: peer_id.clone(),
+                    id,
+                    result: Err(PerfFailure::DialFailure),
+                }));
+            }
+        }
+    }
+
+    fn inject_node_event(&mut self, peer: PeerId, event: PerfHandlerEvent) {
+        let event = match event {
+            PerfHandlerEvent::Finished { id, result } => {
+                if self.running.remove(&id).is_none() {
+                    return
+                }
+                PerfEvent::Finished { peer, id, result }
+            }
+            PerfHandlerEvent::Served(Ok(stats)) => PerfEvent::Served { peer, stats },
+            PerfHandlerEvent::Served(Err(err)) => {
+                debug!("Failed to serve a measurement to {:?}: {}", peer, err);
+                return
+            }
+        };
+        self.pending_actions.push_back(NetworkBehaviourAction::GenerateEvent(event));
+    }
+
+    fn poll(&mut self, _: &mut impl PollParameters)
+        -> Async<NetworkBehaviourAction<(RunId, RunParams), PerfEvent>>
+    {
+        if let Some(action) = self.pending_actions.pop_front() {
+            return Async::Ready(action)
+        }
+
+        Async::NotReady
+    }
+}
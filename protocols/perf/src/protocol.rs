@@ -0,0 +1,341 @@
+// Copyright 2019 Parity Technologies (UK) Ltd.
+//
+// Permission is hereby granted, free of charge, to any person obtaining a
+// copy of this software and associated documentation files (the "Software"),
+// to deal in the Software without restriction, including without limitation
+// the rights to use, copy, modify, merge, publish, distribute, sublicense,
+// and/or sell copies of the Software, and to permit persons to whom the
+// Software is furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS
+// OR IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING
+// FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
+// DEALINGS IN THE SOFTWARE.
+
+//! The `/perf/1.0.0` protocol.
+//!
+//! The protocol works the following way:
+//!
+//! - The dialer sends the number of bytes it wants to receive, as a big-endian `u64`.
+//! - The dialer sends the bytes to upload, then closes its writing side.
+//! - The listener reads until the end of the upload, then sends the requested number of
+//!   bytes and closes the substream.
+//!
+//! The [`run`] and [`serve`] functions drive a measurement on any substream on which the
+//! protocol has been negotiated, independently of a `Swarm`.
+
+use futures::{prelude::*, future, try_ready};
+use libp2p_core::{InboundUpgrade, OutboundUpgrade, UpgradeInfo, upgrade::Negotiated};
+use std::{cmp, io, iter, time::Duration};
+use tokio_io::{AsyncRead, AsyncWrite};
+use void::Void;
+use wasm_timer::Instant;
+
+/// Size of the chunks in which the data is written and read.
+const BUFFER_SIZE: usize = 64 * 1024;
+
+/// The upgrade negotiating the `/perf/1.0.0` protocol. Produces the negotiated substream,
+/// which is then driven by [`run`] or [`serve`].
+#[derive(Default, Debug, Copy, Clone)]
+pub struct Perf;
+
+impl UpgradeInfo for Perf {
+    type Info = &'static [u8];
+    type InfoIter = iter::Once<Self::Info>;
+
+    fn protocol_info(&self) -> Self::InfoIter {
+        iter::once(b"/perf/1.0.0")
+    }
+}
+
+impl<TSocket> InboundUpgrade<TSocket> for Perf {
+    type Output = Negotiated<TSocket>;
+    type Error = Void;
+    type Future = future::FutureResult<Self::Output, Void>;
+
+    fn upgrade_inbound(self, socket: Negotiated<TSocket>, _: Self::Info) -> Self::Future {
+        future::ok(socket)
+    }
+}
+
+impl<TSocket> OutboundUpgrade<TSocket> for Perf {
+    type Output = Negotiated<TSocket>;
+    type Error = Void;
+    type Future = future::FutureResult<Self::Output, Void>;
+
+    fn upgrade_outbound(self, socket: Negotiated<TSocket>, _: Self::Info) -> Self::Future {
+        future::ok(socket)
+    }
+}
+
+/// The parameters of a measurement.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct RunParams {
+    /// Number of bytes the dialer uploads.
+    pub to_send: u64,
+    /// Number of bytes the dialer downloads.
+    pub to_receive: u64,
+}
+
+/// The timings of a measurement, as seen by the dialer.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct RunStats {
+    /// The parameters of the measurement.
+    pub params: RunParams,
+    /// Time between the start of the measurement and the end of the upload.
+    pub upload: Duration,
+    /// Time between the end of the upload and the end of the download.
+    pub download: Duration,
+}
+
+/// The outcome of a measurement, as seen by the listener.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct ServeStats {
+    /// Number of bytes received from the dialer.
+    pub received: u64,
+    /// Number of bytes sent to the dialer.
+    pub sent: u64,
+    /// Total time spent on the measurement.
+    pub duration: Duration,
+}
+
+/// Performs a measurement as the dialer on the given substream.
+pub fn run<TSocket>(socket: TSocket, params: RunParams) -> PerfRun<TSocket>
+where
+    TSocket: AsyncRead + AsyncWrite,
+{
+    PerfRun {
+        socket,
+        params,
+        state: RunState::Header { header: params.to_receive.to_be_bytes(), written: 0 },
+        buffer: vec![0; BUFFER_SIZE],
+        started: Instant::now(),
+        uploaded: None,
+    }
+}
+
+/// Performs a measurement as the listener on the given substream.
+pub fn serve<TSocket>(socket: TSocket) -> PerfServe<TSocket>
+where
+    TSocket: AsyncRead + AsyncWrite,
+{
+    PerfServe {
+        socket,
+        state: ServeState::Header { header: [0; 8], read: 0 },
+        buffer: vec![0; BUFFER_SIZE],
+        started: Instant::now(),
+    }
+}
+
+/// Future returned by [`run`].
+pub struct PerfRun<TSocket> {
+    socket: TSocket,
+    params: RunParams,
+    state: RunState,
+    buffer: Vec<u8>,
+    started: Instant,
+    /// When the upload finished.
+    uploaded: Option<Instant>,
+}
+
+enum RunState {
+    Header { header: [u8; 8], written: usize },
+    Upload { sent: u64 },
+    Close,
+    Download { received: u64 },
+}
+
+impl<TSocket> Future for PerfRun<TSocket>
+where
+    TSocket: AsyncRead + AsyncWrite,
+{
+    type Item = RunStats;
+    type Error = io::Error;
+
+    fn poll(&mut self) -> Poll<RunStats, io::Error> {
+        loop {
+            match self.state {
+                RunState::Header { ref header, ref mut written } => {
+                    while *written < header.len() {
+                        let n = try_ready!(self.socket.poll_write(&header[*written ..]));
+                        if n == 0 {
+                            return Err(io::ErrorKind::WriteZero.into())
+                        }
+                        *written += n;
+                    }
+                    self.state = RunState::Upload { sent: 0 };
+                }
+                RunState::Upload { ref mut sent } => {
+                    while *sent < self.params.to_send {
+                        let len = cmp::min(self.buffer.len() as u64, self.params.to_send - *sent) as usize;
+                        let n = try_ready!(self.socket.poll_write(&self.buffer[.. len]));
+                        if n == 0 {
+                            return Err(io::ErrorKind::WriteZero.into())
+                        }
+                        *sent += n as u64;
+                    }
+                    self.state = RunState::Close;
+                }
+                RunState::Close => {
+                    try_ready!(self.socket.poll_flush());
+                    try_ready!(self.socket.shutdown());
+                    self.uploaded = Some(Instant::now());
+                    self.state = RunState::Download { received: 0 };
+                }
+                RunState::Download { ref mut received } => {
+                    loop {
+                        let n = try_ready!(self.socket.poll_read(&mut self.buffer));
+                        if n == 0 {
+                            break
+                        }
+                        *received += n as u64;
+                        if *received > self.params.to_receive {
+                            return Err(io::Error::new(io::ErrorKind::InvalidData, "Received more data than requested"))
+                        }
+                    }
+                    if *received < self.params.to_receive {
+                        return Err(io::ErrorKind::UnexpectedEof.into())
+                    }
+                    let uploaded = self.uploaded.expect("The upload finishes before the download starts; qed");
+                    return Ok(Async::Ready(RunStats {
+                        params: self.params,
+                        upload: uploaded - self.started,
+                        download: uploaded.elapsed(),
+                    }))
+                }
+            }
+        }
+    }
+}
+
+/// Future returned by [`serve`].
+pub struct PerfServe<TSocket> {
+    socket: TSocket,
+    state: ServeState,
+    buffer: Vec<u8>,
+    started: Instant,
+}
+
+enum ServeState {
+    Header { header: [u8; 8], read: usize },
+    Receive { to_send: u64, received: u64 },
+    Send { to_send: u64, received: u64, sent: u64 },
+    Close { received: u64, sent: u64 },
+}
+
+impl<TSocket> Future for PerfServe<TSocket>
+where
+    TSocket: AsyncRead + AsyncWrite,
+{
+    type Item = ServeStats;
+    type Error = io::Error;
+
+    fn poll(&mut self) -> Poll<ServeStats, io::Error> {
+        loop {
+            match self.state {
+                ServeState::Header { ref mut header, ref mut read } => {
+                    while *read < header.len() {
+                        let n = try_ready!(self.socket.poll_read(&mut header[*read ..]));
+                        if n == 0 {
+                            return Err(io::ErrorKind::UnexpectedEof.into())
+                        }
+                        *read += n;
+                    }
+                    let to_send = u64::from_be_bytes(*header);
+                    self.state = ServeState::Receive { to_send, received: 0 };
+                }
+                ServeState::Receive { to_send, ref mut received } => {
+                    loop {
+                        let n = try_ready!(self.socket.poll_read(&mut self.buffer));
+                        if n == 0 {
+                            break
+                        }
+                        *received += n as u64;
+                    }
+                    self.state = ServeState::Send { to_send, received: *received, sent: 0 };
+                }
+                ServeState::Send { to_send, received, ref mut sent } => {
+                    while *sent < to_send {
+                        let len = cmp::min(self.buffer.len() as u64, to_send - *sent) as usize;
+                        let n = try_ready!(self.socket.poll_write(&self.buffer[.. len]));
+                        if n == 0 {
+                            return Err(io::ErrorKind::WriteZero.into())
+                        }
+                        *sent += n as u64;
+                    }
+                    self.state = ServeState::Close { received, sent: *sent };
+                }
+                ServeState::Close { received, sent } => {
+                    try_ready!(self.socket.poll_flush());
+                    try_ready!(self.socket.shutdown());
+                    return Ok(Async::Ready(ServeStats {
+                        received,
+                        sent,
+                        duration: self.started.elapsed(),
+                    }))
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Perf, RunParams, run, serve};
+    use futures::prelude::*;
+    use libp2p_core::{
+        upgrade,
+        multiaddr::multiaddr,
+        transport::{
+            Transport,
+            ListenerEvent,
+            memory::MemoryTransport
+        }
+    };
+    use rand::{thread_rng, Rng};
+
+    #[test]
+    fn transfers_requested_amounts() {
+        let mem_addr = multiaddr![Memory(thread_rng().gen::<u64>())];
+        let mut listener = MemoryTransport.listen_on(mem_addr).unwrap();
+
+        let listener_addr =
+            if let Ok(Async::Ready(Some(ListenerEvent::NewAddress(a)))) = listener.poll() {
+                a
+            } else {
+                panic!("MemoryTransport not listening on an address!");
+            };
+
+        let server = listener
+            .into_future()
+            .map_err(|(e, _)| e)
+            .and_then(|(listener_event, _)| {
+                let (listener_upgrade, _) = listener_event.unwrap().into_upgrade().unwrap();
+                let conn = listener_upgrade.wait().unwrap();
+                upgrade::apply_inbound(conn, Perf)
+                    .map_err(|e| panic!("{:?}", e))
+            })
+            .and_then(serve);
+
+        let params = RunParams { to_send: 100_000, to_receive: 200_000 };
+        let client = MemoryTransport.dial(listener_addr).unwrap()
+            .map_err(|e| panic!("{:?}", e))
+            .and_then(|c| {
+                upgrade::apply_outbound(c, Perf)
+                    .map_err(|e| panic!("{:?}", e))
+            })
+            .and_then(move |socket| run(socket, params));
+
+        let mut runtime = tokio::runtime::Runtime::new().unwrap();
+        let (run_stats, serve_stats) = runtime.block_on(client.join(server)).unwrap();
+        assert_eq!(run_stats.params, params);
+        assert_eq!(serve_stats.received, 100_000);
+        assert_eq!(serve_stats.sent, 200_000);
+    }
+}
@@ -103,6 +103,10 @@ impl PingConfig {
     /// at any time, i.e. in the absence of ping failures the connection lifetime
     /// is determined by other protocol handlers.
     ///
+    /// If enabled, the connection is only kept alive while it is healthy:
+    /// as soon as a ping fails, the ping protocol no longer opposes the
+    /// connection being closed, until a ping succeeds again.
+    ///
     /// If the maximum  number of allowed ping failures is reached, the
     /// connection is always terminated as a result of [`PingHandler::poll`]
     /// returning an error, regardless of the keep-alive setting.
@@ -221,7 +225,7 @@ where
     }
 
     fn connection_keep_alive(&self) -> KeepAlive {
-        if self.config.keep_alive {
+        if self.config.keep_alive && self.failures == 0 {
             KeepAlive::Yes
         } else {
             KeepAlive::No
@@ -354,4 +358,27 @@ mod tests {
             e => panic!("Unexpected event: {:?}", e)
         }
     }
+
+    #[test]
+    fn keep_alive_while_healthy() {
+        let cfg = PingConfig::new()
+            .with_keep_alive(true)
+            .with_max_failures(NonZeroU32::new(2).unwrap());
+        let mut h = PingHandler::<TcpStream>::new(cfg);
+        assert!(h.connection_keep_alive().is_yes());
+
+        h.inject_dial_upgrade_error((), ProtocolsHandlerUpgrErr::Timeout);
+        match tick(&mut h) {
+            Ok(ProtocolsHandlerEvent::Custom(Err(PingFailure::Timeout))) => {}
+            e => panic!("Unexpected event: {:?}", e)
+        }
+        assert_eq!(h.connection_keep_alive(), KeepAlive::No);
+
+        h.inject_fully_negotiated_outbound(Duration::from_secs(1), ());
+        match tick(&mut h) {
+            Ok(ProtocolsHandlerEvent::Custom(Ok(PingSuccess::Ping { .. }))) => {}
+            e => panic!("Unexpected event: {:?}", e)
+        }
+        assert!(h.connection_keep_alive().is_yes());
+    }
 }
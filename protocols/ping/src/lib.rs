@@ -32,11 +32,14 @@
 //! the connection will be closed.
 //!
 //! The `Ping` network behaviour produces [`PingEvent`]s, which may be consumed from the `Swarm`
-//! by an application, e.g. to collect statistics.
+//! by an application, e.g. to collect statistics. The most recent round-trip time measured
+//! with each connected peer can also be queried with [`Ping::rtt`], e.g. to prefer peers
+//! with low latency.
 //!
-//! > **Note**: The ping protocol does not keep otherwise idle connections alive,
-//! > it only adds an additional condition for terminating the connection, namely
-//! > a certain number of failed ping requests.
+//! > **Note**: By default, the ping protocol does not keep otherwise idle connections
+//! > alive, it only adds an additional condition for terminating the connection, namely
+//! > a certain number of failed ping requests. With [`PingConfig::with_keep_alive`],
+//! > healthy connections are kept alive until a ping fails.
 //!
 //! [`Swarm`]: libp2p_core::Swarm
 //! [`Transport`]: libp2p_core::Transport
@@ -50,8 +53,9 @@ use handler::PingHandler;
 use futures::prelude::*;
 use libp2p_core::{ConnectedPoint, Multiaddr, PeerId};
 use libp2p_swarm::{NetworkBehaviour, NetworkBehaviourAction, PollParameters};
-use std::collections::VecDeque;
+use std::collections::{HashMap, VecDeque};
 use std::marker::PhantomData;
+use std::time::Duration;
 use tokio_io::{AsyncRead, AsyncWrite};
 use void::Void;
 
@@ -62,8 +66,10 @@ use void::Void;
 pub struct Ping<TSubstream> {
     /// Configuration for outbound pings.
     config: PingConfig,
-    /// Queue of events to yield to the swarm.
-    events: VecDeque<PingEvent>,
+    /// Queue of actions to yield to the swarm.
+    events: VecDeque<NetworkBehaviourAction<Void, PingEvent>>,
+    /// The most recent round-trip time measured with each connected peer.
+    rtts: HashMap<PeerId, Duration>,
     _marker: PhantomData<TSubstream>,
 }
 
@@ -82,9 +88,16 @@ impl<TSubstream> Ping<TSubstream> {
         Ping {
             config,
             events: VecDeque::new(),
+            rtts: HashMap::new(),
             _marker: PhantomData,
         }
     }
+
+    /// Returns the round-trip time of the most recent successful outbound ping
+    /// to the given peer, if it is connected and has answered a ping.
+    pub fn rtt(&self, peer: &PeerId) -> Option<Duration> {
+        self.rtts.get(peer).cloned()
+    }
 }
 
 impl<TSubstream> Default for Ping<TSubstream> {
@@ -110,16 +123,22 @@ where
 
     fn inject_connected(&mut self, _: PeerId, _: ConnectedPoint) {}
 
-    fn inject_disconnected(&mut self, _: &PeerId, _: ConnectedPoint) {}
+    fn inject_disconnected(&mut self, peer: &PeerId, _: ConnectedPoint) {
+        self.rtts.remove(peer);
+    }
 
     fn inject_node_event(&mut self, peer: PeerId, result: PingResult) {
-        self.events.push_front(PingEvent { peer, result })
+        if let Ok(PingSuccess::Ping { rtt }) = result {
+            self.rtts.insert(peer.clone(), rtt);
+            self.events.push_front(NetworkBehaviourAction::ReportRoundTripTime { peer_id: peer.clone(), rtt });
+        }
+        self.events.push_front(NetworkBehaviourAction::GenerateEvent(PingEvent { peer, result }))
     }
 
     fn poll(&mut self, _: &mut impl PollParameters) -> Async<NetworkBehaviourAction<Void, PingEvent>>
     {
-        if let Some(e) = self.events.pop_back() {
-            Async::Ready(NetworkBehaviourAction::GenerateEvent(e))
+        if let Some(action) = self.events.pop_back() {
+            Async::Ready(action)
         } else {
             Async::NotReady
         }
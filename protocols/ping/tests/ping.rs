@@ -57,8 +57,10 @@ fn ping() {
         loop {
             match swarm1.poll().expect("Error while polling swarm") {
                 Async::Ready(Some(PingEvent { peer, result })) => match result {
-                    Ok(PingSuccess::Ping { rtt }) =>
-                        return Ok(Async::Ready((pid1.clone(), peer, rtt))),
+                    Ok(PingSuccess::Ping { rtt }) => {
+                        assert_eq!(swarm1.rtt(&peer), Some(rtt));
+                        return Ok(Async::Ready((pid1.clone(), peer, rtt)))
+                    }
                     _ => {}
                 },
                 _ => {
@@ -80,8 +82,10 @@ fn ping() {
         loop {
             match swarm2.poll().expect("Error while polling swarm") {
                 Async::Ready(Some(PingEvent { peer, result })) => match result {
-                    Ok(PingSuccess::Ping { rtt }) =>
-                        return Ok(Async::Ready((pid2.clone(), peer, rtt))),
+                    Ok(PingSuccess::Ping { rtt }) => {
+                        assert_eq!(swarm2.rtt(&peer), Some(rtt));
+                        return Ok(Async::Ready((pid2.clone(), peer, rtt)))
+                    }
                     _ => {}
                 },
                 _ => {
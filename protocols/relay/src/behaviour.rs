@@ -0,0 +1,419 @@
+// Copyright 2019 Parity Technologies (UK) Ltd.
+//
+// Permission is hereby granted, free of charge, to any person obtaining a
+// copy of this software and associated documentation files (the "Software"),
+// to deal in the Software without restriction, including without limitation
+// the rights to use, copy, modify, merge, publish, distribute, sublicense,
+// and/or sell copies of the Software, and to permit persons to whom the
+// Software is furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS
+// OR IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING
+// FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
+// DEALINGS IN THE SOFTWARE.
+
+use crate::RelayConfig;
+use crate::handler::{
+    RelayHandler,
+    RelayHandlerEvent,
+    RelayHandlerIn,
+    RelayHopRequest,
+    RequestId
+};
+use crate::protocol::{Peer, Status};
+use crate::transport::{IncomingRelayedConnection, RelayError, RelayedConnection, TransportToBehaviourMsg};
+use futures::{prelude::*, sync::{mpsc, oneshot}};
+use libp2p_core::{ConnectedPoint, Multiaddr, PeerId};
+use libp2p_swarm::{ConnectionId, NetworkBehaviour, NetworkBehaviourAction, PollParameters, node_event_connection};
+use smallvec::SmallVec;
+use std::collections::{HashMap, VecDeque};
+use tokio_io::{AsyncRead, AsyncWrite};
+
+/// Event generated by the `Relay` network behaviour.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RelayEvent {
+    /// We started relaying a connection from `src` to `dst`.
+    CircuitOpened { src: PeerId, dst: PeerId },
+    /// A connection we relayed from `src` to `dst` has been closed.
+    CircuitClosed { src: PeerId, dst: PeerId },
+    /// We refused, or failed, to relay a connection from `src` to `dst`.
+    CircuitFailed { src: PeerId, dst: PeerId, status: Status },
+}
+
+/// Network behaviour implementing the circuit relay protocol.
+///
+/// Must be created together with its [`RelayTransport`](crate::RelayTransport) by
+/// [`new_transport_and_behaviour`](crate::new_transport_and_behaviour). The behaviour opens the
+/// connections requested by the transport, accepts the connections relayed to us when the
+/// transport is listening on a relayed address, and relays the connections of other peers if
+/// enabled in the [`RelayConfig`].
+///
+/// Only peers we are already connected to are used as destinations of the connections we relay.
+pub struct Relay<TSubstream> {
+    /// Configuration options.
+    config: RelayConfig,
+    /// Receives the requests of the transport.
+    from_transport: mpsc::UnboundedReceiver<TransportToBehaviourMsg>,
+    /// Listeners of the transport, along with the relay they accept connections from, if any.
+    listeners: Vec<(Option<PeerId>, mpsc::UnboundedSender<IncomingRelayedConnection>)>,
+    /// Relays through which we accept connections.
+    listening_relays: SmallVec<[PeerId; 4]>,
+    /// The established connections to each peer, from the oldest to the most recent.
+    connected: HashMap<PeerId, SmallVec<[ConnectionId; 2]>>,
+    /// Addresses of the relays, learned from the addresses dialed or listened on.
+    addresses: HashMap<PeerId, SmallVec<[Multiaddr; 4]>>,
+    /// Relayed connections to open through relays we are not connected to yet.
+    pending_dials: HashMap<PeerId, SmallVec<[(RequestId, Peer); 4]>>,
+    /// Relayed connections whose request has been sent to a relay.
+    outgoing: HashMap<RequestId, OutgoingDial>,
+    /// Relayed connections whose relay we just connected to, waiting to be requested in `poll()`,
+    /// where our own identity is known.
+    ready_dials: VecDeque<(PeerId, ConnectionId, RequestId, Peer)>,
+    /// Inbound requests to relay a connection, waiting to be processed in `poll()`.
+    hop_requests: VecDeque<(PeerId, ConnectionId, RelayHopRequest<TSubstream>)>,
+    /// Number of connections we are relaying.
+    num_circuits: usize,
+    /// Identifier of the next relayed connection that we open.
+    next_request_id: u64,
+    /// Queue of actions to return to the swarm.
+    pending_actions: VecDeque<NetworkBehaviourAction<RelayHandlerIn<TSubstream>, RelayEvent>>,
+}
+
+/// A relayed connection that we asked a relay to open.
+struct OutgoingDial {
+    relay: PeerId,
+    /// The connection to the relay on which the request is sent, once connected.
+    connection: Option<ConnectionId>,
+    sender: oneshot::Sender<Result<RelayedConnection, RelayError>>,
+}
+
+impl<TSubstream> Relay<TSubstream> {
+    pub(crate) fn new(config: RelayConfig, from_transport: mpsc::UnboundedReceiver<TransportToBehaviourMsg>) -> Self {
+        Relay {
+            config,
+            from_transport,
+            listeners: Vec::new(),
+            listening_relays: SmallVec::new(),
+            connected: HashMap::new(),
+            addresses: HashMap::new(),
+            pending_dials: HashMap::new(),
+            outgoing: HashMap::new(),
+            ready_dials: VecDeque::new(),
+            hop_requests: VecDeque::new(),
+            num_circuits: 0,
+            next_request_id: 0,
+            pending_actions: VecDeque::new(),
+        }
+    }
+
+    /// Returns the number of connections we are currently relaying.
+    pub fn num_circuits(&self) -> usize {
+        self.num_circuits
+    }
+
+    fn add_address(&mut self, peer: &PeerId, address: Multiaddr) {
+        let addresses = self.addresses.entry(peer.clone()).or_default();
+        if !addresses.contains(&address) {
+            addresses.push(address);
+        }
+    }
+
+    /// Sends the request to open a relayed connection to a connection to the relay.
+    fn send_hop_request(
+        &mut self,
+        relay: PeerId,
+        connection: ConnectionId,
+        request_id: RequestId,
+        dst: Peer,
+        params: &mut impl PollParameters,
+    ) {
+        match self.outgoing.get_mut(&request_id) {
+            Some(dial) => dial.connection = Some(connection),
+            None => return,
+        }
+        let src = Peer {
+            id: params.local_peer_id().clone(),
+            addrs: params.external_addresses().collect(),
+        };
+        self.pending_actions.push_back(NetworkBehaviourAction::SendEventToConnection {
+            peer_id: relay,
+            connection,
+            event: RelayHandlerIn::OutgoingHopRequest { request_id, src, dst },
+        });
+    }
+
+    /// Processes a message sent by the transport.
+    fn on_transport_message(&mut self, message: TransportToBehaviourMsg, params: &mut impl PollParameters) {
+        match message {
+            TransportToBehaviourMsg::Dial { relay, relay_addr, dst, dst_addr, sender } => {
+                if let Some(addr) = relay_addr {
+                    self.add_address(&relay, addr);
+                }
+                let request_id = RequestId(self.next_request_id);
+                self.next_request_id += 1;
+                self.outgoing.insert(request_id, OutgoingDial { relay: relay.clone(), connection: None, sender });
+
+                let dst = Peer { id: dst, addrs: dst_addr.into_iter().collect() };
+                let connection = self.connected.get(&relay).and_then(|conns| conns.last()).cloned();
+                if let Some(connection) = connection {
+                    self.send_hop_request(relay, connection, request_id, dst, params);
+                } else {
+                    let queue = self.pending_dials.entry(relay.clone()).or_default();
+                    if queue.is_empty() {
+                        self.pending_actions.push_back(NetworkBehaviourAction::DialPeer { peer_id: relay });
+                    }
+                    queue.push((request_id, dst));
+                }
+            }
+            TransportToBehaviourMsg::Listen { relay, sender } => {
+                let relay = relay.map(|(relay, relay_addr)| {
+                    if let Some(addr) = relay_addr {
+                        self.add_address(&relay, addr);
+                    }
+                    if !self.listening_relays.contains(&relay) {
+                        self.listening_relays.push(relay.clone());
+                        match self.connected.get(&relay) {
+                            Some(connections) => for connection in connections {
+                                self.pending_actions.push_back(NetworkBehaviourAction::SendEventToConnection {
+                                    peer_id: relay.clone(),
+                                    connection: *connection,
+                                    event: RelayHandlerIn::SetListening(true),
+                                });
+                            },
+                            None => self.pending_actions.push_back(NetworkBehaviourAction::DialPeer {
+                                peer_id: relay.clone(),
+                            }),
+                        }
+                    }
+                    relay
+                });
+                self.listeners.push((relay, sender));
+            }
+        }
+    }
+
+    /// Processes an inbound request to relay a connection from `src`.
+    fn on_hop_request(
+        &mut self,
+        src: PeerId,
+        connection: ConnectionId,
+        request: RelayHopRequest<TSubstream>,
+        params: &mut impl PollParameters,
+    ) {
+        let dst = request.dst.id.clone();
+        let dst_connection = self.connected.get(&dst).and_then(|conns| conns.last()).cloned();
+        let status = if &dst == params.local_peer_id() {
+            Status::HopCantRelayToSelf
+        } else if self.num_circuits >= self.config.max_circuits {
+            Status::HopCantSpeakRelay
+        } else if let Some(dst_connection) = dst_connection {
+            self.num_circuits += 1;
+            self.pending_actions.push_back(NetworkBehaviourAction::SendEventToConnection {
+                peer_id: dst,
+                connection: dst_connection,
+                event: RelayHandlerIn::OutgoingStopRequest { src, request },
+            });
+            return
+        } else {
+            Status::HopNoConnToDst
+        };
+
+        log::debug!("Refusing to relay a connection from {:?} to {:?}: {}", src, dst, status);
+        self.pending_actions.push_back(NetworkBehaviourAction::SendEventToConnection {
+            peer_id: src.clone(),
+            connection,
+            event: RelayHandlerIn::DenyHopRequest { request, status },
+        });
+        self.pending_actions.push_back(NetworkBehaviourAction::GenerateEvent(
+            RelayEvent::CircuitFailed { src, dst, status }
+        ));
+    }
+
+    /// Processes an event generated by the handler of a connection.
+    fn on_handler_event(&mut self, peer: PeerId, connection: ConnectionId, event: RelayHandlerEvent<TSubstream>)
+    where
+        TSubstream: AsyncRead + AsyncWrite + Send + 'static,
+    {
+        match event {
+            RelayHandlerEvent::IncomingHopRequest(request) => {
+                self.hop_requests.push_back((peer, connection, request));
+            }
+            RelayHandlerEvent::IncomingStopRequest(request) => {
+                let accepted = self.listeners.iter()
+                    .any(|(relay, _)| relay.as_ref().map_or(true, |r| r == &peer));
+                let event = if accepted {
+                    RelayHandlerIn::AcceptStopRequest(request)
+                } else {
+                    RelayHandlerIn::DenyStopRequest { request, status: Status::StopRelayRefused }
+                };
+                self.pending_actions.push_back(NetworkBehaviourAction::SendEventToConnection {
+                    peer_id: peer,
+                    connection,
+                    event,
+                });
+            }
+            RelayHandlerEvent::StopAccepted { src, stream } => {
+                let mut incoming = IncomingRelayedConnection {
+                    connection: RelayedConnection::new(stream),
+                    relay: peer,
+                    src,
+                };
+                let mut n = 0;
+                while n < self.listeners.len() {
+                    let (relay, sender) = &self.listeners[n];
+                    if relay.as_ref().map_or(false, |r| r != &incoming.relay) {
+                        n += 1;
+                        continue
+                    }
+                    match sender.unbounded_send(incoming) {
+                        Ok(()) => return,
+                        Err(err) => {
+                            // The listener has been closed.
+                            incoming = err.into_inner();
+                            self.listeners.remove(n);
+                        }
+                    }
+                }
+                log::debug!("Dropping connection from {:?} relayed by {:?}: no listener", incoming.src, incoming.relay);
+            }
+            RelayHandlerEvent::OutgoingHopSuccess { request_id, stream } => {
+                if let Some(dial) = self.outgoing.remove(&request_id) {
+                    let _ = dial.sender.send(Ok(RelayedConnection::new(stream)));
+                }
+            }
+            RelayHandlerEvent::OutgoingHopFailure { request_id, error } => {
+                if let Some(dial) = self.outgoing.remove(&request_id) {
+                    let _ = dial.sender.send(Err(error));
+                }
+            }
+            RelayHandlerEvent::CircuitOpened { src } => {
+                self.pending_actions.push_back(NetworkBehaviourAction::GenerateEvent(
+                    RelayEvent::CircuitOpened { src, dst: peer }
+                ));
+            }
+            RelayHandlerEvent::CircuitFailed { src } => {
+                self.num_circuits -= 1;
+                self.pending_actions.push_back(NetworkBehaviourAction::GenerateEvent(
+                    RelayEvent::CircuitFailed { src, dst: peer, status: Status::HopCantOpenDstStream }
+                ));
+            }
+            RelayHandlerEvent::CircuitClosed { src } => {
+                self.num_circuits -= 1;
+                self.pending_actions.push_back(NetworkBehaviourAction::GenerateEvent(
+                    RelayEvent::CircuitClosed { src, dst: peer }
+                ));
+            }
+        }
+    }
+}
+
+impl<TSubstream> NetworkBehaviour for Relay<TSubstream>
+where
+    TSubstream: AsyncRead + AsyncWrite + Send + 'static,
+{
+    type ProtocolsHandler = RelayHandler<TSubstream>;
+    type OutEvent = RelayEvent;
+
+    fn new_handler(&mut self) -> Self::ProtocolsHandler {
+        RelayHandler::new(self.config.clone())
+    }
+
+    fn addresses_of_peer(&mut self, peer_id: &PeerId) -> Vec<Multiaddr> {
+        self.addresses.get(peer_id).map(|a| a.to_vec()).unwrap_or_default()
+    }
+
+    fn inject_connected(&mut self, _: PeerId, _: ConnectedPoint) {}
+
+    fn inject_disconnected(&mut self, peer_id: &PeerId, _: ConnectedPoint) {
+        self.connected.remove(peer_id);
+    }
+
+    fn inject_connection_established(&mut self, peer_id: &PeerId, connection: &ConnectionId, _: &ConnectedPoint) {
+        self.connected.entry(peer_id.clone()).or_default().push(*connection);
+
+        if self.listening_relays.contains(peer_id) {
+            self.pending_actions.push_back(NetworkBehaviourAction::SendEventToConnection {
+                peer_id: peer_id.clone(),
+                connection: *connection,
+                event: RelayHandlerIn::SetListening(true),
+            });
+        }
+
+        if let Some(dials) = self.pending_dials.remove(peer_id) {
+            for (request_id, dst) in dials {
+                self.ready_dials.push_back((peer_id.clone(), *connection, request_id, dst));
+            }
+        }
+    }
+
+    fn inject_connection_closed(&mut self, peer_id: &PeerId, connection: &ConnectionId, _: &ConnectedPoint) {
+        if let Some(connections) = self.connected.get_mut(peer_id) {
+            connections.retain(|c| c != connection);
+            if connections.is_empty() {
+                self.connected.remove(peer_id);
+            }
+        }
+
+        let interrupted = self.outgoing.iter()
+            .filter(|(_, d)| d.connection.as_ref() == Some(connection))
+            .map(|(id, _)| *id)
+            .collect::<Vec<_>>();
+        for request_id in interrupted {
+            if let Some(dial) = self.outgoing.remove(&request_id) {
+                let _ = dial.sender.send(Err(RelayError::BehaviourGone));
+            }
+        }
+    }
+
+    fn inject_node_event(&mut self, peer_id: PeerId, event: RelayHandlerEvent<TSubstream>) {
+        match node_event_connection(&self.connected, &peer_id) {
+            Some(connection) => self.inject_connection_event(peer_id, connection, event),
+            None => log::debug!("Dropping relay event from disconnected peer {:?}", peer_id),
+        }
+    }
+
+    fn inject_connection_event(&mut self, peer_id: PeerId, connection: ConnectionId, event: RelayHandlerEvent<TSubstream>) {
+        self.on_handler_event(peer_id, connection, event)
+    }
+
+    fn inject_dial_failure(&mut self, peer_id: &PeerId) {
+        if let Some(dials) = self.pending_dials.remove(peer_id) {
+            for (request_id, _) in dials {
+                if let Some(dial) = self.outgoing.remove(&request_id) {
+                    let _ = dial.sender.send(Err(RelayError::DialFailure));
+                }
+            }
+        }
+        if self.listening_relays.contains(peer_id) {
+            log::warn!("Failed to connect to relay {:?}", peer_id);
+        }
+    }
+
+    fn poll(&mut self, params: &mut impl PollParameters)
+        -> Async<NetworkBehaviourAction<RelayHandlerIn<TSubstream>, Self::OutEvent>>
+    {
+        while let Ok(Async::Ready(Some(message))) = self.from_transport.poll() {
+            self.on_transport_message(message, params);
+        }
+
+        while let Some((src, connection, request)) = self.hop_requests.pop_front() {
+            self.on_hop_request(src, connection, request, params);
+        }
+
+        while let Some((relay, connection, request_id, dst)) = self.ready_dials.pop_front() {
+            self.send_hop_request(relay, connection, request_id, dst, params);
+        }
+
+        if let Some(action) = self.pending_actions.pop_front() {
+            return Async::Ready(action)
+        }
+
+        Async::NotReady
+    }
+}
@@ -0,0 +1,491 @@
+// Copyright 2019 Parity Technologies (UK) Ltd.
+//
+// Permission is hereby granted, free of charge, to any person obtaining a
+// copy of this software and associated documentation files (the "Software"),
+// to deal in the Software without restriction, including without limitation
+// the rights to use, copy, modify, merge, publish, distribute, sublicense,
+// and/or sell copies of the Software, and to permit persons to whom the
+// Software is furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS
+// OR IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING
+// FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
+// DEALINGS IN THE SOFTWARE.
+
+use crate::RelayConfig;
+use crate::protocol::{self, CircuitRelay, IoFuture, MessageType, Peer, RelayListen, RelayRequest, RelayRequestError, Status};
+use crate::transport::RelayError;
+use futures::{prelude::*, sync::oneshot};
+use libp2p_core::{Multiaddr, PeerId, upgrade::{Negotiated, UpgradeError}};
+use libp2p_swarm::{
+    KeepAlive,
+    SubstreamProtocol,
+    ProtocolsHandler,
+    ProtocolsHandlerEvent,
+    ProtocolsHandlerUpgrErr
+};
+use std::{collections::VecDeque, fmt, io::{self, Read, Write}};
+use tokio_io::{AsyncRead, AsyncWrite};
+use void::Void;
+use wasm_timer::Instant;
+
+/// Identifier of a relayed connection that we asked a relay to open.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct RequestId(pub(crate) u64);
+
+impl fmt::Display for RequestId {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+/// Keeps alive the connection whose handler created it. The handler is notified when the guard
+/// is dropped.
+pub(crate) struct ConnectionGuard(oneshot::Sender<()>);
+
+impl ConnectionGuard {
+    /// Creates a guard, along with the receiver that the handler polls to be notified when it's
+    /// dropped.
+    pub(crate) fn new() -> (Self, oneshot::Receiver<()>) {
+        let (sender, receiver) = oneshot::channel();
+        (ConnectionGuard(sender), receiver)
+    }
+}
+
+/// Forgets the guards that have been dropped, and registers the current task to be notified
+/// when the others are.
+pub(crate) fn poll_guards(guards: &mut Vec<oneshot::Receiver<()>>) {
+    let mut n = 0;
+    while n < guards.len() {
+        match guards[n].poll() {
+            Ok(Async::NotReady) => n += 1,
+            Ok(Async::Ready(())) | Err(oneshot::Canceled) => {
+                guards.swap_remove(n);
+            }
+        }
+    }
+}
+
+/// Request received from a remote to relay a connection to a destination.
+pub struct RelayHopRequest<TSubstream> {
+    pub(crate) substream: Negotiated<TSubstream>,
+    pub(crate) dst: Peer,
+    pub(crate) src_addrs: Vec<Multiaddr>,
+    pub(crate) guard: ConnectionGuard,
+}
+
+impl<TSubstream> RelayHopRequest<TSubstream> {
+    /// Returns the destination of the requested circuit.
+    pub fn dst(&self) -> &Peer {
+        &self.dst
+    }
+}
+
+/// Request received from a relay to accept a connection relayed from a source.
+pub struct RelayStopRequest<TSubstream> {
+    pub(crate) substream: Negotiated<TSubstream>,
+    pub(crate) src: Peer,
+    pub(crate) guard: ConnectionGuard,
+}
+
+impl<TSubstream> RelayStopRequest<TSubstream> {
+    /// Returns the source of the relayed connection.
+    pub fn src(&self) -> &Peer {
+        &self.src
+    }
+}
+
+/// Substream of a relayed connection.
+///
+/// Keeps alive the connection to the relay, or to the destination of the relay, on which it was
+/// opened.
+pub struct RelayedStream<TSubstream> {
+    inner: Negotiated<TSubstream>,
+    _guard: ConnectionGuard,
+}
+
+impl<TSubstream> RelayedStream<TSubstream> {
+    pub(crate) fn new(inner: Negotiated<TSubstream>, guard: ConnectionGuard) -> Self {
+        RelayedStream { inner, _guard: guard }
+    }
+}
+
+impl<TSubstream> io::Read for RelayedStream<TSubstream>
+where
+    TSubstream: AsyncRead,
+{
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        self.inner.read(buf)
+    }
+}
+
+impl<TSubstream> AsyncRead for RelayedStream<TSubstream>
+where
+    TSubstream: AsyncRead,
+{
+}
+
+impl<TSubstream> io::Write for RelayedStream<TSubstream>
+where
+    TSubstream: AsyncWrite,
+{
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.inner.write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+impl<TSubstream> AsyncWrite for RelayedStream<TSubstream>
+where
+    TSubstream: AsyncWrite,
+{
+    fn shutdown(&mut self) -> Poll<(), io::Error> {
+        self.inner.shutdown()
+    }
+}
+
+/// Event sent by the behaviour to the `RelayHandler`.
+pub enum RelayHandlerIn<TSubstream> {
+    /// Sets whether we accept the connections relayed by the remote, in which case the
+    /// connection is kept alive.
+    SetListening(bool),
+    /// Asks the remote to relay a connection from us to `dst`.
+    OutgoingHopRequest {
+        request_id: RequestId,
+        /// Our own identity and addresses.
+        src: Peer,
+        dst: Peer,
+    },
+    /// Asks the remote to accept a connection relayed from `src`, and relays it if accepted.
+    OutgoingStopRequest {
+        src: PeerId,
+        request: RelayHopRequest<TSubstream>,
+    },
+    /// Accepts a connection relayed by the remote.
+    AcceptStopRequest(RelayStopRequest<TSubstream>),
+    /// Refuses to relay a connection for the remote.
+    DenyHopRequest {
+        request: RelayHopRequest<TSubstream>,
+        status: Status,
+    },
+    /// Refuses a connection relayed by the remote.
+    DenyStopRequest {
+        request: RelayStopRequest<TSubstream>,
+        status: Status,
+    },
+}
+
+/// Event produced by the `RelayHandler`.
+pub enum RelayHandlerEvent<TSubstream> {
+    /// The remote asks us to relay a connection.
+    IncomingHopRequest(RelayHopRequest<TSubstream>),
+    /// The remote asks us to accept a connection it relays.
+    IncomingStopRequest(RelayStopRequest<TSubstream>),
+    /// A connection relayed by the remote has been accepted.
+    StopAccepted {
+        src: PeerId,
+        stream: RelayedStream<TSubstream>,
+    },
+    /// The remote relays a connection from us.
+    OutgoingHopSuccess {
+        request_id: RequestId,
+        stream: RelayedStream<TSubstream>,
+    },
+    /// The remote refused to relay a connection from us, or the request failed.
+    OutgoingHopFailure {
+        request_id: RequestId,
+        error: RelayError,
+    },
+    /// We started relaying a connection from `src` to the remote.
+    CircuitOpened { src: PeerId },
+    /// The remote didn't accept a connection from `src` that we were asked to relay.
+    CircuitFailed { src: PeerId },
+    /// A connection relayed from `src` to the remote has been closed.
+    CircuitClosed { src: PeerId },
+}
+
+/// Information attached to the outbound substreams being opened.
+pub enum RelayOutboundInfo<TSubstream> {
+    Hop(RequestId),
+    Stop {
+        src: PeerId,
+        request: RelayHopRequest<TSubstream>,
+    },
+}
+
+/// Protocol handler answering the requests of the circuit relay protocol, sending our own
+/// requests, and driving the circuits whose destination is the remote.
+pub struct RelayHandler<TSubstream> {
+    /// Configuration options.
+    config: RelayConfig,
+    /// Value to return from `connection_keep_alive`.
+    keep_alive: KeepAlive,
+    /// Whether we accept the connections relayed by the remote.
+    listening: bool,
+    /// Requests for which an outbound substream must be opened.
+    outbound: VecDeque<(RelayRequest, RelayOutboundInfo<TSubstream>)>,
+    /// Number of outbound substreams being opened or negotiated.
+    num_pending_outbound: usize,
+    /// Answers being sent on inbound substreams.
+    answers: Vec<IoFuture<()>>,
+    /// Inbound `Stop` requests being accepted.
+    accepting: Vec<(PeerId, ConnectionGuard, IoFuture<Negotiated<TSubstream>>)>,
+    /// Circuits whose destination is the remote, with their source.
+    circuits: Vec<(PeerId, ConnectionGuard, IoFuture<()>)>,
+    /// Receivers notified when the guards created by this handler are dropped.
+    guards: Vec<oneshot::Receiver<()>>,
+    /// Events to produce in `poll()`.
+    pending_events: VecDeque<RelayHandlerEvent<TSubstream>>,
+}
+
+impl<TSubstream> RelayHandler<TSubstream> {
+    pub(crate) fn new(config: RelayConfig) -> Self {
+        let keep_alive = KeepAlive::Until(Instant::now() + config.connection_idle_timeout);
+        RelayHandler {
+            config,
+            keep_alive,
+            listening: false,
+            outbound: VecDeque::new(),
+            num_pending_outbound: 0,
+            answers: Vec::new(),
+            accepting: Vec::new(),
+            circuits: Vec::new(),
+            guards: Vec::new(),
+            pending_events: VecDeque::new(),
+        }
+    }
+
+    /// Creates a guard keeping this connection alive.
+    fn new_guard(&mut self) -> ConnectionGuard {
+        let (guard, receiver) = ConnectionGuard::new();
+        self.guards.push(receiver);
+        guard
+    }
+
+    /// Updates the keep-alive after the state of the handler has changed.
+    fn update_keep_alive(&mut self) {
+        let busy = self.listening
+            || !self.outbound.is_empty()
+            || self.num_pending_outbound > 0
+            || !self.answers.is_empty()
+            || !self.accepting.is_empty()
+            || !self.circuits.is_empty()
+            || !self.guards.is_empty();
+        if busy {
+            self.keep_alive = KeepAlive::Yes;
+        } else if let KeepAlive::Yes = self.keep_alive {
+            self.keep_alive = KeepAlive::Until(Instant::now() + self.config.connection_idle_timeout);
+        }
+    }
+}
+
+impl<TSubstream> RelayHandler<TSubstream>
+where
+    TSubstream: AsyncRead + AsyncWrite + Send + 'static,
+{
+    /// Sends a `Status` message on an inbound substream, then closes it.
+    fn answer(&mut self, substream: Negotiated<TSubstream>, status: Status) {
+        let message = CircuitRelay::status(status);
+        self.answers.push(Box::new(protocol::send_message(substream, &message).map(|_| ())));
+    }
+}
+
+impl<TSubstream> ProtocolsHandler for RelayHandler<TSubstream>
+where
+    TSubstream: AsyncRead + AsyncWrite + Send + 'static,
+{
+    type InEvent = RelayHandlerIn<TSubstream>;
+    type OutEvent = RelayHandlerEvent<TSubstream>;
+    type Error = Void;
+    type Substream = TSubstream;
+    type InboundProtocol = RelayListen;
+    type OutboundProtocol = RelayRequest;
+    type OutboundOpenInfo = RelayOutboundInfo<TSubstream>;
+
+    fn listen_protocol(&self) -> SubstreamProtocol<Self::InboundProtocol> {
+        SubstreamProtocol::new(RelayListen).with_timeout(self.config.request_timeout)
+    }
+
+    fn inject_fully_negotiated_inbound(&mut self, (message, substream): (CircuitRelay, Negotiated<TSubstream>)) {
+        match message.kind {
+            MessageType::Hop if !self.config.hop => self.answer(substream, Status::HopCantSpeakRelay),
+            MessageType::Hop => match message.dst_peer {
+                Some(dst) => {
+                    let request = RelayHopRequest {
+                        substream,
+                        dst,
+                        src_addrs: message.src_peer.map(|p| p.addrs).unwrap_or_default(),
+                        guard: self.new_guard(),
+                    };
+                    self.pending_events.push_back(RelayHandlerEvent::IncomingHopRequest(request));
+                }
+                None => self.answer(substream, Status::HopDstMultiaddrInvalid),
+            },
+            MessageType::Stop => match message.src_peer {
+                Some(src) => {
+                    let request = RelayStopRequest { substream, src, guard: self.new_guard() };
+                    self.pending_events.push_back(RelayHandlerEvent::IncomingStopRequest(request));
+                }
+                None => self.answer(substream, Status::StopSrcMultiaddrInvalid),
+            },
+            MessageType::CanHop => {
+                let status = if self.config.hop { Status::Success } else { Status::HopCantSpeakRelay };
+                self.answer(substream, status);
+            }
+            MessageType::Status => self.answer(substream, Status::MalformedMessage),
+        }
+        self.update_keep_alive();
+    }
+
+    fn inject_fully_negotiated_outbound(&mut self, substream: Negotiated<TSubstream>, info: Self::OutboundOpenInfo) {
+        self.num_pending_outbound -= 1;
+        match info {
+            RelayOutboundInfo::Hop(request_id) => {
+                let stream = RelayedStream { inner: substream, _guard: self.new_guard() };
+                self.pending_events.push_back(RelayHandlerEvent::OutgoingHopSuccess { request_id, stream });
+            }
+            RelayOutboundInfo::Stop { src, request } => {
+                // The destination accepted; tell the source and start relaying.
+                let success = CircuitRelay::status(Status::Success);
+                let circuit = protocol::send_message(request.substream, &success)
+                    .and_then(move |src_substream| protocol::circuit(src_substream, substream));
+                self.circuits.push((src.clone(), request.guard, Box::new(circuit)));
+                self.pending_events.push_back(RelayHandlerEvent::CircuitOpened { src });
+            }
+        }
+        self.update_keep_alive();
+    }
+
+    fn inject_event(&mut self, event: Self::InEvent) {
+        match event {
+            RelayHandlerIn::SetListening(listening) => self.listening = listening,
+            RelayHandlerIn::OutgoingHopRequest { request_id, src, dst } => {
+                let message = CircuitRelay {
+                    kind: MessageType::Hop,
+                    src_peer: Some(src),
+                    dst_peer: Some(dst),
+                    code: None,
+                };
+                self.outbound.push_back((RelayRequest { message }, RelayOutboundInfo::Hop(request_id)));
+            }
+            RelayHandlerIn::OutgoingStopRequest { src, request } => {
+                let message = CircuitRelay {
+                    kind: MessageType::Stop,
+                    src_peer: Some(Peer { id: src.clone(), addrs: request.src_addrs.clone() }),
+                    dst_peer: Some(request.dst.clone()),
+                    code: None,
+                };
+                self.outbound.push_back((RelayRequest { message }, RelayOutboundInfo::Stop { src, request }));
+            }
+            RelayHandlerIn::AcceptStopRequest(request) => {
+                let success = CircuitRelay::status(Status::Success);
+                let future = protocol::send_message(request.substream, &success);
+                self.accepting.push((request.src.id, request.guard, future));
+            }
+            RelayHandlerIn::DenyHopRequest { request, status } => self.answer(request.substream, status),
+            RelayHandlerIn::DenyStopRequest { request, status } => self.answer(request.substream, status),
+        }
+        self.update_keep_alive();
+    }
+
+    fn inject_dial_upgrade_error(
+        &mut self,
+        info: Self::OutboundOpenInfo,
+        error: ProtocolsHandlerUpgrErr<RelayRequestError>
+    ) {
+        self.num_pending_outbound -= 1;
+        match info {
+            RelayOutboundInfo::Hop(request_id) => {
+                let error = match error {
+                    ProtocolsHandlerUpgrErr::Timeout | ProtocolsHandlerUpgrErr::Timer => RelayError::Timeout,
+                    ProtocolsHandlerUpgrErr::Upgrade(UpgradeError::Select(_)) => RelayError::UnsupportedProtocol,
+                    ProtocolsHandlerUpgrErr::Upgrade(UpgradeError::Apply(e)) => e.into(),
+                };
+                self.pending_events.push_back(RelayHandlerEvent::OutgoingHopFailure { request_id, error });
+            }
+            RelayOutboundInfo::Stop { src, request } => {
+                log::debug!("Failed to relay a connection from {:?}: {}", src, error);
+                self.answer(request.substream, Status::HopCantOpenDstStream);
+                self.pending_events.push_back(RelayHandlerEvent::CircuitFailed { src });
+            }
+        }
+        self.update_keep_alive();
+    }
+
+    fn connection_keep_alive(&self) -> KeepAlive {
+        self.keep_alive
+    }
+
+    fn poll(&mut self) -> Poll<ProtocolsHandlerEvent<RelayRequest, Self::OutboundOpenInfo, Self::OutEvent>, Void> {
+        poll_guards(&mut self.guards);
+
+        let mut n = 0;
+        while n < self.answers.len() {
+            match self.answers[n].poll() {
+                Ok(Async::NotReady) => n += 1,
+                Ok(Async::Ready(())) => {
+                    self.answers.swap_remove(n);
+                }
+                Err(err) => {
+                    log::debug!("Failed to answer a relay request: {}", err);
+                    self.answers.swap_remove(n);
+                }
+            }
+        }
+
+        let mut n = 0;
+        while n < self.accepting.len() {
+            match self.accepting[n].2.poll() {
+                Ok(Async::NotReady) => n += 1,
+                Ok(Async::Ready(substream)) => {
+                    let (src, guard, _) = self.accepting.swap_remove(n);
+                    let stream = RelayedStream { inner: substream, _guard: guard };
+                    self.pending_events.push_back(RelayHandlerEvent::StopAccepted { src, stream });
+                }
+                Err(err) => {
+                    log::debug!("Failed to accept a relayed connection: {}", err);
+                    self.accepting.swap_remove(n);
+                }
+            }
+        }
+
+        let mut n = 0;
+        while n < self.circuits.len() {
+            match self.circuits[n].2.poll() {
+                Ok(Async::NotReady) => n += 1,
+                Ok(Async::Ready(())) => {
+                    let (src, _, _) = self.circuits.swap_remove(n);
+                    self.pending_events.push_back(RelayHandlerEvent::CircuitClosed { src });
+                }
+                Err(err) => {
+                    let (src, _, _) = self.circuits.swap_remove(n);
+                    log::debug!("Relayed connection from {:?} closed with an error: {}", src, err);
+                    self.pending_events.push_back(RelayHandlerEvent::CircuitClosed { src });
+                }
+            }
+        }
+
+        self.update_keep_alive();
+
+        if let Some(event) = self.pending_events.pop_front() {
+            return Ok(Async::Ready(ProtocolsHandlerEvent::Custom(event)))
+        }
+
+        if let Some((request, info)) = self.outbound.pop_front() {
+            self.num_pending_outbound += 1;
+            let protocol = SubstreamProtocol::new(request).with_timeout(self.config.request_timeout);
+            return Ok(Async::Ready(ProtocolsHandlerEvent::OutboundSubstreamRequest { protocol, info }))
+        }
+
+        Ok(Async::NotReady)
+    }
+}
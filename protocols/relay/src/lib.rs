@@ -0,0 +1,127 @@
+// Copyright 2019 Parity Technologies (UK) Ltd.
+//
+// Permission is hereby granted, free of charge, to any person obtaining a
+// copy of this software and associated documentation files (the "Software"),
+// to deal in the Software without restriction, including without limitation
+// the rights to use, copy, modify, merge, publish, distribute, sublicense,
+// and/or sell copies of the Software, and to permit persons to whom the
+// Software is furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS
+// OR IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING
+// FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
+// DEALINGS IN THE SOFTWARE.
+
+//! Implementation of the [circuit relay] protocol, version 1.
+//!
+//! The circuit relay protocol lets a peer open a connection to another peer through a third
+//! peer, the relay, which forwards the data between the two. It makes peers that can't be
+//! reached directly, typically because they're behind a NAT, reachable through the relays they
+//! are connected to.
+//!
+//! The protocol is implemented by two objects created together with
+//! [`new_transport_and_behaviour`]:
+//!
+//! - The [`RelayTransport`] dials and listens on relayed addresses, which contain `/p2p-circuit`.
+//!   It must be combined with a regular transport, as the connections to the relays are opened
+//!   like any other connection.
+//! - The [`Relay`] network behaviour opens the relayed connections requested by the transport,
+//!   hands the connections relayed to us to the transport, and relays the connections of other
+//!   peers if enabled with [`RelayConfig::with_hop`].
+//!
+//! Relayed connections are upgraded like any other connection, with encryption and
+//! multiplexing, and are therefore opaque to the relay.
+//!
+//! Version 2 of the protocol, with reservations and resource limits, is implemented in the
+//! [`v2`] module and uses the same transport.
+//!
+//! [circuit relay]: https://github.com/libp2p/specs/tree/master/relay
+
+mod behaviour;
+mod handler;
+mod protocol;
+mod transport;
+
+/// Protobuf messages of version 1 of the protocol, generated from `message.proto`.
+mod proto {
+    include!(concat!(env!("OUT_DIR"), "/relay.pb.rs"));
+}
+
+pub mod v2;
+
+pub use behaviour::{Relay, RelayEvent};
+pub use handler::{
+    RelayHandler,
+    RelayHandlerEvent,
+    RelayHandlerIn,
+    RelayHopRequest,
+    RelayOutboundInfo,
+    RelayStopRequest,
+    RelayedStream,
+    RequestId
+};
+pub use protocol::{CircuitRelay, MessageType, Peer, RelayListen, RelayRequest, RelayRequestError, Status, PROTOCOL_NAME};
+pub use transport::{RelayDial, RelayError, RelayListener, RelayTransport, RelayedConnection};
+
+use futures::sync::mpsc;
+use std::time::Duration;
+
+/// Configuration of the [`Relay`] network behaviour.
+#[derive(Debug, Clone)]
+pub struct RelayConfig {
+    hop: bool,
+    max_circuits: usize,
+    request_timeout: Duration,
+    connection_idle_timeout: Duration,
+}
+
+impl Default for RelayConfig {
+    fn default() -> Self {
+        RelayConfig {
+            hop: false,
+            max_circuits: 64,
+            request_timeout: Duration::from_secs(10),
+            connection_idle_timeout: Duration::from_secs(10),
+        }
+    }
+}
+
+impl RelayConfig {
+    /// Sets whether we relay the connections of other peers. Disabled by default.
+    pub fn with_hop(mut self, hop: bool) -> Self {
+        self.hop = hop;
+        self
+    }
+
+    /// Sets the maximum number of connections we relay at the same time. Defaults to 64.
+    pub fn with_max_circuits(mut self, max: usize) -> Self {
+        self.max_circuits = max;
+        self
+    }
+
+    /// Sets how long the relay and the destination have to answer a request. Defaults to
+    /// 10 seconds.
+    pub fn with_request_timeout(mut self, timeout: Duration) -> Self {
+        self.request_timeout = timeout;
+        self
+    }
+
+    /// Sets how long a connection without relayed connections is kept alive. Defaults to
+    /// 10 seconds.
+    pub fn with_connection_idle_timeout(mut self, timeout: Duration) -> Self {
+        self.connection_idle_timeout = timeout;
+        self
+    }
+}
+
+/// Creates a [`RelayTransport`] and the [`Relay`] behaviour that opens its connections.
+pub fn new_transport_and_behaviour<TSubstream>(config: RelayConfig) -> (RelayTransport, Relay<TSubstream>) {
+    let (to_behaviour, from_transport) = mpsc::unbounded();
+    (RelayTransport::new(to_behaviour), Relay::new(config, from_transport))
+}
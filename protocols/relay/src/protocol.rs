@@ -0,0 +1,501 @@
+// Copyright 2019 Parity Technologies (UK) Ltd.
+//
+// Permission is hereby granted, free of charge, to any person obtaining a
+// copy of this software and associated documentation files (the "Software"),
+// to deal in the Software without restriction, including without limitation
+// the rights to use, copy, modify, merge, publish, distribute, sublicense,
+// and/or sell copies of the Software, and to permit persons to whom the
+// Software is furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS
+// OR IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING
+// FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
+// DEALINGS IN THE SOFTWARE.
+
+//! Messages of the circuit relay protocol and upgrades applied on its substreams.
+//!
+//! Messages are protobufs prefixed with their length. Contrary to most other protocols, the
+//! substreams aren't closed once the messages have been exchanged: a successful `HOP` or `STOP`
+//! exchange turns the substream into one end of a relayed connection.
+
+use crate::proto;
+use futures::{future::{self, Either, Loop}, prelude::*};
+use libp2p_core::{
+    InboundUpgrade,
+    Multiaddr,
+    OutboundUpgrade,
+    PeerId,
+    UpgradeInfo,
+    upgrade::Negotiated
+};
+use prost::{DecodeError, Message};
+use std::{cmp, convert::TryFrom, error, fmt, io::{self, Read}, iter, time::Duration};
+use tokio_io::{AsyncRead, AsyncWrite};
+use wasm_timer::{Delay, Instant};
+
+/// Name of the protocol negotiated on the substreams.
+pub const PROTOCOL_NAME: &[u8] = b"/libp2p/circuit/relay/0.1.0";
+
+/// Maximum size of a message.
+const MAX_MESSAGE_SIZE: usize = 4096;
+
+/// Boxed future performing I/O on a substream.
+pub(crate) type IoFuture<T> = Box<dyn Future<Item = T, Error = io::Error> + Send>;
+
+/// Type of a relay message.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum MessageType {
+    /// Asks a relay to open a circuit towards the destination.
+    Hop,
+    /// Asks the destination to accept a circuit opened by a relay.
+    Stop,
+    /// Answer to a `Hop`, `Stop` or `CanHop` message.
+    Status,
+    /// Asks a peer whether it is willing to act as a relay.
+    CanHop,
+}
+
+impl MessageType {
+    fn code(self) -> i32 {
+        match self {
+            MessageType::Hop => 1,
+            MessageType::Stop => 2,
+            MessageType::Status => 3,
+            MessageType::CanHop => 4,
+        }
+    }
+
+    fn from_code(code: i32) -> Option<Self> {
+        match code {
+            1 => Some(MessageType::Hop),
+            2 => Some(MessageType::Stop),
+            3 => Some(MessageType::Status),
+            4 => Some(MessageType::CanHop),
+            _ => None,
+        }
+    }
+}
+
+/// Status code sent in a `Status` message.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Status {
+    Success,
+    HopSrcAddrTooLong,
+    HopDstAddrTooLong,
+    HopSrcMultiaddrInvalid,
+    HopDstMultiaddrInvalid,
+    HopNoConnToDst,
+    HopCantDialDst,
+    HopCantOpenDstStream,
+    HopCantSpeakRelay,
+    HopCantRelayToSelf,
+    StopSrcAddrTooLong,
+    StopDstAddrTooLong,
+    StopSrcMultiaddrInvalid,
+    StopDstMultiaddrInvalid,
+    StopRelayRefused,
+    MalformedMessage,
+}
+
+impl Status {
+    /// Returns the code of the status on the wire.
+    pub fn code(self) -> u32 {
+        match self {
+            Status::Success => 100,
+            Status::HopSrcAddrTooLong => 220,
+            Status::HopDstAddrTooLong => 221,
+            Status::HopSrcMultiaddrInvalid => 250,
+            Status::HopDstMultiaddrInvalid => 251,
+            Status::HopNoConnToDst => 260,
+            Status::HopCantDialDst => 261,
+            Status::HopCantOpenDstStream => 262,
+            Status::HopCantSpeakRelay => 270,
+            Status::HopCantRelayToSelf => 280,
+            Status::StopSrcAddrTooLong => 320,
+            Status::StopDstAddrTooLong => 321,
+            Status::StopSrcMultiaddrInvalid => 350,
+            Status::StopDstMultiaddrInvalid => 351,
+            Status::StopRelayRefused => 390,
+            Status::MalformedMessage => 400,
+        }
+    }
+
+    /// Returns the status corresponding to a code on the wire, if any.
+    pub fn from_code(code: u32) -> Option<Self> {
+        let status = match code {
+            100 => Status::Success,
+            220 => Status::HopSrcAddrTooLong,
+            221 => Status::HopDstAddrTooLong,
+            250 => Status::HopSrcMultiaddrInvalid,
+            251 => Status::HopDstMultiaddrInvalid,
+            260 => Status::HopNoConnToDst,
+            261 => Status::HopCantDialDst,
+            262 => Status::HopCantOpenDstStream,
+            270 => Status::HopCantSpeakRelay,
+            280 => Status::HopCantRelayToSelf,
+            320 => Status::StopSrcAddrTooLong,
+            321 => Status::StopDstAddrTooLong,
+            350 => Status::StopSrcMultiaddrInvalid,
+            351 => Status::StopDstMultiaddrInvalid,
+            390 => Status::StopRelayRefused,
+            400 => Status::MalformedMessage,
+            _ => return None,
+        };
+        Some(status)
+    }
+}
+
+impl fmt::Display for Status {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:?} ({})", self, self.code())
+    }
+}
+
+/// A peer, as described in a relay message.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Peer {
+    /// Identity of the peer.
+    pub id: PeerId,
+    /// Addresses of the peer. Addresses that couldn't be decoded are skipped.
+    pub addrs: Vec<Multiaddr>,
+}
+
+impl Peer {
+    fn to_proto(&self) -> proto::circuit_relay::Peer {
+        proto::circuit_relay::Peer {
+            id: self.id.as_bytes().to_vec(),
+            addrs: self.addrs.iter().map(Multiaddr::to_vec).collect(),
+        }
+    }
+
+    fn from_proto(peer: proto::circuit_relay::Peer) -> Result<Self, DecodeError> {
+        let id = PeerId::from_bytes(peer.id).map_err(|_| DecodeError::new("invalid peer ID"))?;
+        let addrs = peer.addrs
+            .into_iter()
+            .filter_map(|addr| Multiaddr::try_from(addr).ok())
+            .collect();
+        Ok(Peer { id, addrs })
+    }
+}
+
+/// A message of the circuit relay protocol.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CircuitRelay {
+    pub kind: MessageType,
+    pub src_peer: Option<Peer>,
+    pub dst_peer: Option<Peer>,
+    pub code: Option<Status>,
+}
+
+impl CircuitRelay {
+    /// Builds a `Status` message.
+    pub fn status(status: Status) -> Self {
+        CircuitRelay { kind: MessageType::Status, src_peer: None, dst_peer: None, code: Some(status) }
+    }
+
+    /// Encodes the message, without its length prefix.
+    pub fn encode(&self) -> Vec<u8> {
+        let message = proto::CircuitRelay {
+            r#type: Some(self.kind.code()),
+            src_peer: self.src_peer.as_ref().map(Peer::to_proto),
+            dst_peer: self.dst_peer.as_ref().map(Peer::to_proto),
+            code: self.code.map(|code| code.code() as i32),
+        };
+        let mut buf = Vec::with_capacity(message.encoded_len());
+        message.encode(&mut buf).expect("Vec<u8> provides capacity as needed");
+        buf
+    }
+
+    /// Decodes a message, without its length prefix.
+    pub fn decode(bytes: &[u8]) -> Result<Self, DecodeError> {
+        let message = proto::CircuitRelay::decode(bytes)?;
+        let kind = match message.r#type {
+            Some(code) => MessageType::from_code(code)
+                .ok_or_else(|| DecodeError::new("unknown message type"))?,
+            None => return Err(DecodeError::new("missing message type")),
+        };
+        let code = match message.code {
+            Some(code) => {
+                let status = u32::try_from(code).ok().and_then(Status::from_code);
+                Some(status.ok_or_else(|| DecodeError::new("unknown status code"))?)
+            }
+            None => None,
+        };
+        Ok(CircuitRelay {
+            kind,
+            src_peer: message.src_peer.map(Peer::from_proto).transpose()?,
+            dst_peer: message.dst_peer.map(Peer::from_proto).transpose()?,
+            code,
+        })
+    }
+}
+
+/// Writes a length-prefixed message on a substream and flushes it, without closing it.
+pub(crate) fn send_message<S>(socket: S, message: &CircuitRelay) -> IoFuture<S>
+where
+    S: AsyncWrite + Send + 'static,
+{
+    send_frame(socket, message.encode())
+}
+
+/// Reads a length-prefixed message from a substream, without reading anything past it.
+pub(crate) fn recv_message<S>(socket: S) -> IoFuture<(S, CircuitRelay)>
+where
+    S: AsyncRead + Send + 'static,
+{
+    Box::new(recv_frame(socket).and_then(|(socket, bytes)| {
+        let message = CircuitRelay::decode(&bytes)
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+        Ok((socket, message))
+    }))
+}
+
+/// Writes a length-prefixed frame on a substream and flushes it, without closing it.
+pub(crate) fn send_frame<S>(socket: S, body: Vec<u8>) -> IoFuture<S>
+where
+    S: AsyncWrite + Send + 'static,
+{
+    let mut len = unsigned_varint::encode::usize_buffer();
+    let mut bytes = unsigned_varint::encode::usize(body.len(), &mut len).to_vec();
+    bytes.extend_from_slice(&body);
+    Box::new(tokio_io::io::write_all(socket, bytes).and_then(|(socket, _)| tokio_io::io::flush(socket)))
+}
+
+/// Reads a length-prefixed frame from a substream, without reading anything past it.
+pub(crate) fn recv_frame<S>(socket: S) -> IoFuture<(S, Vec<u8>)>
+where
+    S: AsyncRead + Send + 'static,
+{
+    // The length prefix is read byte by byte, as the rest of the substream may be relayed data.
+    let read_len = future::loop_fn((socket, 0u64, 0u32), |(socket, len, shift)| {
+        tokio_io::io::read_exact(socket, [0u8; 1]).and_then(move |(socket, byte)| {
+            let len = len | (u64::from(byte[0] & 0x7f) << shift);
+            if byte[0] & 0x80 == 0 {
+                Ok(Loop::Break((socket, len)))
+            } else if shift >= 56 {
+                Err(io::Error::new(io::ErrorKind::InvalidData, "invalid message length"))
+            } else {
+                Ok(Loop::Continue((socket, len, shift + 7)))
+            }
+        })
+    });
+
+    let frame = read_len
+        .and_then(|(socket, len)| {
+            if len > MAX_MESSAGE_SIZE as u64 {
+                return Err(io::Error::new(io::ErrorKind::InvalidData, "message too large"))
+            }
+            Ok(tokio_io::io::read_exact(socket, vec![0; len as usize]))
+        })
+        .flatten();
+    Box::new(frame)
+}
+
+/// Upgrade applied on inbound substreams. Reads the first message, and produces it along with
+/// the substream.
+#[derive(Debug, Copy, Clone, Default)]
+pub struct RelayListen;
+
+impl UpgradeInfo for RelayListen {
+    type Info = &'static [u8];
+    type InfoIter = iter::Once<Self::Info>;
+
+    fn protocol_info(&self) -> Self::InfoIter {
+        iter::once(PROTOCOL_NAME)
+    }
+}
+
+impl<TSocket> InboundUpgrade<TSocket> for RelayListen
+where
+    TSocket: AsyncRead + AsyncWrite + Send + 'static,
+{
+    type Output = (CircuitRelay, Negotiated<TSocket>);
+    type Error = io::Error;
+    type Future = IoFuture<Self::Output>;
+
+    fn upgrade_inbound(self, socket: Negotiated<TSocket>, _: Self::Info) -> Self::Future {
+        Box::new(recv_message(socket).map(|(socket, message)| (message, socket)))
+    }
+}
+
+/// Upgrade applied on outbound substreams. Sends a `Hop`, `Stop` or `CanHop` message, waits for
+/// the `Status` answer, and produces the substream if the answer is a success.
+#[derive(Debug, Clone)]
+pub struct RelayRequest {
+    pub(crate) message: CircuitRelay,
+}
+
+impl UpgradeInfo for RelayRequest {
+    type Info = &'static [u8];
+    type InfoIter = iter::Once<Self::Info>;
+
+    fn protocol_info(&self) -> Self::InfoIter {
+        iter::once(PROTOCOL_NAME)
+    }
+}
+
+impl<TSocket> OutboundUpgrade<TSocket> for RelayRequest
+where
+    TSocket: AsyncRead + AsyncWrite + Send + 'static,
+{
+    type Output = Negotiated<TSocket>;
+    type Error = RelayRequestError;
+    type Future = Box<dyn Future<Item = Self::Output, Error = Self::Error> + Send>;
+
+    fn upgrade_outbound(self, socket: Negotiated<TSocket>, _: Self::Info) -> Self::Future {
+        let future = send_message(socket, &self.message)
+            .and_then(recv_message)
+            .map_err(RelayRequestError::Io)
+            .and_then(|(socket, answer)| match (answer.kind, answer.code) {
+                (MessageType::Status, Some(Status::Success)) => Ok(socket),
+                (MessageType::Status, Some(status)) => Err(RelayRequestError::Refused(status)),
+                _ => Err(RelayRequestError::UnexpectedMessage),
+            });
+        Box::new(future)
+    }
+}
+
+/// Error while sending a request to a remote.
+#[derive(Debug)]
+pub enum RelayRequestError {
+    /// The remote answered with a status other than `Success`.
+    Refused(Status),
+    /// The remote answered with something else than a status.
+    UnexpectedMessage,
+    /// I/O error on the substream.
+    Io(io::Error),
+}
+
+impl fmt::Display for RelayRequestError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RelayRequestError::Refused(status) => write!(f, "Request refused by the remote: {}", status),
+            RelayRequestError::UnexpectedMessage => write!(f, "Unexpected answer from the remote"),
+            RelayRequestError::Io(e) => write!(f, "I/O error: {}", e),
+        }
+    }
+}
+
+impl error::Error for RelayRequestError {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        match self {
+            RelayRequestError::Io(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+/// Relays the data between two substreams until both directions are closed.
+pub(crate) fn circuit<A, B>(a: A, b: B) -> IoFuture<()>
+where
+    A: AsyncRead + AsyncWrite + Send + 'static,
+    B: AsyncRead + AsyncWrite + Send + 'static,
+{
+    limited_circuit(a, b, None, None)
+}
+
+/// Relays the data between two substreams until both directions are closed, the duration
+/// limit is reached, or the data limit is reached in both directions.
+///
+/// A direction is closed once `max_bytes` bytes have been relayed in it. The substreams are
+/// dropped, and therefore reset, when `max_duration` has elapsed.
+pub(crate) fn limited_circuit<A, B>(a: A, b: B, max_duration: Option<Duration>, max_bytes: Option<u64>) -> IoFuture<()>
+where
+    A: AsyncRead + AsyncWrite + Send + 'static,
+    B: AsyncRead + AsyncWrite + Send + 'static,
+{
+    let max_bytes = max_bytes.unwrap_or(u64::max_value());
+    let (a_read, a_write) = a.split();
+    let (b_read, b_write) = b.split();
+    let a_to_b = tokio_io::io::copy(LimitedRead { inner: a_read, remaining: max_bytes }, b_write)
+        .and_then(|(_, _, b_write)| tokio_io::io::shutdown(b_write));
+    let b_to_a = tokio_io::io::copy(LimitedRead { inner: b_read, remaining: max_bytes }, a_write)
+        .and_then(|(_, _, a_write)| tokio_io::io::shutdown(a_write));
+    let circuit = a_to_b.join(b_to_a).map(|_| ());
+
+    match max_duration {
+        None => Box::new(circuit),
+        Some(duration) => {
+            let deadline = Delay::new(Instant::now() + duration);
+            Box::new(circuit.select2(deadline).then(|result| match result {
+                Ok(_) => Ok(()),
+                Err(Either::A((err, _))) => Err(err),
+                Err(Either::B((err, _))) => Err(io::Error::new(io::ErrorKind::Other, err)),
+            }))
+        }
+    }
+}
+
+/// Reader producing an end of file once a number of bytes has been read.
+struct LimitedRead<R> {
+    inner: R,
+    remaining: u64,
+}
+
+impl<R: AsyncRead> io::Read for LimitedRead<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if self.remaining == 0 {
+            return Ok(0)
+        }
+        let max = cmp::min(buf.len() as u64, self.remaining) as usize;
+        let read = self.inner.read(&mut buf[..max])?;
+        self.remaining -= read as u64;
+        Ok(read)
+    }
+}
+
+impl<R: AsyncRead> AsyncRead for LimitedRead<R> {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use libp2p_core::multiaddr::multiaddr;
+
+    #[test]
+    fn encode_decode_roundtrip() {
+        let message = CircuitRelay {
+            kind: MessageType::Hop,
+            src_peer: Some(Peer {
+                id: PeerId::random(),
+                addrs: vec![multiaddr![Ip4([127, 0, 0, 1]), Tcp(1234u16)]],
+            }),
+            dst_peer: Some(Peer { id: PeerId::random(), addrs: Vec::new() }),
+            code: None,
+        };
+        assert_eq!(CircuitRelay::decode(&message.encode()), Ok(message));
+
+        let status = CircuitRelay::status(Status::HopNoConnToDst);
+        assert_eq!(CircuitRelay::decode(&status.encode()), Ok(status));
+    }
+
+    #[test]
+    fn recv_stops_after_message() {
+        let first = CircuitRelay::status(Status::Success);
+        let mut len = unsigned_varint::encode::usize_buffer();
+        let mut bytes = unsigned_varint::encode::usize(first.encode().len(), &mut len).to_vec();
+        bytes.extend_from_slice(&first.encode());
+        bytes.extend_from_slice(b"relayed data");
+
+        let (rest, message) = recv_message(io::Cursor::new(bytes)).wait().unwrap();
+        assert_eq!(message, first);
+        assert_eq!(&rest.get_ref()[rest.position() as usize..], b"relayed data");
+    }
+
+    #[test]
+    fn unknown_status_is_rejected() {
+        let message = proto::CircuitRelay {
+            r#type: Some(3),
+            code: Some(123),
+            ..Default::default()
+        };
+        let mut bytes = Vec::new();
+        message.encode(&mut bytes).unwrap();
+        assert!(CircuitRelay::decode(&bytes).is_err());
+    }
+}
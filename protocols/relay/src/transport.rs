@@ -0,0 +1,397 @@
+// Copyright 2019 Parity Technologies (UK) Ltd.
+//
+// Permission is hereby granted, free of charge, to any person obtaining a
+// copy of this software and associated documentation files (the "Software"),
+// to deal in the Software without restriction, including without limitation
+// the rights to use, copy, modify, merge, publish, distribute, sublicense,
+// and/or sell copies of the Software, and to permit persons to whom the
+// Software is furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS
+// OR IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING
+// FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
+// DEALINGS IN THE SOFTWARE.
+
+use crate::protocol::{RelayRequestError, Status};
+use crate::v2;
+use futures::{future::{self, FutureResult}, prelude::*, sync::{mpsc, oneshot}};
+use libp2p_core::{
+    Multiaddr,
+    PeerId,
+    Transport,
+    multiaddr::Protocol,
+    transport::{ListenerEvent, TransportError}
+};
+use std::{error, fmt, io::{self, Read, Write}};
+use tokio_io::{AsyncRead, AsyncWrite};
+
+/// Transport dialing and listening on relayed addresses.
+///
+/// Only the addresses containing `/p2p-circuit` are supported, and the connections are opened
+/// by the behaviour created alongside this transport: [`Relay`](crate::Relay) for version 1 of
+/// the protocol, or [`RelayClient`](crate::v2::RelayClient) for version 2. The transport is
+/// therefore meant to be combined with a regular transport, for example with
+/// `tcp.or_transport(relay)`, so that the connections to the relays themselves can be opened.
+///
+/// Dialing `/ip4/1.2.3.4/tcp/4001/p2p/QmRelay/p2p-circuit/p2p/QmDst` opens a connection to `QmDst`
+/// relayed by `QmRelay`, which is reached at `/ip4/1.2.3.4/tcp/4001`. The address of the relay
+/// can be omitted if the `Swarm` already knows it, but its peer ID can't: the connection to the
+/// relay is opened by the behaviour, which must know which peer to open it to.
+///
+/// Listening on `/p2p/QmRelay/p2p-circuit` accepts the connections relayed by `QmRelay`, and keeps
+/// a connection to it open, holding a reservation with version 2. Listening on `/p2p-circuit`
+/// accepts the connections relayed by any relay.
+#[derive(Clone)]
+pub struct RelayTransport {
+    to_behaviour: mpsc::UnboundedSender<TransportToBehaviourMsg>,
+}
+
+impl RelayTransport {
+    pub(crate) fn new(to_behaviour: mpsc::UnboundedSender<TransportToBehaviourMsg>) -> Self {
+        RelayTransport { to_behaviour }
+    }
+}
+
+impl fmt::Debug for RelayTransport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("RelayTransport").finish()
+    }
+}
+
+/// Message sent by the transport to the behaviour.
+pub(crate) enum TransportToBehaviourMsg {
+    /// Open a connection to `dst` through `relay`.
+    Dial {
+        relay: PeerId,
+        relay_addr: Option<Multiaddr>,
+        dst: PeerId,
+        dst_addr: Option<Multiaddr>,
+        sender: oneshot::Sender<Result<RelayedConnection, RelayError>>,
+    },
+    /// Accept the connections relayed by `relay`, or by any relay if `None`.
+    Listen {
+        relay: Option<(PeerId, Option<Multiaddr>)>,
+        sender: mpsc::UnboundedSender<IncomingRelayedConnection>,
+    },
+}
+
+/// Connection relayed to us, sent by the behaviour to a listener.
+pub(crate) struct IncomingRelayedConnection {
+    pub(crate) connection: RelayedConnection,
+    pub(crate) relay: PeerId,
+    pub(crate) src: PeerId,
+}
+
+impl Transport for RelayTransport {
+    type Output = RelayedConnection;
+    type Error = RelayError;
+    type Listener = RelayListener;
+    type ListenerUpgrade = FutureResult<Self::Output, Self::Error>;
+    type Dial = RelayDial;
+
+    fn listen_on(self, addr: Multiaddr) -> Result<Self::Listener, TransportError<Self::Error>> {
+        let relay = match RelayedMultiaddr::parse(&addr) {
+            Some(RelayedMultiaddr { relay_peer_id, relay_addr, dst_peer_id: None, dst_addr: None }) =>
+                relay_peer_id.map(|id| (id, relay_addr)),
+            _ => return Err(TransportError::MultiaddrNotSupported(addr)),
+        };
+
+        let (sender, receiver) = mpsc::unbounded();
+        self.to_behaviour.unbounded_send(TransportToBehaviourMsg::Listen { relay, sender })
+            .map_err(|_| TransportError::Other(RelayError::BehaviourGone))?;
+        Ok(RelayListener { addr, receiver, report_addr: true })
+    }
+
+    fn dial(self, addr: Multiaddr) -> Result<Self::Dial, TransportError<Self::Error>> {
+        let (relay, relay_addr, dst, dst_addr) = match RelayedMultiaddr::parse(&addr) {
+            Some(RelayedMultiaddr {
+                relay_peer_id: Some(relay),
+                relay_addr,
+                dst_peer_id: Some(dst),
+                dst_addr,
+            }) => (relay, relay_addr, dst, dst_addr),
+            _ => return Err(TransportError::MultiaddrNotSupported(addr)),
+        };
+
+        let (sender, receiver) = oneshot::channel();
+        let message = TransportToBehaviourMsg::Dial { relay, relay_addr, dst, dst_addr, sender };
+        self.to_behaviour.unbounded_send(message)
+            .map_err(|_| TransportError::Other(RelayError::BehaviourGone))?;
+        Ok(RelayDial { receiver })
+    }
+}
+
+/// Relayed address, split into its parts.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct RelayedMultiaddr {
+    pub(crate) relay_peer_id: Option<PeerId>,
+    pub(crate) relay_addr: Option<Multiaddr>,
+    pub(crate) dst_peer_id: Option<PeerId>,
+    pub(crate) dst_addr: Option<Multiaddr>,
+}
+
+impl RelayedMultiaddr {
+    /// Splits an address of the form `[<relay addr>][/p2p/<relay>]/p2p-circuit[<dst addr>][/p2p/<dst>]`.
+    ///
+    /// Returns `None` if the address doesn't contain exactly one `/p2p-circuit`, or if a
+    /// `/p2p` protocol doesn't contain a valid peer ID.
+    pub(crate) fn parse(addr: &Multiaddr) -> Option<Self> {
+        let mut before = Multiaddr::empty();
+        let mut after = Multiaddr::empty();
+        let mut circuit_found = false;
+        for protocol in addr.iter() {
+            match protocol {
+                Protocol::P2pCircuit if circuit_found => return None,
+                Protocol::P2pCircuit => circuit_found = true,
+                protocol if circuit_found => after.push(protocol),
+                protocol => before.push(protocol),
+            }
+        }
+        if !circuit_found {
+            return None
+        }
+
+        let (relay_peer_id, relay_addr) = split_peer_id(before)?;
+        let (dst_peer_id, dst_addr) = split_peer_id(after)?;
+        Some(RelayedMultiaddr { relay_peer_id, relay_addr, dst_peer_id, dst_addr })
+    }
+}
+
+/// Splits an address ending with an optional `/p2p/<peer>` into the peer ID and the rest of the
+/// address. Returns `None` if the peer ID is invalid.
+fn split_peer_id(mut addr: Multiaddr) -> Option<(Option<PeerId>, Option<Multiaddr>)> {
+    let peer_id = match addr.pop() {
+        Some(Protocol::P2p(hash)) => Some(PeerId::from_multihash(hash).ok()?),
+        Some(protocol) => {
+            addr.push(protocol);
+            None
+        }
+        None => None,
+    };
+    let addr = if addr.iter().next().is_some() { Some(addr) } else { None };
+    Some((peer_id, addr))
+}
+
+/// Listener for relayed connections.
+pub struct RelayListener {
+    /// The address we're listening on.
+    addr: Multiaddr,
+    /// Receives the relayed connections from the behaviour.
+    receiver: mpsc::UnboundedReceiver<IncomingRelayedConnection>,
+    /// Whether the listen address must be reported.
+    report_addr: bool,
+}
+
+impl Stream for RelayListener {
+    type Item = ListenerEvent<FutureResult<RelayedConnection, RelayError>>;
+    type Error = RelayError;
+
+    fn poll(&mut self) -> Poll<Option<Self::Item>, Self::Error> {
+        if self.report_addr {
+            self.report_addr = false;
+            return Ok(Async::Ready(Some(ListenerEvent::NewAddress(self.addr.clone()))))
+        }
+
+        match self.receiver.poll() {
+            Ok(Async::Ready(Some(incoming))) => {
+                let remote_addr = Multiaddr::empty()
+                    .with(Protocol::P2p(incoming.relay.into()))
+                    .with(Protocol::P2pCircuit)
+                    .with(Protocol::P2p(incoming.src.into()));
+                Ok(Async::Ready(Some(ListenerEvent::Upgrade {
+                    upgrade: future::ok(incoming.connection),
+                    listen_addr: self.addr.clone(),
+                    remote_addr,
+                })))
+            }
+            Ok(Async::Ready(None)) | Err(()) => Ok(Async::Ready(None)),
+            Ok(Async::NotReady) => Ok(Async::NotReady),
+        }
+    }
+}
+
+/// Relayed connection currently being opened.
+pub struct RelayDial {
+    receiver: oneshot::Receiver<Result<RelayedConnection, RelayError>>,
+}
+
+impl Future for RelayDial {
+    type Item = RelayedConnection;
+    type Error = RelayError;
+
+    fn poll(&mut self) -> Poll<Self::Item, Self::Error> {
+        match self.receiver.poll() {
+            Ok(Async::Ready(Ok(connection))) => Ok(Async::Ready(connection)),
+            Ok(Async::Ready(Err(error))) => Err(error),
+            Ok(Async::NotReady) => Ok(Async::NotReady),
+            Err(oneshot::Canceled) => Err(RelayError::BehaviourGone),
+        }
+    }
+}
+
+/// Substream on which the data of a relayed connection is exchanged.
+trait RelayedIo: AsyncRead + AsyncWrite + Send {}
+
+impl<T: AsyncRead + AsyncWrite + Send> RelayedIo for T {}
+
+/// Connection relayed by a relay, to be upgraded like any other connection.
+///
+/// The connection to the relay is kept alive as long as this object exists.
+pub struct RelayedConnection {
+    inner: Box<dyn RelayedIo>,
+}
+
+impl RelayedConnection {
+    pub(crate) fn new<T>(stream: T) -> Self
+    where
+        T: AsyncRead + AsyncWrite + Send + 'static,
+    {
+        RelayedConnection { inner: Box::new(stream) }
+    }
+}
+
+impl fmt::Debug for RelayedConnection {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("RelayedConnection").finish()
+    }
+}
+
+impl io::Read for RelayedConnection {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        self.inner.read(buf)
+    }
+}
+
+impl AsyncRead for RelayedConnection {}
+
+impl io::Write for RelayedConnection {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.inner.write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+impl AsyncWrite for RelayedConnection {
+    fn shutdown(&mut self) -> Poll<(), io::Error> {
+        self.inner.shutdown()
+    }
+}
+
+/// Error while opening a relayed connection.
+#[derive(Debug)]
+pub enum RelayError {
+    /// The [`Relay`](crate::Relay) behaviour has been dropped, or the connection to the relay
+    /// closed before the circuit was opened.
+    BehaviourGone,
+    /// Failed to connect to the relay.
+    DialFailure,
+    /// The relay or the destination refused the circuit.
+    Refused(Status),
+    /// The relay or the destination refused the circuit, with a status of version 2 of the
+    /// protocol.
+    RefusedV2(v2::Status),
+    /// The relay doesn't support the relay protocol.
+    UnsupportedProtocol,
+    /// Opening the circuit timed out.
+    Timeout,
+    /// The relay answered with an unexpected message.
+    UnexpectedMessage,
+    /// I/O error while opening the circuit.
+    Io(io::Error),
+}
+
+impl From<RelayRequestError> for RelayError {
+    fn from(error: RelayRequestError) -> Self {
+        match error {
+            RelayRequestError::Refused(status) => RelayError::Refused(status),
+            RelayRequestError::UnexpectedMessage => RelayError::UnexpectedMessage,
+            RelayRequestError::Io(e) => RelayError::Io(e),
+        }
+    }
+}
+
+impl From<v2::RequestError> for RelayError {
+    fn from(error: v2::RequestError) -> Self {
+        match error {
+            v2::RequestError::Refused(status) => RelayError::RefusedV2(status),
+            v2::RequestError::UnexpectedMessage => RelayError::UnexpectedMessage,
+            v2::RequestError::Io(e) => RelayError::Io(e),
+        }
+    }
+}
+
+impl fmt::Display for RelayError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RelayError::BehaviourGone => write!(f, "The relay behaviour is no longer available"),
+            RelayError::DialFailure => write!(f, "Failed to connect to the relay"),
+            RelayError::Refused(status) => write!(f, "Circuit refused: {}", status),
+            RelayError::RefusedV2(status) => write!(f, "Circuit refused: {}", status),
+            RelayError::UnsupportedProtocol => write!(f, "The relay doesn't support the relay protocol"),
+            RelayError::Timeout => write!(f, "Opening the circuit timed out"),
+            RelayError::UnexpectedMessage => write!(f, "Unexpected answer from the relay"),
+            RelayError::Io(e) => write!(f, "I/O error: {}", e),
+        }
+    }
+}
+
+impl error::Error for RelayError {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        match self {
+            RelayError::Io(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use libp2p_core::multiaddr::multiaddr;
+
+    #[test]
+    fn parse_relayed_addresses() {
+        let relay = PeerId::random();
+        let dst = PeerId::random();
+
+        let addr = multiaddr![Ip4([1, 2, 3, 4]), Tcp(4001u16), P2p(relay.clone()), P2pCircuit, P2p(dst.clone())];
+        assert_eq!(RelayedMultiaddr::parse(&addr), Some(RelayedMultiaddr {
+            relay_peer_id: Some(relay.clone()),
+            relay_addr: Some(multiaddr![Ip4([1, 2, 3, 4]), Tcp(4001u16)]),
+            dst_peer_id: Some(dst),
+            dst_addr: None,
+        }));
+
+        let addr = multiaddr![P2p(relay.clone()), P2pCircuit];
+        assert_eq!(RelayedMultiaddr::parse(&addr), Some(RelayedMultiaddr {
+            relay_peer_id: Some(relay),
+            relay_addr: None,
+            dst_peer_id: None,
+            dst_addr: None,
+        }));
+
+        assert!(RelayedMultiaddr::parse(&multiaddr![Ip4([1, 2, 3, 4]), Tcp(4001u16)]).is_none());
+        assert!(RelayedMultiaddr::parse(&multiaddr![P2pCircuit, P2pCircuit]).is_none());
+    }
+
+    #[test]
+    fn dialing_requires_relay_peer_id() {
+        let (sender, _receiver) = mpsc::unbounded();
+        let transport = RelayTransport::new(sender);
+
+        let addr = multiaddr![Ip4([1, 2, 3, 4]), Tcp(4001u16), P2pCircuit, P2p(PeerId::random())];
+        match transport.dial(addr) {
+            Err(TransportError::MultiaddrNotSupported(_)) => {}
+            _ => panic!("Dialed a relayed address without the peer ID of the relay"),
+        }
+    }
+}
@@ -0,0 +1,82 @@
+// Copyright 2019 Parity Technologies (UK) Ltd.
+//
+// Permission is hereby granted, free of charge, to any person obtaining a
+// copy of this software and associated documentation files (the "Software"),
+// to deal in the Software without restriction, including without limitation
+// the rights to use, copy, modify, merge, publish, distribute, sublicense,
+// and/or sell copies of the Software, and to permit persons to whom the
+// Software is furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS
+// OR IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING
+// FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
+// DEALINGS IN THE SOFTWARE.
+
+//! Implementation of version 2 of the [circuit relay] protocol.
+//!
+//! Contrary to version 1, a relay only relays connections to the clients that reserved a slot
+//! on it, and applies limits to the reservations and the relayed connections:
+//!
+//! - The [`RelayServer`] behaviour bounds the number of reservations and of relayed
+//!   connections, lets reservations expire unless renewed, and closes the relayed connections
+//!   once their duration or data limit is reached. These limits are sent to the clients.
+//! - The [`RelayClient`] behaviour and its [`RelayTransport`](crate::RelayTransport) dial
+//!   through relays, and reserve and renew slots on the relays that the transport listens on.
+//!
+//! Running a public relay is therefore possible without it being drained by bulk transfers:
+//! relayed connections are meant for signalling, for example to coordinate a direct connection.
+//!
+//! [circuit relay]: https://github.com/libp2p/specs/blob/master/relay/circuit-v2.md
+
+mod client;
+mod protocol;
+mod server;
+
+/// Protobuf messages of version 2 of the protocol, generated from `message_v2.proto`.
+mod proto {
+    include!(concat!(env!("OUT_DIR"), "/relay.v2.pb.rs"));
+}
+
+pub use client::{
+    InboundCircuitRequest,
+    RelayClient,
+    RelayClientConfig,
+    RelayClientEvent,
+    RelayClientHandler,
+    RelayClientHandlerEvent,
+    RelayClientHandlerIn,
+    RelayClientOutboundInfo
+};
+pub use protocol::{
+    HopListen,
+    HopMessage,
+    HopMessageType,
+    HopRequest,
+    Limit,
+    RequestError,
+    Reservation,
+    Status,
+    StopListen,
+    StopMessage,
+    StopMessageType,
+    StopRequest,
+    HOP_PROTOCOL_NAME,
+    STOP_PROTOCOL_NAME
+};
+pub use server::{
+    CircuitRequest,
+    RelayServer,
+    RelayServerConfig,
+    RelayServerEvent,
+    RelayServerHandler,
+    RelayServerHandlerEvent,
+    RelayServerHandlerIn,
+    ReservationRequest,
+    StopInfo
+};
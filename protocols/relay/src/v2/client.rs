@@ -0,0 +1,433 @@
+// Copyright 2019 Parity Technologies (UK) Ltd.
+//
+// Permission is hereby granted, free of charge, to any person obtaining a
+// copy of this software and associated documentation files (the "Software"),
+// to deal in the Software without restriction, including without limitation
+// the rights to use, copy, modify, merge, publish, distribute, sublicense,
+// and/or sell copies of the Software, and to permit persons to whom the
+// Software is furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS
+// OR IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING
+// FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
+// DEALINGS IN THE SOFTWARE.
+
+//! The client side of version 2 of the circuit relay protocol.
+
+mod handler;
+
+pub use handler::{
+    InboundCircuitRequest,
+    RelayClientHandler,
+    RelayClientHandlerEvent,
+    RelayClientHandlerIn,
+    RelayClientOutboundInfo
+};
+
+use crate::handler::RequestId;
+use crate::transport::{
+    IncomingRelayedConnection,
+    RelayError,
+    RelayTransport,
+    RelayedConnection,
+    TransportToBehaviourMsg
+};
+use crate::v2::protocol::{Limit, Status};
+use futures::{prelude::*, sync::{mpsc, oneshot}};
+use libp2p_core::{ConnectedPoint, Multiaddr, PeerId};
+use libp2p_swarm::{ConnectionId, NetworkBehaviour, NetworkBehaviourAction, PollParameters, node_event_connection};
+use smallvec::SmallVec;
+use std::{collections::{HashMap, VecDeque}, time::Duration};
+use tokio_io::{AsyncRead, AsyncWrite};
+
+/// Configuration of the [`RelayClient`] network behaviour.
+#[derive(Debug, Clone)]
+pub struct RelayClientConfig {
+    request_timeout: Duration,
+    connection_idle_timeout: Duration,
+}
+
+impl Default for RelayClientConfig {
+    fn default() -> Self {
+        RelayClientConfig {
+            request_timeout: Duration::from_secs(10),
+            connection_idle_timeout: Duration::from_secs(10),
+        }
+    }
+}
+
+impl RelayClientConfig {
+    /// Sets how long the relays have to answer a request. Defaults to 10 seconds.
+    pub fn with_request_timeout(mut self, timeout: Duration) -> Self {
+        self.request_timeout = timeout;
+        self
+    }
+
+    /// Sets how long a connection to a relay without reservation nor relayed connection is
+    /// kept alive. Defaults to 10 seconds.
+    pub fn with_connection_idle_timeout(mut self, timeout: Duration) -> Self {
+        self.connection_idle_timeout = timeout;
+        self
+    }
+}
+
+/// Event generated by the `RelayClient` network behaviour.
+#[derive(Debug)]
+pub enum RelayClientEvent {
+    /// A relay reserved a slot for us, or renewed our reservation.
+    ReservationAccepted {
+        relay: PeerId,
+        renewed: bool,
+        /// Relayed addresses at which we can be reached.
+        addrs: Vec<Multiaddr>,
+        /// Limits that the relay applies to the connections relayed to us.
+        limit: Option<Limit>,
+    },
+    /// A relay refused to reserve a slot for us, or we failed to reach it.
+    ReservationFailed { relay: PeerId, error: RelayError },
+    /// A relay relayed a connection from `src` to us.
+    InboundCircuitEstablished { relay: PeerId, src: PeerId, limit: Option<Limit> },
+    /// A relay relays a connection from us to `dst`.
+    OutboundCircuitEstablished { relay: PeerId, dst: PeerId, limit: Option<Limit> },
+}
+
+/// Network behaviour of a client of relays implementing version 2 of the circuit relay
+/// protocol.
+///
+/// Must be created together with its [`RelayTransport`] by [`RelayClient::new_transport_and_behaviour`].
+/// Listening on `/p2p/QmRelay/p2p-circuit` with the transport reserves a slot on `QmRelay` and
+/// keeps the reservation renewed, so that other peers can connect to us through it.
+pub struct RelayClient<TSubstream> {
+    /// Configuration options.
+    config: RelayClientConfig,
+    /// Receives the requests of the transport.
+    from_transport: mpsc::UnboundedReceiver<TransportToBehaviourMsg>,
+    /// Listeners of the transport, along with the relay they accept connections from, if any.
+    listeners: Vec<(Option<PeerId>, mpsc::UnboundedSender<IncomingRelayedConnection>)>,
+    /// Relays on which we want to hold a reservation.
+    reserving_relays: SmallVec<[PeerId; 4]>,
+    /// The established connections to each peer, from the oldest to the most recent.
+    connected: HashMap<PeerId, SmallVec<[ConnectionId; 2]>>,
+    /// Addresses of the relays, learned from the addresses dialed or listened on.
+    addresses: HashMap<PeerId, SmallVec<[Multiaddr; 4]>>,
+    /// Relayed connections to open through relays we are not connected to yet.
+    pending_dials: HashMap<PeerId, SmallVec<[(RequestId, PeerId); 4]>>,
+    /// Relayed connections whose request has been sent to a relay.
+    outgoing: HashMap<RequestId, OutgoingDial>,
+    /// Identifier of the next relayed connection that we open.
+    next_request_id: u64,
+    /// Queue of actions to return to the swarm.
+    pending_actions: VecDeque<NetworkBehaviourAction<RelayClientHandlerIn<TSubstream>, RelayClientEvent>>,
+}
+
+/// A relayed connection that we asked a relay to open.
+struct OutgoingDial {
+    relay: PeerId,
+    dst: PeerId,
+    /// The connection to the relay on which the request is sent, once connected.
+    connection: Option<ConnectionId>,
+    sender: oneshot::Sender<Result<RelayedConnection, RelayError>>,
+}
+
+impl<TSubstream> RelayClient<TSubstream> {
+    /// Creates a [`RelayTransport`] and the `RelayClient` behaviour that opens its connections.
+    pub fn new_transport_and_behaviour(config: RelayClientConfig) -> (RelayTransport, Self) {
+        let (to_behaviour, from_transport) = mpsc::unbounded();
+        let client = RelayClient {
+            config,
+            from_transport,
+            listeners: Vec::new(),
+            reserving_relays: SmallVec::new(),
+            connected: HashMap::new(),
+            addresses: HashMap::new(),
+            pending_dials: HashMap::new(),
+            outgoing: HashMap::new(),
+            next_request_id: 0,
+            pending_actions: VecDeque::new(),
+        };
+        (RelayTransport::new(to_behaviour), client)
+    }
+
+    /// Reserves a slot on `relay`, dialing it at `address` if we aren't connected to it, and
+    /// keeps the reservation renewed.
+    ///
+    /// This is done automatically when the transport listens on a relayed address. The
+    /// connections relayed to us are only accepted if the transport listens either on
+    /// `/p2p-circuit`, which accepts the connections of all the relays, or on a relayed address
+    /// of `relay`.
+    pub fn reserve(&mut self, relay: PeerId, address: Option<Multiaddr>) {
+        if let Some(addr) = address {
+            self.add_address(&relay, addr);
+        }
+        if self.reserving_relays.contains(&relay) {
+            return
+        }
+        self.reserving_relays.push(relay.clone());
+        let action = match self.connected.get(&relay).and_then(|conns| conns.last()) {
+            Some(connection) => NetworkBehaviourAction::SendEventToConnection {
+                peer_id: relay,
+                connection: *connection,
+                event: RelayClientHandlerIn::Reserve,
+            },
+            None => NetworkBehaviourAction::DialPeer { peer_id: relay },
+        };
+        self.pending_actions.push_back(action);
+    }
+
+    /// Stops renewing the reservation on `relay`, which then expires.
+    pub fn cancel_reservation(&mut self, relay: &PeerId) {
+        if !self.reserving_relays.contains(relay) {
+            return
+        }
+        self.reserving_relays.retain(|r| r != relay);
+        for connection in self.connected.get(relay).into_iter().flatten() {
+            self.pending_actions.push_back(NetworkBehaviourAction::SendEventToConnection {
+                peer_id: relay.clone(),
+                connection: *connection,
+                event: RelayClientHandlerIn::CancelReservation,
+            });
+        }
+    }
+
+    /// Returns the relays on which we hold or request a reservation.
+    pub fn reserving_relays(&self) -> impl Iterator<Item = &PeerId> {
+        self.reserving_relays.iter()
+    }
+
+    fn add_address(&mut self, peer: &PeerId, address: Multiaddr) {
+        let addresses = self.addresses.entry(peer.clone()).or_default();
+        if !addresses.contains(&address) {
+            addresses.push(address);
+        }
+    }
+
+    /// Sends the request to open a relayed connection to a connection to the relay.
+    fn send_connect(&mut self, relay: PeerId, connection: ConnectionId, request_id: RequestId, dst: PeerId) {
+        if let Some(dial) = self.outgoing.get_mut(&request_id) {
+            dial.connection = Some(connection);
+        }
+        self.pending_actions.push_back(NetworkBehaviourAction::SendEventToConnection {
+            peer_id: relay,
+            connection,
+            event: RelayClientHandlerIn::Connect { request_id, dst },
+        });
+    }
+
+    /// Processes a message sent by the transport.
+    fn on_transport_message(&mut self, message: TransportToBehaviourMsg) {
+        match message {
+            TransportToBehaviourMsg::Dial { relay, relay_addr, dst, dst_addr: _, sender } => {
+                if let Some(addr) = relay_addr {
+                    self.add_address(&relay, addr);
+                }
+                let request_id = RequestId(self.next_request_id);
+                self.next_request_id += 1;
+                let dial = OutgoingDial { relay: relay.clone(), dst: dst.clone(), connection: None, sender };
+                self.outgoing.insert(request_id, dial);
+
+                let connection = self.connected.get(&relay).and_then(|conns| conns.last()).cloned();
+                if let Some(connection) = connection {
+                    self.send_connect(relay, connection, request_id, dst);
+                } else {
+                    let queue = self.pending_dials.entry(relay.clone()).or_default();
+                    if queue.is_empty() {
+                        self.pending_actions.push_back(NetworkBehaviourAction::DialPeer { peer_id: relay });
+                    }
+                    queue.push((request_id, dst));
+                }
+            }
+            TransportToBehaviourMsg::Listen { relay, sender } => {
+                let relay = relay.map(|(relay, relay_addr)| {
+                    self.reserve(relay.clone(), relay_addr);
+                    relay
+                });
+                self.listeners.push((relay, sender));
+            }
+        }
+    }
+}
+
+impl<TSubstream> RelayClient<TSubstream>
+where
+    TSubstream: AsyncRead + AsyncWrite + Send + 'static,
+{
+    /// Processes an event generated by the handler of a connection.
+    fn on_handler_event(&mut self, peer: PeerId, connection: ConnectionId, event: RelayClientHandlerEvent<TSubstream>) {
+        let event = match event {
+            RelayClientHandlerEvent::ReservationAccepted { reservation, limit, renewed } => {
+                RelayClientEvent::ReservationAccepted { relay: peer, renewed, addrs: reservation.addrs, limit }
+            }
+            RelayClientHandlerEvent::ReservationFailed { error } => {
+                self.reserving_relays.retain(|r| r != &peer);
+                RelayClientEvent::ReservationFailed { relay: peer, error }
+            }
+            RelayClientHandlerEvent::CircuitRequested(request) => {
+                let accepted = self.listeners.iter()
+                    .any(|(relay, _)| relay.as_ref().map_or(true, |r| r == &peer));
+                let event = if accepted {
+                    RelayClientHandlerIn::AcceptCircuit(request)
+                } else {
+                    RelayClientHandlerIn::DenyCircuit { request, status: Status::PermissionDenied }
+                };
+                self.pending_actions.push_back(NetworkBehaviourAction::SendEventToConnection {
+                    peer_id: peer,
+                    connection,
+                    event,
+                });
+                return
+            }
+            RelayClientHandlerEvent::CircuitAccepted { src, limit, stream } => {
+                let mut incoming = IncomingRelayedConnection {
+                    connection: RelayedConnection::new(stream),
+                    relay: peer.clone(),
+                    src: src.clone(),
+                };
+                let mut n = 0;
+                loop {
+                    if n == self.listeners.len() {
+                        log::debug!("Dropping connection from {:?} relayed by {:?}: no listener", src, peer);
+                        return
+                    }
+                    let (relay, sender) = &self.listeners[n];
+                    if relay.as_ref().map_or(false, |r| r != &peer) {
+                        n += 1;
+                        continue
+                    }
+                    match sender.unbounded_send(incoming) {
+                        Ok(()) => break,
+                        Err(err) => {
+                            // The listener has been closed.
+                            incoming = err.into_inner();
+                            self.listeners.remove(n);
+                        }
+                    }
+                }
+                RelayClientEvent::InboundCircuitEstablished { relay: peer, src, limit }
+            }
+            RelayClientHandlerEvent::ConnectSuccess { request_id, limit, stream } => {
+                let dial = match self.outgoing.remove(&request_id) {
+                    Some(dial) => dial,
+                    None => return,
+                };
+                let _ = dial.sender.send(Ok(RelayedConnection::new(stream)));
+                RelayClientEvent::OutboundCircuitEstablished { relay: dial.relay, dst: dial.dst, limit }
+            }
+            RelayClientHandlerEvent::ConnectFailure { request_id, error } => {
+                if let Some(dial) = self.outgoing.remove(&request_id) {
+                    let _ = dial.sender.send(Err(error));
+                }
+                return
+            }
+        };
+
+        self.pending_actions.push_back(NetworkBehaviourAction::GenerateEvent(event));
+    }
+}
+
+impl<TSubstream> NetworkBehaviour for RelayClient<TSubstream>
+where
+    TSubstream: AsyncRead + AsyncWrite + Send + 'static,
+{
+    type ProtocolsHandler = RelayClientHandler<TSubstream>;
+    type OutEvent = RelayClientEvent;
+
+    fn new_handler(&mut self) -> Self::ProtocolsHandler {
+        RelayClientHandler::new(self.config.request_timeout, self.config.connection_idle_timeout)
+    }
+
+    fn addresses_of_peer(&mut self, peer_id: &PeerId) -> Vec<Multiaddr> {
+        self.addresses.get(peer_id).map(|a| a.to_vec()).unwrap_or_default()
+    }
+
+    fn inject_connected(&mut self, _: PeerId, _: ConnectedPoint) {}
+
+    fn inject_disconnected(&mut self, peer_id: &PeerId, _: ConnectedPoint) {
+        self.connected.remove(peer_id);
+    }
+
+    fn inject_connection_established(&mut self, peer_id: &PeerId, connection: &ConnectionId, _: &ConnectedPoint) {
+        let connections = self.connected.entry(peer_id.clone()).or_default();
+        connections.push(*connection);
+
+        // The reservation is held on the first connection to the relay.
+        if connections.len() == 1 && self.reserving_relays.contains(peer_id) {
+            self.pending_actions.push_back(NetworkBehaviourAction::SendEventToConnection {
+                peer_id: peer_id.clone(),
+                connection: *connection,
+                event: RelayClientHandlerIn::Reserve,
+            });
+        }
+
+        if let Some(dials) = self.pending_dials.remove(peer_id) {
+            for (request_id, dst) in dials {
+                self.send_connect(peer_id.clone(), *connection, request_id, dst);
+            }
+        }
+    }
+
+    fn inject_connection_closed(&mut self, peer_id: &PeerId, connection: &ConnectionId, _: &ConnectedPoint) {
+        if let Some(connections) = self.connected.get_mut(peer_id) {
+            connections.retain(|c| c != connection);
+            if connections.is_empty() {
+                self.connected.remove(peer_id);
+            }
+        }
+
+        let interrupted = self.outgoing.iter()
+            .filter(|(_, d)| d.connection.as_ref() == Some(connection))
+            .map(|(id, _)| *id)
+            .collect::<Vec<_>>();
+        for request_id in interrupted {
+            if let Some(dial) = self.outgoing.remove(&request_id) {
+                let _ = dial.sender.send(Err(RelayError::BehaviourGone));
+            }
+        }
+    }
+
+    fn inject_node_event(&mut self, peer_id: PeerId, event: RelayClientHandlerEvent<TSubstream>) {
+        match node_event_connection(&self.connected, &peer_id) {
+            Some(connection) => self.inject_connection_event(peer_id, connection, event),
+            None => log::debug!("Dropping relay event from disconnected peer {:?}", peer_id),
+        }
+    }
+
+    fn inject_connection_event(&mut self, peer_id: PeerId, connection: ConnectionId, event: RelayClientHandlerEvent<TSubstream>) {
+        self.on_handler_event(peer_id, connection, event)
+    }
+
+    fn inject_dial_failure(&mut self, peer_id: &PeerId) {
+        if let Some(dials) = self.pending_dials.remove(peer_id) {
+            for (request_id, _) in dials {
+                if let Some(dial) = self.outgoing.remove(&request_id) {
+                    let _ = dial.sender.send(Err(RelayError::DialFailure));
+                }
+            }
+        }
+        if self.reserving_relays.contains(peer_id) {
+            self.reserving_relays.retain(|r| r != peer_id);
+            self.pending_actions.push_back(NetworkBehaviourAction::GenerateEvent(
+                RelayClientEvent::ReservationFailed { relay: peer_id.clone(), error: RelayError::DialFailure }
+            ));
+        }
+    }
+
+    fn poll(&mut self, _: &mut impl PollParameters)
+        -> Async<NetworkBehaviourAction<RelayClientHandlerIn<TSubstream>, Self::OutEvent>>
+    {
+        while let Ok(Async::Ready(Some(message))) = self.from_transport.poll() {
+            self.on_transport_message(message);
+        }
+
+        if let Some(action) = self.pending_actions.pop_front() {
+            return Async::Ready(action)
+        }
+
+        Async::NotReady
+    }
+}
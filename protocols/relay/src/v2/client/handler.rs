@@ -0,0 +1,419 @@
+// Copyright 2019 Parity Technologies (UK) Ltd.
+//
+// Permission is hereby granted, free of charge, to any person obtaining a
+// copy of this software and associated documentation files (the "Software"),
+// to deal in the Software without restriction, including without limitation
+// the rights to use, copy, modify, merge, publish, distribute, sublicense,
+// and/or sell copies of the Software, and to permit persons to whom the
+// Software is furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS
+// OR IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING
+// FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
+// DEALINGS IN THE SOFTWARE.
+
+use crate::handler::{ConnectionGuard, RelayedStream, RequestId, poll_guards};
+use crate::protocol::{IoFuture, Peer};
+use crate::transport::RelayError;
+use crate::v2::protocol::{
+    HopMessage,
+    HopMessageType,
+    HopRequest,
+    Limit,
+    RequestError,
+    Reservation,
+    Status,
+    StopListen,
+    StopMessage,
+    StopMessageType,
+    send_stop
+};
+use futures::{prelude::*, sync::oneshot};
+use libp2p_core::{PeerId, upgrade::{Negotiated, UpgradeError}};
+use libp2p_swarm::{
+    KeepAlive,
+    SubstreamProtocol,
+    ProtocolsHandler,
+    ProtocolsHandlerEvent,
+    ProtocolsHandlerUpgrErr
+};
+use std::{cmp, collections::VecDeque, time::{Duration, SystemTime, UNIX_EPOCH}};
+use tokio_io::{AsyncRead, AsyncWrite};
+use void::Void;
+use wasm_timer::{Delay, Instant};
+
+/// Request of a relay to accept a connection relayed from a source.
+pub struct InboundCircuitRequest<TSubstream> {
+    pub(crate) substream: Negotiated<TSubstream>,
+    pub(crate) src: Peer,
+    pub(crate) limit: Option<Limit>,
+    pub(crate) guard: ConnectionGuard,
+}
+
+impl<TSubstream> InboundCircuitRequest<TSubstream> {
+    /// Returns the source of the relayed connection.
+    pub fn src(&self) -> &Peer {
+        &self.src
+    }
+
+    /// Returns the limits that the relay applies to the connection, if any.
+    pub fn limit(&self) -> Option<&Limit> {
+        self.limit.as_ref()
+    }
+}
+
+/// Event sent by the `RelayClient` behaviour to the `RelayClientHandler`.
+pub enum RelayClientHandlerIn<TSubstream> {
+    /// Reserves a slot on the remote, and renews the reservation before it expires.
+    Reserve,
+    /// Stops renewing the reservation on the remote, which then expires.
+    CancelReservation,
+    /// Asks the remote to relay a connection from us to `dst`.
+    Connect {
+        request_id: RequestId,
+        dst: PeerId,
+    },
+    /// Accepts a connection relayed by the remote.
+    AcceptCircuit(InboundCircuitRequest<TSubstream>),
+    /// Refuses a connection relayed by the remote.
+    DenyCircuit {
+        request: InboundCircuitRequest<TSubstream>,
+        status: Status,
+    },
+}
+
+/// Event produced by the `RelayClientHandler`.
+pub enum RelayClientHandlerEvent<TSubstream> {
+    /// The remote reserved a slot for us, or renewed our reservation.
+    ReservationAccepted {
+        reservation: Reservation,
+        limit: Option<Limit>,
+        renewed: bool,
+    },
+    /// The remote refused to reserve a slot for us, or the request failed.
+    ReservationFailed { error: RelayError },
+    /// The remote asks us to accept a connection it relays.
+    CircuitRequested(InboundCircuitRequest<TSubstream>),
+    /// A connection relayed by the remote has been accepted.
+    CircuitAccepted {
+        src: PeerId,
+        limit: Option<Limit>,
+        stream: RelayedStream<TSubstream>,
+    },
+    /// The remote relays a connection from us.
+    ConnectSuccess {
+        request_id: RequestId,
+        limit: Option<Limit>,
+        stream: RelayedStream<TSubstream>,
+    },
+    /// The remote refused to relay a connection from us, or the request failed.
+    ConnectFailure {
+        request_id: RequestId,
+        error: RelayError,
+    },
+}
+
+/// Information attached to the hop substreams being opened.
+#[derive(Debug, Copy, Clone)]
+pub enum RelayClientOutboundInfo {
+    Reserve,
+    Connect(RequestId),
+}
+
+/// Protocol handler of a client of a relay, sending our hop requests to the remote and
+/// answering the stop requests it sends.
+pub struct RelayClientHandler<TSubstream> {
+    /// Timeout of the substream upgrades.
+    request_timeout: Duration,
+    /// How long the connection is kept alive when idle.
+    idle_timeout: Duration,
+    /// Value to return from `connection_keep_alive`.
+    keep_alive: KeepAlive,
+    /// Whether we want to hold a reservation on the remote.
+    reserving: bool,
+    /// Whether we hold a reservation on the remote.
+    reserved: bool,
+    /// Fires when the reservation must be renewed.
+    renewal: Option<Delay>,
+    /// Hop requests for which an outbound substream must be opened.
+    outbound: VecDeque<(HopRequest, RelayClientOutboundInfo)>,
+    /// Number of outbound substreams being opened or negotiated.
+    num_pending_outbound: usize,
+    /// Answers being sent on inbound substreams.
+    answers: Vec<IoFuture<()>>,
+    /// Inbound circuits being accepted.
+    accepting: Vec<(PeerId, Option<Limit>, ConnectionGuard, IoFuture<Negotiated<TSubstream>>)>,
+    /// Receivers notified when the guards created by this handler are dropped.
+    guards: Vec<oneshot::Receiver<()>>,
+    /// Events to produce in `poll()`.
+    pending_events: VecDeque<RelayClientHandlerEvent<TSubstream>>,
+}
+
+impl<TSubstream> RelayClientHandler<TSubstream> {
+    pub(crate) fn new(request_timeout: Duration, idle_timeout: Duration) -> Self {
+        RelayClientHandler {
+            request_timeout,
+            idle_timeout,
+            keep_alive: KeepAlive::Until(Instant::now() + idle_timeout),
+            reserving: false,
+            reserved: false,
+            renewal: None,
+            outbound: VecDeque::new(),
+            num_pending_outbound: 0,
+            answers: Vec::new(),
+            accepting: Vec::new(),
+            guards: Vec::new(),
+            pending_events: VecDeque::new(),
+        }
+    }
+
+    /// Creates a guard keeping this connection alive.
+    fn new_guard(&mut self) -> ConnectionGuard {
+        let (guard, receiver) = ConnectionGuard::new();
+        self.guards.push(receiver);
+        guard
+    }
+
+    /// Queues a reservation request.
+    fn reserve(&mut self) {
+        let message = HopMessage {
+            kind: HopMessageType::Reserve,
+            peer: None,
+            reservation: None,
+            limit: None,
+            status: None,
+        };
+        self.outbound.push_back((HopRequest { message }, RelayClientOutboundInfo::Reserve));
+    }
+
+    /// Updates the keep-alive after the state of the handler has changed.
+    fn update_keep_alive(&mut self) {
+        let busy = self.reserving
+            || !self.outbound.is_empty()
+            || self.num_pending_outbound > 0
+            || !self.answers.is_empty()
+            || !self.accepting.is_empty()
+            || !self.guards.is_empty();
+        if busy {
+            self.keep_alive = KeepAlive::Yes;
+        } else if let KeepAlive::Yes = self.keep_alive {
+            self.keep_alive = KeepAlive::Until(Instant::now() + self.idle_timeout);
+        }
+    }
+}
+
+/// Returns how long to wait before renewing a reservation expiring at `expire`, in seconds since
+/// the Unix epoch.
+fn renewal_delay(expire: u64) -> Duration {
+    let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default().as_secs();
+    let remaining = expire.saturating_sub(now);
+    Duration::from_secs(cmp::max(remaining * 3 / 4, 1))
+}
+
+impl<TSubstream> RelayClientHandler<TSubstream>
+where
+    TSubstream: AsyncRead + AsyncWrite + Send + 'static,
+{
+    /// Sends a message on an inbound substream, then closes it.
+    fn answer(&mut self, substream: Negotiated<TSubstream>, message: StopMessage) {
+        self.answers.push(Box::new(send_stop(substream, &message).map(|_| ())));
+    }
+}
+
+impl<TSubstream> ProtocolsHandler for RelayClientHandler<TSubstream>
+where
+    TSubstream: AsyncRead + AsyncWrite + Send + 'static,
+{
+    type InEvent = RelayClientHandlerIn<TSubstream>;
+    type OutEvent = RelayClientHandlerEvent<TSubstream>;
+    type Error = Void;
+    type Substream = TSubstream;
+    type InboundProtocol = StopListen;
+    type OutboundProtocol = HopRequest;
+    type OutboundOpenInfo = RelayClientOutboundInfo;
+
+    fn listen_protocol(&self) -> SubstreamProtocol<Self::InboundProtocol> {
+        SubstreamProtocol::new(StopListen).with_timeout(self.request_timeout)
+    }
+
+    fn inject_fully_negotiated_inbound(&mut self, (message, substream): (StopMessage, Negotiated<TSubstream>)) {
+        match (message.kind, message.peer) {
+            (StopMessageType::Connect, Some(src)) => {
+                let request = InboundCircuitRequest {
+                    substream,
+                    src,
+                    limit: message.limit,
+                    guard: self.new_guard(),
+                };
+                self.pending_events.push_back(RelayClientHandlerEvent::CircuitRequested(request));
+            }
+            (StopMessageType::Connect, None) => self.answer(substream, StopMessage::status(Status::MalformedMessage)),
+            (StopMessageType::Status, _) => self.answer(substream, StopMessage::status(Status::UnexpectedMessage)),
+        }
+        self.update_keep_alive();
+    }
+
+    fn inject_fully_negotiated_outbound(
+        &mut self,
+        (answer, substream): (HopMessage, Negotiated<TSubstream>),
+        info: RelayClientOutboundInfo,
+    ) {
+        self.num_pending_outbound -= 1;
+        match info {
+            RelayClientOutboundInfo::Reserve => match answer.reservation {
+                Some(reservation) if self.reserving => {
+                    self.renewal = Some(Delay::new(Instant::now() + renewal_delay(reservation.expire)));
+                    let renewed = self.reserved;
+                    self.reserved = true;
+                    self.pending_events.push_back(RelayClientHandlerEvent::ReservationAccepted {
+                        reservation,
+                        limit: answer.limit,
+                        renewed,
+                    });
+                }
+                Some(_) => {}
+                None => {
+                    self.reserving = false;
+                    self.reserved = false;
+                    let error = RelayError::UnexpectedMessage;
+                    self.pending_events.push_back(RelayClientHandlerEvent::ReservationFailed { error });
+                }
+            },
+            RelayClientOutboundInfo::Connect(request_id) => {
+                let stream = RelayedStream::new(substream, self.new_guard());
+                self.pending_events.push_back(RelayClientHandlerEvent::ConnectSuccess {
+                    request_id,
+                    limit: answer.limit,
+                    stream,
+                });
+            }
+        }
+        self.update_keep_alive();
+    }
+
+    fn inject_event(&mut self, event: Self::InEvent) {
+        match event {
+            RelayClientHandlerIn::Reserve => {
+                if !self.reserving {
+                    self.reserving = true;
+                    self.reserve();
+                }
+            }
+            RelayClientHandlerIn::CancelReservation => {
+                self.reserving = false;
+                self.reserved = false;
+                self.renewal = None;
+            }
+            RelayClientHandlerIn::Connect { request_id, dst } => {
+                let message = HopMessage {
+                    kind: HopMessageType::Connect,
+                    peer: Some(Peer { id: dst, addrs: Vec::new() }),
+                    reservation: None,
+                    limit: None,
+                    status: None,
+                };
+                self.outbound.push_back((HopRequest { message }, RelayClientOutboundInfo::Connect(request_id)));
+            }
+            RelayClientHandlerIn::AcceptCircuit(request) => {
+                let future = send_stop(request.substream, &StopMessage::status(Status::Ok));
+                self.accepting.push((request.src.id, request.limit, request.guard, future));
+            }
+            RelayClientHandlerIn::DenyCircuit { request, status } => {
+                self.answer(request.substream, StopMessage::status(status));
+            }
+        }
+        self.update_keep_alive();
+    }
+
+    fn inject_dial_upgrade_error(
+        &mut self,
+        info: RelayClientOutboundInfo,
+        error: ProtocolsHandlerUpgrErr<RequestError>
+    ) {
+        self.num_pending_outbound -= 1;
+        let error = match error {
+            ProtocolsHandlerUpgrErr::Timeout | ProtocolsHandlerUpgrErr::Timer => RelayError::Timeout,
+            ProtocolsHandlerUpgrErr::Upgrade(UpgradeError::Select(_)) => RelayError::UnsupportedProtocol,
+            ProtocolsHandlerUpgrErr::Upgrade(UpgradeError::Apply(e)) => e.into(),
+        };
+        match info {
+            RelayClientOutboundInfo::Reserve => {
+                self.reserving = false;
+                self.reserved = false;
+                self.renewal = None;
+                self.pending_events.push_back(RelayClientHandlerEvent::ReservationFailed { error });
+            }
+            RelayClientOutboundInfo::Connect(request_id) => {
+                self.pending_events.push_back(RelayClientHandlerEvent::ConnectFailure { request_id, error });
+            }
+        }
+        self.update_keep_alive();
+    }
+
+    fn connection_keep_alive(&self) -> KeepAlive {
+        self.keep_alive
+    }
+
+    fn poll(&mut self) -> Poll<ProtocolsHandlerEvent<HopRequest, RelayClientOutboundInfo, Self::OutEvent>, Void> {
+        poll_guards(&mut self.guards);
+
+        let renew = match self.renewal.as_mut().map(|renewal| renewal.poll()) {
+            Some(Ok(Async::NotReady)) | None => false,
+            Some(Ok(Async::Ready(()))) | Some(Err(_)) => true,
+        };
+        if renew {
+            self.renewal = None;
+            self.reserve();
+        }
+
+        let mut n = 0;
+        while n < self.answers.len() {
+            match self.answers[n].poll() {
+                Ok(Async::NotReady) => n += 1,
+                Ok(Async::Ready(())) => {
+                    self.answers.swap_remove(n);
+                }
+                Err(err) => {
+                    log::debug!("Failed to answer a stop request: {}", err);
+                    self.answers.swap_remove(n);
+                }
+            }
+        }
+
+        let mut n = 0;
+        while n < self.accepting.len() {
+            match self.accepting[n].3.poll() {
+                Ok(Async::NotReady) => n += 1,
+                Ok(Async::Ready(substream)) => {
+                    let (src, limit, guard, _) = self.accepting.swap_remove(n);
+                    let stream = RelayedStream::new(substream, guard);
+                    self.pending_events.push_back(RelayClientHandlerEvent::CircuitAccepted { src, limit, stream });
+                }
+                Err(err) => {
+                    log::debug!("Failed to accept a relayed connection: {}", err);
+                    self.accepting.swap_remove(n);
+                }
+            }
+        }
+
+        self.update_keep_alive();
+
+        if let Some(event) = self.pending_events.pop_front() {
+            return Ok(Async::Ready(ProtocolsHandlerEvent::Custom(event)))
+        }
+
+        if let Some((request, info)) = self.outbound.pop_front() {
+            self.num_pending_outbound += 1;
+            let protocol = SubstreamProtocol::new(request).with_timeout(self.request_timeout);
+            return Ok(Async::Ready(ProtocolsHandlerEvent::OutboundSubstreamRequest { protocol, info }))
+        }
+
+        Ok(Async::NotReady)
+    }
+}
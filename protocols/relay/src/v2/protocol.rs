@@ -0,0 +1,546 @@
+// Copyright 2019 Parity Technologies (UK) Ltd.
+//
+// Permission is hereby granted, free of charge, to any person obtaining a
+// copy of this software and associated documentation files (the "Software"),
+// to deal in the Software without restriction, including without limitation
+// the rights to use, copy, modify, merge, publish, distribute, sublicense,
+// and/or sell copies of the Software, and to permit persons to whom the
+// Software is furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS
+// OR IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING
+// FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
+// DEALINGS IN THE SOFTWARE.
+
+//! Messages of version 2 of the circuit relay protocol and upgrades applied on its substreams.
+//!
+//! Clients send `HopMessage`s to relays on the hop protocol to reserve a slot or to connect to
+//! another client, and relays send `StopMessage`s to the destination of a circuit on the stop
+//! protocol. As in version 1, a successful `Connect` exchange turns the substream into one end
+//! of a relayed connection.
+
+use crate::protocol::{IoFuture, Peer, recv_frame, send_frame};
+use crate::v2::proto;
+use futures::prelude::*;
+use libp2p_core::{
+    InboundUpgrade,
+    Multiaddr,
+    OutboundUpgrade,
+    PeerId,
+    UpgradeInfo,
+    upgrade::Negotiated
+};
+use prost::{DecodeError, Message};
+use std::{convert::TryFrom, error, fmt, io, iter, time::Duration};
+use tokio_io::{AsyncRead, AsyncWrite};
+
+/// Name of the protocol spoken with the relays.
+pub const HOP_PROTOCOL_NAME: &[u8] = b"/libp2p/circuit/relay/0.2.0/hop";
+
+/// Name of the protocol spoken by the relays with the destinations of the circuits.
+pub const STOP_PROTOCOL_NAME: &[u8] = b"/libp2p/circuit/relay/0.2.0/stop";
+
+/// Status code sent in answer to a request.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Status {
+    Ok,
+    ReservationRefused,
+    ResourceLimitExceeded,
+    PermissionDenied,
+    ConnectionFailed,
+    NoReservation,
+    MalformedMessage,
+    UnexpectedMessage,
+}
+
+impl Status {
+    /// Returns the code of the status on the wire.
+    pub fn code(self) -> u32 {
+        match self {
+            Status::Ok => 100,
+            Status::ReservationRefused => 200,
+            Status::ResourceLimitExceeded => 201,
+            Status::PermissionDenied => 202,
+            Status::ConnectionFailed => 203,
+            Status::NoReservation => 204,
+            Status::MalformedMessage => 400,
+            Status::UnexpectedMessage => 401,
+        }
+    }
+
+    /// Returns the status corresponding to a code on the wire, if any.
+    pub fn from_code(code: u64) -> Option<Self> {
+        let status = match code {
+            100 => Status::Ok,
+            200 => Status::ReservationRefused,
+            201 => Status::ResourceLimitExceeded,
+            202 => Status::PermissionDenied,
+            203 => Status::ConnectionFailed,
+            204 => Status::NoReservation,
+            400 => Status::MalformedMessage,
+            401 => Status::UnexpectedMessage,
+            _ => return None,
+        };
+        Some(status)
+    }
+}
+
+impl fmt::Display for Status {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:?} ({})", self, self.code())
+    }
+}
+
+/// Limits that a relay applies to a relayed connection.
+#[derive(Debug, Copy, Clone, Default, PartialEq, Eq)]
+pub struct Limit {
+    /// Maximum duration of the connection, with a precision of one second.
+    pub duration: Option<Duration>,
+    /// Maximum number of bytes relayed in each direction.
+    pub data: Option<u64>,
+}
+
+impl Limit {
+    fn to_proto(&self) -> proto::Limit {
+        proto::Limit {
+            duration: self.duration.map(|duration| saturating_u32(duration.as_secs())),
+            data: self.data,
+        }
+    }
+
+    fn from_proto(limit: proto::Limit) -> Self {
+        Limit {
+            duration: limit.duration.map(|secs| Duration::from_secs(u64::from(secs))),
+            data: limit.data,
+        }
+    }
+}
+
+/// Saturating conversion to a `u32`.
+fn saturating_u32(value: u64) -> u32 {
+    if value > u64::from(u32::max_value()) { u32::max_value() } else { value as u32 }
+}
+
+/// Slot reserved by a relay for a client.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Reservation {
+    /// Expiration of the reservation, in seconds since the Unix epoch.
+    pub expire: u64,
+    /// Relayed addresses at which the client can be reached.
+    pub addrs: Vec<Multiaddr>,
+    /// Signed voucher of the reservation, if any.
+    pub voucher: Option<Vec<u8>>,
+}
+
+impl Reservation {
+    fn to_proto(&self) -> proto::Reservation {
+        proto::Reservation {
+            expire: self.expire,
+            addrs: self.addrs.iter().map(Multiaddr::to_vec).collect(),
+            voucher: self.voucher.clone(),
+        }
+    }
+
+    fn from_proto(reservation: proto::Reservation) -> Self {
+        Reservation {
+            expire: reservation.expire,
+            addrs: decode_addrs(reservation.addrs),
+            voucher: reservation.voucher,
+        }
+    }
+}
+
+fn peer_to_proto(peer: &Peer) -> proto::Peer {
+    proto::Peer {
+        id: peer.id.as_bytes().to_vec(),
+        addrs: peer.addrs.iter().map(Multiaddr::to_vec).collect(),
+    }
+}
+
+fn peer_from_proto(peer: proto::Peer) -> Result<Peer, DecodeError> {
+    Ok(Peer {
+        id: PeerId::from_bytes(peer.id).map_err(|_| DecodeError::new("invalid peer ID"))?,
+        addrs: decode_addrs(peer.addrs),
+    })
+}
+
+/// Decodes addresses, skipping those that are invalid.
+fn decode_addrs(addrs: Vec<Vec<u8>>) -> Vec<Multiaddr> {
+    addrs.into_iter().filter_map(|addr| Multiaddr::try_from(addr).ok()).collect()
+}
+
+fn decode_status(code: i32) -> Result<Status, DecodeError> {
+    u64::try_from(code).ok()
+        .and_then(Status::from_code)
+        .ok_or_else(|| DecodeError::new("unknown status code"))
+}
+
+fn encode_message(message: &impl Message) -> Vec<u8> {
+    let mut buf = Vec::with_capacity(message.encoded_len());
+    message.encode(&mut buf).expect("Vec<u8> provides capacity as needed");
+    buf
+}
+
+/// Type of a `HopMessage`.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum HopMessageType {
+    /// Asks the relay to reserve a slot for us.
+    Reserve,
+    /// Asks the relay to open a circuit towards a client that reserved a slot.
+    Connect,
+    /// Answer of the relay.
+    Status,
+}
+
+/// A message sent on the hop protocol.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HopMessage {
+    pub kind: HopMessageType,
+    pub peer: Option<Peer>,
+    pub reservation: Option<Reservation>,
+    pub limit: Option<Limit>,
+    pub status: Option<Status>,
+}
+
+impl HopMessage {
+    /// Builds a `Status` message.
+    pub fn status(status: Status) -> Self {
+        HopMessage { kind: HopMessageType::Status, peer: None, reservation: None, limit: None, status: Some(status) }
+    }
+
+    /// Encodes the message, without its length prefix.
+    pub fn encode(&self) -> Vec<u8> {
+        let kind = match self.kind {
+            HopMessageType::Reserve => 0,
+            HopMessageType::Connect => 1,
+            HopMessageType::Status => 2,
+        };
+        encode_message(&proto::HopMessage {
+            r#type: kind,
+            peer: self.peer.as_ref().map(peer_to_proto),
+            reservation: self.reservation.as_ref().map(Reservation::to_proto),
+            limit: self.limit.as_ref().map(Limit::to_proto),
+            status: self.status.map(|status| status.code() as i32),
+        })
+    }
+
+    /// Decodes a message, without its length prefix.
+    pub fn decode(bytes: &[u8]) -> Result<Self, DecodeError> {
+        let message = proto::HopMessage::decode(bytes)?;
+        let kind = match message.r#type {
+            0 => HopMessageType::Reserve,
+            1 => HopMessageType::Connect,
+            2 => HopMessageType::Status,
+            _ => return Err(DecodeError::new("unknown message type")),
+        };
+        Ok(HopMessage {
+            kind,
+            peer: message.peer.map(peer_from_proto).transpose()?,
+            reservation: message.reservation.map(Reservation::from_proto),
+            limit: message.limit.map(Limit::from_proto),
+            status: message.status.map(decode_status).transpose()?,
+        })
+    }
+}
+
+/// Type of a `StopMessage`.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum StopMessageType {
+    /// Asks the destination to accept a circuit.
+    Connect,
+    /// Answer of the destination.
+    Status,
+}
+
+/// A message sent on the stop protocol.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StopMessage {
+    pub kind: StopMessageType,
+    pub peer: Option<Peer>,
+    pub limit: Option<Limit>,
+    pub status: Option<Status>,
+}
+
+impl StopMessage {
+    /// Builds a `Status` message.
+    pub fn status(status: Status) -> Self {
+        StopMessage { kind: StopMessageType::Status, peer: None, limit: None, status: Some(status) }
+    }
+
+    /// Encodes the message, without its length prefix.
+    pub fn encode(&self) -> Vec<u8> {
+        let kind = match self.kind {
+            StopMessageType::Connect => 0,
+            StopMessageType::Status => 1,
+        };
+        encode_message(&proto::StopMessage {
+            r#type: kind,
+            peer: self.peer.as_ref().map(peer_to_proto),
+            limit: self.limit.as_ref().map(Limit::to_proto),
+            status: self.status.map(|status| status.code() as i32),
+        })
+    }
+
+    /// Decodes a message, without its length prefix.
+    pub fn decode(bytes: &[u8]) -> Result<Self, DecodeError> {
+        let message = proto::StopMessage::decode(bytes)?;
+        let kind = match message.r#type {
+            0 => StopMessageType::Connect,
+            1 => StopMessageType::Status,
+            _ => return Err(DecodeError::new("unknown message type")),
+        };
+        Ok(StopMessage {
+            kind,
+            peer: message.peer.map(peer_from_proto).transpose()?,
+            limit: message.limit.map(Limit::from_proto),
+            status: message.status.map(decode_status).transpose()?,
+        })
+    }
+}
+
+/// Writes a length-prefixed `HopMessage` on a substream, without closing it.
+pub(crate) fn send_hop<S>(socket: S, message: &HopMessage) -> IoFuture<S>
+where
+    S: AsyncWrite + Send + 'static,
+{
+    send_frame(socket, message.encode())
+}
+
+/// Reads a length-prefixed `HopMessage` from a substream.
+pub(crate) fn recv_hop<S>(socket: S) -> IoFuture<(S, HopMessage)>
+where
+    S: AsyncRead + Send + 'static,
+{
+    Box::new(recv_frame(socket).and_then(|(socket, bytes)| {
+        let message = HopMessage::decode(&bytes)
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+        Ok((socket, message))
+    }))
+}
+
+/// Writes a length-prefixed `StopMessage` on a substream, without closing it.
+pub(crate) fn send_stop<S>(socket: S, message: &StopMessage) -> IoFuture<S>
+where
+    S: AsyncWrite + Send + 'static,
+{
+    send_frame(socket, message.encode())
+}
+
+/// Reads a length-prefixed `StopMessage` from a substream.
+pub(crate) fn recv_stop<S>(socket: S) -> IoFuture<(S, StopMessage)>
+where
+    S: AsyncRead + Send + 'static,
+{
+    Box::new(recv_frame(socket).and_then(|(socket, bytes)| {
+        let message = StopMessage::decode(&bytes)
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+        Ok((socket, message))
+    }))
+}
+
+/// Upgrade applied by relays on inbound hop substreams. Reads the first message, and produces
+/// it along with the substream.
+#[derive(Debug, Copy, Clone, Default)]
+pub struct HopListen;
+
+impl UpgradeInfo for HopListen {
+    type Info = &'static [u8];
+    type InfoIter = iter::Once<Self::Info>;
+
+    fn protocol_info(&self) -> Self::InfoIter {
+        iter::once(HOP_PROTOCOL_NAME)
+    }
+}
+
+impl<TSocket> InboundUpgrade<TSocket> for HopListen
+where
+    TSocket: AsyncRead + AsyncWrite + Send + 'static,
+{
+    type Output = (HopMessage, Negotiated<TSocket>);
+    type Error = io::Error;
+    type Future = IoFuture<Self::Output>;
+
+    fn upgrade_inbound(self, socket: Negotiated<TSocket>, _: Self::Info) -> Self::Future {
+        Box::new(recv_hop(socket).map(|(socket, message)| (message, socket)))
+    }
+}
+
+/// Upgrade applied by clients on inbound stop substreams. Reads the first message, and produces
+/// it along with the substream.
+#[derive(Debug, Copy, Clone, Default)]
+pub struct StopListen;
+
+impl UpgradeInfo for StopListen {
+    type Info = &'static [u8];
+    type InfoIter = iter::Once<Self::Info>;
+
+    fn protocol_info(&self) -> Self::InfoIter {
+        iter::once(STOP_PROTOCOL_NAME)
+    }
+}
+
+impl<TSocket> InboundUpgrade<TSocket> for StopListen
+where
+    TSocket: AsyncRead + AsyncWrite + Send + 'static,
+{
+    type Output = (StopMessage, Negotiated<TSocket>);
+    type Error = io::Error;
+    type Future = IoFuture<Self::Output>;
+
+    fn upgrade_inbound(self, socket: Negotiated<TSocket>, _: Self::Info) -> Self::Future {
+        Box::new(recv_stop(socket).map(|(socket, message)| (message, socket)))
+    }
+}
+
+/// Upgrade applied by clients on outbound hop substreams. Sends a `Reserve` or `Connect`
+/// message, and produces the answer of the relay along with the substream if it's a success.
+#[derive(Debug, Clone)]
+pub struct HopRequest {
+    pub(crate) message: HopMessage,
+}
+
+impl UpgradeInfo for HopRequest {
+    type Info = &'static [u8];
+    type InfoIter = iter::Once<Self::Info>;
+
+    fn protocol_info(&self) -> Self::InfoIter {
+        iter::once(HOP_PROTOCOL_NAME)
+    }
+}
+
+impl<TSocket> OutboundUpgrade<TSocket> for HopRequest
+where
+    TSocket: AsyncRead + AsyncWrite + Send + 'static,
+{
+    type Output = (HopMessage, Negotiated<TSocket>);
+    type Error = RequestError;
+    type Future = Box<dyn Future<Item = Self::Output, Error = Self::Error> + Send>;
+
+    fn upgrade_outbound(self, socket: Negotiated<TSocket>, _: Self::Info) -> Self::Future {
+        let future = send_hop(socket, &self.message)
+            .and_then(recv_hop)
+            .map_err(RequestError::Io)
+            .and_then(|(socket, answer)| match (answer.kind, answer.status) {
+                (HopMessageType::Status, Some(Status::Ok)) => Ok((answer, socket)),
+                (HopMessageType::Status, Some(status)) => Err(RequestError::Refused(status)),
+                _ => Err(RequestError::UnexpectedMessage),
+            });
+        Box::new(future)
+    }
+}
+
+/// Upgrade applied by relays on outbound stop substreams. Sends a `Connect` message, and
+/// produces the substream if the destination accepts the circuit.
+#[derive(Debug, Clone)]
+pub struct StopRequest {
+    pub(crate) message: StopMessage,
+}
+
+impl UpgradeInfo for StopRequest {
+    type Info = &'static [u8];
+    type InfoIter = iter::Once<Self::Info>;
+
+    fn protocol_info(&self) -> Self::InfoIter {
+        iter::once(STOP_PROTOCOL_NAME)
+    }
+}
+
+impl<TSocket> OutboundUpgrade<TSocket> for StopRequest
+where
+    TSocket: AsyncRead + AsyncWrite + Send + 'static,
+{
+    type Output = Negotiated<TSocket>;
+    type Error = RequestError;
+    type Future = Box<dyn Future<Item = Self::Output, Error = Self::Error> + Send>;
+
+    fn upgrade_outbound(self, socket: Negotiated<TSocket>, _: Self::Info) -> Self::Future {
+        let future = send_stop(socket, &self.message)
+            .and_then(recv_stop)
+            .map_err(RequestError::Io)
+            .and_then(|(socket, answer)| match (answer.kind, answer.status) {
+                (StopMessageType::Status, Some(Status::Ok)) => Ok(socket),
+                (StopMessageType::Status, Some(status)) => Err(RequestError::Refused(status)),
+                _ => Err(RequestError::UnexpectedMessage),
+            });
+        Box::new(future)
+    }
+}
+
+/// Error while sending a request to a remote.
+#[derive(Debug)]
+pub enum RequestError {
+    /// The remote answered with a status other than `Ok`.
+    Refused(Status),
+    /// The remote answered with something else than a status.
+    UnexpectedMessage,
+    /// I/O error on the substream.
+    Io(io::Error),
+}
+
+impl fmt::Display for RequestError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RequestError::Refused(status) => write!(f, "Request refused by the remote: {}", status),
+            RequestError::UnexpectedMessage => write!(f, "Unexpected answer from the remote"),
+            RequestError::Io(e) => write!(f, "I/O error: {}", e),
+        }
+    }
+}
+
+impl error::Error for RequestError {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        match self {
+            RequestError::Io(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use libp2p_core::{PeerId, multiaddr::multiaddr};
+
+    #[test]
+    fn encode_decode_roundtrip() {
+        let reserve = HopMessage {
+            kind: HopMessageType::Status,
+            peer: None,
+            reservation: Some(Reservation {
+                expire: 1_600_000_000,
+                addrs: vec![multiaddr![Ip4([1, 2, 3, 4]), Tcp(4001u16), P2pCircuit]],
+                voucher: None,
+            }),
+            limit: Some(Limit { duration: Some(Duration::from_secs(120)), data: Some(1 << 17) }),
+            status: Some(Status::Ok),
+        };
+        assert_eq!(HopMessage::decode(&reserve.encode()), Ok(reserve));
+
+        let connect = StopMessage {
+            kind: StopMessageType::Connect,
+            peer: Some(Peer { id: PeerId::random(), addrs: Vec::new() }),
+            limit: Some(Limit::default()),
+            status: None,
+        };
+        assert_eq!(StopMessage::decode(&connect.encode()), Ok(connect));
+    }
+
+    #[test]
+    fn type_zero_is_encoded() {
+        let message = HopMessage {
+            kind: HopMessageType::Reserve,
+            peer: None,
+            reservation: None,
+            limit: None,
+            status: None,
+        };
+        assert!(!message.encode().is_empty());
+        assert_eq!(HopMessage::decode(&message.encode()).map(|m| m.kind), Ok(HopMessageType::Reserve));
+    }
+}
@@ -0,0 +1,403 @@
+// Copyright 2019 Parity Technologies (UK) Ltd.
+//
+// Permission is hereby granted, free of charge, to any person obtaining a
+// copy of this software and associated documentation files (the "Software"),
+// to deal in the Software without restriction, including without limitation
+// the rights to use, copy, modify, merge, publish, distribute, sublicense,
+// and/or sell copies of the Software, and to permit persons to whom the
+// Software is furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS
+// OR IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING
+// FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
+// DEALINGS IN THE SOFTWARE.
+
+//! The relay side of version 2 of the circuit relay protocol.
+
+mod handler;
+
+pub use handler::{
+    CircuitRequest,
+    RelayServerHandler,
+    RelayServerHandlerEvent,
+    RelayServerHandlerIn,
+    ReservationRequest,
+    StopInfo
+};
+
+use crate::protocol::Peer;
+use crate::v2::protocol::{Limit, Reservation, Status};
+use futures::prelude::*;
+use libp2p_core::{ConnectedPoint, Multiaddr, PeerId, multiaddr::Protocol};
+use libp2p_swarm::{ConnectionId, NetworkBehaviour, NetworkBehaviourAction, PollParameters, node_event_connection};
+use smallvec::SmallVec;
+use std::{collections::{HashMap, VecDeque}, time::{Duration, SystemTime, UNIX_EPOCH}};
+use tokio_io::{AsyncRead, AsyncWrite};
+use wasm_timer::{Delay, Instant};
+
+/// Configuration of the [`RelayServer`] network behaviour.
+#[derive(Debug, Clone)]
+pub struct RelayServerConfig {
+    max_reservations: usize,
+    reservation_duration: Duration,
+    max_circuits: usize,
+    max_circuits_per_peer: usize,
+    max_circuit_duration: Duration,
+    max_circuit_bytes: u64,
+    request_timeout: Duration,
+    connection_idle_timeout: Duration,
+}
+
+impl Default for RelayServerConfig {
+    fn default() -> Self {
+        RelayServerConfig {
+            max_reservations: 128,
+            reservation_duration: Duration::from_secs(60 * 60),
+            max_circuits: 16,
+            max_circuits_per_peer: 4,
+            max_circuit_duration: Duration::from_secs(2 * 60),
+            max_circuit_bytes: 1 << 17,
+            request_timeout: Duration::from_secs(10),
+            connection_idle_timeout: Duration::from_secs(10),
+        }
+    }
+}
+
+impl RelayServerConfig {
+    /// Sets the maximum number of clients holding a reservation at the same time. Defaults
+    /// to 128.
+    pub fn with_max_reservations(mut self, max: usize) -> Self {
+        self.max_reservations = max;
+        self
+    }
+
+    /// Sets how long a reservation is valid unless renewed. Defaults to 1 hour.
+    pub fn with_reservation_duration(mut self, duration: Duration) -> Self {
+        self.reservation_duration = duration;
+        self
+    }
+
+    /// Sets the maximum number of connections relayed at the same time, in total and from the
+    /// same source. Default to 16 and 4.
+    pub fn with_max_circuits(mut self, max: usize, max_per_peer: usize) -> Self {
+        self.max_circuits = max;
+        self.max_circuits_per_peer = max_per_peer;
+        self
+    }
+
+    /// Sets how long a relayed connection is kept open. Defaults to 2 minutes.
+    pub fn with_max_circuit_duration(mut self, duration: Duration) -> Self {
+        self.max_circuit_duration = duration;
+        self
+    }
+
+    /// Sets how many bytes are relayed in each direction of a relayed connection before it's
+    /// closed. Defaults to 128 KiB.
+    pub fn with_max_circuit_bytes(mut self, bytes: u64) -> Self {
+        self.max_circuit_bytes = bytes;
+        self
+    }
+
+    /// Sets how long the clients have to send their requests, and the destinations to answer.
+    /// Defaults to 10 seconds.
+    pub fn with_request_timeout(mut self, timeout: Duration) -> Self {
+        self.request_timeout = timeout;
+        self
+    }
+
+    /// Sets how long a connection without reservation nor relayed connection is kept alive.
+    /// Defaults to 10 seconds.
+    pub fn with_connection_idle_timeout(mut self, timeout: Duration) -> Self {
+        self.connection_idle_timeout = timeout;
+        self
+    }
+
+    /// Returns the limits applied to every relayed connection.
+    fn limit(&self) -> Limit {
+        Limit {
+            duration: Some(self.max_circuit_duration),
+            data: Some(self.max_circuit_bytes),
+        }
+    }
+}
+
+/// Event generated by the `RelayServer` network behaviour.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RelayServerEvent {
+    /// A client reserved a slot, or renewed its reservation.
+    ReservationAccepted { peer: PeerId, renewed: bool },
+    /// We refused the reservation of a client.
+    ReservationDenied { peer: PeerId, status: Status },
+    /// The reservation of a client expired without being renewed.
+    ReservationExpired { peer: PeerId },
+    /// We started relaying a connection from `src` to `dst`.
+    CircuitOpened { src: PeerId, dst: PeerId },
+    /// A connection we relayed from `src` to `dst` has been closed.
+    CircuitClosed { src: PeerId, dst: PeerId },
+    /// We refused, or failed, to relay a connection from `src` to `dst`.
+    CircuitFailed { src: PeerId, dst: PeerId, status: Status },
+}
+
+/// Network behaviour of a relay implementing version 2 of the circuit relay protocol.
+///
+/// Clients must reserve a slot before connections can be relayed to them. Reservations expire
+/// unless renewed, or when the connection to the client is closed. The number of reservations
+/// and relayed connections is bounded, and relayed connections are closed once their duration
+/// or data limit is reached. The limits are communicated to the clients in the protocol.
+pub struct RelayServer<TSubstream> {
+    /// Configuration options.
+    config: RelayServerConfig,
+    /// The established connections to each peer, from the oldest to the most recent.
+    connected: HashMap<PeerId, SmallVec<[ConnectionId; 2]>>,
+    /// Clients holding a reservation, with its expiration.
+    reservations: HashMap<PeerId, Instant>,
+    /// Fires when the next reservation expires.
+    expiration_timer: Option<Delay>,
+    /// Number of connections relayed from each source.
+    circuits: HashMap<PeerId, usize>,
+    /// Total number of relayed connections.
+    num_circuits: usize,
+    /// Requests of the clients, waiting to be processed in `poll()`, where our own identity and
+    /// addresses are known.
+    requests: VecDeque<(PeerId, ConnectionId, RelayServerHandlerEvent<TSubstream>)>,
+    /// Queue of actions to return to the swarm.
+    pending_actions: VecDeque<NetworkBehaviourAction<RelayServerHandlerIn<TSubstream>, RelayServerEvent>>,
+}
+
+impl<TSubstream> RelayServer<TSubstream> {
+    /// Creates a `RelayServer` with the given configuration.
+    pub fn new(config: RelayServerConfig) -> Self {
+        RelayServer {
+            config,
+            connected: HashMap::new(),
+            reservations: HashMap::new(),
+            expiration_timer: None,
+            circuits: HashMap::new(),
+            num_circuits: 0,
+            requests: VecDeque::new(),
+            pending_actions: VecDeque::new(),
+        }
+    }
+
+    /// Returns the clients currently holding a reservation.
+    pub fn reservations(&self) -> impl Iterator<Item = &PeerId> {
+        self.reservations.keys()
+    }
+
+    /// Returns the number of connections we are currently relaying.
+    pub fn num_circuits(&self) -> usize {
+        self.num_circuits
+    }
+
+    /// Resets the expiration timer to the next expiration.
+    fn reset_expiration_timer(&mut self) {
+        self.expiration_timer = self.reservations.values().min().map(|at| Delay::new(*at));
+    }
+
+    /// Forgets a relayed connection from `src`.
+    fn remove_circuit(&mut self, src: &PeerId) {
+        self.num_circuits -= 1;
+        if let Some(count) = self.circuits.get_mut(src) {
+            *count -= 1;
+            if *count == 0 {
+                self.circuits.remove(src);
+            }
+        }
+    }
+
+    /// Answers a reservation request of `peer`.
+    fn on_reservation_request(
+        &mut self,
+        peer: PeerId,
+        connection: ConnectionId,
+        request: ReservationRequest<TSubstream>,
+        params: &mut impl PollParameters,
+    ) {
+        let renewed = self.reservations.contains_key(&peer);
+        if !renewed && self.reservations.len() >= self.config.max_reservations {
+            let status = Status::ResourceLimitExceeded;
+            self.pending_actions.push_back(NetworkBehaviourAction::SendEventToConnection {
+                peer_id: peer.clone(),
+                connection,
+                event: RelayServerHandlerIn::DenyReservation { request, status },
+            });
+            self.pending_actions.push_back(NetworkBehaviourAction::GenerateEvent(
+                RelayServerEvent::ReservationDenied { peer, status }
+            ));
+            return
+        }
+
+        self.reservations.insert(peer.clone(), Instant::now() + self.config.reservation_duration);
+        self.reset_expiration_timer();
+
+        let expire = SystemTime::now().duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .checked_add(self.config.reservation_duration)
+            .map_or(u64::max_value(), |d| d.as_secs());
+        let local_peer_id = params.local_peer_id().clone();
+        let addrs = params.external_addresses()
+            .map(|addr| addr.with(Protocol::P2p(local_peer_id.clone().into())).with(Protocol::P2pCircuit))
+            .collect();
+        let reservation = Reservation { expire, addrs, voucher: None };
+        self.pending_actions.push_back(NetworkBehaviourAction::SendEventToConnection {
+            peer_id: peer.clone(),
+            connection,
+            event: RelayServerHandlerIn::AcceptReservation { request, reservation, limit: self.config.limit() },
+        });
+        self.pending_actions.push_back(NetworkBehaviourAction::GenerateEvent(
+            RelayServerEvent::ReservationAccepted { peer, renewed }
+        ));
+    }
+
+    /// Answers a request of `src` to open a circuit.
+    fn on_circuit_request(&mut self, src: PeerId, connection: ConnectionId, request: CircuitRequest<TSubstream>) {
+        let dst = request.dst.id.clone();
+        let dst_connection = self.connected.get(&dst).and_then(|conns| conns.last()).cloned();
+        let status = if !self.reservations.contains_key(&dst) {
+            Status::NoReservation
+        } else if self.num_circuits >= self.config.max_circuits
+            || self.circuits.get(&src).map_or(false, |n| *n >= self.config.max_circuits_per_peer)
+        {
+            Status::ResourceLimitExceeded
+        } else if let Some(dst_connection) = dst_connection {
+            self.num_circuits += 1;
+            *self.circuits.entry(src.clone()).or_insert(0) += 1;
+            self.pending_actions.push_back(NetworkBehaviourAction::SendEventToConnection {
+                peer_id: dst,
+                connection: dst_connection,
+                event: RelayServerHandlerIn::NegotiateCircuit {
+                    src: Peer { id: src, addrs: Vec::new() },
+                    request,
+                    limit: self.config.limit(),
+                },
+            });
+            return
+        } else {
+            Status::ConnectionFailed
+        };
+
+        log::debug!("Refusing to relay a connection from {:?} to {:?}: {}", src, dst, status);
+        self.pending_actions.push_back(NetworkBehaviourAction::SendEventToConnection {
+            peer_id: src.clone(),
+            connection,
+            event: RelayServerHandlerIn::DenyCircuit { request, status },
+        });
+        self.pending_actions.push_back(NetworkBehaviourAction::GenerateEvent(
+            RelayServerEvent::CircuitFailed { src, dst, status }
+        ));
+    }
+}
+
+impl<TSubstream> NetworkBehaviour for RelayServer<TSubstream>
+where
+    TSubstream: AsyncRead + AsyncWrite + Send + 'static,
+{
+    type ProtocolsHandler = RelayServerHandler<TSubstream>;
+    type OutEvent = RelayServerEvent;
+
+    fn new_handler(&mut self) -> Self::ProtocolsHandler {
+        RelayServerHandler::new(self.config.request_timeout, self.config.connection_idle_timeout)
+    }
+
+    fn addresses_of_peer(&mut self, _: &PeerId) -> Vec<Multiaddr> {
+        Vec::new()
+    }
+
+    fn inject_connected(&mut self, _: PeerId, _: ConnectedPoint) {}
+
+    fn inject_disconnected(&mut self, peer_id: &PeerId, _: ConnectedPoint) {
+        self.connected.remove(peer_id);
+        if self.reservations.remove(peer_id).is_some() {
+            self.reset_expiration_timer();
+        }
+    }
+
+    fn inject_connection_established(&mut self, peer_id: &PeerId, connection: &ConnectionId, _: &ConnectedPoint) {
+        self.connected.entry(peer_id.clone()).or_default().push(*connection);
+    }
+
+    fn inject_connection_closed(&mut self, peer_id: &PeerId, connection: &ConnectionId, _: &ConnectedPoint) {
+        if let Some(connections) = self.connected.get_mut(peer_id) {
+            connections.retain(|c| c != connection);
+            if connections.is_empty() {
+                self.connected.remove(peer_id);
+            }
+        }
+    }
+
+    fn inject_node_event(&mut self, peer_id: PeerId, event: RelayServerHandlerEvent<TSubstream>) {
+        match node_event_connection(&self.connected, &peer_id) {
+            Some(connection) => self.inject_connection_event(peer_id, connection, event),
+            None => log::debug!("Dropping relay event from disconnected peer {:?}", peer_id),
+        }
+    }
+
+    fn inject_connection_event(&mut self, peer_id: PeerId, connection: ConnectionId, event: RelayServerHandlerEvent<TSubstream>) {
+        match event {
+            RelayServerHandlerEvent::ReservationRequested(_) | RelayServerHandlerEvent::CircuitRequested(_) => {
+                self.requests.push_back((peer_id, connection, event));
+            }
+            RelayServerHandlerEvent::CircuitOpened { src } => {
+                self.pending_actions.push_back(NetworkBehaviourAction::GenerateEvent(
+                    RelayServerEvent::CircuitOpened { src, dst: peer_id }
+                ));
+            }
+            RelayServerHandlerEvent::CircuitFailed { src, status } => {
+                self.remove_circuit(&src);
+                self.pending_actions.push_back(NetworkBehaviourAction::GenerateEvent(
+                    RelayServerEvent::CircuitFailed { src, dst: peer_id, status }
+                ));
+            }
+            RelayServerHandlerEvent::CircuitClosed { src } => {
+                self.remove_circuit(&src);
+                self.pending_actions.push_back(NetworkBehaviourAction::GenerateEvent(
+                    RelayServerEvent::CircuitClosed { src, dst: peer_id }
+                ));
+            }
+        }
+    }
+
+    fn poll(&mut self, params: &mut impl PollParameters)
+        -> Async<NetworkBehaviourAction<RelayServerHandlerIn<TSubstream>, Self::OutEvent>>
+    {
+        while let Some((peer, connection, event)) = self.requests.pop_front() {
+            match event {
+                RelayServerHandlerEvent::ReservationRequested(request) =>
+                    self.on_reservation_request(peer, connection, request, params),
+                RelayServerHandlerEvent::CircuitRequested(request) =>
+                    self.on_circuit_request(peer, connection, request),
+                _ => {}
+            }
+        }
+
+        while let Some(timer) = self.expiration_timer.as_mut() {
+            if let Ok(Async::NotReady) = timer.poll() {
+                break
+            }
+            let now = Instant::now();
+            let expired = self.reservations.iter()
+                .filter(|(_, at)| **at <= now)
+                .map(|(peer, _)| peer.clone())
+                .collect::<Vec<_>>();
+            for peer in expired {
+                self.reservations.remove(&peer);
+                self.pending_actions.push_back(NetworkBehaviourAction::GenerateEvent(
+                    RelayServerEvent::ReservationExpired { peer }
+                ));
+            }
+            self.reset_expiration_timer();
+        }
+
+        if let Some(action) = self.pending_actions.pop_front() {
+            return Async::Ready(action)
+        }
+
+        Async::NotReady
+    }
+}
@@ -0,0 +1,328 @@
+// Copyright 2019 Parity Technologies (UK) Ltd.
+//
+// Permission is hereby granted, free of charge, to any person obtaining a
+// copy of this software and associated documentation files (the "Software"),
+// to deal in the Software without restriction, including without limitation
+// the rights to use, copy, modify, merge, publish, distribute, sublicense,
+// and/or sell copies of the Software, and to permit persons to whom the
+// Software is furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS
+// OR IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING
+// FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
+// DEALINGS IN THE SOFTWARE.
+
+use crate::handler::{ConnectionGuard, poll_guards};
+use crate::protocol::{self, IoFuture, Peer};
+use crate::v2::protocol::{
+    HopListen,
+    HopMessage,
+    HopMessageType,
+    Limit,
+    RequestError,
+    Reservation,
+    Status,
+    StopMessage,
+    StopMessageType,
+    StopRequest,
+    send_hop
+};
+use futures::{prelude::*, sync::oneshot};
+use libp2p_core::{PeerId, upgrade::{Negotiated, UpgradeError}};
+use libp2p_swarm::{
+    KeepAlive,
+    SubstreamProtocol,
+    ProtocolsHandler,
+    ProtocolsHandlerEvent,
+    ProtocolsHandlerUpgrErr
+};
+use std::{collections::VecDeque, time::Duration};
+use tokio_io::{AsyncRead, AsyncWrite};
+use void::Void;
+use wasm_timer::Instant;
+
+/// Request of the remote to reserve a slot on the relay.
+pub struct ReservationRequest<TSubstream> {
+    pub(crate) substream: Negotiated<TSubstream>,
+    pub(crate) guard: ConnectionGuard,
+}
+
+/// Request of the remote to open a circuit towards a client that reserved a slot.
+pub struct CircuitRequest<TSubstream> {
+    pub(crate) substream: Negotiated<TSubstream>,
+    pub(crate) dst: Peer,
+    pub(crate) guard: ConnectionGuard,
+}
+
+impl<TSubstream> CircuitRequest<TSubstream> {
+    /// Returns the destination of the requested circuit.
+    pub fn dst(&self) -> &Peer {
+        &self.dst
+    }
+}
+
+/// Event sent by the `RelayServer` behaviour to the `RelayServerHandler`.
+pub enum RelayServerHandlerIn<TSubstream> {
+    /// Accepts the reservation requested by the remote.
+    AcceptReservation {
+        request: ReservationRequest<TSubstream>,
+        reservation: Reservation,
+        limit: Limit,
+    },
+    /// Refuses the reservation requested by the remote.
+    DenyReservation {
+        request: ReservationRequest<TSubstream>,
+        status: Status,
+    },
+    /// Refuses the circuit requested by the remote.
+    DenyCircuit {
+        request: CircuitRequest<TSubstream>,
+        status: Status,
+    },
+    /// Asks the remote to accept a circuit from `src`, and relays it within `limit` if
+    /// accepted.
+    NegotiateCircuit {
+        src: Peer,
+        request: CircuitRequest<TSubstream>,
+        limit: Limit,
+    },
+}
+
+/// Event produced by the `RelayServerHandler`.
+pub enum RelayServerHandlerEvent<TSubstream> {
+    /// The remote asks to reserve a slot.
+    ReservationRequested(ReservationRequest<TSubstream>),
+    /// The remote asks to open a circuit.
+    CircuitRequested(CircuitRequest<TSubstream>),
+    /// We started relaying a connection from `src` to the remote.
+    CircuitOpened { src: PeerId },
+    /// The remote didn't accept a connection from `src`.
+    CircuitFailed { src: PeerId, status: Status },
+    /// A connection relayed from `src` to the remote has been closed.
+    CircuitClosed { src: PeerId },
+}
+
+/// Information attached to the stop substreams being opened.
+pub struct StopInfo<TSubstream> {
+    src: PeerId,
+    request: CircuitRequest<TSubstream>,
+    limit: Limit,
+}
+
+/// Protocol handler of a relay, answering the hop requests of the remote and driving the
+/// circuits whose destination is the remote.
+pub struct RelayServerHandler<TSubstream> {
+    /// Timeout of the substream upgrades.
+    request_timeout: Duration,
+    /// How long the connection is kept alive when idle.
+    idle_timeout: Duration,
+    /// Value to return from `connection_keep_alive`.
+    keep_alive: KeepAlive,
+    /// Stop requests for which an outbound substream must be opened.
+    outbound: VecDeque<(StopRequest, StopInfo<TSubstream>)>,
+    /// Number of outbound substreams being opened or negotiated.
+    num_pending_outbound: usize,
+    /// Answers being sent on inbound substreams.
+    answers: Vec<IoFuture<()>>,
+    /// Circuits whose destination is the remote, with their source.
+    circuits: Vec<(PeerId, ConnectionGuard, IoFuture<()>)>,
+    /// Receivers notified when the guards created by this handler are dropped.
+    guards: Vec<oneshot::Receiver<()>>,
+    /// Events to produce in `poll()`.
+    pending_events: VecDeque<RelayServerHandlerEvent<TSubstream>>,
+}
+
+impl<TSubstream> RelayServerHandler<TSubstream> {
+    pub(crate) fn new(request_timeout: Duration, idle_timeout: Duration) -> Self {
+        RelayServerHandler {
+            request_timeout,
+            idle_timeout,
+            keep_alive: KeepAlive::Until(Instant::now() + idle_timeout),
+            outbound: VecDeque::new(),
+            num_pending_outbound: 0,
+            answers: Vec::new(),
+            circuits: Vec::new(),
+            guards: Vec::new(),
+            pending_events: VecDeque::new(),
+        }
+    }
+
+    /// Creates a guard keeping this connection alive.
+    fn new_guard(&mut self) -> ConnectionGuard {
+        let (guard, receiver) = ConnectionGuard::new();
+        self.guards.push(receiver);
+        guard
+    }
+
+    /// Updates the keep-alive after the state of the handler has changed.
+    fn update_keep_alive(&mut self) {
+        let busy = !self.outbound.is_empty()
+            || self.num_pending_outbound > 0
+            || !self.answers.is_empty()
+            || !self.circuits.is_empty()
+            || !self.guards.is_empty();
+        if busy {
+            self.keep_alive = KeepAlive::Yes;
+        } else if let KeepAlive::Yes = self.keep_alive {
+            self.keep_alive = KeepAlive::Until(Instant::now() + self.idle_timeout);
+        }
+    }
+}
+
+impl<TSubstream> RelayServerHandler<TSubstream>
+where
+    TSubstream: AsyncRead + AsyncWrite + Send + 'static,
+{
+    /// Sends a message on an inbound substream, then closes it.
+    fn answer(&mut self, substream: Negotiated<TSubstream>, message: HopMessage) {
+        self.answers.push(Box::new(send_hop(substream, &message).map(|_| ())));
+    }
+}
+
+impl<TSubstream> ProtocolsHandler for RelayServerHandler<TSubstream>
+where
+    TSubstream: AsyncRead + AsyncWrite + Send + 'static,
+{
+    type InEvent = RelayServerHandlerIn<TSubstream>;
+    type OutEvent = RelayServerHandlerEvent<TSubstream>;
+    type Error = Void;
+    type Substream = TSubstream;
+    type InboundProtocol = HopListen;
+    type OutboundProtocol = StopRequest;
+    type OutboundOpenInfo = StopInfo<TSubstream>;
+
+    fn listen_protocol(&self) -> SubstreamProtocol<Self::InboundProtocol> {
+        SubstreamProtocol::new(HopListen).with_timeout(self.request_timeout)
+    }
+
+    fn inject_fully_negotiated_inbound(&mut self, (message, substream): (HopMessage, Negotiated<TSubstream>)) {
+        match message.kind {
+            HopMessageType::Reserve => {
+                let request = ReservationRequest { substream, guard: self.new_guard() };
+                self.pending_events.push_back(RelayServerHandlerEvent::ReservationRequested(request));
+            }
+            HopMessageType::Connect => match message.peer {
+                Some(dst) => {
+                    let request = CircuitRequest { substream, dst, guard: self.new_guard() };
+                    self.pending_events.push_back(RelayServerHandlerEvent::CircuitRequested(request));
+                }
+                None => self.answer(substream, HopMessage::status(Status::MalformedMessage)),
+            },
+            HopMessageType::Status => self.answer(substream, HopMessage::status(Status::UnexpectedMessage)),
+        }
+        self.update_keep_alive();
+    }
+
+    fn inject_fully_negotiated_outbound(&mut self, substream: Negotiated<TSubstream>, info: StopInfo<TSubstream>) {
+        self.num_pending_outbound -= 1;
+        let StopInfo { src, request, limit } = info;
+        // The destination accepted; tell the source and start relaying.
+        let success = HopMessage { limit: Some(limit), ..HopMessage::status(Status::Ok) };
+        let circuit = send_hop(request.substream, &success).and_then(move |src_substream| {
+            protocol::limited_circuit(src_substream, substream, limit.duration, limit.data)
+        });
+        self.circuits.push((src.clone(), request.guard, Box::new(circuit)));
+        self.pending_events.push_back(RelayServerHandlerEvent::CircuitOpened { src });
+        self.update_keep_alive();
+    }
+
+    fn inject_event(&mut self, event: Self::InEvent) {
+        match event {
+            RelayServerHandlerIn::AcceptReservation { request, reservation, limit } => {
+                let message = HopMessage {
+                    reservation: Some(reservation),
+                    limit: Some(limit),
+                    ..HopMessage::status(Status::Ok)
+                };
+                self.answer(request.substream, message);
+            }
+            RelayServerHandlerIn::DenyReservation { request, status } => {
+                self.answer(request.substream, HopMessage::status(status));
+            }
+            RelayServerHandlerIn::DenyCircuit { request, status } => {
+                self.answer(request.substream, HopMessage::status(status));
+            }
+            RelayServerHandlerIn::NegotiateCircuit { src, request, limit } => {
+                let message = StopMessage {
+                    kind: StopMessageType::Connect,
+                    peer: Some(src.clone()),
+                    limit: Some(limit),
+                    status: None,
+                };
+                let info = StopInfo { src: src.id, request, limit };
+                self.outbound.push_back((StopRequest { message }, info));
+            }
+        }
+        self.update_keep_alive();
+    }
+
+    fn inject_dial_upgrade_error(&mut self, info: StopInfo<TSubstream>, error: ProtocolsHandlerUpgrErr<RequestError>) {
+        self.num_pending_outbound -= 1;
+        log::debug!("Failed to relay a connection from {:?}: {}", info.src, error);
+        let status = match error {
+            ProtocolsHandlerUpgrErr::Upgrade(UpgradeError::Apply(RequestError::Refused(status))) => status,
+            _ => Status::ConnectionFailed,
+        };
+        self.answer(info.request.substream, HopMessage::status(Status::ConnectionFailed));
+        self.pending_events.push_back(RelayServerHandlerEvent::CircuitFailed { src: info.src, status });
+        self.update_keep_alive();
+    }
+
+    fn connection_keep_alive(&self) -> KeepAlive {
+        self.keep_alive
+    }
+
+    fn poll(&mut self) -> Poll<ProtocolsHandlerEvent<StopRequest, StopInfo<TSubstream>, Self::OutEvent>, Void> {
+        poll_guards(&mut self.guards);
+
+        let mut n = 0;
+        while n < self.answers.len() {
+            match self.answers[n].poll() {
+                Ok(Async::NotReady) => n += 1,
+                Ok(Async::Ready(())) => {
+                    self.answers.swap_remove(n);
+                }
+                Err(err) => {
+                    log::debug!("Failed to answer a hop request: {}", err);
+                    self.answers.swap_remove(n);
+                }
+            }
+        }
+
+        let mut n = 0;
+        while n < self.circuits.len() {
+            match self.circuits[n].2.poll() {
+                Ok(Async::NotReady) => n += 1,
+                Ok(Async::Ready(())) => {
+                    let (src, _, _) = self.circuits.swap_remove(n);
+                    self.pending_events.push_back(RelayServerHandlerEvent::CircuitClosed { src });
+                }
+                Err(err) => {
+                    let (src, _, _) = self.circuits.swap_remove(n);
+                    log::debug!("Relayed connection from {:?} closed with an error: {}", src, err);
+                    self.pending_events.push_back(RelayServerHandlerEvent::CircuitClosed { src });
+                }
+            }
+        }
+
+        self.update_keep_alive();
+
+        if let Some(event) = self.pending_events.pop_front() {
+            return Ok(Async::Ready(ProtocolsHandlerEvent::Custom(event)))
+        }
+
+        if let Some((request, info)) = self.outbound.pop_front() {
+            self.num_pending_outbound += 1;
+            let protocol = SubstreamProtocol::new(request).with_timeout(self.request_timeout);
+            return Ok(Async::Ready(ProtocolsHandlerEvent::OutboundSubstreamRequest { protocol, info }))
+        }
+
+        Ok(Async::NotReady)
+    }
+}
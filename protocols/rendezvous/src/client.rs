@@ -0,0 +1,291 @@
+// Copyright 2019 Parity Technologies (UK) Ltd.
+//
+// Permission is hereby granted, free of charge, to any person obtaining a
+// copy of this software and associated documentation files (the "Software"),
+// to deal in the Software without restriction, including without limitation
+// the rights to use, copy, modify, merge, publish, distribute, sublicense,
+// and/or sell copies of the Software, and to permit persons to whom the
+// Software is furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS
+// OR IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING
+// FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
+// DEALINGS IN THE SOFTWARE.
+
+//! Rendezvous client, registering the local node and discovering other peers.
+
+use crate::codec::{Cookie, ErrorCode, Message, Namespace, NewRegistration, PeerRecord, Registration, RendezvousCodec, PROTOCOL_NAME};
+use crate::forward_action;
+use futures::prelude::*;
+use libp2p_core::{ConnectedPoint, Multiaddr, PeerId};
+use libp2p_request_response::{
+    RequestId,
+    RequestProtocol,
+    RequestResponse,
+    RequestResponseConfig,
+    RequestResponseEvent,
+    RequestResponseHandler,
+    RequestResponseHandlerEvent,
+    RequestResponseMessage
+};
+use libp2p_swarm::{ConnectionId, NetworkBehaviour, NetworkBehaviourAction, PollParameters};
+use std::{collections::{HashMap, VecDeque}, error, iter, time::Duration};
+use tokio_io::{AsyncRead, AsyncWrite};
+use wasm_timer::Instant;
+
+/// Event generated by the `Rendezvous` network behaviour.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RendezvousEvent {
+    /// We registered in a namespace of a rendezvous node, for the given TTL in seconds.
+    Registered { rendezvous_node: PeerId, namespace: Namespace, ttl: u64 },
+    /// The registration in a namespace of a rendezvous node failed.
+    ///
+    /// Failures to reach the rendezvous node are reported as `ErrorCode::Unavailable`.
+    RegisterFailed { rendezvous_node: PeerId, namespace: Namespace, error: ErrorCode },
+    /// A rendezvous node answered a discovery. The cookie can be passed to the next discovery
+    /// in order to only receive new registrations.
+    Discovered { rendezvous_node: PeerId, registrations: Vec<Registration>, cookie: Cookie },
+    /// A discovery failed.
+    ///
+    /// Failures to reach the rendezvous node are reported as `ErrorCode::Unavailable`.
+    DiscoverFailed { rendezvous_node: PeerId, namespace: Option<Namespace>, error: ErrorCode },
+}
+
+/// Request sent to a rendezvous node, waiting for its answer.
+enum Outbound {
+    Register(Namespace),
+    Unregister,
+    Discover(Option<Namespace>),
+}
+
+/// Network behaviour acting as a rendezvous client.
+///
+/// Registrations must be renewed by the application before their TTL elapses. The addresses of
+/// the discovered peers are returned by `addresses_of_peer` until their registration expires,
+/// so that they can be dialed right away.
+pub struct Rendezvous<TSubstream> {
+    /// The behaviour sending the requests.
+    inner: RequestResponse<TSubstream, RendezvousCodec>,
+    /// Registrations to send in `poll()`, where our addresses are known.
+    to_register: VecDeque<(PeerId, Namespace, Option<u64>)>,
+    /// Requests waiting for an answer.
+    outbound: HashMap<RequestId, Outbound>,
+    /// Addresses of the discovered peers, with the expiration of their registration.
+    discovered: HashMap<PeerId, (Vec<Multiaddr>, Instant)>,
+    /// Queue of events to return to the swarm.
+    pending_events: VecDeque<RendezvousEvent>,
+}
+
+impl<TSubstream> Rendezvous<TSubstream> {
+    /// Creates a `Rendezvous` client behaviour.
+    pub fn new() -> Self {
+        let inner_config = RequestResponseConfig::default().with_max_retries(0);
+        Rendezvous {
+            inner: RequestResponse::new(RendezvousCodec, iter::once(PROTOCOL_NAME), inner_config),
+            to_register: VecDeque::new(),
+            outbound: HashMap::new(),
+            discovered: HashMap::new(),
+            pending_events: VecDeque::new(),
+        }
+    }
+
+    /// Adds an address at which a rendezvous node can be dialed.
+    pub fn add_address(&mut self, rendezvous_node: &PeerId, address: Multiaddr) {
+        self.inner.add_address(rendezvous_node, address)
+    }
+
+    /// Registers the local node in a namespace of a rendezvous node, with our external
+    /// addresses or, if there are none, the addresses we listen on.
+    ///
+    /// The TTL is in seconds. The rendezvous node uses its own default if `None`.
+    pub fn register(&mut self, namespace: Namespace, rendezvous_node: PeerId, ttl: Option<u64>) {
+        self.to_register.push_back((rendezvous_node, namespace, ttl));
+    }
+
+    /// Removes the registration of the local node from a namespace of a rendezvous node.
+    pub fn unregister(&mut self, namespace: Namespace, rendezvous_node: PeerId) {
+        let request_id = self.inner.send_request(&rendezvous_node, Message::Unregister(namespace));
+        self.outbound.insert(request_id, Outbound::Unregister);
+    }
+
+    /// Asks a rendezvous node for the peers registered in a namespace, or in all namespaces if
+    /// `None`.
+    ///
+    /// The cookie returned by a previous discovery of the same namespace restricts the answer
+    /// to the registrations made since, and `limit` bounds the number of registrations returned.
+    pub fn discover(
+        &mut self,
+        namespace: Option<Namespace>,
+        cookie: Option<Cookie>,
+        limit: Option<u64>,
+        rendezvous_node: PeerId,
+    ) {
+        let message = Message::Discover { namespace: namespace.clone(), cookie, limit };
+        let request_id = self.inner.send_request(&rendezvous_node, message);
+        self.outbound.insert(request_id, Outbound::Discover(namespace));
+    }
+
+    /// Processes the answer of a rendezvous node.
+    fn on_response(&mut self, rendezvous_node: PeerId, request_id: RequestId, response: Message) {
+        let event = match (self.outbound.remove(&request_id), response) {
+            (Some(Outbound::Register(namespace)), Message::RegisterResponse(Ok(ttl))) => {
+                RendezvousEvent::Registered { rendezvous_node, namespace, ttl }
+            }
+            (Some(Outbound::Register(namespace)), Message::RegisterResponse(Err(error))) => {
+                RendezvousEvent::RegisterFailed { rendezvous_node, namespace, error }
+            }
+            (Some(Outbound::Discover(_)), Message::DiscoverResponse(Ok((registrations, cookie)))) => {
+                let now = Instant::now();
+                for registration in &registrations {
+                    let expires = now + Duration::from_secs(registration.ttl);
+                    self.discovered.insert(
+                        registration.record.peer_id.clone(),
+                        (registration.record.addresses.clone(), expires),
+                    );
+                }
+                RendezvousEvent::Discovered { rendezvous_node, registrations, cookie }
+            }
+            (Some(Outbound::Discover(namespace)), Message::DiscoverResponse(Err(error))) => {
+                RendezvousEvent::DiscoverFailed { rendezvous_node, namespace, error }
+            }
+            (_, response) => {
+                log::debug!("Unexpected rendezvous answer from {:?}: {:?}", rendezvous_node, response);
+                return
+            }
+        };
+        self.pending_events.push_back(event);
+    }
+
+    /// Processes the failure of a request.
+    fn on_failure(&mut self, rendezvous_node: PeerId, request_id: RequestId) {
+        let event = match self.outbound.remove(&request_id) {
+            Some(Outbound::Register(namespace)) => {
+                RendezvousEvent::RegisterFailed { rendezvous_node, namespace, error: ErrorCode::Unavailable }
+            }
+            Some(Outbound::Discover(namespace)) => {
+                RendezvousEvent::DiscoverFailed { rendezvous_node, namespace, error: ErrorCode::Unavailable }
+            }
+            // Unregistrations aren't answered, their substream is simply closed.
+            Some(Outbound::Unregister) | None => return,
+        };
+        self.pending_events.push_back(event);
+    }
+}
+
+impl<TSubstream> Default for Rendezvous<TSubstream> {
+    fn default() -> Self {
+        Rendezvous::new()
+    }
+}
+
+impl<TSubstream> NetworkBehaviour for Rendezvous<TSubstream>
+where
+    TSubstream: AsyncRead + AsyncWrite,
+{
+    type ProtocolsHandler = RequestResponseHandler<TSubstream, RendezvousCodec>;
+    type OutEvent = RendezvousEvent;
+
+    fn new_handler(&mut self) -> Self::ProtocolsHandler {
+        self.inner.new_handler()
+    }
+
+    fn addresses_of_peer(&mut self, peer_id: &PeerId) -> Vec<Multiaddr> {
+        let mut addresses = self.inner.addresses_of_peer(peer_id);
+        let expired = match self.discovered.get(peer_id) {
+            Some((discovered, expires)) if *expires > Instant::now() => {
+                addresses.extend(discovered.iter().cloned());
+                false
+            }
+            Some(_) => true,
+            None => false,
+        };
+        if expired {
+            self.discovered.remove(peer_id);
+        }
+        addresses
+    }
+
+    fn inject_connected(&mut self, peer_id: PeerId, endpoint: ConnectedPoint) {
+        self.inner.inject_connected(peer_id, endpoint)
+    }
+
+    fn inject_disconnected(&mut self, peer_id: &PeerId, endpoint: ConnectedPoint) {
+        self.inner.inject_disconnected(peer_id, endpoint)
+    }
+
+    fn inject_connection_established(&mut self, peer_id: &PeerId, connection: &ConnectionId, endpoint: &ConnectedPoint) {
+        self.inner.inject_connection_established(peer_id, connection, endpoint)
+    }
+
+    fn inject_connection_closed(&mut self, peer_id: &PeerId, connection: &ConnectionId, endpoint: &ConnectedPoint) {
+        self.inner.inject_connection_closed(peer_id, connection, endpoint)
+    }
+
+    fn inject_node_event(&mut self, peer_id: PeerId, event: RequestResponseHandlerEvent<RendezvousCodec>) {
+        self.inner.inject_node_event(peer_id, event)
+    }
+
+    fn inject_connection_event(&mut self, peer_id: PeerId, connection: ConnectionId, event: RequestResponseHandlerEvent<RendezvousCodec>) {
+        self.inner.inject_connection_event(peer_id, connection, event)
+    }
+
+    fn inject_addr_reach_failure(&mut self, peer_id: Option<&PeerId>, addr: &Multiaddr, error: &dyn error::Error) {
+        self.inner.inject_addr_reach_failure(peer_id, addr, error)
+    }
+
+    fn inject_dial_failure(&mut self, peer_id: &PeerId) {
+        self.inner.inject_dial_failure(peer_id)
+    }
+
+    fn poll(&mut self, params: &mut impl PollParameters)
+        -> Async<NetworkBehaviourAction<RequestProtocol<RendezvousCodec>, Self::OutEvent>>
+    {
+        while let Some((rendezvous_node, namespace, ttl)) = self.to_register.pop_front() {
+            let mut addresses = params.external_addresses().collect::<Vec<_>>();
+            if addresses.is_empty() {
+                addresses = params.listened_addresses().collect();
+            }
+            if addresses.is_empty() {
+                log::debug!("Can't register in {}: no address to register", namespace);
+                self.pending_events.push_back(RendezvousEvent::RegisterFailed {
+                    rendezvous_node,
+                    namespace,
+                    error: ErrorCode::InvalidPeerInfo,
+                });
+                continue
+            }
+            let record = PeerRecord { peer_id: params.local_peer_id().clone(), addresses };
+            let message = Message::Register(NewRegistration { namespace: namespace.clone(), record, ttl });
+            let request_id = self.inner.send_request(&rendezvous_node, message);
+            self.outbound.insert(request_id, Outbound::Register(namespace));
+        }
+
+        loop {
+            if let Some(event) = self.pending_events.pop_front() {
+                return Async::Ready(NetworkBehaviourAction::GenerateEvent(event))
+            }
+
+            match self.inner.poll(params) {
+                Async::NotReady => return Async::NotReady,
+                Async::Ready(action) => match forward_action(action) {
+                    Ok(action) => return Async::Ready(action),
+                    Err(RequestResponseEvent::Message {
+                        peer,
+                        message: RequestResponseMessage::Response { request_id, response },
+                    }) => self.on_response(peer, request_id, response),
+                    Err(RequestResponseEvent::OutboundFailure { peer, request_id, error }) => {
+                        log::debug!("Rendezvous request to {:?} failed: {}", peer, error);
+                        self.on_failure(peer, request_id);
+                    }
+                    Err(_) => {}
+                },
+            }
+        }
+    }
+}
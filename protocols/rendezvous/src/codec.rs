@@ -0,0 +1,427 @@
+// Copyright 2019 Parity Technologies (UK) Ltd.
+//
+// Permission is hereby granted, free of charge, to any person obtaining a
+// copy of this software and associated documentation files (the "Software"),
+// to deal in the Software without restriction, including without limitation
+// the rights to use, copy, modify, merge, publish, distribute, sublicense,
+// and/or sell copies of the Software, and to permit persons to whom the
+// Software is furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS
+// OR IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING
+// FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
+// DEALINGS IN THE SOFTWARE.
+
+//! Messages of the rendezvous protocol and their encoding.
+
+use crate::proto::{self, message as proto_message};
+use libp2p_core::{Multiaddr, PeerId};
+use libp2p_request_response::RequestResponseCodec;
+use prost::{DecodeError, Message as _};
+use std::{convert::TryFrom, error, fmt, io};
+
+/// Name of the protocol negotiated on the substreams.
+pub const PROTOCOL_NAME: &[u8] = b"/rendezvous/1.0.0";
+
+/// Maximum length in bytes of a namespace.
+pub const MAX_NAMESPACE_LEN: usize = 255;
+
+/// TTL in seconds of the registrations that don't specify one.
+pub const DEFAULT_TTL: u64 = 60 * 60 * 2;
+
+/// Name under which peers register and are discovered.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct Namespace(String);
+
+impl Namespace {
+    /// Creates a namespace, which must be at most `MAX_NAMESPACE_LEN` bytes long.
+    pub fn new(value: impl Into<String>) -> Result<Self, NamespaceTooLong> {
+        let value = value.into();
+        if value.len() > MAX_NAMESPACE_LEN {
+            return Err(NamespaceTooLong)
+        }
+        Ok(Namespace(value))
+    }
+
+    /// Returns the namespace as a string.
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+}
+
+impl fmt::Display for Namespace {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+/// Error returned when creating a namespace longer than `MAX_NAMESPACE_LEN` bytes.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct NamespaceTooLong;
+
+impl fmt::Display for NamespaceTooLong {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Namespace longer than {} bytes", MAX_NAMESPACE_LEN)
+    }
+}
+
+impl error::Error for NamespaceTooLong {}
+
+/// Identity and addresses of a registered peer.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PeerRecord {
+    pub peer_id: PeerId,
+    /// Addresses of the peer. Addresses that couldn't be decoded are skipped.
+    pub addresses: Vec<Multiaddr>,
+}
+
+/// Registration requested by a peer.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct NewRegistration {
+    pub namespace: Namespace,
+    pub record: PeerRecord,
+    /// Requested TTL in seconds, or `None` for the default of the server.
+    pub ttl: Option<u64>,
+}
+
+/// Registration accepted by a rendezvous server.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Registration {
+    pub namespace: Namespace,
+    pub record: PeerRecord,
+    /// TTL in seconds.
+    pub ttl: u64,
+}
+
+/// Opaque value returned by a discovery, to be passed to the next one in order to only receive
+/// the registrations made in between.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Cookie {
+    pub(crate) id: u64,
+    pub(crate) namespace: Option<Namespace>,
+}
+
+impl Cookie {
+    /// Returns the namespace of the discovery that returned this cookie.
+    pub fn namespace(&self) -> Option<&Namespace> {
+        self.namespace.as_ref()
+    }
+
+    fn to_bytes(&self) -> Vec<u8> {
+        let mut out = self.id.to_be_bytes().to_vec();
+        if let Some(namespace) = &self.namespace {
+            out.extend_from_slice(namespace.as_str().as_bytes());
+        }
+        out
+    }
+
+    fn from_bytes(bytes: &[u8]) -> Result<Self, DecodeError> {
+        if bytes.len() < 8 {
+            return Err(DecodeError::new("invalid cookie"))
+        }
+        let mut id = [0; 8];
+        id.copy_from_slice(&bytes[..8]);
+        let namespace = if bytes.len() > 8 {
+            let namespace = String::from_utf8(bytes[8..].to_vec())
+                .map_err(|_| DecodeError::new("invalid cookie"))?;
+            Some(Namespace::new(namespace).map_err(|_| DecodeError::new("invalid cookie"))?)
+        } else {
+            None
+        };
+        Ok(Cookie { id: u64::from_be_bytes(id), namespace })
+    }
+}
+
+/// Reason why a rendezvous server refused a request.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum ErrorCode {
+    InvalidNamespace,
+    InvalidPeerInfo,
+    InvalidTtl,
+    InvalidCookie,
+    NotAuthorized,
+    InternalError,
+    Unavailable,
+}
+
+impl ErrorCode {
+    fn code(self) -> i32 {
+        match self {
+            ErrorCode::InvalidNamespace => 100,
+            ErrorCode::InvalidPeerInfo => 101,
+            ErrorCode::InvalidTtl => 102,
+            ErrorCode::InvalidCookie => 103,
+            ErrorCode::NotAuthorized => 200,
+            ErrorCode::InternalError => 300,
+            ErrorCode::Unavailable => 400,
+        }
+    }
+
+    fn from_code(code: i32) -> Option<Self> {
+        match code {
+            100 => Some(ErrorCode::InvalidNamespace),
+            101 => Some(ErrorCode::InvalidPeerInfo),
+            102 => Some(ErrorCode::InvalidTtl),
+            103 => Some(ErrorCode::InvalidCookie),
+            200 => Some(ErrorCode::NotAuthorized),
+            300 => Some(ErrorCode::InternalError),
+            400 => Some(ErrorCode::Unavailable),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for ErrorCode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ErrorCode::InvalidNamespace => write!(f, "E_INVALID_NAMESPACE"),
+            ErrorCode::InvalidPeerInfo => write!(f, "E_INVALID_PEER_INFO"),
+            ErrorCode::InvalidTtl => write!(f, "E_INVALID_TTL"),
+            ErrorCode::InvalidCookie => write!(f, "E_INVALID_COOKIE"),
+            ErrorCode::NotAuthorized => write!(f, "E_NOT_AUTHORIZED"),
+            ErrorCode::InternalError => write!(f, "E_INTERNAL_ERROR"),
+            ErrorCode::Unavailable => write!(f, "E_UNAVAILABLE"),
+        }
+    }
+}
+
+/// A message of the rendezvous protocol.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Message {
+    Register(NewRegistration),
+    /// The TTL in seconds of the registration, or the reason why it was refused.
+    RegisterResponse(Result<u64, ErrorCode>),
+    Unregister(Namespace),
+    Discover {
+        namespace: Option<Namespace>,
+        cookie: Option<Cookie>,
+        limit: Option<u64>,
+    },
+    DiscoverResponse(Result<(Vec<Registration>, Cookie), ErrorCode>),
+}
+
+const REGISTER: i32 = 0;
+const REGISTER_RESPONSE: i32 = 1;
+const UNREGISTER: i32 = 2;
+const DISCOVER: i32 = 3;
+const DISCOVER_RESPONSE: i32 = 4;
+
+impl Message {
+    /// Encodes the message, without its length prefix.
+    pub fn encode(&self) -> Vec<u8> {
+        let mut message = proto::Message::default();
+        match self {
+            Message::Register(registration) => {
+                message.r#type = Some(REGISTER);
+                message.register = Some(encode_register(&registration.namespace, &registration.record, registration.ttl));
+            }
+            Message::RegisterResponse(result) => {
+                message.r#type = Some(REGISTER_RESPONSE);
+                let (status, ttl) = match result {
+                    Ok(ttl) => (0, Some(*ttl)),
+                    Err(error) => (error.code(), None),
+                };
+                message.register_response = Some(proto_message::RegisterResponse {
+                    status: Some(status),
+                    status_text: None,
+                    ttl,
+                });
+            }
+            Message::Unregister(namespace) => {
+                message.r#type = Some(UNREGISTER);
+                message.unregister = Some(proto_message::Unregister {
+                    ns: Some(namespace.as_str().to_owned()),
+                    id: None,
+                });
+            }
+            Message::Discover { namespace, cookie, limit } => {
+                message.r#type = Some(DISCOVER);
+                message.discover = Some(proto_message::Discover {
+                    ns: namespace.as_ref().map(|namespace| namespace.as_str().to_owned()),
+                    limit: *limit,
+                    cookie: cookie.as_ref().map(Cookie::to_bytes),
+                });
+            }
+            Message::DiscoverResponse(result) => {
+                message.r#type = Some(DISCOVER_RESPONSE);
+                let response = match result {
+                    Ok((registrations, cookie)) => proto_message::DiscoverResponse {
+                        registrations: registrations
+                            .iter()
+                            .map(|r| encode_register(&r.namespace, &r.record, Some(r.ttl)))
+                            .collect(),
+                        cookie: Some(cookie.to_bytes()),
+                        status: Some(0),
+                        status_text: None,
+                    },
+                    Err(error) => proto_message::DiscoverResponse {
+                        status: Some(error.code()),
+                        ..Default::default()
+                    },
+                };
+                message.discover_response = Some(response);
+            }
+        }
+
+        let mut buf = Vec::with_capacity(message.encoded_len());
+        message.encode(&mut buf).expect("Vec<u8> provides capacity as needed");
+        buf
+    }
+
+    /// Decodes a message, without its length prefix.
+    pub fn decode(bytes: &[u8]) -> Result<Self, DecodeError> {
+        let message = proto::Message::decode(bytes)?;
+        match message.r#type.ok_or_else(|| DecodeError::new("missing message type"))? {
+            REGISTER => {
+                let register = message.register.ok_or_else(missing_body)?;
+                let (namespace, record, ttl) = decode_register(register)?;
+                Ok(Message::Register(NewRegistration { namespace, record, ttl }))
+            }
+            REGISTER_RESPONSE => {
+                let response = message.register_response.ok_or_else(missing_body)?;
+                let result = match response.status.ok_or_else(|| DecodeError::new("missing status"))? {
+                    0 => Ok(response.ttl.unwrap_or(DEFAULT_TTL)),
+                    code => Err(ErrorCode::from_code(code).ok_or_else(|| DecodeError::new("unknown status"))?),
+                };
+                Ok(Message::RegisterResponse(result))
+            }
+            UNREGISTER => {
+                let unregister = message.unregister.ok_or_else(missing_body)?;
+                let namespace = unregister.ns.ok_or_else(|| DecodeError::new("missing namespace"))?;
+                Ok(Message::Unregister(decode_namespace(namespace)?))
+            }
+            DISCOVER => {
+                let discover = message.discover.ok_or_else(missing_body)?;
+                Ok(Message::Discover {
+                    namespace: discover.ns.map(decode_namespace).transpose()?,
+                    cookie: discover.cookie.map(|cookie| Cookie::from_bytes(&cookie)).transpose()?,
+                    limit: discover.limit,
+                })
+            }
+            DISCOVER_RESPONSE => {
+                let response = message.discover_response.ok_or_else(missing_body)?;
+                let result = match response.status.unwrap_or(0) {
+                    0 => {
+                        let mut registrations = Vec::with_capacity(response.registrations.len());
+                        for register in response.registrations {
+                            let (namespace, record, ttl) = decode_register(register)?;
+                            let ttl = ttl.unwrap_or(DEFAULT_TTL);
+                            registrations.push(Registration { namespace, record, ttl });
+                        }
+                        let cookie = response.cookie.ok_or_else(|| DecodeError::new("missing cookie"))?;
+                        Ok((registrations, Cookie::from_bytes(&cookie)?))
+                    }
+                    code => Err(ErrorCode::from_code(code).ok_or_else(|| DecodeError::new("unknown status"))?),
+                };
+                Ok(Message::DiscoverResponse(result))
+            }
+            _ => Err(DecodeError::new("unknown message type")),
+        }
+    }
+}
+
+fn missing_body() -> DecodeError {
+    DecodeError::new("missing message body")
+}
+
+fn encode_register(namespace: &Namespace, record: &PeerRecord, ttl: Option<u64>) -> proto_message::Register {
+    proto_message::Register {
+        ns: Some(namespace.as_str().to_owned()),
+        peer: Some(proto_message::PeerInfo {
+            id: Some(record.peer_id.as_bytes().to_vec()),
+            addrs: record.addresses.iter().map(Multiaddr::to_vec).collect(),
+        }),
+        ttl,
+    }
+}
+
+fn decode_register(register: proto_message::Register) -> Result<(Namespace, PeerRecord, Option<u64>), DecodeError> {
+    let namespace = register.ns.ok_or_else(|| DecodeError::new("missing namespace"))?;
+    let peer = register.peer.ok_or_else(|| DecodeError::new("missing peer"))?;
+    let peer_id = peer.id.ok_or_else(|| DecodeError::new("missing peer ID"))?;
+    let peer_id = PeerId::from_bytes(peer_id).map_err(|_| DecodeError::new("invalid peer ID"))?;
+    let addresses = peer.addrs
+        .into_iter()
+        .filter_map(|addr| Multiaddr::try_from(addr).ok())
+        .collect();
+    Ok((decode_namespace(namespace)?, PeerRecord { peer_id, addresses }, register.ttl))
+}
+
+fn decode_namespace(namespace: String) -> Result<Namespace, DecodeError> {
+    Namespace::new(namespace).map_err(|_| DecodeError::new("namespace too long"))
+}
+
+/// Codec of the rendezvous protocol, used with the `RequestResponse` behaviour.
+#[derive(Debug, Copy, Clone, Default)]
+pub struct RendezvousCodec;
+
+impl RequestResponseCodec for RendezvousCodec {
+    type Request = Message;
+    type Response = Message;
+
+    fn encode_request(&self, request: Message) -> Vec<u8> {
+        request.encode()
+    }
+
+    fn decode_request(&self, bytes: Vec<u8>) -> Result<Message, io::Error> {
+        Message::decode(&bytes).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+    }
+
+    fn encode_response(&self, response: Message) -> Vec<u8> {
+        response.encode()
+    }
+
+    fn decode_response(&self, bytes: Vec<u8>) -> Result<Message, io::Error> {
+        Message::decode(&bytes).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use libp2p_core::multiaddr::multiaddr;
+
+    fn record() -> PeerRecord {
+        PeerRecord {
+            peer_id: PeerId::random(),
+            addresses: vec![multiaddr![Ip4([1, 2, 3, 4]), Tcp(4001u16)]],
+        }
+    }
+
+    #[test]
+    fn encode_decode_roundtrip() {
+        let namespace = Namespace::new("chat").unwrap();
+        let messages = vec![
+            Message::Register(NewRegistration { namespace: namespace.clone(), record: record(), ttl: Some(3600) }),
+            Message::RegisterResponse(Ok(3600)),
+            Message::RegisterResponse(Err(ErrorCode::InvalidTtl)),
+            Message::Unregister(namespace.clone()),
+            Message::Discover {
+                namespace: Some(namespace.clone()),
+                cookie: Some(Cookie { id: 12, namespace: Some(namespace.clone()) }),
+                limit: Some(10),
+            },
+            Message::Discover { namespace: None, cookie: None, limit: None },
+            Message::DiscoverResponse(Ok((
+                vec![Registration { namespace: namespace.clone(), record: record(), ttl: 60 }],
+                Cookie { id: 13, namespace: None },
+            ))),
+            Message::DiscoverResponse(Err(ErrorCode::InvalidCookie)),
+        ];
+        for message in messages {
+            assert_eq!(Message::decode(&message.encode()), Ok(message));
+        }
+    }
+
+    #[test]
+    fn namespace_length() {
+        assert!(Namespace::new("a".repeat(MAX_NAMESPACE_LEN)).is_ok());
+        assert_eq!(Namespace::new("a".repeat(MAX_NAMESPACE_LEN + 1)), Err(NamespaceTooLong));
+    }
+}
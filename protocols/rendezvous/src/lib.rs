@@ -0,0 +1,95 @@
+// Copyright 2019 Parity Technologies (UK) Ltd.
+//
+// Permission is hereby granted, free of charge, to any person obtaining a
+// copy of this software and associated documentation files (the "Software"),
+// to deal in the Software without restriction, including without limitation
+// the rights to use, copy, modify, merge, publish, distribute, sublicense,
+// and/or sell copies of the Software, and to permit persons to whom the
+// Software is furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS
+// OR IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING
+// FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
+// DEALINGS IN THE SOFTWARE.
+
+//! Implementation of the [rendezvous] protocol.
+//!
+//! Peers register under namespaces at a rendezvous node, for a limited time, and discover the
+//! other peers registered under a namespace by asking the rendezvous node. This is a much
+//! lighter discovery mechanism than a DHT when the peers of an application know a few common
+//! rendezvous nodes.
+//!
+//! The [`Rendezvous`] network behaviour registers the local node and discovers other peers,
+//! while the [`RendezvousServer`] network behaviour acts as a rendezvous node. Both are built
+//! on the `libp2p-request-response` crate.
+//!
+//! [rendezvous]: https://github.com/libp2p/specs/tree/master/rendezvous
+
+mod client;
+mod codec;
+mod server;
+
+/// Protobuf messages of the protocol, generated from `rpc.proto`.
+mod proto {
+    include!(concat!(env!("OUT_DIR"), "/rendezvous.pb.rs"));
+}
+
+pub use client::{Rendezvous, RendezvousEvent};
+pub use codec::{
+    Cookie,
+    ErrorCode,
+    Message,
+    Namespace,
+    NamespaceTooLong,
+    NewRegistration,
+    PeerRecord,
+    Registration,
+    RendezvousCodec,
+    DEFAULT_TTL,
+    MAX_NAMESPACE_LEN,
+    PROTOCOL_NAME
+};
+pub use server::{RendezvousServer, RendezvousServerConfig, RendezvousServerEvent};
+
+use libp2p_request_response::{RequestProtocol, RequestResponseEvent};
+use libp2p_swarm::NetworkBehaviourAction;
+
+/// Converts an action of the inner `RequestResponse` behaviour into an action of the wrapping
+/// behaviour, or returns the event to process if the action is `GenerateEvent`.
+fn forward_action<TOutEvent>(
+    action: NetworkBehaviourAction<RequestProtocol<RendezvousCodec>, RequestResponseEvent<Message, Message>>
+) -> Result<NetworkBehaviourAction<RequestProtocol<RendezvousCodec>, TOutEvent>, RequestResponseEvent<Message, Message>> {
+    Ok(match action {
+        NetworkBehaviourAction::GenerateEvent(event) => return Err(event),
+        NetworkBehaviourAction::DialAddress { address } => NetworkBehaviourAction::DialAddress { address },
+        NetworkBehaviourAction::DialPeer { peer_id } => NetworkBehaviourAction::DialPeer { peer_id },
+        NetworkBehaviourAction::SendEvent { peer_id, event } => NetworkBehaviourAction::SendEvent { peer_id, event },
+        NetworkBehaviourAction::SendEventToConnection { peer_id, connection, event } => {
+            NetworkBehaviourAction::SendEventToConnection { peer_id, connection, event }
+        }
+        NetworkBehaviourAction::ReportObservedAddr { address, observer } => {
+            NetworkBehaviourAction::ReportObservedAddr { address, observer }
+        }
+        NetworkBehaviourAction::ReportRemoteProtocols { peer_id, protocols } => {
+            NetworkBehaviourAction::ReportRemoteProtocols { peer_id, protocols }
+        }
+        NetworkBehaviourAction::ReportRoundTripTime { peer_id, rtt } => {
+            NetworkBehaviourAction::ReportRoundTripTime { peer_id, rtt }
+        }
+        NetworkBehaviourAction::ReportTransfer { peer_id, bytes_received, bytes_sent, duration } => {
+            NetworkBehaviourAction::ReportTransfer { peer_id, bytes_received, bytes_sent, duration }
+        }
+        NetworkBehaviourAction::ReportExternalAddr { address, source } => {
+            NetworkBehaviourAction::ReportExternalAddr { address, source }
+        }
+        NetworkBehaviourAction::RemoveExternalAddr { address } => {
+            NetworkBehaviourAction::RemoveExternalAddr { address }
+        }
+    })
+}
@@ -0,0 +1,414 @@
+// Copyright 2019 Parity Technologies (UK) Ltd.
+//
+// Permission is hereby granted, free of charge, to any person obtaining a
+// copy of this software and associated documentation files (the "Software"),
+// to deal in the Software without restriction, including without limitation
+// the rights to use, copy, modify, merge, publish, distribute, sublicense,
+// and/or sell copies of the Software, and to permit persons to whom the
+// Software is furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS
+// OR IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING
+// FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
+// DEALINGS IN THE SOFTWARE.
+
+//! Rendezvous server, storing the registrations of peers and answering the discoveries.
+
+use crate::codec::{Cookie, ErrorCode, Message, Namespace, NewRegistration, Registration, RendezvousCodec, PROTOCOL_NAME};
+use crate::forward_action;
+use futures::prelude::*;
+use libp2p_core::{ConnectedPoint, Multiaddr, PeerId};
+use libp2p_request_response::{
+    RequestProtocol,
+    RequestResponse,
+    RequestResponseConfig,
+    RequestResponseEvent,
+    RequestResponseHandler,
+    RequestResponseHandlerEvent,
+    RequestResponseMessage,
+    ResponseChannel
+};
+use libp2p_swarm::{ConnectionId, NetworkBehaviour, NetworkBehaviourAction, PollParameters};
+use std::{collections::{HashMap, VecDeque}, error, iter, time::Duration};
+use tokio_io::{AsyncRead, AsyncWrite};
+use wasm_timer::{Delay, Instant};
+
+/// Configuration of the [`RendezvousServer`] network behaviour.
+#[derive(Debug, Clone)]
+pub struct RendezvousServerConfig {
+    min_ttl: u64,
+    max_ttl: u64,
+    max_registrations: usize,
+}
+
+impl Default for RendezvousServerConfig {
+    fn default() -> Self {
+        RendezvousServerConfig {
+            min_ttl: 60 * 60 * 2,
+            max_ttl: 60 * 60 * 72,
+            max_registrations: 10_000,
+        }
+    }
+}
+
+impl RendezvousServerConfig {
+    /// Sets the minimum and maximum TTL, in seconds, that peers can register with. Defaults to
+    /// 2 and 72 hours.
+    ///
+    /// # Panic
+    ///
+    /// Panics if `min` is larger than `max`.
+    pub fn with_ttl_bounds(mut self, min: u64, max: u64) -> Self {
+        assert!(min <= max, "the minimum TTL must not exceed the maximum TTL");
+        self.min_ttl = min;
+        self.max_ttl = max;
+        self
+    }
+
+    /// Sets the maximum number of registrations stored at the same time. Defaults to 10000.
+    pub fn with_max_registrations(mut self, max: usize) -> Self {
+        self.max_registrations = max;
+        self
+    }
+}
+
+/// Event generated by the `RendezvousServer` network behaviour.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RendezvousServerEvent {
+    /// A peer registered, or renewed its registration, in a namespace.
+    PeerRegistered { peer: PeerId, registration: Registration },
+    /// We refused the registration of a peer.
+    PeerNotRegistered { peer: PeerId, namespace: Namespace, error: ErrorCode },
+    /// A peer unregistered from a namespace.
+    PeerUnregistered { peer: PeerId, namespace: Namespace },
+    /// We answered the discovery of a peer.
+    DiscoverServed { enquirer: PeerId, registrations: Vec<Registration> },
+    /// We refused the discovery of a peer.
+    DiscoverNotServed { enquirer: PeerId, error: ErrorCode },
+    /// A registration expired.
+    RegistrationExpired(Registration),
+}
+
+/// A registration stored by the server.
+struct StoredRegistration {
+    /// Increasing identifier, used by the cookies to skip the registrations already discovered.
+    id: u64,
+    registration: Registration,
+    expires: Instant,
+}
+
+/// Network behaviour acting as a rendezvous server.
+///
+/// Stores the registrations of the peers for the requested TTL, bounded by the configuration,
+/// and answers the discoveries with the registrations of a namespace, or of all namespaces.
+/// Peers can only register themselves.
+pub struct RendezvousServer<TSubstream> {
+    /// The behaviour answering the requests.
+    inner: RequestResponse<TSubstream, RendezvousCodec>,
+    /// Configuration options.
+    config: RendezvousServerConfig,
+    /// The registrations, by namespace and peer.
+    registrations: HashMap<(Namespace, PeerId), StoredRegistration>,
+    /// Identifier of the next registration.
+    next_id: u64,
+    /// Fires when the earliest registration expires, if any.
+    next_expiration: Option<Delay>,
+    /// Queue of events to return to the swarm.
+    pending_events: VecDeque<RendezvousServerEvent>,
+}
+
+impl<TSubstream> RendezvousServer<TSubstream> {
+    /// Creates a `RendezvousServer` behaviour with the given configuration.
+    pub fn new(config: RendezvousServerConfig) -> Self {
+        let inner_config = RequestResponseConfig::default().with_max_retries(0);
+        RendezvousServer {
+            inner: RequestResponse::new(RendezvousCodec, iter::once(PROTOCOL_NAME), inner_config),
+            config,
+            registrations: HashMap::new(),
+            next_id: 1,
+            next_expiration: None,
+            pending_events: VecDeque::new(),
+        }
+    }
+
+    /// Returns the registrations currently stored.
+    pub fn registrations(&self) -> impl Iterator<Item = &Registration> {
+        self.registrations.values().map(|r| &r.registration)
+    }
+
+    /// Arms the timer for the earliest expiration.
+    fn update_next_expiration(&mut self) {
+        self.next_expiration = self.registrations.values()
+            .map(|r| r.expires)
+            .min()
+            .map(Delay::new);
+    }
+
+    /// Stores a registration, or returns the reason why it is refused.
+    fn register(&mut self, peer: &PeerId, new: NewRegistration) -> Result<Registration, ErrorCode> {
+        if &new.record.peer_id != peer {
+            return Err(ErrorCode::NotAuthorized)
+        }
+        if new.record.addresses.is_empty() {
+            return Err(ErrorCode::InvalidPeerInfo)
+        }
+        let ttl = new.ttl.unwrap_or(self.config.min_ttl);
+        if ttl < self.config.min_ttl || ttl > self.config.max_ttl {
+            return Err(ErrorCode::InvalidTtl)
+        }
+        let key = (new.namespace.clone(), peer.clone());
+        if !self.registrations.contains_key(&key) && self.registrations.len() >= self.config.max_registrations {
+            return Err(ErrorCode::Unavailable)
+        }
+
+        let registration = Registration { namespace: new.namespace, record: new.record, ttl };
+        let stored = StoredRegistration {
+            id: self.next_id,
+            registration: registration.clone(),
+            expires: Instant::now() + Duration::from_secs(ttl),
+        };
+        self.next_id += 1;
+        self.registrations.insert(key, stored);
+        self.update_next_expiration();
+        Ok(registration)
+    }
+
+    /// Returns the registrations made since the cookie, or the reason why the discovery is
+    /// refused.
+    fn discover(&self, namespace: Option<Namespace>, cookie: Option<Cookie>, limit: Option<u64>)
+        -> Result<(Vec<Registration>, Cookie), ErrorCode>
+    {
+        let since = match cookie {
+            Some(cookie) if cookie.namespace != namespace => return Err(ErrorCode::InvalidCookie),
+            Some(cookie) => cookie.id,
+            None => 0,
+        };
+
+        let mut found = self.registrations.values()
+            .filter(|r| r.id > since)
+            .filter(|r| namespace.as_ref().map_or(true, |ns| &r.registration.namespace == ns))
+            .collect::<Vec<_>>();
+        found.sort_by_key(|r| r.id);
+        if let Some(limit) = limit {
+            found.truncate(limit as usize);
+        }
+
+        let last = found.last().map_or(since, |r| r.id);
+        let registrations = found.into_iter().map(|r| r.registration.clone()).collect();
+        Ok((registrations, Cookie { id: last, namespace }))
+    }
+
+    /// Processes a request received from a peer.
+    fn on_request(&mut self, peer: PeerId, request: Message, channel: ResponseChannel<Message>) {
+        let (response, event) = match request {
+            Message::Register(new) => {
+                let namespace = new.namespace.clone();
+                match self.register(&peer, new) {
+                    Ok(registration) => (
+                        Message::RegisterResponse(Ok(registration.ttl)),
+                        RendezvousServerEvent::PeerRegistered { peer, registration },
+                    ),
+                    Err(error) => (
+                        Message::RegisterResponse(Err(error)),
+                        RendezvousServerEvent::PeerNotRegistered { peer, namespace, error },
+                    ),
+                }
+            }
+            Message::Unregister(namespace) => {
+                // Unregistrations aren't answered: dropping the channel closes the substream.
+                if self.registrations.remove(&(namespace.clone(), peer.clone())).is_some() {
+                    self.update_next_expiration();
+                    self.pending_events.push_back(RendezvousServerEvent::PeerUnregistered { peer, namespace });
+                }
+                return
+            }
+            Message::Discover { namespace, cookie, limit } => {
+                match self.discover(namespace, cookie, limit) {
+                    Ok((registrations, cookie)) => (
+                        Message::DiscoverResponse(Ok((registrations.clone(), cookie))),
+                        RendezvousServerEvent::DiscoverServed { enquirer: peer, registrations },
+                    ),
+                    Err(error) => (
+                        Message::DiscoverResponse(Err(error)),
+                        RendezvousServerEvent::DiscoverNotServed { enquirer: peer, error },
+                    ),
+                }
+            }
+            Message::RegisterResponse(_) | Message::DiscoverResponse(_) => {
+                log::debug!("Ignoring unexpected rendezvous response from {:?}", peer);
+                return
+            }
+        };
+        self.inner.send_response(channel, response);
+        self.pending_events.push_back(event);
+    }
+
+    /// Removes the expired registrations.
+    fn expire(&mut self) {
+        let now = Instant::now();
+        let expired = self.registrations.iter()
+            .filter(|(_, r)| r.expires <= now)
+            .map(|(key, _)| key.clone())
+            .collect::<Vec<_>>();
+        for key in expired {
+            if let Some(stored) = self.registrations.remove(&key) {
+                self.pending_events.push_back(RendezvousServerEvent::RegistrationExpired(stored.registration));
+            }
+        }
+        self.update_next_expiration();
+    }
+}
+
+impl<TSubstream> NetworkBehaviour for RendezvousServer<TSubstream>
+where
+    TSubstream: AsyncRead + AsyncWrite,
+{
+    type ProtocolsHandler = RequestResponseHandler<TSubstream, RendezvousCodec>;
+    type OutEvent = RendezvousServerEvent;
+
+    fn new_handler(&mut self) -> Self::ProtocolsHandler {
+        self.inner.new_handler()
+    }
+
+    fn addresses_of_peer(&mut self, peer_id: &PeerId) -> Vec<Multiaddr> {
+        self.inner.addresses_of_peer(peer_id)
+    }
+
+    fn inject_connected(&mut self, peer_id: PeerId, endpoint: ConnectedPoint) {
+        self.inner.inject_connected(peer_id, endpoint)
+    }
+
+    fn inject_disconnected(&mut self, peer_id: &PeerId, endpoint: ConnectedPoint) {
+        self.inner.inject_disconnected(peer_id, endpoint)
+    }
+
+    fn inject_connection_established(&mut self, peer_id: &PeerId, connection: &ConnectionId, endpoint: &ConnectedPoint) {
+        self.inner.inject_connection_established(peer_id, connection, endpoint)
+    }
+
+    fn inject_connection_closed(&mut self, peer_id: &PeerId, connection: &ConnectionId, endpoint: &ConnectedPoint) {
+        self.inner.inject_connection_closed(peer_id, connection, endpoint)
+    }
+
+    fn inject_node_event(&mut self, peer_id: PeerId, event: RequestResponseHandlerEvent<RendezvousCodec>) {
+        self.inner.inject_node_event(peer_id, event)
+    }
+
+    fn inject_connection_event(&mut self, peer_id: PeerId, connection: ConnectionId, event: RequestResponseHandlerEvent<RendezvousCodec>) {
+        self.inner.inject_connection_event(peer_id, connection, event)
+    }
+
+    fn inject_addr_reach_failure(&mut self, peer_id: Option<&PeerId>, addr: &Multiaddr, error: &dyn error::Error) {
+        self.inner.inject_addr_reach_failure(peer_id, addr, error)
+    }
+
+    fn inject_dial_failure(&mut self, peer_id: &PeerId) {
+        self.inner.inject_dial_failure(peer_id)
+    }
+
+    fn poll(&mut self, params: &mut impl PollParameters)
+        -> Async<NetworkBehaviourAction<RequestProtocol<RendezvousCodec>, Self::OutEvent>>
+    {
+        loop {
+            if let Some(event) = self.pending_events.pop_front() {
+                return Async::Ready(NetworkBehaviourAction::GenerateEvent(event))
+            }
+
+            let expired = match &mut self.next_expiration {
+                Some(delay) => match delay.poll() {
+                    Ok(Async::NotReady) => false,
+                    Ok(Async::Ready(())) => true,
+                    Err(err) => {
+                        log::warn!("Rendezvous expiration timer errored: {}", err);
+                        true
+                    }
+                },
+                None => false,
+            };
+            if expired {
+                self.expire();
+                continue
+            }
+
+            match self.inner.poll(params) {
+                Async::NotReady => return Async::NotReady,
+                Async::Ready(action) => match forward_action(action) {
+                    Ok(action) => return Async::Ready(action),
+                    Err(RequestResponseEvent::Message {
+                        peer,
+                        message: RequestResponseMessage::Request { request, channel, .. },
+                    }) => self.on_request(peer, request, channel),
+                    Err(RequestResponseEvent::InboundFailure { peer, error, .. }) => {
+                        log::debug!("Failed to answer the rendezvous request of {:?}: {}", peer, error);
+                    }
+                    Err(_) => {}
+                },
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::codec::PeerRecord;
+    use libp2p_core::multiaddr::multiaddr;
+    use std::io;
+
+    type TestServer = RendezvousServer<io::Cursor<Vec<u8>>>;
+
+    fn new_registration(peer: &PeerId, namespace: &str, ttl: Option<u64>) -> NewRegistration {
+        NewRegistration {
+            namespace: Namespace::new(namespace).unwrap(),
+            record: PeerRecord {
+                peer_id: peer.clone(),
+                addresses: vec![multiaddr![Ip4([1, 2, 3, 4]), Tcp(4001u16)]],
+            },
+            ttl,
+        }
+    }
+
+    #[test]
+    fn only_registers_sender() {
+        let mut server = TestServer::new(RendezvousServerConfig::default());
+        let peer = PeerId::random();
+        let other = PeerId::random();
+        assert_eq!(server.register(&peer, new_registration(&other, "chat", None)), Err(ErrorCode::NotAuthorized));
+        assert!(server.register(&peer, new_registration(&peer, "chat", None)).is_ok());
+    }
+
+    #[test]
+    fn ttl_bounds() {
+        let mut server = TestServer::new(RendezvousServerConfig::default().with_ttl_bounds(60, 120));
+        let peer = PeerId::random();
+        assert_eq!(server.register(&peer, new_registration(&peer, "chat", Some(30))), Err(ErrorCode::InvalidTtl));
+        assert_eq!(server.register(&peer, new_registration(&peer, "chat", Some(121))), Err(ErrorCode::InvalidTtl));
+        assert_eq!(server.register(&peer, new_registration(&peer, "chat", None)).map(|r| r.ttl), Ok(60));
+    }
+
+    #[test]
+    fn cookie_skips_discovered_registrations() {
+        let mut server = TestServer::new(RendezvousServerConfig::default());
+        let chat = Some(Namespace::new("chat").unwrap());
+        let (a, b, c) = (PeerId::random(), PeerId::random(), PeerId::random());
+        server.register(&a, new_registration(&a, "chat", None)).unwrap();
+        server.register(&b, new_registration(&b, "files", None)).unwrap();
+        server.register(&c, new_registration(&c, "chat", None)).unwrap();
+
+        let (found, cookie) = server.discover(chat.clone(), None, Some(1)).unwrap();
+        assert_eq!(found.iter().map(|r| r.record.peer_id.clone()).collect::<Vec<_>>(), vec![a.clone()]);
+        let (found, cookie) = server.discover(chat.clone(), Some(cookie), None).unwrap();
+        assert_eq!(found.iter().map(|r| r.record.peer_id.clone()).collect::<Vec<_>>(), vec![c]);
+        let (found, cookie) = server.discover(chat.clone(), Some(cookie), None).unwrap();
+        assert!(found.is_empty());
+
+        assert_eq!(server.discover(None, Some(cookie), None), Err(ErrorCode::InvalidCookie));
+        assert_eq!(server.discover(None, None, None).unwrap().0.len(), 3);
+    }
+}
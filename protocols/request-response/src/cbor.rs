@@ -0,0 +1,98 @@
+// Copyright 2019 Parity Technologies (UK) Ltd.
+//
+// Permission is hereby granted, free of charge, to any person obtaining a
+// copy of this software and associated documentation files (the "Software"),
+// to deal in the Software without restriction, including without limitation
+// the rights to use, copy, modify, merge, publish, distribute, sublicense,
+// and/or sell copies of the Software, and to permit persons to whom the
+// Software is furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS
+// OR IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING
+// FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
+// DEALINGS IN THE SOFTWARE.
+
+//! A [`RequestResponseCodec`] encoding requests and responses in CBOR with serde.
+//!
+//! The size of the messages is bounded by
+//! [`RequestResponseConfig::with_max_message_size`](crate::RequestResponseConfig::with_max_message_size):
+//! larger messages are rejected before being decoded.
+
+use crate::codec::RequestResponseCodec;
+use serde::{de::DeserializeOwned, Serialize};
+use std::{fmt, io, marker::PhantomData};
+
+/// Codec encoding requests of type `TRequest` and responses of type `TResponse` in CBOR.
+pub struct CborCodec<TRequest, TResponse> {
+    marker: PhantomData<fn() -> (TRequest, TResponse)>,
+}
+
+impl<TRequest, TResponse> CborCodec<TRequest, TResponse> {
+    /// Creates a new `CborCodec`.
+    pub fn new() -> Self {
+        CborCodec { marker: PhantomData }
+    }
+}
+
+impl<TRequest, TResponse> Default for CborCodec<TRequest, TResponse> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<TRequest, TResponse> Clone for CborCodec<TRequest, TResponse> {
+    fn clone(&self) -> Self {
+        Self::new()
+    }
+}
+
+impl<TRequest, TResponse> fmt::Debug for CborCodec<TRequest, TResponse> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("CborCodec").finish()
+    }
+}
+
+impl<TRequest, TResponse> RequestResponseCodec for CborCodec<TRequest, TResponse>
+where
+    TRequest: Serialize + DeserializeOwned,
+    TResponse: Serialize + DeserializeOwned,
+{
+    type Request = TRequest;
+    type Response = TResponse;
+
+    fn encode_request(&self, request: TRequest) -> Vec<u8> {
+        encode(&request)
+    }
+
+    fn decode_request(&self, bytes: Vec<u8>) -> Result<TRequest, io::Error> {
+        decode(&bytes)
+    }
+
+    fn encode_response(&self, response: TResponse) -> Vec<u8> {
+        encode(&response)
+    }
+
+    fn decode_response(&self, bytes: Vec<u8>) -> Result<TResponse, io::Error> {
+        decode(&bytes)
+    }
+}
+
+/// Encodes a value in CBOR.
+///
+/// # Panic
+///
+/// Panics if the `Serialize` implementation of the value fails, which never happens for
+/// derived implementations.
+fn encode<T: Serialize>(value: &T) -> Vec<u8> {
+    serde_cbor::to_vec(value).expect("Serializing to CBOR only fails if a Serialize implementation fails")
+}
+
+fn decode<T: DeserializeOwned>(bytes: &[u8]) -> Result<T, io::Error> {
+    serde_cbor::from_slice(bytes).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+}
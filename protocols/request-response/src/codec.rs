@@ -0,0 +1,46 @@
+// Copyright 2019 Parity Technologies (UK) Ltd.
+//
+// Permission is hereby granted, free of charge, to any person obtaining a
+// copy of this software and associated documentation files (the "Software"),
+// to deal in the Software without restriction, including without limitation
+// the rights to use, copy, modify, merge, publish, distribute, sublicense,
+// and/or sell copies of the Software, and to permit persons to whom the
+// Software is furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS
+// OR IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING
+// FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
+// DEALINGS IN THE SOFTWARE.
+
+use std::io;
+
+/// A `RequestResponseCodec` defines the request and response types of a
+/// [`RequestResponse`](crate::RequestResponse) protocol and how they are
+/// encoded in the messages exchanged on a substream.
+///
+/// Each request and each response is sent as a single length-prefixed message,
+/// whose size is bounded by [`RequestResponseConfig::with_max_message_size`](crate::RequestResponseConfig::with_max_message_size).
+pub trait RequestResponseCodec: Clone {
+    /// The type of requests sent to remotes.
+    type Request;
+    /// The type of responses sent back for requests.
+    type Response;
+
+    /// Encodes a request into the bytes of a message.
+    fn encode_request(&self, request: Self::Request) -> Vec<u8>;
+
+    /// Decodes a request from the bytes of a message.
+    fn decode_request(&self, bytes: Vec<u8>) -> Result<Self::Request, io::Error>;
+
+    /// Encodes a response into the bytes of a message.
+    fn encode_response(&self, response: Self::Response) -> Vec<u8>;
+
+    /// Decodes a response from the bytes of a message.
+    fn decode_response(&self, bytes: Vec<u8>) -> Result<Self::Response, io::Error>;
+}
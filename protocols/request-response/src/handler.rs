@@ -0,0 +1,289 @@
+// Copyright 2019 Parity Technologies (UK) Ltd.
+//
+// Permission is hereby granted, free of charge, to any person obtaining a
+// copy of this software and associated documentation files (the "Software"),
+// to deal in the Software without restriction, including without limitation
+// the rights to use, copy, modify, merge, publish, distribute, sublicense,
+// and/or sell copies of the Software, and to permit persons to whom the
+// Software is furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS
+// OR IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING
+// FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
+// DEALINGS IN THE SOFTWARE.
+
+use crate::{RequestResponseConfig, InboundFailure, OutboundFailure};
+use crate::codec::RequestResponseCodec;
+use crate::protocol::{RequestId, RequestProtocol, ResponseProtocol};
+use futures::{prelude::*, sync::oneshot};
+use libp2p_core::upgrade::{self, Negotiated, UpgradeError};
+use libp2p_swarm::{
+    KeepAlive,
+    SubstreamProtocol,
+    ProtocolsHandler,
+    ProtocolsHandlerEvent,
+    ProtocolsHandlerUpgrErr
+};
+use smallvec::SmallVec;
+use std::{collections::VecDeque, sync::{Arc, atomic::{AtomicU64, Ordering}}};
+use tokio_io::{AsyncRead, AsyncWrite};
+use wasm_timer::{Delay, Instant};
+use void::Void;
+
+/// Event produced by the `RequestResponseHandler`.
+pub enum RequestResponseHandlerEvent<TCodec>
+where
+    TCodec: RequestResponseCodec,
+{
+    /// A request has been received.
+    Request {
+        request_id: RequestId,
+        request: TCodec::Request,
+        /// Channel on which the behaviour sends the response.
+        sender: oneshot::Sender<TCodec::Response>,
+    },
+    /// The response to an outbound request has been received.
+    Response {
+        request_id: RequestId,
+        response: TCodec::Response,
+    },
+    /// An outbound request failed.
+    OutboundFailure {
+        request_id: RequestId,
+        error: OutboundFailure,
+    },
+    /// An inbound request failed.
+    InboundFailure {
+        request_id: RequestId,
+        error: InboundFailure,
+    },
+}
+
+/// Protocol handler sending requests on new outbound substreams and answering requests received
+/// on inbound substreams.
+pub struct RequestResponseHandler<TSubstream, TCodec>
+where
+    TCodec: RequestResponseCodec,
+{
+    /// The codec of the protocol.
+    codec: TCodec,
+    /// The names of the supported protocols, by order of preference.
+    protocols: SmallVec<[Vec<u8>; 2]>,
+    /// Configuration options.
+    config: RequestResponseConfig,
+    /// Counter shared with the behaviour, used to build the identifiers of inbound requests.
+    next_request_id: Arc<AtomicU64>,
+    /// Value to return from `connection_keep_alive`.
+    keep_alive: KeepAlive,
+    /// Outbound requests for which a substream must be opened.
+    outbound: VecDeque<RequestProtocol<TCodec>>,
+    /// Number of outbound requests whose substream is being opened or negotiated.
+    num_pending_outbound: usize,
+    /// Inbound requests that haven't been answered yet.
+    inbound: Vec<InboundRequest<TSubstream, TCodec>>,
+    /// Events to produce in `poll()`.
+    pending_events: VecDeque<RequestResponseHandlerEvent<TCodec>>,
+}
+
+/// An inbound request and the state of its response.
+struct InboundRequest<TSubstream, TCodec>
+where
+    TCodec: RequestResponseCodec,
+{
+    request_id: RequestId,
+    /// Fires if the response doesn't get sent in time.
+    timeout: Delay,
+    state: InboundState<TSubstream, TCodec>,
+}
+
+enum InboundState<TSubstream, TCodec>
+where
+    TCodec: RequestResponseCodec,
+{
+    /// Waiting for the behaviour to provide the response.
+    WaitResponse(Negotiated<TSubstream>, oneshot::Receiver<TCodec::Response>),
+    /// Sending the response.
+    SendResponse(upgrade::WriteOne<Negotiated<TSubstream>, Vec<u8>>),
+    /// A problem happened during the processing.
+    Poisoned,
+}
+
+impl<TSubstream, TCodec> RequestResponseHandler<TSubstream, TCodec>
+where
+    TCodec: RequestResponseCodec,
+{
+    pub(crate) fn new(
+        codec: TCodec,
+        protocols: SmallVec<[Vec<u8>; 2]>,
+        config: RequestResponseConfig,
+        next_request_id: Arc<AtomicU64>,
+    ) -> Self {
+        let keep_alive = KeepAlive::Until(Instant::now() + config.connection_keep_alive);
+        RequestResponseHandler {
+            codec,
+            protocols,
+            config,
+            next_request_id,
+            keep_alive,
+            outbound: VecDeque::new(),
+            num_pending_outbound: 0,
+            inbound: Vec::new(),
+            pending_events: VecDeque::new(),
+        }
+    }
+
+    /// Updates the keep-alive after a request has completed.
+    fn update_keep_alive(&mut self) {
+        if self.outbound.is_empty() && self.num_pending_outbound == 0 && self.inbound.is_empty() {
+            self.keep_alive = KeepAlive::Until(Instant::now() + self.config.connection_keep_alive);
+        } else {
+            self.keep_alive = KeepAlive::Yes;
+        }
+    }
+}
+
+impl<TSubstream, TCodec> ProtocolsHandler for RequestResponseHandler<TSubstream, TCodec>
+where
+    TSubstream: AsyncRead + AsyncWrite,
+    TCodec: RequestResponseCodec,
+{
+    type InEvent = RequestProtocol<TCodec>;
+    type OutEvent = RequestResponseHandlerEvent<TCodec>;
+    type Error = Void;
+    type Substream = TSubstream;
+    type InboundProtocol = ResponseProtocol<TCodec>;
+    type OutboundProtocol = RequestProtocol<TCodec>;
+    type OutboundOpenInfo = RequestId;
+
+    fn listen_protocol(&self) -> SubstreamProtocol<Self::InboundProtocol> {
+        let protocol = ResponseProtocol {
+            codec: self.codec.clone(),
+            protocols: self.protocols.clone(),
+            max_message_size: self.config.max_message_size,
+        };
+        SubstreamProtocol::new(protocol).with_timeout(self.config.request_timeout)
+    }
+
+    fn inject_fully_negotiated_inbound(&mut self, (request, socket): (TCodec::Request, Negotiated<TSubstream>)) {
+        let request_id = RequestId(self.next_request_id.fetch_add(1, Ordering::Relaxed));
+        let (sender, receiver) = oneshot::channel();
+        self.inbound.push(InboundRequest {
+            request_id,
+            timeout: Delay::new(Instant::now() + self.config.request_timeout),
+            state: InboundState::WaitResponse(socket, receiver),
+        });
+        self.keep_alive = KeepAlive::Yes;
+        self.pending_events.push_back(RequestResponseHandlerEvent::Request { request_id, request, sender });
+    }
+
+    fn inject_fully_negotiated_outbound(&mut self, response: TCodec::Response, request_id: RequestId) {
+        self.num_pending_outbound -= 1;
+        self.update_keep_alive();
+        self.pending_events.push_back(RequestResponseHandlerEvent::Response { request_id, response });
+    }
+
+    fn inject_event(&mut self, request: Self::InEvent) {
+        self.keep_alive = KeepAlive::Yes;
+        self.outbound.push_back(request);
+    }
+
+    fn inject_dial_upgrade_error(
+        &mut self,
+        request_id: RequestId,
+        error: ProtocolsHandlerUpgrErr<upgrade::ReadOneError>
+    ) {
+        self.num_pending_outbound -= 1;
+        self.update_keep_alive();
+        let error = match error {
+            ProtocolsHandlerUpgrErr::Timeout | ProtocolsHandlerUpgrErr::Timer => OutboundFailure::Timeout,
+            ProtocolsHandlerUpgrErr::Upgrade(UpgradeError::Select(_)) => OutboundFailure::UnsupportedProtocols,
+            ProtocolsHandlerUpgrErr::Upgrade(UpgradeError::Apply(e)) => OutboundFailure::Io(e),
+        };
+        self.pending_events.push_back(RequestResponseHandlerEvent::OutboundFailure { request_id, error });
+    }
+
+    fn connection_keep_alive(&self) -> KeepAlive {
+        self.keep_alive
+    }
+
+    fn poll(&mut self) -> Poll<ProtocolsHandlerEvent<RequestProtocol<TCodec>, RequestId, Self::OutEvent>, Void> {
+        if let Some(event) = self.pending_events.pop_front() {
+            return Ok(Async::Ready(ProtocolsHandlerEvent::Custom(event)))
+        }
+
+        // Make progress on the responses to the inbound requests.
+        let mut n = 0;
+        while n < self.inbound.len() {
+            let request_id = self.inbound[n].request_id;
+            match poll_inbound(&mut self.inbound[n], &self.codec) {
+                Ok(Async::NotReady) => n += 1,
+                Ok(Async::Ready(())) => {
+                    self.inbound.swap_remove(n);
+                    self.update_keep_alive();
+                }
+                Err(error) => {
+                    self.inbound.swap_remove(n);
+                    self.update_keep_alive();
+                    return Ok(Async::Ready(ProtocolsHandlerEvent::Custom(
+                        RequestResponseHandlerEvent::InboundFailure { request_id, error }
+                    )))
+                }
+            }
+        }
+
+        if let Some(request) = self.outbound.pop_front() {
+            self.num_pending_outbound += 1;
+            let info = request.request_id;
+            let protocol = SubstreamProtocol::new(request).with_timeout(self.config.request_timeout);
+            return Ok(Async::Ready(ProtocolsHandlerEvent::OutboundSubstreamRequest { protocol, info }))
+        }
+
+        Ok(Async::NotReady)
+    }
+}
+
+/// Polls the response to an inbound request.
+fn poll_inbound<TSubstream, TCodec>(
+    inbound: &mut InboundRequest<TSubstream, TCodec>,
+    codec: &TCodec,
+) -> Poll<(), InboundFailure>
+where
+    TSubstream: AsyncRead + AsyncWrite,
+    TCodec: RequestResponseCodec,
+{
+    match inbound.timeout.poll() {
+        Ok(Async::NotReady) => {}
+        Ok(Async::Ready(())) | Err(_) => return Err(InboundFailure::Timeout),
+    }
+
+    loop {
+        match std::mem::replace(&mut inbound.state, InboundState::Poisoned) {
+            InboundState::WaitResponse(socket, mut receiver) => match receiver.poll() {
+                Ok(Async::Ready(response)) => {
+                    let bytes = codec.encode_response(response);
+                    inbound.state = InboundState::SendResponse(upgrade::write_one(socket, bytes));
+                }
+                Ok(Async::NotReady) => {
+                    inbound.state = InboundState::WaitResponse(socket, receiver);
+                    return Ok(Async::NotReady)
+                }
+                Err(oneshot::Canceled) => return Err(InboundFailure::ResponseOmission),
+            },
+            InboundState::SendResponse(mut write) => match write.poll() {
+                Ok(Async::Ready(())) => return Ok(Async::Ready(())),
+                Ok(Async::NotReady) => {
+                    inbound.state = InboundState::SendResponse(write);
+                    return Ok(Async::NotReady)
+                }
+                Err(err) => return Err(InboundFailure::Io(err)),
+            },
+            InboundState::Poisoned => panic!("Inbound request polled after an error"),
+        }
+    }
+}
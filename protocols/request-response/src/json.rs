@@ -0,0 +1,97 @@
+// Copyright 2019 Parity Technologies (UK) Ltd.
+//
+// Permission is hereby granted, free of charge, to any person obtaining a
+// copy of this software and associated documentation files (the "Software"),
+// to deal in the Software without restriction, including without limitation
+// the rights to use, copy, modify, merge, publish, distribute, sublicense,
+// and/or sell copies of the Software, and to permit persons to whom the
+// Software is furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS
+// OR IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING
+// FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
+// DEALINGS IN THE SOFTWARE.
+
+//! A [`RequestResponseCodec`] encoding requests and responses in JSON with serde.
+//!
+//! The size of the messages is bounded by
+//! [`RequestResponseConfig::with_max_message_size`](crate::RequestResponseConfig::with_max_message_size):
+//! larger messages are rejected before being decoded.
+
+use crate::codec::RequestResponseCodec;
+use serde::{de::DeserializeOwned, Serialize};
+use std::{fmt, io, marker::PhantomData};
+
+/// Codec encoding requests of type `TRequest` and responses of type `TResponse` in JSON.
+pub struct JsonCodec<TRequest, TResponse> {
+    marker: PhantomData<fn() -> (TRequest, TResponse)>,
+}
+
+impl<TRequest, TResponse> JsonCodec<TRequest, TResponse> {
+    /// Creates a new `JsonCodec`.
+    pub fn new() -> Self {
+        JsonCodec { marker: PhantomData }
+    }
+}
+
+impl<TRequest, TResponse> Default for JsonCodec<TRequest, TResponse> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<TRequest, TResponse> Clone for JsonCodec<TRequest, TResponse> {
+    fn clone(&self) -> Self {
+        Self::new()
+    }
+}
+
+impl<TRequest, TResponse> fmt::Debug for JsonCodec<TRequest, TResponse> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("JsonCodec").finish()
+    }
+}
+
+impl<TRequest, TResponse> RequestResponseCodec for JsonCodec<TRequest, TResponse>
+where
+    TRequest: Serialize + DeserializeOwned,
+    TResponse: Serialize + DeserializeOwned,
+{
+    type Request = TRequest;
+    type Response = TResponse;
+
+    fn encode_request(&self, request: TRequest) -> Vec<u8> {
+        encode(&request)
+    }
+
+    fn decode_request(&self, bytes: Vec<u8>) -> Result<TRequest, io::Error> {
+        decode(&bytes)
+    }
+
+    fn encode_response(&self, response: TResponse) -> Vec<u8> {
+        encode(&response)
+    }
+
+    fn decode_response(&self, bytes: Vec<u8>) -> Result<TResponse, io::Error> {
+        decode(&bytes)
+    }
+}
+
+/// Encodes a value in JSON.
+///
+/// # Panic
+///
+/// Panics if the value can't be represented in JSON, e.g. a map whose keys aren't strings.
+fn encode<T: Serialize>(value: &T) -> Vec<u8> {
+    serde_json::to_vec(value).expect("The request-response types must be representable in JSON")
+}
+
+fn decode<T: DeserializeOwned>(bytes: &[u8]) -> Result<T, io::Error> {
+    serde_json::from_slice(bytes).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+}
@@ -0,0 +1,558 @@
+// Copyright 2019 Parity Technologies (UK) Ltd.
+//
+// Permission is hereby granted, free of charge, to any person obtaining a
+// copy of this software and associated documentation files (the "Software"),
+// to deal in the Software without restriction, including without limitation
+// the rights to use, copy, modify, merge, publish, distribute, sublicense,
+// and/or sell copies of the Software, and to permit persons to whom the
+// Software is furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS
+// OR IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING
+// FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
+// DEALINGS IN THE SOFTWARE.
+
+//! Generic request-response protocols.
+//!
+//! The [`RequestResponse`] struct is a [`NetworkBehaviour`] that sends requests to remotes and
+//! answers the requests they send. Each request is sent on a new substream, on which a single
+//! response is expected. The types of requests and responses, and the way they are encoded, are
+//! defined by a [`RequestResponseCodec`].
+//!
+//! Requests are identified by a [`RequestId`], which allows matching the
+//! [`RequestResponseEvent`]s produced by the behaviour with the requests they relate to.
+//!
+//! Requests to peers that aren't connected trigger a dialing attempt. A request that fails
+//! because it timed out or because its connection closed is retried over another connection to
+//! the same peer, if any, up to the configured number of retries.
+//!
+//! Ready-made codecs encoding requests and responses with serde are provided by the
+//! [`cbor`] and [`json`] modules, enabled by the features of the same name.
+//!
+//! [`NetworkBehaviour`]: libp2p_swarm::NetworkBehaviour
+
+#[cfg(feature = "cbor")]
+pub mod cbor;
+#[cfg(feature = "json")]
+pub mod json;
+
+mod codec;
+mod handler;
+mod protocol;
+
+pub use codec::RequestResponseCodec;
+pub use handler::{RequestResponseHandler, RequestResponseHandlerEvent};
+pub use protocol::{RequestId, RequestProtocol, ResponseProtocol};
+
+use futures::{prelude::*, sync::oneshot};
+use libp2p_core::{ConnectedPoint, Multiaddr, PeerId, upgrade::ReadOneError};
+use libp2p_swarm::{ConnectionId, NetworkBehaviour, NetworkBehaviourAction, PollParameters};
+use smallvec::SmallVec;
+use std::{collections::{HashMap, VecDeque}, error, fmt, io, marker::PhantomData, time::Duration};
+use std::sync::{Arc, atomic::{AtomicU64, Ordering}};
+use tokio_io::{AsyncRead, AsyncWrite};
+use wasm_timer::Instant;
+
+/// Configuration of a `RequestResponse` behaviour.
+#[derive(Debug, Clone)]
+pub struct RequestResponseConfig {
+    request_timeout: Duration,
+    connection_keep_alive: Duration,
+    max_message_size: usize,
+    max_retries: u32,
+}
+
+impl Default for RequestResponseConfig {
+    fn default() -> Self {
+        RequestResponseConfig {
+            request_timeout: Duration::from_secs(10),
+            connection_keep_alive: Duration::from_secs(10),
+            max_message_size: 1024 * 1024,
+            max_retries: 1,
+        }
+    }
+}
+
+impl RequestResponseConfig {
+    /// Sets how long we wait for the response to an outbound request, and how long the
+    /// behaviour has to answer an inbound request. Defaults to 10 seconds.
+    pub fn with_request_timeout(mut self, timeout: Duration) -> Self {
+        self.request_timeout = timeout;
+        self
+    }
+
+    /// Sets how long an idle connection is kept alive. Defaults to 10 seconds.
+    pub fn with_connection_keep_alive(mut self, keep_alive: Duration) -> Self {
+        self.connection_keep_alive = keep_alive;
+        self
+    }
+
+    /// Sets the maximum size in bytes of a request or response message. Defaults to 1 MiB.
+    pub fn with_max_message_size(mut self, size: usize) -> Self {
+        self.max_message_size = size;
+        self
+    }
+
+    /// Sets how many times a failed outbound request is sent again over another connection to
+    /// the same peer. Defaults to 1.
+    pub fn with_max_retries(mut self, retries: u32) -> Self {
+        self.max_retries = retries;
+        self
+    }
+}
+
+/// Event generated by the `RequestResponse` network behaviour.
+#[derive(Debug)]
+pub enum RequestResponseEvent<TRequest, TResponse> {
+    /// A request or response has been received.
+    Message {
+        /// The peer that sent the message.
+        peer: PeerId,
+        /// The message.
+        message: RequestResponseMessage<TRequest, TResponse>,
+    },
+    /// An outbound request failed.
+    OutboundFailure {
+        /// The peer the request was sent to.
+        peer: PeerId,
+        /// The identifier of the request.
+        request_id: RequestId,
+        /// The reason of the failure.
+        error: OutboundFailure,
+    },
+    /// We failed to answer an inbound request.
+    InboundFailure {
+        /// The peer the request has been received from.
+        peer: PeerId,
+        /// The identifier of the request.
+        request_id: RequestId,
+        /// The reason of the failure.
+        error: InboundFailure,
+    },
+}
+
+/// A request or response received from a remote.
+#[derive(Debug)]
+pub enum RequestResponseMessage<TRequest, TResponse> {
+    /// A request, which must be answered with `RequestResponse::send_response`.
+    Request {
+        /// The identifier of the request.
+        request_id: RequestId,
+        /// The request.
+        request: TRequest,
+        /// The channel through which the response must be sent.
+        channel: ResponseChannel<TResponse>,
+    },
+    /// The response to one of our requests.
+    Response {
+        /// The identifier of the request this is a response to.
+        request_id: RequestId,
+        /// The response.
+        response: TResponse,
+    },
+}
+
+/// Reason why an outbound request failed.
+#[derive(Debug)]
+pub enum OutboundFailure {
+    /// We couldn't connect to the peer.
+    DialFailure,
+    /// No response has been received in time.
+    Timeout,
+    /// The connection the request was sent on closed before a response was received.
+    ConnectionClosed,
+    /// The remote doesn't support any of the protocols of the request.
+    UnsupportedProtocols,
+    /// An error happened while sending the request or reading the response.
+    Io(ReadOneError),
+}
+
+impl fmt::Display for OutboundFailure {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            OutboundFailure::DialFailure => write!(f, "Failed to dial the peer"),
+            OutboundFailure::Timeout => write!(f, "Timeout while waiting for a response"),
+            OutboundFailure::ConnectionClosed => write!(f, "Connection closed before a response was received"),
+            OutboundFailure::UnsupportedProtocols => write!(f, "The remote supports none of the requested protocols"),
+            OutboundFailure::Io(err) => write!(f, "I/O error: {}", err),
+        }
+    }
+}
+
+impl error::Error for OutboundFailure {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        match self {
+            OutboundFailure::Io(err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+/// Reason why we failed to answer an inbound request.
+#[derive(Debug)]
+pub enum InboundFailure {
+    /// The response hasn't been provided or sent in time.
+    Timeout,
+    /// The `ResponseChannel` has been dropped without sending a response.
+    ResponseOmission,
+    /// An error happened while sending the response.
+    Io(io::Error),
+}
+
+impl fmt::Display for InboundFailure {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            InboundFailure::Timeout => write!(f, "Timeout while sending the response"),
+            InboundFailure::ResponseOmission => write!(f, "No response was provided"),
+            InboundFailure::Io(err) => write!(f, "I/O error: {}", err),
+        }
+    }
+}
+
+impl error::Error for InboundFailure {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        match self {
+            InboundFailure::Io(err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+/// Channel through which the response to an inbound request is sent.
+pub struct ResponseChannel<TResponse> {
+    peer: PeerId,
+    sender: oneshot::Sender<TResponse>,
+}
+
+impl<TResponse> ResponseChannel<TResponse> {
+    /// Returns the peer that sent the request.
+    pub fn peer(&self) -> &PeerId {
+        &self.peer
+    }
+
+    /// Returns `false` if the response can no longer be sent, for example because the
+    /// connection has been closed or because the request timed out.
+    pub fn is_open(&self) -> bool {
+        !self.sender.is_canceled()
+    }
+}
+
+impl<TResponse> fmt::Debug for ResponseChannel<TResponse> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("ResponseChannel")
+            .field("peer", &self.peer)
+            .finish()
+    }
+}
+
+/// An outbound request that has been sent over a connection and awaits a response.
+struct PendingResponse {
+    peer: PeerId,
+    connection: ConnectionId,
+    /// The encoded request, kept in order to retry it over another connection.
+    request: Vec<u8>,
+    retries_left: u32,
+    /// When the request was sent, to measure the round-trip time with the peer.
+    sent_at: Instant,
+}
+
+/// A `NetworkBehaviour` that sends requests and answers requests on new substreams.
+///
+/// See the crate root documentation for more information.
+pub struct RequestResponse<TSubstream, TCodec>
+where
+    TCodec: RequestResponseCodec,
+{
+    /// The codec of the protocol.
+    codec: TCodec,
+    /// The names of the supported protocols, by order of preference.
+    protocols: SmallVec<[Vec<u8>; 2]>,
+    /// Configuration options.
+    config: RequestResponseConfig,
+    /// Counter used to build the identifiers of inbound and outbound requests.
+    next_request_id: Arc<AtomicU64>,
+    /// The established connections to each peer, from the oldest to the most recent.
+    connected: HashMap<PeerId, SmallVec<[ConnectionId; 2]>>,
+    /// Addresses of peers, in addition to the ones that the `Swarm` already knows about.
+    addresses: HashMap<PeerId, SmallVec<[Multiaddr; 6]>>,
+    /// Encoded requests to peers we are not connected to yet.
+    pending_requests: HashMap<PeerId, SmallVec<[(RequestId, Vec<u8>); 8]>>,
+    /// Outbound requests awaiting a response.
+    pending_responses: HashMap<RequestId, PendingResponse>,
+    /// Queue of actions to return to the swarm.
+    pending_actions: VecDeque<NetworkBehaviourAction<
+        RequestProtocol<TCodec>,
+        RequestResponseEvent<TCodec::Request, TCodec::Response>
+    >>,
+    _marker: PhantomData<TSubstream>,
+}
+
+impl<TSubstream, TCodec> RequestResponse<TSubstream, TCodec>
+where
+    TCodec: RequestResponseCodec,
+{
+    /// Creates a new `RequestResponse` behaviour for the given protocols, by order of
+    /// preference.
+    pub fn new<TProtocols>(codec: TCodec, protocols: TProtocols, config: RequestResponseConfig) -> Self
+    where
+        TProtocols: IntoIterator,
+        TProtocols::Item: Into<Vec<u8>>,
+    {
+        RequestResponse {
+            codec,
+            protocols: protocols.into_iter().map(Into::into).collect(),
+            config,
+            next_request_id: Arc::new(AtomicU64::new(0)),
+            connected: HashMap::new(),
+            addresses: HashMap::new(),
+            pending_requests: HashMap::new(),
+            pending_responses: HashMap::new(),
+            pending_actions: VecDeque::new(),
+            _marker: PhantomData,
+        }
+    }
+
+    /// Sends a request to a peer, dialing it first if we're not connected to it.
+    ///
+    /// The response, or the reason why the request failed, is reported with a
+    /// `RequestResponseEvent` carrying the returned `RequestId`.
+    pub fn send_request(&mut self, peer: &PeerId, request: TCodec::Request) -> RequestId {
+        let request_id = RequestId(self.next_request_id.fetch_add(1, Ordering::Relaxed));
+        let request = self.codec.encode_request(request);
+
+        let connection = self.connected.get(peer).and_then(|conns| conns.last()).cloned();
+        if let Some(connection) = connection {
+            self.send_to_connection(peer.clone(), connection, request_id, request, self.config.max_retries);
+        } else {
+            let queue = self.pending_requests.entry(peer.clone()).or_default();
+            if queue.is_empty() {
+                self.pending_actions.push_back(NetworkBehaviourAction::DialPeer { peer_id: peer.clone() });
+            }
+            queue.push((request_id, request));
+        }
+
+        request_id
+    }
+
+    /// Sends the response to an inbound request.
+    ///
+    /// The response is dropped if the channel is no longer open, in which case an
+    /// `InboundFailure` has been or will be reported for the request.
+    pub fn send_response(&mut self, channel: ResponseChannel<TCodec::Response>, response: TCodec::Response) {
+        let _ = channel.sender.send(response);
+    }
+
+    /// Adds an address at which a peer can be dialed.
+    pub fn add_address(&mut self, peer: &PeerId, address: Multiaddr) {
+        let addresses = self.addresses.entry(peer.clone()).or_default();
+        if !addresses.contains(&address) {
+            addresses.push(address);
+        }
+    }
+
+    /// Removes an address of a peer previously added with `add_address`.
+    pub fn remove_address(&mut self, peer: &PeerId, address: &Multiaddr) {
+        if let Some(addresses) = self.addresses.get_mut(peer) {
+            addresses.retain(|a| a != address);
+            if addresses.is_empty() {
+                self.addresses.remove(peer);
+            }
+        }
+    }
+
+    /// Returns `true` if we are connected to the given peer.
+    pub fn is_connected(&self, peer: &PeerId) -> bool {
+        self.connected.contains_key(peer)
+    }
+
+    /// Returns `true` if some requests to the given peer are waiting for a connection or for a
+    /// response.
+    pub fn is_pending(&self, peer: &PeerId) -> bool {
+        self.pending_requests.contains_key(peer) ||
+            self.pending_responses.values().any(|p| &p.peer == peer)
+    }
+
+    /// Sends an encoded request to the handler of a connection.
+    fn send_to_connection(
+        &mut self,
+        peer: PeerId,
+        connection: ConnectionId,
+        request_id: RequestId,
+        request: Vec<u8>,
+        retries_left: u32,
+    ) {
+        let event = RequestProtocol {
+            codec: self.codec.clone(),
+            protocols: self.protocols.clone(),
+            max_message_size: self.config.max_message_size,
+            request_id,
+            request: request.clone(),
+        };
+        self.pending_actions.push_back(NetworkBehaviourAction::SendEventToConnection {
+            peer_id: peer.clone(),
+            connection,
+            event,
+        });
+        self.pending_responses.insert(request_id, PendingResponse {
+            peer,
+            connection,
+            request,
+            retries_left,
+            sent_at: Instant::now(),
+        });
+    }
+
+    /// Retries a failed outbound request over another connection to the same peer, or reports
+    /// the failure if there is no such connection or no retry left.
+    fn retry_or_fail(&mut self, request_id: RequestId, error: OutboundFailure) {
+        let pending = match self.pending_responses.remove(&request_id) {
+            Some(p) => p,
+            None => return,
+        };
+
+        let other_connection = self.connected.get(&pending.peer)
+            .and_then(|conns| conns.iter().rev().find(|c| **c != pending.connection))
+            .cloned();
+        match other_connection {
+            Some(connection) if pending.retries_left > 0 => {
+                log::debug!("Retrying request {} to {:?} after failure: {}", request_id, pending.peer, error);
+                self.send_to_connection(pending.peer, connection, request_id, pending.request, pending.retries_left - 1);
+            }
+            _ => {
+                self.pending_actions.push_back(NetworkBehaviourAction::GenerateEvent(
+                    RequestResponseEvent::OutboundFailure { peer: pending.peer, request_id, error }
+                ));
+            }
+        }
+    }
+
+    /// Processes an event generated by the handler of a connection.
+    fn on_handler_event(
+        &mut self,
+        peer: PeerId,
+        event: RequestResponseHandlerEvent<TCodec>,
+    ) {
+        let event = match event {
+            RequestResponseHandlerEvent::Request { request_id, request, sender } => {
+                let channel = ResponseChannel { peer: peer.clone(), sender };
+                let message = RequestResponseMessage::Request { request_id, request, channel };
+                RequestResponseEvent::Message { peer, message }
+            }
+            RequestResponseHandlerEvent::Response { request_id, response } => {
+                if let Some(pending) = self.pending_responses.remove(&request_id) {
+                    self.pending_actions.push_back(NetworkBehaviourAction::ReportRoundTripTime {
+                        peer_id: peer.clone(),
+                        rtt: pending.sent_at.elapsed(),
+                    });
+                }
+                let message = RequestResponseMessage::Response { request_id, response };
+                RequestResponseEvent::Message { peer, message }
+            }
+            RequestResponseHandlerEvent::OutboundFailure { request_id, error } => {
+                match error {
+                    OutboundFailure::Timeout | OutboundFailure::Io(_) => self.retry_or_fail(request_id, error),
+                    error => {
+                        self.pending_responses.remove(&request_id);
+                        self.pending_actions.push_back(NetworkBehaviourAction::GenerateEvent(
+                            RequestResponseEvent::OutboundFailure { peer, request_id, error }
+                        ));
+                    }
+                }
+                return
+            }
+            RequestResponseHandlerEvent::InboundFailure { request_id, error } => {
+                RequestResponseEvent::InboundFailure { peer, request_id, error }
+            }
+        };
+
+        self.pending_actions.push_back(NetworkBehaviourAction::GenerateEvent(event));
+    }
+}
+
+impl<TSubstream, TCodec> NetworkBehaviour for RequestResponse<TSubstream, TCodec>
+where
+    TSubstream: AsyncRead + AsyncWrite,
+    TCodec: RequestResponseCodec,
+{
+    type ProtocolsHandler = RequestResponseHandler<TSubstream, TCodec>;
+    type OutEvent = RequestResponseEvent<TCodec::Request, TCodec::Response>;
+
+    fn new_handler(&mut self) -> Self::ProtocolsHandler {
+        RequestResponseHandler::new(
+            self.codec.clone(),
+            self.protocols.clone(),
+            self.config.clone(),
+            self.next_request_id.clone(),
+        )
+    }
+
+    fn addresses_of_peer(&mut self, peer_id: &PeerId) -> Vec<Multiaddr> {
+        self.addresses.get(peer_id).map(|a| a.to_vec()).unwrap_or_default()
+    }
+
+    fn inject_connected(&mut self, _: PeerId, _: ConnectedPoint) {}
+
+    fn inject_disconnected(&mut self, peer_id: &PeerId, _: ConnectedPoint) {
+        self.connected.remove(peer_id);
+    }
+
+    fn inject_connection_established(&mut self, peer_id: &PeerId, connection: &ConnectionId, _: &ConnectedPoint) {
+        self.connected.entry(peer_id.clone()).or_default().push(*connection);
+        if let Some(requests) = self.pending_requests.remove(peer_id) {
+            for (request_id, request) in requests {
+                self.send_to_connection(peer_id.clone(), *connection, request_id, request, self.config.max_retries);
+            }
+        }
+    }
+
+    fn inject_connection_closed(&mut self, peer_id: &PeerId, connection: &ConnectionId, _: &ConnectedPoint) {
+        if let Some(connections) = self.connected.get_mut(peer_id) {
+            connections.retain(|c| c != connection);
+            if connections.is_empty() {
+                self.connected.remove(peer_id);
+            }
+        }
+
+        let interrupted = self.pending_responses.iter()
+            .filter(|(_, p)| &p.connection == connection)
+            .map(|(id, _)| *id)
+            .collect::<Vec<_>>();
+        for request_id in interrupted {
+            self.retry_or_fail(request_id, OutboundFailure::ConnectionClosed);
+        }
+    }
+
+    fn inject_node_event(&mut self, peer_id: PeerId, event: RequestResponseHandlerEvent<TCodec>) {
+        self.on_handler_event(peer_id, event)
+    }
+
+    fn inject_dial_failure(&mut self, peer_id: &PeerId) {
+        if let Some(requests) = self.pending_requests.remove(peer_id) {
+            for (request_id, _) in requests {
+                self.pending_actions.push_back(NetworkBehaviourAction::GenerateEvent(
+                    RequestResponseEvent::OutboundFailure {
+                        peer: peer_id.clone(),
+                        request_id,
+                        error: OutboundFailure::DialFailure,
+                    }
+                ));
+            }
+        }
+    }
+
+    fn poll(&mut self, _: &mut impl PollParameters)
+        -> Async<NetworkBehaviourAction<RequestProtocol<TCodec>, Self::OutEvent>>
+    {
+        if let Some(action) = self.pending_actions.pop_front() {
+            return Async::Ready(action)
+        }
+
+        Async::NotReady
+    }
+}
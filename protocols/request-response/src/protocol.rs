@@ -0,0 +1,140 @@
+// Copyright 2019 Parity Technologies (UK) Ltd.
+//
+// Permission is hereby granted, free of charge, to any person obtaining a
+// copy of this software and associated documentation files (the "Software"),
+// to deal in the Software without restriction, including without limitation
+// the rights to use, copy, modify, merge, publish, distribute, sublicense,
+// and/or sell copies of the Software, and to permit persons to whom the
+// Software is furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS
+// OR IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING
+// FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
+// DEALINGS IN THE SOFTWARE.
+
+//! The upgrades applied on the substreams of a request-response protocol.
+//!
+//! The dialer of a substream sends a single request message and closes its writing side, then
+//! the listener sends back a single response message. Messages are prefixed with their length.
+
+use crate::codec::RequestResponseCodec;
+use libp2p_core::{
+    InboundUpgrade,
+    OutboundUpgrade,
+    UpgradeInfo,
+    upgrade::{self, Negotiated, ReadOneError, ReadRespond}
+};
+use smallvec::SmallVec;
+use std::fmt;
+use tokio_io::{AsyncRead, AsyncWrite};
+
+/// Identifier of an inbound or outbound request.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct RequestId(pub(crate) u64);
+
+impl fmt::Display for RequestId {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+/// Upgrade applied on inbound substreams. Reads a request, and produces it along with the
+/// substream on which the response must be sent.
+#[derive(Debug, Clone)]
+pub struct ResponseProtocol<TCodec> {
+    pub(crate) codec: TCodec,
+    pub(crate) protocols: SmallVec<[Vec<u8>; 2]>,
+    pub(crate) max_message_size: usize,
+}
+
+impl<TCodec> UpgradeInfo for ResponseProtocol<TCodec> {
+    type Info = Vec<u8>;
+    type InfoIter = smallvec::IntoIter<[Vec<u8>; 2]>;
+
+    fn protocol_info(&self) -> Self::InfoIter {
+        self.protocols.clone().into_iter()
+    }
+}
+
+impl<TSocket, TCodec> InboundUpgrade<TSocket> for ResponseProtocol<TCodec>
+where
+    TSocket: AsyncRead + AsyncWrite,
+    TCodec: RequestResponseCodec,
+{
+    type Output = (TCodec::Request, Negotiated<TSocket>);
+    type Error = ReadOneError;
+    type Future = ReadRespond<
+        Negotiated<TSocket>,
+        TCodec,
+        fn(Negotiated<TSocket>, Vec<u8>, TCodec) -> Result<Self::Output, ReadOneError>
+    >;
+
+    fn upgrade_inbound(self, socket: Negotiated<TSocket>, _: Self::Info) -> Self::Future {
+        upgrade::read_respond(socket, self.max_message_size, self.codec, decode_request as fn(_, _, _) -> _)
+    }
+}
+
+/// Upgrade applied on outbound substreams. Sends a request and reads the response.
+#[derive(Debug, Clone)]
+pub struct RequestProtocol<TCodec> {
+    pub(crate) codec: TCodec,
+    pub(crate) protocols: SmallVec<[Vec<u8>; 2]>,
+    pub(crate) max_message_size: usize,
+    pub(crate) request_id: RequestId,
+    /// The encoded request.
+    pub(crate) request: Vec<u8>,
+}
+
+impl<TCodec> UpgradeInfo for RequestProtocol<TCodec> {
+    type Info = Vec<u8>;
+    type InfoIter = smallvec::IntoIter<[Vec<u8>; 2]>;
+
+    fn protocol_info(&self) -> Self::InfoIter {
+        self.protocols.clone().into_iter()
+    }
+}
+
+impl<TSocket, TCodec> OutboundUpgrade<TSocket> for RequestProtocol<TCodec>
+where
+    TSocket: AsyncRead + AsyncWrite,
+    TCodec: RequestResponseCodec,
+{
+    type Output = TCodec::Response;
+    type Error = ReadOneError;
+    type Future = upgrade::RequestResponse<
+        Negotiated<TSocket>,
+        TCodec,
+        fn(Vec<u8>, TCodec) -> Result<TCodec::Response, ReadOneError>
+    >;
+
+    fn upgrade_outbound(self, socket: Negotiated<TSocket>, _: Self::Info) -> Self::Future {
+        upgrade::request_response(
+            socket,
+            self.request,
+            self.max_message_size,
+            self.codec,
+            decode_response as fn(_, _) -> _
+        )
+    }
+}
+
+fn decode_request<TSocket, TCodec>(socket: TSocket, bytes: Vec<u8>, codec: TCodec)
+    -> Result<(TCodec::Request, TSocket), ReadOneError>
+where
+    TCodec: RequestResponseCodec,
+{
+    Ok((codec.decode_request(bytes)?, socket))
+}
+
+fn decode_response<TCodec>(bytes: Vec<u8>, codec: TCodec) -> Result<TCodec::Response, ReadOneError>
+where
+    TCodec: RequestResponseCodec,
+{
+    Ok(codec.decode_response(bytes)?)
+}
@@ -0,0 +1,177 @@
+// Copyright 2019 Parity Technologies (UK) Ltd.
+//
+// Permission is hereby granted, free of charge, to any person obtaining a
+// copy of this software and associated documentation files (the "Software"),
+// to deal in the Software without restriction, including without limitation
+// the rights to use, copy, modify, merge, publish, distribute, sublicense,
+// and/or sell copies of the Software, and to permit persons to whom the
+// Software is furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS
+// OR IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING
+// FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
+// DEALINGS IN THE SOFTWARE.
+
+//! Integration tests for the `RequestResponse` network behaviour.
+
+use libp2p_core::{
+    Multiaddr,
+    PeerId,
+    identity,
+    muxing::StreamMuxer,
+    upgrade::{self, OutboundUpgradeExt, InboundUpgradeExt},
+    transport::Transport
+};
+use libp2p_request_response::*;
+use libp2p_yamux as yamux;
+use libp2p_secio::SecioConfig;
+use libp2p_swarm::Swarm;
+use libp2p_tcp::TcpConfig;
+use futures::{future, prelude::*};
+use std::{fmt, io, sync::mpsc::sync_channel};
+use tokio::runtime::Runtime;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Ping(Vec<u8>);
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Pong(Vec<u8>);
+
+#[derive(Clone)]
+struct PingCodec;
+
+impl RequestResponseCodec for PingCodec {
+    type Request = Ping;
+    type Response = Pong;
+
+    fn encode_request(&self, Ping(bytes): Ping) -> Vec<u8> {
+        bytes
+    }
+
+    fn decode_request(&self, bytes: Vec<u8>) -> Result<Ping, io::Error> {
+        Ok(Ping(bytes))
+    }
+
+    fn encode_response(&self, Pong(bytes): Pong) -> Vec<u8> {
+        bytes
+    }
+
+    fn decode_response(&self, bytes: Vec<u8>) -> Result<Pong, io::Error> {
+        Ok(Pong(bytes))
+    }
+}
+
+#[test]
+fn ping_pong() {
+    let ping = Ping(b"ping".to_vec());
+    let pong = Pong(b"pong".to_vec());
+    let protocols = vec![b"/ping/1".to_vec()];
+    let cfg = RequestResponseConfig::default();
+
+    let (peer1_id, trans) = mk_transport();
+    let behaviour = RequestResponse::new(PingCodec, protocols.clone(), cfg.clone());
+    let mut swarm1 = Swarm::new(trans, behaviour, peer1_id.clone());
+
+    let (peer2_id, trans) = mk_transport();
+    let behaviour = RequestResponse::new(PingCodec, protocols, cfg);
+    let mut swarm2 = Swarm::new(trans, behaviour, peer2_id.clone());
+
+    let (tx, rx) = sync_channel::<Multiaddr>(1);
+
+    let addr = "/ip4/127.0.0.1/tcp/0".parse().unwrap();
+    Swarm::listen_on(&mut swarm1, addr).unwrap();
+
+    // Peer 1 answers every request with a pong.
+    let expected_ping = ping.clone();
+    let pong1 = pong.clone();
+    let mut listening = false;
+    let peer1 = future::poll_fn(move || -> Result<Async<()>, ()> {
+        loop {
+            match swarm1.poll().expect("Error while polling swarm") {
+                Async::Ready(Some(RequestResponseEvent::Message {
+                    peer,
+                    message: RequestResponseMessage::Request { request, channel, .. }
+                })) => {
+                    assert_eq!(request, expected_ping);
+                    assert_eq!(peer, peer2_id);
+                    swarm1.send_response(channel, pong1.clone());
+                }
+                Async::Ready(Some(e)) => panic!("Peer1: Unexpected event: {:?}", e),
+                Async::Ready(None) => panic!("Peer1: Swarm closed"),
+                Async::NotReady => {
+                    if !listening {
+                        for l in Swarm::listeners(&swarm1) {
+                            tx.send(l.clone()).unwrap();
+                            listening = true;
+                        }
+                    }
+                    return Ok(Async::NotReady)
+                }
+            }
+        }
+    });
+
+    // Peer 2 sends several requests and expects a pong for each of them.
+    let num_pings = 5;
+    let mut count = 0;
+    let mut request_id = None;
+    let peer2 = future::poll_fn(move || -> Result<Async<()>, ()> {
+        if request_id.is_none() {
+            let addr = rx.recv().unwrap();
+            swarm2.add_address(&peer1_id, addr);
+            request_id = Some(swarm2.send_request(&peer1_id, ping.clone()));
+        }
+        loop {
+            match swarm2.poll().expect("Error while polling swarm") {
+                Async::Ready(Some(RequestResponseEvent::Message {
+                    peer,
+                    message: RequestResponseMessage::Response { request_id: id, response }
+                })) => {
+                    assert_eq!(Some(id), request_id);
+                    assert_eq!(response, pong);
+                    assert_eq!(peer, peer1_id);
+                    count += 1;
+                    if count >= num_pings {
+                        return Ok(Async::Ready(()))
+                    }
+                    request_id = Some(swarm2.send_request(&peer1_id, ping.clone()));
+                }
+                Async::Ready(Some(e)) => panic!("Peer2: Unexpected event: {:?}", e),
+                Async::Ready(None) => panic!("Peer2: Swarm closed"),
+                Async::NotReady => return Ok(Async::NotReady),
+            }
+        }
+    });
+
+    let result = peer1.select(peer2).map_err(|_| panic!());
+    Runtime::new().unwrap().block_on(result).ok().unwrap();
+}
+
+fn mk_transport() -> (PeerId, impl Transport<
+    Output = (PeerId, impl StreamMuxer<Substream = impl Send, OutboundSubstream = impl Send, Error = impl Into<io::Error>>),
+    Listener = impl Send,
+    ListenerUpgrade = impl Send,
+    Dial = impl Send,
+    Error = impl fmt::Debug
+> + Clone) {
+    let id_keys = identity::Keypair::generate_ed25519();
+    let peer_id = id_keys.public().into_peer_id();
+    let transport = TcpConfig::new()
+        .nodelay(true)
+        .with_upgrade(SecioConfig::new(id_keys))
+        .and_then(move |out, endpoint| {
+            let peer_id = out.remote_key.into_peer_id();
+            let peer_id2 = peer_id.clone();
+            let upgrade = yamux::Config::default()
+                .map_outbound(move |muxer| (peer_id, muxer))
+                .map_inbound(move |muxer| (peer_id2, muxer));
+            upgrade::apply(out.stream, upgrade, endpoint)
+        });
+    (peer_id, transport)
+}
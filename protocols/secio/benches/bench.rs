@@ -19,7 +19,7 @@
 // DEALINGS IN THE SOFTWARE.
 
 use criterion::{Bencher, Criterion, criterion_main, criterion_group};
-use futures::prelude::*;
+use futures::{prelude::*, stream};
 use libp2p_core::Transport;
 use tokio::{
     io,
@@ -66,6 +66,52 @@ fn secio_and_send_data(bench: &mut Bencher, data: &[u8]) {
     })
 }
 
+fn secio_and_send_frames(bench: &mut Bencher, frame: &[u8], num_frames: usize) {
+    let key = libp2p_secio::SecioKeyPair::ed25519_generated().unwrap();
+    let transport =
+        libp2p_tcp::TcpConfig::new().with_upgrade(libp2p_secio::SecioConfig::new(key));
+
+    let frame_vec = frame.to_vec();
+
+    bench.iter(move || {
+        let (listener, addr) = transport
+            .clone()
+            .listen_on("/ip4/127.0.0.1/tcp/0".parse().unwrap())
+            .unwrap();
+
+        let listener_side = listener
+            .into_future()
+            .map_err(|(err, _)| err)
+            .and_then(|(client, _)| client.unwrap().0)
+            .map_err(|_| panic!())
+            .and_then(|client| io::read_to_end(client.stream, Vec::new()))
+            .and_then(move |msg| {
+                assert_eq!(msg.1.len(), frame.len() * num_frames);
+                Ok(())
+            });
+
+        let frame_vec = frame_vec.clone();
+        let dialer_side = transport
+            .clone()
+            .dial(addr)
+            .unwrap()
+            .map_err(|_| panic!())
+            .and_then(move |server| {
+                stream::iter_ok::<_, io::Error>(0 .. num_frames)
+                    .fold(server.stream, move |stream, _| {
+                        io::write_all(stream, frame_vec.clone()).map(|(stream, _)| stream)
+                    })
+            })
+            .map(|_| ());
+
+        let combined = listener_side.select(dialer_side)
+            .map_err(|(err, _)| panic!("{:?}", err))
+            .map(|_| ());
+        let mut rt = Runtime::new().unwrap();
+        rt.block_on(combined).unwrap();
+    })
+}
+
 fn raw_tcp_connect_and_send_data(bench: &mut Bencher, data: &[u8]) {
     let transport = libp2p_tcp::TcpConfig::new();
     let data_vec = data.to_vec();
@@ -118,6 +164,9 @@ fn criterion_benchmarks(bench: &mut Criterion) {
     let data = (0 .. 2 * 1024 * 1024).map(|_| rand::random::<u8>()).collect::<Vec<_>>();
     bench.bench_function("secio_connect_and_send_two_mb", { let data = data.clone(); move |b| secio_and_send_data(b, &data) });
     bench.bench_function("raw_tcp_connect_and_send_two_mb", move |b| raw_tcp_connect_and_send_data(b, &data));
+
+    let frame = (0 .. 100).map(|_| rand::random::<u8>()).collect::<Vec<_>>();
+    bench.bench_function("secio_connect_and_send_thousand_small_frames", move |b| secio_and_send_frames(b, &frame, 1000));
 }
 
 criterion_group!(benches, criterion_benchmarks);
@@ -0,0 +1,127 @@
+// Copyright 2019 Parity Technologies (UK) Ltd.
+//
+// Permission is hereby granted, free of charge, to any person obtaining a
+// copy of this software and associated documentation files (the "Software"),
+// to deal in the Software without restriction, including without limitation
+// the rights to use, copy, modify, merge, publish, distribute, sublicense,
+// and/or sell copies of the Software, and to permit persons to whom the
+// Software is furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS
+// OR IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING
+// FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
+// DEALINGS IN THE SOFTWARE.
+
+//! Cache of the decoded public keys of remotes.
+
+use libp2p_core::{PublicKey, identity::error::DecodingError};
+use std::{collections::{HashMap, VecDeque}, sync::{Arc, Mutex}};
+
+/// Default number of remote public keys that are remembered.
+pub(crate) const DEFAULT_KEY_CACHE_SIZE: usize = 1024;
+
+/// Cache of the public keys of remotes, by their protobuf encoding.
+///
+/// Decoding a public key validates it, which for secp256k1 and ed25519 keys involves
+/// decompressing a curve point. Remotes send the same encoded key on every connection, so the
+/// decoded key is remembered.
+///
+/// Cloning a `KeyCache` yields a handle to the same cache.
+#[derive(Clone)]
+pub(crate) struct KeyCache {
+    inner: Arc<Mutex<Inner>>,
+}
+
+struct Inner {
+    /// Maximum number of entries.
+    capacity: usize,
+    /// Decoded keys, by their encoding.
+    entries: HashMap<Vec<u8>, PublicKey>,
+    /// Keys in `entries`, from the oldest insertion to the newest.
+    order: VecDeque<Vec<u8>>,
+}
+
+impl KeyCache {
+    /// Creates a cache remembering at most `capacity` keys. A capacity of zero disables the
+    /// cache.
+    pub fn new(capacity: usize) -> Self {
+        KeyCache {
+            inner: Arc::new(Mutex::new(Inner {
+                capacity,
+                entries: HashMap::new(),
+                order: VecDeque::new(),
+            }))
+        }
+    }
+
+    /// Decodes a public key from its protobuf encoding, or returns the key decoded for a
+    /// previous handshake.
+    pub fn decode(&self, encoded: &[u8]) -> Result<PublicKey, DecodingError> {
+        if let Some(key) = self.inner.lock().expect("not poisoned").entries.get(encoded) {
+            return Ok(key.clone())
+        }
+
+        // The lock is not held during the decoding, so that concurrent handshakes don't wait
+        // for each other.
+        let key = PublicKey::from_protobuf_encoding(encoded)?;
+
+        let mut inner = self.inner.lock().expect("not poisoned");
+        if inner.capacity > 0 && !inner.entries.contains_key(encoded) {
+            inner.entries.insert(encoded.to_vec(), key.clone());
+            inner.order.push_back(encoded.to_vec());
+            while inner.order.len() > inner.capacity {
+                if let Some(oldest) = inner.order.pop_front() {
+                    inner.entries.remove(&oldest);
+                }
+            }
+        }
+        Ok(key)
+    }
+
+    /// Returns the number of keys in the cache.
+    #[cfg(test)]
+    fn len(&self) -> usize {
+        self.inner.lock().expect("not poisoned").entries.len()
+    }
+}
+
+impl Default for KeyCache {
+    fn default() -> Self {
+        KeyCache::new(DEFAULT_KEY_CACHE_SIZE)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use libp2p_core::identity;
+
+    #[test]
+    fn decoded_keys_are_remembered() {
+        let cache = KeyCache::new(2);
+        let keys = (0 .. 3)
+            .map(|_| identity::Keypair::generate_ed25519().public())
+            .collect::<Vec<_>>();
+
+        for key in &keys {
+            let encoded = key.clone().into_protobuf_encoding();
+            assert_eq!(&cache.decode(&encoded).unwrap(), key);
+            assert_eq!(&cache.decode(&encoded).unwrap(), key);
+        }
+        assert_eq!(cache.len(), 2);
+
+        assert!(cache.decode(&[1, 2, 3]).is_err());
+        assert_eq!(cache.len(), 2);
+
+        let disabled = KeyCache::new(0);
+        let encoded = keys[0].clone().into_protobuf_encoding();
+        assert_eq!(disabled.decode(&encoded).unwrap(), keys[0]);
+        assert_eq!(disabled.len(), 0);
+    }
+}
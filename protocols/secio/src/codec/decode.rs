@@ -68,12 +68,12 @@ where
     S: Stream<Item = BytesMut>,
     S::Error: Into<SecioError>,
 {
-    type Item = Vec<u8>;
+    type Item = BytesMut;
     type Error = SecioError;
 
     #[inline]
     fn poll(&mut self) -> Poll<Option<Self::Item>, Self::Error> {
-        let frame = match self.raw_stream.poll() {
+        let mut frame = match self.raw_stream.poll() {
             Ok(Async::Ready(Some(t))) => t,
             Ok(Async::Ready(None)) => return Ok(Async::Ready(None)),
             Ok(Async::NotReady) => return Ok(Async::NotReady),
@@ -95,21 +95,21 @@ where
             }
         }
 
-        let mut data_buf = frame.to_vec();
-        data_buf.truncate(content_length);
+        // The frame is decrypted in place rather than copied, to avoid allocating for each frame.
+        frame.truncate(content_length);
         self.cipher_state
-            .decrypt(&mut data_buf);
+            .decrypt(&mut frame[..]);
 
         if !self.nonce.is_empty() {
-            let n = min(data_buf.len(), self.nonce.len());
-            if data_buf[.. n] != self.nonce[.. n] {
+            let n = min(frame.len(), self.nonce.len());
+            if frame[.. n] != self.nonce[.. n] {
                 return Err(SecioError::NonceVerificationFailed)
             }
             self.nonce.drain(.. n);
-            data_buf.drain(.. n);
+            let _ = frame.split_to(n);
         }
 
-        Ok(Async::Ready(Some(data_buf)))
+        Ok(Async::Ready(Some(frame)))
     }
 }
 
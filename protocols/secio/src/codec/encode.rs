@@ -23,6 +23,10 @@
 use bytes::BytesMut;
 use super::{Hmac, StreamCipher};
 use futures::prelude::*;
+use std::cmp;
+
+/// Minimum capacity allocated at once for the encoded frames.
+const MIN_BUFFER_CAPACITY: usize = 16 * 1024;
 
 /// Wraps around a `Sink`. Encodes the buffers passed to it and passes it to the underlying sink.
 ///
@@ -35,7 +39,10 @@ pub struct EncoderMiddleware<S> {
     cipher_state: StreamCipher,
     hmac: Hmac,
     raw_sink: S,
-    pending: Option<BytesMut> // buffer encrypted data which can not be sent right away
+    pending: Option<BytesMut>, // buffer encrypted data which can not be sent right away
+    /// Buffer the frames are encoded into before being split off and passed to the sink. Its
+    /// allocation is reclaimed once the sink has released all the frames split off it.
+    buffer: BytesMut,
 }
 
 impl<S> EncoderMiddleware<S> {
@@ -44,7 +51,8 @@ impl<S> EncoderMiddleware<S> {
             cipher_state: cipher,
             hmac,
             raw_sink: raw,
-            pending: None
+            pending: None,
+            buffer: BytesMut::new(),
         }
     }
 }
@@ -56,7 +64,7 @@ where
     type SinkItem = BytesMut;
     type SinkError = S::SinkError;
 
-    fn start_send(&mut self, mut data_buf: Self::SinkItem) -> StartSend<Self::SinkItem, Self::SinkError> {
+    fn start_send(&mut self, data_buf: Self::SinkItem) -> StartSend<Self::SinkItem, Self::SinkError> {
         if let Some(data) = self.pending.take() {
             if let AsyncSink::NotReady(data) = self.raw_sink.start_send(data)? {
                 self.pending = Some(data);
@@ -64,11 +72,16 @@ where
             }
         }
         debug_assert!(self.pending.is_none());
+        let frame_len = data_buf.len() + self.hmac.num_bytes();
+        if self.buffer.capacity() < frame_len {
+            self.buffer.reserve(cmp::max(frame_len, MIN_BUFFER_CAPACITY));
+        }
+        self.buffer.extend_from_slice(&data_buf[..]);
         // TODO if SinkError gets refactor to SecioError, then use try_apply_keystream
-        self.cipher_state.encrypt(&mut data_buf[..]);
-        let signature = self.hmac.sign(&data_buf[..]);
-        data_buf.extend_from_slice(signature.as_ref());
-        if let AsyncSink::NotReady(data) = self.raw_sink.start_send(data_buf)? {
+        self.cipher_state.encrypt(&mut self.buffer[..]);
+        self.hmac.sign_in_place(&mut self.buffer);
+        let frame = self.buffer.take();
+        if let AsyncSink::NotReady(data) = self.raw_sink.start_send(frame)? {
             self.pending = Some(data)
         }
         Ok(AsyncSink::Ready)
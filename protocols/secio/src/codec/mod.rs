@@ -25,6 +25,7 @@ use self::decode::DecoderMiddleware;
 use self::encode::EncoderMiddleware;
 
 use aes_ctr::stream_cipher;
+use bytes::BytesMut;
 use crate::algo_support::Digest;
 use hmac::{self, Mac};
 use sha2::{Sha256, Sha512};
@@ -67,19 +68,18 @@ impl Hmac {
         }
     }
 
-    /// Signs the data.
-    // TODO: better return type?
-    pub fn sign(&self, crypted_data: &[u8]) -> Vec<u8> {
+    /// Signs the data in the buffer and appends the signature to it.
+    pub fn sign_in_place(&self, crypted_data: &mut BytesMut) {
         match *self {
             Hmac::Sha256(ref hmac) => {
                 let mut hmac = hmac.clone();
-                hmac.input(crypted_data);
-                hmac.result().code().to_vec()
+                hmac.input(&crypted_data[..]);
+                crypted_data.extend_from_slice(hmac.result().code().as_slice())
             },
             Hmac::Sha512(ref hmac) => {
                 let mut hmac = hmac.clone();
-                hmac.input(crypted_data);
-                hmac.result().code().to_vec()
+                hmac.input(&crypted_data[..]);
+                crypted_data.extend_from_slice(hmac.result().code().as_slice())
             },
         }
     }
@@ -231,7 +231,7 @@ mod tests {
 
         let mut rt = Runtime::new().unwrap();
         let received = rt.block_on(fin).unwrap();
-        assert_eq!(received, data);
+        assert_eq!(&received[..], &data[..]);
     }
 
     #[test]
@@ -21,7 +21,6 @@
 //! Defines the `SecioError` enum that groups all possible errors in SECIO.
 
 use aes_ctr::stream_cipher::LoopError;
-use protobuf::error::ProtobufError;
 use std::error;
 use std::fmt;
 use std::io::Error as IoError;
@@ -33,7 +32,7 @@ pub enum SecioError {
     IoError(IoError),
 
     /// Protocol buffer error.
-    ProtobufError(ProtobufError),
+    ProtobufError(prost::DecodeError),
 
     /// Failed to parse one of the handshake protobuf messages.
     HandshakeParsingFailure,
@@ -141,9 +140,9 @@ impl From<IoError> for SecioError {
     }
 }
 
-impl From<ProtobufError> for SecioError {
+impl From<prost::DecodeError> for SecioError {
     #[inline]
-    fn from(err: ProtobufError) -> SecioError {
+    fn from(err: prost::DecodeError) -> SecioError {
         SecioError::ProtobufError(err)
     }
 }
@@ -24,18 +24,18 @@ use crate::codec::{full_codec, FullCodec, Hmac};
 use crate::stream_cipher::{Cipher, ctr};
 use crate::error::SecioError;
 use crate::exchange;
-use futures::future;
+use futures::{future, Async, AsyncSink};
 use futures::sink::Sink;
 use futures::stream::Stream;
 use futures::Future;
 use libp2p_core::PublicKey;
 use log::{debug, trace};
-use protobuf::parse_from_bytes as protobuf_parse_from_bytes;
-use protobuf::Message as ProtobufMessage;
+use prost::Message;
 use rand::{self, RngCore};
 use sha2::{Digest as ShaDigestTrait, Sha256};
 use std::cmp::{self, Ordering};
 use std::io::{Error as IoError, ErrorKind as IoErrorKind};
+use std::sync::Arc;
 use crate::structs_proto::{Exchange, Propose};
 use tokio_io::codec::length_delimited;
 use tokio_io::{AsyncRead, AsyncWrite};
@@ -53,7 +53,7 @@ struct Local {
     // Locally-generated random number. The array size can be changed without any repercussion.
     nonce: [u8; 16],
     // Our encoded local public key
-    public_key_encoded: Vec<u8>,
+    public_key_encoded: Arc<Vec<u8>>,
     // Our local proposition's raw bytes:
     proposition_bytes: Vec<u8>
 }
@@ -113,38 +113,45 @@ impl HandshakeContext<()> {
             .try_fill_bytes(&mut nonce)
             .map_err(|_| SecioError::NonceGenerationFailed)?;
 
-        let public_key_encoded = self.config.key.public().into_protobuf_encoding();
+        let public_key_encoded = self.config.key_encoded.clone();
 
         // Send our proposition with our nonce, public key and supported protocols.
-        let mut proposition = Propose::new();
-        proposition.set_rand(nonce.to_vec());
-        proposition.set_pubkey(public_key_encoded.clone());
+        let mut proposition = Propose {
+            rand: Some(nonce.to_vec()),
+            pubkey: Some(public_key_encoded.to_vec()),
+            exchanges: None,
+            ciphers: None,
+            hashes: None,
+        };
 
         if let Some(ref p) = self.config.agreements_prop {
             trace!("agreements proposition: {}", p);
-            proposition.set_exchanges(p.clone())
+            proposition.exchanges = Some(p.clone());
         } else {
             trace!("agreements proposition: {}", algo_support::DEFAULT_AGREEMENTS_PROPOSITION);
-            proposition.set_exchanges(algo_support::DEFAULT_AGREEMENTS_PROPOSITION.into())
+            proposition.exchanges = Some(algo_support::DEFAULT_AGREEMENTS_PROPOSITION.into());
         }
 
         if let Some(ref p) = self.config.ciphers_prop {
             trace!("ciphers proposition: {}", p);
-            proposition.set_ciphers(p.clone())
+            proposition.ciphers = Some(p.clone());
         } else {
             trace!("ciphers proposition: {}", algo_support::DEFAULT_CIPHERS_PROPOSITION);
-            proposition.set_ciphers(algo_support::DEFAULT_CIPHERS_PROPOSITION.into())
+            proposition.ciphers = Some(algo_support::DEFAULT_CIPHERS_PROPOSITION.into());
         }
 
         if let Some(ref p) = self.config.digests_prop {
             trace!("digests proposition: {}", p);
-            proposition.set_hashes(p.clone())
+            proposition.hashes = Some(p.clone());
         } else {
             trace!("digests proposition: {}", algo_support::DEFAULT_DIGESTS_PROPOSITION);
-            proposition.set_hashes(algo_support::DEFAULT_DIGESTS_PROPOSITION.into())
+            proposition.hashes = Some(algo_support::DEFAULT_DIGESTS_PROPOSITION.into());
         }
 
-        let proposition_bytes = proposition.write_to_bytes()?;
+        let mut proposition_bytes = Vec::with_capacity(proposition.encoded_len());
+        proposition
+            .encode(&mut proposition_bytes)
+            .expect("Vec<u8> provides capacity as needed");
 
         Ok(HandshakeContext {
             config: self.config,
@@ -160,7 +167,7 @@ impl HandshakeContext<()> {
 impl HandshakeContext<Local> {
     // Process remote proposition.
     fn with_remote(self, b: BytesMut) -> Result<HandshakeContext<Remote>, SecioError> {
-        let mut prop = match protobuf_parse_from_bytes::<Propose>(&b) {
+        let prop = match Propose::decode(&b[..]) {
             Ok(prop) => prop,
             Err(_) => {
                 debug!("failed to parse remote's proposition protobuf message");
@@ -168,10 +175,10 @@ impl HandshakeContext<Local> {
             }
         };
 
-        let public_key_encoded = prop.take_pubkey();
-        let nonce = prop.take_rand();
+        let public_key_encoded = prop.pubkey.unwrap_or_default();
+        let nonce = prop.rand.unwrap_or_default();
 
-        let pubkey = match PublicKey::from_protobuf_encoding(&public_key_encoded) {
+        let pubkey = match self.config.remote_keys.decode(&public_key_encoded) {
             Ok(p) => p,
             Err(_) => {
                 debug!("failed to parse remote's proposition's pubkey protobuf");
@@ -191,7 +198,7 @@ impl HandshakeContext<Local> {
 
             let oh2 = {
                 let mut ctx = Sha256::new();
-                ctx.input(&self.state.public_key_encoded);
+                ctx.input(self.state.public_key_encoded.as_slice());
                 ctx.input(&nonce);
                 ctx.result()
             };
@@ -203,7 +210,7 @@ impl HandshakeContext<Local> {
             let ours = self.config.agreements_prop.as_ref()
                 .map(|s| s.as_ref())
                 .unwrap_or(algo_support::DEFAULT_AGREEMENTS_PROPOSITION);
-            let theirs = &prop.get_exchanges();
+            let theirs = prop.exchanges.as_ref().map(|s| s.as_str()).unwrap_or("");
             match algo_support::select_agreement(hashes_ordering, ours, theirs) {
                 Ok(a) => a,
                 Err(err) => {
@@ -217,7 +224,7 @@ impl HandshakeContext<Local> {
             let ours = self.config.ciphers_prop.as_ref()
                 .map(|s| s.as_ref())
                 .unwrap_or(algo_support::DEFAULT_CIPHERS_PROPOSITION);
-            let theirs = &prop.get_ciphers();
+            let theirs = prop.ciphers.as_ref().map(|s| s.as_str()).unwrap_or("");
             match algo_support::select_cipher(hashes_ordering, ours, theirs) {
                 Ok(a) => {
                     debug!("selected cipher: {:?}", a);
@@ -234,7 +241,7 @@ impl HandshakeContext<Local> {
             let ours = self.config.digests_prop.as_ref()
                 .map(|s| s.as_ref())
                 .unwrap_or(algo_support::DEFAULT_DIGESTS_PROPOSITION);
-            let theirs = &prop.get_hashes();
+            let theirs = prop.hashes.as_ref().map(|s| s.as_str()).unwrap_or("");
             match algo_support::select_digest(hashes_ordering, ours, theirs) {
                 Ok(a) => {
                     debug!("selected hash: {:?}", a);
@@ -354,15 +361,19 @@ where
                 data_to_sign.extend_from_slice(&context.state.remote.proposition_bytes);
                 data_to_sign.extend_from_slice(&tmp_pub_key);
 
-                let mut exchange = Exchange::new();
-                exchange.set_epubkey(tmp_pub_key);
-                match context.config.key.sign(&data_to_sign) {
-                    Ok(sig) => exchange.set_signature(sig),
+                let signature = match context.config.key.sign(&data_to_sign) {
+                    Ok(sig) => sig,
                     Err(_) => return Err(SecioError::SigningFailure)
+                };
+                Exchange {
+                    epubkey: Some(tmp_pub_key),
+                    signature: Some(signature),
                 }
-                exchange
             };
-            let local_exch = exchange.write_to_bytes()?;
+            let mut local_exch = Vec::with_capacity(exchange.encoded_len());
+            exchange
+                .encode(&mut local_exch)
+                .expect("Vec<u8> provides capacity as needed");
             Ok((BytesMut::from(local_exch), socket, context))
         })
         // Send our local `Exchange`.
@@ -386,7 +397,7 @@ where
                         },
                     };
 
-                    let remote_exch = match protobuf_parse_from_bytes::<Exchange>(&raw) {
+                    let remote_exch = match Exchange::decode(&raw[..]) {
                         Ok(e) => e,
                         Err(err) => {
                             debug!("failed to parse remote's exchange protobuf; {:?}", err);
@@ -404,9 +415,10 @@ where
         .and_then(|(remote_exch, socket, context)| {
             let mut data_to_verify = context.state.remote.proposition_bytes.clone();
             data_to_verify.extend_from_slice(&context.state.remote.local.proposition_bytes);
-            data_to_verify.extend_from_slice(remote_exch.get_epubkey());
+            data_to_verify.extend_from_slice(remote_exch.epubkey.as_ref().map_or(&[][..], |k| &k[..]));
 
-            if !context.state.remote.public_key.verify(&data_to_verify, remote_exch.get_signature()) {
+            let signature = remote_exch.signature.as_ref().map_or(&[][..], |s| &s[..]);
+            if !context.state.remote.public_key.verify(&data_to_verify, signature) {
                 return Err(SecioError::SignatureVerificationFailed)
             }
 
@@ -418,7 +430,7 @@ where
         .and_then(|(remote_exch, socket, context)| {
             let (context, local_priv_key) = context.take_private_key();
             let key_size = context.state.remote.chosen_hash.num_bytes();
-            exchange::agree(context.state.remote.chosen_exchange, local_priv_key, remote_exch.get_epubkey(), key_size)
+            exchange::agree(context.state.remote.chosen_exchange, local_priv_key, remote_exch.epubkey.as_ref().map_or(&[][..], |k| &k[..]), key_size)
                 .map(move |key_material| (socket, context, key_material))
         })
         // Generate a key from the local ephemeral private key and the remote ephemeral public key,
@@ -471,12 +483,31 @@ where
             Ok((codec, context))
         })
         // We send back their nonce to check if the connection works.
+        //
+        // The nonce is only buffered. It is flushed along with the first data written on the
+        // connection, which is typically the negotiation of the next upgrade, so that they are
+        // sent in the same flight rather than in two writes. The remote checks it before reading
+        // anything else, hence this doesn't weaken the handshake.
         .and_then(|(codec, context)| {
             let remote_nonce = context.state.remote.nonce.clone();
             trace!("checking encryption by sending back remote's nonce");
-            codec.send(BytesMut::from(remote_nonce))
-                .map(|s| (s, context.state.remote.public_key, context.state.local_tmp_pub_key))
-                .from_err()
+            let mut codec = Some(codec);
+            let mut nonce = Some(BytesMut::from(remote_nonce));
+            future::poll_fn(move || {
+                let mut inner = codec.take().expect("future is not polled after completion; QED");
+                let item = nonce.take().expect("future is not polled after completion; QED");
+                match inner.start_send(item) {
+                    Ok(AsyncSink::Ready) => Ok(Async::Ready(inner)),
+                    Ok(AsyncSink::NotReady(item)) => {
+                        codec = Some(inner);
+                        nonce = Some(item);
+                        Ok(Async::NotReady)
+                    }
+                    Err(err) => Err(err),
+                }
+            })
+            .map(|s| (s, context.state.remote.public_key, context.state.local_tmp_pub_key))
+            .from_err()
         })
 }
 
@@ -81,17 +81,22 @@ use log::debug;
 use rw_stream_sink::RwStreamSink;
 use std::io::{Error as IoError, ErrorKind as IoErrorKind};
 use std::iter;
+use std::sync::Arc;
 use tokio_io::{AsyncRead, AsyncWrite};
 
 mod algo_support;
+mod cache;
 mod codec;
 mod error;
 mod exchange;
 mod handshake;
-// #[allow(rust_2018_idioms)]
-mod structs_proto;
 mod stream_cipher;
 
+/// Protobuf messages of the secio handshake, generated from `structs.proto`.
+pub mod structs_proto {
+    include!(concat!(env!("OUT_DIR"), "/spipe.pb.rs"));
+}
+
 pub use crate::algo_support::Digest;
 pub use crate::exchange::KeyAgreement;
 pub use crate::stream_cipher::Cipher;
@@ -102,6 +107,10 @@ pub use crate::stream_cipher::Cipher;
 pub struct SecioConfig {
     /// Private and public keys of the local node.
     pub(crate) key: identity::Keypair,
+    /// Protobuf encoding of the local public key, computed once and sent in every handshake.
+    pub(crate) key_encoded: Arc<Vec<u8>>,
+    /// Public keys of the remotes of previous handshakes, already decoded.
+    pub(crate) remote_keys: cache::KeyCache,
     pub(crate) agreements_prop: Option<String>,
     pub(crate) ciphers_prop: Option<String>,
     pub(crate) digests_prop: Option<String>
@@ -111,7 +120,9 @@ impl SecioConfig {
     /// Create a new `SecioConfig` with the given keypair.
     pub fn new(kp: identity::Keypair) -> Self {
         SecioConfig {
+            key_encoded: Arc::new(kp.public().into_protobuf_encoding()),
             key: kp,
+            remote_keys: cache::KeyCache::default(),
             agreements_prop: None,
             ciphers_prop: None,
             digests_prop: None
@@ -145,6 +156,16 @@ impl SecioConfig {
         self
     }
 
+    /// Override the number of remote public keys that are remembered across handshakes, so
+    /// that reconnecting to a known remote doesn't decode its key again. The default is 1024,
+    /// and zero disables the cache.
+    ///
+    /// The cache is shared by the clones of this configuration.
+    pub fn key_cache_size(mut self, size: usize) -> Self {
+        self.remote_keys = cache::KeyCache::new(size);
+        self
+    }
+
     fn handshake<T>(self, socket: T) -> impl Future<Item=SecioOutput<T>, Error=SecioError>
     where
         T: AsyncRead + AsyncWrite + Send + 'static
@@ -269,7 +290,7 @@ impl<S> Stream for SecioMiddleware<S>
 where
     S: AsyncRead + AsyncWrite,
 {
-    type Item = Vec<u8>;
+    type Item = BytesMut;
     type Error = SecioError;
 
     #[inline]
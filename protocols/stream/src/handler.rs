@@ -0,0 +1,162 @@
+// Copyright 2019 Parity Technologies (UK) Ltd.
+//
+// Permission is hereby granted, free of charge, to any person obtaining a
+// copy of this software and associated documentation files (the "Software"),
+// to deal in the Software without restriction, including without limitation
+// the rights to use, copy, modify, merge, publish, distribute, sublicense,
+// and/or sell copies of the Software, and to permit persons to whom the
+// Software is furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS
+// OR IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING
+// FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
+// DEALINGS IN THE SOFTWARE.
+
+use crate::{OpenStreamError, RawStream, RawStreamsConfig, Registry};
+use crate::protocol::{AcceptProtocols, OpenProtocol};
+use futures::{prelude::*, sync::oneshot, task::AtomicTask};
+use libp2p_core::upgrade::{Negotiated, UpgradeError};
+use libp2p_swarm::{
+    KeepAlive,
+    SubstreamProtocol,
+    ProtocolsHandler,
+    ProtocolsHandlerEvent,
+    ProtocolsHandlerUpgrErr
+};
+use std::{collections::VecDeque, sync::Arc};
+use tokio_io::{AsyncRead, AsyncWrite};
+use void::Void;
+use wasm_timer::Instant;
+
+/// Request to open a substream, sent by the behaviour to the handler of a connection.
+pub struct OpenRequest<TSubstream> {
+    /// The protocol to negotiate on the substream.
+    pub(crate) protocol: Vec<u8>,
+    /// Channel on which the substream, or the reason why it couldn't be opened, is sent.
+    pub(crate) sender: oneshot::Sender<Result<RawStream<TSubstream>, OpenStreamError>>,
+}
+
+/// Protocol handler that opens substreams on request and accepts the inbound substreams of
+/// the registered protocols.
+pub struct RawStreamsHandler<TSubstream> {
+    /// The protocols accepted on inbound substreams, shared with the behaviour.
+    registry: Registry<TSubstream>,
+    /// Configuration options.
+    config: RawStreamsConfig,
+    /// Shared with every substream produced by the handler. The connection is kept alive as
+    /// long as one of them exists, and the handler is notified when one of them is dropped.
+    alive: Arc<AtomicTask>,
+    /// Last time the handler had a substream alive or being opened.
+    last_active: Instant,
+    /// Requests for which a substream must be opened.
+    pending_opens: VecDeque<OpenRequest<TSubstream>>,
+    /// Number of outbound substreams being opened or negotiated.
+    num_pending_outbound: usize,
+    /// Inbound substreams to report to the behaviour.
+    pending_inbound: VecDeque<RawStream<TSubstream>>,
+}
+
+impl<TSubstream> RawStreamsHandler<TSubstream> {
+    pub(crate) fn new(registry: Registry<TSubstream>, config: RawStreamsConfig) -> Self {
+        RawStreamsHandler {
+            registry,
+            config,
+            alive: Arc::new(AtomicTask::new()),
+            last_active: Instant::now(),
+            pending_opens: VecDeque::new(),
+            num_pending_outbound: 0,
+            pending_inbound: VecDeque::new(),
+        }
+    }
+
+    /// Returns `true` if a substream produced by the handler is alive or being opened.
+    fn is_active(&self) -> bool {
+        !self.pending_opens.is_empty()
+            || self.num_pending_outbound > 0
+            || !self.pending_inbound.is_empty()
+            || Arc::strong_count(&self.alive) > 1
+    }
+}
+
+impl<TSubstream> ProtocolsHandler for RawStreamsHandler<TSubstream>
+where
+    TSubstream: AsyncRead + AsyncWrite,
+{
+    type InEvent = OpenRequest<TSubstream>;
+    type OutEvent = RawStream<TSubstream>;
+    type Error = Void;
+    type Substream = TSubstream;
+    type InboundProtocol = AcceptProtocols;
+    type OutboundProtocol = OpenProtocol;
+    type OutboundOpenInfo = OpenRequest<TSubstream>;
+
+    fn listen_protocol(&self) -> SubstreamProtocol<Self::InboundProtocol> {
+        let protocols = self.registry.lock().keys().cloned().collect();
+        SubstreamProtocol::new(AcceptProtocols { protocols }).with_timeout(self.config.open_timeout)
+    }
+
+    fn inject_fully_negotiated_inbound(&mut self, (protocol, socket): (Vec<u8>, Negotiated<TSubstream>)) {
+        let stream = RawStream::new(protocol, socket, self.alive.clone());
+        self.pending_inbound.push_back(stream);
+    }
+
+    fn inject_fully_negotiated_outbound(&mut self, socket: Negotiated<TSubstream>, request: Self::OutboundOpenInfo) {
+        self.num_pending_outbound -= 1;
+        let stream = RawStream::new(request.protocol, socket, self.alive.clone());
+        let _ = request.sender.send(Ok(stream));
+    }
+
+    fn inject_event(&mut self, request: Self::InEvent) {
+        self.pending_opens.push_back(request);
+    }
+
+    fn inject_dial_upgrade_error(&mut self, request: Self::OutboundOpenInfo, error: ProtocolsHandlerUpgrErr<Void>) {
+        self.num_pending_outbound -= 1;
+        let error = match error {
+            ProtocolsHandlerUpgrErr::Timeout | ProtocolsHandlerUpgrErr::Timer => OpenStreamError::Timeout,
+            ProtocolsHandlerUpgrErr::Upgrade(UpgradeError::Select(e)) =>
+                OpenStreamError::Negotiation(UpgradeError::Select(e)),
+            ProtocolsHandlerUpgrErr::Upgrade(UpgradeError::Apply(e)) => void::unreachable(e),
+        };
+        let _ = request.sender.send(Err(error));
+    }
+
+    fn connection_keep_alive(&self) -> KeepAlive {
+        if self.is_active() {
+            KeepAlive::Yes
+        } else {
+            KeepAlive::Until(self.last_active + self.config.connection_keep_alive)
+        }
+    }
+
+    fn poll(&mut self) -> Poll<ProtocolsHandlerEvent<OpenProtocol, OpenRequest<TSubstream>, RawStream<TSubstream>>, Void> {
+        // Get notified when a substream is dropped, in order to update the keep-alive.
+        self.alive.register();
+        if self.is_active() {
+            self.last_active = Instant::now();
+        }
+
+        if let Some(stream) = self.pending_inbound.pop_front() {
+            return Ok(Async::Ready(ProtocolsHandlerEvent::Custom(stream)))
+        }
+
+        while let Some(request) = self.pending_opens.pop_front() {
+            // The application is no longer interested in the substream.
+            if request.sender.is_canceled() {
+                continue
+            }
+            self.num_pending_outbound += 1;
+            let protocol = OpenProtocol { protocol: request.protocol.clone() };
+            let protocol = SubstreamProtocol::new(protocol).with_timeout(self.config.open_timeout);
+            return Ok(Async::Ready(ProtocolsHandlerEvent::OutboundSubstreamRequest { protocol, info: request }))
+        }
+
+        Ok(Async::NotReady)
+    }
+}
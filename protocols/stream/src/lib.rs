@@ -0,0 +1,483 @@
+// Copyright 2019 Parity Technologies (UK) Ltd.
+//
+// Permission is hereby granted, free of charge, to any person obtaining a
+// copy of this software and associated documentation files (the "Software"),
+// to deal in the Software without restriction, including without limitation
+// the rights to use, copy, modify, merge, publish, distribute, sublicense,
+// and/or sell copies of the Software, and to permit persons to whom the
+// Software is furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS
+// OR IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING
+// FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
+// DEALINGS IN THE SOFTWARE.
+
+//! Raw substreams of application protocols.
+//!
+//! The [`RawStreams`] struct is a [`NetworkBehaviour`] that hands the substreams of arbitrary
+//! protocols to the application, once the protocol has been negotiated, without the need to
+//! write a [`ProtocolsHandler`](libp2p_swarm::ProtocolsHandler). The substreams implement
+//! `AsyncRead` and `AsyncWrite`, and what happens on them is entirely up to the application.
+//!
+//! The behaviour is driven through a [`Control`], obtained with [`RawStreams::control`], which
+//! can be cloned and moved to other tasks:
+//!
+//! - [`Control::accept`] registers a protocol and returns the stream of the inbound substreams
+//!   of this protocol. The protocol is unregistered when the stream is dropped.
+//! - [`Control::open_stream`] opens a substream to a peer, dialing it first if necessary.
+//!
+//! A connection is kept alive as long as one of its substreams produced by this behaviour
+//! exists.
+//!
+//! [`NetworkBehaviour`]: libp2p_swarm::NetworkBehaviour
+
+mod handler;
+mod protocol;
+
+pub use handler::{OpenRequest, RawStreamsHandler};
+pub use protocol::{AcceptProtocols, OpenProtocol};
+
+use futures::{prelude::*, sync::{mpsc, oneshot}, task::AtomicTask};
+use libp2p_core::{ConnectedPoint, Multiaddr, PeerId, upgrade::{Negotiated, UpgradeError}};
+use libp2p_swarm::{NetworkBehaviour, NetworkBehaviourAction, PollParameters};
+use log::debug;
+use parking_lot::Mutex;
+use smallvec::SmallVec;
+use std::{collections::{HashMap, HashSet, VecDeque}, error, fmt, io, sync::Arc, time::Duration};
+use tokio_io::{AsyncRead, AsyncWrite};
+use void::Void;
+
+/// The registered protocols, with the channels on which their inbound substreams are sent.
+pub(crate) type Registry<TSubstream> =
+    Arc<Mutex<HashMap<Vec<u8>, mpsc::Sender<(PeerId, RawStream<TSubstream>)>>>>;
+
+/// Configuration of a `RawStreams` behaviour.
+#[derive(Debug, Clone)]
+pub struct RawStreamsConfig {
+    connection_keep_alive: Duration,
+    open_timeout: Duration,
+    max_pending_inbound: usize,
+}
+
+impl Default for RawStreamsConfig {
+    fn default() -> Self {
+        RawStreamsConfig {
+            connection_keep_alive: Duration::from_secs(10),
+            open_timeout: Duration::from_secs(10),
+            max_pending_inbound: 16,
+        }
+    }
+}
+
+impl RawStreamsConfig {
+    /// Sets how long a connection is kept alive after its last substream has been dropped.
+    /// Defaults to 10 seconds.
+    pub fn with_connection_keep_alive(mut self, keep_alive: Duration) -> Self {
+        self.connection_keep_alive = keep_alive;
+        self
+    }
+
+    /// Sets how long the negotiation of the protocol of a substream may take. Defaults to
+    /// 10 seconds.
+    pub fn with_open_timeout(mut self, timeout: Duration) -> Self {
+        self.open_timeout = timeout;
+        self
+    }
+
+    /// Sets how many inbound substreams of a protocol may wait for the application to accept
+    /// them. Further inbound substreams are dropped. Defaults to 16.
+    pub fn with_max_pending_inbound(mut self, max: usize) -> Self {
+        self.max_pending_inbound = max;
+        self
+    }
+}
+
+/// A substream on which a protocol has been negotiated.
+pub struct RawStream<TSubstream> {
+    protocol: Vec<u8>,
+    inner: Negotiated<TSubstream>,
+    /// Notifies the handler of the connection when the substream is dropped.
+    alive: Arc<AtomicTask>,
+}
+
+impl<TSubstream> RawStream<TSubstream> {
+    pub(crate) fn new(protocol: Vec<u8>, inner: Negotiated<TSubstream>, alive: Arc<AtomicTask>) -> Self {
+        RawStream { protocol, inner, alive }
+    }
+
+    /// Returns the protocol negotiated on the substream.
+    pub fn protocol(&self) -> &[u8] {
+        &self.protocol
+    }
+}
+
+impl<TSubstream> Drop for RawStream<TSubstream> {
+    fn drop(&mut self) {
+        self.alive.notify();
+    }
+}
+
+impl<TSubstream> fmt::Debug for RawStream<TSubstream> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("RawStream")
+            .field("protocol", &String::from_utf8_lossy(&self.protocol))
+            .finish()
+    }
+}
+
+impl<TSubstream> io::Read for RawStream<TSubstream>
+where
+    TSubstream: AsyncRead,
+{
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        self.inner.read(buf)
+    }
+}
+
+impl<TSubstream> AsyncRead for RawStream<TSubstream>
+where
+    TSubstream: AsyncRead,
+{
+    unsafe fn prepare_uninitialized_buffer(&self, buf: &mut [u8]) -> bool {
+        self.inner.prepare_uninitialized_buffer(buf)
+    }
+}
+
+impl<TSubstream> io::Write for RawStream<TSubstream>
+where
+    TSubstream: AsyncWrite,
+{
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.inner.write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+impl<TSubstream> AsyncWrite for RawStream<TSubstream>
+where
+    TSubstream: AsyncWrite,
+{
+    fn shutdown(&mut self) -> Poll<(), io::Error> {
+        self.inner.shutdown()
+    }
+}
+
+/// Reason why a substream couldn't be opened.
+#[derive(Debug)]
+pub enum OpenStreamError {
+    /// We couldn't connect to the peer.
+    DialFailure,
+    /// The connection closed, or the behaviour has been dropped, before the substream was
+    /// opened.
+    ConnectionClosed,
+    /// The substream couldn't be opened or negotiated in time.
+    Timeout,
+    /// The negotiation of the protocol failed, for example because the remote doesn't
+    /// support it.
+    Negotiation(UpgradeError<Void>),
+}
+
+impl fmt::Display for OpenStreamError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            OpenStreamError::DialFailure => write!(f, "Failed to dial the peer"),
+            OpenStreamError::ConnectionClosed => write!(f, "Connection closed before the substream was opened"),
+            OpenStreamError::Timeout => write!(f, "Timeout while opening the substream"),
+            OpenStreamError::Negotiation(err) => write!(f, "Protocol negotiation failed: {}", err),
+        }
+    }
+}
+
+impl error::Error for OpenStreamError {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        match self {
+            OpenStreamError::Negotiation(err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+/// Error returned by [`Control::accept`] if the protocol is already registered.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AlreadyRegistered;
+
+impl fmt::Display for AlreadyRegistered {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "The protocol is already registered")
+    }
+}
+
+impl error::Error for AlreadyRegistered {}
+
+/// Handle to a `RawStreams` behaviour, through which substreams are opened and accepted.
+pub struct Control<TSubstream> {
+    registry: Registry<TSubstream>,
+    commands: mpsc::UnboundedSender<(PeerId, OpenRequest<TSubstream>)>,
+    max_pending_inbound: usize,
+}
+
+impl<TSubstream> Clone for Control<TSubstream> {
+    fn clone(&self) -> Self {
+        Control {
+            registry: self.registry.clone(),
+            commands: self.commands.clone(),
+            max_pending_inbound: self.max_pending_inbound,
+        }
+    }
+}
+
+impl<TSubstream> Control<TSubstream> {
+    /// Registers a protocol, and returns the stream of the inbound substreams on which it has
+    /// been negotiated, along with the peer that opened them.
+    ///
+    /// The protocol is unregistered when the returned stream is dropped.
+    pub fn accept(&self, protocol: impl Into<Vec<u8>>) -> Result<IncomingStreams<TSubstream>, AlreadyRegistered> {
+        let protocol = protocol.into();
+        let mut registry = self.registry.lock();
+        if registry.contains_key(&protocol) {
+            return Err(AlreadyRegistered)
+        }
+        let (sender, receiver) = mpsc::channel(self.max_pending_inbound);
+        registry.insert(protocol.clone(), sender);
+        Ok(IncomingStreams { receiver, registry: self.registry.clone(), protocol })
+    }
+
+    /// Opens a substream to a peer and negotiates the given protocol on it, dialing the peer
+    /// first if we're not connected to it.
+    pub fn open_stream(&self, peer_id: PeerId, protocol: impl Into<Vec<u8>>) -> OpenStream<TSubstream> {
+        let (sender, receiver) = oneshot::channel();
+        let request = OpenRequest { protocol: protocol.into(), sender };
+        // If the behaviour has been dropped, so is the request and the returned future
+        // produces an error.
+        let _ = self.commands.unbounded_send((peer_id, request));
+        OpenStream { receiver }
+    }
+}
+
+impl<TSubstream> fmt::Debug for Control<TSubstream> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Control").finish()
+    }
+}
+
+/// Stream of the inbound substreams of a registered protocol.
+pub struct IncomingStreams<TSubstream> {
+    receiver: mpsc::Receiver<(PeerId, RawStream<TSubstream>)>,
+    registry: Registry<TSubstream>,
+    protocol: Vec<u8>,
+}
+
+impl<TSubstream> Stream for IncomingStreams<TSubstream> {
+    type Item = (PeerId, RawStream<TSubstream>);
+    type Error = Void;
+
+    fn poll(&mut self) -> Poll<Option<Self::Item>, Void> {
+        // The registry holds a sender for as long as `self` exists, so the receiver never
+        // produces an error nor ends.
+        match self.receiver.poll() {
+            Ok(async_item) => Ok(async_item),
+            Err(()) => Ok(Async::Ready(None)),
+        }
+    }
+}
+
+impl<TSubstream> Drop for IncomingStreams<TSubstream> {
+    fn drop(&mut self) {
+        self.registry.lock().remove(&self.protocol);
+    }
+}
+
+impl<TSubstream> fmt::Debug for IncomingStreams<TSubstream> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("IncomingStreams")
+            .field("protocol", &String::from_utf8_lossy(&self.protocol))
+            .finish()
+    }
+}
+
+/// Future that resolves to a substream opened with [`Control::open_stream`].
+pub struct OpenStream<TSubstream> {
+    receiver: oneshot::Receiver<Result<RawStream<TSubstream>, OpenStreamError>>,
+}
+
+impl<TSubstream> Future for OpenStream<TSubstream> {
+    type Item = RawStream<TSubstream>;
+    type Error = OpenStreamError;
+
+    fn poll(&mut self) -> Poll<RawStream<TSubstream>, OpenStreamError> {
+        match self.receiver.poll() {
+            Ok(Async::Ready(Ok(stream))) => Ok(Async::Ready(stream)),
+            Ok(Async::Ready(Err(err))) => Err(err),
+            Ok(Async::NotReady) => Ok(Async::NotReady),
+            Err(oneshot::Canceled) => Err(OpenStreamError::ConnectionClosed),
+        }
+    }
+}
+
+impl<TSubstream> fmt::Debug for OpenStream<TSubstream> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("OpenStream").finish()
+    }
+}
+
+/// A `NetworkBehaviour` that opens and accepts the raw substreams of application protocols.
+///
+/// See the crate root documentation for more information.
+pub struct RawStreams<TSubstream> {
+    /// Configuration options.
+    config: RawStreamsConfig,
+    /// The registered protocols, shared with the handlers and the controls.
+    registry: Registry<TSubstream>,
+    /// Sender of the requests to open substreams, cloned into every control.
+    commands_sender: mpsc::UnboundedSender<(PeerId, OpenRequest<TSubstream>)>,
+    /// Receiver of the requests to open substreams.
+    commands: mpsc::UnboundedReceiver<(PeerId, OpenRequest<TSubstream>)>,
+    /// The peers we are connected to.
+    connected: HashSet<PeerId>,
+    /// Addresses of peers, in addition to the ones that the `Swarm` already knows about.
+    addresses: HashMap<PeerId, SmallVec<[Multiaddr; 6]>>,
+    /// Requests to open substreams to peers we are not connected to yet.
+    pending_requests: HashMap<PeerId, SmallVec<[OpenRequest<TSubstream>; 4]>>,
+    /// Queue of actions to return to the swarm.
+    pending_actions: VecDeque<NetworkBehaviourAction<OpenRequest<TSubstream>, Void>>,
+}
+
+impl<TSubstream> RawStreams<TSubstream> {
+    /// Creates a new `RawStreams` behaviour.
+    pub fn new(config: RawStreamsConfig) -> Self {
+        let (commands_sender, commands) = mpsc::unbounded();
+        RawStreams {
+            config,
+            registry: Arc::new(Mutex::new(HashMap::new())),
+            commands_sender,
+            commands,
+            connected: HashSet::new(),
+            addresses: HashMap::new(),
+            pending_requests: HashMap::new(),
+            pending_actions: VecDeque::new(),
+        }
+    }
+
+    /// Returns a handle through which substreams are opened and accepted.
+    pub fn control(&self) -> Control<TSubstream> {
+        Control {
+            registry: self.registry.clone(),
+            commands: self.commands_sender.clone(),
+            max_pending_inbound: self.config.max_pending_inbound,
+        }
+    }
+
+    /// Adds an address at which a peer can be dialed.
+    pub fn add_address(&mut self, peer: &PeerId, address: Multiaddr) {
+        let addresses = self.addresses.entry(peer.clone()).or_default();
+        if !addresses.contains(&address) {
+            addresses.push(address);
+        }
+    }
+
+    /// Removes an address of a peer previously added with `add_address`.
+    pub fn remove_address(&mut self, peer: &PeerId, address: &Multiaddr) {
+        if let Some(addresses) = self.addresses.get_mut(peer) {
+            addresses.retain(|a| a != address);
+            if addresses.is_empty() {
+                self.addresses.remove(peer);
+            }
+        }
+    }
+
+    /// Sends a request to open a substream to the handler of a connection to the peer, or
+    /// dials the peer if we're not connected to it.
+    fn open(&mut self, peer_id: PeerId, request: OpenRequest<TSubstream>) {
+        if self.connected.contains(&peer_id) {
+            self.pending_actions.push_back(NetworkBehaviourAction::SendEvent { peer_id, event: request });
+            return
+        }
+
+        let queue = self.pending_requests.entry(peer_id.clone()).or_default();
+        if queue.is_empty() {
+            self.pending_actions.push_back(NetworkBehaviourAction::DialPeer { peer_id });
+        }
+        queue.push(request);
+    }
+}
+
+impl<TSubstream> NetworkBehaviour for RawStreams<TSubstream>
+where
+    TSubstream: AsyncRead + AsyncWrite,
+{
+    type ProtocolsHandler = RawStreamsHandler<TSubstream>;
+    type OutEvent = Void;
+
+    fn new_handler(&mut self) -> Self::ProtocolsHandler {
+        RawStreamsHandler::new(self.registry.clone(), self.config.clone())
+    }
+
+    fn addresses_of_peer(&mut self, peer_id: &PeerId) -> Vec<Multiaddr> {
+        self.addresses.get(peer_id).map(|a| a.to_vec()).unwrap_or_default()
+    }
+
+    fn inject_connected(&mut self, peer_id: PeerId, _: ConnectedPoint) {
+        if let Some(requests) = self.pending_requests.remove(&peer_id) {
+            for request in requests {
+                self.pending_actions.push_back(NetworkBehaviourAction::SendEvent {
+                    peer_id: peer_id.clone(),
+                    event: request,
+                });
+            }
+        }
+        self.connected.insert(peer_id);
+    }
+
+    fn inject_disconnected(&mut self, peer_id: &PeerId, _: ConnectedPoint) {
+        self.connected.remove(peer_id);
+    }
+
+    fn inject_dial_failure(&mut self, peer_id: &PeerId) {
+        if let Some(requests) = self.pending_requests.remove(peer_id) {
+            for request in requests {
+                let _ = request.sender.send(Err(OpenStreamError::DialFailure));
+            }
+        }
+    }
+
+    fn inject_node_event(&mut self, peer_id: PeerId, stream: RawStream<TSubstream>) {
+        let mut registry = self.registry.lock();
+        match registry.get_mut(stream.protocol()) {
+            Some(sender) => {
+                if let Err(err) = sender.try_send((peer_id, stream)) {
+                    let (peer_id, stream) = err.into_inner();
+                    debug!("Dropping inbound substream of {:?} for protocol {}: too many pending substreams",
+                        peer_id, String::from_utf8_lossy(stream.protocol()));
+                }
+            }
+            None => {
+                debug!("Dropping inbound substream of {:?} for unregistered protocol {}",
+                    peer_id, String::from_utf8_lossy(stream.protocol()));
+            }
+        }
+    }
+
+    fn poll(&mut self, _: &mut impl PollParameters)
+        -> Async<NetworkBehaviourAction<OpenRequest<TSubstream>, Void>>
+    {
+        // `self` holds a sender, therefore the receiver never ends.
+        while let Ok(Async::Ready(Some((peer_id, request)))) = self.commands.poll() {
+            self.open(peer_id, request);
+        }
+
+        if let Some(action) = self.pending_actions.pop_front() {
+            return Async::Ready(action)
+        }
+
+        Async::NotReady
+    }
+}
@@ -0,0 +1,81 @@
+// Copyright 2019 Parity Technologies (UK) Ltd.
+//
+// Permission is hereby granted, free of charge, to any person obtaining a
+// copy of this software and associated documentation files (the "Software"),
+// to deal in the Software without restriction, including without limitation
+// the rights to use, copy, modify, merge, publish, distribute, sublicense,
+// and/or sell copies of the Software, and to permit persons to whom the
+// Software is furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS
+// OR IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING
+// FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
+// DEALINGS IN THE SOFTWARE.
+
+//! The upgrades applied on the raw substreams.
+//!
+//! Nothing happens on a substream after the protocol has been negotiated, the negotiated
+//! substream is handed to the application as is.
+
+use futures::future::{self, FutureResult};
+use libp2p_core::{InboundUpgrade, OutboundUpgrade, UpgradeInfo, upgrade::Negotiated};
+use smallvec::SmallVec;
+use std::iter;
+use void::Void;
+
+/// Upgrade applied on inbound substreams. Accepts any of the protocols registered at the time
+/// the substream was opened, and produces the negotiated protocol along with the substream.
+#[derive(Debug, Clone)]
+pub struct AcceptProtocols {
+    pub(crate) protocols: SmallVec<[Vec<u8>; 4]>,
+}
+
+impl UpgradeInfo for AcceptProtocols {
+    type Info = Vec<u8>;
+    type InfoIter = smallvec::IntoIter<[Vec<u8>; 4]>;
+
+    fn protocol_info(&self) -> Self::InfoIter {
+        self.protocols.clone().into_iter()
+    }
+}
+
+impl<TSocket> InboundUpgrade<TSocket> for AcceptProtocols {
+    type Output = (Vec<u8>, Negotiated<TSocket>);
+    type Error = Void;
+    type Future = FutureResult<Self::Output, Void>;
+
+    fn upgrade_inbound(self, socket: Negotiated<TSocket>, protocol: Self::Info) -> Self::Future {
+        future::ok((protocol, socket))
+    }
+}
+
+/// Upgrade applied on outbound substreams. Negotiates a single protocol.
+#[derive(Debug, Clone)]
+pub struct OpenProtocol {
+    pub(crate) protocol: Vec<u8>,
+}
+
+impl UpgradeInfo for OpenProtocol {
+    type Info = Vec<u8>;
+    type InfoIter = iter::Once<Vec<u8>>;
+
+    fn protocol_info(&self) -> Self::InfoIter {
+        iter::once(self.protocol.clone())
+    }
+}
+
+impl<TSocket> OutboundUpgrade<TSocket> for OpenProtocol {
+    type Output = Negotiated<TSocket>;
+    type Error = Void;
+    type Future = FutureResult<Self::Output, Void>;
+
+    fn upgrade_outbound(self, socket: Negotiated<TSocket>, _: Self::Info) -> Self::Future {
+        future::ok(socket)
+    }
+}
@@ -0,0 +1,199 @@
+// Copyright 2019 Parity Technologies (UK) Ltd.
+//
+// Permission is hereby granted, free of charge, to any person obtaining a
+// copy of this software and associated documentation files (the "Software"),
+// to deal in the Software without restriction, including without limitation
+// the rights to use, copy, modify, merge, publish, distribute, sublicense,
+// and/or sell copies of the Software, and to permit persons to whom the
+// Software is furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS
+// OR IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING
+// FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
+// DEALINGS IN THE SOFTWARE.
+
+//! Integration tests for the `RawStreams` network behaviour.
+
+use libp2p_core::{
+    Multiaddr,
+    PeerId,
+    identity,
+    muxing::StreamMuxer,
+    upgrade::{self, OutboundUpgradeExt, InboundUpgradeExt},
+    transport::Transport
+};
+use libp2p_stream::*;
+use libp2p_yamux as yamux;
+use libp2p_secio::SecioConfig;
+use libp2p_swarm::{Swarm, SwarmEvent};
+use libp2p_tcp::TcpConfig;
+use futures::{future, prelude::*};
+use std::{fmt, io, thread, time::Duration, sync::mpsc::{self, sync_channel}};
+use tokio::runtime::Runtime;
+use tokio_io::{AsyncRead, AsyncWrite};
+
+/// Connection events reported by the swarms spawned with `spawn_swarm`.
+#[derive(Debug, PartialEq, Eq)]
+enum ConnectionEvent {
+    Established(PeerId),
+    Closed(PeerId),
+}
+
+#[test]
+fn open_and_accept() {
+    let mut runtime = Runtime::new().unwrap();
+    let (peer1_id, control1, addr, _) = spawn_swarm(&mut runtime, RawStreamsConfig::default(), None);
+    let (peer2_id, control2, _, _) = spawn_swarm(&mut runtime, RawStreamsConfig::default(), Some((peer1_id.clone(), addr)));
+
+    // Peer 1 reads everything sent on the first substream it accepts.
+    let incoming = control1.accept(b"/echo/1".to_vec()).unwrap();
+    let accept = incoming.into_future()
+        .map_err(|(e, _)| void::unreachable(e))
+        .and_then(|(item, incoming)| {
+            let (peer, stream) = item.expect("Protocol unregistered while accepting");
+            assert_eq!(stream.protocol(), b"/echo/1");
+            tokio_io::io::read_to_end(stream, Vec::new())
+                .map(move |(stream, data)| (peer, data, stream, incoming))
+                .map_err(|e| panic!("Failed to read from the substream: {:?}", e))
+        });
+
+    // Peer 2 dials peer 1, opens a substream and sends a message on it.
+    let open = control2.open_stream(peer1_id.clone(), b"/echo/1".to_vec())
+        .map_err(|e| panic!("Failed to open the substream: {:?}", e))
+        .and_then(|stream| {
+            assert_eq!(stream.protocol(), b"/echo/1");
+            tokio_io::io::write_all(stream, b"hello".to_vec())
+                .and_then(|(stream, _)| tokio_io::io::shutdown(stream))
+                .map_err(|e| panic!("Failed to write to the substream: {:?}", e))
+        });
+
+    let ((peer, data, _, _), _) = runtime.block_on(accept.join(open)).unwrap();
+    assert_eq!(peer, peer2_id);
+    assert_eq!(data, b"hello");
+}
+
+#[test]
+fn unsupported_protocol_is_rejected() {
+    let mut runtime = Runtime::new().unwrap();
+    let (peer1_id, control1, addr, _) = spawn_swarm(&mut runtime, RawStreamsConfig::default(), None);
+    let (_, control2, _, _) = spawn_swarm(&mut runtime, RawStreamsConfig::default(), Some((peer1_id.clone(), addr)));
+
+    let _incoming = control1.accept(b"/echo/1".to_vec()).unwrap();
+    // A protocol can only be registered once.
+    assert_eq!(control1.accept(b"/echo/1".to_vec()).unwrap_err(), AlreadyRegistered);
+
+    let open = control2.open_stream(peer1_id, b"/unsupported/1".to_vec())
+        .then(|result| Ok::<_, ()>(result));
+    match runtime.block_on(open).unwrap() {
+        Err(OpenStreamError::Negotiation(_)) => {}
+        Err(e) => panic!("Unexpected error: {:?}", e),
+        Ok(stream) => panic!("Unexpected substream: {:?}", stream),
+    }
+}
+
+#[test]
+fn connection_kept_alive_while_streams_exist() {
+    let keep_alive = Duration::from_millis(500);
+    let config = RawStreamsConfig::default().with_connection_keep_alive(keep_alive);
+
+    let mut runtime = Runtime::new().unwrap();
+    let (peer1_id, control1, addr, _) = spawn_swarm(&mut runtime, config.clone(), None);
+    let (_, control2, _, events) = spawn_swarm(&mut runtime, config, Some((peer1_id.clone(), addr)));
+
+    let incoming = control1.accept(b"/echo/1".to_vec()).unwrap();
+    let accept = incoming.into_future()
+        .map_err(|(e, _)| void::unreachable(e))
+        .map(|(item, _)| item.expect("Protocol unregistered while accepting").1);
+    let open = control2.open_stream(peer1_id.clone(), b"/echo/1".to_vec())
+        .map_err(|e| panic!("Failed to open the substream: {:?}", e));
+    let (inbound, outbound) = runtime.block_on(accept.join(open)).unwrap();
+    assert_eq!(events.recv().unwrap(), ConnectionEvent::Established(peer1_id.clone()));
+
+    // The connection outlives the keep-alive duration as long as the substreams exist.
+    thread::sleep(keep_alive * 3);
+    assert!(events.try_recv().is_err());
+
+    // Once they are dropped, the connection is closed after the keep-alive duration.
+    drop(inbound);
+    drop(outbound);
+    let event = events.recv_timeout(Duration::from_secs(10)).expect("Connection not closed");
+    assert_eq!(event, ConnectionEvent::Closed(peer1_id));
+}
+
+/// Spawns a swarm with a `RawStreams` behaviour on the runtime, listening on a local TCP port.
+///
+/// If `peer` is set, the address of this peer is added to the behaviour. Returns the identity of
+/// the swarm, a control of its behaviour, the address it listens on, and the receiver of its
+/// connection events.
+fn spawn_swarm(runtime: &mut Runtime, config: RawStreamsConfig, peer: Option<(PeerId, Multiaddr)>)
+    -> (PeerId, Control<impl AsyncRead + AsyncWrite + Send>, Multiaddr, mpsc::Receiver<ConnectionEvent>)
+{
+    let (peer_id, trans) = mk_transport();
+    let mut behaviour = RawStreams::new(config);
+    if let Some((peer, addr)) = peer {
+        behaviour.add_address(&peer, addr);
+    }
+    let control = behaviour.control();
+    let mut swarm = Swarm::new(trans, behaviour, peer_id.clone());
+
+    let addr = "/ip4/127.0.0.1/tcp/0".parse().unwrap();
+    Swarm::listen_on(&mut swarm, addr).unwrap();
+
+    let (listen_tx, listen_rx) = sync_channel::<Multiaddr>(1);
+    let (events_tx, events_rx) = mpsc::channel();
+    let mut listening = false;
+    runtime.spawn(future::poll_fn(move || -> Result<_, ()> {
+        loop {
+            match Swarm::poll_event(&mut swarm).expect("Error while polling swarm") {
+                Async::Ready(SwarmEvent::Behaviour(event)) => void::unreachable(event),
+                Async::Ready(SwarmEvent::ConnectionEstablished { peer_id, .. }) => {
+                    let _ = events_tx.send(ConnectionEvent::Established(peer_id));
+                }
+                Async::Ready(SwarmEvent::ConnectionClosed { peer_id, .. }) => {
+                    let _ = events_tx.send(ConnectionEvent::Closed(peer_id));
+                }
+                Async::Ready(_) => {}
+                Async::NotReady => {
+                    if !listening {
+                        for l in Swarm::listeners(&swarm) {
+                            listen_tx.send(l.clone()).unwrap();
+                            listening = true;
+                        }
+                    }
+                    return Ok(Async::NotReady)
+                }
+            }
+        }
+    }));
+
+    (peer_id, control, listen_rx.recv().unwrap(), events_rx)
+}
+
+fn mk_transport() -> (PeerId, impl Transport<
+    Output = (PeerId, impl StreamMuxer<Substream = impl Send, OutboundSubstream = impl Send, Error = impl Into<io::Error>>),
+    Listener = impl Send,
+    ListenerUpgrade = impl Send,
+    Dial = impl Send,
+    Error = impl fmt::Debug
+> + Clone) {
+    let id_keys = identity::Keypair::generate_ed25519();
+    let peer_id = id_keys.public().into_peer_id();
+    let transport = TcpConfig::new()
+        .nodelay(true)
+        .with_upgrade(SecioConfig::new(id_keys))
+        .and_then(move |out, endpoint| {
+            let peer_id = out.remote_key.into_peer_id();
+            let peer_id2 = peer_id.clone();
+            let upgrade = yamux::Config::default()
+                .map_outbound(move |muxer| (peer_id, muxer))
+                .map_inbound(move |muxer| (peer_id2, muxer));
+            upgrade::apply(out.stream, upgrade, endpoint)
+        });
+    (peer_id, transport)
+}
@@ -18,15 +18,18 @@
 // FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
 // DEALINGS IN THE SOFTWARE.
 
-use crate::{Multiaddr, core::{Transport, transport::{ListenerEvent, TransportError}}};
+use crate::{Multiaddr, introspection, core::{Transport, muxing::StreamMuxer, transport::{ListenerEvent, TransportError}}};
 use futures::{prelude::*, try_ready};
 use lazy_static::lazy_static;
 use parking_lot::Mutex;
 use smallvec::{smallvec, SmallVec};
-use std::{cmp, io, io::Read, io::Write, sync::Arc, time::Duration};
+use std::{cmp, collections::HashMap, io, io::Read, io::Write, sync::Arc, time::Duration};
 use wasm_timer::Instant;
 
 /// Wraps around a `Transport` and logs the bandwidth that goes through all the opened connections.
+///
+/// The bandwidth can be retrieved from the `BandwidthSinks` returned by `new`, both in total and
+/// broken down by remote address.
 #[derive(Clone)]
 pub struct BandwidthLogging<TInner> {
     inner: TInner,
@@ -37,14 +40,12 @@ impl<TInner> BandwidthLogging<TInner> {
     /// Creates a new `BandwidthLogging` around the transport.
     #[inline]
     pub fn new(inner: TInner, period: Duration) -> (Self, Arc<BandwidthSinks>) {
-        let mut period_seconds = cmp::min(period.as_secs(), 86400) as u32;
-        if period.subsec_nanos() > 0 {
-            period_seconds += 1;
-        }
+        let period_seconds = period_to_seconds(period);
 
         let sink = Arc::new(BandwidthSinks {
-            download: Mutex::new(BandwidthSink::new(period_seconds)),
-            upload: Mutex::new(BandwidthSink::new(period_seconds)),
+            period_seconds,
+            total: Mutex::new(BandwidthCounters::new(period_seconds)),
+            addresses: Mutex::new(HashMap::new()),
         });
 
         let trans = BandwidthLogging {
@@ -76,8 +77,8 @@ where
     fn dial(self, addr: Multiaddr) -> Result<Self::Dial, TransportError<Self::Error>> {
         let sinks = self.sinks;
         self.inner
-            .dial(addr)
-            .map(move |fut| BandwidthFuture { inner: fut, sinks })
+            .dial(addr.clone())
+            .map(move |fut| BandwidthFuture { inner: fut, sinks, remote_addr: Some(addr) })
     }
 }
 
@@ -101,9 +102,18 @@ where
             None => return Ok(Async::Ready(None))
         };
 
-        let event = event.map(|inner| {
-            BandwidthFuture { inner, sinks: self.sinks.clone() }
-        });
+        let event = match event {
+            ListenerEvent::Upgrade { upgrade, listen_addr, remote_addr } => {
+                let upgrade = BandwidthFuture {
+                    inner: upgrade,
+                    sinks: self.sinks.clone(),
+                    remote_addr: Some(remote_addr.clone()),
+                };
+                ListenerEvent::Upgrade { upgrade, listen_addr, remote_addr }
+            }
+            ListenerEvent::NewAddress(a) => ListenerEvent::NewAddress(a),
+            ListenerEvent::AddressExpired(a) => ListenerEvent::AddressExpired(a),
+        };
 
         Ok(Async::Ready(Some(event)))
     }
@@ -114,6 +124,8 @@ where
 pub struct BandwidthFuture<TInner> {
     inner: TInner,
     sinks: Arc<BandwidthSinks>,
+    /// Address of the remote. Taken when the connection is produced.
+    remote_addr: Option<Multiaddr>,
 }
 
 impl<TInner> Future for BandwidthFuture<TInner>
@@ -124,30 +136,131 @@ impl<TInner> Future for BandwidthFuture<TInner>
 
     fn poll(&mut self) -> Poll<Self::Item, Self::Error> {
         let inner = try_ready!(self.inner.poll());
+        let remote_addr = self.remote_addr.take()
+            .expect("BandwidthFuture polled after having produced its connection");
+        let address = self.sinks.address_counters(&remote_addr);
         Ok(Async::Ready(BandwidthConnecLogging {
             inner,
             sinks: self.sinks.clone(),
+            remote_addr,
+            address,
         }))
     }
 }
 
-/// Allows obtaining the average bandwidth of the connections created from a `BandwidthLogging`.
+/// Allows obtaining the bandwidth of the connections created from a `BandwidthLogging`.
+///
+/// This is shared through an `Arc`, and querying it only requires locking the counters for a
+/// short time, so it can be polled frequently, for example to display transfer rates or to
+/// throttle traffic.
 pub struct BandwidthSinks {
-    download: Mutex<BandwidthSink>,
-    upload: Mutex<BandwidthSink>,
+    /// Number of seconds over which the rolling averages are calculated.
+    period_seconds: u32,
+    /// Counters of all the connections.
+    total: Mutex<BandwidthCounters>,
+    /// Counters of the connections to each remote address that is currently connected.
+    addresses: Mutex<HashMap<Multiaddr, Arc<Mutex<BandwidthCounters>>>>,
+}
+
+/// Bandwidth used by connections, as reported by `BandwidthSinks`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BandwidthStats {
+    /// Total number of bytes that have been downloaded.
+    pub total_download: u64,
+    /// Total number of bytes that have been uploaded.
+    pub total_upload: u64,
+    /// Average number of bytes that have been downloaded per second over the period.
+    pub average_download_per_sec: u64,
+    /// Average number of bytes that have been uploaded per second over the period.
+    pub average_upload_per_sec: u64,
 }
 
 impl BandwidthSinks {
     /// Returns the average number of bytes that have been downloaded in the period.
     #[inline]
     pub fn average_download_per_sec(&self) -> u64 {
-        self.download.lock().get()
+        self.total.lock().download.get()
     }
 
     /// Returns the average number of bytes that have been uploaded in the period.
     #[inline]
     pub fn average_upload_per_sec(&self) -> u64 {
-        self.upload.lock().get()
+        self.total.lock().upload.get()
+    }
+
+    /// Returns the total number of bytes that have been downloaded.
+    #[inline]
+    pub fn total_download(&self) -> u64 {
+        self.total.lock().total_download
+    }
+
+    /// Returns the total number of bytes that have been uploaded.
+    #[inline]
+    pub fn total_upload(&self) -> u64 {
+        self.total.lock().total_upload
+    }
+
+    /// Returns the bandwidth statistics of all the connections.
+    pub fn stats(&self) -> BandwidthStats {
+        self.total.lock().stats()
+    }
+
+    /// Returns the bandwidth statistics of the connections to the given remote address, or
+    /// `None` if there is no open connection to this address.
+    pub fn address_stats(&self, addr: &Multiaddr) -> Option<BandwidthStats> {
+        self.addresses.lock()
+            .get(addr)
+            .map(|counters| counters.lock().stats())
+    }
+
+    /// Returns the bandwidth statistics of each remote address with at least one open
+    /// connection.
+    ///
+    /// An address is forgotten once all the connections to it are closed, while its traffic
+    /// remains accounted for in `stats`.
+    pub fn addresses_stats(&self) -> Vec<(Multiaddr, BandwidthStats)> {
+        self.addresses.lock()
+            .iter()
+            .map(|(addr, counters)| (addr.clone(), counters.lock().stats()))
+            .collect()
+    }
+
+    /// Returns the counters of a remote address, inserting them if necessary.
+    fn address_counters(&self, addr: &Multiaddr) -> Arc<Mutex<BandwidthCounters>> {
+        let period_seconds = self.period_seconds;
+        self.addresses.lock()
+            .entry(addr.clone())
+            .or_insert_with(|| Arc::new(Mutex::new(BandwidthCounters::new(period_seconds))))
+            .clone()
+    }
+
+    fn inject_read(&self, address: &Mutex<BandwidthCounters>, bytes: usize) {
+        self.total.lock().inject_read(bytes);
+        address.lock().inject_read(bytes);
+    }
+
+    fn inject_written(&self, address: &Mutex<BandwidthCounters>, bytes: usize) {
+        self.total.lock().inject_written(bytes);
+        address.lock().inject_written(bytes);
+    }
+}
+
+/// Fills in the traffic of the node and of each of its connections in introspection snapshots.
+impl introspection::StateProvider for BandwidthSinks {
+    fn provide(&self, state: &mut introspection::State) {
+        state.traffic = Some(traffic(self.stats()));
+        for connection in &mut state.connections {
+            connection.traffic = self.address_stats(&connection.remote_addr).map(traffic);
+        }
+    }
+}
+
+fn traffic(stats: BandwidthStats) -> introspection::Traffic {
+    introspection::Traffic {
+        total_in: stats.total_download,
+        total_out: stats.total_upload,
+        rate_in: stats.average_download_per_sec,
+        rate_out: stats.average_upload_per_sec,
     }
 }
 
@@ -155,6 +268,21 @@ impl BandwidthSinks {
 pub struct BandwidthConnecLogging<TInner> {
     inner: TInner,
     sinks: Arc<BandwidthSinks>,
+    /// Address of the remote.
+    remote_addr: Multiaddr,
+    /// Counters of the remote address, shared with the other connections to the same address.
+    address: Arc<Mutex<BandwidthCounters>>,
+}
+
+impl<TInner> Drop for BandwidthConnecLogging<TInner> {
+    fn drop(&mut self) {
+        // New clones of the counters are only created while holding the lock on `addresses`,
+        // so the count can't increase in the meantime.
+        let mut addresses = self.sinks.addresses.lock();
+        if Arc::strong_count(&self.address) <= 2 {
+            addresses.remove(&self.remote_addr);
+        }
+    }
 }
 
 impl<TInner> Read for BandwidthConnecLogging<TInner>
@@ -163,7 +291,7 @@ impl<TInner> Read for BandwidthConnecLogging<TInner>
     #[inline]
     fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
         let num_bytes = self.inner.read(buf)?;
-        self.sinks.download.lock().inject(num_bytes);
+        self.sinks.inject_read(&self.address, num_bytes);
         Ok(num_bytes)
     }
 }
@@ -186,7 +314,7 @@ impl<TInner> Write for BandwidthConnecLogging<TInner>
     #[inline]
     fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
         let num_bytes = self.inner.write(buf)?;
-        self.sinks.upload.lock().inject(num_bytes);
+        self.sinks.inject_written(&self.address, num_bytes);
         Ok(num_bytes)
     }
 
@@ -205,6 +333,434 @@ impl<TInner> tokio_io::AsyncWrite for BandwidthConnecLogging<TInner>
     }
 }
 
+/// Name under which `ProtocolBandwidthSinks` reports the traffic of substreams whose protocol
+/// couldn't be determined.
+pub const UNKNOWN_PROTOCOL: &str = "<unknown>";
+
+/// Wraps around a `StreamMuxer` and logs the bandwidth that goes through its substreams, grouped
+/// by the protocol negotiated on each substream.
+///
+/// The protocol of a substream is determined by observing the multistream-select messages that
+/// are exchanged at the beginning of the substream. The bytes of the negotiation itself are
+/// attributed to the negotiated protocol as well.
+///
+/// This is typically applied to the output of a transport with `Transport::map`:
+///
+/// ```ignore
+/// let sinks = ProtocolBandwidthSinks::new(Duration::from_secs(5));
+/// let transport = transport.map({
+///     let sinks = sinks.clone();
+///     move |(peer_id, muxer), _| (peer_id, BandwidthMuxer::new(muxer, sinks))
+/// });
+/// ```
+pub struct BandwidthMuxer<TMuxer> {
+    inner: TMuxer,
+    sinks: Arc<ProtocolBandwidthSinks>,
+}
+
+impl<TMuxer> BandwidthMuxer<TMuxer> {
+    /// Creates a new `BandwidthMuxer` around the muxer, reporting to the given sinks.
+    #[inline]
+    pub fn new(inner: TMuxer, sinks: Arc<ProtocolBandwidthSinks>) -> Self {
+        BandwidthMuxer { inner, sinks }
+    }
+
+    fn wrap<TSubstream>(&self, inner: TSubstream) -> BandwidthSubstream<TSubstream> {
+        BandwidthSubstream {
+            inner,
+            state: SubstreamState::Sniffing {
+                sniffer: ProtocolSniffer::default(),
+                read: 0,
+                written: 0,
+            },
+        }
+    }
+}
+
+impl<TMuxer> StreamMuxer for BandwidthMuxer<TMuxer>
+where
+    TMuxer: StreamMuxer,
+{
+    type Substream = BandwidthSubstream<TMuxer::Substream>;
+    type OutboundSubstream = TMuxer::OutboundSubstream;
+    type Error = TMuxer::Error;
+
+    fn poll_inbound(&self) -> Poll<Self::Substream, Self::Error> {
+        let substream = try_ready!(self.inner.poll_inbound());
+        Ok(Async::Ready(self.wrap(substream)))
+    }
+
+    #[inline]
+    fn open_outbound(&self) -> Self::OutboundSubstream {
+        self.inner.open_outbound()
+    }
+
+    fn poll_outbound(&self, s: &mut Self::OutboundSubstream) -> Poll<Self::Substream, Self::Error> {
+        let substream = try_ready!(self.inner.poll_outbound(s));
+        Ok(Async::Ready(self.wrap(substream)))
+    }
+
+    #[inline]
+    fn destroy_outbound(&self, s: Self::OutboundSubstream) {
+        self.inner.destroy_outbound(s)
+    }
+
+    unsafe fn prepare_uninitialized_buffer(&self, buf: &mut [u8]) -> bool {
+        self.inner.prepare_uninitialized_buffer(buf)
+    }
+
+    fn read_substream(&self, s: &mut Self::Substream, buf: &mut [u8]) -> Poll<usize, Self::Error> {
+        let num_bytes = try_ready!(self.inner.read_substream(&mut s.inner, buf));
+        s.state.on_read(&buf[..num_bytes], &self.sinks);
+        Ok(Async::Ready(num_bytes))
+    }
+
+    fn write_substream(&self, s: &mut Self::Substream, buf: &[u8]) -> Poll<usize, Self::Error> {
+        let num_bytes = try_ready!(self.inner.write_substream(&mut s.inner, buf));
+        s.state.on_written(&buf[..num_bytes], &self.sinks);
+        Ok(Async::Ready(num_bytes))
+    }
+
+    #[inline]
+    fn flush_substream(&self, s: &mut Self::Substream) -> Poll<(), Self::Error> {
+        self.inner.flush_substream(&mut s.inner)
+    }
+
+    #[inline]
+    fn shutdown_substream(&self, s: &mut Self::Substream) -> Poll<(), Self::Error> {
+        self.inner.shutdown_substream(&mut s.inner)
+    }
+
+    fn destroy_substream(&self, mut s: Self::Substream) {
+        s.state.give_up(&self.sinks);
+        self.inner.destroy_substream(s.inner)
+    }
+
+    #[inline]
+    fn is_remote_acknowledged(&self) -> bool {
+        self.inner.is_remote_acknowledged()
+    }
+
+    #[inline]
+    fn close(&self) -> Poll<(), Self::Error> {
+        self.inner.close()
+    }
+
+    #[inline]
+    fn flush_all(&self) -> Poll<(), Self::Error> {
+        self.inner.flush_all()
+    }
+}
+
+/// Substream of a `BandwidthMuxer`.
+pub struct BandwidthSubstream<TInner> {
+    inner: TInner,
+    state: SubstreamState,
+}
+
+/// Whether we know the protocol of a substream.
+enum SubstreamState {
+    /// The protocol is still being negotiated. Contains the number of bytes transferred so far,
+    /// which will be attributed to the protocol once it is known.
+    Sniffing {
+        sniffer: ProtocolSniffer,
+        read: usize,
+        written: usize,
+    },
+    /// The protocol is known.
+    Known(Arc<Mutex<BandwidthCounters>>),
+}
+
+impl SubstreamState {
+    fn on_read(&mut self, data: &[u8], sinks: &ProtocolBandwidthSinks) {
+        match self {
+            SubstreamState::Known(counters) => counters.lock().inject_read(data.len()),
+            SubstreamState::Sniffing { sniffer, read, .. } => {
+                *read += data.len();
+                sniffer.on_read(data);
+                self.try_resolve(sinks);
+            }
+        }
+    }
+
+    fn on_written(&mut self, data: &[u8], sinks: &ProtocolBandwidthSinks) {
+        match self {
+            SubstreamState::Known(counters) => counters.lock().inject_written(data.len()),
+            SubstreamState::Sniffing { sniffer, written, .. } => {
+                *written += data.len();
+                sniffer.on_written(data);
+                self.try_resolve(sinks);
+            }
+        }
+    }
+
+    /// If the protocol has been determined, or if we failed to determine it, switches to the
+    /// `Known` state.
+    fn try_resolve(&mut self, sinks: &ProtocolBandwidthSinks) {
+        let protocol = match self {
+            SubstreamState::Sniffing { sniffer, .. } => match sniffer.protocol() {
+                Some(p) => String::from_utf8_lossy(p).into_owned(),
+                None if sniffer.has_failed() => UNKNOWN_PROTOCOL.to_owned(),
+                None => return,
+            },
+            SubstreamState::Known(_) => return,
+        };
+        self.resolve(protocol, sinks)
+    }
+
+    /// Attributes the traffic of a substream whose protocol is still unknown to
+    /// `UNKNOWN_PROTOCOL`.
+    fn give_up(&mut self, sinks: &ProtocolBandwidthSinks) {
+        if let SubstreamState::Sniffing { .. } = self {
+            self.resolve(UNKNOWN_PROTOCOL.to_owned(), sinks)
+        }
+    }
+
+    fn resolve(&mut self, protocol: String, sinks: &ProtocolBandwidthSinks) {
+        let counters = sinks.counters(protocol);
+        if let SubstreamState::Sniffing { read, written, .. } = self {
+            let mut c = counters.lock();
+            c.inject_read(*read);
+            c.inject_written(*written);
+        }
+        *self = SubstreamState::Known(counters);
+    }
+}
+
+/// Allows obtaining the bandwidth of the substreams created from `BandwidthMuxer`s, grouped by
+/// protocol.
+pub struct ProtocolBandwidthSinks {
+    /// Number of seconds over which the rolling averages are calculated.
+    period_seconds: u32,
+    /// Counters of each protocol.
+    protocols: Mutex<HashMap<String, Arc<Mutex<BandwidthCounters>>>>,
+}
+
+/// Bandwidth used by a protocol, as reported by `ProtocolBandwidthSinks`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ProtocolBandwidthStats {
+    /// Name of the protocol, or `UNKNOWN_PROTOCOL`.
+    pub protocol: String,
+    /// Total number of bytes that have been downloaded.
+    pub total_download: u64,
+    /// Total number of bytes that have been uploaded.
+    pub total_upload: u64,
+    /// Average number of bytes that have been downloaded per second over the period.
+    pub average_download_per_sec: u64,
+    /// Average number of bytes that have been uploaded per second over the period.
+    pub average_upload_per_sec: u64,
+}
+
+impl ProtocolBandwidthSinks {
+    /// Creates new sinks whose rolling averages are calculated over `period`.
+    pub fn new(period: Duration) -> Arc<Self> {
+        Arc::new(ProtocolBandwidthSinks {
+            period_seconds: period_to_seconds(period),
+            protocols: Mutex::new(HashMap::new()),
+        })
+    }
+
+    /// Returns the bandwidth statistics of every protocol that has been negotiated so far.
+    pub fn stats(&self) -> Vec<ProtocolBandwidthStats> {
+        self.protocols.lock()
+            .iter()
+            .map(|(protocol, counters)| counters.lock().protocol_stats(protocol.clone()))
+            .collect()
+    }
+
+    /// Returns the bandwidth statistics of the given protocol, or `None` if this protocol has
+    /// never been negotiated.
+    pub fn protocol_stats(&self, protocol: &str) -> Option<ProtocolBandwidthStats> {
+        self.protocols.lock()
+            .get(protocol)
+            .map(|counters| counters.lock().protocol_stats(protocol.to_owned()))
+    }
+
+    /// Returns the counters of a protocol, inserting them if necessary.
+    fn counters(&self, protocol: String) -> Arc<Mutex<BandwidthCounters>> {
+        let period_seconds = self.period_seconds;
+        self.protocols.lock()
+            .entry(protocol)
+            .or_insert_with(|| Arc::new(Mutex::new(BandwidthCounters::new(period_seconds))))
+            .clone()
+    }
+}
+
+/// Bandwidth counters of a single protocol or remote address.
+struct BandwidthCounters {
+    total_download: u64,
+    total_upload: u64,
+    download: BandwidthSink,
+    upload: BandwidthSink,
+}
+
+impl BandwidthCounters {
+    fn new(period_seconds: u32) -> Self {
+        BandwidthCounters {
+            total_download: 0,
+            total_upload: 0,
+            download: BandwidthSink::new(period_seconds),
+            upload: BandwidthSink::new(period_seconds),
+        }
+    }
+
+    fn inject_read(&mut self, bytes: usize) {
+        self.total_download = self.total_download.saturating_add(bytes as u64);
+        self.download.inject(bytes);
+    }
+
+    fn inject_written(&mut self, bytes: usize) {
+        self.total_upload = self.total_upload.saturating_add(bytes as u64);
+        self.upload.inject(bytes);
+    }
+
+    fn stats(&mut self) -> BandwidthStats {
+        BandwidthStats {
+            total_download: self.total_download,
+            total_upload: self.total_upload,
+            average_download_per_sec: self.download.get(),
+            average_upload_per_sec: self.upload.get(),
+        }
+    }
+
+    fn protocol_stats(&mut self, protocol: String) -> ProtocolBandwidthStats {
+        let stats = self.stats();
+        ProtocolBandwidthStats {
+            protocol,
+            total_download: stats.total_download,
+            total_upload: stats.total_upload,
+            average_download_per_sec: stats.average_download_per_sec,
+            average_upload_per_sec: stats.average_upload_per_sec,
+        }
+    }
+}
+
+/// Maximum number of bytes of multistream-select messages that we buffer in each direction before
+/// giving up on finding the protocol of a substream.
+const MAX_SNIFFED_BYTES: usize = 1024;
+
+/// Determines the protocol negotiated on a substream by parsing the multistream-select messages
+/// flowing through it.
+///
+/// A protocol is considered negotiated once it has been both sent and received, as the listener
+/// echoes back the protocol proposed by the dialer in order to accept it.
+#[derive(Default)]
+pub(crate) struct ProtocolSniffer {
+    read: MessageParser,
+    written: MessageParser,
+    read_protocols: SmallVec<[Vec<u8>; 2]>,
+    written_protocols: SmallVec<[Vec<u8>; 2]>,
+    negotiated: Option<Vec<u8>>,
+}
+
+impl ProtocolSniffer {
+    pub(crate) fn on_read(&mut self, data: &[u8]) {
+        if self.negotiated.is_none() {
+            self.read.feed(data, &mut self.read_protocols);
+            self.update();
+        }
+    }
+
+    pub(crate) fn on_written(&mut self, data: &[u8]) {
+        if self.negotiated.is_none() {
+            self.written.feed(data, &mut self.written_protocols);
+            self.update();
+        }
+    }
+
+    fn update(&mut self) {
+        let written = &self.written_protocols;
+        self.negotiated = self.read_protocols.iter()
+            .find(|p| written.contains(p))
+            .cloned();
+    }
+
+    /// Returns the negotiated protocol, if known.
+    pub(crate) fn protocol(&self) -> Option<&[u8]> {
+        self.negotiated.as_ref().map(|p| &p[..])
+    }
+
+    /// Returns true if the traffic in either direction doesn't look like multistream-select.
+    pub(crate) fn has_failed(&self) -> bool {
+        self.negotiated.is_none() && (self.read.failed || self.written.failed)
+    }
+}
+
+/// Splits a stream of bytes into multistream-select messages.
+#[derive(Default)]
+struct MessageParser {
+    buffer: Vec<u8>,
+    failed: bool,
+}
+
+impl MessageParser {
+    /// Feeds bytes to the parser, and pushes the protocol names it finds to `protocols`.
+    fn feed(&mut self, data: &[u8], protocols: &mut SmallVec<[Vec<u8>; 2]>) {
+        if self.failed {
+            return
+        }
+
+        self.buffer.extend_from_slice(data);
+        loop {
+            let (len, len_len) = match decode_uvarint(&self.buffer) {
+                Some(Ok(v)) => v,
+                Some(Err(())) => { self.failed = true; break }
+                None => break,
+            };
+            if len == 0 || len > MAX_SNIFFED_BYTES {
+                self.failed = true;
+                break
+            }
+            if self.buffer.len() < len_len + len {
+                break
+            }
+
+            let message = &self.buffer[len_len .. len_len + len];
+            if message.last() != Some(&b'\n') {
+                self.failed = true;
+                break
+            }
+            let message = &message[.. len - 1];
+            if message.starts_with(b"/") && message != b"/multistream/1.0.0" {
+                protocols.push(message.to_vec());
+            }
+            self.buffer.drain(.. len_len + len);
+        }
+
+        if self.buffer.len() > MAX_SNIFFED_BYTES {
+            self.failed = true;
+        }
+        if self.failed {
+            self.buffer = Vec::new();
+        }
+    }
+}
+
+/// Decodes an unsigned varint at the start of `buf`. Returns the value and the number of bytes
+/// it occupies, `None` if more bytes are needed, or an error if the value is too large.
+fn decode_uvarint(buf: &[u8]) -> Option<Result<(usize, usize), ()>> {
+    let mut value = 0usize;
+    for (n, byte) in buf.iter().enumerate() {
+        if n >= 3 {
+            return Some(Err(()))
+        }
+        value |= usize::from(byte & 0x7f) << (7 * n);
+        if byte & 0x80 == 0 {
+            return Some(Ok((value, n + 1)))
+        }
+    }
+    None
+}
+
+/// Converts a period into a number of seconds, rounded up and capped to one day.
+fn period_to_seconds(period: Duration) -> u32 {
+    let mut period_seconds = cmp::min(period.as_secs(), 86400) as u32;
+    if period.subsec_nanos() > 0 {
+        period_seconds += 1;
+    }
+    period_seconds
+}
+
 /// Returns the number of seconds that have elapsed between an arbitrary EPOCH and now.
 #[inline]
 fn current_second() -> u32 {
@@ -277,6 +833,67 @@ mod tests {
     use std::{thread, time::Duration};
     use super::*;
 
+    fn message(content: &[u8]) -> Vec<u8> {
+        let mut out = vec![content.len() as u8 + 1];
+        out.extend_from_slice(content);
+        out.push(b'\n');
+        out
+    }
+
+    #[test]
+    fn sniffer_finds_negotiated_protocol() {
+        let mut sniffer = ProtocolSniffer::default();
+        sniffer.on_written(&message(b"/multistream/1.0.0"));
+        sniffer.on_written(&message(b"/foo/1.0.0"));
+        sniffer.on_read(&message(b"/multistream/1.0.0"));
+        sniffer.on_read(&message(b"na"));
+        assert!(sniffer.protocol().is_none());
+        sniffer.on_written(&message(b"/bar/1.0.0"));
+        let mut response = message(b"/bar/1.0.0");
+        response.extend_from_slice(b"application data");
+        sniffer.on_read(&response[..4]);
+        assert!(sniffer.protocol().is_none());
+        sniffer.on_read(&response[4..]);
+        assert_eq!(sniffer.protocol(), Some(&b"/bar/1.0.0"[..]));
+        assert!(!sniffer.has_failed());
+    }
+
+    #[test]
+    fn sniffer_gives_up_on_garbage() {
+        let mut sniffer = ProtocolSniffer::default();
+        sniffer.on_read(&[0xff, 0xff, 0xff, 0xff]);
+        assert!(sniffer.has_failed());
+        assert!(sniffer.protocol().is_none());
+    }
+
+    #[test]
+    fn connections_are_broken_down_by_address() {
+        let (_, sinks) = BandwidthLogging::new((), Duration::from_secs(1));
+        let addr: Multiaddr = "/ip4/1.2.3.4/tcp/1000".parse().unwrap();
+        let mut future = BandwidthFuture {
+            inner: futures::future::ok::<_, ()>(io::Cursor::new(vec![0u8; 10])),
+            sinks: sinks.clone(),
+            remote_addr: Some(addr.clone()),
+        };
+        let mut conn = match future.poll() {
+            Ok(Async::Ready(conn)) => conn,
+            _ => panic!("the connection is immediately ready"),
+        };
+
+        let mut buf = [0; 4];
+        conn.read_exact(&mut buf).unwrap();
+        conn.write_all(b"hello").unwrap();
+        assert_eq!(sinks.total_download(), 4);
+        assert_eq!(sinks.total_upload(), 5);
+        let stats = sinks.address_stats(&addr).unwrap();
+        assert_eq!((stats.total_download, stats.total_upload), (4, 5));
+        assert_eq!(sinks.addresses_stats().len(), 1);
+
+        drop(conn);
+        assert!(sinks.address_stats(&addr).is_none());
+        assert_eq!(sinks.stats().total_upload, 5);
+    }
+
     #[test]
     fn sink_works() {
         let mut sink = BandwidthSink::new(5);
@@ -0,0 +1,452 @@
+// Copyright 2019 Parity Technologies (UK) Ltd.
+//
+// Permission is hereby granted, free of charge, to any person obtaining a
+// copy of this software and associated documentation files (the "Software"),
+// to deal in the Software without restriction, including without limitation
+// the rights to use, copy, modify, merge, publish, distribute, sublicense,
+// and/or sell copies of the Software, and to permit persons to whom the
+// Software is furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS
+// OR IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING
+// FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
+// DEALINGS IN THE SOFTWARE.
+
+//! Capture of the plaintext data exchanged on substreams, for debugging purposes.
+//!
+//! Since the traffic of libp2p connections is encrypted, it can't be inspected with the usual
+//! packet capture tools. Wrapping the muxer of the connections in a [`CaptureMuxer`] writes
+//! every chunk of data read from or written to a substream, after decryption, to a
+//! [`FrameCapture`], along with the protocol negotiated on the substream, the direction of the
+//! data and a timestamp. The resulting file can be read back with [`read_frames`].
+//!
+//! This is typically applied to the output of a transport with `Transport::map`:
+//!
+//! ```ignore
+//! let capture = FrameCapture::create("libp2p.capture")?;
+//! let transport = transport.map({
+//!     let capture = capture.clone();
+//!     move |(peer_id, muxer), _| {
+//!         let muxer = CaptureMuxer::new(muxer, peer_id.clone(), capture.clone());
+//!         (peer_id, muxer)
+//!     }
+//! });
+//! ```
+//!
+//! > **Note**: The capture contains all the data exchanged with the remotes in the clear, and
+//! >           writing it slows down every substream. It must never be enabled in production.
+//!
+//! # Format
+//!
+//! A capture starts with the 8 bytes `LP2PCAP\x01`, followed by one record per frame. All the
+//! integers are big-endian. Each record consists of:
+//!
+//! - the timestamp of the frame, in microseconds since the Unix epoch, as a `u64`;
+//! - the number of the connection within the capture, as a `u64`;
+//! - the number of the substream within the connection, as a `u64`;
+//! - the direction of the frame, as a `u8`: `0` for data read, `1` for data written;
+//! - the `PeerId` of the remote, as a `u16` length followed by its bytes;
+//! - the protocol negotiated on the substream, as a `u16` length followed by its bytes, or an
+//!   empty string if the protocol isn't known yet, as is the case for the frames of the
+//!   negotiation itself;
+//! - the data of the frame, as a `u32` length followed by its bytes.
+
+use crate::{PeerId, bandwidth::ProtocolSniffer, core::muxing::StreamMuxer};
+use futures::{prelude::*, try_ready};
+use parking_lot::Mutex;
+use std::{cmp, fs, io, path::Path, sync::Arc};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// Bytes at the start of every capture.
+const MAGIC: &[u8; 8] = b"LP2PCAP\x01";
+
+/// Destination of the frames captured by `CaptureMuxer`s.
+///
+/// A single capture is usually shared by all the connections of a node. If writing to it fails,
+/// the error is kept and the capture stops.
+pub struct FrameCapture {
+    writer: Mutex<CaptureWriter>,
+    next_connection: AtomicUsize,
+}
+
+struct CaptureWriter {
+    inner: Box<dyn io::Write + Send>,
+    error: Option<io::Error>,
+}
+
+impl FrameCapture {
+    /// Creates a capture that writes to the given writer.
+    pub fn new(mut writer: impl io::Write + Send + 'static) -> io::Result<Arc<Self>> {
+        writer.write_all(MAGIC)?;
+        Ok(Arc::new(FrameCapture {
+            writer: Mutex::new(CaptureWriter { inner: Box::new(writer), error: None }),
+            next_connection: AtomicUsize::new(0),
+        }))
+    }
+
+    /// Creates a capture that writes to a file, truncating it if it exists.
+    pub fn create(path: impl AsRef<Path>) -> io::Result<Arc<Self>> {
+        FrameCapture::new(io::BufWriter::new(fs::File::create(path)?))
+    }
+
+    /// Returns the error that stopped the capture, if any.
+    pub fn error(&self) -> Option<io::Error> {
+        self.writer.lock().error.as_ref().map(|e| io::Error::new(e.kind(), e.to_string()))
+    }
+
+    /// Flushes the frames written so far.
+    pub fn flush(&self) -> io::Result<()> {
+        self.writer.lock().inner.flush()
+    }
+
+    fn record(&self, frame: &CapturedFrame) {
+        let mut writer = self.writer.lock();
+        if writer.error.is_some() {
+            return
+        }
+        if let Err(err) = write_frame(&mut writer.inner, frame) {
+            writer.error = Some(err);
+        }
+    }
+}
+
+/// Direction of a captured frame.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Direction {
+    /// The data has been read from the substream.
+    Inbound,
+    /// The data has been written to the substream.
+    Outbound,
+}
+
+/// A chunk of plaintext data that went through a substream.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CapturedFrame {
+    /// When the data was read or written.
+    pub timestamp: SystemTime,
+    /// Number of the connection within the capture.
+    pub connection: u64,
+    /// Number of the substream within the connection.
+    pub substream: u64,
+    /// Whether the data was read or written.
+    pub direction: Direction,
+    /// Identity of the remote.
+    pub peer_id: PeerId,
+    /// Protocol negotiated on the substream, if it was known at the time.
+    pub protocol: Option<String>,
+    /// The data.
+    pub data: Vec<u8>,
+}
+
+fn write_frame(w: &mut impl io::Write, frame: &CapturedFrame) -> io::Result<()> {
+    let timestamp = frame.timestamp.duration_since(UNIX_EPOCH).unwrap_or_default();
+    let micros = timestamp.as_secs() * 1_000_000 + u64::from(timestamp.subsec_micros());
+    let peer_id = frame.peer_id.as_bytes();
+    let protocol = frame.protocol.as_ref().map_or(&b""[..], |p| p.as_bytes());
+    let protocol = &protocol[.. cmp::min(protocol.len(), usize::from(u16::max_value()))];
+
+    let mut header = Vec::with_capacity(31 + peer_id.len() + protocol.len());
+    header.extend_from_slice(&micros.to_be_bytes());
+    header.extend_from_slice(&frame.connection.to_be_bytes());
+    header.extend_from_slice(&frame.substream.to_be_bytes());
+    header.push(match frame.direction { Direction::Inbound => 0, Direction::Outbound => 1 });
+    header.extend_from_slice(&(peer_id.len() as u16).to_be_bytes());
+    header.extend_from_slice(peer_id);
+    header.extend_from_slice(&(protocol.len() as u16).to_be_bytes());
+    header.extend_from_slice(protocol);
+    header.extend_from_slice(&(frame.data.len() as u32).to_be_bytes());
+    w.write_all(&header)?;
+    w.write_all(&frame.data)
+}
+
+/// Reads back a capture written by a `FrameCapture`.
+///
+/// Fails if the capture doesn't start with the expected bytes.
+pub fn read_frames<R: io::Read>(mut reader: R) -> io::Result<Frames<R>> {
+    let mut magic = [0; 8];
+    reader.read_exact(&mut magic)?;
+    if &magic != MAGIC {
+        return Err(invalid_data("not a frame capture"))
+    }
+    Ok(Frames { reader })
+}
+
+/// Iterator over the frames of a capture, returned by `read_frames`.
+pub struct Frames<R> {
+    reader: R,
+}
+
+impl<R: io::Read> Frames<R> {
+    fn read_frame(&mut self, first: u8) -> io::Result<CapturedFrame> {
+        let mut timestamp = [first, 0, 0, 0, 0, 0, 0, 0];
+        self.reader.read_exact(&mut timestamp[1..])?;
+        let timestamp = UNIX_EPOCH + Duration::from_micros(u64::from_be_bytes(timestamp));
+        let connection = self.read_u64()?;
+        let substream = self.read_u64()?;
+        let direction = match self.read_bytes(1)?[0] {
+            0 => Direction::Inbound,
+            1 => Direction::Outbound,
+            _ => return Err(invalid_data("invalid direction")),
+        };
+        let len = self.read_u16()?;
+        let peer_id = PeerId::from_bytes(self.read_bytes(len)?)
+            .map_err(|_| invalid_data("invalid peer id"))?;
+        let len = self.read_u16()?;
+        let protocol = String::from_utf8(self.read_bytes(len)?)
+            .map_err(|_| invalid_data("invalid protocol name"))?;
+        let len = self.read_u32()?;
+        let data = self.read_bytes(len)?;
+        Ok(CapturedFrame {
+            timestamp,
+            connection,
+            substream,
+            direction,
+            peer_id,
+            protocol: if protocol.is_empty() { None } else { Some(protocol) },
+            data,
+        })
+    }
+
+    fn read_bytes(&mut self, len: usize) -> io::Result<Vec<u8>> {
+        let mut buf = vec![0; len];
+        self.reader.read_exact(&mut buf)?;
+        Ok(buf)
+    }
+
+    fn read_u16(&mut self) -> io::Result<usize> {
+        let mut buf = [0; 2];
+        self.reader.read_exact(&mut buf)?;
+        Ok(usize::from(u16::from_be_bytes(buf)))
+    }
+
+    fn read_u32(&mut self) -> io::Result<usize> {
+        let mut buf = [0; 4];
+        self.reader.read_exact(&mut buf)?;
+        Ok(u32::from_be_bytes(buf) as usize)
+    }
+
+    fn read_u64(&mut self) -> io::Result<u64> {
+        let mut buf = [0; 8];
+        self.reader.read_exact(&mut buf)?;
+        Ok(u64::from_be_bytes(buf))
+    }
+}
+
+impl<R: io::Read> Iterator for Frames<R> {
+    type Item = io::Result<CapturedFrame>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        // The end of the capture is only valid between two records.
+        let mut first = [0];
+        match self.reader.read(&mut first) {
+            Ok(0) => None,
+            Ok(_) => Some(self.read_frame(first[0])),
+            Err(err) => Some(Err(err)),
+        }
+    }
+}
+
+fn invalid_data(msg: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, msg)
+}
+
+/// Wraps around a `StreamMuxer` and writes the data that goes through its substreams to a
+/// `FrameCapture`.
+pub struct CaptureMuxer<TMuxer> {
+    inner: TMuxer,
+    peer_id: PeerId,
+    capture: Arc<FrameCapture>,
+    connection: u64,
+    next_substream: AtomicUsize,
+}
+
+impl<TMuxer> CaptureMuxer<TMuxer> {
+    /// Creates a new `CaptureMuxer` around the muxer of a connection to `peer_id`.
+    pub fn new(inner: TMuxer, peer_id: PeerId, capture: Arc<FrameCapture>) -> Self {
+        let connection = capture.next_connection.fetch_add(1, Ordering::Relaxed) as u64;
+        CaptureMuxer {
+            inner,
+            peer_id,
+            capture,
+            connection,
+            next_substream: AtomicUsize::new(0),
+        }
+    }
+
+    fn wrap<TSubstream>(&self, inner: TSubstream) -> CaptureSubstream<TSubstream> {
+        CaptureSubstream {
+            inner,
+            id: self.next_substream.fetch_add(1, Ordering::Relaxed) as u64,
+            sniffer: Some(ProtocolSniffer::default()),
+            protocol: None,
+        }
+    }
+
+    fn record<T>(&self, s: &mut CaptureSubstream<T>, direction: Direction, data: &[u8]) {
+        if data.is_empty() {
+            return
+        }
+        if let Some(sniffer) = s.sniffer.as_mut() {
+            match direction {
+                Direction::Inbound => sniffer.on_read(data),
+                Direction::Outbound => sniffer.on_written(data),
+            }
+            if let Some(protocol) = sniffer.protocol() {
+                s.protocol = Some(String::from_utf8_lossy(protocol).into_owned());
+                s.sniffer = None;
+            } else if sniffer.has_failed() {
+                s.sniffer = None;
+            }
+        }
+        self.capture.record(&CapturedFrame {
+            timestamp: SystemTime::now(),
+            connection: self.connection,
+            substream: s.id,
+            direction,
+            peer_id: self.peer_id.clone(),
+            protocol: s.protocol.clone(),
+            data: data.to_vec(),
+        })
+    }
+}
+
+impl<TMuxer> StreamMuxer for CaptureMuxer<TMuxer>
+where
+    TMuxer: StreamMuxer,
+{
+    type Substream = CaptureSubstream<TMuxer::Substream>;
+    type OutboundSubstream = TMuxer::OutboundSubstream;
+    type Error = TMuxer::Error;
+
+    fn poll_inbound(&self) -> Poll<Self::Substream, Self::Error> {
+        let substream = try_ready!(self.inner.poll_inbound());
+        Ok(Async::Ready(self.wrap(substream)))
+    }
+
+    #[inline]
+    fn open_outbound(&self) -> Self::OutboundSubstream {
+        self.inner.open_outbound()
+    }
+
+    fn poll_outbound(&self, s: &mut Self::OutboundSubstream) -> Poll<Self::Substream, Self::Error> {
+        let substream = try_ready!(self.inner.poll_outbound(s));
+        Ok(Async::Ready(self.wrap(substream)))
+    }
+
+    #[inline]
+    fn destroy_outbound(&self, s: Self::OutboundSubstream) {
+        self.inner.destroy_outbound(s)
+    }
+
+    unsafe fn prepare_uninitialized_buffer(&self, buf: &mut [u8]) -> bool {
+        self.inner.prepare_uninitialized_buffer(buf)
+    }
+
+    fn read_substream(&self, s: &mut Self::Substream, buf: &mut [u8]) -> Poll<usize, Self::Error> {
+        let num_bytes = try_ready!(self.inner.read_substream(&mut s.inner, buf));
+        self.record(s, Direction::Inbound, &buf[..num_bytes]);
+        Ok(Async::Ready(num_bytes))
+    }
+
+    fn write_substream(&self, s: &mut Self::Substream, buf: &[u8]) -> Poll<usize, Self::Error> {
+        let num_bytes = try_ready!(self.inner.write_substream(&mut s.inner, buf));
+        self.record(s, Direction::Outbound, &buf[..num_bytes]);
+        Ok(Async::Ready(num_bytes))
+    }
+
+    #[inline]
+    fn flush_substream(&self, s: &mut Self::Substream) -> Poll<(), Self::Error> {
+        self.inner.flush_substream(&mut s.inner)
+    }
+
+    #[inline]
+    fn shutdown_substream(&self, s: &mut Self::Substream) -> Poll<(), Self::Error> {
+        self.inner.shutdown_substream(&mut s.inner)
+    }
+
+    #[inline]
+    fn destroy_substream(&self, s: Self::Substream) {
+        self.inner.destroy_substream(s.inner)
+    }
+
+    #[inline]
+    fn is_remote_acknowledged(&self) -> bool {
+        self.inner.is_remote_acknowledged()
+    }
+
+    #[inline]
+    fn close(&self) -> Poll<(), Self::Error> {
+        self.inner.close()
+    }
+
+    #[inline]
+    fn flush_all(&self) -> Poll<(), Self::Error> {
+        self.inner.flush_all()
+    }
+}
+
+/// Substream of a `CaptureMuxer`.
+pub struct CaptureSubstream<TInner> {
+    inner: TInner,
+    /// Number of the substream within the connection.
+    id: u64,
+    /// Finds the negotiated protocol. `None` once the protocol is known or can't be determined.
+    sniffer: Option<ProtocolSniffer>,
+    /// The negotiated protocol, if known.
+    protocol: Option<String>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Clone, Default)]
+    struct Buffer(Arc<Mutex<Vec<u8>>>);
+
+    impl io::Write for Buffer {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.0.lock().extend_from_slice(buf);
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn frames_roundtrip() {
+        let buffer = Buffer::default();
+        let capture = FrameCapture::new(buffer.clone()).unwrap();
+        let frame = CapturedFrame {
+            timestamp: UNIX_EPOCH + Duration::from_micros(1_571_140_800_123_456),
+            connection: 3,
+            substream: 7,
+            direction: Direction::Outbound,
+            peer_id: PeerId::random(),
+            protocol: Some("/ipfs/ping/1.0.0".to_owned()),
+            data: b"hello".to_vec(),
+        };
+        let negotiation = CapturedFrame {
+            direction: Direction::Inbound,
+            protocol: None,
+            data: b"\x13/multistream/1.0.0\n".to_vec(),
+            .. frame.clone()
+        };
+        capture.record(&negotiation);
+        capture.record(&frame);
+        assert!(capture.error().is_none());
+
+        let bytes = buffer.0.lock().clone();
+        let frames = read_frames(&bytes[..]).unwrap().collect::<Result<Vec<_>, _>>().unwrap();
+        assert_eq!(frames, vec![negotiation, frame]);
+        assert!(read_frames(&bytes[..bytes.len() - 1]).unwrap().last().unwrap().is_err());
+    }
+}
@@ -0,0 +1,299 @@
+// Copyright 2019 Parity Technologies (UK) Ltd.
+//
+// Permission is hereby granted, free of charge, to any person obtaining a
+// copy of this software and associated documentation files (the "Software"),
+// to deal in the Software without restriction, including without limitation
+// the rights to use, copy, modify, merge, publish, distribute, sublicense,
+// and/or sell copies of the Software, and to permit persons to whom the
+// Software is furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS
+// OR IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING
+// FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
+// DEALINGS IN THE SOFTWARE.
+//! Aggregated health and connectivity status of the local node.
+//!
+//! The [`Health`] network behaviour combines, in a single [`HealthStatus`]:
+//!
+//! - the NAT status of the node, as determined by AutoNAT;
+//! - the number of confirmed external addresses;
+//! - the number of bootstrap peers we are connected to;
+//! - the size of the routing table of the DHT.
+//!
+//! The status is considered ready once the requirements of the [`HealthConfig`] are met, which
+//! is meant to serve as the readiness probe of orchestration systems. A [`HealthEvent`] is
+//! generated every time the status changes.
+//!
+//! The confirmed external addresses and the connections to the bootstrap peers are tracked by
+//! the behaviour itself. Since behaviours don't see each other, the NAT status and the size of
+//! the routing table must be passed on by the application, typically when processing the events
+//! of the `AutoNat` and `Kademlia` behaviours:
+//!
+//! ```ignore
+//! impl<TSubstream> NetworkBehaviourEventProcess<AutoNatEvent> for MyBehaviour<TSubstream> {
+//!     fn inject_event(&mut self, event: AutoNatEvent) {
+//!         let AutoNatEvent::StatusChanged { new, .. } = event;
+//!         self.health.set_nat_status(new);
+//!     }
+//! }
+//!
+//! impl<TSubstream> NetworkBehaviourEventProcess<KademliaEvent> for MyBehaviour<TSubstream> {
+//!     fn inject_event(&mut self, _: KademliaEvent) {
+//!         let size = self.kademlia.kbuckets_entries().count();
+//!         self.health.set_routing_table_size(size);
+//!     }
+//! }
+//! ```
+
+use crate::{
+    Multiaddr,
+    PeerId,
+    autonat::NatStatus,
+    core::ConnectedPoint,
+    swarm::{NetworkBehaviour, NetworkBehaviourAction, PollParameters, ProtocolsHandler},
+    swarm::protocols_handler::DummyProtocolsHandler
+};
+use futures::{prelude::*, task::{self, Task}};
+use std::{collections::HashSet, marker::PhantomData, mem};
+use tokio_io::{AsyncRead, AsyncWrite};
+
+/// Requirements for the local node to be considered ready.
+#[derive(Debug, Clone, Default)]
+pub struct HealthConfig {
+    bootstrap_peers: HashSet<PeerId>,
+    min_bootstrap_connections: usize,
+    min_external_addresses: usize,
+    min_routing_table_size: usize,
+    require_public: bool,
+}
+
+impl HealthConfig {
+    /// Creates a configuration without any requirement, with which the node is always ready.
+    pub fn new() -> Self {
+        HealthConfig::default()
+    }
+
+    /// Adds a bootstrap peer, whose connectivity is reported in the status.
+    pub fn with_bootstrap_peer(mut self, peer_id: PeerId) -> Self {
+        self.bootstrap_peers.insert(peer_id);
+        self
+    }
+
+    /// Sets the number of bootstrap peers we must be connected to.
+    pub fn with_min_bootstrap_connections(mut self, min: usize) -> Self {
+        self.min_bootstrap_connections = min;
+        self
+    }
+
+    /// Sets the number of confirmed external addresses we must have.
+    pub fn with_min_external_addresses(mut self, min: usize) -> Self {
+        self.min_external_addresses = min;
+        self
+    }
+
+    /// Sets the number of peers the routing table of the DHT must contain.
+    pub fn with_min_routing_table_size(mut self, min: usize) -> Self {
+        self.min_routing_table_size = min;
+        self
+    }
+
+    /// Sets whether AutoNAT must have determined that the node is publicly reachable.
+    pub fn with_require_public(mut self, require: bool) -> Self {
+        self.require_public = require;
+        self
+    }
+}
+
+/// Health and connectivity status of the local node.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HealthStatus {
+    /// The NAT status of the node, as reported with `Health::set_nat_status`.
+    pub nat_status: NatStatus,
+    /// The number of confirmed external addresses.
+    pub external_addresses: usize,
+    /// The number of bootstrap peers we are connected to.
+    pub bootstrap_connections: usize,
+    /// The number of configured bootstrap peers.
+    pub bootstrap_peers: usize,
+    /// The number of peers in the routing table of the DHT, as reported with
+    /// `Health::set_routing_table_size`.
+    pub routing_table_size: usize,
+    /// Whether the requirements of the `HealthConfig` are met.
+    pub ready: bool,
+}
+
+/// Event generated by the `Health` network behaviour.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum HealthEvent {
+    /// The status of the node changed.
+    StatusChanged {
+        old: HealthStatus,
+        new: HealthStatus,
+    },
+}
+
+/// Network behaviour aggregating the health and connectivity status of the local node.
+///
+/// See the module documentation for more information.
+pub struct Health<TSubstream> {
+    config: HealthConfig,
+    /// The status as of the last `HealthEvent`.
+    status: HealthStatus,
+    nat_status: NatStatus,
+    routing_table_size: usize,
+    /// Bootstrap peers we are connected to.
+    connected_bootstrap_peers: HashSet<PeerId>,
+    /// The task to wake up when the status is changed from outside of `poll`.
+    task: Option<Task>,
+    _marker: PhantomData<TSubstream>,
+}
+
+impl<TSubstream> Health<TSubstream> {
+    /// Creates a new `Health` behaviour with the given requirements.
+    pub fn new(config: HealthConfig) -> Self {
+        let mut health = Health {
+            status: HealthStatus {
+                nat_status: NatStatus::Unknown,
+                external_addresses: 0,
+                bootstrap_connections: 0,
+                bootstrap_peers: config.bootstrap_peers.len(),
+                routing_table_size: 0,
+                ready: false,
+            },
+            config,
+            nat_status: NatStatus::Unknown,
+            routing_table_size: 0,
+            connected_bootstrap_peers: HashSet::new(),
+            task: None,
+            _marker: PhantomData,
+        };
+        health.status = health.compute_status(0);
+        health
+    }
+
+    /// Returns the current status of the node.
+    pub fn status(&self) -> &HealthStatus {
+        &self.status
+    }
+
+    /// Returns whether the requirements of the `HealthConfig` are met.
+    pub fn is_ready(&self) -> bool {
+        self.status.ready
+    }
+
+    /// Reports the NAT status of the node, as determined by the `AutoNat` behaviour.
+    pub fn set_nat_status(&mut self, status: NatStatus) {
+        self.nat_status = status;
+        self.notify();
+    }
+
+    /// Reports the number of peers in the routing table of the DHT.
+    pub fn set_routing_table_size(&mut self, size: usize) {
+        self.routing_table_size = size;
+        self.notify();
+    }
+
+    fn notify(&mut self) {
+        if let Some(task) = self.task.take() {
+            task.notify();
+        }
+    }
+
+    fn compute_status(&self, external_addresses: usize) -> HealthStatus {
+        let bootstrap_connections = self.connected_bootstrap_peers.len();
+        let ready = bootstrap_connections >= self.config.min_bootstrap_connections
+            && external_addresses >= self.config.min_external_addresses
+            && self.routing_table_size >= self.config.min_routing_table_size
+            && (!self.config.require_public || self.nat_status.is_public());
+        HealthStatus {
+            nat_status: self.nat_status.clone(),
+            external_addresses,
+            bootstrap_connections,
+            bootstrap_peers: self.config.bootstrap_peers.len(),
+            routing_table_size: self.routing_table_size,
+            ready,
+        }
+    }
+}
+
+impl<TSubstream> NetworkBehaviour for Health<TSubstream>
+where
+    TSubstream: AsyncRead + AsyncWrite,
+{
+    type ProtocolsHandler = DummyProtocolsHandler<TSubstream>;
+    type OutEvent = HealthEvent;
+
+    fn new_handler(&mut self) -> Self::ProtocolsHandler {
+        DummyProtocolsHandler::default()
+    }
+
+    fn addresses_of_peer(&mut self, _: &PeerId) -> Vec<Multiaddr> {
+        Vec::new()
+    }
+
+    fn inject_connected(&mut self, peer_id: PeerId, _: ConnectedPoint) {
+        if self.config.bootstrap_peers.contains(&peer_id) {
+            self.connected_bootstrap_peers.insert(peer_id);
+        }
+    }
+
+    fn inject_disconnected(&mut self, peer_id: &PeerId, _: ConnectedPoint) {
+        self.connected_bootstrap_peers.remove(peer_id);
+    }
+
+    fn inject_node_event(&mut self, _: PeerId, event: <Self::ProtocolsHandler as ProtocolsHandler>::OutEvent) {
+        match event {}
+    }
+
+    fn poll(&mut self, params: &mut impl PollParameters)
+        -> Async<NetworkBehaviourAction<<Self::ProtocolsHandler as ProtocolsHandler>::InEvent, Self::OutEvent>>
+    {
+        self.task = Some(task::current());
+
+        let status = self.compute_status(params.external_addresses().len());
+        if status == self.status {
+            return Async::NotReady
+        }
+
+        let old = mem::replace(&mut self.status, status.clone());
+        Async::Ready(NetworkBehaviourAction::GenerateEvent(HealthEvent::StatusChanged { old, new: status }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::transport::dummy::DummyStream;
+
+    #[test]
+    fn ready_once_requirements_are_met() {
+        let bootstrap = PeerId::random();
+        let config = HealthConfig::new()
+            .with_bootstrap_peer(bootstrap.clone())
+            .with_min_bootstrap_connections(1)
+            .with_min_routing_table_size(2)
+            .with_require_public(true);
+        let mut health = Health::<DummyStream>::new(config);
+        assert!(!health.is_ready());
+
+        let endpoint = ConnectedPoint::Dialer { address: "/memory/1".parse().unwrap() };
+        health.inject_connected(PeerId::random(), endpoint.clone());
+        health.inject_connected(bootstrap.clone(), endpoint.clone());
+        health.set_routing_table_size(2);
+        let status = health.compute_status(0);
+        assert_eq!(status.bootstrap_connections, 1);
+        assert!(!status.ready);
+
+        health.set_nat_status(NatStatus::Public("/ip4/1.2.3.4/tcp/4001".parse().unwrap()));
+        assert!(health.compute_status(0).ready);
+
+        health.inject_disconnected(&bootstrap, endpoint);
+        assert!(!health.compute_status(0).ready);
+    }
+}
@@ -161,8 +161,18 @@ pub use multihash;
 pub use tokio_io;
 pub use tokio_codec;
 
+#[doc(inline)]
+pub use libp2p_autonat as autonat;
+#[doc(inline)]
+pub use libp2p_autorelay as autorelay;
+#[doc(inline)]
+pub use libp2p_bitswap as bitswap;
 #[doc(inline)]
 pub use libp2p_core as core;
+#[doc(inline)]
+pub use libp2p_dcutr as dcutr;
+#[doc(inline)]
+pub use libp2p_delegated_routing as delegated_routing;
 #[cfg(not(any(target_os = "emscripten", target_os = "unknown")))]
 #[doc(inline)]
 pub use libp2p_deflate as deflate;
@@ -172,11 +182,19 @@ pub use libp2p_dns as dns;
 #[doc(inline)]
 pub use libp2p_identify as identify;
 #[doc(inline)]
+pub use libp2p_introspection as introspection;
+#[doc(inline)]
 pub use libp2p_kad as kad;
 #[doc(inline)]
 pub use libp2p_floodsub as floodsub;
 #[doc(inline)]
+pub use libp2p_gossipsub as gossipsub;
+#[doc(inline)]
+pub use libp2p_graphsync as graphsync;
+#[doc(inline)]
 pub use libp2p_mplex as mplex;
+#[doc(inline)]
+pub use libp2p_named_pipe as named_pipe;
 #[cfg(not(any(target_os = "emscripten", target_os = "unknown")))]
 #[doc(inline)]
 pub use libp2p_mdns as mdns;
@@ -184,14 +202,27 @@ pub use libp2p_mdns as mdns;
 #[doc(inline)]
 pub use libp2p_noise as noise;
 #[doc(inline)]
+pub use libp2p_perf as perf;
+#[doc(inline)]
 pub use libp2p_ping as ping;
+#[cfg(not(any(target_os = "emscripten", target_os = "unknown")))]
+#[doc(inline)]
+pub use libp2p_port_mapping as port_mapping;
 #[doc(inline)]
 pub use libp2p_plaintext as plaintext;
 #[doc(inline)]
 pub use libp2p_ratelimit as ratelimit;
 #[doc(inline)]
+pub use libp2p_relay as relay;
+#[doc(inline)]
+pub use libp2p_rendezvous as rendezvous;
+#[doc(inline)]
+pub use libp2p_request_response as request_response;
+#[doc(inline)]
 pub use libp2p_secio as secio;
 #[doc(inline)]
+pub use libp2p_stream as stream;
+#[doc(inline)]
 pub use libp2p_swarm as swarm;
 #[cfg(not(any(target_os = "emscripten", target_os = "unknown")))]
 #[doc(inline)]
@@ -209,7 +240,10 @@ pub use libp2p_yamux as yamux;
 mod transport_ext;
 
 pub mod bandwidth;
+pub mod capture;
+pub mod health;
 pub mod simple;
+pub mod substreams;
 
 pub use self::core::{
     identity,
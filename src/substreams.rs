@@ -0,0 +1,354 @@
+// Copyright 2019 Parity Technologies (UK) Ltd.
+//
+// Permission is hereby granted, free of charge, to any person obtaining a
+// copy of this software and associated documentation files (the "Software"),
+// to deal in the Software without restriction, including without limitation
+// the rights to use, copy, modify, merge, publish, distribute, sublicense,
+// and/or sell copies of the Software, and to permit persons to whom the
+// Software is furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS
+// OR IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING
+// FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
+// DEALINGS IN THE SOFTWARE.
+//! Inspection of the substreams that are open on each connection.
+//!
+//! Wrapping the muxer of the connections in a [`TrackedMuxer`] registers every substream in a
+//! [`SubstreamTracker`], along with its direction, the moment it was opened and the protocol
+//! negotiated on it, until it is closed. This makes it possible to find out which protocol is
+//! responsible when a node holds more substreams than expected.
+//!
+//! This is typically applied to the output of a transport with `Transport::map`:
+//!
+//! ```ignore
+//! let tracker = SubstreamTracker::new();
+//! let transport = transport.map({
+//!     let tracker = tracker.clone();
+//!     move |(peer_id, muxer), endpoint| {
+//!         let muxer = TrackedMuxer::new(muxer, peer_id.clone(), endpoint, tracker.clone());
+//!         (peer_id, muxer)
+//!     }
+//! });
+//! ```
+
+use crate::{PeerId, bandwidth::ProtocolSniffer, core::{ConnectedPoint, muxing::StreamMuxer}};
+use futures::{prelude::*, try_ready};
+use parking_lot::Mutex;
+use std::{collections::HashMap, sync::Arc, time::Duration};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use wasm_timer::Instant;
+
+/// Registry of the substreams open on the connections wrapped in `TrackedMuxer`s.
+#[derive(Default)]
+pub struct SubstreamTracker {
+    connections: Mutex<HashMap<u64, ConnectionEntry>>,
+    next_connection: AtomicUsize,
+}
+
+struct ConnectionEntry {
+    peer_id: PeerId,
+    endpoint: ConnectedPoint,
+    substreams: HashMap<u64, SubstreamEntry>,
+}
+
+struct SubstreamEntry {
+    direction: Direction,
+    opened: Instant,
+    protocol: Option<String>,
+}
+
+/// Which side opened a substream.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum Direction {
+    /// The substream was opened by the remote.
+    Inbound,
+    /// The substream was opened by the local node.
+    Outbound,
+}
+
+/// The substreams open on a connection, as returned by `SubstreamTracker::connections`.
+#[derive(Debug, Clone)]
+pub struct ConnectionSubstreams {
+    /// Identity of the remote.
+    pub peer_id: PeerId,
+    /// Endpoint of the connection.
+    pub endpoint: ConnectedPoint,
+    /// The substreams currently open, from the oldest to the most recent.
+    pub substreams: Vec<OpenSubstream>,
+}
+
+/// A substream open on a connection.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct OpenSubstream {
+    /// The protocol negotiated on the substream, or `None` if the negotiation isn't over.
+    pub protocol: Option<String>,
+    /// Which side opened the substream.
+    pub direction: Direction,
+    /// How long ago the substream was opened.
+    pub age: Duration,
+}
+
+impl SubstreamTracker {
+    /// Creates a new, empty, tracker.
+    pub fn new() -> Arc<Self> {
+        Arc::new(SubstreamTracker::default())
+    }
+
+    /// Returns the substreams currently open on each connection.
+    pub fn connections(&self) -> Vec<ConnectionSubstreams> {
+        let now = Instant::now();
+        let connections = self.connections.lock();
+        let mut ids = connections.keys().cloned().collect::<Vec<_>>();
+        ids.sort();
+        ids.into_iter().map(|id| {
+            let connection = &connections[&id];
+            let mut substreams = connection.substreams.iter().collect::<Vec<_>>();
+            substreams.sort_by_key(|(id, _)| **id);
+            ConnectionSubstreams {
+                peer_id: connection.peer_id.clone(),
+                endpoint: connection.endpoint.clone(),
+                substreams: substreams.into_iter().map(|(_, s)| OpenSubstream {
+                    protocol: s.protocol.clone(),
+                    direction: s.direction,
+                    age: now.duration_since(s.opened),
+                }).collect(),
+            }
+        }).collect()
+    }
+
+    /// Returns the number of open substreams for each negotiated protocol, across all the
+    /// connections. The substreams whose negotiation isn't over are not counted.
+    pub fn count_by_protocol(&self) -> HashMap<String, usize> {
+        let mut counts = HashMap::new();
+        for connection in self.connections.lock().values() {
+            for protocol in connection.substreams.values().filter_map(|s| s.protocol.as_ref()) {
+                *counts.entry(protocol.clone()).or_insert(0) += 1;
+            }
+        }
+        counts
+    }
+
+    fn add_connection(&self, peer_id: PeerId, endpoint: ConnectedPoint) -> u64 {
+        let id = self.next_connection.fetch_add(1, Ordering::Relaxed) as u64;
+        let entry = ConnectionEntry { peer_id, endpoint, substreams: HashMap::new() };
+        self.connections.lock().insert(id, entry);
+        id
+    }
+
+    fn remove_connection(&self, connection: u64) {
+        self.connections.lock().remove(&connection);
+    }
+
+    fn add_substream(&self, connection: u64, substream: u64, direction: Direction) {
+        if let Some(c) = self.connections.lock().get_mut(&connection) {
+            let entry = SubstreamEntry { direction, opened: Instant::now(), protocol: None };
+            c.substreams.insert(substream, entry);
+        }
+    }
+
+    fn set_protocol(&self, connection: u64, substream: u64, protocol: String) {
+        if let Some(c) = self.connections.lock().get_mut(&connection) {
+            if let Some(s) = c.substreams.get_mut(&substream) {
+                s.protocol = Some(protocol);
+            }
+        }
+    }
+
+    fn remove_substream(&self, connection: u64, substream: u64) {
+        if let Some(c) = self.connections.lock().get_mut(&connection) {
+            c.substreams.remove(&substream);
+        }
+    }
+}
+
+/// Wraps around a `StreamMuxer` and registers its substreams in a `SubstreamTracker`.
+///
+/// The connection is removed from the tracker when the muxer is dropped.
+pub struct TrackedMuxer<TMuxer> {
+    inner: TMuxer,
+    tracker: Arc<SubstreamTracker>,
+    connection: u64,
+    next_substream: AtomicUsize,
+}
+
+impl<TMuxer> TrackedMuxer<TMuxer> {
+    /// Creates a new `TrackedMuxer` around the muxer of a connection to `peer_id`.
+    pub fn new(inner: TMuxer, peer_id: PeerId, endpoint: ConnectedPoint, tracker: Arc<SubstreamTracker>) -> Self {
+        let connection = tracker.add_connection(peer_id, endpoint);
+        TrackedMuxer {
+            inner,
+            tracker,
+            connection,
+            next_substream: AtomicUsize::new(0),
+        }
+    }
+
+    fn wrap<TSubstream>(&self, inner: TSubstream, direction: Direction) -> TrackedSubstream<TSubstream> {
+        let id = self.next_substream.fetch_add(1, Ordering::Relaxed) as u64;
+        self.tracker.add_substream(self.connection, id, direction);
+        TrackedSubstream {
+            inner,
+            tracker: self.tracker.clone(),
+            connection: self.connection,
+            id,
+            sniffer: Some(ProtocolSniffer::default()),
+        }
+    }
+}
+
+impl<TMuxer> Drop for TrackedMuxer<TMuxer> {
+    fn drop(&mut self) {
+        self.tracker.remove_connection(self.connection)
+    }
+}
+
+impl<TMuxer> StreamMuxer for TrackedMuxer<TMuxer>
+where
+    TMuxer: StreamMuxer,
+{
+    type Substream = TrackedSubstream<TMuxer::Substream>;
+    type OutboundSubstream = TMuxer::OutboundSubstream;
+    type Error = TMuxer::Error;
+
+    fn poll_inbound(&self) -> Poll<Self::Substream, Self::Error> {
+        let substream = try_ready!(self.inner.poll_inbound());
+        Ok(Async::Ready(self.wrap(substream, Direction::Inbound)))
+    }
+
+    #[inline]
+    fn open_outbound(&self) -> Self::OutboundSubstream {
+        self.inner.open_outbound()
+    }
+
+    fn poll_outbound(&self, s: &mut Self::OutboundSubstream) -> Poll<Self::Substream, Self::Error> {
+        let substream = try_ready!(self.inner.poll_outbound(s));
+        Ok(Async::Ready(self.wrap(substream, Direction::Outbound)))
+    }
+
+    #[inline]
+    fn destroy_outbound(&self, s: Self::OutboundSubstream) {
+        self.inner.destroy_outbound(s)
+    }
+
+    unsafe fn prepare_uninitialized_buffer(&self, buf: &mut [u8]) -> bool {
+        self.inner.prepare_uninitialized_buffer(buf)
+    }
+
+    fn read_substream(&self, s: &mut Self::Substream, buf: &mut [u8]) -> Poll<usize, Self::Error> {
+        let num_bytes = try_ready!(self.inner.read_substream(s.inner_mut(), buf));
+        s.sniff(|sniffer| sniffer.on_read(&buf[..num_bytes]));
+        Ok(Async::Ready(num_bytes))
+    }
+
+    fn write_substream(&self, s: &mut Self::Substream, buf: &[u8]) -> Poll<usize, Self::Error> {
+        let num_bytes = try_ready!(self.inner.write_substream(s.inner_mut(), buf));
+        s.sniff(|sniffer| sniffer.on_written(&buf[..num_bytes]));
+        Ok(Async::Ready(num_bytes))
+    }
+
+    #[inline]
+    fn flush_substream(&self, s: &mut Self::Substream) -> Poll<(), Self::Error> {
+        self.inner.flush_substream(s.inner_mut())
+    }
+
+    #[inline]
+    fn shutdown_substream(&self, s: &mut Self::Substream) -> Poll<(), Self::Error> {
+        self.inner.shutdown_substream(s.inner_mut())
+    }
+
+    fn destroy_substream(&self, mut s: Self::Substream) {
+        if let Some(inner) = s.inner.take() {
+            self.inner.destroy_substream(inner)
+        }
+    }
+
+    #[inline]
+    fn is_remote_acknowledged(&self) -> bool {
+        self.inner.is_remote_acknowledged()
+    }
+
+    #[inline]
+    fn close(&self) -> Poll<(), Self::Error> {
+        self.inner.close()
+    }
+
+    #[inline]
+    fn flush_all(&self) -> Poll<(), Self::Error> {
+        self.inner.flush_all()
+    }
+}
+
+/// Substream of a `TrackedMuxer`.
+///
+/// The substream is removed from the tracker when it is dropped.
+pub struct TrackedSubstream<TInner> {
+    /// The wrapped substream. Only `None` while being destroyed.
+    inner: Option<TInner>,
+    tracker: Arc<SubstreamTracker>,
+    connection: u64,
+    /// Number of the substream within the connection.
+    id: u64,
+    /// Finds the negotiated protocol. `None` once the protocol is known or can't be determined.
+    sniffer: Option<ProtocolSniffer>,
+}
+
+impl<TInner> TrackedSubstream<TInner> {
+    fn inner_mut(&mut self) -> &mut TInner {
+        self.inner.as_mut().expect("the substream is only taken when destroyed; QED")
+    }
+
+    fn sniff(&mut self, feed: impl FnOnce(&mut ProtocolSniffer)) {
+        if let Some(sniffer) = self.sniffer.as_mut() {
+            feed(sniffer);
+            if let Some(protocol) = sniffer.protocol() {
+                let protocol = String::from_utf8_lossy(protocol).into_owned();
+                self.tracker.set_protocol(self.connection, self.id, protocol);
+                self.sniffer = None;
+            } else if sniffer.has_failed() {
+                self.sniffer = None;
+            }
+        }
+    }
+}
+
+impl<TInner> Drop for TrackedSubstream<TInner> {
+    fn drop(&mut self) {
+        self.tracker.remove_substream(self.connection, self.id)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn substreams_are_listed_per_connection() {
+        let tracker = SubstreamTracker::new();
+        let peer_id = PeerId::random();
+        let endpoint = ConnectedPoint::Dialer { address: "/memory/1".parse().unwrap() };
+        let connection = tracker.add_connection(peer_id.clone(), endpoint);
+        tracker.add_substream(connection, 0, Direction::Outbound);
+        tracker.add_substream(connection, 1, Direction::Inbound);
+        tracker.set_protocol(connection, 1, "/ipfs/ping/1.0.0".to_owned());
+
+        let connections = tracker.connections();
+        assert_eq!(connections.len(), 1);
+        assert_eq!(connections[0].peer_id, peer_id);
+        let substreams = &connections[0].substreams;
+        assert_eq!(substreams.len(), 2);
+        assert_eq!((substreams[0].direction, substreams[0].protocol.as_ref()), (Direction::Outbound, None));
+        assert_eq!(substreams[1].protocol.as_ref().map(|p| p.as_str()), Some("/ipfs/ping/1.0.0"));
+        assert_eq!(tracker.count_by_protocol().get("/ipfs/ping/1.0.0"), Some(&1));
+
+        tracker.remove_substream(connection, 1);
+        assert_eq!(tracker.connections()[0].substreams.len(), 1);
+        tracker.remove_connection(connection);
+        assert!(tracker.connections().is_empty());
+    }
+}
@@ -0,0 +1,224 @@
+// Copyright 2019 Parity Technologies (UK) Ltd.
+//
+// Permission is hereby granted, free of charge, to any person obtaining a
+// copy of this software and associated documentation files (the "Software"),
+// to deal in the Software without restriction, including without limitation
+// the rights to use, copy, modify, merge, publish, distribute, sublicense,
+// and/or sell copies of the Software, and to permit persons to whom the
+// Software is furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS
+// OR IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING
+// FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
+// DEALINGS IN THE SOFTWARE.
+
+
+use libp2p_core::{BoxedRng, PeerId};
+use rand::Rng;
+use std::{collections::HashMap, time::Duration};
+use wasm_timer::Instant;
+
+/// Configuration of the backoff applied by the `Swarm` to peers that we failed to dial.
+///
+/// After each consecutive dialing failure, the delay before the peer can be dialed again is
+/// doubled, starting from `initial_delay` and up to `max_delay`. A random fraction of up to
+/// `jitter` of that delay is then subtracted from it, so that peers that failed at the same time
+/// aren't all redialed at the same time.
+#[derive(Debug, Clone)]
+pub struct DialBackoffConfig {
+    initial_delay: Duration,
+    max_delay: Duration,
+    jitter: f64,
+    max_attempts: Option<u32>,
+}
+
+impl DialBackoffConfig {
+    /// Sets the delay to wait after the first dialing failure.
+    pub fn with_initial_delay(mut self, delay: Duration) -> Self {
+        self.initial_delay = delay;
+        self
+    }
+
+    /// Sets the maximum delay between two dialing attempts.
+    pub fn with_max_delay(mut self, delay: Duration) -> Self {
+        self.max_delay = delay;
+        self
+    }
+
+    /// Sets the fraction of the delay, between `0.0` and `1.0`, that is randomly subtracted from
+    /// each delay.
+    pub fn with_jitter(mut self, jitter: f64) -> Self {
+        self.jitter = jitter.max(0.0).min(1.0);
+        self
+    }
+
+    /// Sets the number of consecutive dialing failures after which we stop dialing the peer
+    /// altogether, until we successfully connect to it or its backoff is reset.
+    pub fn with_max_attempts(mut self, attempts: Option<u32>) -> Self {
+        self.max_attempts = attempts;
+        self
+    }
+
+    /// Returns the delay to wait after the given number of consecutive failures, without jitter.
+    fn delay(&self, failures: u32) -> Duration {
+        let factor = 1u32.checked_shl(failures.saturating_sub(1)).unwrap_or(u32::max_value());
+        self.initial_delay.checked_mul(factor)
+            .map_or(self.max_delay, |d| if d < self.max_delay { d } else { self.max_delay })
+    }
+}
+
+impl Default for DialBackoffConfig {
+    fn default() -> Self {
+        DialBackoffConfig {
+            initial_delay: Duration::from_secs(1),
+            max_delay: Duration::from_secs(5 * 60),
+            jitter: 0.1,
+            max_attempts: None,
+        }
+    }
+}
+
+/// Backoff state of a single peer.
+#[derive(Debug)]
+struct PeerBackoff {
+    /// Number of consecutive dialing failures.
+    failures: u32,
+    /// Moment before which we don't dial the peer.
+    until: Instant,
+}
+
+/// Keeps track of the peers we have recently failed to dial.
+#[derive(Debug)]
+pub(crate) struct DialBackoff {
+    config: DialBackoffConfig,
+    peers: HashMap<PeerId, PeerBackoff>,
+    /// Source of the jitter.
+    rng: BoxedRng,
+}
+
+impl DialBackoff {
+    /// Creates a new `DialBackoff` with the given configuration.
+    pub(crate) fn new(config: DialBackoffConfig, rng: BoxedRng) -> Self {
+        DialBackoff {
+            config,
+            peers: HashMap::new(),
+            rng,
+        }
+    }
+
+    /// Returns true if we must not dial the given peer at the moment.
+    pub(crate) fn is_backed_off(&self, peer_id: &PeerId) -> bool {
+        match self.peers.get(peer_id) {
+            Some(backoff) => {
+                let exhausted = self.config.max_attempts
+                    .map_or(false, |max| backoff.failures >= max);
+                exhausted || backoff.until > Instant::now()
+            },
+            None => false,
+        }
+    }
+
+    /// Records that we failed to dial the given peer.
+    pub(crate) fn record_failure(&mut self, peer_id: PeerId) {
+        // Peers whose backoff has expired for longer than `max_delay` are forgotten, so that
+        // their next failure starts over from `initial_delay`.
+        let now = Instant::now();
+        let config = &self.config;
+        self.peers.retain(|_, b| {
+            b.until + config.max_delay > now ||
+                config.max_attempts.map_or(false, |max| b.failures >= max)
+        });
+
+        let failures = self.peers.get(&peer_id).map_or(0, |b| b.failures).saturating_add(1);
+        let delay = apply_jitter(self.config.delay(failures), self.config.jitter, &mut self.rng);
+        self.peers.insert(peer_id, PeerBackoff { failures, until: now + delay });
+    }
+
+    /// Forgets about the backoff of the given peer, for example because we are now connected to
+    /// it.
+    pub(crate) fn reset(&mut self, peer_id: &PeerId) {
+        self.peers.remove(peer_id);
+    }
+
+    /// Forgets about the backoff of all the peers.
+    pub(crate) fn clear(&mut self) {
+        self.peers.clear();
+    }
+}
+
+/// Subtracts a random fraction of up to `jitter` from `delay`.
+fn apply_jitter(delay: Duration, jitter: f64, rng: &mut impl Rng) -> Duration {
+    if jitter <= 0.0 {
+        return delay
+    }
+
+    let nanos = delay.as_secs().saturating_mul(1_000_000_000)
+        .saturating_add(u64::from(delay.subsec_nanos()));
+    let factor = 1.0 - jitter * rng.gen::<f64>();
+    Duration::from_nanos((nanos as f64 * factor) as u64)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn delay_is_exponential_and_capped() {
+        let config = DialBackoffConfig::default()
+            .with_initial_delay(Duration::from_secs(1))
+            .with_max_delay(Duration::from_secs(10));
+        assert_eq!(config.delay(1), Duration::from_secs(1));
+        assert_eq!(config.delay(2), Duration::from_secs(2));
+        assert_eq!(config.delay(4), Duration::from_secs(8));
+        assert_eq!(config.delay(5), Duration::from_secs(10));
+        assert_eq!(config.delay(200), Duration::from_secs(10));
+    }
+
+    #[test]
+    fn jitter_only_shortens_delay() {
+        for _ in 0..100 {
+            let delay = apply_jitter(Duration::from_secs(10), 0.5, &mut rand::thread_rng());
+            assert!(delay <= Duration::from_secs(10));
+            assert!(delay >= Duration::from_secs(5));
+        }
+    }
+
+    #[test]
+    fn seeded_jitter_is_reproducible() {
+        let delay = |seed| apply_jitter(Duration::from_secs(10), 0.5, &mut BoxedRng::seeded(seed));
+        assert_eq!(delay(1), delay(1));
+    }
+
+    #[test]
+    fn failures_back_off_until_reset() {
+        let config = DialBackoffConfig::default()
+            .with_initial_delay(Duration::from_secs(60))
+            .with_max_attempts(Some(2));
+        let mut backoff = DialBackoff::new(config, BoxedRng::default());
+        let peer_id = PeerId::random();
+        assert!(!backoff.is_backed_off(&peer_id));
+        backoff.record_failure(peer_id.clone());
+        assert!(backoff.is_backed_off(&peer_id));
+        backoff.reset(&peer_id);
+        assert!(!backoff.is_backed_off(&peer_id));
+    }
+
+    #[test]
+    fn max_attempts_exhausted() {
+        let config = DialBackoffConfig::default()
+            .with_initial_delay(Duration::from_secs(0))
+            .with_max_attempts(Some(2));
+        let mut backoff = DialBackoff::new(config, BoxedRng::default());
+        let peer_id = PeerId::random();
+        backoff.record_failure(peer_id.clone());
+        assert!(!backoff.is_backed_off(&peer_id));
+        backoff.record_failure(peer_id.clone());
+        assert!(backoff.is_backed_off(&peer_id));
+    }
+}
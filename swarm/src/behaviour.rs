@@ -19,9 +19,12 @@
 // DEALINGS IN THE SOFTWARE.
 
 use crate::protocols_handler::{IntoProtocolsHandler, ProtocolsHandler};
-use libp2p_core::{ConnectedPoint, Multiaddr, PeerId};
+use crate::peer_stats::PeerStats;
+use crate::registry::AddressSource;
+use crate::snapshot::BehaviourSummary;
+use libp2p_core::{ConnectedPoint, Multiaddr, PeerId, nodes::ConnectionId};
 use futures::prelude::*;
-use std::error;
+use std::{collections::HashMap, error, time::Duration};
 
 /// A behaviour for the network. Allows customizing the swarm.
 ///
@@ -60,6 +63,9 @@ pub trait NetworkBehaviour {
     /// given endpoint.
     ///
     /// This node now has a handler (as spawned by `new_handler`) running in the background.
+    ///
+    /// If multiple connections per peer are allowed, this is only called for the first
+    /// connection to the node.
     fn inject_connected(&mut self, peer_id: PeerId, endpoint: ConnectedPoint);
 
     /// Indicates the behaviour that we disconnected from the node with the given peer id. The
@@ -67,8 +73,27 @@ pub trait NetworkBehaviour {
     ///
     /// There is no handler running anymore for this node. Any event that has been sent to it may
     /// or may not have been processed by the handler.
+    ///
+    /// If multiple connections per peer are allowed, this is only called once the last
+    /// connection to the node has been closed.
     fn inject_disconnected(&mut self, peer_id: &PeerId, endpoint: ConnectedPoint);
 
+    /// Indicates the behaviour that a new connection to the node with the given peer id has been
+    /// established.
+    ///
+    /// This is called for every connection, including the first one, right after
+    /// `inject_connected`.
+    fn inject_connection_established(&mut self, _peer_id: &PeerId, _connection: &ConnectionId, _endpoint: &ConnectedPoint) {
+    }
+
+    /// Indicates the behaviour that a connection to the node with the given peer id has been
+    /// closed.
+    ///
+    /// This is called for every connection, including the last one, right before
+    /// `inject_disconnected`.
+    fn inject_connection_closed(&mut self, _peer_id: &PeerId, _connection: &ConnectionId, _endpoint: &ConnectedPoint) {
+    }
+
     /// Indicates the behaviour that we replace the connection from the node with another.
     ///
     /// The handler that used to be dedicated to this node has been destroyed and replaced with a
@@ -93,6 +118,20 @@ pub trait NetworkBehaviour {
         event: <<Self::ProtocolsHandler as IntoProtocolsHandler>::Handler as ProtocolsHandler>::OutEvent
     );
 
+    /// Informs the behaviour about an event generated by the handler dedicated to a specific
+    /// connection with the peer identified by `peer_id`.
+    ///
+    /// This is the method called by the `Swarm`. The default implementation ignores the
+    /// connection and calls `inject_node_event`.
+    fn inject_connection_event(
+        &mut self,
+        peer_id: PeerId,
+        _connection: ConnectionId,
+        event: <<Self::ProtocolsHandler as IntoProtocolsHandler>::Handler as ProtocolsHandler>::OutEvent
+    ) {
+        self.inject_node_event(peer_id, event)
+    }
+
     /// Indicates to the behaviour that we tried to reach an address, but failed.
     ///
     /// If we were trying to reach a specific node, its ID is passed as parameter. If this is the
@@ -108,6 +147,13 @@ pub trait NetworkBehaviour {
     fn inject_dial_failure(&mut self, _peer_id: &PeerId) {
     }
 
+    /// Indicates to the behaviour that a banned peer has connected to us, or that we have
+    /// connected to a banned peer, and that the connection has been closed as a result.
+    ///
+    /// `inject_connected` is never called for this connection.
+    fn inject_banned_peer_connection(&mut self, _peer_id: &PeerId, _endpoint: &ConnectedPoint) {
+    }
+
     /// Indicates to the behaviour that we have started listening on a new multiaddr.
     fn inject_new_listen_addr(&mut self, _addr: &Multiaddr) {
     }
@@ -117,10 +163,49 @@ pub trait NetworkBehaviour {
     fn inject_expired_listen_addr(&mut self, _addr: &Multiaddr) {
     }
 
+    /// Indicates to the behaviour that a listener has closed because of an error.
+    ///
+    /// `inject_expired_listen_addr` is called beforehand for each of the addresses the listener
+    /// was listening on.
+    fn inject_listener_error(&mut self, _err: &dyn error::Error) {
+    }
+
     /// Indicates to the behaviour that we have discovered a new external address for us.
     fn inject_new_external_addr(&mut self, _addr: &Multiaddr) {
     }
 
+    /// Indicates to the behaviour that the network of the host has changed, as reported with
+    /// `Swarm::notify_network_change`.
+    ///
+    /// Unless the change is `NetworkChange::InterfaceUp`, the connections are being closed and
+    /// the addresses we are reachable at have likely changed.
+    fn inject_network_change(&mut self, _change: &NetworkChange) {
+    }
+
+    /// Indicates to the behaviour that the swarm is shutting down, as requested with
+    /// `Swarm::shutdown`.
+    ///
+    /// The connections are being gracefully closed and no new connection will be established.
+    /// The behaviour can still send events to the handlers, for example to say goodbye to the
+    /// remotes, as long as their connection is open.
+    fn inject_shutdown(&mut self) {
+    }
+
+    /// Indicates to the behaviour the protocols that a remote supports, as reported with
+    /// [`NetworkBehaviourAction::ReportRemoteProtocols`], for example by the identify protocol.
+    ///
+    /// Behaviours can use this information to avoid opening substreams for protocols that the
+    /// remote doesn't support. The list replaces the one previously reported for this peer.
+    fn inject_remote_protocols(&mut self, _peer_id: &PeerId, _protocols: &[String]) {
+    }
+
+    /// Adds entries describing the state of the behaviour to a summary, which is part of the
+    /// snapshots returned by `Swarm::snapshot` and `Swarm::subscribe_snapshots`.
+    ///
+    /// This is called every time a snapshot is taken and should therefore be cheap.
+    fn summarize(&self, _summary: &mut BehaviourSummary) {
+    }
+
     /// Polls for things that swarm should do.
     ///
     /// This API mimics the API of the `Stream` trait. The method may register the current task in
@@ -129,6 +214,36 @@ pub trait NetworkBehaviour {
         -> Async<NetworkBehaviourAction<<<Self::ProtocolsHandler as IntoProtocolsHandler>::Handler as ProtocolsHandler>::InEvent, Self::OutEvent>>;
 }
 
+/// A change of the network of the host, as reported by the platform.
+///
+/// Mobile platforms report these changes, for example through `ConnectivityManager` on Android
+/// or `NWPathMonitor` on iOS, and they should be forwarded to `Swarm::notify_network_change`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NetworkChange {
+    /// A network interface became available, while the existing ones are still usable.
+    InterfaceUp,
+    /// A network interface went away. The connections established through it are dead.
+    InterfaceDown,
+    /// The host switched to another kind of connectivity, for example from WiFi to cellular.
+    /// The connections established through the previous network are dead.
+    ConnectivityChanged,
+}
+
+/// Returns the connection that an event passed to [`NetworkBehaviour::inject_node_event`] is
+/// attributed to, for behaviours that handle the events of their handlers per connection and
+/// record the established connections of each peer in `connections`.
+///
+/// The `Swarm` always calls [`NetworkBehaviour::inject_connection_event`]. `inject_node_event`
+/// is only called by wrappers that don't know which connection produced the event, in which case
+/// the event is attributed to the most recently established connection of the peer. Returns
+/// `None` if the peer has no connection, in which case the event should be dropped.
+pub fn node_event_connection<C>(connections: &HashMap<PeerId, C>, peer_id: &PeerId) -> Option<ConnectionId>
+where
+    C: AsRef<[ConnectionId]>
+{
+    connections.get(peer_id).and_then(|c| c.as_ref().last()).cloned()
+}
+
 /// Parameters passed to `poll()`, that the `NetworkBehaviour` has access to.
 pub trait PollParameters {
     /// Iterator returned by [`supported_protocols`].
@@ -154,10 +269,34 @@ pub trait PollParameters {
 
     /// Returns the peer id of the local node.
     fn local_peer_id(&self) -> &PeerId;
+
+    /// Returns true if we recently failed to dial the given peer and the `Swarm` refuses to dial
+    /// it again at the moment.
+    ///
+    /// Dialing a peer that is backed off immediately results in `inject_dial_failure` being
+    /// called.
+    ///
+    /// The default implementation returns `false`.
+    fn is_dial_backed_off(&self, _peer_id: &PeerId) -> bool {
+        false
+    }
+
+    /// Returns the latency and throughput statistics of a peer we are connected to, if any was
+    /// reported with [`NetworkBehaviourAction::ReportRoundTripTime`] or
+    /// [`NetworkBehaviourAction::ReportTransfer`].
+    ///
+    /// The default implementation returns `None`.
+    fn peer_stats(&self, _peer_id: &PeerId) -> Option<PeerStats> {
+        None
+    }
 }
 
 /// Used when deriving `NetworkBehaviour`. When deriving `NetworkBehaviour`, must be implemented
 /// for all the possible event types generated by the various fields.
+///
+/// This isn't required if the struct is marked with `#[behaviour(event_process = false)]`. In
+/// that case, the events of the fields are converted into the `out_event` of the struct using
+/// `From` and returned by the swarm directly.
 // TODO: document how the custom behaviour works and link this here
 pub trait NetworkBehaviourEventProcess<TEvent> {
     /// Called when one of the fields of the type you're deriving `NetworkBehaviour` on generates
@@ -208,14 +347,89 @@ pub enum NetworkBehaviourAction<TInEvent, TOutEvent> {
         event: TInEvent,
     },
 
+    /// Instructs the `Swarm` to send a message to the handler dedicated to a specific connection
+    /// with the peer.
+    ///
+    /// If the connection no longer exists, the message is ignored, even if other connections to
+    /// the peer are open.
+    SendEventToConnection {
+        /// The peer to which to send the message.
+        peer_id: PeerId,
+        /// The connection the handler of which must receive the message.
+        connection: ConnectionId,
+        /// The message to send.
+        event: TInEvent,
+    },
+
     /// Informs the `Swarm` about a multi-address observed by a remote for
     /// the local node.
     ///
-    /// It is advisable to issue `ReportObservedAddr` actions at a fixed frequency
-    /// per node. This way address information will be more accurate over time
-    /// and individual outliers carry less weight.
+    /// The address is only considered confirmed, and returned by
+    /// [`PollParameters::external_addresses`], once enough distinct peers have reported it.
     ReportObservedAddr {
         /// The observed address of the local node.
         address: Multiaddr,
+        /// The peer that has observed the address.
+        observer: PeerId,
+    },
+
+    /// Informs the `Swarm` about the protocols that a remote supports, as advertised by the
+    /// remote.
+    ///
+    /// The `Swarm` records them in its `PeerStore`, where they can be queried with
+    /// `Swarm::supported_protocols`, and passes them to
+    /// [`NetworkBehaviour::inject_remote_protocols`].
+    ReportRemoteProtocols {
+        /// The peer whose protocols are reported.
+        peer_id: PeerId,
+        /// The protocols supported by the peer, e.g. `/ipfs/ping/1.0.0`.
+        protocols: Vec<String>,
+    },
+
+    /// Informs the `Swarm` about a round-trip time measured with a peer, e.g. by a ping or
+    /// between sending a request and receiving its response.
+    ///
+    /// The `Swarm` averages the reported values in the statistics returned by
+    /// [`PollParameters::peer_stats`] and `Swarm::peer_stats`.
+    ReportRoundTripTime {
+        /// The peer the round-trip time was measured with.
+        peer_id: PeerId,
+        /// The measured round-trip time.
+        rtt: Duration,
+    },
+
+    /// Informs the `Swarm` about data that has been exchanged with a peer, in order to estimate
+    /// the throughput of the peer.
+    ///
+    /// The `Swarm` averages the reported values in the statistics returned by
+    /// [`PollParameters::peer_stats`] and `Swarm::peer_stats`.
+    ReportTransfer {
+        /// The peer the data was exchanged with.
+        peer_id: PeerId,
+        /// Number of bytes received from the peer.
+        bytes_received: u64,
+        /// Number of bytes sent to the peer.
+        bytes_sent: u64,
+        /// How long the transfer took.
+        duration: Duration,
+    },
+
+    /// Informs the `Swarm` about an external address of the local node that comes from the
+    /// given source, e.g. a port mapped on the gateway.
+    ///
+    /// Unlike with `ReportObservedAddr`, whether the address is confirmed only depends on the
+    /// source. See [`AddressSource`].
+    ReportExternalAddr {
+        /// The external address of the local node.
+        address: Multiaddr,
+        /// Where the address comes from.
+        source: AddressSource,
+    },
+
+    /// Instructs the `Swarm` to forget about an external address of the local node, e.g. because
+    /// the port mapping it was obtained from has expired.
+    RemoveExternalAddr {
+        /// The address to forget about.
+        address: Multiaddr,
     },
 }
@@ -0,0 +1,1139 @@
+// Copyright 2019 Parity Technologies (UK) Ltd.
+//
+// Permission is hereby granted, free of charge, to any person obtaining a
+// copy of this software and associated documentation files (the "Software"),
+// to deal in the Software without restriction, including without limitation
+// the rights to use, copy, modify, merge, publish, distribute, sublicense,
+// and/or sell copies of the Software, and to permit persons to whom the
+// Software is furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS
+// OR IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING
+// FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
+// DEALINGS IN THE SOFTWARE.
+
+//! Composition of network behaviours at runtime.
+//!
+//! [`DynBehaviours`] is a `NetworkBehaviour` made of a set of behaviours that can be added and
+//! removed while the `Swarm` is running, for example in order to load protocol modules
+//! dynamically. The behaviours are stored as [`DynBehaviour`] trait objects, an object-safe
+//! version of `NetworkBehaviour` that is implemented for every network behaviour whose types
+//! are `Send` and `'static`.
+//!
+//! Since their types are erased, the events produced by the behaviours are boxed into a
+//! [`DynEvent`] that must be downcast to their original type, and the behaviours themselves can
+//! be accessed with [`DynBehaviours::get_mut`].
+//!
+//! > **Note**: The handlers of a behaviour are only created for the connections opened after the
+//! >           behaviour has been added. The handlers of a removed behaviour are kept until their
+//! >           connection closes, and the events they produce are discarded.
+
+use crate::{BehaviourSummary, NetworkBehaviour, NetworkBehaviourAction, NetworkChange, PeerStats, PollParameters};
+use crate::protocols_handler::{
+    IntoProtocolsHandler,
+    KeepAlive,
+    ProtocolsHandler,
+    ProtocolsHandlerEvent,
+    ProtocolsHandlerUpgrErr,
+    SubstreamProtocol,
+};
+use futures::prelude::*;
+use libp2p_core::{
+    ConnectedPoint,
+    Multiaddr,
+    PeerId,
+    nodes::ConnectionId,
+    upgrade::{InboundUpgrade, Negotiated, OutboundUpgrade, ProtocolName, UpgradeError, UpgradeInfo}
+};
+use std::{any::Any, cmp, error, fmt, time::Duration};
+use tokio_io::{AsyncRead, AsyncWrite};
+
+/// An event whose type has been erased.
+pub type DynEvent = Box<dyn Any + Send>;
+
+/// Future of an upgrade whose output and error types have been erased.
+type DynUpgradeFuture = Box<dyn Future<Item = DynEvent, Error = DynError> + Send>;
+
+/// Identifier of a behaviour within a `DynBehaviours`.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct DynBehaviourId(u64);
+
+/// An error whose type has been erased.
+#[derive(Debug)]
+pub struct DynError(Box<dyn error::Error + Send>);
+
+impl DynError {
+    fn new<TErr>(err: TErr) -> Self
+    where
+        TErr: error::Error + Send + 'static,
+    {
+        DynError(Box::new(err))
+    }
+
+    /// Returns the original error, if it is of type `TErr`.
+    pub fn downcast<TErr>(self) -> Result<TErr, DynError>
+    where
+        TErr: error::Error + 'static,
+    {
+        self.0.downcast::<TErr>().map(|err| *err).map_err(DynError)
+    }
+}
+
+impl fmt::Display for DynError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Display::fmt(&self.0, f)
+    }
+}
+
+impl error::Error for DynError {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        self.0.source()
+    }
+}
+
+/// Turns an event of a known type back from a `DynEvent`.
+fn downcast<T: 'static>(event: DynEvent) -> T {
+    *event.downcast::<T>()
+        .expect("DynBehaviours only passes to a behaviour or handler events of the types it \
+                 produces or expects; QED")
+}
+
+/// Object-safe subset of [`NetworkBehaviour`], implemented for all the network behaviours whose
+/// types are `Send` and `'static`.
+///
+/// See the module documentation for more information.
+pub trait DynBehaviour<TSubstream>: Send {
+    /// Equivalent to `NetworkBehaviour::new_handler`.
+    fn new_handler(&mut self) -> DynIntoProtocolsHandler<TSubstream>;
+
+    /// Equivalent to `NetworkBehaviour::addresses_of_peer`.
+    fn addresses_of_peer(&mut self, peer_id: &PeerId) -> Vec<Multiaddr>;
+
+    /// Equivalent to `NetworkBehaviour::inject_connected`.
+    fn inject_connected(&mut self, peer_id: PeerId, endpoint: ConnectedPoint);
+
+    /// Equivalent to `NetworkBehaviour::inject_disconnected`.
+    fn inject_disconnected(&mut self, peer_id: &PeerId, endpoint: ConnectedPoint);
+
+    /// Equivalent to `NetworkBehaviour::inject_connection_established`.
+    fn inject_connection_established(&mut self, peer_id: &PeerId, connection: &ConnectionId, endpoint: &ConnectedPoint);
+
+    /// Equivalent to `NetworkBehaviour::inject_connection_closed`.
+    fn inject_connection_closed(&mut self, peer_id: &PeerId, connection: &ConnectionId, endpoint: &ConnectedPoint);
+
+    /// Equivalent to `NetworkBehaviour::inject_replaced`.
+    fn inject_replaced(&mut self, peer_id: PeerId, closed_endpoint: ConnectedPoint, new_endpoint: ConnectedPoint);
+
+    /// Equivalent to `NetworkBehaviour::inject_node_event`.
+    fn inject_node_event(&mut self, peer_id: PeerId, event: DynEvent);
+
+    /// Equivalent to `NetworkBehaviour::inject_connection_event`.
+    fn inject_connection_event(&mut self, peer_id: PeerId, connection: ConnectionId, event: DynEvent);
+
+    /// Equivalent to `NetworkBehaviour::inject_addr_reach_failure`.
+    fn inject_addr_reach_failure(&mut self, peer_id: Option<&PeerId>, addr: &Multiaddr, error: &dyn error::Error);
+
+    /// Equivalent to `NetworkBehaviour::inject_dial_failure`.
+    fn inject_dial_failure(&mut self, peer_id: &PeerId);
+
+    /// Equivalent to `NetworkBehaviour::inject_banned_peer_connection`.
+    fn inject_banned_peer_connection(&mut self, peer_id: &PeerId, endpoint: &ConnectedPoint);
+
+    /// Equivalent to `NetworkBehaviour::inject_new_listen_addr`.
+    fn inject_new_listen_addr(&mut self, addr: &Multiaddr);
+
+    /// Equivalent to `NetworkBehaviour::inject_expired_listen_addr`.
+    fn inject_expired_listen_addr(&mut self, addr: &Multiaddr);
+
+    /// Equivalent to `NetworkBehaviour::inject_listener_error`.
+    fn inject_listener_error(&mut self, err: &dyn error::Error);
+
+    /// Equivalent to `NetworkBehaviour::inject_new_external_addr`.
+    fn inject_new_external_addr(&mut self, addr: &Multiaddr);
+
+    /// Equivalent to `NetworkBehaviour::inject_network_change`.
+    fn inject_network_change(&mut self, change: &NetworkChange);
+
+    /// Equivalent to `NetworkBehaviour::inject_shutdown`.
+    fn inject_shutdown(&mut self);
+
+    /// Equivalent to `NetworkBehaviour::inject_remote_protocols`.
+    fn inject_remote_protocols(&mut self, peer_id: &PeerId, protocols: &[String]);
+
+    /// Equivalent to `NetworkBehaviour::summarize`.
+    fn summarize(&self, summary: &mut BehaviourSummary);
+
+    /// Equivalent to `NetworkBehaviour::poll`, with the events boxed.
+    fn poll(&mut self, params: &mut DynPollParameters<'_>) -> Async<NetworkBehaviourAction<DynEvent, DynEvent>>;
+
+    /// Returns the behaviour as `Any`, in order to downcast it to its concrete type.
+    fn as_any(&self) -> &dyn Any;
+
+    /// Returns the behaviour as `Any`, in order to downcast it to its concrete type.
+    fn as_any_mut(&mut self) -> &mut dyn Any;
+}
+
+impl<TBehaviour, TSubstream, TProtoHandler, THandler, TInProto, TOutProto> DynBehaviour<TSubstream> for TBehaviour
+where
+    TBehaviour: NetworkBehaviour<ProtocolsHandler = TProtoHandler> + Send + 'static,
+    TBehaviour::OutEvent: Send + 'static,
+    TProtoHandler: IntoProtocolsHandler<Handler = THandler> + Send + 'static,
+    THandler: ProtocolsHandler<Substream = TSubstream, InboundProtocol = TInProto, OutboundProtocol = TOutProto> + Send + 'static,
+    THandler::InEvent: Send + 'static,
+    THandler::OutEvent: Send + 'static,
+    THandler::Error: Send + 'static,
+    THandler::OutboundOpenInfo: Send + 'static,
+    TInProto: InboundUpgrade<TSubstream> + Send + 'static,
+    TInProto::Info: Send + 'static,
+    TInProto::Output: Send + 'static,
+    TInProto::Error: error::Error + Send + 'static,
+    TInProto::Future: Send + 'static,
+    TOutProto: OutboundUpgrade<TSubstream> + Send + 'static,
+    TOutProto::Info: Send + 'static,
+    TOutProto::Output: Send + 'static,
+    TOutProto::Error: error::Error + Send + 'static,
+    TOutProto::Future: Send + 'static,
+{
+    fn new_handler(&mut self) -> DynIntoProtocolsHandler<TSubstream> {
+        DynIntoProtocolsHandler(Box::new(IntoHandlerWrapper(NetworkBehaviour::new_handler(self))))
+    }
+
+    fn addresses_of_peer(&mut self, peer_id: &PeerId) -> Vec<Multiaddr> {
+        NetworkBehaviour::addresses_of_peer(self, peer_id)
+    }
+
+    fn inject_connected(&mut self, peer_id: PeerId, endpoint: ConnectedPoint) {
+        NetworkBehaviour::inject_connected(self, peer_id, endpoint)
+    }
+
+    fn inject_disconnected(&mut self, peer_id: &PeerId, endpoint: ConnectedPoint) {
+        NetworkBehaviour::inject_disconnected(self, peer_id, endpoint)
+    }
+
+    fn inject_connection_established(&mut self, peer_id: &PeerId, connection: &ConnectionId, endpoint: &ConnectedPoint) {
+        NetworkBehaviour::inject_connection_established(self, peer_id, connection, endpoint)
+    }
+
+    fn inject_connection_closed(&mut self, peer_id: &PeerId, connection: &ConnectionId, endpoint: &ConnectedPoint) {
+        NetworkBehaviour::inject_connection_closed(self, peer_id, connection, endpoint)
+    }
+
+    fn inject_replaced(&mut self, peer_id: PeerId, closed_endpoint: ConnectedPoint, new_endpoint: ConnectedPoint) {
+        NetworkBehaviour::inject_replaced(self, peer_id, closed_endpoint, new_endpoint)
+    }
+
+    fn inject_node_event(&mut self, peer_id: PeerId, event: DynEvent) {
+        NetworkBehaviour::inject_node_event(self, peer_id, downcast::<THandler::OutEvent>(event))
+    }
+
+    fn inject_connection_event(&mut self, peer_id: PeerId, connection: ConnectionId, event: DynEvent) {
+        NetworkBehaviour::inject_connection_event(self, peer_id, connection, downcast::<THandler::OutEvent>(event))
+    }
+
+    fn inject_addr_reach_failure(&mut self, peer_id: Option<&PeerId>, addr: &Multiaddr, error: &dyn error::Error) {
+        NetworkBehaviour::inject_addr_reach_failure(self, peer_id, addr, error)
+    }
+
+    fn inject_dial_failure(&mut self, peer_id: &PeerId) {
+        NetworkBehaviour::inject_dial_failure(self, peer_id)
+    }
+
+    fn inject_banned_peer_connection(&mut self, peer_id: &PeerId, endpoint: &ConnectedPoint) {
+        NetworkBehaviour::inject_banned_peer_connection(self, peer_id, endpoint)
+    }
+
+    fn inject_new_listen_addr(&mut self, addr: &Multiaddr) {
+        NetworkBehaviour::inject_new_listen_addr(self, addr)
+    }
+
+    fn inject_expired_listen_addr(&mut self, addr: &Multiaddr) {
+        NetworkBehaviour::inject_expired_listen_addr(self, addr)
+    }
+
+    fn inject_listener_error(&mut self, err: &dyn error::Error) {
+        NetworkBehaviour::inject_listener_error(self, err)
+    }
+
+    fn inject_new_external_addr(&mut self, addr: &Multiaddr) {
+        NetworkBehaviour::inject_new_external_addr(self, addr)
+    }
+
+    fn inject_network_change(&mut self, change: &NetworkChange) {
+        NetworkBehaviour::inject_network_change(self, change)
+    }
+
+    fn inject_shutdown(&mut self) {
+        NetworkBehaviour::inject_shutdown(self)
+    }
+
+    fn inject_remote_protocols(&mut self, peer_id: &PeerId, protocols: &[String]) {
+        NetworkBehaviour::inject_remote_protocols(self, peer_id, protocols)
+    }
+
+    fn summarize(&self, summary: &mut BehaviourSummary) {
+        NetworkBehaviour::summarize(self, summary)
+    }
+
+    fn poll(&mut self, params: &mut DynPollParameters<'_>) -> Async<NetworkBehaviourAction<DynEvent, DynEvent>> {
+        let action = match NetworkBehaviour::poll(self, params) {
+            Async::Ready(action) => action,
+            Async::NotReady => return Async::NotReady,
+        };
+
+        Async::Ready(match action {
+            NetworkBehaviourAction::GenerateEvent(event) =>
+                NetworkBehaviourAction::GenerateEvent(Box::new(event) as DynEvent),
+            NetworkBehaviourAction::DialAddress { address } =>
+                NetworkBehaviourAction::DialAddress { address },
+            NetworkBehaviourAction::DialPeer { peer_id } =>
+                NetworkBehaviourAction::DialPeer { peer_id },
+            NetworkBehaviourAction::SendEvent { peer_id, event } =>
+                NetworkBehaviourAction::SendEvent { peer_id, event: Box::new(event) as DynEvent },
+            NetworkBehaviourAction::SendEventToConnection { peer_id, connection, event } =>
+                NetworkBehaviourAction::SendEventToConnection { peer_id, connection, event: Box::new(event) as DynEvent },
+            NetworkBehaviourAction::ReportObservedAddr { address, observer } =>
+                NetworkBehaviourAction::ReportObservedAddr { address, observer },
+            NetworkBehaviourAction::ReportRemoteProtocols { peer_id, protocols } =>
+                NetworkBehaviourAction::ReportRemoteProtocols { peer_id, protocols },
+            NetworkBehaviourAction::ReportRoundTripTime { peer_id, rtt } =>
+                NetworkBehaviourAction::ReportRoundTripTime { peer_id, rtt },
+            NetworkBehaviourAction::ReportTransfer { peer_id, bytes_received, bytes_sent, duration } =>
+                NetworkBehaviourAction::ReportTransfer { peer_id, bytes_received, bytes_sent, duration },
+            NetworkBehaviourAction::ReportExternalAddr { address, source } =>
+                NetworkBehaviourAction::ReportExternalAddr { address, source },
+            NetworkBehaviourAction::RemoveExternalAddr { address } =>
+                NetworkBehaviourAction::RemoveExternalAddr { address },
+        })
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn as_any_mut(&mut self) -> &mut dyn Any {
+        self
+    }
+}
+
+/// `PollParameters` passed to the `DynBehaviour`s.
+///
+/// > **Note**: The supported protocols are those of the behaviours that were present when the
+/// >           `Swarm` was created.
+pub struct DynPollParameters<'a> {
+    supported_protocols: Vec<Vec<u8>>,
+    listened_addresses: Vec<Multiaddr>,
+    external_addresses: Vec<Multiaddr>,
+    local_peer_id: PeerId,
+    is_dial_backed_off: Box<dyn Fn(&PeerId) -> bool + 'a>,
+    peer_stats: Box<dyn Fn(&PeerId) -> Option<PeerStats> + 'a>,
+}
+
+impl<'a> DynPollParameters<'a> {
+    /// Builds a `DynPollParameters` from the parameters passed by the `Swarm`.
+    fn new<TParams: PollParameters>(params: &'a TParams) -> Self {
+        DynPollParameters {
+            supported_protocols: params.supported_protocols().collect(),
+            listened_addresses: params.listened_addresses().collect(),
+            external_addresses: params.external_addresses().collect(),
+            local_peer_id: params.local_peer_id().clone(),
+            is_dial_backed_off: Box::new(move |peer_id| params.is_dial_backed_off(peer_id)),
+            peer_stats: Box::new(move |peer_id| params.peer_stats(peer_id)),
+        }
+    }
+}
+
+impl<'a> PollParameters for DynPollParameters<'a> {
+    type SupportedProtocolsIter = std::vec::IntoIter<Vec<u8>>;
+    type ListenedAddressesIter = std::vec::IntoIter<Multiaddr>;
+    type ExternalAddressesIter = std::vec::IntoIter<Multiaddr>;
+
+    fn supported_protocols(&self) -> Self::SupportedProtocolsIter {
+        self.supported_protocols.clone().into_iter()
+    }
+
+    fn listened_addresses(&self) -> Self::ListenedAddressesIter {
+        self.listened_addresses.clone().into_iter()
+    }
+
+    fn external_addresses(&self) -> Self::ExternalAddressesIter {
+        self.external_addresses.clone().into_iter()
+    }
+
+    fn local_peer_id(&self) -> &PeerId {
+        &self.local_peer_id
+    }
+
+    fn is_dial_backed_off(&self, peer_id: &PeerId) -> bool {
+        (self.is_dial_backed_off)(peer_id)
+    }
+
+    fn peer_stats(&self, peer_id: &PeerId) -> Option<PeerStats> {
+        (self.peer_stats)(peer_id)
+    }
+}
+
+/// Event produced by a behaviour of a `DynBehaviours`.
+#[derive(Debug)]
+pub struct DynBehavioursEvent {
+    /// The behaviour that produced the event.
+    pub id: DynBehaviourId,
+    /// The event, to downcast to the `OutEvent` of the behaviour.
+    pub event: DynEvent,
+}
+
+/// Network behaviour made of a set of `DynBehaviour`s that can be added and removed at runtime.
+///
+/// See the module documentation for more information.
+pub struct DynBehaviours<TSubstream> {
+    /// The behaviours, in the order they have been added.
+    behaviours: Vec<(DynBehaviourId, Box<dyn DynBehaviour<TSubstream>>)>,
+    /// Identifier to assign to the next behaviour.
+    next_id: u64,
+    /// Index of the behaviour to poll first, so that all the behaviours get polled in turn.
+    next_poll: usize,
+}
+
+impl<TSubstream> DynBehaviours<TSubstream> {
+    /// Creates a new `DynBehaviours` without any behaviour.
+    pub fn new() -> Self {
+        DynBehaviours {
+            behaviours: Vec::new(),
+            next_id: 0,
+            next_poll: 0,
+        }
+    }
+
+    /// Adds a behaviour and returns its identifier.
+    pub fn add<TBehaviour>(&mut self, behaviour: TBehaviour) -> DynBehaviourId
+    where
+        TBehaviour: DynBehaviour<TSubstream> + 'static,
+    {
+        self.add_boxed(Box::new(behaviour))
+    }
+
+    /// Adds a boxed behaviour and returns its identifier.
+    pub fn add_boxed(&mut self, behaviour: Box<dyn DynBehaviour<TSubstream>>) -> DynBehaviourId {
+        let id = DynBehaviourId(self.next_id);
+        self.next_id += 1;
+        self.behaviours.push((id, behaviour));
+        id
+    }
+
+    /// Removes a behaviour. Returns `None` if there is no behaviour with this identifier.
+    pub fn remove(&mut self, id: DynBehaviourId) -> Option<Box<dyn DynBehaviour<TSubstream>>> {
+        let index = self.behaviours.iter().position(|(i, _)| *i == id)?;
+        Some(self.behaviours.remove(index).1)
+    }
+
+    /// Returns the behaviour with the given identifier, if it is of type `TBehaviour`.
+    pub fn get<TBehaviour: 'static>(&self, id: DynBehaviourId) -> Option<&TBehaviour> {
+        self.behaviours.iter()
+            .find(|(i, _)| *i == id)
+            .and_then(|(_, b)| b.as_any().downcast_ref())
+    }
+
+    /// Returns the behaviour with the given identifier, if it is of type `TBehaviour`.
+    pub fn get_mut<TBehaviour: 'static>(&mut self, id: DynBehaviourId) -> Option<&mut TBehaviour> {
+        self.behaviours.iter_mut()
+            .find(|(i, _)| *i == id)
+            .and_then(|(_, b)| b.as_any_mut().downcast_mut())
+    }
+
+    /// Returns the identifiers of the behaviours, in the order they have been added.
+    pub fn ids(&self) -> impl Iterator<Item = DynBehaviourId> + '_ {
+        self.behaviours.iter().map(|(id, _)| *id)
+    }
+
+    /// Returns the number of behaviours.
+    pub fn len(&self) -> usize {
+        self.behaviours.len()
+    }
+
+    /// Returns true if there is no behaviour.
+    pub fn is_empty(&self) -> bool {
+        self.behaviours.is_empty()
+    }
+
+    /// Returns the behaviour with the given identifier.
+    fn behaviour_mut(&mut self, id: DynBehaviourId) -> Option<&mut Box<dyn DynBehaviour<TSubstream>>> {
+        self.behaviours.iter_mut().find(|(i, _)| *i == id).map(|(_, b)| b)
+    }
+}
+
+impl<TSubstream> Default for DynBehaviours<TSubstream> {
+    fn default() -> Self {
+        DynBehaviours::new()
+    }
+}
+
+impl<TSubstream> NetworkBehaviour for DynBehaviours<TSubstream>
+where
+    TSubstream: AsyncRead + AsyncWrite,
+{
+    type ProtocolsHandler = DynBehavioursIntoHandler<TSubstream>;
+    type OutEvent = DynBehavioursEvent;
+
+    fn new_handler(&mut self) -> Self::ProtocolsHandler {
+        DynBehavioursIntoHandler {
+            handlers: self.behaviours.iter_mut()
+                .map(|(id, b)| (*id, b.new_handler().0))
+                .collect(),
+        }
+    }
+
+    fn addresses_of_peer(&mut self, peer_id: &PeerId) -> Vec<Multiaddr> {
+        let mut addresses = Vec::new();
+        for (_, behaviour) in &mut self.behaviours {
+            for addr in behaviour.addresses_of_peer(peer_id) {
+                if !addresses.contains(&addr) {
+                    addresses.push(addr);
+                }
+            }
+        }
+        addresses
+    }
+
+    fn inject_connected(&mut self, peer_id: PeerId, endpoint: ConnectedPoint) {
+        for (_, behaviour) in &mut self.behaviours {
+            behaviour.inject_connected(peer_id.clone(), endpoint.clone());
+        }
+    }
+
+    fn inject_disconnected(&mut self, peer_id: &PeerId, endpoint: ConnectedPoint) {
+        for (_, behaviour) in &mut self.behaviours {
+            behaviour.inject_disconnected(peer_id, endpoint.clone());
+        }
+    }
+
+    fn inject_connection_established(&mut self, peer_id: &PeerId, connection: &ConnectionId, endpoint: &ConnectedPoint) {
+        for (_, behaviour) in &mut self.behaviours {
+            behaviour.inject_connection_established(peer_id, connection, endpoint);
+        }
+    }
+
+    fn inject_connection_closed(&mut self, peer_id: &PeerId, connection: &ConnectionId, endpoint: &ConnectedPoint) {
+        for (_, behaviour) in &mut self.behaviours {
+            behaviour.inject_connection_closed(peer_id, connection, endpoint);
+        }
+    }
+
+    fn inject_replaced(&mut self, peer_id: PeerId, closed_endpoint: ConnectedPoint, new_endpoint: ConnectedPoint) {
+        for (_, behaviour) in &mut self.behaviours {
+            behaviour.inject_replaced(peer_id.clone(), closed_endpoint.clone(), new_endpoint.clone());
+        }
+    }
+
+    fn inject_node_event(&mut self, peer_id: PeerId, (id, event): (DynBehaviourId, DynEvent)) {
+        if let Some(behaviour) = self.behaviour_mut(id) {
+            behaviour.inject_node_event(peer_id, event);
+        }
+    }
+
+    fn inject_connection_event(&mut self, peer_id: PeerId, connection: ConnectionId, (id, event): (DynBehaviourId, DynEvent)) {
+        if let Some(behaviour) = self.behaviour_mut(id) {
+            behaviour.inject_connection_event(peer_id, connection, event);
+        }
+    }
+
+    fn inject_addr_reach_failure(&mut self, peer_id: Option<&PeerId>, addr: &Multiaddr, error: &dyn error::Error) {
+        for (_, behaviour) in &mut self.behaviours {
+            behaviour.inject_addr_reach_failure(peer_id, addr, error);
+        }
+    }
+
+    fn inject_dial_failure(&mut self, peer_id: &PeerId) {
+        for (_, behaviour) in &mut self.behaviours {
+            behaviour.inject_dial_failure(peer_id);
+        }
+    }
+
+    fn inject_banned_peer_connection(&mut self, peer_id: &PeerId, endpoint: &ConnectedPoint) {
+        for (_, behaviour) in &mut self.behaviours {
+            behaviour.inject_banned_peer_connection(peer_id, endpoint);
+        }
+    }
+
+    fn inject_new_listen_addr(&mut self, addr: &Multiaddr) {
+        for (_, behaviour) in &mut self.behaviours {
+            behaviour.inject_new_listen_addr(addr);
+        }
+    }
+
+    fn inject_expired_listen_addr(&mut self, addr: &Multiaddr) {
+        for (_, behaviour) in &mut self.behaviours {
+            behaviour.inject_expired_listen_addr(addr);
+        }
+    }
+
+    fn inject_listener_error(&mut self, err: &dyn error::Error) {
+        for (_, behaviour) in &mut self.behaviours {
+            behaviour.inject_listener_error(err);
+        }
+    }
+
+    fn inject_new_external_addr(&mut self, addr: &Multiaddr) {
+        for (_, behaviour) in &mut self.behaviours {
+            behaviour.inject_new_external_addr(addr);
+        }
+    }
+
+    fn inject_network_change(&mut self, change: &NetworkChange) {
+        for (_, behaviour) in &mut self.behaviours {
+            behaviour.inject_network_change(change);
+        }
+    }
+
+    fn inject_shutdown(&mut self) {
+        for (_, behaviour) in &mut self.behaviours {
+            behaviour.inject_shutdown();
+        }
+    }
+
+    fn inject_remote_protocols(&mut self, peer_id: &PeerId, protocols: &[String]) {
+        for (_, behaviour) in &mut self.behaviours {
+            behaviour.inject_remote_protocols(peer_id, protocols);
+        }
+    }
+
+    fn summarize(&self, summary: &mut BehaviourSummary) {
+        for (_, behaviour) in &self.behaviours {
+            behaviour.summarize(summary);
+        }
+    }
+
+    fn poll(&mut self, params: &mut impl PollParameters)
+        -> Async<NetworkBehaviourAction<(DynBehaviourId, DynEvent), DynBehavioursEvent>>
+    {
+        let num_behaviours = self.behaviours.len();
+        if num_behaviours == 0 {
+            return Async::NotReady
+        }
+
+        let mut params = DynPollParameters::new(params);
+        for n in 0..num_behaviours {
+            let index = (self.next_poll + n) % num_behaviours;
+            let (id, behaviour) = &mut self.behaviours[index];
+            let id = *id;
+            let action = match behaviour.poll(&mut params) {
+                Async::Ready(action) => action,
+                Async::NotReady => continue,
+            };
+            self.next_poll = (index + 1) % num_behaviours;
+
+            return Async::Ready(match action {
+                NetworkBehaviourAction::GenerateEvent(event) =>
+                    NetworkBehaviourAction::GenerateEvent(DynBehavioursEvent { id, event }),
+                NetworkBehaviourAction::DialAddress { address } =>
+                    NetworkBehaviourAction::DialAddress { address },
+                NetworkBehaviourAction::DialPeer { peer_id } =>
+                    NetworkBehaviourAction::DialPeer { peer_id },
+                NetworkBehaviourAction::SendEvent { peer_id, event } =>
+                    NetworkBehaviourAction::SendEvent { peer_id, event: (id, event) },
+                NetworkBehaviourAction::SendEventToConnection { peer_id, connection, event } =>
+                    NetworkBehaviourAction::SendEventToConnection { peer_id, connection, event: (id, event) },
+                NetworkBehaviourAction::ReportObservedAddr { address, observer } =>
+                    NetworkBehaviourAction::ReportObservedAddr { address, observer },
+                NetworkBehaviourAction::ReportRemoteProtocols { peer_id, protocols } =>
+                    NetworkBehaviourAction::ReportRemoteProtocols { peer_id, protocols },
+                NetworkBehaviourAction::ReportRoundTripTime { peer_id, rtt } =>
+                    NetworkBehaviourAction::ReportRoundTripTime { peer_id, rtt },
+                NetworkBehaviourAction::ReportTransfer { peer_id, bytes_received, bytes_sent, duration } =>
+                    NetworkBehaviourAction::ReportTransfer { peer_id, bytes_received, bytes_sent, duration },
+                NetworkBehaviourAction::ReportExternalAddr { address, source } =>
+                    NetworkBehaviourAction::ReportExternalAddr { address, source },
+                NetworkBehaviourAction::RemoveExternalAddr { address } =>
+                    NetworkBehaviourAction::RemoveExternalAddr { address },
+            })
+        }
+
+        Async::NotReady
+    }
+}
+
+/// Object-safe version of `InboundUpgrade`.
+trait ErasedInbound<TSubstream>: Send {
+    /// Returns the names of the protocols, in the order of `protocol_info`.
+    fn protocol_names(&self) -> Vec<Vec<u8>>;
+
+    /// Starts the upgrade for the protocol at the given index of `protocol_names`.
+    fn upgrade_inbound(self: Box<Self>, socket: Negotiated<TSubstream>, index: usize) -> DynUpgradeFuture;
+}
+
+/// Object-safe version of `OutboundUpgrade`.
+trait ErasedOutbound<TSubstream>: Send {
+    /// Returns the names of the protocols, in the order of `protocol_info`.
+    fn protocol_names(&self) -> Vec<Vec<u8>>;
+
+    /// Starts the upgrade for the protocol at the given index of `protocol_names`.
+    fn upgrade_outbound(self: Box<Self>, socket: Negotiated<TSubstream>, index: usize) -> DynUpgradeFuture;
+}
+
+/// Implementation of `ErasedInbound` and `ErasedOutbound` for an upgrade.
+struct UpgradeWrapper<TUpgrade: UpgradeInfo> {
+    upgrade: TUpgrade,
+    infos: Vec<TUpgrade::Info>,
+}
+
+impl<TUpgrade: UpgradeInfo> UpgradeWrapper<TUpgrade> {
+    fn new(upgrade: TUpgrade) -> Self {
+        let infos = upgrade.protocol_info().into_iter().collect();
+        UpgradeWrapper { upgrade, infos }
+    }
+
+    fn protocol_names(&self) -> Vec<Vec<u8>> {
+        self.infos.iter().map(|info| info.protocol_name().to_vec()).collect()
+    }
+}
+
+impl<TUpgrade, TSubstream> ErasedInbound<TSubstream> for UpgradeWrapper<TUpgrade>
+where
+    TUpgrade: InboundUpgrade<TSubstream> + Send + 'static,
+    TUpgrade::Info: Send + 'static,
+    TUpgrade::Output: Send + 'static,
+    TUpgrade::Error: error::Error + Send + 'static,
+    TUpgrade::Future: Send + 'static,
+{
+    fn protocol_names(&self) -> Vec<Vec<u8>> {
+        UpgradeWrapper::protocol_names(self)
+    }
+
+    fn upgrade_inbound(self: Box<Self>, socket: Negotiated<TSubstream>, index: usize) -> DynUpgradeFuture {
+        let UpgradeWrapper { upgrade, mut infos } = *self;
+        let info = infos.swap_remove(index);
+        Box::new(upgrade.upgrade_inbound(socket, info)
+            .map(|out| Box::new(out) as DynEvent)
+            .map_err(DynError::new))
+    }
+}
+
+impl<TUpgrade, TSubstream> ErasedOutbound<TSubstream> for UpgradeWrapper<TUpgrade>
+where
+    TUpgrade: OutboundUpgrade<TSubstream> + Send + 'static,
+    TUpgrade::Info: Send + 'static,
+    TUpgrade::Output: Send + 'static,
+    TUpgrade::Error: error::Error + Send + 'static,
+    TUpgrade::Future: Send + 'static,
+{
+    fn protocol_names(&self) -> Vec<Vec<u8>> {
+        UpgradeWrapper::protocol_names(self)
+    }
+
+    fn upgrade_outbound(self: Box<Self>, socket: Negotiated<TSubstream>, index: usize) -> DynUpgradeFuture {
+        let UpgradeWrapper { upgrade, mut infos } = *self;
+        let info = infos.swap_remove(index);
+        Box::new(upgrade.upgrade_outbound(socket, info)
+            .map(|out| Box::new(out) as DynEvent)
+            .map_err(DynError::new))
+    }
+}
+
+/// Protocol negotiated by a `DynInboundUpgrade` or a `DynOutboundUpgrade`.
+#[derive(Debug, Clone)]
+pub struct DynProtocolInfo {
+    name: Vec<u8>,
+    /// Index of the upgrade that supports the protocol.
+    upgrade: usize,
+    /// Index of the protocol within the protocols of the upgrade.
+    index: usize,
+}
+
+impl AsRef<[u8]> for DynProtocolInfo {
+    fn as_ref(&self) -> &[u8] {
+        &self.name
+    }
+}
+
+/// Inbound upgrade of a `DynBehavioursHandler`, supporting the protocols of all the handlers.
+pub struct DynInboundUpgrade<TSubstream> {
+    upgrades: Vec<(DynBehaviourId, Box<dyn ErasedInbound<TSubstream>>)>,
+}
+
+impl<TSubstream> UpgradeInfo for DynInboundUpgrade<TSubstream> {
+    type Info = DynProtocolInfo;
+    type InfoIter = Vec<DynProtocolInfo>;
+
+    fn protocol_info(&self) -> Self::InfoIter {
+        let mut infos = Vec::new();
+        for (upgrade, (_, inner)) in self.upgrades.iter().enumerate() {
+            for (index, name) in inner.protocol_names().into_iter().enumerate() {
+                infos.push(DynProtocolInfo { name, upgrade, index });
+            }
+        }
+        infos
+    }
+}
+
+impl<TSubstream> InboundUpgrade<TSubstream> for DynInboundUpgrade<TSubstream> {
+    type Output = (DynBehaviourId, DynEvent);
+    type Error = DynError;
+    type Future = Box<dyn Future<Item = Self::Output, Error = DynError> + Send>;
+
+    fn upgrade_inbound(mut self, socket: Negotiated<TSubstream>, info: Self::Info) -> Self::Future {
+        let (id, upgrade) = self.upgrades.swap_remove(info.upgrade);
+        Box::new(upgrade.upgrade_inbound(socket, info.index).map(move |out| (id, out)))
+    }
+}
+
+/// Outbound upgrade of a `DynBehavioursHandler`.
+pub struct DynOutboundUpgrade<TSubstream> {
+    upgrade: Box<dyn ErasedOutbound<TSubstream>>,
+}
+
+impl<TSubstream> UpgradeInfo for DynOutboundUpgrade<TSubstream> {
+    type Info = DynProtocolInfo;
+    type InfoIter = Vec<DynProtocolInfo>;
+
+    fn protocol_info(&self) -> Self::InfoIter {
+        self.upgrade.protocol_names()
+            .into_iter()
+            .enumerate()
+            .map(|(index, name)| DynProtocolInfo { name, upgrade: 0, index })
+            .collect()
+    }
+}
+
+impl<TSubstream> OutboundUpgrade<TSubstream> for DynOutboundUpgrade<TSubstream> {
+    type Output = DynEvent;
+    type Error = DynError;
+    type Future = DynUpgradeFuture;
+
+    fn upgrade_outbound(self, socket: Negotiated<TSubstream>, info: Self::Info) -> Self::Future {
+        self.upgrade.upgrade_outbound(socket, info.index)
+    }
+}
+
+/// Object-safe version of `ProtocolsHandler`.
+trait ErasedHandler<TSubstream>: Send {
+    fn listen_protocol(&self) -> SubstreamProtocol<Box<dyn ErasedInbound<TSubstream>>>;
+    fn inject_fully_negotiated_inbound(&mut self, output: DynEvent);
+    fn inject_fully_negotiated_outbound(&mut self, output: DynEvent, info: DynEvent);
+    fn inject_event(&mut self, event: DynEvent);
+    fn inject_dial_upgrade_error(&mut self, info: DynEvent, error: ProtocolsHandlerUpgrErr<DynError>);
+    fn connection_keep_alive(&self) -> KeepAlive;
+    fn poll(&mut self) -> Poll<ProtocolsHandlerEvent<Box<dyn ErasedOutbound<TSubstream>>, DynEvent, DynEvent>, DynError>;
+}
+
+/// Implementation of `ErasedHandler` for a `ProtocolsHandler`.
+struct HandlerWrapper<THandler>(THandler);
+
+impl<THandler, TSubstream, TInProto, TOutProto> ErasedHandler<TSubstream> for HandlerWrapper<THandler>
+where
+    THandler: ProtocolsHandler<Substream = TSubstream, InboundProtocol = TInProto, OutboundProtocol = TOutProto> + Send + 'static,
+    THandler::InEvent: Send + 'static,
+    THandler::OutEvent: Send + 'static,
+    THandler::Error: Send + 'static,
+    THandler::OutboundOpenInfo: Send + 'static,
+    TInProto: InboundUpgrade<TSubstream> + Send + 'static,
+    TInProto::Info: Send + 'static,
+    TInProto::Output: Send + 'static,
+    TInProto::Error: error::Error + Send + 'static,
+    TInProto::Future: Send + 'static,
+    TOutProto: OutboundUpgrade<TSubstream> + Send + 'static,
+    TOutProto::Info: Send + 'static,
+    TOutProto::Output: Send + 'static,
+    TOutProto::Error: error::Error + Send + 'static,
+    TOutProto::Future: Send + 'static,
+{
+    fn listen_protocol(&self) -> SubstreamProtocol<Box<dyn ErasedInbound<TSubstream>>> {
+        self.0.listen_protocol()
+            .map_upgrade(|upgrade| Box::new(UpgradeWrapper::new(upgrade)) as Box<dyn ErasedInbound<TSubstream>>)
+    }
+
+    fn inject_fully_negotiated_inbound(&mut self, output: DynEvent) {
+        self.0.inject_fully_negotiated_inbound(downcast::<TInProto::Output>(output))
+    }
+
+    fn inject_fully_negotiated_outbound(&mut self, output: DynEvent, info: DynEvent) {
+        self.0.inject_fully_negotiated_outbound(
+            downcast::<TOutProto::Output>(output),
+            downcast::<THandler::OutboundOpenInfo>(info)
+        )
+    }
+
+    fn inject_event(&mut self, event: DynEvent) {
+        self.0.inject_event(downcast::<THandler::InEvent>(event))
+    }
+
+    fn inject_dial_upgrade_error(&mut self, info: DynEvent, error: ProtocolsHandlerUpgrErr<DynError>) {
+        let error = match error {
+            ProtocolsHandlerUpgrErr::Timeout => ProtocolsHandlerUpgrErr::Timeout,
+            ProtocolsHandlerUpgrErr::Timer => ProtocolsHandlerUpgrErr::Timer,
+            ProtocolsHandlerUpgrErr::Upgrade(UpgradeError::Select(err)) =>
+                ProtocolsHandlerUpgrErr::Upgrade(UpgradeError::Select(err)),
+            ProtocolsHandlerUpgrErr::Upgrade(UpgradeError::Apply(err)) => {
+                let err = err.downcast::<TOutProto::Error>()
+                    .expect("The error has been produced by the upgrade of this handler; QED");
+                ProtocolsHandlerUpgrErr::Upgrade(UpgradeError::Apply(err))
+            }
+        };
+        self.0.inject_dial_upgrade_error(downcast::<THandler::OutboundOpenInfo>(info), error)
+    }
+
+    fn connection_keep_alive(&self) -> KeepAlive {
+        self.0.connection_keep_alive()
+    }
+
+    fn poll(&mut self) -> Poll<ProtocolsHandlerEvent<Box<dyn ErasedOutbound<TSubstream>>, DynEvent, DynEvent>, DynError> {
+        let event = match self.0.poll().map_err(DynError::new)? {
+            Async::Ready(event) => event,
+            Async::NotReady => return Ok(Async::NotReady),
+        };
+
+        Ok(Async::Ready(event
+            .map_protocol(|upgrade| Box::new(UpgradeWrapper::new(upgrade)) as Box<dyn ErasedOutbound<TSubstream>>)
+            .map_outbound_open_info(|info| Box::new(info) as DynEvent)
+            .map_custom(|event| Box::new(event) as DynEvent)))
+    }
+}
+
+/// Object-safe version of `IntoProtocolsHandler`.
+trait ErasedIntoHandler<TSubstream>: Send {
+    fn into_handler(self: Box<Self>, remote_peer_id: &PeerId, connected_point: &ConnectedPoint) -> Box<dyn ErasedHandler<TSubstream>>;
+    fn inbound_protocol(&self) -> Box<dyn ErasedInbound<TSubstream>>;
+}
+
+/// Implementation of `ErasedIntoHandler` for an `IntoProtocolsHandler`.
+struct IntoHandlerWrapper<TIntoHandler>(TIntoHandler);
+
+impl<TIntoHandler, TSubstream, THandler, TInProto, TOutProto> ErasedIntoHandler<TSubstream> for IntoHandlerWrapper<TIntoHandler>
+where
+    TIntoHandler: IntoProtocolsHandler<Handler = THandler> + Send + 'static,
+    THandler: ProtocolsHandler<Substream = TSubstream, InboundProtocol = TInProto, OutboundProtocol = TOutProto> + Send + 'static,
+    THandler::InEvent: Send + 'static,
+    THandler::OutEvent: Send + 'static,
+    THandler::Error: Send + 'static,
+    THandler::OutboundOpenInfo: Send + 'static,
+    TInProto: InboundUpgrade<TSubstream> + Send + 'static,
+    TInProto::Info: Send + 'static,
+    TInProto::Output: Send + 'static,
+    TInProto::Error: error::Error + Send + 'static,
+    TInProto::Future: Send + 'static,
+    TOutProto: OutboundUpgrade<TSubstream> + Send + 'static,
+    TOutProto::Info: Send + 'static,
+    TOutProto::Output: Send + 'static,
+    TOutProto::Error: error::Error + Send + 'static,
+    TOutProto::Future: Send + 'static,
+{
+    fn into_handler(self: Box<Self>, remote_peer_id: &PeerId, connected_point: &ConnectedPoint) -> Box<dyn ErasedHandler<TSubstream>> {
+        Box::new(HandlerWrapper(self.0.into_handler(remote_peer_id, connected_point)))
+    }
+
+    fn inbound_protocol(&self) -> Box<dyn ErasedInbound<TSubstream>> {
+        Box::new(UpgradeWrapper::new(self.0.inbound_protocol()))
+    }
+}
+
+/// Handler prototype of a `DynBehaviour`, as returned by `DynBehaviour::new_handler`.
+pub struct DynIntoProtocolsHandler<TSubstream>(Box<dyn ErasedIntoHandler<TSubstream>>);
+
+/// Implementation of `IntoProtocolsHandler` for `DynBehaviours`.
+pub struct DynBehavioursIntoHandler<TSubstream> {
+    handlers: Vec<(DynBehaviourId, Box<dyn ErasedIntoHandler<TSubstream>>)>,
+}
+
+impl<TSubstream> IntoProtocolsHandler for DynBehavioursIntoHandler<TSubstream>
+where
+    TSubstream: AsyncRead + AsyncWrite,
+{
+    type Handler = DynBehavioursHandler<TSubstream>;
+
+    fn into_handler(self, remote_peer_id: &PeerId, connected_point: &ConnectedPoint) -> Self::Handler {
+        DynBehavioursHandler {
+            handlers: self.handlers.into_iter()
+                .map(|(id, h)| (id, h.into_handler(remote_peer_id, connected_point)))
+                .collect(),
+        }
+    }
+
+    fn inbound_protocol(&self) -> DynInboundUpgrade<TSubstream> {
+        DynInboundUpgrade {
+            upgrades: self.handlers.iter().map(|(id, h)| (*id, h.inbound_protocol())).collect(),
+        }
+    }
+}
+
+/// Implementation of `ProtocolsHandler` for `DynBehaviours`, that dispatches the substreams and
+/// events between the handlers of the behaviours.
+pub struct DynBehavioursHandler<TSubstream> {
+    handlers: Vec<(DynBehaviourId, Box<dyn ErasedHandler<TSubstream>>)>,
+}
+
+impl<TSubstream> DynBehavioursHandler<TSubstream> {
+    fn handler_mut(&mut self, id: DynBehaviourId) -> Option<&mut Box<dyn ErasedHandler<TSubstream>>> {
+        self.handlers.iter_mut().find(|(i, _)| *i == id).map(|(_, h)| h)
+    }
+}
+
+impl<TSubstream> ProtocolsHandler for DynBehavioursHandler<TSubstream>
+where
+    TSubstream: AsyncRead + AsyncWrite,
+{
+    type InEvent = (DynBehaviourId, DynEvent);
+    type OutEvent = (DynBehaviourId, DynEvent);
+    type Error = DynError;
+    type Substream = TSubstream;
+    type InboundProtocol = DynInboundUpgrade<TSubstream>;
+    type OutboundProtocol = DynOutboundUpgrade<TSubstream>;
+    type OutboundOpenInfo = (DynBehaviourId, DynEvent);
+
+    fn listen_protocol(&self) -> SubstreamProtocol<Self::InboundProtocol> {
+        let mut timeout = Duration::from_secs(0);
+        let upgrades = self.handlers.iter()
+            .map(|(id, h)| {
+                let protocol = h.listen_protocol();
+                timeout = cmp::max(timeout, *protocol.timeout());
+                (*id, protocol.into_upgrade())
+            })
+            .collect();
+        SubstreamProtocol::new(DynInboundUpgrade { upgrades }).with_timeout(timeout)
+    }
+
+    fn inject_fully_negotiated_inbound(&mut self, (id, output): (DynBehaviourId, DynEvent)) {
+        if let Some(handler) = self.handler_mut(id) {
+            handler.inject_fully_negotiated_inbound(output)
+        }
+    }
+
+    fn inject_fully_negotiated_outbound(&mut self, output: DynEvent, (id, info): Self::OutboundOpenInfo) {
+        if let Some(handler) = self.handler_mut(id) {
+            handler.inject_fully_negotiated_outbound(output, info)
+        }
+    }
+
+    fn inject_event(&mut self, (id, event): Self::InEvent) {
+        if let Some(handler) = self.handler_mut(id) {
+            handler.inject_event(event)
+        }
+    }
+
+    fn inject_dial_upgrade_error(&mut self, (id, info): Self::OutboundOpenInfo, error: ProtocolsHandlerUpgrErr<DynError>) {
+        if let Some(handler) = self.handler_mut(id) {
+            handler.inject_dial_upgrade_error(info, error)
+        }
+    }
+
+    fn connection_keep_alive(&self) -> KeepAlive {
+        self.handlers.iter()
+            .map(|(_, h)| h.connection_keep_alive())
+            .max()
+            .unwrap_or(KeepAlive::No)
+    }
+
+    fn poll(&mut self) -> Poll<ProtocolsHandlerEvent<Self::OutboundProtocol, Self::OutboundOpenInfo, Self::OutEvent>, Self::Error> {
+        for (id, handler) in &mut self.handlers {
+            let id = *id;
+            match handler.poll()? {
+                Async::Ready(ProtocolsHandlerEvent::OutboundSubstreamRequest { protocol, info }) => {
+                    return Ok(Async::Ready(ProtocolsHandlerEvent::OutboundSubstreamRequest {
+                        protocol: protocol.map_upgrade(|upgrade| DynOutboundUpgrade { upgrade }),
+                        info: (id, info),
+                    }))
+                }
+                Async::Ready(ProtocolsHandlerEvent::Custom(event)) => {
+                    return Ok(Async::Ready(ProtocolsHandlerEvent::Custom((id, event))))
+                }
+                Async::NotReady => {}
+            }
+        }
+
+        Ok(Async::NotReady)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::protocols_handler::DummyProtocolsHandler;
+    use libp2p_core::transport::dummy::DummyStream;
+    use std::{iter, marker::PhantomData};
+
+    type TestSubstream = DummyStream;
+
+    /// Behaviour that generates the given events then stays idle.
+    struct Emitter<TSubstream> {
+        events: Vec<u32>,
+        marker: PhantomData<TSubstream>,
+    }
+
+    impl<TSubstream> NetworkBehaviour for Emitter<TSubstream>
+    where
+        TSubstream: AsyncRead + AsyncWrite,
+    {
+        type ProtocolsHandler = DummyProtocolsHandler<TSubstream>;
+        type OutEvent = u32;
+
+        fn new_handler(&mut self) -> Self::ProtocolsHandler {
+            DummyProtocolsHandler::default()
+        }
+
+        fn addresses_of_peer(&mut self, _: &PeerId) -> Vec<Multiaddr> {
+            Vec::new()
+        }
+
+        fn inject_connected(&mut self, _: PeerId, _: ConnectedPoint) {}
+
+        fn inject_disconnected(&mut self, _: &PeerId, _: ConnectedPoint) {}
+
+        fn inject_node_event(&mut self, _: PeerId, _: void::Void) {}
+
+        fn poll(&mut self, _: &mut impl PollParameters) -> Async<NetworkBehaviourAction<void::Void, u32>> {
+            match self.events.pop() {
+                Some(event) => Async::Ready(NetworkBehaviourAction::GenerateEvent(event)),
+                None => Async::NotReady,
+            }
+        }
+    }
+
+    struct TestPollParameters(PeerId);
+
+    impl PollParameters for TestPollParameters {
+        type SupportedProtocolsIter = iter::Empty<Vec<u8>>;
+        type ListenedAddressesIter = iter::Empty<Multiaddr>;
+        type ExternalAddressesIter = iter::Empty<Multiaddr>;
+
+        fn supported_protocols(&self) -> Self::SupportedProtocolsIter { iter::empty() }
+        fn listened_addresses(&self) -> Self::ListenedAddressesIter { iter::empty() }
+        fn external_addresses(&self) -> Self::ExternalAddressesIter { iter::empty() }
+        fn local_peer_id(&self) -> &PeerId { &self.0 }
+    }
+
+    #[test]
+    fn events_are_tagged_with_the_behaviour_id() {
+        let mut behaviours = DynBehaviours::<TestSubstream>::new();
+        let first = behaviours.add(Emitter { events: vec![1], marker: PhantomData });
+        let second = behaviours.add(Emitter { events: vec![2], marker: PhantomData });
+        let mut params = TestPollParameters(PeerId::random());
+
+        let mut received = Vec::new();
+        while let Async::Ready(action) = NetworkBehaviour::poll(&mut behaviours, &mut params) {
+            match action {
+                NetworkBehaviourAction::GenerateEvent(DynBehavioursEvent { id, event }) =>
+                    received.push((id, downcast::<u32>(event))),
+                _ => panic!("unexpected action"),
+            }
+        }
+        received.sort();
+        assert_eq!(received, vec![(first, 1), (second, 2)]);
+    }
+
+    #[test]
+    fn behaviours_can_be_added_and_removed() {
+        let mut behaviours = DynBehaviours::<TestSubstream>::new();
+        let id = behaviours.add(Emitter { events: vec![7], marker: PhantomData });
+        assert!(behaviours.get_mut::<Emitter<TestSubstream>>(id).is_some());
+        assert!(behaviours.get::<u32>(id).is_none());
+
+        assert!(behaviours.remove(id).is_some());
+        assert!(behaviours.is_empty());
+        assert!(behaviours.remove(id).is_none());
+
+        let mut params = TestPollParameters(PeerId::random());
+        assert!(NetworkBehaviour::poll(&mut behaviours, &mut params).is_not_ready());
+    }
+}
@@ -0,0 +1,112 @@
+// Copyright 2019 Parity Technologies (UK) Ltd.
+//
+// Permission is hereby granted, free of charge, to any person obtaining a
+// copy of this software and associated documentation files (the "Software"),
+// to deal in the Software without restriction, including without limitation
+// the rights to use, copy, modify, merge, publish, distribute, sublicense,
+// and/or sell copies of the Software, and to permit persons to whom the
+// Software is furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS
+// OR IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING
+// FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
+// DEALINGS IN THE SOFTWARE.
+use crate::event_log::Record;
+use std::{collections::VecDeque, fmt, io, sync::{Arc, Mutex}};
+
+/// The most recent events of a `Swarm`, kept in memory for post-mortem debugging.
+///
+/// Enabled with `SwarmBuilder::event_history`, which determines how many events are kept. Once
+/// the history is full, recording an event discards the oldest one. Events are recorded as JSON
+/// objects in the same format as `SwarmBuilder::event_log`, with the events generated by the
+/// `NetworkBehaviour` recorded as `behaviour` events whose `description` is their `Debug`
+/// representation.
+///
+/// This is a cheap handle to the history that can be cloned and moved elsewhere, for example in
+/// a panic hook, and dumped at any time.
+#[derive(Clone)]
+pub struct EventHistory {
+    inner: Arc<Mutex<Inner>>,
+}
+
+struct Inner {
+    capacity: usize,
+    events: VecDeque<String>,
+}
+
+impl EventHistory {
+    pub(crate) fn new(capacity: usize) -> Self {
+        let inner = Inner { capacity, events: VecDeque::with_capacity(capacity) };
+        EventHistory { inner: Arc::new(Mutex::new(inner)) }
+    }
+
+    pub(crate) fn record(&self, record: &Record) {
+        let mut inner = self.inner.lock().unwrap_or_else(|e| e.into_inner());
+        if inner.capacity == 0 {
+            return
+        }
+        if inner.events.len() == inner.capacity {
+            inner.events.pop_front();
+        }
+        inner.events.push_back(record.to_json());
+    }
+
+    /// Returns the recorded events as JSON objects, from the oldest to the most recent.
+    pub fn events(&self) -> Vec<String> {
+        let inner = self.inner.lock().unwrap_or_else(|e| e.into_inner());
+        inner.events.iter().cloned().collect()
+    }
+
+    /// Writes the recorded events to `writer`, one JSON object per line, from the oldest to the
+    /// most recent.
+    pub fn dump(&self, mut writer: impl io::Write) -> io::Result<()> {
+        for event in self.events() {
+            writer.write_all(event.as_bytes())?;
+            writer.write_all(b"\n")?;
+        }
+        writer.flush()
+    }
+
+    /// Discards the recorded events.
+    pub fn clear(&self) {
+        self.inner.lock().unwrap_or_else(|e| e.into_inner()).events.clear();
+    }
+}
+
+impl fmt::Debug for EventHistory {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let inner = self.inner.lock().unwrap_or_else(|e| e.into_inner());
+        f.debug_struct("EventHistory")
+            .field("capacity", &inner.capacity)
+            .field("len", &inner.events.len())
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::event_log;
+
+    #[test]
+    fn oldest_events_are_discarded() {
+        let history = EventHistory::new(2);
+        for event in &["first", "second", "third"] {
+            history.record(&event_log::behaviour_event(event));
+        }
+        let events = history.events();
+        assert_eq!(events.len(), 2);
+        assert!(events[0].ends_with(",\"event\":\"behaviour\",\"description\":\"\\\"second\\\"\"}"));
+        assert!(events[1].ends_with(",\"event\":\"behaviour\",\"description\":\"\\\"third\\\"\"}"));
+
+        let mut dump = Vec::new();
+        history.dump(&mut dump).unwrap();
+        assert_eq!(String::from_utf8(dump).unwrap(), format!("{}\n{}\n", events[0], events[1]));
+    }
+}
@@ -0,0 +1,277 @@
+// Copyright 2019 Parity Technologies (UK) Ltd.
+//
+// Permission is hereby granted, free of charge, to any person obtaining a
+// copy of this software and associated documentation files (the "Software"),
+// to deal in the Software without restriction, including without limitation
+// the rights to use, copy, modify, merge, publish, distribute, sublicense,
+// and/or sell copies of the Software, and to permit persons to whom the
+// Software is furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS
+// OR IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING
+// FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
+// DEALINGS IN THE SOFTWARE.
+
+use crate::{ConnectionClosedCause, SwarmEvent};
+use libp2p_core::{ConnectedPoint, Multiaddr, nodes::network::IncomingInfo};
+use std::{fmt, fmt::Write as _, io, time::{SystemTime, UNIX_EPOCH}};
+
+/// Writes the connection lifecycle and negotiation events of a `Swarm` to a writer, as JSON
+/// objects separated by newlines.
+///
+/// Every object has a `timestamp` in milliseconds since the Unix epoch and an `event` name,
+/// followed by the fields relevant to that event:
+///
+/// - `connection_established`: `peer`, `connection`, `address`, `direction`,
+///   `num_established` and, for outbound connections, `established_in_ms`;
+/// - `connection_closed`: `peer`, `connection`, `address`, `direction`, `num_established` and
+///   `cause`;
+/// - `unreachable_addr`: `peer` if known, `address`, `cause` and, if the negotiation of an
+///   upgrade failed, the protocols involved as `negotiation`;
+/// - `incoming_connection` and `incoming_connection_refused`: `address`, `local_address` and
+///   `direction`;
+/// - `incoming_connection_error`: `address`, `local_address`, `direction` and `cause`.
+///
+/// The `direction` is either `inbound` or `outbound`. For inbound connections, `address` is the
+/// address of the remote and `local_address` the address of the listener.
+///
+/// If writing fails, the error is kept and no further event is written.
+pub(crate) struct EventLog {
+    writer: Box<dyn io::Write + Send>,
+    error: Option<io::Error>,
+}
+
+impl EventLog {
+    pub(crate) fn new(writer: Box<dyn io::Write + Send>) -> Self {
+        EventLog { writer, error: None }
+    }
+
+    /// Returns the error that stopped the log, if any.
+    pub(crate) fn error(&self) -> Option<&io::Error> {
+        self.error.as_ref()
+    }
+
+    /// Writes a record, as returned by one of the functions of this module.
+    pub(crate) fn write(&mut self, record: &Record) {
+        if self.error.is_some() {
+            return
+        }
+        let mut line = record.to_json();
+        line.push('\n');
+        let result = self.writer.write_all(line.as_bytes()).and_then(|()| self.writer.flush());
+        if let Err(err) = result {
+            self.error = Some(err);
+        }
+    }
+}
+
+impl fmt::Debug for EventLog {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("EventLog").field("error", &self.error).finish()
+    }
+}
+
+/// Builds the record of an event about to be returned by `Swarm::poll_event`. Returns `None` for
+/// the events generated by the `NetworkBehaviour` and for the events recorded by another function.
+pub(crate) fn swarm_event<TBvEv, TConnInfo, THandlerErr>(
+    event: &SwarmEvent<TBvEv, TConnInfo, THandlerErr>
+) -> Option<Record>
+where
+    THandlerErr: fmt::Display,
+{
+    let record = match event {
+        SwarmEvent::Behaviour(_) => return None,
+        SwarmEvent::ConnectionEstablished { peer_id, connection, endpoint, num_established, established_in, .. } => {
+            let record = Record::new("connection_established")
+                .string("peer", peer_id.to_base58())
+                .number("connection", connection)
+                .endpoint(endpoint)
+                .number("num_established", num_established);
+            match established_in {
+                Some(duration) => record.number("established_in_ms", duration.as_millis()),
+                None => record,
+            }
+        },
+        SwarmEvent::ConnectionClosed { peer_id, connection, endpoint, num_established, cause } => {
+            Record::new("connection_closed")
+                .string("peer", peer_id.to_base58())
+                .number("connection", connection)
+                .endpoint(endpoint)
+                .number("num_established", num_established)
+                .string("cause", closed_cause(cause))
+        },
+        SwarmEvent::UnreachableAddr { peer_id, address, cause, negotiation } => {
+            let record = Record::new("unreachable_addr");
+            let record = match peer_id {
+                Some(peer_id) => record.string("peer", peer_id.to_base58()),
+                None => record,
+            };
+            let record = record.string("address", address).string("cause", cause.as_str());
+            match negotiation {
+                Some(failure) => record.string("negotiation", failure),
+                None => record,
+            }
+        },
+        // Recorded by `incoming_connection_error`, which has the full error.
+        SwarmEvent::IncomingConnectionError { .. } => return None,
+        SwarmEvent::IncomingConnectionRateLimited { local_addr, send_back_addr, reason } => {
+            Record::new("incoming_connection_rate_limited")
+                .incoming(send_back_addr, local_addr)
+                .string("reason", reason.as_str())
+        },
+    };
+    Some(record)
+}
+
+/// Builds the record of an incoming connection, before any upgrade has been applied to it.
+pub(crate) fn incoming_connection(info: &IncomingInfo<'_>, allowed: bool) -> Record {
+    let event = if allowed { "incoming_connection" } else { "incoming_connection_refused" };
+    Record::new(event).incoming(info.send_back_addr, info.listen_addr)
+}
+
+/// Builds the record of the failure to upgrade an incoming connection.
+pub(crate) fn incoming_connection_error(
+    send_back_addr: &Multiaddr,
+    listen_addr: &Multiaddr,
+    error: impl fmt::Display
+) -> Record {
+    Record::new("incoming_connection_error")
+        .incoming(send_back_addr, listen_addr)
+        .string("cause", error)
+}
+
+/// Builds the record of an event generated by the `NetworkBehaviour`, described with its `Debug`
+/// implementation.
+pub(crate) fn behaviour_event(event: &impl fmt::Debug) -> Record {
+    Record::new("behaviour").string("description", format!("{:?}", event))
+}
+
+/// A JSON object being built, without its closing brace.
+pub(crate) struct Record {
+    line: String,
+}
+
+impl Record {
+    fn new(event: &str) -> Self {
+        let timestamp = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_millis())
+            .unwrap_or(0);
+        Record { line: format!("{{\"timestamp\":{},\"event\":\"{}\"", timestamp, event) }
+    }
+
+    /// Returns the record as a JSON object, on a single line.
+    pub(crate) fn to_json(&self) -> String {
+        let mut json = self.line.clone();
+        json.push('}');
+        json
+    }
+
+    fn number(mut self, name: &str, value: impl fmt::Display) -> Self {
+        let _ = write!(self.line, ",\"{}\":{}", name, value);
+        self
+    }
+
+    fn string(mut self, name: &str, value: impl fmt::Display) -> Self {
+        let _ = write!(self.line, ",\"{}\":\"", name);
+        for c in value.to_string().chars() {
+            match c {
+                '"' => self.line.push_str("\\\""),
+                '\\' => self.line.push_str("\\\\"),
+                '\n' => self.line.push_str("\\n"),
+                '\r' => self.line.push_str("\\r"),
+                '\t' => self.line.push_str("\\t"),
+                c if (c as u32) < 0x20 => { let _ = write!(self.line, "\\u{:04x}", c as u32); },
+                c => self.line.push(c),
+            }
+        }
+        self.line.push('"');
+        self
+    }
+
+    fn endpoint(self, endpoint: &ConnectedPoint) -> Self {
+        match endpoint {
+            ConnectedPoint::Dialer { address } => {
+                self.string("address", address).string("direction", "outbound")
+            },
+            ConnectedPoint::Listener { listen_addr, send_back_addr } => {
+                self.incoming(send_back_addr, listen_addr)
+            },
+        }
+    }
+
+    fn incoming(self, send_back_addr: &Multiaddr, listen_addr: &Multiaddr) -> Self {
+        self.string("address", send_back_addr)
+            .string("local_address", listen_addr)
+            .string("direction", "inbound")
+    }
+}
+
+fn closed_cause<THandlerErr: fmt::Display>(cause: &ConnectionClosedCause<THandlerErr>) -> String {
+    match cause {
+        ConnectionClosedCause::Replaced => "replaced".to_owned(),
+        ConnectionClosedCause::KeepAliveTimeout => "keep_alive_timeout".to_owned(),
+        ConnectionClosedCause::Disconnected => "disconnected".to_owned(),
+        ConnectionClosedCause::Banned => "banned".to_owned(),
+        cause => cause.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use libp2p_core::PeerId;
+    use std::sync::{Arc, Mutex};
+
+    #[derive(Clone, Default)]
+    struct Buffer(Arc<Mutex<Vec<u8>>>);
+
+    impl io::Write for Buffer {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.0.lock().unwrap().extend_from_slice(buf);
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn writes_json_lines() {
+        let buffer = Buffer::default();
+        let mut log = EventLog::new(Box::new(buffer.clone()));
+        let peer_id = PeerId::random();
+        let address: Multiaddr = "/ip4/1.2.3.4/tcp/4001".parse().unwrap();
+
+        let record = swarm_event(&SwarmEvent::<(), (), io::Error>::UnreachableAddr {
+            peer_id: Some(peer_id.clone()),
+            address: address.clone(),
+            cause: crate::DialFailureCause::Transport,
+            negotiation: None,
+        });
+        log.write(&record.unwrap());
+        let listen_addr: Multiaddr = "/ip4/0.0.0.0/tcp/4001".parse().unwrap();
+        log.write(&incoming_connection_error(&address, &listen_addr, "bad \"handshake\"\n"));
+
+        let output = String::from_utf8(buffer.0.lock().unwrap().clone()).unwrap();
+        let lines = output.lines().collect::<Vec<_>>();
+        assert_eq!(lines.len(), 2);
+        assert!(lines[0].starts_with("{\"timestamp\":"));
+        assert!(lines[0].ends_with(&format!(
+            ",\"event\":\"unreachable_addr\",\"peer\":\"{}\",\"address\":\"/ip4/1.2.3.4/tcp/4001\",\"cause\":\"transport\"}}",
+            peer_id.to_base58()
+        )));
+        assert!(lines[1].ends_with(
+            ",\"event\":\"incoming_connection_error\",\"address\":\"/ip4/1.2.3.4/tcp/4001\",\
+             \"local_address\":\"/ip4/0.0.0.0/tcp/4001\",\"direction\":\"inbound\",\
+             \"cause\":\"bad \\\"handshake\\\"\\n\"}"
+        ));
+    }
+}
@@ -0,0 +1,214 @@
+// Copyright 2019 Parity Technologies (UK) Ltd.
+//
+// Permission is hereby granted, free of charge, to any person obtaining a
+// copy of this software and associated documentation files (the "Software"),
+// to deal in the Software without restriction, including without limitation
+// the rights to use, copy, modify, merge, publish, distribute, sublicense,
+// and/or sell copies of the Software, and to permit persons to whom the
+// Software is furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS
+// OR IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING
+// FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
+// DEALINGS IN THE SOFTWARE.
+
+use libp2p_core::{ConnectedPoint, Multiaddr, PeerId, multiaddr::Protocol, nodes::network::IncomingInfo};
+use std::{collections::HashSet, sync::{Arc, Mutex}};
+
+/// Decides which connections the `Swarm` is allowed to open or to accept.
+///
+/// The methods of this trait are called at the various stages of the lifetime of a connection,
+/// which makes it possible to refuse a connection as early as possible. For example, refusing an
+/// incoming connection in [`ConnectionGater::allow_incoming`] happens before the encryption
+/// handshake, while [`ConnectionGater::allow_secured`] is the earliest point where the identity
+/// of the remote is known.
+///
+/// The `Swarm` only sees the connections before and after their upgrades, and calls all the
+/// methods but [`ConnectionGater::allow_secured`], which the transport has to call itself
+/// between the security and the multiplexing upgrades. The transport and the `Swarm` then need
+/// gaters that share their state, like the clones of an [`AllowListGater`].
+///
+/// In all cases, the `NetworkBehaviour` is never informed about a connection that has been
+/// refused.
+///
+/// All methods return `true` by default.
+pub trait ConnectionGater {
+    /// Called before dialing a peer whose `PeerId` is known.
+    ///
+    /// Returning `false` aborts the attempt, and `inject_dial_failure` is called on the
+    /// `NetworkBehaviour`.
+    fn allow_dial_peer(&mut self, _peer_id: &PeerId) -> bool {
+        true
+    }
+
+    /// Called before dialing an address, whether the `PeerId` of the remote is known or not.
+    ///
+    /// When dialing a peer, the addresses for which this method returns `false` are skipped.
+    fn allow_dial_addr(&mut self, _addr: &Multiaddr) -> bool {
+        true
+    }
+
+    /// Called when a listener produces a new incoming connection, before any upgrade is applied
+    /// to it.
+    ///
+    /// Returning `false` drops the connection immediately.
+    fn allow_incoming(&mut self, _info: &IncomingInfo<'_>) -> bool {
+        true
+    }
+
+    /// Called by the transport once the security handshake of a connection has completed and the
+    /// identity of the remote is known, before the multiplexing protocol is negotiated.
+    ///
+    /// The transport is expected to abort the upgrade of the connection if `false` is returned,
+    /// e.g. by returning an error from the closure passed to `Transport::and_then` after the
+    /// security upgrade. The `Swarm` never calls this method.
+    fn allow_secured(&mut self, _peer_id: &PeerId, _endpoint: &ConnectedPoint) -> bool {
+        true
+    }
+
+    /// Called once a connection has been fully upgraded, and before the `NetworkBehaviour` is
+    /// notified.
+    ///
+    /// By then, both the security handshake and the negotiation of the multiplexing protocol
+    /// have completed, so refusing a connection here costs a few more round trips than refusing
+    /// it in [`ConnectionGater::allow_secured`]. Gaters that refuse peers based on their
+    /// identity should still implement this method, as it is the only check of the identity
+    /// when the transport doesn't call [`ConnectionGater::allow_secured`].
+    ///
+    /// Returning `false` closes the connection.
+    fn allow_established(&mut self, _peer_id: &PeerId, _endpoint: &ConnectedPoint) -> bool {
+        true
+    }
+}
+
+/// Implementation of `ConnectionGater` that allows every connection.
+#[derive(Debug, Copy, Clone, Default)]
+pub struct DummyConnectionGater;
+
+impl ConnectionGater for DummyConnectionGater {}
+
+/// Implementation of `ConnectionGater` that only allows connections with an explicit set of
+/// peers.
+///
+/// All the clones of an `AllowListGater` share the same set of allowed peers, which makes it
+/// possible to allow or disallow peers after the gater has been passed to the `Swarm`.
+///
+/// The identity of the remote of an incoming connection is only known once the encryption
+/// handshake has completed. If the transport calls [`ConnectionGater::allow_secured`] on a
+/// clone of the gater, connections with peers that aren't allowed are aborted right after this
+/// handshake, before the multiplexing protocol is negotiated. Otherwise, they are closed by the
+/// `Swarm` once fully upgraded. In both cases, the `NetworkBehaviour` is never informed. Dialing
+/// a peer that isn't allowed, or an address whose `/p2p` component designates such a peer, is
+/// refused.
+///
+/// > **Note**: Disallowing a peer doesn't close the existing connections to it. Use
+/// >           `Swarm::disconnect_peer_id` for that.
+#[derive(Debug, Clone, Default)]
+pub struct AllowListGater {
+    allowed: Arc<Mutex<HashSet<PeerId>>>,
+}
+
+impl AllowListGater {
+    /// Creates a new `AllowListGater` that doesn't allow any peer.
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    /// Allows the given peer. Returns `false` if the peer was already allowed.
+    pub fn allow(&self, peer_id: PeerId) -> bool {
+        self.allowed().insert(peer_id)
+    }
+
+    /// Disallows the given peer. Returns `false` if the peer wasn't allowed.
+    pub fn disallow(&self, peer_id: &PeerId) -> bool {
+        self.allowed().remove(peer_id)
+    }
+
+    /// Returns true if the given peer is allowed.
+    pub fn is_allowed(&self, peer_id: &PeerId) -> bool {
+        self.allowed().contains(peer_id)
+    }
+
+    /// Returns the list of allowed peers.
+    pub fn allowed_peers(&self) -> Vec<PeerId> {
+        self.allowed().iter().cloned().collect()
+    }
+
+    fn allowed(&self) -> std::sync::MutexGuard<'_, HashSet<PeerId>> {
+        // The set is always left in a consistent state, even if a thread panicked while holding
+        // the lock.
+        self.allowed.lock().unwrap_or_else(|err| err.into_inner())
+    }
+}
+
+impl std::iter::FromIterator<PeerId> for AllowListGater {
+    fn from_iter<I: IntoIterator<Item = PeerId>>(iter: I) -> Self {
+        AllowListGater {
+            allowed: Arc::new(Mutex::new(iter.into_iter().collect())),
+        }
+    }
+}
+
+impl ConnectionGater for AllowListGater {
+    fn allow_dial_peer(&mut self, peer_id: &PeerId) -> bool {
+        self.is_allowed(peer_id)
+    }
+
+    fn allow_dial_addr(&mut self, addr: &Multiaddr) -> bool {
+        match addr.iter().last() {
+            Some(Protocol::P2p(hash)) => match PeerId::from_multihash(hash) {
+                Ok(peer_id) => self.is_allowed(&peer_id),
+                Err(_) => false,
+            },
+            _ => true,
+        }
+    }
+
+    fn allow_secured(&mut self, peer_id: &PeerId, _: &ConnectedPoint) -> bool {
+        self.is_allowed(peer_id)
+    }
+
+    fn allow_established(&mut self, peer_id: &PeerId, _: &ConnectedPoint) -> bool {
+        self.is_allowed(peer_id)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn allow_list_is_shared_between_clones() {
+        let gater = AllowListGater::new();
+        let mut in_swarm = gater.clone();
+        let peer_id = PeerId::random();
+        let endpoint = ConnectedPoint::Dialer { address: "/memory/1234".parse().unwrap() };
+
+        assert!(!in_swarm.allow_dial_peer(&peer_id));
+        assert!(gater.allow(peer_id.clone()));
+        assert!(in_swarm.allow_dial_peer(&peer_id));
+        assert!(in_swarm.allow_established(&peer_id, &endpoint));
+        assert!(gater.clone().allow_secured(&peer_id, &endpoint));
+        assert!(gater.disallow(&peer_id));
+        assert!(!in_swarm.allow_established(&peer_id, &endpoint));
+        assert!(!gater.clone().allow_secured(&peer_id, &endpoint));
+    }
+
+    #[test]
+    fn dialing_addresses_of_unknown_peers_is_refused() {
+        let allowed = PeerId::random();
+        let unknown = PeerId::random();
+        let mut gater = std::iter::once(allowed.clone()).collect::<AllowListGater>();
+
+        let addr: Multiaddr = "/ip4/127.0.0.1/tcp/1234".parse().unwrap();
+        assert!(gater.allow_dial_addr(&addr));
+        assert!(gater.allow_dial_addr(&addr.clone().with(Protocol::P2p(allowed.into()))));
+        assert!(!gater.allow_dial_addr(&addr.with(Protocol::P2p(unknown.into()))));
+    }
+}
@@ -0,0 +1,86 @@
+// Copyright 2019 Parity Technologies (UK) Ltd.
+//
+// Permission is hereby granted, free of charge, to any person obtaining a
+// copy of this software and associated documentation files (the "Software"),
+// to deal in the Software without restriction, including without limitation
+// the rights to use, copy, modify, merge, publish, distribute, sublicense,
+// and/or sell copies of the Software, and to permit persons to whom the
+// Software is furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS
+// OR IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING
+// FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
+// DEALINGS IN THE SOFTWARE.
+//! Acting on a `Swarm` from tasks other than the one polling it.
+
+use futures::sync::{mpsc, oneshot};
+use std::fmt;
+
+/// A function sent through a `SwarmHandle`, to run on the swarm.
+pub(crate) type Command<TSwarm> = Box<dyn FnOnce(&mut TSwarm) + Send>;
+
+/// Cloneable handle to a swarm, obtained with `Swarm::handle`.
+///
+/// A single task owns and polls the swarm, while any number of other tasks hold handles and
+/// send it functions to run. The functions run on the task polling the swarm, in the order in
+/// which they have been sent, the next time the swarm is polled. They have access to the whole
+/// swarm and, through it, to the behaviour, for example:
+///
+/// - `handle.call(move |swarm| Swarm::dial_addr(swarm, addr))` to dial an address;
+/// - `handle.call(move |swarm| swarm.floodsub.publish(&topic, data))` to publish a message;
+/// - `handle.call(|swarm| Swarm::listeners(swarm).cloned().collect::<Vec<_>>())` to query the
+///   state of the swarm.
+///
+/// Substreams are opened by the behaviour; `libp2p-stream` provides a cloneable `Control` to
+/// open raw streams from any task.
+pub struct SwarmHandle<TSwarm> {
+    commands: mpsc::UnboundedSender<Command<TSwarm>>,
+}
+
+impl<TSwarm> SwarmHandle<TSwarm> {
+    pub(crate) fn new(commands: mpsc::UnboundedSender<Command<TSwarm>>) -> Self {
+        SwarmHandle { commands }
+    }
+
+    /// Runs `f` on the swarm, and returns a future resolving to its result.
+    ///
+    /// The future fails with `Canceled` if the swarm is dropped before `f` has run.
+    pub fn call<F, R>(&self, f: F) -> oneshot::Receiver<R>
+    where
+        F: FnOnce(&mut TSwarm) -> R + Send + 'static,
+        R: Send + 'static,
+    {
+        let (tx, rx) = oneshot::channel();
+        let command: Command<TSwarm> = Box::new(move |swarm: &mut TSwarm| {
+            let _ = tx.send(f(swarm));
+        });
+        // If the swarm is gone, `tx` is dropped with the command and `rx` is canceled.
+        let _ = self.commands.unbounded_send(command);
+        rx
+    }
+
+    /// Returns true if the swarm has been dropped.
+    pub fn is_closed(&self) -> bool {
+        self.commands.is_closed()
+    }
+}
+
+impl<TSwarm> Clone for SwarmHandle<TSwarm> {
+    fn clone(&self) -> Self {
+        SwarmHandle { commands: self.commands.clone() }
+    }
+}
+
+impl<TSwarm> fmt::Debug for SwarmHandle<TSwarm> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("SwarmHandle")
+            .field("closed", &self.is_closed())
+            .finish()
+    }
+}
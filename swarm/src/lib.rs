@@ -52,10 +52,32 @@
 //! remote should behave: how to handle incoming substreams, which protocols
 //! are supported, when to open a new outbound substream, etc.
 //!
+//! # `async`/`await`
+//!
+//! The `Swarm` is built on version 0.1 of `futures`: it is a `Stream` of the
+//! events of the behaviour, and [`Swarm::next_event`] is a future resolving
+//! to its next event of any kind. Code written with `std::future` and
+//! `async`/`await` can drive them through the compatibility layer of
+//! `futures` 0.3, with `Compat01As03`, e.g.
+//! `Swarm::next_event(&mut swarm).compat().await`, on an executor that also
+//! provides a tokio 0.1 reactor for the transports.
+//!
 
+mod backoff;
 mod behaviour;
+mod event_history;
+mod event_log;
+mod gater;
+mod handle;
+mod peer_stats;
+mod peer_store;
+mod ranking;
+mod rate_limit;
 mod registry;
+mod snapshot;
+mod state;
 
+pub mod dynamic;
 pub mod protocols_handler;
 pub mod toggle;
 
@@ -63,9 +85,25 @@ pub use behaviour::{
     NetworkBehaviour,
     NetworkBehaviourAction,
     NetworkBehaviourEventProcess,
-    PollParameters
+    NetworkChange,
+    PollParameters,
+    node_event_connection
 };
+pub use backoff::DialBackoffConfig;
+pub use event_history::EventHistory;
+pub use gater::{AllowListGater, ConnectionGater, DummyConnectionGater};
+pub use handle::SwarmHandle;
+pub use peer_stats::PeerStats;
+pub use peer_store::{CONNECTED_ADDRESS_TTL, MAX_ADDRESS_TTL, PeerStore, PeerStoreSnapshot};
+pub use rate_limit::{InboundRateLimitConfig, RateLimitReason};
+pub use registry::{AddressSource, RESTORED_ADDRESS_TTL};
+pub use snapshot::{BehaviourSummary, SwarmSnapshot};
+pub use state::{STATE_FORMAT_VERSION, SwarmState};
+pub use libp2p_core::nodes::{ConnectionId, EstablishedConnection};
+pub use libp2p_core::nodes::tasks::{ConnectionTask, TaskExecutor};
 pub use protocols_handler::{
+    IdleTimeout,
+    IdleTimeoutError,
     IntoProtocolsHandler,
     IntoProtocolsHandlerSelect,
     KeepAlive,
@@ -74,26 +112,35 @@ pub use protocols_handler::{
     ProtocolsHandlerSelect,
     ProtocolsHandlerUpgrErr,
     OneShotHandler,
-    SubstreamProtocol
+    SubstreamProtocol,
+    SubstreamTimedOut
 };
 
 use protocols_handler::{NodeHandlerWrapperBuilder, NodeHandlerWrapper, NodeHandlerWrapperError};
-use futures::prelude::*;
+use futures::{prelude::*, future::{self, Executor}, sync::mpsc};
 use libp2p_core::{
-    Transport, Multiaddr, PeerId, InboundUpgrade, OutboundUpgrade, UpgradeInfo, ProtocolName,
+    BoxedRng, ConnectedPoint, Transport, Multiaddr, PeerId, InboundUpgrade, OutboundUpgrade, UpgradeInfo, ProtocolName,
     muxing::StreamMuxer,
     nodes::{
         collection::ConnectionInfo,
-        handled_node::NodeHandler,
+        handled_node::{HandledNodeError, NodeHandler},
         node::Substream,
-        network::{self, Network, NetworkEvent}
+        network::{self, ConnectionLimits, Network, NetworkEvent}
     },
-    transport::TransportError
+    transport::TransportError,
+    upgrade::{self, NegotiationFailure}
 };
-use registry::{Addresses, AddressIntoIter};
+use backoff::DialBackoff;
+use rate_limit::InboundRateLimiter;
+use event_log::{EventLog, Record};
+use peer_stats::PeerStatsStore;
+use ranking::AddressRanking;
+use registry::ExternalAddresses;
 use smallvec::SmallVec;
-use std::{error, fmt, io, ops::{Deref, DerefMut}};
-use std::collections::HashSet;
+use snapshot::Subscriptions;
+use std::{error, fmt, io, num::{NonZeroU8, NonZeroU32, NonZeroUsize}, ops::{Deref, DerefMut}, time::Duration};
+use std::collections::{BTreeMap, HashMap, VecDeque};
+use wasm_timer::{Delay, Instant};
 
 /// Contains the state of the network, plus the way it should behave.
 pub type Swarm<TTransport, TBehaviour, TConnInfo = PeerId> = ExpandedSwarm<
@@ -110,6 +157,7 @@ pub type Swarm<TTransport, TBehaviour, TConnInfo = PeerId> = ExpandedSwarm<
 pub struct ExpandedSwarm<TTransport, TBehaviour, TInEvent, TOutEvent, THandler, THandlerErr, TConnInfo = PeerId>
 where
     TTransport: Transport,
+    TBehaviour: NetworkBehaviour,
 {
     network: Network<
         TTransport,
@@ -133,26 +181,225 @@ where
 
     /// List of multiaddresses we're listening on, after account for external IP addresses and
     /// similar mechanisms.
-    external_addrs: Addresses,
+    external_addrs: ExternalAddresses,
 
     /// List of nodes for which we deny any incoming connection.
-    banned_peers: HashSet<PeerId>,
+    banned_peers: BannedPeers,
+
+    /// Decides which connections we are allowed to open or accept.
+    gater: Box<dyn ConnectionGater + Send>,
+
+    /// Peers that we recently failed to dial, if dial backoff is enabled.
+    dial_backoff: Option<DialBackoff>,
+
+    /// Recent incoming connections of each IP address, if inbound rate limiting is enabled.
+    inbound_rate_limit: Option<InboundRateLimiter>,
+
+    /// How long connections are kept alive after all handlers have stopped voting for them.
+    idle_timeout: Duration,
+
+    /// Addresses, protocols and metadata known about other peers.
+    peer_store: PeerStore,
+
+    /// Latency and success history of the addresses we dialed, used to order dialing attempts.
+    address_ranking: AddressRanking,
+
+    /// Latency and throughput statistics of the peers we are connected to.
+    peer_stats: PeerStatsStore,
+
+    /// Where connection events are recorded, if enabled with `SwarmBuilder::event_log`.
+    event_log: Option<EventLog>,
+
+    /// The most recent events, if enabled with `SwarmBuilder::event_history`, and how to record
+    /// the events of the behaviour.
+    event_history: Option<(EventHistory, fn(&TBehaviour::OutEvent) -> Record)>,
+
+    /// Subscribers to the snapshots of the state of the swarm.
+    snapshots: Subscriptions,
+
+    /// Whether `Swarm::shutdown` has been called.
+    shutting_down: bool,
+
+    /// Functions sent through the `SwarmHandle`s, to run on the swarm.
+    commands: mpsc::UnboundedReceiver<handle::Command<ExpandedSwarm<TTransport, TBehaviour, TInEvent, TOutEvent, THandler, THandlerErr, TConnInfo>>>,
 
-    /// Pending event message to be delivered.
+    /// Sender cloned into the `SwarmHandle`s.
+    commands_sender: mpsc::UnboundedSender<handle::Command<ExpandedSwarm<TTransport, TBehaviour, TInEvent, TOutEvent, THandler, THandlerErr, TConnInfo>>>,
+
+    /// Pending event message to be delivered, and the specific connection it is destined to, if
+    /// any.
     ///
-    /// If the pair's second element is `AsyncSink::NotReady`, the event
+    /// If the tuple's last element is `AsyncSink::NotReady`, the event
     /// message has yet to be sent using `PeerMut::start_send_event`.
     ///
-    /// If the pair's second element is `AsyncSink::Ready`, the event
+    /// If the tuple's last element is `AsyncSink::Ready`, the event
     /// message has been sent and needs to be flushed using
     /// `PeerMut::complete_send_event`.
-    send_event_to_complete: Option<(PeerId, AsyncSink<TInEvent>)>
+    send_event_to_complete: Option<(PeerId, Option<ConnectionId>, AsyncSink<TInEvent>)>,
+
+    /// Events that have been generated but not returned by `poll_event` yet.
+    pending_events: VecDeque<SwarmEvent<TBehaviour::OutEvent, TConnInfo, THandlerErr>>,
+}
+
+/// Event generated by the `Swarm`.
+#[derive(Debug)]
+pub enum SwarmEvent<TBvEv, TConnInfo, THandlerErr> {
+    /// Event generated by the `NetworkBehaviour`.
+    Behaviour(TBvEv),
+
+    /// A connection to a peer has been established.
+    ConnectionEstablished {
+        /// Identity of the peer we are connected to.
+        peer_id: PeerId,
+        /// Identifier of the new connection.
+        connection: ConnectionId,
+        /// Information about the connection, as produced by the transport. Transports that keep
+        /// track of the security and multiplexing protocols that have been negotiated expose
+        /// them here.
+        info: TConnInfo,
+        /// Endpoint of the connection.
+        endpoint: ConnectedPoint,
+        /// Number of established connections to this peer, including this one.
+        num_established: NonZeroU32,
+        /// For connections we dialed, how long it took to reach the address and negotiate the
+        /// connection. `None` for incoming connections.
+        established_in: Option<Duration>,
+    },
+
+    /// Failed to reach an address that we were dialing.
+    UnreachableAddr {
+        /// Identity of the peer we were dialing, if known.
+        peer_id: Option<PeerId>,
+        /// The address we failed to reach.
+        address: Multiaddr,
+        /// Why the address couldn't be reached.
+        cause: DialFailureCause,
+        /// If the cause is `DialFailureCause::Negotiation`, the protocols involved in the failed
+        /// negotiation.
+        negotiation: Option<NegotiationFailure>,
+    },
+
+    /// An incoming connection failed to be negotiated.
+    IncomingConnectionError {
+        /// The address of the listener which received the connection.
+        local_addr: Multiaddr,
+        /// The address of the remote.
+        send_back_addr: Multiaddr,
+        /// If the connection failed because the remote doesn't support any of the protocols we
+        /// support for one of its upgrades, the protocols involved in the failed negotiation.
+        negotiation: Option<NegotiationFailure>,
+    },
+
+    /// An incoming connection has been refused because its IP address exceeded one of the
+    /// limits of the `InboundRateLimitConfig`.
+    IncomingConnectionRateLimited {
+        /// The address of the listener which received the connection.
+        local_addr: Multiaddr,
+        /// The address of the remote.
+        send_back_addr: Multiaddr,
+        /// Which limit has been exceeded.
+        reason: RateLimitReason,
+    },
+
+    /// A connection to a peer has been closed.
+    ConnectionClosed {
+        /// Identity of the peer we were connected to.
+        peer_id: PeerId,
+        /// Identifier of the closed connection.
+        connection: ConnectionId,
+        /// Endpoint of the connection.
+        endpoint: ConnectedPoint,
+        /// Number of other connections to this peer that are still established.
+        num_established: u32,
+        /// Why the connection has been closed.
+        cause: ConnectionClosedCause<THandlerErr>,
+    },
+}
+
+/// Reason why dialing an address failed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum DialFailureCause {
+    /// The transport doesn't support the address.
+    MultiaddrNotSupported,
+    /// The transport failed to reach the address or to negotiate the connection.
+    Transport,
+    /// The remote doesn't support any of the protocols we offered for one of the upgrades of the
+    /// connection, e.g. the encryption or the multiplexing protocol.
+    Negotiation,
+    /// The remote has a different identity than the one we were dialing.
+    PeerIdMismatch,
+    /// The remote has the same identity as the local node.
+    LocalPeerId,
+    /// A connection limit has been reached.
+    ConnectionLimit,
+}
+
+impl DialFailureCause {
+    /// Returns a short, stable name of the cause, e.g. for labelling metrics.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            DialFailureCause::MultiaddrNotSupported => "multiaddr_not_supported",
+            DialFailureCause::Transport => "transport",
+            DialFailureCause::Negotiation => "negotiation",
+            DialFailureCause::PeerIdMismatch => "peer_id_mismatch",
+            DialFailureCause::LocalPeerId => "local_peer_id",
+            DialFailureCause::ConnectionLimit => "connection_limit",
+        }
+    }
+}
+
+impl fmt::Display for DialFailureCause {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            DialFailureCause::MultiaddrNotSupported => write!(f, "Address not supported"),
+            DialFailureCause::Transport => write!(f, "Transport error"),
+            DialFailureCause::Negotiation => write!(f, "No protocol in common with the remote"),
+            DialFailureCause::PeerIdMismatch => write!(f, "Unexpected peer ID"),
+            DialFailureCause::LocalPeerId => write!(f, "Dialed the local peer"),
+            DialFailureCause::ConnectionLimit => write!(f, "Connection limit reached"),
+        }
+    }
+}
+
+/// Reason why a connection has been closed.
+#[derive(Debug)]
+pub enum ConnectionClosedCause<THandlerErr> {
+    /// The connection has been replaced with a new connection to the same peer.
+    Replaced,
+    /// None of the handlers wanted to keep the connection alive anymore.
+    KeepAliveTimeout,
+    /// An I/O error happened on the connection.
+    Io(io::Error),
+    /// The handler of the connection produced an error.
+    Handler(THandlerErr),
+    /// The connection has been gracefully closed with `Swarm::disconnect_peer_id`.
+    Disconnected,
+    /// The connection has been gracefully closed because the peer has been banned with
+    /// `Swarm::ban_peer_id`.
+    Banned,
+}
+
+impl<THandlerErr> fmt::Display for ConnectionClosedCause<THandlerErr>
+where
+    THandlerErr: fmt::Display,
+{
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ConnectionClosedCause::Replaced => write!(f, "Connection replaced"),
+            ConnectionClosedCause::KeepAliveTimeout => write!(f, "Keep-alive timeout"),
+            ConnectionClosedCause::Io(err) => write!(f, "I/O error: {}", err),
+            ConnectionClosedCause::Handler(err) => write!(f, "Handler error: {}", err),
+            ConnectionClosedCause::Disconnected => write!(f, "Disconnected on request"),
+            ConnectionClosedCause::Banned => write!(f, "Peer banned"),
+        }
+    }
 }
 
 impl<TTransport, TBehaviour, TInEvent, TOutEvent, THandler, THandlerErr, TConnInfo> Deref for
     ExpandedSwarm<TTransport, TBehaviour, TInEvent, TOutEvent, THandler, THandlerErr, TConnInfo>
 where
     TTransport: Transport,
+    TBehaviour: NetworkBehaviour,
 {
     type Target = TBehaviour;
 
@@ -165,6 +412,7 @@ impl<TTransport, TBehaviour, TInEvent, TOutEvent, THandler, THandlerErr, TConnIn
     ExpandedSwarm<TTransport, TBehaviour, TInEvent, TOutEvent, THandler, THandlerErr, TConnInfo>
 where
     TTransport: Transport,
+    TBehaviour: NetworkBehaviour,
 {
     fn deref_mut(&mut self) -> &mut Self::Target {
         &mut self.behaviour
@@ -224,10 +472,18 @@ where TBehaviour: NetworkBehaviour<ProtocolsHandler = THandler>,
 
     /// Tries to dial the given address.
     ///
-    /// Returns an error if the address is not supported.
-    pub fn dial_addr(me: &mut Self, addr: Multiaddr) -> Result<(), TransportError<TTransport::Error>> {
+    /// Returns an error if the address is not supported or if the `ConnectionGater` denies it.
+    pub fn dial_addr(me: &mut Self, addr: Multiaddr) -> Result<(), DialError<TTransport::Error>> {
+        if !me.gater.allow_dial_addr(&addr) {
+            return Err(DialError::Denied)
+        }
+        if me.shutting_down {
+            return Err(DialError::ShuttingDown)
+        }
         let handler = me.behaviour.new_handler();
-        me.network.dial(addr, handler.into_node_handler_builder())
+        let handler = handler.into_node_handler_builder().with_idle_timeout(me.idle_timeout);
+        me.network.dial(addr, handler)
+            .map_err(DialError::Transport)
     }
 
     /// Tries to reach the given peer using the elements in the topology.
@@ -235,11 +491,31 @@ where TBehaviour: NetworkBehaviour<ProtocolsHandler = THandler>,
     /// Has no effect if we are already connected to that peer, or if no address is known for the
     /// peer.
     pub fn dial(me: &mut Self, peer_id: PeerId) {
-        let addrs = me.behaviour.addresses_of_peer(&peer_id);
+        if me.shutting_down || me.banned_peers.contains(&peer_id) ||
+            Self::is_dial_backed_off(me, &peer_id) || !me.gater.allow_dial_peer(&peer_id)
+        {
+            me.behaviour.inject_dial_failure(&peer_id);
+            return
+        }
+        let mut addrs = me.behaviour.addresses_of_peer(&peer_id);
+        for addr in me.peer_store.addresses(&peer_id) {
+            if !addrs.contains(addr) {
+                addrs.push(addr.clone());
+            }
+        }
+        let gater = &mut me.gater;
+        let mut addrs = addrs.into_iter()
+            .filter(|addr| gater.allow_dial_addr(addr))
+            .collect::<Vec<_>>();
+        me.address_ranking.sort(&mut addrs);
         match me.network.peer(peer_id.clone()) {
             network::Peer::NotConnected(peer) => {
-                let handler = me.behaviour.new_handler().into_node_handler_builder();
-                if peer.connect_iter(addrs, handler).is_err() {
+                let behaviour = &mut me.behaviour;
+                let idle_timeout = me.idle_timeout;
+                let new_handler = || behaviour.new_handler()
+                    .into_node_handler_builder()
+                    .with_idle_timeout(idle_timeout);
+                if peer.connect_iter_concurrent(addrs, new_handler).is_err() {
                     me.behaviour.inject_dial_failure(&peer_id);
                 }
             },
@@ -248,6 +524,87 @@ where TBehaviour: NetworkBehaviour<ProtocolsHandler = THandler>,
             },
             network::Peer::Connected(_) | network::Peer::LocalNode => {}
         }
+        Self::record_dial_starts(me, &peer_id);
+    }
+
+    /// Informs the inbound rate limiter, if any, that an incoming connection is no longer being
+    /// negotiated.
+    fn incoming_finished(me: &mut Self, endpoint: &ConnectedPoint) {
+        if let (Some(limiter), ConnectedPoint::Listener { send_back_addr, .. }) =
+            (me.inbound_rate_limit.as_mut(), endpoint)
+        {
+            limiter.pending_finished(send_back_addr);
+        }
+    }
+
+    /// Notifies the `NetworkBehaviour` that a connection has been closed and queues the
+    /// corresponding `SwarmEvent`.
+    fn connection_closed(
+        me: &mut Self,
+        peer_id: PeerId,
+        connection: ConnectionId,
+        endpoint: ConnectedPoint,
+        cause: ConnectionClosedCause<THandlerErr>,
+    ) {
+        me.behaviour.inject_connection_closed(&peer_id, &connection, &endpoint);
+        let num_established = me.network.peer(peer_id.clone())
+            .into_connected()
+            .map_or(0, |p| p.connection_ids().count() as u32);
+        if num_established == 0 {
+            me.peer_stats.remove(&peer_id);
+            me.behaviour.inject_disconnected(&peer_id, endpoint.clone());
+        }
+        me.pending_events.push_back(SwarmEvent::ConnectionClosed {
+            peer_id,
+            connection,
+            endpoint,
+            num_established,
+            cause,
+        });
+    }
+
+    /// Writes the record built by `record` to the event log and the event history, if at least
+    /// one of them is enabled.
+    fn record_event(me: &mut Self, record: impl FnOnce() -> Option<Record>) {
+        if me.event_log.is_none() && me.event_history.is_none() {
+            return
+        }
+        if let Some(record) = record() {
+            if let Some(log) = me.event_log.as_mut() {
+                log.write(&record);
+            }
+            if let Some((history, _)) = me.event_history.as_ref() {
+                history.record(&record);
+            }
+        }
+    }
+
+    /// Informs the `AddressRanking` about the addresses currently being dialed for the given
+    /// peer.
+    fn record_dial_starts(me: &mut Self, peer_id: &PeerId) {
+        if let Some(peer) = me.network.peer(peer_id.clone()).into_pending_connect() {
+            for addr in peer.attempted_multiaddrs() {
+                me.address_ranking.dial_started(peer_id, addr);
+            }
+        }
+    }
+
+    /// Returns the protocols that the given peer is known to support, as reported with
+    /// `NetworkBehaviourAction::ReportRemoteProtocols`.
+    ///
+    /// The list is empty if the peer hasn't reported its protocols yet.
+    pub fn supported_protocols<'a>(me: &'a Self, peer_id: &PeerId) -> impl Iterator<Item = &'a str> + 'a {
+        me.peer_store.protocols(peer_id)
+    }
+
+    /// Returns the `PeerStore` of the swarm.
+    pub fn peer_store(me: &Self) -> &PeerStore {
+        &me.peer_store
+    }
+
+    /// Returns the `PeerStore` of the swarm, for example in order to add addresses.
+    pub fn peer_store_mut(me: &mut Self) -> &mut PeerStore {
+        &mut me.peer_store
     }
 
     /// Returns an iterator that produces the list of addresses we're listening on.
@@ -257,21 +614,98 @@ where TBehaviour: NetworkBehaviour<ProtocolsHandler = THandler>,
 
     /// Returns an iterator that produces the list of addresses that other nodes can use to reach
     /// us.
+    ///
+    /// Only the addresses that are confirmed are returned. See [`AddressSource`].
     pub fn external_addresses(me: &Self) -> impl Iterator<Item = &Multiaddr> {
         me.external_addrs.iter()
     }
 
+    /// Returns the state of the swarm worth persisting across restarts, with an empty routing
+    /// table. See [`SwarmState`].
+    pub fn state(me: &Self) -> SwarmState {
+        SwarmState {
+            peer_store: me.peer_store.clone(),
+            external_addresses: me.external_addrs.iter().cloned().collect(),
+            routing_table: Vec::new(),
+        }
+    }
+
+    /// Restores a state previously obtained with [`Swarm::state`], typically on startup.
+    ///
+    /// The peer store is merged into the one of the swarm, along with the addresses of the
+    /// routing table. The external addresses are reported with [`AddressSource::Restored`], so
+    /// that they are only advertised until they expire or the network changes, unless remotes
+    /// confirm them again in the meantime. Then up to `max_dials` of the known peers are dialed,
+    /// the peers of the routing table first. Seeding the routing table of the behaviour is up
+    /// to the application.
+    pub fn restore_state(me: &mut Self, state: SwarmState, max_dials: usize) {
+        me.peer_store.merge(&state.peer_store);
+        for (peer_id, addrs) in &state.routing_table {
+            for addr in addrs {
+                me.peer_store.add_address(peer_id.clone(), addr.clone(), CONNECTED_ADDRESS_TTL);
+            }
+        }
+        for addr in state.external_addresses {
+            Self::report_external_address(me, addr, AddressSource::Restored);
+        }
+
+        let mut to_dial = Vec::new();
+        let known_peers = state.routing_table.iter().map(|(peer_id, _)| peer_id)
+            .chain(state.peer_store.peers());
+        for peer_id in known_peers {
+            if to_dial.len() >= max_dials {
+                break
+            }
+            if peer_id != me.network.local_peer_id() && !to_dial.contains(peer_id) {
+                to_dial.push(peer_id.clone());
+            }
+        }
+        for peer_id in to_dial {
+            Self::dial(me, peer_id);
+        }
+    }
+
+    /// Returns the candidate external addresses reported by remotes, alongside with the number
+    /// of distinct peers that have reported them, whether they are confirmed or not.
+    pub fn external_address_candidates(me: &Self) -> impl Iterator<Item = (&Multiaddr, usize)> {
+        me.external_addrs.candidates()
+    }
+
     /// Returns the peer ID of the swarm passed as parameter.
     pub fn local_peer_id(me: &Self) -> &PeerId {
         &me.network.local_peer_id()
     }
 
+    /// Returns a snapshot of all the established connections, with their endpoint and age.
+    ///
+    /// > **Note**: The muxer and security protocols negotiated on a connection, as well as its
+    /// >           open substreams, are internal to the transport and the handler and are
+    /// >           therefore not reported.
+    pub fn connections(me: &Self) -> impl Iterator<Item = EstablishedConnection<'_, TConnInfo>> {
+        me.network.connections()
+    }
+
     /// Adds an external address.
     ///
     /// An external address is an address we are listening on but that accounts for things such as
     /// NAT traversal.
     pub fn add_external_address(me: &mut Self, addr: Multiaddr) {
-        me.external_addrs.add(addr)
+        Self::report_external_address(me, addr, AddressSource::Manual)
+    }
+
+    /// Reports a candidate external address from the given source.
+    ///
+    /// If the address becomes confirmed as a result, `inject_new_external_addr` is called on the
+    /// `NetworkBehaviour`.
+    pub fn report_external_address(me: &mut Self, addr: Multiaddr, source: AddressSource) {
+        if me.external_addrs.add(addr.clone(), source) {
+            me.behaviour.inject_new_external_addr(&addr);
+        }
+    }
+
+    /// Removes an external address, whatever its source.
+    pub fn remove_external_address(me: &mut Self, addr: &Multiaddr) {
+        me.external_addrs.remove(addr)
     }
 
     /// Returns the connection info of a node, or `None` if we're not connected to it.
@@ -284,190 +718,733 @@ where TBehaviour: NetworkBehaviour<ProtocolsHandler = THandler>,
         }
     }
 
-    /// Bans a peer by its peer ID.
+    /// Bans a peer by its peer ID, for the given duration or forever if `None`.
+    ///
+    /// The existing connections to this peer, if any, are gracefully closed like with
+    /// [`Swarm::disconnect_peer_id`], and reported with `ConnectionClosedCause::Banned`. Any
+    /// incoming connection and any dialing attempt will immediately be rejected until the ban
+    /// expires. Connections rejected
+    /// this way are reported with [`NetworkBehaviour::inject_banned_peer_connection`].
     ///
-    /// Any incoming connection and any dialing attempt will immediately be rejected.
-    /// This function has no effect is the peer is already banned.
-    pub fn ban_peer_id(me: &mut Self, peer_id: PeerId) {
-        me.banned_peers.insert(peer_id.clone());
-        if let Some(c) = me.network.peer(peer_id).into_connected() {
-            c.close();
+    /// Banning a peer that is already banned overwrites the expiration of the previous ban.
+    pub fn ban_peer_id(me: &mut Self, peer_id: PeerId, duration: Option<Duration>) {
+        me.banned_peers.ban(peer_id.clone(), duration);
+        if let Some(mut peer) = me.network.peer(peer_id).into_connected() {
+            peer.disconnect();
         }
     }
 
     /// Unbans a peer.
     pub fn unban_peer_id(me: &mut Self, peer_id: PeerId) {
-        me.banned_peers.remove(&peer_id);
+        me.banned_peers.unban(&peer_id);
     }
-}
 
-impl<TTransport, TBehaviour, TMuxer, TInEvent, TOutEvent, THandler, THandlerErr, TConnInfo> Stream for
-    ExpandedSwarm<TTransport, TBehaviour, TInEvent, TOutEvent, THandler, THandlerErr, TConnInfo>
-where TBehaviour: NetworkBehaviour<ProtocolsHandler = THandler>,
-      TMuxer: StreamMuxer + Send + Sync + 'static,
-      <TMuxer as StreamMuxer>::OutboundSubstream: Send + 'static,
-      <TMuxer as StreamMuxer>::Substream: Send + 'static,
-      TTransport: Transport<Output = (TConnInfo, TMuxer)> + Clone,
-      TTransport::Error: Send + 'static,
-      TTransport::Listener: Send + 'static,
-      TTransport::ListenerUpgrade: Send + 'static,
-      TTransport::Dial: Send + 'static,
-      THandlerErr: error::Error,
-      THandler: IntoProtocolsHandler + Send + 'static,
-      <THandler as IntoProtocolsHandler>::Handler: ProtocolsHandler<InEvent = TInEvent, OutEvent = TOutEvent, Substream = Substream<TMuxer>, Error = THandlerErr> + Send + 'static,
-      <<THandler as IntoProtocolsHandler>::Handler as ProtocolsHandler>::InEvent: Send + 'static,
-      <<THandler as IntoProtocolsHandler>::Handler as ProtocolsHandler>::OutEvent: Send + 'static,
-      <<THandler as IntoProtocolsHandler>::Handler as ProtocolsHandler>::Error: Send + 'static,
-      <<THandler as IntoProtocolsHandler>::Handler as ProtocolsHandler>::OutboundOpenInfo: Send + 'static, // TODO: shouldn't be necessary
-      <<THandler as IntoProtocolsHandler>::Handler as ProtocolsHandler>::InboundProtocol: InboundUpgrade<Substream<TMuxer>> + Send + 'static,
-      <<<THandler as IntoProtocolsHandler>::Handler as ProtocolsHandler>::InboundProtocol as InboundUpgrade<Substream<TMuxer>>>::Future: Send + 'static,
-      <<<THandler as IntoProtocolsHandler>::Handler as ProtocolsHandler>::InboundProtocol as InboundUpgrade<Substream<TMuxer>>>::Error: Send + 'static,
-      <<<THandler as IntoProtocolsHandler>::Handler as ProtocolsHandler>::InboundProtocol as UpgradeInfo>::Info: Send + 'static,
-      <<<THandler as IntoProtocolsHandler>::Handler as ProtocolsHandler>::InboundProtocol as UpgradeInfo>::InfoIter: Send + 'static,
-      <<<<THandler as IntoProtocolsHandler>::Handler as ProtocolsHandler>::InboundProtocol as UpgradeInfo>::InfoIter as IntoIterator>::IntoIter: Send + 'static,
-      <<THandler as IntoProtocolsHandler>::Handler as ProtocolsHandler>::OutboundProtocol: OutboundUpgrade<Substream<TMuxer>> + Send + 'static,
-      <<<THandler as IntoProtocolsHandler>::Handler as ProtocolsHandler>::OutboundProtocol as OutboundUpgrade<Substream<TMuxer>>>::Future: Send + 'static,
-      <<<THandler as IntoProtocolsHandler>::Handler as ProtocolsHandler>::OutboundProtocol as OutboundUpgrade<Substream<TMuxer>>>::Error: Send + 'static,
-      <<<THandler as IntoProtocolsHandler>::Handler as ProtocolsHandler>::OutboundProtocol as UpgradeInfo>::Info: Send + 'static,
-      <<<THandler as IntoProtocolsHandler>::Handler as ProtocolsHandler>::OutboundProtocol as UpgradeInfo>::InfoIter: Send + 'static,
-      <<<<THandler as IntoProtocolsHandler>::Handler as ProtocolsHandler>::OutboundProtocol as UpgradeInfo>::InfoIter as IntoIterator>::IntoIter: Send + 'static,
-      <NodeHandlerWrapper<<THandler as IntoProtocolsHandler>::Handler> as NodeHandler>::OutboundOpenInfo: Send + 'static, // TODO: shouldn't be necessary
-      TConnInfo: ConnectionInfo<PeerId = PeerId> + fmt::Debug + Clone + Send + 'static,
-{
-    type Item = TBehaviour::OutEvent;
-    type Error = io::Error;
+    /// Gracefully closes all the connections to a peer and cancels the ongoing attempts to dial
+    /// it.
+    ///
+    /// The data that the connections still have to send is flushed before they are closed.
+    /// Once a connection is fully closed, the `NetworkBehaviour` is notified and a
+    /// `SwarmEvent::ConnectionClosed` with `ConnectionClosedCause::Disconnected` is generated;
+    /// the peer is fully disconnected when `num_established` reaches zero.
+    ///
+    /// Returns an error if we are not connected to the peer.
+    pub fn disconnect_peer_id(me: &mut Self, peer_id: PeerId) -> Result<(), ()> {
+        match me.network.peer(peer_id).into_connected() {
+            Some(mut peer) => {
+                peer.disconnect();
+                Ok(())
+            }
+            None => Err(()),
+        }
+    }
+
+    /// Returns true if we recently failed to dial the given peer and the dial backoff prevents us
+    /// from dialing it again at the moment.
+    ///
+    /// Always returns false if dial backoff is disabled.
+    pub fn is_dial_backed_off(me: &Self, peer_id: &PeerId) -> bool {
+        me.dial_backoff.as_ref().map_or(false, |b| b.is_backed_off(peer_id))
+    }
+
+    /// Forgets about the previous dialing failures of the given peer, allowing it to be dialed
+    /// again immediately.
+    pub fn reset_dial_backoff(me: &mut Self, peer_id: &PeerId) {
+        if let Some(backoff) = me.dial_backoff.as_mut() {
+            backoff.reset(peer_id);
+        }
+    }
+
+    /// Informs the swarm that the network of the host has changed.
+    ///
+    /// When a mobile device switches from WiFi to cellular, the connections established over
+    /// WiFi are dead, but nothing tells us so until they time out, which can take minutes. When
+    /// notified of a change, the swarm:
+    ///
+    /// - forgets about all the previous dialing failures, as peers may be reachable again;
+    /// - forgets about the external addresses reported by remotes, which have to be confirmed
+    ///   again, while the addresses added manually or by port mapping are kept;
+    /// - unless `change` is `NetworkChange::InterfaceUp`, gracefully closes all the connections,
+    ///   so that the behaviours reconnect over the new network;
+    /// - calls `NetworkBehaviour::inject_network_change`.
+    ///
+    /// The listeners keep running. A listener on an unspecified address, such as
+    /// `/ip4/0.0.0.0/tcp/0`, also accepts connections on the interfaces that appeared since.
+    pub fn notify_network_change(me: &mut Self, change: NetworkChange) {
+        if let Some(backoff) = me.dial_backoff.as_mut() {
+            backoff.clear();
+        }
+        me.external_addrs.clear_observed();
+
+        if change != NetworkChange::InterfaceUp {
+            let peers = me.network.connected_peers().cloned().collect::<Vec<_>>();
+            for peer_id in peers {
+                if let Some(mut peer) = me.network.peer(peer_id).into_connected() {
+                    peer.disconnect();
+                }
+            }
+        }
+
+        me.behaviour.inject_network_change(&change);
+    }
+
+    /// Returns the latency and throughput statistics of a peer we are connected to, as reported
+    /// by the `NetworkBehaviour`.
+    pub fn peer_stats(me: &Self, peer_id: &PeerId) -> Option<&PeerStats> {
+        me.peer_stats.get(peer_id)
+    }
+
+    /// Returns the connected peers with a known average round-trip time, from the
+    /// lowest to the highest, e.g. to pick the closest peers to send requests to.
+    pub fn peers_by_rtt(me: &Self) -> Vec<(PeerId, Duration)> {
+        me.peer_stats.peers_by_rtt()
+    }
+
+    /// Returns true if the given peer is currently banned.
+    pub fn is_banned(me: &Self, peer_id: &PeerId) -> bool {
+        me.banned_peers.contains(peer_id)
+    }
+
+    /// Returns the error that stopped the event log enabled with `SwarmBuilder::event_log`, if
+    /// writing to it failed.
+    pub fn event_log_error(me: &Self) -> Option<&io::Error> {
+        me.event_log.as_ref().and_then(|log| log.error())
+    }
+
+    /// Returns the history of the most recent events enabled with `SwarmBuilder::event_history`.
+    pub fn event_history(me: &Self) -> Option<EventHistory> {
+        me.event_history.as_ref().map(|(history, _)| history.clone())
+    }
+
+    /// Takes a snapshot of the state of the swarm, including the summary of the
+    /// `NetworkBehaviour`.
+    pub fn snapshot(me: &Self) -> SwarmSnapshot {
+        let mut connections_by_transport = BTreeMap::new();
+        for connection in me.network.connections() {
+            let transport = snapshot::transport_name(connection.endpoint());
+            *connections_by_transport.entry(transport).or_insert(0) += 1;
+        }
+        let mut behaviour = BehaviourSummary::new();
+        me.behaviour.summarize(&mut behaviour);
+        SwarmSnapshot {
+            taken_at: Instant::now(),
+            num_peers: me.network.connected_peers().count(),
+            num_connections: me.network.num_connections(),
+            connections_by_transport,
+            pending_dials: me.network.pending_connection_peers().count(),
+            pending_unknown_dials: me.network.unknown_dials().count(),
+            pending_incoming: me.network.num_incoming_negotiated(),
+            listened_addrs: me.listened_addrs.iter().cloned().collect(),
+            behaviour,
+        }
+    }
+
+    /// Returns a stream of snapshots of the state of the swarm: one right away, then one every
+    /// `interval`.
+    ///
+    /// The snapshots are taken while the swarm is polled, which is what drives the stream. If a
+    /// snapshot hasn't been received by the time the next one is taken, the next one is dropped.
+    /// Dropping the stream unsubscribes from the snapshots.
+    pub fn subscribe_snapshots(me: &mut Self, interval: Duration) -> mpsc::Receiver<SwarmSnapshot> {
+        me.snapshots.subscribe(interval)
+    }
+
+    /// Returns a new handle allowing other tasks to act on the swarm while this one polls it.
+    ///
+    /// See [`SwarmHandle`].
+    pub fn handle(me: &Self) -> SwarmHandle<Self> {
+        SwarmHandle::new(me.commands_sender.clone())
+    }
+
+    /// Gracefully shuts the swarm down.
+    ///
+    /// The listeners are closed, the ongoing dialing attempts are interrupted, the behaviour is
+    /// notified with `NetworkBehaviour::inject_shutdown`, and all the connections are gracefully
+    /// closed, flushing the data they still have to send. From then on, the swarm no longer
+    /// dials, and the connections that were still being negotiated are closed as soon as they
+    /// are established.
+    ///
+    /// The returned future drives the swarm until all the connections are closed or until
+    /// `deadline` has elapsed, whichever comes first, after which the swarm can be dropped
+    /// without resetting connections abruptly. The events produced meanwhile are discarded.
+    pub fn shutdown<'a>(me: &'a mut Self, deadline: Duration) -> impl Future<Item = (), Error = io::Error> + 'a {
+        if !me.shutting_down {
+            me.shutting_down = true;
+            for addr in me.network.close_listeners() {
+                me.listened_addrs.retain(|a| a != &addr);
+                me.behaviour.inject_expired_listen_addr(&addr);
+            }
+            let dialing = me.network.pending_connection_peers().cloned().collect::<Vec<_>>();
+            for peer_id in dialing {
+                if let Some(peer) = me.network.peer(peer_id).into_pending_connect() {
+                    peer.interrupt();
+                }
+            }
+            me.behaviour.inject_shutdown();
+            let connected = me.network.connected_peers().cloned().collect::<Vec<_>>();
+            for peer_id in connected {
+                if let Some(mut peer) = me.network.peer(peer_id).into_connected() {
+                    peer.disconnect();
+                }
+            }
+        }
+
+        let mut deadline = Delay::new(Instant::now() + deadline);
+        future::poll_fn(move || {
+            loop {
+                if me.network.connected_peers().next().is_none() {
+                    return Ok(Async::Ready(()))
+                }
+                if let Async::NotReady = Self::poll_event(&mut *me)? {
+                    break
+                }
+            }
+            match deadline.poll() {
+                Ok(Async::NotReady) => Ok(Async::NotReady),
+                Ok(Async::Ready(())) | Err(_) => Ok(Async::Ready(())),
+            }
+        })
+    }
+
+    /// Returns a future resolving to the next event of the swarm, including the events about
+    /// connections.
+    ///
+    /// This is [`poll_event`](ExpandedSwarm::poll_event) wrapped in a future, meant for code
+    /// that awaits the events one at a time rather than polling the swarm, see the
+    /// documentation of the crate about `async`/`await`.
+    pub fn next_event<'a>(me: &'a mut Self)
+        -> impl Future<Item = SwarmEvent<TBehaviour::OutEvent, TConnInfo, THandlerErr>, Error = io::Error> + 'a
+    {
+        future::poll_fn(move || Self::poll_event(&mut *me))
+    }
+
+    /// Polls the swarm for the next event, including the events about connections.
+    ///
+    /// Polling the `Swarm` as a `Stream` calls this method and only returns the events generated
+    /// by the `NetworkBehaviour`, discarding the other ones.
+    pub fn poll_event(me: &mut Self) -> Poll<SwarmEvent<TBehaviour::OutEvent, TConnInfo, THandlerErr>, io::Error> {
+        if !me.snapshots.is_empty() && me.snapshots.poll_due() {
+            let snapshot = Self::snapshot(me);
+            me.snapshots.send(snapshot);
+        }
 
-    fn poll(&mut self) -> Poll<Option<Self::Item>, io::Error> {
         loop {
+            while let Ok(Async::Ready(Some(command))) = me.commands.poll() {
+                command(&mut *me);
+            }
+
+            if let Some(event) = me.pending_events.pop_front() {
+                ExpandedSwarm::record_event(me, || event_log::swarm_event(&event));
+                return Ok(Async::Ready(event))
+            }
+
             let mut network_not_ready = false;
 
-            match self.network.poll() {
+            match me.network.poll() {
                 Async::NotReady => network_not_ready = true,
-                Async::Ready(NetworkEvent::NodeEvent { conn_info, event }) => {
-                    self.behaviour.inject_node_event(conn_info.peer_id().clone(), event);
+                Async::Ready(NetworkEvent::NodeEvent { connection, conn_info, event }) => {
+                    me.behaviour.inject_connection_event(conn_info.peer_id().clone(), connection, event);
                 },
-                Async::Ready(NetworkEvent::Connected { conn_info, endpoint }) => {
-                    if self.banned_peers.contains(conn_info.peer_id()) {
-                        self.network.peer(conn_info.peer_id().clone())
-                            .into_connected()
-                            .expect("the Network just notified us that we were connected; QED")
-                            .close();
+                Async::Ready(NetworkEvent::Connected { connection, conn_info, endpoint }) => {
+                    ExpandedSwarm::incoming_finished(me, &endpoint);
+                    let peer_id = conn_info.peer_id().clone();
+                    let banned = me.banned_peers.contains(&peer_id);
+                    let mut peer = me.network.peer(peer_id.clone())
+                        .into_connected()
+                        .expect("the Network just notified us that we were connected; QED");
+                    if banned || me.shutting_down || !me.gater.allow_established(&peer_id, &endpoint) {
+                        peer.close_connection(connection);
+                        if banned {
+                            me.behaviour.inject_banned_peer_connection(&peer_id, &endpoint);
+                        }
                     } else {
-                        self.behaviour.inject_connected(conn_info.peer_id().clone(), endpoint);
+                        let first_connection = peer.connection_ids().count() == 1;
+                        if let Some(backoff) = me.dial_backoff.as_mut() {
+                            backoff.reset(&peer_id);
+                        }
+                        let established_in = if let ConnectedPoint::Dialer { ref address } = endpoint {
+                            me.peer_store.add_address(peer_id.clone(), address.clone(), CONNECTED_ADDRESS_TTL);
+                            me.address_ranking.dial_succeeded(&peer_id, address)
+                        } else {
+                            None
+                        };
+                        let num_established = peer.connection_ids().count() as u32;
+                        if first_connection {
+                            me.behaviour.inject_connected(peer_id.clone(), endpoint.clone());
+                        }
+                        me.behaviour.inject_connection_established(&peer_id, &connection, &endpoint);
+                        me.pending_events.push_back(SwarmEvent::ConnectionEstablished {
+                            peer_id,
+                            connection,
+                            info: conn_info,
+                            endpoint,
+                            num_established: NonZeroU32::new(num_established)
+                                .expect("the connection has just been established; QED"),
+                            established_in,
+                        });
                     }
                 },
-                Async::Ready(NetworkEvent::NodeClosed { conn_info, endpoint, .. }) => {
-                    self.behaviour.inject_disconnected(conn_info.peer_id(), endpoint);
+                Async::Ready(NetworkEvent::NodeClosed { connection, conn_info, endpoint, error }) => {
+                    let cause = match error {
+                        HandledNodeError::Node(err) => ConnectionClosedCause::Io(err),
+                        HandledNodeError::Handler(NodeHandlerWrapperError::UselessTimeout) =>
+                            ConnectionClosedCause::KeepAliveTimeout,
+                        HandledNodeError::Handler(NodeHandlerWrapperError::Handler(err)) =>
+                            ConnectionClosedCause::Handler(err),
+                    };
+                    let peer_id = conn_info.peer_id().clone();
+                    ExpandedSwarm::connection_closed(me, peer_id, connection, endpoint, cause);
                 },
-                Async::Ready(NetworkEvent::Replaced { new_info, closed_endpoint, endpoint, .. }) => {
-                    self.behaviour.inject_replaced(new_info.peer_id().clone(), closed_endpoint, endpoint);
+                Async::Ready(NetworkEvent::NodeDisconnected { connection, conn_info, endpoint }) => {
+                    let peer_id = conn_info.peer_id().clone();
+                    let cause = if me.banned_peers.contains(&peer_id) {
+                        ConnectionClosedCause::Banned
+                    } else {
+                        ConnectionClosedCause::Disconnected
+                    };
+                    ExpandedSwarm::connection_closed(me, peer_id, connection, endpoint, cause);
+                },
+                Async::Ready(NetworkEvent::Replaced {
+                    connection,
+                    closed_connection,
+                    new_info,
+                    closed_endpoint,
+                    endpoint,
+                    ..
+                }) => {
+                    ExpandedSwarm::incoming_finished(me, &endpoint);
+                    let peer_id = new_info.peer_id().clone();
+                    let banned = me.banned_peers.contains(&peer_id);
+                    if banned || me.shutting_down || !me.gater.allow_established(&peer_id, &endpoint) {
+                        me.network.peer(peer_id.clone())
+                            .into_connected()
+                            .expect("the Network just notified us that we were connected; QED")
+                            .close_connection(connection);
+                        let cause = ConnectionClosedCause::Replaced;
+                        ExpandedSwarm::connection_closed(me, peer_id.clone(), closed_connection, closed_endpoint, cause);
+                        if banned {
+                            me.behaviour.inject_banned_peer_connection(&peer_id, &endpoint);
+                        }
+                    } else {
+                        let established_in = if let ConnectedPoint::Dialer { ref address } = endpoint {
+                            me.address_ranking.dial_succeeded(&peer_id, address)
+                        } else {
+                            None
+                        };
+                        me.behaviour.inject_connection_closed(&peer_id, &closed_connection, &closed_endpoint);
+                        me.behaviour.inject_replaced(peer_id.clone(), closed_endpoint.clone(), endpoint.clone());
+                        me.behaviour.inject_connection_established(&peer_id, &connection, &endpoint);
+                        let num_established = me.network.peer(peer_id.clone())
+                            .into_connected()
+                            .map_or(1, |p| p.connection_ids().count() as u32);
+                        me.pending_events.push_back(SwarmEvent::ConnectionClosed {
+                            peer_id: peer_id.clone(),
+                            connection: closed_connection,
+                            endpoint: closed_endpoint,
+                            num_established: num_established - 1,
+                            cause: ConnectionClosedCause::Replaced,
+                        });
+                        me.pending_events.push_back(SwarmEvent::ConnectionEstablished {
+                            peer_id,
+                            connection,
+                            info: new_info,
+                            endpoint,
+                            num_established: NonZeroU32::new(num_established)
+                                .expect("the connection has just been established; QED"),
+                            established_in,
+                        });
+                    }
                 },
                 Async::Ready(NetworkEvent::IncomingConnection(incoming)) => {
-                    let handler = self.behaviour.new_handler();
-                    incoming.accept(handler.into_node_handler_builder());
+                    let mut allowed = me.gater.allow_incoming(&incoming.info());
+                    if allowed {
+                        let info = incoming.info();
+                        if let Some(Err(reason)) = me.inbound_rate_limit.as_mut()
+                            .map(|limiter| limiter.try_accept(info.send_back_addr))
+                        {
+                            allowed = false;
+                            me.pending_events.push_back(SwarmEvent::IncomingConnectionRateLimited {
+                                local_addr: info.listen_addr.clone(),
+                                send_back_addr: info.send_back_addr.clone(),
+                                reason,
+                            });
+                        }
+                    }
+                    ExpandedSwarm::record_event(me, || {
+                        Some(event_log::incoming_connection(&incoming.info(), allowed))
+                    });
+                    if allowed {
+                        let handler = me.behaviour.new_handler();
+                        incoming.accept(
+                            handler.into_node_handler_builder().with_idle_timeout(me.idle_timeout)
+                        );
+                    }
                 },
                 Async::Ready(NetworkEvent::NewListenerAddress { listen_addr }) => {
-                    if !self.listened_addrs.contains(&listen_addr) {
-                        self.listened_addrs.push(listen_addr.clone())
+                    if !me.listened_addrs.contains(&listen_addr) {
+                        me.listened_addrs.push(listen_addr.clone())
                     }
-                    self.behaviour.inject_new_listen_addr(&listen_addr);
+                    me.behaviour.inject_new_listen_addr(&listen_addr);
                 }
                 Async::Ready(NetworkEvent::ExpiredListenerAddress { listen_addr }) => {
-                    self.listened_addrs.retain(|a| a != &listen_addr);
-                    self.behaviour.inject_expired_listen_addr(&listen_addr);
+                    me.listened_addrs.retain(|a| a != &listen_addr);
+                    me.behaviour.inject_expired_listen_addr(&listen_addr);
                 }
-                Async::Ready(NetworkEvent::ListenerClosed { .. }) => {},
-                Async::Ready(NetworkEvent::IncomingConnectionError { .. }) => {},
+                Async::Ready(NetworkEvent::ListenerClosed { addresses, result, .. }) => {
+                    for addr in addresses {
+                        me.listened_addrs.retain(|a| a != &addr);
+                        me.behaviour.inject_expired_listen_addr(&addr);
+                    }
+                    if let Err(err) = result {
+                        me.behaviour.inject_listener_error(&err);
+                    }
+                },
+                Async::Ready(NetworkEvent::IncomingConnectionError { listen_addr, send_back_addr, error }) => {
+                    if let Some(limiter) = me.inbound_rate_limit.as_mut() {
+                        limiter.pending_finished(&send_back_addr);
+                    }
+                    let negotiation = match &error {
+                        network::IncomingError::Transport(TransportError::Other(err)) =>
+                            upgrade::negotiation_failure(err).cloned(),
+                        _ => None,
+                    };
+                    ExpandedSwarm::record_event(me, || {
+                        Some(event_log::incoming_connection_error(&send_back_addr, &listen_addr, error))
+                    });
+                    me.pending_events.push_back(SwarmEvent::IncomingConnectionError {
+                        local_addr: listen_addr,
+                        send_back_addr,
+                        negotiation,
+                    });
+                },
                 Async::Ready(NetworkEvent::DialError { peer_id, multiaddr, error, new_state }) => {
-                    self.behaviour.inject_addr_reach_failure(Some(&peer_id), &multiaddr, &error);
-                    if let network::PeerState::NotConnected = new_state {
-                        self.behaviour.inject_dial_failure(&peer_id);
+                    me.address_ranking.dial_failed(Some(&peer_id), &multiaddr);
+                    me.behaviour.inject_addr_reach_failure(Some(&peer_id), &multiaddr, &error);
+                    let mut negotiation = None;
+                    let cause = match error {
+                        network::NetworkReachError::Transport(TransportError::MultiaddrNotSupported(_)) =>
+                            DialFailureCause::MultiaddrNotSupported,
+                        network::NetworkReachError::Transport(TransportError::Other(err)) => {
+                            negotiation = upgrade::negotiation_failure(&err).cloned();
+                            if negotiation.is_some() {
+                                DialFailureCause::Negotiation
+                            } else {
+                                DialFailureCause::Transport
+                            }
+                        },
+                        network::NetworkReachError::PeerIdMismatch { .. } => DialFailureCause::PeerIdMismatch,
+                        network::NetworkReachError::ConnectionLimit(_) => DialFailureCause::ConnectionLimit,
+                    };
+                    me.pending_events.push_back(SwarmEvent::UnreachableAddr {
+                        peer_id: Some(peer_id.clone()),
+                        address: multiaddr,
+                        cause,
+                        negotiation,
+                    });
+                    match new_state {
+                        network::PeerState::NotConnected => {
+                            me.address_ranking.dials_stopped(&peer_id);
+                            if let Some(backoff) = me.dial_backoff.as_mut() {
+                                backoff.record_failure(peer_id.clone());
+                            }
+                            me.behaviour.inject_dial_failure(&peer_id);
+                        },
+                        network::PeerState::Dialing { .. } => Self::record_dial_starts(me, &peer_id),
+                        _ => {},
                     }
                 },
                 Async::Ready(NetworkEvent::UnknownPeerDialError { multiaddr, error, .. }) => {
-                    self.behaviour.inject_addr_reach_failure(None, &multiaddr, &error);
+                    me.address_ranking.dial_failed(None, &multiaddr);
+                    me.behaviour.inject_addr_reach_failure(None, &multiaddr, &error);
+                    let mut negotiation = None;
+                    let cause = match error {
+                        network::UnknownPeerDialErr::Transport(TransportError::MultiaddrNotSupported(_)) =>
+                            DialFailureCause::MultiaddrNotSupported,
+                        network::UnknownPeerDialErr::Transport(TransportError::Other(err)) => {
+                            negotiation = upgrade::negotiation_failure(&err).cloned();
+                            if negotiation.is_some() {
+                                DialFailureCause::Negotiation
+                            } else {
+                                DialFailureCause::Transport
+                            }
+                        },
+                        network::UnknownPeerDialErr::FoundLocalPeerId => DialFailureCause::LocalPeerId,
+                        network::UnknownPeerDialErr::ConnectionLimit(_) => DialFailureCause::ConnectionLimit,
+                    };
+                    me.pending_events.push_back(SwarmEvent::UnreachableAddr {
+                        peer_id: None,
+                        address: multiaddr,
+                        cause,
+                        negotiation,
+                    });
                 },
             }
 
             // Try to deliver pending event.
-            if let Some((id, pending)) = self.send_event_to_complete.take() {
-                if let Some(mut peer) = self.network.peer(id.clone()).into_connected() {
+            if let Some((id, connection, pending)) = me.send_event_to_complete.take() {
+                if let Some(mut peer) = me.network.peer(id.clone()).into_connected() {
                     if let AsyncSink::NotReady(e) = pending {
-                        if let Ok(a@AsyncSink::NotReady(_)) = peer.start_send_event(e) {
-                            self.send_event_to_complete = Some((id, a))
-                        } else if let Ok(Async::NotReady) = peer.complete_send_event() {
-                            self.send_event_to_complete = Some((id, AsyncSink::Ready))
+                        let sent = match connection {
+                            Some(c) => peer.start_send_event_to(c, e).ok(),
+                            None => Some(peer.start_send_event(e)),
+                        };
+                        if let Some(Ok(a@AsyncSink::NotReady(_))) = sent {
+                            me.send_event_to_complete = Some((id, connection, a))
+                        } else if let Some(Ok(AsyncSink::Ready)) = sent {
+                            let complete = match connection {
+                                Some(c) => peer.complete_send_event_to(c),
+                                None => peer.complete_send_event(),
+                            };
+                            if let Ok(Async::NotReady) = complete {
+                                me.send_event_to_complete = Some((id, connection, AsyncSink::Ready))
+                            }
+                        }
+                    } else {
+                        let complete = match connection {
+                            Some(c) => peer.complete_send_event_to(c),
+                            None => peer.complete_send_event(),
+                        };
+                        if let Ok(Async::NotReady) = complete {
+                            me.send_event_to_complete = Some((id, connection, AsyncSink::Ready))
                         }
-                    } else if let Ok(Async::NotReady) = peer.complete_send_event() {
-                        self.send_event_to_complete = Some((id, AsyncSink::Ready))
                     }
                 }
             }
-            if self.send_event_to_complete.is_some() {
+            if me.send_event_to_complete.is_some() {
                 return Ok(Async::NotReady)
             }
 
             let behaviour_poll = {
                 let mut parameters = SwarmPollParameters {
-                    local_peer_id: &mut self.network.local_peer_id(),
-                    supported_protocols: &self.supported_protocols,
-                    listened_addrs: &self.listened_addrs,
-                    external_addrs: &self.external_addrs
+                    local_peer_id: &mut me.network.local_peer_id(),
+                    supported_protocols: &me.supported_protocols,
+                    listened_addrs: &me.listened_addrs,
+                    external_addrs: &me.external_addrs,
+                    dial_backoff: me.dial_backoff.as_ref(),
+                    peer_stats: &me.peer_stats,
                 };
-                self.behaviour.poll(&mut parameters)
+                me.behaviour.poll(&mut parameters)
             };
 
             match behaviour_poll {
                 Async::NotReady if network_not_ready => return Ok(Async::NotReady),
                 Async::NotReady => (),
                 Async::Ready(NetworkBehaviourAction::GenerateEvent(event)) => {
-                    return Ok(Async::Ready(Some(event)))
+                    if let Some((history, describe)) = me.event_history.as_ref() {
+                        history.record(&describe(&event));
+                    }
+                    return Ok(Async::Ready(SwarmEvent::Behaviour(event)))
                 },
                 Async::Ready(NetworkBehaviourAction::DialAddress { address }) => {
-                    let _ = ExpandedSwarm::dial_addr(self, address);
+                    let _ = ExpandedSwarm::dial_addr(me, address);
                 },
                 Async::Ready(NetworkBehaviourAction::DialPeer { peer_id }) => {
-                    if self.banned_peers.contains(&peer_id) {
-                        self.behaviour.inject_dial_failure(&peer_id);
-                    } else {
-                        ExpandedSwarm::dial(self, peer_id);
-                    }
+                    ExpandedSwarm::dial(me, peer_id);
                 },
                 Async::Ready(NetworkBehaviourAction::SendEvent { peer_id, event }) => {
-                    if let Some(mut peer) = self.network.peer(peer_id.clone()).into_connected() {
+                    if let Some(mut peer) = me.network.peer(peer_id.clone()).into_connected() {
                         if let Ok(a@AsyncSink::NotReady(_)) = peer.start_send_event(event) {
-                            self.send_event_to_complete = Some((peer_id, a))
+                            me.send_event_to_complete = Some((peer_id, None, a))
                         } else if let Ok(Async::NotReady) = peer.complete_send_event() {
-                            self.send_event_to_complete = Some((peer_id, AsyncSink::Ready))
+                            me.send_event_to_complete = Some((peer_id, None, AsyncSink::Ready))
+                        }
+                    }
+                },
+                Async::Ready(NetworkBehaviourAction::SendEventToConnection { peer_id, connection, event }) => {
+                    if let Some(mut peer) = me.network.peer(peer_id.clone()).into_connected() {
+                        match peer.start_send_event_to(connection, event) {
+                            Ok(Ok(a@AsyncSink::NotReady(_))) => {
+                                me.send_event_to_complete = Some((peer_id, Some(connection), a))
+                            }
+                            Ok(Ok(AsyncSink::Ready)) => {
+                                if let Ok(Async::NotReady) = peer.complete_send_event_to(connection) {
+                                    me.send_event_to_complete = Some((peer_id, Some(connection), AsyncSink::Ready))
+                                }
+                            }
+                            Ok(Err(())) | Err(_) => {}
                         }
                     }
                 },
-                Async::Ready(NetworkBehaviourAction::ReportObservedAddr { address }) => {
-                    for addr in self.network.address_translation(&address) {
-                        if self.external_addrs.iter().all(|a| *a != addr) {
-                            self.behaviour.inject_new_external_addr(&addr);
+                Async::Ready(NetworkBehaviourAction::ReportObservedAddr { address, observer }) => {
+                    for addr in me.network.address_translation(&address) {
+                        let source = AddressSource::Observed(observer.clone());
+                        if me.external_addrs.add(addr.clone(), source) {
+                            me.behaviour.inject_new_external_addr(&addr);
                         }
-                        self.external_addrs.add(addr)
                     }
                 },
+                Async::Ready(NetworkBehaviourAction::ReportRemoteProtocols { peer_id, protocols }) => {
+                    me.behaviour.inject_remote_protocols(&peer_id, &protocols);
+                    me.peer_store.set_protocols(peer_id, protocols);
+                },
+                Async::Ready(NetworkBehaviourAction::ReportRoundTripTime { peer_id, rtt }) => {
+                    if me.network.peer(peer_id.clone()).into_connected().is_some() {
+                        me.peer_stats.add_rtt(peer_id, rtt);
+                    }
+                },
+                Async::Ready(NetworkBehaviourAction::ReportTransfer { peer_id, bytes_received, bytes_sent, duration }) => {
+                    if me.network.peer(peer_id.clone()).into_connected().is_some() {
+                        me.peer_stats.add_transfer(peer_id, bytes_received, bytes_sent, duration);
+                    }
+                },
+                Async::Ready(NetworkBehaviourAction::ReportExternalAddr { address, source }) => {
+                    Self::report_external_address(me, address, source);
+                },
+                Async::Ready(NetworkBehaviourAction::RemoveExternalAddr { address }) => {
+                    Self::remove_external_address(me, &address);
+                },
+            }
+        }
+    }
+}
+
+impl<TTransport, TBehaviour, TMuxer, TInEvent, TOutEvent, THandler, THandlerErr, TConnInfo> Stream for
+    ExpandedSwarm<TTransport, TBehaviour, TInEvent, TOutEvent, THandler, THandlerErr, TConnInfo>
+where TBehaviour: NetworkBehaviour<ProtocolsHandler = THandler>,
+      TMuxer: StreamMuxer + Send + Sync + 'static,
+      <TMuxer as StreamMuxer>::OutboundSubstream: Send + 'static,
+      <TMuxer as StreamMuxer>::Substream: Send + 'static,
+      TTransport: Transport<Output = (TConnInfo, TMuxer)> + Clone,
+      TTransport::Error: Send + 'static,
+      TTransport::Listener: Send + 'static,
+      TTransport::ListenerUpgrade: Send + 'static,
+      TTransport::Dial: Send + 'static,
+      THandlerErr: error::Error,
+      THandler: IntoProtocolsHandler + Send + 'static,
+      <THandler as IntoProtocolsHandler>::Handler: ProtocolsHandler<InEvent = TInEvent, OutEvent = TOutEvent, Substream = Substream<TMuxer>, Error = THandlerErr> + Send + 'static,
+      <<THandler as IntoProtocolsHandler>::Handler as ProtocolsHandler>::InEvent: Send + 'static,
+      <<THandler as IntoProtocolsHandler>::Handler as ProtocolsHandler>::OutEvent: Send + 'static,
+      <<THandler as IntoProtocolsHandler>::Handler as ProtocolsHandler>::Error: Send + 'static,
+      <<THandler as IntoProtocolsHandler>::Handler as ProtocolsHandler>::OutboundOpenInfo: Send + 'static, // TODO: shouldn't be necessary
+      <<THandler as IntoProtocolsHandler>::Handler as ProtocolsHandler>::InboundProtocol: InboundUpgrade<Substream<TMuxer>> + Send + 'static,
+      <<<THandler as IntoProtocolsHandler>::Handler as ProtocolsHandler>::InboundProtocol as InboundUpgrade<Substream<TMuxer>>>::Future: Send + 'static,
+      <<<THandler as IntoProtocolsHandler>::Handler as ProtocolsHandler>::InboundProtocol as InboundUpgrade<Substream<TMuxer>>>::Error: Send + 'static,
+      <<<THandler as IntoProtocolsHandler>::Handler as ProtocolsHandler>::InboundProtocol as UpgradeInfo>::Info: Send + 'static,
+      <<<THandler as IntoProtocolsHandler>::Handler as ProtocolsHandler>::InboundProtocol as UpgradeInfo>::InfoIter: Send + 'static,
+      <<<<THandler as IntoProtocolsHandler>::Handler as ProtocolsHandler>::InboundProtocol as UpgradeInfo>::InfoIter as IntoIterator>::IntoIter: Send + 'static,
+      <<THandler as IntoProtocolsHandler>::Handler as ProtocolsHandler>::OutboundProtocol: OutboundUpgrade<Substream<TMuxer>> + Send + 'static,
+      <<<THandler as IntoProtocolsHandler>::Handler as ProtocolsHandler>::OutboundProtocol as OutboundUpgrade<Substream<TMuxer>>>::Future: Send + 'static,
+      <<<THandler as IntoProtocolsHandler>::Handler as ProtocolsHandler>::OutboundProtocol as OutboundUpgrade<Substream<TMuxer>>>::Error: Send + 'static,
+      <<<THandler as IntoProtocolsHandler>::Handler as ProtocolsHandler>::OutboundProtocol as UpgradeInfo>::Info: Send + 'static,
+      <<<THandler as IntoProtocolsHandler>::Handler as ProtocolsHandler>::OutboundProtocol as UpgradeInfo>::InfoIter: Send + 'static,
+      <<<<THandler as IntoProtocolsHandler>::Handler as ProtocolsHandler>::OutboundProtocol as UpgradeInfo>::InfoIter as IntoIterator>::IntoIter: Send + 'static,
+      <NodeHandlerWrapper<<THandler as IntoProtocolsHandler>::Handler> as NodeHandler>::OutboundOpenInfo: Send + 'static, // TODO: shouldn't be necessary
+      TConnInfo: ConnectionInfo<PeerId = PeerId> + fmt::Debug + Clone + Send + 'static,
+{
+    type Item = TBehaviour::OutEvent;
+    type Error = io::Error;
+
+    fn poll(&mut self) -> Poll<Option<Self::Item>, io::Error> {
+        loop {
+            match ExpandedSwarm::poll_event(self)? {
+                Async::Ready(SwarmEvent::Behaviour(event)) => return Ok(Async::Ready(Some(event))),
+                Async::Ready(_) => {},
+                Async::NotReady => return Ok(Async::NotReady),
             }
         }
     }
 }
 
+/// List of banned peers, alongside with the moment when their ban expires.
+#[derive(Debug, Default)]
+struct BannedPeers {
+    /// For each banned peer, when the ban expires. `None` if the ban never expires.
+    peers: HashMap<PeerId, Option<Instant>>,
+}
+
+impl BannedPeers {
+    /// Bans a peer. Also cleans up the bans that have expired.
+    fn ban(&mut self, peer_id: PeerId, duration: Option<Duration>) {
+        let now = Instant::now();
+        self.peers.retain(|_, expires| expires.map_or(true, |e| e > now));
+        self.peers.insert(peer_id, duration.map(|d| now + d));
+    }
+
+    /// Removes the ban of a peer, if any.
+    fn unban(&mut self, peer_id: &PeerId) {
+        self.peers.remove(peer_id);
+    }
+
+    /// Returns true if the peer is banned and its ban hasn't expired yet.
+    fn contains(&self, peer_id: &PeerId) -> bool {
+        match self.peers.get(peer_id) {
+            Some(Some(expires)) => *expires > Instant::now(),
+            Some(None) => true,
+            None => false,
+        }
+    }
+}
+
+/// Error that can happen when starting to dial an address.
+#[derive(Debug)]
+pub enum DialError<TErr> {
+    /// The transport refused to dial the address.
+    Transport(TransportError<TErr>),
+    /// The `ConnectionGater` denied the dialing attempt.
+    Denied,
+    /// The swarm is shutting down.
+    ShuttingDown,
+}
+
+impl<TErr> fmt::Display for DialError<TErr>
+where
+    TErr: fmt::Display
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DialError::Transport(err) => write!(f, "{}", err),
+            DialError::Denied => write!(f, "Dialing attempt denied by the connection gater"),
+            DialError::ShuttingDown => write!(f, "The swarm is shutting down"),
+        }
+    }
+}
+
+impl<TErr> error::Error for DialError<TErr>
+where
+    TErr: error::Error + 'static
+{
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        match self {
+            DialError::Transport(err) => Some(err),
+            DialError::Denied | DialError::ShuttingDown => None,
+        }
+    }
+}
+
 /// Parameters passed to `poll()`, that the `NetworkBehaviour` has access to.
 // TODO: #[derive(Debug)]
 pub struct SwarmPollParameters<'a> {
     local_peer_id: &'a PeerId,
     supported_protocols: &'a [Vec<u8>],
     listened_addrs: &'a [Multiaddr],
-    external_addrs: &'a Addresses,
+    external_addrs: &'a ExternalAddresses,
+    dial_backoff: Option<&'a DialBackoff>,
+    peer_stats: &'a PeerStatsStore,
 }
 
 impl<'a> PollParameters for SwarmPollParameters<'a> {
     type SupportedProtocolsIter = std::vec::IntoIter<Vec<u8>>;
     type ListenedAddressesIter = std::vec::IntoIter<Multiaddr>;
-    type ExternalAddressesIter = AddressIntoIter;
+    type ExternalAddressesIter = std::vec::IntoIter<Multiaddr>;
 
     fn supported_protocols(&self) -> Self::SupportedProtocolsIter {
         self.supported_protocols.to_vec().into_iter()
@@ -478,16 +1455,37 @@ impl<'a> PollParameters for SwarmPollParameters<'a> {
     }
 
     fn external_addresses(&self) -> Self::ExternalAddressesIter {
-        self.external_addrs.clone().into_iter()
+        self.external_addrs.iter().cloned().collect::<Vec<_>>().into_iter()
     }
 
     fn local_peer_id(&self) -> &PeerId {
         self.local_peer_id
     }
+
+    fn is_dial_backed_off(&self, peer_id: &PeerId) -> bool {
+        self.dial_backoff.map_or(false, |b| b.is_backed_off(peer_id))
+    }
+
+    fn peer_stats(&self, peer_id: &PeerId) -> Option<PeerStats> {
+        self.peer_stats.get(peer_id).cloned()
+    }
 }
 
-pub struct SwarmBuilder<TTransport, TBehaviour> {
-    incoming_limit: Option<u32>,
+pub struct SwarmBuilder<TTransport, TBehaviour>
+where
+    TBehaviour: NetworkBehaviour,
+{
+    limits: ConnectionLimits,
+    gater: Box<dyn ConnectionGater + Send>,
+    dial_backoff: Option<DialBackoffConfig>,
+    inbound_rate_limit: Option<InboundRateLimitConfig>,
+    rng: BoxedRng,
+    external_address_confirmations: NonZeroUsize,
+    idle_timeout: Duration,
+    executor: TaskExecutor,
+    peer_store: PeerStore,
+    event_log: Option<EventLog>,
+    event_history: Option<(EventHistory, fn(&TBehaviour::OutEvent) -> Record)>,
     local_peer_id: PeerId,
     transport: TTransport,
     behaviour: TBehaviour,
@@ -526,7 +1524,17 @@ where TBehaviour: NetworkBehaviour,
 {
     pub fn new(transport: TTransport, behaviour: TBehaviour, local_peer_id: PeerId) -> Self {
         SwarmBuilder {
-            incoming_limit: None,
+            limits: ConnectionLimits::default(),
+            gater: Box::new(DummyConnectionGater),
+            dial_backoff: None,
+            inbound_rate_limit: None,
+            rng: BoxedRng::default(),
+            external_address_confirmations: NonZeroUsize::new(1).expect("1 > 0"),
+            idle_timeout: Duration::from_secs(0),
+            executor: TaskExecutor::Default,
+            peer_store: PeerStore::new(),
+            event_log: None,
+            event_history: None,
             local_peer_id,
             transport,
             behaviour,
@@ -534,10 +1542,134 @@ where TBehaviour: NetworkBehaviour,
     }
 
     pub fn incoming_limit(mut self, incoming_limit: Option<u32>) -> Self {
-        self.incoming_limit = incoming_limit;
+        self.limits = self.limits.with_max_pending_incoming(incoming_limit);
+        self
+    }
+
+    /// Configures the limits on the number of pending and established connections.
+    ///
+    /// > **Note**: Overwrites any limit previously set with `incoming_limit`.
+    pub fn connection_limits(mut self, limits: ConnectionLimits) -> Self {
+        self.limits = limits;
+        self
+    }
+
+    /// Configures the number of known addresses of a peer that are dialed at the same time.
+    ///
+    /// The overall number of concurrent dials is bounded by the maximum number of pending
+    /// outgoing connections of the `ConnectionLimits`. Defaults to 1.
+    pub fn dial_concurrency_factor(mut self, factor: NonZeroU8) -> Self {
+        self.limits = self.limits.with_dial_concurrency_factor(factor);
+        self
+    }
+
+    /// Configures the `ConnectionGater` deciding which connections may be opened or accepted.
+    pub fn connection_gater(mut self, gater: impl ConnectionGater + Send + 'static) -> Self {
+        self.gater = Box::new(gater);
+        self
+    }
+
+    /// Enables backing off from peers that we failed to dial, using the given configuration.
+    ///
+    /// Disabled by default.
+    pub fn dial_backoff(mut self, config: DialBackoffConfig) -> Self {
+        self.dial_backoff = Some(config);
+        self
+    }
+
+    /// Enables limiting the rate of incoming connections per remote IP address, using the given
+    /// configuration. Refused connections are reported with
+    /// `SwarmEvent::IncomingConnectionRateLimited`.
+    ///
+    /// Disabled by default.
+    pub fn inbound_rate_limit(mut self, config: InboundRateLimitConfig) -> Self {
+        self.inbound_rate_limit = Some(config);
+        self
+    }
+
+    /// Sets the generator of the dialing jitter of the backoff.
+    pub fn rng(mut self, rng: BoxedRng) -> Self {
+        self.rng = rng;
+        self
+    }
+
+    /// Sets the number of distinct peers that must report an external address before it is
+    /// confirmed and returned by `PollParameters::external_addresses`.
+    ///
+    /// Defaults to 1, i.e. every reported address is confirmed, as before this option existed.
+    /// A higher value protects against a single remote reporting bogus addresses.
+    pub fn external_address_confirmations(mut self, confirmations: NonZeroUsize) -> Self {
+        self.external_address_confirmations = confirmations;
+        self
+    }
+
+    /// Sets how long a connection is kept alive once its `ProtocolsHandler` returns
+    /// `KeepAlive::No`, i.e. when no protocol needs the connection anymore.
+    ///
+    /// Defaults to zero, meaning that such connections are closed immediately.
+    pub fn idle_connection_timeout(mut self, timeout: Duration) -> Self {
+        self.idle_timeout = timeout;
         self
     }
 
+    /// Sets the way the background tasks dedicated to the connections are run.
+    ///
+    /// Defaults to `TaskExecutor::Default`, which spawns the tasks on the default tokio executor
+    /// if there is one, and polls them as part of polling the `Swarm` otherwise.
+    pub fn task_executor(mut self, executor: TaskExecutor) -> Self {
+        self.executor = executor;
+        self
+    }
+
+    /// Sets the initial content of the `PeerStore`, for example as restored with
+    /// `PeerStore::load`.
+    pub fn peer_store(mut self, peer_store: PeerStore) -> Self {
+        self.peer_store = peer_store;
+        self
+    }
+
+    /// Writes the connection lifecycle and negotiation events of the `Swarm` to `writer`, as one
+    /// JSON object per line.
+    ///
+    /// Each object has a `timestamp` in milliseconds since the Unix epoch and an `event` name,
+    /// such as `connection_established`, `connection_closed`, `unreachable_addr`,
+    /// `incoming_connection` or `incoming_connection_error`, along with the peer, the addresses,
+    /// the direction of the connection and the cause of failures where applicable.
+    ///
+    /// The writer is flushed after each event. If writing fails, the log stops and the error is
+    /// returned by `Swarm::event_log_error`.
+    pub fn event_log(mut self, writer: impl io::Write + Send + 'static) -> Self {
+        self.event_log = Some(EventLog::new(Box::new(writer)));
+        self
+    }
+
+    /// Keeps the last `capacity` events of the `Swarm` in memory, including the events generated
+    /// by the `NetworkBehaviour`, so that they can be dumped when something goes wrong without
+    /// having to log them permanently.
+    ///
+    /// The events are recorded in the same format as with `event_log`, the events of the
+    /// behaviour being described with their `Debug` implementation. The history is accessed
+    /// through `Swarm::event_history`.
+    pub fn event_history(mut self, capacity: usize) -> Self
+    where
+        TBehaviour::OutEvent: fmt::Debug,
+    {
+        fn describe<TOutEvent: fmt::Debug>(event: &TOutEvent) -> Record {
+            event_log::behaviour_event(event)
+        }
+        self.event_history = Some((EventHistory::new(capacity), describe::<TBehaviour::OutEvent>));
+        self
+    }
+
+    /// Spawns the background tasks dedicated to the connections on the given executor.
+    ///
+    /// Tasks that the executor refuses are polled as part of polling the `Swarm`. The tasks are
+    /// boxed futures, like the other background tasks of libp2p, so that the same executor can
+    /// also be given to `DnsConfig::with_executor` of `libp2p-dns`.
+    pub fn executor(self, executor: impl Executor<ConnectionTask> + Send + 'static) -> Self {
+        self.task_executor(TaskExecutor::Custom(Box::new(executor)))
+    }
+
     pub fn build(mut self) -> Swarm<TTransport, TBehaviour, TConnInfo> {
         let supported_protocols = self.behaviour
             .new_handler()
@@ -547,16 +1679,33 @@ where TBehaviour: NetworkBehaviour,
             .map(|info| info.protocol_name().to_vec())
             .collect();
 
-        let network = Network::new_with_incoming_limit(self.transport, self.local_peer_id, self.incoming_limit);
+        let mut network = Network::new_with_limits(self.transport, self.local_peer_id, self.limits);
+        network.set_executor(self.executor);
+        let (commands_sender, commands) = mpsc::unbounded();
+        let rng = self.rng;
 
         ExpandedSwarm {
             network,
             behaviour: self.behaviour,
             supported_protocols,
             listened_addrs: SmallVec::new(),
-            external_addrs: Addresses::default(),
-            banned_peers: HashSet::new(),
-            send_event_to_complete: None
+            external_addrs: ExternalAddresses::new(self.external_address_confirmations),
+            banned_peers: BannedPeers::default(),
+            gater: self.gater,
+            dial_backoff: self.dial_backoff.map(move |config| DialBackoff::new(config, rng)),
+            inbound_rate_limit: self.inbound_rate_limit.map(InboundRateLimiter::new),
+            idle_timeout: self.idle_timeout,
+            peer_store: self.peer_store,
+            address_ranking: AddressRanking::new(),
+            peer_stats: PeerStatsStore::default(),
+            event_log: self.event_log,
+            event_history: self.event_history,
+            snapshots: Subscriptions::default(),
+            shutting_down: false,
+            commands,
+            commands_sender,
+            send_event_to_complete: None,
+            pending_events: VecDeque::new(),
         }
     }
 }
@@ -564,18 +1713,22 @@ where TBehaviour: NetworkBehaviour,
 #[cfg(test)]
 mod tests {
     use crate::protocols_handler::{DummyProtocolsHandler, ProtocolsHandler};
-    use crate::{NetworkBehaviour, NetworkBehaviourAction, PollParameters, SwarmBuilder};
+    use crate::{
+        AddressSource, BannedPeers, BehaviourSummary, ConnectionGater, DialBackoffConfig, DialError,
+        NetworkBehaviour, NetworkBehaviourAction, NetworkChange, PollParameters, Swarm, SwarmBuilder
+    };
     use libp2p_core::{
         ConnectedPoint,
         identity,
         Multiaddr,
         PeerId,
         PublicKey,
+        nodes::network::ConnectionLimits,
         transport::dummy::{DummyStream, DummyTransport}
     };
     use libp2p_mplex::Multiplex;
-    use futures::prelude::*;
-    use std::marker::PhantomData;
+    use futures::{future, prelude::*};
+    use std::{marker::PhantomData, time::Duration};
     use tokio_io::{AsyncRead, AsyncWrite};
     use void::Void;
 
@@ -615,6 +1768,17 @@ mod tests {
             Async::NotReady
         }
 
+        fn summarize(&self, summary: &mut BehaviourSummary) {
+            summary.insert("dummy.state", "idle");
+        }
+    }
+
+    struct DenyAllGater;
+
+    impl ConnectionGater for DenyAllGater {
+        fn allow_dial_addr(&mut self, _: &Multiaddr) -> bool {
+            false
+        }
     }
 
     fn get_random_id() -> PublicKey {
@@ -631,6 +1795,54 @@ mod tests {
         assert_eq!(swarm.network.incoming_limit(), Some(4));
     }
 
+    #[test]
+    fn test_build_swarm_with_connection_limits() {
+        let id = get_random_id();
+        let transport = DummyTransport::<(PeerId, Multiplex<DummyStream>)>::new();
+        let behaviour = DummyBehaviour{marker: PhantomData};
+        let limits = ConnectionLimits::default()
+            .with_max_pending_outgoing(Some(8))
+            .with_max_established(Some(50));
+        let swarm = SwarmBuilder::new(transport, behaviour, id.into())
+            .incoming_limit(Some(4))
+            .connection_limits(limits)
+            .build();
+        assert_eq!(swarm.network.limits().max_pending_outgoing(), Some(8));
+        assert_eq!(swarm.network.limits().max_established(), Some(50));
+        assert!(swarm.network.incoming_limit().is_none());
+    }
+
+    #[test]
+    fn connection_gater_denies_dial_addr() {
+        let id = get_random_id();
+        let transport = DummyTransport::<(PeerId, Multiplex<DummyStream>)>::new();
+        let behaviour = DummyBehaviour{marker: PhantomData};
+        let mut swarm = SwarmBuilder::new(transport, behaviour, id.into())
+            .connection_gater(DenyAllGater)
+            .build();
+        let addr: Multiaddr = "/memory/1234".parse().unwrap();
+        match Swarm::dial_addr(&mut swarm, addr) {
+            Err(DialError::Denied) => {},
+            _ => panic!("expected the dialing attempt to be denied"),
+        }
+    }
+
+    #[test]
+    fn banned_peers_expire() {
+        let mut banned = BannedPeers::default();
+        let forever = PeerId::random();
+        let expired = PeerId::random();
+        let later = PeerId::random();
+        banned.ban(forever.clone(), None);
+        banned.ban(expired.clone(), Some(Duration::from_secs(0)));
+        banned.ban(later.clone(), Some(Duration::from_secs(3600)));
+        assert!(banned.contains(&forever));
+        assert!(!banned.contains(&expired));
+        assert!(banned.contains(&later));
+        banned.unban(&later);
+        assert!(!banned.contains(&later));
+    }
+
     #[test]
     fn test_build_swarm_with_max_listeners_none() {
         let id = get_random_id();
@@ -639,4 +1851,92 @@ mod tests {
         let swarm = SwarmBuilder::new(transport, behaviour, id.into()).build();
         assert!(swarm.network.incoming_limit().is_none())
     }
+
+    #[test]
+    fn snapshots_are_sent_to_subscribers() {
+        let id = get_random_id();
+        let transport = DummyTransport::<(PeerId, Multiplex<DummyStream>)>::new();
+        let behaviour = DummyBehaviour{marker: PhantomData};
+        let mut swarm = SwarmBuilder::new(transport, behaviour, id.into()).build();
+        let mut snapshots = Swarm::subscribe_snapshots(&mut swarm, Duration::from_secs(3600));
+        let snapshot = future::poll_fn(|| {
+            let _ = Swarm::poll_event(&mut swarm);
+            snapshots.poll()
+        }).wait().unwrap().expect("the swarm is alive");
+        assert_eq!(snapshot.num_peers, 0);
+        assert_eq!(snapshot.num_connections, 0);
+        assert!(snapshot.connections_by_transport.is_empty());
+        assert_eq!(snapshot.pending_dials, 0);
+        assert_eq!(snapshot.behaviour.get("dummy.state"), Some("idle"));
+
+        drop(snapshots);
+        let _ = future::lazy(|| Swarm::poll_event(&mut swarm)).wait();
+        assert!(swarm.snapshots.is_empty());
+    }
+
+    #[test]
+    fn next_event_waits_for_an_event() {
+        let id = get_random_id();
+        let transport = DummyTransport::<(PeerId, Multiplex<DummyStream>)>::new();
+        let behaviour = DummyBehaviour{marker: PhantomData};
+        let mut swarm = SwarmBuilder::new(transport, behaviour, id.into()).build();
+        let polled = future::lazy(|| Swarm::next_event(&mut swarm).poll()).wait();
+        assert!(polled.unwrap().is_not_ready());
+    }
+
+    #[test]
+    fn network_change_resets_backoff_and_observed_addresses() {
+        let id = get_random_id();
+        let transport = DummyTransport::<(PeerId, Multiplex<DummyStream>)>::new();
+        let behaviour = DummyBehaviour{marker: PhantomData};
+        let mut swarm = SwarmBuilder::new(transport, behaviour, id.into())
+            .dial_backoff(DialBackoffConfig::default().with_initial_delay(Duration::from_secs(60)))
+            .build();
+
+        let peer_id = PeerId::random();
+        swarm.dial_backoff.as_mut().unwrap().record_failure(peer_id.clone());
+        let manual: Multiaddr = "/ip4/1.2.3.4/tcp/1".parse().unwrap();
+        let observed: Multiaddr = "/ip4/5.6.7.8/tcp/1".parse().unwrap();
+        Swarm::add_external_address(&mut swarm, manual.clone());
+        Swarm::report_external_address(&mut swarm, observed.clone(), AddressSource::Observed(PeerId::random()));
+        assert!(Swarm::is_dial_backed_off(&swarm, &peer_id));
+
+        Swarm::notify_network_change(&mut swarm, NetworkChange::ConnectivityChanged);
+        assert!(!Swarm::is_dial_backed_off(&swarm, &peer_id));
+        assert_eq!(Swarm::external_addresses(&swarm).collect::<Vec<_>>(), vec![&manual]);
+        assert_eq!(Swarm::external_address_candidates(&swarm).count(), 0);
+    }
+
+    #[test]
+    fn shutdown_completes_and_denies_dialing() {
+        let id = get_random_id();
+        let transport = DummyTransport::<(PeerId, Multiplex<DummyStream>)>::new();
+        let behaviour = DummyBehaviour{marker: PhantomData};
+        let mut swarm = SwarmBuilder::new(transport, behaviour, id.into()).build();
+        Swarm::shutdown(&mut swarm, Duration::from_secs(60)).wait().unwrap();
+
+        let addr: Multiaddr = "/memory/1234".parse().unwrap();
+        match Swarm::dial_addr(&mut swarm, addr) {
+            Err(DialError::ShuttingDown) => {},
+            _ => panic!("expected the dialing attempt to be refused"),
+        }
+    }
+
+    #[test]
+    fn handle_calls_run_when_the_swarm_is_polled() {
+        let id = get_random_id();
+        let transport = DummyTransport::<(PeerId, Multiplex<DummyStream>)>::new();
+        let behaviour = DummyBehaviour{marker: PhantomData};
+        let mut swarm = SwarmBuilder::new(transport, behaviour, id.into()).build();
+        let handle = Swarm::handle(&swarm);
+
+        let listeners = handle.clone().call(|swarm| Swarm::listeners(swarm).count());
+        let _ = future::lazy(|| Swarm::poll_event(&mut swarm)).wait();
+        assert_eq!(listeners.wait().unwrap(), 0);
+
+        let never_run = handle.call(|swarm| Swarm::listeners(swarm).count());
+        drop(swarm);
+        assert!(never_run.wait().is_err());
+        assert!(handle.is_closed());
+    }
 }
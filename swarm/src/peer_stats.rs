@@ -0,0 +1,167 @@
+// Copyright 2019 Parity Technologies (UK) Ltd.
+//
+// Permission is hereby granted, free of charge, to any person obtaining a
+// copy of this software and associated documentation files (the "Software"),
+// to deal in the Software without restriction, including without limitation
+// the rights to use, copy, modify, merge, publish, distribute, sublicense,
+// and/or sell copies of the Software, and to permit persons to whom the
+// Software is furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS
+// OR IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING
+// FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
+// DEALINGS IN THE SOFTWARE.
+use libp2p_core::PeerId;
+use std::{collections::HashMap, time::Duration};
+
+/// Weight of a new sample in the exponentially weighted moving averages.
+const SMOOTHING: f64 = 0.2;
+
+/// Latency and throughput statistics of a peer, as reported by the `NetworkBehaviour` with
+/// `NetworkBehaviourAction::ReportRoundTripTime` and `NetworkBehaviourAction::ReportTransfer`.
+///
+/// Each value is an exponentially weighted moving average of the samples, which gives more
+/// weight to the recent ones.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct PeerStats {
+    rtt: Option<f64>,
+    rtt_samples: u64,
+    download_per_sec: Option<f64>,
+    upload_per_sec: Option<f64>,
+}
+
+impl PeerStats {
+    /// Returns the average round-trip time with the peer, if any was reported.
+    pub fn rtt(&self) -> Option<Duration> {
+        self.rtt.map(|secs| Duration::from_nanos((secs * 1e9).round() as u64))
+    }
+
+    /// Returns the number of round-trip times that have been reported.
+    pub fn rtt_samples(&self) -> u64 {
+        self.rtt_samples
+    }
+
+    /// Returns the average number of bytes per second received from the peer, if any transfer
+    /// was reported.
+    pub fn download_per_sec(&self) -> Option<f64> {
+        self.download_per_sec
+    }
+
+    /// Returns the average number of bytes per second sent to the peer, if any transfer was
+    /// reported.
+    pub fn upload_per_sec(&self) -> Option<f64> {
+        self.upload_per_sec
+    }
+
+    fn add_rtt(&mut self, rtt: Duration) {
+        ewma(&mut self.rtt, secs(rtt));
+        self.rtt_samples = self.rtt_samples.saturating_add(1);
+    }
+
+    fn add_transfer(&mut self, bytes_received: u64, bytes_sent: u64, duration: Duration) {
+        let duration = secs(duration);
+        if duration <= 0.0 {
+            return
+        }
+        if bytes_received > 0 {
+            ewma(&mut self.download_per_sec, bytes_received as f64 / duration);
+        }
+        if bytes_sent > 0 {
+            ewma(&mut self.upload_per_sec, bytes_sent as f64 / duration);
+        }
+    }
+}
+
+fn secs(duration: Duration) -> f64 {
+    duration.as_secs() as f64 + f64::from(duration.subsec_nanos()) / 1e9
+}
+
+fn ewma(average: &mut Option<f64>, sample: f64) {
+    *average = Some(match *average {
+        Some(average) => average + SMOOTHING * (sample - average),
+        None => sample,
+    });
+}
+
+/// Statistics of the peers we are connected to.
+#[derive(Debug, Default)]
+pub(crate) struct PeerStatsStore {
+    peers: HashMap<PeerId, PeerStats>,
+}
+
+impl PeerStatsStore {
+    /// Returns the statistics of a peer, if any sample was reported.
+    pub(crate) fn get(&self, peer_id: &PeerId) -> Option<&PeerStats> {
+        self.peers.get(peer_id)
+    }
+
+    /// Returns the peers with a known round-trip time, from the lowest to the highest.
+    pub(crate) fn peers_by_rtt(&self) -> Vec<(PeerId, Duration)> {
+        let mut peers = self.peers.iter()
+            .filter_map(|(peer_id, stats)| stats.rtt().map(|rtt| (peer_id.clone(), rtt)))
+            .collect::<Vec<_>>();
+        peers.sort_by_key(|(_, rtt)| *rtt);
+        peers
+    }
+
+    /// Adds a round-trip time sample.
+    pub(crate) fn add_rtt(&mut self, peer_id: PeerId, rtt: Duration) {
+        self.peers.entry(peer_id).or_default().add_rtt(rtt)
+    }
+
+    /// Adds a transfer sample.
+    pub(crate) fn add_transfer(&mut self, peer_id: PeerId, bytes_received: u64, bytes_sent: u64, duration: Duration) {
+        self.peers.entry(peer_id).or_default().add_transfer(bytes_received, bytes_sent, duration)
+    }
+
+    /// Forgets about a peer, once we are disconnected from it.
+    pub(crate) fn remove(&mut self, peer_id: &PeerId) {
+        self.peers.remove(peer_id);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn averages_favour_recent_samples() {
+        let mut stats = PeerStatsStore::default();
+        let peer_id = PeerId::random();
+        assert!(stats.get(&peer_id).is_none());
+
+        stats.add_rtt(peer_id.clone(), Duration::from_millis(100));
+        assert_eq!(stats.get(&peer_id).unwrap().rtt(), Some(Duration::from_millis(100)));
+        stats.add_rtt(peer_id.clone(), Duration::from_millis(200));
+        let rtt = stats.get(&peer_id).unwrap().rtt().unwrap();
+        assert!(rtt > Duration::from_millis(119) && rtt < Duration::from_millis(121));
+        assert_eq!(stats.get(&peer_id).unwrap().rtt_samples(), 2);
+
+        stats.add_transfer(peer_id.clone(), 1000, 0, Duration::from_millis(500));
+        assert_eq!(stats.get(&peer_id).unwrap().download_per_sec(), Some(2000.0));
+        assert_eq!(stats.get(&peer_id).unwrap().upload_per_sec(), None);
+
+        stats.remove(&peer_id);
+        assert!(stats.get(&peer_id).is_none());
+    }
+
+    #[test]
+    fn peers_sorted_by_rtt() {
+        let mut stats = PeerStatsStore::default();
+        let (slow, fast, unknown) = (PeerId::random(), PeerId::random(), PeerId::random());
+        stats.add_rtt(slow.clone(), Duration::from_millis(300));
+        stats.add_rtt(fast.clone(), Duration::from_millis(20));
+        stats.add_transfer(unknown, 1000, 1000, Duration::from_secs(1));
+
+        assert_eq!(stats.peers_by_rtt(), vec![
+            (fast, Duration::from_millis(20)),
+            (slow, Duration::from_millis(300)),
+        ]);
+    }
+}
@@ -0,0 +1,576 @@
+// Copyright 2019 Parity Technologies (UK) Ltd.
+//
+// Permission is hereby granted, free of charge, to any person obtaining a
+// copy of this software and associated documentation files (the "Software"),
+// to deal in the Software without restriction, including without limitation
+// the rights to use, copy, modify, merge, publish, distribute, sublicense,
+// and/or sell copies of the Software, and to permit persons to whom the
+// Software is furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS
+// OR IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING
+// FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
+// DEALINGS IN THE SOFTWARE.
+
+use libp2p_core::{Multiaddr, PeerId};
+use std::{
+    cmp,
+    collections::{HashMap, hash_map::DefaultHasher},
+    convert::TryFrom,
+    fs,
+    hash::{Hash, Hasher},
+    io::{self, BufRead, Write},
+    path::Path,
+    sync::Arc,
+    time::Duration,
+};
+use wasm_timer::Instant;
+
+/// How long the address of a peer we successfully dialed is remembered by default.
+pub const CONNECTED_ADDRESS_TTL: Duration = Duration::from_secs(60 * 60);
+
+/// Maximum time-to-live of an address. Longer time-to-lives are capped to this value.
+pub const MAX_ADDRESS_TTL: Duration = Duration::from_secs(365 * 24 * 60 * 60);
+
+/// Address book shared by all the behaviours of a `Swarm`.
+///
+/// Records, for each peer, the addresses it can be reached at, the protocols it supports and
+/// arbitrary metadata. Addresses expire after the time-to-live they were added with.
+///
+/// The `Swarm` consults the store when dialing a peer, in addition to the addresses returned by
+/// `NetworkBehaviour::addresses_of_peer`, and records the addresses of the peers it successfully
+/// dials. The store can be saved to disk with [`PeerStore::save`] and restored with
+/// [`PeerStore::load`].
+///
+/// Other tasks can read the store without going through the `Swarm` by holding a
+/// [`PeerStoreSnapshot`], obtained with [`PeerStore::snapshot`]. Peers are spread over a fixed
+/// number of shards, each behind an `Arc`. Taking a snapshot only clones these `Arc`s, and
+/// reading from it never takes a lock. The first write to a shard that a snapshot still refers
+/// to copies that shard, and the following writes to it are applied in place until the next
+/// snapshot is taken. Writes are therefore batched between snapshots, and a single write
+/// never copies more than one shard.
+#[derive(Debug, Clone, Default)]
+pub struct PeerStore {
+    shards: Shards,
+}
+
+/// Read-only view of a [`PeerStore`] at the time [`PeerStore::snapshot`] was called.
+///
+/// Cheap to clone, and can be sent to and read from any thread without synchronization.
+#[derive(Debug, Clone, Default)]
+pub struct PeerStoreSnapshot {
+    shards: Shards,
+}
+
+/// Number of shards the peers of a store are spread over.
+const NUM_SHARDS: usize = 16;
+
+/// The peers of a store, spread over `NUM_SHARDS` copy-on-write maps.
+#[derive(Debug, Clone)]
+struct Shards(Vec<Arc<HashMap<PeerId, PeerRecord>>>);
+
+impl Default for Shards {
+    fn default() -> Self {
+        Shards((0 .. NUM_SHARDS).map(|_| Arc::new(HashMap::new())).collect())
+    }
+}
+
+impl Shards {
+    fn index(peer_id: &PeerId) -> usize {
+        let mut hasher = DefaultHasher::new();
+        peer_id.hash(&mut hasher);
+        (hasher.finish() % NUM_SHARDS as u64) as usize
+    }
+
+    fn get(&self, peer_id: &PeerId) -> Option<&PeerRecord> {
+        self.0[Shards::index(peer_id)].get(peer_id)
+    }
+
+    /// Returns the shard of the given peer for writing, copying it if a snapshot refers to it.
+    fn shard_mut(&mut self, peer_id: &PeerId) -> &mut HashMap<PeerId, PeerRecord> {
+        Arc::make_mut(&mut self.0[Shards::index(peer_id)])
+    }
+
+    /// Like `shard_mut`, but leaves the shard untouched if the peer isn't known.
+    fn get_mut(&mut self, peer_id: &PeerId) -> Option<&mut PeerRecord> {
+        if self.get(peer_id).is_none() {
+            return None;
+        }
+        self.shard_mut(peer_id).get_mut(peer_id)
+    }
+
+    fn iter(&self) -> impl Iterator<Item = (&PeerId, &PeerRecord)> {
+        self.0.iter().flat_map(|shard| shard.iter())
+    }
+
+    fn addresses<'a>(&'a self, peer_id: &PeerId) -> impl Iterator<Item = &'a Multiaddr> + 'a {
+        let now = Instant::now();
+        self.get(peer_id)
+            .into_iter()
+            .flat_map(move |r| r.addresses.iter().filter(move |(_, e)| *e > now).map(|(a, _)| a))
+    }
+
+    fn protocols<'a>(&'a self, peer_id: &PeerId) -> impl Iterator<Item = &'a str> + 'a {
+        self.get(peer_id)
+            .into_iter()
+            .flat_map(|r| r.protocols.iter().map(|p| p.as_str()))
+    }
+
+    fn metadata(&self, peer_id: &PeerId, key: &str) -> Option<&[u8]> {
+        self.get(peer_id)
+            .and_then(|r| r.metadata.get(key))
+            .map(|v| v.as_slice())
+    }
+}
+
+/// Everything known about a peer.
+#[derive(Debug, Clone, Default)]
+struct PeerRecord {
+    /// Known addresses, with when they expire.
+    addresses: Vec<(Multiaddr, Instant)>,
+    /// Protocols the peer supports.
+    protocols: Vec<String>,
+    /// Arbitrary metadata.
+    metadata: HashMap<String, Vec<u8>>,
+}
+
+impl PeerRecord {
+    fn is_empty(&self) -> bool {
+        self.addresses.is_empty() && self.protocols.is_empty() && self.metadata.is_empty()
+    }
+}
+
+impl PeerStore {
+    /// Creates an empty store.
+    pub fn new() -> Self {
+        PeerStore::default()
+    }
+
+    /// Returns a read-only view of the current content of the store.
+    ///
+    /// The snapshot isn't affected by later modifications of the store.
+    pub fn snapshot(&self) -> PeerStoreSnapshot {
+        PeerStoreSnapshot { shards: self.shards.clone() }
+    }
+
+    /// Adds an address for the given peer, valid for `ttl`.
+    ///
+    /// If the address is already known, its expiration is pushed back if needed but never
+    /// brought forward. `ttl` is capped to [`MAX_ADDRESS_TTL`].
+    pub fn add_address(&mut self, peer_id: PeerId, addr: Multiaddr, ttl: Duration) {
+        let expires = Instant::now() + cmp::min(ttl, MAX_ADDRESS_TTL);
+        let record = self.shards.shard_mut(&peer_id).entry(peer_id).or_default();
+        if let Some(entry) = record.addresses.iter_mut().find(|(a, _)| *a == addr) {
+            if entry.1 < expires {
+                entry.1 = expires;
+            }
+        } else {
+            record.addresses.push((addr, expires));
+        }
+    }
+
+    /// Removes an address of the given peer. Returns `true` if the address was known.
+    pub fn remove_address(&mut self, peer_id: &PeerId, addr: &Multiaddr) -> bool {
+        let known = self.shards.get(peer_id)
+            .map_or(false, |r| r.addresses.iter().any(|(a, _)| a == addr));
+        if !known {
+            return false;
+        }
+        if let Some(record) = self.shards.get_mut(peer_id) {
+            record.addresses.retain(|(a, _)| a != addr);
+        }
+        self.remove_if_empty(peer_id);
+        true
+    }
+
+    /// Returns the addresses of the given peer that haven't expired.
+    pub fn addresses<'a>(&'a self, peer_id: &PeerId) -> impl Iterator<Item = &'a Multiaddr> + 'a {
+        self.shards.addresses(peer_id)
+    }
+
+    /// Sets the protocols supported by the given peer, replacing the previous ones.
+    pub fn set_protocols<I>(&mut self, peer_id: PeerId, protocols: I)
+    where
+        I: IntoIterator<Item = String>,
+    {
+        let protocols = protocols.into_iter().collect::<Vec<_>>();
+        let unchanged = self.shards.get(&peer_id)
+            .map_or(protocols.is_empty(), |r| r.protocols == protocols);
+        if unchanged {
+            return;
+        }
+        self.shards.shard_mut(&peer_id).entry(peer_id.clone()).or_default().protocols = protocols;
+        self.remove_if_empty(&peer_id);
+    }
+
+    /// Returns the protocols supported by the given peer.
+    pub fn protocols<'a>(&'a self, peer_id: &PeerId) -> impl Iterator<Item = &'a str> + 'a {
+        self.shards.protocols(peer_id)
+    }
+
+    /// Returns `true` if the given peer is known to support the protocol.
+    pub fn supports_protocol(&self, peer_id: &PeerId, protocol: &str) -> bool {
+        self.protocols(peer_id).any(|p| p == protocol)
+    }
+
+    /// Sets a metadata entry of the given peer.
+    pub fn set_metadata(&mut self, peer_id: PeerId, key: impl Into<String>, value: Vec<u8>) {
+        self.shards.shard_mut(&peer_id).entry(peer_id).or_default().metadata.insert(key.into(), value);
+    }
+
+    /// Returns a metadata entry of the given peer.
+    pub fn metadata(&self, peer_id: &PeerId, key: &str) -> Option<&[u8]> {
+        self.shards.metadata(peer_id, key)
+    }
+
+    /// Removes a metadata entry of the given peer and returns it.
+    pub fn remove_metadata(&mut self, peer_id: &PeerId, key: &str) -> Option<Vec<u8>> {
+        if self.shards.metadata(peer_id, key).is_none() {
+            return None;
+        }
+        let removed = self.shards.get_mut(peer_id).and_then(|r| r.metadata.remove(key));
+        self.remove_if_empty(peer_id);
+        removed
+    }
+
+    /// Forgets everything about the given peer.
+    pub fn remove_peer(&mut self, peer_id: &PeerId) {
+        if self.shards.get(peer_id).is_some() {
+            self.shards.shard_mut(peer_id).remove(peer_id);
+        }
+    }
+
+    /// Returns the list of peers the store knows about.
+    pub fn peers(&self) -> impl Iterator<Item = &PeerId> {
+        self.shards.iter().map(|(peer_id, _)| peer_id)
+    }
+
+    /// Removes the addresses that have expired, and the peers nothing is known about anymore.
+    ///
+    /// Only the shards containing expired addresses are modified.
+    pub fn prune(&mut self) {
+        let now = Instant::now();
+        for shard in self.shards.0.iter_mut() {
+            let expired = shard.values()
+                .any(|r| r.is_empty() || r.addresses.iter().any(|(_, e)| *e <= now));
+            if !expired {
+                continue;
+            }
+            Arc::make_mut(shard).retain(|_, record| {
+                record.addresses.retain(|(_, e)| *e > now);
+                !record.is_empty()
+            });
+        }
+    }
+
+    /// Adds everything known by `other` to the store.
+    ///
+    /// Addresses known by both stores expire at the latest of their expirations.
+    pub fn merge(&mut self, other: &PeerStore) {
+        for (peer_id, theirs) in other.shards.iter() {
+            let record = self.shards.shard_mut(peer_id).entry(peer_id.clone()).or_default();
+            for (addr, expires) in &theirs.addresses {
+                match record.addresses.iter_mut().find(|(a, _)| a == addr) {
+                    Some((_, e)) => *e = std::cmp::max(*e, *expires),
+                    None => record.addresses.push((addr.clone(), *expires)),
+                }
+            }
+            for protocol in &theirs.protocols {
+                if !record.protocols.contains(protocol) {
+                    record.protocols.push(protocol.clone());
+                }
+            }
+            for (key, value) in &theirs.metadata {
+                record.metadata.insert(key.clone(), value.clone());
+            }
+        }
+    }
+
+    /// Writes the content of the store in a line-based text format.
+    ///
+    /// Addresses, protocols and metadata are hex-encoded, so that values reported by remotes
+    /// can't inject entries in the output. Protocols containing a line break, which no legitimate
+    /// protocol name does, are skipped, as are expired addresses. The remaining time-to-live of the other addresses is
+    /// written, so that they expire at the same time once restored.
+    pub fn write_to<W: Write>(&self, mut writer: W) -> io::Result<()> {
+        let now = Instant::now();
+        for (peer_id, record) in self.shards.iter() {
+            writeln!(writer, "peer {}", peer_id.to_base58())?;
+            for (addr, expires) in &record.addresses {
+                if *expires > now {
+                    writeln!(writer, "addr {} {}", to_hex(&addr.to_vec()), (*expires - now).as_secs())?;
+                }
+            }
+            for protocol in record.protocols.iter().filter(|p| !is_multiline(p)) {
+                writeln!(writer, "proto {}", to_hex(protocol.as_bytes()))?;
+            }
+            for (key, value) in &record.metadata {
+                writeln!(writer, "meta {} {}", to_hex(key.as_bytes()), to_hex(value))?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Reads a store previously written with [`PeerStore::write_to`].
+    pub fn read_from<R: BufRead>(reader: R) -> io::Result<Self> {
+        let mut store = PeerStore::new();
+        let mut current = None;
+        for line in reader.lines() {
+            let line = line?;
+            if line.is_empty() {
+                continue;
+            }
+            let (kind, rest) = match line.find(' ') {
+                Some(pos) => (&line[.. pos], &line[pos + 1 ..]),
+                None => return Err(invalid_data("missing value")),
+            };
+            if kind == "peer" {
+                let peer_id = rest.parse::<PeerId>().map_err(|_| invalid_data("invalid peer ID"))?;
+                current = Some(peer_id);
+                continue;
+            }
+            let peer_id = current.clone().ok_or_else(|| invalid_data("entry without a peer"))?;
+            match kind {
+                "addr" => {
+                    let mut parts = rest.split(' ');
+                    let addr = parts.next()
+                        .and_then(from_hex)
+                        .and_then(|a| Multiaddr::try_from(a).ok())
+                        .ok_or_else(|| invalid_data("invalid address"))?;
+                    let ttl = parts.next()
+                        .and_then(|t| t.parse::<u64>().ok())
+                        .ok_or_else(|| invalid_data("invalid time-to-live"))?;
+                    store.add_address(peer_id, addr, Duration::from_secs(ttl));
+                }
+                "proto" => {
+                    let protocol = from_hex(rest)
+                        .and_then(|p| String::from_utf8(p).ok())
+                        .filter(|p| !is_multiline(p))
+                        .ok_or_else(|| invalid_data("invalid protocol"))?;
+                    store.shards.shard_mut(&peer_id).entry(peer_id).or_default().protocols.push(protocol);
+                }
+                "meta" => {
+                    let mut parts = rest.split(' ');
+                    let key = parts.next()
+                        .and_then(from_hex)
+                        .and_then(|k| String::from_utf8(k).ok())
+                        .ok_or_else(|| invalid_data("invalid metadata key"))?;
+                    let value = parts.next()
+                        .and_then(from_hex)
+                        .ok_or_else(|| invalid_data("invalid metadata value"))?;
+                    store.set_metadata(peer_id, key, value);
+                }
+                _ => return Err(invalid_data("unknown entry")),
+            }
+        }
+        Ok(store)
+    }
+
+    /// Saves the store to the given file, replacing its content.
+    ///
+    /// The store is written to a temporary file next to `path`, which then replaces `path`, so
+    /// that the previous content is kept if writing fails.
+    pub fn save(&self, path: impl AsRef<Path>) -> io::Result<()> {
+        save_atomically(path.as_ref(), |file| self.write_to(file))
+    }
+
+    /// Loads a store from a file previously written with [`PeerStore::save`].
+    pub fn load(path: impl AsRef<Path>) -> io::Result<Self> {
+        let file = fs::File::open(path)?;
+        PeerStore::read_from(io::BufReader::new(file))
+    }
+
+    fn remove_if_empty(&mut self, peer_id: &PeerId) {
+        if self.shards.get(peer_id).map_or(false, |r| r.is_empty()) {
+            self.shards.shard_mut(peer_id).remove(peer_id);
+        }
+    }
+}
+
+impl PeerStoreSnapshot {
+    /// Returns the addresses of the given peer that haven't expired.
+    pub fn addresses<'a>(&'a self, peer_id: &PeerId) -> impl Iterator<Item = &'a Multiaddr> + 'a {
+        self.shards.addresses(peer_id)
+    }
+
+    /// Returns the protocols supported by the given peer.
+    pub fn protocols<'a>(&'a self, peer_id: &PeerId) -> impl Iterator<Item = &'a str> + 'a {
+        self.shards.protocols(peer_id)
+    }
+
+    /// Returns `true` if the given peer is known to support the protocol.
+    pub fn supports_protocol(&self, peer_id: &PeerId, protocol: &str) -> bool {
+        self.protocols(peer_id).any(|p| p == protocol)
+    }
+
+    /// Returns a metadata entry of the given peer.
+    pub fn metadata(&self, peer_id: &PeerId, key: &str) -> Option<&[u8]> {
+        self.shards.metadata(peer_id, key)
+    }
+
+    /// Returns the list of peers the snapshot knows about.
+    pub fn peers(&self) -> impl Iterator<Item = &PeerId> {
+        self.shards.iter().map(|(peer_id, _)| peer_id)
+    }
+}
+
+/// Writes a file with `write` by writing to a temporary file in the same directory, which is
+/// synced to the disk and then renamed to `path`.
+pub(crate) fn save_atomically<F>(path: &Path, write: F) -> io::Result<()>
+where
+    F: FnOnce(&mut io::BufWriter<fs::File>) -> io::Result<()>
+{
+    let mut tmp_name = path.file_name()
+        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "path without a file name"))?
+        .to_os_string();
+    tmp_name.push(".tmp");
+    let tmp_path = path.with_file_name(tmp_name);
+
+    let result = (|| -> io::Result<()> {
+        let mut file = io::BufWriter::new(fs::File::create(&tmp_path)?);
+        write(&mut file)?;
+        file.into_inner()?.sync_all()?;
+        fs::rename(&tmp_path, path)
+    })();
+    if result.is_err() {
+        let _ = fs::remove_file(&tmp_path);
+    }
+    result
+}
+
+fn is_multiline(s: &str) -> bool {
+    s.contains(|c| c == '\n' || c == '\r')
+}
+
+pub(crate) fn invalid_data(msg: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, msg)
+}
+
+pub(crate) fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+pub(crate) fn from_hex(s: &str) -> Option<Vec<u8>> {
+    if s.len() % 2 != 0 {
+        return None;
+    }
+    (0 .. s.len())
+        .step_by(2)
+        .map(|i| s.get(i .. i + 2).and_then(|b| u8::from_str_radix(b, 16).ok()))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use libp2p_core::PeerId;
+
+    #[test]
+    fn expired_addresses_are_ignored() {
+        let mut store = PeerStore::new();
+        let peer_id = PeerId::random();
+        let addr1: Multiaddr = "/ip4/127.0.0.1/tcp/1234".parse().unwrap();
+        let addr2: Multiaddr = "/ip4/127.0.0.1/tcp/5678".parse().unwrap();
+        store.add_address(peer_id.clone(), addr1.clone(), Duration::from_secs(60));
+        store.add_address(peer_id.clone(), addr2, Duration::from_secs(0));
+        assert_eq!(store.addresses(&peer_id).collect::<Vec<_>>(), vec![&addr1]);
+
+        store.prune();
+        assert!(store.remove_address(&peer_id, &addr1));
+        assert_eq!(store.peers().count(), 0);
+    }
+
+    #[test]
+    fn write_then_read() {
+        let mut store = PeerStore::new();
+        let peer_id = PeerId::random();
+        let addr: Multiaddr = "/ip4/127.0.0.1/tcp/1234".parse().unwrap();
+        store.add_address(peer_id.clone(), addr.clone(), Duration::from_secs(600));
+        store.set_protocols(peer_id.clone(), vec!["/ipfs/ping/1.0.0".to_owned()]);
+        store.set_metadata(peer_id.clone(), "agent version", b"rust-libp2p".to_vec());
+
+        let mut buf = Vec::new();
+        store.write_to(&mut buf).unwrap();
+        let restored = PeerStore::read_from(&buf[..]).unwrap();
+
+        assert_eq!(restored.addresses(&peer_id).collect::<Vec<_>>(), vec![&addr]);
+        assert!(restored.supports_protocol(&peer_id, "/ipfs/ping/1.0.0"));
+        assert_eq!(restored.metadata(&peer_id, "agent version"), Some(&b"rust-libp2p"[..]));
+    }
+
+    #[test]
+    fn merge_keeps_latest_expiration() {
+        let peer_id = PeerId::random();
+        let addr: Multiaddr = "/ip4/127.0.0.1/tcp/1234".parse().unwrap();
+        let mut store = PeerStore::new();
+        store.add_address(peer_id.clone(), addr.clone(), Duration::from_secs(0));
+        let mut other = PeerStore::new();
+        other.add_address(peer_id.clone(), addr.clone(), Duration::from_secs(600));
+        other.set_protocols(peer_id.clone(), vec!["/ipfs/ping/1.0.0".to_owned()]);
+
+        store.merge(&other);
+        assert_eq!(store.addresses(&peer_id).collect::<Vec<_>>(), vec![&addr]);
+        assert!(store.supports_protocol(&peer_id, "/ipfs/ping/1.0.0"));
+    }
+
+    #[test]
+    fn remote_values_cant_inject_entries() {
+        let mut store = PeerStore::new();
+        let peer_id = PeerId::random();
+        let forged = PeerId::random();
+        let forged_protocol = format!("/x\npeer {}\naddr /ip4/1.2.3.4/tcp/1 600", forged.to_base58());
+        store.set_protocols(peer_id.clone(), vec!["/ipfs/ping/1.0.0".to_owned(), forged_protocol.clone()]);
+
+        let mut buf = Vec::new();
+        store.write_to(&mut buf).unwrap();
+        let restored = PeerStore::read_from(&buf[..]).unwrap();
+
+        assert_eq!(restored.peers().collect::<Vec<_>>(), vec![&peer_id]);
+        assert_eq!(restored.protocols(&peer_id).collect::<Vec<_>>(), vec!["/ipfs/ping/1.0.0"]);
+
+        let file = format!("peer {}\nproto {}\n", peer_id.to_base58(), to_hex(forged_protocol.as_bytes()));
+        assert!(PeerStore::read_from(file.as_bytes()).is_err());
+    }
+
+    #[test]
+    fn oversized_ttl_is_capped() {
+        let peer_id = PeerId::random();
+        let addr: Multiaddr = "/ip4/127.0.0.1/tcp/1234".parse().unwrap();
+        let file = format!("peer {}\naddr {} {}\n", peer_id.to_base58(), to_hex(&addr.to_vec()), u64::max_value());
+
+        let restored = PeerStore::read_from(file.as_bytes()).unwrap();
+        assert_eq!(restored.addresses(&peer_id).collect::<Vec<_>>(), vec![&addr]);
+
+        let mut buf = Vec::new();
+        restored.write_to(&mut buf).unwrap();
+        let ttl = String::from_utf8(buf).unwrap().lines()
+            .find(|l| l.starts_with("addr "))
+            .and_then(|l| l.rsplit(' ').next().and_then(|t| t.parse::<u64>().ok()))
+            .unwrap();
+        assert!(ttl <= MAX_ADDRESS_TTL.as_secs());
+    }
+
+    #[test]
+    fn snapshot_is_not_affected_by_writes() {
+        let mut store = PeerStore::new();
+        let peer1 = PeerId::random();
+        let peer2 = PeerId::random();
+        let addr: Multiaddr = "/ip4/127.0.0.1/tcp/1234".parse().unwrap();
+        store.add_address(peer1.clone(), addr.clone(), Duration::from_secs(60));
+
+        let snapshot = store.snapshot();
+        store.remove_peer(&peer1);
+        store.set_protocols(peer2.clone(), vec!["/ipfs/ping/1.0.0".to_owned()]);
+
+        assert_eq!(snapshot.addresses(&peer1).collect::<Vec<_>>(), vec![&addr]);
+        assert!(!snapshot.supports_protocol(&peer2, "/ipfs/ping/1.0.0"));
+        assert_eq!(store.addresses(&peer1).count(), 0);
+        assert!(store.snapshot().supports_protocol(&peer2, "/ipfs/ping/1.0.0"));
+    }
+}
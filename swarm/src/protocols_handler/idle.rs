@@ -0,0 +1,308 @@
+// Copyright 2019 Parity Technologies (UK) Ltd.
+//
+// Permission is hereby granted, free of charge, to any person obtaining a
+// copy of this software and associated documentation files (the "Software"),
+// to deal in the Software without restriction, including without limitation
+// the rights to use, copy, modify, merge, publish, distribute, sublicense,
+// and/or sell copies of the Software, and to permit persons to whom the
+// Software is furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS
+// OR IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING
+// FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
+// DEALINGS IN THE SOFTWARE.
+
+use futures::prelude::*;
+use std::{error, fmt, io, time::Duration};
+use tokio_io::{AsyncRead, AsyncWrite};
+use wasm_timer::{Delay, Instant};
+
+/// Wraps around a substream, or around the output of an upgrade applied on a substream, and
+/// produces an error once nothing has been sent or received for a certain duration.
+///
+/// Handlers that keep substreams around after they have been negotiated can use this wrapper
+/// so that substreams whose remote never answers are dropped instead of accumulating until
+/// the connection closes. Any successful read, write, received item or accepted item resets
+/// the timer.
+///
+/// When used as a `Stream` or a `Sink`, expiration produces [`IdleTimeoutError::Idle`]. When
+/// used as an `AsyncRead` or `AsyncWrite`, it produces an `io::Error` of kind `TimedOut`.
+pub struct IdleTimeout<T> {
+    /// The wrapped substream.
+    inner: T,
+    /// Duration of inactivity after which the substream is considered idle.
+    timeout: Duration,
+    /// Fires when the substream becomes idle.
+    delay: Delay,
+}
+
+impl<T> IdleTimeout<T> {
+    /// Wraps around `inner`. The substream is considered idle if no activity happens on it for
+    /// `timeout`.
+    pub fn new(inner: T, timeout: Duration) -> Self {
+        IdleTimeout {
+            inner,
+            timeout,
+            delay: Delay::new(Instant::now() + timeout),
+        }
+    }
+
+    /// Returns the configured idle timeout.
+    pub fn timeout(&self) -> Duration {
+        self.timeout
+    }
+
+    /// Returns a reference to the wrapped substream.
+    pub fn get_ref(&self) -> &T {
+        &self.inner
+    }
+
+    /// Returns a mutable reference to the wrapped substream.
+    ///
+    /// > **Note**: Activity happening through this reference doesn't reset the timer.
+    pub fn get_mut(&mut self) -> &mut T {
+        &mut self.inner
+    }
+
+    /// Destroys the wrapper and returns the substream.
+    pub fn into_inner(self) -> T {
+        self.inner
+    }
+
+    /// Pushes back the moment the substream is considered idle.
+    fn reset(&mut self) {
+        self.delay.reset(Instant::now() + self.timeout);
+    }
+
+    /// Returns `true` if the substream is idle. Otherwise, the current task will be notified
+    /// once the timeout elapses.
+    fn is_idle(&mut self) -> bool {
+        match self.delay.poll() {
+            Ok(Async::NotReady) => false,
+            // If the timer is broken we can no longer enforce the timeout, in which case we
+            // prefer closing the substream over leaking it.
+            Ok(Async::Ready(())) | Err(_) => true,
+        }
+    }
+
+    /// Builds the `io::Error` returned through `AsyncRead` and `AsyncWrite`.
+    fn io_error() -> io::Error {
+        io::Error::new(io::ErrorKind::TimedOut, "substream has been idle for too long")
+    }
+}
+
+impl<T> fmt::Debug for IdleTimeout<T>
+where
+    T: fmt::Debug
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("IdleTimeout")
+            .field("inner", &self.inner)
+            .field("timeout", &self.timeout)
+            .finish()
+    }
+}
+
+impl<T> Stream for IdleTimeout<T>
+where
+    T: Stream
+{
+    type Item = T::Item;
+    type Error = IdleTimeoutError<T::Error>;
+
+    fn poll(&mut self) -> Poll<Option<Self::Item>, Self::Error> {
+        if let Async::Ready(item) = self.inner.poll().map_err(IdleTimeoutError::Inner)? {
+            self.reset();
+            return Ok(Async::Ready(item));
+        }
+
+        if self.is_idle() {
+            return Err(IdleTimeoutError::Idle);
+        }
+
+        Ok(Async::NotReady)
+    }
+}
+
+impl<T> Sink for IdleTimeout<T>
+where
+    T: Sink
+{
+    type SinkItem = T::SinkItem;
+    type SinkError = IdleTimeoutError<T::SinkError>;
+
+    fn start_send(&mut self, item: Self::SinkItem) -> StartSend<Self::SinkItem, Self::SinkError> {
+        match self.inner.start_send(item).map_err(IdleTimeoutError::Inner)? {
+            AsyncSink::Ready => {
+                self.reset();
+                Ok(AsyncSink::Ready)
+            }
+            AsyncSink::NotReady(item) => {
+                if self.is_idle() {
+                    return Err(IdleTimeoutError::Idle);
+                }
+                Ok(AsyncSink::NotReady(item))
+            }
+        }
+    }
+
+    fn poll_complete(&mut self) -> Poll<(), Self::SinkError> {
+        if let Async::Ready(()) = self.inner.poll_complete().map_err(IdleTimeoutError::Inner)? {
+            return Ok(Async::Ready(()));
+        }
+
+        if self.is_idle() {
+            return Err(IdleTimeoutError::Idle);
+        }
+
+        Ok(Async::NotReady)
+    }
+
+    fn close(&mut self) -> Poll<(), Self::SinkError> {
+        self.inner.close().map_err(IdleTimeoutError::Inner)
+    }
+}
+
+impl<T> io::Read for IdleTimeout<T>
+where
+    T: io::Read
+{
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        match self.inner.read(buf) {
+            Ok(n) => {
+                self.reset();
+                Ok(n)
+            }
+            Err(ref err) if err.kind() == io::ErrorKind::WouldBlock && self.is_idle() =>
+                Err(Self::io_error()),
+            Err(err) => Err(err),
+        }
+    }
+}
+
+impl<T> AsyncRead for IdleTimeout<T>
+where
+    T: AsyncRead
+{
+    unsafe fn prepare_uninitialized_buffer(&self, buf: &mut [u8]) -> bool {
+        self.inner.prepare_uninitialized_buffer(buf)
+    }
+}
+
+impl<T> io::Write for IdleTimeout<T>
+where
+    T: io::Write
+{
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        match self.inner.write(buf) {
+            Ok(n) => {
+                self.reset();
+                Ok(n)
+            }
+            Err(ref err) if err.kind() == io::ErrorKind::WouldBlock && self.is_idle() =>
+                Err(Self::io_error()),
+            Err(err) => Err(err),
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match self.inner.flush() {
+            Err(ref err) if err.kind() == io::ErrorKind::WouldBlock && self.is_idle() =>
+                Err(Self::io_error()),
+            other => other,
+        }
+    }
+}
+
+impl<T> AsyncWrite for IdleTimeout<T>
+where
+    T: AsyncWrite
+{
+    fn shutdown(&mut self) -> Poll<(), io::Error> {
+        self.inner.shutdown()
+    }
+}
+
+/// Error produced by an [`IdleTimeout`] used as a `Stream` or a `Sink`.
+#[derive(Debug)]
+pub enum IdleTimeoutError<TErr> {
+    /// Nothing has been sent or received on the substream for the configured duration.
+    Idle,
+    /// Error produced by the wrapped substream.
+    Inner(TErr),
+}
+
+impl<TErr> IdleTimeoutError<TErr> {
+    /// Returns `true` if the error is the result of the substream being idle.
+    pub fn is_idle(&self) -> bool {
+        match self {
+            IdleTimeoutError::Idle => true,
+            IdleTimeoutError::Inner(_) => false,
+        }
+    }
+}
+
+impl<TErr> fmt::Display for IdleTimeoutError<TErr>
+where
+    TErr: fmt::Display
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            IdleTimeoutError::Idle => write!(f, "Substream has been idle for too long"),
+            IdleTimeoutError::Inner(err) => write!(f, "{}", err),
+        }
+    }
+}
+
+impl<TErr> error::Error for IdleTimeoutError<TErr>
+where
+    TErr: error::Error + 'static
+{
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        match self {
+            IdleTimeoutError::Idle => None,
+            IdleTimeoutError::Inner(err) => Some(err),
+        }
+    }
+}
+
+/// Event produced by the [`OneShotHandler`] when an outbound substream
+/// didn't finish within the configured substream timeout and has been closed.
+///
+/// Contrary to other upgrade errors, this doesn't close the connection.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct SubstreamTimedOut;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use futures::{future, sync::mpsc};
+
+    #[test]
+    fn idle_stream_errors() {
+        let (_tx, rx) = mpsc::channel::<u32>(1);
+        let stream = IdleTimeout::new(rx, Duration::from_millis(50));
+        match stream.into_future().wait() {
+            Err((err, _)) => assert!(err.is_idle()),
+            Ok(_) => panic!("idle stream produced an item"),
+        }
+    }
+
+    #[test]
+    fn activity_resets_timer() {
+        let (tx, rx) = mpsc::channel::<u32>(4);
+        let mut stream = IdleTimeout::new(rx, Duration::from_secs(60));
+        let tx = tx.send(1).wait().unwrap();
+        let item = future::poll_fn(|| stream.poll()).wait().unwrap();
+        assert_eq!(item, Some(1));
+        drop(tx);
+        let item = future::poll_fn(|| stream.poll()).wait().unwrap();
+        assert_eq!(item, None);
+    }
+}
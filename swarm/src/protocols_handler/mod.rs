@@ -38,6 +38,7 @@
 //! >           the network as a whole, see the `NetworkBehaviour` trait.
 
 mod dummy;
+mod idle;
 mod map_in;
 mod map_out;
 mod node_handler;
@@ -55,6 +56,7 @@ use tokio_io::{AsyncRead, AsyncWrite};
 use wasm_timer::Instant;
 
 pub use dummy::DummyProtocolsHandler;
+pub use idle::{IdleTimeout, IdleTimeoutError, SubstreamTimedOut};
 pub use map_in::MapInEvent;
 pub use map_out::MapOutEvent;
 pub use node_handler::{NodeHandlerWrapper, NodeHandlerWrapperBuilder, NodeHandlerWrapperError};
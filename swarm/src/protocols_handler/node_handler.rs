@@ -34,12 +34,14 @@ use libp2p_core::{
     upgrade::{self, InboundUpgradeApply, OutboundUpgradeApply}
 };
 use std::{error, fmt, time::Duration};
-use wasm_timer::{Delay, Timeout};
+use wasm_timer::{Delay, Instant, Timeout};
 
 /// Prototype for a `NodeHandlerWrapper`.
 pub struct NodeHandlerWrapperBuilder<TIntoProtoHandler> {
     /// The underlying handler.
     handler: TIntoProtoHandler,
+    /// How long to keep the connection alive once the handler no longer needs it.
+    idle_timeout: Duration,
 }
 
 impl<TIntoProtoHandler> NodeHandlerWrapperBuilder<TIntoProtoHandler>
@@ -51,9 +53,18 @@ where
     pub(crate) fn new(handler: TIntoProtoHandler) -> Self {
         NodeHandlerWrapperBuilder {
             handler,
+            idle_timeout: Duration::from_secs(0),
         }
     }
 
+    /// Sets how long the connection is kept alive after the handler has returned
+    /// [`KeepAlive::No`], provided that it doesn't change its mind in the meantime.
+    #[inline]
+    pub(crate) fn with_idle_timeout(mut self, idle_timeout: Duration) -> Self {
+        self.idle_timeout = idle_timeout;
+        self
+    }
+
     /// Builds the `NodeHandlerWrapper`.
     #[deprecated(note = "Pass the NodeHandlerWrapperBuilder directly")]
     #[inline]
@@ -67,6 +78,7 @@ where
             queued_dial_upgrades: Vec::new(),
             unique_dial_upgrade_id: 0,
             shutdown: Shutdown::None,
+            idle_timeout: self.idle_timeout,
         }
     }
 }
@@ -88,6 +100,7 @@ where
             queued_dial_upgrades: Vec::new(),
             unique_dial_upgrade_id: 0,
             shutdown: Shutdown::None,
+            idle_timeout: self.idle_timeout,
         }
     }
 }
@@ -116,6 +129,8 @@ where
     unique_dial_upgrade_id: u64,
     /// The currently planned connection & handler shutdown.
     shutdown: Shutdown,
+    /// How long to keep the connection alive once the handler no longer needs it.
+    idle_timeout: Duration,
 }
 
 /// The options for a planned connection & handler shutdown.
@@ -126,7 +141,8 @@ where
 ///
 /// A planned shutdown is always postponed for as long as there are ingoing
 /// or outgoing substreams being negotiated, i.e. it is a graceful, "idle"
-/// shutdown.
+/// shutdown. When the handler returns [`KeepAlive::No`], the shutdown is
+/// additionally delayed by the idle timeout configured on the `Swarm`.
 enum Shutdown {
     /// No shutdown is planned.
     None,
@@ -276,13 +292,25 @@ where
 
         // Ask the handler whether it wants the connection (and the handler itself)
         // to be kept alive, which determines the planned shutdown, if any.
+        let idle_timeout = self.idle_timeout;
         match (&mut self.shutdown, self.handler.connection_keep_alive()) {
             (Shutdown::Later(d), KeepAlive::Until(t)) =>
                 if d.deadline() != t {
                     d.reset(t)
                 },
             (_, KeepAlive::Until(t)) => self.shutdown = Shutdown::Later(Delay::new(t)),
-            (_, KeepAlive::No) => self.shutdown = Shutdown::Asap,
+            (_, KeepAlive::No) if idle_timeout == Duration::from_secs(0) =>
+                self.shutdown = Shutdown::Asap,
+            // Don't push back a shutdown that is planned earlier than the idle timeout.
+            (Shutdown::Later(d), KeepAlive::No) => {
+                let t = Instant::now() + idle_timeout;
+                if d.deadline() > t {
+                    d.reset(t)
+                }
+            },
+            (Shutdown::Asap, KeepAlive::No) => {},
+            (Shutdown::None, KeepAlive::No) =>
+                self.shutdown = Shutdown::Later(Delay::new(Instant::now() + idle_timeout)),
             (_, KeepAlive::Yes) => self.shutdown = Shutdown::None
         };
 
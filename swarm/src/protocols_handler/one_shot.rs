@@ -23,7 +23,8 @@ use crate::protocols_handler::{
     ProtocolsHandler,
     ProtocolsHandlerEvent,
     ProtocolsHandlerUpgrErr,
-    SubstreamProtocol
+    SubstreamProtocol,
+    SubstreamTimedOut
 };
 use futures::prelude::*;
 use libp2p_core::upgrade::{InboundUpgrade, OutboundUpgrade};
@@ -35,6 +36,10 @@ use wasm_timer::Instant;
 /// Implementation of `ProtocolsHandler` that opens a new substream for each individual message.
 ///
 /// This struct is meant to be a helper for other implementations to use.
+///
+/// Outbound substreams that don't complete within the substream timeout (see
+/// [`OneShotHandler::with_substream_timeout`]) are closed and reported as a
+/// [`SubstreamTimedOut`] event, rather than closing the whole connection.
 // TODO: Debug
 pub struct OneShotHandler<TSubstream, TInProto, TOutProto, TOutEvent>
 where
@@ -57,6 +62,9 @@ where
     keep_alive: KeepAlive,
     /// After the given duration has elapsed, an inactive connection will shutdown.
     inactive_timeout: Duration,
+    /// Maximum duration of an outbound substream, from its opening until the upgrade
+    /// has completed.
+    substream_timeout: Duration,
     /// Pin the `TSubstream` generic.
     marker: PhantomData<TSubstream>,
 }
@@ -81,10 +89,21 @@ where
             max_dial_negotiated: 8,
             keep_alive: KeepAlive::Yes,
             inactive_timeout,
+            substream_timeout: Duration::from_secs(10),
             marker: PhantomData,
         }
     }
 
+    /// Sets the maximum duration of an outbound substream, after which it is closed and a
+    /// [`SubstreamTimedOut`] event is produced. Defaults to 10 seconds.
+    ///
+    /// > **Note**: The timeout of inbound substreams is the one of the listen protocol.
+    #[inline]
+    pub fn with_substream_timeout(mut self, timeout: Duration) -> Self {
+        self.substream_timeout = timeout;
+        self
+    }
+
     /// Returns the number of pending requests.
     #[inline]
     pub fn pending_requests(&self) -> u32 {
@@ -138,6 +157,7 @@ where
     TInProto::Output: Into<TOutEvent>,
     TOutProto::Output: Into<TOutEvent>,
     TOutProto::Error: error::Error + 'static,
+    SubstreamTimedOut: Into<TOutEvent>,
     SubstreamProtocol<TInProto>: Clone,
 {
     type InEvent = TOutProto;
@@ -196,6 +216,16 @@ where
             <Self::OutboundProtocol as OutboundUpgrade<Self::Substream>>::Error,
         >,
     ) {
+        self.dial_negotiated -= 1;
+
+        if let ProtocolsHandlerUpgrErr::Timeout = error {
+            if self.dial_negotiated == 0 && self.dial_queue.is_empty() {
+                self.keep_alive = KeepAlive::Until(Instant::now() + self.inactive_timeout);
+            }
+            self.events_out.push(SubstreamTimedOut.into());
+            return;
+        }
+
         if self.pending_error.is_none() {
             self.pending_error = Some(error);
         }
@@ -229,7 +259,8 @@ where
                 self.dial_negotiated += 1;
                 return Ok(Async::Ready(
                     ProtocolsHandlerEvent::OutboundSubstreamRequest {
-                        protocol: SubstreamProtocol::new(self.dial_queue.remove(0)),
+                        protocol: SubstreamProtocol::new(self.dial_queue.remove(0))
+                            .with_timeout(self.substream_timeout),
                         info: (),
                     },
                 ));
@@ -0,0 +1,203 @@
+// Copyright 2019 Parity Technologies (UK) Ltd.
+//
+// Permission is hereby granted, free of charge, to any person obtaining a
+// copy of this software and associated documentation files (the "Software"),
+// to deal in the Software without restriction, including without limitation
+// the rights to use, copy, modify, merge, publish, distribute, sublicense,
+// and/or sell copies of the Software, and to permit persons to whom the
+// Software is furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS
+// OR IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING
+// FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
+// DEALINGS IN THE SOFTWARE.
+
+use libp2p_core::{Multiaddr, PeerId};
+use std::{collections::HashMap, time::Duration};
+use wasm_timer::Instant;
+
+/// Maximum number of addresses for which we keep statistics.
+const MAX_TRACKED_ADDRESSES: usize = 4096;
+
+/// Duration after which a successful dial no longer counts as recent.
+const SUCCESS_FRESHNESS: Duration = Duration::from_secs(60 * 60);
+
+/// Dialing history of a single address.
+#[derive(Debug)]
+struct AddressStats {
+    /// Smoothed duration between the start of a dial and the connection being established.
+    latency: Option<Duration>,
+    /// Moment of the last successful dial.
+    last_success: Option<Instant>,
+    /// Number of failures since the last successful dial.
+    failures: u32,
+    /// Moment of the last update, used to evict the least recently used statistics.
+    last_update: Instant,
+}
+
+/// Keeps track of the latency and success of the dials to each address, in order to try the most
+/// promising addresses of a peer first.
+///
+/// Addresses that have recently been dialed successfully come first, by increasing latency,
+/// followed by the addresses we know nothing about, followed by the addresses whose last dial
+/// failed, by increasing number of failures.
+#[derive(Debug, Default)]
+pub(crate) struct AddressRanking {
+    stats: HashMap<Multiaddr, AddressStats>,
+    /// Dials in progress, with the moment they started.
+    pending: HashMap<PeerId, Vec<(Multiaddr, Instant)>>,
+}
+
+impl AddressRanking {
+    /// Creates a new `AddressRanking` without any history.
+    pub(crate) fn new() -> Self {
+        Default::default()
+    }
+
+    /// Sorts the addresses of a peer from the most to the least promising. Addresses that rank
+    /// the same keep their relative order.
+    pub(crate) fn sort(&self, addrs: &mut [Multiaddr]) {
+        let now = Instant::now();
+        addrs.sort_by_key(|addr| match self.stats.get(addr) {
+            Some(stats) if stats.failures == 0 && stats.last_success
+                .map_or(false, |t| now.duration_since(t) < SUCCESS_FRESHNESS) =>
+                (0, stats.latency.unwrap_or_default(), 0),
+            Some(stats) if stats.failures > 0 => (2, Duration::default(), stats.failures),
+            _ => (1, Duration::default(), 0),
+        });
+    }
+
+    /// Records that we started dialing the given address of a peer. Has no effect if a dial to
+    /// this address is already in progress.
+    pub(crate) fn dial_started(&mut self, peer_id: &PeerId, addr: &Multiaddr) {
+        let pending = self.pending.entry(peer_id.clone()).or_default();
+        if pending.iter().all(|(a, _)| a != addr) {
+            pending.push((addr.clone(), Instant::now()));
+        }
+    }
+
+    /// Records that dialing the given address succeeded. The other dials in progress to the same
+    /// peer are forgotten, as they are interrupted.
+    ///
+    /// Returns how long it took to establish the connection, if the start of the dial is known.
+    pub(crate) fn dial_succeeded(&mut self, peer_id: &PeerId, addr: &Multiaddr) -> Option<Duration> {
+        let now = Instant::now();
+        let latency = self.pending.remove(peer_id)
+            .and_then(|pending| pending.into_iter().find(|(a, _)| a == addr))
+            .map(|(_, start)| now.duration_since(start));
+
+        let stats = self.entry(addr, now);
+        stats.failures = 0;
+        stats.last_success = Some(now);
+        if let Some(latency) = latency {
+            // Exponential moving average, giving a weight of 1/4 to the new sample.
+            stats.latency = Some(match stats.latency {
+                Some(old) => (old * 3 + latency) / 4,
+                None => latency,
+            });
+        }
+        latency
+    }
+
+    /// Records that dialing the given address failed.
+    pub(crate) fn dial_failed(&mut self, peer_id: Option<&PeerId>, addr: &Multiaddr) {
+        if let Some(peer_id) = peer_id {
+            if let Some(pending) = self.pending.get_mut(peer_id) {
+                pending.retain(|(a, _)| a != addr);
+                if pending.is_empty() {
+                    self.pending.remove(peer_id);
+                }
+            }
+        }
+
+        let stats = self.entry(addr, Instant::now());
+        stats.failures = stats.failures.saturating_add(1);
+    }
+
+    /// Forgets about the dials in progress to the given peer.
+    pub(crate) fn dials_stopped(&mut self, peer_id: &PeerId) {
+        self.pending.remove(peer_id);
+    }
+
+    /// Returns the statistics of an address, creating them if necessary.
+    fn entry(&mut self, addr: &Multiaddr, now: Instant) -> &mut AddressStats {
+        if !self.stats.contains_key(addr) && self.stats.len() >= MAX_TRACKED_ADDRESSES {
+            let oldest = self.stats.iter()
+                .min_by_key(|(_, s)| s.last_update)
+                .map(|(a, _)| a.clone());
+            if let Some(oldest) = oldest {
+                self.stats.remove(&oldest);
+            }
+        }
+
+        let stats = self.stats.entry(addr.clone()).or_insert_with(|| AddressStats {
+            latency: None,
+            last_success: None,
+            failures: 0,
+            last_update: now,
+        });
+        stats.last_update = now;
+        stats
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn successful_addresses_first_failed_last() {
+        let peer_id = PeerId::random();
+        let ok: Multiaddr = "/ip4/1.2.3.4/tcp/1".parse().unwrap();
+        let unknown: Multiaddr = "/ip4/1.2.3.4/tcp/2".parse().unwrap();
+        let failed: Multiaddr = "/ip4/1.2.3.4/tcp/3".parse().unwrap();
+
+        let mut ranking = AddressRanking::new();
+        ranking.dial_started(&peer_id, &failed);
+        ranking.dial_failed(Some(&peer_id), &failed);
+        ranking.dial_started(&peer_id, &ok);
+        ranking.dial_succeeded(&peer_id, &ok);
+
+        let mut addrs = vec![failed.clone(), unknown.clone(), ok.clone()];
+        ranking.sort(&mut addrs);
+        assert_eq!(addrs, vec![ok, unknown, failed]);
+    }
+
+    #[test]
+    fn faster_addresses_first() {
+        let peer_id = PeerId::random();
+        let slow: Multiaddr = "/ip4/1.2.3.4/tcp/1".parse().unwrap();
+        let fast: Multiaddr = "/ip4/1.2.3.4/tcp/2".parse().unwrap();
+
+        let mut ranking = AddressRanking::new();
+        ranking.dial_succeeded(&peer_id, &fast);
+        ranking.dial_succeeded(&peer_id, &slow);
+        ranking.stats.get_mut(&fast).unwrap().latency = Some(Duration::from_millis(10));
+        ranking.stats.get_mut(&slow).unwrap().latency = Some(Duration::from_millis(500));
+
+        let mut addrs = vec![slow.clone(), fast.clone()];
+        ranking.sort(&mut addrs);
+        assert_eq!(addrs, vec![fast, slow]);
+    }
+
+    #[test]
+    fn success_interrupts_other_dials() {
+        let peer_id = PeerId::random();
+        let a: Multiaddr = "/ip4/1.2.3.4/tcp/1".parse().unwrap();
+        let b: Multiaddr = "/ip4/1.2.3.4/tcp/2".parse().unwrap();
+
+        let mut ranking = AddressRanking::new();
+        ranking.dial_started(&peer_id, &a);
+        ranking.dial_started(&peer_id, &b);
+        ranking.dial_succeeded(&peer_id, &a);
+        assert!(ranking.pending.is_empty());
+        assert!(ranking.stats.get(&a).unwrap().latency.is_some());
+        assert!(ranking.stats.get(&b).is_none());
+    }
+}
@@ -0,0 +1,230 @@
+// Copyright 2019 Parity Technologies (UK) Ltd.
+//
+// Permission is hereby granted, free of charge, to any person obtaining a
+// copy of this software and associated documentation files (the "Software"),
+// to deal in the Software without restriction, including without limitation
+// the rights to use, copy, modify, merge, publish, distribute, sublicense,
+// and/or sell copies of the Software, and to permit persons to whom the
+// Software is furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS
+// OR IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING
+// FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
+// DEALINGS IN THE SOFTWARE.
+
+use libp2p_core::{Multiaddr, multiaddr::Protocol};
+use std::{collections::{HashMap, VecDeque}, fmt, net::IpAddr, time::Duration};
+use wasm_timer::Instant;
+
+/// Configuration of the rate limiting applied by the `Swarm` to incoming connections, based on
+/// the IP address of the remote.
+///
+/// Incoming connections are refused when the remote IP address has already opened
+/// `max_per_window` connections during the last `window`, or when `max_pending` connections
+/// from that address are still being negotiated. Connections whose remote address doesn't
+/// contain an IP address are never limited.
+#[derive(Debug, Clone)]
+pub struct InboundRateLimitConfig {
+    window: Duration,
+    max_per_window: u32,
+    max_pending: u32,
+}
+
+impl InboundRateLimitConfig {
+    /// Sets the duration over which new connections from a single IP address are counted.
+    pub fn with_window(mut self, window: Duration) -> Self {
+        self.window = window;
+        self
+    }
+
+    /// Sets the maximum number of new connections accepted from a single IP address during
+    /// the window.
+    pub fn with_max_per_window(mut self, max: u32) -> Self {
+        self.max_per_window = max;
+        self
+    }
+
+    /// Sets the maximum number of connections from a single IP address that can be negotiating
+    /// at the same time.
+    pub fn with_max_pending(mut self, max: u32) -> Self {
+        self.max_pending = max;
+        self
+    }
+}
+
+impl Default for InboundRateLimitConfig {
+    fn default() -> Self {
+        InboundRateLimitConfig {
+            window: Duration::from_secs(60),
+            max_per_window: 30,
+            max_pending: 4,
+        }
+    }
+}
+
+/// Which limit of the [`InboundRateLimitConfig`] caused an incoming connection to be refused.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum RateLimitReason {
+    /// The IP address has opened too many connections during the window.
+    TooManyConnections,
+    /// Too many connections from the IP address are still being negotiated.
+    TooManyPending,
+}
+
+impl RateLimitReason {
+    /// Returns a short identifier of the reason, suitable for logs and metrics.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            RateLimitReason::TooManyConnections => "too_many_connections",
+            RateLimitReason::TooManyPending => "too_many_pending",
+        }
+    }
+}
+
+impl fmt::Display for RateLimitReason {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RateLimitReason::TooManyConnections =>
+                write!(f, "Too many new connections from this IP address"),
+            RateLimitReason::TooManyPending =>
+                write!(f, "Too many pending connections from this IP address"),
+        }
+    }
+}
+
+/// Recent activity of a single IP address.
+#[derive(Debug, Default)]
+struct IpActivity {
+    /// When the connections accepted during the window were received, oldest first.
+    accepted: VecDeque<Instant>,
+    /// Number of connections that are still being negotiated.
+    pending: u32,
+}
+
+/// Keeps track of the incoming connections of each IP address.
+#[derive(Debug)]
+pub(crate) struct InboundRateLimiter {
+    config: InboundRateLimitConfig,
+    ips: HashMap<IpAddr, IpActivity>,
+}
+
+impl InboundRateLimiter {
+    /// Creates a new `InboundRateLimiter` with the given configuration.
+    pub(crate) fn new(config: InboundRateLimitConfig) -> Self {
+        InboundRateLimiter {
+            config,
+            ips: HashMap::new(),
+        }
+    }
+
+    /// Called when a listener produces a new incoming connection from `send_back_addr`.
+    ///
+    /// If the connection is allowed, it is counted as pending until `pending_finished` is
+    /// called with the same address.
+    pub(crate) fn try_accept(&mut self, send_back_addr: &Multiaddr) -> Result<(), RateLimitReason> {
+        let ip = match ip_of(send_back_addr) {
+            Some(ip) => ip,
+            None => return Ok(()),
+        };
+
+        let now = Instant::now();
+        self.prune(now);
+
+        let activity = self.ips.entry(ip).or_default();
+        if activity.pending >= self.config.max_pending {
+            return Err(RateLimitReason::TooManyPending);
+        }
+        if activity.accepted.len() >= self.config.max_per_window as usize {
+            return Err(RateLimitReason::TooManyConnections);
+        }
+
+        activity.accepted.push_back(now);
+        activity.pending += 1;
+        Ok(())
+    }
+
+    /// Called when an incoming connection from `send_back_addr` has finished negotiating,
+    /// whether successfully or not.
+    pub(crate) fn pending_finished(&mut self, send_back_addr: &Multiaddr) {
+        if let Some(activity) = ip_of(send_back_addr).and_then(|ip| self.ips.get_mut(&ip)) {
+            activity.pending = activity.pending.saturating_sub(1);
+        }
+    }
+
+    /// Forgets about the connections that are older than the window, and about the IP
+    /// addresses that no longer have any activity.
+    fn prune(&mut self, now: Instant) {
+        let window = self.config.window;
+        self.ips.retain(|_, activity| {
+            while activity.accepted.front().map_or(false, |t| *t + window <= now) {
+                activity.accepted.pop_front();
+            }
+            activity.pending > 0 || !activity.accepted.is_empty()
+        });
+    }
+}
+
+/// Returns the IP address at the start of `addr`, if any.
+fn ip_of(addr: &Multiaddr) -> Option<IpAddr> {
+    match addr.iter().next() {
+        Some(Protocol::Ip4(ip)) => Some(IpAddr::V4(ip)),
+        Some(Protocol::Ip6(ip)) => Some(IpAddr::V6(ip)),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn limits_connections_per_window() {
+        let config = InboundRateLimitConfig::default()
+            .with_max_per_window(2)
+            .with_max_pending(10);
+        let mut limiter = InboundRateLimiter::new(config);
+        let addr: Multiaddr = "/ip4/1.2.3.4/tcp/1000".parse().unwrap();
+        let other: Multiaddr = "/ip4/5.6.7.8/tcp/1000".parse().unwrap();
+        assert_eq!(limiter.try_accept(&addr), Ok(()));
+        assert_eq!(limiter.try_accept(&addr), Ok(()));
+        assert_eq!(limiter.try_accept(&addr), Err(RateLimitReason::TooManyConnections));
+        assert_eq!(limiter.try_accept(&other), Ok(()));
+    }
+
+    #[test]
+    fn window_expires() {
+        let config = InboundRateLimitConfig::default()
+            .with_window(Duration::from_secs(0))
+            .with_max_per_window(1);
+        let mut limiter = InboundRateLimiter::new(config);
+        let addr: Multiaddr = "/ip4/1.2.3.4/tcp/1000".parse().unwrap();
+        assert_eq!(limiter.try_accept(&addr), Ok(()));
+        limiter.pending_finished(&addr);
+        assert_eq!(limiter.try_accept(&addr), Ok(()));
+    }
+
+    #[test]
+    fn limits_pending_connections() {
+        let config = InboundRateLimitConfig::default().with_max_pending(1);
+        let mut limiter = InboundRateLimiter::new(config);
+        let addr: Multiaddr = "/ip6/::1/tcp/1000".parse().unwrap();
+        assert_eq!(limiter.try_accept(&addr), Ok(()));
+        assert_eq!(limiter.try_accept(&addr), Err(RateLimitReason::TooManyPending));
+        limiter.pending_finished(&addr);
+        assert_eq!(limiter.try_accept(&addr), Ok(()));
+    }
+
+    #[test]
+    fn addresses_without_ip_are_not_limited() {
+        let config = InboundRateLimitConfig::default().with_max_pending(0);
+        let mut limiter = InboundRateLimiter::new(config);
+        let addr: Multiaddr = "/memory/5".parse().unwrap();
+        assert_eq!(limiter.try_accept(&addr), Ok(()));
+    }
+}
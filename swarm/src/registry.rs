@@ -18,9 +18,170 @@
 // FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
 // DEALINGS IN THE SOFTWARE.
 
-use libp2p_core::Multiaddr;
+use libp2p_core::{Multiaddr, PeerId};
 use smallvec::SmallVec;
-use std::{collections::VecDeque, num::NonZeroUsize};
+use std::{collections::{HashMap, VecDeque}, num::NonZeroUsize, time::Duration};
+use wasm_timer::Instant;
+
+/// How long a restored external address is considered confirmed.
+pub const RESTORED_ADDRESS_TTL: Duration = Duration::from_secs(10 * 60);
+
+/// Where a candidate external address comes from.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum AddressSource {
+    /// The address has been configured manually. It is considered confirmed.
+    Manual,
+    /// The address has been obtained by mapping a port on the gateway, for example with UPnP.
+    /// It is considered confirmed.
+    PortMapping,
+    /// The address goes through a relay on which we hold a reservation. It is considered
+    /// confirmed.
+    Relayed,
+    /// The address has been reported by the given remote peer. It is considered confirmed once
+    /// enough distinct peers have reported it.
+    Observed(PeerId),
+    /// The address was confirmed before the node restarted. It is considered confirmed for
+    /// [`RESTORED_ADDRESS_TTL`], by which time remotes have usually reported it again if it's
+    /// still valid, or until the network changes.
+    Restored,
+}
+
+/// Holds the candidate external addresses of the local node.
+///
+/// Addresses that are observed by remotes are only considered confirmed after they have been
+/// reported by a minimum number of distinct peers, so that a single misbehaving peer can't make
+/// us advertise arbitrary addresses.
+#[derive(Debug, Clone)]
+pub struct ExternalAddresses {
+    /// Addresses that are confirmed regardless of the reports of remotes.
+    trusted: SmallVec<[Multiaddr; 4]>,
+    /// Restored addresses, with when they expire.
+    restored: SmallVec<[(Multiaddr, Instant); 4]>,
+    /// Recent reports of remotes.
+    reports: Addresses,
+    /// For each address in `reports`, the distinct peers that have reported it.
+    observers: HashMap<Multiaddr, SmallVec<[PeerId; 4]>>,
+    /// Number of distinct peers that must report an address before it is confirmed.
+    min_confirmations: NonZeroUsize,
+}
+
+impl Default for ExternalAddresses {
+    fn default() -> Self {
+        ExternalAddresses::new(NonZeroUsize::new(1).expect("1 > 0"))
+    }
+}
+
+impl ExternalAddresses {
+    /// Creates a new collection, where observed addresses are confirmed once `min_confirmations`
+    /// distinct peers have reported them.
+    pub fn new(min_confirmations: NonZeroUsize) -> Self {
+        ExternalAddresses {
+            trusted: SmallVec::new(),
+            restored: SmallVec::new(),
+            reports: Addresses::default(),
+            observers: HashMap::new(),
+            min_confirmations,
+        }
+    }
+
+    /// Adds a candidate address.
+    ///
+    /// Returns `true` if the address wasn't confirmed before, and is confirmed now.
+    pub fn add(&mut self, addr: Multiaddr, source: AddressSource) -> bool {
+        let was_confirmed = self.is_confirmed(&addr);
+
+        match source {
+            AddressSource::Manual | AddressSource::PortMapping | AddressSource::Relayed => {
+                if !self.trusted.contains(&addr) {
+                    self.trusted.push(addr.clone());
+                }
+            }
+            AddressSource::Restored => {
+                let now = Instant::now();
+                self.restored.retain(|(a, e)| *e > now && *a != addr);
+                self.restored.push((addr.clone(), now + RESTORED_ADDRESS_TTL));
+            }
+            AddressSource::Observed(peer_id) => {
+                self.reports.add(addr.clone());
+                let observers = self.observers.entry(addr.clone()).or_insert_with(SmallVec::new);
+                if !observers.contains(&peer_id) {
+                    observers.push(peer_id);
+                }
+                // Forget about the addresses that have disappeared from the reports.
+                let reports = &self.reports;
+                self.observers.retain(|a, _| reports.iter().any(|r| r == a));
+            }
+        }
+
+        !was_confirmed && self.is_confirmed(&addr)
+    }
+
+    /// Removes an address, whatever its source.
+    ///
+    /// If the address is reported again later, it has to be confirmed again.
+    pub fn remove(&mut self, addr: &Multiaddr) {
+        self.trusted.retain(|a| a != addr);
+        self.restored.retain(|(a, _)| a != addr);
+        self.observers.remove(addr);
+    }
+
+    /// Forgets about the addresses reported by remotes and the restored addresses. The trusted
+    /// addresses are kept.
+    pub fn clear_observed(&mut self) {
+        self.restored.clear();
+        self.reports = Addresses::new(self.reports.limit);
+        self.observers.clear();
+    }
+
+    /// Returns `true` if the address is confirmed.
+    pub fn is_confirmed(&self, addr: &Multiaddr) -> bool {
+        self.trusted.contains(addr) || self.is_restored(addr) ||
+            self.confirmations(addr) >= self.min_confirmations.get()
+    }
+
+    /// Returns the number of distinct peers that have reported the address.
+    pub fn confirmations(&self, addr: &Multiaddr) -> usize {
+        self.observers.get(addr).map_or(0, |o| o.len())
+    }
+
+    /// Returns the confirmed addresses.
+    ///
+    /// Trusted addresses come first, followed with the restored addresses and the observed
+    /// addresses ordered by descending number of confirmations.
+    pub fn iter(&self) -> impl Iterator<Item = &Multiaddr> {
+        let now = Instant::now();
+        let trusted = &self.trusted;
+        let restored = self.restored.iter()
+            .filter(move |(a, e)| *e > now && !trusted.contains(a))
+            .map(|(a, _)| a);
+        self.trusted.iter().chain(restored).chain(self.confirmed_observed().into_iter())
+    }
+
+    /// Returns `true` if the address has been restored and hasn't expired yet.
+    fn is_restored(&self, addr: &Multiaddr) -> bool {
+        let now = Instant::now();
+        self.restored.iter().any(|(a, e)| a == addr && *e > now)
+    }
+
+    /// Returns all the candidate addresses reported by remotes, with their number of
+    /// confirmations, ordered by descending number of confirmations.
+    pub fn candidates(&self) -> impl Iterator<Item = (&Multiaddr, usize)> {
+        let mut candidates = self.observers.iter()
+            .map(|(a, o)| (a, o.len()))
+            .collect::<Vec<_>>();
+        candidates.sort_by(|a, b| b.1.cmp(&a.1));
+        candidates.into_iter()
+    }
+
+    /// Returns the observed addresses that are confirmed and not trusted.
+    fn confirmed_observed(&self) -> Vec<&Multiaddr> {
+        let min = self.min_confirmations.get();
+        self.candidates()
+            .filter(|&(a, n)| n >= min && !self.trusted.contains(a) && !self.is_restored(a))
+            .map(|(a, _)| a)
+            .collect()
+    }
+}
 
 /// Hold a ranked collection of [`Multiaddr`] values.
 ///
@@ -105,13 +266,6 @@ impl Addresses {
     pub fn iter(&self) -> AddressIter<'_> {
         AddressIter { items: &self.registry, offset: 0 }
     }
-
-    /// Return an iterator over all [`Multiaddr`] values.
-    ///
-    /// The iteration is ordered by descending score.
-    pub fn into_iter(self) -> AddressIntoIter {
-        AddressIntoIter { items: self.registry }
-    }
 }
 
 /// An iterator over [`Multiaddr`] values.
@@ -141,31 +295,6 @@ impl<'a> Iterator for AddressIter<'a> {
 
 impl<'a> ExactSizeIterator for AddressIter<'a> {}
 
-/// An iterator over [`Multiaddr`] values.
-#[derive(Clone)]
-pub struct AddressIntoIter {
-    items: SmallVec<[Record; 8]>,
-}
-
-impl Iterator for AddressIntoIter {
-    type Item = Multiaddr;
-
-    fn next(&mut self) -> Option<Self::Item> {
-        if !self.items.is_empty() {
-            Some(self.items.remove(0).addr)
-        } else {
-            None
-        }
-    }
-
-    fn size_hint(&self) -> (usize, Option<usize>) {
-        let n = self.items.len();
-        (n, Some(n))
-    }
-}
-
-impl ExactSizeIterator for AddressIntoIter {}
-
 // Reverse insertion sort.
 fn isort(xs: &mut [Record]) {
     for i in 1 .. xs.len() {
@@ -180,11 +309,11 @@ fn isort(xs: &mut [Record]) {
 
 #[cfg(test)]
 mod tests {
-    use libp2p_core::multiaddr::{Multiaddr, Protocol};
+    use libp2p_core::{PeerId, multiaddr::{Multiaddr, Protocol}};
     use quickcheck::{Arbitrary, Gen, QuickCheck};
     use rand::Rng;
     use std::num::NonZeroUsize;
-    use super::{isort, Addresses, Record};
+    use super::{isort, Addresses, AddressSource, ExternalAddresses, Record};
 
     #[test]
     fn isort_sorts() {
@@ -255,4 +384,44 @@ mod tests {
 
         QuickCheck::new().quickcheck(property as fn(Vec<Ma>, u8) -> bool)
     }
+
+    #[test]
+    fn observed_address_needs_distinct_confirmations() {
+        let mut addresses = ExternalAddresses::new(NonZeroUsize::new(2).unwrap());
+        let addr: Multiaddr = "/ip4/1.2.3.4/tcp/4001".parse().unwrap();
+        let misbehaving = PeerId::random();
+
+        for _ in 0 .. 10 {
+            assert!(!addresses.add(addr.clone(), AddressSource::Observed(misbehaving.clone())));
+        }
+        assert_eq!(addresses.confirmations(&addr), 1);
+        assert!(addresses.iter().next().is_none());
+
+        assert!(addresses.add(addr.clone(), AddressSource::Observed(PeerId::random())));
+        assert_eq!(addresses.iter().collect::<Vec<_>>(), vec![&addr]);
+    }
+
+    #[test]
+    fn manual_address_is_confirmed() {
+        let mut addresses = ExternalAddresses::default();
+        let addr: Multiaddr = "/ip4/1.2.3.4/tcp/4001".parse().unwrap();
+        assert!(addresses.add(addr.clone(), AddressSource::Manual));
+        assert!(!addresses.add(addr.clone(), AddressSource::Observed(PeerId::random())));
+        assert_eq!(addresses.iter().collect::<Vec<_>>(), vec![&addr]);
+        addresses.remove(&addr);
+        assert!(addresses.iter().next().is_none());
+    }
+
+    #[test]
+    fn restored_address_is_forgotten_on_network_change() {
+        let mut addresses = ExternalAddresses::default();
+        let addr: Multiaddr = "/ip4/1.2.3.4/tcp/4001".parse().unwrap();
+        assert!(addresses.add(addr.clone(), AddressSource::Restored));
+        assert!(!addresses.add(addr.clone(), AddressSource::Observed(PeerId::random())));
+        assert_eq!(addresses.iter().collect::<Vec<_>>(), vec![&addr]);
+
+        addresses.clear_observed();
+        assert!(!addresses.is_confirmed(&addr));
+        assert!(addresses.iter().next().is_none());
+    }
 }
@@ -0,0 +1,190 @@
+// Copyright 2019 Parity Technologies (UK) Ltd.
+//
+// Permission is hereby granted, free of charge, to any person obtaining a
+// copy of this software and associated documentation files (the "Software"),
+// to deal in the Software without restriction, including without limitation
+// the rights to use, copy, modify, merge, publish, distribute, sublicense,
+// and/or sell copies of the Software, and to permit persons to whom the
+// Software is furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS
+// OR IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING
+// FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
+// DEALINGS IN THE SOFTWARE.
+//! Periodic snapshots of the state of a `Swarm`, see `Swarm::subscribe_snapshots`.
+
+use futures::{prelude::*, sync::mpsc};
+use libp2p_core::{ConnectedPoint, Multiaddr, multiaddr::Protocol};
+use std::{collections::BTreeMap, fmt, time::Duration};
+use wasm_timer::{Delay, Instant};
+
+/// Lightweight view of the state of a `Swarm` at a given moment.
+#[derive(Debug, Clone)]
+pub struct SwarmSnapshot {
+    /// When the snapshot was taken.
+    pub taken_at: Instant,
+    /// Number of peers we have at least one established connection to.
+    pub num_peers: usize,
+    /// Number of established connections.
+    pub num_connections: usize,
+    /// Number of established connections for each transport, identified by the names of the
+    /// protocols of the address of the connection, without the IP address, DNS name and peer ID
+    /// parts. For example, a connection to `/ip4/1.2.3.4/tcp/30333/ws` is counted as `tcp/ws`.
+    pub connections_by_transport: BTreeMap<String, usize>,
+    /// Number of peers we are dialing.
+    pub pending_dials: usize,
+    /// Number of addresses we are dialing without knowing the peer behind them.
+    pub pending_unknown_dials: usize,
+    /// Number of incoming connections that are still being negotiated.
+    pub pending_incoming: usize,
+    /// Addresses we are listening on.
+    pub listened_addrs: Vec<Multiaddr>,
+    /// Summary reported by the `NetworkBehaviour` with `NetworkBehaviour::summarize`.
+    pub behaviour: BehaviourSummary,
+}
+
+/// Key-value pairs describing the state of a `NetworkBehaviour`, filled by
+/// `NetworkBehaviour::summarize`.
+///
+/// Behaviours are expected to prefix their keys with their name, e.g. `kad.records`, so that
+/// the summaries of the behaviours composed in a single `Swarm` don't collide.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct BehaviourSummary {
+    entries: BTreeMap<String, String>,
+}
+
+impl BehaviourSummary {
+    /// Creates an empty summary.
+    pub fn new() -> Self {
+        BehaviourSummary::default()
+    }
+
+    /// Sets the value of an entry, replacing the previous value if any.
+    pub fn insert(&mut self, key: impl Into<String>, value: impl fmt::Display) {
+        self.entries.insert(key.into(), value.to_string());
+    }
+
+    /// Returns the value of an entry.
+    pub fn get(&self, key: &str) -> Option<&str> {
+        self.entries.get(key).map(|v| v.as_str())
+    }
+
+    /// Returns true if the summary has no entry.
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Returns the entries of the summary, ordered by key.
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &str)> {
+        self.entries.iter().map(|(k, v)| (k.as_str(), v.as_str()))
+    }
+}
+
+/// Returns the name under which connections on the given endpoint are counted in
+/// `SwarmSnapshot::connections_by_transport`.
+pub(crate) fn transport_name(endpoint: &ConnectedPoint) -> String {
+    let address = match endpoint {
+        ConnectedPoint::Dialer { address } => address,
+        ConnectedPoint::Listener { listen_addr, .. } => listen_addr,
+    };
+
+    let mut name = String::new();
+    for protocol in address.iter() {
+        match protocol {
+            Protocol::Ip4(_) | Protocol::Ip6(_) | Protocol::Dns4(_) | Protocol::Dns6(_) |
+            Protocol::Dnsaddr(_) | Protocol::P2p(_) | Protocol::Certhash(_) | Protocol::Sni(_) => continue,
+            _ => {}
+        }
+        // The string representation of a protocol is `/<name>` followed by its value, if any.
+        let protocol = protocol.to_string();
+        if let Some(proto_name) = protocol.split('/').nth(1) {
+            if !name.is_empty() {
+                name.push('/');
+            }
+            name.push_str(proto_name);
+        }
+    }
+    name
+}
+
+/// Subscribers to the snapshots of a `Swarm`.
+#[derive(Default)]
+pub(crate) struct Subscriptions {
+    subscribers: Vec<Subscriber>,
+}
+
+struct Subscriber {
+    sender: mpsc::Sender<SwarmSnapshot>,
+    interval: Duration,
+    next: Delay,
+}
+
+impl Subscriptions {
+    /// Adds a subscriber that receives a snapshot right away, then every `interval`.
+    pub(crate) fn subscribe(&mut self, interval: Duration) -> mpsc::Receiver<SwarmSnapshot> {
+        // A snapshot that the subscriber hasn't consumed yet is never queued behind another one;
+        // the new one is dropped instead.
+        let (sender, receiver) = mpsc::channel(0);
+        self.subscribers.push(Subscriber {
+            sender,
+            interval,
+            next: Delay::new(Instant::now()),
+        });
+        receiver
+    }
+
+    /// Returns true if some subscriber is due a snapshot. Registers the current task to be woken
+    /// up when the next one is due otherwise.
+    ///
+    /// Also removes the subscribers whose receiver has been dropped.
+    pub(crate) fn poll_due(&mut self) -> bool {
+        self.subscribers.retain(|s| !s.sender.is_closed());
+        let mut due = false;
+        for subscriber in &mut self.subscribers {
+            if let Ok(Async::Ready(())) | Err(_) = subscriber.next.poll() {
+                due = true;
+            }
+        }
+        due
+    }
+
+    /// Sends the snapshot to the subscribers it is due to.
+    pub(crate) fn send(&mut self, snapshot: SwarmSnapshot) {
+        let now = Instant::now();
+        for subscriber in &mut self.subscribers {
+            match subscriber.next.poll() {
+                Ok(Async::Ready(())) | Err(_) => {},
+                Ok(Async::NotReady) => continue,
+            }
+            let _ = subscriber.sender.try_send(snapshot.clone());
+            subscriber.next.reset(now + subscriber.interval);
+            // Register the task for the next snapshot.
+            let _ = subscriber.next.poll();
+        }
+    }
+
+    /// Returns true if there is no subscriber.
+    pub(crate) fn is_empty(&self) -> bool {
+        self.subscribers.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn transport_names() {
+        let name = |addr: &str| transport_name(&ConnectedPoint::Dialer { address: addr.parse().unwrap() });
+        assert_eq!(name("/ip4/1.2.3.4/tcp/30333"), "tcp");
+        assert_eq!(name("/dns4/example.com/tcp/443/wss"), "tcp/wss");
+        assert_eq!(name("/memory/5"), "memory");
+        assert_eq!(name("/ip6/::1/udp/4001/quic/p2p/QmcgpsyWgH8Y8ajJz1Cu72KnS5uo2Aa2LpzU7kinSupNKC"), "udp/quic");
+    }
+}
@@ -0,0 +1,200 @@
+// Copyright 2019 Parity Technologies (UK) Ltd.
+//
+// Permission is hereby granted, free of charge, to any person obtaining a
+// copy of this software and associated documentation files (the "Software"),
+// to deal in the Software without restriction, including without limitation
+// the rights to use, copy, modify, merge, publish, distribute, sublicense,
+// and/or sell copies of the Software, and to permit persons to whom the
+// Software is furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS
+// OR IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING
+// FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
+// DEALINGS IN THE SOFTWARE.
+
+use crate::peer_store::{PeerStore, from_hex, invalid_data, save_atomically, to_hex};
+use libp2p_core::{Multiaddr, PeerId};
+use std::{convert::TryFrom, fs, io::{self, BufRead, Write}, path::Path};
+
+/// Version of the format written by [`SwarmState::write_to`].
+///
+/// Files written with another version are rejected by [`SwarmState::read_from`].
+pub const STATE_FORMAT_VERSION: u32 = 1;
+
+/// First word of the files written by [`SwarmState::write_to`].
+const MAGIC: &str = "libp2p-swarm-state";
+
+/// State of a `Swarm` worth keeping across restarts, so that a restarting node reaches the
+/// network it knew about instead of discovering it again from its bootstrap nodes only.
+///
+/// Obtained with `Swarm::state` and restored with `Swarm::restore_state`. The `Swarm` doesn't
+/// know about the routing table of its behaviour, which the application fills in and restores
+/// itself, e.g. with `Kademlia::routing_table` and `Kademlia::restore_routing_table`.
+#[derive(Debug, Clone, Default)]
+pub struct SwarmState {
+    /// The addresses, protocols and metadata of the known peers.
+    pub peer_store: PeerStore,
+    /// The confirmed external addresses of the local node.
+    pub external_addresses: Vec<Multiaddr>,
+    /// The peers of the routing table of the DHT, with their addresses.
+    pub routing_table: Vec<(PeerId, Vec<Multiaddr>)>,
+}
+
+impl SwarmState {
+    /// Writes the state in a versioned, line-based text format.
+    ///
+    /// Addresses are hex-encoded, like in the peer store, as a DNS address can contain spaces
+    /// and line breaks. The peer store is written last, in the format of [`PeerStore::write_to`].
+    pub fn write_to<W: Write>(&self, mut writer: W) -> io::Result<()> {
+        writeln!(writer, "{} {}", MAGIC, STATE_FORMAT_VERSION)?;
+        for addr in &self.external_addresses {
+            writeln!(writer, "external {}", to_hex(&addr.to_vec()))?;
+        }
+        for (peer_id, addrs) in &self.routing_table {
+            write!(writer, "route {}", peer_id.to_base58())?;
+            for addr in addrs {
+                write!(writer, " {}", to_hex(&addr.to_vec()))?;
+            }
+            writeln!(writer)?;
+        }
+        writeln!(writer, "peers")?;
+        self.peer_store.write_to(writer)
+    }
+
+    /// Reads a state previously written with [`SwarmState::write_to`].
+    pub fn read_from<R: BufRead>(mut reader: R) -> io::Result<Self> {
+        let mut header = String::new();
+        reader.read_line(&mut header)?;
+        let mut parts = header.trim_end().split(' ');
+        if parts.next() != Some(MAGIC) {
+            return Err(invalid_data("not a swarm state"));
+        }
+        let version = parts.next()
+            .and_then(|v| v.parse::<u32>().ok())
+            .ok_or_else(|| invalid_data("invalid version"))?;
+        if version != STATE_FORMAT_VERSION {
+            return Err(invalid_data("unsupported version"));
+        }
+
+        let mut state = SwarmState::default();
+        loop {
+            let mut line = String::new();
+            if reader.read_line(&mut line)? == 0 {
+                return Ok(state);
+            }
+            let mut parts = line.trim_end().split(' ');
+            match parts.next() {
+                Some("") => {}
+                Some("external") => {
+                    let addr = parts.next()
+                        .and_then(decode_addr)
+                        .ok_or_else(|| invalid_data("invalid address"))?;
+                    state.external_addresses.push(addr);
+                }
+                Some("route") => {
+                    let peer_id = parts.next()
+                        .and_then(|p| p.parse::<PeerId>().ok())
+                        .ok_or_else(|| invalid_data("invalid peer ID"))?;
+                    let addrs = parts
+                        .map(|a| decode_addr(a).ok_or_else(|| invalid_data("invalid address")))
+                        .collect::<Result<Vec<_>, _>>()?;
+                    state.routing_table.push((peer_id, addrs));
+                }
+                Some("peers") => {
+                    state.peer_store = PeerStore::read_from(reader)?;
+                    return Ok(state);
+                }
+                _ => return Err(invalid_data("unknown entry")),
+            }
+        }
+    }
+
+    /// Saves the state to the given file, replacing its content.
+    ///
+    /// The state is written to a temporary file next to `path`, which then replaces `path`, so
+    /// that the previous state is kept if the node crashes while saving.
+    pub fn save(&self, path: impl AsRef<Path>) -> io::Result<()> {
+        save_atomically(path.as_ref(), |file| self.write_to(file))
+    }
+
+    /// Loads a state from a file previously written with [`SwarmState::save`].
+    pub fn load(path: impl AsRef<Path>) -> io::Result<Self> {
+        let file = fs::File::open(path)?;
+        SwarmState::read_from(io::BufReader::new(file))
+    }
+}
+
+fn decode_addr(s: &str) -> Option<Multiaddr> {
+    from_hex(s).and_then(|a| Multiaddr::try_from(a).ok())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use libp2p_core::multiaddr::Protocol;
+    use std::time::Duration;
+
+    #[test]
+    fn write_then_read() {
+        let peer_id = PeerId::random();
+        let addr: Multiaddr = "/ip4/127.0.0.1/tcp/1234".parse().unwrap();
+        let mut state = SwarmState::default();
+        state.peer_store.add_address(peer_id.clone(), addr.clone(), Duration::from_secs(600));
+        state.external_addresses.push("/ip4/1.2.3.4/tcp/4001".parse().unwrap());
+        state.routing_table.push((peer_id.clone(), vec![addr.clone()]));
+
+        let mut buf = Vec::new();
+        state.write_to(&mut buf).unwrap();
+        let restored = SwarmState::read_from(&buf[..]).unwrap();
+
+        assert_eq!(restored.peer_store.addresses(&peer_id).collect::<Vec<_>>(), vec![&addr]);
+        assert_eq!(restored.external_addresses, state.external_addresses);
+        assert_eq!(restored.routing_table, state.routing_table);
+    }
+
+    #[test]
+    fn dns_addresses_with_line_breaks() {
+        let peer_id = PeerId::random();
+        let addr = Multiaddr::from(Protocol::Dns4("evil\nexternal /ip4/6.6.6.6/tcp/1".into()))
+            .with(Protocol::Tcp(4001));
+        let mut state = SwarmState::default();
+        state.external_addresses.push(addr.clone());
+        state.routing_table.push((peer_id.clone(), vec![addr.clone()]));
+
+        let mut buf = Vec::new();
+        state.write_to(&mut buf).unwrap();
+        let restored = SwarmState::read_from(&buf[..]).unwrap();
+
+        assert_eq!(restored.external_addresses, vec![addr.clone()]);
+        assert_eq!(restored.routing_table, vec![(peer_id, vec![addr])]);
+    }
+
+    #[test]
+    fn save_replaces_previous_state() {
+        let path = std::env::temp_dir().join(format!("swarm-state-{}", PeerId::random().to_base58()));
+        let mut state = SwarmState::default();
+        state.external_addresses.push("/ip4/1.2.3.4/tcp/4001".parse().unwrap());
+        state.save(&path).unwrap();
+        state.external_addresses.push("/ip4/5.6.7.8/tcp/4001".parse().unwrap());
+        state.save(&path).unwrap();
+
+        let restored = SwarmState::load(&path).unwrap();
+        assert_eq!(restored.external_addresses, state.external_addresses);
+        assert!(!path.with_file_name(format!("{}.tmp", path.file_name().unwrap().to_str().unwrap())).exists());
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn other_versions_are_rejected() {
+        let buf = format!("{} {}\npeers\n", MAGIC, STATE_FORMAT_VERSION + 1);
+        let err = SwarmState::read_from(buf.as_bytes()).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+        assert!(SwarmState::read_from(&b"peers\n"[..]).is_err());
+    }
+}
@@ -18,7 +18,7 @@
 // FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
 // DEALINGS IN THE SOFTWARE.
 
-use crate::{NetworkBehaviour, NetworkBehaviourAction, NetworkBehaviourEventProcess, PollParameters};
+use crate::{BehaviourSummary, NetworkBehaviour, NetworkBehaviourAction, NetworkBehaviourEventProcess, NetworkChange, PollParameters};
 use crate::protocols_handler::{
     KeepAlive,
     SubstreamProtocol,
@@ -32,21 +32,107 @@ use libp2p_core::{
     PeerId,
     Multiaddr,
     either::EitherOutput,
+    nodes::ConnectionId,
     upgrade::{InboundUpgrade, OutboundUpgrade, DeniedUpgrade, EitherUpgrade}
 };
-use futures::prelude::*;
-use std::error;
+use futures::{prelude::*, task::{self, Task}};
+use std::{error, sync::{Arc, Mutex, atomic::{AtomicBool, Ordering}}};
 
 /// Implementation of `NetworkBehaviour` that can be either in the disabled or enabled state.
 ///
-/// The state can only be chosen at initialization.
+/// If a behaviour has been provided at initialization, it can later be disabled and enabled
+/// again with [`Toggle::disable`] and [`Toggle::enable`]. While disabled, the behaviour isn't
+/// polled, and the handlers it has spawned refuse all inbound substreams and stop producing
+/// events. The behaviour is still informed about connections and disconnections, so that its
+/// state is up to date once enabled again.
 pub struct Toggle<TBehaviour> {
     inner: Option<TBehaviour>,
+    state: Arc<ToggleState>,
+}
+
+impl<TBehaviour> Toggle<TBehaviour> {
+    /// Returns `true` if a behaviour has been provided and is currently enabled.
+    pub fn is_enabled(&self) -> bool {
+        self.inner.is_some() && self.state.is_enabled()
+    }
+
+    /// Enables the behaviour, if one has been provided at initialization.
+    ///
+    /// The handlers spawned by the behaviour accept inbound substreams again.
+    pub fn enable(&mut self) {
+        if self.inner.is_some() {
+            self.state.set_enabled(true);
+        }
+    }
+
+    /// Disables the behaviour.
+    ///
+    /// The behaviour is no longer polled, and its handlers refuse inbound substreams until it is
+    /// enabled again.
+    pub fn disable(&mut self) {
+        self.state.set_enabled(false);
+    }
+
+    /// Returns a reference to the inner behaviour, if any.
+    pub fn inner(&self) -> Option<&TBehaviour> {
+        self.inner.as_ref()
+    }
+
+    /// Returns a mutable reference to the inner behaviour, if any.
+    pub fn inner_mut(&mut self) -> Option<&mut TBehaviour> {
+        self.inner.as_mut()
+    }
 }
 
 impl<TBehaviour> From<Option<TBehaviour>> for Toggle<TBehaviour> {
     fn from(inner: Option<TBehaviour>) -> Self {
-        Toggle { inner }
+        let state = Arc::new(ToggleState::new(inner.is_some()));
+        Toggle { inner, state }
+    }
+}
+
+/// State shared between a `Toggle` and the handlers it has spawned.
+#[derive(Debug)]
+struct ToggleState {
+    enabled: AtomicBool,
+    /// Tasks to notify when the behaviour is enabled again.
+    to_notify: Mutex<Vec<Task>>,
+}
+
+impl ToggleState {
+    fn new(enabled: bool) -> Self {
+        ToggleState {
+            enabled: AtomicBool::new(enabled),
+            to_notify: Mutex::new(Vec::new()),
+        }
+    }
+
+    fn is_enabled(&self) -> bool {
+        self.enabled.load(Ordering::SeqCst)
+    }
+
+    fn set_enabled(&self, enabled: bool) {
+        self.enabled.store(enabled, Ordering::SeqCst);
+        if enabled {
+            let tasks = std::mem::replace(&mut *self.to_notify.lock().expect("poisoned lock"), Vec::new());
+            for task in tasks {
+                task.notify();
+            }
+        }
+    }
+
+    /// Returns `true` if enabled. Otherwise, registers the current task to be notified once
+    /// enabled again.
+    fn poll_enabled(&self) -> bool {
+        if self.is_enabled() {
+            return true;
+        }
+        let mut to_notify = self.to_notify.lock().expect("poisoned lock");
+        if !to_notify.iter().any(|t| t.will_notify_current()) {
+            to_notify.push(task::current());
+        }
+        // Check again in case the state has changed before we registered the task.
+        self.is_enabled()
     }
 }
 
@@ -59,7 +145,8 @@ where
 
     fn new_handler(&mut self) -> Self::ProtocolsHandler {
         ToggleIntoProtoHandler {
-            inner: self.inner.as_mut().map(|i| i.new_handler())
+            inner: self.inner.as_mut().map(|i| i.new_handler()),
+            state: self.state.clone(),
         }
     }
 
@@ -79,6 +166,18 @@ where
         }
     }
 
+    fn inject_connection_established(&mut self, peer_id: &PeerId, connection: &ConnectionId, endpoint: &ConnectedPoint) {
+        if let Some(inner) = self.inner.as_mut() {
+            inner.inject_connection_established(peer_id, connection, endpoint)
+        }
+    }
+
+    fn inject_connection_closed(&mut self, peer_id: &PeerId, connection: &ConnectionId, endpoint: &ConnectedPoint) {
+        if let Some(inner) = self.inner.as_mut() {
+            inner.inject_connection_closed(peer_id, connection, endpoint)
+        }
+    }
+
     fn inject_replaced(&mut self, peer_id: PeerId, closed_endpoint: ConnectedPoint, new_endpoint: ConnectedPoint) {
         if let Some(inner) = self.inner.as_mut() {
             inner.inject_replaced(peer_id, closed_endpoint, new_endpoint)
@@ -95,6 +194,17 @@ where
         }
     }
 
+    fn inject_connection_event(
+        &mut self,
+        peer_id: PeerId,
+        connection: ConnectionId,
+        event: <<Self::ProtocolsHandler as IntoProtocolsHandler>::Handler as ProtocolsHandler>::OutEvent
+    ) {
+        if let Some(inner) = self.inner.as_mut() {
+            inner.inject_connection_event(peer_id, connection, event);
+        }
+    }
+
     fn inject_addr_reach_failure(&mut self, peer_id: Option<&PeerId>, addr: &Multiaddr, error: &dyn error::Error) {
         if let Some(inner) = self.inner.as_mut() {
             inner.inject_addr_reach_failure(peer_id, addr, error)
@@ -107,6 +217,12 @@ where
         }
     }
 
+    fn inject_banned_peer_connection(&mut self, peer_id: &PeerId, endpoint: &ConnectedPoint) {
+        if let Some(inner) = self.inner.as_mut() {
+            inner.inject_banned_peer_connection(peer_id, endpoint)
+        }
+    }
+
     fn inject_new_listen_addr(&mut self, addr: &Multiaddr) {
         if let Some(inner) = self.inner.as_mut() {
             inner.inject_new_listen_addr(addr)
@@ -119,15 +235,49 @@ where
         }
     }
 
+    fn inject_listener_error(&mut self, err: &dyn error::Error) {
+        if let Some(inner) = self.inner.as_mut() {
+            inner.inject_listener_error(err)
+        }
+    }
+
     fn inject_new_external_addr(&mut self, addr: &Multiaddr) {
         if let Some(inner) = self.inner.as_mut() {
             inner.inject_new_external_addr(addr)
         }
     }
 
+    fn inject_network_change(&mut self, change: &NetworkChange) {
+        if let Some(inner) = self.inner.as_mut() {
+            inner.inject_network_change(change)
+        }
+    }
+
+    fn inject_shutdown(&mut self) {
+        if let Some(inner) = self.inner.as_mut() {
+            inner.inject_shutdown()
+        }
+    }
+
+    fn inject_remote_protocols(&mut self, peer_id: &PeerId, protocols: &[String]) {
+        if let Some(inner) = self.inner.as_mut() {
+            inner.inject_remote_protocols(peer_id, protocols)
+        }
+    }
+
+    fn summarize(&self, summary: &mut BehaviourSummary) {
+        if let Some(inner) = self.inner.as_ref() {
+            inner.summarize(summary)
+        }
+    }
+
     fn poll(&mut self, params: &mut impl PollParameters)
         -> Async<NetworkBehaviourAction<<<Self::ProtocolsHandler as IntoProtocolsHandler>::Handler as ProtocolsHandler>::InEvent, Self::OutEvent>>
     {
+        if !self.state.poll_enabled() {
+            return Async::NotReady
+        }
+
         if let Some(inner) = self.inner.as_mut() {
             inner.poll(params)
         } else {
@@ -150,6 +300,7 @@ where
 /// Implementation of `IntoProtocolsHandler` that can be in the disabled state.
 pub struct ToggleIntoProtoHandler<TInner> {
     inner: Option<TInner>,
+    state: Arc<ToggleState>,
 }
 
 impl<TInner> IntoProtocolsHandler for ToggleIntoProtoHandler<TInner>
@@ -160,15 +311,15 @@ where
 
     fn into_handler(self, remote_peer_id: &PeerId, connected_point: &ConnectedPoint) -> Self::Handler {
         ToggleProtoHandler {
-            inner: self.inner.map(|h| h.into_handler(remote_peer_id, connected_point))
+            inner: self.inner.map(|h| h.into_handler(remote_peer_id, connected_point)),
+            state: self.state,
         }
     }
 
     fn inbound_protocol(&self) -> <Self::Handler as ProtocolsHandler>::InboundProtocol {
-        if let Some(inner) = self.inner.as_ref() {
-            EitherUpgrade::A(inner.inbound_protocol())
-        } else {
-            EitherUpgrade::B(DeniedUpgrade)
+        match self.inner.as_ref() {
+            Some(inner) if self.state.is_enabled() => EitherUpgrade::A(inner.inbound_protocol()),
+            _ => EitherUpgrade::B(DeniedUpgrade),
         }
     }
 }
@@ -176,6 +327,7 @@ where
 /// Implementation of `ProtocolsHandler` that can be in the disabled state.
 pub struct ToggleProtoHandler<TInner> {
     inner: Option<TInner>,
+    state: Arc<ToggleState>,
 }
 
 impl<TInner> ProtocolsHandler for ToggleProtoHandler<TInner>
@@ -191,10 +343,9 @@ where
     type OutboundOpenInfo = TInner::OutboundOpenInfo;
 
     fn listen_protocol(&self) -> SubstreamProtocol<Self::InboundProtocol> {
-        if let Some(inner) = self.inner.as_ref() {
-            inner.listen_protocol().map_upgrade(EitherUpgrade::A)
-        } else {
-            SubstreamProtocol::new(EitherUpgrade::B(DeniedUpgrade))
+        match self.inner.as_ref() {
+            Some(inner) if self.state.is_enabled() => inner.listen_protocol().map_upgrade(EitherUpgrade::A),
+            _ => SubstreamProtocol::new(EitherUpgrade::B(DeniedUpgrade)),
         }
     }
 
@@ -231,6 +382,10 @@ where
     }
 
     fn connection_keep_alive(&self) -> KeepAlive {
+        if !self.state.is_enabled() {
+            return KeepAlive::No
+        }
+
         self.inner.as_ref().map(|h| h.connection_keep_alive())
             .unwrap_or(KeepAlive::No)
     }
@@ -241,6 +396,10 @@ where
         ProtocolsHandlerEvent<Self::OutboundProtocol, Self::OutboundOpenInfo, Self::OutEvent>,
         Self::Error,
     > {
+        if !self.state.poll_enabled() {
+            return Ok(Async::NotReady)
+        }
+
         if let Some(inner) = self.inner.as_mut() {
             inner.poll()
         } else {
@@ -32,17 +32,41 @@
 //! `/dns4/` or `/dns6/` component, a DNS resolve will be performed and the component will be
 //! replaced with respectively an `/ip4/` or an `/ip6/` component.
 //!
+//! A `/dnsaddr/` component stands for a list of multiaddresses, published in the `TXT` records
+//! of a domain name. These can't be dialed directly, but can be expanded with
+//! [`DnsConfig::resolve_dnsaddr`], once a [`TxtResolver`] has been configured.
+//!
+//! By default, the `/dns4/` and `/dns6/` lookups are performed by the system resolver on a
+//! dedicated thread pool. [`DnsConfig::with_executor`] runs them on an executor provided by the
+//! user instead, and [`DnsConfig::set_ip_resolver`] replaces the resolver altogether.
+//!
 
-use futures::{future::{self, Either, FutureResult, JoinAll}, prelude::*, stream, try_ready};
+use futures::{future::{self, Either, Executor, FutureResult, JoinAll, Loop}, prelude::*, stream, sync::oneshot, try_ready};
 use libp2p_core::{
     Transport,
     multiaddr::{Protocol, Multiaddr},
     transport::{TransportError, ListenerEvent}
 };
 use log::{debug, trace, log_enabled, Level};
-use std::{error, fmt, io, marker::PhantomData, net::IpAddr};
+use std::{
+    collections::{HashSet, VecDeque},
+    error,
+    fmt,
+    io,
+    marker::PhantomData,
+    net::{IpAddr, ToSocketAddrs},
+    sync::Arc
+};
 use tokio_dns::{CpuPoolResolver, Resolver};
 
+/// Future resolving a domain name into IP addresses.
+pub type IpLookup = Box<dyn Future<Item = Vec<IpAddr>, Error = io::Error> + Send>;
+
+/// Background task performing a lookup, as passed to the executor of
+/// [`DnsConfig::with_executor`]. This is the same type as the tasks of the connections, so that a
+/// single executor can run both.
+pub type LookupTask = Box<dyn Future<Item = (), Error = ()> + Send>;
+
 /// Represents the configuration for a DNS transport capability of libp2p.
 ///
 /// This struct implements the `Transport` trait and holds an underlying transport. Any call to
@@ -53,7 +77,73 @@ use tokio_dns::{CpuPoolResolver, Resolver};
 #[derive(Clone)]
 pub struct DnsConfig<T> {
     inner: T,
-    resolver: CpuPoolResolver,
+    resolver: Arc<dyn IpResolver>,
+    txt_resolver: Option<Arc<dyn TxtResolver>>,
+    max_dnsaddr_depth: usize,
+}
+
+/// Prefix of the `TXT` records containing the multiaddresses of a `/dnsaddr/` component.
+const DNSADDR_PREFIX: &str = "dnsaddr=";
+
+/// Resolver of domain names into IP addresses, used to resolve `/dns4/` and `/dns6/` components.
+pub trait IpResolver: Send + Sync {
+    /// Looks up the IP addresses of the given domain name.
+    fn resolve_ip(&self, name: &str) -> IpLookup;
+}
+
+impl IpResolver for CpuPoolResolver {
+    fn resolve_ip(&self, name: &str) -> IpLookup {
+        self.resolve(name)
+    }
+}
+
+/// Resolver performing the blocking lookups of the system resolver on an executor.
+struct ExecutorResolver<E>(E);
+
+impl<E> IpResolver for ExecutorResolver<E>
+where
+    E: Executor<LookupTask> + Send + Sync
+{
+    fn resolve_ip(&self, name: &str) -> IpLookup {
+        let (tx, rx) = oneshot::channel();
+        let name = name.to_owned();
+        let task: LookupTask = Box::new(future::lazy(move || {
+            let addrs = (name.as_str(), 0).to_socket_addrs()
+                .map(|addrs| addrs.map(|addr| addr.ip()).collect());
+            let _ = tx.send(addrs);
+            Ok::<_, ()>(())
+        }));
+        if let Err(err) = self.0.execute(task) {
+            let msg = format!("the executor refused the lookup: {:?}", err.kind());
+            return Box::new(future::err(io::Error::new(io::ErrorKind::Other, msg)))
+        }
+        Box::new(rx.then(|result| match result {
+            Ok(addrs) => addrs,
+            Err(oneshot::Canceled) =>
+                Err(io::Error::new(io::ErrorKind::Other, "the lookup task has been dropped")),
+        }))
+    }
+}
+
+/// Resolver of DNS `TXT` records, used to resolve `/dnsaddr/` components.
+pub trait TxtResolver: Send + Sync {
+    /// Looks up the `TXT` records of the given domain name.
+    fn resolve_txt(&self, name: &str) -> Box<dyn Future<Item = Vec<String>, Error = io::Error> + Send>;
+}
+
+#[cfg(feature = "trust-dns")]
+impl TxtResolver for trust_dns_resolver::AsyncResolver {
+    fn resolve_txt(&self, name: &str) -> Box<dyn Future<Item = Vec<String>, Error = io::Error> + Send> {
+        let lookup = self.txt_lookup(name)
+            .map(|records| {
+                records.iter()
+                    .flat_map(|txt| txt.txt_data().iter())
+                    .map(|data| String::from_utf8_lossy(data).into_owned())
+                    .collect()
+            })
+            .map_err(|e| io::Error::new(io::ErrorKind::Other, e.to_string()));
+        Box::new(lookup)
+    }
 }
 
 impl<T> DnsConfig<T> {
@@ -66,11 +156,142 @@ impl<T> DnsConfig<T> {
     pub fn with_resolve_threads(inner: T, num_threads: usize) -> DnsConfig<T> {
         trace!("Created a CpuPoolResolver");
 
+        DnsConfig::with_ip_resolver(inner, CpuPoolResolver::new(num_threads))
+    }
+
+    /// Same as `new`, but the lookups are spawned on the given executor rather than on a
+    /// dedicated thread pool.
+    ///
+    /// The lookups of the system resolver are blocking, so the executor should be able to
+    /// run blocking tasks without starving its other tasks.
+    pub fn with_executor(inner: T, executor: impl Executor<LookupTask> + Send + Sync + 'static) -> DnsConfig<T> {
+        DnsConfig::with_ip_resolver(inner, ExecutorResolver(executor))
+    }
+
+    /// Same as `new`, but with a custom resolver of IP addresses.
+    pub fn with_ip_resolver(inner: T, resolver: impl IpResolver + 'static) -> DnsConfig<T> {
         DnsConfig {
             inner,
-            resolver: CpuPoolResolver::new(num_threads),
+            resolver: Arc::new(resolver),
+            txt_resolver: None,
+            max_dnsaddr_depth: 4,
         }
     }
+
+    /// Sets the resolver of IP addresses used to resolve `/dns4/` and `/dns6/` components.
+    pub fn set_ip_resolver(&mut self, resolver: impl IpResolver + 'static) -> &mut Self {
+        self.resolver = Arc::new(resolver);
+        self
+    }
+
+    /// Sets the resolver of `TXT` records used to resolve `/dnsaddr/` components.
+    pub fn set_txt_resolver(&mut self, resolver: impl TxtResolver + 'static) -> &mut Self {
+        self.txt_resolver = Some(Arc::new(resolver));
+        self
+    }
+
+    /// Sets the maximum number of nested `/dnsaddr/` lookups, the first one included.
+    ///
+    /// Defaults to 4.
+    pub fn set_max_dnsaddr_depth(&mut self, depth: usize) -> &mut Self {
+        self.max_dnsaddr_depth = depth;
+        self
+    }
+
+    /// Resolves a multiaddress starting with a `/dnsaddr/` component into the multiaddresses
+    /// published in the `_dnsaddr` `TXT` records of its domain name.
+    ///
+    /// Published multiaddresses that start with `/dnsaddr/` are resolved recursively, up to
+    /// the configured depth, and each domain name is looked up at most once. If `addr` ends
+    /// with a `/p2p/` component, only the multiaddresses ending with the same component are
+    /// returned.
+    pub fn resolve_dnsaddr<E>(&self, addr: Multiaddr) -> impl Future<Item = Vec<Multiaddr>, Error = DnsErr<E>> {
+        let resolver = match (&self.txt_resolver, addr.iter().next()) {
+            (Some(resolver), Some(Protocol::Dnsaddr(_))) => resolver.clone(),
+            _ => return Either::A(future::err(DnsErr::MultiaddrNotSupported))
+        };
+
+        let max_depth = self.max_dnsaddr_depth;
+        let peer: Option<Protocol<'static>> = match addr.iter().last() {
+            Some(Protocol::P2p(peer)) => Some(Protocol::P2p(peer)),
+            _ => None
+        };
+        let original = addr.to_string();
+        let mut pending = VecDeque::new();
+        pending.push_back((addr, 0));
+        let resolution = DnsaddrResolution { pending, resolved: Vec::new(), seen: HashSet::new() };
+
+        let future = future::loop_fn(resolution, move |mut resolution| {
+            let (addr, depth) = match resolution.pending.pop_front() {
+                Some(next) => next,
+                None => return Either::A(future::ok(Loop::Break(resolution)))
+            };
+
+            let name = match addr.iter().next() {
+                Some(Protocol::Dnsaddr(name)) => name.into_owned(),
+                _ => {
+                    if !resolution.resolved.contains(&addr) {
+                        resolution.resolved.push(addr)
+                    }
+                    return Either::A(future::ok(Loop::Continue(resolution)))
+                }
+            };
+
+            if depth >= max_depth {
+                debug!("Not resolving {}: maximum /dnsaddr depth reached", addr);
+                return Either::A(future::ok(Loop::Continue(resolution)))
+            }
+            if !resolution.seen.insert(name.clone()) {
+                debug!("Not resolving {}: already resolved", addr);
+                return Either::A(future::ok(Loop::Continue(resolution)))
+            }
+
+            let domain_name = format!("_dnsaddr.{}", name);
+            trace!("Looking up TXT records of {}", domain_name);
+            Either::B(resolver.resolve_txt(&domain_name).then(move |result| match result {
+                Ok(records) => {
+                    for record in records {
+                        if !record.starts_with(DNSADDR_PREFIX) {
+                            continue
+                        }
+                        match record[DNSADDR_PREFIX.len() ..].parse::<Multiaddr>() {
+                            Ok(a) => resolution.pending.push_back((a, depth + 1)),
+                            Err(err) => debug!("Invalid multiaddr in TXT record of {}: {}", domain_name, err)
+                        }
+                    }
+                    Ok(Loop::Continue(resolution))
+                }
+                Err(error) if depth == 0 => Err(DnsErr::ResolveError { domain_name, error }),
+                Err(error) => {
+                    debug!("Failed to look up TXT records of {}: {}", domain_name, error);
+                    Ok(Loop::Continue(resolution))
+                }
+            }))
+        })
+        .and_then(move |resolution| {
+            let resolved = resolution.resolved.into_iter()
+                .filter(|a| peer.is_none() || a.iter().last() == peer)
+                .collect::<Vec<_>>();
+            debug!("/dnsaddr resolution outcome: {} => {:?}", original, resolved);
+            if resolved.is_empty() {
+                Err(DnsErr::ResolveFail(original))
+            } else {
+                Ok(resolved)
+            }
+        });
+
+        Either::B(future)
+    }
+}
+
+/// State of the resolution of a `/dnsaddr/` component.
+struct DnsaddrResolution {
+    /// Multiaddresses left to process, with the number of lookups they result from.
+    pending: VecDeque<(Multiaddr, usize)>,
+    /// Multiaddresses without `/dnsaddr/` component found so far.
+    resolved: Vec<Multiaddr>,
+    /// Domain names already looked up.
+    seen: HashSet<String>,
 }
 
 impl<T> fmt::Debug for DnsConfig<T>
@@ -96,7 +317,7 @@ where
     type ListenerUpgrade = future::MapErr<T::ListenerUpgrade, fn(T::Error) -> Self::Error>;
     type Dial = Either<future::MapErr<T::Dial, fn(T::Error) -> Self::Error>,
         DialFuture<T, JoinFuture<JoinAll<std::vec::IntoIter<Either<
-            ResolveFuture<tokio_dns::IoFuture<Vec<IpAddr>>, T::Error>,
+            ResolveFuture<IpLookup, T::Error>,
             FutureResult<Protocol<'static>, Self::Error>>>>
         >>
     >;
@@ -136,7 +357,7 @@ where
                         } else {
                             None
                         },
-                        inner: resolver.resolve(name),
+                        inner: resolver.resolve_ip(name),
                         ty: ResolveTy::Dns4,
                         error_ty: PhantomData,
                     }),
@@ -147,7 +368,7 @@ where
                         } else {
                             None
                         },
-                        inner: resolver.resolve(name),
+                        inner: resolver.resolve_ip(name),
                         ty: ResolveTy::Dns6,
                         error_ty: PhantomData,
                     }),
@@ -308,13 +529,14 @@ where
 #[cfg(test)]
 mod tests {
     use libp2p_tcp::TcpConfig;
-    use futures::future;
+    use futures::{future, Future};
     use libp2p_core::{
         Transport,
         multiaddr::{Protocol, Multiaddr},
         transport::TransportError
     };
-    use super::DnsConfig;
+    use super::{DnsConfig, LookupTask, TxtResolver};
+    use std::{collections::HashMap, io};
 
     #[test]
     fn basic_resolve() {
@@ -358,4 +580,84 @@ mod tests {
             .dial("/dns6/example.com/tcp/20000".parse().unwrap())
             .unwrap();
     }
+
+    #[test]
+    fn lookups_run_on_the_executor() {
+        use std::sync::{Arc, atomic::{AtomicUsize, Ordering}};
+
+        /// Executor running the tasks on the spot, and counting them.
+        struct InlineExecutor(Arc<AtomicUsize>);
+
+        impl future::Executor<LookupTask> for InlineExecutor {
+            fn execute(&self, task: LookupTask) -> Result<(), future::ExecuteError<LookupTask>> {
+                self.0.fetch_add(1, Ordering::SeqCst);
+                let _ = task.wait();
+                Ok(())
+            }
+        }
+
+        let spawned = Arc::new(AtomicUsize::new(0));
+        let transport = DnsConfig::with_executor(TcpConfig::new(), InlineExecutor(spawned.clone()));
+        let addrs = transport.resolver.resolve_ip("localhost").wait().unwrap();
+        assert!(!addrs.is_empty());
+        assert!(addrs.iter().all(|addr| addr.is_loopback()));
+        assert_eq!(spawned.load(Ordering::SeqCst), 1);
+    }
+
+    #[derive(Clone)]
+    struct StaticTxtResolver(HashMap<&'static str, Vec<&'static str>>);
+
+    impl TxtResolver for StaticTxtResolver {
+        fn resolve_txt(&self, name: &str) -> Box<dyn Future<Item = Vec<String>, Error = io::Error> + Send> {
+            let records = self.0.get(name)
+                .map(|r| r.iter().map(|s| s.to_string()).collect())
+                .ok_or_else(|| io::ErrorKind::NotFound.into());
+            Box::new(future::result(records))
+        }
+    }
+
+    #[test]
+    fn dnsaddr_resolve() {
+        const PEER_A: &str = "QmNnooDu7bfjPFoTZYxMNLWUQJyrVwtbZg5gBMjTezGAJN";
+        const PEER_B: &str = "QmcgpsyWgH8Y8ajJz1Cu72KnS5uo2Aa2LpzU7kinSupNKC";
+
+        let mut records = HashMap::new();
+        records.insert("_dnsaddr.bootstrap.example.org", vec![
+            "dnsaddr=/dnsaddr/a.example.org/p2p/QmNnooDu7bfjPFoTZYxMNLWUQJyrVwtbZg5gBMjTezGAJN",
+            "dnsaddr=/dnsaddr/b.example.org/p2p/QmcgpsyWgH8Y8ajJz1Cu72KnS5uo2Aa2LpzU7kinSupNKC",
+            "dnsaddr=/dnsaddr/bootstrap.example.org",
+            "unrelated record",
+        ]);
+        records.insert("_dnsaddr.a.example.org", vec![
+            "dnsaddr=/ip4/1.2.3.4/tcp/4001/p2p/QmNnooDu7bfjPFoTZYxMNLWUQJyrVwtbZg5gBMjTezGAJN",
+            "dnsaddr=/ip6/::1/tcp/4001/p2p/QmNnooDu7bfjPFoTZYxMNLWUQJyrVwtbZg5gBMjTezGAJN",
+        ]);
+        records.insert("_dnsaddr.b.example.org", vec![
+            "dnsaddr=/ip4/5.6.7.8/tcp/4001/p2p/QmcgpsyWgH8Y8ajJz1Cu72KnS5uo2Aa2LpzU7kinSupNKC",
+            "dnsaddr=/ip4/5.6.7.8/tcp/4001/p2p/QmcgpsyWgH8Y8ajJz1Cu72KnS5uo2Aa2LpzU7kinSupNKC",
+        ]);
+
+        let mut transport = DnsConfig::new(TcpConfig::new());
+        transport.set_txt_resolver(StaticTxtResolver(records));
+
+        let all = transport.resolve_dnsaddr::<io::Error>("/dnsaddr/bootstrap.example.org".parse().unwrap())
+            .wait()
+            .unwrap();
+        assert_eq!(all, vec![
+            format!("/ip4/1.2.3.4/tcp/4001/p2p/{}", PEER_A).parse::<Multiaddr>().unwrap(),
+            format!("/ip6/::1/tcp/4001/p2p/{}", PEER_A).parse().unwrap(),
+            format!("/ip4/5.6.7.8/tcp/4001/p2p/{}", PEER_B).parse().unwrap(),
+        ]);
+
+        let b = format!("/dnsaddr/bootstrap.example.org/p2p/{}", PEER_B).parse().unwrap();
+        let only_b = transport.resolve_dnsaddr::<io::Error>(b).wait().unwrap();
+        assert_eq!(only_b, vec![format!("/ip4/5.6.7.8/tcp/4001/p2p/{}", PEER_B).parse::<Multiaddr>().unwrap()]);
+
+        transport.set_max_dnsaddr_depth(1);
+        let too_deep = transport.resolve_dnsaddr::<io::Error>("/dnsaddr/bootstrap.example.org".parse().unwrap());
+        assert!(too_deep.wait().is_err());
+
+        let unknown = transport.resolve_dnsaddr::<io::Error>("/dnsaddr/unknown.example.org".parse().unwrap());
+        assert!(unknown.wait().is_err());
+    }
 }
@@ -0,0 +1,382 @@
+// Copyright 2019 Parity Technologies (UK) Ltd.
+//
+// Permission is hereby granted, free of charge, to any person obtaining a
+// copy of this software and associated documentation files (the "Software"),
+// to deal in the Software without restriction, including without limitation
+// the rights to use, copy, modify, merge, publish, distribute, sublicense,
+// and/or sell copies of the Software, and to permit persons to whom the
+// Software is furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS
+// OR IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING
+// FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
+// DEALINGS IN THE SOFTWARE.
+//! Implementation of the libp2p `Transport` trait for Windows named pipes.
+//!
+//! Uses [the *tokio* library](https://tokio.rs).
+//!
+//! # Platform support
+//!
+//! This transport only works on Windows. It is the equivalent of the Unix domain sockets
+//! transport, for example for the communication between a daemon and a user interface running
+//! on the same machine.
+//!
+//! # Usage
+//!
+//! The `PipeConfig` transport supports multiaddresses with a single `/unix` component
+//! containing the name of a local pipe, such as `/unix/\\.\pipe\my-daemon`.
+//!
+//! Example:
+//!
+//! ```
+//! # #[cfg(windows)] {
+//! use libp2p_named_pipe::PipeConfig;
+//!
+//! let pipe = PipeConfig::new();
+//! # }
+//! ```
+//!
+//! The `PipeConfig` structs implements the `Transport` trait of the `core` library. See the
+//! documentation of `core` and of libp2p in general to learn how to use the `Transport` trait.
+//!
+//! # Access control
+//!
+//! By default, the pipes we listen on get the default security descriptor of Windows, which
+//! grants full access to the LocalSystem account, to the administrators and to the owner of
+//! the process, and read access to everyone else. Use `PipeConfig::security_descriptor` to
+//! restrict or extend the set of users that can connect. Remote clients are rejected unless
+//! `PipeConfig::reject_remote_clients` is set to `false`.
+
+#![cfg(windows)]
+
+use futures::{prelude::*, future::{self, FutureResult}, try_ready};
+use libp2p_core::{
+    Transport,
+    multiaddr::{Protocol, Multiaddr},
+    transport::{ListenerEvent, TransportError}
+};
+use log::debug;
+use std::{ffi::OsStr, fs::OpenOptions, io, iter, mem, ptr, sync::Arc};
+use std::os::windows::{ffi::OsStrExt, fs::OpenOptionsExt, io::{FromRawHandle, IntoRawHandle, RawHandle}};
+use tokio_named_pipes::NamedPipe;
+use tokio_reactor::Handle;
+use winapi::shared::minwindef::{DWORD, FALSE};
+use winapi::um::{handleapi::INVALID_HANDLE_VALUE, minwinbase::SECURITY_ATTRIBUTES, winnt::PSECURITY_DESCRIPTOR};
+use winapi::um::sddl::ConvertStringSecurityDescriptorToSecurityDescriptorW;
+use winapi::um::winbase::{
+    CreateNamedPipeW, LocalFree, FILE_FLAG_FIRST_PIPE_INSTANCE, FILE_FLAG_OVERLAPPED,
+    PIPE_ACCESS_DUPLEX, PIPE_READMODE_BYTE, PIPE_REJECT_REMOTE_CLIENTS, PIPE_TYPE_BYTE,
+    PIPE_UNLIMITED_INSTANCES, PIPE_WAIT
+};
+
+/// Prefix of the names of the pipes of the local machine.
+const LOCAL_PIPE_PREFIX: &str = r"\\.\pipe\";
+
+/// Revision of the SDDL format understood by `ConvertStringSecurityDescriptorToSecurityDescriptorW`.
+const SDDL_REVISION_1: DWORD = 1;
+
+/// Size of the input and output buffers of the pipes we create.
+const BUFFER_SIZE: DWORD = 64 * 1024;
+
+/// Represents the configuration for a Windows named pipes transport capability for libp2p.
+///
+/// The pipes created by libp2p will need to be progressed by running the futures and streams
+/// obtained by libp2p through the tokio reactor.
+#[derive(Debug, Clone)]
+pub struct PipeConfig {
+    /// Security descriptor of the pipes we listen on, if not the default one.
+    security_descriptor: Option<Arc<SecurityDescriptor>>,
+    /// Whether connections from other machines are refused.
+    reject_remote_clients: bool,
+}
+
+impl PipeConfig {
+    /// Creates a new configuration object for named pipes.
+    pub fn new() -> PipeConfig {
+        PipeConfig {
+            security_descriptor: None,
+            reject_remote_clients: true,
+        }
+    }
+
+    /// Sets the security descriptor of the pipes we listen on, in the [Security Descriptor
+    /// Definition Language](https://docs.microsoft.com/windows/win32/secauthz/security-descriptor-definition-language).
+    ///
+    /// For example, `D:(A;;GA;;;AU)` allows all authenticated users to connect.
+    ///
+    /// Returns an error if the descriptor can't be parsed.
+    pub fn security_descriptor(mut self, sddl: &str) -> io::Result<Self> {
+        self.security_descriptor = Some(Arc::new(SecurityDescriptor::from_sddl(sddl)?));
+        Ok(self)
+    }
+
+    /// Sets whether connections from other machines are refused. Defaults to `true`.
+    pub fn reject_remote_clients(mut self, reject: bool) -> Self {
+        self.reject_remote_clients = reject;
+        self
+    }
+
+    /// Creates a new instance of the pipe with the given name, waiting for a client.
+    ///
+    /// If `first` is true, fails if a pipe with this name already exists.
+    fn create_instance(&self, name: &OsStr, first: bool) -> io::Result<NamedPipe> {
+        let name = name.encode_wide().chain(iter::once(0)).collect::<Vec<_>>();
+        let mut attributes = SECURITY_ATTRIBUTES {
+            nLength: mem::size_of::<SECURITY_ATTRIBUTES>() as DWORD,
+            lpSecurityDescriptor: self.security_descriptor.as_ref().map_or(ptr::null_mut(), |sd| sd.0),
+            bInheritHandle: FALSE,
+        };
+        let open_mode = PIPE_ACCESS_DUPLEX | FILE_FLAG_OVERLAPPED |
+            if first { FILE_FLAG_FIRST_PIPE_INSTANCE } else { 0 };
+        let pipe_mode = PIPE_TYPE_BYTE | PIPE_READMODE_BYTE | PIPE_WAIT |
+            if self.reject_remote_clients { PIPE_REJECT_REMOTE_CLIENTS } else { 0 };
+
+        let handle = unsafe {
+            CreateNamedPipeW(
+                name.as_ptr(),
+                open_mode,
+                pipe_mode,
+                PIPE_UNLIMITED_INSTANCES,
+                BUFFER_SIZE,
+                BUFFER_SIZE,
+                0,
+                &mut attributes
+            )
+        };
+        if handle == INVALID_HANDLE_VALUE {
+            return Err(io::Error::last_os_error())
+        }
+
+        let pipe = unsafe { mio_named_pipes::NamedPipe::from_raw_handle(handle as RawHandle) };
+        NamedPipe::from_pipe(pipe, &Handle::default())
+    }
+}
+
+impl Default for PipeConfig {
+    fn default() -> Self {
+        PipeConfig::new()
+    }
+}
+
+impl Transport for PipeConfig {
+    type Output = NamedPipe;
+    type Error = io::Error;
+    type Listener = PipeListenStream;
+    type ListenerUpgrade = FutureResult<Self::Output, io::Error>;
+    type Dial = PipeDialFuture;
+
+    fn listen_on(self, addr: Multiaddr) -> Result<Self::Listener, TransportError<Self::Error>> {
+        let name = match multiaddr_to_pipe_name(&addr) {
+            Ok(name) => name,
+            Err(()) => return Err(TransportError::MultiaddrNotSupported(addr)),
+        };
+        let pending = self.create_instance(OsStr::new(&name), true).map_err(TransportError::Other)?;
+        debug!("Now listening on {}", addr);
+        Ok(PipeListenStream {
+            config: self,
+            name,
+            addr,
+            pending: Some(pending),
+            connecting: false,
+            tell_new_addr: true,
+        })
+    }
+
+    fn dial(self, addr: Multiaddr) -> Result<Self::Dial, TransportError<Self::Error>> {
+        match multiaddr_to_pipe_name(&addr) {
+            Ok(name) => {
+                debug!("Dialing {}", addr);
+                Ok(PipeDialFuture { name: Some(name) })
+            }
+            Err(()) => Err(TransportError::MultiaddrNotSupported(addr)),
+        }
+    }
+}
+
+/// Turns a `Multiaddr` containing a single `Unix` component into the name of a local pipe.
+fn multiaddr_to_pipe_name(addr: &Multiaddr) -> Result<String, ()> {
+    let mut iter = addr.iter();
+    let name = match iter.next() {
+        Some(Protocol::Unix(ref name)) => name.to_string(),
+        _ => return Err(())
+    };
+
+    if iter.next().is_some() {
+        return Err(());
+    }
+
+    if !name.starts_with(LOCAL_PIPE_PREFIX) || name.len() == LOCAL_PIPE_PREFIX.len() {
+        return Err(());
+    }
+
+    Ok(name)
+}
+
+/// A security descriptor allocated by `ConvertStringSecurityDescriptorToSecurityDescriptorW`.
+#[derive(Debug)]
+struct SecurityDescriptor(PSECURITY_DESCRIPTOR);
+
+// The descriptor is never modified after its creation.
+unsafe impl Send for SecurityDescriptor {}
+unsafe impl Sync for SecurityDescriptor {}
+
+impl SecurityDescriptor {
+    fn from_sddl(sddl: &str) -> io::Result<Self> {
+        let sddl = OsStr::new(sddl).encode_wide().chain(iter::once(0)).collect::<Vec<_>>();
+        let mut descriptor = ptr::null_mut();
+        let result = unsafe {
+            ConvertStringSecurityDescriptorToSecurityDescriptorW(
+                sddl.as_ptr(),
+                SDDL_REVISION_1,
+                &mut descriptor,
+                ptr::null_mut()
+            )
+        };
+        if result == 0 {
+            return Err(io::Error::last_os_error())
+        }
+        Ok(SecurityDescriptor(descriptor))
+    }
+}
+
+impl Drop for SecurityDescriptor {
+    fn drop(&mut self) {
+        unsafe { LocalFree(self.0); }
+    }
+}
+
+/// Future that connects to a pipe.
+#[derive(Debug)]
+pub struct PipeDialFuture {
+    name: Option<String>,
+}
+
+impl Future for PipeDialFuture {
+    type Item = NamedPipe;
+    type Error = io::Error;
+
+    fn poll(&mut self) -> Poll<NamedPipe, io::Error> {
+        let name = self.name.take().expect("future polled after completion");
+        // Fails with `ERROR_PIPE_BUSY` if the listener is in the middle of accepting another
+        // client, in which case the dialer is expected to try again.
+        let file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .custom_flags(FILE_FLAG_OVERLAPPED)
+            .open(&name)?;
+        let pipe = unsafe { mio_named_pipes::NamedPipe::from_raw_handle(file.into_raw_handle()) };
+        Ok(Async::Ready(NamedPipe::from_pipe(pipe, &Handle::default())?))
+    }
+}
+
+/// Stream of the clients connecting to a pipe.
+///
+/// A pipe instance accepts a single client, so a new instance is created every time a client
+/// connects to the previous one.
+pub struct PipeListenStream {
+    config: PipeConfig,
+    /// Name of the pipe.
+    name: String,
+    /// Address we are listening on.
+    addr: Multiaddr,
+    /// Instance waiting for the next client.
+    pending: Option<NamedPipe>,
+    /// Whether a client is connecting to `pending`.
+    connecting: bool,
+    /// Whether the `NewAddress` event still has to be produced.
+    tell_new_addr: bool,
+}
+
+impl Stream for PipeListenStream {
+    type Item = ListenerEvent<FutureResult<NamedPipe, io::Error>>;
+    type Error = io::Error;
+
+    fn poll(&mut self) -> Poll<Option<Self::Item>, io::Error> {
+        if self.tell_new_addr {
+            self.tell_new_addr = false;
+            return Ok(Async::Ready(Some(ListenerEvent::NewAddress(self.addr.clone()))))
+        }
+
+        if self.pending.is_none() {
+            self.pending = Some(self.config.create_instance(OsStr::new(&self.name), false)?);
+        }
+        let pipe = self.pending.as_ref().expect("pending instance created above");
+
+        if !self.connecting {
+            match pipe.connect() {
+                Ok(()) => {}
+                Err(ref err) if err.kind() == io::ErrorKind::WouldBlock => self.connecting = true,
+                Err(err) => return Err(err),
+            }
+        }
+        if self.connecting {
+            // The instance becomes writable once a client is connected.
+            try_ready!(pipe.poll_write_ready());
+            self.connecting = false;
+        }
+
+        debug!("incoming connection on {}", self.addr);
+        let pipe = self.pending.take().expect("pending instance created above");
+        // Create the next instance right away, so that clients don't find the pipe busy.
+        self.pending = Some(self.config.create_instance(OsStr::new(&self.name), false)?);
+        Ok(Async::Ready(Some(ListenerEvent::Upgrade {
+            upgrade: future::ok(pipe),
+            listen_addr: self.addr.clone(),
+            remote_addr: self.addr.clone()
+        })))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{multiaddr_to_pipe_name, PipeConfig};
+    use futures::prelude::*;
+    use libp2p_core::{
+        Transport,
+        multiaddr::{Protocol, Multiaddr},
+        transport::ListenerEvent
+    };
+    use tokio::runtime::current_thread::Runtime;
+
+    #[test]
+    fn multiaddr_to_pipe_name_conversion() {
+        assert!(multiaddr_to_pipe_name(&"/ip4/127.0.0.1/tcp/1234".parse::<Multiaddr>().unwrap()).is_err());
+        assert!(multiaddr_to_pipe_name(&Multiaddr::from(Protocol::Unix("/tmp/foo".into()))).is_err());
+        assert!(multiaddr_to_pipe_name(&Multiaddr::from(Protocol::Unix(r"\\.\pipe\".into()))).is_err());
+        assert_eq!(
+            multiaddr_to_pipe_name(&Multiaddr::from(Protocol::Unix(r"\\.\pipe\foo".into()))),
+            Ok(r"\\.\pipe\foo".to_owned())
+        );
+    }
+
+    #[test]
+    fn communicating_between_dialer_and_listener() {
+        let name = format!(r"\\.\pipe\libp2p-test-{}", rand_suffix());
+        let addr = Multiaddr::from(Protocol::Unix(name.into()));
+
+        let mut rt = Runtime::new().unwrap();
+        let listener = PipeConfig::new().listen_on(addr.clone()).unwrap()
+            .filter_map(ListenerEvent::into_upgrade)
+            .into_future()
+            .map_err(|(err, _)| err)
+            .and_then(|(upgrade, _)| upgrade.expect("the listener is alive").0)
+            .and_then(|pipe| tokio_io::io::read_exact(pipe, [0; 3]))
+            .map(|(_, buf)| assert_eq!(buf, [1, 2, 3]));
+        let dialer = PipeConfig::new().dial(addr).unwrap()
+            .and_then(|pipe| tokio_io::io::write_all(pipe, [1u8, 2, 3]))
+            .map(|_| ());
+
+        rt.block_on(listener.join(dialer)).unwrap();
+    }
+
+    fn rand_suffix() -> u64 {
+        use std::time::{SystemTime, UNIX_EPOCH};
+        SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_nanos() as u64 ^ u64::from(std::process::id())
+    }
+}
@@ -0,0 +1,64 @@
+// Copyright 2019 Parity Technologies (UK) Ltd.
+//
+// Permission is hereby granted, free of charge, to any person obtaining a
+// copy of this software and associated documentation files (the "Software"),
+// to deal in the Software without restriction, including without limitation
+// the rights to use, copy, modify, merge, publish, distribute, sublicense,
+// and/or sell copies of the Software, and to permit persons to whom the
+// Software is furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS
+// OR IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING
+// FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
+// DEALINGS IN THE SOFTWARE.
+//! Benchmarks of the throughput of a single connection over the loopback interface.
+//!
+//! They serve as the baseline that alternative socket backends must be compared against.
+
+use criterion::{Benchmark, Criterion, Throughput, criterion_main, criterion_group};
+use futures::prelude::*;
+use libp2p_core::{Transport, transport::ListenerEvent};
+use libp2p_tcp::TcpConfig;
+use tokio::runtime::current_thread::Runtime;
+
+/// Number of bytes sent on each connection.
+const TRANSFER_SIZE: usize = 16 * 1024 * 1024;
+
+fn loopback_transfer(c: &mut Criterion) {
+    c.bench("tcp", Benchmark::new("send 16 MiB over loopback", |b| {
+        let mut rt = Runtime::new().unwrap();
+        let listener = TcpConfig::new().listen_on("/ip4/127.0.0.1/tcp/0".parse().unwrap()).unwrap();
+        let (event, listener) = rt.block_on(listener.into_future()).map_err(|(err, _)| err).unwrap();
+        let addr = event.and_then(ListenerEvent::into_new_address).expect("the first event is the address");
+
+        // Read everything the dialer sends, then acknowledge with a single byte.
+        let server = listener
+            .filter_map(ListenerEvent::into_upgrade)
+            .for_each(|(upgrade, _)| {
+                upgrade
+                    .and_then(|socket| tokio_io::io::read_to_end(socket, Vec::with_capacity(TRANSFER_SIZE)))
+                    .and_then(|(socket, _)| tokio_io::io::write_all(socket, [1u8]))
+                    .map(|_| ())
+            })
+            .map_err(|err| panic!("{:?}", err));
+        rt.spawn(server);
+
+        let data = vec![0u8; TRANSFER_SIZE];
+        b.iter(|| {
+            let transfer = TcpConfig::new().dial(addr.clone()).unwrap()
+                .and_then(|socket| tokio_io::io::write_all(socket, &data[..]))
+                .and_then(|(socket, _)| tokio_io::io::shutdown(socket))
+                .and_then(|socket| tokio_io::io::read_exact(socket, [0u8; 1]));
+            rt.block_on(transfer).unwrap();
+        })
+    }).throughput(Throughput::Bytes(TRANSFER_SIZE as u32)).sample_size(10));
+}
+
+criterion_group!(benches, loopback_transfer);
+criterion_main!(benches);
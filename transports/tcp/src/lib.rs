@@ -22,6 +22,11 @@
 //!
 //! Uses [the *tokio* library](https://tokio.rs).
 //!
+//! The sockets are registered with the tokio 0.1 reactor of the current thread, or with the
+//! default background reactor if there is none, so the transport works from any executor as
+//! long as a tokio 0.1 reactor is available to the process. Other runtimes, such as
+//! `async-std`, are built on `std::future` and can't provide the sockets of this transport.
+//!
 //! # Usage
 //!
 //! Example:
@@ -52,16 +57,18 @@ use libp2p_core::{
 };
 use log::{debug, trace};
 use std::{
-    collections::VecDeque,
+    collections::{HashSet, VecDeque},
     fmt,
     io::{self, Read, Write},
     iter::{self, FromIterator},
     net::{IpAddr, SocketAddr},
+    sync::{Arc, Mutex},
     time::Duration,
     vec::IntoIter
 };
 use tk_listen::{ListenExt, SleepOnError};
 use tokio_io::{AsyncRead, AsyncWrite};
+use tokio_reactor::Handle;
 use tokio_tcp::{ConnectFuture, Incoming, TcpStream};
 
 /// Represents the configuration for a TCP/IP transport capability for libp2p.
@@ -82,6 +89,26 @@ pub struct TcpConfig {
     keepalive: Option<Option<Duration>>,
     /// `TCP_NODELAY` to set for opened sockets, or `None` to keep default.
     nodelay: Option<bool>,
+    /// Addresses of the listeners whose port is reused for dialing, or `None` if port reuse is
+    /// disabled.
+    port_reuse: Option<PortReuse>,
+}
+
+/// Socket addresses of the listeners that are candidates for port reuse, shared between the
+/// clones of a `TcpConfig`.
+#[derive(Debug, Clone, Default)]
+struct PortReuse {
+    listen_addrs: Arc<Mutex<HashSet<SocketAddr>>>,
+}
+
+impl PortReuse {
+    /// Returns a listen address of the same IP version as `remote`, if any.
+    fn local_dial_addr(&self, remote: &SocketAddr) -> Option<SocketAddr> {
+        let addrs = self.listen_addrs.lock().expect("the lock is never poisoned");
+        addrs.iter()
+            .find(|a| a.is_ipv4() == remote.is_ipv4() && a.ip().is_loopback() == remote.ip().is_loopback())
+            .cloned()
+    }
 }
 
 impl TcpConfig {
@@ -94,6 +121,7 @@ impl TcpConfig {
             ttl: None,
             keepalive: None,
             nodelay: None,
+            port_reuse: None,
         }
     }
 
@@ -126,6 +154,17 @@ impl TcpConfig {
         self.nodelay = Some(value);
         self
     }
+
+    /// Sets whether outgoing connections use the port of one of our listeners as local port.
+    ///
+    /// Listening sockets are then created with `SO_REUSEADDR` and `SO_REUSEPORT`, and dialing
+    /// sockets are bound to the address of a listener of the same IP version before connecting.
+    /// The remote then observes the same address for our incoming and outgoing connections,
+    /// which is what NAT hole punching relies on. Port reuse is disabled by default.
+    pub fn port_reuse(mut self, value: bool) -> Self {
+        self.port_reuse = if value { Some(PortReuse::default()) } else { None };
+        self
+    }
 }
 
 impl Transport for TcpConfig {
@@ -143,10 +182,23 @@ impl Transport for TcpConfig {
                 return Err(TransportError::MultiaddrNotSupported(addr))
             };
 
-        let listener = tokio_tcp::TcpListener::bind(&socket_addr).map_err(TransportError::Other)?;
+        let listener =
+            if self.port_reuse.is_some() {
+                let listener = reusable_builder(&socket_addr)
+                    .and_then(|b| { b.bind(&socket_addr)?; b.listen(1024) })
+                    .map_err(TransportError::Other)?;
+                tokio_tcp::TcpListener::from_std(listener, &Handle::default())
+                    .map_err(TransportError::Other)?
+            } else {
+                tokio_tcp::TcpListener::bind(&socket_addr).map_err(TransportError::Other)?
+            };
         let local_addr = listener.local_addr().map_err(TransportError::Other)?;
         let port = local_addr.port();
 
+        if let Some(port_reuse) = &self.port_reuse {
+            port_reuse.listen_addrs.lock().expect("the lock is never poisoned").insert(local_addr);
+        }
+
         // Determine all our listen addresses which is either a single local IP address
         // or (if a wildcard IP address was used) the addresses of all our interfaces,
         // as reported by `get_if_addrs`.
@@ -179,6 +231,7 @@ impl Transport for TcpConfig {
 
         let stream = TcpListenStream {
             inner: Ok(listener.incoming().sleep_on_error(self.sleep_on_error)),
+            local_addr,
             port,
             addrs,
             pending: VecDeque::new(),
@@ -207,8 +260,20 @@ impl Transport for TcpConfig {
 
         debug!("Dialing {}", addr);
 
+        let local_addr = self.port_reuse.as_ref().and_then(|p| p.local_dial_addr(&socket_addr));
+        let inner =
+            if let Some(local_addr) = local_addr {
+                trace!("Binding dialing socket to {}", local_addr);
+                let stream = reusable_builder(&socket_addr)
+                    .and_then(|b| { b.bind(&local_addr)?; b.to_tcp_stream() })
+                    .map_err(TransportError::Other)?;
+                TcpStream::connect_std(stream, &socket_addr, &Handle::default())
+            } else {
+                TcpStream::connect(&socket_addr)
+            };
+
         let future = TcpDialFut {
-            inner: TcpStream::connect(&socket_addr),
+            inner,
             config: self
         };
 
@@ -268,6 +333,28 @@ fn host_addresses(port: u16) -> io::Result<Vec<(IpAddr, IpNet, Multiaddr)>> {
     Ok(addrs)
 }
 
+/// Creates a socket builder for the IP version of `addr`, with `SO_REUSEADDR` and, where
+/// supported, `SO_REUSEPORT` enabled.
+fn reusable_builder(addr: &SocketAddr) -> io::Result<net2::TcpBuilder> {
+    let builder = if addr.is_ipv4() {
+        net2::TcpBuilder::new_v4()?
+    } else {
+        net2::TcpBuilder::new_v6()?
+    };
+    builder.reuse_address(true)?;
+    reuse_port(&builder)?;
+    Ok(builder)
+}
+
+#[cfg(unix)]
+fn reuse_port(b: &net2::TcpBuilder) -> io::Result<()> {
+    net2::unix::UnixTcpBuilderExt::reuse_port(b, true)?;
+    Ok(())
+}
+
+#[cfg(not(unix))]
+fn reuse_port(_: &net2::TcpBuilder) -> io::Result<()> { Ok(()) }
+
 /// Applies the socket configuration parameters to a socket.
 fn apply_config(config: &TcpConfig, socket: &TcpStream) -> Result<(), io::Error> {
     if let Some(recv_buffer_size) = config.recv_buffer_size {
@@ -357,6 +444,8 @@ type Buffer = VecDeque<ListenerEvent<FutureResult<TcpTransStream, io::Error>>>;
 pub struct TcpListenStream {
     /// Stream of incoming sockets.
     inner: Result<SleepOnError<Incoming>, Option<io::Error>>,
+    /// The socket address the listener is bound to.
+    local_addr: SocketAddr,
     /// The port which we use as our listen port in listener event addresses.
     port: u16,
     /// The set of known addresses.
@@ -485,6 +574,14 @@ impl Stream for TcpListenStream {
     }
 }
 
+impl Drop for TcpListenStream {
+    fn drop(&mut self) {
+        if let Some(port_reuse) = &self.config.port_reuse {
+            port_reuse.listen_addrs.lock().expect("the lock is never poisoned").remove(&self.local_addr);
+        }
+    }
+}
+
 impl fmt::Debug for TcpListenStream {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self.inner {
@@ -686,6 +783,30 @@ mod tests {
         let _ = rt.block_on(action).unwrap();
     }
 
+    #[test]
+    fn port_reuse_dials_from_listen_port() {
+        let mut rt = Runtime::new().unwrap();
+        let addr = "/ip4/127.0.0.1/tcp/0".parse::<Multiaddr>().unwrap();
+
+        let listener = TcpConfig::new().port_reuse(true).listen_on(addr.clone()).unwrap();
+        let (event, listener) = rt.block_on(listener.into_future()).map_err(|(e, _)| e).unwrap();
+        let listen_addr = event.unwrap().into_new_address().unwrap();
+
+        let dialer = TcpConfig::new().port_reuse(true);
+        let dialer_listener = dialer.clone().listen_on(addr).unwrap();
+        let (event, _dialer_listener) = rt.block_on(dialer_listener.into_future()).map_err(|(e, _)| e).unwrap();
+        let dialer_listen_addr = event.unwrap().into_new_address().unwrap();
+
+        let dial = dialer.dial(listen_addr).unwrap();
+        let incoming = listener
+            .filter_map(ListenerEvent::into_upgrade)
+            .into_future()
+            .map_err(|(e, _)| e);
+        let (incoming, _) = rt.block_on(incoming.join(dial)).unwrap();
+        let (_, remote_addr) = incoming.unwrap();
+        assert_eq!(remote_addr, dialer_listen_addr);
+    }
+
     #[test]
     fn replace_port_0_in_returned_multiaddr_ipv4() {
         let tcp = TcpConfig::new();
@@ -44,6 +44,12 @@ use url::Url;
 /// Max. number of payload bytes of a single frame.
 const MAX_DATA_SIZE: u64 = 256 * 1024 * 1024;
 
+/// Max. number of bytes we read while looking for the request line of a handshake request.
+const MAX_REQUEST_LINE_SIZE: usize = 8 * 1024;
+
+/// Response sent to clients requesting a path we do not listen on.
+const NOT_FOUND: &[u8] = b"HTTP/1.1 404 Not Found\r\nContent-Length: 0\r\n\r\n";
+
 /// A Websocket transport whose output type is a [`Stream`] and [`Sink`] of
 /// frame payloads which does not implement [`AsyncRead`] or
 /// [`AsyncWrite`]. See [`crate::WsConfig`] if you require the latter.
@@ -121,15 +127,19 @@ where
     fn listen_on(self, addr: Multiaddr) -> Result<Self::Listener, TransportError<Self::Error>> {
         let mut inner_addr = addr.clone();
 
-        let (use_tls, proto) = match inner_addr.pop() {
-            Some(p@Protocol::Wss(_)) =>
+        let (use_tls, proto, path) = match inner_addr.pop() {
+            Some(Protocol::Wss(path)) =>
                 if self.tls_config.server.is_some() {
-                    (true, p)
+                    let path = path.into_owned();
+                    (true, Protocol::Wss(path.clone().into()), path)
                 } else {
                     debug!("/wss address but TLS server support is not configured");
                     return Err(TransportError::MultiaddrNotSupported(addr))
                 }
-            Some(p@Protocol::Ws(_)) => (false, p),
+            Some(Protocol::Ws(path)) => {
+                let path = path.into_owned();
+                (false, Protocol::Ws(path.clone().into()), path)
+            }
             _ => {
                 debug!("{} is not a websocket multiaddr", addr);
                 return Err(TransportError::MultiaddrNotSupported(addr))
@@ -158,6 +168,7 @@ where
                     let remote1 = remote_addr.clone(); // used for logging
                     let remote2 = remote_addr.clone(); // used for logging
                     let tls_config = tls_config.clone();
+                    let path = path.clone();
                     let upgraded = upgrade.map_err(Error::Transport)
                         .and_then(move |stream| {
                             trace!("incoming connection from {}", remote1);
@@ -177,32 +188,37 @@ where
                         })
                         .and_then(move |stream| {
                             trace!("receiving websocket handshake request from {}", remote2);
-                            let mut s = handshake::Server::new();
-                            if use_deflate {
-                                s.add_extension(Box::new(Deflate::new(Mode::Server)));
-                            }
-                            Framed::new(stream, s)
-                                .into_future()
-                                .map_err(|(e, _framed)| Error::Handshake(Box::new(e)))
-                                .and_then(move |(request, framed)| {
-                                    if let Some(r) = request {
-                                        trace!("accepting websocket handshake request from {}", remote2);
-                                        let key = Vec::from(r.key());
-                                        Either::A(framed.send(Ok(handshake::Accept::new(key)))
-                                            .map_err(|e| Error::Base(Box::new(e)))
-                                            .map(move |f| {
-                                                trace!("websocket handshake with {} successful", remote2);
-                                                let (mut handshake, mut c) =
-                                                    new_connection(f, max_size, Mode::Server);
-                                                c.add_extensions(handshake.drain_extensions());
-                                                BytesConnection { inner: c }
-                                            }))
-                                    } else {
-                                        debug!("connection to {} terminated during handshake", remote2);
-                                        let e: io::Error = io::ErrorKind::ConnectionAborted.into();
-                                        Either::B(future::err(Error::Handshake(Box::new(e))))
-                                    }
-                                })
+                            accept_path(stream, path).and_then(move |(stream, read_buf)| {
+                                let mut s = handshake::Server::new();
+                                if use_deflate {
+                                    s.add_extension(Box::new(Deflate::new(Mode::Server)));
+                                }
+                                // The bytes read while checking the path are part of the request.
+                                let mut parts = FramedParts::new(stream, s);
+                                parts.read_buf = read_buf;
+                                Framed::from_parts(parts)
+                                    .into_future()
+                                    .map_err(|(e, _framed)| Error::Handshake(Box::new(e)))
+                                    .and_then(move |(request, framed)| {
+                                        if let Some(r) = request {
+                                            trace!("accepting websocket handshake request from {}", remote2);
+                                            let key = Vec::from(r.key());
+                                            Either::A(framed.send(Ok(handshake::Accept::new(key)))
+                                                .map_err(|e| Error::Base(Box::new(e)))
+                                                .map(move |f| {
+                                                    trace!("websocket handshake with {} successful", remote2);
+                                                    let (mut handshake, mut c) =
+                                                        new_connection(f, max_size, Mode::Server);
+                                                    c.add_extensions(handshake.drain_extensions());
+                                                    BytesConnection { inner: c }
+                                                }))
+                                        } else {
+                                            debug!("connection to {} terminated during handshake", remote2);
+                                            let e: io::Error = io::ErrorKind::ConnectionAborted.into();
+                                            Either::B(future::err(Error::Handshake(Box::new(e))))
+                                        }
+                                    })
+                            })
                         });
                     ListenerEvent::Upgrade {
                         upgrade: Box::new(upgraded) as Box<dyn Future<Item = _, Error = _> + Send>,
@@ -252,28 +268,44 @@ where
 
     let WsConfig { transport, max_data_size, tls_config, .. } = config;
 
-    let (host_port, dns_name) = match host_and_dnsname(&address) {
-        Ok(x) => x,
-        Err(e) => return Either::A(future::err(e))
-    };
-
     let mut inner_addr = address.clone();
 
-    let (use_tls, path) = match inner_addr.pop() {
+    let (mut use_tls, path) = match inner_addr.pop() {
         Some(Protocol::Ws(path)) => (false, path),
-        Some(Protocol::Wss(path)) => {
-            if dns_name.is_none() {
-                debug!("no DNS name in {}", address);
-                return Either::A(future::err(Error::InvalidMultiaddr(address)))
-            }
-            (true, path)
-        }
+        Some(Protocol::Wss(path)) => (true, path),
         _ => {
             debug!("{} is not a websocket multiaddr", address);
             return Either::A(future::err(Error::InvalidMultiaddr(address)))
         }
     };
 
+    // `/tls/ws` is equivalent to `/wss`, and `/tls/sni/<name>/ws` sets the server name
+    // explicitly, e.g. when the address only contains an IP address.
+    let sni = match inner_addr.iter().last() {
+        Some(Protocol::Sni(name)) => Some(name.into_owned()),
+        _ => None
+    };
+    if sni.is_some() {
+        inner_addr.pop();
+    }
+    if let Some(Protocol::Tls) = inner_addr.iter().last() {
+        inner_addr.pop();
+        use_tls = true;
+    } else if sni.is_some() {
+        debug!("/sni without /tls in {}", address);
+        return Either::A(future::err(Error::InvalidMultiaddr(address)))
+    }
+
+    let (host_port, dns_name) = match host_and_dnsname(&inner_addr, sni.as_ref().map(String::as_str)) {
+        Ok(x) => x,
+        Err(e) => return Either::A(future::err(e))
+    };
+
+    if use_tls && dns_name.is_none() {
+        debug!("no DNS name in {}", address);
+        return Either::A(future::err(Error::InvalidMultiaddr(address)))
+    }
+
     let dial = match transport.dial(inner_addr) {
         Ok(dial) => dial,
         Err(TransportError::MultiaddrNotSupported(a)) =>
@@ -340,21 +372,27 @@ where
 }
 
 // Extract host, port and optionally the DNS name from the given [`Multiaddr`].
-fn host_and_dnsname<T>(addr: &Multiaddr) -> Result<(String, Option<webpki::DNSName>), Error<T>> {
+//
+// The server name of an `/sni` component, if any, takes precedence over the host of the address.
+fn host_and_dnsname<T>(addr: &Multiaddr, sni: Option<&str>)
+    -> Result<(String, Option<webpki::DNSName>), Error<T>>
+{
     let mut iter = addr.iter();
-    match (iter.next(), iter.next()) {
-        (Some(Protocol::Ip4(ip)), Some(Protocol::Tcp(port))) =>
-            Ok((format!("{}:{}", ip, port), None)),
-        (Some(Protocol::Ip6(ip)), Some(Protocol::Tcp(port))) =>
-            Ok((format!("{}:{}", ip, port), None)),
+    let (host, port, dns_name) = match (iter.next(), iter.next()) {
+        (Some(Protocol::Ip4(ip)), Some(Protocol::Tcp(port))) => (ip.to_string(), port, None),
+        (Some(Protocol::Ip6(ip)), Some(Protocol::Tcp(port))) => (ip.to_string(), port, None),
         (Some(Protocol::Dns4(h)), Some(Protocol::Tcp(port))) =>
-            Ok((format!("{}:{}", &h, port), Some(tls::dns_name_ref(&h)?.to_owned()))),
+            (h.to_string(), port, Some(tls::dns_name_ref(&h)?.to_owned())),
         (Some(Protocol::Dns6(h)), Some(Protocol::Tcp(port))) =>
-            Ok((format!("{}:{}", &h, port), Some(tls::dns_name_ref(&h)?.to_owned()))),
+            (h.to_string(), port, Some(tls::dns_name_ref(&h)?.to_owned())),
         _ => {
             debug!("multi-address format not supported: {}", addr);
-            Err(Error::InvalidMultiaddr(addr.clone()))
+            return Err(Error::InvalidMultiaddr(addr.clone()))
         }
+    };
+    match sni {
+        Some(name) => Ok((format!("{}:{}", name, port), Some(tls::dns_name_ref(name)?.to_owned()))),
+        None => Ok((format!("{}:{}", host, port), dns_name))
     }
 }
 
@@ -396,6 +434,74 @@ fn location_to_multiaddr<T>(location: &str) -> Result<Multiaddr, Error<T>> {
     }
 }
 
+/// Read the request line of a client's handshake request and check that it targets
+/// the path we are listening on.
+///
+/// A listener on the root path `/` accepts requests for any path. Requests for other
+/// paths are answered with `404 Not Found`. On success the bytes read so far are
+/// returned alongside the stream, as they have to be fed to the handshake codec.
+fn accept_path<S, E>(stream: S, path: String) -> impl Future<Item = (S, BytesMut), Error = Error<E>>
+where
+    S: AsyncRead + AsyncWrite
+{
+    ReadRequestLine { stream: Some(stream), buf: BytesMut::new() }
+        .map_err(|e| Error::Handshake(Box::new(e)))
+        .and_then(move |(stream, buf)| {
+            let accepted = match request_path(&buf) {
+                Some(p) => path == "/" || p == path,
+                None => false
+            };
+            if accepted {
+                return Either::A(future::ok((stream, buf)))
+            }
+            debug!("websocket handshake request does not target {}", path);
+            Either::B(tokio_io::io::write_all(stream, NOT_FOUND).then(|_| {
+                let e: io::Error = io::ErrorKind::NotFound.into();
+                Err(Error::Handshake(Box::new(e)))
+            }))
+        })
+}
+
+/// Extract the path of the request target from the request line of an HTTP request,
+/// without any query string.
+fn request_path(buf: &[u8]) -> Option<&str> {
+    let end = buf.windows(2).position(|w| w == b"\r\n")?;
+    let line = std::str::from_utf8(&buf[.. end]).ok()?;
+    let target = line.split(' ').nth(1)?;
+    target.split('?').next()
+}
+
+/// A future reading from a stream until the first line of an HTTP request is complete.
+struct ReadRequestLine<S> {
+    stream: Option<S>,
+    buf: BytesMut
+}
+
+impl<S: AsyncRead> Future for ReadRequestLine<S> {
+    type Item = (S, BytesMut);
+    type Error = io::Error;
+
+    fn poll(&mut self) -> Poll<Self::Item, Self::Error> {
+        loop {
+            if self.buf.windows(2).any(|w| w == b"\r\n") {
+                let stream = self.stream.take().expect("ReadRequestLine polled after completion");
+                let buf = std::mem::replace(&mut self.buf, BytesMut::new());
+                return Ok(Async::Ready((stream, buf)))
+            }
+            if self.buf.len() >= MAX_REQUEST_LINE_SIZE {
+                return Err(io::Error::new(io::ErrorKind::InvalidData, "request line too long"))
+            }
+            let mut chunk = [0; 1024];
+            let stream = self.stream.as_mut().expect("ReadRequestLine polled after completion");
+            let n = try_ready!(stream.poll_read(&mut chunk));
+            if n == 0 {
+                return Err(io::ErrorKind::UnexpectedEof.into())
+            }
+            self.buf.extend_from_slice(&chunk[.. n])
+        }
+    }
+}
+
 /// Create a `Connection` from an existing `Framed` value.
 fn new_connection<T, C>(framed: Framed<T, C>, max_size: u64, mode: Mode) -> (C, Connection<T>)
 where
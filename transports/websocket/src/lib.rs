@@ -218,4 +218,82 @@ mod tests {
         let mut rt = Runtime::new().unwrap();
         let _ = rt.block_on(future).unwrap();
     }
+
+    #[test]
+    fn dialer_connects_to_listener_path() {
+        let ws_config = WsConfig::new(tcp::TcpConfig::new());
+
+        let mut listener = ws_config.clone()
+            .listen_on("/ip4/127.0.0.1/tcp/0/x-parity-ws/%2Fsubstrate".parse().unwrap())
+            .unwrap();
+
+        let addr = listener.by_ref().wait()
+            .next()
+            .expect("some event")
+            .expect("no error")
+            .into_new_address()
+            .expect("listen address");
+
+        assert_eq!(Some(Protocol::Ws("/substrate".into())), addr.iter().nth(2));
+
+        let listener = listener
+            .filter_map(ListenerEvent::into_upgrade)
+            .into_future()
+            .map_err(|(e, _)| e)
+            .and_then(|(c, _)| c.unwrap().0);
+
+        let dialer = ws_config.clone().dial(addr.clone()).unwrap();
+
+        let future = listener
+            .select(dialer)
+            .map_err(|(e, _)| e)
+            .and_then(|(_, n)| n);
+
+        let mut rt = Runtime::new().unwrap();
+        let _ = rt.block_on(future).unwrap();
+    }
+
+    #[test]
+    fn listener_rejects_unknown_path() {
+        let ws_config = WsConfig::new(tcp::TcpConfig::new());
+
+        let mut listener = ws_config.clone()
+            .listen_on("/ip4/127.0.0.1/tcp/0/x-parity-ws/%2Fsubstrate".parse().unwrap())
+            .unwrap();
+
+        let mut addr = listener.by_ref().wait()
+            .next()
+            .expect("some event")
+            .expect("no error")
+            .into_new_address()
+            .expect("listen address");
+
+        addr.pop();
+        let addr = addr.with(Protocol::Ws("/other".into()));
+
+        let listener = listener
+            .filter_map(ListenerEvent::into_upgrade)
+            .into_future()
+            .map_err(|(e, _)| e)
+            .and_then(|(c, _)| c.unwrap().0);
+
+        let dialer = ws_config.clone().dial(addr).unwrap();
+
+        let future = listener
+            .join(dialer)
+            .map(|_| ());
+
+        let mut rt = Runtime::new().unwrap();
+        assert!(rt.block_on(future).is_err());
+    }
+
+    #[test]
+    fn sni_requires_tls() {
+        let ws_config = WsConfig::new(tcp::TcpConfig::new());
+        let addr = "/ip4/127.0.0.1/tcp/443/sni/example.com/ws".parse().unwrap();
+        let dialer = ws_config.dial(addr).unwrap();
+
+        let mut rt = Runtime::new().unwrap();
+        assert!(rt.block_on(dialer).is_err());
+    }
 }